@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+use ariel_os::{
+    board,
+    debug::log::*,
+    input,
+    keyboard::{Action::*, Keyboard, Layer, keycode::*},
+    usb::keyboard::UsbKeyboard,
+};
+
+#[cfg(context = "nrf52840dk")]
+board::define_board! {
+    leds: [P0_13: Low, P0_14: Low, P0_15: Low, P0_16: Low],
+    buttons: [P0_11: Low, Up, P0_12: Low, Up, P0_24: Low, Up, P0_25: Low, Up],
+    keypad: {
+        rows: [P0_03, P0_04],
+        columns: [P0_28, P0_29, P0_30],
+    },
+}
+
+static LAYERS: [Layer<2, 3>; 2] = [
+    [
+        [
+            Keys(&[LEFT_CTRL, C]),
+            Keys(&[LEFT_CTRL, V]),
+            Keys(&[LEFT_CTRL, Z]),
+        ],
+        [Key(PAGE_UP), Key(PAGE_DOWN), HoldLayer(1)],
+    ],
+    [
+        [Key(UP), Key(DOWN), Keys(&[LEFT_CTRL, LEFT_SHIFT, Z])],
+        [Key(HOME), Key(END), Transparent],
+    ],
+];
+
+#[ariel_os::task(autostart, usb_builder_hook)]
+async fn macropad() {
+    let mut usb_keyboard = USB_BUILDER_HOOK.with(UsbKeyboard::new).await;
+
+    let Some(keypad) = board::take_keypad() else {
+        error!("The board has no key matrix");
+        return;
+    };
+    if input::add_keypad(keypad).is_err() {
+        error!("Could not add the key matrix to the input service");
+        return;
+    }
+
+    info!("Macro pad ready");
+    usb_keyboard.run(&mut Keyboard::new(&LAYERS)).await
+}
@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+use ariel_os::{
+    debug::log::{info, warn},
+    usb::provisioning::UsbProvisioning,
+};
+
+#[ariel_os::task(autostart, usb_builder_hook)]
+async fn main() {
+    let requested = UsbProvisioning::requested().await;
+
+    // The hook needs to be used in any case for the USB device to start.
+    let provisioning = USB_BUILDER_HOOK
+        .with(|builder| requested.then(|| UsbProvisioning::new(builder)))
+        .await;
+
+    if let Some(mut provisioning) = provisioning {
+        info!("Provisioning, connect to the USB serial port");
+        provisioning.run().await;
+        info!("Provisioning done, rebooting");
+        ariel_os::power::reboot();
+    }
+
+    let Ok(vault) = ariel_os::vault::vault().await else {
+        warn!("Vault unavailable");
+        return;
+    };
+    match vault.key("app-key").load::<16>().await {
+        Ok(Some(_)) => info!("The application key is provisioned"),
+        Ok(None) => info!("No application key, hold button 1 at boot to provision one"),
+        Err(_) => warn!("The application key could not be loaded"),
+    }
+
+    let mut buffer = [0; 32];
+    if let Ok(Some(name)) = ariel_os::storage::get_blob("device-name", &mut buffer).await {
+        if let Ok(name) = core::str::from_utf8(name) {
+            info!("Device name: {}", name);
+        }
+    }
+}
@@ -5,12 +5,10 @@ use ariel_os::{
     cell::StaticCell,
     debug::log::{Hex, info},
     reexports::embassy_usb,
-    usb::UsbDriver,
-};
-use embassy_usb::{
-    class::cdc_acm::{CdcAcmClass, State},
-    driver::EndpointError,
+    usb::cdc_acm::{self, CdcAcmSerial},
 };
+use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
+use embedded_io_async::{Read, Write};
 
 const MAX_FULL_SPEED_PACKET_SIZE: u8 = 64;
 
@@ -38,7 +36,7 @@ async fn main() {
     static STATE: StaticCell<State> = StaticCell::new();
 
     // Create and inject the USB class on the system USB builder.
-    let mut class = USB_BUILDER_HOOK
+    let class = USB_BUILDER_HOOK
         .with(|builder| {
             CdcAcmClass::new(
                 builder,
@@ -47,33 +45,24 @@ async fn main() {
             )
         })
         .await;
+    // Use the class as a byte stream.
+    let mut serial = CdcAcmSerial::new(class);
 
-    // Do stuff with the class!
     loop {
-        class.wait_connection().await;
+        serial.wait_connection().await;
         info!("Connected");
-        let _ = echo(&mut class).await;
+        let _ = echo(&mut serial).await;
         info!("Disconnected");
     }
 }
 
-struct Disconnected {}
-
-impl From<EndpointError> for Disconnected {
-    fn from(val: EndpointError) -> Self {
-        match val {
-            EndpointError::BufferOverflow => panic!("Buffer overflow"),
-            EndpointError::Disabled => Disconnected {},
-        }
-    }
-}
-
-async fn echo(class: &mut CdcAcmClass<'static, UsbDriver>) -> Result<(), Disconnected> {
-    let mut buf = [0; MAX_FULL_SPEED_PACKET_SIZE as usize];
+async fn echo(serial: &mut CdcAcmSerial) -> Result<(), cdc_acm::Error> {
+    let mut buf = [0; 64];
     loop {
-        let n = class.read_packet(&mut buf).await?;
+        let n = serial.read(&mut buf).await?;
         let data = &buf[..n];
         info!("data: {}", Hex(data));
-        class.write_packet(data).await?;
+        serial.write_all(data).await?;
+        serial.flush().await?;
     }
 }
@@ -0,0 +1,79 @@
+//! Provides allocations that fail instead of panicking when the heap is exhausted.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::alloc::Layout;
+
+/// Error returned when an allocation fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocError;
+
+impl core::fmt::Display for AllocError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "memory allocation failed")
+    }
+}
+
+impl core::error::Error for AllocError {}
+
+/// Allocates a [`Box`] holding `value`.
+///
+/// # Errors
+///
+/// Returns [`AllocError`] if the heap is exhausted.
+pub fn try_box<T>(value: T) -> Result<Box<T>, AllocError> {
+    let layout = Layout::new::<T>();
+
+    if layout.size() == 0 {
+        // Does not allocate.
+        return Ok(Box::new(value));
+    }
+
+    // SAFETY: the layout has a non-zero size.
+    let ptr = unsafe { alloc::alloc::alloc(layout) }.cast::<T>();
+
+    if ptr.is_null() {
+        return Err(failed(layout));
+    }
+
+    // SAFETY: `ptr` was allocated by the global allocator with the layout of `T`, so it is valid
+    // for writes and properly aligned.
+    unsafe {
+        ptr.write(value);
+        Ok(Box::from_raw(ptr))
+    }
+}
+
+/// Allocates a [`Vec`] holding `len` clones of `value`, like `vec![value; len]`.
+///
+/// # Errors
+///
+/// Returns [`AllocError`] if the heap is exhausted.
+pub fn try_vec<T: Clone>(value: T, len: usize) -> Result<Vec<T>, AllocError> {
+    let mut vec = try_vec_with_capacity(len)?;
+    vec.resize(len, value);
+    Ok(vec)
+}
+
+/// Allocates an empty [`Vec`] that can hold `capacity` elements without reallocating.
+///
+/// # Errors
+///
+/// Returns [`AllocError`] if the heap is exhausted.
+pub fn try_vec_with_capacity<T>(capacity: usize) -> Result<Vec<T>, AllocError> {
+    // Requests that can never be satisfied are not reported as failures.
+    let layout = Layout::array::<T>(capacity).map_err(|_| AllocError)?;
+
+    let mut vec = Vec::new();
+    vec.try_reserve_exact(capacity)
+        .map_err(|_| failed(layout))?;
+    Ok(vec)
+}
+
+fn failed(layout: Layout) -> AllocError {
+    // On Cortex-M, failures are reported by the global allocator itself.
+    if cfg!(not(context = "cortex-m")) {
+        crate::stats::report_failure(layout);
+    }
+
+    AllocError
+}
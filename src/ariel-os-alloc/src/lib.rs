@@ -1,19 +1,34 @@
 //! This crate glues a suitable allocator into Ariel OS.
+//!
+//! Allocations through the `alloc` crate panic when the heap is exhausted.
+//! The [`try_box()`], [`try_vec()`] and [`try_vec_with_capacity()`] functions instead return an
+//! [`AllocError`], which allows applications to handle exhaustion, e.g., by dropping a connection.
+//!
+//! Failed allocations are logged, and reported to the hook set with [`set_oom_hook()`].
+//! The usage of the heap is returned by [`stats()`].
 
 #![no_std]
 #![deny(missing_docs)]
 // required for tests:
 #![cfg_attr(test, no_main)]
 
+extern crate alloc;
+
+mod fallible;
+mod stats;
+
+pub use fallible::{AllocError, try_box, try_vec, try_vec_with_capacity};
+pub use stats::{OomHookAlreadySet, Stats, set_oom_hook, stats};
+
 // With embedded-test enabled, this crate gets built *twice*, once regularly
 // as the system alloc it is supposed to be, and once as test application.
 // In the latter case, `cfg(test)` is set.
 // So we *only* set up the global stuff if *not* testing in order to avoid clashes.
 #[cfg(not(test))]
-pub use alloc::init;
+pub use heap::init;
 
 #[cfg(not(test))]
-mod alloc {
+mod heap {
     const CONFIG_HEAPSIZE: usize =
         ariel_os_utils::usize_from_env_or!("CONFIG_HEAPSIZE", 2048, "heap size (in bytes)");
 
@@ -44,10 +59,12 @@ mod alloc {
     unsafe fn init_embedded_alloc() {
         use ariel_os_debug::log::debug;
 
-        use embedded_alloc::TlsfHeap as Heap;
+        use embedded_alloc::TlsfHeap;
+
+        use crate::stats::TrackingHeap;
 
         #[global_allocator]
-        static HEAP: Heap = const { Heap::empty() };
+        static HEAP: TrackingHeap<TlsfHeap> = const { TrackingHeap::new(TlsfHeap::empty()) };
 
         unsafe extern "C" {
             static __sheap: u32;
@@ -66,7 +83,9 @@ mod alloc {
             size, start
         );
 
-        unsafe { HEAP.init(start, size) }
+        crate::stats::set_size(size);
+
+        unsafe { HEAP.inner().init(start, size) }
     }
 
     /// Initializes an `esp_alloc` heap.
@@ -117,4 +136,15 @@ mod tests {
         some_vec.push(i);
         assert!(some_vec[0] == i);
     }
+
+    #[test]
+    async fn fallible() {
+        let boxed = crate::try_box(0xdeadbeefu32).unwrap();
+        assert!(*boxed == 0xdeadbeef);
+
+        let buffer = crate::try_vec(0u8, 64).unwrap();
+        assert!(buffer.len() == 64);
+
+        assert!(crate::try_vec(0u8, usize::MAX / 2).is_err());
+    }
 }
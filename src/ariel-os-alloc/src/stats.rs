@@ -0,0 +1,148 @@
+//! Tracks the usage of the heap, and reports failed allocations.
+
+use core::alloc::Layout;
+
+use ariel_os_debug::log::warn;
+use embassy_sync::once_lock::OnceLock;
+use portable_atomic::{AtomicUsize, Ordering};
+
+static OOM_HOOK: OnceLock<fn(Layout)> = OnceLock::new();
+
+static FAILURES: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(all(context = "cortex-m", not(test)))]
+static SIZE: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(all(context = "cortex-m", not(test)))]
+static USED: AtomicUsize = AtomicUsize::new(0);
+
+/// Usage of the heap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Stats {
+    /// Number of bytes currently allocated.
+    pub used: usize,
+    /// Number of bytes currently available, regardless of fragmentation.
+    pub free: usize,
+    /// Number of failed allocations since boot.
+    pub failures: usize,
+}
+
+/// Returns the usage of the heap.
+#[must_use]
+pub fn stats() -> Stats {
+    #[cfg(all(context = "cortex-m", not(test)))]
+    let (used, free) = {
+        let used = USED.load(Ordering::Relaxed);
+        (used, SIZE.load(Ordering::Relaxed).saturating_sub(used))
+    };
+    #[cfg(all(context = "esp", not(test)))]
+    let (used, free) = (esp_alloc::HEAP.used(), esp_alloc::HEAP.free());
+    #[cfg(not(all(any(context = "cortex-m", context = "esp"), not(test))))]
+    let (used, free) = (0, 0);
+
+    Stats {
+        used,
+        free,
+        failures: FAILURES.load(Ordering::Relaxed),
+    }
+}
+
+/// Error returned by [`set_oom_hook()`] when a hook is already set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OomHookAlreadySet;
+
+impl core::fmt::Display for OomHookAlreadySet {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "an out-of-memory hook is already set")
+    }
+}
+
+impl core::error::Error for OomHookAlreadySet {}
+
+/// Sets the hook called with the layout of each failed allocation.
+///
+/// The hook is called before the allocation error is returned, or, for allocations that cannot
+/// fail, before panicking.
+/// It must not allocate.
+///
+/// On ESP devices, only the failures of the fallible functions of this crate are reported.
+///
+/// # Errors
+///
+/// Returns [`OomHookAlreadySet`] if a hook is already set.
+pub fn set_oom_hook(hook: fn(Layout)) -> Result<(), OomHookAlreadySet> {
+    OOM_HOOK.init(hook).map_err(|_| OomHookAlreadySet)
+}
+
+pub(crate) fn report_failure(layout: Layout) {
+    FAILURES.fetch_add(1, Ordering::Relaxed);
+
+    warn!("ariel-os-alloc: failed to allocate {} bytes", layout.size());
+
+    if let Some(hook) = OOM_HOOK.try_get() {
+        hook(layout);
+    }
+}
+
+#[cfg(all(context = "cortex-m", not(test)))]
+pub(crate) fn set_size(size: usize) {
+    SIZE.store(size, Ordering::Relaxed);
+}
+
+/// Wraps an allocator to track its usage, and report its failures.
+#[cfg(all(context = "cortex-m", not(test)))]
+pub(crate) struct TrackingHeap<A> {
+    inner: A,
+}
+
+#[cfg(all(context = "cortex-m", not(test)))]
+impl<A> TrackingHeap<A> {
+    pub(crate) const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+
+    pub(crate) fn inner(&self) -> &A {
+        &self.inner
+    }
+}
+
+#[cfg(all(context = "cortex-m", not(test)))]
+// SAFETY: forwards to the wrapped allocator.
+unsafe impl<A: core::alloc::GlobalAlloc> core::alloc::GlobalAlloc for TrackingHeap<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // SAFETY: upheld by the caller.
+        let ptr = unsafe { self.inner.alloc(layout) };
+
+        if ptr.is_null() {
+            report_failure(layout);
+        } else {
+            USED.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: upheld by the caller.
+        unsafe { self.inner.dealloc(ptr, layout) };
+
+        USED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // SAFETY: upheld by the caller.
+        let new_ptr = unsafe { self.inner.realloc(ptr, layout, new_size) };
+
+        if new_ptr.is_null() {
+            // SAFETY: the caller guarantees that the new layout is valid.
+            let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+            report_failure(new_layout);
+        } else {
+            USED.fetch_add(new_size, Ordering::Relaxed);
+            USED.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+
+        new_ptr
+    }
+}
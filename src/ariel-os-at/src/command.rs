@@ -0,0 +1,123 @@
+//! Provides the parsing of command lines.
+
+/// Maximum number of arguments of commands.
+const MAX_ARGUMENTS: usize = 4;
+
+/// A command, parsed from a command line.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Command<'a> {
+    /// A basic command, eg. `E0`.
+    Basic(&'a [u8]),
+    /// An extended command, eg. `+STOREGET="key"`, of its name without the `+`.
+    Extended(&'a [u8], Form<'a>),
+}
+
+/// The form in which an extended command is invoked.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Form<'a> {
+    /// The command is executed without arguments, eg. `+NAME`.
+    Execute,
+    /// The current value is read, eg. `+NAME?`.
+    Read,
+    /// The supported arguments are tested, eg. `+NAME=?`.
+    Test,
+    /// The command is executed with arguments, eg. `+NAME=1,"two"`.
+    Set(heapless::Vec<&'a [u8], MAX_ARGUMENTS>),
+}
+
+impl<'a> Command<'a> {
+    /// Parses a command `line`, which starts with the `AT` prefix.
+    ///
+    /// Returns `None` if the line is not a valid command line.
+    pub(crate) fn parse(line: &'a [u8]) -> Option<Self> {
+        let (prefix, command) = line.split_first_chunk::<2>()?;
+        if !prefix.eq_ignore_ascii_case(b"AT") {
+            return None;
+        }
+        let Some(command) = command.strip_prefix(b"+") else {
+            return Some(Self::Basic(command));
+        };
+
+        let name_len = command
+            .iter()
+            .position(|&byte| byte == b'?' || byte == b'=')
+            .unwrap_or(command.len());
+        let (name, form) = command.split_at(name_len);
+        if name.is_empty() {
+            return None;
+        }
+        let form = match form {
+            b"" => Form::Execute,
+            b"?" => Form::Read,
+            b"=?" => Form::Test,
+            form => Form::Set(parse_arguments(form.strip_prefix(b"=")?)?),
+        };
+        Some(Self::Extended(name, form))
+    }
+}
+
+/// Parses the comma-separated `arguments` of a command, which are either quoted strings or bare
+/// values.
+///
+/// Returns `None` if the arguments are malformed, or too many.
+fn parse_arguments(mut arguments: &[u8]) -> Option<heapless::Vec<&[u8], MAX_ARGUMENTS>> {
+    let mut parsed = heapless::Vec::new();
+    loop {
+        let (argument, rest) = if let Some(quoted) = arguments.strip_prefix(b"\"") {
+            let end = quoted.iter().position(|&byte| byte == b'"')?;
+            let (argument, rest) = quoted.split_at(end);
+            (argument, rest.get(1..)?)
+        } else {
+            let end = arguments
+                .iter()
+                .position(|&byte| byte == b',')
+                .unwrap_or(arguments.len());
+            arguments.split_at(end)
+        };
+        parsed.push(argument).ok()?;
+        match rest.split_first() {
+            None => return Some(parsed),
+            Some((b',', rest)) => arguments = rest,
+            Some(_) => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use super::*;
+
+    /// Returns the `Set` form with `arguments`.
+    fn set<'a>(arguments: &[&'a [u8]]) -> Form<'a> {
+        Form::Set(heapless::Vec::from_slice(arguments).unwrap())
+    }
+
+    #[test]
+    fn parse() {
+        for (line, command) in [
+            (b"AT".as_slice(), Some(Command::Basic(b""))),
+            (b"at", Some(Command::Basic(b""))),
+            (b"ATE0", Some(Command::Basic(b"E0"))),
+            (b"AT+NAME", Some(Command::Extended(b"NAME", Form::Execute))),
+            (b"AT+NAME?", Some(Command::Extended(b"NAME", Form::Read))),
+            (b"AT+NAME=?", Some(Command::Extended(b"NAME", Form::Test))),
+            (b"AT+NAME=", Some(Command::Extended(b"NAME", set(&[b""])))),
+            (
+                b"AT+NAME=1,\"two, 2\",",
+                Some(Command::Extended(b"NAME", set(&[b"1", b"two, 2", b""]))),
+            ),
+            (b"", None),
+            (b"A", None),
+            (b"XT", None),
+            (b"AT+", None),
+            (b"AT+?", None),
+            (b"AT+NAME??", None),
+            (b"AT+NAME=\"open", None),
+            (b"AT+NAME=\"closed\"1", None),
+            (b"AT+NAME=1,2,3,4,5", None),
+        ] {
+            assert_eq!(Command::parse(line), command, "{}", line.escape_ascii());
+        }
+    }
+}
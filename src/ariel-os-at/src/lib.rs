@@ -0,0 +1,567 @@
+//! Provides an AT command server, through which hosts that only speak to modems use the device as
+//! their connectivity module.
+//!
+//! [`run()`] interprets the commands received on a byte stream implementing the
+//! [`embedded_io_async`] traits, eg. a UART or a USB CDC-ACM serial port:
+//!
+//! ```ignore
+//! ariel_os::at::run(uart).await?;
+//! ```
+//!
+//! Command lines start with `AT`, end with a carriage return, and hold a single command. They are
+//! echoed back unless echo is disabled, and answered with `OK` or `ERROR`, preceded by any
+//! information response:
+//!
+//! | Command | Response | Description |
+//! |---------|----------|-------------|
+//! | `AT` | | Checks that the server is responsive. |
+//! | `ATE0`, `ATE1` | | Disables or enables echo. |
+//! | `ATI` | `Ariel OS 0.2.0, nrf52840dk` | Identifies the OS and the board. |
+//! | `AT+NETSTAT?` | `+NETSTAT: 1,"10.42.0.61/24"` | Reads whether the link is up, and the IPv4 address, empty when not configured. |
+//! | `AT+STORESET="key","value"` | | Stores a value, with the `storage` feature. |
+//! | `AT+STOREGET="key"` | `+STOREGET: "value"` | Reads a stored value, without response when not stored, with the `storage` feature. |
+//! | `AT+COAPGET="coap://192.0.2.1/path"` | `+COAPGET: 48656c6c6f` | Sends a CoAP GET request, and returns the response payload in hexadecimal, with the `coap` feature. |
+//! | `AT+COAPPOST="coap://192.0.2.1/path","48656c6c6f"` | `+COAPPOST: 4f4b` | Sends a CoAP POST request with a payload in hexadecimal, with the `coap` feature. |
+//!
+//! Extended commands (starting with `+`) answer `OK` when tested, eg. with `AT+NETSTAT=?`. CoAP
+//! URIs take an IP address as host, as no name resolution takes place, and responses are returned
+//! whichever their code. As the CoAP client, the server needs to run on the executor of the
+//! network stack.
+
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+mod command;
+
+use core::fmt::Write as _;
+
+use embedded_io_async::{Read, Write};
+
+use crate::command::{Command, Form};
+
+/// Maximum length of command lines.
+const MAX_LINE_LEN: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_AT_MAX_LINE_LEN",
+    512,
+    "maximum length of AT command lines"
+);
+
+/// Maximum length of values read from the storage, and of CoAP response payloads.
+#[cfg(any(feature = "storage", feature = "coap"))]
+const MAX_VALUE_LEN: usize = 256;
+
+/// Maximum length of the names of extended commands.
+const MAX_NAME_LEN: usize = 16;
+
+/// Byte erasing the last received byte of the command line.
+const BACKSPACE: u8 = 0x08;
+/// Byte erasing the last received byte of the command line, as sent by some terminals.
+const DELETE: u8 = 0x7f;
+
+/// The failure of a command, which is answered with `ERROR`.
+#[derive(Debug)]
+struct CommandError;
+
+/// Serves AT commands received on `stream`.
+///
+/// Returns once the byte stream ends.
+///
+/// # Errors
+///
+/// Returns the error of the byte stream if reading or writing fails.
+pub async fn run<S: Read + Write>(mut stream: S) -> Result<(), S::Error> {
+    let mut echo = true;
+    let mut line = heapless::Vec::<u8, MAX_LINE_LEN>::new();
+    let mut overflowed = false;
+    let mut bytes = [0; 32];
+    loop {
+        let len = stream.read(&mut bytes).await?;
+        if len == 0 {
+            return Ok(());
+        }
+        let received = bytes.get(..len).unwrap_or_default();
+        if echo {
+            stream.write_all(received).await?;
+            stream.flush().await?;
+        }
+        for &byte in received {
+            match byte {
+                b'\r' if line.is_empty() && !overflowed => {}
+                b'\r' => {
+                    let result = if core::mem::take(&mut overflowed) {
+                        Err(CommandError)
+                    } else {
+                        execute(&mut stream, &line, &mut echo).await?
+                    };
+                    line.clear();
+                    let code: &[u8] = if result.is_ok() {
+                        b"\r\nOK\r\n"
+                    } else {
+                        b"\r\nERROR\r\n"
+                    };
+                    stream.write_all(code).await?;
+                    stream.flush().await?;
+                }
+                b'\n' => {}
+                BACKSPACE | DELETE => {
+                    line.pop();
+                }
+                byte => {
+                    if line.push(byte).is_err() {
+                        overflowed = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Executes the command `line`, writing its information response to `stream`.
+///
+/// # Errors
+///
+/// Returns the error of the byte stream if writing fails, and otherwise whether the command
+/// succeeded.
+async fn execute<S: Write>(
+    stream: &mut S,
+    line: &[u8],
+    echo: &mut bool,
+) -> Result<Result<(), CommandError>, S::Error> {
+    let Some(command) = Command::parse(line) else {
+        return Ok(Err(CommandError));
+    };
+    let (name, form) = match command {
+        Command::Basic(b"") => return Ok(Ok(())),
+        Command::Basic(b"E0" | b"e0") => {
+            *echo = false;
+            return Ok(Ok(()));
+        }
+        Command::Basic(b"E1" | b"e1") => {
+            *echo = true;
+            return Ok(Ok(()));
+        }
+        Command::Basic(b"I" | b"i") => {
+            use ariel_os_buildinfo::{BOARD, OS_NAME, OS_VERSION};
+
+            respond(
+                stream,
+                &[
+                    OS_NAME.as_bytes(),
+                    b" ",
+                    OS_VERSION.as_bytes(),
+                    b", ",
+                    BOARD.as_bytes(),
+                ],
+            )
+            .await?;
+            return Ok(Ok(()));
+        }
+        Command::Basic(_) => return Ok(Err(CommandError)),
+        Command::Extended(name, form) => (name, form),
+    };
+
+    execute_extended(stream, name, form).await
+}
+
+/// Executes the extended command `name` in `form`, writing its information response to
+/// `stream`.
+///
+/// # Errors
+///
+/// Returns the error of the byte stream if writing fails, and otherwise whether the command
+/// succeeded.
+async fn execute_extended<S: Write>(
+    stream: &mut S,
+    name: &[u8],
+    form: Form<'_>,
+) -> Result<Result<(), CommandError>, S::Error> {
+    let mut uppercase = [0; MAX_NAME_LEN];
+    let Some(name) = uppercase.get_mut(..name.len()).map(|uppercase| {
+        uppercase.copy_from_slice(name);
+        uppercase.make_ascii_uppercase();
+        &*uppercase
+    }) else {
+        return Ok(Err(CommandError));
+    };
+
+    match (name, form) {
+        (b"NETSTAT" | b"STORESET" | b"STOREGET" | b"COAPGET" | b"COAPPOST", Form::Test) => {
+            Ok(Ok(()))
+        }
+        (b"NETSTAT", Form::Read) => net_status(stream).await,
+        #[cfg(feature = "storage")]
+        (b"STORESET", Form::Set(arguments)) => Ok(store_set(&arguments).await),
+        #[cfg(feature = "storage")]
+        (b"STOREGET", Form::Set(arguments)) => store_get(stream, &arguments).await,
+        #[cfg(feature = "coap")]
+        (b"COAPGET", Form::Set(arguments)) => {
+            let [uri] = arguments.as_slice() else {
+                return Ok(Err(CommandError));
+            };
+            match coap::get(uri).await {
+                Ok(payload) => {
+                    respond_hex(stream, b"+COAPGET: ", &payload).await?;
+                    Ok(Ok(()))
+                }
+                Err(err) => Ok(Err(err)),
+            }
+        }
+        #[cfg(feature = "coap")]
+        (b"COAPPOST", Form::Set(arguments)) => {
+            let [uri, payload] = arguments.as_slice() else {
+                return Ok(Err(CommandError));
+            };
+            let mut request_payload = heapless::Vec::<u8, { MAX_LINE_LEN / 2 }>::new();
+            if decode_hex(payload, &mut request_payload).is_none() {
+                return Ok(Err(CommandError));
+            }
+            match coap::post(uri, &request_payload).await {
+                Ok(payload) => {
+                    respond_hex(stream, b"+COAPPOST: ", &payload).await?;
+                    Ok(Ok(()))
+                }
+                Err(err) => Ok(Err(err)),
+            }
+        }
+        _ => Ok(Err(CommandError)),
+    }
+}
+
+/// Stores the value of the `key` and `value` `arguments`.
+///
+/// # Errors
+///
+/// Returns an error if the arguments are invalid, or storing fails.
+#[cfg(feature = "storage")]
+async fn store_set(arguments: &[&[u8]]) -> Result<(), CommandError> {
+    let [key, value] = arguments else {
+        return Err(CommandError);
+    };
+    let key = core::str::from_utf8(key).map_err(|_| CommandError)?;
+    ariel_os_storage::insert_blob(key, value)
+        .await
+        .map_err(|_| CommandError)
+}
+
+/// Writes the stored value of the `key` `arguments` to `stream`.
+///
+/// # Errors
+///
+/// Returns the error of the byte stream if writing fails, and otherwise whether the arguments
+/// are valid, and reading succeeded.
+#[cfg(feature = "storage")]
+async fn store_get<S: Write>(
+    stream: &mut S,
+    arguments: &[&[u8]],
+) -> Result<Result<(), CommandError>, S::Error> {
+    let [key] = arguments else {
+        return Ok(Err(CommandError));
+    };
+    let Ok(key) = core::str::from_utf8(key) else {
+        return Ok(Err(CommandError));
+    };
+    let mut buffer = [0; MAX_VALUE_LEN];
+    match ariel_os_storage::get_blob(key, &mut buffer).await {
+        Ok(Some(value)) => {
+            respond(stream, &[b"+STOREGET: \"", value, b"\""]).await?;
+            Ok(Ok(()))
+        }
+        Ok(None) => Ok(Ok(())),
+        Err(_) => Ok(Err(CommandError)),
+    }
+}
+
+/// Writes the state of the network interface to `stream`.
+///
+/// # Errors
+///
+/// Returns the error of the byte stream if writing fails, and otherwise whether the network is
+/// available.
+async fn net_status<S: Write>(stream: &mut S) -> Result<Result<(), CommandError>, S::Error> {
+    let Some(stack) = ariel_os_embassy::net::network_stack().await else {
+        return Ok(Err(CommandError));
+    };
+    let mut response = heapless::String::<48>::new();
+    let written = match stack.config_v4() {
+        Some(config) => write!(
+            response,
+            "+NETSTAT: {},\"{}\"",
+            u8::from(stack.is_link_up()),
+            config.address
+        ),
+        None => write!(response, "+NETSTAT: {},\"\"", u8::from(stack.is_link_up())),
+    };
+    if written.is_err() {
+        return Ok(Err(CommandError));
+    }
+    respond(stream, &[response.as_bytes()]).await?;
+    Ok(Ok(()))
+}
+
+/// Writes an information response made of `parts` to `stream`.
+///
+/// # Errors
+///
+/// Returns the error of the byte stream if writing fails.
+async fn respond<S: Write>(stream: &mut S, parts: &[&[u8]]) -> Result<(), S::Error> {
+    stream.write_all(b"\r\n").await?;
+    for part in parts {
+        stream.write_all(part).await?;
+    }
+    stream.write_all(b"\r\n").await
+}
+
+/// Writes an information response made of `prefix` and `data` in hexadecimal to `stream`.
+///
+/// # Errors
+///
+/// Returns the error of the byte stream if writing fails.
+#[cfg(feature = "coap")]
+async fn respond_hex<S: Write>(stream: &mut S, prefix: &[u8], data: &[u8]) -> Result<(), S::Error> {
+    stream.write_all(b"\r\n").await?;
+    stream.write_all(prefix).await?;
+    for chunk in data.chunks(16) {
+        let mut hex = [0; 32];
+        for (digits, byte) in hex.chunks_exact_mut(2).zip(chunk) {
+            digits.copy_from_slice(&[hex_digit(byte >> 4), hex_digit(byte & 0xf)]);
+        }
+        stream
+            .write_all(hex.get(..chunk.len() * 2).unwrap_or_default())
+            .await?;
+    }
+    stream.write_all(b"\r\n").await
+}
+
+/// Returns the lowercase hexadecimal digit of `nibble`.
+#[cfg(feature = "coap")]
+fn hex_digit(nibble: u8) -> u8 {
+    if nibble < 10 {
+        b'0' + nibble
+    } else {
+        b'a' + nibble - 10
+    }
+}
+
+/// Decodes `hex` into `data`.
+///
+/// Returns `None` if `hex` is not valid hexadecimal, or too long.
+#[cfg(feature = "coap")]
+fn decode_hex<const N: usize>(hex: &[u8], data: &mut heapless::Vec<u8, N>) -> Option<()> {
+    let digits = hex.chunks_exact(2);
+    if !digits.remainder().is_empty() {
+        return None;
+    }
+    let value = |digit: u8| {
+        char::from(digit)
+            .to_digit(16)
+            .and_then(|value| u8::try_from(value).ok())
+    };
+    for pair in digits {
+        let &[high, low] = pair else {
+            return None;
+        };
+        data.push(value(high)? << 4 | value(low)?).ok()?;
+    }
+    Some(())
+}
+
+#[cfg(feature = "coap")]
+mod coap {
+    //! Provides the CoAP client commands.
+
+    use core::net::{IpAddr, SocketAddr};
+
+    use coap_request::Stack as _;
+
+    use crate::{CommandError, MAX_VALUE_LEN};
+
+    /// Default port of CoAP servers.
+    const DEFAULT_PORT: u16 = 5683;
+
+    /// A CoAP response payload.
+    pub(crate) type Payload = heapless::Vec<u8, MAX_VALUE_LEN>;
+
+    /// Sends a GET request to `uri`, and returns the response payload.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URI is invalid, the request fails, or the response payload is too
+    /// long.
+    pub(crate) async fn get(uri: &[u8]) -> Result<Payload, CommandError> {
+        let (endpoint, path) = parse_uri(uri).ok_or(CommandError)?;
+        let request = coap_request_implementations::Code::get()
+            .with_path(path)
+            .processing_response_payload_through(|payload| Payload::from_slice(payload).ok());
+        let client = ariel_os_coap::coap_client().await;
+        match client.to(endpoint).request(request).await {
+            Ok(Some(payload)) => Ok(payload),
+            _ => Err(CommandError),
+        }
+    }
+
+    /// Sends a POST request with `payload` to `uri`, and returns the response payload.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URI is invalid, the request fails, or the response payload is too
+    /// long.
+    pub(crate) async fn post(uri: &[u8], payload: &[u8]) -> Result<Payload, CommandError> {
+        let (endpoint, path) = parse_uri(uri).ok_or(CommandError)?;
+        let request = coap_request_implementations::Code::post()
+            .with_path(path)
+            .with_request_payload_slice(payload)
+            .processing_response_payload_through(|payload| Payload::from_slice(payload).ok());
+        let client = ariel_os_coap::coap_client().await;
+        match client.to(endpoint).request(request).await {
+            Ok(Some(payload)) => Ok(payload),
+            _ => Err(CommandError),
+        }
+    }
+
+    /// Parses a `coap://` URI whose host is an IP address into the endpoint and the path.
+    fn parse_uri(uri: &[u8]) -> Option<(SocketAddr, &str)> {
+        let uri = core::str::from_utf8(uri).ok()?;
+        let (scheme, rest) = uri.split_at_checked("coap://".len())?;
+        if !scheme.eq_ignore_ascii_case("coap://") {
+            return None;
+        }
+        let (authority, path) = rest.find('/').map_or((rest, "/"), |end| rest.split_at(end));
+        let endpoint = match authority.parse::<SocketAddr>() {
+            Ok(endpoint) => endpoint,
+            Err(_) => {
+                let host = authority
+                    .strip_prefix('[')
+                    .and_then(|host| host.strip_suffix(']'))
+                    .unwrap_or(authority);
+                SocketAddr::new(host.parse::<IpAddr>().ok()?, DEFAULT_PORT)
+            }
+        };
+        Some((endpoint, path))
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use core::convert::Infallible;
+
+    use embassy_futures::block_on;
+
+    use super::*;
+
+    /// A byte stream that reads `input` a line at a time, as typed on a terminal, and collects
+    /// what is written.
+    struct Stream<'a> {
+        input: &'a [u8],
+        output: Vec<u8>,
+    }
+
+    impl embedded_io_async::ErrorType for Stream<'_> {
+        type Error = Infallible;
+    }
+
+    impl Read for Stream<'_> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            let line_len = self
+                .input
+                .iter()
+                .position(|&byte| byte == b'\r')
+                .map_or(self.input.len(), |end| end + 1);
+            let len = line_len.min(buf.len());
+            let (read, rest) = self.input.split_at(len);
+            buf.get_mut(..len).unwrap().copy_from_slice(read);
+            self.input = rest;
+            Ok(len)
+        }
+    }
+
+    impl Write for Stream<'_> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+            self.output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    /// Serves the commands of `input` until it ends, and returns the output.
+    fn serve(input: &[u8]) -> Vec<u8> {
+        let mut stream = Stream {
+            input,
+            output: Vec::new(),
+        };
+        block_on(run(&mut stream)).unwrap();
+        stream.output
+    }
+
+    #[test]
+    fn well_formed_lines() {
+        let identification = format!(
+            "\r\n{} {}, {}\r\n",
+            ariel_os_buildinfo::OS_NAME,
+            ariel_os_buildinfo::OS_VERSION,
+            ariel_os_buildinfo::BOARD
+        );
+        let expected = [
+            b"AT\r\r\nOK\r\n".as_slice(),
+            b"ATI\r",
+            identification.as_bytes(),
+            b"\r\nOK\r\n",
+            // Echo is disabled after the echo of the command that disables it.
+            b"ate0\r\r\nOK\r\n",
+            b"\r\nOK\r\n",
+            b"\r\nOK\r\n",
+        ]
+        .concat();
+        assert_eq!(serve(b"AT\rATI\rate0\r\nAT+NETSTAT=?\rAtX\x7f\r"), expected);
+    }
+
+    #[test]
+    fn empty_lines_are_ignored() {
+        assert_eq!(serve(b"ATE0\r\r\n\r"), b"ATE0\r\r\nOK\r\n");
+    }
+
+    #[test]
+    fn malformed_lines() {
+        for line in [
+            b"HELLO\r".as_slice(),
+            b"A\r",
+            b"ATX\r",
+            b"AT+\r",
+            b"AT+=?\r",
+            b"AT+NETSTAT=\"open\r",
+            b"AT+NETSTAT=\"quoted\"trailing\r",
+            b"AT+NETSTAT=1,2,3,4,5\r",
+            b"AT+NETSTAT?!\r",
+            b"AT+UNKNOWN?\r",
+            b"AT+AVERYLONGCOMMANDNAME?\r",
+        ] {
+            let input = [b"ATE0\r", line].concat();
+            assert_eq!(
+                serve(&input),
+                b"ATE0\r\r\nOK\r\n\r\nERROR\r\n",
+                "{}",
+                line.escape_ascii()
+            );
+        }
+    }
+
+    #[test]
+    fn oversized_lines() {
+        let mut input = b"ATE0\r".to_vec();
+        // A line of the maximum length still fits, and can be erased back to a command.
+        input.extend_from_slice(b"AT");
+        input.resize(input.len() + MAX_LINE_LEN - 2, b'E');
+        input.resize(input.len() + MAX_LINE_LEN - 2, BACKSPACE);
+        input.push(b'\r');
+        // A longer line is answered with an error even if it is erased back to a command, without
+        // affecting the following lines.
+        input.extend_from_slice(b"AT");
+        input.resize(input.len() + MAX_LINE_LEN - 1, b'E');
+        input.resize(input.len() + MAX_LINE_LEN, BACKSPACE);
+        input.extend_from_slice(b"\rAT\r");
+        assert_eq!(
+            serve(&input),
+            b"ATE0\r\r\nOK\r\n\r\nOK\r\n\r\nERROR\r\n\r\nOK\r\n"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! Serves attestation tokens as a CoAP resource.
+//!
+//! A verifier sends a POST request whose payload is its nonce, and receives an attestation token
+//! for that nonce with the Content-Format `application/eat+cwt`:
+//!
+//! ```ignore
+//! let attester = ariel_os::attestation::attester().await?;
+//! let handler = new_dispatcher().at(
+//!     &["attest"],
+//!     AttestationResource::new(attester, Evidence::new().with_secure_boot(true)),
+//! );
+//! ```
+//!
+//! The resource does not restrict who can obtain tokens; tokens contain only information that the
+//! device is meant to disclose to verifiers.
+
+use coap_message::{
+    Code as _, MinimalWritableMessage, MutableWritableMessage, OptionNumber as _, ReadableMessage,
+};
+use coap_message_utils::{Error as CoAPError, OptionsExt as _};
+
+use crate::{Attester, Evidence, MAX_NONCE_LEN, MAX_TOKEN_LEN, MIN_NONCE_LEN};
+
+/// CoAP Content-Format of `application/eat+cwt`.
+const CONTENT_FORMAT_EAT_CWT: u16 = 263;
+
+/// A CoAP resource that produces an attestation token for the nonce sent in a POST request.
+pub struct AttestationResource {
+    attester: Attester,
+    evidence: Evidence<'static>,
+}
+
+impl AttestationResource {
+    /// Creates a resource that produces tokens from the given attester and evidence.
+    #[must_use]
+    pub fn new(attester: Attester, evidence: Evidence<'static>) -> Self {
+        Self { attester, evidence }
+    }
+}
+
+impl coap_handler::Handler for AttestationResource {
+    type RequestData = heapless::Vec<u8, MAX_NONCE_LEN>;
+    type ExtractRequestError = CoAPError;
+    type BuildResponseError<M: MinimalWritableMessage> = CoAPError;
+
+    fn extract_request_data<M: ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, Self::ExtractRequestError> {
+        if request.code().into() != coap_numbers::code::POST {
+            return Err(CoAPError::method_not_allowed());
+        }
+        request.options().ignore_elective_others()?;
+
+        let nonce = request.payload();
+        if nonce.len() < MIN_NONCE_LEN {
+            return Err(CoAPError::bad_request());
+        }
+        heapless::Vec::from_slice(nonce).map_err(|()| CoAPError::bad_request())
+    }
+
+    fn estimate_length(&mut self, _nonce: &Self::RequestData) -> usize {
+        // Content-Format option and payload marker
+        MAX_TOKEN_LEN + 4
+    }
+
+    fn build_response<M: MutableWritableMessage>(
+        &mut self,
+        response: &mut M,
+        nonce: Self::RequestData,
+    ) -> Result<(), Self::BuildResponseError<M>> {
+        let mut buffer = [0; MAX_TOKEN_LEN];
+        let token = self
+            .attester
+            .token(&nonce, &self.evidence, &mut buffer)
+            .map_err(|_| CoAPError::internal_server_error())?;
+
+        response.set_code(
+            M::Code::new(coap_numbers::code::CHANGED).map_err(CoAPError::from_unionerror)?,
+        );
+        response
+            .add_option_uint(
+                M::OptionNumber::new(coap_numbers::option::CONTENT_FORMAT)
+                    .map_err(CoAPError::from_unionerror)?,
+                CONTENT_FORMAT_EAT_CWT,
+            )
+            .map_err(CoAPError::from_unionerror)?;
+        response
+            .set_payload(token)
+            .map_err(CoAPError::from_unionerror)?;
+        Ok(())
+    }
+}
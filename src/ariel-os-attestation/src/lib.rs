@@ -0,0 +1,256 @@
+//! Provides Entity Attestation Tokens (EAT) through which a device can prove its state to a
+//! backend.
+//!
+//! An attestation token is a set of claims as defined in
+//! [RFC9711](https://www.rfc-editor.org/rfc/rfc9711), encoded as a CBOR Web Token and signed with
+//! the device key (see [`ariel_os_identity::device_key`]) as a `COSE_Sign1` using ES256. A
+//! backend can verify it with the device's public key (eg. from its CCS recorded during
+//! provisioning) before it delivers secrets or configuration to the device.
+//!
+//! The token contains the following claims:
+//!
+//! * `eat_nonce`: the nonce chosen by the verifier, which proves the freshness of the token;
+//! * `ueid`: an identifier of type RAND, which is the SHA-256 digest of the device's public key;
+//! * `oemboot` and `dbgstat`: the boot state of the device, if provided in the [`Evidence`];
+//! * `measurements`: the SHA-256 digest of the firmware, if provided in the [`Evidence`]. It is
+//!   expressed as a single measurement with the Content-Format `application/octet-stream` whose
+//!   value is the digest.
+//!
+//! ```ignore
+//! let attester = ariel_os::attestation::attester().await?;
+//! let evidence = Evidence::new().with_firmware_digest(&firmware_digest);
+//! let mut buffer = [0; ariel_os::attestation::MAX_TOKEN_LEN];
+//! let token = attester.token(&nonce, &evidence, &mut buffer)?;
+//! ```
+//!
+//! With the `coap` feature, tokens can also be served to verifiers through the
+//! [`coap::AttestationResource`].
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+#[cfg(feature = "coap")]
+pub mod coap;
+
+use ariel_os_identity::device_key::{self, DeviceKey};
+use cosecore::{HeaderMap, alg};
+use sha2::{Digest, Sha256};
+
+/// Minimum length of the nonce of an attestation token.
+pub const MIN_NONCE_LEN: usize = 8;
+
+/// Maximum length of the nonce of an attestation token.
+pub const MAX_NONCE_LEN: usize = 64;
+
+/// Maximum length of an encoded claims set, with a nonce of [`MAX_NONCE_LEN`] and all
+/// [`Evidence`] present.
+const MAX_CLAIMS_LEN: usize = 160;
+
+/// Buffer size that is sufficient for any token produced by [`Attester::token()`].
+// Tag, array header, protected header, unprotected header, payload and signature
+pub const MAX_TOKEN_LEN: usize = 1 + 1 + 4 + 1 + (2 + MAX_CLAIMS_LEN) + (2 + 64);
+
+/// UEID type for identifiers that are random numbers.
+const UEID_TYPE_RAND: u8 = 0x01;
+/// Length of the UEID: the type byte and a SHA-256 digest.
+const UEID_LEN: usize = 33;
+
+/// CoAP Content-Format of `application/octet-stream`, used for the firmware digest measurement.
+const CONTENT_FORMAT_OCTET_STREAM: u16 = 42;
+
+/// Creates an [`Attester`] that signs with the device key.
+///
+/// # Errors
+///
+/// Returns [`Error::DeviceKey`] if the device key could not be loaded.
+pub async fn attester() -> Result<Attester, Error> {
+    let key = device_key::device_key().await.map_err(Error::DeviceKey)?;
+
+    let mut ueid = [0; UEID_LEN];
+    let (ueid_type, digest) = ueid.split_at_mut(1);
+    ueid_type.copy_from_slice(&[UEID_TYPE_RAND]);
+    digest.copy_from_slice(&Sha256::digest(key.public_key()));
+
+    Ok(Attester { key, ueid })
+}
+
+/// Produces attestation tokens signed with the device key.
+///
+/// Obtain it through [`attester()`].
+pub struct Attester {
+    key: DeviceKey,
+    ueid: [u8; UEID_LEN],
+}
+
+impl Attester {
+    /// Returns the UEID that identifies the device in the tokens.
+    #[must_use]
+    pub fn ueid(&self) -> &[u8] {
+        &self.ueid
+    }
+
+    /// Builds an attestation token for the given nonce and evidence into `buffer`.
+    ///
+    /// The nonce is chosen by the verifier, and needs to be between [`MIN_NONCE_LEN`] and
+    /// [`MAX_NONCE_LEN`] bytes long.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidNonce`] if the nonce has an unsupported length, and
+    /// [`Error::BufferTooSmall`] if the token does not fit into `buffer`; a buffer of
+    /// [`MAX_TOKEN_LEN`] bytes is always sufficient.
+    pub fn token<'b>(
+        &self,
+        nonce: &[u8],
+        evidence: &Evidence<'_>,
+        buffer: &'b mut [u8],
+    ) -> Result<&'b [u8], Error> {
+        if !(MIN_NONCE_LEN..=MAX_NONCE_LEN).contains(&nonce.len()) {
+            return Err(Error::InvalidNonce);
+        }
+
+        let claims = ClaimsSet {
+            nonce,
+            ueid: &self.ueid,
+            oemboot: evidence.secure_boot,
+            dbgstat: evidence.debug_status.map(|status| status as u8),
+            measurements: evidence.firmware_digest.map(|digest| {
+                [Measurement {
+                    content_type: CONTENT_FORMAT_OCTET_STREAM,
+                    content: digest,
+                }]
+            }),
+        };
+        let mut payload = [0; MAX_CLAIMS_LEN];
+        let mut cursor = minicbor::encode::write::Cursor::new(payload.as_mut_slice());
+        minicbor::encode(&claims, &mut cursor).map_err(|_| Error::BufferTooSmall)?;
+        let payload_len = cursor.position();
+        let payload = payload.get(..payload_len).ok_or(Error::BufferTooSmall)?;
+
+        cosecore::sign1(
+            &HeaderMap::new().with_alg(alg::ES256),
+            &HeaderMap::new(),
+            payload,
+            &[],
+            |to_be_signed| self.key.sign(to_be_signed),
+            buffer,
+        )
+        .map_err(|_| Error::BufferTooSmall)
+    }
+}
+
+/// Claims about the device's state that are included in attestation tokens.
+///
+/// Ariel OS can not determine these on its own; they are provided by the application, which may
+/// obtain them from the bootloader. Claims that are not set are left out of the token.
+#[derive(Debug, Default, Clone, Copy)]
+#[non_exhaustive]
+pub struct Evidence<'a> {
+    /// Whether the firmware was verified by the bootloader (the `oemboot` claim).
+    pub secure_boot: Option<bool>,
+    /// The state of the debug facilities (the `dbgstat` claim).
+    pub debug_status: Option<DebugStatus>,
+    /// The SHA-256 digest of the firmware.
+    pub firmware_digest: Option<&'a [u8; 32]>,
+}
+
+impl<'a> Evidence<'a> {
+    /// Creates evidence without any claims.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            secure_boot: None,
+            debug_status: None,
+            firmware_digest: None,
+        }
+    }
+
+    /// Sets whether the firmware was verified by the bootloader.
+    #[must_use]
+    pub const fn with_secure_boot(mut self, secure_boot: bool) -> Self {
+        self.secure_boot = Some(secure_boot);
+        self
+    }
+
+    /// Sets the state of the debug facilities.
+    #[must_use]
+    pub const fn with_debug_status(mut self, debug_status: DebugStatus) -> Self {
+        self.debug_status = Some(debug_status);
+        self
+    }
+
+    /// Sets the SHA-256 digest of the firmware.
+    #[must_use]
+    pub const fn with_firmware_digest(mut self, firmware_digest: &'a [u8; 32]) -> Self {
+        self.firmware_digest = Some(firmware_digest);
+        self
+    }
+}
+
+/// State of the debug facilities of the device, as expressed in the `dbgstat` claim.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DebugStatus {
+    /// Debugging is enabled.
+    Enabled = 0,
+    /// Debugging is disabled, but may be enabled again without a reboot.
+    Disabled = 1,
+    /// Debugging has been disabled since boot, and can not be enabled until the next boot.
+    DisabledSinceBoot = 2,
+    /// Debugging is permanently disabled for the device, but not for its components.
+    DisabledPermanently = 3,
+    /// Debugging is permanently disabled for the device and all its components.
+    DisabledFullyAndPermanently = 4,
+}
+
+/// An EAT claims set, as far as it is produced by this crate.
+///
+/// Full attribute references are in the [CWT Claims
+/// registry](https://www.iana.org/assignments/cwt/cwt.xhtml#claims-registry).
+#[derive(minicbor::Encode)]
+#[cbor(map)]
+struct ClaimsSet<'a> {
+    #[cbor(n(10), with = "minicbor::bytes")]
+    nonce: &'a [u8],
+    #[cbor(n(256), with = "minicbor::bytes")]
+    ueid: &'a [u8],
+    #[n(262)]
+    oemboot: Option<bool>,
+    #[n(263)]
+    dbgstat: Option<u8>,
+    #[n(273)]
+    measurements: Option<[Measurement<'a>; 1]>,
+}
+
+/// An entry of the `measurements` claim.
+#[derive(minicbor::Encode)]
+#[cbor(array)]
+struct Measurement<'a> {
+    #[n(0)]
+    content_type: u16,
+    #[cbor(n(1), with = "minicbor::bytes")]
+    content: &'a [u8],
+}
+
+/// Errors that can occur when producing attestation tokens.
+#[derive(Debug)]
+pub enum Error {
+    /// The device key could not be loaded.
+    DeviceKey(device_key::Error),
+    /// The nonce is too short or too long.
+    InvalidNonce,
+    /// The provided buffer is too small for the token.
+    BufferTooSmall,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::DeviceKey(error) => write!(f, "device key unavailable: {error}"),
+            Self::InvalidNonce => write!(f, "invalid nonce length"),
+            Self::BufferTooSmall => write!(f, "buffer too small"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
@@ -0,0 +1,151 @@
+//! Lowers the sample rate of captured frames.
+//!
+//! The [`Decimator`] is a third-order cascaded integrator-comb (CIC) filter, which low-pass
+//! filters the samples before keeping one out of every `factor`, so that frequencies above the
+//! new Nyquist frequency do not alias into the decimated samples.
+//! It requires no multiplication per sample, but attenuates the upper part of the remaining band
+//! as well: at a quarter of the new sample rate, by about 2.7 dB.
+//!
+//! ```
+//! use ariel_os_audio::decimation::Decimator;
+//!
+//! // 16 kHz to 2 kHz.
+//! let mut decimator = Decimator::new(8);
+//! let frame = [1000; 256];
+//! let mut decimated = [0; 32];
+//! assert_eq!(decimator.process(&frame, &mut decimated), 32);
+//! assert_eq!(decimated[31], 1000);
+//! ```
+
+/// Number of integrator and comb stages.
+const ORDER: u32 = 3;
+
+/// Decimates samples by a constant factor.
+#[derive(Debug, Clone)]
+pub struct Decimator {
+    factor: u16,
+    phase: u16,
+    integrators: [i64; ORDER as usize],
+    combs: [i64; ORDER as usize],
+}
+
+impl Decimator {
+    /// Creates a decimator keeping one out of every `factor` samples, which is at least 1.
+    #[must_use]
+    pub const fn new(factor: u16) -> Self {
+        Self {
+            factor: if factor == 0 { 1 } else { factor },
+            phase: 0,
+            integrators: [0; ORDER as usize],
+            combs: [0; ORDER as usize],
+        }
+    }
+
+    /// Returns the decimation factor.
+    #[must_use]
+    pub const fn factor(&self) -> u16 {
+        self.factor
+    }
+
+    /// Filters the `input` samples, and writes the decimated samples into `output`, whose
+    /// number it returns.
+    ///
+    /// Consecutive frames are filtered as a continuous signal, even if their length is not a
+    /// multiple of the factor.
+    /// `output` should hold `input.len() / factor + 1` samples; decimated samples that do not fit
+    /// are dropped.
+    pub fn process(&mut self, input: &[i16], output: &mut [i16]) -> usize {
+        // The gain of the filter is `factor^ORDER`.
+        let gain = i64::from(self.factor).pow(ORDER);
+        let mut len = 0;
+        for &sample in input {
+            let mut value = i64::from(sample);
+            for integrator in &mut self.integrators {
+                *integrator = integrator.wrapping_add(value);
+                value = *integrator;
+            }
+
+            self.phase += 1;
+            if self.phase < self.factor {
+                continue;
+            }
+            self.phase = 0;
+
+            for comb in &mut self.combs {
+                let delayed = core::mem::replace(comb, value);
+                value = value.wrapping_sub(delayed);
+            }
+            if let Some(slot) = output.get_mut(len) {
+                let value = (value / gain).clamp(i16::MIN.into(), i16::MAX.into());
+                // The value was clamped into the range of `i16`.
+                #[expect(clippy::cast_possible_truncation)]
+                let value = value as i16;
+                *slot = value;
+                len += 1;
+            }
+        }
+        len
+    }
+
+    /// Resets the state of the filter, eg. before filtering a discontinuous signal.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.factor);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dc() {
+        let mut decimator = Decimator::new(4);
+        let mut output = [0; 8];
+        assert_eq!(decimator.process(&[-2000; 32], &mut output), 8);
+        // The filter settles after `ORDER - 1` decimated samples.
+        assert_eq!(output[2..], [-2000; 6]);
+    }
+
+    #[test]
+    fn frames() {
+        let mut decimator = Decimator::new(3);
+        let mut output = [0; 8];
+        assert_eq!(decimator.process(&[100; 10], &mut output), 3);
+        // The phase is carried over.
+        assert_eq!(decimator.process(&[100; 5], &mut output), 2);
+        assert_eq!(output[..2], [100; 2]);
+
+        decimator.reset();
+        assert_eq!(decimator.process(&[100; 2], &mut output), 0);
+    }
+
+    #[test]
+    fn attenuation() {
+        let mut decimator = Decimator::new(4);
+        let input: [i16; 64] = core::array::from_fn(|i| if i % 2 == 0 { 10_000 } else { -10_000 });
+        let mut output = [0; 16];
+        decimator.process(&input, &mut output);
+        // The Nyquist frequency of the input is rejected, once the filter settled.
+        assert!(output[2..].iter().all(|sample| sample.abs() < 100));
+    }
+
+    #[test]
+    fn full_scale() {
+        let mut decimator = Decimator::new(16);
+        let mut output = [0; 4];
+        assert_eq!(decimator.process(&[i16::MIN; 64], &mut output), 4);
+        assert_eq!(output[3], i16::MIN);
+
+        let mut decimator = Decimator::new(0);
+        assert_eq!(decimator.factor(), 1);
+        assert_eq!(decimator.process(&[i16::MAX; 4], &mut output), 4);
+        assert_eq!(output, [i16::MAX; 4]);
+    }
+
+    #[test]
+    fn output_too_short() {
+        let mut decimator = Decimator::new(2);
+        let mut output = [0; 2];
+        assert_eq!(decimator.process(&[1; 8], &mut output), 2);
+    }
+}
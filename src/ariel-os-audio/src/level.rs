@@ -0,0 +1,113 @@
+/// Decibels per doubling of the amplitude, in hundredths of decibels.
+const CENTIBELS_PER_OCTAVE: i32 = 602;
+
+/// Number of fractional bits of the logarithms.
+const LOG2_FRACTION_BITS: u32 = 16;
+
+/// The sound level of samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Level {
+    /// Highest absolute value of the samples.
+    pub peak: u16,
+    /// Root mean square of the samples.
+    pub rms: u16,
+}
+
+impl Level {
+    /// Returns the level of `samples`, eg. of a frame.
+    ///
+    /// The DC offset of some microphones is included in the level; it is removed by high-pass
+    /// filtering the samples first.
+    #[must_use]
+    pub fn of(samples: &[i16]) -> Self {
+        let peak = samples
+            .iter()
+            .map(|sample| sample.unsigned_abs())
+            .max()
+            .unwrap_or(0);
+        let sum_of_squares: u64 = samples
+            .iter()
+            .map(|&sample| u64::from(sample.unsigned_abs()).pow(2))
+            .sum();
+        let mean_square = sum_of_squares / u64::try_from(samples.len().max(1)).unwrap_or(u64::MAX);
+        // The root of a mean of squares of `u16`s fits into a `u16`.
+        let rms = u16::try_from(mean_square.isqrt()).unwrap_or(u16::MAX);
+        Self { peak, rms }
+    }
+
+    /// Returns the RMS level relative to the full scale, in hundredths of dBFS.
+    ///
+    /// The level of a full-scale square wave is 0 dBFS, and that of silence is [`i16::MIN`].
+    #[must_use]
+    pub fn dbfs(&self) -> i16 {
+        if self.rms == 0 {
+            return i16::MIN;
+        }
+        let full_scale = 15 << LOG2_FRACTION_BITS;
+        let octaves = log2(u32::from(self.rms)) - full_scale;
+        let centibels = (octaves * CENTIBELS_PER_OCTAVE + (1 << (LOG2_FRACTION_BITS - 1)))
+            >> LOG2_FRACTION_BITS;
+        // The level lies between -90 dBFS and 0 dBFS.
+        i16::try_from(centibels).unwrap_or(i16::MIN)
+    }
+}
+
+/// Returns the base-2 logarithm of `x`, which is not 0, with [`LOG2_FRACTION_BITS`] fractional
+/// bits.
+fn log2(x: u32) -> i32 {
+    let integer = x.ilog2();
+    // Mantissa in [1, 2), with 31 fractional bits.
+    let mut mantissa = u64::from(x) << (31 - integer);
+    let mut fraction = 0;
+    for _ in 0..LOG2_FRACTION_BITS {
+        mantissa = (mantissa * mantissa) >> 31;
+        fraction <<= 1;
+        if mantissa >= 2 << 31 {
+            mantissa >>= 1;
+            fraction |= 1;
+        }
+    }
+    // The logarithm of a `u32` fits into an `i32`.
+    #[expect(clippy::cast_possible_wrap)]
+    let log2 = ((integer << LOG2_FRACTION_BITS) | fraction) as i32;
+    log2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level() {
+        assert_eq!(Level::of(&[]), Level { peak: 0, rms: 0 });
+        assert_eq!(Level::of(&[3, -4, 3, -4]), Level { peak: 4, rms: 3 });
+        assert_eq!(
+            Level::of(&[i16::MIN, i16::MAX]),
+            Level {
+                peak: 32768,
+                rms: 32767
+            }
+        );
+    }
+
+    #[test]
+    fn dbfs() {
+        let level = |rms| Level { peak: rms, rms };
+        assert_eq!(level(32767).dbfs(), 0);
+        assert_eq!(level(16384).dbfs(), -602);
+        // A full-scale sine wave.
+        assert_eq!(level(23170).dbfs(), -301);
+        assert_eq!(level(1).dbfs(), -9030);
+        assert_eq!(level(0).dbfs(), i16::MIN);
+    }
+
+    #[test]
+    fn log() {
+        assert_eq!(log2(1), 0);
+        assert_eq!(log2(2), 1 << 16);
+        assert_eq!(log2(1 << 20), 20 << 16);
+        // log2(3) = 1.58496…
+        assert!((103_871..=103_872).contains(&log2(3)));
+    }
+}
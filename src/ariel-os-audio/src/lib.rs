@@ -0,0 +1,91 @@
+//! Provides audio capture from digital microphones, eg. for sound-level sensing.
+//!
+//! Microphones capture frames of mono 16-bit PCM samples continuously, into DMA buffers which
+//! they alternate between, through the [`Microphone`] trait.
+//! It is implemented by the PDM peripheral of nRF MCUs for PDM microphones, and by the I2S
+//! peripheral of ESP32 MCUs for I2S microphones.
+//!
+//! Frames are passed to a closure while the next one is captured, which must therefore process
+//! them in less time than a frame lasts.
+//! A [`FrameStream`] decouples the processing from the capture, which lets tasks await frames:
+//!
+//! ```ignore
+//! static FRAMES: FrameStream<256> = FrameStream::new();
+//!
+//! #[ariel_os::task(autostart, peripherals)]
+//! async fn capture(peripherals: pins::Peripherals) {
+//!     let mut microphone = Pdm::new(peripherals.pdm, peripherals.clk, peripherals.din, config);
+//!     let _ = FRAMES.run(&mut microphone).await;
+//! }
+//!
+//! loop {
+//!     let frame = FRAMES.next().await;
+//!     info!("{} dBFS", Level::of(&frame).dbfs() / 100);
+//! }
+//! ```
+//!
+//! The sample rate of frames is lowered with a [`Decimator`](decimation::Decimator), eg. to
+//! save processing time when only the low frequencies are of interest.
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+pub mod decimation;
+mod level;
+mod stream;
+
+pub use level::Level;
+pub use stream::FrameStream;
+
+/// Tells whether a [`Microphone`] keeps capturing frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Flow {
+    /// Captures the next frame.
+    Continue,
+    /// Stops capturing.
+    Stop,
+}
+
+/// Captures frames of mono 16-bit PCM samples.
+pub trait Microphone {
+    /// Returns the number of samples captured per second.
+    fn sample_rate(&self) -> u32;
+
+    /// Captures frames of `N` samples continuously, and passes each of them to `sink`, until it
+    /// returns [`Flow::Stop`].
+    ///
+    /// `sink` is called while the next frame is captured, and must return before it is
+    /// complete.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Overrun`] if samples were lost because `sink` took too long, and
+    /// [`Error::Hardware`] if the microphone interface fails.
+    fn capture<const N: usize, S>(&mut self, sink: S) -> impl Future<Output = Result<(), Error>>
+    where
+        S: FnMut(&[i16; N]) -> Flow;
+}
+
+/// Audio-related errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// Samples were lost because frames were not processed in time.
+    Overrun,
+    /// The microphone interface failed.
+    Hardware,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Overrun => write!(f, "audio samples lost"),
+            Self::Hardware => write!(f, "audio hardware failure"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
@@ -0,0 +1,113 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Channel};
+
+use crate::{Error, Flow, Microphone};
+
+/// Number of captured frames that wait for being obtained.
+const QUEUE_LEN: usize = 2;
+
+/// Frames of `N` samples captured by a [`Microphone`], which tasks wait for.
+///
+/// Frames that are not obtained before the next two are captured are dropped, and counted as
+/// [overruns](FrameStream::overruns()).
+pub struct FrameStream<const N: usize> {
+    frames: Channel<CriticalSectionRawMutex, [i16; N], QUEUE_LEN>,
+    overruns: AtomicU32,
+}
+
+impl<const N: usize> FrameStream<N> {
+    /// Creates a stream of frames.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            frames: Channel::new(),
+            overruns: AtomicU32::new(0),
+        }
+    }
+
+    /// Captures frames from `microphone` into the stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of [`Microphone::capture()`].
+    pub async fn run<M: Microphone>(&self, microphone: &mut M) -> Result<(), Error> {
+        microphone
+            .capture(|frame: &[i16; N]| {
+                if self.frames.try_send(*frame).is_err() {
+                    self.overruns.fetch_add(1, Ordering::Relaxed);
+                }
+                Flow::Continue
+            })
+            .await
+    }
+
+    /// Waits for the next frame.
+    pub async fn next(&self) -> [i16; N] {
+        self.frames.receive().await
+    }
+
+    /// Returns the number of frames dropped because they were not obtained in time.
+    #[must_use]
+    pub fn overruns(&self) -> u32 {
+        self.overruns.load(Ordering::Relaxed)
+    }
+}
+
+impl<const N: usize> Default for FrameStream<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+
+    use super::*;
+
+    /// Captures frames counting up from 0, until the given number of frames was captured.
+    struct Counter {
+        frames: i16,
+    }
+
+    impl Microphone for Counter {
+        fn sample_rate(&self) -> u32 {
+            16_000
+        }
+
+        async fn capture<const N: usize, S>(&mut self, mut sink: S) -> Result<(), Error>
+        where
+            S: FnMut(&[i16; N]) -> Flow,
+        {
+            for i in 0..self.frames {
+                if sink(&[i; N]) == Flow::Stop {
+                    return Ok(());
+                }
+            }
+            Err(Error::Hardware)
+        }
+    }
+
+    #[test]
+    fn frames() {
+        let stream = FrameStream::<4>::new();
+        assert_eq!(
+            block_on(stream.run(&mut Counter { frames: 2 })),
+            Err(Error::Hardware)
+        );
+        assert_eq!(block_on(stream.next()), [0; 4]);
+        assert_eq!(block_on(stream.next()), [1; 4]);
+        assert_eq!(stream.overruns(), 0);
+    }
+
+    #[test]
+    fn overruns() {
+        let stream = FrameStream::<4>::new();
+        let _ = block_on(stream.run(&mut Counter { frames: 5 }));
+        assert_eq!(stream.overruns(), 3);
+        // The oldest frames are kept.
+        assert_eq!(block_on(stream.next()), [0; 4]);
+        assert_eq!(block_on(stream.next()), [1; 4]);
+    }
+}
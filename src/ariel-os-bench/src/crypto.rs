@@ -0,0 +1,250 @@
+//! Standardized benchmarks of cryptographic primitives.
+//!
+//! The benchmarks measure the primitives that Ariel OS and its network stacks rely on
+//! (SHA-256, AES-GCM, ECDSA P-256 signing and verification, and X25519 key agreement) on fixed
+//! inputs, so that results are comparable across boards and backends. They help choosing the
+//! backend to use on a given target.
+//!
+//! [`software()`] runs all benchmarks on the pure Rust implementations. Other backends (eg. drivers
+//! for hardware accelerators) can be measured with the same inputs by passing them to the
+//! individual benchmark functions, as long as they implement the corresponding
+//! [RustCrypto](https://github.com/RustCrypto) traits:
+//!
+//! ```ignore
+//! for measurement in ariel_os::bench::crypto::software(10)? {
+//!     info!("{}", measurement);
+//! }
+//! let hardware = ariel_os::bench::crypto::aes_128_gcm("cryptocell", &accelerated_cipher, 10)?;
+//! ```
+//!
+//! Results are reported in system timer ticks per operation (see [`benchmark()`]); on Cortex-M,
+//! these are CPU cycles. [`Measurement::bytes_per_second()`] converts them into a throughput.
+
+use core::hint::black_box;
+
+use aes_gcm::{
+    Aes128Gcm, KeyInit,
+    aead::{AeadInPlace, Nonce},
+};
+use p256::ecdsa::{
+    Signature, SigningKey,
+    signature::{Signer, Verifier},
+};
+use sha2::{Digest, Sha256};
+
+use crate::{Error, benchmark};
+
+/// Length of the message that is hashed or encrypted in the benchmarks of bulk operations.
+pub const MESSAGE_LEN: usize = 1024;
+
+/// The message processed by the benchmarks.
+const MESSAGE: [u8; MESSAGE_LEN] = [0x5a; MESSAGE_LEN];
+
+/// Fixed key material, so that the benchmarks do not depend on a random number generator.
+const KEY_MATERIAL: [u8; 32] = [0x2a; 32];
+
+/// Name of the backend measured by [`software()`].
+const SOFTWARE: &str = "software";
+
+/// A cryptographic operation that is benchmarked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Operation {
+    /// Hashing [`MESSAGE_LEN`] bytes with SHA-256.
+    Sha256,
+    /// Encrypting [`MESSAGE_LEN`] bytes with AES-128-GCM.
+    Aes128GcmEncrypt,
+    /// Signing a message with ECDSA on P-256.
+    P256Sign,
+    /// Verifying an ECDSA signature on P-256.
+    P256Verify,
+    /// A Diffie-Hellman key agreement on Curve25519.
+    X25519,
+}
+
+impl Operation {
+    /// Returns the number of bytes processed by one operation, for bulk operations.
+    #[must_use]
+    pub fn bytes(self) -> Option<usize> {
+        match self {
+            Self::Sha256 | Self::Aes128GcmEncrypt => Some(MESSAGE_LEN),
+            Self::P256Sign | Self::P256Verify | Self::X25519 => None,
+        }
+    }
+}
+
+impl core::fmt::Display for Operation {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Sha256 => write!(f, "SHA-256"),
+            Self::Aes128GcmEncrypt => write!(f, "AES-128-GCM encrypt"),
+            Self::P256Sign => write!(f, "P-256 sign"),
+            Self::P256Verify => write!(f, "P-256 verify"),
+            Self::X25519 => write!(f, "X25519"),
+        }
+    }
+}
+
+/// The result of benchmarking an operation on a backend.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Measurement {
+    /// The operation that was benchmarked.
+    pub operation: Operation,
+    /// The backend that performed the operation.
+    pub backend: &'static str,
+    /// The mean number of system timer ticks per operation.
+    pub ticks: usize,
+}
+
+impl Measurement {
+    /// Returns the throughput of a bulk operation in bytes per second, given the frequency of the
+    /// system timer in Hz (on Cortex-M, the CPU clock frequency).
+    ///
+    /// Returns `None` for operations that do not process bulk data.
+    #[must_use]
+    pub fn bytes_per_second(&self, timer_hz: u64) -> Option<u64> {
+        let bytes = u64::try_from(self.operation.bytes()?).ok()?;
+        let ticks = u64::try_from(self.ticks).ok()?;
+        (bytes * timer_hz).checked_div(ticks)
+    }
+}
+
+impl core::fmt::Display for Measurement {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{} ({}): {} ticks per operation",
+            self.operation, self.backend, self.ticks
+        )
+    }
+}
+
+/// Runs all benchmarks on the software implementations, running each operation `iterations`
+/// times.
+///
+/// # Errors
+///
+/// Returns an error if the system timer wrapped during a benchmark.
+pub fn software(iterations: usize) -> Result<[Measurement; 5], Error> {
+    let signing_key = signing_key();
+    let signature: Signature = signing_key.sign(&MESSAGE);
+
+    Ok([
+        sha256::<Sha256>(SOFTWARE, iterations)?,
+        aes_128_gcm(SOFTWARE, &Aes128Gcm::new(&[0x2a; 16].into()), iterations)?,
+        p256_sign(SOFTWARE, &signing_key, iterations)?,
+        p256_verify(
+            SOFTWARE,
+            signing_key.verifying_key(),
+            &signature,
+            iterations,
+        )?,
+        x25519(iterations)?,
+    ])
+}
+
+/// Benchmarks hashing [`MESSAGE_LEN`] bytes with the SHA-256 implementation `D`.
+///
+/// # Errors
+///
+/// Returns an error if the system timer wrapped during the benchmark.
+pub fn sha256<D: Digest>(backend: &'static str, iterations: usize) -> Result<Measurement, Error> {
+    measure(Operation::Sha256, backend, iterations, || {
+        black_box(D::digest(black_box(&MESSAGE)));
+    })
+}
+
+/// Benchmarks encrypting [`MESSAGE_LEN`] bytes with an AES-128-GCM `cipher`.
+///
+/// # Errors
+///
+/// Returns an error if the system timer wrapped during the benchmark.
+pub fn aes_128_gcm<A: AeadInPlace>(
+    backend: &'static str,
+    cipher: &A,
+    iterations: usize,
+) -> Result<Measurement, Error> {
+    let nonce = Nonce::<A>::default();
+    let mut buffer = MESSAGE;
+    measure(Operation::Aes128GcmEncrypt, backend, iterations, || {
+        // Encryption only fails for messages far longer than this one.
+        let _ = black_box(cipher.encrypt_in_place_detached(&nonce, &[], black_box(&mut buffer)));
+    })
+}
+
+/// Benchmarks signing a message with an ECDSA P-256 `signer`.
+///
+/// # Errors
+///
+/// Returns an error if the system timer wrapped during the benchmark.
+pub fn p256_sign<S: Signer<Signature>>(
+    backend: &'static str,
+    signer: &S,
+    iterations: usize,
+) -> Result<Measurement, Error> {
+    measure(Operation::P256Sign, backend, iterations, || {
+        black_box(signer.sign(black_box(&MESSAGE)));
+    })
+}
+
+/// Benchmarks verifying an ECDSA P-256 `signature` of the benchmark message with a `verifier`.
+///
+/// # Errors
+///
+/// Returns an error if the system timer wrapped during the benchmark.
+pub fn p256_verify<V: Verifier<Signature>>(
+    backend: &'static str,
+    verifier: &V,
+    signature: &Signature,
+    iterations: usize,
+) -> Result<Measurement, Error> {
+    measure(Operation::P256Verify, backend, iterations, || {
+        let _ = black_box(verifier.verify(black_box(&MESSAGE), signature));
+    })
+}
+
+/// Benchmarks an X25519 key agreement in software.
+///
+/// There is no common trait for Diffie-Hellman implementations; other backends can be measured
+/// with [`measure()`].
+///
+/// # Errors
+///
+/// Returns an error if the system timer wrapped during the benchmark.
+pub fn x25519(iterations: usize) -> Result<Measurement, Error> {
+    let secret = x25519_dalek::StaticSecret::from(KEY_MATERIAL);
+    let public = x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::from([0x17; 32]));
+    measure(Operation::X25519, SOFTWARE, iterations, || {
+        black_box(secret.diffie_hellman(black_box(&public)));
+    })
+}
+
+/// Benchmarks `f` as an implementation of `operation` on `backend`.
+///
+/// Unlike [`benchmark()`], this measures every iteration separately, so that the system timer
+/// does not wrap even when slow operations run many times.
+///
+/// # Errors
+///
+/// Returns an error if the system timer wrapped during an iteration.
+pub fn measure<F: FnMut()>(
+    operation: Operation,
+    backend: &'static str,
+    iterations: usize,
+    mut f: F,
+) -> Result<Measurement, Error> {
+    let mut total = 0;
+    for _ in 0..iterations {
+        total += benchmark(1, &mut f)?;
+    }
+    Ok(Measurement {
+        operation,
+        backend,
+        ticks: total.checked_div(iterations).unwrap_or(0),
+    })
+}
+
+fn signing_key() -> SigningKey {
+    SigningKey::from_bytes(&KEY_MATERIAL.into()).expect("the key material is a valid scalar")
+}
@@ -1,8 +1,12 @@
 //! Provides on-board benchmarking facilities.
 
 #![cfg_attr(not(test), no_std)]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
 #![deny(missing_docs)]
 
+#[cfg(feature = "crypto")]
+pub mod crypto;
+
 cfg_if::cfg_if! {
     if #[cfg(context = "cortex-m")] {
         mod cortexm;
@@ -0,0 +1,169 @@
+//! A minimal bootloader for A/B firmware updates.
+//!
+//! The bootloader shares the slot [`Layout`] and the boot state with [`ariel_os_update`], and
+//! boots firmware images in the MCUboot image format, as signed by `imgtool sign` with an ECDSA
+//! P-256 key. At every boot, [`Bootloader::prepare()`]:
+//!
+//! 1. stays in the bootloader if the firmware requested DFU mode through the [`mailbox`];
+//! 2. swaps the slots as requested by the firmware (see [`ariel_os_update::boot`]);
+//! 3. verifies the signature of the firmware in the active slot, and reverts an update on trial
+//!    whose signature is not valid.
+//!
+//! The firmware is then started through [`jump()`]:
+//!
+//! ```ignore
+//! let mut bootloader = Bootloader::new(flash, &keys)?;
+//! match bootloader.prepare().await? {
+//!     Boot::Firmware { vector_table } => unsafe { jump(vector_table) },
+//!     Boot::Dfu => run_dfu(bootloader.updater()).await,
+//! }
+//! ```
+//!
+//! As the slots are swapped through the state partition, it needs to span at least two erase
+//! pages (see [`ariel_os_update::boot`]). Firmware images are written with
+//! [`SlotWriter::finalize()`](ariel_os_update::SlotWriter::finalize), as this bootloader does not
+//! read the MCUboot trailer.
+//!
+//! # Configuration
+//!
+//! Besides the `CONFIG_UPDATE_*` environment variables of the layout, the bootloader is
+//! configured through the following environment variables, given as decimal numbers:
+//!
+//! | Environment variable                | Description                                   |
+//! | ----------------------------------- | --------------------------------------------- |
+//! | `CONFIG_BOOTLOADER_FLASH_BASE`      | Address the flash is mapped to (default: 0)   |
+//! | `CONFIG_BOOTLOADER_MAILBOX_ADDRESS` | Address of the [`mailbox`] in RAM             |
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+pub mod mailbox;
+
+pub use ariel_os_update::{Error, Layout, Partition, Updater};
+pub use p256::ecdsa::VerifyingKey;
+
+use ariel_os_update::{BootState, boot, layout, mcuboot};
+use embedded_storage_async::nor_flash::NorFlash;
+
+/// Address the flash is mapped to in memory.
+const FLASH_BASE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_BOOTLOADER_FLASH_BASE",
+    0,
+    "address the flash is mapped to in memory"
+);
+
+/// What to boot into, as decided by [`Bootloader::prepare()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Boot {
+    /// Start the firmware in the active slot, whose vector table is at the given address.
+    Firmware {
+        /// Address of the vector table of the firmware.
+        vector_table: usize,
+    },
+    /// Stay in the bootloader to receive a firmware, as requested through the [`mailbox`].
+    Dfu,
+}
+
+/// Prepares the slots for booting, and checks the firmware to boot.
+pub struct Bootloader<'k, F> {
+    updater: Updater<F>,
+    keys: &'k [VerifyingKey],
+}
+
+impl<'k, F: NorFlash> Bootloader<'k, F> {
+    /// Creates a bootloader for the [configured layout](layout::CONFIGURED) on `flash`, which
+    /// boots images signed by one of `keys`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Layout`] if the layout is not valid for the flash.
+    pub fn new(flash: F, keys: &'k [VerifyingKey]) -> Result<Self, Error> {
+        Self::with_layout(flash, layout::CONFIGURED, keys)
+    }
+
+    /// Creates a bootloader for the slots described by `layout` on `flash`, which boots images
+    /// signed by one of `keys`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Layout`] if the layout is not valid for the flash.
+    pub fn with_layout(flash: F, layout: Layout, keys: &'k [VerifyingKey]) -> Result<Self, Error> {
+        Ok(Self {
+            updater: Updater::new(flash, layout)?,
+            keys,
+        })
+    }
+
+    /// Returns the updater of the slots, eg. to write a firmware received in DFU mode.
+    pub fn updater(&mut self) -> &mut Updater<F> {
+        &mut self.updater
+    }
+
+    /// Prepares the slots for booting, and returns what to boot into.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidImage`] or [`Error::Unauthenticated`] if there is no firmware to
+    /// boot, in which case the bootloader should stay in DFU mode, and [`Error::Flash`] if
+    /// accessing the flash failed.
+    pub async fn prepare(&mut self) -> Result<Boot, Error> {
+        if mailbox::take() == Some(mailbox::Request::Dfu) {
+            return Ok(Boot::Dfu);
+        }
+        let state = boot::apply(&mut self.updater).await?;
+        match self.verify_active().await {
+            Err(Error::InvalidImage | Error::Unauthenticated) if state == BootState::Testing => {
+                // The update cannot be booted, so the previous firmware is swapped back in.
+                self.updater.mark_revert().await?;
+                boot::apply(&mut self.updater).await?;
+                self.verify_active().await
+            }
+            result => result,
+        }
+    }
+
+    /// Verifies the firmware in the active slot.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`mcuboot::verify()`].
+    async fn verify_active(&mut self) -> Result<Boot, Error> {
+        let header = mcuboot::verify(&mut self.updater, mcuboot::Slot::Primary, self.keys).await?;
+        let offset =
+            usize::try_from(self.updater.layout().active.offset).map_err(|_| Error::Layout)?;
+        Ok(Boot::Firmware {
+            vector_table: FLASH_BASE + offset + usize::from(header.header_size),
+        })
+    }
+}
+
+/// Starts the firmware whose vector table is at `vector_table`.
+///
+/// # Safety
+///
+/// `vector_table` needs to point to the vector table of a valid firmware, as returned by
+/// [`Bootloader::prepare()`]. Peripherals used by the bootloader should be reset before, as the
+/// firmware expects them in their reset state.
+pub unsafe fn jump(vector_table: usize) -> ! {
+    cfg_if::cfg_if! {
+        if #[cfg(context = "cortex-m")] {
+            /// Address of the Vector Table Offset Register.
+            const VTOR: usize = 0xe000_ed08;
+
+            // SAFETY: writing the VTOR is sound as interrupts of the bootloader are not used
+            // anymore, and the caller ensures that the vector table is valid.
+            unsafe {
+                core::ptr::with_exposed_provenance_mut::<usize>(VTOR).write_volatile(vector_table);
+                cortex_m::asm::bootload(core::ptr::with_exposed_provenance(vector_table))
+            }
+        } else if #[cfg(context = "ariel-os")] {
+            let _ = vector_table;
+            compile_error!("jumping to the firmware is not yet implemented for this platform")
+        } else {
+            let _ = vector_table;
+            #[expect(clippy::empty_loop, reason = "for platform-independent tooling only")]
+            loop {}
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! A mailbox in RAM through which the firmware sends requests to the bootloader.
+//!
+//! The mailbox is a word of RAM at the address configured through the
+//! `CONFIG_BOOTLOADER_MAILBOX_ADDRESS` environment variable, which needs to be the same for the
+//! firmware and the bootloader. That RAM needs to be left out of the memory regions of both, so
+//! that it is neither used nor initialized by either; its contents then survive a reboot.
+//!
+//! ```ignore
+//! // In the firmware:
+//! ariel_os::bootloader::mailbox::reboot_into_dfu();
+//! ```
+
+/// Address of the mailbox word.
+const ADDRESS: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_BOOTLOADER_MAILBOX_ADDRESS",
+    0,
+    "address of the bootloader mailbox in RAM"
+);
+
+const MAGIC_DFU: u32 = 0x4146_5544;
+
+/// A request from the firmware to the bootloader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Request {
+    /// Stay in the bootloader to receive a firmware through device firmware upgrade (DFU),
+    /// instead of booting the firmware.
+    Dfu,
+}
+
+impl Request {
+    const fn magic(self) -> u32 {
+        match self {
+            Self::Dfu => MAGIC_DFU,
+        }
+    }
+}
+
+/// Leaves `request` for the bootloader at the next boot.
+///
+/// # Panics
+///
+/// Panics if `CONFIG_BOOTLOADER_MAILBOX_ADDRESS` is not set.
+pub fn request(request: Request) {
+    // SAFETY: the mailbox word is reserved for this purpose, and left out of the memory regions
+    // of the firmware.
+    unsafe { mailbox().write_volatile(request.magic()) }
+}
+
+/// Takes the request left in the mailbox, if any.
+///
+/// The mailbox is cleared, so that the request is only handled once.
+///
+/// # Panics
+///
+/// Panics if `CONFIG_BOOTLOADER_MAILBOX_ADDRESS` is not set.
+#[must_use]
+pub fn take() -> Option<Request> {
+    let mailbox = mailbox();
+    // SAFETY: the mailbox word is reserved for this purpose, and left out of the memory regions
+    // of the bootloader.
+    let magic = unsafe { mailbox.read_volatile() };
+    // SAFETY: as above.
+    unsafe { mailbox.write_volatile(0) };
+    match magic {
+        MAGIC_DFU => Some(Request::Dfu),
+        _ => None,
+    }
+}
+
+/// Requests the bootloader to stay in DFU mode, and reboots into it.
+///
+/// # Panics
+///
+/// Panics if `CONFIG_BOOTLOADER_MAILBOX_ADDRESS` is not set.
+pub fn reboot_into_dfu() -> ! {
+    request(Request::Dfu);
+    ariel_os_power::reboot()
+}
+
+/// Returns a pointer to the mailbox word.
+///
+/// # Panics
+///
+/// Panics if `CONFIG_BOOTLOADER_MAILBOX_ADDRESS` is not set.
+fn mailbox() -> *mut u32 {
+    assert_ne!(
+        ADDRESS, 0,
+        "CONFIG_BOOTLOADER_MAILBOX_ADDRESS needs to be set to use the bootloader mailbox"
+    );
+    core::ptr::with_exposed_provenance_mut(ADDRESS)
+}
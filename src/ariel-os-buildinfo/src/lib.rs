@@ -14,3 +14,32 @@ pub const BOARD: &str = ariel_os_utils::str_from_env_or!(
 
 /// The operating system's name.
 pub const OS_NAME: &str = "Ariel OS";
+
+/// The operating system's version.
+pub const OS_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The application name.
+///
+/// The application name is read from the `CONFIG_APP_NAME` environment variable, which is
+/// expected to be provided by the build system.
+pub const APP_NAME: &str = ariel_os_utils::str_from_env_or!(
+    "CONFIG_APP_NAME",
+    "unknown",
+    "application name provided by the build system"
+);
+
+/// The application version.
+///
+/// The application version is read from the `CONFIG_APP_VERSION` environment variable.
+pub const APP_VERSION: &str =
+    ariel_os_utils::str_from_env_or!("CONFIG_APP_VERSION", "unknown", "application version");
+
+/// An identifier of the exact build, eg. the hash of the commit it was built from.
+///
+/// The build identifier is read from the `CONFIG_BUILD_HASH` environment variable, which can be
+/// set eg. by CI systems.
+pub const BUILD_HASH: &str = ariel_os_utils::str_from_env_or!(
+    "CONFIG_BUILD_HASH",
+    "unknown",
+    "identifier of the exact build"
+);
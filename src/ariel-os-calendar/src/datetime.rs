@@ -0,0 +1,239 @@
+//! Conversion between Unix time and calendar dates and times.
+
+/// Seconds in a day.
+pub(crate) const SECS_PER_DAY: i64 = 86_400;
+
+/// A day of the week.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Weekday {
+    /// Sunday.
+    Sunday,
+    /// Monday.
+    Monday,
+    /// Tuesday.
+    Tuesday,
+    /// Wednesday.
+    Wednesday,
+    /// Thursday.
+    Thursday,
+    /// Friday.
+    Friday,
+    /// Saturday.
+    Saturday,
+}
+
+impl Weekday {
+    /// Returns the number of the day, from 0 for Sunday to 6 for Saturday, as in cron schedules.
+    #[must_use]
+    pub fn number(self) -> u8 {
+        self as u8
+    }
+
+    fn from_number(number: u8) -> Self {
+        match number {
+            0 => Self::Sunday,
+            1 => Self::Monday,
+            2 => Self::Tuesday,
+            3 => Self::Wednesday,
+            4 => Self::Thursday,
+            5 => Self::Friday,
+            _ => Self::Saturday,
+        }
+    }
+}
+
+/// A date and time of the proleptic Gregorian calendar, at a resolution of seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DateTime {
+    /// The year.
+    pub year: i32,
+    /// The month, from 1 to 12.
+    pub month: u8,
+    /// The day of the month, from 1 to 31.
+    pub day: u8,
+    /// The hour, from 0 to 23.
+    pub hour: u8,
+    /// The minute, from 0 to 59.
+    pub minute: u8,
+    /// The second, from 0 to 59.
+    pub second: u8,
+    /// The day of the week.
+    pub weekday: Weekday,
+}
+
+impl DateTime {
+    /// Returns the given date and time, with its day of the week.
+    ///
+    /// Returns `None` if a component is out of its range, eg. for February 30.
+    #[must_use]
+    pub fn new(year: i32, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Option<Self> {
+        if !(1..=12).contains(&month)
+            || !(1..=days_in_month(year, month)).contains(&day)
+            || hour > 23
+            || minute > 59
+            || second > 59
+        {
+            return None;
+        }
+        let date_time = Self::from_local_seconds(days_from_civil(year, month, day) * SECS_PER_DAY);
+        Some(Self {
+            hour,
+            minute,
+            second,
+            ..date_time
+        })
+    }
+
+    /// Returns the date and time at `timestamp`, in seconds since the Unix epoch, in the time
+    /// zone of `offset`.
+    ///
+    /// Timestamps after the end of the year 9999 are clamped.
+    #[must_use]
+    pub fn from_unix(timestamp: u64, offset: crate::UtcOffset) -> Self {
+        Self::from_local_seconds(crate::timestamp_seconds(timestamp) + i64::from(offset.seconds))
+    }
+
+    /// Returns the date and time in seconds since the Unix epoch, as a local time in the time zone
+    /// of `offset`.
+    ///
+    /// Returns `None` for times before the Unix epoch.
+    #[must_use]
+    pub fn to_unix(self, offset: crate::UtcOffset) -> Option<u64> {
+        u64::try_from(self.to_local_seconds() - i64::from(offset.seconds)).ok()
+    }
+
+    /// Returns the date and time at `seconds` since the Unix epoch, ignoring time zones.
+    // Components are reduced to their ranges before the casts.
+    #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub(crate) fn from_local_seconds(seconds: i64) -> Self {
+        let days = seconds.div_euclid(SECS_PER_DAY);
+        let time = seconds.rem_euclid(SECS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        Self {
+            year,
+            month,
+            day,
+            hour: (time / 3600) as u8,
+            minute: (time / 60 % 60) as u8,
+            second: (time % 60) as u8,
+            // 1970-01-01 was a Thursday.
+            weekday: Weekday::from_number((days + 4).rem_euclid(7) as u8),
+        }
+    }
+
+    /// Returns the date and time in seconds since the Unix epoch, ignoring time zones.
+    pub(crate) fn to_local_seconds(self) -> i64 {
+        days_from_civil(self.year, self.month, self.day) * SECS_PER_DAY
+            + i64::from(self.hour) * 3600
+            + i64::from(self.minute) * 60
+            + i64::from(self.second)
+    }
+}
+
+/// Returns the number of days since the Unix epoch of the start of the given date.
+pub(crate) fn days_from_civil(year: i32, month: u8, day: u8) -> i64 {
+    // See <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+    let year = i64::from(year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let shifted_month = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * shifted_month + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Returns the year, month and day of the day at `days` since the Unix epoch.
+// Components are reduced to their ranges before the casts; years fit into `i32` for the
+// timestamps supported.
+#[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub(crate) fn civil_from_days(days: i64) -> (i32, u8, u8) {
+    // See <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year as i32, month as u8, day as u8)
+}
+
+/// Returns the number of days of the given month.
+pub(crate) fn days_in_month(year: i32, month: u8) -> u8 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UtcOffset;
+
+    #[test]
+    fn conversion() {
+        let epoch = DateTime::from_unix(0, UtcOffset::UTC);
+        assert_eq!((epoch.year, epoch.month, epoch.day), (1970, 1, 1));
+        assert_eq!(epoch.weekday, Weekday::Thursday);
+
+        // 2024-02-29T13:45:30Z
+        let leap = DateTime::from_unix(1_709_214_330, UtcOffset::UTC);
+        assert_eq!(
+            leap,
+            DateTime {
+                year: 2024,
+                month: 2,
+                day: 29,
+                hour: 13,
+                minute: 45,
+                second: 30,
+                weekday: Weekday::Thursday,
+            }
+        );
+
+        let local = DateTime::from_unix(1_709_214_330, UtcOffset::from_minutes(-14 * 60));
+        assert_eq!((local.day, local.hour), (28, 23));
+        assert_eq!(local.weekday, Weekday::Wednesday);
+    }
+
+    #[test]
+    fn construction() {
+        let leap = DateTime::new(2024, 2, 29, 13, 45, 30).unwrap();
+        assert_eq!(leap.weekday, Weekday::Thursday);
+        assert_eq!(leap.to_unix(UtcOffset::UTC), Some(1_709_214_330));
+        assert_eq!(
+            leap.to_unix(UtcOffset::from_minutes(60)),
+            Some(1_709_214_330 - 3600)
+        );
+
+        assert_eq!(DateTime::new(2023, 2, 29, 0, 0, 0), None);
+        assert_eq!(DateTime::new(2024, 13, 1, 0, 0, 0), None);
+        assert_eq!(DateTime::new(2024, 1, 1, 24, 0, 0), None);
+        assert_eq!(
+            DateTime::new(1969, 12, 31, 23, 59, 59).and_then(|d| d.to_unix(UtcOffset::UTC)),
+            None
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        for days in (-800_000..800_000).step_by(97) {
+            let (year, month, day) = civil_from_days(days);
+            assert!(day >= 1 && day <= days_in_month(year, month));
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+}
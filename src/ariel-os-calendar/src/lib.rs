@@ -0,0 +1,160 @@
+//! Provides a wall clock, and alarms on recurring calendar schedules in local time.
+//!
+//! Ariel OS does not keep track of the wall clock time on its own; the application sets it with
+//! [`set_now()`] once it knows the time, eg. from a real time clock or obtained through the
+//! network. From then on, the wall clock follows the system timer. Sources that mark the start of
+//! a second precisely, like the PPS output of GNSS receivers, set it with [`set_now_at()`].
+//!
+//! [`Schedule`]s describe recurring times like cron does, eg. every day at 02:00, and
+//! [`wait_next()`] waits until the next of them in the local time of a [`TimeZone`]:
+//!
+//! ```ignore
+//! use ariel_os::calendar::{self, Schedule, TimeZone};
+//!
+//! const MAINTENANCE: Schedule = Schedule::daily(2, 0);
+//!
+//! let zone = TimeZone::from_posix("CET-1CEST,M3.5.0,M10.5.0/3")?;
+//! loop {
+//!     calendar::wait_next(&MAINTENANCE, &zone).await?;
+//!     run_maintenance().await;
+//! }
+//! ```
+//!
+//! Time zones are either fixed offsets from UTC, or follow the rules of daylight saving time of
+//! a POSIX `TZ` string or TZif data. With the `storage` feature, the time zone of the device can
+//! be persisted with [`store_time_zone()`].
+//!
+//! # Limitations
+//!
+//! Alarms are timers of the system timer, which keep the MCU in its usual sleep states while
+//! waiting; waking the MCU from deeper standby states through a real time clock is not supported.
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+mod datetime;
+mod schedule;
+mod tz;
+
+use core::cell::Cell;
+
+use embassy_time::{Duration, Instant, Timer};
+
+pub use datetime::{DateTime, Weekday};
+pub use schedule::Schedule;
+pub use tz::TimeZone;
+#[cfg(feature = "storage")]
+pub use tz::{load_time_zone, store_time_zone};
+
+/// Maximum time an alarm sleeps before checking the wall clock again, so that changes of the
+/// wall clock are taken into account.
+const MAX_SLEEP: Duration = Duration::from_secs(3600);
+
+/// The last second of the year 9999, in seconds since the Unix epoch.
+const MAX_TIMESTAMP: u64 = 253_402_300_799;
+
+/// The wall clock time at the start of the system timer, in microseconds since the Unix epoch.
+static EPOCH: critical_section::Mutex<Cell<Option<u64>>> =
+    critical_section::Mutex::new(Cell::new(None));
+
+/// An offset from UTC of a time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct UtcOffset {
+    seconds: i32,
+}
+
+impl UtcOffset {
+    /// The offset of UTC itself.
+    pub const UTC: Self = Self { seconds: 0 };
+
+    /// Returns the offset of `minutes` ahead of UTC (negative west of UTC).
+    #[must_use]
+    pub const fn from_minutes(minutes: i16) -> Self {
+        Self {
+            seconds: minutes as i32 * 60,
+        }
+    }
+}
+
+/// Errors that can occur when working with the calendar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The schedule is malformed.
+    InvalidSchedule,
+    /// The wall clock has not been set.
+    ClockNotSet,
+    /// The schedule does not match any time.
+    NoOccurrence,
+    /// The time zone rule is malformed.
+    InvalidTimeZone,
+    /// Accessing the storage failed.
+    Storage,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::InvalidSchedule => write!(f, "invalid schedule"),
+            Self::ClockNotSet => write!(f, "wall clock not set"),
+            Self::NoOccurrence => write!(f, "schedule never matches"),
+            Self::InvalidTimeZone => write!(f, "invalid time zone"),
+            Self::Storage => write!(f, "storage access failed"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Sets the wall clock to `timestamp`, in seconds since the Unix epoch.
+pub fn set_now(timestamp: u64) {
+    set_now_at(timestamp, Instant::now());
+}
+
+/// Sets the wall clock so that the second `timestamp`, in seconds since the Unix epoch, started at
+/// `instant` of the system timer.
+pub fn set_now_at(timestamp: u64, instant: Instant) {
+    let epoch = timestamp
+        .saturating_mul(1_000_000)
+        .saturating_sub(instant.as_micros());
+    critical_section::with(|cs| EPOCH.borrow(cs).set(Some(epoch)));
+}
+
+/// Returns the wall clock time, in seconds since the Unix epoch.
+///
+/// Returns `None` if the wall clock has not been set with [`set_now()`].
+#[must_use]
+pub fn now() -> Option<u64> {
+    let epoch = critical_section::with(|cs| EPOCH.borrow(cs).get())?;
+    Some(epoch.saturating_add(Instant::now().as_micros()) / 1_000_000)
+}
+
+/// Waits until the next time matching `schedule` in the local time of `zone`, and returns that
+/// time, in seconds since the Unix epoch.
+///
+/// When the wall clock is changed while waiting, the alarm follows the change within an hour.
+///
+/// # Errors
+///
+/// Returns [`Error::ClockNotSet`] if the wall clock has not been set, and
+/// [`Error::NoOccurrence`] if the schedule does not match any time.
+pub async fn wait_next(schedule: &Schedule, zone: &TimeZone) -> Result<u64, Error> {
+    let next = schedule
+        .next_after(now().ok_or(Error::ClockNotSet)?, zone)
+        .ok_or(Error::NoOccurrence)?;
+    loop {
+        let current = now().ok_or(Error::ClockNotSet)?;
+        if current >= next {
+            return Ok(next);
+        }
+        Timer::after(Duration::from_secs(next - current).min(MAX_SLEEP)).await;
+    }
+}
+
+/// Returns `timestamp` as a signed number of seconds, clamped to the end of the year 9999.
+fn timestamp_seconds(timestamp: u64) -> i64 {
+    // Cannot fail, the clamped timestamp fits.
+    i64::try_from(timestamp.min(MAX_TIMESTAMP)).unwrap_or_default()
+}
@@ -0,0 +1,322 @@
+//! Recurring schedules of calendar dates and times.
+
+use crate::{
+    Error, TimeZone,
+    datetime::{DateTime, SECS_PER_DAY, Weekday, days_from_civil},
+};
+
+/// Number of years searched for the next occurrence of a schedule, which covers schedules on
+/// February 29 across the non-leap years at the turn of centuries.
+const SEARCH_YEARS: i64 = 9;
+
+/// A recurring schedule, at a resolution of minutes, like the ones of cron.
+///
+/// A time matches the schedule when its minute, hour, month, and day match. The day matches when
+/// either its day of the month or its day of the week does, unless one of them is not restricted
+/// (`*` in cron), in which case only the other one needs to match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Schedule {
+    minutes: u64,
+    hours: u32,
+    days: u32,
+    months: u16,
+    weekdays: u8,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Schedule {
+    /// Returns a schedule at `minute` past every hour.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `minute` is larger than 59.
+    #[must_use]
+    pub const fn hourly(minute: u8) -> Self {
+        Self::daily_at_hours(u32::MAX >> 8, minute)
+    }
+
+    /// Returns a schedule at `hour`:`minute` every day.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `hour` is larger than 23, or `minute` is larger than 59.
+    #[must_use]
+    pub const fn daily(hour: u8, minute: u8) -> Self {
+        assert!(hour < 24, "invalid hour");
+        Self::daily_at_hours(1 << hour, minute)
+    }
+
+    /// Returns a schedule at `hour`:`minute` every week, on `weekday`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `hour` is larger than 23, or `minute` is larger than 59.
+    #[must_use]
+    pub const fn weekly(weekday: Weekday, hour: u8, minute: u8) -> Self {
+        let mut schedule = Self::daily(hour, minute);
+        schedule.weekdays = 1 << weekday as u8;
+        schedule.any_weekday = false;
+        schedule
+    }
+
+    /// Returns a schedule at `minute` past the hours of the `hours` mask, every day.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `minute` is larger than 59.
+    const fn daily_at_hours(hours: u32, minute: u8) -> Self {
+        assert!(minute < 60, "invalid minute");
+        Self {
+            minutes: 1 << minute,
+            hours,
+            days: u32::MAX << 1,
+            months: 0b1_1111_1111_1110,
+            weekdays: u8::MAX >> 1,
+            any_day: true,
+            any_weekday: true,
+        }
+    }
+
+    /// Parses a schedule in the syntax of cron, eg. `30 2 * * 1-5` for 02:30 on weekdays.
+    ///
+    /// The five fields are the minute, hour, day of the month, month and day of the week (0 or 7
+    /// for Sunday). Each field is `*`, or a list of values and ranges separated by commas, where
+    /// `*` and ranges can be followed by a step, as in `*/15` or `8-18/2`. Names of months and
+    /// days are not supported.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidSchedule`] if `cron` is not a valid schedule.
+    pub fn from_cron(cron: &str) -> Result<Self, Error> {
+        let mut fields = cron.split_ascii_whitespace();
+        let mut next_field = |min, max| {
+            let field = fields.next().ok_or(Error::InvalidSchedule)?;
+            parse_field(field, min, max)
+                .map(|values| (values, field.starts_with('*')))
+                .ok_or(Error::InvalidSchedule)
+        };
+        let (minutes, _) = next_field(0, 59)?;
+        let (hours, _) = next_field(0, 23)?;
+        let (days, any_day) = next_field(1, 31)?;
+        let (months, _) = next_field(1, 12)?;
+        let (weekdays, any_weekday) = next_field(0, 7)?;
+        if fields.next().is_some() {
+            return Err(Error::InvalidSchedule);
+        }
+
+        // The values were checked to be in range, so the masks fit.
+        #[expect(clippy::cast_possible_truncation)]
+        Ok(Self {
+            minutes,
+            hours: hours as u32,
+            days: days as u32,
+            months: months as u16,
+            // Sunday is both 0 and 7.
+            weekdays: ((weekdays | weekdays >> 7) & 0x7f) as u8,
+            any_day,
+            any_weekday,
+        })
+    }
+
+    /// Returns whether the schedule matches the minute of `date_time`.
+    #[must_use]
+    pub fn matches(&self, date_time: &DateTime) -> bool {
+        self.minutes & 1 << date_time.minute != 0
+            && self.hours & 1 << date_time.hour != 0
+            && self.months & 1 << date_time.month != 0
+            && self.matches_day(date_time)
+    }
+
+    fn matches_day(&self, date_time: &DateTime) -> bool {
+        let day = self.days & 1 << date_time.day != 0;
+        let weekday = self.weekdays & 1 << date_time.weekday.number() != 0;
+        match (self.any_day, self.any_weekday) {
+            (true, _) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+
+    /// Returns the first time matching the schedule after `timestamp`, in seconds since the Unix
+    /// epoch, with the schedule applying in the local time of `zone`.
+    ///
+    /// Local times skipped when daylight saving time starts are shifted by the length of the gap,
+    /// and local times repeated when it ends only match once, see [`TimeZone::timestamp()`].
+    ///
+    /// Returns `None` if the schedule does not match any time, eg. on February 30.
+    #[must_use]
+    pub fn next_after(&self, timestamp: u64, zone: &TimeZone) -> Option<u64> {
+        let timestamp = crate::timestamp_seconds(timestamp);
+        let mut local = zone.local_seconds(timestamp);
+        let end = local + SEARCH_YEARS * 366 * SECS_PER_DAY;
+        loop {
+            local = self.next_local_after(local, end)?;
+            if let Some(next) = zone.timestamp_after(local, timestamp) {
+                return u64::try_from(next).ok();
+            }
+        }
+    }
+
+    /// Returns the first local time matching the schedule after `local` and before `end`, in
+    /// seconds since the Unix epoch ignoring time zones.
+    fn next_local_after(&self, local: i64, end: i64) -> Option<i64> {
+        let mut time = (local.div_euclid(60) + 1) * 60;
+        while time < end {
+            let date_time = DateTime::from_local_seconds(time);
+            time = if self.months & 1 << date_time.month == 0 {
+                let (year, month) = if date_time.month == 12 {
+                    (date_time.year + 1, 1)
+                } else {
+                    (date_time.year, date_time.month + 1)
+                };
+                days_from_civil(year, month, 1) * SECS_PER_DAY
+            } else if !self.matches_day(&date_time) {
+                (time.div_euclid(SECS_PER_DAY) + 1) * SECS_PER_DAY
+            } else if self.hours & 1 << date_time.hour == 0 {
+                (time.div_euclid(3600) + 1) * 3600
+            } else if self.minutes & 1 << date_time.minute == 0 {
+                time + 60
+            } else {
+                return Some(time);
+            };
+        }
+        None
+    }
+}
+
+/// Parses a field of a cron schedule, and returns the mask of its values.
+fn parse_field(field: &str, min: u8, max: u8) -> Option<u64> {
+    field.split(',').try_fold(0, |mask, item| {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, Some(step.parse::<u8>().ok().filter(|&s| s > 0)?)),
+            None => (item, None),
+        };
+        let (first, last) = match (range, range.split_once('-')) {
+            ("*", _) => (min, max),
+            (_, Some((first, last))) => (first.parse().ok()?, last.parse().ok()?),
+            // A single value with a step extends up to the maximum.
+            (value, None) => {
+                let value = value.parse().ok()?;
+                (value, if step.is_some() { max } else { value })
+            }
+        };
+        if first < min || last > max || first > last {
+            return None;
+        }
+        let values = (first..=last).step_by(usize::from(step.unwrap_or(1)));
+        Some(values.fold(mask, |mask, value| mask | 1 << value))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UtcOffset;
+
+    /// 2024-01-01T00:00:00Z, a Monday.
+    const NEW_YEAR: u64 = 1_704_067_200;
+    const HOUR: u64 = 3600;
+    const DAY: u64 = 86_400;
+
+    #[test]
+    fn daily() {
+        let schedule = Schedule::daily(2, 0);
+        assert_eq!(
+            schedule.next_after(NEW_YEAR, &TimeZone::UTC),
+            Some(NEW_YEAR + 2 * HOUR)
+        );
+        assert_eq!(
+            schedule.next_after(NEW_YEAR + 2 * HOUR, &TimeZone::UTC),
+            Some(NEW_YEAR + DAY + 2 * HOUR)
+        );
+        // 02:00 in UTC+01:00 is 01:00 UTC.
+        assert_eq!(
+            schedule.next_after(NEW_YEAR, &TimeZone::fixed(UtcOffset::from_minutes(60))),
+            Some(NEW_YEAR + HOUR)
+        );
+        assert_eq!(Schedule::from_cron("0 2 * * *"), Ok(schedule));
+    }
+
+    #[test]
+    fn weekly() {
+        let schedule = Schedule::weekly(Weekday::Sunday, 12, 30);
+        assert_eq!(
+            schedule.next_after(NEW_YEAR, &TimeZone::UTC),
+            Some(NEW_YEAR + 6 * DAY + 12 * HOUR + 30 * 60)
+        );
+        assert_eq!(Schedule::from_cron("30 12 * * 7"), Ok(schedule));
+        assert_eq!(Schedule::from_cron("30 12 * * 0"), Ok(schedule));
+    }
+
+    #[test]
+    fn daylight_saving_time() {
+        let cet = TimeZone::from_posix("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        let schedule = Schedule::daily(2, 30);
+
+        // 2024-03-30T01:30:00Z, 02:30 the day before 02:00 is skipped to 03:00.
+        let start = 1_711_762_200;
+        assert_eq!(schedule.next_after(start - HOUR, &cet), Some(start));
+        assert_eq!(schedule.next_after(start, &cet), Some(start + DAY));
+        assert_eq!(
+            schedule.next_after(start + DAY, &cet),
+            Some(start + 2 * DAY - HOUR)
+        );
+
+        // 2024-10-27T00:30:00Z, 02:30 before 03:00 is repeated from 02:00.
+        let end = 1_729_989_000;
+        assert_eq!(schedule.next_after(end - HOUR, &cet), Some(end));
+        assert_eq!(schedule.next_after(end, &cet), Some(end + DAY + HOUR));
+    }
+
+    #[test]
+    fn cron() {
+        // Every 15 minutes during working hours on weekdays.
+        let schedule = Schedule::from_cron("*/15 8-17 * * 1-5").unwrap();
+        assert_eq!(
+            schedule.next_after(NEW_YEAR + 8 * HOUR + 50 * 60, &TimeZone::UTC),
+            Some(NEW_YEAR + 9 * HOUR)
+        );
+        // From Friday evening to Monday morning.
+        assert_eq!(
+            schedule.next_after(NEW_YEAR + 4 * DAY + 18 * HOUR, &TimeZone::UTC),
+            Some(NEW_YEAR + 7 * DAY + 8 * HOUR)
+        );
+
+        // On the 13th, or on Fridays.
+        let schedule = Schedule::from_cron("0 0 13 * 5").unwrap();
+        assert_eq!(
+            schedule.next_after(NEW_YEAR, &TimeZone::UTC),
+            Some(NEW_YEAR + 4 * DAY)
+        );
+        assert_eq!(
+            schedule.next_after(NEW_YEAR + 11 * DAY, &TimeZone::UTC),
+            Some(NEW_YEAR + 12 * DAY)
+        );
+
+        // Leap days only.
+        let schedule = Schedule::from_cron("0 0 29 2 *").unwrap();
+        assert_eq!(
+            schedule.next_after(NEW_YEAR + 60 * DAY, &TimeZone::UTC),
+            Some(NEW_YEAR + (4 * 365 + 1 + 59) * DAY)
+        );
+        assert_eq!(
+            Schedule::from_cron("0 0 30 2 *")
+                .unwrap()
+                .next_after(NEW_YEAR, &TimeZone::UTC),
+            None
+        );
+
+        for invalid in [
+            "",
+            "0 2 * *",
+            "0 2 * * * *",
+            "60 * * * *",
+            "0 0 0 * *",
+            "*/0 * * * *",
+        ] {
+            assert_eq!(Schedule::from_cron(invalid), Err(Error::InvalidSchedule));
+        }
+    }
+}
@@ -0,0 +1,542 @@
+//! Time zones, with their daylight saving time rules.
+
+use crate::{
+    Error, UtcOffset,
+    datetime::{DateTime, SECS_PER_DAY, civil_from_days, days_from_civil, days_in_month},
+};
+
+/// Storage key under which the time zone is persisted.
+#[cfg(feature = "storage")]
+const TIME_ZONE_KEY: &str = "ariel-os-calendar.time-zone";
+
+/// Maximum length of the rule of a persisted time zone.
+#[cfg(feature = "storage")]
+const MAX_RULE_LEN: usize = 64;
+
+/// Time of the day of daylight saving time transitions when a rule does not specify it.
+const DEFAULT_TRANSITION_TIME: i32 = 2 * 3600;
+
+/// A time zone, with a standard offset from UTC and optionally daylight saving time.
+///
+/// Time zones are described by rules in the format of the `TZ` environment variable of POSIX,
+/// eg. `CET-1CEST,M3.5.0,M10.5.0/3` for Central European Time, which can also be extracted from
+/// TZif files (as in `/usr/share/zoneinfo`). Only the current rule is kept: conversions of past
+/// times apply it even if the time zone had different rules back then.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TimeZone {
+    std: UtcOffset,
+    dst: Option<Dst>,
+}
+
+/// Daylight saving time of a time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Dst {
+    offset: UtcOffset,
+    start: Transition,
+    end: Transition,
+}
+
+/// A yearly transition between standard and daylight saving time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Transition {
+    date: TransitionDate,
+    /// Local time of the transition, in seconds after the start of the day, in the time in effect
+    /// before the transition.
+    time: i32,
+}
+
+/// The day of the year of a [`Transition`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum TransitionDate {
+    /// Day of the year from 1 to 365, not counting February 29 (`Jn`).
+    Julian(u16),
+    /// Day of the year from 0 to 365, counting February 29 (`n`).
+    Day(u16),
+    /// Day of the week from 0 (Sunday) to 6, of the week from 1 to 5 (the last one) of the month
+    /// (`Mm.w.d`).
+    MonthWeekDay { month: u8, week: u8, weekday: u8 },
+}
+
+impl TimeZone {
+    /// The time zone of UTC itself.
+    pub const UTC: Self = Self::fixed(UtcOffset::UTC);
+
+    /// Returns a time zone with the fixed `offset`, without daylight saving time.
+    #[must_use]
+    pub const fn fixed(offset: UtcOffset) -> Self {
+        Self {
+            std: offset,
+            dst: None,
+        }
+    }
+
+    /// Parses a time zone rule in the format of the `TZ` environment variable of POSIX, eg.
+    /// `CET-1CEST,M3.5.0,M10.5.0/3`.
+    ///
+    /// Offsets in these rules are counted positive west of UTC. The extensions of RFC 8536 are
+    /// supported; rules with daylight saving time need to specify its start and end.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidTimeZone`] if `rule` is not a valid rule.
+    pub fn from_posix(rule: &str) -> Result<Self, Error> {
+        let mut parser = Parser {
+            rest: rule.as_bytes(),
+        };
+        parser.time_zone().ok_or(Error::InvalidTimeZone)
+    }
+
+    /// Parses the rule of a time zone from TZif data (RFC 8536), as in the files of
+    /// `/usr/share/zoneinfo`.
+    ///
+    /// Only the rule for future times at the end of the data is used, which requires TZif data
+    /// of version 2 or later.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidTimeZone`] if `tzif` is not TZif data of version 2 or later with
+    /// a valid rule.
+    pub fn from_tzif(tzif: &[u8]) -> Result<Self, Error> {
+        Self::from_posix(tzif_rule(tzif).ok_or(Error::InvalidTimeZone)?)
+    }
+
+    /// Returns the offset from UTC in the time zone at `timestamp`, in seconds since the Unix
+    /// epoch.
+    #[must_use]
+    pub fn offset_at(&self, timestamp: u64) -> UtcOffset {
+        self.offset_at_seconds(crate::timestamp_seconds(timestamp))
+    }
+
+    /// Returns the local date and time at `timestamp`, in seconds since the Unix epoch.
+    #[must_use]
+    pub fn local_time(&self, timestamp: u64) -> DateTime {
+        DateTime::from_unix(timestamp, self.offset_at(timestamp))
+    }
+
+    /// Returns the time at which the local time is `date_time`, in seconds since the Unix epoch.
+    ///
+    /// When daylight saving time ends and the local time occurs twice, this returns the earlier
+    /// one. When it starts and the local time is skipped, this returns the time the given local
+    /// time would have had without the transition, which is later than the transition.
+    ///
+    /// Returns `None` if the time is before the Unix epoch.
+    #[must_use]
+    pub fn timestamp(&self, date_time: &DateTime) -> Option<u64> {
+        let utc = self.timestamp_after(date_time.to_local_seconds(), i64::MIN)?;
+        u64::try_from(utc).ok()
+    }
+
+    /// Returns the first time after `after` at which the local time is `local`, both in seconds
+    /// since the Unix epoch, with the same rules as [`TimeZone::timestamp()`].
+    pub(crate) fn timestamp_after(&self, local: i64, after: i64) -> Option<i64> {
+        let mut offsets = [
+            Some(self.std.seconds),
+            self.dst.map(|dst| dst.offset.seconds),
+        ];
+        // Try the larger offset first, which results in the earlier time.
+        offsets.sort_unstable_by(|a, b| b.cmp(a));
+        let mut skipped = true;
+        for offset in offsets.into_iter().flatten() {
+            let utc = local - i64::from(offset);
+            if self.offset_at_seconds(utc).seconds == offset {
+                if utc > after {
+                    return Some(utc);
+                }
+                skipped = false;
+            }
+        }
+        if !skipped {
+            return None;
+        }
+        // The local time was skipped when daylight saving time started, use the offset before the
+        // transition, which is the smaller one.
+        let utc = local - i64::from(offsets.into_iter().flatten().min()?);
+        (utc > after).then_some(utc)
+    }
+
+    /// Returns the local time at `timestamp`, both in seconds since the Unix epoch.
+    pub(crate) fn local_seconds(&self, timestamp: i64) -> i64 {
+        timestamp + i64::from(self.offset_at_seconds(timestamp).seconds)
+    }
+
+    fn offset_at_seconds(&self, timestamp: i64) -> UtcOffset {
+        let Some(dst) = self.dst else {
+            return self.std;
+        };
+        let (year, _, _) =
+            civil_from_days((timestamp + i64::from(self.std.seconds)).div_euclid(SECS_PER_DAY));
+        let start = dst.start.at(year) - i64::from(self.std.seconds);
+        let end = dst.end.at(year) - i64::from(dst.offset.seconds);
+        let in_dst = if start < end {
+            start <= timestamp && timestamp < end
+        } else {
+            // Daylight saving time across the end of the year, as on the southern hemisphere.
+            timestamp < end || start <= timestamp
+        };
+        if in_dst { dst.offset } else { self.std }
+    }
+}
+
+impl From<UtcOffset> for TimeZone {
+    fn from(offset: UtcOffset) -> Self {
+        Self::fixed(offset)
+    }
+}
+
+impl Transition {
+    /// Returns the local time of the transition in `year`, in seconds since the Unix epoch.
+    fn at(self, year: i32) -> i64 {
+        let new_year = days_from_civil(year, 1, 1);
+        let day = match self.date {
+            TransitionDate::Julian(day) => {
+                let leap_day = days_in_month(year, 2) == 29 && day >= 60;
+                new_year + i64::from(day) - 1 + i64::from(leap_day)
+            }
+            TransitionDate::Day(day) => new_year + i64::from(day),
+            TransitionDate::MonthWeekDay {
+                month,
+                week,
+                weekday,
+            } => {
+                let first = days_from_civil(year, month, 1);
+                // 1970-01-01 was a Thursday.
+                let first_weekday = (first + 4).rem_euclid(7);
+                let mut day = first
+                    + (i64::from(weekday) - first_weekday).rem_euclid(7)
+                    + i64::from(week - 1) * 7;
+                // The fifth week stands for the last one, which may be the fourth.
+                while day >= first + i64::from(days_in_month(year, month)) {
+                    day -= 7;
+                }
+                day
+            }
+        };
+        day * SECS_PER_DAY + i64::from(self.time)
+    }
+}
+
+/// Returns the rule for future times at the end of TZif data of version 2 or later.
+fn tzif_rule(tzif: &[u8]) -> Option<&str> {
+    /// Length of the header of each data block.
+    const HEADER_LEN: usize = 44;
+
+    /// Returns the length of the data block at the start of `data`, with times of `time_len`
+    /// bytes, including its header.
+    fn block_len(data: &[u8], time_len: usize) -> Option<usize> {
+        if data.get(..4)? != b"TZif" {
+            return None;
+        }
+        let count = |index: usize| -> Option<usize> {
+            let bytes = data.get(20 + index * 4..24 + index * 4)?;
+            usize::try_from(u32::from_be_bytes(bytes.try_into().ok()?)).ok()
+        };
+        let (isutcnt, isstdcnt, leapcnt) = (count(0)?, count(1)?, count(2)?);
+        let (timecnt, typecnt, charcnt) = (count(3)?, count(4)?, count(5)?);
+        Some(
+            HEADER_LEN
+                + timecnt * (time_len + 1)
+                + typecnt * 6
+                + charcnt
+                + leapcnt * (time_len + 4)
+                + isstdcnt
+                + isutcnt,
+        )
+    }
+
+    // The version 1 data block is followed by a second block and the rule, since version 2.
+    if tzif.get(4).is_none_or(|&version| version < b'2') {
+        return None;
+    }
+    let data = tzif.get(block_len(tzif, 4)?..)?;
+    let footer = data.get(block_len(data, 8)?..)?;
+    let rule = footer.strip_prefix(b"\n")?;
+    let end = rule.iter().position(|&byte| byte == b'\n')?;
+    core::str::from_utf8(rule.get(..end)?).ok()
+}
+
+/// A parser of time zone rules of POSIX.
+struct Parser<'a> {
+    rest: &'a [u8],
+}
+
+impl Parser<'_> {
+    fn time_zone(&mut self) -> Option<TimeZone> {
+        self.name()?;
+        let std = UtcOffset {
+            seconds: -self.time(24)?,
+        };
+        if self.rest.is_empty() {
+            return Some(TimeZone::fixed(std));
+        }
+
+        self.name()?;
+        let dst_offset = if self.peek() == Some(b',') {
+            UtcOffset {
+                seconds: std.seconds + 3600,
+            }
+        } else {
+            UtcOffset {
+                seconds: -self.time(24)?,
+            }
+        };
+        self.expect(b',')?;
+        let start = self.transition()?;
+        self.expect(b',')?;
+        let end = self.transition()?;
+        if !self.rest.is_empty() {
+            return None;
+        }
+        Some(TimeZone {
+            std,
+            dst: Some(Dst {
+                offset: dst_offset,
+                start,
+                end,
+            }),
+        })
+    }
+
+    /// Parses the abbreviation of a time zone, either alphabetic or quoted in `<>`.
+    fn name(&mut self) -> Option<()> {
+        let len = if self.peek() == Some(b'<') {
+            let end = self.rest.iter().position(|&byte| byte == b'>')?;
+            self.advance(1);
+            let len = end - 1;
+            let name = self.rest.get(..len)?;
+            if !name
+                .iter()
+                .all(|byte| byte.is_ascii_alphanumeric() || *byte == b'+' || *byte == b'-')
+            {
+                return None;
+            }
+            self.advance(len + 1);
+            len
+        } else {
+            let len = self
+                .rest
+                .iter()
+                .take_while(|byte| byte.is_ascii_alphabetic())
+                .count();
+            self.advance(len);
+            len
+        };
+        (len >= 3).then_some(())
+    }
+
+    fn transition(&mut self) -> Option<Transition> {
+        let date = match self.peek()? {
+            b'J' => {
+                self.advance(1);
+                TransitionDate::Julian(self.number(1, 365)?)
+            }
+            b'M' => {
+                self.advance(1);
+                let month = self.number(1, 12)?;
+                self.expect(b'.')?;
+                let week = self.number(1, 5)?;
+                self.expect(b'.')?;
+                let weekday = self.number(0, 6)?;
+                // The values were checked to be in range, so they fit.
+                #[expect(clippy::cast_possible_truncation)]
+                TransitionDate::MonthWeekDay {
+                    month: month as u8,
+                    week: week as u8,
+                    weekday: weekday as u8,
+                }
+            }
+            _ => TransitionDate::Day(self.number(0, 365)?),
+        };
+        let time = if self.peek() == Some(b'/') {
+            self.advance(1);
+            self.time(167)?
+        } else {
+            DEFAULT_TRANSITION_TIME
+        };
+        Some(Transition { date, time })
+    }
+
+    /// Parses a time of `[+-]hh[:mm[:ss]]`, in seconds.
+    fn time(&mut self, max_hours: u16) -> Option<i32> {
+        let sign = match self.peek() {
+            Some(b'-') => {
+                self.advance(1);
+                -1
+            }
+            Some(b'+') => {
+                self.advance(1);
+                1
+            }
+            _ => 1,
+        };
+        let mut seconds = i32::from(self.number(0, max_hours)?) * 3600;
+        for unit in [60, 1] {
+            if self.peek() != Some(b':') {
+                break;
+            }
+            self.advance(1);
+            seconds += i32::from(self.number(0, 59)?) * unit;
+        }
+        Some(sign * seconds)
+    }
+
+    fn number(&mut self, min: u16, max: u16) -> Option<u16> {
+        let len = self
+            .rest
+            .iter()
+            .take_while(|byte| byte.is_ascii_digit())
+            .count();
+        let number = core::str::from_utf8(self.rest.get(..len)?)
+            .ok()?
+            .parse()
+            .ok()?;
+        self.advance(len);
+        (min..=max).contains(&number).then_some(number)
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        (self.peek()? == byte).then(|| self.advance(1))
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.rest.first().copied()
+    }
+
+    fn advance(&mut self, len: usize) {
+        self.rest = self.rest.get(len..).unwrap_or_default();
+    }
+}
+
+/// Persists the time zone described by `data`, and returns it.
+///
+/// `data` is either TZif data, or a rule in the format of the `TZ` environment variable of POSIX,
+/// see [`TimeZone::from_tzif()`] and [`TimeZone::from_posix()`].
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidTimeZone`] if `data` does not describe a valid time zone, or if its
+/// rule is too long to be stored, and [`Error::Storage`] if it could not be written to storage.
+#[cfg(feature = "storage")]
+pub async fn store_time_zone(data: &[u8]) -> Result<TimeZone, Error> {
+    let rule = if data.starts_with(b"TZif") {
+        tzif_rule(data)
+    } else {
+        core::str::from_utf8(data).ok()
+    }
+    .filter(|rule| rule.len() <= MAX_RULE_LEN)
+    .ok_or(Error::InvalidTimeZone)?;
+    let time_zone = TimeZone::from_posix(rule)?;
+    ariel_os_storage::insert_blob(TIME_ZONE_KEY, rule.as_bytes())
+        .await
+        .map_err(|_| Error::Storage)?;
+    Ok(time_zone)
+}
+
+/// Loads the time zone persisted with [`store_time_zone()`].
+///
+/// Returns `None` if no time zone has been stored.
+///
+/// # Errors
+///
+/// Returns [`Error::Storage`] if it could not be read from storage, and
+/// [`Error::InvalidTimeZone`] if the stored rule is invalid.
+#[cfg(feature = "storage")]
+pub async fn load_time_zone() -> Result<Option<TimeZone>, Error> {
+    let mut buffer = [0; MAX_RULE_LEN];
+    let Some(rule) = ariel_os_storage::get_blob(TIME_ZONE_KEY, &mut buffer)
+        .await
+        .map_err(|_| Error::Storage)?
+    else {
+        return Ok(None);
+    };
+    let rule = core::str::from_utf8(rule).map_err(|_| Error::InvalidTimeZone)?;
+    TimeZone::from_posix(rule).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-03-31T01:00:00Z, when Central European Summer Time starts.
+    const CEST_START: u64 = 1_711_846_800;
+    /// 2024-10-27T01:00:00Z, when Central European Summer Time ends.
+    const CEST_END: u64 = 1_729_990_800;
+
+    #[test]
+    fn posix() {
+        let cet = TimeZone::from_posix("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        assert_eq!(cet.offset_at(CEST_START - 1), UtcOffset::from_minutes(60));
+        assert_eq!(cet.offset_at(CEST_START), UtcOffset::from_minutes(120));
+        assert_eq!(cet.offset_at(CEST_END - 1), UtcOffset::from_minutes(120));
+        assert_eq!(cet.offset_at(CEST_END), UtcOffset::from_minutes(60));
+
+        // Daylight saving time across the end of the year.
+        let nzst = TimeZone::from_posix("NZST-12NZDT,M9.5.0,M4.1.0/3").unwrap();
+        assert_eq!(
+            nzst.offset_at(1_704_067_200),
+            UtcOffset::from_minutes(13 * 60)
+        );
+        assert_eq!(
+            nzst.offset_at(1_719_792_000),
+            UtcOffset::from_minutes(12 * 60)
+        );
+
+        assert_eq!(
+            TimeZone::from_posix("<-03>3"),
+            Ok(TimeZone::fixed(UtcOffset::from_minutes(-3 * 60)))
+        );
+        for invalid in [
+            "",
+            "CET",
+            "CET-1CEST",
+            "CET-1CEST,M3.5.0",
+            "CET-1CEST,M13.5.0,M10.5.0",
+        ] {
+            assert_eq!(TimeZone::from_posix(invalid), Err(Error::InvalidTimeZone));
+        }
+    }
+
+    #[test]
+    fn local() {
+        let cet = TimeZone::from_posix("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        let local = |hour, minute| DateTime {
+            hour,
+            minute,
+            ..cet.local_time(CEST_START)
+        };
+        assert_eq!(cet.timestamp(&local(1, 30)), Some(CEST_START - 1800));
+        // Skipped from 02:00 to 03:00, shifted by an hour.
+        assert_eq!(cet.timestamp(&local(2, 30)), Some(CEST_START + 1800));
+        assert_eq!(cet.timestamp(&local(3, 30)), Some(CEST_START + 1800));
+
+        // Repeated from 02:00 to 03:00, the earlier one is returned.
+        let local = |hour, minute| DateTime {
+            hour,
+            minute,
+            ..cet.local_time(CEST_END)
+        };
+        assert_eq!(cet.timestamp(&local(2, 30)), Some(CEST_END - 1800));
+        assert_eq!(cet.timestamp(&local(3, 30)), Some(CEST_END + 5400));
+        assert_eq!(cet.local_time(CEST_END + 5400).hour, 3);
+    }
+
+    #[test]
+    fn tzif() {
+        const FOOTER: &[u8] = b"\nCET-1CEST,M3.5.0,M10.5.0/3\n";
+        // Empty data blocks, followed by the rule.
+        let mut tzif = [0; 2 * 44 + FOOTER.len()];
+        tzif[..5].copy_from_slice(b"TZif2");
+        tzif[44..49].copy_from_slice(b"TZif2");
+        tzif[88..].copy_from_slice(FOOTER);
+        assert_eq!(
+            TimeZone::from_tzif(&tzif),
+            TimeZone::from_posix("CET-1CEST,M3.5.0,M10.5.0/3")
+        );
+
+        tzif[4] = 0;
+        assert_eq!(TimeZone::from_tzif(&tzif), Err(Error::InvalidTimeZone));
+    }
+}
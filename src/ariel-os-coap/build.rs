@@ -74,21 +74,26 @@ fn main() {
     }
 
     build::rerun_if_env_changed("PEERS_YML");
-    let peers_yml = std::path::PathBuf::from(std::env::var("PEERS_YML").unwrap());
-
-    build::rerun_if_changed(&peers_yml);
-    let peers_file = std::fs::File::open(&peers_yml)
-        .map_err(|e| {
-            format!(
-                "{} while opening {} inside {}",
-                e,
-                peers_yml.display(),
-                std::env::current_dir().unwrap().display()
-            )
-        })
-        .expect("no peers.yml usable in specified location");
-
-    let peers: Vec<Peer> = serde_yml::from_reader(peers_file).expect("failed to parse peers.yml");
+    let peers: Vec<Peer> = match std::env::var("PEERS_YML") {
+        Ok(peers_yml) => {
+            let peers_yml = std::path::PathBuf::from(peers_yml);
+            build::rerun_if_changed(&peers_yml);
+            let peers_file = std::fs::File::open(&peers_yml)
+                .map_err(|e| {
+                    format!(
+                        "{} while opening {} inside {}",
+                        e,
+                        peers_yml.display(),
+                        std::env::current_dir().unwrap().display()
+                    )
+                })
+                .expect("no peers.yml usable in specified location");
+            serde_yml::from_reader(peers_file).expect("failed to parse peers.yml")
+        }
+        // Host tests are not run through laze, which provides the peers.
+        Err(_) if build::cargo_feature("_test") => Vec::new(),
+        Err(e) => panic!("PEERS_YML: {e}"),
+    };
 
     let mut unauthenticated_scope = None;
     let mut chain_once_per_kccs = String::new();
@@ -0,0 +1,306 @@
+//! Bridges CoAP resources and MQTT topics.
+//!
+//! This does not depend on a particular MQTT client: the application connects to the broker,
+//! passes a [`Publisher`] to [`Bridge::run()`], and passes the messages it receives on the
+//! command topics to [`Bridge::command()`].
+//!
+//! The mappings between resources and topics are kept in the `coap-bridge` storage table, so that
+//! they can be changed at runtime (eg. by a provisioning tool) without rebuilding the firmware:
+//!
+//! ```ignore
+//! Bridge::add(&Mapping {
+//!     id: 1,
+//!     endpoint: "[2001:db8::1]:5683".try_into().unwrap(),
+//!     path: "/temperature".try_into().unwrap(),
+//!     topic: "sensors/temperature".try_into().unwrap(),
+//!     direction: Direction::Publish,
+//!     interval_secs: 10,
+//! })
+//! .await?;
+//!
+//! let bridge = Bridge::load().await?;
+//! join(bridge.run(&mut publisher), async {
+//!     loop {
+//!         let (topic, payload) = subscription.next().await;
+//!         bridge.command(&topic, &payload).await;
+//!     }
+//! })
+//! .await;
+//! ```
+//!
+//! The CoAP client does not support observing resources yet, so resources mapped to topics are
+//! fetched at the interval of their mapping instead, and their representation is only published
+//! when it changed since it was last published.
+//!
+//! # Configuration
+//!
+//! - `CONFIG_COAP_BRIDGE_MAX_PAYLOAD_LEN` (default: 256): maximum length of the representations
+//!   and commands that are bridged, in bytes.
+//!
+//! Mappings with long paths or topics may need a larger `CONFIG_STORAGE_TABLE_MAX_RECORD_SIZE`.
+
+use core::{convert::Infallible, net::SocketAddr};
+
+use ariel_os_debug::log::{debug, warn};
+use ariel_os_storage::table::{Record, TABLE_MAX_RECORDS};
+use coapcore::caching::ETag;
+use embassy_time::{Duration, Instant, Timer};
+use serde::{Deserialize, Serialize};
+
+/// Maximum length of the bridged payloads, configured through the
+/// `CONFIG_COAP_BRIDGE_MAX_PAYLOAD_LEN` environment variable.
+pub const MAX_PAYLOAD_LEN: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_COAP_BRIDGE_MAX_PAYLOAD_LEN",
+    256,
+    "maximum length of the payloads bridged between CoAP and MQTT"
+);
+
+/// Maximum length of [`Mapping::endpoint`], which fits any IPv6 socket address.
+pub const MAX_ENDPOINT_LEN: usize = 47;
+
+/// Maximum length of [`Mapping::path`].
+pub const MAX_PATH_LEN: usize = 32;
+
+/// Maximum length of [`Mapping::topic`].
+pub const MAX_TOPIC_LEN: usize = 32;
+
+type Payload = heapless::Vec<u8, MAX_PAYLOAD_LEN>;
+
+/// Direction in which a [`Mapping`] bridges messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    /// The representation of the resource is published on the topic.
+    Publish,
+    /// Messages received on the topic are sent to the resource in PUT requests.
+    Command,
+}
+
+/// A mapping between a CoAP resource and an MQTT topic.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Record)]
+#[record(table = "coap-bridge")]
+pub struct Mapping {
+    /// Identifier of the mapping in the table.
+    #[record(key)]
+    pub id: u32,
+    /// Socket address of the CoAP server, eg. `[2001:db8::1]:5683`.
+    pub endpoint: heapless::String<MAX_ENDPOINT_LEN>,
+    /// Path of the resource on the CoAP server, eg. `/temperature`.
+    pub path: heapless::String<MAX_PATH_LEN>,
+    /// MQTT topic.
+    #[record(index)]
+    pub topic: heapless::String<MAX_TOPIC_LEN>,
+    /// Direction in which messages are bridged.
+    pub direction: Direction,
+    /// Interval at which the resource is fetched, in seconds, for [`Direction::Publish`]; at
+    /// least 1 second.
+    pub interval_secs: u32,
+}
+
+/// A publisher of messages on MQTT topics, implemented on top of an MQTT client.
+pub trait Publisher {
+    /// Error returned when a message cannot be published.
+    type Error;
+
+    /// Publishes `payload` on `topic`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the message could not be published, eg. because the connection to the
+    /// broker was lost.
+    fn publish(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+    ) -> impl Future<Output = Result<(), Self::Error>>;
+}
+
+/// Errors of the bridge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The mappings could not be read from or written to storage.
+    Storage,
+    /// The endpoint of a mapping is not a socket address.
+    InvalidEndpoint,
+    /// A CoAP request failed, or its response was too long.
+    Request,
+    /// A message could not be published.
+    Publish,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Storage => write!(f, "storage error"),
+            Self::InvalidEndpoint => write!(f, "invalid endpoint"),
+            Self::Request => write!(f, "CoAP request failed"),
+            Self::Publish => write!(f, "publishing failed"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Bridges messages as set up by the stored [`Mapping`]s.
+pub struct Bridge {
+    mappings: heapless::Vec<Mapping, TABLE_MAX_RECORDS>,
+}
+
+impl Bridge {
+    /// Loads the mappings from storage.
+    ///
+    /// Mappings changed afterwards are only taken into account by bridges loaded after the
+    /// change.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Storage`] if the mappings cannot be read.
+    pub async fn load() -> Result<Self, Error> {
+        let mut storage = ariel_os_storage::lock().await;
+        let mut table = storage.table::<Mapping>();
+        let mut mappings = heapless::Vec::new();
+        for id in table.keys(..).await.map_err(|_| Error::Storage)? {
+            if let Some(mapping) = table.get(id).await.map_err(|_| Error::Storage)? {
+                // There are at most `TABLE_MAX_RECORDS` records.
+                let _ = mappings.push(mapping);
+            }
+        }
+        Ok(Self { mappings })
+    }
+
+    /// Stores `mapping`, replacing the mapping with the same identifier, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidEndpoint`] if the endpoint of the mapping is not a socket address,
+    /// and [`Error::Storage`] if the mapping cannot be stored.
+    pub async fn add(mapping: &Mapping) -> Result<(), Error> {
+        mapping.socket_addr()?;
+        let mut storage = ariel_os_storage::lock().await;
+        storage
+            .table::<Mapping>()
+            .insert(mapping)
+            .await
+            .map_err(|_| Error::Storage)
+    }
+
+    /// Removes the mapping identified by `id` from storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Storage`] if the mapping cannot be removed.
+    pub async fn remove(id: u32) -> Result<(), Error> {
+        let mut storage = ariel_os_storage::lock().await;
+        storage
+            .table::<Mapping>()
+            .remove(id)
+            .await
+            .map_err(|_| Error::Storage)
+    }
+
+    /// Returns the mappings of the bridge.
+    #[must_use]
+    pub fn mappings(&self) -> &[Mapping] {
+        &self.mappings
+    }
+
+    /// Publishes the representations of the resources of the [`Direction::Publish`] mappings,
+    /// whenever they changed.
+    ///
+    /// Resources that cannot be fetched are tried again at the next interval.
+    /// This needs to run in the thread that runs the network stack, see
+    /// [`coap_client()`](crate::coap_client()).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Publish`] if a message could not be published, eg. so that the application
+    /// reconnects to the broker before running the bridge again.
+    pub async fn run<P: Publisher>(&self, publisher: &mut P) -> Result<Infallible, Error> {
+        // When each resource is next fetched, and the ETag of its last published representation.
+        let mut states: heapless::Vec<(Instant, Option<ETag>), TABLE_MAX_RECORDS> = self
+            .mappings
+            .iter()
+            .map(|_| (Instant::now(), None))
+            .collect();
+
+        loop {
+            let next = self
+                .mappings
+                .iter()
+                .zip(&mut states)
+                .filter(|(mapping, _)| mapping.direction == Direction::Publish)
+                .min_by_key(|(_, (at, _))| *at);
+            let Some((mapping, (at, last_etag))) = next else {
+                // Nothing to publish.
+                return core::future::pending().await;
+            };
+
+            Timer::at(*at).await;
+            *at += Duration::from_secs(mapping.interval_secs.max(1).into());
+
+            let Ok(representation) = fetch(mapping).await else {
+                warn!("bridge: fetching {} failed", mapping.path.as_str());
+                continue;
+            };
+            let etag = ETag::from_representation(&representation);
+            if *last_etag == Some(etag) {
+                continue;
+            }
+            debug!("bridge: publishing {}", mapping.topic.as_str());
+            publisher
+                .publish(&mapping.topic, &representation)
+                .await
+                .map_err(|_| Error::Publish)?;
+            *last_etag = Some(etag);
+        }
+    }
+
+    /// Sends `payload`, received on `topic`, to the resource of the [`Direction::Command`]
+    /// mapping of that topic, in a PUT request.
+    ///
+    /// Returns whether a mapping of the topic exists.
+    /// This needs to run in the thread that runs the network stack, see
+    /// [`coap_client()`](crate::coap_client()).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Request`] if the request failed.
+    pub async fn command(&self, topic: &str, payload: &[u8]) -> Result<bool, Error> {
+        let Some(mapping) = self
+            .mappings
+            .iter()
+            .find(|mapping| mapping.direction == Direction::Command && mapping.topic == topic)
+        else {
+            return Ok(false);
+        };
+
+        let request = coap_request_implementations::Code::put()
+            .with_path(&mapping.path)
+            .with_request_payload_slice(payload)
+            .processing_response_payload_through(|_| ());
+        let client = crate::coap_client().await;
+        match client.to(mapping.socket_addr()?).request(request).await {
+            Ok(Some(())) => Ok(true),
+            _ => Err(Error::Request),
+        }
+    }
+}
+
+impl Mapping {
+    /// Returns the socket address of the endpoint.
+    fn socket_addr(&self) -> Result<SocketAddr, Error> {
+        self.endpoint.parse().map_err(|_| Error::InvalidEndpoint)
+    }
+}
+
+/// Fetches the representation of the resource of `mapping`.
+async fn fetch(mapping: &Mapping) -> Result<Payload, Error> {
+    let request = coap_request_implementations::Code::get()
+        .with_path(&mapping.path)
+        .processing_response_payload_through(|payload| Payload::from_slice(payload).ok());
+    let client = crate::coap_client().await;
+    match client.to(mapping.socket_addr()?).request(request).await {
+        Ok(Some(Some(payload))) => Ok(payload),
+        _ => Err(Error::Request),
+    }
+}
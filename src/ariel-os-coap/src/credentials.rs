@@ -0,0 +1,862 @@
+//! Rotation of the credentials of peers, through a management resource.
+//!
+//! With the storage-backed server configuration, the peers of the device are set by `peers.yml`
+//! at build time, and possibly by provisioning (see the `provisioning` feature). This allows controllers to install new credentials at runtime and to retire old
+//! ones, so that long-lived devices can recover from the compromise of a key without being
+//! reflashed.
+//!
+//! A rotation is done in two steps, so that a controller cannot lock itself out by installing a
+//! credential it is unable to use:
+//!
+//! 1. A peer allowed to POST to `/credentials` sends a CBOR array of the new credential (a CCS,
+//!    as in `peers.yml`), its scope (an AIF value), and the list of public keys (the `x`
+//!    coordinate of the P-256 key, see [`lakers::Credential::public_key()`]) of the credentials
+//!    to retire, which can be installed, provisioned, or come from `peers.yml`:
+//!
+//!    ```text
+//!    [h'a2027734…', [["/diag/uptime", 1], ["/credentials", 7]], [h'ac75e9ec…']]
+//!    ```
+//!
+//!    The resulting set of credentials is staged in storage, and read back for verification.
+//! 2. Once staged, the new credential is recognized, but is only allowed to POST to
+//!    `/credentials/commit`. Doing so with it proves that its holder can use it, and atomically
+//!    replaces the active set with the staged one, which takes effect immediately.
+//!
+//! A GET request to `/credentials` returns a CBOR map with the public keys of the installed and
+//! retired credentials, and of the staged one if any:
+//!
+//! ```text
+//! { "installed": [h'1b0c…'], "retired": [h'ac75e9ec…'], "staged": h'5d2e…' }
+//! ```
+//!
+//! A DELETE request discards the staged credential. Staged credentials that are not committed
+//! before the device restarts are discarded as well.
+//!
+//! As staging and committing access storage, they complete shortly after the response has been
+//! sent; controllers can check their progress with GET requests.
+//!
+//! Access to `/credentials` needs to be limited to the administrators of the device in
+//! `peers.yml`. No peer should be allowed to POST to `/credentials/commit`, as it could commit
+//! staged credentials without proving that they are usable.
+//!
+//! # Configuration
+//!
+//! - `CONFIG_COAP_CREDENTIALS_TABLE_SIZE` (default: 1024): maximum size of the encoded set of
+//!   installed and retired credentials, in bytes.
+
+use core::cell::RefCell;
+
+use ariel_os_debug::log::{info, warn};
+use cbor_macro::cbor;
+use coap_message::{
+    Code as _, MinimalWritableMessage, MutableWritableMessage, OptionNumber as _, ReadableMessage,
+};
+use coap_message_utils::{Error as CoAPError, OptionsExt as _};
+use coapcore::scope::{AifValue, UnionScope};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use minicbor::{
+    Decoder, Encoder,
+    encode::{Write as _, write::Cursor},
+};
+
+/// CoAP Content-Format of `application/cbor`.
+const CONTENT_FORMAT_CBOR: u16 = 60;
+
+const TABLE_SIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_COAP_CREDENTIALS_TABLE_SIZE",
+    1024,
+    "maximum size of the encoded set of installed and retired credentials"
+);
+
+/// Maximum number of installed or retired credentials.
+const MAX_ENTRIES: usize = 8;
+
+/// Length of the public keys identifying credentials.
+const PUBLIC_KEY_LEN: usize = 32;
+
+/// Maximum length of the encoded state reported by GET requests.
+const MAX_STATE_LEN: usize = 3 * 2 + (2 * MAX_ENTRIES + 1) * (PUBLIC_KEY_LEN + 2) + 32;
+
+/// Storage key of the slot holding the active set of credentials.
+const ACTIVE_SLOT_KEY: &str = "ariel-os-coap.peers";
+
+/// Storage keys of the blobs of the two slots, one holding the active set, and the other the
+/// staged one.
+const SLOT_KEYS: [&str; 2] = ["ariel-os-coap.peers.0", "ariel-os-coap.peers.1"];
+
+/// Scope of a staged credential, which may only commit itself.
+const COMMIT_SCOPE: cboritem::CborItem = cbor!([["/credentials/commit", 2 / POST /]]);
+
+/// An encoded set of credentials.
+///
+/// It is a CBOR array of the installed credentials, as arrays of a CCS and an AIF scope, and of
+/// the public keys of the retired ones.
+type EncodedTable = heapless::Vec<u8, TABLE_SIZE>;
+
+static STATE: critical_section::Mutex<RefCell<State>> =
+    critical_section::Mutex::new(RefCell::new(State {
+        active: EncodedTable::new(),
+        active_slot: None,
+        pending: None,
+        generation: 0,
+    }));
+
+/// Wakes up [`persist()`] when there is something to store.
+static PERSIST: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+struct State {
+    /// The active set of credentials, empty if none was ever committed.
+    active: EncodedTable,
+    /// The slot of `active`.
+    active_slot: Option<usize>,
+    /// The set of credentials being staged or committed.
+    pending: Option<Pending>,
+    /// Incremented at each staging, so that outdated stagings are not reported as staged.
+    generation: u32,
+}
+
+impl State {
+    /// Returns the slot into which the pending set is staged.
+    fn staging_slot(&self) -> usize {
+        match self.active_slot {
+            Some(0) => 1,
+            _ => 0,
+        }
+    }
+}
+
+struct Pending {
+    table: EncodedTable,
+    credential: lakers::Credential,
+    status: Status,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    /// The set is being written to storage.
+    Staging,
+    /// The set is in storage, and the new credential can be used to commit it.
+    Staged,
+    /// The set is being made the active one.
+    Committing,
+}
+
+/// The decoded set of credentials of an [`EncodedTable`].
+struct Table<'a> {
+    /// The CCS and AIF scope of each installed credential.
+    installed: heapless::Vec<(&'a [u8], &'a [u8]), MAX_ENTRIES>,
+    /// The public key of each retired credential.
+    retired: heapless::Vec<&'a [u8], MAX_ENTRIES>,
+}
+
+impl<'a> Table<'a> {
+    /// Returns the empty set.
+    fn empty() -> Self {
+        Self {
+            installed: heapless::Vec::new(),
+            retired: heapless::Vec::new(),
+        }
+    }
+
+    /// Decodes a set of credentials, where an empty slice is the empty set.
+    fn decode(bytes: &'a [u8]) -> Option<Self> {
+        let mut table = Self::empty();
+        if bytes.is_empty() {
+            return Some(table);
+        }
+
+        let mut decoder = Decoder::new(bytes);
+        if decoder.array().ok()? != Some(2) {
+            return None;
+        }
+        for _ in 0..decoder.array().ok()?? {
+            if decoder.array().ok()? != Some(2) {
+                return None;
+            }
+            let kccs = decoder.bytes().ok()?;
+            let scope = decoded_item(&mut decoder)?;
+            table.installed.push((kccs, scope)).ok()?;
+        }
+        for _ in 0..decoder.array().ok()?? {
+            table.retired.push(decoder.bytes().ok()?).ok()?;
+        }
+        Some(table)
+    }
+
+    /// Encodes the set of credentials into `buffer`, and returns the encoded length.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the encoded set does not fit into `buffer`.
+    fn encode(
+        &self,
+        buffer: &mut [u8],
+    ) -> Result<usize, minicbor::encode::Error<minicbor::encode::write::EndOfSlice>> {
+        let mut encoder = Encoder::new(Cursor::new(buffer));
+        encoder.array(2)?.array(self.installed.len() as u64)?;
+        for (kccs, scope) in &self.installed {
+            encoder.array(2)?.bytes(kccs)?;
+            // The scope is kept as it was encoded in the request.
+            encoder
+                .writer_mut()
+                .write_all(scope)
+                .map_err(minicbor::encode::Error::write)?;
+        }
+        encoder.array(self.retired.len() as u64)?;
+        for public_key in &self.retired {
+            encoder.bytes(public_key)?;
+        }
+        Ok(encoder.into_writer().position())
+    }
+
+    /// Returns whether the credential with `public_key` is retired.
+    fn is_retired(&self, public_key: &[u8]) -> bool {
+        self.retired.contains(&public_key)
+    }
+}
+
+/// Returns the encoded CBOR item at the position of `decoder`, and skips it.
+fn decoded_item<'b>(decoder: &mut Decoder<'b>) -> Option<&'b [u8]> {
+    let start = decoder.position();
+    decoder.skip().ok()?;
+    decoder.input().get(start..decoder.position())
+}
+
+/// Returns the public key identifying the credential `kccs`.
+fn public_key(kccs: &[u8]) -> Option<[u8; PUBLIC_KEY_LEN]> {
+    lakers::Credential::parse_ccs(kccs).ok()?.public_key()
+}
+
+/// Loads the active set of credentials from storage.
+///
+/// # Panics
+///
+/// Panics if the storage cannot be read.
+pub(crate) async fn load() {
+    let Some(slot) = ariel_os_storage::get::<u8>(ACTIVE_SLOT_KEY)
+        .await
+        .expect("flash error prevents startup")
+    else {
+        return;
+    };
+    let Some(key) = SLOT_KEYS.get(usize::from(slot)) else {
+        warn!("Invalid slot of installed CoAP credentials, ignoring them");
+        return;
+    };
+
+    let mut buffer = [0; TABLE_SIZE];
+    let table = ariel_os_storage::get_blob(key, &mut buffer)
+        .await
+        .expect("flash error prevents startup");
+    let Some(table) = table.filter(|table| Table::decode(table).is_some()) else {
+        warn!("Installed CoAP credentials are unreadable, ignoring them");
+        return;
+    };
+    info!("Using installed CoAP credentials");
+
+    critical_section::with(|cs| {
+        let mut state = STATE.borrow_ref_mut(cs);
+        // Cannot fail, the table was read into a buffer of that size.
+        state.active = EncodedTable::from_slice(table).unwrap_or_default();
+        state.active_slot = Some(usize::from(slot));
+    });
+}
+
+/// Returns the installed or staged credential matching `id_cred_x`, with its scope.
+pub(crate) fn expand_id_cred_x(
+    id_cred_x: &lakers::IdCred,
+) -> Option<(lakers::Credential, UnionScope)> {
+    critical_section::with(|cs| {
+        let state = STATE.borrow_ref(cs);
+        let table = Table::decode(&state.active).unwrap_or_else(Table::empty);
+        for (kccs, scope) in table.installed {
+            let Ok(credential) = lakers::Credential::parse_ccs(kccs) else {
+                continue;
+            };
+            if crate::stored::matches(&credential, id_cred_x) {
+                return Some((credential, AifValue::parse(scope).ok()?.into()));
+            }
+        }
+
+        let pending = state.pending.as_ref()?;
+        if pending.status != Status::Staging
+            && crate::stored::matches(&pending.credential, id_cred_x)
+        {
+            #[expect(clippy::clone_on_copy, reason = "Lakers items are overly copy happy")]
+            let credential = pending.credential.clone();
+            let scope = AifValue::parse(&COMMIT_SCOPE).ok()?;
+            return Some((credential, scope.into()));
+        }
+        None
+    })
+}
+
+/// Returns whether `credential` from `peers.yml` or provisioning was retired.
+pub(crate) fn is_retired(credential: &lakers::Credential) -> bool {
+    let Some(public_key) = credential.public_key() else {
+        return false;
+    };
+    critical_section::with(|cs| {
+        Table::decode(&STATE.borrow_ref(cs).active)
+            .is_some_and(|table| table.is_retired(&public_key))
+    })
+}
+
+/// Stages and commits sets of credentials as requested through [`CredentialsResource`].
+#[ariel_os_macros::task(autostart)]
+async fn persist() {
+    loop {
+        PERSIST.wait().await;
+
+        let mut buffer = [0; TABLE_SIZE];
+        let job = critical_section::with(|cs| {
+            let state = STATE.borrow_ref(cs);
+            let pending = state.pending.as_ref()?;
+            let job = match pending.status {
+                Status::Staging => {
+                    buffer
+                        .get_mut(..pending.table.len())?
+                        .copy_from_slice(&pending.table);
+                    Job::Stage(pending.table.len())
+                }
+                Status::Committing => Job::Commit,
+                Status::Staged => return None,
+            };
+            Some((job, state.staging_slot(), state.generation))
+        });
+        let Some((job, slot, generation)) = job else {
+            continue;
+        };
+        // Cannot fail, there are two slots.
+        let key = SLOT_KEYS.get(slot).copied().unwrap_or_default();
+
+        match job {
+            Job::Stage(len) => {
+                let table = buffer.get(..len).unwrap_or_default();
+                let staged = stage(key, table).await;
+                critical_section::with(|cs| {
+                    let mut state = STATE.borrow_ref_mut(cs);
+                    if state.generation != generation {
+                        // Another set was submitted meanwhile, and is staged next.
+                        return;
+                    }
+                    if staged {
+                        if let Some(pending) = state.pending.as_mut() {
+                            pending.status = Status::Staged;
+                        }
+                    } else {
+                        state.pending = None;
+                    }
+                });
+                if staged {
+                    info!("Staged new CoAP credential");
+                } else {
+                    warn!("Failed to stage new CoAP credential");
+                }
+            }
+            Job::Commit => {
+                // The slot is switched with a single write, so that the change is atomic.
+                // Cannot fail, slots are either 0 or 1.
+                let slot_number = u8::try_from(slot).unwrap_or_default();
+                let committed = ariel_os_storage::insert(ACTIVE_SLOT_KEY, slot_number)
+                    .await
+                    .is_ok();
+                critical_section::with(|cs| {
+                    let mut state = STATE.borrow_ref_mut(cs);
+                    let state = &mut *state;
+                    let Some(pending) = state.pending.as_mut() else {
+                        return;
+                    };
+                    if committed {
+                        state.active = core::mem::take(&mut pending.table);
+                        state.active_slot = Some(slot);
+                        state.pending = None;
+                    } else {
+                        pending.status = Status::Staged;
+                    }
+                });
+                if committed {
+                    info!("Committed new CoAP credential");
+                } else {
+                    warn!("Failed to commit new CoAP credential");
+                }
+            }
+        }
+    }
+}
+
+enum Job {
+    /// Stage the set of the given length in the buffer.
+    Stage(usize),
+    Commit,
+}
+
+/// Writes `table` to the blob at `key`, and returns whether it reads back unchanged.
+async fn stage(key: &str, table: &[u8]) -> bool {
+    if ariel_os_storage::insert_blob(key, table).await.is_err() {
+        return false;
+    }
+    let mut buffer = [0; TABLE_SIZE];
+    matches!(ariel_os_storage::get_blob(key, &mut buffer).await, Ok(Some(stored)) if stored == table)
+}
+
+/// A CoAP resource for rotating credentials, see the [module documentation](self).
+#[derive(Debug)]
+pub struct CredentialsResource {
+    commit: bool,
+}
+
+impl CredentialsResource {
+    /// Creates the resource managing the credentials, served at `/credentials`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { commit: false }
+    }
+
+    /// Creates the resource committing the staged credential, served at `/credentials/commit`.
+    #[must_use]
+    pub fn commit() -> Self {
+        Self { commit: true }
+    }
+}
+
+impl Default for CredentialsResource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The operation requested on the resource.
+#[derive(Debug, Clone, Copy)]
+pub enum Request {
+    /// Report the credentials.
+    Get,
+    /// Stage a new set of credentials.
+    Stage,
+    /// Discard the staged credential.
+    Discard,
+    /// Commit the staged credential.
+    Commit,
+}
+
+impl coap_handler::Handler for CredentialsResource {
+    type RequestData = Request;
+    type ExtractRequestError = CoAPError;
+    type BuildResponseError<M: MinimalWritableMessage> = CoAPError;
+
+    fn extract_request_data<M: ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, Self::ExtractRequestError> {
+        let request_data = match (self.commit, request.code().into()) {
+            (false, coap_numbers::code::GET) => Request::Get,
+            (false, coap_numbers::code::POST) => Request::Stage,
+            (false, coap_numbers::code::DELETE) => Request::Discard,
+            (true, coap_numbers::code::POST) => Request::Commit,
+            _ => return Err(CoAPError::method_not_allowed()),
+        };
+        request.options().ignore_elective_others()?;
+
+        match request_data {
+            Request::Stage => stage_request(request.payload())?,
+            Request::Discard => discard()?,
+            Request::Commit => commit()?,
+            Request::Get => {}
+        }
+        Ok(request_data)
+    }
+
+    fn estimate_length(&mut self, _request: &Self::RequestData) -> usize {
+        // Content-Format option and payload marker
+        MAX_STATE_LEN + 4
+    }
+
+    fn build_response<M: MutableWritableMessage>(
+        &mut self,
+        response: &mut M,
+        request: Self::RequestData,
+    ) -> Result<(), Self::BuildResponseError<M>> {
+        let code = match request {
+            Request::Get => coap_numbers::code::CONTENT,
+            Request::Stage | Request::Commit => coap_numbers::code::CHANGED,
+            Request::Discard => coap_numbers::code::DELETED,
+        };
+        response.set_code(M::Code::new(code).map_err(CoAPError::from_unionerror)?);
+        if !matches!(request, Request::Get) {
+            return Ok(());
+        }
+
+        let mut buffer = [0; MAX_STATE_LEN];
+        let len = encode_state(&mut buffer).map_err(|_| CoAPError::internal_server_error())?;
+        response
+            .add_option_uint(
+                M::OptionNumber::new(coap_numbers::option::CONTENT_FORMAT)
+                    .map_err(CoAPError::from_unionerror)?,
+                CONTENT_FORMAT_CBOR,
+            )
+            .map_err(CoAPError::from_unionerror)?;
+        response
+            .set_payload(
+                buffer
+                    .get(..len)
+                    .ok_or_else(CoAPError::internal_server_error)?,
+            )
+            .map_err(CoAPError::from_unionerror)?;
+        Ok(())
+    }
+}
+
+/// Stages the set of credentials requested by `payload`.
+///
+/// # Errors
+///
+/// Returns an error if the request is malformed, or if the set cannot be staged now.
+fn stage_request(payload: &[u8]) -> Result<(), CoAPError> {
+    let mut decoder = Decoder::new(payload);
+    if decoder.array().ok() != Some(Some(3)) {
+        return Err(CoAPError::bad_request());
+    }
+    let kccs = decoder.bytes().map_err(|_| CoAPError::bad_request())?;
+    let scope = decoded_item(&mut decoder).ok_or_else(CoAPError::bad_request)?;
+    let retire_count = decoder
+        .array()
+        .ok()
+        .flatten()
+        .ok_or_else(CoAPError::bad_request)?;
+    let mut retire = heapless::Vec::<&[u8], MAX_ENTRIES>::new();
+    for _ in 0..retire_count {
+        let public_key = decoder.bytes().map_err(|_| CoAPError::bad_request())?;
+        if public_key.len() != PUBLIC_KEY_LEN {
+            return Err(CoAPError::bad_request());
+        }
+        retire
+            .push(public_key)
+            .map_err(|_| CoAPError::bad_request())?;
+    }
+    if decoder.position() != payload.len() {
+        return Err(CoAPError::bad_request());
+    }
+
+    let credential = lakers::Credential::parse_ccs(kccs).map_err(|_| CoAPError::bad_request())?;
+    let new_public_key = credential.public_key().ok_or_else(CoAPError::bad_request)?;
+    if AifValue::parse(scope).is_err() || retire.contains(&new_public_key.as_slice()) {
+        return Err(CoAPError::bad_request());
+    }
+
+    critical_section::with(|cs| {
+        let mut state = STATE.borrow_ref_mut(cs);
+        let state = &mut *state;
+        if state
+            .pending
+            .as_ref()
+            .is_some_and(|pending| pending.status == Status::Committing)
+        {
+            return Err(CoAPError::service_unavailable());
+        }
+
+        let active = Table::decode(&state.active).ok_or_else(CoAPError::internal_server_error)?;
+        let mut table = Table::empty();
+        for &(installed_kccs, installed_scope) in &active.installed {
+            let installed_key = public_key(installed_kccs);
+            let replaced = installed_key
+                .is_some_and(|key| key == new_public_key || retire.contains(&key.as_slice()));
+            if !replaced {
+                table
+                    .installed
+                    .push((installed_kccs, installed_scope))
+                    .map_err(|_| CoAPError::bad_request())?;
+            }
+        }
+        table
+            .installed
+            .push((kccs, scope))
+            .map_err(|_| CoAPError::bad_request())?;
+        for &public_key in active.retired.iter().chain(&retire) {
+            if public_key != new_public_key.as_slice() && !table.is_retired(public_key) {
+                table
+                    .retired
+                    .push(public_key)
+                    .map_err(|_| CoAPError::bad_request())?;
+            }
+        }
+
+        let mut encoded = [0; TABLE_SIZE];
+        let len = table
+            .encode(&mut encoded)
+            .map_err(|_| CoAPError::bad_request())?;
+        let table = EncodedTable::from_slice(encoded.get(..len).unwrap_or_default())
+            .map_err(|()| CoAPError::internal_server_error())?;
+
+        state.generation = state.generation.wrapping_add(1);
+        state.pending = Some(Pending {
+            table,
+            credential,
+            status: Status::Staging,
+        });
+        Ok(())
+    })?;
+    PERSIST.signal(());
+    Ok(())
+}
+
+/// Discards the staged credential.
+///
+/// # Errors
+///
+/// Returns an error if the credential is being committed.
+fn discard() -> Result<(), CoAPError> {
+    critical_section::with(|cs| {
+        let mut state = STATE.borrow_ref_mut(cs);
+        if state
+            .pending
+            .as_ref()
+            .is_some_and(|pending| pending.status == Status::Committing)
+        {
+            return Err(CoAPError::service_unavailable());
+        }
+        state.pending = None;
+        // Outdates any staging in progress.
+        state.generation = state.generation.wrapping_add(1);
+        Ok(())
+    })
+}
+
+/// Commits the staged credential.
+///
+/// # Errors
+///
+/// Returns an error if no credential is staged.
+fn commit() -> Result<(), CoAPError> {
+    critical_section::with(|cs| {
+        let mut state = STATE.borrow_ref_mut(cs);
+        match state.pending.as_mut() {
+            Some(pending) if pending.status == Status::Staged => {
+                pending.status = Status::Committing;
+                Ok(())
+            }
+            _ => Err(CoAPError::not_found()),
+        }
+    })?;
+    PERSIST.signal(());
+    Ok(())
+}
+
+/// Encodes the public keys of the installed, retired and staged credentials into `buffer`, and
+/// returns the encoded length.
+///
+/// # Errors
+///
+/// Returns an error if the encoded state does not fit into `buffer`.
+fn encode_state(
+    buffer: &mut [u8],
+) -> Result<usize, minicbor::encode::Error<minicbor::encode::write::EndOfSlice>> {
+    let mut encoder = Encoder::new(Cursor::new(buffer));
+    critical_section::with(|cs| {
+        let state = STATE.borrow_ref(cs);
+        let table = Table::decode(&state.active).unwrap_or_else(Table::empty);
+        let staged = state
+            .pending
+            .as_ref()
+            .filter(|pending| pending.status != Status::Staging)
+            .and_then(|pending| pending.credential.public_key());
+
+        encoder
+            .map(2 + u64::from(staged.is_some()))?
+            .str("installed")?
+            .array(table.installed.len() as u64)?;
+        for (kccs, _) in &table.installed {
+            encoder.bytes(&public_key(kccs).unwrap_or_default())?;
+        }
+        encoder.str("retired")?.array(table.retired.len() as u64)?;
+        for public_key in &table.retired {
+            encoder.bytes(public_key)?;
+        }
+        if let Some(staged) = staged {
+            encoder.str("staged")?.bytes(&staged)?;
+        }
+        Ok(())
+    })?;
+    Ok(encoder.into_writer().position())
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use coap_handler::Handler as _;
+    use coap_message_implementations::inmemory::Message;
+    use coapcore::scope::Scope as _;
+    use hexlit::hex;
+
+    use super::*;
+
+    /// Credential of the administrator, which performs the rotations.
+    const ADMIN: &[u8] = &hex!(
+        "a2027734322d35302d33312d46462d45462d33372d33322d333908a101a5010202412b2001215820ac75e9ece3e50bfc8ed60399889522405c47bf16df96660a41298cb4307f7eb62258206e5de611388a4b8a8211334ac7d37ecb52a387d257e6db3c2a93df21ff3affc8"
+    );
+    /// Credential installed by the rotations.
+    const NEW: &[u8] = &hex!(
+        "a2026b6578616d706c652e65647508a101a501020241322001215820bbc34960526ea4d32e940cad2a234148ddc21791a12afbcbac93622046dd44f02258204519e257236b2a0ce2023f0931f1f386ca7afda64fcde0108c224c51eabf6072"
+    );
+    /// Credential that is neither installed nor staged.
+    const OTHER: &[u8] = &hex!(
+        "a2026b6578616d706c652e65647508a101a501020241332001215820cbc34960526ea4d32e940cad2a234148ddc21791a12afbcbac93622046dd44f02258204519e257236b2a0ce2023f0931f1f386ca7afda64fcde0108c224c51eabf6072"
+    );
+    /// `[["/diag/uptime", 1]]`
+    const SCOPE: &[u8] = &hex!("81826c2f646961672f757074696d6501");
+
+    /// Serializes the tests, which share the global state.
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    /// Resets the global state, and returns the guard serializing the tests.
+    fn reset() -> std::sync::MutexGuard<'static, ()> {
+        let guard = LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        critical_section::with(|cs| {
+            *STATE.borrow_ref_mut(cs) = State {
+                active: EncodedTable::new(),
+                active_slot: None,
+                pending: None,
+                generation: 0,
+            };
+        });
+        guard
+    }
+
+    /// Returns the request to stage `kccs`, retiring the credentials with `retire`.
+    fn stage_payload(kccs: &[u8], retire: &[&[u8]]) -> std::vec::Vec<u8> {
+        let mut buffer = [0; 256];
+        let mut encoder = Encoder::new(Cursor::new(buffer.as_mut_slice()));
+        encoder.array(3).unwrap().bytes(kccs).unwrap();
+        encoder.writer_mut().write_all(SCOPE).unwrap();
+        encoder.array(retire.len() as u64).unwrap();
+        for public_key in retire {
+            encoder.bytes(public_key).unwrap();
+        }
+        let len = encoder.into_writer().position();
+        buffer.get(..len).unwrap().to_vec()
+    }
+
+    /// Marks the pending set as `status`, as done by [`persist()`].
+    fn set_status(status: Status) {
+        critical_section::with(|cs| {
+            STATE.borrow_ref_mut(cs).pending.as_mut().unwrap().status = status;
+        });
+    }
+
+    fn status() -> Option<Status> {
+        critical_section::with(|cs| {
+            STATE
+                .borrow_ref(cs)
+                .pending
+                .as_ref()
+                .map(|pending| pending.status)
+        })
+    }
+
+    /// Returns the scope that the credential `kccs` is given when used.
+    fn scope_of(kccs: &[u8]) -> Option<UnionScope> {
+        let credential = lakers::Credential::parse_ccs(kccs).unwrap();
+        expand_id_cred_x(&credential.by_kid().unwrap()).map(|(_, scope)| scope)
+    }
+
+    /// Passes a request with `code` and `payload` to `resource`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error with which the resource rejects the request.
+    fn request(
+        resource: &mut CredentialsResource,
+        code: u8,
+        payload: &[u8],
+    ) -> Result<Request, CoAPError> {
+        let mut options_and_payload = std::vec::Vec::new();
+        if !payload.is_empty() {
+            options_and_payload.push(0xff);
+            options_and_payload.extend_from_slice(payload);
+        }
+        resource.extract_request_data(&Message::new(code, &options_and_payload))
+    }
+
+    #[test]
+    fn table_round_trip() {
+        let admin_key = public_key(ADMIN).unwrap();
+        let mut table = Table::empty();
+        table.installed.push((NEW, SCOPE)).unwrap();
+        table.installed.push((OTHER, SCOPE)).unwrap();
+        table.retired.push(&admin_key).unwrap();
+
+        let mut buffer = [0; TABLE_SIZE];
+        let len = table.encode(&mut buffer).unwrap();
+        let encoded = buffer.get(..len).unwrap();
+        let decoded = Table::decode(encoded).unwrap();
+        assert_eq!(decoded.installed, table.installed);
+        assert_eq!(decoded.retired, table.retired);
+        assert!(decoded.is_retired(&admin_key));
+        assert!(!decoded.is_retired(&public_key(NEW).unwrap()));
+
+        // Nothing was committed yet.
+        let empty = Table::decode(&[]).unwrap();
+        assert!(empty.installed.is_empty() && empty.retired.is_empty());
+        // Truncated tables are rejected.
+        assert!(Table::decode(encoded.get(..len - 1).unwrap()).is_none());
+        // Too small buffers are reported.
+        assert!(table.encode(&mut [0; 64]).is_err());
+    }
+
+    #[test]
+    fn only_the_staged_credential_can_commit() {
+        let _guard = reset();
+        let admin_key = public_key(ADMIN).unwrap();
+        stage_request(&stage_payload(NEW, &[&admin_key])).unwrap();
+
+        // The staged credential is only recognized once it is in storage.
+        assert!(scope_of(NEW).is_none());
+        assert!(commit().is_err());
+        set_status(Status::Staged);
+
+        let commit_message = Message::new(coap_numbers::code::POST, b"\xbbcredentials\x06commit");
+        let stage_message = Message::new(coap_numbers::code::POST, b"\xbbcredentials");
+        let scope = scope_of(NEW).unwrap();
+        assert!(scope.request_is_allowed(&commit_message));
+        assert!(!scope.request_is_allowed(&stage_message));
+        // Other credentials are not given the commit scope.
+        assert!(scope_of(OTHER).is_none());
+        assert!(scope_of(ADMIN).is_none());
+
+        commit().unwrap();
+        assert_eq!(status(), Some(Status::Committing));
+        // The set being committed can neither be replaced nor discarded.
+        assert!(stage_request(&stage_payload(OTHER, &[])).is_err());
+        assert!(discard().is_err());
+    }
+
+    #[test]
+    fn delete_discards_the_staged_credential() {
+        let _guard = reset();
+        let mut resource = CredentialsResource::new();
+        let payload = stage_payload(NEW, &[]);
+        assert!(matches!(
+            request(&mut resource, coap_numbers::code::POST, &payload),
+            Ok(Request::Stage)
+        ));
+        set_status(Status::Staged);
+        assert!(scope_of(NEW).is_some());
+
+        assert!(matches!(
+            request(&mut resource, coap_numbers::code::DELETE, &[]),
+            Ok(Request::Discard)
+        ));
+        assert_eq!(status(), None);
+        assert!(scope_of(NEW).is_none());
+        assert!(commit().is_err());
+
+        // A staging still in progress is outdated, so that its result is ignored once done.
+        let generation = || critical_section::with(|cs| STATE.borrow_ref(cs).generation);
+        request(&mut resource, coap_numbers::code::POST, &payload).unwrap();
+        let staging = generation();
+        request(&mut resource, coap_numbers::code::DELETE, &[]).unwrap();
+        assert_eq!(status(), None);
+        assert_ne!(generation(), staging);
+    }
+}
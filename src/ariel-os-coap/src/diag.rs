@@ -0,0 +1,352 @@
+//! Diagnostics resources, which give every device a uniform health surface.
+//!
+//! Each resource answers GET requests with CBOR with the Content-Format `application/cbor`:
+//!
+//! - `/diag/uptime`: the time since the system started, in milliseconds.
+//!
+//!   ```text
+//!   { "uptime": 3600042 }
+//!   ```
+//! - `/diag/mem`: the usage of the stack of the CoAP server, in bytes, and with the `alloc`
+//!   feature, the usage of the heap.
+//!
+//!   ```text
+//!   { "stack": { "size": 32768, "used-max": 12040 },
+//!     "heap": { "used": 1024, "free": 15360, "failures": 0 } }
+//!   ```
+//! - `/diag/net`: the state of the network interface, with its addresses in the interface format
+//!   of [RFC 9164](https://www.rfc-editor.org/rfc/rfc9164), which are omitted when not
+//!   configured.
+//!
+//!   ```text
+//!   { "link-up": true, "ipv4": 52([h'0a2a0011', 24]), "ipv6": 54([h'fd00…01', 64]) }
+//!   ```
+//! - `/diag/threads`: with the `threading` feature, the threads and the usage of their stacks,
+//!   in bytes.
+//!
+//!   ```text
+//!   [ { "id": 0, "priority": 1, "stack-size": 2048, "stack-used-max": 612 } ]
+//!   ```
+//! - `/diag/coap`: the counters of the requests of unauthenticated peers processed by the CoAP
+//!   server, and of those rejected by its limits, see [`server_stats()`](crate::server_stats).
+//!
+//!   ```text
+//!   { "handshakes-started": 12, "handshakes-rejected": 0, "handshakes-amplification-limited": 1,
+//!     "tokens-verified": 3, "tokens-rate-limited": 0 }
+//!   ```
+//!
+//! With the `diag` feature, the resources are served by the automatically started server. As any
+//! resource, they are subject to the server's access policy: as they expose details about the
+//! device, access to them is best limited to its administrators.
+//!
+//! Applications running their own CoAP server can add the resources to their handler:
+//!
+//! ```ignore
+//! let handler = new_dispatcher()
+//!     .at(&["diag", "uptime"], DiagResource::uptime())
+//!     .at(&["diag", "mem"], DiagResource::mem())
+//!     .at(&["diag", "net"], DiagResource::net(network_stack().await.unwrap()))
+//!     .at(&["diag", "threads"], DiagResource::threads())
+//!     .at(&["diag", "coap"], DiagResource::coap());
+//! ```
+
+use ariel_os_embassy::NetworkStack;
+use coap_message::{
+    Code as _, MinimalWritableMessage, MutableWritableMessage, OptionNumber as _, ReadableMessage,
+};
+use coap_message_utils::{Error as CoAPError, OptionsExt as _};
+use minicbor::{
+    Encoder,
+    data::Tag,
+    encode::write::{Cursor, EndOfSlice},
+};
+
+/// CoAP Content-Format of `application/cbor`.
+const CONTENT_FORMAT_CBOR: u16 = 60;
+
+/// Maximum length of the encoded diagnostics.
+const MAX_LEN: usize = 512;
+
+/// CBOR tag of IPv4 addresses, see RFC 9164.
+const TAG_IPV4: u64 = 52;
+
+/// CBOR tag of IPv6 addresses, see RFC 9164.
+const TAG_IPV6: u64 = 54;
+
+type EncodeError = minicbor::encode::Error<EndOfSlice>;
+
+/// A CoAP resource that reports diagnostics of the device.
+pub struct DiagResource {
+    diagnostic: Diagnostic,
+}
+
+impl core::fmt::Debug for DiagResource {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // The network stack does not implement `Debug`.
+        f.debug_struct("DiagResource").finish_non_exhaustive()
+    }
+}
+
+/// The diagnostics reported by a [`DiagResource`].
+enum Diagnostic {
+    Uptime,
+    Mem,
+    Net(NetworkStack),
+    #[cfg(feature = "threading")]
+    Threads,
+    Coap,
+}
+
+impl DiagResource {
+    /// Creates the resource reporting the uptime, served at `/diag/uptime`.
+    #[must_use]
+    pub fn uptime() -> Self {
+        Self {
+            diagnostic: Diagnostic::Uptime,
+        }
+    }
+
+    /// Creates the resource reporting the memory usage, served at `/diag/mem`.
+    #[must_use]
+    pub fn mem() -> Self {
+        Self {
+            diagnostic: Diagnostic::Mem,
+        }
+    }
+
+    /// Creates the resource reporting the state of the network interface of `stack`, served at
+    /// `/diag/net`.
+    #[must_use]
+    pub fn net(stack: NetworkStack) -> Self {
+        Self {
+            diagnostic: Diagnostic::Net(stack),
+        }
+    }
+
+    /// Creates the resource reporting the threads, served at `/diag/threads`.
+    #[cfg(feature = "threading")]
+    #[must_use]
+    pub fn threads() -> Self {
+        Self {
+            diagnostic: Diagnostic::Threads,
+        }
+    }
+
+    /// Creates the resource reporting the counters of the CoAP server, served at `/diag/coap`.
+    #[must_use]
+    pub fn coap() -> Self {
+        Self {
+            diagnostic: Diagnostic::Coap,
+        }
+    }
+
+    /// Encodes the diagnostics into `buffer`, and returns the encoded length.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the encoded diagnostics do not fit into `buffer`.
+    fn encode(&self, buffer: &mut [u8]) -> Result<usize, EncodeError> {
+        let mut encoder = Encoder::new(Cursor::new(buffer));
+        match &self.diagnostic {
+            Diagnostic::Uptime => encode_uptime(&mut encoder)?,
+            Diagnostic::Mem => encode_mem(&mut encoder)?,
+            Diagnostic::Net(stack) => encode_net(&mut encoder, *stack)?,
+            #[cfg(feature = "threading")]
+            Diagnostic::Threads => encode_threads(&mut encoder)?,
+            Diagnostic::Coap => encode_coap(&mut encoder, crate::server_stats())?,
+        }
+        Ok(encoder.into_writer().position())
+    }
+}
+
+impl coap_handler::Handler for DiagResource {
+    type RequestData = ();
+    type ExtractRequestError = CoAPError;
+    type BuildResponseError<M: MinimalWritableMessage> = CoAPError;
+
+    fn extract_request_data<M: ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, Self::ExtractRequestError> {
+        if request.code().into() != coap_numbers::code::GET {
+            return Err(CoAPError::method_not_allowed());
+        }
+        request.options().ignore_elective_others()?;
+        Ok(())
+    }
+
+    fn estimate_length(&mut self, (): &Self::RequestData) -> usize {
+        // Content-Format option and payload marker
+        MAX_LEN + 4
+    }
+
+    fn build_response<M: MutableWritableMessage>(
+        &mut self,
+        response: &mut M,
+        (): Self::RequestData,
+    ) -> Result<(), Self::BuildResponseError<M>> {
+        let mut buffer = [0; MAX_LEN];
+        let len = self
+            .encode(&mut buffer)
+            .map_err(|_| CoAPError::internal_server_error())?;
+
+        response.set_code(
+            M::Code::new(coap_numbers::code::CONTENT).map_err(CoAPError::from_unionerror)?,
+        );
+        response
+            .add_option_uint(
+                M::OptionNumber::new(coap_numbers::option::CONTENT_FORMAT)
+                    .map_err(CoAPError::from_unionerror)?,
+                CONTENT_FORMAT_CBOR,
+            )
+            .map_err(CoAPError::from_unionerror)?;
+        response
+            .set_payload(
+                buffer
+                    .get(..len)
+                    .ok_or_else(CoAPError::internal_server_error)?,
+            )
+            .map_err(CoAPError::from_unionerror)?;
+        Ok(())
+    }
+}
+
+/// Encodes the uptime.
+///
+/// # Errors
+///
+/// Returns an error if the encoded uptime does not fit into the buffer of `encoder`.
+fn encode_uptime(encoder: &mut Encoder<Cursor<&mut [u8]>>) -> Result<(), EncodeError> {
+    encoder
+        .map(1)?
+        .str("uptime")?
+        .u64(ariel_os_embassy::time::uptime().as_millis())?;
+    Ok(())
+}
+
+/// Encodes the usage of the stack of the current task, and of the heap if enabled.
+///
+/// # Errors
+///
+/// Returns an error if the encoded usages do not fit into the buffer of `encoder`.
+fn encode_mem(encoder: &mut Encoder<Cursor<&mut [u8]>>) -> Result<(), EncodeError> {
+    let stack = ariel_os_rt::stack::Stack::get();
+    encoder
+        .map(1 + u64::from(cfg!(feature = "alloc")))?
+        .str("stack")?
+        .map(2)?
+        .str("size")?
+        .u64(stack.size() as u64)?
+        .str("used-max")?
+        .u64(stack.used_max() as u64)?;
+
+    #[cfg(feature = "alloc")]
+    {
+        let heap = ariel_os_alloc::stats();
+        encoder
+            .str("heap")?
+            .map(3)?
+            .str("used")?
+            .u64(heap.used as u64)?
+            .str("free")?
+            .u64(heap.free as u64)?
+            .str("failures")?
+            .u64(heap.failures as u64)?;
+    }
+    Ok(())
+}
+
+/// Encodes the state of the network interface of `stack`.
+///
+/// # Errors
+///
+/// Returns an error if the encoded state does not fit into the buffer of `encoder`.
+fn encode_net(
+    encoder: &mut Encoder<Cursor<&mut [u8]>>,
+    stack: NetworkStack,
+) -> Result<(), EncodeError> {
+    let ipv4 = stack.config_v4().map(|config| config.address);
+    let ipv6 = stack.config_v6().map(|config| config.address);
+
+    encoder
+        .map(1 + u64::from(ipv4.is_some()) + u64::from(ipv6.is_some()))?
+        .str("link-up")?
+        .bool(stack.is_link_up())?;
+    if let Some(cidr) = ipv4 {
+        encoder
+            .str("ipv4")?
+            .tag(Tag::new(TAG_IPV4))?
+            .array(2)?
+            .bytes(&cidr.address().octets())?
+            .u8(cidr.prefix_len())?;
+    }
+    if let Some(cidr) = ipv6 {
+        encoder
+            .str("ipv6")?
+            .tag(Tag::new(TAG_IPV6))?
+            .array(2)?
+            .bytes(&cidr.address().octets())?
+            .u8(cidr.prefix_len())?;
+    }
+    Ok(())
+}
+
+/// Encodes the counters of `stats`.
+///
+/// # Errors
+///
+/// Returns an error if the encoded counters do not fit into the buffer of `encoder`.
+fn encode_coap(
+    encoder: &mut Encoder<Cursor<&mut [u8]>>,
+    stats: &coapcore::limits::Stats,
+) -> Result<(), EncodeError> {
+    encoder
+        .map(5)?
+        .str("handshakes-started")?
+        .u32(stats.handshakes_started())?
+        .str("handshakes-rejected")?
+        .u32(stats.handshakes_rejected())?
+        .str("handshakes-amplification-limited")?
+        .u32(stats.handshakes_amplification_limited())?
+        .str("tokens-verified")?
+        .u32(stats.tokens_verified())?
+        .str("tokens-rate-limited")?
+        .u32(stats.tokens_rate_limited())?;
+    Ok(())
+}
+
+/// Encodes the threads and the usage of their stacks.
+///
+/// # Errors
+///
+/// Returns an error if the encoded threads do not fit into the buffer of `encoder`.
+#[cfg(feature = "threading")]
+fn encode_threads(encoder: &mut Encoder<Cursor<&mut [u8]>>) -> Result<(), EncodeError> {
+    use ariel_os_threads::{THREAD_COUNT, ThreadId};
+
+    // Collected first, so that the length of the array stays consistent with its items even when
+    // threads are created meanwhile.
+    let threads: heapless::Vec<ThreadId, THREAD_COUNT> = (0..THREAD_COUNT)
+        .filter_map(|id| u8::try_from(id).ok())
+        .map(ThreadId::new)
+        .filter(|&thread_id| ariel_os_threads::is_valid_tid(thread_id))
+        .collect();
+
+    encoder.array(threads.len() as u64)?;
+    for thread_id in threads {
+        let priority = ariel_os_threads::get_priority(thread_id).map_or(0, usize::from);
+        let (lowest, highest) = ariel_os_threads::stack_limits(thread_id).unwrap_or_default();
+        let used_max = ariel_os_threads::stack_used_max(thread_id).unwrap_or_default();
+        encoder
+            .map(4)?
+            .str("id")?
+            .u64(usize::from(thread_id) as u64)?
+            .str("priority")?
+            .u64(priority as u64)?
+            .str("stack-size")?
+            .u64((highest - lowest) as u64)?
+            .str("stack-used-max")?
+            .u64(used_max as u64)?;
+    }
+    Ok(())
+}
@@ -5,7 +5,29 @@
 //! and selects [`embedded_nal_coap`] for CoAP over UDP, it selects [`ariel_os_random`] as a source
 //! of randomness, and [`lakers_crypto_rustcrypto`] for the cryptographic algorithm
 //! implementations.
-#![no_std]
+//!
+//! # Configuration
+//!
+//! - `CONFIG_COAP_CONCURRENT_REQUESTS` (default: 3): maximum number of concurrent requests sent
+//!   by the CoAP client.
+//! - `CONFIG_COAP_SOCKET_BUFFER_SIZE` (default: 1500): size of each of the receive and transmit
+//!   buffers of the UDP socket, in bytes, which limits the size of CoAP messages.
+//! - `CONFIG_COAP_SOCKET_PACKET_COUNT` (default: 2): maximum number of packets queued in each of
+//!   the receive and transmit buffers of the UDP socket.
+//! - `CONFIG_COAP_CREDENTIALS_TABLE_SIZE` (default: 1024): maximum size of the encoded set of
+//!   credentials installed through [`credentials`], in bytes.
+//! - `CONFIG_COAP_BRIDGE_MAX_PAYLOAD_LEN` (default: 256): maximum length of the payloads bridged
+//!   by [`bridge`], in bytes.
+//! - `CONFIG_COAP_MAX_PENDING_HANDSHAKES` (default: 2): maximum number of EDHOC handshakes of
+//!   unauthenticated peers that the server keeps pending at the same time.
+//! - `CONFIG_COAP_TOKEN_VERIFICATION_RATE` (default: 4): maximum number of ACE tokens the server
+//!   verifies per second. As the server does not keep track of time yet, this is not enforced.
+//! - `CONFIG_COAP_AMPLIFICATION_FACTOR` (default: 3): factor by which an EDHOC message 2 sent by the
+//!   server may be larger than the message 1 it responds to.
+//!
+//! See [`coapcore::limits`] for details on these limits. How often they were enforced is counted
+//! in [`server_stats()`].
+#![cfg_attr(not(test), no_std)]
 #![deny(missing_docs)]
 
 // Moving work from https://github.com/embassy-rs/embassy/pull/2519 in here for the time being
@@ -14,6 +36,21 @@ mod udp_nal;
 #[cfg(feature = "coap-server-config-storage")]
 mod stored;
 
+#[cfg(feature = "mqtt-bridge")]
+pub mod bridge;
+
+#[cfg(feature = "credential-rotation")]
+pub mod credentials;
+
+#[cfg(feature = "diag")]
+pub mod diag;
+
+#[cfg(feature = "negotiation")]
+pub mod negotiation;
+
+#[cfg(feature = "update")]
+pub mod update;
+
 use ariel_os_debug::log::info;
 use ariel_os_embassy::cell::SameExecutorCell;
 use coap_handler_implementations::ReportingHandlerBuilder;
@@ -21,7 +58,45 @@ use embassy_net::udp::{PacketMetadata, UdpSocket};
 use embassy_sync::watch::Watch;
 use static_cell::StaticCell;
 
-const CONCURRENT_REQUESTS: usize = 3;
+const CONCURRENT_REQUESTS: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_COAP_CONCURRENT_REQUESTS",
+    3,
+    "maximum number of concurrent requests sent by the CoAP client"
+);
+const SOCKET_BUFFER_SIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_COAP_SOCKET_BUFFER_SIZE",
+    1500,
+    "size of the receive and transmit buffers of the CoAP socket"
+);
+const SOCKET_PACKET_COUNT: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_COAP_SOCKET_PACKET_COUNT",
+    2,
+    "maximum number of packets queued in the buffers of the CoAP socket"
+);
+
+const MAX_PENDING_HANDSHAKES: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_COAP_MAX_PENDING_HANDSHAKES",
+    2,
+    "maximum number of pending EDHOC handshakes of the CoAP server"
+);
+const TOKEN_VERIFICATION_RATE: u32 = ariel_os_utils::u32_from_env_or!(
+    "CONFIG_COAP_TOKEN_VERIFICATION_RATE",
+    4,
+    "maximum number of ACE tokens verified per second by the CoAP server"
+);
+const AMPLIFICATION_FACTOR: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_COAP_AMPLIFICATION_FACTOR",
+    3,
+    "factor by which EDHOC message 2 may be larger than message 1"
+);
+
+/// Limits enforced by the CoAP server on unauthenticated peers.
+const LIMITS: coapcore::limits::Limits = coapcore::limits::Limits::new()
+    .with_max_pending_handshakes(MAX_PENDING_HANDSHAKES)
+    .with_token_verifications_per_second(TOKEN_VERIFICATION_RATE)
+    .with_amplification_factor(AMPLIFICATION_FACTOR);
+
+static SERVER_STATS: coapcore::limits::Stats = coapcore::limits::Stats::new();
 
 static CLIENT_READY: Watch<
     embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
@@ -47,6 +122,8 @@ mod demo_setup {
             ["/.well-known/core", 1],
             ["/poem", 1],
             ["/hello", 1],
+            ["/version", 1],
+            ["/sensors", 1],
             / any operation /
             ["/led", 63]
     ]);
@@ -54,6 +131,16 @@ mod demo_setup {
     /// Scope usable by the the administrator of the demo device.
     const ADMIN_SCOPE: cboritem::CborItem = cbor!([
             ["/stdout", 17 / GET and FETCH /],
+            ["/crash", 9 / GET and DELETE /],
+            ["/update", 1],
+            ["/update/install", 2 / POST /],
+            ["/update/confirm", 2 / POST /],
+            ["/update/revert", 2 / POST /],
+            ["/diag/uptime", 1],
+            ["/diag/mem", 1],
+            ["/diag/net", 1],
+            ["/diag/threads", 1],
+            ["/diag/coap", 1],
             ["/.well-known/core", 1],
             ["/poem", 1]
     ]);
@@ -125,12 +212,12 @@ async fn coap_run_impl(handler: impl coap_handler::Handler + coap_handler::Repor
     // request, because we shouldn't hand out a client early).
     stack.wait_config_up().await;
 
-    // FIXME trim to CoAP requirements (those values are just a likely good starting point for "we
-    // process any message immediately anyway")
-    let mut rx_meta = [PacketMetadata::EMPTY; 2];
-    let mut rx_buffer = [0; 1500];
-    let mut tx_meta = [PacketMetadata::EMPTY; 2];
-    let mut tx_buffer = [0; 1500];
+    // The defaults are just a likely good starting point for "we process any message immediately
+    // anyway".
+    let mut rx_meta = [PacketMetadata::EMPTY; SOCKET_PACKET_COUNT];
+    let mut rx_buffer = [0; SOCKET_BUFFER_SIZE];
+    let mut tx_meta = [PacketMetadata::EMPTY; SOCKET_PACKET_COUNT];
+    let mut tx_buffer = [0; SOCKET_BUFFER_SIZE];
 
     let socket = UdpSocket::new(
         stack,
@@ -173,7 +260,9 @@ async fn coap_run_impl(handler: impl coap_handler::Handler + coap_handler::Repor
         || lakers_crypto_rustcrypto::Crypto::new(ariel_os_random::crypto_rng()),
         ariel_os_random::crypto_rng(),
         coapcore::time::TimeUnknown,
-    );
+    )
+    .with_limits(LIMITS)
+    .with_stats(&SERVER_STATS);
 
     info!("Server is ready.");
 
@@ -201,6 +290,15 @@ async fn coap_run_impl(handler: impl coap_handler::Handler + coap_handler::Repor
     unreachable!("embassy-net's sockets do not get closed (but embedded-nal-coap can't know that)");
 }
 
+/// Returns the counters of the requests of unauthenticated peers processed by the CoAP server, and
+/// of those rejected by its limits.
+///
+/// The counters stay at zero until [`coap_run()`] has been called.
+#[must_use]
+pub fn server_stats() -> &'static coapcore::limits::Stats {
+    &SERVER_STATS
+}
+
 /// Returns a CoAP client requester.
 ///
 /// This asynchronously blocks until [`coap_run()`] has been called (which happens at startup
@@ -228,7 +326,13 @@ pub async fn coap_client()
 ///
 /// * It provides the backend for the CoAP client operation (which leaves message sending to that
 ///   task).
-/// * It runs any CoAP server components provided by the OS (none yet).
+/// * It runs any CoAP server components provided by the OS (with the `version` feature, the
+///   firmware versions at `/version`; with the `sensors` feature, the sensor readings below
+///   `/sensors`; with the `crash-report` feature, the last crash report at `/crash`; with the
+///   `diag` feature, the diagnostics resources below `/diag`; with the `credential-rotation`
+///   feature, the credentials management resource at `/credentials`; with the `settings`
+///   feature, the settings at `/settings`; with the `update` feature, the status and commands
+///   of firmware updates at `/update`).
 #[cfg(not(feature = "coap-server"))]
 #[ariel_os_macros::task(autostart)]
 async fn coap_run() {
@@ -236,5 +340,77 @@ async fn coap_run() {
 
     // FIXME: Provide an "all system components" constructor in this crate.
     let handler = new_dispatcher();
+    #[cfg(feature = "version")]
+    let handler = {
+        use coap_handler_implementations::HandlerBuilder;
+
+        handler.at_with_attributes(
+            &["version"],
+            &[],
+            ariel_os_version::coap::VersionResource::new(),
+        )
+    };
+    #[cfg(feature = "sensors")]
+    let handler = {
+        use coap_handler_implementations::HandlerBuilder;
+
+        handler.below(&["sensors"], ariel_os_sensors::coap::SensorsResource::new())
+    };
+    #[cfg(feature = "crash-report")]
+    let handler = {
+        use coap_handler_implementations::HandlerBuilder;
+
+        handler.at_with_attributes(&["crash"], &[], ariel_os_crash::coap::CrashResource::new())
+    };
+    #[cfg(feature = "diag")]
+    let handler = {
+        use coap_handler_implementations::HandlerBuilder;
+        use diag::DiagResource;
+
+        let stack = ariel_os_embassy::net::network_stack().await.unwrap();
+        let handler = handler
+            .at_with_attributes(&["diag", "uptime"], &[], DiagResource::uptime())
+            .at_with_attributes(&["diag", "mem"], &[], DiagResource::mem())
+            .at_with_attributes(&["diag", "net"], &[], DiagResource::net(stack))
+            .at_with_attributes(&["diag", "coap"], &[], DiagResource::coap());
+        #[cfg(feature = "threading")]
+        let handler =
+            handler.at_with_attributes(&["diag", "threads"], &[], DiagResource::threads());
+        handler
+    };
+    #[cfg(feature = "credential-rotation")]
+    let handler = {
+        use coap_handler_implementations::HandlerBuilder;
+        use credentials::CredentialsResource;
+
+        handler
+            .at_with_attributes(&["credentials"], &[], CredentialsResource::new())
+            .at_with_attributes(
+                &["credentials", "commit"],
+                &[],
+                CredentialsResource::commit(),
+            )
+    };
+    #[cfg(feature = "settings")]
+    let handler = {
+        use coap_handler_implementations::HandlerBuilder;
+
+        handler.at_with_attributes(
+            &["settings"],
+            &[],
+            ariel_os_settings::coap::SettingsResource::new(),
+        )
+    };
+    #[cfg(feature = "update")]
+    let handler = {
+        use coap_handler_implementations::HandlerBuilder;
+        use update::UpdateResource;
+
+        handler
+            .at_with_attributes(&["update"], &[], UpdateResource::new())
+            .at_with_attributes(&["update", "install"], &[], UpdateResource::install())
+            .at_with_attributes(&["update", "confirm"], &[], UpdateResource::confirm())
+            .at_with_attributes(&["update", "revert"], &[], UpdateResource::revert())
+    };
     coap_run_impl(handler).await;
 }
@@ -0,0 +1,209 @@
+//! Content-Format negotiation, for resources serving several representations.
+//!
+//! A [`NegotiatedResource`] answers GET requests with one of its [`Representation`]s, picked by
+//! the Accept option of the request, eg. CBOR for constrained clients and JSON for others:
+//!
+//! ```ignore
+//! fn cbor(state: &Status, buffer: &mut [u8]) -> Option<usize> { ... }
+//! fn json(state: &Status, buffer: &mut [u8]) -> Option<usize> { ... }
+//!
+//! let resource = NegotiatedResource::new(
+//!     Status::new(),
+//!     [
+//!         Representation::new(content_format::CBOR, cbor),
+//!         Representation::new(content_format::JSON, json),
+//!     ],
+//!     256,
+//! );
+//! let handler = new_dispatcher().at(&["status"], resource);
+//! ```
+//!
+//! Requests without an Accept option get the first representation, and requests accepting none
+//! of them get a 4.06 Not Acceptable response.
+//!
+//! Handlers implementing other methods as well can negotiate the representation themselves with
+//! [`accept()`] and [`select()`].
+
+use coap_message::{
+    Code as _, MessageOption, MinimalWritableMessage, MutableWritableMessage, OptionNumber as _,
+    ReadableMessage,
+};
+use coap_message_utils::{Error as CoAPError, OptionsExt as _};
+
+/// Common CoAP Content-Formats.
+pub mod content_format {
+    /// `text/plain; charset=utf-8`
+    pub const TEXT_PLAIN: u16 = 0;
+    /// `application/link-format`
+    pub const LINK_FORMAT: u16 = 40;
+    /// `application/json`
+    pub const JSON: u16 = 50;
+    /// `application/cbor`
+    pub const CBOR: u16 = 60;
+}
+
+/// A representation of the state `S` of a resource, in one Content-Format.
+pub struct Representation<S> {
+    content_format: u16,
+    write: fn(&S, &mut [u8]) -> Option<usize>,
+}
+
+impl<S> Representation<S> {
+    /// Creates a representation in `content_format`, which `write` writes into the buffer it is
+    /// given, returning its length, or `None` if it does not fit.
+    #[must_use]
+    pub const fn new(content_format: u16, write: fn(&S, &mut [u8]) -> Option<usize>) -> Self {
+        Self {
+            content_format,
+            write,
+        }
+    }
+
+    /// Returns the Content-Format of the representation.
+    #[must_use]
+    pub const fn content_format(&self) -> u16 {
+        self.content_format
+    }
+}
+
+impl<S> core::fmt::Debug for Representation<S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Representation")
+            .field("content_format", &self.content_format)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A CoAP resource serving its state `S` in the `N` Content-Formats of its representations.
+pub struct NegotiatedResource<S, const N: usize> {
+    state: S,
+    representations: [Representation<S>; N],
+    max_len: usize,
+}
+
+impl<S, const N: usize> NegotiatedResource<S, N> {
+    /// Creates a resource serving `state` in its `representations`, the first of which is the
+    /// default, and none of which is longer than `max_len`.
+    #[must_use]
+    pub const fn new(state: S, representations: [Representation<S>; N], max_len: usize) -> Self {
+        Self {
+            state,
+            representations,
+            max_len,
+        }
+    }
+
+    /// Returns the state served by the resource.
+    pub fn state(&mut self) -> &mut S {
+        &mut self.state
+    }
+}
+
+impl<S, const N: usize> core::fmt::Debug for NegotiatedResource<S, N> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("NegotiatedResource")
+            .field("representations", &self.representations)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, const N: usize> coap_handler::Handler for NegotiatedResource<S, N> {
+    /// Index of the representation to respond with.
+    type RequestData = usize;
+    type ExtractRequestError = CoAPError;
+    type BuildResponseError<M: MinimalWritableMessage> = CoAPError;
+
+    fn extract_request_data<M: ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, Self::ExtractRequestError> {
+        if request.code().into() != coap_numbers::code::GET {
+            return Err(CoAPError::method_not_allowed());
+        }
+        let mut accepted = None;
+        accept(request.options(), &mut accepted).ignore_elective_others()?;
+        select(
+            accepted,
+            self.representations
+                .iter()
+                .map(Representation::content_format),
+        )
+    }
+
+    fn estimate_length(&mut self, _request: &Self::RequestData) -> usize {
+        // Content-Format option and payload marker
+        self.max_len + 4
+    }
+
+    fn build_response<M: MutableWritableMessage>(
+        &mut self,
+        response: &mut M,
+        index: Self::RequestData,
+    ) -> Result<(), Self::BuildResponseError<M>> {
+        let representation = self
+            .representations
+            .get(index)
+            .ok_or_else(CoAPError::internal_server_error)?;
+
+        response.set_code(
+            M::Code::new(coap_numbers::code::CONTENT).map_err(CoAPError::from_unionerror)?,
+        );
+        response
+            .add_option_uint(
+                M::OptionNumber::new(coap_numbers::option::CONTENT_FORMAT)
+                    .map_err(CoAPError::from_unionerror)?,
+                representation.content_format,
+            )
+            .map_err(CoAPError::from_unionerror)?;
+
+        // The payload marker takes a byte of the available space.
+        let available = response.available_space().saturating_sub(1);
+        let payload = response
+            .payload_mut_with_len(available.min(self.max_len))
+            .map_err(|_| CoAPError::internal_server_error())?;
+        let len = (representation.write)(&self.state, payload)
+            .ok_or_else(CoAPError::internal_server_error)?;
+        response
+            .truncate(len)
+            .map_err(|_| CoAPError::internal_server_error())?;
+        Ok(())
+    }
+}
+
+/// Takes the value of the first Accept option of `options` into `accepted`.
+///
+/// The other options are returned, eg. to be checked with
+/// [`ignore_elective_others()`](coap_message_utils::OptionsExt::ignore_elective_others).
+pub fn accept<'a, O: MessageOption>(
+    options: impl Iterator<Item = O> + 'a,
+    accepted: &'a mut Option<u16>,
+) -> impl Iterator<Item = O> + 'a {
+    options.filter(move |option| {
+        if option.number() != coap_numbers::option::ACCEPT {
+            return true;
+        }
+        if accepted.is_none() {
+            *accepted = option.value_uint();
+        }
+        false
+    })
+}
+
+/// Returns the index of the Content-Format to respond with among the `supported` ones.
+///
+/// The `accepted` Content-Format is picked if any, and the first supported one otherwise.
+///
+/// # Errors
+///
+/// Returns a 4.06 Not Acceptable error if the accepted Content-Format is not supported.
+pub fn select(
+    accepted: Option<u16>,
+    mut supported: impl Iterator<Item = u16>,
+) -> Result<usize, CoAPError> {
+    match accepted {
+        None => Ok(0),
+        Some(accepted) => supported
+            .position(|content_format| content_format == accepted)
+            .ok_or_else(|| CoAPError::bad_option(coap_numbers::option::ACCEPT)),
+    }
+}
@@ -1,7 +1,6 @@
 //! Credential and key configuration backed by ariel-os storage
 
-use ariel_os_debug::log::{debug, info};
-use cbor_macro::cbo;
+use ariel_os_debug::log::info;
 use coapcore::seccfg::ServerSecurityConfig;
 
 mod flash_peers {
@@ -16,6 +15,8 @@ pub async fn server_security_config() -> impl ServerSecurityConfig {
 // don't have the async context to access any storage at CoAP time.
 struct StoredPolicy {
     own_edhoc_credential: (lakers::Credential, lakers::BytesP256ElemLen),
+    #[cfg(feature = "provisioning")]
+    provisioned_peers: ariel_os_provisioning::peers::Peers,
 }
 
 impl ServerSecurityConfig for StoredPolicy {
@@ -32,16 +33,43 @@ impl ServerSecurityConfig for StoredPolicy {
         &self,
         id_cred_x: lakers::IdCred,
     ) -> Option<(lakers::Credential, StoredClaims)> {
+        #[cfg(feature = "credential-rotation")]
+        if let Some((credential, scope)) = crate::credentials::expand_id_cred_x(&id_cred_x) {
+            return Some((credential, StoredClaims { scope }));
+        }
+
         for (credential, scope) in flash_peers::kccs() {
-            if credential.by_kid().is_ok_and(|by_kid| by_kid == id_cred_x)
-                || credential
-                    .by_value()
-                    .is_ok_and(|by_value| by_value == id_cred_x)
-            {
+            #[cfg(feature = "credential-rotation")]
+            if crate::credentials::is_retired(&credential) {
+                continue;
+            }
+            if matches(&credential, &id_cred_x) {
                 return Some((credential, StoredClaims { scope }));
             }
         }
 
+        #[cfg(feature = "provisioning")]
+        for (kccs, scope) in self.provisioned_peers.iter() {
+            let (Ok(credential), Ok(scope)) = (
+                lakers::Credential::parse_ccs(kccs),
+                coapcore::scope::AifValue::parse(scope),
+            ) else {
+                continue;
+            };
+            #[cfg(feature = "credential-rotation")]
+            if crate::credentials::is_retired(&credential) {
+                continue;
+            }
+            if matches(&credential, &id_cred_x) {
+                return Some((
+                    credential,
+                    StoredClaims {
+                        scope: scope.into(),
+                    },
+                ));
+            }
+        }
+
         // FIXME: This should be a default behavior -- but should it be part of a utility function
         // for expand_id_cred_x, or should it be where that is called?
         if let Some(credential_by_value) = id_cred_x.get_ccs() {
@@ -59,52 +87,38 @@ impl ServerSecurityConfig for StoredPolicy {
     }
 }
 
-/// Generates a private key and some credential matching it.
-///
-/// The 60 byte is kind of arbitrary; it's long enough for this, but needs to also accommodate
-/// anything that gets loaded. It currently contains an Key ID b"", which is convenient because it
-/// enables sending the key by reference.
-fn generate_credpair() -> (heapless::Vec<u8, 60>, lakers::BytesP256ElemLen) {
-    use lakers::CryptoTrait;
-    let mut crypto = lakers_crypto_rustcrypto::Crypto::new(ariel_os_random::crypto_rng());
-    let (private, public) = crypto.p256_generate_key_pair();
-    let mut credential = heapless::Vec::from_slice(&cbo!(
-        r#"{
-        /cnf/ 8: {/ COSE_Key / 1: {
-            /kty/  1: /EC2/ 2,
-            /kid/  2: '',
-            /crv/ -1: /P-256/ 1,
-            /x/   -2: h'0000000000000000000000000000000000000000000000000000000000000000'
-        }}
-    }"#
-    ))
-    .expect("Fits by construction");
-    let public_start = credential.len() - 32;
-    credential[public_start..].copy_from_slice(&public);
-    debug!("Generated private/public key pair.");
-    (credential, private)
+/// Returns whether `id_cred_x` refers to `credential`, by key ID or by value.
+pub(crate) fn matches(credential: &lakers::Credential, id_cred_x: &lakers::IdCred) -> bool {
+    credential.by_kid().is_ok_and(|by_kid| by_kid == *id_cred_x)
+        || credential
+            .by_value()
+            .is_ok_and(|by_value| by_value == *id_cred_x)
 }
 
 impl StoredPolicy {
     async fn load() -> Self {
         // Storage format: ([u8], [u8; 32]), where the former is a CCS, and the latter the
-        // corresponding key. We may need to extend the latter to be a COSE_Key when crypto agility
-        // becomes a thing.
+        // corresponding key. This is not written any more, but devices that generated their own
+        // credential before the device key was introduced keep using it.
         const OWN_CREDENTIAL_KEY: &str = "ariel-os-coap.own-edhoc-credential";
 
-        let (credential, key) = match ariel_os_storage::get(OWN_CREDENTIAL_KEY)
-            .await
-            .expect("flash error prevents startup")
-        {
-            Some(credpair) => credpair,
-            None => {
-                let credpair = generate_credpair();
-                ariel_os_storage::insert(OWN_CREDENTIAL_KEY, credpair.clone())
-                    .await
-                    .expect("flash error prevents startup");
-                credpair
-            }
-        };
+        // The 60 byte is kind of arbitrary; it needs to accommodate anything that gets loaded.
+        let (credential, key): (heapless::Vec<u8, 60>, lakers::BytesP256ElemLen) =
+            match ariel_os_storage::get(OWN_CREDENTIAL_KEY)
+                .await
+                .expect("flash error prevents startup")
+            {
+                Some(credpair) => credpair,
+                None => {
+                    let device_key = ariel_os_identity::device_key::device_key()
+                        .await
+                        .expect("flash error prevents startup");
+                    (
+                        heapless::Vec::from_slice(&device_key.ccs()).expect("Fits by construction"),
+                        device_key.secret_key_bytes(),
+                    )
+                }
+            };
 
         info!("CoAP server identity: {=[u8]:02x}", credential); // :02x could be :cbor
 
@@ -112,8 +126,18 @@ impl StoredPolicy {
             lakers::Credential::parse_ccs(&credential).expect("Processable by construction");
         let own_edhoc_credential = (credential, key);
 
+        #[cfg(feature = "credential-rotation")]
+        crate::credentials::load().await;
+
+        #[cfg(feature = "provisioning")]
+        let provisioned_peers = ariel_os_provisioning::peers::Peers::load()
+            .await
+            .expect("flash error prevents startup");
+
         Self {
             own_edhoc_credential,
+            #[cfg(feature = "provisioning")]
+            provisioned_peers,
         }
     }
 }
@@ -0,0 +1,329 @@
+//! Status and control of firmware updates, so that device-management servers can drive updates
+//! end-to-end.
+//!
+//! A GET request to `/update` returns the [`Status`] of the firmware slots as a CBOR map with the
+//! Content-Format `application/cbor`:
+//!
+//! ```text
+//! { "state": "testing", "confirmed": false, "boot-attempts": 1,
+//!   "version": "1.3.0", "build": "3f2a9c1", "error": "invalid-image",
+//!   "lwm2m-state": 3, "lwm2m-result": 5 }
+//! ```
+//!
+//! The `state` is the [`BootState`] (`idle`, `pending`, `testing` or `revert`), `version` and
+//! `build` identify the running firmware, and `error` is the last error reported through
+//! [`report_error()`](ariel_os_update::report_error) since startup, if any. The `confirmed` and
+//! `error` entries are omitted if not known. For servers speaking LwM2M, `lwm2m-state` and
+//! `lwm2m-result` hold the values of the State and Update Result resources of the LwM2M Firmware
+//! Update object (5):
+//!
+//! | `state`   | `lwm2m-state`    |
+//! | --------- | ---------------- |
+//! | `idle`    | 0 (Idle)         |
+//! | `pending` | 2 (Downloaded)   |
+//! | `testing` | 3 (Updating)     |
+//! | `revert`  | 3 (Updating)     |
+//!
+//! As reading the status accesses the flash, it is read in the background after every request
+//! and command, so that a change shows from the next GET request on. Until it was read once, GET
+//! requests are answered with 5.03 Service Unavailable.
+//!
+//! Commands are executed by POST requests without payload, like the Execute operation of LwM2M:
+//!
+//! - `/update/install` reboots into the pending update (see [`ariel_os_update::install()`]);
+//! - `/update/confirm` confirms the running firmware (see [`ariel_os_update::confirm()`]);
+//! - `/update/revert` reboots into the previous firmware while the running one is on trial (see
+//!   [`ariel_os_update::revert()`]).
+//!
+//! Commands are executed shortly after the response has been sent; a command that fails is
+//! reported as the last error. Images are written into the inactive slot through transports of
+//! their own, eg. downloads.
+//!
+//! Access to these resources needs to be limited to the administrators of the device, as they
+//! allow rebooting it into another firmware.
+
+use core::cell::Cell;
+
+use ariel_os_debug::log::{info, warn};
+use ariel_os_update::{BootState, Error, Status};
+use coap_message::{
+    Code as _, MinimalWritableMessage, MutableWritableMessage, OptionNumber as _, ReadableMessage,
+};
+use coap_message_utils::{Error as CoAPError, OptionsExt as _};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use minicbor::{Encoder, encode::write::Cursor};
+
+/// CoAP Content-Format of `application/cbor`.
+const CONTENT_FORMAT_CBOR: u16 = 60;
+
+/// Maximum length of the encoded status.
+const MAX_LEN: usize = 256;
+
+/// The status read last by [`run()`], if any.
+static STATUS: critical_section::Mutex<Cell<Option<Status>>> =
+    critical_section::Mutex::new(Cell::new(None));
+
+/// The command received last, until it is executed.
+static PENDING: critical_section::Mutex<Cell<Option<Command>>> =
+    critical_section::Mutex::new(Cell::new(None));
+
+/// Wakes up [`run()`] to execute the pending command, if any, and to read the status again.
+static WAKE: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// A command of [`UpdateResource`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Install,
+    Confirm,
+    Revert,
+}
+
+impl Command {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Install => "install",
+            Self::Confirm => "confirm",
+            Self::Revert => "revert",
+        }
+    }
+}
+
+/// A CoAP resource that reports the status of firmware updates, or executes one of their
+/// commands.
+#[derive(Debug)]
+pub struct UpdateResource {
+    command: Option<Command>,
+}
+
+impl UpdateResource {
+    /// Creates the resource reporting the status, served at `/update`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { command: None }
+    }
+
+    /// Creates the resource rebooting into the pending update, served at `/update/install`.
+    #[must_use]
+    pub fn install() -> Self {
+        Self {
+            command: Some(Command::Install),
+        }
+    }
+
+    /// Creates the resource confirming the running firmware, served at `/update/confirm`.
+    #[must_use]
+    pub fn confirm() -> Self {
+        Self {
+            command: Some(Command::Confirm),
+        }
+    }
+
+    /// Creates the resource reverting to the previous firmware, served at `/update/revert`.
+    #[must_use]
+    pub fn revert() -> Self {
+        Self {
+            command: Some(Command::Revert),
+        }
+    }
+}
+
+impl Default for UpdateResource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The operation requested on the resource.
+#[derive(Debug, Clone, Copy)]
+pub enum Request {
+    /// Report the status.
+    Get,
+    /// Execute the command of the resource.
+    Execute,
+}
+
+impl coap_handler::Handler for UpdateResource {
+    type RequestData = Request;
+    type ExtractRequestError = CoAPError;
+    type BuildResponseError<M: MinimalWritableMessage> = CoAPError;
+
+    fn extract_request_data<M: ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, Self::ExtractRequestError> {
+        let request_data = match (self.command, request.code().into()) {
+            (None, coap_numbers::code::GET) => Request::Get,
+            (Some(_), coap_numbers::code::POST) => Request::Execute,
+            _ => return Err(CoAPError::method_not_allowed()),
+        };
+        request.options().ignore_elective_others()?;
+
+        if let Some(command) = self.command {
+            if !request.payload().is_empty() {
+                return Err(CoAPError::bad_request());
+            }
+            stage(command)?;
+        }
+        WAKE.signal(());
+        Ok(request_data)
+    }
+
+    fn estimate_length(&mut self, _request: &Self::RequestData) -> usize {
+        // Content-Format option and payload marker
+        MAX_LEN + 4
+    }
+
+    fn build_response<M: MutableWritableMessage>(
+        &mut self,
+        response: &mut M,
+        request: Self::RequestData,
+    ) -> Result<(), Self::BuildResponseError<M>> {
+        if let Request::Execute = request {
+            response.set_code(
+                M::Code::new(coap_numbers::code::CHANGED).map_err(CoAPError::from_unionerror)?,
+            );
+            return Ok(());
+        }
+
+        let status = critical_section::with(|cs| STATUS.borrow(cs).get())
+            .ok_or_else(CoAPError::service_unavailable)?;
+        let mut buffer = [0; MAX_LEN];
+        let len = encode(status, &mut buffer).map_err(|_| CoAPError::internal_server_error())?;
+
+        response.set_code(
+            M::Code::new(coap_numbers::code::CONTENT).map_err(CoAPError::from_unionerror)?,
+        );
+        response
+            .add_option_uint(
+                M::OptionNumber::new(coap_numbers::option::CONTENT_FORMAT)
+                    .map_err(CoAPError::from_unionerror)?,
+                CONTENT_FORMAT_CBOR,
+            )
+            .map_err(CoAPError::from_unionerror)?;
+        response
+            .set_payload(
+                buffer
+                    .get(..len)
+                    .ok_or_else(CoAPError::internal_server_error)?,
+            )
+            .map_err(CoAPError::from_unionerror)?;
+        Ok(())
+    }
+}
+
+/// Hands `command` to [`run()`].
+///
+/// # Errors
+///
+/// Returns an error if another command is still being executed.
+fn stage(command: Command) -> Result<(), CoAPError> {
+    critical_section::with(|cs| {
+        let pending = PENDING.borrow(cs);
+        if pending.get().is_some() {
+            return Err(CoAPError::service_unavailable());
+        }
+        pending.set(Some(command));
+        Ok(())
+    })
+}
+
+/// Executes the commands received through [`UpdateResource`], and reads the status for it.
+#[ariel_os_macros::task(autostart)]
+async fn run() {
+    loop {
+        if let Some(command) = critical_section::with(|cs| PENDING.borrow(cs).get()) {
+            info!("update: executing {}", command.name());
+            // Installing and reverting only return if they failed.
+            let result = match command {
+                Command::Install => ariel_os_update::install().await.map(|never| match never {}),
+                Command::Confirm => ariel_os_update::confirm().await,
+                Command::Revert => ariel_os_update::revert().await.map(|never| match never {}),
+            };
+            if let Err(error) = result {
+                warn!("update: {} failed", command.name());
+                ariel_os_update::report_error(error);
+            }
+            critical_section::with(|cs| PENDING.borrow(cs).set(None));
+        }
+
+        match ariel_os_update::status().await {
+            Ok(status) => critical_section::with(|cs| STATUS.borrow(cs).set(Some(status))),
+            Err(_) => warn!("update: reading the status failed"),
+        }
+        WAKE.wait().await;
+    }
+}
+
+/// Encodes `status` into `buffer`, and returns the encoded length.
+///
+/// # Errors
+///
+/// Returns an error if the encoded status does not fit into `buffer`.
+fn encode(
+    status: Status,
+    buffer: &mut [u8],
+) -> Result<usize, minicbor::encode::Error<minicbor::encode::write::EndOfSlice>> {
+    let (state, lwm2m_state) = match status.state {
+        BootState::Idle => ("idle", 0),
+        BootState::Pending => ("pending", 2),
+        BootState::Testing => ("testing", 3),
+        BootState::Revert => ("revert", 3),
+    };
+    let mut encoder = Encoder::new(Cursor::new(buffer));
+    encoder
+        .map(6 + u64::from(status.confirmed.is_some()) + u64::from(status.last_error.is_some()))?;
+    encoder
+        .str("state")?
+        .str(state)?
+        .str("boot-attempts")?
+        .u32(status.boot_attempts)?
+        .str("version")?
+        .str(ariel_os_buildinfo::APP_VERSION)?
+        .str("build")?
+        .str(ariel_os_buildinfo::BUILD_HASH)?;
+    if let Some(confirmed) = status.confirmed {
+        encoder.str("confirmed")?.bool(confirmed)?;
+    }
+    if let Some(error) = status.last_error {
+        encoder.str("error")?.str(error_name(error))?;
+    }
+    encoder
+        .str("lwm2m-state")?
+        .u8(lwm2m_state)?
+        .str("lwm2m-result")?
+        .u8(status.last_error.map_or(0, lwm2m_result))?;
+    Ok(encoder.into_writer().position())
+}
+
+/// Returns the name under which `error` is reported.
+fn error_name(error: Error) -> &'static str {
+    match error {
+        Error::Flash => "flash",
+        Error::Layout => "layout",
+        Error::ImageTooLarge => "image-too-large",
+        Error::OutOfOrder => "out-of-order",
+        Error::InvalidState => "invalid-state",
+        Error::InvalidImage => "invalid-image",
+        Error::InvalidPatch => "invalid-patch",
+        Error::InvalidChunk => "invalid-chunk",
+        Error::Storage => "storage",
+        Error::Unauthenticated => "unauthenticated",
+        _ => "other",
+    }
+}
+
+/// Returns the value of the Update Result resource of the LwM2M Firmware Update object for
+/// `error`.
+fn lwm2m_result(error: Error) -> u8 {
+    match error {
+        // Not enough flash memory for the new firmware package.
+        Error::ImageTooLarge => 2,
+        // Integrity check failure for new downloaded package.
+        Error::InvalidImage
+        | Error::InvalidPatch
+        | Error::InvalidChunk
+        | Error::Unauthenticated => 5,
+        // Firmware update failed.
+        _ => 8,
+    }
+}
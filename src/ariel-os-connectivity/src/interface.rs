@@ -0,0 +1,133 @@
+//! Network interfaces managed by the [`Manager`](crate::Manager).
+
+use embassy_net::Stack;
+
+/// Kinds of network interfaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Kind {
+    /// Wired Ethernet, including Ethernet over USB.
+    Ethernet,
+    /// Wi-Fi.
+    Wifi,
+    /// Cellular networks, eg. LTE-M or NB-IoT.
+    Cellular,
+    /// Any other kind of interface.
+    Other,
+}
+
+/// A network interface, with the network stack running over it.
+///
+/// Implementations drive the hardware of the interface, eg. to join a Wi-Fi network or to attach
+/// to a cellular network; the network stack is run by its own task, as usual.
+pub trait Interface {
+    /// Error returned when bringing the interface up fails.
+    type Error;
+
+    /// Returns the kind of the interface.
+    fn kind(&self) -> Kind;
+
+    /// Returns the network stack running over the interface.
+    fn stack(&self) -> Stack<'static>;
+
+    /// Brings the interface up, eg. by powering its radio and joining a network.
+    ///
+    /// The manager then waits for the network stack to be configured.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the interface could not be brought up.
+    fn up(&mut self) -> impl Future<Output = Result<(), Self::Error>>;
+
+    /// Brings the interface down, eg. to save power while another interface is used.
+    fn down(&mut self) -> impl Future<Output = ()>;
+}
+
+/// Network interfaces, in order of decreasing priority.
+///
+/// This is implemented for arrays of interfaces, and for tuples of up to four interfaces of
+/// different types.
+pub trait Interfaces {
+    /// Number of interfaces.
+    const LEN: usize;
+
+    /// Returns the kind of the interface at `index`, or `None` if there is no such interface.
+    fn kind(&self, index: usize) -> Option<Kind>;
+
+    /// Returns the network stack of the interface at `index`, or `None` if there is no such
+    /// interface.
+    fn stack(&self, index: usize) -> Option<Stack<'static>>;
+
+    /// Brings the interface at `index` up, and returns whether that succeeded.
+    fn up(&mut self, index: usize) -> impl Future<Output = bool>;
+
+    /// Brings the interface at `index` down.
+    fn down(&mut self, index: usize) -> impl Future<Output = ()>;
+}
+
+impl<I: Interface, const N: usize> Interfaces for [I; N] {
+    const LEN: usize = N;
+
+    fn kind(&self, index: usize) -> Option<Kind> {
+        self.get(index).map(Interface::kind)
+    }
+
+    fn stack(&self, index: usize) -> Option<Stack<'static>> {
+        self.get(index).map(Interface::stack)
+    }
+
+    async fn up(&mut self, index: usize) -> bool {
+        match self.get_mut(index) {
+            Some(interface) => interface.up().await.is_ok(),
+            None => false,
+        }
+    }
+
+    async fn down(&mut self, index: usize) {
+        if let Some(interface) = self.get_mut(index) {
+            interface.down().await;
+        }
+    }
+}
+
+macro_rules! impl_interfaces_for_tuple {
+    ($len:literal: $($index:tt $interface:ident),+) => {
+        impl<$($interface: Interface),+> Interfaces for ($($interface,)+) {
+            const LEN: usize = $len;
+
+            fn kind(&self, index: usize) -> Option<Kind> {
+                match index {
+                    $($index => Some(self.$index.kind()),)+
+                    _ => None,
+                }
+            }
+
+            fn stack(&self, index: usize) -> Option<Stack<'static>> {
+                match index {
+                    $($index => Some(self.$index.stack()),)+
+                    _ => None,
+                }
+            }
+
+            async fn up(&mut self, index: usize) -> bool {
+                match index {
+                    $($index => self.$index.up().await.is_ok(),)+
+                    _ => false,
+                }
+            }
+
+            async fn down(&mut self, index: usize) {
+                match index {
+                    $($index => self.$index.down().await,)+
+                    _ => {}
+                }
+            }
+        }
+    };
+}
+
+impl_interfaces_for_tuple!(1: 0 A);
+impl_interfaces_for_tuple!(2: 0 A, 1 B);
+impl_interfaces_for_tuple!(3: 0 A, 1 B, 2 C);
+impl_interfaces_for_tuple!(4: 0 A, 1 B, 2 C, 3 D);
@@ -0,0 +1,266 @@
+//! Provides a connectivity manager, which brings up network interfaces in order of priority and
+//! fails over between them.
+//!
+//! The [`Manager`] brings up the first of its [`Interfaces`] through which the network is
+//! reachable, eg. Ethernet, then Wi-Fi, then cellular. It then checks the reachability of the
+//! network through the active interface with a [`Probe`], eg. a [`CoapPing`] to a server, and
+//! fails over to the next interface when too many probes fail in a row. While a lower-priority
+//! interface is active, the higher-priority ones are tried again regularly, so that the manager
+//! fails back to them once they work again.
+//!
+//! The active interface is published as a [`Path`], which applications follow through
+//! [`active_path()`] and [`path_changes()`] to send their traffic through the network stack of
+//! that interface:
+//!
+//! ```ignore
+//! let interfaces = (ethernet, wifi, cellular);
+//! let probe = CoapPing::new(SERVER);
+//! Manager::new(interfaces, probe, Config::default()).run().await
+//! ```
+//!
+//! Each interface runs its own network stack; interfaces that are not active are brought down,
+//! eg. to save the power of their radio.
+//!
+//! # Configuration
+//!
+//! The following environment variables can be used to configure the manager:
+//!
+//! - `CONFIG_CONNECTIVITY_MAX_RECEIVERS` (default: 2): maximum number of [`path_changes()`]
+//!   receivers at a time.
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+mod interface;
+mod probe;
+
+pub use interface::{Interface, Interfaces, Kind};
+pub use probe::{CoapPing, LinkProbe, Probe};
+
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    watch::{DynReceiver, Watch},
+};
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+
+/// Maximum number of [`path_changes()`] receivers at a time.
+const MAX_RECEIVERS: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_CONNECTIVITY_MAX_RECEIVERS",
+    2,
+    "maximum number of receivers of connectivity path changes"
+);
+
+static ACTIVE_PATH: Watch<CriticalSectionRawMutex, Option<Path>, MAX_RECEIVERS> = Watch::new();
+
+/// The interface through which the network is reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Path {
+    /// Index of the interface among the [`Interfaces`] of the [`Manager`].
+    pub index: usize,
+    /// Kind of the interface.
+    pub kind: Kind,
+}
+
+/// Returns the interface through which the network is currently reached, if any.
+#[must_use]
+pub fn active_path() -> Option<Path> {
+    ACTIVE_PATH.try_get().flatten()
+}
+
+/// Returns a receiver of the changes of the [`active_path()`], or `None` if there are too many
+/// receivers already.
+#[must_use]
+pub fn path_changes() -> Option<DynReceiver<'static, Option<Path>>> {
+    ACTIVE_PATH.dyn_receiver()
+}
+
+/// Configuration of the [`Manager`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// Time between probes of the active interface.
+    pub probe_interval: Duration,
+    /// Number of probes failing in a row after which the manager fails over.
+    pub max_failures: u8,
+    /// Time an interface has to get its network stack configured once brought up.
+    pub up_timeout: Duration,
+    /// Time between attempts to fail back to higher-priority interfaces.
+    pub failback_interval: Duration,
+    /// Time between attempts to bring up an interface when none works.
+    pub retry_interval: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            probe_interval: Duration::from_secs(30),
+            max_failures: 3,
+            up_timeout: Duration::from_secs(60),
+            failback_interval: Duration::from_secs(300),
+            retry_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+/// A connectivity manager, which fails over between network interfaces.
+pub struct Manager<I, P> {
+    interfaces: I,
+    probe: P,
+    config: Config,
+}
+
+impl<I: Interfaces, P: Probe> Manager<I, P> {
+    /// Creates a manager of `interfaces`, given in order of decreasing priority, whose
+    /// reachability is checked with `probe`.
+    #[must_use]
+    pub const fn new(interfaces: I, probe: P, config: Config) -> Self {
+        Self {
+            interfaces,
+            probe,
+            config,
+        }
+    }
+
+    /// Runs the manager, which publishes the [`active_path()`].
+    ///
+    /// A single manager must be run at a time.
+    pub async fn run(&mut self) -> ! {
+        // Interface that just failed, which is tried again after all the others.
+        let mut failed = None;
+        loop {
+            let active = match self.bring_up_first(I::LEN, failed).await {
+                Some(active) => Some(active),
+                None => match failed {
+                    Some(failed) if self.bring_up(failed).await => Some(failed),
+                    _ => None,
+                },
+            };
+            let Some(mut active) = active else {
+                failed = None;
+                Timer::after(self.config.retry_interval).await;
+                continue;
+            };
+            self.publish(Some(active));
+
+            let mut health = Health::new(self.config.max_failures);
+            let mut next_failback = Instant::now() + self.config.failback_interval;
+            loop {
+                Timer::after(self.config.probe_interval).await;
+
+                let reachable = match self.interfaces.stack(active) {
+                    Some(stack) => self.probe.probe(stack).await,
+                    None => false,
+                };
+                if !health.record(reachable) {
+                    break;
+                }
+
+                if active > 0 && Instant::now() >= next_failback {
+                    // Bring up the preferred interface before bringing down the active one, so
+                    // that the network stays reachable.
+                    if let Some(preferred) = self.bring_up_first(active, None).await {
+                        self.interfaces.down(active).await;
+                        active = preferred;
+                        health = Health::new(self.config.max_failures);
+                        self.publish(Some(active));
+                    }
+                    next_failback = Instant::now() + self.config.failback_interval;
+                }
+            }
+
+            self.publish(None);
+            self.interfaces.down(active).await;
+            failed = Some(active);
+        }
+    }
+
+    /// Brings up the first interface before `end` through which the network is reachable,
+    /// skipping `skipped`, and returns its index.
+    async fn bring_up_first(&mut self, end: usize, skipped: Option<usize>) -> Option<usize> {
+        for index in 0..end {
+            if Some(index) != skipped && self.bring_up(index).await {
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    /// Brings up the interface at `index`, and returns whether the network is reachable through
+    /// it; the interface is brought down again otherwise.
+    async fn bring_up(&mut self, index: usize) -> bool {
+        if !self.interfaces.up(index).await {
+            return false;
+        }
+        let reachable = match self.interfaces.stack(index) {
+            Some(stack) => {
+                with_timeout(self.config.up_timeout, stack.wait_config_up())
+                    .await
+                    .is_ok()
+                    && self.probe.probe(stack).await
+            }
+            None => false,
+        };
+        if !reachable {
+            self.interfaces.down(index).await;
+        }
+        reachable
+    }
+
+    fn publish(&self, active: Option<usize>) {
+        let path =
+            active.and_then(|index| self.interfaces.kind(index).map(|kind| Path { index, kind }));
+        ACTIVE_PATH.sender().send(path);
+    }
+}
+
+/// Tracks the probes failing in a row.
+struct Health {
+    failures: u8,
+    max_failures: u8,
+}
+
+impl Health {
+    fn new(max_failures: u8) -> Self {
+        Self {
+            failures: 0,
+            max_failures,
+        }
+    }
+
+    /// Records the result of a probe, and returns whether the interface is still considered
+    /// working.
+    fn record(&mut self, reachable: bool) -> bool {
+        if reachable {
+            self.failures = 0;
+        } else {
+            self.failures = self.failures.saturating_add(1);
+        }
+        self.failures < self.max_failures.max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_in_a_row() {
+        let mut health = Health::new(3);
+        assert!(health.record(false));
+        assert!(health.record(false));
+        assert!(health.record(true));
+        assert!(health.record(false));
+        assert!(health.record(false));
+        assert!(!health.record(false));
+    }
+
+    #[test]
+    fn single_failure() {
+        let mut health = Health::new(0);
+        assert!(health.record(true));
+        assert!(!health.record(false));
+    }
+}
@@ -0,0 +1,130 @@
+//! Probes checking whether the network is reachable through an interface.
+
+use embassy_net::{
+    IpEndpoint, Stack,
+    udp::{PacketMetadata, UdpSocket},
+};
+use embassy_time::{Duration, with_timeout};
+
+/// A check of whether the network is reachable through a network stack.
+pub trait Probe {
+    /// Returns whether the network is reachable through `stack`.
+    fn probe(&mut self, stack: Stack<'static>) -> impl Future<Output = bool>;
+}
+
+/// A [`Probe`] that only checks that the link is up and that the stack is configured, eg. that
+/// DHCP succeeded.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LinkProbe;
+
+impl Probe for LinkProbe {
+    async fn probe(&mut self, stack: Stack<'static>) -> bool {
+        stack.is_link_up() && stack.is_config_up()
+    }
+}
+
+/// Length of CoAP pings, and of the resets answering them.
+const PING_LEN: usize = 4;
+
+/// A [`Probe`] that sends a CoAP ping (an empty confirmable message) to a server, which answers
+/// with a reset message when reachable.
+///
+/// This checks reachability end-to-end, and doubles as a keepalive for the NAT mappings on the
+/// way to the server.
+#[derive(Debug, Clone, Copy)]
+pub struct CoapPing {
+    server: IpEndpoint,
+    timeout: Duration,
+    message_id: u16,
+}
+
+impl CoapPing {
+    /// Creates a probe pinging `server`, which waits 2 seconds for its answer.
+    #[must_use]
+    pub const fn new(server: IpEndpoint) -> Self {
+        Self {
+            server,
+            timeout: Duration::from_secs(2),
+            message_id: 0,
+        }
+    }
+
+    /// Returns the probe with the given time to wait for the answer of the server.
+    #[must_use]
+    pub const fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+}
+
+impl Probe for CoapPing {
+    async fn probe(&mut self, stack: Stack<'static>) -> bool {
+        let mut rx_meta = [PacketMetadata::EMPTY; 1];
+        let mut rx_buffer = [0; PING_LEN];
+        let mut tx_meta = [PacketMetadata::EMPTY; 1];
+        let mut tx_buffer = [0; PING_LEN];
+        let mut socket = UdpSocket::new(
+            stack,
+            &mut rx_meta,
+            &mut rx_buffer,
+            &mut tx_meta,
+            &mut tx_buffer,
+        );
+        if socket.bind(0).is_err() {
+            return false;
+        }
+
+        self.message_id = self.message_id.wrapping_add(1);
+        if socket
+            .send_to(&ping(self.message_id), self.server)
+            .await
+            .is_err()
+        {
+            return false;
+        }
+
+        let answer = async {
+            loop {
+                let mut buf = [0; PING_LEN];
+                // Longer datagrams are truncated, and thus not taken for resets.
+                if let Ok((len, metadata)) = socket.recv_from(&mut buf).await
+                    && metadata.endpoint == self.server
+                    && buf
+                        .get(..len)
+                        .is_some_and(|answer| is_reset(answer, self.message_id))
+                {
+                    return;
+                }
+            }
+        };
+        with_timeout(self.timeout, answer).await.is_ok()
+    }
+}
+
+/// Returns a CoAP ping with `message_id`: a confirmable message with neither code nor token.
+fn ping(message_id: u16) -> [u8; PING_LEN] {
+    let [high, low] = message_id.to_be_bytes();
+    [0x40, 0x00, high, low]
+}
+
+/// Returns whether `message` is a CoAP reset message answering the ping with `message_id`.
+fn is_reset(message: &[u8], message_id: u16) -> bool {
+    let [high, low] = message_id.to_be_bytes();
+    message == [0x70, 0x00, high, low]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ping_and_reset() {
+        assert_eq!(ping(0x1234), [0x40, 0x00, 0x12, 0x34]);
+        assert!(is_reset(&[0x70, 0x00, 0x12, 0x34], 0x1234));
+        // Answer to another ping.
+        assert!(!is_reset(&[0x70, 0x00, 0x12, 0x35], 0x1234));
+        // Acknowledgement.
+        assert!(!is_reset(&[0x60, 0x00, 0x12, 0x34], 0x1234));
+        // Reset with a token.
+        assert!(!is_reset(&[0x71, 0x00, 0x12, 0x34, 0x01], 0x1234));
+    }
+}
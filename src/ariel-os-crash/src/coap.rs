@@ -0,0 +1,141 @@
+//! Serves the last crash report as a CoAP resource, so that diagnostics can be retrieved from
+//! deployed devices.
+//!
+//! A GET request returns a CBOR map with the Content-Format `application/cbor`, or 4.04 Not
+//! Found if there was no crash since the report was last cleared:
+//!
+//! ```text
+//! { "kind": "panic", "message": "panicked at src/main.rs:12:5:\nindex out of bounds" }
+//! ```
+//!
+//! The `kind` is either `panic` or `fault`. A DELETE request acknowledges the report, which
+//! [clears](crate::clear) it.
+//!
+//! As crash reports may reveal details of the firmware, the resource should only be accessible to
+//! administrators through the security configuration of the CoAP server.
+//! Applications running their own CoAP server can add the resource to their handler:
+//!
+//! ```ignore
+//! let handler = new_dispatcher().at(&["crash"], CrashResource::new());
+//! ```
+
+use coap_message::{
+    Code as _, MinimalWritableMessage, MutableWritableMessage, OptionNumber as _, ReadableMessage,
+};
+use coap_message_utils::{Error as CoAPError, OptionsExt as _};
+use minicbor::{Encoder, encode::write::Cursor};
+
+use crate::{Kind, MAX_MESSAGE_LEN, Report};
+
+/// CoAP Content-Format of `application/cbor`.
+const CONTENT_FORMAT_CBOR: u16 = 60;
+
+/// Maximum length of the encoded report.
+const MAX_LEN: usize = MAX_MESSAGE_LEN + 32;
+
+/// A CoAP resource that serves the last crash [`Report`].
+#[derive(Debug, Default)]
+pub struct CrashResource {
+    _private: (),
+}
+
+impl CrashResource {
+    /// Creates the resource.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+/// The operation requested on the resource.
+#[derive(Debug, Clone, Copy)]
+pub enum Request {
+    /// Retrieve the report.
+    Get,
+    /// Clear the report.
+    Delete,
+}
+
+impl coap_handler::Handler for CrashResource {
+    type RequestData = Request;
+    type ExtractRequestError = CoAPError;
+    type BuildResponseError<M: MinimalWritableMessage> = CoAPError;
+
+    fn extract_request_data<M: ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, Self::ExtractRequestError> {
+        let request_data = match request.code().into() {
+            coap_numbers::code::GET => Request::Get,
+            coap_numbers::code::DELETE => Request::Delete,
+            _ => return Err(CoAPError::method_not_allowed()),
+        };
+        request.options().ignore_elective_others()?;
+        Ok(request_data)
+    }
+
+    fn estimate_length(&mut self, _request: &Self::RequestData) -> usize {
+        // Content-Format option and payload marker
+        MAX_LEN + 4
+    }
+
+    fn build_response<M: MutableWritableMessage>(
+        &mut self,
+        response: &mut M,
+        request: Self::RequestData,
+    ) -> Result<(), Self::BuildResponseError<M>> {
+        if let Request::Delete = request {
+            crate::clear();
+            response.set_code(
+                M::Code::new(coap_numbers::code::DELETED).map_err(CoAPError::from_unionerror)?,
+            );
+            return Ok(());
+        }
+
+        let report = crate::last_report().ok_or_else(CoAPError::not_found)?;
+        let mut buffer = [0; MAX_LEN];
+        let len = encode(&report, &mut buffer).map_err(|_| CoAPError::internal_server_error())?;
+
+        response.set_code(
+            M::Code::new(coap_numbers::code::CONTENT).map_err(CoAPError::from_unionerror)?,
+        );
+        response
+            .add_option_uint(
+                M::OptionNumber::new(coap_numbers::option::CONTENT_FORMAT)
+                    .map_err(CoAPError::from_unionerror)?,
+                CONTENT_FORMAT_CBOR,
+            )
+            .map_err(CoAPError::from_unionerror)?;
+        response
+            .set_payload(
+                buffer
+                    .get(..len)
+                    .ok_or_else(CoAPError::internal_server_error)?,
+            )
+            .map_err(CoAPError::from_unionerror)?;
+        Ok(())
+    }
+}
+
+/// Encodes `report` into `buffer`, and returns the encoded length.
+///
+/// # Errors
+///
+/// Returns an error if the encoded report does not fit into `buffer`.
+fn encode(
+    report: &Report,
+    buffer: &mut [u8],
+) -> Result<usize, minicbor::encode::Error<minicbor::encode::write::EndOfSlice>> {
+    let kind = match report.kind() {
+        Kind::Panic => "panic",
+        Kind::Fault => "fault",
+    };
+    let mut encoder = Encoder::new(Cursor::new(buffer));
+    encoder
+        .map(2)?
+        .str("kind")?
+        .str(kind)?
+        .str("message")?
+        .str(report.message())?;
+    Ok(encoder.into_writer().position())
+}
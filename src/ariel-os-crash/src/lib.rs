@@ -0,0 +1,303 @@
+//! Provides crash reports, which record the last panic or fault of the MCU across resets.
+//!
+//! When the MCU panics or faults, a [`Report`] is written to a RAM area that is not initialized
+//! at startup, and the MCU is reset.
+//! After the reset, the report can be retrieved with [`last_report()`], eg. to be sent to a
+//! server, and removed with [`clear()`] once it has been handled.
+//! With the `coap` feature, the report is also served as a CoAP resource, see [`coap`].
+//!
+//! ```ignore
+//! if let Some(report) = crash::last_report() {
+//!     info!("Crashed before the last reset: {}", report.message());
+//!     crash::clear();
+//! }
+//! ```
+//!
+//! Reports are kept across resets, but are lost when the device loses power.
+//! Messages longer than [`MAX_MESSAGE_LEN`] bytes are truncated.
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+#[cfg(feature = "coap")]
+pub mod coap;
+
+use core::{
+    cell::UnsafeCell,
+    fmt::Write as _,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Maximum length of the messages of reports, in bytes.
+pub const MAX_MESSAGE_LEN: usize = 192;
+
+/// Marks the slot as holding a report (`CRSH`).
+const MAGIC: u32 = 0x4352_5348;
+
+/// The slot the report is stored in, which is placed in a RAM section that is not initialized at
+/// startup on Cortex-M.
+#[cfg_attr(context = "cortex-m", unsafe(link_section = ".uninit.ariel-os-crash"))]
+static SLOT: SlotCell = SlotCell(UnsafeCell::new(MaybeUninit::uninit()));
+
+struct SlotCell(UnsafeCell<MaybeUninit<Slot>>);
+
+// SAFETY: the slot is only accessed in critical sections, and by the panic and fault handlers,
+// which do not return.
+unsafe impl Sync for SlotCell {}
+
+/// Whether a fault was recorded since startup, whose report is kept over the one of the panic
+/// that the fault handler raises.
+static FAULT_RECORDED: AtomicBool = AtomicBool::new(false);
+
+/// The kind of a crash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Kind {
+    /// The firmware panicked.
+    Panic,
+    /// The MCU raised a fault, eg. a `HardFault` on Cortex-M.
+    Fault,
+}
+
+impl Kind {
+    fn from_u32(kind: u32) -> Option<Self> {
+        match kind {
+            0 => Some(Self::Panic),
+            1 => Some(Self::Fault),
+            _ => None,
+        }
+    }
+
+    fn to_u32(self) -> u32 {
+        match self {
+            Self::Panic => 0,
+            Self::Fault => 1,
+        }
+    }
+}
+
+/// The report of a crash.
+#[derive(Clone)]
+pub struct Report {
+    kind: Kind,
+    message: [u8; MAX_MESSAGE_LEN],
+    message_len: usize,
+}
+
+impl Report {
+    /// Returns the kind of the crash.
+    #[must_use]
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    /// Returns the message of the crash: the panic message along with its location, or the
+    /// registers of the fault.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        self.message
+            .get(..self.message_len)
+            .and_then(|message| core::str::from_utf8(message).ok())
+            .unwrap_or_default()
+    }
+}
+
+impl core::fmt::Debug for Report {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Report")
+            .field("kind", &self.kind)
+            .field("message", &self.message())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns the report of the last crash, if there was one since it was last cleared.
+#[must_use]
+pub fn last_report() -> Option<Report> {
+    critical_section::with(|_| {
+        // SAFETY: the slot is retained across resets and may hold any bit pattern, all of which
+        // are valid for it, as it only contains integers; the magic and the checksum tell apart
+        // reports from leftovers.
+        let slot = unsafe { SLOT.0.get().read_volatile().assume_init() };
+        slot.report()
+    })
+}
+
+/// Clears the report of the last crash, eg. once it has been handled.
+pub fn clear() {
+    critical_section::with(|_| {
+        // SAFETY: accesses to the slot are serialized by the critical section.
+        unsafe { SLOT.0.get().write_volatile(MaybeUninit::new(Slot::empty())) };
+    });
+}
+
+/// Records the report of a panic.
+#[doc(hidden)]
+pub fn record_panic(info: &core::panic::PanicInfo<'_>) {
+    if !FAULT_RECORDED.load(Ordering::Relaxed) {
+        record(Kind::Panic, format_args!("{info}"));
+    }
+}
+
+/// Records the report of a fault, with the registers of the exception frame.
+#[doc(hidden)]
+pub fn record_fault(pc: u32, lr: u32, xpsr: u32) {
+    FAULT_RECORDED.store(true, Ordering::Relaxed);
+    record(
+        Kind::Fault,
+        format_args!("HardFault at PC {pc:#010x}, LR {lr:#010x}, xPSR {xpsr:#010x}"),
+    );
+}
+
+fn record(kind: Kind, message: core::fmt::Arguments<'_>) {
+    let slot = Slot::new(kind, message);
+    // SAFETY: this is only called by the panic and fault handlers, which do not return, so that
+    // the slot is not accessed concurrently.
+    unsafe { SLOT.0.get().write_volatile(MaybeUninit::new(slot)) };
+}
+
+/// A report as stored across resets.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Slot {
+    magic: u32,
+    checksum: u32,
+    kind: u32,
+    message_len: u32,
+    message: [u8; MAX_MESSAGE_LEN],
+}
+
+impl Slot {
+    fn empty() -> Self {
+        Self {
+            magic: 0,
+            checksum: 0,
+            kind: 0,
+            message_len: 0,
+            message: [0; MAX_MESSAGE_LEN],
+        }
+    }
+
+    fn new(kind: Kind, message: core::fmt::Arguments<'_>) -> Self {
+        let mut writer = MessageWriter {
+            message: [0; MAX_MESSAGE_LEN],
+            len: 0,
+        };
+        // Cannot fail, messages are truncated instead.
+        let _ = writer.write_fmt(message);
+
+        let mut slot = Self {
+            magic: MAGIC,
+            checksum: 0,
+            kind: kind.to_u32(),
+            // The message length is at most `MAX_MESSAGE_LEN`.
+            #[expect(clippy::cast_possible_truncation)]
+            message_len: writer.len as u32,
+            message: writer.message,
+        };
+        slot.checksum = slot.compute_checksum();
+        slot
+    }
+
+    fn report(&self) -> Option<Report> {
+        if self.magic != MAGIC || self.checksum != self.compute_checksum() {
+            return None;
+        }
+        let message_len = usize::try_from(self.message_len)
+            .ok()
+            .filter(|&len| len <= MAX_MESSAGE_LEN)?;
+        Some(Report {
+            kind: Kind::from_u32(self.kind)?,
+            message: self.message,
+            message_len,
+        })
+    }
+
+    /// Returns the FNV-1a hash of the contents of the slot.
+    fn compute_checksum(&self) -> u32 {
+        self.kind
+            .to_le_bytes()
+            .iter()
+            .chain(&self.message_len.to_le_bytes())
+            .chain(&self.message)
+            .fold(0x811c_9dc5, |hash, &byte| {
+                (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+            })
+    }
+}
+
+/// Writes a message, truncating it at a character boundary when it is too long.
+struct MessageWriter {
+    message: [u8; MAX_MESSAGE_LEN],
+    len: usize,
+}
+
+impl core::fmt::Write for MessageWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let available = MAX_MESSAGE_LEN - self.len;
+        let mut end = s.len().min(available);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        let bytes = s.as_bytes().get(..end).unwrap_or_default();
+        if let Some(dest) = self.message.get_mut(self.len..self.len + end) {
+            dest.copy_from_slice(bytes);
+            self.len += end;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let slot = Slot::new(
+            Kind::Panic,
+            format_args!("panicked at {}:{}", "main.rs", 12),
+        );
+        let report = slot.report().unwrap();
+        assert_eq!(report.kind(), Kind::Panic);
+        assert_eq!(report.message(), "panicked at main.rs:12");
+
+        let slot = Slot::new(Kind::Fault, format_args!("PC {:#010x}", 0x1234));
+        let report = slot.report().unwrap();
+        assert_eq!(report.kind(), Kind::Fault);
+        assert_eq!(report.message(), "PC 0x00001234");
+    }
+
+    #[test]
+    fn leftovers() {
+        assert!(Slot::empty().report().is_none());
+
+        let mut slot = Slot::new(Kind::Panic, format_args!("panicked"));
+        slot.message_len -= 1;
+        assert!(slot.report().is_none());
+
+        let mut slot = Slot::new(Kind::Panic, format_args!("panicked"));
+        slot.kind = 7;
+        slot.checksum = slot.compute_checksum();
+        assert!(slot.report().is_none());
+    }
+
+    /// Displays as `MAX_MESSAGE_LEN` two-byte characters.
+    struct Long;
+
+    impl core::fmt::Display for Long {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            (0..MAX_MESSAGE_LEN).try_for_each(|_| f.write_str("é"))
+        }
+    }
+
+    #[test]
+    fn truncation() {
+        let slot = Slot::new(Kind::Panic, format_args!("x{Long}"));
+        let report = slot.report().unwrap();
+        assert_eq!(report.message().len(), MAX_MESSAGE_LEN - 1);
+        assert!(report.message().starts_with("xéé"));
+    }
+}
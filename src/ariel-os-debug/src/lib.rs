@@ -81,17 +81,47 @@ mod backend {
 
     #[doc(hidden)]
     pub fn init() {
-        #[cfg(not(feature = "defmt"))]
+        #[cfg(all(not(feature = "defmt"), not(feature = "rtt-link")))]
         {
             use rtt_target::ChannelMode::NoBlockTrim;
 
             rtt_target::rtt_init_print!(NoBlockTrim);
         }
 
+        #[cfg(all(not(feature = "defmt"), feature = "rtt-link"))]
+        {
+            use rtt_target::ChannelMode::NoBlockTrim;
+            const PRINT_BUFFER_SIZE: usize = 1024;
+            const LINK_BUFFER_SIZE: usize = crate::link::BUFFER_SIZE;
+            let channels = rtt_target::rtt_init! {
+                up: {
+                    0: {
+                        size: PRINT_BUFFER_SIZE,
+                        mode: NoBlockTrim,
+                        name: "Terminal"
+                    }
+                    1: {
+                        size: LINK_BUFFER_SIZE,
+                        mode: NoBlockTrim,
+                        name: "ariel-os-link"
+                    }
+                }
+                down: {
+                    0: {
+                        size: LINK_BUFFER_SIZE,
+                        name: "ariel-os-link"
+                    }
+                }
+            };
+
+            rtt_target::set_print_channel(channels.up.0);
+            crate::link::init(channels.up.1, channels.down.0);
+        }
+
         #[cfg(feature = "log")]
         crate::logger::init();
 
-        #[cfg(feature = "defmt")]
+        #[cfg(all(feature = "defmt", not(feature = "rtt-link")))]
         {
             use rtt_target::ChannelMode::NoBlockSkip;
             const DEFMT_BUFFER_SIZE: usize = 1024;
@@ -108,6 +138,79 @@ mod backend {
 
             rtt_target::set_defmt_channel(channels.up.0);
         }
+
+        #[cfg(all(feature = "defmt", feature = "rtt-link"))]
+        {
+            use rtt_target::ChannelMode::{NoBlockSkip, NoBlockTrim};
+            const DEFMT_BUFFER_SIZE: usize = 1024;
+            const LINK_BUFFER_SIZE: usize = crate::link::BUFFER_SIZE;
+            let channels = rtt_target::rtt_init! {
+                up: {
+                    0: {
+                        size: DEFMT_BUFFER_SIZE,
+                        mode: NoBlockSkip,
+                        // probe-run autodetects whether defmt is in use based on this channel name
+                        name: "defmt"
+                    }
+                    1: {
+                        size: LINK_BUFFER_SIZE,
+                        mode: NoBlockTrim,
+                        name: "ariel-os-link"
+                    }
+                }
+                down: {
+                    0: {
+                        size: LINK_BUFFER_SIZE,
+                        name: "ariel-os-link"
+                    }
+                }
+            };
+
+            rtt_target::set_defmt_channel(channels.up.0);
+            crate::link::init(channels.up.1, channels.down.0);
+        }
+    }
+}
+
+#[cfg(feature = "log-sink")]
+pub mod sink;
+
+#[cfg(feature = "rtt-link")]
+pub mod link {
+    //! Provides a pair of RTT channels for host tools, next to the debug output.
+    //!
+    //! The up (device to host) and down (host to device) channels are both named
+    //! `ariel-os-link`, and are only set up when the debug output goes through RTT, ie. with the
+    //! `debug-console` feature.
+
+    use core::cell::RefCell;
+
+    use rtt_target::{DownChannel, UpChannel};
+
+    /// Size of the buffer of each channel.
+    #[cfg_attr(
+        not(feature = "debug-console"),
+        expect(dead_code, reason = "RTT is only set up with the debug console")
+    )]
+    pub(crate) const BUFFER_SIZE: usize = 256;
+
+    static CHANNELS: critical_section::Mutex<RefCell<Option<(UpChannel, DownChannel)>>> =
+        critical_section::Mutex::new(RefCell::new(None));
+
+    #[cfg_attr(
+        not(feature = "debug-console"),
+        expect(dead_code, reason = "RTT is only set up with the debug console")
+    )]
+    pub(crate) fn init(up: UpChannel, down: DownChannel) {
+        critical_section::with(|cs| *CHANNELS.borrow_ref_mut(cs) = Some((up, down)));
+    }
+
+    /// Takes the up and down `ariel-os-link` channels.
+    ///
+    /// Returns `None` if they have already been taken, or if they have not been set up.
+    #[must_use]
+    pub fn take() -> Option<(UpChannel, DownChannel)> {
+        critical_section::with(|cs| CHANNELS.take(cs))
     }
 }
 
@@ -178,7 +281,11 @@ pub mod backend {
 #[cfg(not(feature = "debug-console"))]
 mod backend {
     #[doc(hidden)]
-    pub fn init() {}
+    pub fn init() {
+        // Sinks receive log records even without a debug output.
+        #[cfg(feature = "log-sink")]
+        crate::logger::init();
+    }
 
     /// Prints to the debug output, with a newline.
     #[macro_export]
@@ -265,6 +372,9 @@ mod logger {
         fn log(&self, record: &Record) {
             if self.enabled(record.metadata()) {
                 crate::println!("[{}] {}", record.level(), record.args());
+
+                #[cfg(feature = "log-sink")]
+                crate::sink::dispatch(record);
             }
         }
 
@@ -0,0 +1,198 @@
+//! Forwards log records to sinks of the application, as CBOR items.
+//!
+//! Every record logged through the `log` facade is handed to the [`LogSink`]s registered with
+//! [`register()`], in addition to being printed on the debug output (if enabled). This allows
+//! applications to route records into channels of their own, eg. a black-box recorder in flash or
+//! a radio link:
+//!
+//! ```ignore
+//! use ariel_os::debug::sink::{self, LogSink};
+//!
+//! struct Recorder;
+//!
+//! impl LogSink for Recorder {
+//!     fn record(&self, record: &[u8]) {
+//!         // Copy `record` into a ring buffer, to be written to flash by a task.
+//!     }
+//! }
+//!
+//! static RECORDER: Recorder = Recorder;
+//!
+//! sink::register(&RECORDER).unwrap();
+//! ```
+//!
+//! Each record is a CBOR map:
+//!
+//! ```text
+//! { "level": "warn", "target": "ariel_os_coap::update", "time": 1234567,
+//!   "msg": "update: reading the status failed" }
+//! ```
+//!
+//! The `level` is one of `error`, `warn`, `info`, `debug` and `trace`, `target` is the target of
+//! the record (by default, the path of the module it was logged from), `time` is the time since
+//! startup in microseconds, and `msg` is the formatted message. The target and the message are
+//! truncated to [`MAX_TARGET_LEN`] and [`MAX_MESSAGE_LEN`] bytes respectively.
+//!
+//! Sinks are called from wherever the record was logged, which may be an interrupt handler, and
+//! only receive the records that pass the maximum level to log (set with the laze variable
+//! `LOG`). They should only copy the record somewhere, and must not log themselves. As records
+//! logged through `defmt` are only formatted on the host, they are not available to sinks.
+
+use core::{cell::Cell, fmt::Write as _};
+
+use log::{Level, Record};
+use minicbor::{
+    Encoder,
+    encode::write::{Cursor, EndOfSlice},
+};
+
+/// Maximum number of sinks that can be registered.
+pub const MAX_SINKS: usize = 4;
+
+/// Maximum length of the target of a record, in bytes.
+pub const MAX_TARGET_LEN: usize = 64;
+
+/// Maximum length of the message of a record, in bytes.
+pub const MAX_MESSAGE_LEN: usize = 192;
+
+/// Maximum length of an encoded record, leaving room for the keys and the other values.
+const MAX_RECORD_LEN: usize = MAX_TARGET_LEN + MAX_MESSAGE_LEN + 64;
+
+static SINKS: critical_section::Mutex<Cell<[Option<&'static dyn LogSink>; MAX_SINKS]>> =
+    critical_section::Mutex::new(Cell::new([None; MAX_SINKS]));
+
+/// A destination of log records, provided by the application.
+pub trait LogSink: Sync {
+    /// Receives a log record, encoded as a CBOR map.
+    ///
+    /// `record` is only valid for the duration of the call.
+    fn record(&self, record: &[u8]);
+}
+
+/// Registers `sink` to receive all log records from now on.
+///
+/// # Errors
+///
+/// Returns [`TooManySinks`] if [`MAX_SINKS`] sinks are already registered.
+pub fn register(sink: &'static dyn LogSink) -> Result<(), TooManySinks> {
+    critical_section::with(|cs| {
+        let sinks = SINKS.borrow(cs);
+        let mut registered = sinks.get();
+        let slot = registered
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(TooManySinks)?;
+        *slot = Some(sink);
+        sinks.set(registered);
+        Ok(())
+    })
+}
+
+/// Encodes `record` and hands it to the registered sinks.
+pub(crate) fn dispatch(record: &Record<'_>) {
+    let sinks = critical_section::with(|cs| SINKS.borrow(cs).get());
+    if sinks.iter().all(Option::is_none) {
+        return;
+    }
+
+    let mut buffer = [0; MAX_RECORD_LEN];
+    // Cannot fail, the target and the message are truncated to fit.
+    let Ok(len) = encode(record, &mut buffer) else {
+        return;
+    };
+    let encoded = buffer.get(..len).unwrap_or_default();
+
+    // The sinks are called outside of the critical section, so that they may take their own.
+    for sink in sinks.iter().flatten() {
+        sink.record(encoded);
+    }
+}
+
+/// Encodes `record` into `buffer`, and returns the encoded length.
+///
+/// # Errors
+///
+/// Returns an error if the encoded record does not fit into `buffer`.
+fn encode(
+    record: &Record<'_>,
+    buffer: &mut [u8],
+) -> Result<usize, minicbor::encode::Error<EndOfSlice>> {
+    let mut target = Truncated::<MAX_TARGET_LEN>::new();
+    // Cannot fail, overlong targets are truncated.
+    let _ = target.write_str(record.target());
+    let mut message = Truncated::<MAX_MESSAGE_LEN>::new();
+    // Cannot fail, overlong messages are truncated.
+    let _ = write!(message, "{}", record.args());
+
+    let mut encoder = Encoder::new(Cursor::new(buffer));
+    encoder
+        .map(4)?
+        .str("level")?
+        .str(level_name(record.level()))?
+        .str("target")?
+        .str(target.as_str())?
+        .str("time")?
+        .u64(embassy_time::Instant::now().as_micros())?
+        .str("msg")?
+        .str(message.as_str())?;
+    Ok(encoder.into_writer().position())
+}
+
+/// Returns the name under which `level` is encoded.
+fn level_name(level: Level) -> &'static str {
+    match level {
+        Level::Error => "error",
+        Level::Warn => "warn",
+        Level::Info => "info",
+        Level::Debug => "debug",
+        Level::Trace => "trace",
+    }
+}
+
+/// A string of at most `N` bytes, which silently drops what is written beyond.
+struct Truncated<const N: usize> {
+    buffer: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Truncated<N> {
+    fn new() -> Self {
+        Self {
+            buffer: [0; N],
+            len: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        // Only whole characters are written.
+        core::str::from_utf8(self.buffer.get(..self.len).unwrap_or_default()).unwrap_or_default()
+    }
+}
+
+impl<const N: usize> core::fmt::Write for Truncated<N> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let available = N - self.len;
+        let mut end = s.len().min(available);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        let bytes = s.as_bytes().get(..end).unwrap_or_default();
+        if let Some(dest) = self.buffer.get_mut(self.len..self.len + end) {
+            dest.copy_from_slice(bytes);
+            self.len += end;
+        }
+        Ok(())
+    }
+}
+
+/// Error returned by [`register()`] when [`MAX_SINKS`] sinks are already registered.
+#[derive(Debug)]
+pub struct TooManySinks;
+
+impl core::fmt::Display for TooManySinks {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "too many log sinks")
+    }
+}
+
+impl core::error::Error for TooManySinks {}
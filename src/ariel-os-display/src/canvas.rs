@@ -0,0 +1,271 @@
+//! Provides a framebuffer for color displays.
+
+use core::convert::Infallible;
+
+use embedded_graphics_core::{
+    Pixel,
+    draw_target::DrawTarget,
+    geometry::{Dimensions, OriginDimensions, Point, Size},
+    pixelcolor::{IntoStorage, Rgb565},
+    primitives::Rectangle,
+};
+
+/// Framebuffer of RGB565 pixels, which keeps track of the rows drawn on.
+///
+/// Pixels are stored row by row, in the big-endian byte order the display controllers expect, so
+/// that rows can be sent to the display as they are.
+/// Drawing outside of the canvas is ignored.
+pub struct Canvas<'a> {
+    buffer: &'a mut [u8],
+    width: u16,
+    height: u16,
+    /// First and last rows drawn on since the last call to [`Canvas::take_dirty_rows()`].
+    dirty_rows: Option<(u16, u16)>,
+}
+
+impl<'a> Canvas<'a> {
+    /// Bytes per pixel.
+    pub const BYTES_PER_PIXEL: usize = 2;
+
+    /// Creates a canvas of `width` by `height` pixels, using `buffer` as framebuffer.
+    ///
+    /// The whole canvas is considered drawn on, so that its initial content gets sent to the
+    /// display.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` is smaller than [`Canvas::buffer_len()`].
+    #[must_use]
+    pub fn new(buffer: &'a mut [u8], width: u16, height: u16) -> Self {
+        let len = Self::buffer_len(width, height);
+        assert!(buffer.len() >= len, "the framebuffer is too small");
+        let (buffer, _) = buffer.split_at_mut(len);
+
+        Self {
+            buffer,
+            width,
+            height,
+            dirty_rows: height.checked_sub(1).map(|last| (0, last)),
+        }
+    }
+
+    /// Returns the size in bytes of the framebuffer of a canvas of `width` by `height` pixels.
+    #[must_use]
+    pub const fn buffer_len(width: u16, height: u16) -> usize {
+        width as usize * height as usize * Self::BYTES_PER_PIXEL
+    }
+
+    /// Returns the content of `rows`, as sent to the display.
+    #[must_use]
+    pub fn rows(&self, first: u16, last: u16) -> &[u8] {
+        let row_len = usize::from(self.width) * Self::BYTES_PER_PIXEL;
+        let start = usize::from(first) * row_len;
+        let end = (usize::from(last) + 1) * row_len;
+        self.buffer.get(start..end).unwrap_or_default()
+    }
+
+    /// Returns the width and height of the canvas.
+    #[cfg(any(test, feature = "_mipi-dcs"))]
+    pub(crate) fn dimensions(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    /// Returns the first and last rows drawn on since the previous call, if any.
+    pub fn take_dirty_rows(&mut self) -> Option<(u16, u16)> {
+        self.dirty_rows.take()
+    }
+
+    /// Copies the content of `other`, which the display then shows once it is flushed.
+    ///
+    /// The canvas is thus considered not drawn on. `other` must have the same dimensions; otherwise,
+    /// only the part of the framebuffers both have is copied.
+    #[cfg(any(test, feature = "_mipi-dcs"))]
+    pub(crate) fn copy_from(&mut self, other: &Canvas<'_>) {
+        debug_assert_eq!(
+            self.dimensions(),
+            other.dimensions(),
+            "canvases of different dimensions"
+        );
+        for (byte, other) in self.buffer.iter_mut().zip(other.buffer.iter()) {
+            *byte = *other;
+        }
+        self.dirty_rows = None;
+    }
+
+    /// Swaps the framebuffers of the canvases, which must have the same dimensions.
+    #[cfg(any(test, feature = "_mipi-dcs"))]
+    pub(crate) fn swap(&mut self, other: &mut Canvas<'a>) {
+        debug_assert_eq!(
+            self.dimensions(),
+            other.dimensions(),
+            "canvases of different dimensions"
+        );
+        core::mem::swap(&mut self.buffer, &mut other.buffer);
+        core::mem::swap(&mut self.dirty_rows, &mut other.dirty_rows);
+    }
+
+    fn mark_dirty(&mut self, first: u16, last: u16) {
+        self.dirty_rows = Some(match self.dirty_rows {
+            Some((dirty_first, dirty_last)) => (dirty_first.min(first), dirty_last.max(last)),
+            None => (first, last),
+        });
+    }
+
+    fn set_pixel(&mut self, x: u16, y: u16, color: Rgb565) {
+        let index =
+            (usize::from(y) * usize::from(self.width) + usize::from(x)) * Self::BYTES_PER_PIXEL;
+        if let Some(pixel) = self.buffer.get_mut(index..index + Self::BYTES_PER_PIXEL) {
+            pixel.copy_from_slice(&color.into_storage().to_be_bytes());
+        }
+    }
+}
+
+impl OriginDimensions for Canvas<'_> {
+    fn size(&self) -> Size {
+        Size::new(self.width.into(), self.height.into())
+    }
+}
+
+impl DrawTarget for Canvas<'_> {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(Point { x, y }, color) in pixels {
+            let (Ok(x), Ok(y)) = (u16::try_from(x), u16::try_from(y)) else {
+                continue;
+            };
+            if x < self.width && y < self.height {
+                self.set_pixel(x, y, color);
+                self.mark_dirty(y, y);
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area.intersection(&self.bounding_box());
+        let Some(bottom_right) = area.bottom_right() else {
+            return Ok(());
+        };
+
+        // The intersection with the bounding box of the canvas fits into `u16`s.
+        #[expect(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let (left, top, right, bottom) = (
+            area.top_left.x as u16,
+            area.top_left.y as u16,
+            bottom_right.x as u16,
+            bottom_right.y as u16,
+        );
+        for y in top..=bottom {
+            for x in left..=right {
+                self.set_pixel(x, y, color);
+            }
+        }
+        self.mark_dirty(top, bottom);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use embedded_graphics_core::{
+        Drawable as _,
+        pixelcolor::{RgbColor as _, WebColors as _},
+    };
+
+    use super::*;
+
+    const WIDTH: u16 = 4;
+    const HEIGHT: u16 = 3;
+    const LEN: usize = Canvas::buffer_len(WIDTH, HEIGHT);
+
+    #[test]
+    fn rows() {
+        let mut buffer = [0; LEN + 2];
+        let mut canvas = Canvas::new(&mut buffer, WIDTH, HEIGHT);
+        // Drawing outside of the canvas is ignored.
+        canvas
+            .draw_iter([
+                Pixel(Point::new(1, 1), Rgb565::new(0x1f, 0, 0x01)),
+                Pixel(Point::new(-1, 1), Rgb565::WHITE),
+                Pixel(Point::new(4, 1), Rgb565::WHITE),
+                Pixel(Point::new(0, 3), Rgb565::WHITE),
+            ])
+            .unwrap();
+
+        let row = canvas.rows(1, 1);
+        assert_eq!(row, [0, 0, 0xf8, 0x01, 0, 0, 0, 0]);
+        assert_eq!(canvas.rows(0, 2).len(), LEN);
+        assert_eq!(canvas.rows(0, 2).get(10..12), Some(&[0xf8, 0x01][..]));
+        // Rows beyond the canvas are empty.
+        assert!(canvas.rows(2, 3).is_empty());
+    }
+
+    #[test]
+    fn dirty_rows() {
+        let mut buffer = [0; LEN];
+        let mut canvas = Canvas::new(&mut buffer, WIDTH, HEIGHT);
+        // The initial content is to be sent.
+        assert_eq!(canvas.take_dirty_rows(), Some((0, 2)));
+        assert_eq!(canvas.take_dirty_rows(), None);
+
+        Pixel(Point::new(0, 2), Rgb565::RED)
+            .draw(&mut canvas)
+            .unwrap();
+        Pixel(Point::new(3, 1), Rgb565::RED)
+            .draw(&mut canvas)
+            .unwrap();
+        assert_eq!(canvas.take_dirty_rows(), Some((1, 2)));
+
+        // Only the part of the area within the canvas is drawn on.
+        canvas
+            .fill_solid(
+                &Rectangle::new(Point::new(-2, -2), Size::new(3, 3)),
+                Rgb565::CSS_ORANGE,
+            )
+            .unwrap();
+        assert_eq!(canvas.take_dirty_rows(), Some((0, 0)));
+        canvas
+            .fill_solid(
+                &Rectangle::new(Point::new(5, 0), Size::new(2, 2)),
+                Rgb565::CSS_ORANGE,
+            )
+            .unwrap();
+        assert_eq!(canvas.take_dirty_rows(), None);
+
+        // Nothing is drawn on an empty canvas.
+        let mut canvas = Canvas::new(&mut [], 0, 0);
+        assert_eq!(canvas.take_dirty_rows(), None);
+        Pixel(Point::new(0, 0), Rgb565::RED)
+            .draw(&mut canvas)
+            .unwrap();
+        assert_eq!(canvas.take_dirty_rows(), None);
+    }
+
+    #[test]
+    fn copy_and_swap() {
+        let (mut front, mut back) = ([0; LEN], [0; LEN]);
+        let mut front = Canvas::new(&mut front, WIDTH, HEIGHT);
+        let mut back = Canvas::new(&mut back, WIDTH, HEIGHT);
+        let _ = back.take_dirty_rows();
+        front
+            .fill_solid(&front.bounding_box(), Rgb565::BLUE)
+            .unwrap();
+
+        front.swap(&mut back);
+        // The drawn content and its dirty rows moved to the back buffer.
+        assert_eq!(front.take_dirty_rows(), None);
+        assert_eq!(back.take_dirty_rows(), Some((0, 2)));
+        assert!(front.rows(0, 2).iter().all(|byte| *byte == 0));
+
+        front.copy_from(&back);
+        assert_eq!(front.rows(0, 2), back.rows(0, 2));
+        assert_eq!(front.rows(0, 0).get(..2), Some(&[0x00, 0x1f][..]));
+        assert_eq!(front.take_dirty_rows(), None);
+    }
+}
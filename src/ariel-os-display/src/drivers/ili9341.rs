@@ -0,0 +1,26 @@
+//! Driver for the Ilitek `ILI9341` display controller, connected through SPI.
+//!
+//! The default configuration is that of the common 240×320 panels, whose columns are mirrored and
+//! whose colors are in BGR order.
+
+use super::mipi_dcs::{self, Controller, MipiDcsDisplay};
+
+pub use super::mipi_dcs::Orientation;
+
+/// The `ILI9341` display controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Model;
+
+impl Controller for Model {
+    // Mirrored columns and BGR order.
+    const MEMORY_ACCESS_CONTROL: u8 = 0x48;
+    const DEFAULT_WIDTH: u16 = 240;
+    const DEFAULT_HEIGHT: u16 = 320;
+    const DEFAULT_INVERT_COLORS: bool = false;
+}
+
+/// Configuration of an `ILI9341` display.
+pub type Config = mipi_dcs::Config<Model>;
+
+/// An `ILI9341` display, connected through the SPI device `S`, with the D/C pin `D`.
+pub type Ili9341<'a, S, D> = MipiDcsDisplay<'a, S, D, Model>;
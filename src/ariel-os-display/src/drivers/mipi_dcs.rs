@@ -0,0 +1,389 @@
+//! Provides the implementation shared by the drivers of display controllers implementing the MIPI
+//! Display Command Set (DCS), connected through SPI with a data/command (D/C) pin.
+//!
+//! Pixels are sent as RGB565. The framebuffer is provided by the application, eg. from a
+//! `static` `ConstStaticCell`, and must be of [`Canvas::buffer_len()`] bytes.
+//!
+//! Providing a second framebuffer with [`MipiDcsDisplay::with_back_buffer()`] enables double
+//! buffering, which allows [`MipiDcsDisplay::flush_and_draw()`] to draw the next frame while the
+//! current one is sent to the display.
+//!
+//! # Configuration
+//!
+//! The framebuffer is sent in SPI transfers of at most `CONFIG_DISPLAY_SPI_CHUNK_SIZE` bytes
+//! (default: 65535), which must not exceed the maximum DMA transfer size of the MCU, eg. 255 on
+//! the nRF52832.
+
+use core::marker::PhantomData;
+
+use embassy_futures::join::join;
+use embassy_time::Timer;
+use embedded_graphics_core::{
+    Pixel,
+    draw_target::DrawTarget,
+    geometry::{OriginDimensions, Size},
+    pixelcolor::Rgb565,
+    primitives::Rectangle,
+};
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::spi::SpiDevice;
+
+use crate::{Error, canvas::Canvas};
+
+const CHUNK_SIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_DISPLAY_SPI_CHUNK_SIZE",
+    65535,
+    "maximum size of the SPI transfers sending framebuffers to displays"
+);
+
+const CMD_SOFT_RESET: u8 = 0x01;
+const CMD_SLEEP_OUT: u8 = 0x11;
+const CMD_NORMAL_MODE_ON: u8 = 0x13;
+const CMD_INVERSION_OFF: u8 = 0x20;
+const CMD_INVERSION_ON: u8 = 0x21;
+const CMD_DISPLAY_OFF: u8 = 0x28;
+const CMD_DISPLAY_ON: u8 = 0x29;
+const CMD_COLUMN_ADDRESS_SET: u8 = 0x2a;
+const CMD_ROW_ADDRESS_SET: u8 = 0x2b;
+const CMD_MEMORY_WRITE: u8 = 0x2c;
+const CMD_MEMORY_ACCESS_CONTROL: u8 = 0x36;
+const CMD_PIXEL_FORMAT_SET: u8 = 0x3a;
+
+/// 16 bits per pixel, for both the RGB and the MCU interfaces.
+const PIXEL_FORMAT_RGB565: u8 = 0x55;
+
+/// Bits of the memory access control register.
+const MADCTL_MY: u8 = 0x80;
+const MADCTL_MX: u8 = 0x40;
+const MADCTL_MV: u8 = 0x20;
+
+/// Duration after a reset or leaving sleep mode before the controller accepts commands, in
+/// milliseconds.
+const WAKE_UP_MS: u64 = 120;
+
+/// A display controller implementing the MIPI Display Command Set.
+pub trait Controller {
+    /// Bits of the memory access control register to set in every orientation, ie. the color
+    /// order and the mirroring of the panel.
+    const MEMORY_ACCESS_CONTROL: u8;
+    /// Width of the panel usually driven by the controller, in its default orientation.
+    const DEFAULT_WIDTH: u16;
+    /// Height of the panel usually driven by the controller, in its default orientation.
+    const DEFAULT_HEIGHT: u16;
+    /// Whether the panel usually driven by the controller requires the colors to be inverted.
+    const DEFAULT_INVERT_COLORS: bool;
+}
+
+/// Orientation of a display, as the clockwise rotation from its default orientation.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Orientation {
+    /// Default orientation.
+    #[default]
+    Deg0,
+    /// Rotated by 90 degrees.
+    Deg90,
+    /// Rotated by 180 degrees.
+    Deg180,
+    /// Rotated by 270 degrees.
+    Deg270,
+}
+
+impl Orientation {
+    fn memory_access_control(self) -> u8 {
+        match self {
+            Self::Deg0 => 0,
+            Self::Deg90 => MADCTL_MV | MADCTL_MX,
+            Self::Deg180 => MADCTL_MX | MADCTL_MY,
+            Self::Deg270 => MADCTL_MV | MADCTL_MY,
+        }
+    }
+
+    fn is_landscape(self) -> bool {
+        matches!(self, Self::Deg90 | Self::Deg270)
+    }
+}
+
+/// Configuration of a display driven by controller `C`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Config<C> {
+    /// Width of the panel in its default orientation, in pixels.
+    pub width: u16,
+    /// Height of the panel in its default orientation, in pixels.
+    pub height: u16,
+    /// Column of the memory of the controller at which the panel starts, in `orientation`.
+    pub x_offset: u16,
+    /// Row of the memory of the controller at which the panel starts, in `orientation`.
+    pub y_offset: u16,
+    /// Orientation of the display.
+    pub orientation: Orientation,
+    /// Whether to invert the colors, which some panels require.
+    pub invert_colors: bool,
+    _controller: PhantomData<C>,
+}
+
+impl<C: Controller> Default for Config<C> {
+    fn default() -> Self {
+        Self {
+            width: C::DEFAULT_WIDTH,
+            height: C::DEFAULT_HEIGHT,
+            x_offset: 0,
+            y_offset: 0,
+            orientation: Orientation::default(),
+            invert_colors: C::DEFAULT_INVERT_COLORS,
+            _controller: PhantomData,
+        }
+    }
+}
+
+/// A display driven by controller `C`, connected through `spi`, with the D/C pin `dc`.
+///
+/// Drawing on the display draws into its framebuffer, which is sent to the display by
+/// [`MipiDcsDisplay::flush()`].
+pub struct MipiDcsDisplay<'a, S, D, C> {
+    bus: Bus<S, D>,
+    canvas: Canvas<'a>,
+    back: Option<Canvas<'a>>,
+    _controller: PhantomData<C>,
+}
+
+impl<'a, S: SpiDevice, D: OutputPin, C: Controller> MipiDcsDisplay<'a, S, D, C> {
+    /// Initializes the display, using `buffer` as framebuffer.
+    ///
+    /// The display is switched on once the framebuffer is first flushed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DisplayAccess`] if the controller cannot be initialized.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` is smaller than [`Canvas::buffer_len()`].
+    pub async fn init(
+        spi: S,
+        dc: D,
+        buffer: &'a mut [u8],
+        config: Config<C>,
+    ) -> Result<Self, Error> {
+        let (width, height) = if config.orientation.is_landscape() {
+            (config.height, config.width)
+        } else {
+            (config.width, config.height)
+        };
+
+        let mut bus = Bus {
+            spi,
+            dc,
+            x_offset: config.x_offset,
+            y_offset: config.y_offset,
+            width,
+            switched: false,
+        };
+
+        bus.command(CMD_SOFT_RESET, &[]).await?;
+        Timer::after_millis(WAKE_UP_MS).await;
+        bus.command(CMD_SLEEP_OUT, &[]).await?;
+        Timer::after_millis(WAKE_UP_MS).await;
+        bus.command(CMD_PIXEL_FORMAT_SET, &[PIXEL_FORMAT_RGB565])
+            .await?;
+        bus.command(
+            CMD_MEMORY_ACCESS_CONTROL,
+            &[C::MEMORY_ACCESS_CONTROL ^ config.orientation.memory_access_control()],
+        )
+        .await?;
+        let inversion = if config.invert_colors {
+            CMD_INVERSION_ON
+        } else {
+            CMD_INVERSION_OFF
+        };
+        bus.command(inversion, &[]).await?;
+        bus.command(CMD_NORMAL_MODE_ON, &[]).await?;
+
+        Ok(Self {
+            bus,
+            canvas: Canvas::new(buffer, width, height),
+            back: None,
+            _controller: PhantomData,
+        })
+    }
+
+    /// Enables double buffering, using `buffer` as second framebuffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` is smaller than [`Canvas::buffer_len()`].
+    #[must_use]
+    pub fn with_back_buffer(mut self, buffer: &'a mut [u8]) -> Self {
+        let (width, height) = self.canvas.dimensions();
+        self.back = Some(Canvas::new(buffer, width, height));
+        self
+    }
+
+    /// Returns the framebuffer, to draw on.
+    pub fn canvas(&mut self) -> &mut Canvas<'a> {
+        &mut self.canvas
+    }
+
+    /// Sends the rows of the framebuffer drawn on since the previous flush to the display.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DisplayAccess`] if communicating with the controller fails.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        let rows = self.canvas.take_dirty_rows();
+        self.bus.send(&self.canvas, rows).await
+    }
+
+    /// Sends the rows of the framebuffer drawn on since the previous flush to the display, and
+    /// calls `draw` to draw the next frame.
+    ///
+    /// With double buffering, `draw` draws while the current frame is sent, on a copy of it.
+    /// Otherwise, it draws once the frame is sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DisplayAccess`] if communicating with the controller fails.
+    pub async fn flush_and_draw(
+        &mut self,
+        draw: impl FnOnce(&mut Canvas<'a>),
+    ) -> Result<(), Error> {
+        let Some(back) = &mut self.back else {
+            self.flush().await?;
+            draw(&mut self.canvas);
+            return Ok(());
+        };
+
+        // The back buffer becomes the one sent, while the other one gets drawn on.
+        self.canvas.swap(back);
+        let rows = back.take_dirty_rows();
+        let (canvas, back) = (&mut self.canvas, &*back);
+        let (res, ()) = join(self.bus.send(back, rows), async {
+            canvas.copy_from(back);
+            draw(canvas);
+        })
+        .await;
+        res
+    }
+
+    /// Switches the display on or off, keeping the content of its memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DisplayAccess`] if communicating with the controller fails.
+    pub async fn set_on(&mut self, on: bool) -> Result<(), Error> {
+        self.bus.set_on(on).await
+    }
+
+    /// Returns the SPI device and the D/C pin of the display.
+    pub fn release(self) -> (S, D) {
+        (self.bus.spi, self.bus.dc)
+    }
+}
+
+impl<S, D, C> OriginDimensions for MipiDcsDisplay<'_, S, D, C> {
+    fn size(&self) -> Size {
+        self.canvas.size()
+    }
+}
+
+impl<S, D, C> DrawTarget for MipiDcsDisplay<'_, S, D, C> {
+    type Color = Rgb565;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.canvas.draw_iter(pixels)
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.canvas.fill_solid(area, color)
+    }
+}
+
+/// The SPI device and D/C pin of a display.
+struct Bus<S, D> {
+    spi: S,
+    dc: D,
+    x_offset: u16,
+    y_offset: u16,
+    width: u16,
+    /// Whether the display was switched on or off since its initialization.
+    switched: bool,
+}
+
+impl<S: SpiDevice, D: OutputPin> Bus<S, D> {
+    /// Sends `command`, followed by its `params`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DisplayAccess`] if communicating with the controller fails.
+    async fn command(&mut self, command: u8, params: &[u8]) -> Result<(), Error> {
+        self.dc.set_low().map_err(|_| Error::DisplayAccess)?;
+        self.spi
+            .write(&[command])
+            .await
+            .map_err(|_| Error::DisplayAccess)?;
+        if !params.is_empty() {
+            self.dc.set_high().map_err(|_| Error::DisplayAccess)?;
+            self.spi
+                .write(params)
+                .await
+                .map_err(|_| Error::DisplayAccess)?;
+        }
+        Ok(())
+    }
+
+    /// Switches the display on or off.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DisplayAccess`] if communicating with the controller fails.
+    async fn set_on(&mut self, on: bool) -> Result<(), Error> {
+        let command = if on { CMD_DISPLAY_ON } else { CMD_DISPLAY_OFF };
+        self.command(command, &[]).await?;
+        self.switched = true;
+        Ok(())
+    }
+
+    /// Sends `rows` of `canvas`, and switches the display on if it was never switched on or off.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DisplayAccess`] if communicating with the controller fails.
+    async fn send(&mut self, canvas: &Canvas<'_>, rows: Option<(u16, u16)>) -> Result<(), Error> {
+        if let Some((first, last)) = rows {
+            let [x_start_hi, x_start_lo] = self.x_offset.to_be_bytes();
+            let [x_end_hi, x_end_lo] = (self.x_offset + self.width - 1).to_be_bytes();
+            let [y_start_hi, y_start_lo] = (self.y_offset + first).to_be_bytes();
+            let [y_end_hi, y_end_lo] = (self.y_offset + last).to_be_bytes();
+
+            self.command(
+                CMD_COLUMN_ADDRESS_SET,
+                &[x_start_hi, x_start_lo, x_end_hi, x_end_lo],
+            )
+            .await?;
+            self.command(
+                CMD_ROW_ADDRESS_SET,
+                &[y_start_hi, y_start_lo, y_end_hi, y_end_lo],
+            )
+            .await?;
+            self.command(CMD_MEMORY_WRITE, &[]).await?;
+
+            self.dc.set_high().map_err(|_| Error::DisplayAccess)?;
+            for chunk in canvas.rows(first, last).chunks(CHUNK_SIZE) {
+                self.spi
+                    .write(chunk)
+                    .await
+                    .map_err(|_| Error::DisplayAccess)?;
+            }
+        }
+
+        if !self.switched {
+            self.set_on(true).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,13 @@
+//! Provides drivers for common display controllers.
+//!
+//! Each driver provides a display type implementing the `DrawTarget` trait of
+//! `embedded-graphics`, which is initialized with the bus the display is connected to.
+
+#[cfg(feature = "ili9341")]
+pub mod ili9341;
+#[cfg(feature = "_mipi-dcs")]
+pub mod mipi_dcs;
+#[cfg(feature = "ssd1306")]
+pub mod ssd1306;
+#[cfg(feature = "st7789")]
+pub mod st7789;
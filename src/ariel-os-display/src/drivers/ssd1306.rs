@@ -0,0 +1,317 @@
+//! Driver for the Solomon Systech `SSD1306` monochrome OLED display controller, connected through
+//! I2C.
+//!
+//! The framebuffer is kept in the driver, as it only takes 1 KiB.
+
+use core::convert::Infallible;
+
+use embedded_graphics_core::{
+    Pixel,
+    draw_target::DrawTarget,
+    geometry::{OriginDimensions, Point, Size},
+    pixelcolor::BinaryColor,
+};
+use embedded_hal_async::i2c::{I2c, Operation};
+
+use crate::Error;
+
+/// Control byte preceding commands.
+const CONTROL_COMMANDS: u8 = 0x00;
+/// Control byte preceding data written to the memory of the controller.
+const CONTROL_DATA: u8 = 0x40;
+
+const CMD_SET_CONTRAST: u8 = 0x81;
+const CMD_DISPLAY_OFF: u8 = 0xae;
+const CMD_DISPLAY_ON: u8 = 0xaf;
+const CMD_SET_COLUMN_ADDRESS: u8 = 0x21;
+const CMD_SET_PAGE_ADDRESS: u8 = 0x22;
+
+const WIDTH: u16 = 128;
+const LAST_COLUMN: u8 = 127;
+const MAX_PAGES: usize = 8;
+/// Rows of pixels per page of the memory of the controller.
+const PAGE_HEIGHT: u16 = 8;
+
+/// Size of the panel, in pixels.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PanelSize {
+    /// 128×64 pixels.
+    #[default]
+    W128H64,
+    /// 128×32 pixels.
+    W128H32,
+}
+
+impl PanelSize {
+    fn height(self) -> u16 {
+        match self {
+            Self::W128H64 => 64,
+            Self::W128H32 => 32,
+        }
+    }
+
+    /// Returns the pages of the panel, one per bit.
+    fn pages(self) -> u8 {
+        match self {
+            Self::W128H64 => 0xff,
+            Self::W128H32 => 0x0f,
+        }
+    }
+
+    /// Returns the configuration of the COM pins, which depends on how the panel is wired.
+    fn com_pins(self) -> u8 {
+        match self {
+            Self::W128H64 => 0x12,
+            Self::W128H32 => 0x02,
+        }
+    }
+}
+
+/// Configuration of an `SSD1306` display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// I2C address of the device, which depends on the level of its `SA0` pin.
+    pub address: u8,
+    /// Size of the panel.
+    pub size: PanelSize,
+    /// Whether to rotate the display by 180 degrees.
+    pub rotate_180: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address: 0x3c,
+            size: PanelSize::default(),
+            rotate_180: false,
+        }
+    }
+}
+
+/// An `SSD1306` display, connected through `I`.
+///
+/// Drawing on the display draws into its framebuffer, which is sent to the display by
+/// [`Ssd1306::flush()`].
+pub struct Ssd1306<I> {
+    i2c: I,
+    address: u8,
+    size: PanelSize,
+    /// Pages of `WIDTH` bytes, each holding 8 rows of pixels, one per bit.
+    buffer: [u8; WIDTH as usize * MAX_PAGES],
+    /// Pages drawn on since the previous flush, one per bit.
+    dirty_pages: u8,
+    /// Whether the display was switched on or off since its initialization.
+    switched: bool,
+}
+
+impl<I: I2c> Ssd1306<I> {
+    /// Initializes the display connected through `i2c`.
+    ///
+    /// The display is switched on once the framebuffer is first flushed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DisplayAccess`] if the controller cannot be initialized.
+    pub async fn init(i2c: I, config: Config) -> Result<Self, Error> {
+        let mut display = Self {
+            i2c,
+            address: config.address,
+            size: config.size,
+            buffer: [0; WIDTH as usize * MAX_PAGES],
+            dirty_pages: config.size.pages(),
+            switched: false,
+        };
+
+        // The height is at most 64.
+        #[expect(clippy::cast_possible_truncation)]
+        let multiplex_ratio = config.size.height() as u8 - 1;
+        let (segment_remap, com_scan_direction) = if config.rotate_180 {
+            (0xa0, 0xc0)
+        } else {
+            (0xa1, 0xc8)
+        };
+
+        display
+            .commands(&[
+                CMD_DISPLAY_OFF,
+                // Clock divide ratio and oscillator frequency.
+                0xd5,
+                0x80,
+                0xa8,
+                multiplex_ratio,
+                // No display offset, and start line 0.
+                0xd3,
+                0x00,
+                0x40,
+                // Enable the charge pump.
+                0x8d,
+                0x14,
+                // Horizontal addressing mode.
+                0x20,
+                0x00,
+                segment_remap,
+                com_scan_direction,
+                0xda,
+                config.size.com_pins(),
+                CMD_SET_CONTRAST,
+                0xcf,
+                // Pre-charge period and VCOMH deselect level.
+                0xd9,
+                0xf1,
+                0xdb,
+                0x40,
+                // Display the content of the memory, not inverted.
+                0xa4,
+                0xa6,
+                // Deactivate scrolling.
+                0x2e,
+            ])
+            .await?;
+
+        Ok(display)
+    }
+
+    /// Sends the pages of the framebuffer drawn on since the previous flush to the display.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DisplayAccess`] if communicating with the controller fails.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        let dirty_pages = core::mem::take(&mut self.dirty_pages);
+        if dirty_pages != 0 {
+            // Both are less than `MAX_PAGES`.
+            #[expect(clippy::cast_possible_truncation)]
+            let (first, last) = (
+                dirty_pages.trailing_zeros() as u8,
+                7 - dirty_pages.leading_zeros() as u8,
+            );
+            self.commands(&[
+                CMD_SET_COLUMN_ADDRESS,
+                0,
+                LAST_COLUMN,
+                CMD_SET_PAGE_ADDRESS,
+                first,
+                last,
+            ])
+            .await?;
+
+            // Sending one page per transfer keeps transfers short enough for all I2C
+            // controllers.
+            let pages = self
+                .buffer
+                .get(usize::from(first) * usize::from(WIDTH)..)
+                .unwrap_or_default()
+                .chunks(usize::from(WIDTH))
+                .take(usize::from(last - first) + 1);
+            for page in pages {
+                self.i2c
+                    .transaction(
+                        self.address,
+                        &mut [Operation::Write(&[CONTROL_DATA]), Operation::Write(page)],
+                    )
+                    .await
+                    .map_err(|_| Error::DisplayAccess)?;
+            }
+        }
+
+        if !self.switched {
+            self.set_on(true).await?;
+        }
+        Ok(())
+    }
+
+    /// Switches the display on or off, keeping the content of its memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DisplayAccess`] if communicating with the controller fails.
+    pub async fn set_on(&mut self, on: bool) -> Result<(), Error> {
+        let command = if on { CMD_DISPLAY_ON } else { CMD_DISPLAY_OFF };
+        self.commands(&[command]).await?;
+        self.switched = true;
+        Ok(())
+    }
+
+    /// Sets the contrast of the display, from 0 to 255.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DisplayAccess`] if communicating with the controller fails.
+    pub async fn set_contrast(&mut self, contrast: u8) -> Result<(), Error> {
+        self.commands(&[CMD_SET_CONTRAST, contrast]).await
+    }
+
+    /// Returns the I2C device of the display.
+    pub fn release(self) -> I {
+        self.i2c
+    }
+
+    /// Sends `commands`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DisplayAccess`] if communicating with the controller fails.
+    async fn commands(&mut self, commands: &[u8]) -> Result<(), Error> {
+        // Adjacent write operations are sent as a single write.
+        self.i2c
+            .transaction(
+                self.address,
+                &mut [
+                    Operation::Write(&[CONTROL_COMMANDS]),
+                    Operation::Write(commands),
+                ],
+            )
+            .await
+            .map_err(|_| Error::DisplayAccess)
+    }
+}
+
+impl<I> OriginDimensions for Ssd1306<I> {
+    fn size(&self) -> Size {
+        Size::new(WIDTH.into(), self.size.height().into())
+    }
+}
+
+impl<I> DrawTarget for Ssd1306<I> {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<P>(&mut self, pixels: P) -> Result<(), Self::Error>
+    where
+        P: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let height = self.size.height();
+        for Pixel(Point { x, y }, color) in pixels {
+            let (Ok(x), Ok(y)) = (u16::try_from(x), u16::try_from(y)) else {
+                continue;
+            };
+            if x >= WIDTH || y >= height {
+                continue;
+            }
+
+            let page = y / PAGE_HEIGHT;
+            let bit = 1u8 << (y % PAGE_HEIGHT);
+            if let Some(byte) = self
+                .buffer
+                .get_mut(usize::from(page) * usize::from(WIDTH) + usize::from(x))
+            {
+                if color.is_on() {
+                    *byte |= bit;
+                } else {
+                    *byte &= !bit;
+                }
+                self.dirty_pages |= 1 << page;
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.buffer.fill(if color.is_on() { 0xff } else { 0x00 });
+        self.dirty_pages = self.size.pages();
+        Ok(())
+    }
+}
@@ -0,0 +1,26 @@
+//! Driver for the Sitronix `ST7789` display controller, connected through SPI.
+//!
+//! The default configuration is that of the common 240×240 IPS panels, which require the colors
+//! to be inverted. Panels smaller than the memory of the controller (240×320) need offsets, eg.
+//! 52 columns and 40 rows for the 135×240 panels in their default orientation.
+
+use super::mipi_dcs::{self, Controller, MipiDcsDisplay};
+
+pub use super::mipi_dcs::Orientation;
+
+/// The `ST7789` display controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Model;
+
+impl Controller for Model {
+    const MEMORY_ACCESS_CONTROL: u8 = 0x00;
+    const DEFAULT_WIDTH: u16 = 240;
+    const DEFAULT_HEIGHT: u16 = 240;
+    const DEFAULT_INVERT_COLORS: bool = true;
+}
+
+/// Configuration of an `ST7789` display.
+pub type Config = mipi_dcs::Config<Model>;
+
+/// An `ST7789` display, connected through the SPI device `S`, with the D/C pin `D`.
+pub type St7789<'a, S, D> = MipiDcsDisplay<'a, S, D, Model>;
@@ -0,0 +1,54 @@
+//! Provides drivers for displays, which can be drawn on with [`embedded-graphics`].
+//!
+//! Each driver provides a display type implementing the [`DrawTarget`] trait of
+//! `embedded-graphics`, which draws into a framebuffer in RAM. Drawing is thus synchronous and
+//! does not access the display, which is only updated by the `flush()` method of the display:
+//! only the parts of the framebuffer that were drawn on since the previous flush are sent.
+//!
+//! ```ignore
+//! use embedded_graphics::{prelude::*, primitives::{Circle, PrimitiveStyle}};
+//!
+//! let mut display = Ssd1306::init(i2c_device, ssd1306::Config::default()).await?;
+//! Circle::new(Point::new(48, 16), 32)
+//!     .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+//!     .draw(&mut display)?;
+//! display.flush().await?;
+//! ```
+//!
+//! The drivers are given the bus the display is connected to, eg. an
+//! `ariel_os::i2c::controller::I2cDevice` or an `ariel_os::spi::main::SpiDevice`, whose transfers
+//! use DMA on the MCUs that support it. Each driver is enabled through the feature, and the laze
+//! module `display-<driver>`, of the same name as its module.
+//!
+//! [`embedded-graphics`]: https://docs.rs/embedded-graphics
+//! [`DrawTarget`]: embedded_graphics_core::draw_target::DrawTarget
+
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+pub mod canvas;
+#[cfg(feature = "_drivers")]
+pub mod drivers;
+
+#[doc(no_inline)]
+pub use embedded_graphics_core;
+
+/// Errors of displays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// Communicating with the display controller failed.
+    DisplayAccess,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::DisplayAccess => write!(f, "display access failed"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
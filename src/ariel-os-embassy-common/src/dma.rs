@@ -0,0 +1,442 @@
+//! Provides statically allocated buffers suitable for DMA transfers.
+//!
+//! DMA engines cannot access every memory region, and some require buffers to be aligned:
+//!
+//! - On nRF, EasyDMA can only access RAM, so buffers in flash (eg. `const`s or non-mutable
+//!   `static`s) are rejected or need to be copied first.
+//! - On ESP32, DMA buffers need to be word-aligned and located in internal RAM.
+//! - On Cortex-M7 STM32s, buffers are aligned on cache lines, so that cache maintenance
+//!   operations on a buffer never affect neighboring data.
+//!   The RAM region used on these MCUs (eg. the AXI SRAM on the STM32H7) is accessible to the
+//!   general-purpose DMA controllers.
+//!
+//! [`dma_buffer!`](crate::dma_buffer) declares a [`DmaBuffer`] satisfying these requirements for
+//! the target MCU, and returns a `'static` mutable reference to it:
+//!
+//! ```ignore
+//! let buffer: &'static mut DmaBuffer<1024> = dma_buffer!(1024);
+//! ```
+//!
+//! # Data cache
+//!
+//! On Cortex-M7, the data cache is not coherent with DMA transfers once enabled: the DMA engine
+//! does not see data still held in the cache, and the CPU may keep reading stale cache lines
+//! after the DMA engine wrote into RAM.
+//! Drivers performing DMA transfers therefore need to:
+//!
+//! - [`clean()`] the buffer the DMA engine reads from, before the transfer,
+//! - [`invalidate()`] the buffer the DMA engine writes into, both before and after the transfer.
+//!
+//! Invalidating discards whole cache lines, so buffers written into need to be aligned on cache
+//! lines, which [`DmaBuffer`]s are.
+//! Both operations do nothing when the data cache is disabled, and on other MCUs.
+//!
+//! # Protection of inactive buffers
+//!
+//! Drivers that stop using a buffer, eg. when they are dropped, can [`DmaBuffer::deactivate()`]
+//! it until it is used again.
+//! In debug builds on ARMv7-M and ARMv8-M MCUs, inactive buffers are protected with a region of
+//! the MPU, so that the CPU faults as soon as a driver accesses a buffer it no longer owns (eg.
+//! through a pointer kept for a DMA transfer), instead of silently corrupting memory:
+//!
+//! - On ARMv7-M, regions are aligned on their size, which is a power of two, so the largest such
+//!   block within the buffer is made inaccessible.
+//! - On ARMv8-M, which has no permission denying access to privileged code, the buffer is made
+//!   read-only, rounded inwards to 32 bytes, so that writes fault.
+//!
+//! The upper half of the MPU regions is used for this, so that these regions take precedence
+//! over any other; buffers deactivated while all of them are in use are not protected.
+//! DMA transfers are not affected by the MPU.
+
+use core::ops::{Deref, DerefMut};
+
+/// Alignment of [`DmaBuffer`]s on the target MCU, in bytes.
+#[cfg(context = "cortex-m7f")]
+pub const ALIGNMENT: usize = 32;
+/// Alignment of [`DmaBuffer`]s on the target MCU, in bytes.
+#[cfg(not(context = "cortex-m7f"))]
+pub const ALIGNMENT: usize = 4;
+
+/// A buffer of `N` bytes, aligned for DMA transfers on the target MCU.
+///
+/// Buffers should be declared with [`dma_buffer!`](crate::dma_buffer), which places them in RAM
+/// accessible to the DMA engine.
+/// On Cortex-M7, the size of the buffer is rounded up to a multiple of [`ALIGNMENT`].
+// NOTE: `repr(align)` does not accept a constant, so this needs to be kept in sync with
+// `ALIGNMENT`.
+#[cfg_attr(context = "cortex-m7f", repr(C, align(32)))]
+#[cfg_attr(not(context = "cortex-m7f"), repr(C, align(4)))]
+pub struct DmaBuffer<const N: usize>([u8; N]);
+
+impl<const N: usize> DmaBuffer<N> {
+    /// Creates a zeroed buffer.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self([0; N])
+    }
+
+    /// Returns the bytes of the buffer as an array, eg. to pass them to a HAL driver expecting an
+    /// array.
+    ///
+    /// When called on a `&'static mut DmaBuffer`, the returned reference is `'static` as well.
+    #[must_use]
+    pub fn as_mut_array(&mut self) -> &mut [u8; N] {
+        &mut self.0
+    }
+
+    /// Writes the data of the buffer held in the data cache back to RAM, before a DMA transfer
+    /// reading from it.
+    ///
+    /// See [`clean()`].
+    pub fn clean(&self) {
+        clean(&self.0);
+    }
+
+    /// Discards the data of the buffer held in the data cache, both before and after a DMA
+    /// transfer writing into it.
+    ///
+    /// See [`invalidate()`].
+    pub fn invalidate(&mut self) {
+        // `DmaBuffer`s are aligned on, and sized in, cache lines.
+        let _ = invalidate(&mut self.0);
+    }
+
+    /// Marks the buffer as no longer used, until it is [activated](InactiveDmaBuffer::activate)
+    /// again.
+    ///
+    /// In debug builds, the buffer is protected from accesses by the CPU; see
+    /// [Protection of inactive buffers](self#protection-of-inactive-buffers).
+    #[must_use]
+    pub fn deactivate(&'static mut self) -> InactiveDmaBuffer<N> {
+        #[cfg(all(debug_assertions, any(armv7m, armv8m)))]
+        let region = protection::protect(&self.0);
+        InactiveDmaBuffer {
+            buffer: self,
+            #[cfg(all(debug_assertions, any(armv7m, armv8m)))]
+            region,
+        }
+    }
+}
+
+impl<const N: usize> Default for DmaBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Deref for DmaBuffer<N> {
+    type Target = [u8; N];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<const N: usize> DerefMut for DmaBuffer<N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// A [`DmaBuffer`] that is not used, and protected from accesses by the CPU in debug builds.
+///
+/// Dropping it leaves the buffer protected for good.
+pub struct InactiveDmaBuffer<const N: usize> {
+    buffer: &'static mut DmaBuffer<N>,
+    /// MPU region protecting the buffer, if any.
+    #[cfg(all(debug_assertions, any(armv7m, armv8m)))]
+    region: Option<u32>,
+}
+
+impl<const N: usize> InactiveDmaBuffer<N> {
+    /// Lifts the protection of the buffer, and returns it to be used again.
+    #[must_use]
+    pub fn activate(self) -> &'static mut DmaBuffer<N> {
+        #[cfg(all(debug_assertions, any(armv7m, armv8m)))]
+        if let Some(region) = self.region {
+            protection::unprotect(region);
+        }
+        self.buffer
+    }
+}
+
+/// Error returned when a buffer is not aligned on cache lines, so that invalidating it would
+/// discard neighboring data as well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MisalignedBufferError;
+
+impl core::fmt::Display for MisalignedBufferError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("buffer not aligned on cache lines")
+    }
+}
+
+impl core::error::Error for MisalignedBufferError {}
+
+/// Writes the data of `buffer` held in the data cache back to RAM, so that a DMA transfer reading
+/// from `buffer` sees what the CPU wrote into it.
+///
+/// Any buffer can be cleaned: cleaning the cache lines it shares with neighboring data does not
+/// affect them.
+pub fn clean(buffer: &[u8]) {
+    #[cfg(context = "cortex-m7f")]
+    if let Some(lines) = cache_lines(buffer) {
+        // SAFETY: cleaning does not change the contents of the memory.
+        unsafe { maintain(lines, Operation::Clean) };
+    }
+    #[cfg(not(context = "cortex-m7f"))]
+    let _ = buffer;
+}
+
+/// Discards the data of `buffer` held in the data cache.
+///
+/// This needs to be done before a DMA transfer writing into `buffer`, so that dirty cache lines
+/// are not written back over the transferred data, and after it, so that the CPU reads the
+/// transferred data instead of stale cache lines.
+///
+/// # Errors
+///
+/// Returns [`MisalignedBufferError`] if the data cache is enabled and `buffer` does not start and
+/// end on cache line boundaries, in which case nothing is invalidated.
+pub fn invalidate(buffer: &mut [u8]) -> Result<(), MisalignedBufferError> {
+    #[cfg(context = "cortex-m7f")]
+    if let Some(lines) = cache_lines(buffer) {
+        if lines.start != buffer.as_ptr().addr() || lines.len() != buffer.len() {
+            return Err(MisalignedBufferError);
+        }
+        // SAFETY: the buffer spans whole cache lines, so no other data is discarded, and it is
+        // borrowed mutably, so no reference observes its contents changing.
+        unsafe { maintain(lines, Operation::Invalidate) };
+    }
+    #[cfg(not(context = "cortex-m7f"))]
+    let _ = buffer;
+    Ok(())
+}
+
+/// Returns the addresses of the cache lines spanned by `buffer`, if the data cache is enabled.
+#[cfg(context = "cortex-m7f")]
+fn cache_lines(buffer: &[u8]) -> Option<core::ops::Range<usize>> {
+    if !cortex_m::peripheral::SCB::dcache_enabled() {
+        return None;
+    }
+    let start = buffer.as_ptr().addr();
+    let end = start + buffer.len();
+    Some(start & !(ALIGNMENT - 1)..end.next_multiple_of(ALIGNMENT))
+}
+
+/// A cache maintenance operation.
+#[cfg(context = "cortex-m7f")]
+enum Operation {
+    /// Writes dirty cache lines back to memory.
+    Clean,
+    /// Discards cache lines.
+    Invalidate,
+}
+
+/// Applies a cache maintenance `operation` to each of the cache `lines`.
+///
+/// # Safety
+///
+/// Invalidating must not discard data still in use.
+#[cfg(context = "cortex-m7f")]
+unsafe fn maintain(lines: core::ops::Range<usize>, operation: Operation) {
+    // SAFETY: the cache maintenance registers are write-only and stateless, so they can be
+    // written to concurrently.
+    let cbp = unsafe { &*cortex_m::peripheral::CBP::PTR };
+    cortex_m::asm::dsb();
+    for line in lines.step_by(ALIGNMENT) {
+        // Addresses are 32-bit on Cortex-M.
+        #[expect(clippy::cast_possible_truncation)]
+        let line = line as u32;
+        // SAFETY: the caller ensures that no data in use is discarded.
+        unsafe {
+            match operation {
+                Operation::Clean => cbp.dccmvac.write(line),
+                Operation::Invalidate => cbp.dcimvac.write(line),
+            }
+        }
+    }
+    cortex_m::asm::dsb();
+    cortex_m::asm::isb();
+}
+
+/// Returns the base address and the base-2 logarithm of the size of the largest block of at least
+/// 32 bytes, aligned on its power-of-two size, between the `start` and `end` addresses.
+#[cfg(any(test, all(debug_assertions, armv7m)))]
+fn largest_aligned_block(start: usize, end: usize) -> Option<(usize, u32)> {
+    let mut block = None;
+    for log2_size in 5..usize::BITS {
+        let size = 1 << log2_size;
+        let Some(base) = start.checked_next_multiple_of(size) else {
+            break;
+        };
+        if base
+            .checked_add(size)
+            .is_none_or(|block_end| block_end > end)
+        {
+            // Larger blocks would not fit either.
+            break;
+        }
+        block = Some((base, log2_size));
+    }
+    block
+}
+
+/// Returns the addresses between `start` and `end`, rounded inwards to 32 bytes, if any.
+#[cfg(any(test, all(debug_assertions, armv8m)))]
+fn inner_32_byte_range(start: usize, end: usize) -> Option<core::ops::Range<usize>> {
+    let start = start.checked_next_multiple_of(32)?;
+    let end = end & !31;
+    (start < end).then_some(start..end)
+}
+
+/// Protects inactive buffers with MPU regions.
+#[cfg(all(debug_assertions, any(armv7m, armv8m)))]
+mod protection {
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    use cortex_m::peripheral::MPU;
+
+    /// MPU regions in use, one bit each.
+    static USED_REGIONS: AtomicU32 = AtomicU32::new(0);
+
+    /// Enables the MPU, and the default memory map for privileged code outside of its regions.
+    const CTRL_ENABLE: u32 = 0b101;
+
+    /// Protects `buffer` with a free MPU region, and returns that region, if any.
+    pub(super) fn protect(buffer: &[u8]) -> Option<u32> {
+        let start = buffer.as_ptr().addr();
+        let end = start + buffer.len();
+
+        // Addresses are 32-bit on Cortex-M.
+        #[cfg(armv7m)]
+        #[expect(clippy::cast_possible_truncation)]
+        let (rbar, rasr) = {
+            let (base, log2_size) = super::largest_aligned_block(start, end)?;
+            // Execute never, no access, size of 2^(SIZE + 1) bytes, enabled.
+            (base as u32, 1 << 28 | (log2_size - 1) << 1 | 1)
+        };
+        #[cfg(armv8m)]
+        #[expect(clippy::cast_possible_truncation)]
+        let (rbar, rlar) = {
+            let range = super::inner_32_byte_range(start, end)?;
+            // Read-only by privileged code, execute never; normal memory attributes from
+            // MAIR index 7, enabled.
+            (
+                range.start as u32 | 0b10 << 1 | 1,
+                (range.end - 32) as u32 | 7 << 1 | 1,
+            )
+        };
+
+        cortex_m::interrupt::free(|_| {
+            // SAFETY: the MPU registers are only written with interrupts disabled, so that
+            // region programming sequences are not interleaved.
+            let mpu = unsafe { &*MPU::PTR };
+            let regions = (mpu._type.read() >> 8) & 0xff;
+            let used = USED_REGIONS.load(Ordering::Relaxed);
+            let region = (regions / 2..regions).find(|region| used & (1 << region) == 0)?;
+            USED_REGIONS.store(used | 1 << region, Ordering::Relaxed);
+
+            // SAFETY: the region only covers memory within the buffer, which is not used until
+            // the region is disabled again, and it takes precedence over lower regions only.
+            unsafe {
+                #[cfg(armv8m)]
+                mpu.mair[1].modify(|mair| mair | 0xff << 24);
+                mpu.rnr.write(region);
+                mpu.rbar.write(rbar);
+                #[cfg(armv7m)]
+                mpu.rasr.write(rasr);
+                #[cfg(armv8m)]
+                mpu.rlar.write(rlar);
+                mpu.ctrl.write(CTRL_ENABLE);
+            }
+            cortex_m::asm::dsb();
+            cortex_m::asm::isb();
+            Some(region)
+        })
+    }
+
+    /// Disables the MPU `region`.
+    pub(super) fn unprotect(region: u32) {
+        cortex_m::interrupt::free(|_| {
+            // SAFETY: as above.
+            let mpu = unsafe { &*MPU::PTR };
+            // SAFETY: disabling a region only lifts restrictions.
+            unsafe {
+                mpu.rnr.write(region);
+                #[cfg(armv7m)]
+                mpu.rasr.write(0);
+                #[cfg(armv8m)]
+                mpu.rlar.write(0);
+            }
+            cortex_m::asm::dsb();
+            cortex_m::asm::isb();
+            let used = USED_REGIONS.load(Ordering::Relaxed);
+            USED_REGIONS.store(used & !(1 << region), Ordering::Relaxed);
+        });
+    }
+}
+
+/// Declares a [`DmaBuffer`] of the given number of bytes, and returns a `&'static mut` reference
+/// to it.
+///
+/// The buffer is a `static`, located in RAM accessible to the DMA engine of the target MCU.
+///
+/// # Panics
+///
+/// Each invocation of the macro declares a single buffer, whose reference can only be obtained
+/// once: the expression panics if it is evaluated a second time, eg. in a loop.
+#[macro_export]
+macro_rules! dma_buffer {
+    ($len:expr) => {{
+        static BUFFER: $crate::reexports::static_cell::ConstStaticCell<
+            $crate::dma::DmaBuffer<{ $len }>,
+        > = $crate::reexports::static_cell::ConstStaticCell::new($crate::dma::DmaBuffer::new());
+        BUFFER.take()
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alignment() {
+        assert_eq!(core::mem::align_of::<DmaBuffer<3>>(), ALIGNMENT);
+        let buffer = crate::dma_buffer!(10);
+        assert_eq!(buffer.as_ptr().addr() % ALIGNMENT, 0);
+        assert_eq!(buffer.len(), 10);
+    }
+
+    #[test]
+    fn protected_blocks() {
+        assert_eq!(
+            largest_aligned_block(0x2000_0000, 0x2000_0400),
+            Some((0x2000_0000, 10))
+        );
+        assert_eq!(
+            largest_aligned_block(0x2000_0020, 0x2000_0400),
+            Some((0x2000_0200, 9))
+        );
+        assert_eq!(
+            largest_aligned_block(0x2000_0004, 0x2000_0044),
+            Some((0x2000_0020, 5))
+        );
+        assert_eq!(largest_aligned_block(0x2000_0004, 0x2000_0024), None);
+
+        assert_eq!(
+            inner_32_byte_range(0x2000_0004, 0x2000_0064),
+            Some(0x2000_0020..0x2000_0060)
+        );
+        assert_eq!(inner_32_byte_range(0x2000_0004, 0x2000_0030), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "taken")]
+    fn single_take() {
+        let take = || crate::dma_buffer!(4);
+        take().copy_from_slice(&[1, 2, 3, 4]);
+        take();
+    }
+}
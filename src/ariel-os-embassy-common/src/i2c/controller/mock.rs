@@ -0,0 +1,208 @@
+//! Provides a mock I2C bus, to test drivers of I2C devices on the host.
+//!
+//! The [`I2cMock`] is given the operations the driver is expected to perform, in order, along
+//! with the data the devices return:
+//!
+//! ```
+//! # embassy_futures::block_on(async {
+//! use ariel_os_embassy_common::i2c::controller::mock::{Expectation, I2cMock};
+//! use embedded_hal_async::i2c::I2c as _;
+//!
+//! let mut i2c = I2cMock::new(&[
+//!     // Reading the ID register of the device.
+//!     Expectation::Write { address: 0x76, data: &[0xd0] },
+//!     Expectation::Read { address: 0x76, data: &[0x60] },
+//! ]);
+//!
+//! let mut id = [0];
+//! i2c.write_read(0x76, &[0xd0], &mut id).await.unwrap();
+//! assert_eq!(id, [0x60]);
+//! i2c.done();
+//! # });
+//! ```
+//!
+//! Expectations are matched against the operations of the transactions, regardless of how the
+//! operations are grouped into transactions.
+
+use embedded_hal_async::i2c::{ErrorType, I2c, Operation};
+
+use super::Error;
+
+/// An operation expected by the [`I2cMock`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expectation<'a> {
+    /// Expects `data` to be written to the device at `address`.
+    Write {
+        /// Address of the device.
+        address: u8,
+        /// Data expected to be written.
+        data: &'a [u8],
+    },
+    /// Expects `data.len()` bytes to be read from the device at `address`, which returns `data`.
+    Read {
+        /// Address of the device.
+        address: u8,
+        /// Data returned by the device.
+        data: &'a [u8],
+    },
+    /// Expects any operation on the device at `address`, which fails with `error`.
+    Error {
+        /// Address of the device.
+        address: u8,
+        /// Error returned by the bus.
+        error: Error,
+    },
+}
+
+/// A mock I2C bus, which checks the operations performed on it against expectations.
+///
+/// # Panics
+///
+/// Operations panic if they do not match the next expectation.
+#[derive(Debug)]
+pub struct I2cMock<'a> {
+    expectations: &'a [Expectation<'a>],
+    met: usize,
+}
+
+impl<'a> I2cMock<'a> {
+    /// Creates a mock I2C bus expecting the `expectations`, in order.
+    #[must_use]
+    pub const fn new(expectations: &'a [Expectation<'a>]) -> Self {
+        Self {
+            expectations,
+            met: 0,
+        }
+    }
+
+    /// Checks that all the expectations were met.
+    ///
+    /// # Panics
+    ///
+    /// Panics if some expectations were not met.
+    pub fn done(&self) {
+        let remaining = self.expectations.get(self.met..).unwrap_or_default();
+        assert!(
+            remaining.is_empty(),
+            "expected further operations: {remaining:?}"
+        );
+    }
+
+    fn next_expectation(&mut self, operation: &Operation<'_>) -> &'a Expectation<'a> {
+        let Some(expectation) = self.expectations.get(self.met) else {
+            panic!("unexpected operation once all expectations were met: {operation:?}");
+        };
+        self.met += 1;
+        expectation
+    }
+}
+
+impl ErrorType for I2cMock<'_> {
+    type Error = Error;
+}
+
+impl I2c for I2cMock<'_> {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        for operation in operations {
+            let expectation = self.next_expectation(operation);
+            let expected_address = match expectation {
+                Expectation::Write { address, .. }
+                | Expectation::Read { address, .. }
+                | Expectation::Error { address, .. } => *address,
+            };
+            assert_eq!(
+                address, expected_address,
+                "unexpected address for {operation:?}"
+            );
+
+            match (operation, expectation) {
+                (Operation::Write(written), Expectation::Write { data, .. }) => {
+                    assert_eq!(written, data, "unexpected data written");
+                }
+                (Operation::Read(buffer), Expectation::Read { data, .. }) => {
+                    assert_eq!(buffer.len(), data.len(), "unexpected read length");
+                    buffer.copy_from_slice(data);
+                }
+                (_, Expectation::Error { error, .. }) => return Err(error.clone()),
+                (operation, expectation) => {
+                    panic!("expected {expectation:?}, got {operation:?}");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+
+    use super::*;
+
+    #[test]
+    fn register_access() {
+        let mut i2c = I2cMock::new(&[
+            Expectation::Write {
+                address: 0x18,
+                data: &[0x20, 0x57],
+            },
+            Expectation::Write {
+                address: 0x18,
+                data: &[0xa8],
+            },
+            Expectation::Read {
+                address: 0x18,
+                data: &[1, 2, 3],
+            },
+        ]);
+
+        let mut values = [0; 3];
+        block_on(async {
+            i2c.write(0x18, &[0x20, 0x57]).await.unwrap();
+            i2c.write_read(0x18, &[0xa8], &mut values).await.unwrap();
+        });
+
+        assert_eq!(values, [1, 2, 3]);
+        i2c.done();
+    }
+
+    #[test]
+    fn error() {
+        let mut i2c = I2cMock::new(&[Expectation::Error {
+            address: 0x18,
+            error: Error::Timeout,
+        }]);
+
+        let result = block_on(i2c.read(0x18, &mut [0; 2]));
+
+        assert_eq!(result, Err(Error::Timeout));
+        i2c.done();
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected data written")]
+    fn unexpected_write() {
+        let mut i2c = I2cMock::new(&[Expectation::Write {
+            address: 0x18,
+            data: &[0x20, 0x57],
+        }]);
+
+        let _ = block_on(i2c.write(0x18, &[0x20, 0x07]));
+    }
+
+    #[test]
+    #[should_panic(expected = "expected further operations")]
+    fn missing_operation() {
+        let i2c = I2cMock::new(&[Expectation::Write {
+            address: 0x18,
+            data: &[0x20, 0x57],
+        }]);
+
+        i2c.done();
+    }
+}
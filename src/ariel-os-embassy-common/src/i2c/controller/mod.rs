@@ -2,11 +2,19 @@
 
 use embassy_time::Duration;
 
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod recovery;
+
 pub use embedded_hal::i2c::Operation;
 pub use fugit::KilohertzU32 as Kilohertz;
 
-/// Timeout value for I2C operations.
+/// Default timeout value for I2C operations, which can be changed through the `timeout` field of
+/// the HAL-specific configuration of the bus.
 ///
+/// The timeout applies to each operation as a whole, eg. to a complete transaction.
+/// It is enforced by the I2C peripheral where it supports it, and through a timer otherwise; the
+/// operation then fails with [`Error::Timeout`].
 /// HALs are allowed to timeout earlier.
 pub const I2C_TIMEOUT: Duration = Duration::from_millis(100);
 
@@ -68,7 +76,8 @@ pub enum Error {
     NoAcknowledge(NoAcknowledgeSource),
     /// Overrun of the receive buffer.
     Overrun,
-    /// Timeout when attempting to use the bus; most likely the target device is not connected.
+    /// The operation did not complete within the timeout of the bus; most likely the target
+    /// device is not connected, or holds the bus (see [`recovery`]).
     Timeout,
     /// An other error occurred.
     Other,
@@ -171,17 +180,16 @@ macro_rules! impl_async_i2c_for_driver_enum {
 #[macro_export]
 macro_rules! handle_i2c_timeout_res {
     ($i2c:ident, $op:ident, $address:ident, $( $param:ident ),+) => {{
-        let res = $crate::reexports::embassy_futures::select::select(
+        let res = $crate::timeout::with_timeout(
+            $i2c.timeout,
             // Disambiguate between the trait methods and the direct methods.
             $crate::reexports::embedded_hal_async::i2c::I2c::$op(&mut $i2c.twim, $address, $( $param ),+),
-            $crate::reexports::embassy_time::Timer::after($crate::i2c::controller::I2C_TIMEOUT),
         ).await;
 
-        if let $crate::reexports::embassy_futures::select::Either::First(op) = res {
+        match res {
             // `from_error` is defined in each HAL
-            op.map_err(from_error)
-        } else {
-            Err($crate::i2c::controller::Error::NoAcknowledge($crate::i2c::controller::NoAcknowledgeSource::Unknown))
+            Ok(op) => op.map_err(from_error),
+            Err($crate::timeout::TimeoutError) => Err($crate::i2c::controller::Error::Timeout),
         }
     }}
 }
@@ -0,0 +1,208 @@
+//! Provides recovery of an I2C bus held by a target device.
+//!
+//! A target device that was interrupted in the middle of a read, eg. by a reset of the MCU, may
+//! keep driving SDA low while waiting for the clock cycles of the remaining bits.
+//! All operations on the bus then fail, usually with [`Error::Timeout`] or
+//! [`Error::ArbitrationLoss`].
+//! [`recover_bus()`] clocks SCL until the target device releases SDA, and terminates the transfer
+//! with a STOP condition, as described in section 3.1.16 of the I2C specification (UM10204).
+//!
+//! Recovery drives the SDA and SCL pins directly, as open-drain GPIOs of the HAL, and must
+//! therefore happen while the I2C driver is not using them, ie. before it is created:
+//!
+//! ```ignore
+//! // `scl` and `sda` are open-drain GPIOs on the pins of the bus, borrowing their peripherals,
+//! // eg. `embassy_nrf::gpio::Flex`es configured with `OutputDrive::Standard0Disconnect1`.
+//! if let Err(err) = recover_bus(&mut scl, &mut sda, &mut embassy_time::Delay).await {
+//!     warn!("could not recover the I2C bus: {:?}", err);
+//! }
+//! drop((scl, sda));
+//!
+//! let i2c_bus = pins::SensorI2c::new(peripherals.i2c_sda, peripherals.i2c_scl, i2c_config);
+//! ```
+
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal_async::delay::DelayNs;
+
+use super::Error;
+
+/// Maximum number of clock pulses needed for a target device to release SDA: the remaining bits
+/// of a byte and the acknowledgement bit.
+const MAX_CLOCK_PULSES: u8 = 9;
+
+/// Half of the clock period in standard mode, in microseconds.
+const HALF_PERIOD_US: u32 = 5;
+
+/// Frees an I2C bus of which SDA is held low by a target device.
+///
+/// `scl` and `sda` must be configured as open-drain outputs, and `sda` must read the level of
+/// the line.
+/// Nothing is done if SDA is already released.
+///
+/// # Errors
+///
+/// Returns [`Error::Bus`] if SDA is still held low after nine clock pulses, and
+/// [`Error::Other`] if driving or reading a pin fails.
+pub async fn recover_bus<SCL, SDA, D>(
+    scl: &mut SCL,
+    sda: &mut SDA,
+    delay: &mut D,
+) -> Result<(), Error>
+where
+    SCL: OutputPin,
+    SDA: InputPin + OutputPin,
+    D: DelayNs,
+{
+    sda.set_high().map_err(|_| Error::Other)?;
+    scl.set_high().map_err(|_| Error::Other)?;
+    delay.delay_us(HALF_PERIOD_US).await;
+
+    if sda.is_high().map_err(|_| Error::Other)? {
+        return Ok(());
+    }
+
+    for _ in 0..MAX_CLOCK_PULSES {
+        scl.set_low().map_err(|_| Error::Other)?;
+        delay.delay_us(HALF_PERIOD_US).await;
+        scl.set_high().map_err(|_| Error::Other)?;
+        delay.delay_us(HALF_PERIOD_US).await;
+
+        if sda.is_high().map_err(|_| Error::Other)? {
+            break;
+        }
+    }
+
+    if sda.is_low().map_err(|_| Error::Other)? {
+        return Err(Error::Bus);
+    }
+
+    // STOP condition: SDA rising while SCL is high.
+    scl.set_low().map_err(|_| Error::Other)?;
+    sda.set_low().map_err(|_| Error::Other)?;
+    delay.delay_us(HALF_PERIOD_US).await;
+    scl.set_high().map_err(|_| Error::Other)?;
+    delay.delay_us(HALF_PERIOD_US).await;
+    sda.set_high().map_err(|_| Error::Other)?;
+    delay.delay_us(HALF_PERIOD_US).await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{cell::Cell, convert::Infallible};
+
+    use embassy_futures::block_on;
+    use embedded_hal::digital::ErrorType;
+
+    use super::*;
+
+    /// Lines of a bus with a target device holding SDA low for a number of clock pulses.
+    struct Bus {
+        pulses_until_release: Cell<u8>,
+        pulses: Cell<u8>,
+        stops: Cell<u8>,
+        scl_high: Cell<bool>,
+        sda_high: Cell<bool>,
+    }
+
+    impl Bus {
+        fn new(pulses_until_release: u8) -> Self {
+            Self {
+                pulses_until_release: Cell::new(pulses_until_release),
+                pulses: Cell::new(0),
+                stops: Cell::new(0),
+                scl_high: Cell::new(true),
+                sda_high: Cell::new(true),
+            }
+        }
+
+        fn sda_released(&self) -> bool {
+            self.pulses_until_release.get() == 0
+        }
+    }
+
+    struct Scl<'a>(&'a Bus);
+    struct Sda<'a>(&'a Bus);
+    struct NoDelay;
+
+    impl ErrorType for Scl<'_> {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for Scl<'_> {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.0.scl_high.set(false);
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            if !self.0.scl_high.replace(true) {
+                self.0.pulses.set(self.0.pulses.get() + 1);
+                let remaining = self.0.pulses_until_release.get();
+                self.0.pulses_until_release.set(remaining.saturating_sub(1));
+            }
+            Ok(())
+        }
+    }
+
+    impl ErrorType for Sda<'_> {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for Sda<'_> {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.0.sda_high.set(false);
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            if !self.0.sda_high.replace(true) && self.0.scl_high.get() {
+                self.0.stops.set(self.0.stops.get() + 1);
+            }
+            Ok(())
+        }
+    }
+
+    impl InputPin for Sda<'_> {
+        fn is_high(&mut self) -> Result<bool, Self::Error> {
+            Ok(self.0.sda_high.get() && self.0.sda_released())
+        }
+
+        fn is_low(&mut self) -> Result<bool, Self::Error> {
+            self.is_high().map(|high| !high)
+        }
+    }
+
+    impl DelayNs for NoDelay {
+        async fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    fn recover(bus: &Bus) -> Result<(), Error> {
+        block_on(recover_bus(&mut Scl(bus), &mut Sda(bus), &mut NoDelay))
+    }
+
+    #[test]
+    fn released_bus() {
+        let bus = Bus::new(0);
+        assert_eq!(recover(&bus), Ok(()));
+        assert_eq!(bus.pulses.get(), 0);
+        assert_eq!(bus.stops.get(), 0);
+    }
+
+    #[test]
+    fn held_bus() {
+        let bus = Bus::new(3);
+        assert_eq!(recover(&bus), Ok(()));
+        assert_eq!(bus.pulses.get(), 4);
+        assert_eq!(bus.stops.get(), 1);
+    }
+
+    #[test]
+    fn stuck_bus() {
+        let bus = Bus::new(u8::MAX);
+        assert_eq!(recover(&bus), Err(Error::Bus));
+        assert_eq!(bus.pulses.get(), MAX_CLOCK_PULSES);
+        assert_eq!(bus.stops.get(), 0);
+    }
+}
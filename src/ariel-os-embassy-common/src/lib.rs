@@ -4,6 +4,7 @@
 #![cfg_attr(nightly, feature(doc_auto_cfg))]
 #![deny(missing_docs)]
 
+pub mod dma;
 pub mod gpio;
 
 #[cfg(context = "cortex-m")]
@@ -23,6 +24,8 @@ pub mod identity;
 #[cfg(feature = "spi")]
 pub mod spi;
 
+pub mod timeout;
+
 pub mod reexports {
     //! Crate re-exports.
 
@@ -30,4 +33,5 @@ pub mod reexports {
     pub use embassy_futures;
     pub use embassy_time;
     pub use embedded_hal_async;
+    pub use static_cell;
 }
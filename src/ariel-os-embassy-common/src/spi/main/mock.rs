@@ -0,0 +1,238 @@
+//! Provides a mock SPI device, to test drivers of SPI devices on the host.
+//!
+//! The [`SpiMock`] is given the operations the driver is expected to perform, in order, along
+//! with the data the device returns:
+//!
+//! ```
+//! # embassy_futures::block_on(async {
+//! use ariel_os_embassy_common::spi::main::mock::{Expectation, SpiMock};
+//! use embedded_hal_async::spi::SpiDevice as _;
+//!
+//! let mut spi = SpiMock::new(&[
+//!     // Reading the ID register of the device.
+//!     Expectation::Transfer { write: &[0x80, 0], read: &[0, 0x24] },
+//! ]);
+//!
+//! let mut id = [0x80, 0];
+//! spi.transfer_in_place(&mut id).await.unwrap();
+//! assert_eq!(id, [0, 0x24]);
+//! spi.done();
+//! # });
+//! ```
+//!
+//! Expectations are matched against the operations of the transactions, regardless of how the
+//! operations are grouped into transactions.
+//! Delays within transactions are ignored.
+//!
+//! The [`SpiMock`] is also an [`SpiBus`], for drivers that handle the chip select of the device
+//! themselves; each operation on the bus is then matched against the next expectation.
+
+use embedded_hal::spi::ErrorKind;
+use embedded_hal_async::spi::{ErrorType, Operation, SpiBus, SpiDevice};
+
+/// An operation expected by the [`SpiMock`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expectation<'a> {
+    /// Expects the data to be written.
+    Write(&'a [u8]),
+    /// Expects as many bytes to be read as the data holds, which the device returns.
+    Read(&'a [u8]),
+    /// Expects `write` to be written while `read.len()` bytes are read, which the device returns
+    /// as `read`.
+    ///
+    /// This matches both transfers and in-place transfers.
+    Transfer {
+        /// Data expected to be written.
+        write: &'a [u8],
+        /// Data returned by the device.
+        read: &'a [u8],
+    },
+    /// Expects any operation, which fails with `kind`.
+    Error(ErrorKind),
+}
+
+/// A mock SPI device, which checks the operations performed on it against expectations.
+///
+/// # Panics
+///
+/// Operations panic if they do not match the next expectation.
+#[derive(Debug)]
+pub struct SpiMock<'a> {
+    expectations: &'a [Expectation<'a>],
+    met: usize,
+}
+
+impl<'a> SpiMock<'a> {
+    /// Creates a mock SPI device expecting the `expectations`, in order.
+    #[must_use]
+    pub const fn new(expectations: &'a [Expectation<'a>]) -> Self {
+        Self {
+            expectations,
+            met: 0,
+        }
+    }
+
+    /// Checks that all the expectations were met.
+    ///
+    /// # Panics
+    ///
+    /// Panics if some expectations were not met.
+    pub fn done(&self) {
+        let remaining = self.expectations.get(self.met..).unwrap_or_default();
+        assert!(
+            remaining.is_empty(),
+            "expected further operations: {remaining:?}"
+        );
+    }
+
+    /// Returns the next expectation, which `operation` is to be checked against.
+    ///
+    /// # Panics
+    ///
+    /// Panics if all the expectations were met already.
+    fn next_expectation(&mut self, operation: &Operation<'_, u8>) -> Expectation<'a> {
+        let Some(expectation) = self.expectations.get(self.met) else {
+            panic!("unexpected operation once all expectations were met: {operation:?}");
+        };
+        self.met += 1;
+        *expectation
+    }
+}
+
+impl ErrorType for SpiMock<'_> {
+    type Error = ErrorKind;
+}
+
+impl SpiDevice for SpiMock<'_> {
+    async fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        for operation in operations {
+            if let Operation::DelayNs(_) = operation {
+                continue;
+            }
+
+            let expectation = self.next_expectation(operation);
+            match (operation, expectation) {
+                (Operation::Write(written), Expectation::Write(data)) => {
+                    assert_eq!(*written, data, "unexpected data written");
+                }
+                (Operation::Read(buffer), Expectation::Read(data)) => {
+                    assert_eq!(buffer.len(), data.len(), "unexpected read length");
+                    buffer.copy_from_slice(data);
+                }
+                (Operation::Transfer(buffer, written), Expectation::Transfer { write, read }) => {
+                    assert_eq!(*written, write, "unexpected data written");
+                    assert_eq!(buffer.len(), read.len(), "unexpected read length");
+                    buffer.copy_from_slice(read);
+                }
+                (Operation::TransferInPlace(buffer), Expectation::Transfer { write, read }) => {
+                    assert_eq!(*buffer, write, "unexpected data written");
+                    assert_eq!(buffer.len(), read.len(), "unexpected read length");
+                    buffer.copy_from_slice(read);
+                }
+                (_, Expectation::Error(kind)) => return Err(kind),
+                (operation, expectation) => {
+                    panic!("expected {expectation:?}, got {operation:?}");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SpiBus for SpiMock<'_> {
+    async fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.transaction(&mut [Operation::Read(words)]).await
+    }
+
+    async fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        self.transaction(&mut [Operation::Write(words)]).await
+    }
+
+    async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        self.transaction(&mut [Operation::Transfer(read, write)])
+            .await
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.transaction(&mut [Operation::TransferInPlace(words)])
+            .await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use embassy_futures::block_on;
+
+    // The methods of `SpiBus` are called explicitly, as they share their names with those of
+    // `SpiDevice`.
+    use super::{ErrorKind, Expectation, Operation, SpiDevice, SpiMock};
+
+    #[test]
+    fn register_access() {
+        let mut spi = SpiMock::new(&[
+            Expectation::Write(&[0x20, 0x57]),
+            Expectation::Write(&[0xe8]),
+            Expectation::Read(&[1, 2, 3]),
+        ]);
+
+        let mut values = [0; 3];
+        block_on(async {
+            spi.write(&[0x20, 0x57]).await.unwrap();
+            spi.transaction(&mut [Operation::Write(&[0xe8]), Operation::Read(&mut values)])
+                .await
+                .unwrap();
+        });
+
+        assert_eq!(values, [1, 2, 3]);
+        spi.done();
+    }
+
+    #[test]
+    fn bus() {
+        let mut spi = SpiMock::new(&[
+            Expectation::Write(&[0x0b]),
+            Expectation::Transfer {
+                write: &[0xff, 0xff],
+                read: &[4, 5],
+            },
+        ]);
+
+        let mut values = [0xff; 2];
+        block_on(async {
+            super::SpiBus::write(&mut spi, &[0x0b]).await.unwrap();
+            super::SpiBus::transfer_in_place(&mut spi, &mut values)
+                .await
+                .unwrap();
+        });
+
+        assert_eq!(values, [4, 5]);
+        spi.done();
+    }
+
+    #[test]
+    fn error() {
+        let mut spi = SpiMock::new(&[Expectation::Error(ErrorKind::Overrun)]);
+
+        let result = block_on(spi.read(&mut [0; 2]));
+
+        assert_eq!(result, Err(ErrorKind::Overrun));
+        spi.done();
+    }
+
+    #[test]
+    #[should_panic(expected = "expected Read")]
+    fn unexpected_operation() {
+        let mut spi = SpiMock::new(&[Expectation::Read(&[1])]);
+
+        let _ = block_on(spi.write(&[1]));
+    }
+}
@@ -2,6 +2,9 @@
 
 pub use fugit::KilohertzU32 as Kilohertz;
 
+#[cfg(feature = "mock")]
+pub mod mock;
+
 // FIXME: rename this to Bitrate and use bps instead?
 /// SPI bus frequencies supported on all MCUs.
 #[derive(Copy, Clone)]
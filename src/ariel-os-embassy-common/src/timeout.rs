@@ -0,0 +1,130 @@
+//! Provides timeouts and deadlines for futures.
+//!
+//! Timeouts are reported as [`TimeoutError`]s. Error types of fallible operations can implement
+//! `From<TimeoutError>`, so that timeouts are propagated with `?`, and [`try_with_timeout()`]
+//! merges them with the errors of the operation:
+//!
+//! ```ignore
+//! let len = try_with_timeout(RESPONSE_TIMEOUT, self.receive()).await?;
+//! ```
+//!
+//! A [`Deadline`] bounds the time taken by several operations together, eg. by all the steps of
+//! a request.
+
+use core::future::Future;
+
+use embassy_time::{Duration, Instant};
+pub use embassy_time::{TimeoutError, with_timeout};
+
+/// Runs a fallible `future` until it completes, or until `timeout` has elapsed.
+///
+/// # Errors
+///
+/// Returns the error of the future if it failed, and the conversion of a [`TimeoutError`] if it
+/// did not complete in time.
+pub async fn try_with_timeout<F, T, E>(timeout: Duration, future: F) -> Result<T, E>
+where
+    F: Future<Output = Result<T, E>>,
+    E: From<TimeoutError>,
+{
+    with_timeout(timeout, future).await?
+}
+
+/// A point in time by which operations need to complete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Deadline {
+    at: Instant,
+}
+
+impl Deadline {
+    /// Creates a deadline at `instant`.
+    #[must_use]
+    pub fn at(instant: Instant) -> Self {
+        Self { at: instant }
+    }
+
+    /// Creates a deadline once `duration` has elapsed from now.
+    #[must_use]
+    pub fn after(duration: Duration) -> Self {
+        Self::at(Instant::now().checked_add(duration).unwrap_or(Instant::MAX))
+    }
+
+    /// Returns the instant of the deadline.
+    #[must_use]
+    pub fn instant(&self) -> Instant {
+        self.at
+    }
+
+    /// Returns the time left until the deadline, which is zero once it has passed.
+    #[must_use]
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+
+    /// Returns whether the deadline has passed.
+    #[must_use]
+    pub fn has_passed(&self) -> bool {
+        Instant::now() >= self.at
+    }
+
+    /// Runs `future` until it completes, or until the deadline.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TimeoutError`] if the future did not complete by the deadline.
+    pub async fn run<F: Future>(&self, future: F) -> Result<F::Output, TimeoutError> {
+        embassy_time::with_deadline(self.at, future).await
+    }
+
+    /// Runs a fallible `future` until it completes, or until the deadline.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the future if it failed, and the conversion of a [`TimeoutError`] if
+    /// it did not complete by the deadline.
+    pub async fn try_run<F, T, E>(&self, future: F) -> Result<T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+        E: From<TimeoutError>,
+    {
+        self.run(future).await?
+    }
+}
+
+/// Waits for the first of two to four futures to complete, or until a timeout has elapsed.
+///
+/// This expands to a future whose output is a `Result` holding the
+/// [`Either`](embassy_futures::select::Either) type matching the number of futures, or a
+/// [`TimeoutError`].
+///
+/// ```ignore
+/// match select_with_timeout!(Duration::from_secs(1), button.wait_for_press(), signal.wait())
+///     .await
+/// {
+///     Ok(Either::First(())) => info!("button pressed"),
+///     Ok(Either::Second(value)) => info!("signaled: {}", value),
+///     Err(TimeoutError) => info!("timeout"),
+/// }
+/// ```
+#[macro_export]
+macro_rules! select_with_timeout {
+    ($timeout:expr, $first:expr, $second:expr $(,)?) => {
+        $crate::timeout::with_timeout(
+            $timeout,
+            $crate::reexports::embassy_futures::select::select($first, $second),
+        )
+    };
+    ($timeout:expr, $first:expr, $second:expr, $third:expr $(,)?) => {
+        $crate::timeout::with_timeout(
+            $timeout,
+            $crate::reexports::embassy_futures::select::select3($first, $second, $third),
+        )
+    };
+    ($timeout:expr, $first:expr, $second:expr, $third:expr, $fourth:expr $(,)?) => {
+        $crate::timeout::with_timeout(
+            $timeout,
+            $crate::reexports::embassy_futures::select::select4($first, $second, $third, $fourth),
+        )
+    };
+}
@@ -0,0 +1,565 @@
+//! Provides the LEDs and buttons of the board.
+//!
+//! The pins of the LEDs and buttons are taken from the peripherals at startup, according to the
+//! definition of the board, so that applications do not need to know them:
+//!
+//! ```ignore
+//! use ariel_os::board;
+//!
+//! #[ariel_os::task(autostart)]
+//! async fn main() {
+//!     let (Some(led), Some(button)) = (board::leds().first(), board::buttons().first()) else {
+//!         return;
+//!     };
+//!     loop {
+//!         button.wait_for_press().await;
+//!         led.toggle();
+//!     }
+//! }
+//! ```
+//!
+//! The LEDs and buttons are given in the order of their labels on the board; boards without a
+//! definition have none. Their pins are not available to applications.
+//!
+//! Applications can override the definition of the board with [`define_board!`], eg. to
+//! repurpose the pin of an LED or to add buttons connected to the board.
+//! With the `input` feature, the definition can also include a key matrix, which is handed over
+//! to the input service as a keypad, see [`take_keypad()`].
+#![deny(missing_docs)]
+
+use core::cell::RefCell;
+
+use embassy_sync::{
+    blocking_mutex::{Mutex as BlockingMutex, raw::CriticalSectionRawMutex},
+    mutex::Mutex,
+    once_lock::OnceLock,
+};
+use embassy_time::{Duration, Timer};
+
+#[cfg(feature = "input")]
+use crate::input::Keypad;
+use crate::{
+    gpio::{Input, IntEnabledInput, Level, Output, Pull},
+    hal,
+};
+
+/// Maximum number of LEDs of a board.
+const MAX_LEDS: usize = 4;
+/// Maximum number of buttons of a board.
+const MAX_BUTTONS: usize = 4;
+
+/// Duration a button needs to stay at a level before it is considered pressed or released.
+const DEBOUNCE: Duration = Duration::from_millis(20);
+
+/// Durations of the blinks of [`Led::blink_code()`].
+const CODE_ON: Duration = Duration::from_millis(200);
+const CODE_OFF: Duration = Duration::from_millis(300);
+const CODE_PAUSE: Duration = Duration::from_millis(1500);
+
+static LEDS: OnceLock<heapless::Vec<Led, MAX_LEDS>> = OnceLock::new();
+static BUTTONS: OnceLock<heapless::Vec<Button, MAX_BUTTONS>> = OnceLock::new();
+#[cfg(feature = "input")]
+static KEYPAD: BlockingMutex<CriticalSectionRawMutex, RefCell<Option<Keypad>>> =
+    BlockingMutex::new(RefCell::new(None));
+
+/// Returns the LEDs of the board.
+pub fn leds() -> &'static [Led] {
+    LEDS.try_get().map_or(&[], |leds| leds.as_slice())
+}
+
+/// Returns the buttons of the board.
+pub fn buttons() -> &'static [Button] {
+    BUTTONS.try_get().map_or(&[], |buttons| buttons.as_slice())
+}
+
+/// Takes the key matrix of the board, to hand it over to the input service with
+/// [`add_keypad()`](crate::input::add_keypad()).
+///
+/// Returns `None` if the board has no key matrix, or if it was taken already.
+#[cfg(feature = "input")]
+pub fn take_keypad() -> Option<Keypad> {
+    KEYPAD.lock(|keypad| keypad.borrow_mut().take())
+}
+
+/// An LED of the board.
+pub struct Led {
+    output: BlockingMutex<CriticalSectionRawMutex, RefCell<Output>>,
+    active: Level,
+}
+
+impl Led {
+    fn new(
+        pin: impl hal::peripheral::Peripheral<P: hal::gpio::output::OutputPin> + 'static,
+        active: Level,
+    ) -> Self {
+        let off = Level::from(!bool::from(active));
+        Self {
+            output: BlockingMutex::new(RefCell::new(Output::new(pin, off))),
+            active,
+        }
+    }
+
+    /// Switches the LED on.
+    pub fn on(&self) {
+        self.set(true);
+    }
+
+    /// Switches the LED off.
+    pub fn off(&self) {
+        self.set(false);
+    }
+
+    /// Switches the LED on if `on` is `true`, off otherwise.
+    pub fn set(&self, on: bool) {
+        let level = Level::from(on == bool::from(self.active));
+        self.output
+            .lock(|output| output.borrow_mut().set_level(level));
+    }
+
+    /// Toggles the LED.
+    pub fn toggle(&self) {
+        self.output.lock(|output| output.borrow_mut().toggle());
+    }
+
+    /// Blinks the LED `count` times, keeping it on for `on` and off for `off` each time.
+    pub async fn blink(&self, count: u32, on: Duration, off: Duration) {
+        for _ in 0..count {
+            self.on();
+            Timer::after(on).await;
+            self.off();
+            Timer::after(off).await;
+        }
+    }
+
+    /// Blinks `code` short times, followed by a pause, eg. to report an error state.
+    ///
+    /// Calling this in a loop repeats the code, which is counted between the pauses.
+    pub async fn blink_code(&self, code: u8) {
+        self.blink(u32::from(code), CODE_ON, CODE_OFF).await;
+        Timer::after(CODE_PAUSE).await;
+    }
+}
+
+/// A button of the board.
+pub struct Button {
+    input: Mutex<CriticalSectionRawMutex, IntEnabledInput>,
+    active: Level,
+}
+
+impl Button {
+    fn new(
+        pin: impl hal::peripheral::Peripheral<P: hal::gpio::input::InputPin> + 'static,
+        active: Level,
+        pull: Pull,
+    ) -> Option<Self> {
+        let input = Input::builder(pin, pull).build_with_interrupt().ok()?;
+        Some(Self {
+            input: Mutex::new(input),
+            active,
+        })
+    }
+
+    /// Returns whether the button is pressed.
+    pub async fn is_pressed(&self) -> bool {
+        self.input.lock().await.get_level() == self.active
+    }
+
+    /// Waits until the button is pressed; returns immediately if it is pressed already.
+    pub async fn wait_for_press(&self) {
+        self.wait_for(self.active).await;
+    }
+
+    /// Waits until the button is released; returns immediately if it is released already.
+    pub async fn wait_for_release(&self) {
+        self.wait_for(Level::from(!bool::from(self.active))).await;
+    }
+
+    async fn wait_for(&self, level: Level) {
+        let mut input = self.input.lock().await;
+        loop {
+            match level {
+                Level::Low => input.wait_for_low().await,
+                Level::High => input.wait_for_high().await,
+            }
+            Timer::after(DEBOUNCE).await;
+            if input.get_level() == level {
+                return;
+            }
+        }
+    }
+}
+
+/// Takes the pins of the LEDs and buttons of the board from `peripherals`.
+pub(crate) fn init(peripherals: &mut hal::OptionalPeripherals) {
+    #[cfg(not(feature = "board-config-override"))]
+    let definition = definition::take(peripherals);
+    #[cfg(feature = "board-config-override")]
+    let definition = {
+        unsafe extern "Rust" {
+            fn __ariel_os_board_definition(
+                peripherals: &mut hal::OptionalPeripherals,
+            ) -> Definition;
+        }
+        unsafe { __ariel_os_board_definition(peripherals) }
+    };
+    #[cfg(feature = "input")]
+    if !definition.keypad_rows.is_empty() && !definition.keypad_columns.is_empty() {
+        // Cannot fail, `check_pins()` ensures there are not too many rows and columns.
+        let keypad = Keypad::new(definition.keypad_rows, definition.keypad_columns).ok();
+        KEYPAD.lock(|cell| *cell.borrow_mut() = keypad);
+    }
+    let _ = LEDS.init(definition.leds);
+    let _ = BUTTONS.init(definition.buttons);
+}
+
+pub use crate::define_board;
+
+/// Defines the LEDs and the buttons of the board, along with the level they are active at, and
+/// optionally its key matrix, overriding the definition provided by Ariel OS.
+///
+/// **Important**: for this definition to be taken into account, the `board-config-override` Cargo
+/// feature needs to be enabled on the `ariel-os` dependency.
+///
+/// This allows to use pins that are not connected on the board, or to repurpose the pins of LEDs
+/// and buttons of the board, which are then available to the application. The pins are given by
+/// their names in the `peripherals` module of the HAL; buttons additionally take the pull of their
+/// input.
+///
+/// The rows of the key matrix are driven low one after the other, and its columns are pulled up;
+/// defining a key matrix requires the `input` Cargo feature.
+///
+/// Using a pin more than once, using a pin that the system takes, eg. for the debug UART, or
+/// giving too many LEDs or buttons, results in a compile-time error.
+///
+/// # Examples
+///
+/// The following only keeps the first LED and button of the nRF52840-DK, and adds a button
+/// connected to another pin:
+///
+/// ```ignore
+/// ariel_os::board::define_board! {
+///     leds: [P0_13: Low],
+///     buttons: [P0_11: Low, Up, P0_03: High, Down],
+/// }
+/// ```
+///
+/// The following adds a key matrix of two rows and three columns:
+///
+/// ```ignore
+/// ariel_os::board::define_board! {
+///     leds: [P0_13: Low, P0_14: Low, P0_15: Low, P0_16: Low],
+///     buttons: [P0_11: Low, Up, P0_12: Low, Up, P0_24: Low, Up, P0_25: Low, Up],
+///     keypad: {
+///         rows: [P0_03, P0_04],
+///         columns: [P0_28, P0_29, P0_30],
+///     },
+/// }
+/// ```
+#[macro_export]
+macro_rules! define_board {
+    (
+        leds: [$($led:ident: $led_active:ident),* $(,)?],
+        buttons: [$($button:ident: $button_active:ident, $pull:ident),* $(,)?]
+        $(, keypad: {
+            rows: [$($row:ident),* $(,)?],
+            columns: [$($column:ident),* $(,)?] $(,)?
+        })? $(,)?
+    ) => {
+        const _: () = $crate::board::check_pins(
+            &[$(stringify!($led)),*],
+            &[$(stringify!($button)),*],
+            &[$($(stringify!($row)),*)?],
+            &[$($(stringify!($column)),*)?],
+        );
+
+        // SAFETY: the compiler prevents from defining multiple functions with the same name in the
+        // same crate and the function signature matches the one expected in `ariel-os-embassy`.
+        #[unsafe(no_mangle)]
+        #[allow(unused_mut, unused_variables, reason = "boards without LEDs or buttons")]
+        fn __ariel_os_board_definition(
+            peripherals: &mut $crate::hal::OptionalPeripherals,
+        ) -> $crate::board::Definition {
+            $crate::define_board!(
+                @take peripherals,
+                leds: [$($led: $led_active),*],
+                buttons: [$($button: $button_active, $pull),*],
+                rows: [$($($row),*)?],
+                columns: [$($($column),*)?],
+            )
+        }
+    };
+    (
+        @take $peripherals:ident,
+        leds: [$($led:ident: $led_active:ident),*],
+        buttons: [$($button:ident: $button_active:ident, $pull:ident),*],
+        rows: [$($row:ident),*],
+        columns: [$($column:ident),*] $(,)?
+    ) => {{
+        let mut definition = $crate::board::Definition::default();
+        $(
+            definition.add_led(
+                $peripherals.$led.take(),
+                $crate::gpio::Level::$led_active,
+            );
+        )*
+        $(
+            definition.add_button(
+                $peripherals.$button.take(),
+                $crate::gpio::Level::$button_active,
+                $crate::gpio::Pull::$pull,
+            );
+        )*
+        $(
+            definition.add_keypad_row($peripherals.$row.take());
+        )*
+        $(
+            definition.add_keypad_column($peripherals.$column.take());
+        )*
+        definition
+    }};
+}
+
+/// The LEDs, buttons and key matrix of a board, as defined by [`define_board!`].
+#[doc(hidden)]
+#[derive(Default)]
+pub struct Definition {
+    leds: heapless::Vec<Led, MAX_LEDS>,
+    buttons: heapless::Vec<Button, MAX_BUTTONS>,
+    #[cfg(feature = "input")]
+    keypad_rows: heapless::Vec<Output, { crate::input::MAX_ROWS }>,
+    #[cfg(feature = "input")]
+    keypad_columns: heapless::Vec<Input, { crate::input::MAX_COLUMNS }>,
+}
+
+impl Definition {
+    /// Adds an LED on `pin`, unless it was already taken.
+    pub fn add_led(
+        &mut self,
+        pin: Option<impl hal::peripheral::Peripheral<P: hal::gpio::output::OutputPin> + 'static>,
+        active: Level,
+    ) {
+        if let Some(pin) = pin {
+            // Cannot fail, `check_pins()` ensures there are at most `MAX_LEDS` LEDs.
+            let _ = self.leds.push(Led::new(pin, active));
+        }
+    }
+
+    /// Adds a button on `pin`, unless it was already taken.
+    pub fn add_button(
+        &mut self,
+        pin: Option<impl hal::peripheral::Peripheral<P: hal::gpio::input::InputPin> + 'static>,
+        active: Level,
+        pull: Pull,
+    ) {
+        if let Some(button) = pin.and_then(|pin| Button::new(pin, active, pull)) {
+            // Cannot fail, `check_pins()` ensures there are at most `MAX_BUTTONS` buttons.
+            let _ = self.buttons.push(button);
+        }
+    }
+
+    /// Adds a row of the key matrix on `pin`, unless it was already taken.
+    #[cfg(feature = "input")]
+    pub fn add_keypad_row(
+        &mut self,
+        pin: Option<impl hal::peripheral::Peripheral<P: hal::gpio::output::OutputPin> + 'static>,
+    ) {
+        if let Some(pin) = pin {
+            // Cannot fail, `check_pins()` ensures there are at most `MAX_ROWS` rows.
+            let _ = self.keypad_rows.push(Output::new(pin, Level::High));
+        }
+    }
+
+    /// Adds a column of the key matrix on `pin`, unless it was already taken.
+    #[cfg(feature = "input")]
+    pub fn add_keypad_column(
+        &mut self,
+        pin: Option<impl hal::peripheral::Peripheral<P: hal::gpio::input::InputPin> + 'static>,
+    ) {
+        if let Some(pin) = pin {
+            // Cannot fail, `check_pins()` ensures there are at most `MAX_COLUMNS` columns.
+            let _ = self.keypad_columns.push(Input::new(pin, Pull::Up));
+        }
+    }
+}
+
+/// Checks the pins of the LEDs, buttons and key matrix of a board at compile time.
+///
+/// # Panics
+///
+/// Panics if there are too many LEDs, buttons, rows or columns, if a pin is used more than once,
+/// or if a pin is taken by the system.
+#[doc(hidden)]
+pub const fn check_pins(
+    leds: &[&str],
+    buttons: &[&str],
+    keypad_rows: &[&str],
+    keypad_columns: &[&str],
+) {
+    assert!(leds.len() <= MAX_LEDS, "too many LEDs for the board");
+    assert!(
+        buttons.len() <= MAX_BUTTONS,
+        "too many buttons for the board"
+    );
+    #[cfg(feature = "input")]
+    assert!(
+        keypad_rows.len() <= crate::input::MAX_ROWS,
+        "too many rows of the key matrix of the board"
+    );
+    #[cfg(feature = "input")]
+    assert!(
+        keypad_columns.len() <= crate::input::MAX_COLUMNS,
+        "too many columns of the key matrix of the board"
+    );
+
+    let groups = [leds, buttons, keypad_rows, keypad_columns];
+    let mut remaining_groups = groups.as_slice();
+    while let [group, other_groups @ ..] = remaining_groups {
+        let mut remaining = *group;
+        while let [pin, rest @ ..] = remaining {
+            check_pin(pin, rest);
+            let mut others = other_groups;
+            while let [other, more @ ..] = others {
+                check_pin(pin, other);
+                others = more;
+            }
+            remaining = rest;
+        }
+        remaining_groups = other_groups;
+    }
+}
+
+/// Checks that `pin` is not in `others` and is not taken by the system.
+const fn check_pin(pin: &str, others: &[&str]) {
+    if contains(others, pin) {
+        ariel_os_utils::env::const_panic::concat_panic!(
+            "pin `",
+            display: pin,
+            "` is used more than once by the board"
+        );
+    }
+    #[cfg(feature = "debug-uart")]
+    if contains(crate::debug_uart::PINS, pin) {
+        ariel_os_utils::env::const_panic::concat_panic!(
+            "pin `",
+            display: pin,
+            "` is used by the debug UART"
+        );
+    }
+}
+
+/// Returns whether `pins` contains `pin`.
+const fn contains(pins: &[&str], pin: &str) -> bool {
+    let mut remaining = pins;
+    while let [other, rest @ ..] = remaining {
+        if str_eq(other, pin) {
+            return true;
+        }
+        remaining = rest;
+    }
+    false
+}
+
+/// Returns whether `a` and `b` are equal, in const contexts.
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (a, b) {
+            ([], []) => return true,
+            ([a_first, a_rest @ ..], [b_first, b_rest @ ..]) if *a_first == *b_first => {
+                a = a_rest;
+                b = b_rest;
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// Defines the LEDs and the buttons of the board provided by Ariel OS.
+#[cfg(not(feature = "board-config-override"))]
+macro_rules! define_default_board {
+    (
+        leds: [$($led:ident: $led_active:ident),* $(,)?],
+        buttons: [$($button:ident: $button_active:ident, $pull:ident),* $(,)?] $(,)?
+    ) => {
+        const _: () = super::check_pins(
+            &[$(stringify!($led)),*],
+            &[$(stringify!($button)),*],
+            &[],
+            &[],
+        );
+
+        #[allow(unused_mut, unused_variables, reason = "boards without LEDs or buttons")]
+        pub(super) fn take(peripherals: &mut super::hal::OptionalPeripherals) -> super::Definition {
+            $crate::define_board!(
+                @take peripherals,
+                leds: [$($led: $led_active),*],
+                buttons: [$($button: $button_active, $pull),*],
+                rows: [],
+                columns: [],
+            )
+        }
+    };
+}
+
+#[cfg(not(feature = "board-config-override"))]
+mod definition {
+    cfg_if::cfg_if! {
+        if #[cfg(context = "nrf52840dk")] {
+            define_default_board! {
+                leds: [P0_13: Low, P0_14: Low, P0_15: Low, P0_16: Low],
+                buttons: [P0_11: Low, Up, P0_12: Low, Up, P0_24: Low, Up, P0_25: Low, Up],
+            }
+        } else if #[cfg(context = "nrf52dk")] {
+            define_default_board! {
+                leds: [P0_17: Low, P0_18: Low, P0_19: Low, P0_20: Low],
+                buttons: [P0_13: Low, Up, P0_14: Low, Up, P0_15: Low, Up, P0_16: Low, Up],
+            }
+        } else if #[cfg(context = "nrf5340dk")] {
+            define_default_board! {
+                leds: [P0_28: Low, P0_29: Low, P0_30: Low, P0_31: Low],
+                buttons: [P0_23: Low, Up, P0_24: Low, Up, P0_08: Low, Up, P0_09: Low, Up],
+            }
+        } else if #[cfg(context = "nrf9160dk-nrf9160")] {
+            define_default_board! {
+                leds: [P0_02: High, P0_03: High, P0_04: High, P0_05: High],
+                buttons: [P0_06: Low, Up, P0_07: Low, Up],
+            }
+        } else if #[cfg(context = "bbc-microbit-v2")] {
+            // The LEDs form a matrix, which is not supported.
+            define_default_board! {
+                leds: [],
+                buttons: [P0_14: Low, None, P0_23: Low, None],
+            }
+        } else if #[cfg(any(context = "rpi-pico", context = "rpi-pico2"))] {
+            define_default_board! {
+                leds: [PIN_25: High],
+                buttons: [],
+            }
+        } else if #[cfg(any(context = "st-nucleo-f401re", context = "st-nucleo-f411re"))] {
+            define_default_board! {
+                leds: [PA5: High],
+                buttons: [PC13: Low, None],
+            }
+        } else if #[cfg(context = "st-nucleo-h755zi-q")] {
+            define_default_board! {
+                leds: [PB0: High, PE1: High, PB14: High],
+                buttons: [PC13: High, None],
+            }
+        } else if #[cfg(context = "st-nucleo-wb55")] {
+            define_default_board! {
+                leds: [PB5: High, PB0: High, PB1: High],
+                buttons: [PC4: Low, Up, PD0: Low, Up, PD1: Low, Up],
+            }
+        } else if #[cfg(context = "st-b-l475e-iot01a")] {
+            define_default_board! {
+                leds: [PA5: High, PB14: High],
+                buttons: [PC13: Low, None],
+            }
+        } else {
+            define_default_board! {
+                leds: [],
+                buttons: [],
+            }
+        }
+    }
+}
@@ -12,6 +12,10 @@ static DEBUG_UART: embassy_sync::once_lock::OnceLock<
     >,
 > = embassy_sync::once_lock::OnceLock::new();
 
+/// Names of the pins taken by the debug UART.
+#[cfg_attr(not(feature = "board"), expect(dead_code))]
+pub(crate) const PINS: &[&str] = iot_lab::PINS;
+
 #[expect(clippy::missing_panics_doc)]
 pub fn init(peripherals: &mut crate::hal::OptionalPeripherals) {
     // TODO: this could later be replaced with our UART abstraction and app configuration.
@@ -59,6 +63,15 @@ fn write_debug_uart(buffer: &[u8]) -> Result<(), ariel_os_debug::Error> {
 mod iot_lab {
     //! UART configuration required for [IoT-LAB](https://www.iot-lab.info).
 
+    #[cfg(any(context = "nrf52dk", context = "nrf52840dk"))]
+    pub(super) const PINS: &[&str] = &["P0_08", "P0_06"];
+    #[cfg(any(context = "st-b-l475e-iot01a", context = "st-nucleo-wb55"))]
+    pub(super) const PINS: &[&str] = &["PB7", "PB6"];
+    #[cfg(context = "stm32u083c-dk")]
+    pub(super) const PINS: &[&str] = &["PA3", "PA2"];
+    #[cfg(context = "netduinoplus2-qemu")]
+    pub(super) const PINS: &[&str] = &["PA10", "PA9"];
+
     #[cfg(context = "nrf")]
     pub fn get_uart_driver(peripherals: &mut crate::hal::OptionalPeripherals) -> super::UartDriver {
         let mut config = embassy_nrf::uarte::Config::default();
@@ -106,6 +119,17 @@ mod iot_lab {
             )
         };
 
+        #[cfg(context = "netduinoplus2-qemu")]
+        let (p, uart_rx, uart_tx) = {
+            // QEMU connects this USART to its standard I/O, and ignores the baudrate.
+            config.baudrate = 115_200;
+            (
+                peripherals.USART1.take().unwrap(),
+                peripherals.PA10.take().unwrap(),
+                peripherals.PA9.take().unwrap(),
+            )
+        };
+
         embassy_stm32::usart::Uart::new_blocking(p, uart_rx, uart_tx, config).unwrap()
     }
 }
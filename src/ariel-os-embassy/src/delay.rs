@@ -0,0 +1,98 @@
+//! Provides busy-wait delays with a resolution finer than the system timer.
+//!
+//! Timers have the granularity of the system timer, which is often around 30 µs, and yield to
+//! other tasks. Bit-banged protocols and start-up sequences of sensors may instead require short
+//! delays of a few micro- or nanoseconds, which [`delay_us()`] and [`delay_ns()`] provide by
+//! spinning the core in a busy loop.
+//!
+//! The busy loop runs on the core clock, and is calibrated against the system timer at startup.
+//! The calibration only holds as long as the frequency of the core clock is unchanged:
+//! [`calibrate()`] needs to be called again after changing it.
+//!
+//! Delays last *at least* the requested time, and may last longer when the core is interrupted
+//! while waiting.
+
+use embassy_time::{Duration, Instant};
+use portable_atomic::{AtomicU32, Ordering};
+
+/// Minimum time over which the busy loop is measured when calibrating.
+///
+/// The system timer often ticks at 32 768 Hz, so that this results in an error of less than 1 %.
+const CALIBRATION_TIME: Duration = Duration::from_millis(5);
+
+/// Number of iterations of the busy loop in the first calibration measurement.
+const CALIBRATION_START_LOOPS: u32 = 1024;
+
+/// Iterations of the busy loop per second, 0 until calibrated.
+static LOOPS_PER_SEC: AtomicU32 = AtomicU32::new(0);
+
+/// Calibrates the busy loop against the system timer.
+///
+/// This is done at startup, and needs to be called again after the frequency of the core clock
+/// has changed. This blocks for about 10 ms, during which interrupts are disabled.
+pub fn calibrate() {
+    let mut loops = CALIBRATION_START_LOOPS;
+    let elapsed = loop {
+        let elapsed = critical_section::with(|_| measure(loops));
+        if elapsed >= CALIBRATION_TIME {
+            break elapsed;
+        }
+        match loops.checked_mul(2) {
+            Some(doubled) => loops = doubled,
+            None => break elapsed,
+        }
+    };
+
+    // The measured time is at most one tick shorter than the actual one, so that this rather
+    // overestimates the speed of the loop, and delays rather last longer.
+    let loops_per_sec = (u64::from(loops) * embassy_time::TICK_HZ)
+        .checked_div(elapsed.as_ticks())
+        .unwrap_or(u64::MAX);
+    let loops_per_sec = u32::try_from(loops_per_sec).unwrap_or(u32::MAX);
+    LOOPS_PER_SEC.store(loops_per_sec, Ordering::Relaxed);
+
+    ariel_os_debug::log::debug!("delay: {} loops per second", loops_per_sec);
+}
+
+/// Blocks for at least `us` microseconds.
+pub fn delay_us(us: u32) {
+    spin(loops_for(us, 1_000_000));
+}
+
+/// Blocks for at least `ns` nanoseconds.
+pub fn delay_ns(ns: u32) {
+    spin(loops_for(ns, 1_000_000_000));
+}
+
+/// Returns the number of loops needed to wait for `time` in units of `1 / units_per_sec` seconds.
+fn loops_for(time: u32, units_per_sec: u64) -> u32 {
+    let mut loops_per_sec = LOOPS_PER_SEC.load(Ordering::Relaxed);
+    if loops_per_sec == 0 {
+        calibrate();
+        loops_per_sec = LOOPS_PER_SEC.load(Ordering::Relaxed);
+    }
+    // Cannot overflow, as the product of two `u32`s fits into a `u64`.
+    let loops = (u64::from(time) * u64::from(loops_per_sec)).div_ceil(units_per_sec);
+    u32::try_from(loops).unwrap_or(u32::MAX)
+}
+
+/// Returns the time taken by running the busy loop for `loops` iterations.
+fn measure(loops: u32) -> Duration {
+    // Start at a tick boundary, so that the measurement is at most one tick short.
+    let previous = Instant::now();
+    let mut start = Instant::now();
+    while start == previous {
+        start = Instant::now();
+    }
+    spin(loops);
+    Instant::now().duration_since(start)
+}
+
+/// Runs the busy loop for `loops` iterations.
+// Never inlined, so that the loop is the same code wherever it is used, as calibrated.
+#[inline(never)]
+fn spin(loops: u32) {
+    for i in 0..loops {
+        core::hint::black_box(i);
+    }
+}
@@ -0,0 +1,60 @@
+//! Checks that the portable peripheral types implement the `embedded-hal` and
+//! `embedded-hal-async` traits, which drivers from the ecosystem are written against.
+//!
+//! On the host, the MCU-specific driver types are those of the dummy HAL, which implements the same
+//! traits as the actual HALs.
+
+use embedded_hal::digital::{InputPin, OutputPin, StatefulOutputPin};
+
+use crate::gpio;
+
+fn input_pin<T: InputPin>() {}
+
+fn output_pin<T: OutputPin + StatefulOutputPin>() {}
+
+#[cfg(feature = "external-interrupts")]
+fn wait<T: embedded_hal_async::digital::Wait>() {}
+
+#[cfg(feature = "i2c")]
+fn i2c<T: embedded_hal_async::i2c::I2c>() {}
+
+#[cfg(feature = "spi")]
+fn spi_bus<T: embedded_hal_async::spi::SpiBus>() {}
+
+#[cfg(feature = "spi")]
+fn spi_device<T: embedded_hal_async::spi::SpiDevice>() {}
+
+#[cfg(feature = "time")]
+fn delay<T: embedded_hal_async::delay::DelayNs>() {}
+
+#[test]
+fn gpio() {
+    input_pin::<gpio::Input>();
+    output_pin::<gpio::Output>();
+
+    #[cfg(feature = "external-interrupts")]
+    {
+        input_pin::<gpio::IntEnabledInput>();
+        wait::<gpio::IntEnabledInput>();
+    }
+}
+
+#[cfg(feature = "i2c")]
+#[test]
+fn i2c_controller() {
+    i2c::<crate::hal::i2c::controller::I2c>();
+    i2c::<crate::i2c::controller::I2cDevice>();
+}
+
+#[cfg(feature = "spi")]
+#[test]
+fn spi_main() {
+    spi_bus::<crate::hal::spi::main::Spi>();
+    spi_device::<crate::spi::main::SpiDevice>();
+}
+
+#[cfg(feature = "time")]
+#[test]
+fn time() {
+    delay::<crate::api::time::Delay>();
+}
@@ -3,7 +3,8 @@
 //! # Note
 //!
 //! This API does not currently provide a way of using the same GPIO pin as an input and an output
-//! alternatively.
+//! alternatively, apart from tearing down the GPIO with [`reclaim`](crate::reclaim) and building
+//! another one, which is not suitable for bit-banging.
 //! If you have a use case for this, especially if this is not regarding bit-banging, please open
 //! an issue on our repository.
 #![deny(missing_docs)]
@@ -19,6 +19,8 @@ pub use ariel_os_embassy_common::i2c::controller::*;
 /// Despite the driver interface being `async`, it may block during operations.
 /// However, it cannot block indefinitely as a timeout is implemented, either by leveraging
 /// I2C-specific hardware capabilities or through a generic software timeout.
+/// The timeout is set in the configuration of the MCU-specific driver, and operations that reach
+/// it fail with [`Error::Timeout`]; see [`recovery`] for freeing a bus held by a target device.
 // TODO: do we actually need a CriticalSectionRawMutex here?
 pub type I2cDevice = InnerI2cDevice<'static, CriticalSectionRawMutex, hal::i2c::controller::I2c>;
 
@@ -0,0 +1,436 @@
+//! Provides an input service, which scans keypad matrices, decodes rotary encoders and debounces
+//! buttons in background tasks.
+//!
+//! Inputs are handed over to the service, which reports what happens to them as [`Event`]s:
+//!
+//! ```ignore
+//! use ariel_os::input::{self, Event, Key};
+//!
+//! let encoder = input::add_encoder(input::RotaryEncoder::new(a, b))?;
+//! let button = input::add_button(input::Button::new(pin, Level::Low))?;
+//!
+//! loop {
+//!     match input::next_event().await {
+//!         Event::Rotated { encoder: id, steps } if id == encoder => volume += steps,
+//!         Event::Pressed(Key::Button(id)) if id == button => mute = !mute,
+//!         _ => {}
+//!     }
+//! }
+//! ```
+//!
+//! Keys of keypads and buttons are reported alike, when pressed and when released.
+//!
+//! # Configuration
+//!
+//! - `CONFIG_INPUT_MAX_KEYPADS` (default: 1): maximum number of keypads.
+//! - `CONFIG_INPUT_MAX_ENCODERS` (default: 2): maximum number of rotary encoders.
+//! - `CONFIG_INPUT_MAX_BUTTONS` (default: 4): maximum number of buttons.
+//! - `CONFIG_INPUT_QUEUE_SIZE` (default: 8): number of events queued until they are received;
+//!   the background tasks wait while the queue is full.
+#![deny(missing_docs)]
+
+use core::cell::Cell;
+
+use embassy_futures::select::select;
+use embassy_sync::{
+    blocking_mutex::{CriticalSectionMutex, raw::CriticalSectionRawMutex},
+    channel::Channel,
+};
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::{
+    asynch,
+    gpio::{Input, IntEnabledInput, Level, Output},
+};
+
+/// Maximum number of keypads, configured through the `CONFIG_INPUT_MAX_KEYPADS` environment
+/// variable.
+pub const MAX_KEYPADS: usize =
+    ariel_os_utils::usize_from_env_or!("CONFIG_INPUT_MAX_KEYPADS", 1, "maximum number of keypads");
+
+/// Maximum number of rotary encoders, configured through the `CONFIG_INPUT_MAX_ENCODERS`
+/// environment variable.
+pub const MAX_ENCODERS: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_INPUT_MAX_ENCODERS",
+    2,
+    "maximum number of rotary encoders"
+);
+
+/// Maximum number of buttons, configured through the `CONFIG_INPUT_MAX_BUTTONS` environment
+/// variable.
+pub const MAX_BUTTONS: usize =
+    ariel_os_utils::usize_from_env_or!("CONFIG_INPUT_MAX_BUTTONS", 4, "maximum number of buttons");
+
+/// Number of queued events, configured through the `CONFIG_INPUT_QUEUE_SIZE` environment
+/// variable.
+pub const QUEUE_SIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_INPUT_QUEUE_SIZE",
+    8,
+    "number of queued input events"
+);
+
+/// Maximum number of rows of keypads.
+pub const MAX_ROWS: usize = 8;
+/// Maximum number of columns of keypads.
+pub const MAX_COLUMNS: usize = 8;
+
+/// Period of the scans of keypads; keys need to be stable during two scans to be reported.
+const SCAN_PERIOD: Duration = Duration::from_millis(10);
+/// Delay between driving a row of a keypad and reading its columns.
+const SETTLE_TIME: Duration = Duration::from_micros(10);
+
+/// Duration a button needs to stay at a level before it is considered pressed or released.
+const DEBOUNCE: Duration = Duration::from_millis(20);
+
+/// Intervals between detents of rotary encoders under which their steps are multiplied, along
+/// with the multipliers, from the fastest.
+const ACCELERATION: [(Duration, i32); 2] = [
+    (Duration::from_millis(30), 5),
+    (Duration::from_millis(80), 2),
+];
+
+static EVENTS: Channel<CriticalSectionRawMutex, Event, QUEUE_SIZE> = Channel::new();
+
+static KEYPAD_COUNT: CriticalSectionMutex<Cell<u8>> = CriticalSectionMutex::new(Cell::new(0));
+static ENCODER_COUNT: CriticalSectionMutex<Cell<u8>> = CriticalSectionMutex::new(Cell::new(0));
+static BUTTON_COUNT: CriticalSectionMutex<Cell<u8>> = CriticalSectionMutex::new(Cell::new(0));
+
+/// An input event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A key was pressed.
+    Pressed(Key),
+    /// A key was released.
+    Released(Key),
+    /// A rotary encoder was turned.
+    Rotated {
+        /// Identifier of the encoder, returned by [`add_encoder()`].
+        encoder: u8,
+        /// Number of steps, clockwise if positive, which is multiplied when the encoder is
+        /// turned fast.
+        steps: i32,
+    },
+}
+
+/// A key, which is either a key of a keypad or a button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    /// A key of a keypad.
+    Keypad {
+        /// Identifier of the keypad, returned by [`add_keypad()`].
+        keypad: u8,
+        /// Row of the key, from `0`.
+        row: u8,
+        /// Column of the key, from `0`.
+        column: u8,
+    },
+    /// A button, whose identifier was returned by [`add_button()`].
+    Button(u8),
+}
+
+/// Waits for the next input event.
+pub async fn next_event() -> Event {
+    EVENTS.receive().await
+}
+
+/// Hands `keypad` over to the input service, and returns its identifier.
+///
+/// # Errors
+///
+/// Returns [`Error::TooManyInputs`] if [`MAX_KEYPADS`] keypads have been added already.
+pub fn add_keypad(keypad: Keypad) -> Result<u8, Error> {
+    let id = next_id(&KEYPAD_COUNT);
+    asynch::spawner()
+        .spawn(keypad_task(id, keypad))
+        .map_err(|_| Error::TooManyInputs)?;
+    Ok(id)
+}
+
+/// Hands `encoder` over to the input service, and returns its identifier.
+///
+/// # Errors
+///
+/// Returns [`Error::TooManyInputs`] if [`MAX_ENCODERS`] encoders have been added already.
+pub fn add_encoder(encoder: RotaryEncoder) -> Result<u8, Error> {
+    let id = next_id(&ENCODER_COUNT);
+    asynch::spawner()
+        .spawn(encoder_task(id, encoder))
+        .map_err(|_| Error::TooManyInputs)?;
+    Ok(id)
+}
+
+/// Hands `button` over to the input service, and returns its identifier.
+///
+/// # Errors
+///
+/// Returns [`Error::TooManyInputs`] if [`MAX_BUTTONS`] buttons have been added already.
+pub fn add_button(button: Button) -> Result<u8, Error> {
+    let id = next_id(&BUTTON_COUNT);
+    asynch::spawner()
+        .spawn(button_task(id, button))
+        .map_err(|_| Error::TooManyInputs)?;
+    Ok(id)
+}
+
+fn next_id(count: &CriticalSectionMutex<Cell<u8>>) -> u8 {
+    count.lock(|count| {
+        let id = count.get();
+        count.set(id.wrapping_add(1));
+        id
+    })
+}
+
+#[embassy_executor::task(pool_size = MAX_KEYPADS)]
+async fn keypad_task(id: u8, mut keypad: Keypad) -> ! {
+    keypad.run(id).await
+}
+
+#[embassy_executor::task(pool_size = MAX_ENCODERS)]
+async fn encoder_task(id: u8, mut encoder: RotaryEncoder) -> ! {
+    encoder.run(id).await
+}
+
+#[embassy_executor::task(pool_size = MAX_BUTTONS)]
+async fn button_task(id: u8, mut button: Button) -> ! {
+    button.run(id).await
+}
+
+/// A keypad matrix, whose keys connect its rows and columns.
+pub struct Keypad {
+    rows: heapless::Vec<Output, MAX_ROWS>,
+    columns: heapless::Vec<Input, MAX_COLUMNS>,
+}
+
+impl Keypad {
+    /// Returns a keypad whose rows are driven by `rows`, and whose columns are read by `columns`.
+    ///
+    /// The rows are driven low one after the other, and the columns need to be pulled up.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::TooManyPins`] if there are more than [`MAX_ROWS`] rows or
+    /// [`MAX_COLUMNS`] columns.
+    pub fn new(
+        rows: impl IntoIterator<Item = Output>,
+        columns: impl IntoIterator<Item = Input>,
+    ) -> Result<Self, Error> {
+        let mut keypad = Self {
+            rows: heapless::Vec::new(),
+            columns: heapless::Vec::new(),
+        };
+        for mut row in rows {
+            row.set_high();
+            keypad.rows.push(row).map_err(|_| Error::TooManyPins)?;
+        }
+        for column in columns {
+            keypad
+                .columns
+                .push(column)
+                .map_err(|_| Error::TooManyPins)?;
+        }
+        Ok(keypad)
+    }
+
+    /// Returns the pressed keys, as a bit field of columns per row.
+    async fn scan(&mut self) -> [u8; MAX_ROWS] {
+        let mut pressed = [0; MAX_ROWS];
+        for (row, pressed) in self.rows.iter_mut().zip(&mut pressed) {
+            row.set_low();
+            Timer::after(SETTLE_TIME).await;
+            for (column, input) in self.columns.iter().enumerate() {
+                if input.is_low() {
+                    *pressed |= 1 << column;
+                }
+            }
+            row.set_high();
+        }
+        pressed
+    }
+
+    async fn run(&mut self, id: u8) -> ! {
+        let mut reported = [0; MAX_ROWS];
+        let mut previous = [0; MAX_ROWS];
+        loop {
+            Timer::after(SCAN_PERIOD).await;
+            let pressed = self.scan().await;
+
+            for (row, ((&pressed, &previous), reported)) in
+                (0u8..).zip(pressed.iter().zip(&previous).zip(&mut reported))
+            {
+                // Keys that were stable during the last two scans.
+                let changed = !(pressed ^ previous) & (pressed ^ *reported);
+                for column in (0u8..).take(self.columns.len()) {
+                    if changed & (1 << column) == 0 {
+                        continue;
+                    }
+                    let key = Key::Keypad {
+                        keypad: id,
+                        row,
+                        column,
+                    };
+                    let event = if pressed & (1 << column) != 0 {
+                        Event::Pressed(key)
+                    } else {
+                        Event::Released(key)
+                    };
+                    EVENTS.send(event).await;
+                }
+                *reported ^= changed;
+            }
+            previous = pressed;
+        }
+    }
+}
+
+/// A rotary encoder with quadrature outputs.
+pub struct RotaryEncoder {
+    a: IntEnabledInput,
+    b: IntEnabledInput,
+    transitions_per_detent: i8,
+    acceleration: bool,
+}
+
+impl RotaryEncoder {
+    /// Returns a rotary encoder whose outputs are read by `a` and `b`, which is turned clockwise
+    /// when `a` leads.
+    ///
+    /// By default, the encoder has four transitions of its outputs per detent, and its steps are
+    /// multiplied when it is turned fast.
+    #[must_use]
+    pub fn new(a: IntEnabledInput, b: IntEnabledInput) -> Self {
+        Self {
+            a,
+            b,
+            transitions_per_detent: 4,
+            acceleration: true,
+        }
+    }
+
+    /// Sets the number of transitions of the outputs per detent, which is `1`, `2` or `4`
+    /// depending on the encoder.
+    #[must_use]
+    pub fn with_transitions_per_detent(self, transitions_per_detent: u8) -> Self {
+        Self {
+            transitions_per_detent: i8::try_from(transitions_per_detent.clamp(1, 4)).unwrap_or(4),
+            ..self
+        }
+    }
+
+    /// Enables the multiplication of the steps when the encoder is turned fast, if `acceleration`
+    /// is `true`.
+    #[must_use]
+    pub fn with_acceleration(self, acceleration: bool) -> Self {
+        Self {
+            acceleration,
+            ..self
+        }
+    }
+
+    fn state(&self) -> (bool, bool) {
+        (self.a.is_high(), self.b.is_high())
+    }
+
+    async fn run(&mut self, id: u8) -> ! {
+        let mut state = self.state();
+        let mut transitions = 0;
+        let mut last_detent = None;
+        loop {
+            select(self.a.wait_for_any_edge(), self.b.wait_for_any_edge()).await;
+            let new_state = self.state();
+            // Bouncing contacts go back and forth between adjacent states, which cancels out, and
+            // missed transitions are ignored.
+            transitions += match (state, new_state) {
+                ((false, false), (true, false))
+                | ((true, false), (true, true))
+                | ((true, true), (false, true))
+                | ((false, true), (false, false)) => 1,
+                ((true, false), (false, false))
+                | ((true, true), (true, false))
+                | ((false, true), (true, true))
+                | ((false, false), (false, true)) => -1,
+                _ => 0,
+            };
+            state = new_state;
+
+            if transitions.abs() < self.transitions_per_detent {
+                continue;
+            }
+            let direction = i32::from(transitions.signum());
+            transitions = 0;
+
+            let now = Instant::now();
+            let multiplier = match last_detent {
+                Some(last_detent) if self.acceleration => {
+                    let interval = now.duration_since(last_detent);
+                    ACCELERATION
+                        .iter()
+                        .find(|(threshold, _)| interval < *threshold)
+                        .map_or(1, |(_, multiplier)| *multiplier)
+                }
+                _ => 1,
+            };
+            last_detent = Some(now);
+
+            EVENTS
+                .send(Event::Rotated {
+                    encoder: id,
+                    steps: direction * multiplier,
+                })
+                .await;
+        }
+    }
+}
+
+/// A button, connected to a GPIO.
+pub struct Button {
+    input: IntEnabledInput,
+    active: Level,
+}
+
+impl Button {
+    /// Returns a button read by `input`, which is at `active` level when the button is pressed.
+    #[must_use]
+    pub fn new(input: IntEnabledInput, active: Level) -> Self {
+        Self { input, active }
+    }
+
+    async fn run(&mut self, id: u8) -> ! {
+        let mut pressed = self.input.get_level() == self.active;
+        loop {
+            self.input.wait_for_any_edge().await;
+            Timer::after(DEBOUNCE).await;
+            if (self.input.get_level() == self.active) == pressed {
+                continue;
+            }
+            pressed = !pressed;
+
+            let key = Key::Button(id);
+            let event = if pressed {
+                Event::Pressed(key)
+            } else {
+                Event::Released(key)
+            };
+            EVENTS.send(event).await;
+        }
+    }
+}
+
+/// Input-related errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The maximum number of inputs of this kind has been reached.
+    TooManyInputs,
+    /// The keypad has too many rows or columns.
+    TooManyPins,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::TooManyInputs => write!(f, "too many inputs"),
+            Self::TooManyPins => write!(f, "too many rows or columns"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
@@ -0,0 +1,112 @@
+//! Provides interrupt handlers of the application that run outside of the executor, for loops
+//! that cannot tolerate its jitter, eg. motor control or metering.
+//!
+//! [`fast_interrupt!`] binds a handler to an interrupt of the MCU, at the highest priority:
+//!
+//! ```ignore
+//! use ariel_os::interrupt::{FastInterrupt as _, Signal, fast_interrupt};
+//!
+//! static POSITION: Signal<u32> = Signal::new();
+//!
+//! fast_interrupt!(MotorLoop, TIMER1, || {
+//!     let position = read_encoder();
+//!     drive_motor(position);
+//!     // Hands the position off to a task.
+//!     POSITION.signal(position);
+//! });
+//!
+//! // Once the timer is set up:
+//! MotorLoop::enable();
+//!
+//! // In a task:
+//! let position = POSITION.wait().await;
+//! ```
+//!
+//! # Latency
+//!
+//! The handler is called directly from the vector table, and is never delayed by the executor,
+//! by tasks or by threads. It starts after the interrupt latency of the core (12 cycles on
+//! Cortex-M3, M4 and M33, 15 cycles on Cortex-M0+, plus the wait states of the flash memory),
+//! delayed by at most:
+//!
+//! - the longest critical section of the system, as critical sections mask all interrupts,
+//! - the handler of another interrupt at the same priority, as these do not preempt each other;
+//!   the interrupts of drivers and of the executor must thus be set to lower priorities (greater
+//!   numbers) to not delay the handler.
+//!
+//! Handlers must be short and must not block: they run before anything else on the core.
+//!
+//! # Handing off to tasks
+//!
+//! Handlers hand results off to tasks through [`Signal`]s, for the latest value, through
+//! [`Channel`]s, for a queue of values, or through the rings of the `ring` module, for streams of
+//! bytes or frames. Signals and channels take a critical section of a few instructions.
+
+use crate::hal::interrupt::{Interrupt, InterruptExt as _, Priority};
+
+pub use crate::fast_interrupt;
+
+/// A [`Signal`](embassy_sync::signal::Signal) through which handlers hand the latest value off
+/// to a task.
+pub type Signal<T> =
+    embassy_sync::signal::Signal<embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex, T>;
+
+/// A [`Channel`](embassy_sync::channel::Channel) through which handlers hand up to `N` values off
+/// to tasks.
+pub type Channel<T, const N: usize> = embassy_sync::channel::Channel<
+    embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
+    T,
+    N,
+>;
+
+/// An interrupt bound to a handler with [`fast_interrupt!`].
+pub trait FastInterrupt {
+    /// The interrupt of the MCU.
+    const IRQ: Interrupt;
+
+    /// Enables the interrupt, at the highest priority.
+    fn enable() {
+        Self::IRQ.set_priority(Priority::P0);
+        // SAFETY: the critical sections of the system mask all interrupts instead of relying on
+        // interrupts being masked in the NVIC, and the interrupt has a handler bound.
+        unsafe { Self::IRQ.enable() };
+    }
+
+    /// Disables the interrupt; it stays pending if it was.
+    fn disable() {
+        Self::IRQ.disable();
+    }
+
+    /// Makes the interrupt pending, so that the handler is called as soon as it is enabled.
+    fn pend() {
+        Self::IRQ.pend();
+    }
+}
+
+/// Binds `$handler`, a closure capturing nothing, to the interrupt `$irq` of the MCU.
+///
+/// `$name` is declared as a [`FastInterrupt`], through which the interrupt is then enabled.
+/// Binding an interrupt also used by a driver fails at link time.
+///
+/// See the [module documentation](crate::interrupt) for details.
+#[macro_export]
+macro_rules! fast_interrupt {
+    ($(#[$attr:meta])* $vis:vis $name:ident, $irq:ident, $handler:expr) => {
+        $(#[$attr])*
+        #[derive(Debug)]
+        $vis struct $name;
+
+        impl $crate::interrupt::FastInterrupt for $name {
+            const IRQ: $crate::hal::interrupt::Interrupt = $crate::hal::interrupt::Interrupt::$irq;
+        }
+
+        #[allow(non_snake_case)]
+        // SAFETY: the symbol is the vector of the interrupt, which is defined only once as
+        // otherwise linking fails.
+        #[unsafe(no_mangle)]
+        unsafe extern "C" fn $irq() {
+            const HANDLER: fn() = $handler;
+            HANDLER();
+        }
+    };
+}
@@ -7,18 +7,33 @@ pub mod gpio;
 
 pub use ariel_os_hal as hal;
 
+#[cfg(feature = "board")]
+pub mod board;
+
 #[cfg(feature = "executor-thread")]
 use ariel_os_embassy_common::executor_thread;
 
 #[cfg(feature = "debug-uart")]
 pub mod debug_uart;
 
+#[cfg(feature = "delay")]
+pub mod delay;
+
 #[cfg(feature = "i2c")]
 pub mod i2c;
 
+#[cfg(feature = "input")]
+pub mod input;
+
+#[cfg(all(feature = "fast-interrupts", context = "cortex-m"))]
+pub mod interrupt;
+
 #[cfg(feature = "spi")]
 pub mod spi;
 
+#[cfg(feature = "time")]
+pub mod time;
+
 #[cfg(feature = "usb")]
 pub mod usb;
 
@@ -40,7 +55,10 @@ use linkme::distributed_slice;
 
 // All items of this module are re-exported at the root of `ariel_os`.
 pub mod api {
-    pub use crate::{EMBASSY_TASKS, asynch, delegate, gpio, hal};
+    pub use crate::{
+        EMBASSY_TASKS, INIT_HOOKS, InitHook, TEST_DONE, TESTS, TestCase, asynch, delegate, gpio,
+        hal, reclaim,
+    };
 
     pub mod cell {
         //! Shareable containers.
@@ -48,22 +66,40 @@ pub mod api {
         pub use static_cell::{ConstStaticCell, StaticCell};
     }
 
-    #[cfg(feature = "time")]
-    pub mod time {
-        //! Provides time-related facilities.
-        // NOTE: we may want to re-export more items in the future, but not re-export the whole
-        // crate.
-        pub use embassy_time::{Delay, Duration, Instant, TICK_HZ, Timer};
+    pub mod dma {
+        //! Buffers suitable for DMA transfers.
+        //!
+        //! See [`dma_buffer!`] for declaring a buffer in RAM accessible to the DMA engine, and
+        //! [`clean()`] and [`invalidate()`] for keeping buffers coherent with the data cache.
+        //! In debug builds, buffers drivers no longer use are protected once
+        //! [deactivated](DmaBuffer::deactivate).
+
+        pub use ariel_os_embassy_common::{
+            dma::{
+                ALIGNMENT, DmaBuffer, InactiveDmaBuffer, MisalignedBufferError, clean, invalidate,
+            },
+            dma_buffer,
+        };
     }
 
     #[cfg(feature = "ble")]
     pub use crate::ble;
+    #[cfg(feature = "board")]
+    pub use crate::board;
+    #[cfg(feature = "delay")]
+    pub use crate::delay;
     #[cfg(feature = "i2c")]
     pub use crate::i2c;
+    #[cfg(feature = "input")]
+    pub use crate::input;
+    #[cfg(all(feature = "fast-interrupts", context = "cortex-m"))]
+    pub use crate::interrupt;
     #[cfg(feature = "net")]
     pub use crate::net;
     #[cfg(feature = "spi")]
     pub use crate::spi;
+    #[cfg(feature = "time")]
+    pub use crate::time;
     #[cfg(feature = "usb")]
     pub use crate::usb;
 }
@@ -109,6 +145,13 @@ pub use net::NetworkStack;
 pub mod asynch;
 pub mod cell;
 pub mod delegate;
+pub mod reclaim;
+mod test_runner;
+
+#[cfg(test)]
+mod embedded_hal_tests;
+
+pub use test_runner::{TEST_DONE, TESTS, TestCase};
 
 #[cfg(feature = "executor-thread")]
 pub mod thread_executor;
@@ -119,6 +162,27 @@ pub type Task = fn(asynch::Spawner, &mut hal::OptionalPeripherals);
 #[distributed_slice]
 pub static EMBASSY_TASKS: [Task] = [..];
 
+/// A function registered with the `init` macro, run before tasks are started.
+#[doc(hidden)]
+pub struct InitHook {
+    /// Hooks with a lower priority are run first.
+    pub priority: u8,
+    pub func: fn(&mut hal::OptionalPeripherals),
+}
+
+#[doc(hidden)]
+#[distributed_slice]
+pub static INIT_HOOKS: [InitHook] = [..];
+
+/// Runs the registered init hooks, in order of increasing priority.
+fn run_init_hooks(peripherals: &mut hal::OptionalPeripherals) {
+    for priority in u8::MIN..=u8::MAX {
+        for hook in INIT_HOOKS.iter().filter(|hook| hook.priority == priority) {
+            (hook.func)(peripherals);
+        }
+    }
+}
+
 #[cfg(not(any(
     feature = "executor-interrupt",
     feature = "executor-none",
@@ -181,6 +245,50 @@ fn init() {
         .run(|spawner| spawner.must_spawn(init_task(p)));
 }
 
+#[cfg(feature = "sensors-calibration")]
+#[embassy_executor::task]
+async fn sensors_calibration_task() -> ! {
+    ariel_os_sensors::calibration::run().await
+}
+
+#[cfg(feature = "sensors-sampling")]
+#[embassy_executor::task]
+async fn sensors_sampling_task() -> ! {
+    ariel_os_sensors::sampling::SAMPLER.run().await
+}
+
+#[cfg(feature = "storage-write-behind")]
+#[embassy_executor::task]
+async fn storage_flush_task() -> ! {
+    let mut ticker = embassy_time::Ticker::every(embassy_time::Duration::from_secs(
+        ariel_os_storage::WRITE_BEHIND_FLUSH_INTERVAL_SECS,
+    ));
+    loop {
+        ticker.next().await;
+        if ariel_os_storage::flush().await.is_err() {
+            ariel_os_debug::log::warn!("storage: flushing failed");
+        }
+    }
+}
+
+#[cfg(all(
+    feature = "hwrng",
+    feature = "csprng",
+    feature = "time",
+    not(feature = "random-seed")
+))]
+#[embassy_executor::task]
+async fn hwrng_reseed_task() -> ! {
+    let mut ticker = embassy_time::Ticker::every(embassy_time::Duration::from_secs(
+        ariel_os_random::HWRNG_RESEED_INTERVAL_SECS,
+    ));
+    loop {
+        ticker.next().await;
+        // Failures are handled according to the hardware RNG failure policy.
+        let _ = ariel_os_random::reseed_from_hwrng();
+    }
+}
+
 #[embassy_executor::task]
 #[allow(clippy::too_many_lines)]
 async fn init_task(mut peripherals: hal::OptionalPeripherals) {
@@ -192,6 +300,14 @@ async fn init_task(mut peripherals: hal::OptionalPeripherals) {
 
     debug!("ariel-os-embassy::init_task()");
 
+    // Map the external flash before anything can call the functions placed there.
+    #[cfg(feature = "xip")]
+    hal::xip::init(&mut peripherals);
+
+    // Calibrate before anything can use the delays.
+    #[cfg(feature = "delay")]
+    delay::calibrate();
+
     #[cfg(all(context = "stm32", feature = "external-interrupts"))]
     hal::extint_registry::EXTINT_REGISTRY.init(&mut peripherals);
 
@@ -201,8 +317,21 @@ async fn init_task(mut peripherals: hal::OptionalPeripherals) {
     #[cfg(feature = "spi")]
     hal::spi::init(&mut peripherals);
 
-    #[cfg(feature = "hwrng")]
+    // Take the pins of the board before tasks can take them.
+    #[cfg(feature = "board")]
+    board::init(&mut peripherals);
+
+    #[cfg(feature = "random-seed")]
+    ariel_os_random::construct_seeded_rng();
+    #[cfg(all(feature = "hwrng", not(feature = "random-seed")))]
     hal::hwrng::construct_rng(&mut peripherals);
+    #[cfg(all(
+        feature = "hwrng",
+        feature = "csprng",
+        feature = "time",
+        not(feature = "random-seed")
+    ))]
+    spawner.spawn(hwrng_reseed_task()).unwrap();
     // Clock startup and entropy collection may lend themselves to parallelization, provided that
     // doesn't impact runtime RAM or flash use.
 
@@ -211,6 +340,17 @@ async fn init_task(mut peripherals: hal::OptionalPeripherals) {
     #[cfg(feature = "storage")]
     embassy_futures::block_on(ariel_os_storage::init(&mut peripherals));
 
+    #[cfg(feature = "storage-write-behind")]
+    spawner.spawn(storage_flush_task()).unwrap();
+
+    // Count the boot before anything else can crash, so that an update that keeps crashing gets
+    // reverted.
+    #[cfg(feature = "update")]
+    embassy_futures::block_on(ariel_os_update::record_boot());
+
+    #[cfg(feature = "storage")]
+    embassy_futures::block_on(time::record_boot_session());
+
     #[cfg(all(feature = "usb", context = "nrf"))]
     hal::usb::init();
 
@@ -222,6 +362,8 @@ async fn init_task(mut peripherals: hal::OptionalPeripherals) {
     #[cfg(feature = "usb")]
     let usb_peripherals = hal::usb::Peripherals::new(&mut peripherals);
 
+    run_init_hooks(&mut peripherals);
+
     // Tasks have to be started before driver initializations so that the tasks are able to
     // configure the drivers using hooks.
     for task in EMBASSY_TASKS {
@@ -268,8 +410,12 @@ async fn init_task(mut peripherals: hal::OptionalPeripherals) {
         };
         use static_cell::StaticCell;
 
+        use crate::usb::ethernet::{RX_BUFFER_COUNT, TX_BUFFER_COUNT};
+
         static CDC_ECM_STATE: StaticCell<CdcNcmState> = StaticCell::new();
-        static NET_STATE: StaticCell<NetState<{ net::ETHERNET_MTU }, 4, 4>> = StaticCell::new();
+        static NET_STATE: StaticCell<
+            NetState<{ net::ETHERNET_MTU }, RX_BUFFER_COUNT, TX_BUFFER_COUNT>,
+        > = StaticCell::new();
 
         // Host's MAC addr. This is the MAC the host "thinks" its USB-to-ethernet adapter has.
         let host_mac_addr = crate::hal::identity::DeviceId::get()
@@ -288,10 +434,11 @@ async fn init_task(mut peripherals: hal::OptionalPeripherals) {
             .map(|d| d.interface_eui48(0).0)
             .unwrap_or([0xCA, 0xCC, 0xCC, 0xCC, 0xCC, 0xCC]);
 
-        let (runner, device) = usb_cdc_ecm.into_embassy_net_device::<{ net::ETHERNET_MTU }, 4, 4>(
-            NET_STATE.init_with(NetState::new),
-            our_mac_addr,
-        );
+        let (runner, device) = usb_cdc_ecm
+            .into_embassy_net_device::<{ net::ETHERNET_MTU }, RX_BUFFER_COUNT, TX_BUFFER_COUNT>(
+                NET_STATE.init_with(NetState::new),
+                our_mac_addr,
+            );
 
         spawner.spawn(usb::ethernet::usb_ncm_task(runner)).unwrap();
 
@@ -319,6 +466,9 @@ async fn init_task(mut peripherals: hal::OptionalPeripherals) {
         (net_device, control)
     };
 
+    #[cfg(feature = "wifi-esp-eap")]
+    spawner.spawn(wifi::eap_config_task()).unwrap();
+
     #[cfg(feature = "wifi-esp")]
     let device = hal::wifi::esp_wifi::init(&mut peripherals, spawner);
 
@@ -348,7 +498,10 @@ async fn init_task(mut peripherals: hal::OptionalPeripherals) {
         // condition list up to date.
         let device: NetworkDevice = net::new_dummy();
 
-        let config = net::config();
+        #[cfg_attr(not(feature = "network-lease-cache"), expect(unused_mut))]
+        let mut config = net::config();
+        #[cfg(feature = "network-lease-cache")]
+        let lease_start = net::lease::apply(&mut config).await;
 
         let seed = net::unique_seed();
         debug!("Network stack seed: {:#x}", seed);
@@ -363,6 +516,11 @@ async fn init_task(mut peripherals: hal::OptionalPeripherals) {
 
         spawner.spawn(net::net_task(runner)).unwrap();
 
+        #[cfg(feature = "network-lease-cache")]
+        if let Some(start) = lease_start {
+            spawner.spawn(net::lease::lease_task(stack, start)).unwrap();
+        }
+
         if crate::net::STACK
             .init(SameExecutorCell::new(stack, spawner))
             .is_err()
@@ -376,11 +534,21 @@ async fn init_task(mut peripherals: hal::OptionalPeripherals) {
         hal::cyw43::join(control).await;
     };
 
-    // mark used
-    let _ = peripherals;
+    #[cfg(feature = "sensors-calibration")]
+    spawner.spawn(sensors_calibration_task()).unwrap();
+
+    #[cfg(feature = "sensors-sampling")]
+    spawner.spawn(sensors_sampling_task()).unwrap();
 
     debug!("ariel-os-embassy::init_task() done");
 
     #[cfg(feature = "threading")]
     ariel_os_threads::events::THREAD_START_EVENT.set();
+
+    // Run the tests once the system is fully initialized, with the peripherals left over.
+    #[cfg(feature = "test-runner")]
+    test_runner::run(spawner, &mut peripherals).await;
+
+    // mark used
+    let _ = peripherals;
 }
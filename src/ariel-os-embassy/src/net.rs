@@ -6,6 +6,9 @@
 
 #![deny(missing_docs)]
 
+#[cfg(feature = "network-lease-cache")]
+pub(crate) mod lease;
+
 use embassy_net::{Runner, Stack};
 use embassy_sync::once_lock::OnceLock;
 
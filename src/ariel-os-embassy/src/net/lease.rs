@@ -0,0 +1,148 @@
+//! Persists the IPv4 configuration obtained through DHCP, for fast-boot networking.
+//!
+//! When a lease was persisted during a previous boot, the network stack starts with its
+//! configuration right away, so that applications can send packets without waiting for DHCP, eg.
+//! after waking up from deep sleep. The configuration is then re-validated through DHCP in the
+//! background, once the link is up and the delay set by `CONFIG_NET_LEASE_REVALIDATION_DELAY_MS`
+//! (default: 2000) has elapsed; the stack has no IPv4 address during that DHCP exchange, which
+//! usually yields the same address again. Each configuration obtained through DHCP, including the
+//! DNS servers, is persisted as the last known good one.
+//!
+//! The neighbor cache of the network stack is not accessible through [`embassy_net`], and is thus
+//! not persisted.
+
+use embassy_net::{ConfigV4, DhcpConfig, Ipv4Address, Ipv4Cidr, Stack, StaticConfigV4};
+use serde::{Deserialize, Serialize};
+
+use ariel_os_debug::log::{debug, info, warn};
+
+/// Storage key under which the last known good IPv4 configuration is persisted.
+const LEASE_KEY: &str = "ariel-os-embassy.network-lease";
+
+/// Time between the link coming up and the re-validation of a persisted lease.
+const REVALIDATION_DELAY: embassy_time::Duration =
+    embassy_time::Duration::from_millis(ariel_os_utils::u64_from_env_or!(
+        "CONFIG_NET_LEASE_REVALIDATION_DELAY_MS",
+        2000,
+        "time between the link coming up and the re-validation of a persisted DHCP lease"
+    ));
+
+/// IPv4 configuration, as persisted in storage.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Lease {
+    address: [u8; 4],
+    prefix_len: u8,
+    gateway: Option<[u8; 4]>,
+    dns_servers: [Option<[u8; 4]>; 3],
+}
+
+impl From<&StaticConfigV4> for Lease {
+    fn from(config: &StaticConfigV4) -> Self {
+        let mut dns_servers = [None; 3];
+        for (slot, server) in dns_servers.iter_mut().zip(&config.dns_servers) {
+            *slot = Some(server.octets());
+        }
+        Self {
+            address: config.address.address().octets(),
+            prefix_len: config.address.prefix_len(),
+            gateway: config.gateway.map(|gateway| gateway.octets()),
+            dns_servers,
+        }
+    }
+}
+
+impl From<&Lease> for StaticConfigV4 {
+    fn from(lease: &Lease) -> Self {
+        let mut dns_servers = heapless::Vec::new();
+        for server in lease.dns_servers.iter().flatten() {
+            // There are as many slots in the lease as in the configuration.
+            let _ = dns_servers.push(Ipv4Address::from(*server));
+        }
+        Self {
+            address: Ipv4Cidr::new(Ipv4Address::from(lease.address), lease.prefix_len),
+            gateway: lease.gateway.map(Ipv4Address::from),
+            dns_servers,
+        }
+    }
+}
+
+/// How the network stack was started, as returned by [`apply()`].
+pub(crate) enum Start {
+    /// Through DHCP.
+    Dhcp,
+    /// With the persisted lease, which is re-validated with this DHCP configuration.
+    Persisted(DhcpConfig),
+}
+
+/// Replaces the DHCP configuration of `config`, if any, by the persisted lease if any.
+///
+/// Returns `None` if `config` does not use DHCP, in which case there is no lease to persist.
+pub(crate) async fn apply(config: &mut embassy_net::Config) -> Option<Start> {
+    let ConfigV4::Dhcp(dhcp) = &config.ipv4 else {
+        return None;
+    };
+    let Ok(Some(lease)) = ariel_os_storage::get::<Lease>(LEASE_KEY).await else {
+        return Some(Start::Dhcp);
+    };
+    let dhcp = dhcp.clone();
+    let lease = StaticConfigV4::from(&lease);
+    info!(
+        "network: starting with the persisted lease of {}",
+        lease.address
+    );
+    config.ipv4 = ConfigV4::Static(lease);
+    Some(Start::Persisted(dhcp))
+}
+
+/// Re-validates the persisted lease through DHCP if the stack started with it, then persists
+/// each configuration obtained through DHCP.
+#[embassy_executor::task]
+pub(crate) async fn lease_task(stack: Stack<'static>, start: Start) -> ! {
+    let mut persisted = None;
+    if let Start::Persisted(dhcp) = start {
+        persisted = stack.config_v4().as_ref().map(Lease::from);
+        stack.wait_link_up().await;
+        embassy_time::Timer::after(REVALIDATION_DELAY).await;
+        debug!("network: re-validating the persisted lease");
+        stack.set_config_v4(ConfigV4::Dhcp(dhcp));
+    }
+
+    loop {
+        stack.wait_config_up().await;
+        if let Some(config) = stack.config_v4() {
+            let lease = Lease::from(&config);
+            if persisted.as_ref() != Some(&lease) {
+                if ariel_os_storage::insert(LEASE_KEY, lease.clone())
+                    .await
+                    .is_ok()
+                {
+                    debug!("network: persisted the lease of {}", config.address);
+                    persisted = Some(lease);
+                } else {
+                    warn!("network: persisting the lease failed");
+                }
+            }
+        }
+        stack.wait_config_down().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut dns_servers = heapless::Vec::new();
+        let _ = dns_servers.push(Ipv4Address::new(192, 168, 1, 1));
+        let config = StaticConfigV4 {
+            address: Ipv4Cidr::new(Ipv4Address::new(192, 168, 1, 42), 24),
+            gateway: Some(Ipv4Address::new(192, 168, 1, 1)),
+            dns_servers,
+        };
+
+        let lease = Lease::from(&config);
+        assert_eq!(lease.dns_servers, [Some([192, 168, 1, 1]), None, None]);
+        assert_eq!(StaticConfigV4::from(&lease), config);
+    }
+}
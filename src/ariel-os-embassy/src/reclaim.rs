@@ -0,0 +1,148 @@
+//! Provides drivers whose peripherals can be reclaimed, to reconfigure them at runtime.
+//!
+//! Drivers otherwise keep the peripherals they are given for the whole execution of the program,
+//! which prevents using the same peripherals for different purposes over time, e.g., reading a
+//! bootstrapping level on a pin through a GPIO input before using that pin with a UART.
+//! Reclaiming the peripherals of a [`Reclaimable`] driver tears down the driver, after which the
+//! peripherals can be used to build another driver:
+//!
+//! ```ignore
+//! use ariel_os::{gpio::{Input, Level, Output, Pull}, reclaim::Reclaimable};
+//!
+//! // SAFETY: the closure only uses the peripherals to build the driver.
+//! let input = unsafe {
+//!     Reclaimable::new(peripherals, |peripherals: pins::Peripherals| {
+//!         Input::new(peripherals.pin, Pull::Up)
+//!     })
+//! };
+//! let level = input.get_level();
+//!
+//! // SAFETY: the driver was not moved out of `input`.
+//! let peripherals = unsafe { input.reclaim() };
+//! let output = Output::new(peripherals.pin, Level::Low);
+//! ```
+//!
+//! The peripherals must be defined with the
+//! [`define_peripherals!`](crate::hal::define_peripherals!) or
+//! [`group_peripherals!`](crate::hal::group_peripherals!) macros.
+#![deny(missing_docs)]
+
+use core::ops::{Deref, DerefMut};
+
+use crate::hal::ClonePeripherals;
+
+/// A driver whose peripherals can be reclaimed.
+///
+/// Dereferences to the driver. As the mutable reference allows moving the driver out, eg. with
+/// [`core::mem::replace()`], [`Reclaimable::reclaim()`] is unsafe.
+pub struct Reclaimable<P, D> {
+    // Declared first so that it is dropped first.
+    driver: D,
+    peripherals: P,
+}
+
+impl<P: ClonePeripherals, D> Reclaimable<P, D> {
+    /// Builds a driver with `build`, from `peripherals`.
+    ///
+    /// # Safety
+    ///
+    /// `build` must only use the peripherals it is given to build the driver it returns, and must
+    /// not keep them anywhere else: they are handed out again by [`Reclaimable::reclaim()`].
+    pub unsafe fn new(peripherals: P, build: impl FnOnce(P) -> D) -> Self {
+        // SAFETY: the copies are only used by the driver, as guaranteed by the caller, which is
+        // torn down before the peripherals are handed out again.
+        let copies = unsafe { peripherals.clone_unchecked() };
+
+        Self {
+            driver: build(copies),
+            peripherals,
+        }
+    }
+
+    /// Tears down the driver and returns its peripherals.
+    ///
+    /// # Safety
+    ///
+    /// The driver must be the one built in [`Reclaimable::new()`]: it must not have been moved out
+    /// through the mutable reference, eg. with [`core::mem::replace()`] or [`core::mem::swap()`],
+    /// as it would then keep using the peripherals that are handed out.
+    pub unsafe fn reclaim(self) -> P {
+        let Self {
+            driver,
+            peripherals,
+        } = self;
+
+        drop(driver);
+
+        peripherals
+    }
+}
+
+impl<P, D> Deref for Reclaimable<P, D> {
+    type Target = D;
+
+    fn deref(&self) -> &Self::Target {
+        &self.driver
+    }
+}
+
+impl<P, D> DerefMut for Reclaimable<P, D> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.driver
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+
+    /// Whether the peripherals are in use by a driver.
+    static IN_USE: AtomicBool = AtomicBool::new(false);
+
+    struct Peripherals;
+
+    impl ClonePeripherals for Peripherals {
+        unsafe fn clone_unchecked(&self) -> Self {
+            Self
+        }
+    }
+
+    struct Driver {
+        _peripherals: Peripherals,
+    }
+
+    impl Driver {
+        fn new(peripherals: Peripherals) -> Self {
+            assert!(!IN_USE.swap(true, Ordering::Relaxed));
+            Self {
+                _peripherals: peripherals,
+            }
+        }
+    }
+
+    impl Drop for Driver {
+        fn drop(&mut self) {
+            IN_USE.store(false, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn driver_is_dropped_before_reclaiming() {
+        // SAFETY: the closure only uses the peripherals to build the driver.
+        let driver = unsafe { Reclaimable::new(Peripherals, Driver::new) };
+        assert!(IN_USE.load(Ordering::Relaxed));
+
+        // SAFETY: the driver was not moved out.
+        let peripherals = unsafe { driver.reclaim() };
+        assert!(!IN_USE.load(Ordering::Relaxed));
+
+        // The peripherals can be used for another driver.
+        // SAFETY: the closure only uses the peripherals to build the driver.
+        let driver = unsafe { Reclaimable::new(peripherals, Driver::new) };
+        // SAFETY: the driver was not moved out.
+        let _peripherals = unsafe { driver.reclaim() };
+    }
+}
@@ -6,6 +6,7 @@ use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use crate::{gpio, hal};
 
 pub use ariel_os_embassy_common::spi::main::*;
+pub use embedded_hal_async::spi::Operation;
 
 /// An SPI driver implementing [`embedded_hal_async::spi::SpiDevice`].
 ///
@@ -17,6 +18,31 @@ pub use ariel_os_embassy_common::spi::main::*;
 /// [`SpiBus`](embedded_hal::spi::SpiBus) and an
 /// [`SpiDevice`](embedded_hal::spi::SpiDevice).
 ///
+/// # Transactions
+///
+/// [`transaction()`](embedded_hal_async::spi::SpiDevice::transaction) keeps the CS signal
+/// asserted across a sequence of [`Operation`]s, as required by command protocols where a command
+/// is followed by an address, a dummy cycle or a wait before the data.
+/// The bus is locked for the whole transaction, so that other devices on the same bus cannot
+/// interleave their own operations.
+///
+/// ```ignore
+/// use ariel_os::spi::main::Operation;
+/// use embedded_hal_async::spi::SpiDevice as _;
+///
+/// // Fast read of a SPI NOR flash: command, 24-bit address, and a dummy byte before the data.
+/// let mut data = [0; 16];
+/// spi_device
+///     .transaction(&mut [
+///         Operation::Write(&[0x0b, 0x00, 0x10, 0x00, 0x00]),
+///         Operation::Read(&mut data),
+///     ])
+///     .await?;
+/// ```
+///
+/// [`Operation::DelayNs`] requires the `time` Cargo feature; without it, transactions
+/// containing delays fail without asserting CS.
+///
 /// # Note
 ///
 /// Despite the driver interface being `async`, it may block during operations.
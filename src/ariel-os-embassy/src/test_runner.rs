@@ -0,0 +1,58 @@
+//! Runs the tests registered with the `test` macro.
+
+use ariel_os_debug::{
+    ExitCode, exit,
+    log::{info, warn},
+};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use linkme::distributed_slice;
+
+use crate::{asynch::Spawner, hal};
+
+/// A function registered with the `test` macro.
+#[doc(hidden)]
+pub struct TestCase {
+    /// Path of the function.
+    pub name: &'static str,
+    /// Whether the test is skipped.
+    pub ignore: bool,
+    /// Spawns the task running the test, which signals [`TEST_DONE`] once the test returns.
+    pub spawn: fn(Spawner, &mut hal::OptionalPeripherals),
+}
+
+#[doc(hidden)]
+#[distributed_slice]
+pub static TESTS: [TestCase] = [..];
+
+#[doc(hidden)]
+pub static TEST_DONE: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Runs the registered tests one after the other, then terminates the debug session.
+///
+/// A failing test panics, which terminates the debug session with a failure.
+#[cfg_attr(not(feature = "test-runner"), expect(dead_code))]
+pub(crate) async fn run(spawner: Spawner, peripherals: &mut hal::OptionalPeripherals) {
+    info!("running {} tests", TESTS.len());
+
+    let mut ignored = 0;
+    for test in TESTS {
+        if test.ignore {
+            warn!("test {} ... ignored", test.name);
+            ignored += 1;
+            continue;
+        }
+
+        info!("test {} ...", test.name);
+        (test.spawn)(spawner, peripherals);
+        TEST_DONE.wait().await;
+        info!("test {} ... ok", test.name);
+    }
+
+    info!(
+        "test result: ok. {} passed; {} ignored",
+        TESTS.len() - ignored,
+        ignored
+    );
+
+    exit(ExitCode::SUCCESS);
+}
@@ -0,0 +1,60 @@
+//! Provides time-related facilities.
+//!
+//! Besides timers, this provides timeouts and deadlines for any future (see
+//! [`with_timeout()`], [`try_with_timeout()`], [`Deadline`] and [`select_with_timeout!`]), the
+//! [`uptime()`] of the system and, with storage, a `boot_session()` counter. The uptime and the
+//! boot session together identify points in time across reboots without a wall clock, eg. to
+//! correlate logs of several boots.
+
+pub use ariel_os_embassy_common::{
+    select_with_timeout,
+    timeout::{Deadline, TimeoutError, try_with_timeout, with_timeout},
+};
+// NOTE: we may want to re-export more items in the future, but not re-export the whole
+// crate.
+pub use embassy_time::{Delay, Duration, Instant, TICK_HZ, Timer};
+
+#[cfg(feature = "storage")]
+use portable_atomic::{AtomicU32, Ordering};
+
+/// Storage key under which the boot session counter is persisted.
+#[cfg(feature = "storage")]
+const BOOT_SESSION_KEY: &str = "ariel-os-embassy.boot-session";
+
+#[cfg(feature = "storage")]
+static BOOT_SESSION: AtomicU32 = AtomicU32::new(0);
+
+/// Returns the time elapsed since the system started.
+///
+/// The uptime is counted in ticks of [`TICK_HZ`] on 64 bits, so that it never wraps around in
+/// practice: even at a tick rate of 1 MHz, this would take more than 500 000 years.
+#[must_use]
+pub fn uptime() -> Duration {
+    Duration::from_ticks(Instant::now().as_ticks())
+}
+
+/// Returns the boot session counter, which is incremented at each boot and persisted in storage.
+///
+/// The first boot after the storage has been erased is session 1. Returns 0 if the counter could
+/// not be read from or written to storage.
+#[cfg(feature = "storage")]
+#[must_use]
+pub fn boot_session() -> u32 {
+    BOOT_SESSION.load(Ordering::Relaxed)
+}
+
+/// Increments the persisted boot session counter.
+#[cfg(feature = "storage")]
+pub(crate) async fn record_boot_session() {
+    let Ok(previous) = ariel_os_storage::get::<u32>(BOOT_SESSION_KEY).await else {
+        return;
+    };
+    let session = previous.unwrap_or(0).saturating_add(1);
+    if ariel_os_storage::insert(BOOT_SESSION_KEY, session)
+        .await
+        .is_ok()
+    {
+        BOOT_SESSION.store(session, Ordering::Relaxed);
+        ariel_os_debug::log::debug!("boot session {}", session);
+    }
+}
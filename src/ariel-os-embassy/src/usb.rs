@@ -4,9 +4,21 @@
 //!
 //! To provide a custom USB configuration, use the [`ariel_os::config`](ariel_os_macros::config)
 //! attribute macro.
+//!
+//! With the `usb-ethernet` feature, the number of Ethernet frames buffered in each direction is
+//! configured through the `CONFIG_USB_ETHERNET_RX_BUFFER_COUNT` and
+//! `CONFIG_USB_ETHERNET_TX_BUFFER_COUNT` environment variables (default: 4).
 
 #![deny(missing_docs)]
 
+pub mod cdc_acm;
+#[cfg(feature = "usb-inspect")]
+pub mod inspect;
+#[cfg(feature = "usb-keyboard")]
+pub mod keyboard;
+#[cfg(feature = "usb-provisioning")]
+pub mod provisioning;
+
 pub use crate::hal::usb::UsbDriver;
 
 /// Builder for a USB device stack.
@@ -30,6 +42,19 @@ pub(crate) mod ethernet {
 
     use crate::{hal::usb::UsbDriver, net::ETHERNET_MTU};
 
+    /// Number of received Ethernet frames buffered.
+    pub(crate) const RX_BUFFER_COUNT: usize = ariel_os_utils::usize_from_env_or!(
+        "CONFIG_USB_ETHERNET_RX_BUFFER_COUNT",
+        4,
+        "number of received Ethernet frames buffered by USB Ethernet"
+    );
+    /// Number of Ethernet frames to transmit buffered.
+    pub(crate) const TX_BUFFER_COUNT: usize = ariel_os_utils::usize_from_env_or!(
+        "CONFIG_USB_ETHERNET_TX_BUFFER_COUNT",
+        4,
+        "number of Ethernet frames to transmit buffered by USB Ethernet"
+    );
+
     #[allow(dead_code, reason = "use depends on enabled features")]
     pub type NetworkDevice = Device<'static, ETHERNET_MTU>;
 
@@ -46,7 +71,11 @@ pub(crate) fn config() -> embassy_usb::Config<'static> {
         // Create embassy-usb Config
         let mut config = embassy_usb::Config::new(0xc0de, 0xcafe);
         config.manufacturer = Some(ariel_os_buildinfo::OS_NAME);
-        config.product = Some("USB-Ethernet example");
+        config.product = Some(ariel_os_utils::const_str::concat!(
+            ariel_os_buildinfo::APP_NAME,
+            " ",
+            ariel_os_buildinfo::APP_VERSION,
+        ));
         config.serial_number = Some("12345678");
         config.max_power = 100;
         config.max_packet_size_0 = 64;
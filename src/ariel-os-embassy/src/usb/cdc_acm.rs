@@ -0,0 +1,156 @@
+//! Provides a byte-stream interface to USB CDC ACM (serial) classes.
+//!
+//! [`CdcAcmSerial`] implements [`embedded_io_async::Read`] and [`embedded_io_async::Write`], so
+//! that crates working on byte streams (AT-command parsers, line editors, ...) can be used on
+//! USB serial ports, as they can on TCP sockets.
+
+use embassy_usb::{class::cdc_acm::CdcAcmClass, driver::EndpointError};
+use embedded_io_async::{ErrorKind, ErrorType, Read, Write};
+
+use crate::hal::usb::UsbDriver;
+
+/// Maximum packet size supported by [`CdcAcmSerial`], which is the maximum packet size of bulk
+/// endpoints on full-speed USB.
+pub const MAX_PACKET_SIZE: u16 = 64;
+
+/// A USB CDC ACM class, used as a byte stream.
+///
+/// Received packets are buffered, so that reads can use buffers of any size.
+/// Writes send at most one packet each; [`Write::flush()`] terminates the current transfer, so
+/// that the host gets the data written even when the last packet was a full one.
+pub struct CdcAcmSerial {
+    class: CdcAcmClass<'static, UsbDriver>,
+    rx_buffer: [u8; MAX_PACKET_SIZE as usize],
+    rx_start: usize,
+    rx_end: usize,
+    needs_zlp: bool,
+}
+
+impl CdcAcmSerial {
+    /// Wraps a CDC ACM class.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the maximum packet size of the class is larger than [`MAX_PACKET_SIZE`].
+    #[must_use]
+    pub fn new(class: CdcAcmClass<'static, UsbDriver>) -> Self {
+        assert!(
+            class.max_packet_size() <= MAX_PACKET_SIZE,
+            "the maximum packet size of the class is not supported"
+        );
+
+        Self {
+            class,
+            rx_buffer: [0; MAX_PACKET_SIZE as usize],
+            rx_start: 0,
+            rx_end: 0,
+            needs_zlp: false,
+        }
+    }
+
+    /// Waits until the host has opened the serial port.
+    pub async fn wait_connection(&mut self) {
+        self.class.wait_connection().await;
+    }
+
+    /// Returns the wrapped CDC ACM class.
+    ///
+    /// Received data not read yet is discarded.
+    #[must_use]
+    pub fn into_inner(self) -> CdcAcmClass<'static, UsbDriver> {
+        self.class
+    }
+}
+
+/// Error returned by [`CdcAcmSerial`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The USB device was disconnected or its configuration was reset.
+    ///
+    /// This maps to [`ErrorKind::ConnectionReset`], as the connection reset of a TCP socket does.
+    Disconnected,
+}
+
+impl From<EndpointError> for Error {
+    fn from(err: EndpointError) -> Self {
+        match err {
+            EndpointError::Disabled => Self::Disconnected,
+            // Packets are read into a buffer of the maximum packet size, and written in chunks of
+            // at most that size.
+            EndpointError::BufferOverflow => unreachable!(),
+        }
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Disconnected => write!(f, "USB disconnected"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl embedded_io_async::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Disconnected => ErrorKind::ConnectionReset,
+        }
+    }
+}
+
+impl ErrorType for CdcAcmSerial {
+    type Error = Error;
+}
+
+impl Read for CdcAcmSerial {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Zero-length packets only terminate transfers and carry no data.
+        while self.rx_start == self.rx_end {
+            self.rx_end = self.class.read_packet(&mut self.rx_buffer).await?;
+            self.rx_start = 0;
+        }
+
+        let pending = self
+            .rx_buffer
+            .get(self.rx_start..self.rx_end)
+            .unwrap_or_default();
+        let len = pending.len().min(buf.len());
+        for (dest, src) in buf.iter_mut().zip(pending) {
+            *dest = *src;
+        }
+        self.rx_start += len;
+
+        Ok(len)
+    }
+}
+
+impl Write for CdcAcmSerial {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let max_packet_size = usize::from(self.class.max_packet_size());
+        let Some(packet) = buf.chunks(max_packet_size).next() else {
+            return Ok(0);
+        };
+
+        self.class.write_packet(packet).await?;
+        // The host only considers a transfer complete once it receives a packet shorter than the
+        // maximum packet size.
+        self.needs_zlp = packet.len() == max_packet_size;
+
+        Ok(packet.len())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        if self.needs_zlp {
+            self.class.write_packet(&[]).await?;
+            self.needs_zlp = false;
+        }
+
+        Ok(())
+    }
+}
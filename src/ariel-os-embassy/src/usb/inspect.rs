@@ -0,0 +1,50 @@
+//! Provides the [inspection service](ariel_os_inspect) on a USB serial port.
+//!
+//! ```ignore
+//! use ariel_os::usb::inspect::UsbInspect;
+//!
+//! #[ariel_os::task(autostart, usb_builder_hook)]
+//! async fn inspect() {
+//!     let mut inspect = USB_BUILDER_HOOK.with(|builder| UsbInspect::new(builder)).await;
+//!     inspect.run().await;
+//! }
+//! ```
+
+use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
+use static_cell::StaticCell;
+
+use crate::usb::{
+    UsbBuilder,
+    cdc_acm::{CdcAcmSerial, MAX_PACKET_SIZE},
+};
+
+static STATE: StaticCell<State<'static>> = StaticCell::new();
+
+/// The inspection service, served on a USB CDC ACM (serial) class.
+pub struct UsbInspect {
+    serial: CdcAcmSerial,
+}
+
+impl UsbInspect {
+    /// Adds a CDC ACM class for inspection to the USB device being built with `builder`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called more than once.
+    #[must_use]
+    pub fn new(builder: &mut UsbBuilder) -> Self {
+        let class = CdcAcmClass::new(builder, STATE.init_with(State::new), MAX_PACKET_SIZE);
+        Self {
+            serial: CdcAcmSerial::new(class),
+        }
+    }
+
+    /// Serves the inspection service, each time the host opens the serial port.
+    pub async fn run(&mut self) -> ! {
+        loop {
+            self.serial.wait_connection().await;
+            // Errors only mean that the host disconnected.
+            let _ = ariel_os_inspect::serve(&mut self.serial).await;
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Provides a USB HID keyboard, which sends the keys pressed on the keypads of the input service.
+//!
+//! The keys are mapped with an [`ariel_os_keyboard::Keyboard`], whose layers can turn a small
+//! keypad into a macro pad:
+//!
+//! ```ignore
+//! use ariel_os::{board, input, keyboard::Keyboard, usb::keyboard::UsbKeyboard};
+//!
+//! #[ariel_os::task(autostart, usb_builder_hook)]
+//! async fn keyboard() {
+//!     let mut usb_keyboard = USB_BUILDER_HOOK.with(UsbKeyboard::new).await;
+//!     input::add_keypad(board::take_keypad().unwrap()).unwrap();
+//!     usb_keyboard.run(&mut Keyboard::new(&LAYERS)).await
+//! }
+//! ```
+
+use ariel_os_keyboard::{Keyboard, REPORT_DESCRIPTOR, REPORT_LEN, Report};
+use embassy_usb::{
+    class::hid::{self, HidWriter},
+    driver::EndpointError,
+};
+use static_cell::StaticCell;
+
+use crate::{
+    hal::usb::UsbDriver,
+    input::{self, Event, Key},
+    usb::UsbBuilder,
+};
+
+/// Polling interval of the keyboard requested from the host, in milliseconds.
+const POLL_MS: u8 = 10;
+
+static STATE: StaticCell<hid::State<'static>> = StaticCell::new();
+
+/// A USB HID keyboard, following the boot keyboard protocol.
+pub struct UsbKeyboard {
+    writer: HidWriter<'static, UsbDriver, REPORT_LEN>,
+}
+
+impl UsbKeyboard {
+    /// Adds a HID keyboard class to the USB device being built with `builder`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called more than once.
+    #[must_use]
+    pub fn new(builder: &mut UsbBuilder) -> Self {
+        let config = hid::Config {
+            report_descriptor: REPORT_DESCRIPTOR,
+            request_handler: None,
+            poll_ms: POLL_MS,
+            // The reports fit into a single packet.
+            #[expect(clippy::cast_possible_truncation)]
+            max_packet_size: REPORT_LEN as u16,
+        };
+        let state = STATE.init_with(hid::State::new);
+
+        Self {
+            writer: HidWriter::new(builder, state, config),
+        }
+    }
+
+    /// Sends `report` to the host.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the host has not configured the device.
+    pub async fn write_report(&mut self, report: &Report) -> Result<(), EndpointError> {
+        self.writer.write(&report.to_bytes()).await
+    }
+
+    /// Sends the keys pressed on the keypads added to the [input service](crate::input), mapped
+    /// with `keyboard`.
+    ///
+    /// The keyboard takes over the events of the input service, and ignores the events of the
+    /// other inputs. Changes happening while the host has not configured the device are sent with
+    /// the next change.
+    pub async fn run<const ROWS: usize, const COLUMNS: usize>(
+        &mut self,
+        keyboard: &mut Keyboard<'_, ROWS, COLUMNS>,
+    ) -> ! {
+        let mut sent = Report::default();
+        loop {
+            match input::next_event().await {
+                Event::Pressed(Key::Keypad { row, column, .. }) => {
+                    keyboard.press(usize::from(row), usize::from(column));
+                }
+                Event::Released(Key::Keypad { row, column, .. }) => {
+                    keyboard.release(usize::from(row), usize::from(column));
+                }
+                _ => continue,
+            }
+
+            let report = keyboard.report();
+            if report != sent && self.write_report(&report).await.is_ok() {
+                sent = report;
+            }
+        }
+    }
+}
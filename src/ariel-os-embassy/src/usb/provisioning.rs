@@ -0,0 +1,76 @@
+//! Provides the [provisioning service](ariel_os_provisioning) on a USB serial port.
+//!
+//! The service is only meant to be exposed when provisioning is requested by holding the first
+//! button of the board at boot (see [`UsbProvisioning::requested()`]), and the device is reset
+//! once the host has ended the session:
+//!
+//! ```ignore
+//! use ariel_os::usb::provisioning::UsbProvisioning;
+//!
+//! #[ariel_os::task(autostart, usb_builder_hook)]
+//! async fn provisioning() {
+//!     let requested = UsbProvisioning::requested().await;
+//!     // The hook needs to be used in any case for the USB device to start.
+//!     let provisioning = USB_BUILDER_HOOK
+//!         .with(|builder| requested.then(|| UsbProvisioning::new(builder)))
+//!         .await;
+//!     if let Some(mut provisioning) = provisioning {
+//!         provisioning.run().await;
+//!         ariel_os::power::reboot();
+//!     }
+//! }
+//! ```
+
+use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
+use static_cell::StaticCell;
+
+use crate::usb::{
+    UsbBuilder,
+    cdc_acm::{CdcAcmSerial, MAX_PACKET_SIZE},
+};
+
+static STATE: StaticCell<State<'static>> = StaticCell::new();
+
+/// The provisioning service, served on a USB CDC ACM (serial) class.
+pub struct UsbProvisioning {
+    serial: CdcAcmSerial,
+}
+
+impl UsbProvisioning {
+    /// Returns whether provisioning is requested, by holding the first button of the board.
+    ///
+    /// This is meant to be checked at boot, before the USB device is built. Provisioning is
+    /// never requested on boards without buttons.
+    pub async fn requested() -> bool {
+        match crate::board::buttons().first() {
+            Some(button) => button.is_pressed().await,
+            None => false,
+        }
+    }
+
+    /// Adds a CDC ACM class for provisioning to the USB device being built with `builder`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called more than once.
+    #[must_use]
+    pub fn new(builder: &mut UsbBuilder) -> Self {
+        let class = CdcAcmClass::new(builder, STATE.init_with(State::new), MAX_PACKET_SIZE);
+        Self {
+            serial: CdcAcmSerial::new(class),
+        }
+    }
+
+    /// Serves provisioning sessions until the host ends one with `DONE`.
+    ///
+    /// When the host disconnects during a session, the requests received so far are kept, and a
+    /// new session is served once it reconnects.
+    pub async fn run(&mut self) {
+        loop {
+            self.serial.wait_connection().await;
+            if ariel_os_provisioning::serve(&mut self.serial).await.is_ok() {
+                return;
+            }
+        }
+    }
+}
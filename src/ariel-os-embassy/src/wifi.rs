@@ -3,3 +3,129 @@ pub(crate) use crate::hal::cyw43::NetworkDevice;
 
 #[cfg(feature = "wifi-esp")]
 pub(crate) use crate::hal::wifi::esp_wifi::NetworkDevice;
+
+#[cfg(feature = "wifi-esp-eap")]
+pub(crate) use eap::eap_config_task;
+
+#[cfg(feature = "wifi-esp-eap")]
+mod eap {
+    //! Loads the credentials for WPA2-Enterprise networks.
+    //!
+    //! PEAP is used if a password is stored in the vault under [`EAP_PASSWORD_SECRET`];
+    //! otherwise, EAP-TLS is used with the device key and its certificate (see
+    //! [`ariel_os_identity::device_key`]).
+    //! The authentication server is verified against the CA certificate stored (in DER encoding)
+    //! under the [`EAP_CA_CERTIFICATE_KEY`] storage key, if any.
+
+    use ariel_os_debug::log::{error, info, warn};
+    use ariel_os_identity::device_key;
+    use static_cell::StaticCell;
+
+    use crate::hal::wifi::esp_wifi::{EAP_CONFIG, EapConfig, EapCredentials};
+
+    /// Name of the vault secret holding the PEAP password.
+    pub(crate) const EAP_PASSWORD_SECRET: &str = "wifi-eap-password";
+
+    /// Storage key of the CA certificate the authentication server is verified against.
+    pub(crate) const EAP_CA_CERTIFICATE_KEY: &str = "ariel-os.wifi-eap-ca-certificate";
+
+    const MAX_CERTIFICATE_LEN: usize = ariel_os_utils::usize_from_env_or!(
+        "CONFIG_WIFI_EAP_MAX_CERTIFICATE_LEN",
+        1024,
+        "maximum length of the Wi-Fi EAP certificates, in DER encoding"
+    );
+
+    /// SEC1 encoding of a P-256 private key, up to the 32 bytes of the key itself.
+    const SEC1_PREFIX: [u8; 7] = [0x30, 0x31, 0x02, 0x01, 0x01, 0x04, 0x20];
+    /// SEC1 encoding of a P-256 private key, after the 32 bytes of the key itself: the
+    /// `prime256v1` curve parameter.
+    const SEC1_SUFFIX: [u8; 12] = [
+        0xa0, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07,
+    ];
+    const SEC1_LEN: usize = SEC1_PREFIX.len() + 32 + SEC1_SUFFIX.len();
+
+    #[embassy_executor::task]
+    pub(crate) async fn eap_config_task() {
+        static CA_CERTIFICATE: StaticCell<[u8; MAX_CERTIFICATE_LEN]> = StaticCell::new();
+
+        let Some(credentials) = load_credentials().await else {
+            error!("wifi: no EAP password in the vault, nor device certificate");
+            return;
+        };
+
+        let ca_certificate = ariel_os_storage::get_blob(
+            EAP_CA_CERTIFICATE_KEY,
+            CA_CERTIFICATE.init([0; MAX_CERTIFICATE_LEN]),
+        )
+        .await
+        .ok()
+        .flatten();
+        if ca_certificate.is_none() {
+            warn!("wifi: no EAP CA certificate, the authentication server is not verified");
+        }
+
+        let _ = EAP_CONFIG.init(EapConfig {
+            credentials,
+            ca_certificate,
+        });
+    }
+
+    async fn load_credentials() -> Option<EapCredentials> {
+        match load_password().await {
+            Ok(Some(password)) => {
+                info!("wifi: using PEAP");
+                return Some(EapCredentials::Peap { password });
+            }
+            Ok(None) => {}
+            Err(()) => error!("wifi: loading the EAP password failed"),
+        }
+
+        let credentials = load_certificate_and_key().await;
+        if credentials.is_some() {
+            info!("wifi: using EAP-TLS");
+        }
+        credentials
+    }
+
+    async fn load_password() -> Result<Option<heapless::String<64>>, ()> {
+        let vault = ariel_os_vault::vault().await.map_err(|_| ())?;
+        let Some((secret, len)) = vault
+            .key(EAP_PASSWORD_SECRET)
+            .load_up_to::<64>()
+            .await
+            .map_err(|_| ())?
+        else {
+            return Ok(None);
+        };
+        let password = secret.expose().get(..len).ok_or(())?;
+        let password = core::str::from_utf8(password).map_err(|_| ())?;
+        password.try_into().map(Some)
+    }
+
+    async fn load_certificate_and_key() -> Option<EapCredentials> {
+        static CERTIFICATE: StaticCell<[u8; MAX_CERTIFICATE_LEN]> = StaticCell::new();
+        static PRIVATE_KEY: StaticCell<[u8; SEC1_LEN]> = StaticCell::new();
+
+        let certificate = device_key::certificate(CERTIFICATE.init([0; MAX_CERTIFICATE_LEN]))
+            .await
+            .ok()
+            .flatten()?;
+        let device_key = device_key::device_key().await.ok()?;
+
+        // The private key needs to stay available for reconnecting, and is thus kept in RAM.
+        let private_key = PRIVATE_KEY.init([0; SEC1_LEN]);
+        for (byte, source) in private_key.iter_mut().zip(
+            SEC1_PREFIX
+                .iter()
+                .chain(&device_key.secret_key_bytes())
+                .chain(&SEC1_SUFFIX),
+        ) {
+            *byte = *source;
+        }
+
+        Some(EapCredentials::Tls {
+            certificate,
+            private_key,
+        })
+    }
+}
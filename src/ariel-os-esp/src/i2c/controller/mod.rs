@@ -1,6 +1,8 @@
 //! Provides support for the I2C communication bus in controller mode.
 
-use ariel_os_embassy_common::impl_async_i2c_for_driver_enum;
+use ariel_os_embassy_common::{
+    i2c::controller::I2C_TIMEOUT, impl_async_i2c_for_driver_enum, reexports::embassy_time::Duration,
+};
 use esp_hal::{
     Async,
     gpio::interconnect::PeripheralOutput,
@@ -15,12 +17,16 @@ use esp_hal::{
 pub struct Config {
     /// The frequency at which the bus should operate.
     pub frequency: Frequency,
+    /// Timeout of each operation on the bus, after which it fails with
+    /// [`Error::Timeout`](ariel_os_embassy_common::i2c::controller::Error::Timeout).
+    pub timeout: Duration,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             frequency: Frequency::_100k,
+            timeout: I2C_TIMEOUT,
         }
     }
 }
@@ -90,6 +96,7 @@ macro_rules! define_i2c_drivers {
             /// Peripheral-specific I2C driver.
             pub struct $peripheral {
                 twim: EspI2c<'static, Async>,
+                timeout: Duration,
             }
 
             impl $peripheral {
@@ -133,7 +140,7 @@ macro_rules! define_i2c_drivers {
                         .with_sda(sda_pin)
                         .with_scl(scl_pin);
 
-                    I2c::$peripheral(Self { twim })
+                    I2c::$peripheral(Self { twim, timeout: config.timeout })
                 }
             }
         )*
@@ -146,6 +153,9 @@ macro_rules! define_i2c_drivers {
             )*
         }
 
+        /// Names of the I2C peripherals for which a driver is available.
+        pub const PERIPHERALS: &[&str] = &[$( stringify!($peripheral) ),*];
+
         impl embedded_hal_async::i2c::ErrorType for I2c {
             type Error = ariel_os_embassy_common::i2c::controller::Error;
         }
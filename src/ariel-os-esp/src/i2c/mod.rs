@@ -5,18 +5,29 @@ pub mod controller;
 
 #[doc(hidden)]
 pub fn init(peripherals: &mut crate::OptionalPeripherals) {
+    // This macro has to be defined in this function so that the `peripherals` variables exists.
+    macro_rules! take_all_i2c_peripherals {
+        ($( $peripheral:ident ),*) => {
+            $(
+                let peripheral = peripherals.$peripheral.take();
+                debug_assert!(
+                    peripheral.is_some(),
+                    concat!(stringify!($peripheral), " was taken before initialization"),
+                );
+            )*
+        }
+    }
+
     // Take all I2C peripherals and do nothing with them.
     cfg_if::cfg_if! {
         if #[cfg(context = "esp32")] {
-            let _ = peripherals.I2C0.take().unwrap();
-            let _ = peripherals.I2C1.take().unwrap();
+            take_all_i2c_peripherals!(I2C0, I2C1);
         } else if #[cfg(context = "esp32c3")] {
-            let _ = peripherals.I2C0.take().unwrap();
+            take_all_i2c_peripherals!(I2C0);
         } else if #[cfg(context = "esp32c6")] {
-            let _ = peripherals.I2C0.take().unwrap();
+            take_all_i2c_peripherals!(I2C0);
         } else if #[cfg(context = "esp32s3")] {
-            let _ = peripherals.I2C0.take().unwrap();
-            let _ = peripherals.I2C1.take().unwrap();
+            take_all_i2c_peripherals!(I2C0, I2C1);
         } else {
             compile_error!("this ESP32 chip is not supported");
         }
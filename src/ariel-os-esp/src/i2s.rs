@@ -0,0 +1,170 @@
+//! Provides audio capture from I2S microphones with the I2S peripheral.
+//!
+//! The peripheral is the controller of the bus: it outputs the bit clock and the word select
+//! signal, and receives 32-bit words into a circular DMA buffer.
+//! A single microphone is captured, on the channel selected through [`Config::channel`]; the 16
+//! most significant bits of its words are kept, which suits microphones with 18- or 24-bit
+//! samples, eg. the INMP441 or the ICS-43434.
+
+use ariel_os_audio::{Error, Flow, Microphone};
+use ariel_os_embassy_common::dma_buffer;
+use esp_hal::{
+    dma::{DmaChannelFor, DmaError},
+    dma_circular_descriptors,
+    gpio::interconnect::{PeripheralInput, PeripheralOutput},
+    i2s::master::{self, AnyI2s, DataFormat, I2s, RegisterAccess, Standard, asynch},
+    peripheral::Peripheral,
+};
+
+/// Size of the circular DMA buffer, in bytes.
+const DMA_BUFFER_LEN: usize = 4 * 1024;
+
+/// Size of a word, in bytes.
+const WORD_LEN: usize = 4;
+
+/// Number of bytes popped from the DMA buffer at once: the words of the left and right channels,
+/// a number of times.
+const CHUNK_LEN: usize = 32 * 2 * WORD_LEN;
+
+/// Channel of the I2S bus the microphone outputs its samples on.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Channel {
+    /// The microphone outputs its samples while word select is low, usually when its L/R pin is
+    /// tied low.
+    #[default]
+    Left,
+    /// The microphone outputs its samples while word select is high, usually when its L/R pin is
+    /// tied high.
+    Right,
+}
+
+/// I2S microphone configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// Number of samples captured per second.
+    pub sample_rate: u32,
+    /// Channel the microphone is on.
+    pub channel: Channel,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            sample_rate: 16_000,
+            channel: Channel::Left,
+        }
+    }
+}
+
+/// An I2S microphone, captured by the I2S peripheral.
+pub struct I2sMicrophone {
+    transfer: asynch::I2sReadDmaTransferAsync<'static, &'static mut [u8; DMA_BUFFER_LEN]>,
+    config: Config,
+}
+
+impl I2sMicrophone {
+    /// Returns a driver implementing [`Microphone`] for the I2S microphone connected to `bclk`,
+    /// `ws` and `din`.
+    ///
+    /// The capture starts right away, into the DMA buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the DMA transfer cannot be started.
+    #[must_use]
+    pub fn new<CH: DmaChannelFor<AnyI2s>>(
+        i2s: impl Peripheral<P = impl RegisterAccess> + 'static,
+        dma_channel: impl Peripheral<P = CH> + 'static,
+        bclk: impl Peripheral<P = impl PeripheralOutput> + 'static,
+        ws: impl Peripheral<P = impl PeripheralOutput> + 'static,
+        din: impl Peripheral<P = impl PeripheralInput> + 'static,
+        config: Config,
+    ) -> Self {
+        // Make this struct a compile-time-enforced singleton: having multiple statics defined
+        // with the same name would result in a compile-time error.
+        #[allow(dead_code)]
+        static PREVENT_MULTIPLE_I2S_MICROPHONE: () = ();
+
+        let rx_buffer = dma_buffer!(DMA_BUFFER_LEN).as_mut_array();
+        let (rx_descriptors, tx_descriptors) = dma_circular_descriptors!(DMA_BUFFER_LEN, 0);
+
+        let i2s = I2s::new(
+            i2s,
+            Standard::Philips,
+            DataFormat::Data32Channel32,
+            fugit::HertzU32::Hz(config.sample_rate),
+            dma_channel,
+            rx_descriptors,
+            tx_descriptors,
+        )
+        .into_async();
+        let rx = i2s.i2s_rx.with_bclk(bclk).with_ws(ws).with_din(din).build();
+        let transfer = rx.read_dma_circular_async(rx_buffer).unwrap();
+
+        Self { transfer, config }
+    }
+}
+
+impl Microphone for I2sMicrophone {
+    fn sample_rate(&self) -> u32 {
+        self.config.sample_rate
+    }
+
+    async fn capture<const N: usize, S>(&mut self, mut sink: S) -> Result<(), Error>
+    where
+        S: FnMut(&[i16; N]) -> Flow,
+    {
+        let channel_offset = match self.config.channel {
+            Channel::Left => 0,
+            Channel::Right => WORD_LEN,
+        };
+        let mut chunk = [0; CHUNK_LEN];
+        // Bytes of an incomplete pair of words, kept at the start of the chunk.
+        let mut pending = 0;
+        let mut frame = [0; N];
+        let mut len = 0;
+
+        loop {
+            let popped = self
+                .transfer
+                .pop(chunk.get_mut(pending..).unwrap_or_default())
+                .await
+                .map_err(|err| match err {
+                    master::Error::DmaError(DmaError::Late) => Error::Overrun,
+                    _ => Error::Hardware,
+                })?;
+            let received = pending + popped;
+
+            let mut pairs = chunk
+                .get(..received)
+                .unwrap_or_default()
+                .chunks_exact(2 * WORD_LEN);
+            for pair in pairs.by_ref() {
+                let Some(word) = pair
+                    .get(channel_offset..)
+                    .and_then(|word| word.first_chunk::<WORD_LEN>())
+                else {
+                    continue;
+                };
+                let [_, _, low, high] = *word;
+                let Some(sample) = frame.get_mut(len) else {
+                    continue;
+                };
+                *sample = i16::from_le_bytes([low, high]);
+                len += 1;
+
+                if len == N {
+                    len = 0;
+                    if sink(&frame) == Flow::Stop {
+                        return Ok(());
+                    }
+                }
+            }
+            pending = pairs.remainder().len();
+            chunk.copy_within(received - pending..received, 0);
+        }
+    }
+}
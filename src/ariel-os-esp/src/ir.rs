@@ -0,0 +1,203 @@
+//! Provides IR remote control with the RMT peripheral.
+//!
+//! The RMT peripheral modulates and samples the signals in hardware, with a resolution of one
+//! microsecond.
+
+use ariel_os_ir::{Error, Receiver, Transmitter};
+use esp_hal::{
+    Async,
+    gpio::interconnect::{PeripheralInput, PeripheralOutput},
+    peripheral::Peripheral,
+    peripherals,
+    rmt::{
+        Channel, PulseCode, Rmt, RxChannelAsync, RxChannelConfig, RxChannelCreatorAsync,
+        TxChannelAsync, TxChannelConfig, TxChannelCreatorAsync,
+    },
+};
+
+/// Frequency of the source clock of the RMT peripheral, in MHz.
+const SOURCE_CLOCK_MHZ: u32 = 80;
+/// Divider of the source clock, such that durations are counted in microseconds.
+// The source clock frequency fits into the divider.
+#[expect(clippy::cast_possible_truncation)]
+const CLOCK_DIVIDER: u8 = SOURCE_CLOCK_MHZ as u8;
+
+/// Maximum number of pulse codes, each holding a mark and a space, that fit into the memory of a
+/// channel.
+const MAX_CODES: usize = 48;
+/// Maximum duration of marks and spaces, which pulse codes store on 15 bits.
+const MAX_DURATION: u16 = 0x7fff;
+
+/// Duration of the spaces after which signals are considered complete, in microseconds.
+const IDLE_THRESHOLD: u16 = 10_000;
+
+// NOTE(hal): the channels usable for transmitting and for receiving depend on the MCU.
+#[cfg(context = "esp32")]
+const RX_CHANNEL: u8 = 1;
+#[cfg(any(context = "esp32c3", context = "esp32c6"))]
+const RX_CHANNEL: u8 = 2;
+#[cfg(context = "esp32s3")]
+const RX_CHANNEL: u8 = 4;
+
+/// IR transmitter configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct TransmitterConfig {
+    /// Carrier frequency in Hz, eg. the
+    /// [carrier frequency of the protocol](ariel_os_ir::Protocol::carrier_frequency).
+    pub carrier_frequency: u32,
+}
+
+impl Default for TransmitterConfig {
+    fn default() -> Self {
+        Self {
+            carrier_frequency: 38_000,
+        }
+    }
+}
+
+/// An IR receiver module, sampled by the RMT peripheral.
+pub struct RmtReceiver {
+    channel: Channel<Async, RX_CHANNEL>,
+}
+
+impl RmtReceiver {
+    /// Returns a driver implementing [`Receiver`] for the IR receiver module whose output is
+    /// connected to `pin`.
+    ///
+    /// The output of receiver modules is low during marks.
+    #[must_use]
+    pub fn new<P: PeripheralInput>(pin: impl Peripheral<P = P> + 'static) -> Self {
+        // Make this struct a compile-time-enforced singleton: having multiple statics defined
+        // with the same name would result in a compile-time error.
+        #[allow(dead_code)]
+        static PREVENT_MULTIPLE_RMT_RECEIVER: () = ();
+
+        let rmt = new_rmt();
+        let config = RxChannelConfig {
+            clk_divider: CLOCK_DIVIDER,
+            idle_threshold: IDLE_THRESHOLD,
+            ..RxChannelConfig::default()
+        };
+
+        #[cfg(context = "esp32")]
+        let channel = rmt.channel1;
+        #[cfg(any(context = "esp32c3", context = "esp32c6"))]
+        let channel = rmt.channel2;
+        #[cfg(context = "esp32s3")]
+        let channel = rmt.channel4;
+
+        let channel = channel.configure(pin, config).unwrap();
+
+        Self { channel }
+    }
+}
+
+impl Receiver for RmtReceiver {
+    async fn receive_timings(&mut self, timings: &mut [u16]) -> Result<usize, Error> {
+        let mut codes = [0u32; MAX_CODES];
+        self.channel
+            .receive(&mut codes)
+            .await
+            .map_err(|_| Error::Hardware)?;
+
+        let halves = codes.iter().flat_map(|code| {
+            [
+                (code.level1(), code.length1()),
+                (code.level2(), code.length2()),
+            ]
+        });
+
+        let mut len = 0;
+        // Signals end with a zero-length half, and marks are low.
+        for (_, duration) in halves
+            .take_while(|&(_, duration)| duration != 0)
+            .skip_while(|&(level, _)| level)
+        {
+            if let Some(timing) = timings.get_mut(len) {
+                *timing = duration;
+                len += 1;
+            }
+        }
+        Ok(len)
+    }
+}
+
+/// An IR LED, driven by the RMT peripheral.
+pub struct RmtTransmitter {
+    channel: Channel<Async, 0>,
+}
+
+impl RmtTransmitter {
+    /// Returns a driver implementing [`Transmitter`] for the IR LED connected to `pin`.
+    #[must_use]
+    pub fn new<P: PeripheralOutput>(
+        pin: impl Peripheral<P = P> + 'static,
+        config: TransmitterConfig,
+    ) -> Self {
+        // Make this struct a compile-time-enforced singleton: having multiple statics defined
+        // with the same name would result in a compile-time error.
+        #[allow(dead_code)]
+        static PREVENT_MULTIPLE_RMT_TRANSMITTER: () = ();
+
+        // The carrier is counted in cycles of the source clock.
+        let half_period = SOURCE_CLOCK_MHZ * 1_000_000 / config.carrier_frequency / 2;
+        let half_period = u16::try_from(half_period).unwrap_or(u16::MAX);
+
+        let rmt = new_rmt();
+        let config = TxChannelConfig {
+            clk_divider: CLOCK_DIVIDER,
+            idle_output: true,
+            idle_output_level: false,
+            carrier_modulation: true,
+            carrier_high: half_period,
+            carrier_low: half_period,
+            carrier_level: true,
+            ..TxChannelConfig::default()
+        };
+        let channel = rmt.channel0.configure(pin, config).unwrap();
+
+        Self { channel }
+    }
+}
+
+impl Transmitter for RmtTransmitter {
+    async fn transmit_timings(&mut self, timings: &[u16]) -> Result<(), Error> {
+        let mut codes = [0u32; MAX_CODES];
+        let mut len = 0;
+        for pair in timings.chunks(2) {
+            let (mark, space) = match *pair {
+                [mark, space] => (mark, space),
+                // A zero-length space ends the signal.
+                [mark] => (mark, 0),
+                _ => continue,
+            };
+            *codes.get_mut(len).ok_or(Error::BufferTooSmall)? =
+                PulseCode::new(true, mark.min(MAX_DURATION), false, space.min(MAX_DURATION));
+            len += 1;
+        }
+        // Signals with a trailing space need a separate end marker.
+        if timings.len() % 2 == 0 {
+            *codes.get_mut(len).ok_or(Error::BufferTooSmall)? = PulseCode::empty();
+            len += 1;
+        }
+
+        self.channel
+            .transmit(codes.get(..len).unwrap_or_default())
+            .await
+            .map_err(|_| Error::Hardware)
+    }
+}
+
+/// Returns the RMT peripheral, whose channels are split between the receiver and transmitter.
+fn new_rmt() -> Rmt<'static, Async> {
+    // FIXME(safety): enforce that the init code indeed has run
+    // SAFETY: the receiver and transmitter being singletons, and using distinct channels,
+    // prevents us from using the channels multiple times.
+    let rmt_peripheral = unsafe { peripherals::RMT::steal() };
+
+    Rmt::new(rmt_peripheral, fugit::HertzU32::MHz(SOURCE_CLOCK_MHZ))
+        .unwrap()
+        .into_async()
+}
@@ -20,6 +20,12 @@ pub mod hwrng {
 #[cfg(feature = "i2c")]
 pub mod i2c;
 
+#[cfg(feature = "audio-i2s")]
+pub mod i2s;
+
+#[cfg(feature = "ir-rmt")]
+pub mod ir;
+
 #[doc(hidden)]
 pub mod identity {
     use ariel_os_embassy_common::identity;
@@ -124,7 +130,11 @@ pub fn init() -> OptionalPeripherals {
     let rng = esp_hal::rng::Rng::new(peripherals.RNG.take().unwrap());
 
     #[cfg(feature = "hwrng")]
-    ariel_os_random::construct_rng(rng);
+    {
+        // The RNG is kept, so that it can be read again to reseed the global RNG.
+        static HWRNG: static_cell::StaticCell<esp_hal::rng::Rng> = static_cell::StaticCell::new();
+        ariel_os_random::construct_rng_from_hwrng(HWRNG.init(rng));
+    }
 
     #[cfg(feature = "wifi-esp")]
     {
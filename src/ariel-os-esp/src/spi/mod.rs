@@ -23,18 +23,29 @@ fn from_bit_order(bit_order: BitOrder) -> esp_hal::spi::BitOrder {
 
 #[doc(hidden)]
 pub fn init(peripherals: &mut crate::OptionalPeripherals) {
+    // This macro has to be defined in this function so that the `peripherals` variables exists.
+    macro_rules! take_all_spi_peripherals {
+        ($( $peripheral:ident ),*) => {
+            $(
+                let peripheral = peripherals.$peripheral.take();
+                debug_assert!(
+                    peripheral.is_some(),
+                    concat!(stringify!($peripheral), " was taken before initialization"),
+                );
+            )*
+        }
+    }
+
     // Take all SPI peripherals and do nothing with them.
     cfg_if::cfg_if! {
         if #[cfg(context = "esp32")] {
-            let _ = peripherals.SPI2.take().unwrap();
-            let _ = peripherals.SPI3.take().unwrap();
+            take_all_spi_peripherals!(SPI2, SPI3);
         } else if #[cfg(context = "esp32c3")] {
-            let _ = peripherals.SPI2.take().unwrap();
+            take_all_spi_peripherals!(SPI2);
         } else if #[cfg(context = "esp32c6")] {
-            let _ = peripherals.SPI2.take().unwrap();
+            take_all_spi_peripherals!(SPI2);
         } else if #[cfg(context = "esp32s3")] {
-            let _ = peripherals.SPI2.take().unwrap();
-            let _ = peripherals.SPI3.take().unwrap();
+            take_all_spi_peripherals!(SPI2, SPI3);
         } else {
             compile_error!("this ESP32 chip is not supported");
         }
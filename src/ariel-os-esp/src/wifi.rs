@@ -6,4 +6,17 @@ pub mod esp_wifi;
 // TODO: this should be factored out in ariel-os-embassy again
 pub(crate) const WIFI_NETWORK: &str =
     str_from_env!("CONFIG_WIFI_NETWORK", "Wi-Fi SSID (network name)");
+#[cfg(not(feature = "wifi-eap"))]
 pub(crate) const WIFI_PASSWORD: &str = str_from_env!("CONFIG_WIFI_PASSWORD", "Wi-Fi password");
+
+#[cfg(feature = "wifi-eap")]
+pub(crate) const WIFI_EAP_IDENTITY: &str = str_from_env!(
+    "CONFIG_WIFI_EAP_IDENTITY",
+    "Wi-Fi EAP identity, sent in the clear before the tunnel is established"
+);
+#[cfg(feature = "wifi-eap")]
+pub(crate) const WIFI_EAP_USERNAME: &str = ariel_os_utils::str_from_env_or!(
+    "CONFIG_WIFI_EAP_USERNAME",
+    WIFI_EAP_IDENTITY,
+    "Wi-Fi EAP username for PEAP, sent inside the tunnel"
+);
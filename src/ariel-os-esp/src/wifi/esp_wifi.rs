@@ -1,13 +1,14 @@
 use ariel_os_debug::log::{debug, info};
 use embassy_executor::Spawner;
 use embassy_time::{Duration, Timer};
+#[cfg(not(feature = "wifi-eap"))]
+use esp_wifi::wifi::ClientConfiguration;
+#[cfg(feature = "wifi-eap")]
+use esp_wifi::wifi::{AuthMethod, EapClientConfiguration};
 use esp_wifi::{
     EspWifiController,
     config::PowerSaveMode,
-    wifi::{
-        ClientConfiguration, Configuration, WifiController, WifiDevice, WifiEvent, WifiStaDevice,
-        WifiState,
-    },
+    wifi::{Configuration, WifiController, WifiDevice, WifiEvent, WifiStaDevice, WifiState},
 };
 use once_cell::sync::OnceCell;
 
@@ -20,6 +21,42 @@ pub type NetworkDevice = WifiDevice<'static, WifiStaDevice>;
 // sure.
 pub static WIFI_INIT: OnceCell<EspWifiController> = OnceCell::new();
 
+/// Credentials for WPA2-Enterprise networks.
+///
+/// These are loaded from the vault by `ariel-os-embassy`, which can only happen once storage is
+/// available, so they are passed through [`EAP_CONFIG`].
+#[cfg(feature = "wifi-eap")]
+pub enum EapCredentials {
+    /// PEAP (with MSCHAPv2), authenticating with a username and password.
+    Peap {
+        /// The password matching `CONFIG_WIFI_EAP_USERNAME`.
+        password: heapless::String<64>,
+    },
+    /// EAP-TLS, authenticating with a client certificate.
+    Tls {
+        /// The client certificate, in DER encoding.
+        certificate: &'static [u8],
+        /// The private key of the client certificate, in DER encoding.
+        private_key: &'static [u8],
+    },
+}
+
+/// Configuration for WPA2-Enterprise networks, in addition to the build-time configuration.
+#[cfg(feature = "wifi-eap")]
+pub struct EapConfig {
+    /// The credentials of the device.
+    pub credentials: EapCredentials,
+    /// The CA certificate the authentication server is verified against, in DER encoding.
+    ///
+    /// The authentication server is not verified if this is `None`.
+    pub ca_certificate: Option<&'static [u8]>,
+}
+
+/// Passes the [`EapConfig`] to the connection task, which waits for it before connecting.
+#[cfg(feature = "wifi-eap")]
+pub static EAP_CONFIG: embassy_sync::once_lock::OnceLock<EapConfig> =
+    embassy_sync::once_lock::OnceLock::new();
+
 pub fn init(peripherals: &mut crate::OptionalPeripherals, spawner: Spawner) -> NetworkDevice {
     let wifi = peripherals.WIFI.take().unwrap();
     let init = WIFI_INIT.get().unwrap();
@@ -51,11 +88,14 @@ async fn connection(mut controller: WifiController<'static>) {
         }
         if !matches!(controller.is_started(), Ok(true)) {
             debug!("Configuring Wi-Fi");
+            #[cfg(not(feature = "wifi-eap"))]
             let client_config = Configuration::Client(ClientConfiguration {
                 ssid: crate::wifi::WIFI_NETWORK.try_into().unwrap(),
                 password: crate::wifi::WIFI_PASSWORD.try_into().unwrap(),
                 ..Default::default()
             });
+            #[cfg(feature = "wifi-eap")]
+            let client_config = eap_client_configuration(EAP_CONFIG.get().await);
             controller.set_configuration(&client_config).unwrap();
             debug!("Starting Wi-Fi");
             controller.start_async().await.unwrap();
@@ -72,3 +112,29 @@ async fn connection(mut controller: WifiController<'static>) {
         }
     }
 }
+
+#[cfg(feature = "wifi-eap")]
+fn eap_client_configuration(config: &EapConfig) -> Configuration {
+    let (username, password, certificate_and_key) = match &config.credentials {
+        EapCredentials::Peap { password } => (
+            Some(crate::wifi::WIFI_EAP_USERNAME.try_into().unwrap()),
+            Some(password.clone()),
+            None,
+        ),
+        EapCredentials::Tls {
+            certificate,
+            private_key,
+        } => (None, None, Some((*certificate, *private_key, None))),
+    };
+
+    Configuration::EapClient(EapClientConfiguration {
+        ssid: crate::wifi::WIFI_NETWORK.try_into().unwrap(),
+        auth_method: AuthMethod::WPA2Enterprise,
+        identity: Some(crate::wifi::WIFI_EAP_IDENTITY.try_into().unwrap()),
+        username,
+        password,
+        ca_cert: config.ca_certificate,
+        certificate_and_key,
+        ..Default::default()
+    })
+}
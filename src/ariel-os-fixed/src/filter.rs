@@ -0,0 +1,223 @@
+//! Provides filters on fixed-point samples.
+//!
+//! An [`Ema`] smooths noisy readings with a single multiplication per sample, and a [`Biquad`]
+//! implements second-order filters, eg. low-pass filters with a sharper cutoff, whose
+//! [`Coefficients`] are computed beforehand with any filter design tool:
+//!
+//! ```
+//! use ariel_os_fixed::{Q16, filter::{Biquad, Coefficients, Ema}};
+//!
+//! let mut ema = Ema::new(Q16::from_ratio(1, 8));
+//! let smoothed = ema.update(Q16::from_int(21));
+//!
+//! // Butterworth low-pass filter with a cutoff at a tenth of the sample rate.
+//! let coefficients = Coefficients::from_f32(
+//!     [0.067_455_27, 0.134_910_55, 0.067_455_27],
+//!     [1., -1.142_980_5, 0.412_801_6],
+//! );
+//! let mut biquad = Biquad::new(coefficients);
+//! let filtered = biquad.update(Q16::from_int(21));
+//! ```
+
+use crate::Fixed;
+
+/// Number of fractional bits of the [`Coefficients`] of [`Biquad`] filters.
+const COEFFICIENT_FRAC: u32 = 30;
+
+/// A coefficient of a [`Biquad`] filter, in [-2, 2).
+pub type Coefficient = Fixed<COEFFICIENT_FRAC>;
+
+/// An exponential moving average, which smooths samples by moving towards each of them by a
+/// fraction `alpha` of the distance.
+///
+/// Lower values of `alpha` smooth more, but also react more slowly. With few fractional bits,
+/// the average may stop short of a constant input by up to 1/(2 `alpha`) times the resolution of
+/// the format, as moves smaller than the resolution are rounded away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Ema<const FRAC: u32> {
+    alpha: Fixed<FRAC>,
+    value: Option<Fixed<FRAC>>,
+}
+
+impl<const FRAC: u32> Ema<FRAC> {
+    /// Creates an average with the smoothing factor `alpha`, between 0 and 1.
+    #[must_use]
+    pub const fn new(alpha: Fixed<FRAC>) -> Self {
+        Self { alpha, value: None }
+    }
+
+    /// Adds a sample, and returns the updated average.
+    ///
+    /// The first sample after creation or after [`reset()`](Self::reset) is taken as the average.
+    pub fn update(&mut self, sample: Fixed<FRAC>) -> Fixed<FRAC> {
+        let value = match self.value {
+            Some(value) => value + self.alpha * (sample - value),
+            None => sample,
+        };
+        self.value = Some(value);
+        value
+    }
+
+    /// Returns the current average, if any sample was added.
+    #[must_use]
+    pub const fn value(&self) -> Option<Fixed<FRAC>> {
+        self.value
+    }
+
+    /// Forgets the samples added so far.
+    pub fn reset(&mut self) {
+        self.value = None;
+    }
+}
+
+/// The coefficients of a [`Biquad`] filter, normalized so that a0 is 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Coefficients {
+    b: [Coefficient; 3],
+    a: [Coefficient; 2],
+}
+
+impl Coefficients {
+    /// Creates coefficients from the feedforward coefficients `b` (b0, b1, b2) and the feedback
+    /// coefficients `a` (a1, a2), normalized so that a0 is 1.
+    #[must_use]
+    pub const fn new(b: [Coefficient; 3], a: [Coefficient; 2]) -> Self {
+        Self { b, a }
+    }
+
+    /// Converts the feedforward coefficients `b` (b0, b1, b2) and the feedback coefficients `a`
+    /// (a0, a1, a2), as given by filter design tools.
+    ///
+    /// The coefficients are normalized so that a0 is 1, and saturate outside of [-2, 2), which
+    /// they are within for stable filters.
+    #[must_use]
+    pub fn from_f32(b: [f32; 3], a: [f32; 3]) -> Self {
+        let [a0, a1, a2] = a;
+        Self {
+            b: b.map(|b| Coefficient::from_f32(b / a0)),
+            a: [a1, a2].map(|a| Coefficient::from_f32(a / a0)),
+        }
+    }
+}
+
+/// A second-order IIR filter, in direct form I.
+///
+/// Products are accumulated on 64 bits, and only the output is rounded, so that the filter does
+/// not accumulate rounding errors from its intermediate results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Biquad<const FRAC: u32> {
+    coefficients: Coefficients,
+    /// The previous two inputs, latest first.
+    inputs: [Fixed<FRAC>; 2],
+    /// The previous two outputs, latest first.
+    outputs: [Fixed<FRAC>; 2],
+}
+
+impl<const FRAC: u32> Biquad<FRAC> {
+    /// Creates a filter with `coefficients`, whose previous inputs and outputs are zero.
+    #[must_use]
+    pub const fn new(coefficients: Coefficients) -> Self {
+        Self {
+            coefficients,
+            inputs: [Fixed::ZERO; 2],
+            outputs: [Fixed::ZERO; 2],
+        }
+    }
+
+    /// Filters a sample, and returns the output of the filter.
+    pub fn update(&mut self, sample: Fixed<FRAC>) -> Fixed<FRAC> {
+        let [b0, b1, b2] = self.coefficients.b;
+        let [a1, a2] = self.coefficients.a;
+        let [x1, x2] = self.inputs;
+        let [y1, y2] = self.outputs;
+        // The sum of the five products, each below 2⁶², is within the range of i64.
+        let accumulator = product(b0, sample) + product(b1, x1) + product(b2, x2)
+            - product(a1, y1)
+            - product(a2, y2);
+        let output = Fixed::from_bits(crate::saturate(crate::round_shift(
+            accumulator,
+            COEFFICIENT_FRAC,
+        )));
+        self.inputs = [sample, x1];
+        self.outputs = [output, y1];
+        output
+    }
+
+    /// Sets the previous inputs and outputs to zero.
+    pub fn reset(&mut self) {
+        self.inputs = [Fixed::ZERO; 2];
+        self.outputs = [Fixed::ZERO; 2];
+    }
+}
+
+/// Returns the product of `coefficient` and `value`, with the fractional bits of both.
+fn product<const FRAC: u32>(coefficient: Coefficient, value: Fixed<FRAC>) -> i64 {
+    i64::from(coefficient.to_bits()) * i64::from(value.to_bits())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Q16;
+
+    #[test]
+    fn ema() {
+        let mut ema = Ema::new(Q16::from_ratio(1, 4));
+        assert_eq!(ema.value(), None);
+        assert_eq!(ema.update(Q16::from_int(8)), Q16::from_int(8));
+        assert_eq!(ema.update(Q16::from_int(16)), Q16::from_int(10));
+        assert_eq!(ema.update(Q16::from_int(16)), Q16::from_ratio(23, 2));
+        for _ in 0..100 {
+            ema.update(Q16::from_int(16));
+        }
+        assert_eq!(ema.value().map(Fixed::round), Some(16));
+
+        ema.reset();
+        assert_eq!(ema.update(Q16::from_int(-3)), Q16::from_int(-3));
+    }
+
+    #[test]
+    fn biquad_pass_through() {
+        let coefficients = Coefficients::new(
+            [Coefficient::ONE, Fixed::ZERO, Fixed::ZERO],
+            [Fixed::ZERO, Fixed::ZERO],
+        );
+        let mut biquad = Biquad::new(coefficients);
+        for value in [3, -7, 12] {
+            assert_eq!(biquad.update(Q16::from_int(value)), Q16::from_int(value));
+        }
+    }
+
+    #[test]
+    fn biquad_low_pass() {
+        // Butterworth low-pass filter with a cutoff at a tenth of the sample rate, whose gain is 1
+        // at DC.
+        let b = [0.067_455_27, 0.134_910_55, 0.067_455_27];
+        let a = [1., -1.142_980_5, 0.412_801_6];
+        let coefficients = Coefficients::from_f32(b, a);
+        let mut biquad = Biquad::new(coefficients);
+
+        // The step response converges to the input, without overshooting much.
+        let mut max = Q16::ZERO;
+        for _ in 0..100 {
+            max = max.max(biquad.update(Q16::from_int(100)));
+        }
+        assert!(max < Q16::from_int(106));
+        assert_eq!(biquad.update(Q16::from_int(100)).round(), 100);
+
+        // An alternating input at the Nyquist frequency is removed.
+        biquad.reset();
+        let mut last = Q16::ZERO;
+        for i in 0..100 {
+            last = biquad.update(Q16::from_int(if i % 2 == 0 { 100 } else { -100 }));
+        }
+        assert!(last.abs() < Q16::from_int(1));
+
+        // Normalization by a0.
+        let scaled = Coefficients::from_f32(b.map(|b| b * 2.), a.map(|a| a * 2.));
+        assert_eq!(scaled, coefficients);
+    }
+}
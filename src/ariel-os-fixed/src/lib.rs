@@ -0,0 +1,393 @@
+//! Provides fixed-point math for sensor and control code, so that MCUs without an FPU get
+//! consistent numerics without software floating-point.
+//!
+//! A [`Fixed<FRAC>`](Fixed) is a 32-bit signed number with `FRAC` fractional bits, ie. in the
+//! Q-format with 32 − `FRAC` integer bits (including the sign) and `FRAC` fractional bits, eg.
+//! [`Q16`] for values in [-32768, 32768) with a resolution of 2⁻¹⁶:
+//!
+//! ```
+//! use ariel_os_fixed::Q16;
+//!
+//! let celsius = Q16::from_ratio(2315, 100);
+//! let fahrenheit = celsius * Q16::from_ratio(9, 5) + Q16::from_int(32);
+//! assert_eq!(fahrenheit.round(), 74);
+//! ```
+//!
+//! Arithmetic saturates at the bounds of the format instead of wrapping around or panicking, and
+//! results are rounded to the nearest representable value, with ties away from zero.
+//!
+//! The [`filter`] module provides filters on fixed-point samples, for smoothing sensor readings
+//! or in control loops.
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+pub mod filter;
+
+use core::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+
+/// A fixed-point number with 16 integer bits (including the sign) and 16 fractional bits.
+pub type Q16 = Fixed<16>;
+
+/// A fixed-point number with 1 integer bit (the sign) and 31 fractional bits, in [-1, 1).
+pub type Q31 = Fixed<31>;
+
+/// A 32-bit signed fixed-point number with `FRAC` fractional bits.
+///
+/// `FRAC` must be lower than 32.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed<const FRAC: u32>(i32);
+
+impl<const FRAC: u32> Fixed<FRAC> {
+    const VALID: () = assert!(
+        FRAC < 32,
+        "fixed-point numbers have at most 31 fractional bits"
+    );
+
+    /// Zero.
+    pub const ZERO: Self = Self(0);
+    /// One, or the largest value if it cannot be represented.
+    pub const ONE: Self = Self::from_int(1);
+    /// The smallest value.
+    pub const MIN: Self = Self(i32::MIN);
+    /// The largest value.
+    pub const MAX: Self = Self(i32::MAX);
+    /// The smallest positive value, 2^-`FRAC`.
+    pub const DELTA: Self = Self(1);
+
+    /// Creates a number from its raw representation, `bits` × 2^-`FRAC`.
+    #[must_use]
+    pub const fn from_bits(bits: i32) -> Self {
+        let () = Self::VALID;
+        Self(bits)
+    }
+
+    /// Returns the raw representation of the number, which is the number × 2^`FRAC`.
+    #[must_use]
+    pub const fn to_bits(self) -> i32 {
+        self.0
+    }
+
+    /// Converts an integer, saturating if it cannot be represented.
+    #[must_use]
+    pub const fn from_int(value: i32) -> Self {
+        Self::from_bits(saturate((value as i64) << FRAC))
+    }
+
+    /// Returns `numerator`/`denominator`, rounded and saturating.
+    ///
+    /// A zero `denominator` saturates according to the sign of `numerator`.
+    #[must_use]
+    pub const fn from_ratio(numerator: i32, denominator: i32) -> Self {
+        Self::from_bits(div_round((numerator as i64) << FRAC, denominator as i64))
+    }
+
+    /// Converts a floating-point number, rounded and saturating; NaN is converted to zero.
+    #[must_use]
+    pub fn from_f32(value: f32) -> Self {
+        let scaled = value * scale::<FRAC>();
+        let rounded = if scaled < 0. {
+            scaled - 0.5
+        } else {
+            scaled + 0.5
+        };
+        #[expect(
+            clippy::cast_possible_truncation,
+            reason = "float to integer casts saturate, as intended"
+        )]
+        Self::from_bits(rounded as i32)
+    }
+
+    /// Converts the number to a floating-point number, which may be rounded.
+    #[must_use]
+    pub fn to_f32(self) -> f32 {
+        #[expect(
+            clippy::cast_precision_loss,
+            reason = "rounding to the precision of f32 is intended"
+        )]
+        let bits = self.0 as f32;
+        bits / scale::<FRAC>()
+    }
+
+    /// Returns the largest integer lower than or equal to the number.
+    #[must_use]
+    pub const fn to_int(self) -> i32 {
+        // Fractional bits are at most 31, so that the shift cannot overflow.
+        self.0 >> FRAC
+    }
+
+    /// Returns the nearest integer, with ties away from zero.
+    #[must_use]
+    pub const fn round(self) -> i32 {
+        saturate(round_shift(self.0 as i64, FRAC))
+    }
+
+    /// Converts the number to another number of fractional bits, rounded and saturating.
+    #[must_use]
+    pub const fn convert<const TO: u32>(self) -> Fixed<TO> {
+        let bits = self.0 as i64;
+        Fixed::from_bits(if TO >= FRAC {
+            saturate(bits << (TO - FRAC))
+        } else {
+            saturate(round_shift(bits, FRAC - TO))
+        })
+    }
+
+    /// Returns the absolute value, saturating.
+    #[must_use]
+    pub const fn abs(self) -> Self {
+        Self(self.0.saturating_abs())
+    }
+
+    /// Returns `self` + `other`, saturating.
+    #[must_use]
+    pub const fn saturating_add(self, other: Self) -> Self {
+        Self(self.0.saturating_add(other.0))
+    }
+
+    /// Returns `self` − `other`, saturating.
+    #[must_use]
+    pub const fn saturating_sub(self, other: Self) -> Self {
+        Self(self.0.saturating_sub(other.0))
+    }
+
+    /// Returns `self` × `other`, rounded and saturating.
+    #[must_use]
+    pub const fn saturating_mul(self, other: Self) -> Self {
+        Self(saturate(round_shift(self.0 as i64 * other.0 as i64, FRAC)))
+    }
+
+    /// Returns `self` × `factor`, saturating.
+    #[must_use]
+    pub const fn saturating_mul_int(self, factor: i32) -> Self {
+        Self(self.0.saturating_mul(factor))
+    }
+
+    /// Returns `self`/`other`, rounded and saturating.
+    ///
+    /// Dividing by zero saturates according to the sign of `self`.
+    #[must_use]
+    pub const fn saturating_div(self, other: Self) -> Self {
+        Self(div_round((self.0 as i64) << FRAC, other.0 as i64))
+    }
+}
+
+impl<const FRAC: u32> Add for Fixed<FRAC> {
+    type Output = Self;
+
+    /// Saturates, see [`Fixed::saturating_add()`].
+    fn add(self, other: Self) -> Self {
+        self.saturating_add(other)
+    }
+}
+
+impl<const FRAC: u32> AddAssign for Fixed<FRAC> {
+    fn add_assign(&mut self, other: Self) {
+        *self = self.saturating_add(other);
+    }
+}
+
+impl<const FRAC: u32> Sub for Fixed<FRAC> {
+    type Output = Self;
+
+    /// Saturates, see [`Fixed::saturating_sub()`].
+    fn sub(self, other: Self) -> Self {
+        self.saturating_sub(other)
+    }
+}
+
+impl<const FRAC: u32> SubAssign for Fixed<FRAC> {
+    fn sub_assign(&mut self, other: Self) {
+        *self = self.saturating_sub(other);
+    }
+}
+
+impl<const FRAC: u32> Mul for Fixed<FRAC> {
+    type Output = Self;
+
+    /// Rounds and saturates, see [`Fixed::saturating_mul()`].
+    fn mul(self, other: Self) -> Self {
+        self.saturating_mul(other)
+    }
+}
+
+impl<const FRAC: u32> MulAssign for Fixed<FRAC> {
+    fn mul_assign(&mut self, other: Self) {
+        *self = self.saturating_mul(other);
+    }
+}
+
+impl<const FRAC: u32> Div for Fixed<FRAC> {
+    type Output = Self;
+
+    /// Rounds and saturates, see [`Fixed::saturating_div()`].
+    fn div(self, other: Self) -> Self {
+        self.saturating_div(other)
+    }
+}
+
+impl<const FRAC: u32> Neg for Fixed<FRAC> {
+    type Output = Self;
+
+    /// Saturates, as the negation of [`Fixed::MIN`] cannot be represented.
+    fn neg(self) -> Self {
+        Self(self.0.saturating_neg())
+    }
+}
+
+impl<const FRAC: u32> core::fmt::Debug for Fixed<FRAC> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(&self.to_f32(), f)
+    }
+}
+
+impl<const FRAC: u32> core::fmt::Display for Fixed<FRAC> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(&self.to_f32(), f)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<const FRAC: u32> defmt::Format for Fixed<FRAC> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{}", self.to_f32());
+    }
+}
+
+/// Returns 2^`FRAC`.
+fn scale<const FRAC: u32>() -> f32 {
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "powers of two are represented exactly"
+    )]
+    let scale = (1_u64 << FRAC) as f32;
+    scale
+}
+
+/// Returns `value`, saturated to the range of `i32`.
+#[expect(
+    clippy::cast_possible_truncation,
+    reason = "the value is within the range of i32"
+)]
+const fn saturate(value: i64) -> i32 {
+    if value > i32::MAX as i64 {
+        i32::MAX
+    } else if value < i32::MIN as i64 {
+        i32::MIN
+    } else {
+        value as i32
+    }
+}
+
+/// Returns `value` / 2^`shift`, rounded with ties away from zero.
+///
+/// `value` must be within ±2⁶², which products of two `i32` are.
+const fn round_shift(value: i64, shift: u32) -> i64 {
+    if shift == 0 {
+        return value;
+    }
+    let half = 1 << (shift - 1);
+    if value < 0 {
+        -((half - value) >> shift)
+    } else {
+        (value + half) >> shift
+    }
+}
+
+/// Returns `numerator`/`denominator`, rounded with ties away from zero and saturated to the range
+/// of `i32`.
+///
+/// A zero `denominator` saturates according to the sign of `numerator`.
+const fn div_round(numerator: i64, denominator: i64) -> i32 {
+    if denominator == 0 {
+        return if numerator < 0 {
+            i32::MIN
+        } else if numerator > 0 {
+            i32::MAX
+        } else {
+            0
+        };
+    }
+    // Both are within ±2⁶³ − 1, as they are shifted `i32`s, so that these cannot overflow.
+    let quotient = numerator / denominator;
+    let remainder = numerator % denominator;
+    let rounded = if 2 * remainder.unsigned_abs() >= denominator.unsigned_abs() {
+        if (numerator < 0) == (denominator < 0) {
+            quotient + 1
+        } else {
+            quotient - 1
+        }
+    } else {
+        quotient
+    };
+    saturate(rounded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        assert_eq!(Q16::from_int(3).to_bits(), 3 << 16);
+        assert_eq!(Q16::from_int(40_000), Q16::MAX);
+        assert_eq!(Q16::from_int(-40_000), Q16::MIN);
+        assert_eq!(Q31::ONE, Q31::MAX);
+
+        assert_eq!(Q16::from_ratio(1, 2).to_bits(), 1 << 15);
+        assert_eq!(Q16::from_ratio(-1, 3).to_bits(), -21845);
+        assert_eq!(Q16::from_ratio(1, 0), Q16::MAX);
+        assert_eq!(Q16::from_ratio(0, 0), Q16::ZERO);
+
+        assert_eq!(Q16::from_f32(1.5).to_bits(), 3 << 15);
+        assert_eq!(Q16::from_f32(-1.5).to_bits(), -(3 << 15));
+        assert_eq!(Q16::from_f32(1e9), Q16::MAX);
+        assert_eq!(Q16::from_f32(f32::NAN), Q16::ZERO);
+        assert!((Q16::from_ratio(-7, 4).to_f32() + 1.75).abs() < f32::EPSILON);
+
+        assert_eq!(Q16::from_ratio(-3, 2).to_int(), -2);
+        assert_eq!(Q16::from_ratio(-3, 2).round(), -2);
+        assert_eq!(Q16::from_ratio(5, 2).round(), 3);
+        assert_eq!(Q16::from_ratio(9, 4).round(), 2);
+
+        let half: Fixed<8> = Q16::from_ratio(1, 2).convert();
+        assert_eq!(half.to_bits(), 1 << 7);
+        assert_eq!(Q16::MAX.convert::<24>(), Fixed::<24>::MAX);
+        assert_eq!(Q16::DELTA.convert::<8>(), Fixed::<8>::ZERO);
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = Q16::from_ratio(3, 2);
+        let b = Q16::from_ratio(-1, 4);
+        assert_eq!(a + b, Q16::from_ratio(5, 4));
+        assert_eq!(a - b, Q16::from_ratio(7, 4));
+        assert_eq!(a * b, Q16::from_ratio(-3, 8));
+        assert_eq!(a / b, Q16::from_int(-6));
+        assert_eq!(-a, Q16::from_ratio(-3, 2));
+        assert_eq!(b.abs(), Q16::from_ratio(1, 4));
+        assert_eq!(a.saturating_mul_int(4), Q16::from_int(6));
+
+        // Rounding of the products, with ties away from zero.
+        assert_eq!(Q16::DELTA * Q16::from_ratio(1, 2), Q16::DELTA);
+        assert_eq!(-Q16::DELTA * Q16::from_ratio(1, 2), -Q16::DELTA);
+        assert_eq!(Q16::DELTA * Q16::from_ratio(1, 4), Q16::ZERO);
+
+        // Saturation.
+        assert_eq!(Q16::MAX + Q16::ONE, Q16::MAX);
+        assert_eq!(Q16::MIN - Q16::ONE, Q16::MIN);
+        assert_eq!(-Q16::MIN, Q16::MAX);
+        assert_eq!(Q16::from_int(300) * Q16::from_int(300), Q16::MAX);
+        assert_eq!(Q16::from_int(300) * Q16::from_int(-300), Q16::MIN);
+        assert_eq!(Q16::ONE / Q16::ZERO, Q16::MAX);
+        assert_eq!(-Q16::ONE / Q16::ZERO, Q16::MIN);
+        assert_eq!(Q16::from_int(30_000) / Q16::from_ratio(1, 2), Q16::MAX);
+
+        let mut c = a;
+        c += b;
+        c -= b;
+        c *= Q16::from_int(2);
+        assert_eq!(c, Q16::from_int(3));
+    }
+}
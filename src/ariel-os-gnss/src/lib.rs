@@ -0,0 +1,100 @@
+//! Provides support for GNSS receivers, which give the position of the device and the time.
+//!
+//! Receivers report their fixes over a serial link as NMEA 0183 sentences, which the [`nmea`]
+//! module parses, and u-blox receivers also as UBX messages, which the [`ubx`] module parses with
+//! the `ubx` feature. [`Gnss::run()`] reads them from a byte stream implementing
+//! [`embedded_io_async::Read`], eg. a buffered UART, and exposes the fixes through the sensor
+//! framework:
+//!
+//! ```ignore
+//! use ariel_os::gnss::Gnss;
+//!
+//! static GNSS: Gnss = Gnss::new(Some("gnss"));
+//! ariel_os::sensors::register_sensor!(GNSS);
+//!
+//! #[ariel_os::task(autostart, peripherals)]
+//! async fn gnss(peripherals: pins::Peripherals) {
+//!     let uart = pins::GnssUart::new(peripherals.uart_rx, peripherals.uart_tx, uart_config);
+//!     let _ = GNSS.run(uart).await;
+//! }
+//! ```
+//!
+//! Readings of the [`Gnss`] sensor consist of the latitude and the longitude, in 10⁻⁷ degrees,
+//! followed by the altitude above mean sea level, in centimeters. The complete latest [`Fix`],
+//! including its time and quality, is returned by [`Gnss::fix()`].
+//!
+//! # Wall clock
+//!
+//! The time of fixes is only known to within the delay of their transmission. Receivers mark the
+//! start of each second precisely on their PPS (pulse per second) output; once it is connected to
+//! a GPIO, [`Gnss::discipline_wall_clock()`] sets the wall clock of the
+//! [`calendar`](ariel_os_calendar) at each pulse, to the time of the fix that follows it:
+//!
+//! ```ignore
+//! let pps = gpio::Input::builder(peripherals.gnss_pps, gpio::Pull::None)
+//!     .build_with_interrupt()
+//!     .unwrap();
+//! let _ = GNSS.discipline_wall_clock(pps).await;
+//! ```
+//!
+//! Pulses are timestamped when their interrupt is handled, which bounds the accuracy of the wall
+//! clock to the interrupt latency rather than to that of a hardware input capture.
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+pub mod nmea;
+mod sensor;
+#[cfg(feature = "ubx")]
+pub mod ubx;
+
+pub use sensor::Gnss;
+
+/// Quality of a [`Fix`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Quality {
+    /// The position is not known.
+    #[default]
+    NoFix,
+    /// The position is computed from the satellite signals only.
+    Autonomous,
+    /// The position is corrected with differential data, eg. from SBAS satellites.
+    Differential,
+    /// The position is corrected through real-time kinematics, with fixed ambiguities.
+    RtkFixed,
+    /// The position is corrected through real-time kinematics, with floating ambiguities.
+    RtkFloat,
+    /// The position is estimated from previous fixes and motion sensors.
+    DeadReckoning,
+}
+
+/// A position on the WGS 84 ellipsoid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Position {
+    /// Latitude, in 10⁻⁷ degrees, positive north of the equator.
+    pub latitude: i32,
+    /// Longitude, in 10⁻⁷ degrees, positive east of the prime meridian.
+    pub longitude: i32,
+}
+
+/// A fix of a GNSS receiver.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Fix {
+    /// Time of the fix, in whole seconds since the Unix epoch, once the receiver knows the date.
+    pub timestamp: Option<u64>,
+    /// Quality of the fix.
+    pub quality: Quality,
+    /// Position, when the receiver has a fix.
+    pub position: Option<Position>,
+    /// Altitude above mean sea level, in centimeters.
+    pub altitude: Option<i32>,
+    /// Number of satellites used.
+    pub satellites: Option<u8>,
+    /// Horizontal dilution of precision, in hundredths.
+    pub hdop: Option<u16>,
+}
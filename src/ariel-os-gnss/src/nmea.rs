@@ -0,0 +1,407 @@
+//! Parses the NMEA 0183 sentences of GNSS receivers.
+//!
+//! Fixes are obtained from the GGA sentences, which carry the position, the altitude and the
+//! quality of the fix, and take their date from the RMC sentences. Receivers that do not send GGA
+//! sentences get their fixes from the RMC sentences only, without altitude. Sentences of all
+//! talkers are accepted, eg. `$GPGGA` as well as `$GNGGA`, and sentences with a missing or wrong
+//! checksum are dropped.
+//!
+//! ```
+//! use ariel_os_gnss::nmea::Parser;
+//!
+//! let mut parser = Parser::new();
+//! let sentences = b"$GPRMC,092750.000,A,5321.6802,N,00630.3372,W,0.02,31.66,280511,,,A*43\r\n\
+//!     $GPGGA,092750.000,5321.6802,N,00630.3372,W,1,8,1.03,61.7,M,55.2,M,,*76\r\n";
+//! let fix = sentences.iter().filter_map(|byte| parser.push(*byte)).last().unwrap();
+//! assert_eq!(fix.timestamp, Some(1_306_574_870));
+//! assert_eq!(fix.altitude, Some(6170));
+//! ```
+
+use ariel_os_calendar::{DateTime, UtcOffset};
+
+use crate::{Fix, Position, Quality};
+
+/// Maximum length of sentences, between `$` and the end of the line.
+///
+/// The standard allows 80 characters, which some receivers exceed.
+const MAX_SENTENCE_LEN: usize = 120;
+
+const MILLIS_PER_DAY: u32 = 86_400_000;
+
+/// Parses NMEA sentences, byte by byte, into [`Fix`]es.
+#[derive(Debug)]
+pub struct Parser {
+    sentence: [u8; MAX_SENTENCE_LEN],
+    len: usize,
+    in_sentence: bool,
+    state: State,
+}
+
+/// State kept across sentences.
+#[derive(Debug)]
+struct State {
+    /// Start of the day of the last date received, in seconds since the Unix epoch, and time of
+    /// day of the sentence it was received in, in milliseconds.
+    date: Option<(u64, u32)>,
+    /// Whether the receiver sends GGA sentences.
+    gga_seen: bool,
+}
+
+impl Parser {
+    /// Creates a parser.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            sentence: [0; MAX_SENTENCE_LEN],
+            len: 0,
+            in_sentence: false,
+            state: State {
+                date: None,
+                gga_seen: false,
+            },
+        }
+    }
+
+    /// Parses the next received byte, and returns the fix completed by it, if any.
+    pub fn push(&mut self, byte: u8) -> Option<Fix> {
+        match byte {
+            b'$' => {
+                self.len = 0;
+                self.in_sentence = true;
+            }
+            b'\r' | b'\n' if self.in_sentence => {
+                self.in_sentence = false;
+                let (data, checksum) = split_checksum(self.sentence.get(..self.len)?)?;
+                if checksum == data.iter().fold(0, |sum, byte| sum ^ byte) {
+                    return self.state.parse(data);
+                }
+            }
+            _ if self.in_sentence => {
+                if let Some(slot) = self.sentence.get_mut(self.len) {
+                    *slot = byte;
+                    self.len += 1;
+                } else {
+                    self.in_sentence = false;
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl State {
+    /// Parses the data of a sentence whose checksum was verified.
+    fn parse(&mut self, data: &[u8]) -> Option<Fix> {
+        let mut fields = data.split(|byte| *byte == b',');
+        let address = fields.next()?;
+        match address.get(address.len().checked_sub(3)?..)? {
+            b"GGA" => {
+                self.gga_seen = true;
+                self.parse_gga(fields)
+            }
+            b"RMC" => {
+                let fix = self.parse_rmc(fields)?;
+                (!self.gga_seen).then_some(fix)
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_gga<'a>(&self, mut fields: impl Iterator<Item = &'a [u8]>) -> Option<Fix> {
+        let time = time_of_day(fields.next()?);
+        let position = position(
+            fields.next()?,
+            fields.next()?,
+            fields.next()?,
+            fields.next()?,
+        );
+        let quality = match fields.next()? {
+            b"1" => Quality::Autonomous,
+            b"2" | b"3" => Quality::Differential,
+            b"4" => Quality::RtkFixed,
+            b"5" => Quality::RtkFloat,
+            b"6" => Quality::DeadReckoning,
+            _ => Quality::NoFix,
+        };
+        let satellites = decimal(fields.next()?, 0).and_then(|n| u8::try_from(n).ok());
+        let hdop = decimal(fields.next()?, 2).and_then(|n| u16::try_from(n).ok());
+        let altitude = decimal(fields.next()?, 2).and_then(|n| i32::try_from(n).ok());
+        let has_fix = quality != Quality::NoFix;
+        Some(Fix {
+            timestamp: time.and_then(|time| self.timestamp(time)),
+            quality,
+            position: position.filter(|_| has_fix),
+            altitude: altitude.filter(|_| has_fix),
+            satellites,
+            hdop,
+        })
+    }
+
+    fn parse_rmc<'a>(&mut self, mut fields: impl Iterator<Item = &'a [u8]>) -> Option<Fix> {
+        let time = time_of_day(fields.next()?);
+        let valid = fields.next()? == b"A";
+        let position = position(
+            fields.next()?,
+            fields.next()?,
+            fields.next()?,
+            fields.next()?,
+        );
+        // Speed and course.
+        fields.next()?;
+        fields.next()?;
+        if let (Some(date), Some(time)) = (date(fields.next()?), time) {
+            self.date = Some((date, time));
+        }
+        // Magnetic variation, and the mode indicator of NMEA 2.3 and later.
+        let quality = match fields.nth(2) {
+            _ if !valid => Quality::NoFix,
+            Some(b"D") => Quality::Differential,
+            Some(b"R") => Quality::RtkFixed,
+            Some(b"F") => Quality::RtkFloat,
+            Some(b"E") => Quality::DeadReckoning,
+            Some(b"N") => Quality::NoFix,
+            _ => Quality::Autonomous,
+        };
+        Some(Fix {
+            timestamp: time.and_then(|time| self.timestamp(time)),
+            quality,
+            position: position.filter(|_| quality != Quality::NoFix),
+            ..Fix::default()
+        })
+    }
+
+    /// Returns the timestamp of `time` of day, in milliseconds, from the last date received.
+    fn timestamp(&self, time: u32) -> Option<u64> {
+        let (day, date_time) = self.date?;
+        // The day has changed since the date was received.
+        let day = if time < date_time { day + 86_400 } else { day };
+        Some(day + u64::from(time / 1000))
+    }
+}
+
+/// Splits a sentence into its data and its checksum.
+fn split_checksum(sentence: &[u8]) -> Option<(&[u8], u8)> {
+    let separator = sentence.iter().rposition(|byte| *byte == b'*')?;
+    let data = sentence.get(..separator)?;
+    let checksum = sentence.get(separator + 1..)?;
+    let checksum = core::str::from_utf8(checksum).ok()?;
+    Some((data, u8::from_str_radix(checksum, 16).ok()?))
+}
+
+/// Parses a decimal number, with `decimals` fractional digits, as an integer scaled by
+/// `10^decimals`; further digits are truncated.
+fn decimal(field: &[u8], decimals: u32) -> Option<i64> {
+    let (negative, field) = match field.split_first()? {
+        (b'-', rest) => (true, rest),
+        _ => (false, field),
+    };
+    let (integer, fraction) = match field.iter().position(|byte| *byte == b'.') {
+        Some(point) => (field.get(..point)?, field.get(point + 1..)?),
+        None => (field, &[][..]),
+    };
+    if integer.is_empty() || integer.len() > 10 {
+        return None;
+    }
+    let mut value: i64 = 0;
+    let fraction = fraction.iter().chain(core::iter::repeat(&b'0'));
+    for digit in integer.iter().chain(fraction.take(decimals as usize)) {
+        if !digit.is_ascii_digit() {
+            return None;
+        }
+        value = value * 10 + i64::from(digit - b'0');
+    }
+    Some(if negative { -value } else { value })
+}
+
+/// Parses a time of day, `hhmmss.sss`, into milliseconds.
+fn time_of_day(field: &[u8]) -> Option<u32> {
+    let time = u32::try_from(decimal(field, 3)?).ok()?;
+    let (hours, minutes, millis) = (time / 10_000_000, time / 100_000 % 100, time % 100_000);
+    let time = hours * 3_600_000 + minutes * 60_000 + millis;
+    (time < MILLIS_PER_DAY).then_some(time)
+}
+
+/// Parses a date, `ddmmyy`, into the start of the day, in seconds since the Unix epoch.
+///
+/// Years are taken to lie between 2000 and 2099.
+fn date(field: &[u8]) -> Option<u64> {
+    if field.len() != 6 {
+        return None;
+    }
+    let date = u32::try_from(decimal(field, 0)?).ok()?;
+    let (day, month, year) = (date / 10_000, date / 100 % 100, date % 100);
+    let date_time = DateTime::new(
+        2000 + i32::try_from(year).ok()?,
+        u8::try_from(month).ok()?,
+        u8::try_from(day).ok()?,
+        0,
+        0,
+        0,
+    )?;
+    date_time.to_unix(UtcOffset::UTC)
+}
+
+/// Parses a position, `ddmm.mmmm,N,dddmm.mmmm,E`.
+fn position(latitude: &[u8], north: &[u8], longitude: &[u8], east: &[u8]) -> Option<Position> {
+    let latitude = coordinate(latitude, 90)?;
+    let longitude = coordinate(longitude, 180)?;
+    Some(Position {
+        latitude: match north {
+            b"N" => latitude,
+            b"S" => -latitude,
+            _ => return None,
+        },
+        longitude: match east {
+            b"E" => longitude,
+            b"W" => -longitude,
+            _ => return None,
+        },
+    })
+}
+
+/// Parses a coordinate in degrees and minutes, `dddmm.mmmm`, into 10⁻⁷ degrees.
+fn coordinate(field: &[u8], max_degrees: i64) -> Option<i32> {
+    // Minutes are scaled by 10^5.
+    let value = decimal(field, 5)?;
+    let (degrees, minutes) = (value / 10_000_000, value % 10_000_000);
+    if value < 0 || degrees > max_degrees || minutes >= 6_000_000 {
+        return None;
+    }
+    i32::try_from(degrees * 10_000_000 + minutes * 10 / 6).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(parser: &mut Parser, sentences: &[u8]) -> Option<Fix> {
+        sentences
+            .iter()
+            .filter_map(|byte| parser.push(*byte))
+            .last()
+    }
+
+    #[test]
+    fn gga_and_rmc() {
+        let mut parser = Parser::new();
+        let fix = parse(
+            &mut parser,
+            b"$GNRMC,235959.00,A,4717.11399,N,00833.91590,E,0.004,77.52,091202,,,A*40\r\n\
+              $GNGGA,235959.00,4717.11399,N,00833.91590,E,1,08,1.01,499.6,M,48.0,M,,*4F\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            fix,
+            Fix {
+                // 2002-12-09T23:59:59Z
+                timestamp: Some(1_039_478_399),
+                quality: Quality::Autonomous,
+                position: Some(Position {
+                    latitude: 472_852_331,
+                    longitude: 85_652_650,
+                }),
+                altitude: Some(49960),
+                satellites: Some(8),
+                hdop: Some(101),
+            }
+        );
+
+        // The next GGA sentence is past midnight.
+        let fix = parse(
+            &mut parser,
+            b"$GNGGA,000000.00,4717.11399,S,00833.91590,W,2,08,1.01,-4.5,M,48.0,M,,*6C\n",
+        )
+        .unwrap();
+        assert_eq!(fix.timestamp, Some(1_039_478_400));
+        assert_eq!(fix.quality, Quality::Differential);
+        assert_eq!(
+            fix.position,
+            Some(Position {
+                latitude: -472_852_331,
+                longitude: -85_652_650,
+            })
+        );
+        assert_eq!(fix.altitude, Some(-450));
+    }
+
+    #[test]
+    fn no_fix() {
+        let mut parser = Parser::new();
+        let fix = parse(&mut parser, b"$GPGGA,092750.000,,,,,0,0,,,M,,M,,*41\r\n").unwrap();
+        assert_eq!(
+            fix,
+            Fix {
+                satellites: Some(0),
+                ..Fix::default()
+            }
+        );
+    }
+
+    #[test]
+    fn rmc_only() {
+        let mut parser = Parser::new();
+        let fix = parse(
+            &mut parser,
+            b"$GPRMC,092750.000,A,5321.6802,N,00630.3372,W,0.02,31.66,280511,,,D*46\r\n",
+        )
+        .unwrap();
+        assert_eq!(fix.timestamp, Some(1_306_574_870));
+        assert_eq!(fix.quality, Quality::Differential);
+        assert!(fix.position.is_some());
+        assert_eq!(fix.altitude, None);
+    }
+
+    #[test]
+    fn invalid_sentences() {
+        let mut parser = Parser::new();
+        // Wrong checksum.
+        assert_eq!(
+            parse(
+                &mut parser,
+                b"$GPGGA,092750.000,5321.6802,N,00630.3372,W,1,8,1.03,61.7,M,55.2,M,,*77\r\n"
+            ),
+            None
+        );
+        // Missing checksum.
+        assert_eq!(
+            parse(
+                &mut parser,
+                b"$GPGGA,092750.000,5321.6802,N,00630.3372,W,1,8,1.03,61.7,M,55.2,M,,\r\n"
+            ),
+            None
+        );
+        // Truncated by the start of the next sentence.
+        assert_eq!(
+            parse(&mut parser, b"$GPGGA,092750.000,5321.6$GPGSA,A,3,,,*1C\r\n"),
+            None
+        );
+        // Overlong.
+        let mut overlong = [b'0'; 200];
+        overlong[0] = b'$';
+        assert_eq!(parse(&mut parser, &overlong), None);
+        assert_eq!(parse(&mut parser, b"\r\n"), None);
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(decimal(b"61.7", 2), Some(6170));
+        assert_eq!(decimal(b"-0.123", 2), Some(-12));
+        assert_eq!(decimal(b"12", 1), Some(120));
+        assert_eq!(decimal(b"", 1), None);
+        assert_eq!(decimal(b"1a", 0), None);
+        assert_eq!(time_of_day(b"235959.999"), Some(MILLIS_PER_DAY - 1));
+        assert_eq!(time_of_day(b"240000"), None);
+        assert_eq!(date(b"290224"), Some(1_709_164_800));
+        assert_eq!(date(b"300223"), None);
+        assert_eq!(coordinate(b"00000.00000", 180), Some(0));
+        assert_eq!(coordinate(b"9100.0", 90), None);
+        assert_eq!(coordinate(b"1060.0", 90), None);
+    }
+}
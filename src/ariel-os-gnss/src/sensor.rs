@@ -0,0 +1,219 @@
+use core::{
+    cell::Cell,
+    convert::Infallible,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use ariel_os_sensors::{
+    Accuracy, Category, Error, Label, MeasurementUnit, Mode, ReadingChannel, ReadingResult,
+    ReadingWaiter, Sample, Samples, Sensor, State, signaling::Signaling,
+};
+use embassy_futures::poll_once;
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    signal::Signal,
+};
+use embassy_time::{Duration, Instant, with_timeout};
+use embedded_hal_async::digital::Wait;
+use embedded_io_async::Read;
+
+use crate::{Fix, Quality, nmea};
+
+/// Maximum delay between a pulse of the PPS output and the fix of the second it marks.
+const PPS_FIX_TIMEOUT: Duration = Duration::from_millis(900);
+
+const CHANNELS: &[ReadingChannel] = &[
+    ReadingChannel::new(Label::Latitude, -7, MeasurementUnit::Degree),
+    ReadingChannel::new(Label::Longitude, -7, MeasurementUnit::Degree),
+    ReadingChannel::new(Label::Altitude, -2, MeasurementUnit::Meter),
+];
+
+/// A GNSS receiver.
+///
+/// Readings are obtained from the latest fix: in [`Mode::OneShot`], a triggered measurement
+/// resolves with the next fix, and in [`Mode::Triggered`], every fix with a position is signaled.
+/// A fix without a position is read as [`Error::NotReady`].
+pub struct Gnss {
+    label: Option<&'static str>,
+    state: AtomicU8,
+    signaling: Signaling,
+    fix: Mutex<CriticalSectionRawMutex, Cell<Option<Fix>>>,
+    new_fix: Signal<CriticalSectionRawMutex, Fix>,
+}
+
+impl Gnss {
+    /// Creates a GNSS receiver, labeled `label`.
+    #[must_use]
+    pub const fn new(label: Option<&'static str>) -> Self {
+        Self {
+            label,
+            state: AtomicU8::new(State::Uninitialized as u8),
+            signaling: Signaling::new(),
+            fix: Mutex::new(Cell::new(None)),
+            new_fix: Signal::new(),
+        }
+    }
+
+    /// Runs the driver, parsing the NMEA sentences, and the UBX messages with the `ubx` feature,
+    /// read from `uart`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SensorAccess`] if reading from `uart` fails or reaches its end.
+    pub async fn run<R: Read>(&'static self, mut uart: R) -> Result<Infallible, Error> {
+        let mut nmea = nmea::Parser::new();
+        #[cfg(feature = "ubx")]
+        let mut ubx = crate::ubx::Parser::new();
+        let mut buffer = [0; 64];
+
+        self.set_state(State::OneShot);
+        loop {
+            let len = match uart.read(&mut buffer).await {
+                Ok(0) | Err(_) => {
+                    self.set_state(State::Uninitialized);
+                    return Err(Error::SensorAccess);
+                }
+                Ok(len) => len,
+            };
+            for byte in buffer.get(..len).unwrap_or_default() {
+                if let Some(fix) = nmea.push(*byte) {
+                    self.publish(fix);
+                }
+                #[cfg(feature = "ubx")]
+                if let Some(fix) = ubx.push(*byte) {
+                    self.publish(fix);
+                }
+            }
+        }
+    }
+
+    /// Returns the latest fix, if any was received.
+    #[must_use]
+    pub fn fix(&self) -> Option<Fix> {
+        self.fix.lock(Cell::get)
+    }
+
+    /// Sets the wall clock at each rising edge of `pps`, the PPS output of the receiver, to the
+    /// time of the fix received after it.
+    ///
+    /// Pulses not followed by a fix with a time within 900 ms are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `pps` if waiting for a pulse fails.
+    pub async fn discipline_wall_clock<P: Wait>(
+        &'static self,
+        mut pps: P,
+    ) -> Result<Infallible, P::Error> {
+        loop {
+            pps.wait_for_rising_edge().await?;
+            let edge = Instant::now();
+            self.new_fix.reset();
+            if let Ok(fix) = with_timeout(PPS_FIX_TIMEOUT, self.new_fix.wait()).await
+                && fix.quality != Quality::NoFix
+                && let Some(timestamp) = fix.timestamp
+            {
+                ariel_os_calendar::set_now_at(timestamp, edge);
+            }
+        }
+    }
+
+    fn publish(&self, fix: Fix) {
+        self.fix.lock(|cell| cell.set(Some(fix)));
+        self.new_fix.signal(fix);
+
+        let reading = reading(&fix);
+        match self.state() {
+            State::OneShot => {
+                if poll_once(self.signaling.wait_for_trigger()).is_ready() {
+                    self.signaling.signal_reading(reading);
+                }
+            }
+            State::Triggered => {
+                if reading.is_ok() {
+                    self.signaling.signal_reading(reading);
+                }
+            }
+            State::Uninitialized | State::Disabled => {}
+        }
+    }
+
+    fn set_state(&self, state: State) {
+        self.state.store(state as u8, Ordering::Release);
+    }
+}
+
+/// Returns the reading of `fix`.
+///
+/// # Errors
+///
+/// Returns [`Error::NotReady`] if `fix` has no position.
+fn reading(fix: &Fix) -> ReadingResult {
+    let position = fix.position.ok_or(Error::NotReady)?;
+    Ok(Samples::from_array([
+        Sample::new(position.latitude, Accuracy::Unknown),
+        Sample::new(position.longitude, Accuracy::Unknown),
+        Sample::new(fix.altitude.unwrap_or_default(), Accuracy::Unknown),
+    ]))
+}
+
+impl Sensor for Gnss {
+    fn trigger_measurement(&self) -> Result<(), Error> {
+        match self.state() {
+            State::Uninitialized => Err(Error::Uninitialized),
+            State::Disabled => Err(Error::Disabled),
+            State::OneShot => {
+                self.signaling.trigger_measurement();
+                Ok(())
+            }
+            // The next fix is signaled anyway.
+            State::Triggered => Ok(()),
+        }
+    }
+
+    fn wait_for_reading(&'static self) -> ReadingWaiter {
+        match self.state() {
+            State::Uninitialized => ReadingWaiter::err(Error::Uninitialized),
+            State::Disabled => ReadingWaiter::err(Error::Disabled),
+            State::OneShot | State::Triggered => self.signaling.wait_for_reading(),
+        }
+    }
+
+    fn set_mode(&self, mode: Mode) -> Result<State, Error> {
+        if self.state() == State::Uninitialized {
+            return Err(Error::Uninitialized);
+        }
+        let state = State::from(mode);
+        self.set_state(state);
+        Ok(state)
+    }
+
+    fn state(&self) -> State {
+        match self.state.load(Ordering::Acquire) {
+            x if x == State::Disabled as u8 => State::Disabled,
+            x if x == State::OneShot as u8 => State::OneShot,
+            x if x == State::Triggered as u8 => State::Triggered,
+            _ => State::Uninitialized,
+        }
+    }
+
+    fn categories(&self) -> &'static [Category] {
+        &[Category::Location]
+    }
+
+    fn reading_channels(&self) -> &'static [ReadingChannel] {
+        CHANNELS
+    }
+
+    fn label(&self) -> Option<&'static str> {
+        self.label
+    }
+
+    fn display_name(&self) -> Option<&'static str> {
+        Some("GNSS receiver")
+    }
+
+    fn part_number(&self) -> Option<&'static str> {
+        None
+    }
+}
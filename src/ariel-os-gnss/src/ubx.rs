@@ -0,0 +1,258 @@
+//! Parses the UBX messages of u-blox receivers.
+//!
+//! Fixes are obtained from the UBX-NAV-PVT messages, which u-blox receivers send once enabled
+//! in their configuration; other messages are skipped. Compared to NMEA sentences, they carry the
+//! complete fix in a single message, at a higher resolution.
+//!
+//! The NAV-PVT message does not include the horizontal dilution of precision, which is therefore
+//! missing from the fixes.
+
+use ariel_os_calendar::{DateTime, UtcOffset};
+
+use crate::{Fix, Position, Quality};
+
+const SYNC: [u8; 2] = [0xb5, 0x62];
+
+/// Length of the class, the ID and the length of the payload of messages.
+const HEADER_LEN: usize = 4;
+
+const CLASS_NAV: u8 = 0x01;
+const ID_NAV_PVT: u8 = 0x07;
+const NAV_PVT_LEN: usize = 92;
+
+/// Parses UBX messages, byte by byte, into [`Fix`]es.
+#[derive(Debug)]
+pub struct Parser {
+    /// Header, payload and checksum of the current message.
+    message: [u8; HEADER_LEN + NAV_PVT_LEN + 2],
+    len: usize,
+    /// Number of bytes of the synchronization characters received.
+    synced: usize,
+}
+
+impl Parser {
+    /// Creates a parser.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            message: [0; HEADER_LEN + NAV_PVT_LEN + 2],
+            len: 0,
+            synced: 0,
+        }
+    }
+
+    /// Parses the next received byte, and returns the fix completed by it, if any.
+    pub fn push(&mut self, byte: u8) -> Option<Fix> {
+        if self.synced < SYNC.len() {
+            if SYNC.get(self.synced) == Some(&byte) {
+                self.synced += 1;
+            } else {
+                self.synced = usize::from(SYNC.first() == Some(&byte));
+            }
+            self.len = 0;
+            return None;
+        }
+
+        *self.message.get_mut(self.len)? = byte;
+        self.len += 1;
+        let [class, id, len_low, len_high] = *self.message.first_chunk::<HEADER_LEN>()?;
+        if self.len < HEADER_LEN {
+            return None;
+        }
+        let payload_len = usize::from(u16::from_le_bytes([len_low, len_high]));
+        if payload_len != NAV_PVT_LEN || (class, id) != (CLASS_NAV, ID_NAV_PVT) {
+            // Skips the message; the checksum protects against synchronizing on its bytes.
+            self.synced = 0;
+            return None;
+        }
+        if self.len < HEADER_LEN + payload_len + 2 {
+            return None;
+        }
+
+        self.synced = 0;
+        let (message, checksum) = self.message.split_last_chunk::<2>()?;
+        if *checksum != fletcher(message) {
+            return None;
+        }
+        nav_pvt(message.get(HEADER_LEN..)?)
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the 8-bit Fletcher checksum of `data`.
+fn fletcher(data: &[u8]) -> [u8; 2] {
+    data.iter().fold([0u8, 0u8], |[a, b], byte| {
+        let a = a.wrapping_add(*byte);
+        [a, b.wrapping_add(a)]
+    })
+}
+
+/// Parses the payload of a UBX-NAV-PVT message.
+fn nav_pvt(payload: &[u8]) -> Option<Fix> {
+    let u8_at = |offset: usize| payload.get(offset).copied();
+    let u16_at = |offset: usize| Some(u16::from_le_bytes(*payload.get(offset..)?.first_chunk()?));
+    let i32_at = |offset: usize| Some(i32::from_le_bytes(*payload.get(offset..)?.first_chunk()?));
+
+    let valid = u8_at(11)?;
+    let timestamp = if valid & 0b11 == 0b11 {
+        let date_time = DateTime::new(
+            i32::from(u16_at(4)?),
+            u8_at(6)?,
+            u8_at(7)?,
+            u8_at(8)?,
+            u8_at(9)?,
+            u8_at(10)?,
+        );
+        // The second is rounded: the nanoseconds are negative when it has not started yet.
+        let started = i32_at(16)? >= 0;
+        date_time
+            .and_then(|date_time| date_time.to_unix(UtcOffset::UTC))
+            .and_then(|timestamp| timestamp.checked_sub(u64::from(!started)))
+    } else {
+        None
+    };
+
+    let fix_type = u8_at(20)?;
+    let flags = u8_at(21)?;
+    let fix_ok = flags & 0b1 != 0;
+    let quality = match (fix_type, flags >> 6) {
+        _ if !fix_ok => Quality::NoFix,
+        (1, _) => Quality::DeadReckoning,
+        (2..=4, 2) => Quality::RtkFixed,
+        (2..=4, 1) => Quality::RtkFloat,
+        (2..=4, _) if flags & 0b10 != 0 => Quality::Differential,
+        (2..=4, _) => Quality::Autonomous,
+        _ => Quality::NoFix,
+    };
+    let has_fix = quality != Quality::NoFix;
+    // Only 3D fixes have an altitude.
+    let has_altitude = has_fix && matches!(fix_type, 3 | 4);
+
+    Some(Fix {
+        timestamp,
+        quality,
+        position: has_fix
+            .then(|| {
+                Some(Position {
+                    latitude: i32_at(28)?,
+                    longitude: i32_at(24)?,
+                })
+            })
+            .flatten(),
+        // The altitude is in millimeters.
+        altitude: has_altitude.then(|| i32_at(36).map(|mm| mm / 10)).flatten(),
+        satellites: u8_at(23),
+        hdop: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nav_pvt_message(edit: impl FnOnce(&mut [u8; NAV_PVT_LEN])) -> [u8; NAV_PVT_LEN + 8] {
+        let mut payload = [0; NAV_PVT_LEN];
+        // 2024-02-29T13:45:30Z, valid date and time.
+        payload[4..6].copy_from_slice(&2024u16.to_le_bytes());
+        payload[6..12].copy_from_slice(&[2, 29, 13, 45, 30, 0b11]);
+        payload[16..20].copy_from_slice(&250_000i32.to_le_bytes());
+        // 3D fix, fix OK, 11 satellites.
+        payload[20..24].copy_from_slice(&[3, 0b1, 0, 11]);
+        payload[24..28].copy_from_slice(&85_652_650i32.to_le_bytes());
+        payload[28..32].copy_from_slice(&(-472_852_331i32).to_le_bytes());
+        payload[36..40].copy_from_slice(&499_612i32.to_le_bytes());
+        edit(&mut payload);
+
+        let mut message = [0; NAV_PVT_LEN + 8];
+        let len = u16::try_from(NAV_PVT_LEN).unwrap().to_le_bytes();
+        message[..4].copy_from_slice(&[0xb5, 0x62, CLASS_NAV, ID_NAV_PVT]);
+        message[4..6].copy_from_slice(&len);
+        message[6..NAV_PVT_LEN + 6].copy_from_slice(&payload);
+        let checksum = fletcher(&message[2..NAV_PVT_LEN + 6]);
+        message[NAV_PVT_LEN + 6..].copy_from_slice(&checksum);
+        message
+    }
+
+    fn parse(parser: &mut Parser, bytes: &[u8]) -> Option<Fix> {
+        bytes.iter().filter_map(|byte| parser.push(*byte)).last()
+    }
+
+    #[test]
+    fn fix() {
+        let mut parser = Parser::new();
+        // Preceded by noise, and by a message of another class.
+        let fix = parse(
+            &mut parser,
+            &[0xb5, 0x00, 0xb5, 0x62, 0x05, 0x01, 2, 0, 6, 1, 0x0f, 0x38],
+        );
+        assert_eq!(fix, None);
+
+        let fix = parse(&mut parser, &nav_pvt_message(|_| {})).unwrap();
+        assert_eq!(
+            fix,
+            Fix {
+                timestamp: Some(1_709_214_330),
+                quality: Quality::Autonomous,
+                position: Some(Position {
+                    latitude: -472_852_331,
+                    longitude: 85_652_650,
+                }),
+                altitude: Some(49961),
+                satellites: Some(11),
+                hdop: None,
+            }
+        );
+    }
+
+    #[test]
+    fn qualities() {
+        let mut parser = Parser::new();
+        let mut quality = |fix_type: u8, flags: u8| {
+            parse(
+                &mut parser,
+                &nav_pvt_message(|payload| payload[20..22].copy_from_slice(&[fix_type, flags])),
+            )
+            .unwrap()
+        };
+        assert_eq!(quality(3, 0b1000_0001).quality, Quality::RtkFixed);
+        assert_eq!(quality(3, 0b0100_0001).quality, Quality::RtkFloat);
+        assert_eq!(quality(3, 0b11).quality, Quality::Differential);
+        assert_eq!(quality(1, 0b1).quality, Quality::DeadReckoning);
+        let fix = quality(2, 0b1);
+        assert_eq!(fix.quality, Quality::Autonomous);
+        assert!(fix.position.is_some());
+        assert_eq!(fix.altitude, None);
+        let fix = quality(3, 0);
+        assert_eq!(fix.quality, Quality::NoFix);
+        assert_eq!(fix.position, None);
+    }
+
+    #[test]
+    fn time() {
+        let mut parser = Parser::new();
+        let fix = parse(
+            &mut parser,
+            &nav_pvt_message(|payload| payload[16..20].copy_from_slice(&(-1000i32).to_le_bytes())),
+        )
+        .unwrap();
+        assert_eq!(fix.timestamp, Some(1_709_214_329));
+
+        let fix = parse(&mut parser, &nav_pvt_message(|payload| payload[11] = 0b10)).unwrap();
+        assert_eq!(fix.timestamp, None);
+    }
+
+    #[test]
+    fn corrupted() {
+        let mut parser = Parser::new();
+        let mut message = nav_pvt_message(|_| {});
+        message[30] ^= 1;
+        assert_eq!(parse(&mut parser, &message), None);
+        // The parser synchronizes on the next message.
+        assert!(parse(&mut parser, &nav_pvt_message(|_| {})).is_some());
+    }
+}
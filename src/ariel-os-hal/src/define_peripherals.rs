@@ -49,6 +49,36 @@ macro_rules! define_peripherals {
                 }
             }
         }
+
+        impl $crate::ClonePeripherals for $peripherals {
+            unsafe fn clone_unchecked(&self) -> Self {
+                $peripherals {
+                    $(
+                        $(#[$inner])*
+                        // SAFETY: upheld by the caller.
+                        $peripheral_name: unsafe {
+                            $crate::peripheral::Peripheral::clone_unchecked(&self.$peripheral_name)
+                        }
+                    ),*
+                }
+            }
+        }
+
+        impl $crate::LendPeripherals<$peripherals> for &mut $crate::OptionalPeripherals {
+            unsafe fn lend_peripherals(&mut self) -> $peripherals {
+                $peripherals {
+                    $(
+                        $(#[$inner])*
+                        // SAFETY: upheld by the caller.
+                        $peripheral_name: unsafe {
+                            $crate::peripheral::Peripheral::clone_unchecked(
+                                self.$peripheral_field.as_ref().unwrap(),
+                            )
+                        }
+                    ),*
+                }
+            }
+        }
     }
 }
 
@@ -85,6 +115,32 @@ macro_rules! group_peripherals {
                 }
             }
         }
+
+        impl $crate::LendPeripherals<$group> for &mut $crate::OptionalPeripherals {
+            unsafe fn lend_peripherals(&mut self) -> $group {
+                $group {
+                    $(
+                        $(#[$inner])*
+                        // SAFETY: upheld by the caller.
+                        $peripheral_name: unsafe { self.lend_peripherals() }
+                    ),*
+                }
+            }
+        }
+
+        impl $crate::ClonePeripherals for $group {
+            unsafe fn clone_unchecked(&self) -> Self {
+                $group {
+                    $(
+                        $(#[$inner])*
+                        // SAFETY: upheld by the caller.
+                        $peripheral_name: unsafe {
+                            $crate::ClonePeripherals::clone_unchecked(&self.$peripheral_name)
+                        }
+                    ),*
+                }
+            }
+        }
     }
 }
 
@@ -92,3 +148,24 @@ macro_rules! group_peripherals {
 pub trait TakePeripherals<T> {
     fn take_peripherals(&mut self) -> T;
 }
+
+#[doc(hidden)]
+pub trait ClonePeripherals {
+    /// Returns a copy of the peripherals.
+    ///
+    /// # Safety
+    ///
+    /// Only one of the copies of a peripheral may be used at any time.
+    #[must_use]
+    unsafe fn clone_unchecked(&self) -> Self;
+}
+
+#[doc(hidden)]
+pub trait LendPeripherals<T> {
+    /// Returns copies of the peripherals, leaving them in place.
+    ///
+    /// # Safety
+    ///
+    /// Only one of the copies of a peripheral may be used at any time.
+    unsafe fn lend_peripherals(&mut self) -> T;
+}
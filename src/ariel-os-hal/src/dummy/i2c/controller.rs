@@ -17,6 +17,23 @@ pub enum I2c {
     Hidden,
 }
 
+/// Names of the I2C peripherals for which a driver is available.
+pub const PERIPHERALS: &[&str] = &[];
+
+impl embedded_hal_async::i2c::ErrorType for I2c {
+    type Error = ariel_os_embassy_common::i2c::controller::Error;
+}
+
+impl embedded_hal_async::i2c::I2c for I2c {
+    async fn transaction(
+        &mut self,
+        _address: u8,
+        _operations: &mut [embedded_hal_async::i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        unimplemented!();
+    }
+}
+
 /// MCU-specific I2C bus frequency.
 #[expect(clippy::manual_non_exhaustive)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
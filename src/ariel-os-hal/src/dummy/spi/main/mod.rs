@@ -21,6 +21,35 @@ pub enum Spi {
     Hidden,
 }
 
+/// Names of the SPI peripherals for which a driver is available.
+pub const PERIPHERALS: &[&str] = &[];
+
+impl embedded_hal_async::spi::ErrorType for Spi {
+    type Error = embedded_hal::spi::ErrorKind;
+}
+
+impl embedded_hal_async::spi::SpiBus for Spi {
+    async fn read(&mut self, _words: &mut [u8]) -> Result<(), Self::Error> {
+        unimplemented!();
+    }
+
+    async fn write(&mut self, _words: &[u8]) -> Result<(), Self::Error> {
+        unimplemented!();
+    }
+
+    async fn transfer(&mut self, _read: &mut [u8], _write: &[u8]) -> Result<(), Self::Error> {
+        unimplemented!();
+    }
+
+    async fn transfer_in_place(&mut self, _words: &mut [u8]) -> Result<(), Self::Error> {
+        unimplemented!();
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        unimplemented!();
+    }
+}
+
 /// MCU-specific I2C bus frequency.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Frequency {
@@ -0,0 +1,164 @@
+//! Describes the peripherals provided by the current build.
+//!
+//! The [`inventory()`](crate::inventory()) is derived from the board and MCU the firmware is built
+//! for, and from the drivers enabled in the build, so that generic applications can adapt to the
+//! board at runtime instead of requiring per-board code:
+//!
+//! ```ignore
+//! let inventory = ariel_os::hal::inventory();
+//! if inventory.i2c_controllers.is_empty() {
+//!     info!("no I2C bus on {}, skipping sensors", inventory.board);
+//! }
+//! ```
+
+use core::ops::Range;
+
+/// The peripherals provided by the current build, as returned by
+/// [`inventory()`](crate::inventory()).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Inventory {
+    /// Name of the board, as given by the build system.
+    pub board: &'static str,
+    /// Names of the I2C controllers for which a driver is available, eg. `TWISPI0`.
+    ///
+    /// Empty unless the `i2c` laze module is selected.
+    pub i2c_controllers: &'static [&'static str],
+    /// Names of the SPI controllers for which a driver is available, eg. `SPI2`.
+    ///
+    /// Empty unless the `spi` laze module is selected.
+    pub spi_controllers: &'static [&'static str],
+    /// GPIO ports of the MCU.
+    ///
+    /// Empty on MCUs whose ports depend on their package, which currently includes the STM32s.
+    pub gpio_ports: &'static [GpioPort],
+    /// Radios for which a driver is enabled.
+    pub radios: &'static [Radio],
+    /// Flash range used by the storage, in the address space of the flash driver.
+    ///
+    /// `None` unless the `storage` laze module is selected.
+    pub storage: Option<Range<u32>>,
+}
+
+/// A GPIO port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GpioPort {
+    /// Name of the port, eg. `P0`, or `GPIO` on MCUs with a single, unnamed port.
+    pub name: &'static str,
+    /// Number of pins of the port, numbered from 0.
+    ///
+    /// Some numbers may not be bonded out, or may be used by the flash or the board.
+    pub pins: u8,
+}
+
+/// A radio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Radio {
+    /// Bluetooth Low Energy.
+    Ble,
+    /// Wi-Fi.
+    Wifi,
+    /// NFC tag.
+    Nfc,
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(context = "nrf52840")] {
+        const GPIO_PORTS: &[GpioPort] = &[
+            GpioPort { name: "P0", pins: 32 },
+            GpioPort { name: "P1", pins: 16 },
+        ];
+    } else if #[cfg(context = "nrf52833")] {
+        const GPIO_PORTS: &[GpioPort] = &[
+            GpioPort { name: "P0", pins: 32 },
+            GpioPort { name: "P1", pins: 10 },
+        ];
+    } else if #[cfg(context = "nrf53")] {
+        const GPIO_PORTS: &[GpioPort] = &[
+            GpioPort { name: "P0", pins: 32 },
+            GpioPort { name: "P1", pins: 16 },
+        ];
+    } else if #[cfg(context = "nrf")] {
+        const GPIO_PORTS: &[GpioPort] = &[GpioPort { name: "P0", pins: 32 }];
+    } else if #[cfg(context = "rp")] {
+        const GPIO_PORTS: &[GpioPort] = &[GpioPort { name: "GPIO", pins: 30 }];
+    } else if #[cfg(context = "esp32")] {
+        const GPIO_PORTS: &[GpioPort] = &[GpioPort { name: "GPIO", pins: 40 }];
+    } else if #[cfg(context = "esp32c3")] {
+        const GPIO_PORTS: &[GpioPort] = &[GpioPort { name: "GPIO", pins: 22 }];
+    } else if #[cfg(context = "esp32c6")] {
+        const GPIO_PORTS: &[GpioPort] = &[GpioPort { name: "GPIO", pins: 31 }];
+    } else if #[cfg(context = "esp32s3")] {
+        const GPIO_PORTS: &[GpioPort] = &[GpioPort { name: "GPIO", pins: 49 }];
+    } else {
+        const GPIO_PORTS: &[GpioPort] = &[];
+    }
+}
+
+const RADIOS: &[Radio] = &[
+    #[cfg(feature = "ble")]
+    Radio::Ble,
+    #[cfg(any(feature = "wifi-cyw43", feature = "wifi-esp"))]
+    Radio::Wifi,
+    #[cfg(feature = "nfct")]
+    Radio::Nfc,
+];
+
+/// Returns the peripherals provided by the current build.
+#[must_use]
+pub fn inventory() -> Inventory {
+    Inventory {
+        board: ariel_os_buildinfo::BOARD,
+        #[cfg(feature = "i2c")]
+        i2c_controllers: crate::i2c::controller::PERIPHERALS,
+        #[cfg(not(feature = "i2c"))]
+        i2c_controllers: &[],
+        #[cfg(feature = "spi")]
+        spi_controllers: crate::spi::main::PERIPHERALS,
+        #[cfg(not(feature = "spi"))]
+        spi_controllers: &[],
+        gpio_ports: GPIO_PORTS,
+        radios: RADIOS,
+        #[cfg(feature = "storage")]
+        storage: Some(storage_range()),
+        #[cfg(not(feature = "storage"))]
+        storage: None,
+    }
+}
+
+/// Returns the flash range reserved for the storage by the linker, in the address space of the
+/// flash driver.
+///
+/// This expects two symbols `__storage_start` and `__storage_end`.
+/// The offset between the linker flash address map and the flash driver address map is
+/// configured here for each MCU family.
+#[cfg(feature = "storage")]
+#[doc(hidden)]
+#[must_use]
+pub fn storage_range() -> Range<u32> {
+    #[cfg(all(context = "nrf", not(context = "nrf5340-net")))]
+    const OFFSET: usize = 0x0;
+    #[cfg(context = "nrf5340-net")]
+    const OFFSET: usize = 0x0100_0000;
+    #[cfg(context = "rp")]
+    const OFFSET: usize = 0x1000_0000;
+    #[cfg(context = "stm32")]
+    const OFFSET: usize = 0x0800_0000;
+    // Default for platform-independent tooling.
+    #[cfg(not(context = "ariel-os"))]
+    const OFFSET: usize = 0x0;
+
+    unsafe extern "C" {
+        static __storage_start: u32;
+        static __storage_end: u32;
+    }
+
+    let start = &raw const __storage_start as usize - OFFSET;
+    let end = &raw const __storage_end as usize - OFFSET;
+
+    #[expect(clippy::cast_possible_truncation)]
+    let (start, end) = (start as u32, end as u32);
+
+    start..end
+}
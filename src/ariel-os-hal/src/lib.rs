@@ -26,8 +26,10 @@
 
 #[doc(hidden)]
 pub mod define_peripherals;
+pub mod inventory;
 
 pub use define_peripherals::*;
+pub use inventory::inventory;
 
 cfg_if::cfg_if! {
     if #[cfg(context = "nrf")] {
@@ -0,0 +1,228 @@
+//! Access to the device's own asymmetric key pair and the credentials built from it.
+//!
+//! The device key is a P-256 key pair that is generated from the system's cryptographically secure
+//! random number generator the first time it is requested, and persisted in storage from then on.
+//! Unlike the identifiers returned by [`device_id_bytes()`](crate::device_id_bytes), it is not
+//! derived from any property of the hardware: it is a secret, and is regenerated after the storage
+//! has been erased.
+//!
+//! The public part of the key can be presented in several shapes:
+//!
+//! * as a CWT Claims Set (CCS) through [`DeviceKey::ccs()`], which is the credential format used
+//!   with EDHOC;
+//! * as a PKCS #10 certificate signing request through [`DeviceKey::csr()`], which can be sent to
+//!   a certification authority when enrolling the device into a PKI. The resulting X.509
+//!   certificate can then be kept on the device using [`set_certificate()`], and retrieved for use
+//!   with TLS through [`certificate()`].
+//!
+//! Devices can also be provisioned with a key pair generated outside of them, using
+//! [`set_device_key()`].
+
+mod der;
+
+use ariel_os_debug::log::debug;
+use p256::{
+    SecretKey,
+    ecdsa::{Signature, SigningKey, signature::Signer},
+    elliptic_curve::sec1::ToEncodedPoint,
+};
+
+/// Storage key under which the device's secret key is persisted.
+const DEVICE_KEY_KEY: &str = "ariel-os-identity.device-key";
+/// Storage key under which the device's X.509 certificate is persisted.
+const CERTIFICATE_KEY: &str = "ariel-os-identity.certificate";
+
+/// Length of the CCS returned by [`DeviceKey::ccs()`].
+pub const CCS_LEN: usize = 46;
+
+/// Length of a serialized public key, see [`DeviceKey::public_key()`].
+pub const PUBLIC_KEY_LEN: usize = 65;
+
+/// The device's own P-256 key pair.
+///
+/// Obtain it through [`device_key()`].
+pub struct DeviceKey {
+    secret: SecretKey,
+}
+
+/// Loads the device's key pair, generating and persisting one if none exists yet.
+///
+/// # Errors
+///
+/// Returns [`Error::Storage`] if the key could not be read from or written to storage, and
+/// [`Error::InvalidStoredKey`] if the stored key is not a valid P-256 secret key.
+pub async fn device_key() -> Result<DeviceKey, Error> {
+    // Holding the lock across both steps ensures that concurrent callers can not both generate a
+    // key.
+    let mut storage = ariel_os_storage::lock().await;
+
+    if let Some(bytes) = storage
+        .get::<[u8; 32]>(DEVICE_KEY_KEY)
+        .await
+        .map_err(|_| Error::Storage)?
+    {
+        let secret = SecretKey::from_bytes(&bytes.into()).map_err(|_| Error::InvalidStoredKey)?;
+        return Ok(DeviceKey { secret });
+    }
+
+    let secret = SecretKey::random(&mut ariel_os_random::crypto_rng());
+    let bytes: [u8; 32] = secret.to_bytes().into();
+    storage
+        .insert(DEVICE_KEY_KEY, bytes)
+        .await
+        .map_err(|_| Error::Storage)?;
+    debug!("Generated device key pair.");
+
+    Ok(DeviceKey { secret })
+}
+
+/// Replaces the device's key pair with the one of the given secret key, and persists it.
+///
+/// This is meant for provisioning devices with keys generated outside of them. Credentials built
+/// from the previous key, including a [certificate](set_certificate()), are no longer valid
+/// afterwards.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidKey`] if `secret` is not a valid P-256 secret key (a big-endian
+/// scalar), and [`Error::Storage`] if the key could not be written to storage.
+pub async fn set_device_key(secret: &[u8; 32]) -> Result<DeviceKey, Error> {
+    let secret_key = SecretKey::from_bytes(&(*secret).into()).map_err(|_| Error::InvalidKey)?;
+    ariel_os_storage::insert(DEVICE_KEY_KEY, *secret)
+        .await
+        .map_err(|_| Error::Storage)?;
+    debug!("Replaced device key pair.");
+
+    Ok(DeviceKey { secret: secret_key })
+}
+
+impl DeviceKey {
+    /// Returns the public key as an uncompressed SEC1 encoded point (`0x04 || x || y`).
+    #[must_use]
+    #[expect(clippy::missing_panics_doc, reason = "does not panic")]
+    pub fn public_key(&self) -> [u8; PUBLIC_KEY_LEN] {
+        let point = self.secret.public_key().to_encoded_point(false);
+        point
+            .as_bytes()
+            .try_into()
+            .expect("uncompressed P-256 points have a fixed length")
+    }
+
+    /// Returns the secret key as a big-endian scalar.
+    ///
+    /// This is needed to hand the key to cryptographic libraries such as EDHOC implementations.
+    /// Callers should not store or transmit it.
+    #[must_use]
+    pub fn secret_key_bytes(&self) -> [u8; 32] {
+        self.secret.to_bytes().into()
+    }
+
+    /// Returns the public key as a CWT Claims Set (CCS) credential suitable for EDHOC.
+    ///
+    /// The credential contains only a confirmation claim with a COSE key that has an empty key ID,
+    /// which allows peers to refer to the credential by reference.
+    #[must_use]
+    #[expect(clippy::missing_panics_doc, reason = "does not panic")]
+    pub fn ccs(&self) -> [u8; CCS_LEN] {
+        const PREFIX: [u8; 14] = [
+            0xa1, // map(1)
+            0x08, // /cnf/ 8
+            0xa1, // map(1)
+            0x01, // /COSE_Key/ 1
+            0xa4, // map(4)
+            0x01, 0x02, // /kty/ 1: /EC2/ 2
+            0x02, 0x40, // /kid/ 2: ''
+            0x20, 0x01, // /crv/ -1: /P-256/ 1
+            0x21, 0x58, 0x20, // /x/ -2: bytes(32)
+        ];
+
+        let point = self.secret.public_key().to_encoded_point(false);
+        let mut ccs = [0; CCS_LEN];
+        let (prefix, x) = ccs.split_at_mut(PREFIX.len());
+        prefix.copy_from_slice(&PREFIX);
+        x.copy_from_slice(point.x().expect("public keys are never the identity point"));
+        ccs
+    }
+
+    /// Signs a message using ECDSA with SHA-256, returning the signature as `r || s`.
+    #[must_use]
+    pub fn sign(&self, message: &[u8]) -> [u8; 64] {
+        let signature: Signature = SigningKey::from(&self.secret).sign(message);
+        signature.to_bytes().into()
+    }
+
+    /// Builds a PKCS #10 certificate signing request for this key into `buffer`.
+    ///
+    /// The subject of the request consists of the given common name only; a certification
+    /// authority is expected to fill in any further details according to its policies.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::BufferTooSmall`] if the request does not fit into `buffer`; around 200
+    /// bytes plus the length of the common name are sufficient.
+    pub fn csr<'b>(&self, common_name: &str, buffer: &'b mut [u8]) -> Result<&'b [u8], Error> {
+        der::certification_request(
+            common_name,
+            &self.public_key(),
+            |info| self.sign(info),
+            buffer,
+        )
+    }
+}
+
+/// Loads the device's X.509 certificate (in DER encoding) into `buffer`.
+///
+/// Returns `None` if no certificate has been set through [`set_certificate()`].
+///
+/// # Errors
+///
+/// Returns [`Error::BufferTooSmall`] if the certificate does not fit into `buffer`, and
+/// [`Error::Storage`] if it could not be read from storage.
+pub async fn certificate(buffer: &mut [u8]) -> Result<Option<&[u8]>, Error> {
+    ariel_os_storage::get_blob(CERTIFICATE_KEY, buffer)
+        .await
+        .map_err(|e| match e {
+            sequential_storage::Error::BufferTooSmall(_) => Error::BufferTooSmall,
+            _ => Error::Storage,
+        })
+}
+
+/// Persists the device's X.509 certificate, which is given in DER encoding.
+///
+/// The certificate is expected to be issued for the [`DeviceKey`], typically in response to a
+/// [certificate signing request](DeviceKey::csr); this is not verified.
+///
+/// # Errors
+///
+/// Returns [`Error::Storage`] if the certificate could not be written to storage.
+pub async fn set_certificate(der: &[u8]) -> Result<(), Error> {
+    ariel_os_storage::insert_blob(CERTIFICATE_KEY, der)
+        .await
+        .map_err(|_| Error::Storage)
+}
+
+/// Errors that can occur when working with the device key.
+#[derive(Debug)]
+pub enum Error {
+    /// Accessing the storage failed.
+    Storage,
+    /// The key found in storage is not a valid key.
+    InvalidStoredKey,
+    /// The given key is not a valid key.
+    InvalidKey,
+    /// The provided buffer is too small for the requested data.
+    BufferTooSmall,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Storage => write!(f, "storage access failed"),
+            Self::InvalidStoredKey => write!(f, "stored device key is invalid"),
+            Self::InvalidKey => write!(f, "device key is invalid"),
+            Self::BufferTooSmall => write!(f, "buffer too small"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
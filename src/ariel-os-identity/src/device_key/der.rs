@@ -0,0 +1,175 @@
+//! Minimal DER encoder for the PKCS #10 certificate signing requests built in this crate.
+//!
+//! Only what is needed for an ECDSA P-256 request with a common name is implemented; this is not a
+//! general purpose ASN.1 library.
+
+use super::{Error, PUBLIC_KEY_LEN};
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_BIT_STRING: u8 = 0x03;
+const TAG_UTF8_STRING: u8 = 0x0c;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+
+/// `version` field of the `CertificationRequestInfo`: `INTEGER 0`.
+const VERSION_1: [u8; 3] = [TAG_INTEGER, 0x01, 0x00];
+/// Type of the single attribute in the subject: `OBJECT IDENTIFIER 2.5.4.3` (`commonName`).
+const OID_COMMON_NAME: [u8; 5] = [0x06, 0x03, 0x55, 0x04, 0x03];
+/// Prefix of the `SubjectPublicKeyInfo` for an uncompressed P-256 key (`id-ecPublicKey`,
+/// `prime256v1`), up to the start of the `subjectPublicKey` bit string's content.
+const SPKI_PREFIX: [u8; 27] = [
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a,
+    0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+    // The public key follows, starting with its 0x04 format byte.
+    0x04,
+];
+/// Empty set of `attributes`: `[0] IMPLICIT SET {}`.
+const NO_ATTRIBUTES: [u8; 2] = [0xa0, 0x00];
+/// `AlgorithmIdentifier` for `ecdsa-with-SHA256`.
+const ECDSA_WITH_SHA256: [u8; 12] = [
+    0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02,
+];
+
+/// Maximum length of a TLV header as produced by [`Writer::header()`].
+const MAX_HEADER_LEN: usize = 4;
+
+/// Builds a DER encoded `CertificationRequest` (RFC 2986) into `buffer`.
+///
+/// `sign` is called with the encoded `CertificationRequestInfo`, and needs to return an ECDSA
+/// signature as `r || s`.
+pub(super) fn certification_request<'b>(
+    common_name: &str,
+    public_key: &[u8; PUBLIC_KEY_LEN],
+    sign: impl FnOnce(&[u8]) -> [u8; 64],
+    buffer: &'b mut [u8],
+) -> Result<&'b [u8], Error> {
+    let atv_len = OID_COMMON_NAME.len() + tlv_len(common_name.len());
+    let rdn_len = tlv_len(atv_len);
+    let name_len = tlv_len(rdn_len);
+    let info_len = VERSION_1.len() + tlv_len(name_len) + SPKI_PREFIX.len() + PUBLIC_KEY_LEN - 1 + 2;
+
+    // The outer header's length is only known once the signature is known, so leave room for the
+    // largest possible one.
+    let mut writer = Writer {
+        buffer,
+        len: MAX_HEADER_LEN,
+    };
+    let info_start = writer.len;
+    writer.header(TAG_SEQUENCE, info_len)?;
+    writer.push(&VERSION_1)?;
+    writer.header(TAG_SEQUENCE, name_len)?;
+    writer.header(TAG_SET, rdn_len)?;
+    writer.header(TAG_SEQUENCE, atv_len)?;
+    writer.push(&OID_COMMON_NAME)?;
+    writer.header(TAG_UTF8_STRING, common_name.len())?;
+    writer.push(common_name.as_bytes())?;
+    writer.push(&SPKI_PREFIX)?;
+    let (_format, coordinates) = public_key.split_first().expect("public key is not empty");
+    writer.push(coordinates)?;
+    writer.push(&NO_ATTRIBUTES)?;
+    let info_end = writer.len;
+
+    let signature = sign(
+        writer
+            .buffer
+            .get(info_start..info_end)
+            .expect("was just written"),
+    );
+    let (r, s) = signature.split_at(32);
+    let signature_value_len = integer_len(r) + integer_len(s);
+    // The bit string starts with a byte indicating that there are no unused bits.
+    let bit_string_len = 1 + tlv_len(signature_value_len);
+    let outer_len = (info_end - info_start) + ECDSA_WITH_SHA256.len() + tlv_len(bit_string_len);
+
+    // Now that the outer length is known, place its header right in front of the request info.
+    let outer_header_len = tlv_len(outer_len) - outer_len;
+    let start = MAX_HEADER_LEN - outer_header_len;
+    writer.len = start;
+    writer.header(TAG_SEQUENCE, outer_len)?;
+    writer.len = info_end;
+
+    writer.push(&ECDSA_WITH_SHA256)?;
+    writer.header(TAG_BIT_STRING, bit_string_len)?;
+    writer.push(&[0])?;
+    writer.header(TAG_SEQUENCE, signature_value_len)?;
+    writer.integer(r)?;
+    writer.integer(s)?;
+
+    let end = writer.len;
+    Ok(writer.buffer.get(start..end).expect("was just written"))
+}
+
+/// Returns the length of a TLV item with a content of `len` bytes.
+fn tlv_len(len: usize) -> usize {
+    let length_len = match len {
+        0..0x80 => 1,
+        0x80..0x100 => 2,
+        _ => 3,
+    };
+    1 + length_len + len
+}
+
+/// Returns the encoded length of an unsigned big-endian integer.
+fn integer_len(value: &[u8]) -> usize {
+    tlv_len(integer_content(value).len() + usize::from(needs_padding(value)))
+}
+
+/// Strips the leading zeros off a big-endian integer, leaving at least one byte.
+fn integer_content(value: &[u8]) -> &[u8] {
+    let leading_zeros = value.iter().take_while(|b| **b == 0).count();
+    value
+        .get(leading_zeros.min(value.len().saturating_sub(1))..)
+        .unwrap_or_default()
+}
+
+/// Returns whether an unsigned integer needs a leading zero byte so it does not read as negative.
+fn needs_padding(value: &[u8]) -> bool {
+    integer_content(value)
+        .first()
+        .is_some_and(|b| b & 0x80 != 0)
+}
+
+/// Appends DER items to a buffer.
+struct Writer<'b> {
+    buffer: &'b mut [u8],
+    len: usize,
+}
+
+impl Writer<'_> {
+    fn push(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let end = self.len + bytes.len();
+        self.buffer
+            .get_mut(self.len..end)
+            .ok_or(Error::BufferTooSmall)?
+            .copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    /// Writes the tag and length of an item with a content of `len` bytes.
+    ///
+    /// Lengths are limited to 16 bits, which is plenty for the items built here.
+    #[expect(
+        clippy::cast_possible_truncation,
+        reason = "lengths are checked to fit the encoding"
+    )]
+    fn header(&mut self, tag: u8, len: usize) -> Result<(), Error> {
+        match len {
+            0..0x80 => self.push(&[tag, len as u8]),
+            0x80..0x100 => self.push(&[tag, 0x81, len as u8]),
+            0x100..0x1_0000 => self.push(&[tag, 0x82, (len >> 8) as u8, len as u8]),
+            _ => Err(Error::BufferTooSmall),
+        }
+    }
+
+    /// Writes an unsigned big-endian integer.
+    fn integer(&mut self, value: &[u8]) -> Result<(), Error> {
+        let content = integer_content(value);
+        let padding = needs_padding(value);
+        self.header(TAG_INTEGER, content.len() + usize::from(padding))?;
+        if padding {
+            self.push(&[0])?;
+        }
+        self.push(content)
+    }
+}
@@ -24,11 +24,18 @@
 //!
 //! Other identifiers, such as the EUI-48 addresses provided by [`interface_eui48()`], are usually
 //! derived from the main identity, but have different properties.
+//!
+//! Beyond identifiers, the [`device_key`] module provides a cryptographic identity: a key pair
+//! that is unique to the device, along with credentials (CCS, X.509 certificates) built from it.
 #![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
 #![deny(missing_docs)]
 // required for tests:
 #![cfg_attr(test, no_main)]
 
+#[cfg(feature = "device-key")]
+pub mod device_key;
+
 pub use ariel_os_embassy_common::identity::Eui48;
 
 /// Obtains a unique identifier of the device in its byte serialized form.
@@ -0,0 +1,59 @@
+//! Parsing of the requests of the inspection protocol.
+
+/// A request of the inspection protocol, with its hex-encoded arguments.
+pub(crate) enum Command<'a> {
+    List,
+    Get(&'a str),
+    Set { key: &'a str, value: &'a str },
+    Remove(&'a str),
+    Flush,
+}
+
+impl<'a> Command<'a> {
+    /// Parses a request line, without its line terminator.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error message to report if the line is not a known request.
+    pub(crate) fn parse(line: &'a str) -> Result<Self, &'static str> {
+        let mut words = line.split_ascii_whitespace();
+        let command = match (words.next(), words.next(), words.next()) {
+            (Some("LIST"), None, None) => Self::List,
+            (Some("GET"), Some(key), None) => Self::Get(key),
+            (Some("SET"), Some(key), Some(value)) => Self::Set { key, value },
+            (Some("REMOVE"), Some(key), None) => Self::Remove(key),
+            (Some("FLUSH"), None, None) => Self::Flush,
+            _ => return Err("unknown request"),
+        };
+        if words.next().is_some() {
+            return Err("unknown request");
+        }
+        Ok(command)
+    }
+}
+
+/// Decodes `hex` into `buffer`, and returns the decoded bytes.
+///
+/// Returns `None` if `hex` is not valid hex, or if it does not fit into `buffer`.
+pub(crate) fn decode_hex<'b>(hex: &str, buffer: &'b mut [u8]) -> Option<&'b [u8]> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    let decoded = buffer.get_mut(..hex.len() / 2)?;
+    for (byte, pair) in decoded.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+        let [high, low] = pair else {
+            return None;
+        };
+        *byte = (nibble(*high)? << 4) | nibble(*low)?;
+    }
+    Some(decoded)
+}
+
+fn nibble(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
@@ -0,0 +1,281 @@
+//! Provides an inspection service, which lets host tools list, read and write the items in
+//! [storage](ariel_os_storage) of a running device over a byte stream, eg. for bench debugging
+//! without a network.
+//!
+//! The service speaks a line-based text protocol, so that it can be driven by scripts as well as
+//! from a serial terminal. It runs on anything implementing [`embedded_io_async::Read`] and
+//! [`embedded_io_async::Write`]; it is served on the RTT channels of a debug probe with the
+//! `inspect-rtt` feature (see [`rtt`](crate::rtt)), and on a USB serial port with the
+//! `usb-inspect` feature (see `ariel_os::usb::inspect`). Unlike the CoAP resources of the device,
+//! it needs neither a network nor credentials: anyone with access to the byte stream can read and
+//! change all items, so it should only be enabled in builds meant for the bench.
+//!
+//! # Protocol
+//!
+//! Each request is a line terminated by `\n` (a preceding `\r` is ignored), which is answered by
+//! a line holding either `OK`, possibly followed by values, or `ERR` followed by an error
+//! message. Values are the items as they are serialized in storage (ie., with
+//! [`postcard`](https://docs.rs/postcard) for items stored with [`ariel_os_storage::insert()`]),
+//! hex-encoded.
+//!
+//! | Request | Effect | Response values |
+//! | --- | --- | --- |
+//! | `LIST` | lists the keys, each on a `KEY <key>` line before the response | number of keys |
+//! | `GET <key>` | | value |
+//! | `SET <key> <value>` | stores the value | |
+//! | `REMOVE <key>` | removes the item | |
+//! | `FLUSH` | writes the items held in RAM to flash (see [`ariel_os_storage::flush()`]) | |
+//!
+//! Keys containing whitespace are listed, but can not be accessed. Blobs show up as their length
+//! under their key, and as their chunks under keys derived from it (see
+//! [`Storage::insert_blob()`](ariel_os_storage::Storage::insert_blob())).
+//!
+//! ```text
+//! > LIST
+//! < KEY ARIEL_INIT_MARK
+//! < KEY counter
+//! < OK 2
+//! > GET counter
+//! < OK 2a
+//! > SET counter 00
+//! < OK
+//! > GET missing
+//! < ERR not found
+//! ```
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+mod command;
+#[cfg(feature = "rtt")]
+pub mod rtt;
+
+use core::{convert::Infallible, fmt::Write as _};
+
+use ariel_os_debug::log::info;
+use ariel_os_storage::{DATA_BUFFER_SIZE, MAX_KEY_LEN};
+use arrayvec::{ArrayString, ArrayVec};
+use embedded_io_async::{Read, Write};
+
+use command::{Command, decode_hex};
+
+/// Maximum number of keys listed by `LIST`.
+pub const MAX_KEYS: usize = 32;
+
+/// Maximum length of a request line, without its line terminator.
+pub const MAX_LINE_LEN: usize = "SET ".len() + MAX_KEY_LEN + 1 + 2 * DATA_BUFFER_SIZE;
+
+/// Maximum length of a response line, including its line terminator.
+const MAX_RESPONSE_LEN: usize = 2 * DATA_BUFFER_SIZE + 8;
+
+type Response = heapless::String<MAX_RESPONSE_LEN>;
+
+type Keys = ArrayVec<ArrayString<MAX_KEY_LEN>, MAX_KEYS>;
+
+/// Serves the inspection service on `io`, until it fails or is closed.
+///
+/// Each request is applied as soon as it has been received.
+///
+/// # Errors
+///
+/// Returns [`Error::Io`] if reading from or writing to `io` failed, and [`Error::Closed`] if `io`
+/// was closed.
+pub async fn serve<IO: Read + Write>(io: &mut IO) -> Result<Infallible, Error<IO::Error>> {
+    let mut line = [0; MAX_LINE_LEN];
+
+    loop {
+        let mut response = Response::new();
+        let mut keys = Keys::new();
+        let result = match read_line(io, &mut line).await? {
+            Some(request) => match core::str::from_utf8(request) {
+                Ok(request) => handle(request, &mut response, &mut keys).await,
+                Err(_) => Err("invalid request"),
+            },
+            None => Err("request too long"),
+        };
+
+        let status = match result {
+            Ok(()) => "OK",
+            Err(message) => {
+                response.clear();
+                keys.clear();
+                // Cannot fail, error messages are short.
+                let _ = write!(response, " {message}");
+                "ERR"
+            }
+        };
+        for key in &keys {
+            for part in ["KEY ", key, "\n"] {
+                io.write_all(part.as_bytes()).await.map_err(Error::Io)?;
+            }
+        }
+        for part in [status, &response, "\n"] {
+            io.write_all(part.as_bytes()).await.map_err(Error::Io)?;
+        }
+        io.flush().await.map_err(Error::Io)?;
+    }
+}
+
+/// Reads a request line into `buffer`, and returns it without its line terminator.
+///
+/// Returns `None` if the line does not fit into `buffer`, in which case the rest of the line is
+/// discarded.
+///
+/// # Errors
+///
+/// Returns [`Error::Io`] if reading failed, and [`Error::Closed`] if `io` was closed.
+async fn read_line<'b, IO: Read>(
+    io: &mut IO,
+    buffer: &'b mut [u8],
+) -> Result<Option<&'b [u8]>, Error<IO::Error>> {
+    let mut len = 0;
+    let mut overflow = false;
+    loop {
+        let mut byte = [0];
+        if io.read(&mut byte).await.map_err(Error::Io)? == 0 {
+            return Err(Error::Closed);
+        }
+        if byte == [b'\n'] {
+            break;
+        }
+        match buffer.get_mut(len) {
+            Some(slot) if !overflow => {
+                *slot = byte[0];
+                len += 1;
+            }
+            _ => overflow = true,
+        }
+    }
+    if overflow {
+        return Ok(None);
+    }
+
+    let line = buffer.get(..len).unwrap_or_default();
+    Ok(Some(line.strip_suffix(b"\r").unwrap_or(line)))
+}
+
+/// Applies `request`, and writes the values of the response into `response`, and the keys to
+/// list before it into `keys`.
+///
+/// # Errors
+///
+/// Returns the error message to report if the request failed.
+async fn handle(
+    request: &str,
+    response: &mut Response,
+    keys: &mut Keys,
+) -> Result<(), &'static str> {
+    match Command::parse(request)? {
+        Command::List => {
+            *keys = ariel_os_storage::lock()
+                .await
+                .keys::<MAX_KEYS>()
+                .await
+                .map_err(|e| match e {
+                    sequential_storage::Error::BufferTooSmall(_) => "too many keys",
+                    e => storage_error(&e),
+                })?;
+            write!(response, " {}", keys.len()).map_err(|_| "response too long")?;
+        }
+        Command::Get(key) => {
+            let mut buffer = [0; DATA_BUFFER_SIZE];
+            let value = ariel_os_storage::lock()
+                .await
+                .get_serialized(key, &mut buffer)
+                .await
+                .map_err(|e| storage_error(&e))?
+                .ok_or("not found")?;
+            write_hex(response, value)?;
+        }
+        Command::Set { key, value } => {
+            let mut buffer = [0; DATA_BUFFER_SIZE];
+            let value = decode_hex(value, &mut buffer).ok_or("invalid value")?;
+            ariel_os_storage::lock()
+                .await
+                .insert_raw(key, value)
+                .await
+                .map_err(|e| storage_error(&e))?;
+            info!("inspect: stored {}", key);
+        }
+        Command::Remove(key) => {
+            remove(key).await?;
+            info!("inspect: removed {}", key);
+        }
+        Command::Flush => ariel_os_storage::flush()
+            .await
+            .map_err(|e| storage_error(&e))?,
+    }
+    Ok(())
+}
+
+/// Removes the item stored under `key`.
+///
+/// # Errors
+///
+/// Returns the error message to report if removing failed.
+// STM32 flash drivers do not implement `MultiwriteNorFlash`.
+#[cfg(not(context = "stm32"))]
+async fn remove(key: &str) -> Result<(), &'static str> {
+    ariel_os_storage::remove(key)
+        .await
+        .map_err(|e| storage_error(&e))
+}
+
+/// Removes the item stored under `key`.
+///
+/// # Errors
+///
+/// Always returns an error, as items can not be removed on this platform.
+#[cfg(context = "stm32")]
+#[expect(clippy::unused_async, reason = "signature of the other platforms")]
+async fn remove(_key: &str) -> Result<(), &'static str> {
+    Err("not supported")
+}
+
+/// Returns the error message to report for a storage error.
+fn storage_error<E>(error: &sequential_storage::Error<E>) -> &'static str {
+    match error {
+        sequential_storage::Error::SerializationError(_) => "invalid key",
+        sequential_storage::Error::ItemTooBig | sequential_storage::Error::BufferTooSmall(_) => {
+            "value too large"
+        }
+        sequential_storage::Error::FullStorage => "storage full",
+        _ => "storage access failed",
+    }
+}
+
+/// Appends a space and the hex encoding of `bytes` to `response`.
+///
+/// # Errors
+///
+/// Returns the error message to report if `response` is full.
+fn write_hex(response: &mut Response, bytes: &[u8]) -> Result<(), &'static str> {
+    write!(response, " ").map_err(|_| "response too long")?;
+    for byte in bytes {
+        write!(response, "{byte:02x}").map_err(|_| "response too long")?;
+    }
+    Ok(())
+}
+
+/// Errors that can occur when serving the inspection service.
+///
+/// Errors of individual requests are reported to the host instead.
+#[derive(Debug)]
+pub enum Error<E> {
+    /// Reading from or writing to the byte stream failed.
+    Io(E),
+    /// The byte stream was closed.
+    Closed,
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: {e}"),
+            Self::Closed => write!(f, "closed"),
+        }
+    }
+}
+
+impl<E: core::error::Error> core::error::Error for Error<E> {}
@@ -0,0 +1,74 @@
+//! Provides the inspection service on the RTT channels of a debug probe.
+//!
+//! The service is served on the `ariel-os-link` up and down channels, next to the debug output,
+//! which needs to go through RTT too (see the `rtt-target` feature):
+//!
+//! ```ignore
+//! use ariel_os::inspect::rtt::RttLink;
+//!
+//! #[ariel_os::task(autostart)]
+//! async fn inspect() {
+//!     if let Some(mut link) = RttLink::take() {
+//!         let _ = ariel_os::inspect::serve(&mut link).await;
+//!     }
+//! }
+//! ```
+//!
+//! The host tool attaches to the channels through the debug probe, eg. with the RTT support of
+//! `probe-rs`. As RTT channels have no notion of being opened, the service keeps running while no
+//! host is attached.
+
+use core::convert::Infallible;
+
+use embassy_time::{Duration, Timer};
+use embedded_io_async::{ErrorType, Read, Write};
+use rtt_target::{DownChannel, UpChannel};
+
+/// Interval at which the channels are polled, as the host does not signal reads or writes.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The `ariel-os-link` RTT channels, used as a byte stream.
+pub struct RttLink {
+    up: UpChannel,
+    down: DownChannel,
+}
+
+impl RttLink {
+    /// Takes the `ariel-os-link` RTT channels.
+    ///
+    /// Returns `None` if they have already been taken, or if the debug output does not go
+    /// through RTT.
+    #[must_use]
+    pub fn take() -> Option<Self> {
+        ariel_os_debug::link::take().map(|(up, down)| Self { up, down })
+    }
+}
+
+impl ErrorType for RttLink {
+    type Error = Infallible;
+}
+
+impl Read for RttLink {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        loop {
+            let len = self.down.read(buf);
+            if len > 0 || buf.is_empty() {
+                return Ok(len);
+            }
+            Timer::after(POLL_INTERVAL).await;
+        }
+    }
+}
+
+impl Write for RttLink {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        loop {
+            // Writes as much as fits into the channel buffer.
+            let len = self.up.write(buf);
+            if len > 0 || buf.is_empty() {
+                return Ok(len);
+            }
+            Timer::after(POLL_INTERVAL).await;
+        }
+    }
+}
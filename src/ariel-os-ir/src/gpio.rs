@@ -0,0 +1,117 @@
+//! Provides drivers for IR receiver modules connected to GPIOs, and for IR LEDs driven by PWM
+//! outputs.
+//!
+//! The [`GpioReceiver`] timestamps the edges of the output of the receiver module, which works
+//! with any GPIO supporting interrupts, with a precision bounded by the interrupt latency and the
+//! tick rate of the timer.
+//! The [`PwmTransmitter`] switches the PWM output driving the IR LED on during marks, and off
+//! during spaces; the PWM output is to be configured with the carrier frequency of the protocol,
+//! eg. [`Protocol::carrier_frequency()`](crate::Protocol::carrier_frequency).
+
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+use embedded_hal::pwm::SetDutyCycle;
+use embedded_hal_async::digital::Wait;
+
+use crate::{Error, Receiver, Transmitter};
+
+/// Duration of the spaces after which signals are considered complete, which is longer than the
+/// spaces of the supported protocols.
+const IDLE_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Duty cycle of the carrier during marks, as a fraction.
+const DUTY_CYCLE: (u16, u16) = (1, 3);
+
+/// An IR receiver module, connected to a GPIO.
+pub struct GpioReceiver<P> {
+    pin: P,
+}
+
+impl<P: Wait> GpioReceiver<P> {
+    /// Returns a driver for the IR receiver module whose output is connected to `pin`.
+    ///
+    /// The output of receiver modules is low during marks, and is usually pulled up by the
+    /// module.
+    #[must_use]
+    pub fn new(pin: P) -> Self {
+        Self { pin }
+    }
+
+    /// Returns the pin.
+    pub fn release(self) -> P {
+        self.pin
+    }
+}
+
+impl<P: Wait> Receiver for GpioReceiver<P> {
+    async fn receive_timings(&mut self, timings: &mut [u16]) -> Result<usize, Error> {
+        self.pin.wait_for_low().await.map_err(|_| Error::Hardware)?;
+
+        let mut start = Instant::now();
+        let mut len = 0;
+        let mut mark = true;
+        loop {
+            if mark {
+                self.pin
+                    .wait_for_high()
+                    .await
+                    .map_err(|_| Error::Hardware)?;
+            } else if let Ok(result) = with_timeout(IDLE_TIMEOUT, self.pin.wait_for_low()).await {
+                result.map_err(|_| Error::Hardware)?;
+            } else {
+                // The signal ended with the previous mark.
+                return Ok(len);
+            }
+
+            let now = Instant::now();
+            if let Some(timing) = timings.get_mut(len) {
+                *timing = u16::try_from(now.duration_since(start).as_micros()).unwrap_or(u16::MAX);
+                len += 1;
+            }
+            start = now;
+            mark = !mark;
+        }
+    }
+}
+
+/// An IR LED, driven by a PWM output.
+pub struct PwmTransmitter<P> {
+    pwm: P,
+}
+
+impl<P: SetDutyCycle> PwmTransmitter<P> {
+    /// Returns a driver for the IR LED driven by `pwm`, whose frequency must be the carrier
+    /// frequency.
+    #[must_use]
+    pub fn new(pwm: P) -> Self {
+        Self { pwm }
+    }
+
+    /// Returns the PWM output.
+    pub fn release(self) -> P {
+        self.pwm
+    }
+}
+
+impl<P: SetDutyCycle> Transmitter for PwmTransmitter<P> {
+    async fn transmit_timings(&mut self, timings: &[u16]) -> Result<(), Error> {
+        // Marks and spaces are timed from the start of the signal, so that timer latencies do not
+        // add up.
+        let mut deadline = Instant::now();
+        for (i, &duration) in timings.iter().enumerate() {
+            let result = if i % 2 == 0 {
+                let (numerator, denominator) = DUTY_CYCLE;
+                self.pwm.set_duty_cycle_fraction(numerator, denominator)
+            } else {
+                self.pwm.set_duty_cycle_fully_off()
+            };
+            result.map_err(|_| Error::Hardware)?;
+
+            deadline += Duration::from_micros(u64::from(duration));
+            Timer::at(deadline).await;
+        }
+
+        self.pwm
+            .set_duty_cycle_fully_off()
+            .map_err(|_| Error::Hardware)
+    }
+}
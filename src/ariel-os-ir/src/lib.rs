@@ -0,0 +1,228 @@
+//! Provides infrared remote control, to send and receive the codes of consumer-device remotes.
+//!
+//! IR signals are sequences of marks, during which the IR LED blinks at a carrier frequency, and
+//! of spaces, during which it is off.
+//! Remote control [`Protocol`]s encode [`Code`]s into the timings of these marks and spaces; the
+//! supported protocols are listed in [`PROTOCOLS`], which receivers try in turn.
+//! Signals are received by demodulating IR receiver modules and sent by IR LEDs, through a
+//! [`Receiver`] and a [`Transmitter`], which are implemented by the RMT peripheral of ESP32 MCUs
+//! and by the [`gpio`] drivers.
+//!
+//! ```ignore
+//! loop {
+//!     let received = receiver.receive(ir::PROTOCOLS).await?;
+//!     info!("{}: {:?}", received.protocol, received.code);
+//! }
+//! ```
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+#[cfg(feature = "gpio")]
+pub mod gpio;
+pub mod nec;
+pub mod rc5;
+
+/// Maximum number of marks and spaces of the signals handled by [`Receiver::receive()`] and
+/// [`Transmitter::transmit()`].
+pub const MAX_TIMINGS: usize = 128;
+
+/// The protocols supported by this crate.
+pub const PROTOCOLS: &[&dyn Protocol] = &[&nec::Nec, &rc5::Rc5];
+
+/// A code sent by a remote control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Code {
+    /// Address of the device the code is meant for.
+    pub address: u16,
+    /// Command of the code, usually identifying the pressed key.
+    pub command: u16,
+    /// Toggle bit, which protocols like RC5 flip on every key press, to distinguish held keys
+    /// from repeatedly pressed ones.
+    pub toggle: bool,
+    /// Whether the code is a repeat code, sent by protocols like NEC while a key is held.
+    pub repeat: bool,
+}
+
+impl Code {
+    /// Returns a code with the `address` and `command`.
+    #[must_use]
+    pub const fn new(address: u16, command: u16) -> Self {
+        Self {
+            address,
+            command,
+            toggle: false,
+            repeat: false,
+        }
+    }
+}
+
+/// A code received with a [`Receiver`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Received {
+    /// Name of the protocol the code was decoded with.
+    pub protocol: &'static str,
+    /// The decoded code.
+    pub code: Code,
+}
+
+/// An IR remote control protocol.
+///
+/// Signals are represented by the durations of their marks and spaces in microseconds,
+/// alternating and starting with a mark.
+pub trait Protocol {
+    /// Returns the name of the protocol.
+    fn name(&self) -> &'static str;
+
+    /// Returns the carrier frequency of the protocol in Hz, which transmitters are to be
+    /// configured with.
+    fn carrier_frequency(&self) -> u32;
+
+    /// Encodes `code` into `timings`, and returns the number of marks and spaces.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidCode`] if the protocol cannot carry the code, and
+    /// [`Error::BufferTooSmall`] if the signal does not fit into `timings`.
+    fn encode(&self, code: &Code, timings: &mut [u16]) -> Result<usize, Error>;
+
+    /// Decodes the signal `timings`, and returns `None` if it is not a valid signal of the
+    /// protocol.
+    fn decode(&self, timings: &[u16]) -> Option<Code>;
+}
+
+/// Decodes the signal `timings` with the first of the `protocols` that accepts it.
+#[must_use]
+pub fn decode(protocols: &[&dyn Protocol], timings: &[u16]) -> Option<Received> {
+    protocols.iter().find_map(|protocol| {
+        protocol.decode(timings).map(|code| Received {
+            protocol: protocol.name(),
+            code,
+        })
+    })
+}
+
+/// Receives IR signals.
+pub trait Receiver {
+    /// Waits for a signal, stores the durations of its marks and spaces in microseconds into
+    /// `timings`, and returns their number.
+    ///
+    /// Signals end when no mark has been received for some time, and are truncated if they do not
+    /// fit into `timings`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Hardware`] if the receiver fails.
+    fn receive_timings(
+        &mut self,
+        timings: &mut [u16],
+    ) -> impl Future<Output = Result<usize, Error>>;
+
+    /// Waits for a signal that one of the `protocols` can decode, eg. [`PROTOCOLS`], and returns
+    /// its code.
+    ///
+    /// Signals that cannot be decoded are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Hardware`] if the receiver fails.
+    fn receive(
+        &mut self,
+        protocols: &[&dyn Protocol],
+    ) -> impl Future<Output = Result<Received, Error>> {
+        async move {
+            let mut timings = [0; MAX_TIMINGS];
+            loop {
+                let len = self.receive_timings(&mut timings).await?;
+                if let Some(received) = decode(protocols, timings.get(..len).unwrap_or_default()) {
+                    return Ok(received);
+                }
+            }
+        }
+    }
+}
+
+/// Transmits IR signals.
+///
+/// Transmitters modulate marks with a fixed carrier frequency, which is usually configured when
+/// they are created; receivers are tolerant enough to accept the carrier frequencies of the
+/// common protocols.
+pub trait Transmitter {
+    /// Transmits the signal `timings`, which are the durations of its marks and spaces in
+    /// microseconds, alternating and starting with a mark.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::BufferTooSmall`] if the transmitter cannot handle that many marks and
+    /// spaces, and [`Error::Hardware`] if it fails.
+    fn transmit_timings(&mut self, timings: &[u16]) -> impl Future<Output = Result<(), Error>>;
+
+    /// Encodes `code` with `protocol`, and transmits it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidCode`] if the protocol cannot carry the code, and
+    /// [`Error::Hardware`] if the transmitter fails.
+    fn transmit(
+        &mut self,
+        protocol: &dyn Protocol,
+        code: &Code,
+    ) -> impl Future<Output = Result<(), Error>> {
+        async move {
+            let mut timings = [0; MAX_TIMINGS];
+            let len = protocol.encode(code, &mut timings)?;
+            self.transmit_timings(timings.get(..len).unwrap_or_default())
+                .await
+        }
+    }
+}
+
+/// Returns whether the `actual` duration of a mark or space matches the `expected` one, within
+/// the tolerance of receivers and of timers.
+fn matches(actual: u16, expected: u16) -> bool {
+    actual.abs_diff(expected) <= expected / 3
+}
+
+/// Writes the `signal` into `timings`, and returns its length.
+///
+/// # Errors
+///
+/// Returns [`Error::BufferTooSmall`] if the signal does not fit into `timings`.
+fn write_signal(
+    signal: impl IntoIterator<Item = u16>,
+    timings: &mut [u16],
+) -> Result<usize, Error> {
+    let mut len = 0;
+    for duration in signal {
+        *timings.get_mut(len).ok_or(Error::BufferTooSmall)? = duration;
+        len += 1;
+    }
+    Ok(len)
+}
+
+/// IR-related errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The buffer is too small for the signal.
+    BufferTooSmall,
+    /// The protocol cannot carry the code.
+    InvalidCode,
+    /// The receiver or transmitter failed.
+    Hardware,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::BufferTooSmall => write!(f, "buffer too small"),
+            Self::InvalidCode => write!(f, "code not supported by the protocol"),
+            Self::Hardware => write!(f, "IR hardware failure"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
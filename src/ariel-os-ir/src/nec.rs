@@ -0,0 +1,209 @@
+//! Provides the NEC protocol, used by many Asian consumer devices.
+//!
+//! Codes have an 8-bit address, or a 16-bit one with the extended variant of the protocol, and an
+//! 8-bit command.
+//! While a key is held, remotes send repeat codes, which carry neither; they are decoded into
+//! [`Code`]s with [`repeat`](Code::repeat) set, whose address and command are zero.
+
+use crate::{Code, Error, Protocol, matches, write_signal};
+
+/// Duration of the mark starting frames.
+const LEADER_MARK: u16 = 9000;
+/// Duration of the space following the leader mark of frames.
+const LEADER_SPACE: u16 = 4500;
+/// Duration of the space following the leader mark of repeat codes.
+const REPEAT_SPACE: u16 = 2250;
+/// Duration of the marks preceding each bit, and ending frames.
+const BIT_MARK: u16 = 562;
+/// Duration of the space of zero bits.
+const ZERO_SPACE: u16 = 562;
+/// Duration of the space of one bits.
+const ONE_SPACE: u16 = 1687;
+
+/// Number of bits of frames.
+const BITS: usize = 32;
+
+/// The NEC protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Nec;
+
+impl Protocol for Nec {
+    fn name(&self) -> &'static str {
+        "NEC"
+    }
+
+    fn carrier_frequency(&self) -> u32 {
+        38_000
+    }
+
+    fn encode(&self, code: &Code, timings: &mut [u16]) -> Result<usize, Error> {
+        if code.repeat {
+            return write_signal([LEADER_MARK, REPEAT_SPACE, BIT_MARK], timings);
+        }
+
+        let command = u8::try_from(code.command).map_err(|_| Error::InvalidCode)?;
+        let [address_low, address_high] = code.address.to_le_bytes();
+        // 8-bit addresses are followed by their complement, which extended addresses must differ
+        // from.
+        let address_high = if code.address <= 0xff {
+            !address_low
+        } else if address_high == !address_low {
+            return Err(Error::InvalidCode);
+        } else {
+            address_high
+        };
+
+        // Bits are sent least significant first.
+        let data = u32::from_le_bytes([address_low, address_high, command, !command]);
+        let bits = (0..BITS).flat_map(|i| {
+            let space = if (data >> i) & 1 == 1 {
+                ONE_SPACE
+            } else {
+                ZERO_SPACE
+            };
+            [BIT_MARK, space]
+        });
+        write_signal(
+            [LEADER_MARK, LEADER_SPACE]
+                .into_iter()
+                .chain(bits)
+                .chain([BIT_MARK]),
+            timings,
+        )
+    }
+
+    fn decode(&self, timings: &[u16]) -> Option<Code> {
+        let (&[leader_mark, leader_space], rest) = timings.split_first_chunk()?;
+        if !matches(leader_mark, LEADER_MARK) {
+            return None;
+        }
+
+        if matches(leader_space, REPEAT_SPACE) {
+            let &[mark] = rest else {
+                return None;
+            };
+            return matches(mark, BIT_MARK).then_some(Code {
+                repeat: true,
+                ..Code::default()
+            });
+        }
+
+        if !matches(leader_space, LEADER_SPACE) || rest.len() != 2 * BITS + 1 {
+            return None;
+        }
+        let bits = rest.chunks_exact(2);
+        if !bits.remainder().iter().all(|&mark| matches(mark, BIT_MARK)) {
+            return None;
+        }
+        let mut data = 0u32;
+        for (i, bit) in bits.enumerate() {
+            let &[mark, space] = bit else {
+                return None;
+            };
+            if !matches(mark, BIT_MARK) {
+                return None;
+            }
+            if matches(space, ONE_SPACE) {
+                data |= 1 << i;
+            } else if !matches(space, ZERO_SPACE) {
+                return None;
+            }
+        }
+
+        let [address_low, address_high, command, command_complement] = data.to_le_bytes();
+        if command != !command_complement {
+            return None;
+        }
+        let address = if address_high == !address_low {
+            u16::from(address_low)
+        } else {
+            u16::from_le_bytes([address_low, address_high])
+        };
+        Some(Code::new(address, u16::from(command)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded(code: Code) -> ([u16; 80], usize) {
+        let mut timings = [0; 80];
+        let len = Nec.encode(&code, &mut timings).unwrap();
+        (timings, len)
+    }
+
+    #[test]
+    fn frame() {
+        let (timings, len) = encoded(Code::new(0x04, 0x08));
+        assert_eq!(len, 67);
+        // Address 0x04, sent least significant bit first.
+        assert_eq!(
+            timings.get(..8),
+            Some([9000, 4500, 562, 562, 562, 562, 562, 1687].as_slice())
+        );
+        assert_eq!(timings.get(66), Some(&562));
+        assert_eq!(
+            Nec.decode(timings.get(..len).unwrap()),
+            Some(Code::new(0x04, 0x08))
+        );
+    }
+
+    #[test]
+    fn extended_address() {
+        let code = Code::new(0x1234, 0xff);
+        let (timings, len) = encoded(code);
+        assert_eq!(Nec.decode(timings.get(..len).unwrap()), Some(code));
+
+        // The high byte is the complement of the low byte.
+        assert_eq!(
+            Nec.encode(&Code::new(0xfe01, 0x00), &mut [0; 80]),
+            Err(Error::InvalidCode)
+        );
+        assert_eq!(
+            Nec.encode(&Code::new(0x01, 0x100), &mut [0; 80]),
+            Err(Error::InvalidCode)
+        );
+    }
+
+    #[test]
+    fn repeat() {
+        let code = Code {
+            repeat: true,
+            ..Code::default()
+        };
+        let (timings, len) = encoded(code);
+        assert_eq!(timings.get(..len), Some([9000, 2250, 562].as_slice()));
+        assert_eq!(Nec.decode(&[8850, 2300, 600]), Some(code));
+    }
+
+    #[test]
+    fn jitter() {
+        let (mut timings, len) = encoded(Code::new(0x00, 0x45));
+        // Receivers lengthen marks, and shorten spaces.
+        for (i, duration) in timings.iter_mut().take(len).enumerate() {
+            if i % 2 == 0 {
+                *duration += 120;
+            } else {
+                *duration -= 120;
+            }
+        }
+        assert_eq!(
+            Nec.decode(timings.get(..len).unwrap()),
+            Some(Code::new(0x00, 0x45))
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let (mut timings, len) = encoded(Code::new(0x00, 0x45));
+        assert_eq!(Nec.decode(timings.get(..len - 2).unwrap()), None);
+        // Flip the second bit of the command.
+        *timings.get_mut(2 + 2 * 17 + 1).unwrap() = ONE_SPACE;
+        assert_eq!(Nec.decode(timings.get(..len).unwrap()), None);
+        assert_eq!(
+            Nec.encode(&Code::new(0x00, 0x45), &mut [0; 66]),
+            Err(Error::BufferTooSmall)
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Provides the Philips RC-5 protocol, used by many European consumer devices.
+//!
+//! Codes have a 5-bit address and a 7-bit command, and carry a [toggle bit](Code::toggle).
+//! Commands from 64 on use the RC-5X extension of the protocol.
+
+use crate::{Code, Error, Protocol, matches, write_signal};
+
+/// Duration of the halves of bits, which are Manchester-encoded.
+const HALF_BIT: u16 = 889;
+
+/// Number of bits of frames.
+const BITS: usize = 14;
+/// Number of halves of bits of frames.
+const HALVES: usize = 2 * BITS;
+
+/// Maximum address of codes.
+const MAX_ADDRESS: u16 = 0x1f;
+/// Maximum command of codes.
+const MAX_COMMAND: u16 = 0x7f;
+
+/// The RC-5 protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rc5;
+
+impl Protocol for Rc5 {
+    fn name(&self) -> &'static str {
+        "RC-5"
+    }
+
+    fn carrier_frequency(&self) -> u32 {
+        36_000
+    }
+
+    fn encode(&self, code: &Code, timings: &mut [u16]) -> Result<usize, Error> {
+        if code.address > MAX_ADDRESS || code.command > MAX_COMMAND {
+            return Err(Error::InvalidCode);
+        }
+
+        // The start bit, followed by the inverted seventh bit of the command, the toggle bit, the
+        // address and the rest of the command.
+        let bits = 1 << 13
+            | u16::from(code.command & 0x40 == 0) << 12
+            | u16::from(code.toggle) << 11
+            | code.address << 6
+            | code.command & 0x3f;
+        // Ones are a space followed by a mark, zeros a mark followed by a space.
+        let halves = (0..BITS).rev().flat_map(|i| {
+            let one = (bits >> i) & 1 == 1;
+            [!one, one]
+        });
+
+        // Merge adjacent halves of the same level, skipping the leading space of the start bit.
+        let mut runs = [0; HALVES];
+        let mut len = 0usize;
+        let mut previous = false;
+        for mark in halves {
+            if mark != previous {
+                len += 1;
+                previous = mark;
+            }
+            if let Some(run) = len.checked_sub(1).and_then(|i| runs.get_mut(i)) {
+                *run += HALF_BIT;
+            }
+        }
+        // Signals end with a mark.
+        if !previous {
+            len -= 1;
+        }
+
+        write_signal(runs.into_iter().take(len), timings)
+    }
+
+    fn decode(&self, timings: &[u16]) -> Option<Code> {
+        // The leading space of the start bit is not part of the signal.
+        let mut halves = [false; HALVES];
+        let mut len = 1;
+        for (i, &duration) in timings.iter().enumerate() {
+            let mark = i % 2 == 0;
+            let count = if matches(duration, HALF_BIT) {
+                1
+            } else if matches(duration, 2 * HALF_BIT) {
+                2
+            } else {
+                return None;
+            };
+            for _ in 0..count {
+                *halves.get_mut(len)? = mark;
+                len += 1;
+            }
+        }
+        // The trailing space of the last bit is not part of the signal either.
+        if len == HALVES - 1 {
+            len += 1;
+        }
+        if len != HALVES {
+            return None;
+        }
+
+        let mut bits = 0u16;
+        for half in halves.chunks_exact(2) {
+            let one = match *half {
+                [false, true] => true,
+                [true, false] => false,
+                _ => return None,
+            };
+            bits = bits << 1 | u16::from(one);
+        }
+
+        let command_high = if (bits >> 12) & 1 == 0 { 0x40 } else { 0 };
+        Some(Code {
+            address: (bits >> 6) & MAX_ADDRESS,
+            command: command_high | bits & 0x3f,
+            toggle: (bits >> 11) & 1 == 1,
+            repeat: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const H: u16 = HALF_BIT;
+
+    fn encoded(code: Code) -> ([u16; 32], usize) {
+        let mut timings = [0; 32];
+        let len = Rc5.encode(&code, &mut timings).unwrap();
+        (timings, len)
+    }
+
+    #[test]
+    fn frame() {
+        let code = Code {
+            toggle: true,
+            ..Code::new(0x05, 0x35)
+        };
+        let (timings, len) = encoded(code);
+        // Bits 1 1 1 00101 110101.
+        assert_eq!(
+            timings.get(..len),
+            Some(
+                [
+                    H,
+                    H,
+                    H,
+                    H,
+                    2 * H,
+                    H,
+                    H,
+                    2 * H,
+                    2 * H,
+                    2 * H,
+                    H,
+                    H,
+                    H,
+                    H,
+                    2 * H,
+                    2 * H,
+                    2 * H,
+                    2 * H,
+                    H,
+                ]
+                .as_slice()
+            )
+        );
+        assert_eq!(Rc5.decode(timings.get(..len).unwrap()), Some(code));
+    }
+
+    #[test]
+    fn extended_command() {
+        for command in [0x00, 0x3f, 0x40, 0x7f] {
+            let code = Code::new(0x1f, command);
+            let (timings, len) = encoded(code);
+            assert_eq!(Rc5.decode(timings.get(..len).unwrap()), Some(code));
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            Rc5.encode(&Code::new(0x20, 0x00), &mut [0; 32]),
+            Err(Error::InvalidCode)
+        );
+        assert_eq!(
+            Rc5.encode(&Code::new(0x00, 0x80), &mut [0; 32]),
+            Err(Error::InvalidCode)
+        );
+        assert_eq!(Rc5.decode(&[H, H, H]), None);
+        assert_eq!(Rc5.decode(&[9000, 4500, 562]), None);
+    }
+}
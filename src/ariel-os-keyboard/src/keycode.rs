@@ -0,0 +1,191 @@
+//! Provides the keycodes of the HID Keyboard/Keypad usage page, named after a US layout.
+//!
+//! The keycodes are the usage IDs of the
+//! [HID Usage Tables](https://usb.org/document-library/hid-usage-tables-15); hosts map them to
+//! characters according to their configured layout.
+
+/// No key.
+pub const NO: u8 = 0x00;
+/// Reported in all key slots when more keys are pressed than a report can hold.
+pub const ERROR_ROLL_OVER: u8 = 0x01;
+/// `a` and `A`.
+pub const A: u8 = 0x04;
+/// `b` and `B`.
+pub const B: u8 = 0x05;
+/// `c` and `C`.
+pub const C: u8 = 0x06;
+/// `d` and `D`.
+pub const D: u8 = 0x07;
+/// `e` and `E`.
+pub const E: u8 = 0x08;
+/// `f` and `F`.
+pub const F: u8 = 0x09;
+/// `g` and `G`.
+pub const G: u8 = 0x0a;
+/// `h` and `H`.
+pub const H: u8 = 0x0b;
+/// `i` and `I`.
+pub const I: u8 = 0x0c;
+/// `j` and `J`.
+pub const J: u8 = 0x0d;
+/// `k` and `K`.
+pub const K: u8 = 0x0e;
+/// `l` and `L`.
+pub const L: u8 = 0x0f;
+/// `m` and `M`.
+pub const M: u8 = 0x10;
+/// `n` and `N`.
+pub const N: u8 = 0x11;
+/// `o` and `O`.
+pub const O: u8 = 0x12;
+/// `p` and `P`.
+pub const P: u8 = 0x13;
+/// `q` and `Q`.
+pub const Q: u8 = 0x14;
+/// `r` and `R`.
+pub const R: u8 = 0x15;
+/// `s` and `S`.
+pub const S: u8 = 0x16;
+/// `t` and `T`.
+pub const T: u8 = 0x17;
+/// `u` and `U`.
+pub const U: u8 = 0x18;
+/// `v` and `V`.
+pub const V: u8 = 0x19;
+/// `w` and `W`.
+pub const W: u8 = 0x1a;
+/// `x` and `X`.
+pub const X: u8 = 0x1b;
+/// `y` and `Y`.
+pub const Y: u8 = 0x1c;
+/// `z` and `Z`.
+pub const Z: u8 = 0x1d;
+/// `1` and `!`.
+pub const N1: u8 = 0x1e;
+/// `2` and `@`.
+pub const N2: u8 = 0x1f;
+/// `3` and `#`.
+pub const N3: u8 = 0x20;
+/// `4` and `$`.
+pub const N4: u8 = 0x21;
+/// `5` and `%`.
+pub const N5: u8 = 0x22;
+/// `6` and `^`.
+pub const N6: u8 = 0x23;
+/// `7` and `&`.
+pub const N7: u8 = 0x24;
+/// `8` and `*`.
+pub const N8: u8 = 0x25;
+/// `9` and `(`.
+pub const N9: u8 = 0x26;
+/// `0` and `)`.
+pub const N0: u8 = 0x27;
+/// Enter.
+pub const ENTER: u8 = 0x28;
+/// Escape.
+pub const ESCAPE: u8 = 0x29;
+/// Backspace.
+pub const BACKSPACE: u8 = 0x2a;
+/// Tab.
+pub const TAB: u8 = 0x2b;
+/// Space bar.
+pub const SPACE: u8 = 0x2c;
+/// `-` and `_`.
+pub const MINUS: u8 = 0x2d;
+/// `=` and `+`.
+pub const EQUAL: u8 = 0x2e;
+/// `[` and `{`.
+pub const LEFT_BRACKET: u8 = 0x2f;
+/// `]` and `}`.
+pub const RIGHT_BRACKET: u8 = 0x30;
+/// `\` and `|`.
+pub const BACKSLASH: u8 = 0x31;
+/// `;` and `:`.
+pub const SEMICOLON: u8 = 0x33;
+/// `'` and `"`.
+pub const QUOTE: u8 = 0x34;
+/// `` ` `` and `~`.
+pub const GRAVE: u8 = 0x35;
+/// `,` and `<`.
+pub const COMMA: u8 = 0x36;
+/// `.` and `>`.
+pub const DOT: u8 = 0x37;
+/// `/` and `?`.
+pub const SLASH: u8 = 0x38;
+/// Caps Lock.
+pub const CAPS_LOCK: u8 = 0x39;
+/// F1.
+pub const F1: u8 = 0x3a;
+/// F2.
+pub const F2: u8 = 0x3b;
+/// F3.
+pub const F3: u8 = 0x3c;
+/// F4.
+pub const F4: u8 = 0x3d;
+/// F5.
+pub const F5: u8 = 0x3e;
+/// F6.
+pub const F6: u8 = 0x3f;
+/// F7.
+pub const F7: u8 = 0x40;
+/// F8.
+pub const F8: u8 = 0x41;
+/// F9.
+pub const F9: u8 = 0x42;
+/// F10.
+pub const F10: u8 = 0x43;
+/// F11.
+pub const F11: u8 = 0x44;
+/// F12.
+pub const F12: u8 = 0x45;
+/// Print Screen.
+pub const PRINT_SCREEN: u8 = 0x46;
+/// Scroll Lock.
+pub const SCROLL_LOCK: u8 = 0x47;
+/// Pause.
+pub const PAUSE: u8 = 0x48;
+/// Insert.
+pub const INSERT: u8 = 0x49;
+/// Home.
+pub const HOME: u8 = 0x4a;
+/// Page Up.
+pub const PAGE_UP: u8 = 0x4b;
+/// Delete.
+pub const DELETE: u8 = 0x4c;
+/// End.
+pub const END: u8 = 0x4d;
+/// Page Down.
+pub const PAGE_DOWN: u8 = 0x4e;
+/// Right arrow.
+pub const RIGHT: u8 = 0x4f;
+/// Left arrow.
+pub const LEFT: u8 = 0x50;
+/// Down arrow.
+pub const DOWN: u8 = 0x51;
+/// Up arrow.
+pub const UP: u8 = 0x52;
+/// Left Control, reported as a modifier.
+pub const LEFT_CTRL: u8 = 0xe0;
+/// Left Shift, reported as a modifier.
+pub const LEFT_SHIFT: u8 = 0xe1;
+/// Left Alt, reported as a modifier.
+pub const LEFT_ALT: u8 = 0xe2;
+/// Left GUI (Windows, Command), reported as a modifier.
+pub const LEFT_GUI: u8 = 0xe3;
+/// Right Control, reported as a modifier.
+pub const RIGHT_CTRL: u8 = 0xe4;
+/// Right Shift, reported as a modifier.
+pub const RIGHT_SHIFT: u8 = 0xe5;
+/// Right Alt, reported as a modifier.
+pub const RIGHT_ALT: u8 = 0xe6;
+/// Right GUI (Windows, Command), reported as a modifier.
+pub const RIGHT_GUI: u8 = 0xe7;
+
+/// Returns the bit of the modifier byte of reports corresponding to `keycode`, if it is the
+/// keycode of a modifier.
+pub(crate) fn modifier_bit(keycode: u8) -> Option<u8> {
+    match keycode {
+        LEFT_CTRL..=RIGHT_GUI => Some(1 << (keycode - LEFT_CTRL)),
+        _ => None,
+    }
+}
@@ -0,0 +1,360 @@
+//! Provides keyboard keymaps with layers, turning the keys pressed on a key matrix into HID
+//! keyboard reports.
+//!
+//! A keymap is a stack of [`Layer`]s, each mapping the keys of the matrix to [`Action`]s; the
+//! [`Keyboard`] resolves the action of a key on the topmost active layer when it is pressed, and
+//! tracks the pressed keys to produce [`Report`]s, which are sent to the host over USB or BLE HID.
+//!
+//! ```ignore
+//! use ariel_os::keyboard::{Action::*, Keyboard, Layer, keycode::*};
+//!
+//! static LAYERS: [Layer<2, 2>; 2] = [
+//!     [[Key(A), Key(B)], [Keys(&[LEFT_CTRL, C]), HoldLayer(1)]],
+//!     [[Key(UP), Key(DOWN)], [Transparent, Transparent]],
+//! ];
+//!
+//! let mut keyboard = Keyboard::new(&LAYERS);
+//! keyboard.press(0, 0);
+//! send(keyboard.report().to_bytes()).await;
+//! ```
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+pub mod keycode;
+
+/// Maximum number of layers of a keymap.
+pub const MAX_LAYERS: usize = 32;
+
+/// Length of the reports returned by [`Report::to_bytes()`].
+pub const REPORT_LEN: usize = 8;
+
+/// Number of keys, besides modifiers, that a report holds.
+pub const ROLLOVER: usize = 6;
+
+/// HID report descriptor of the reports returned by [`Report::to_bytes()`], which is the one of
+/// boot keyboards, so that reports are understood by BIOSes too.
+pub const REPORT_DESCRIPTOR: &[u8] = &[
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x06, // Usage (Keyboard)
+    0xa1, 0x01, // Collection (Application)
+    0x05, 0x07, //   Usage Page (Keyboard/Keypad)
+    0x19, 0xe0, //   Usage Minimum (Left Control)
+    0x29, 0xe7, //   Usage Maximum (Right GUI)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x01, //   Logical Maximum (1)
+    0x75, 0x01, //   Report Size (1)
+    0x95, 0x08, //   Report Count (8)
+    0x81, 0x02, //   Input (Data, Variable, Absolute): modifiers
+    0x95, 0x01, //   Report Count (1)
+    0x75, 0x08, //   Report Size (8)
+    0x81, 0x01, //   Input (Constant): reserved
+    0x95, 0x05, //   Report Count (5)
+    0x75, 0x01, //   Report Size (1)
+    0x05, 0x08, //   Usage Page (LEDs)
+    0x19, 0x01, //   Usage Minimum (Num Lock)
+    0x29, 0x05, //   Usage Maximum (Kana)
+    0x91, 0x02, //   Output (Data, Variable, Absolute): LEDs
+    0x95, 0x01, //   Report Count (1)
+    0x75, 0x03, //   Report Size (3)
+    0x91, 0x01, //   Output (Constant): padding
+    0x95, 0x06, //   Report Count (6)
+    0x75, 0x08, //   Report Size (8)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x65, //   Logical Maximum (101)
+    0x05, 0x07, //   Usage Page (Keyboard/Keypad)
+    0x19, 0x00, //   Usage Minimum (0)
+    0x29, 0x65, //   Usage Maximum (101)
+    0x81, 0x00, //   Input (Data, Array): keys
+    0xc0, // End Collection
+];
+
+/// A layer of a keymap, mapping each key of a matrix of `ROWS` rows and `COLUMNS` columns to an
+/// action.
+pub type Layer<const ROWS: usize, const COLUMNS: usize> = [[Action; COLUMNS]; ROWS];
+
+/// The action of a key of a layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Action {
+    /// Does nothing.
+    #[default]
+    None,
+    /// Does what the key does on the next active layer below.
+    Transparent,
+    /// Presses the key with the [`keycode`], which may be the keycode of a modifier.
+    Key(u8),
+    /// Presses the keys with the [`keycode`]s together, eg. a shortcut like `Ctrl+C`.
+    Keys(&'static [u8]),
+    /// Activates the layer while the key is held.
+    HoldLayer(u8),
+    /// Toggles the layer when the key is pressed.
+    ToggleLayer(u8),
+}
+
+/// A keyboard, tracking the pressed keys of a key matrix of `ROWS` rows and `COLUMNS` columns.
+pub struct Keyboard<'a, const ROWS: usize, const COLUMNS: usize> {
+    layers: &'a [Layer<ROWS, COLUMNS>],
+    /// Actions of the pressed keys, resolved when they were pressed.
+    pressed: [[Action; COLUMNS]; ROWS],
+    /// Layers toggled by [`Action::ToggleLayer`], as a bit mask.
+    toggled: u32,
+}
+
+impl<'a, const ROWS: usize, const COLUMNS: usize> Keyboard<'a, ROWS, COLUMNS> {
+    /// Returns a keyboard with the keymap `layers`, from the base layer, which is always active.
+    ///
+    /// Layers beyond [`MAX_LAYERS`] are never active.
+    #[must_use]
+    pub const fn new(layers: &'a [Layer<ROWS, COLUMNS>]) -> Self {
+        Self {
+            layers,
+            pressed: [[Action::None; COLUMNS]; ROWS],
+            toggled: 0,
+        }
+    }
+
+    /// Records that the key at `row` and `column` is pressed.
+    ///
+    /// The action of the key is resolved on the layers active at that time, and sticks until the
+    /// key is released.
+    pub fn press(&mut self, row: usize, column: usize) {
+        let action = self.action(row, column);
+        if let Action::ToggleLayer(layer) = action {
+            self.toggled ^= layer_bit(usize::from(layer));
+        }
+        if let Some(pressed) = self.pressed_mut(row, column) {
+            *pressed = action;
+        }
+    }
+
+    /// Records that the key at `row` and `column` is released.
+    pub fn release(&mut self, row: usize, column: usize) {
+        if let Some(pressed) = self.pressed_mut(row, column) {
+            *pressed = Action::None;
+        }
+    }
+
+    /// Releases all keys, and deactivates the toggled layers.
+    pub fn reset(&mut self) {
+        self.pressed = [[Action::None; COLUMNS]; ROWS];
+        self.toggled = 0;
+    }
+
+    /// Returns the layers that are currently active, as a bit mask.
+    #[must_use]
+    pub fn active_layers(&self) -> u32 {
+        self.pressed
+            .iter()
+            .flatten()
+            .fold(1 | self.toggled, |active, action| match *action {
+                Action::HoldLayer(layer) => active | layer_bit(usize::from(layer)),
+                _ => active,
+            })
+    }
+
+    /// Returns the report of the currently pressed keys.
+    ///
+    /// When more than [`ROLLOVER`] keys other than modifiers are pressed, all key slots report
+    /// [`keycode::ERROR_ROLL_OVER`], as the HID specification requires.
+    #[must_use]
+    pub fn report(&self) -> Report {
+        let mut report = Report::default();
+        let mut len = 0;
+        let mut rolled_over = false;
+
+        let keycodes = self
+            .pressed
+            .iter()
+            .flatten()
+            .flat_map(|action| match action {
+                Action::Key(keycode) => core::slice::from_ref(keycode),
+                Action::Keys(keycodes) => keycodes,
+                _ => &[],
+            });
+        for &keycode in keycodes {
+            if let Some(bit) = keycode::modifier_bit(keycode) {
+                report.modifiers |= bit;
+            } else if keycode != keycode::NO && !report.keycodes.contains(&keycode) {
+                if let Some(slot) = report.keycodes.get_mut(len) {
+                    *slot = keycode;
+                    len += 1;
+                } else {
+                    rolled_over = true;
+                }
+            }
+        }
+
+        if rolled_over {
+            report.keycodes = [keycode::ERROR_ROLL_OVER; ROLLOVER];
+        }
+        report
+    }
+
+    fn pressed_mut(&mut self, row: usize, column: usize) -> Option<&mut Action> {
+        self.pressed.get_mut(row)?.get_mut(column)
+    }
+
+    /// Returns the action of the key at `row` and `column` on the topmost active layer.
+    fn action(&self, row: usize, column: usize) -> Action {
+        let active = self.active_layers();
+        self.layers
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|&(layer, _)| active & layer_bit(layer) != 0)
+            .map(|(_, layer)| {
+                layer
+                    .get(row)
+                    .and_then(|row| row.get(column))
+                    .copied()
+                    .unwrap_or_default()
+            })
+            .find(|&action| action != Action::Transparent)
+            .unwrap_or_default()
+    }
+}
+
+/// Returns the bit of `layer` in masks of layers, which is `0` for layers beyond [`MAX_LAYERS`].
+fn layer_bit(layer: usize) -> u32 {
+    u32::try_from(layer)
+        .ok()
+        .and_then(|layer| 1u32.checked_shl(layer))
+        .unwrap_or(0)
+}
+
+/// A HID keyboard report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Report {
+    /// Pressed modifiers, one bit each, from [`keycode::LEFT_CTRL`] to [`keycode::RIGHT_GUI`].
+    pub modifiers: u8,
+    /// Keycodes of the other pressed keys, padded with [`keycode::NO`].
+    pub keycodes: [u8; ROLLOVER],
+}
+
+impl Report {
+    /// Returns the report in the format described by [`REPORT_DESCRIPTOR`].
+    #[must_use]
+    pub fn to_bytes(&self) -> [u8; REPORT_LEN] {
+        let [k0, k1, k2, k3, k4, k5] = self.keycodes;
+        [self.modifiers, 0, k0, k1, k2, k3, k4, k5]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action::*, keycode::*, *};
+
+    static LAYERS: [Layer<2, 3>; 3] = [
+        [
+            [Key(A), Key(B), Key(LEFT_SHIFT)],
+            [HoldLayer(1), ToggleLayer(2), Keys(&[LEFT_CTRL, C])],
+        ],
+        [[Key(UP), Transparent, None], [None, None, Key(DOWN)]],
+        [
+            [Key(N1), Transparent, Transparent],
+            [Transparent, Transparent, Transparent],
+        ],
+    ];
+
+    fn keys(keyboard: &Keyboard<'_, 2, 3>) -> [u8; REPORT_LEN] {
+        keyboard.report().to_bytes()
+    }
+
+    #[test]
+    fn keys_and_modifiers() {
+        let mut keyboard = Keyboard::new(&LAYERS);
+        assert_eq!(keys(&keyboard), [0; REPORT_LEN]);
+
+        keyboard.press(0, 0);
+        keyboard.press(0, 2);
+        assert_eq!(keys(&keyboard), [0x02, 0, A, 0, 0, 0, 0, 0]);
+
+        keyboard.press(1, 2);
+        assert_eq!(keys(&keyboard), [0x03, 0, A, C, 0, 0, 0, 0]);
+
+        keyboard.release(0, 0);
+        keyboard.release(0, 2);
+        assert_eq!(keys(&keyboard), [0x01, 0, C, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn momentary_layer() {
+        let mut keyboard = Keyboard::new(&LAYERS);
+
+        keyboard.press(1, 0);
+        keyboard.press(0, 0);
+        keyboard.press(0, 1);
+        keyboard.press(1, 2);
+        assert_eq!(keys(&keyboard), [0, 0, UP, B, DOWN, 0, 0, 0]);
+
+        // Keys keep their action until they are released.
+        keyboard.release(1, 0);
+        assert_eq!(keys(&keyboard), [0, 0, UP, B, DOWN, 0, 0, 0]);
+
+        keyboard.release(0, 0);
+        keyboard.press(0, 0);
+        assert_eq!(keys(&keyboard), [0, 0, A, B, DOWN, 0, 0, 0]);
+    }
+
+    #[test]
+    fn toggled_layer() {
+        let mut keyboard = Keyboard::new(&LAYERS);
+
+        keyboard.press(1, 1);
+        keyboard.release(1, 1);
+        assert_eq!(keyboard.active_layers(), 0b101);
+        keyboard.press(0, 0);
+        keyboard.press(0, 1);
+        assert_eq!(keys(&keyboard), [0, 0, N1, B, 0, 0, 0, 0]);
+        keyboard.release(0, 0);
+        keyboard.release(0, 1);
+
+        keyboard.press(1, 1);
+        keyboard.release(1, 1);
+        assert_eq!(keyboard.active_layers(), 0b1);
+        keyboard.press(0, 0);
+        assert_eq!(keys(&keyboard), [0, 0, A, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn rollover() {
+        static LAYERS: [Layer<1, 8>; 1] = [[[
+            Key(A),
+            Key(B),
+            Key(C),
+            Key(D),
+            Key(E),
+            Key(F),
+            Key(G),
+            Key(LEFT_ALT),
+        ]]];
+        let mut keyboard = Keyboard::new(&LAYERS);
+
+        for column in 0..6 {
+            keyboard.press(0, column);
+        }
+        keyboard.press(0, 7);
+        assert_eq!(keyboard.report().to_bytes(), [0x04, 0, A, B, C, D, E, F]);
+
+        keyboard.press(0, 6);
+        assert_eq!(
+            keyboard.report().to_bytes(),
+            [0x04, 0, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01]
+        );
+
+        keyboard.release(0, 0);
+        assert_eq!(keyboard.report().to_bytes(), [0x04, 0, B, C, D, E, F, G]);
+    }
+
+    #[test]
+    fn out_of_range() {
+        let mut keyboard = Keyboard::new(&LAYERS);
+        keyboard.press(2, 0);
+        keyboard.press(0, 3);
+        keyboard.release(5, 5);
+        assert_eq!(keys(&keyboard), [0; REPORT_LEN]);
+    }
+}
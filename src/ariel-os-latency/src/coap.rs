@@ -0,0 +1,163 @@
+//! Serves summaries of histograms as a CoAP resource, so that latencies can be retrieved from
+//! deployed devices.
+//!
+//! A GET request returns a CBOR map with the Content-Format `application/cbor`, with the
+//! [`Summary`] of each histogram by its name, in microseconds, and `null` for histograms without
+//! samples:
+//!
+//! ```text
+//! { "handler": { "count": 42, "min": 120, "p50": 255, "p90": 447, "p99": 991, "max": 1010 } }
+//! ```
+//!
+//! A DELETE request [resets](Histogram::reset) the histograms.
+//!
+//! Applications running their own CoAP server can add the resource to their handler:
+//!
+//! ```ignore
+//! static HISTOGRAMS: [&Histogram; 1] = [&HANDLER_LATENCY];
+//!
+//! let handler = new_dispatcher().at(&["latency"], LatencyResource::new(&HISTOGRAMS));
+//! ```
+
+use coap_message::{
+    Code as _, MinimalWritableMessage, MutableWritableMessage, OptionNumber as _, ReadableMessage,
+};
+use coap_message_utils::{Error as CoAPError, OptionsExt as _};
+use minicbor::{Encoder, encode::write::Cursor};
+
+use crate::{Histogram, Summary};
+
+/// CoAP Content-Format of `application/cbor`.
+const CONTENT_FORMAT_CBOR: u16 = 60;
+
+/// Maximum length of the encoded summaries.
+const MAX_LEN: usize = 512;
+
+/// A CoAP resource that serves the [`Summary`] of histograms.
+#[derive(Debug)]
+pub struct LatencyResource {
+    histograms: &'static [&'static Histogram],
+}
+
+impl LatencyResource {
+    /// Creates the resource, serving the summaries of `histograms`.
+    #[must_use]
+    pub fn new(histograms: &'static [&'static Histogram]) -> Self {
+        Self { histograms }
+    }
+}
+
+/// The operation requested on the resource.
+#[derive(Debug, Clone, Copy)]
+pub enum Request {
+    /// Retrieve the summaries.
+    Get,
+    /// Reset the histograms.
+    Delete,
+}
+
+impl coap_handler::Handler for LatencyResource {
+    type RequestData = Request;
+    type ExtractRequestError = CoAPError;
+    type BuildResponseError<M: MinimalWritableMessage> = CoAPError;
+
+    fn extract_request_data<M: ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, Self::ExtractRequestError> {
+        let request_data = match request.code().into() {
+            coap_numbers::code::GET => Request::Get,
+            coap_numbers::code::DELETE => Request::Delete,
+            _ => return Err(CoAPError::method_not_allowed()),
+        };
+        request.options().ignore_elective_others()?;
+        Ok(request_data)
+    }
+
+    fn estimate_length(&mut self, _request: &Self::RequestData) -> usize {
+        // Content-Format option and payload marker
+        MAX_LEN + 4
+    }
+
+    fn build_response<M: MutableWritableMessage>(
+        &mut self,
+        response: &mut M,
+        request: Self::RequestData,
+    ) -> Result<(), Self::BuildResponseError<M>> {
+        if let Request::Delete = request {
+            for histogram in self.histograms {
+                histogram.reset();
+            }
+            response.set_code(
+                M::Code::new(coap_numbers::code::DELETED).map_err(CoAPError::from_unionerror)?,
+            );
+            return Ok(());
+        }
+
+        let mut buffer = [0; MAX_LEN];
+        let len =
+            encode(self.histograms, &mut buffer).map_err(|_| CoAPError::internal_server_error())?;
+
+        response.set_code(
+            M::Code::new(coap_numbers::code::CONTENT).map_err(CoAPError::from_unionerror)?,
+        );
+        response
+            .add_option_uint(
+                M::OptionNumber::new(coap_numbers::option::CONTENT_FORMAT)
+                    .map_err(CoAPError::from_unionerror)?,
+                CONTENT_FORMAT_CBOR,
+            )
+            .map_err(CoAPError::from_unionerror)?;
+        response
+            .set_payload(
+                buffer
+                    .get(..len)
+                    .ok_or_else(CoAPError::internal_server_error)?,
+            )
+            .map_err(CoAPError::from_unionerror)?;
+        Ok(())
+    }
+}
+
+/// Encodes the summaries of `histograms` into `buffer`, and returns the encoded length.
+///
+/// # Errors
+///
+/// Returns an error if the encoded summaries do not fit into `buffer`.
+fn encode(
+    histograms: &[&Histogram],
+    buffer: &mut [u8],
+) -> Result<usize, minicbor::encode::Error<minicbor::encode::write::EndOfSlice>> {
+    let mut encoder = Encoder::new(Cursor::new(buffer));
+    encoder.map(histograms.len() as u64)?;
+    for histogram in histograms {
+        encoder.str(histogram.name())?;
+        let Some(summary) = histogram.summary() else {
+            encoder.null()?;
+            continue;
+        };
+        let Summary {
+            count,
+            min,
+            p50,
+            p90,
+            p99,
+            max,
+        } = summary;
+        encoder
+            .map(6)?
+            .str("count")?
+            .u32(count)?
+            .str("min")?
+            .u64(min.as_micros())?
+            .str("p50")?
+            .u64(p50.as_micros())?
+            .str("p90")?
+            .u64(p90.as_micros())?
+            .str("p99")?
+            .u64(p99.as_micros())?
+            .str("max")?
+            .u64(max.as_micros())?;
+    }
+    Ok(encoder.into_writer().position())
+}
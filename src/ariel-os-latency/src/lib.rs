@@ -0,0 +1,320 @@
+//! Provides latency measurement with stopwatches feeding histograms.
+//!
+//! A [`Stopwatch`] measures the time taken by an operation, eg. the handling of a CoAP request or
+//! of an interrupt, and records it into a [`Histogram`]. Histograms keep the distribution of the
+//! latencies, from which percentiles can be queried, and summaries logged with
+//! [`Histogram::log_summary()`]:
+//!
+//! ```ignore
+//! use ariel_os::latency::{Histogram, Stopwatch};
+//!
+//! static HANDLER_LATENCY: Histogram = Histogram::new("handler");
+//!
+//! let stopwatch = Stopwatch::start();
+//! handle_request();
+//! stopwatch.stop(&HANDLER_LATENCY);
+//!
+//! HANDLER_LATENCY.log_summary();
+//! ```
+//!
+//! With the `coap` feature, summaries are also served as a CoAP resource, see [`coap`].
+//!
+//! Like HDR histograms, histograms have a fixed relative precision: latencies are counted in
+//! buckets whose width is at most 1/8 of their values (exact values below 16 µs), up to about
+//! 16.8 s, above which they are counted in the last bucket.
+//! Latencies are measured with the system timer, and can thus not be more precise than its ticks.
+//! Recording takes a short critical section, so that histograms can be shared with interrupt
+//! handlers.
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+#[cfg(feature = "coap")]
+pub mod coap;
+
+use core::cell::RefCell;
+
+use embassy_time::{Duration, Instant};
+
+/// Number of bits of the values of a bucket that are kept, which sets the relative precision.
+const SUB_BUCKET_BITS: u32 = 4;
+
+/// Number of buckets of exact values, which are followed by the buckets of each power of two.
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+
+/// Number of bits of the largest latency, in microseconds, that is counted precisely.
+const MAX_BITS: u32 = 24;
+
+/// Number of buckets of a histogram.
+const BUCKETS: usize = SUB_BUCKETS + (MAX_BITS - SUB_BUCKET_BITS) as usize * SUB_BUCKETS / 2;
+
+/// Measures the time elapsed since it was started.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Stopwatch {
+    start: Instant,
+}
+
+impl Stopwatch {
+    /// Starts a stopwatch.
+    #[must_use]
+    pub fn start() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+
+    /// Returns the time elapsed since the stopwatch was started.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Stops the stopwatch, records the elapsed time into `histogram`, and returns it.
+    pub fn stop(self, histogram: &Histogram) -> Duration {
+        let elapsed = self.elapsed();
+        histogram.record(elapsed);
+        elapsed
+    }
+}
+
+/// A histogram of latencies.
+#[derive(Debug)]
+pub struct Histogram {
+    name: &'static str,
+    counts: critical_section::Mutex<RefCell<Counts>>,
+}
+
+impl Histogram {
+    /// Creates an empty histogram, named `name` in summaries.
+    #[must_use]
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            counts: critical_section::Mutex::new(RefCell::new(Counts::new())),
+        }
+    }
+
+    /// Returns the name of the histogram.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Records a `latency`.
+    pub fn record(&self, latency: Duration) {
+        critical_section::with(|cs| self.counts.borrow_ref_mut(cs).record(latency.as_micros()));
+    }
+
+    /// Runs `f`, and records the time it took.
+    pub fn measure<T>(&self, f: impl FnOnce() -> T) -> T {
+        let stopwatch = Stopwatch::start();
+        let output = f();
+        stopwatch.stop(self);
+        output
+    }
+
+    /// Runs `future` to completion, and records the time it took.
+    pub async fn measure_async<F: Future>(&self, future: F) -> F::Output {
+        let stopwatch = Stopwatch::start();
+        let output = future.await;
+        stopwatch.stop(self);
+        output
+    }
+
+    /// Returns the latency below which `percentile` percent of the recorded latencies are.
+    ///
+    /// The latency is rounded up to the precision of the histogram. Returns `None` if no latency
+    /// has been recorded.
+    #[must_use]
+    pub fn percentile(&self, percentile: u8) -> Option<Duration> {
+        critical_section::with(|cs| self.counts.borrow_ref(cs).percentile(percentile))
+            .map(Duration::from_micros)
+    }
+
+    /// Returns a summary of the recorded latencies.
+    ///
+    /// Returns `None` if no latency has been recorded.
+    #[must_use]
+    pub fn summary(&self) -> Option<Summary> {
+        critical_section::with(|cs| self.counts.borrow_ref(cs).summary())
+    }
+
+    /// Logs a summary of the recorded latencies, in microseconds.
+    pub fn log_summary(&self) {
+        if let Some(Summary {
+            count,
+            min,
+            p50,
+            p90,
+            p99,
+            max,
+        }) = self.summary()
+        {
+            // Without a log backend, the fields are not used otherwise.
+            let _ = (count, min, p50, p90, p99, max);
+            ariel_os_debug::log::info!(
+                "{}: {} samples, min {} us, p50 {} us, p90 {} us, p99 {} us, max {} us",
+                self.name,
+                count,
+                min.as_micros(),
+                p50.as_micros(),
+                p90.as_micros(),
+                p99.as_micros(),
+                max.as_micros(),
+            );
+        } else {
+            ariel_os_debug::log::info!("{}: no samples", self.name);
+        }
+    }
+
+    /// Removes all recorded latencies.
+    pub fn reset(&self) {
+        critical_section::with(|cs| *self.counts.borrow_ref_mut(cs) = Counts::new());
+    }
+}
+
+/// A summary of the latencies recorded into a [`Histogram`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Summary {
+    /// The number of recorded latencies.
+    pub count: u32,
+    /// The smallest latency.
+    pub min: Duration,
+    /// The median latency.
+    pub p50: Duration,
+    /// The 90th percentile of the latencies.
+    pub p90: Duration,
+    /// The 99th percentile of the latencies.
+    pub p99: Duration,
+    /// The largest latency.
+    pub max: Duration,
+}
+
+/// The counts of the latencies of a histogram, in microseconds.
+#[derive(Debug)]
+struct Counts {
+    buckets: [u32; BUCKETS],
+    count: u32,
+    min: u64,
+    max: u64,
+}
+
+impl Counts {
+    const fn new() -> Self {
+        Self {
+            buckets: [0; BUCKETS],
+            count: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+
+    fn record(&mut self, value: u64) {
+        if let Some(bucket) = self.buckets.get_mut(bucket_index(value)) {
+            *bucket = bucket.saturating_add(1);
+        }
+        self.count = self.count.saturating_add(1);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    fn percentile(&self, percentile: u8) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = (u64::from(self.count) * u64::from(percentile.min(100))).div_ceil(100);
+        let mut seen = 0;
+        for (index, &count) in self.buckets.iter().enumerate() {
+            seen += u64::from(count);
+            if seen >= rank.max(1) {
+                return Some(bucket_max(index).clamp(self.min, self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    fn summary(&self) -> Option<Summary> {
+        if self.count == 0 {
+            return None;
+        }
+        let percentile = |percentile| self.percentile(percentile).map(Duration::from_micros);
+        Some(Summary {
+            count: self.count,
+            min: Duration::from_micros(self.min),
+            p50: percentile(50)?,
+            p90: percentile(90)?,
+            p99: percentile(99)?,
+            max: Duration::from_micros(self.max),
+        })
+    }
+}
+
+/// Returns the index of the bucket counting `value`.
+fn bucket_index(value: u64) -> usize {
+    let value = value.min((1 << MAX_BITS) - 1);
+    let sub_buckets = SUB_BUCKETS as u64;
+    let index = if value < sub_buckets {
+        value
+    } else {
+        // Keep the `SUB_BUCKET_BITS` most significant bits of the value, the first of which is
+        // set.
+        let shift = value.ilog2() + 1 - SUB_BUCKET_BITS;
+        let sub_bucket = (value >> shift) - sub_buckets / 2;
+        sub_buckets + u64::from(shift - 1) * sub_buckets / 2 + sub_bucket
+    };
+    // Cannot fail, the index is smaller than the number of buckets.
+    usize::try_from(index).unwrap_or(BUCKETS - 1)
+}
+
+/// Returns the largest value counted in the bucket at `index`.
+fn bucket_max(index: usize) -> u64 {
+    let Some(index) = index.checked_sub(SUB_BUCKETS) else {
+        return index as u64;
+    };
+    let shift = index / (SUB_BUCKETS / 2) + 1;
+    let sub_bucket = (index % (SUB_BUCKETS / 2) + SUB_BUCKETS / 2) as u64;
+    ((sub_bucket + 1) << shift) - 1
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets() {
+        for value in 0..1 << MAX_BITS {
+            let index = bucket_index(value);
+            assert!(index < BUCKETS);
+            assert!(bucket_max(index) >= value);
+            // At most 1/8 of the value wide.
+            assert!(bucket_max(index) - value <= value / 8);
+            assert!(index == 0 || bucket_max(index - 1) < value);
+        }
+        assert_eq!(bucket_index(u64::MAX), BUCKETS - 1);
+    }
+
+    #[test]
+    fn percentiles() {
+        let mut counts = Counts::new();
+        assert_eq!(counts.percentile(50), None);
+        assert_eq!(counts.summary(), None);
+
+        for value in 1..=100 {
+            counts.record(value * 10);
+        }
+        assert_eq!(counts.percentile(0), Some(10));
+        // 500 is counted in the bucket from 480 to 511.
+        assert_eq!(counts.percentile(50), Some(511));
+        assert_eq!(counts.percentile(100), Some(1000));
+
+        let summary = counts.summary().unwrap();
+        assert_eq!(summary.count, 100);
+        assert_eq!(summary.min, Duration::from_micros(10));
+        assert_eq!(summary.p99, Duration::from_micros(1000));
+        assert_eq!(summary.max, Duration::from_micros(1000));
+    }
+}
@@ -0,0 +1,119 @@
+/// Registers a non-async function to be run during system initialization.
+///
+/// Registered functions are run in order of increasing priority, once the drivers of the system
+/// are initialized and before tasks are started. This allows drivers and subsystems to set
+/// themselves up without the application having to call them.
+/// Functions with the same priority are run in an unspecified order.
+///
+/// # Parameters
+///
+/// - `priority`: (*optional*) the priority of the function, from `0` to `255`; functions with a
+///   lower priority are run first (defaults to `128`).
+/// - `peripherals`: (*optional*) provide the function with a peripheral struct as parameter.
+///   The peripheral struct must be defined with the `ariel_os::hal::define_peripherals!` macro.
+///
+/// # Examples
+///
+/// ```ignore
+/// #[ariel_os::init(priority = 10, peripherals)]
+/// fn init_display(peripherals: /* your peripheral type */) {}
+/// ```
+///
+/// # Panics
+///
+/// This macro panics when the `ariel-os` crate cannot be found as a dependency of the crate where
+/// this macro is used.
+#[proc_macro_attribute]
+pub fn init(args: TokenStream, item: TokenStream) -> TokenStream {
+    use quote::{format_ident, quote};
+
+    #[allow(clippy::wildcard_imports)]
+    use init::*;
+
+    let mut attrs = Attributes::default();
+    let init_attr_parser = syn::meta::parser(|meta| attrs.parse(&meta));
+    syn::parse_macro_input!(args with init_attr_parser);
+
+    let init_function = syn::parse_macro_input!(item as syn::ItemFn);
+    let init_function_name = &init_function.sig.ident;
+    let is_async = init_function.sig.asyncness.is_some();
+
+    assert!(
+        !is_async,
+        "init functions cannot be async, consider using `task` instead",
+    );
+
+    if !attrs.peripherals {
+        let param_count = init_function.sig.inputs.len();
+        assert!(
+            param_count == 0,
+            "to provide this function with peripherals, use the `{PERIPHERALS_PARAM}` macro parameter",
+        );
+    }
+
+    let ariel_os_crate = utils::ariel_os_crate_or_internal(Some("ariel-os-embassy"));
+
+    let new_function_name = format_ident!("__init_{init_function_name}");
+    let hook_name = format_ident!("__INIT_HOOK_{init_function_name}");
+
+    let priority = attrs.priority.unwrap_or_else(|| syn::parse_quote! { 128 });
+
+    let peripheral_param = if attrs.peripherals {
+        quote! {peripherals.take_peripherals()}
+    } else {
+        quote! {}
+    };
+
+    let expanded = quote! {
+        #[allow(non_snake_case)]
+        fn #new_function_name(mut peripherals: &mut #ariel_os_crate::hal::OptionalPeripherals) {
+            use #ariel_os_crate::hal::TakePeripherals;
+            #init_function_name(#peripheral_param);
+        }
+
+        #[allow(non_upper_case_globals)]
+        #[#ariel_os_crate::reexports::linkme::distributed_slice(#ariel_os_crate::INIT_HOOKS)]
+        #[linkme(crate = #ariel_os_crate::reexports::linkme)]
+        static #hook_name: #ariel_os_crate::InitHook = #ariel_os_crate::InitHook {
+            priority: #priority,
+            func: #new_function_name,
+        };
+
+        #init_function
+    };
+
+    TokenStream::from(expanded)
+}
+
+// Define these types in a module to avoid polluting the crate's namespace, as this file is
+// `included!` in the crate's root.
+mod init {
+    pub const PRIORITY_PARAM: &str = "priority";
+    pub const PERIPHERALS_PARAM: &str = "peripherals";
+
+    #[derive(Debug, Default)]
+    pub struct Attributes {
+        pub priority: Option<syn::Expr>,
+        pub peripherals: bool,
+    }
+
+    impl Attributes {
+        #[allow(clippy::missing_errors_doc)]
+        pub fn parse(&mut self, attr: &syn::meta::ParseNestedMeta) -> syn::Result<()> {
+            if attr.path.is_ident(PRIORITY_PARAM) {
+                let value = attr.value()?;
+                self.priority = Some(value.parse()?);
+                return Ok(());
+            }
+
+            if attr.path.is_ident(PERIPHERALS_PARAM) {
+                self.peripherals = true;
+                return Ok(());
+            }
+
+            Err(attr.error(format!(
+                "unsupported parameter (`{PRIORITY_PARAM}` and `{PERIPHERALS_PARAM}` are supported)"
+            )))
+        }
+    }
+}
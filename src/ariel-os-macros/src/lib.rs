@@ -3,6 +3,10 @@ mod utils;
 use proc_macro::TokenStream;
 
 include!("config.rs");
+include!("init.rs");
+include!("record.rs");
 include!("spawner.rs");
 include!("task.rs");
+include!("test.rs");
 include!("thread.rs");
+include!("xip.rs");
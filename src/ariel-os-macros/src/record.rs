@@ -0,0 +1,186 @@
+/// Derives the `Record` trait for a struct, whose instances can then be stored in a table of the
+/// storage.
+///
+/// The struct must have named fields, and implement `Serialize` and `Deserialize`.
+///
+/// # Parameters
+///
+/// - `table`: the name of the table, under which it is stored, as a struct attribute.
+/// - `key`: marks the field of type `u32` that identifies a record, as a field attribute.
+/// - `index`: (*optional*) marks the field by whose value records can be found, as a field
+///   attribute.
+///
+/// The schema of the table is a hash of the names and types of the fields: changing them makes
+/// the table unreadable until it is cleared.
+///
+/// # Examples
+///
+/// ```ignore
+/// use ariel_os::storage::Record;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize, Record)]
+/// #[record(table = "peers")]
+/// struct Peer {
+///     #[record(key)]
+///     id: u32,
+///     #[record(index)]
+///     kid: [u8; 4],
+///     sequence_number: u64,
+/// }
+/// ```
+///
+/// # Panics
+///
+/// This macro panics when the `ariel-os` crate cannot be found as a dependency of the crate where
+/// this macro is used, or when a parameter is missing or misplaced.
+#[proc_macro_derive(Record, attributes(record))]
+pub fn derive_record(item: TokenStream) -> TokenStream {
+    #[allow(clippy::wildcard_imports)]
+    use record::*;
+
+    use quote::quote;
+
+    use crate::utils::find_crate;
+
+    let record_struct = syn::parse_macro_input!(item as syn::DeriveInput);
+    let struct_name = &record_struct.ident;
+
+    assert!(
+        record_struct.generics.params.is_empty(),
+        "records cannot be generic"
+    );
+    let syn::Data::Struct(syn::DataStruct {
+        fields: syn::Fields::Named(fields),
+        ..
+    }) = &record_struct.data
+    else {
+        panic!("records must be structs with named fields");
+    };
+
+    let mut struct_attrs = StructAttributes::default();
+    for attr in record_struct
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident(ATTR))
+    {
+        attr.parse_nested_meta(|meta| struct_attrs.parse(&meta))
+            .unwrap_or_else(|err| panic!("{err}"));
+    }
+    let Some(table) = struct_attrs.table else {
+        panic!("the `{TABLE_PARAM}` parameter must be provided");
+    };
+
+    let mut key_field = None;
+    let mut index_field = None;
+    for field in &fields.named {
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident(ATTR)) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident(KEY_PARAM) {
+                    assert!(key_field.is_none(), "only one field can be the key");
+                    key_field = Some(field);
+                    Ok(())
+                } else if meta.path.is_ident(INDEX_PARAM) {
+                    assert!(index_field.is_none(), "only one field can be indexed");
+                    index_field = Some(field);
+                    Ok(())
+                } else {
+                    Err(meta.error(format!(
+                        "unsupported parameter, expected `{KEY_PARAM}` or `{INDEX_PARAM}`"
+                    )))
+                }
+            })
+            .unwrap_or_else(|err| panic!("{err}"));
+        }
+    }
+    let Some(key_field) = key_field else {
+        panic!("a field must be marked with `#[{ATTR}({KEY_PARAM})]`");
+    };
+    let key_name = &key_field.ident;
+
+    let (index_type, index_expr) = if let Some(index_field) = index_field {
+        let index_type = &index_field.ty;
+        let index_name = &index_field.ident;
+        (quote! {#index_type}, quote! {&self.#index_name})
+    } else {
+        (quote! {()}, quote! {&()})
+    };
+
+    let schema = schema_hash(fields);
+
+    let storage_crate = match (find_crate("ariel-os"), find_crate("ariel-os-storage")) {
+        (Some(ariel_os), _) => quote! { #ariel_os::storage },
+        (None, Some(ariel_os_storage)) => quote! { #ariel_os_storage },
+        _ => panic!(r#"neither "ariel-os" nor "ariel-os-storage" found in dependencies!"#),
+    };
+
+    let expanded = quote! {
+        impl #storage_crate::Record for #struct_name {
+            const TABLE: &'static str = #table;
+            const SCHEMA: u32 = #schema;
+
+            type Index = #index_type;
+
+            fn key(&self) -> u32 {
+                self.#key_name
+            }
+
+            fn index(&self) -> &Self::Index {
+                #index_expr
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+// Define these types in a module to avoid polluting the crate's namespace, as this file is
+// `included!` in the crate's root.
+mod record {
+    pub const ATTR: &str = "record";
+    pub const TABLE_PARAM: &str = "table";
+    pub const KEY_PARAM: &str = "key";
+    pub const INDEX_PARAM: &str = "index";
+
+    #[derive(Default)]
+    pub struct StructAttributes {
+        pub table: Option<syn::LitStr>,
+    }
+
+    impl StructAttributes {
+        /// Parses the attributes of the struct.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error when an unsupported parameter is found.
+        pub fn parse(&mut self, meta: &syn::meta::ParseNestedMeta) -> syn::Result<()> {
+            if meta.path.is_ident(TABLE_PARAM) {
+                self.table = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error(format!("unsupported parameter, expected `{TABLE_PARAM}`")))
+            }
+        }
+    }
+
+    /// Returns the FNV-1a hash of the names and types of `fields`.
+    pub fn schema_hash(fields: &syn::FieldsNamed) -> u32 {
+        use quote::ToTokens;
+
+        let schema = fields
+            .named
+            .iter()
+            .map(|field| {
+                format!(
+                    "{}:{}",
+                    field.ident.to_token_stream(),
+                    field.ty.to_token_stream()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(";");
+        schema.bytes().fold(0x811c_9dc5, |hash, byte| {
+            (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+        })
+    }
+}
@@ -17,9 +17,15 @@
 ///         - `usb_builder_hook`: when present, the macro will define a static `USB_BUILDER_HOOK`
 ///           of type `UsbBuilderHook`, allowing to access and modify the system-provided
 ///           `embassy_usb::Builder` through `Delegate::with()`, *before* it is built by the system.
+///     - `args`: (*optional*) provide the function with the values of the given expressions,
+///       evaluated when the task is spawned, as parameters following the peripheral struct, eg.
+///       `args = (CONFIG_RATE, 3)`.
+///     - `spawn_if`: (*optional*) only spawn the task if the given `bool` expression, evaluated at
+///       startup, is `true`, eg. `spawn_if = ariel_os::config::bool_from_env_or!(...)`.
 /// - `pool_size`: (*optional*) set the maximum number of concurrent tasks that can be spawned for
-///   the function (defaults to `1`).
-///   Cannot be used on `autostart` tasks.
+///   the function (defaults to `1`).
+///   On `autostart` tasks, as many tasks are spawned at startup; `peripherals` cannot be used
+///   then.
 ///
 /// # Examples
 ///
@@ -28,6 +34,13 @@
 /// async fn task(peripherals: /* your peripheral type */) {}
 /// ```
 ///
+/// This spawns two tasks at startup, each provided with a reference to `QUEUE`:
+///
+/// ```ignore
+/// #[ariel_os::task(autostart, pool_size = 2, args = (&QUEUE))]
+/// async fn worker(queue: &'static Queue) {}
+/// ```
+///
 /// See Ariel OS examples for more.
 ///
 /// # Panics
@@ -53,18 +66,34 @@ pub fn task(args: TokenStream, item: TokenStream) -> TokenStream {
 
     if attrs.autostart {
         assert!(
-            attrs.pool_size.is_none(),
-            "pool size cannot be set on an `{AUTOSTART_PARAM}` task",
+            attrs.pool_size.is_none() || !attrs.peripherals,
+            "pool size cannot be set on a task receiving `{PERIPHERALS_PARAM}`",
         );
 
-        if !attrs.peripherals {
-            let param_count = task_function.sig.inputs.len();
+        let param_count = task_function.sig.inputs.len();
+        if !attrs.peripherals && attrs.args.is_empty() {
             assert!(
                 param_count == 0,
                 "to provide this function with peripherals, use the `{PERIPHERALS_PARAM}` macro parameter",
             );
+        } else {
+            let expected_param_count = usize::from(attrs.peripherals) + attrs.args.len();
+            assert!(
+                param_count == expected_param_count,
+                "the function must take {expected_param_count} parameter(s): the peripheral struct if `{PERIPHERALS_PARAM}` is used, followed by the `{ARGS_PARAM}`",
+            );
         }
     } else {
+        assert!(
+            attrs.args.is_empty(),
+            "the task must be `{AUTOSTART_PARAM}` to receive `{ARGS_PARAM}`"
+        );
+
+        assert!(
+            attrs.spawn_if.is_none(),
+            "the task must be `{AUTOSTART_PARAM}` to use `{SPAWN_IF_PARAM}`"
+        );
+
         assert!(
             !attrs.peripherals,
             "the task must be `{AUTOSTART_PARAM}` to receive peripherals"
@@ -81,11 +110,11 @@ pub fn task(args: TokenStream, item: TokenStream) -> TokenStream {
     let ariel_os_crate = utils::ariel_os_crate_or_internal(Some("ariel-os-embassy"));
 
     let expanded = if attrs.autostart {
-        let peripheral_param = if attrs.peripherals {
-            quote! {peripherals.take_peripherals()}
-        } else {
-            quote! {}
-        };
+        let spawn = task::generate_spawn(task_function_name, &attrs);
+        let pool_size = attrs
+            .pool_size
+            .as_ref()
+            .map_or_else(|| quote! { 1 }, |pool_size| quote! { #pool_size });
 
         let hooks = Hook::hook_definitions();
         let delegates = task::generate_delegates(&ariel_os_crate, &hooks, &attrs);
@@ -103,11 +132,10 @@ pub fn task(args: TokenStream, item: TokenStream) -> TokenStream {
                 mut peripherals: &mut #ariel_os_crate::hal::OptionalPeripherals,
             ) {
                 use #ariel_os_crate::hal::TakePeripherals;
-                let task = #task_function_name(#peripheral_param);
-                spawner.spawn(task).unwrap();
+                #spawn
             }
 
-            #[#ariel_os_crate::reexports::embassy_executor::task(embassy_executor = #ariel_os_crate::reexports::embassy_executor)]
+            #[#ariel_os_crate::reexports::embassy_executor::task(pool_size = #pool_size, embassy_executor = #ariel_os_crate::reexports::embassy_executor)]
             #task_function
         }
     } else {
@@ -128,12 +156,16 @@ mod task {
     pub const AUTOSTART_PARAM: &str = "autostart";
     pub const PERIPHERALS_PARAM: &str = "peripherals";
     pub const POOL_SIZE_PARAM: &str = "pool_size";
+    pub const ARGS_PARAM: &str = "args";
+    pub const SPAWN_IF_PARAM: &str = "spawn_if";
 
     #[derive(Debug, Default)]
     pub struct Attributes {
         pub autostart: bool,
         pub peripherals: bool,
         pub pool_size: Option<syn::Expr>,
+        pub args: Vec<syn::Expr>,
+        pub spawn_if: Option<syn::Expr>,
         pub hooks: Vec<Hook>,
     }
 
@@ -156,6 +188,22 @@ mod task {
                 return Ok(());
             }
 
+            if attr.path.is_ident(ARGS_PARAM) {
+                // A parenthesized expression is a single argument, a tuple lists several.
+                self.args = match attr.value()?.parse()? {
+                    syn::Expr::Tuple(tuple) => tuple.elems.into_iter().collect(),
+                    syn::Expr::Paren(paren) => vec![*paren.expr],
+                    arg => vec![arg],
+                };
+                return Ok(());
+            }
+
+            if attr.path.is_ident(SPAWN_IF_PARAM) {
+                let value = attr.value()?;
+                self.spawn_if = Some(value.parse()?);
+                return Ok(());
+            }
+
             // The order in which hooks are passed to the macro is enforced here
             for HookDefinition { kind, .. } in Hook::hook_definitions() {
                 if attr.path.is_ident(kind.param_name()) {
@@ -166,11 +214,49 @@ mod task {
 
             let supported_hooks = Hook::format_list();
             Err(attr.error(format!(
-                "unsupported parameter (`{AUTOSTART_PARAM}`, `{PERIPHERALS_PARAM}`, `{POOL_SIZE_PARAM}`, and hooks {supported_hooks} are supported)"
+                "unsupported parameter (`{AUTOSTART_PARAM}`, `{PERIPHERALS_PARAM}`, `{ARGS_PARAM}`, `{SPAWN_IF_PARAM}`, `{POOL_SIZE_PARAM}`, and hooks {supported_hooks} are supported)"
             )))
         }
     }
 
+    /// Generates the spawning of the task at startup.
+    pub fn generate_spawn(
+        task_function_name: &syn::Ident,
+        attrs: &Attributes,
+    ) -> proc_macro2::TokenStream {
+        use quote::quote;
+
+        let peripheral_param = attrs
+            .peripherals
+            .then(|| quote! {peripherals.take_peripherals()});
+        let params = peripheral_param
+            .into_iter()
+            .chain(attrs.args.iter().map(|arg| quote! {#arg}));
+
+        let spawn = quote! {
+            let task = #task_function_name(#(#params),*);
+            spawner.spawn(task).unwrap();
+        };
+        let spawn = if let Some(pool_size) = &attrs.pool_size {
+            quote! {
+                for _ in 0..#pool_size {
+                    #spawn
+                }
+            }
+        } else {
+            spawn
+        };
+        if let Some(spawn_if) = &attrs.spawn_if {
+            quote! {
+                if #spawn_if {
+                    #spawn
+                }
+            }
+        } else {
+            spawn
+        }
+    }
+
     #[derive(Debug, PartialEq, Eq, Hash, enum_iterator::Sequence)]
     pub enum Hook {
         UsbBuilder,
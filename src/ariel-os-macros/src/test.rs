@@ -0,0 +1,151 @@
+/// Registers a function as a test, run on the target by the test runner of Ariel OS.
+///
+/// When the `test-runner` laze module is selected, registered tests are run one after the
+/// other once the system is initialized, and their results are reported on the debug output.
+/// A test passes if it returns, and fails if it panics, which ends the test run.
+/// The test runner then terminates the debug session, with an exit code reporting the result of
+/// the test run.
+///
+/// The function can be async or not.
+///
+/// # Parameters
+///
+/// - `peripherals`: (*optional*) provide the function with a peripheral struct as parameter.
+///   The peripheral struct must be defined with the `ariel_os::hal::define_peripherals!` macro.
+///   Each test is provided with the peripherals it requests, even when previous tests used them;
+///   the drivers built from them must thus be dropped before the test returns.
+/// - `ignore`: (*optional*) do not run the test.
+///
+/// # Examples
+///
+/// ```ignore
+/// #[ariel_os::test(peripherals)]
+/// async fn led_turns_on(peripherals: /* your peripheral type */) {}
+/// ```
+///
+/// # Panics
+///
+/// This macro panics when the `ariel-os` crate cannot be found as a dependency of the crate where
+/// this macro is used.
+#[proc_macro_attribute]
+pub fn test(args: TokenStream, item: TokenStream) -> TokenStream {
+    use quote::{format_ident, quote};
+
+    #[allow(clippy::wildcard_imports)]
+    use test::*;
+
+    let mut attrs = Attributes::default();
+    let test_attr_parser = syn::meta::parser(|meta| attrs.parse(&meta));
+    syn::parse_macro_input!(args with test_attr_parser);
+
+    let test_function = syn::parse_macro_input!(item as syn::ItemFn);
+    let test_function_name = &test_function.sig.ident;
+    let is_async = test_function.sig.asyncness.is_some();
+
+    let param_count = test_function.sig.inputs.len();
+    let peripheral_type = if attrs.peripherals {
+        let Some(syn::FnArg::Typed(peripheral_param)) = test_function.sig.inputs.first() else {
+            panic!("the function must take the peripheral struct as parameter");
+        };
+        assert!(
+            param_count == 1,
+            "the function must only take the peripheral struct as parameter"
+        );
+        Some(&peripheral_param.ty)
+    } else {
+        assert!(
+            param_count == 0,
+            "to provide this function with peripherals, use the `{PERIPHERALS_PARAM}` macro parameter",
+        );
+        None
+    };
+
+    let ariel_os_crate = utils::ariel_os_crate_or_internal(Some("ariel-os-embassy"));
+
+    let spawn_function_name = format_ident!("__spawn_test_{test_function_name}");
+    let task_function_name = format_ident!("__test_task_{test_function_name}");
+    let test_case_name = format_ident!("__TEST_CASE_{test_function_name}");
+
+    let ignore = attrs.ignore;
+
+    let (task_param, task_arg, lent_peripherals) = if let Some(peripheral_type) = peripheral_type {
+        (
+            quote! {peripherals: #peripheral_type},
+            quote! {peripherals},
+            // SAFETY: tests are run one at a time, and drop the drivers built from the
+            // peripherals before returning.
+            quote! {unsafe { peripherals.lend_peripherals() }},
+        )
+    } else {
+        (quote! {}, quote! {}, quote! {})
+    };
+
+    let call = if is_async {
+        quote! {#test_function_name(#task_arg).await}
+    } else {
+        quote! {#test_function_name(#task_arg)}
+    };
+
+    let expanded = quote! {
+        #[allow(non_snake_case)]
+        fn #spawn_function_name(
+            spawner: #ariel_os_crate::asynch::Spawner,
+            mut peripherals: &mut #ariel_os_crate::hal::OptionalPeripherals,
+        ) {
+            use #ariel_os_crate::hal::LendPeripherals;
+            spawner.spawn(#task_function_name(#lent_peripherals)).unwrap();
+        }
+
+        #[allow(non_snake_case)]
+        #[#ariel_os_crate::reexports::embassy_executor::task(embassy_executor = #ariel_os_crate::reexports::embassy_executor)]
+        async fn #task_function_name(#task_param) {
+            #call;
+            #ariel_os_crate::TEST_DONE.signal(());
+        }
+
+        #[allow(non_upper_case_globals)]
+        #[#ariel_os_crate::reexports::linkme::distributed_slice(#ariel_os_crate::TESTS)]
+        #[linkme(crate = #ariel_os_crate::reexports::linkme)]
+        static #test_case_name: #ariel_os_crate::TestCase = #ariel_os_crate::TestCase {
+            name: concat!(module_path!(), "::", stringify!(#test_function_name)),
+            ignore: #ignore,
+            spawn: #spawn_function_name,
+        };
+
+        #test_function
+    };
+
+    TokenStream::from(expanded)
+}
+
+// Define these types in a module to avoid polluting the crate's namespace, as this file is
+// `included!` in the crate's root.
+mod test {
+    pub const PERIPHERALS_PARAM: &str = "peripherals";
+    pub const IGNORE_PARAM: &str = "ignore";
+
+    #[derive(Debug, Default)]
+    pub struct Attributes {
+        pub peripherals: bool,
+        pub ignore: bool,
+    }
+
+    impl Attributes {
+        #[allow(clippy::missing_errors_doc)]
+        pub fn parse(&mut self, attr: &syn::meta::ParseNestedMeta) -> syn::Result<()> {
+            if attr.path.is_ident(PERIPHERALS_PARAM) {
+                self.peripherals = true;
+                return Ok(());
+            }
+
+            if attr.path.is_ident(IGNORE_PARAM) {
+                self.ignore = true;
+                return Ok(());
+            }
+
+            Err(attr.error(format!(
+                "unsupported parameter (`{PERIPHERALS_PARAM}` and `{IGNORE_PARAM}` are supported)"
+            )))
+        }
+    }
+}
@@ -5,7 +5,8 @@
 /// # Parameters
 ///
 /// - `autostart`: (*mandatory*) autostart the thread.
-/// - `stacksize`: (*optional*) the size of the stack allocated to the thread (in bytes).
+/// - `stacksize`: (*optional*) the size of the stack allocated to the thread (in bytes); defaults
+///   to `CONFIG_THREAD_STACKSIZE_DEFAULT`, or 2048 bytes.
 /// - `priority`: (*optional*) the thread's priority.
 /// - `no_wait`: (*optional*) don't wait for system initialization to be finished
 ///   before starting the thread.
@@ -71,12 +72,13 @@ pub fn thread(args: TokenStream, item: TokenStream) -> TokenStream {
 
     let fn_name = thread_function.sig.ident.clone();
     let trampoline_function_name = format_ident!("__{fn_name}_trampoline");
+    let thread_name = fn_name.to_string();
 
     let Parameters {
         stack_size,
         priority,
         affinity,
-    } = Parameters::from(attrs);
+    } = Parameters::new(attrs, &thread_crate);
 
     let expanded = quote! {
         #[inline(always)]
@@ -88,7 +90,7 @@ pub fn thread(args: TokenStream, item: TokenStream) -> TokenStream {
             #fn_name()
         }
 
-        #thread_crate::autostart_thread!(#trampoline_function_name, stacksize = #stack_size, priority = #priority, affinity = #affinity);
+        #thread_crate::autostart_thread!(#trampoline_function_name, name = #thread_name, stacksize = #stack_size, priority = #priority, affinity = #affinity);
     };
 
     TokenStream::from(expanded)
@@ -101,27 +103,19 @@ mod thread {
         pub affinity: syn::Expr,
     }
 
-    impl Default for Parameters {
-        fn default() -> Self {
-            // TODO: proper values
-            Self {
-                stack_size: syn::parse_quote! { 2048 },
-                priority: syn::parse_quote! { 1 },
-                affinity: syn::parse_quote! { None },
-            }
-        }
-    }
-
-    impl From<Attributes> for Parameters {
-        fn from(attrs: Attributes) -> Self {
-            let default = Self::default();
-
-            let stack_size = attrs.stack_size.unwrap_or(default.stack_size);
-            let priority = attrs.priority.unwrap_or(default.priority);
-            let affinity = attrs
-                .affinity
-                .map(|expr| syn::parse_quote! { Some(#expr) })
-                .unwrap_or(default.affinity);
+    impl Parameters {
+        /// Returns the parameters given by `attrs`, with defaults for the missing ones.
+        ///
+        /// The default stack size is configured in `thread_crate`.
+        pub fn new(attrs: Attributes, thread_crate: &proc_macro2::TokenStream) -> Self {
+            let stack_size = attrs
+                .stack_size
+                .unwrap_or_else(|| syn::parse_quote! { #thread_crate::THREAD_STACKSIZE_DEFAULT });
+            let priority = attrs.priority.unwrap_or_else(|| syn::parse_quote! { 1 });
+            let affinity = attrs.affinity.map_or_else(
+                || syn::parse_quote! { None },
+                |expr| syn::parse_quote! { Some(#expr) },
+            );
 
             Self {
                 stack_size,
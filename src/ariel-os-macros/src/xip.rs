@@ -0,0 +1,68 @@
+/// Places a function or a `static` in the external flash, from which it is executed in place.
+///
+/// This relieves the internal flash of rarely-used code and data, eg. setup, diagnostics or
+/// large lookup tables, at the cost of slower execution.
+/// Functions are never inlined into their callers, so that their code stays in the external
+/// flash.
+///
+/// **Important**: items are only placed in the external flash when the `xip` laze module is
+/// selected; they stay in the internal flash otherwise.
+/// They must only be used once the system has mapped the external flash, which it does before
+/// running the [`macro@init`] functions and starting the tasks; threads may start earlier.
+/// Statics in the external flash are read-only, and thus cannot have interior mutability.
+///
+/// # Examples
+///
+/// ```ignore
+/// #[ariel_os::xip]
+/// fn self_test() -> bool {
+///     // ...
+/// }
+///
+/// #[ariel_os::xip]
+/// static GAMMA_TABLE: [u16; 1024] = [/* ... */];
+/// ```
+///
+/// # Panics
+///
+/// This macro panics when the `ariel-os` crate cannot be found as a dependency of the crate where
+/// this macro is used.
+#[proc_macro_attribute]
+pub fn xip(args: TokenStream, item: TokenStream) -> TokenStream {
+    use quote::quote;
+
+    assert!(args.is_empty(), "this macro does not take arguments");
+
+    let item = syn::parse_macro_input!(item as syn::Item);
+
+    let (section, item) = match item {
+        syn::Item::Fn(function) => {
+            assert!(
+                !function
+                    .attrs
+                    .iter()
+                    .any(|attr| attr.path().is_ident("inline")),
+                "functions executed in place cannot be inlined",
+            );
+            let section = format!(".xip.text.{}", function.sig.ident);
+            (section, quote! { #[inline(never)] #function })
+        }
+        syn::Item::Static(static_) => {
+            assert!(
+                matches!(static_.mutability, syn::StaticMutability::None),
+                "statics in the external flash cannot be mutable",
+            );
+            let section = format!(".xip.rodata.{}", static_.ident);
+            (section, quote! { #static_ })
+        }
+        _ => panic!("only functions and statics can be executed in place"),
+    };
+
+    let ariel_os_crate = utils::ariel_os_crate();
+
+    let expanded = quote! {
+        #ariel_os_crate::rt::xip_section!(#section, #item);
+    };
+
+    TokenStream::from(expanded)
+}
@@ -0,0 +1,5 @@
+#![no_main]
+
+// FAIL: init functions cannot be async
+#[ariel_os::init]
+async fn init() {}
@@ -0,0 +1,7 @@
+#![no_main]
+
+// FAIL: the `peripherals` parameter is required in this case
+#[ariel_os::init(priority = 10)]
+fn init(_peripherals: Peripherals) {}
+
+struct Peripherals;
@@ -0,0 +1,8 @@
+#![no_main]
+
+// FAIL: a field must be marked as the key
+#[derive(ariel_os_macros::Record)]
+#[record(table = "peers")]
+struct Peer {
+    id: u32,
+}
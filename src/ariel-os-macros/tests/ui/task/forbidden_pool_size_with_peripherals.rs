@@ -0,0 +1,7 @@
+#![no_main]
+
+// FAIL: the `pool_size` parameter cannot be used on a task receiving peripherals
+#[ariel_os::task(autostart, peripherals, pool_size = 4)]
+async fn main(_foo: Bar) {}
+
+struct Bar;
@@ -0,0 +1,5 @@
+#![no_main]
+
+// FAIL: the function must take as many parameters as there are arguments
+#[ariel_os::task(autostart, args = (1, 2))]
+async fn main(_foo: u32) {}
@@ -0,0 +1,5 @@
+#![no_main]
+
+// FAIL: the `autostart` parameter must be present when providing arguments
+#[ariel_os::task(args = (1))]
+async fn main(_foo: u32) {}
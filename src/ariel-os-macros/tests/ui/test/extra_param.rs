@@ -0,0 +1,7 @@
+#![no_main]
+
+// FAIL: tests can only take the peripheral struct as parameter
+#[ariel_os::test(peripherals)]
+async fn test(_peripherals: Peripherals, _value: u32) {}
+
+struct Peripherals;
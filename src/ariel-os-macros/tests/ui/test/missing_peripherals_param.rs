@@ -0,0 +1,7 @@
+#![no_main]
+
+// FAIL: the `peripherals` parameter is required in this case
+#[ariel_os::test]
+async fn test(_peripherals: Peripherals) {}
+
+struct Peripherals;
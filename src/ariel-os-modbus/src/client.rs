@@ -0,0 +1,497 @@
+//! Provides Modbus clients.
+
+use crate::{
+    COIL_OFF, COIL_ON, Error, Exception, MAX_PDU_LEN, MAX_READ_BITS, MAX_READ_REGISTERS,
+    MAX_WRITE_BITS, MAX_WRITE_REGISTERS, PduWriter, function, packed_len, unpack_bits,
+    unpack_registers,
+};
+
+/// A transport of Modbus requests and responses, eg. [`RtuTransport`] or [`TcpTransport`].
+///
+/// [`RtuTransport`]: crate::rtu::RtuTransport
+/// [`TcpTransport`]: crate::tcp::TcpTransport
+pub trait Transport {
+    /// Error of the byte stream of the transport.
+    type Error;
+
+    /// Sends the `request` PDU to the server of address `unit`, and receives the response PDU
+    /// into `response`.
+    ///
+    /// Returns the length of the response, which is zero for requests that are broadcast and thus
+    /// not answered.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if sending the request or receiving the response fails.
+    fn transact(
+        &mut self,
+        unit: u8,
+        request: &[u8],
+        response: &mut [u8],
+    ) -> impl Future<Output = Result<usize, Error<Self::Error>>>;
+}
+
+/// A Modbus client (master), which sends requests to servers through the transport `T`.
+///
+/// Requests are addressed to the server of address `unit`; on serial lines, requests to unit 0
+/// are broadcast to all servers, and only writing is possible.
+pub struct Client<T> {
+    transport: T,
+}
+
+impl<T: Transport> Client<T> {
+    /// Creates a client sending requests through `transport`.
+    #[must_use]
+    pub const fn new(transport: T) -> Self {
+        Self { transport }
+    }
+
+    /// Returns the transport of the client.
+    pub fn release(self) -> T {
+        self.transport
+    }
+
+    /// Reads the coils starting at `address` into `coils`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidQuantity`] if `coils` is empty or longer than 2000, and an error if
+    /// the request fails.
+    pub async fn read_coils(
+        &mut self,
+        unit: u8,
+        address: u16,
+        coils: &mut [bool],
+    ) -> Result<(), Error<T::Error>> {
+        self.read_bits(unit, function::READ_COILS, address, coils)
+            .await
+    }
+
+    /// Reads the discrete inputs starting at `address` into `inputs`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidQuantity`] if `inputs` is empty or longer than 2000, and an error if
+    /// the request fails.
+    pub async fn read_discrete_inputs(
+        &mut self,
+        unit: u8,
+        address: u16,
+        inputs: &mut [bool],
+    ) -> Result<(), Error<T::Error>> {
+        self.read_bits(unit, function::READ_DISCRETE_INPUTS, address, inputs)
+            .await
+    }
+
+    /// Reads the holding registers starting at `address` into `registers`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidQuantity`] if `registers` is empty or longer than 125, and an error
+    /// if the request fails.
+    pub async fn read_holding_registers(
+        &mut self,
+        unit: u8,
+        address: u16,
+        registers: &mut [u16],
+    ) -> Result<(), Error<T::Error>> {
+        self.read_registers(unit, function::READ_HOLDING_REGISTERS, address, registers)
+            .await
+    }
+
+    /// Reads the input registers starting at `address` into `registers`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidQuantity`] if `registers` is empty or longer than 125, and an error
+    /// if the request fails.
+    pub async fn read_input_registers(
+        &mut self,
+        unit: u8,
+        address: u16,
+        registers: &mut [u16],
+    ) -> Result<(), Error<T::Error>> {
+        self.read_registers(unit, function::READ_INPUT_REGISTERS, address, registers)
+            .await
+    }
+
+    /// Writes `value` to the coil at `address`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails.
+    pub async fn write_single_coil(
+        &mut self,
+        unit: u8,
+        address: u16,
+        value: bool,
+    ) -> Result<(), Error<T::Error>> {
+        let value = if value { COIL_ON } else { COIL_OFF };
+        self.write(unit, function::WRITE_SINGLE_COIL, address, value, |_| {})
+            .await
+    }
+
+    /// Writes `value` to the holding register at `address`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails.
+    pub async fn write_single_register(
+        &mut self,
+        unit: u8,
+        address: u16,
+        value: u16,
+    ) -> Result<(), Error<T::Error>> {
+        self.write(
+            unit,
+            function::WRITE_SINGLE_REGISTER,
+            address,
+            value,
+            |_| {},
+        )
+        .await
+    }
+
+    /// Writes `coils` to the coils starting at `address`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidQuantity`] if `coils` is empty or longer than 1968, and an error if
+    /// the request fails.
+    pub async fn write_multiple_coils(
+        &mut self,
+        unit: u8,
+        address: u16,
+        coils: &[bool],
+    ) -> Result<(), Error<T::Error>> {
+        let count = quantity(coils.len(), MAX_WRITE_BITS)?;
+        self.write(
+            unit,
+            function::WRITE_MULTIPLE_COILS,
+            address,
+            count,
+            |writer| {
+                // At most 246 bytes.
+                #[expect(clippy::cast_possible_truncation)]
+                writer.push(packed_len(count) as u8);
+                writer.push_bits(coils);
+            },
+        )
+        .await
+    }
+
+    /// Writes `registers` to the holding registers starting at `address`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidQuantity`] if `registers` is empty or longer than 123, and an error
+    /// if the request fails.
+    pub async fn write_multiple_registers(
+        &mut self,
+        unit: u8,
+        address: u16,
+        registers: &[u16],
+    ) -> Result<(), Error<T::Error>> {
+        let count = quantity(registers.len(), MAX_WRITE_REGISTERS)?;
+        self.write(
+            unit,
+            function::WRITE_MULTIPLE_REGISTERS,
+            address,
+            count,
+            |writer| {
+                // At most 246 bytes.
+                #[expect(clippy::cast_possible_truncation)]
+                writer.push(count as u8 * 2);
+                for register in registers {
+                    writer.push_u16(*register);
+                }
+            },
+        )
+        .await
+    }
+
+    /// Reads the coils or discrete inputs starting at `address` into `bits`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidQuantity`] if `bits` is empty or too long, and an error if the
+    /// request fails.
+    async fn read_bits(
+        &mut self,
+        unit: u8,
+        function: u8,
+        address: u16,
+        bits: &mut [bool],
+    ) -> Result<(), Error<T::Error>> {
+        let count = quantity(bits.len(), MAX_READ_BITS)?;
+        let mut response = [0; MAX_PDU_LEN];
+        let data = self
+            .read(unit, function, address, count, &mut response)
+            .await?;
+
+        if data.len() != packed_len(count) {
+            return Err(Error::InvalidResponse);
+        }
+        unpack_bits(data, bits);
+        Ok(())
+    }
+
+    /// Reads the holding or input registers starting at `address` into `registers`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidQuantity`] if `registers` is empty or too long, and an error if the
+    /// request fails.
+    async fn read_registers(
+        &mut self,
+        unit: u8,
+        function: u8,
+        address: u16,
+        registers: &mut [u16],
+    ) -> Result<(), Error<T::Error>> {
+        let count = quantity(registers.len(), MAX_READ_REGISTERS)?;
+        let mut response = [0; MAX_PDU_LEN];
+        let data = self
+            .read(unit, function, address, count, &mut response)
+            .await?;
+
+        if data.len() != 2 * usize::from(count) {
+            return Err(Error::InvalidResponse);
+        }
+        unpack_registers(data, registers);
+        Ok(())
+    }
+
+    /// Sends a read request, and returns the values of the response.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails.
+    async fn read<'r>(
+        &mut self,
+        unit: u8,
+        function: u8,
+        address: u16,
+        count: u16,
+        response: &'r mut [u8; MAX_PDU_LEN],
+    ) -> Result<&'r [u8], Error<T::Error>> {
+        let mut request = [0; 5];
+        let mut writer = PduWriter::new(&mut request);
+        writer.push(function);
+        writer.push_u16(address);
+        writer.push_u16(count);
+
+        let data = self.transact(unit, &request, response).await?;
+        match *data {
+            [byte_count, ref values @ ..] if values.len() == usize::from(byte_count) => Ok(values),
+            _ => Err(Error::InvalidResponse),
+        }
+    }
+
+    /// Sends a write request of `function`, whose values are written by `write_values`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails.
+    async fn write(
+        &mut self,
+        unit: u8,
+        function: u8,
+        address: u16,
+        value: u16,
+        write_values: impl FnOnce(&mut PduWriter<'_>),
+    ) -> Result<(), Error<T::Error>> {
+        let mut request = [0; MAX_PDU_LEN];
+        let mut writer = PduWriter::new(&mut request);
+        writer.push(function);
+        writer.push_u16(address);
+        writer.push_u16(value);
+        write_values(&mut writer);
+        let len = writer.len();
+
+        let request = request.get(..len).unwrap_or_default();
+        let mut response = [0; MAX_PDU_LEN];
+        let data = self.transact(unit, request, &mut response).await?;
+
+        // Servers echo the address and value of the request; broadcast requests are not answered.
+        if !data.is_empty() && Some(data) != request.get(1..5) {
+            return Err(Error::InvalidResponse);
+        }
+        Ok(())
+    }
+
+    /// Sends `request`, and returns the data of the response, following its function code.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Exception`] if the server returned an exception, and an error if the
+    /// request fails.
+    async fn transact<'r>(
+        &mut self,
+        unit: u8,
+        request: &[u8],
+        response: &'r mut [u8; MAX_PDU_LEN],
+    ) -> Result<&'r [u8], Error<T::Error>> {
+        let len = self.transport.transact(unit, request, response).await?;
+        let response = response.get(..len).unwrap_or_default();
+
+        let (Some(&function), Some((&response_function, data))) =
+            (request.first(), response.split_first())
+        else {
+            return Ok(&[]);
+        };
+        if response_function == function | function::EXCEPTION {
+            let code = data.first().ok_or(Error::InvalidResponse)?;
+            return Err(Error::Exception(Exception::from_code(*code)));
+        }
+        if response_function != function {
+            return Err(Error::InvalidResponse);
+        }
+        Ok(data)
+    }
+}
+
+/// Returns the number of values to read or write.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidQuantity`] if `len` is zero or greater than `max`.
+fn quantity<E>(len: usize, max: u16) -> Result<u16, Error<E>> {
+    u16::try_from(len)
+        .ok()
+        .filter(|count| (1..=max).contains(count))
+        .ok_or(Error::InvalidQuantity)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use embassy_futures::block_on;
+
+    use super::*;
+    use crate::{RegisterMap, server};
+
+    /// A transport processing requests with a register map.
+    struct Loopback<M>(M);
+
+    impl<M: RegisterMap> Transport for Loopback<M> {
+        type Error = Infallible;
+
+        async fn transact(
+            &mut self,
+            _unit: u8,
+            request: &[u8],
+            response: &mut [u8],
+        ) -> Result<usize, Error<Self::Error>> {
+            Ok(server::process(&mut self.0, request, response))
+        }
+    }
+
+    #[derive(Default)]
+    struct Map {
+        coils: [bool; 16],
+        holding: [u16; 8],
+    }
+
+    impl RegisterMap for Map {
+        fn read_coils(&mut self, address: u16, coils: &mut [bool]) -> Result<(), Exception> {
+            coils.copy_from_slice(server::range(&self.coils, address, coils.len())?);
+            Ok(())
+        }
+
+        fn read_holding_registers(
+            &mut self,
+            address: u16,
+            registers: &mut [u16],
+        ) -> Result<(), Exception> {
+            registers.copy_from_slice(server::range(&self.holding, address, registers.len())?);
+            Ok(())
+        }
+
+        fn write_coils(&mut self, address: u16, coils: &[bool]) -> Result<(), Exception> {
+            server::range_mut(&mut self.coils, address, coils.len())?.copy_from_slice(coils);
+            Ok(())
+        }
+
+        fn write_holding_registers(
+            &mut self,
+            address: u16,
+            registers: &[u16],
+        ) -> Result<(), Exception> {
+            server::range_mut(&mut self.holding, address, registers.len())?
+                .copy_from_slice(registers);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn registers() {
+        let mut client = Client::new(Loopback(Map::default()));
+        let mut registers = [0; 3];
+
+        block_on(async {
+            client
+                .write_multiple_registers(1, 2, &[0x1234, 0xabcd])
+                .await
+                .unwrap();
+            client.write_single_register(1, 4, 7).await.unwrap();
+            client
+                .read_holding_registers(1, 2, &mut registers)
+                .await
+                .unwrap();
+        });
+
+        assert_eq!(registers, [0x1234, 0xabcd, 7]);
+    }
+
+    #[test]
+    fn coils() {
+        let mut client = Client::new(Loopback(Map::default()));
+        let mut coils = [false; 11];
+
+        block_on(async {
+            client
+                .write_multiple_coils(
+                    1,
+                    3,
+                    &[true, false, true, true, false, false, true, true, true],
+                )
+                .await
+                .unwrap();
+            client.write_single_coil(1, 13, true).await.unwrap();
+            client.read_coils(1, 3, &mut coils).await.unwrap();
+        });
+
+        assert_eq!(
+            coils,
+            [
+                true, false, true, true, false, false, true, true, true, false, true
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut client = Client::new(Loopback(Map::default()));
+
+        block_on(async {
+            assert_eq!(
+                client.read_input_registers(1, 0, &mut [0; 1]).await,
+                Err(Error::Exception(Exception::IllegalFunction))
+            );
+            assert_eq!(
+                client.read_holding_registers(1, 6, &mut [0; 4]).await,
+                Err(Error::Exception(Exception::IllegalDataAddress))
+            );
+            assert_eq!(
+                client.read_holding_registers(1, 0, &mut [0; 126]).await,
+                Err(Error::InvalidQuantity)
+            );
+            assert_eq!(
+                client.write_multiple_coils(1, 0, &[]).await,
+                Err(Error::InvalidQuantity)
+            );
+        });
+    }
+}
@@ -0,0 +1,30 @@
+//! Provides the CRC of Modbus RTU frames.
+
+/// Returns the CRC-16/MODBUS of `data`, which is sent least significant byte first.
+pub(crate) fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xffff_u16;
+    for byte in data {
+        crc ^= u16::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ 0xa001
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_holding_registers_request() {
+        assert_eq!(
+            crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01]).to_le_bytes(),
+            [0x84, 0x0a]
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! Provides Modbus clients and servers, over serial lines (Modbus RTU) and TCP.
+//!
+//! Devices are bridged into PLC environments either as servers (slaves), which expose a
+//! [`RegisterMap`] of coils and registers, or as clients (masters), which poll other devices with
+//! a [`Client`].
+//!
+//! Both transports run on byte streams implementing the [`embedded_io_async`] traits: an
+//! RS-485-capable UART for [`rtu`], and a TCP socket of the network stack for [`tcp`].
+//!
+//! ```ignore
+//! struct Pump {
+//!     setpoint: u16,
+//! }
+//!
+//! impl RegisterMap for Pump {
+//!     fn read_holding_registers(
+//!         &mut self,
+//!         address: u16,
+//!         registers: &mut [u16],
+//!     ) -> Result<(), Exception> {
+//!         registers.copy_from_slice(server::range(&[self.setpoint], address, registers.len())?);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! socket.accept(tcp::PORT).await?;
+//! tcp::serve(&mut socket, &mut Pump { setpoint: 1200 }).await?;
+//! ```
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+mod client;
+mod crc;
+pub mod rtu;
+pub mod server;
+pub mod tcp;
+
+use embedded_io_async::ReadExactError;
+
+pub use client::{Client, Transport};
+pub use server::RegisterMap;
+
+/// Maximum length of protocol data units (PDUs), which consist of a function code and its data.
+pub const MAX_PDU_LEN: usize = 253;
+
+/// Function codes.
+mod function {
+    pub const READ_COILS: u8 = 0x01;
+    pub const READ_DISCRETE_INPUTS: u8 = 0x02;
+    pub const READ_HOLDING_REGISTERS: u8 = 0x03;
+    pub const READ_INPUT_REGISTERS: u8 = 0x04;
+    pub const WRITE_SINGLE_COIL: u8 = 0x05;
+    pub const WRITE_SINGLE_REGISTER: u8 = 0x06;
+    pub const WRITE_MULTIPLE_COILS: u8 = 0x0f;
+    pub const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+    /// Bit set in the function code of exception responses.
+    pub const EXCEPTION: u8 = 0x80;
+}
+
+/// Maximum number of coils or discrete inputs read by a request.
+const MAX_READ_BITS: u16 = 2000;
+/// Maximum number of registers read by a request.
+const MAX_READ_REGISTERS: u16 = 125;
+/// Maximum number of coils written by a request.
+const MAX_WRITE_BITS: u16 = 1968;
+/// Maximum number of registers written by a request.
+const MAX_WRITE_REGISTERS: u16 = 123;
+
+/// Value of a coil set by a [`function::WRITE_SINGLE_COIL`] request.
+const COIL_ON: u16 = 0xff00;
+/// Value of a coil cleared by a [`function::WRITE_SINGLE_COIL`] request.
+const COIL_OFF: u16 = 0x0000;
+
+/// Exceptions, returned by servers instead of a response when they cannot process a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Exception {
+    /// The function is not supported by the server.
+    IllegalFunction,
+    /// The address is not part of the register map of the server.
+    IllegalDataAddress,
+    /// A value of the request is not allowed.
+    IllegalDataValue,
+    /// The server failed to perform the action requested.
+    ServerDeviceFailure,
+    /// The server accepted the request, but needs a long time to process it.
+    Acknowledge,
+    /// The server is busy processing another request.
+    ServerDeviceBusy,
+    /// The gateway has no path to the target device.
+    GatewayPathUnavailable,
+    /// The target device did not respond to the gateway.
+    GatewayTargetDeviceFailedToRespond,
+    /// Any other exception, of the given code.
+    Other(u8),
+}
+
+impl Exception {
+    /// Returns the exception code.
+    #[must_use]
+    pub fn code(self) -> u8 {
+        match self {
+            Self::IllegalFunction => 0x01,
+            Self::IllegalDataAddress => 0x02,
+            Self::IllegalDataValue => 0x03,
+            Self::ServerDeviceFailure => 0x04,
+            Self::Acknowledge => 0x05,
+            Self::ServerDeviceBusy => 0x06,
+            Self::GatewayPathUnavailable => 0x0a,
+            Self::GatewayTargetDeviceFailedToRespond => 0x0b,
+            Self::Other(code) => code,
+        }
+    }
+
+    /// Returns the exception of the given code.
+    #[must_use]
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            0x01 => Self::IllegalFunction,
+            0x02 => Self::IllegalDataAddress,
+            0x03 => Self::IllegalDataValue,
+            0x04 => Self::ServerDeviceFailure,
+            0x05 => Self::Acknowledge,
+            0x06 => Self::ServerDeviceBusy,
+            0x0a => Self::GatewayPathUnavailable,
+            0x0b => Self::GatewayTargetDeviceFailedToRespond,
+            code => Self::Other(code),
+        }
+    }
+}
+
+impl core::fmt::Display for Exception {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::IllegalFunction => write!(f, "illegal function"),
+            Self::IllegalDataAddress => write!(f, "illegal data address"),
+            Self::IllegalDataValue => write!(f, "illegal data value"),
+            Self::ServerDeviceFailure => write!(f, "server device failure"),
+            Self::Acknowledge => write!(f, "acknowledge"),
+            Self::ServerDeviceBusy => write!(f, "server device busy"),
+            Self::GatewayPathUnavailable => write!(f, "gateway path unavailable"),
+            Self::GatewayTargetDeviceFailedToRespond => {
+                write!(f, "gateway target device failed to respond")
+            }
+            Self::Other(code) => write!(f, "exception {code:#04x}"),
+        }
+    }
+}
+
+impl core::error::Error for Exception {}
+
+/// Errors of Modbus clients, on byte streams whose errors are `E`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// Reading from or writing to the byte stream failed.
+    Io(E),
+    /// The byte stream ended.
+    Disconnected,
+    /// No response was received in time.
+    Timeout,
+    /// The response is malformed, or does not match the request.
+    InvalidResponse,
+    /// The server returned an exception.
+    Exception(Exception),
+    /// The number of values to read or write is zero, or too large for a single request.
+    InvalidQuantity,
+}
+
+impl<E: core::fmt::Debug> core::fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "I/O error: {err:?}"),
+            Self::Disconnected => write!(f, "disconnected"),
+            Self::Timeout => write!(f, "timeout"),
+            Self::InvalidResponse => write!(f, "invalid response"),
+            Self::Exception(exception) => write!(f, "exception: {exception}"),
+            Self::InvalidQuantity => write!(f, "invalid quantity"),
+        }
+    }
+}
+
+impl<E: core::fmt::Debug> core::error::Error for Error<E> {}
+
+impl<E> From<embassy_time::TimeoutError> for Error<E> {
+    fn from(_: embassy_time::TimeoutError) -> Self {
+        Self::Timeout
+    }
+}
+
+impl<E> From<ReadExactError<E>> for Error<E> {
+    fn from(err: ReadExactError<E>) -> Self {
+        match err {
+            ReadExactError::UnexpectedEof => Self::Disconnected,
+            ReadExactError::Other(err) => Self::Io(err),
+        }
+    }
+}
+
+/// Writes a PDU into a buffer.
+///
+/// The length of PDUs is checked before writing them, bytes that do not fit are thus ignored.
+struct PduWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> PduWriter<'a> {
+    fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, len: 0 }
+    }
+
+    fn push(&mut self, byte: u8) {
+        if let Some(slot) = self.buf.get_mut(self.len) {
+            *slot = byte;
+            self.len += 1;
+        }
+    }
+
+    fn push_slice(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.push(*byte);
+        }
+    }
+
+    fn push_u16(&mut self, value: u16) {
+        for byte in value.to_be_bytes() {
+            self.push(byte);
+        }
+    }
+
+    /// Pushes `bits`, packed eight per byte, least significant bit first.
+    fn push_bits(&mut self, bits: &[bool]) {
+        for chunk in bits.chunks(8) {
+            let byte = chunk
+                .iter()
+                .enumerate()
+                .fold(0, |byte, (i, bit)| byte | (u8::from(*bit) << i));
+            self.push(byte);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// Unpacks bits packed by [`PduWriter::push_bits()`] into `bits`.
+fn unpack_bits(bytes: &[u8], bits: &mut [bool]) {
+    for (chunk, byte) in bits.chunks_mut(8).zip(bytes) {
+        for (i, bit) in chunk.iter_mut().enumerate() {
+            *bit = byte & (1 << i) != 0;
+        }
+    }
+}
+
+/// Returns the number of bytes `count` packed bits take.
+fn packed_len(count: u16) -> usize {
+    usize::from(count).div_ceil(8)
+}
+
+/// Unpacks big-endian registers from `bytes` into `registers`.
+fn unpack_registers(bytes: &[u8], registers: &mut [u16]) {
+    for (register, chunk) in registers.iter_mut().zip(bytes.chunks_exact(2)) {
+        if let &[high, low] = chunk {
+            *register = u16::from_be_bytes([high, low]);
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Provides Modbus RTU, over serial lines.
+//!
+//! Frames are delimited by silences of 3.5 characters on the line, whose duration is derived from
+//! the baud rate of the [`Config`].
+//! The byte stream is typically an RS-485-capable UART, which switches its transceiver between
+//! transmitting and receiving itself, eg. through a driver-enable pin controlled by the UART
+//! peripheral.
+
+use embassy_time::{Duration, with_timeout};
+use embedded_io_async::{Read, Write};
+
+use crate::{Error, MAX_PDU_LEN, PduWriter, RegisterMap, Transport, crc::crc16, server};
+
+/// Address to which requests are broadcast to all servers, which do not answer them.
+pub const BROADCAST: u8 = 0;
+
+/// Maximum length of frames, which consist of an address, a PDU and a CRC.
+const MAX_FRAME_LEN: usize = 1 + MAX_PDU_LEN + 2;
+/// Minimum length of frames, whose PDU contains at least a function code.
+const MIN_FRAME_LEN: usize = 4;
+
+/// Configuration of Modbus RTU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// Baud rate of the serial line, which must match the configuration of the byte stream.
+    pub baudrate: u32,
+    /// Time after which clients stop waiting for a response.
+    pub response_timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            baudrate: 19_200,
+            response_timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+impl Config {
+    /// Returns the silence delimiting frames.
+    fn frame_gap(&self) -> Duration {
+        // The specification fixes the silence to 1.75 ms above 19200 baud; below, it lasts 3.5
+        // characters of 11 bits.
+        if self.baudrate > 19_200 {
+            Duration::from_micros(1750)
+        } else {
+            Duration::from_micros(38_500_000 / u64::from(self.baudrate.max(1)))
+        }
+    }
+}
+
+/// A Modbus RTU transport, for [`Client`]s, on the byte stream `S`.
+///
+/// [`Client`]: crate::Client
+pub struct RtuTransport<S> {
+    stream: S,
+    config: Config,
+}
+
+impl<S> RtuTransport<S> {
+    /// Creates a transport on `stream`.
+    #[must_use]
+    pub const fn new(stream: S, config: Config) -> Self {
+        Self { stream, config }
+    }
+
+    /// Returns the byte stream of the transport.
+    pub fn release(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: Read + Write> Transport for RtuTransport<S> {
+    type Error = S::Error;
+
+    async fn transact(
+        &mut self,
+        unit: u8,
+        request: &[u8],
+        response: &mut [u8],
+    ) -> Result<usize, Error<Self::Error>> {
+        send_frame(&mut self.stream, unit, request)
+            .await
+            .map_err(Error::Io)?;
+        if unit == BROADCAST {
+            return Ok(0);
+        }
+
+        let mut frame = [0; MAX_FRAME_LEN];
+        let len = with_timeout(
+            self.config.response_timeout,
+            receive_frame(&mut self.stream, &mut frame, self.config.frame_gap()),
+        )
+        .await?
+        .map_err(Error::Io)?
+        .ok_or(Error::Disconnected)?;
+
+        let (address, pdu) =
+            parse_frame(frame.get(..len).unwrap_or_default()).ok_or(Error::InvalidResponse)?;
+        if address != unit {
+            return Err(Error::InvalidResponse);
+        }
+        response
+            .get_mut(..pdu.len())
+            .ok_or(Error::InvalidResponse)?
+            .copy_from_slice(pdu);
+        Ok(pdu.len())
+    }
+}
+
+/// Serves `map` as the server of address `address` on `stream`.
+///
+/// Frames that are corrupted or addressed to other servers are ignored, and broadcast requests
+/// are processed without being answered.
+///
+/// Returns once the byte stream ends.
+///
+/// # Errors
+///
+/// Returns the error of the byte stream if reading or writing fails.
+pub async fn serve<S: Read + Write, M: RegisterMap + ?Sized>(
+    mut stream: S,
+    address: u8,
+    config: &Config,
+    map: &mut M,
+) -> Result<(), S::Error> {
+    let mut frame = [0; MAX_FRAME_LEN];
+    let mut response = [0; MAX_PDU_LEN];
+    loop {
+        let Some(len) = receive_frame(&mut stream, &mut frame, config.frame_gap()).await? else {
+            return Ok(());
+        };
+        let Some((unit, request)) = parse_frame(frame.get(..len).unwrap_or_default()) else {
+            continue;
+        };
+        if unit != address && unit != BROADCAST {
+            continue;
+        }
+
+        let response_len = server::process(map, request, &mut response);
+        if unit != BROADCAST && response_len != 0 {
+            let response = response.get(..response_len).unwrap_or_default();
+            send_frame(&mut stream, address, response).await?;
+        }
+    }
+}
+
+/// Sends a frame of `pdu` addressed to `address`.
+///
+/// # Errors
+///
+/// Returns the error of the byte stream if writing fails.
+async fn send_frame<S: Write>(stream: &mut S, address: u8, pdu: &[u8]) -> Result<(), S::Error> {
+    let mut frame = [0; MAX_FRAME_LEN];
+    let mut writer = PduWriter::new(&mut frame);
+    writer.push(address);
+    writer.push_slice(pdu);
+    let len = writer.len();
+    let crc = crc16(frame.get(..len).unwrap_or_default());
+
+    let mut writer = PduWriter::new(frame.get_mut(len..).unwrap_or_default());
+    writer.push_slice(&crc.to_le_bytes());
+
+    stream
+        .write_all(frame.get(..len + 2).unwrap_or_default())
+        .await?;
+    stream.flush().await
+}
+
+/// Receives a frame into `frame`, which ends once the line stays silent for `gap`.
+///
+/// Returns the length of the frame, which is zero if the frame does not fit, or `None` if the
+/// byte stream ended.
+///
+/// # Errors
+///
+/// Returns the error of the byte stream if reading fails.
+async fn receive_frame<S: Read>(
+    stream: &mut S,
+    frame: &mut [u8; MAX_FRAME_LEN],
+    gap: Duration,
+) -> Result<Option<usize>, S::Error> {
+    let mut len = stream.read(frame).await?;
+    if len == 0 {
+        return Ok(None);
+    }
+
+    let mut overflow = false;
+    loop {
+        let mut discarded = [0; 16];
+        let buf = match frame.get_mut(len..) {
+            Some(free) if !free.is_empty() => free,
+            _ => {
+                overflow = true;
+                discarded.as_mut_slice()
+            }
+        };
+
+        match with_timeout(gap, stream.read(buf)).await {
+            Ok(Ok(0)) | Err(_) => break,
+            Ok(Ok(read)) => {
+                if !overflow {
+                    len += read;
+                }
+            }
+            Ok(Err(err)) => return Err(err),
+        }
+    }
+
+    Ok(Some(if overflow { 0 } else { len }))
+}
+
+/// Returns the address and the PDU of `frame`, if its CRC is valid.
+fn parse_frame(frame: &[u8]) -> Option<(u8, &[u8])> {
+    if frame.len() < MIN_FRAME_LEN {
+        return None;
+    }
+    let (content, crc) = frame.split_at(frame.len() - 2);
+    let &[crc_low, crc_high] = crc else {
+        return None;
+    };
+    if crc16(content) != u16::from_le_bytes([crc_low, crc_high]) {
+        return None;
+    }
+    content.split_first().map(|(address, pdu)| (*address, pdu))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames() {
+        let frame = [0x11, 0x03, 0x00, 0x6b, 0x00, 0x03, 0x76, 0x87];
+        assert_eq!(
+            parse_frame(&frame),
+            Some((0x11, &[0x03, 0x00, 0x6b, 0x00, 0x03][..]))
+        );
+
+        let corrupted = [0x11, 0x03, 0x00, 0x6c, 0x00, 0x03, 0x76, 0x87];
+        assert_eq!(parse_frame(&corrupted), None);
+        assert_eq!(parse_frame(&[0x11, 0x03, 0x00]), None);
+    }
+
+    #[test]
+    fn frame_gap() {
+        let mut config = Config::default();
+        assert_eq!(config.frame_gap(), Duration::from_micros(2005));
+        config.baudrate = 115_200;
+        assert_eq!(config.frame_gap(), Duration::from_micros(1750));
+    }
+}
@@ -0,0 +1,391 @@
+//! Provides the register maps of Modbus servers.
+//!
+//! The register map of a server is defined by implementing [`RegisterMap`], whose methods each
+//! handle one of the four Modbus data tables; the [`range()`] and [`range_mut()`] helpers map
+//! requests onto arrays of values. The register map is then served by [`rtu::serve()`] or
+//! [`tcp::serve()`].
+//!
+//! [`rtu::serve()`]: crate::rtu::serve()
+//! [`tcp::serve()`]: crate::tcp::serve()
+
+use crate::{
+    COIL_OFF, COIL_ON, Exception, MAX_READ_BITS, MAX_READ_REGISTERS, MAX_WRITE_BITS,
+    MAX_WRITE_REGISTERS, PduWriter, function, packed_len, unpack_bits, unpack_registers,
+};
+
+/// The register map of a server, ie. its coils, discrete inputs, holding registers and input
+/// registers.
+///
+/// All methods have a default implementation returning [`Exception::IllegalFunction`], so that
+/// only the tables the server provides need to be implemented.
+/// The methods are called with at least one value, and return the exception to respond with on
+/// error, eg. [`Exception::IllegalDataAddress`] if some of the values do not exist.
+#[expect(unused_variables, reason = "default implementations")]
+pub trait RegisterMap {
+    /// Reads the coils starting at `address` into `coils`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the exception to respond with if the coils cannot be read.
+    fn read_coils(&mut self, address: u16, coils: &mut [bool]) -> Result<(), Exception> {
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Reads the discrete inputs starting at `address` into `inputs`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the exception to respond with if the inputs cannot be read.
+    fn read_discrete_inputs(&mut self, address: u16, inputs: &mut [bool]) -> Result<(), Exception> {
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Reads the holding registers starting at `address` into `registers`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the exception to respond with if the registers cannot be read.
+    fn read_holding_registers(
+        &mut self,
+        address: u16,
+        registers: &mut [u16],
+    ) -> Result<(), Exception> {
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Reads the input registers starting at `address` into `registers`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the exception to respond with if the registers cannot be read.
+    fn read_input_registers(
+        &mut self,
+        address: u16,
+        registers: &mut [u16],
+    ) -> Result<(), Exception> {
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Writes `coils` to the coils starting at `address`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the exception to respond with if the coils cannot be written.
+    fn write_coils(&mut self, address: u16, coils: &[bool]) -> Result<(), Exception> {
+        Err(Exception::IllegalFunction)
+    }
+
+    /// Writes `registers` to the holding registers starting at `address`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the exception to respond with if the registers cannot be written.
+    fn write_holding_registers(
+        &mut self,
+        address: u16,
+        registers: &[u16],
+    ) -> Result<(), Exception> {
+        Err(Exception::IllegalFunction)
+    }
+}
+
+/// Returns the `count` values starting at `address` of a table whose values start at address 0.
+///
+/// # Errors
+///
+/// Returns [`Exception::IllegalDataAddress`] if some of the values are not in `values`.
+pub fn range<T>(values: &[T], address: u16, count: usize) -> Result<&[T], Exception> {
+    let start = usize::from(address);
+    values
+        .get(start..start + count)
+        .ok_or(Exception::IllegalDataAddress)
+}
+
+/// Returns the `count` values starting at `address` of a table whose values start at address 0.
+///
+/// # Errors
+///
+/// Returns [`Exception::IllegalDataAddress`] if some of the values are not in `values`.
+pub fn range_mut<T>(values: &mut [T], address: u16, count: usize) -> Result<&mut [T], Exception> {
+    let start = usize::from(address);
+    values
+        .get_mut(start..start + count)
+        .ok_or(Exception::IllegalDataAddress)
+}
+
+/// Processes the `request` PDU with `map`, and writes the response PDU into `response`.
+///
+/// Returns the length of the response, which is zero if the request is empty.
+pub(crate) fn process<M: RegisterMap + ?Sized>(
+    map: &mut M,
+    request: &[u8],
+    response: &mut [u8],
+) -> usize {
+    let Some((&function, data)) = request.split_first() else {
+        return 0;
+    };
+
+    match process_function(map, function, data, response) {
+        Ok(len) => len,
+        Err(exception) => {
+            let mut writer = PduWriter::new(response);
+            writer.push(function | function::EXCEPTION);
+            writer.push(exception.code());
+            writer.len()
+        }
+    }
+}
+
+/// Processes a request of `function`, and writes the response PDU into `response`.
+///
+/// Returns the length of the response.
+///
+/// # Errors
+///
+/// Returns the exception to respond with if the request cannot be processed.
+fn process_function<M: RegisterMap + ?Sized>(
+    map: &mut M,
+    function: u8,
+    data: &[u8],
+    response: &mut [u8],
+) -> Result<usize, Exception> {
+    let mut writer = PduWriter::new(response);
+    writer.push(function);
+
+    match function {
+        function::READ_COILS | function::READ_DISCRETE_INPUTS => {
+            let (address, count) = parse_u16_pair(data)?;
+            if !(1..=MAX_READ_BITS).contains(&count) {
+                return Err(Exception::IllegalDataValue);
+            }
+
+            let mut bits = [false; MAX_READ_BITS as usize];
+            let bits = bits.get_mut(..usize::from(count)).unwrap_or_default();
+            if function == function::READ_COILS {
+                map.read_coils(address, bits)?;
+            } else {
+                map.read_discrete_inputs(address, bits)?;
+            }
+
+            // At most 250 bytes.
+            #[expect(clippy::cast_possible_truncation)]
+            writer.push(packed_len(count) as u8);
+            writer.push_bits(bits);
+        }
+        function::READ_HOLDING_REGISTERS | function::READ_INPUT_REGISTERS => {
+            let (address, count) = parse_u16_pair(data)?;
+            if !(1..=MAX_READ_REGISTERS).contains(&count) {
+                return Err(Exception::IllegalDataValue);
+            }
+
+            let mut registers = [0; MAX_READ_REGISTERS as usize];
+            let registers = registers.get_mut(..usize::from(count)).unwrap_or_default();
+            if function == function::READ_HOLDING_REGISTERS {
+                map.read_holding_registers(address, registers)?;
+            } else {
+                map.read_input_registers(address, registers)?;
+            }
+
+            // At most 250 bytes.
+            #[expect(clippy::cast_possible_truncation)]
+            writer.push(count as u8 * 2);
+            for register in registers {
+                writer.push_u16(*register);
+            }
+        }
+        function::WRITE_SINGLE_COIL => {
+            let (address, value) = parse_u16_pair(data)?;
+            let coil = match value {
+                COIL_ON => true,
+                COIL_OFF => false,
+                _ => return Err(Exception::IllegalDataValue),
+            };
+            map.write_coils(address, &[coil])?;
+
+            writer.push_u16(address);
+            writer.push_u16(value);
+        }
+        function::WRITE_SINGLE_REGISTER => {
+            let (address, value) = parse_u16_pair(data)?;
+            map.write_holding_registers(address, &[value])?;
+
+            writer.push_u16(address);
+            writer.push_u16(value);
+        }
+        function::WRITE_MULTIPLE_COILS => {
+            let (address, count, values) = parse_write_multiple(data)?;
+            if !(1..=MAX_WRITE_BITS).contains(&count) || values.len() != packed_len(count) {
+                return Err(Exception::IllegalDataValue);
+            }
+
+            let mut bits = [false; MAX_WRITE_BITS as usize];
+            let bits = bits.get_mut(..usize::from(count)).unwrap_or_default();
+            unpack_bits(values, bits);
+            map.write_coils(address, bits)?;
+
+            writer.push_u16(address);
+            writer.push_u16(count);
+        }
+        function::WRITE_MULTIPLE_REGISTERS => {
+            let (address, count, values) = parse_write_multiple(data)?;
+            if !(1..=MAX_WRITE_REGISTERS).contains(&count) || values.len() != 2 * usize::from(count)
+            {
+                return Err(Exception::IllegalDataValue);
+            }
+
+            let mut registers = [0; MAX_WRITE_REGISTERS as usize];
+            let registers = registers.get_mut(..usize::from(count)).unwrap_or_default();
+            unpack_registers(values, registers);
+            map.write_holding_registers(address, registers)?;
+
+            writer.push_u16(address);
+            writer.push_u16(count);
+        }
+        _ => return Err(Exception::IllegalFunction),
+    }
+
+    Ok(writer.len())
+}
+
+/// Parses the address and the quantity or value of a request.
+///
+/// # Errors
+///
+/// Returns [`Exception::IllegalDataValue`] if the request is malformed.
+fn parse_u16_pair(data: &[u8]) -> Result<(u16, u16), Exception> {
+    let &[a0, a1, b0, b1] = data else {
+        return Err(Exception::IllegalDataValue);
+    };
+    Ok((u16::from_be_bytes([a0, a1]), u16::from_be_bytes([b0, b1])))
+}
+
+/// Parses the address, quantity and values of a request writing multiple values.
+///
+/// # Errors
+///
+/// Returns [`Exception::IllegalDataValue`] if the request is malformed.
+fn parse_write_multiple(data: &[u8]) -> Result<(u16, u16, &[u8]), Exception> {
+    let &[a0, a1, c0, c1, byte_count, ref values @ ..] = data else {
+        return Err(Exception::IllegalDataValue);
+    };
+    if values.len() != usize::from(byte_count) {
+        return Err(Exception::IllegalDataValue);
+    }
+    Ok((
+        u16::from_be_bytes([a0, a1]),
+        u16::from_be_bytes([c0, c1]),
+        values,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MAX_PDU_LEN;
+
+    #[derive(Default)]
+    struct Map {
+        coils: [bool; 10],
+        holding: [u16; 4],
+    }
+
+    impl RegisterMap for Map {
+        fn read_coils(&mut self, address: u16, coils: &mut [bool]) -> Result<(), Exception> {
+            coils.copy_from_slice(range(&self.coils, address, coils.len())?);
+            Ok(())
+        }
+
+        fn read_holding_registers(
+            &mut self,
+            address: u16,
+            registers: &mut [u16],
+        ) -> Result<(), Exception> {
+            registers.copy_from_slice(range(&self.holding, address, registers.len())?);
+            Ok(())
+        }
+
+        fn write_coils(&mut self, address: u16, coils: &[bool]) -> Result<(), Exception> {
+            range_mut(&mut self.coils, address, coils.len())?.copy_from_slice(coils);
+            Ok(())
+        }
+
+        fn write_holding_registers(
+            &mut self,
+            address: u16,
+            registers: &[u16],
+        ) -> Result<(), Exception> {
+            range_mut(&mut self.holding, address, registers.len())?.copy_from_slice(registers);
+            Ok(())
+        }
+    }
+
+    fn assert_response(map: &mut Map, request: &[u8], expected: &[u8]) {
+        let mut response = [0; MAX_PDU_LEN];
+        let len = process(map, request, &mut response);
+        assert_eq!(response.get(..len), Some(expected));
+    }
+
+    #[test]
+    fn read_holding_registers() {
+        let mut map = Map {
+            holding: [0x1234, 0x5678, 0, 0],
+            ..Default::default()
+        };
+
+        assert_response(
+            &mut map,
+            &[0x03, 0x00, 0x00, 0x00, 0x02],
+            &[0x03, 0x04, 0x12, 0x34, 0x56, 0x78],
+        );
+    }
+
+    #[test]
+    fn read_coils() {
+        let mut map = Map {
+            coils: [
+                true, false, false, false, false, false, false, false, false, true,
+            ],
+            ..Default::default()
+        };
+
+        assert_response(
+            &mut map,
+            &[0x01, 0x00, 0x00, 0x00, 0x0a],
+            &[0x01, 0x02, 0x01, 0x02],
+        );
+    }
+
+    #[test]
+    fn write_multiple() {
+        let mut map = Map::default();
+
+        assert_response(
+            &mut map,
+            &[0x10, 0x00, 0x01, 0x00, 0x02, 0x04, 0x00, 0x0a, 0x01, 0x02],
+            &[0x10, 0x00, 0x01, 0x00, 0x02],
+        );
+        assert_eq!(map.holding, [0, 0x000a, 0x0102, 0]);
+
+        assert_response(
+            &mut map,
+            &[0x0f, 0x00, 0x02, 0x00, 0x03, 0x01, 0x05],
+            &[0x0f, 0x00, 0x02, 0x00, 0x03],
+        );
+        assert_eq!(
+            map.coils.get(..5),
+            Some(&[false, false, true, false, true][..])
+        );
+    }
+
+    #[test]
+    fn exceptions() {
+        let mut map = Map::default();
+
+        // Unsupported function.
+        assert_response(&mut map, &[0x04, 0x00, 0x00, 0x00, 0x01], &[0x84, 0x01]);
+        // Out of the register map.
+        assert_response(&mut map, &[0x03, 0x00, 0x03, 0x00, 0x02], &[0x83, 0x02]);
+        // Invalid coil value.
+        assert_response(&mut map, &[0x05, 0x00, 0x00, 0x12, 0x34], &[0x85, 0x03]);
+    }
+}
@@ -0,0 +1,263 @@
+//! Provides Modbus TCP.
+//!
+//! Servers accept connections on [`PORT`], and serve each of them with [`serve()`]; clients
+//! connect to it, and send requests through a [`TcpTransport`]. Both run on connected TCP
+//! sockets, eg. `embassy_net::tcp::TcpSocket`.
+
+use embassy_time::{Duration, with_timeout};
+use embedded_io_async::{Read, ReadExactError, Write};
+
+use crate::{Error, MAX_PDU_LEN, PduWriter, RegisterMap, Transport, server};
+
+/// TCP port of Modbus servers.
+pub const PORT: u16 = 502;
+
+/// Length of the header preceding PDUs (MBAP header).
+const HEADER_LEN: usize = 7;
+/// Protocol identifier of Modbus.
+const PROTOCOL_ID: u16 = 0;
+
+/// Configuration of Modbus TCP clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// Time after which the client stops waiting for a response.
+    pub response_timeout: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            response_timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+/// A Modbus TCP transport, for [`Client`]s, on the connected socket `S`.
+///
+/// The unit of requests identifies the server behind gateways; servers reached directly usually
+/// ignore it, or expect `0xff`.
+///
+/// [`Client`]: crate::Client
+pub struct TcpTransport<S> {
+    stream: S,
+    config: Config,
+    transaction_id: u16,
+}
+
+impl<S> TcpTransport<S> {
+    /// Creates a transport on `stream`.
+    #[must_use]
+    pub const fn new(stream: S, config: Config) -> Self {
+        Self {
+            stream,
+            config,
+            transaction_id: 0,
+        }
+    }
+
+    /// Returns the socket of the transport.
+    pub fn release(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: Read + Write> Transport for TcpTransport<S> {
+    type Error = S::Error;
+
+    async fn transact(
+        &mut self,
+        unit: u8,
+        request: &[u8],
+        response: &mut [u8],
+    ) -> Result<usize, Error<Self::Error>> {
+        self.transaction_id = self.transaction_id.wrapping_add(1);
+        send(&mut self.stream, self.transaction_id, unit, request)
+            .await
+            .map_err(Error::Io)?;
+
+        with_timeout(self.config.response_timeout, async {
+            let mut pdu = [0; MAX_PDU_LEN];
+            loop {
+                let (transaction_id, response_unit, len) = receive(&mut self.stream, &mut pdu)
+                    .await?
+                    .ok_or(Error::InvalidResponse)?;
+                // Responses to earlier requests, which timed out, are skipped.
+                if transaction_id != self.transaction_id {
+                    continue;
+                }
+                if response_unit != unit {
+                    return Err(Error::InvalidResponse);
+                }
+
+                response
+                    .get_mut(..len)
+                    .ok_or(Error::InvalidResponse)?
+                    .copy_from_slice(pdu.get(..len).unwrap_or_default());
+                return Ok(len);
+            }
+        })
+        .await?
+    }
+}
+
+/// Serves `map` on the connected socket `stream`, answering requests of any unit.
+///
+/// Returns once the client closes the connection, or sends a malformed header, after which the
+/// connection should be closed.
+///
+/// # Errors
+///
+/// Returns the error of the socket if reading or writing fails.
+pub async fn serve<S: Read + Write, M: RegisterMap + ?Sized>(
+    mut stream: S,
+    map: &mut M,
+) -> Result<(), S::Error> {
+    let mut request = [0; MAX_PDU_LEN];
+    let mut response = [0; MAX_PDU_LEN];
+    loop {
+        let (transaction_id, unit, len) = match receive(&mut stream, &mut request).await {
+            Ok(Some(header)) => header,
+            Ok(None) | Err(ReadExactError::UnexpectedEof) => return Ok(()),
+            Err(ReadExactError::Other(err)) => return Err(err),
+        };
+
+        let request = request.get(..len).unwrap_or_default();
+        let response_len = server::process(map, request, &mut response);
+        let response = response.get(..response_len).unwrap_or_default();
+        send(&mut stream, transaction_id, unit, response).await?;
+    }
+}
+
+/// Sends `pdu`, preceded by its header.
+///
+/// # Errors
+///
+/// Returns the error of the socket if writing fails.
+async fn send<S: Write>(
+    stream: &mut S,
+    transaction_id: u16,
+    unit: u8,
+    pdu: &[u8],
+) -> Result<(), S::Error> {
+    let mut adu = [0; HEADER_LEN + MAX_PDU_LEN];
+    let mut writer = PduWriter::new(&mut adu);
+    writer.push_u16(transaction_id);
+    writer.push_u16(PROTOCOL_ID);
+    // The length covers the unit identifier and the PDU, of at most `MAX_PDU_LEN` bytes.
+    #[expect(clippy::cast_possible_truncation)]
+    writer.push_u16(pdu.len() as u16 + 1);
+    writer.push(unit);
+    writer.push_slice(pdu);
+    let len = writer.len();
+
+    stream.write_all(adu.get(..len).unwrap_or_default()).await?;
+    stream.flush().await
+}
+
+/// Receives a PDU into `pdu`.
+///
+/// Returns the transaction identifier, the unit identifier and the length of the PDU, or `None`
+/// if the header is malformed.
+///
+/// # Errors
+///
+/// Returns an error if reading fails, or the socket is closed.
+async fn receive<S: Read>(
+    stream: &mut S,
+    pdu: &mut [u8; MAX_PDU_LEN],
+) -> Result<Option<(u16, u8, usize)>, ReadExactError<S::Error>> {
+    let mut header = [0; HEADER_LEN];
+    stream.read_exact(&mut header).await?;
+
+    let [t0, t1, p0, p1, l0, l1, unit] = header;
+    let len = usize::from(u16::from_be_bytes([l0, l1])).wrapping_sub(1);
+    if u16::from_be_bytes([p0, p1]) != PROTOCOL_ID || !(1..=MAX_PDU_LEN).contains(&len) {
+        return Ok(None);
+    }
+
+    stream
+        .read_exact(pdu.get_mut(..len).unwrap_or_default())
+        .await?;
+    Ok(Some((u16::from_be_bytes([t0, t1]), unit, len)))
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use embassy_futures::block_on;
+
+    use super::*;
+    use crate::Exception;
+
+    /// A byte stream reading from `input`, and writing to `output`.
+    struct Stream {
+        input: &'static [u8],
+        output: [u8; 64],
+        output_len: usize,
+    }
+
+    impl embedded_io_async::ErrorType for Stream {
+        type Error = Infallible;
+    }
+
+    impl Read for Stream {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.input.read(buf).await
+        }
+    }
+
+    impl Write for Stream {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            let output = self.output.get_mut(self.output_len..).unwrap_or_default();
+            let len = buf.len().min(output.len());
+            output
+                .get_mut(..len)
+                .unwrap_or_default()
+                .copy_from_slice(buf.get(..len).unwrap_or_default());
+            self.output_len += len;
+            Ok(len)
+        }
+    }
+
+    struct Map;
+
+    impl RegisterMap for Map {
+        fn read_input_registers(
+            &mut self,
+            address: u16,
+            registers: &mut [u16],
+        ) -> Result<(), Exception> {
+            registers.copy_from_slice(server::range(&[0x0102, 0x0304], address, registers.len())?);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn serve_requests() {
+        let mut stream = Stream {
+            input: &[
+                // Reading input register 1.
+                0x00, 0x2a, 0x00, 0x00, 0x00, 0x06, 0xff, 0x04, 0x00, 0x01, 0x00, 0x01,
+                // Unsupported function.
+                0x00, 0x2b, 0x00, 0x00, 0x00, 0x02, 0xff, 0x2b,
+            ],
+            output: [0; 64],
+            output_len: 0,
+        };
+
+        block_on(serve(&mut stream, &mut Map)).unwrap();
+
+        assert_eq!(
+            stream.output.get(..stream.output_len),
+            Some(
+                &[
+                    0x00, 0x2a, 0x00, 0x00, 0x00, 0x05, 0xff, 0x04, 0x02, 0x03, 0x04, //
+                    0x00, 0x2b, 0x00, 0x00, 0x00, 0x03, 0xff, 0xab, 0x01,
+                ][..]
+            )
+        );
+    }
+}
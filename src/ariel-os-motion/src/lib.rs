@@ -0,0 +1,38 @@
+//! Provides motion control, driving servos with PWM outputs and stepper motors with GPIOs.
+//!
+//! [`Servo`](servo::Servo)s are positioned by the width of the pulses of a 50 Hz PWM output, and
+//! [`Stepper`](stepper::Stepper)s are moved by pulses on the step input of their driver, which
+//! are timed with acceleration ramps.
+//!
+//! ```ignore
+//! let mut servo = Servo::new(pwm, servo::Config::default());
+//! servo.set_angle(90.0)?;
+//!
+//! let mut stepper = Stepper::new(step_pin, dir_pin, stepper::Config::default());
+//! stepper.move_to(3200).await?;
+//! ```
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+pub mod servo;
+pub mod stepper;
+
+/// Motion control-related errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Setting the PWM output or GPIO failed.
+    OutputAccess,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::OutputAccess => write!(f, "output access failed"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
@@ -0,0 +1,139 @@
+//! Provides control of hobby servos, positioned by the width of the pulses they receive every
+//! 20 ms.
+
+use embedded_hal::pwm::SetDutyCycle;
+
+use crate::Error;
+
+/// Frequency of the pulses, which the PWM output driving servos must be configured with.
+pub const FREQUENCY: u32 = 50;
+
+/// Period of the pulses, in microseconds.
+const PERIOD: u16 = 20_000;
+
+/// Configuration of a servo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// Width of the pulses moving the servo to the angle 0, in microseconds.
+    pub min_pulse_width: u16,
+    /// Width of the pulses moving the servo to its maximum angle, in microseconds.
+    pub max_pulse_width: u16,
+    /// Maximum angle of the servo, in degrees.
+    pub max_angle: u16,
+}
+
+impl Default for Config {
+    /// Returns the configuration of the standard pulse widths, which is within the range of
+    /// most servos.
+    fn default() -> Self {
+        Self {
+            min_pulse_width: 1000,
+            max_pulse_width: 2000,
+            max_angle: 180,
+        }
+    }
+}
+
+impl Config {
+    /// Returns the width of the pulses moving the servo to `angle`, in microseconds.
+    fn pulse_width(self, angle: f32) -> u16 {
+        let max_angle = f32::from(self.max_angle);
+        let angle = if angle.is_nan() {
+            0.0
+        } else {
+            angle.clamp(0.0, max_angle)
+        };
+        let range = f32::from(self.max_pulse_width) - f32::from(self.min_pulse_width);
+        let offset = if max_angle > 0.0 {
+            range * angle / max_angle
+        } else {
+            0.0
+        };
+        // The pulse width is between the minimum and maximum ones, and rounding is done by
+        // adding one half and truncating.
+        #[expect(clippy::cast_possible_truncation)]
+        #[expect(clippy::cast_sign_loss)]
+        let pulse_width = (f32::from(self.min_pulse_width) + offset + 0.5) as u16;
+        pulse_width
+    }
+}
+
+/// A servo, driven by a PWM output.
+pub struct Servo<P> {
+    pwm: P,
+    config: Config,
+}
+
+impl<P: SetDutyCycle> Servo<P> {
+    /// Returns a driver for the servo driven by `pwm`, whose frequency must be [`FREQUENCY`].
+    ///
+    /// The servo is not moved until an angle is set.
+    #[must_use]
+    pub fn new(pwm: P, config: Config) -> Self {
+        Self { pwm, config }
+    }
+
+    /// Moves the servo to `angle`, in degrees, which is clamped to the range of the servo.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::OutputAccess`] if setting the PWM output fails.
+    pub fn set_angle(&mut self, angle: f32) -> Result<(), Error> {
+        self.set_pulse_width(self.config.pulse_width(angle))
+    }
+
+    /// Sends pulses of `pulse_width` microseconds to the servo, eg. to calibrate it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::OutputAccess`] if setting the PWM output fails.
+    pub fn set_pulse_width(&mut self, pulse_width: u16) -> Result<(), Error> {
+        self.pwm
+            .set_duty_cycle_fraction(pulse_width.min(PERIOD), PERIOD)
+            .map_err(|_| Error::OutputAccess)
+    }
+
+    /// Stops sending pulses, which lets most servos turn freely.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::OutputAccess`] if setting the PWM output fails.
+    pub fn disable(&mut self) -> Result<(), Error> {
+        self.pwm
+            .set_duty_cycle_fully_off()
+            .map_err(|_| Error::OutputAccess)
+    }
+
+    /// Returns the PWM output.
+    pub fn release(self) -> P {
+        self.pwm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pulse_width() {
+        let config = Config::default();
+        assert_eq!(config.pulse_width(0.0), 1000);
+        assert_eq!(config.pulse_width(90.0), 1500);
+        assert_eq!(config.pulse_width(180.0), 2000);
+        assert_eq!(config.pulse_width(45.1), 1251);
+    }
+
+    #[test]
+    fn clamped() {
+        let config = Config {
+            min_pulse_width: 500,
+            max_pulse_width: 2500,
+            max_angle: 270,
+        };
+        assert_eq!(config.pulse_width(-10.0), 500);
+        assert_eq!(config.pulse_width(300.0), 2500);
+        assert_eq!(config.pulse_width(f32::NAN), 500);
+    }
+}
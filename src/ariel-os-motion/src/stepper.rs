@@ -0,0 +1,198 @@
+//! Provides control of stepper motors, through drivers with step and direction inputs, eg. the
+//! A4988 or the DRV8825.
+//!
+//! Motors are accelerated and decelerated along trapezoidal speed ramps, so that they do not
+//! stall nor skip steps.
+//! Steps are timed with the system timer, which is backed by a hardware timer; the precision of
+//! the step timing is thus bounded by its tick rate.
+
+use embassy_time::{Duration, Instant, Timer};
+use embedded_hal::digital::OutputPin;
+
+use crate::Error;
+
+/// Width of the step pulses, which is above the minimum of common drivers.
+const STEP_PULSE_WIDTH: Duration = Duration::from_micros(5);
+/// Delay between a change of direction and the next step pulse.
+const DIRECTION_SETUP_TIME: Duration = Duration::from_micros(5);
+
+/// Configuration of a stepper motor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// Maximum speed, in steps per second.
+    pub max_speed: u32,
+    /// Acceleration and deceleration, in steps per second squared; `0` disables the ramps.
+    pub acceleration: u32,
+    /// Whether the direction input is low when moving forward, instead of high.
+    pub invert_direction: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_speed: 1000,
+            acceleration: 2000,
+            invert_direction: false,
+        }
+    }
+}
+
+impl Config {
+    /// Returns the interval between the step `step` and the next one, out of a move of `steps`
+    /// steps, in microseconds.
+    ///
+    /// The speed reachable after accelerating over `n` steps is `sqrt(2 * acceleration * n)`.
+    fn step_interval(&self, step: u32, steps: u32) -> u64 {
+        let max_speed = u64::from(self.max_speed).max(1);
+        let speed = if self.acceleration == 0 {
+            max_speed
+        } else {
+            let acceleration = 2 * u64::from(self.acceleration);
+            let from_start = u64::from(step) + 1;
+            let to_end = u64::from(steps.saturating_sub(step)).max(1);
+            (acceleration * from_start)
+                .isqrt()
+                .min((acceleration * to_end).isqrt())
+                .min(max_speed)
+                .max(1)
+        };
+        1_000_000 / speed
+    }
+}
+
+/// A stepper motor, connected through a driver with step and direction inputs.
+pub struct Stepper<S, D> {
+    step: S,
+    direction: D,
+    config: Config,
+    position: i32,
+}
+
+impl<S: OutputPin, D: OutputPin> Stepper<S, D> {
+    /// Returns a driver for the stepper motor whose driver has its step and direction inputs
+    /// connected to `step` and `direction`.
+    ///
+    /// The initial position of the motor is `0`.
+    #[must_use]
+    pub fn new(step: S, direction: D, config: Config) -> Self {
+        Self {
+            step,
+            direction,
+            config,
+            position: 0,
+        }
+    }
+
+    /// Returns the position of the motor, in steps.
+    #[must_use]
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// Sets the position of the motor without moving it, eg. after homing it.
+    pub fn set_position(&mut self, position: i32) {
+        self.position = position;
+    }
+
+    /// Moves the motor to `position`, in steps.
+    ///
+    /// The position is kept up to date if the returned future is dropped, which stops the motor
+    /// without decelerating.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::OutputAccess`] if setting the step or direction output fails.
+    pub async fn move_to(&mut self, position: i32) -> Result<(), Error> {
+        let forward = position > self.position;
+        let steps = position.abs_diff(self.position);
+        if steps == 0 {
+            return Ok(());
+        }
+
+        let direction_high = forward != self.config.invert_direction;
+        self.direction
+            .set_state(direction_high.into())
+            .map_err(|_| Error::OutputAccess)?;
+        Timer::after(DIRECTION_SETUP_TIME).await;
+
+        // Steps are timed from the start of the move, so that timer latencies do not add up.
+        let mut deadline = Instant::now();
+        for step in 0..steps {
+            self.step.set_high().map_err(|_| Error::OutputAccess)?;
+            Timer::after(STEP_PULSE_WIDTH).await;
+            self.step.set_low().map_err(|_| Error::OutputAccess)?;
+            self.position = if forward {
+                self.position + 1
+            } else {
+                self.position - 1
+            };
+
+            deadline += Duration::from_micros(self.config.step_interval(step, steps));
+            Timer::at(deadline).await;
+        }
+        Ok(())
+    }
+
+    /// Moves the motor by `steps`, forward if positive.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::OutputAccess`] if setting the step or direction output fails.
+    pub async fn move_by(&mut self, steps: i32) -> Result<(), Error> {
+        self.move_to(self.position.saturating_add(steps)).await
+    }
+
+    /// Returns the step and direction outputs.
+    pub fn release(self) -> (S, D) {
+        (self.step, self.direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ramp() {
+        let config = Config::default();
+        let steps = 1000;
+        // Accelerating from sqrt(4000) steps per second.
+        assert_eq!(config.step_interval(0, steps), 1_000_000 / 63);
+        assert_eq!(config.step_interval(99, steps), 1_000_000 / 632);
+        // Cruising from 250 steps on.
+        assert_eq!(config.step_interval(249, steps), 1000);
+        assert_eq!(config.step_interval(500, steps), 1000);
+        // Decelerating symmetrically.
+        assert_eq!(config.step_interval(900, steps), 1_000_000 / 632);
+        assert_eq!(config.step_interval(999, steps), 1_000_000 / 63);
+    }
+
+    #[test]
+    fn short_move() {
+        let config = Config::default();
+        // The maximum speed is not reached, and the ramps meet in the middle.
+        let intervals = [0, 1, 2, 3].map(|step| config.step_interval(step, 4));
+        assert_eq!(
+            intervals,
+            [
+                1_000_000 / 63,
+                1_000_000 / 89,
+                1_000_000 / 89,
+                1_000_000 / 63
+            ]
+        );
+    }
+
+    #[test]
+    fn no_ramp() {
+        let config = Config {
+            acceleration: 0,
+            max_speed: 500,
+            ..Config::default()
+        };
+        assert_eq!(config.step_interval(0, 10), 2000);
+        assert_eq!(config.step_interval(9, 10), 2000);
+    }
+}
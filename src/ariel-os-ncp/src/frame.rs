@@ -0,0 +1,165 @@
+//! Provides the framing of messages on the link to the host.
+//!
+//! Frames are delimited as in SLIP (RFC 1055), and end with a CRC of their content.
+
+use embedded_io_async::Write;
+
+/// Byte delimiting frames.
+const END: u8 = 0xc0;
+/// Byte escaping the next byte.
+const ESC: u8 = 0xdb;
+/// Escaped [`END`].
+const ESC_END: u8 = 0xdc;
+/// Escaped [`ESC`].
+const ESC_ESC: u8 = 0xdd;
+
+/// Length of the CRC ending frames.
+pub(crate) const CRC_LEN: usize = 2;
+
+/// Returns the CRC-16/IBM-SDLC of `data`, which is sent least significant byte first.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xffff_u16;
+    for byte in data {
+        crc ^= u16::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ 0x8408
+            };
+        }
+    }
+    !crc
+}
+
+/// Reassembles frames of contents up to `N` bytes long from the bytes received from the host.
+pub(crate) struct Decoder<const N: usize> {
+    // Also holds the CRC.
+    buffer: [u8; N],
+    len: usize,
+    escaped: bool,
+    overflowed: bool,
+}
+
+impl<const N: usize> Decoder<N> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            buffer: [0; N],
+            len: 0,
+            escaped: false,
+            overflowed: false,
+        }
+    }
+
+    /// Processes a received `byte`, and returns the content of the frame it ends, if any.
+    ///
+    /// Frames that are too long, or whose CRC does not match, are dropped.
+    pub(crate) fn push(&mut self, byte: u8) -> Option<&[u8]> {
+        match byte {
+            END => {
+                let len = core::mem::take(&mut self.len);
+                let overflowed = core::mem::take(&mut self.overflowed);
+                self.escaped = false;
+                if overflowed {
+                    return None;
+                }
+                let (content, crc) = self
+                    .buffer
+                    .get(..len)?
+                    .split_at_checked(len.checked_sub(CRC_LEN)?)?;
+                (crc == crc16(content).to_le_bytes()).then_some(content)
+            }
+            ESC => {
+                self.escaped = true;
+                None
+            }
+            byte => {
+                let byte = match (core::mem::take(&mut self.escaped), byte) {
+                    (true, ESC_END) => END,
+                    (true, ESC_ESC) => ESC,
+                    (_, byte) => byte,
+                };
+                if let Some(slot) = self.buffer.get_mut(self.len) {
+                    *slot = byte;
+                    self.len += 1;
+                } else {
+                    self.overflowed = true;
+                }
+                None
+            }
+        }
+    }
+}
+
+/// Sends a frame of `content` to the host.
+///
+/// The frame also starts with a delimiter, so that the host discards any noise received before.
+///
+/// # Errors
+///
+/// Returns the error of the byte stream if writing fails.
+pub(crate) async fn send<W: Write>(writer: &mut W, content: &[u8]) -> Result<(), W::Error> {
+    writer.write_all(&[END]).await?;
+    let crc = crc16(content).to_le_bytes();
+    for data in [content, &crc] {
+        for run in data.split_inclusive(|&byte| byte == END || byte == ESC) {
+            match run.split_last() {
+                Some((&END, plain)) => {
+                    writer.write_all(plain).await?;
+                    writer.write_all(&[ESC, ESC_END]).await?;
+                }
+                Some((&ESC, plain)) => {
+                    writer.write_all(plain).await?;
+                    writer.write_all(&[ESC, ESC_ESC]).await?;
+                }
+                _ => writer.write_all(run).await?,
+            }
+        }
+    }
+    writer.write_all(&[END]).await?;
+    writer.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_value() {
+        assert_eq!(crc16(b"123456789"), 0x906e);
+    }
+
+    #[test]
+    fn round_trip() {
+        let content = [0x03, 0x2a, END, 0x00, ESC, ESC_END];
+        let mut buffer = [0; 32];
+        let mut writer = buffer.as_mut_slice();
+        embassy_futures::block_on(send(&mut writer, &content)).unwrap();
+        let unused = writer.len();
+        let encoded = buffer.get(..buffer.len() - unused).unwrap();
+        assert_eq!(encoded.first(), Some(&END));
+        assert!(!encoded.get(1..encoded.len() - 1).unwrap().contains(&END));
+
+        let mut decoder = Decoder::<16>::new();
+        let (&last, bytes) = encoded.split_last().unwrap();
+        for &byte in bytes {
+            assert_eq!(decoder.push(byte), None);
+        }
+        assert_eq!(decoder.push(last), Some(&content[..]));
+    }
+
+    #[test]
+    fn corrupted() {
+        let mut decoder = Decoder::<16>::new();
+        for byte in [0x03, 0x2a, 0x00, 0x00] {
+            decoder.push(byte);
+        }
+        assert_eq!(decoder.push(END), None);
+
+        let mut decoder = Decoder::<2>::new();
+        for byte in [0x01, 0x02, 0x03] {
+            decoder.push(byte);
+        }
+        assert_eq!(decoder.push(END), None);
+    }
+}
@@ -0,0 +1,507 @@
+//! Provides a network co-processor (NCP) mode, in which the device exposes its network stack to a
+//! host microcontroller.
+//!
+//! The host opens UDP sockets of the network stack of the device, and sends and receives
+//! datagrams through them, over a link such as a UART. This turns cheap Wi-Fi chips running
+//! Ariel OS into drop-in network co-processors. CoAP runs over these sockets: the host binds a
+//! socket, eg. to port 5683, and runs its CoAP client or server over it.
+//!
+//! [`run()`] serves the host on any byte stream implementing the [`embedded_io_async`] traits,
+//! split into its receiving and transmitting halves:
+//!
+//! ```ignore
+//! let stack = ariel_os::net::network_stack().await.unwrap();
+//! let mut resources = ariel_os::ncp::Resources::new();
+//! let (rx, tx) = uart.split();
+//! ariel_os::ncp::run(stack, &mut resources, rx, tx).await?;
+//! ```
+//!
+//! # Protocol
+//!
+//! Frames are delimited as in SLIP (RFC 1055), and consist of a command, a token, a body, and the
+//! CRC-16/IBM-SDLC (as used by HDLC) of these, least significant byte first. Frames that are
+//! corrupted are dropped, and the host retransmits requests that are not answered in time.
+//!
+//! The host sends requests, with a token of its choice, which the device answers with a response
+//! of the same token and the command with its most significant bit set. The body of responses
+//! starts with a status: 0 when the request succeeded, 1 when it is malformed, 2 when its command
+//! is unknown, 3 when the socket is not available, 4 when binding the socket failed, and 5 when
+//! sending the datagram failed. The rest of the body is only present on success.
+//!
+//! | Command | Request body | Response body |
+//! |---------|--------------|---------------|
+//! | `0x01`: status | — | flags: link up (bit 0), IPv4 configured (bit 1), IPv6 configured (bit 2), then the configured IPv4 and IPv6 addresses, each followed by its prefix length |
+//! | `0x02`: bind a UDP socket | local port | socket |
+//! | `0x03`: send a UDP datagram | socket, remote endpoint, datagram | — |
+//! | `0x04`: close a UDP socket | socket | — |
+//!
+//! The device sends the datagrams received on bound sockets as `0x40` events, with a token of 0,
+//! and a body made of the socket, the remote endpoint, and the datagram.
+//!
+//! Ports are sent most significant byte first. Endpoints consist of the IP version (4 or 6), the
+//! address, and the port.
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+mod frame;
+
+use core::{
+    cell::RefCell,
+    convert::Infallible,
+    future::poll_fn,
+    net::{Ipv4Addr, Ipv6Addr},
+    task::Poll,
+};
+
+use embassy_futures::select::{Either, select};
+use embassy_net::{
+    IpAddress, IpEndpoint, Stack,
+    udp::{PacketMetadata, UdpSocket},
+};
+use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
+use embedded_io_async::{Read, Write};
+
+use crate::frame::Decoder;
+
+/// Number of UDP sockets available to the host.
+const SOCKETS: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_NCP_UDP_SOCKETS",
+    4,
+    "number of UDP sockets available to the host of the network co-processor"
+);
+
+/// Maximum length of the datagrams sent and received on behalf of the host.
+const MAX_DATAGRAM_LEN: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_NCP_MAX_DATAGRAM_LEN",
+    1280,
+    "maximum length of the datagrams of the network co-processor"
+);
+
+/// Number of datagrams buffered by each socket, in each direction.
+const PACKETS: usize = 4;
+
+/// Maximum length of encoded endpoints: the IP version, an IPv6 address, and a port.
+const MAX_ENDPOINT_LEN: usize = 1 + 16 + 2;
+
+/// Length of the command and the token of frames.
+const HEADER_LEN: usize = 2;
+
+/// Maximum length of the headers of frames carrying datagrams, up to the datagram.
+const MAX_DATAGRAM_HEADER_LEN: usize = HEADER_LEN + 1 + MAX_ENDPOINT_LEN;
+
+/// Maximum length of the content of frames.
+const MAX_FRAME_LEN: usize = MAX_DATAGRAM_HEADER_LEN + MAX_DATAGRAM_LEN;
+
+/// Maximum length of responses, which is that of status responses.
+const MAX_RESPONSE_LEN: usize = HEADER_LEN + 1 + 1 + (4 + 1) + (16 + 1);
+
+/// Commands, and events.
+mod command {
+    pub const STATUS: u8 = 0x01;
+    pub const UDP_BIND: u8 = 0x02;
+    pub const UDP_SEND: u8 = 0x03;
+    pub const UDP_CLOSE: u8 = 0x04;
+
+    pub const UDP_RECEIVED: u8 = 0x40;
+
+    /// Bit set in the command of responses.
+    pub const RESPONSE: u8 = 0x80;
+}
+
+/// Flags of status responses.
+mod flags {
+    pub const LINK_UP: u8 = 1 << 0;
+    pub const IPV4: u8 = 1 << 1;
+    pub const IPV6: u8 = 1 << 2;
+}
+
+/// Statuses of responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum Status {
+    Success = 0,
+    Malformed = 1,
+    UnknownCommand = 2,
+    UnavailableSocket = 3,
+    BindFailed = 4,
+    SendFailed = 5,
+}
+
+/// The buffers of the sockets available to the host.
+///
+/// At several kilobytes, these are best kept in a `static`, eg. through a `StaticCell`.
+pub struct Resources {
+    sockets: [SocketBuffers; SOCKETS],
+}
+
+impl Resources {
+    /// Creates the buffers.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            sockets: [const { SocketBuffers::new() }; SOCKETS],
+        }
+    }
+}
+
+impl Default for Resources {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl core::fmt::Debug for Resources {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Resources").finish_non_exhaustive()
+    }
+}
+
+/// The buffers of a socket.
+struct SocketBuffers {
+    rx_metadata: [PacketMetadata; PACKETS],
+    rx: [u8; MAX_DATAGRAM_LEN],
+    tx_metadata: [PacketMetadata; PACKETS],
+    tx: [u8; MAX_DATAGRAM_LEN],
+}
+
+impl SocketBuffers {
+    const fn new() -> Self {
+        Self {
+            rx_metadata: [PacketMetadata::EMPTY; PACKETS],
+            rx: [0; MAX_DATAGRAM_LEN],
+            tx_metadata: [PacketMetadata::EMPTY; PACKETS],
+            tx: [0; MAX_DATAGRAM_LEN],
+        }
+    }
+
+    /// Creates an unbound socket of `stack` on the buffers.
+    fn socket<'a>(&'a mut self, stack: Stack<'a>) -> UdpSocket<'a> {
+        UdpSocket::new(
+            stack,
+            &mut self.rx_metadata,
+            &mut self.rx,
+            &mut self.tx_metadata,
+            &mut self.tx,
+        )
+    }
+}
+
+/// The UDP sockets available to the host, shared between the processing of requests and the
+/// forwarding of received datagrams.
+type Sockets<'a> = RefCell<[UdpSocket<'a>; SOCKETS]>;
+
+/// Serves the host, exposing `stack` to it through the link made of `rx` and `tx`.
+///
+/// Returns once the byte stream ends, which closes all sockets.
+///
+/// # Errors
+///
+/// Returns the error of the byte stream if reading or writing fails.
+pub async fn run<'a, R: Read, W: Write<Error = R::Error>>(
+    stack: Stack<'a>,
+    resources: &'a mut Resources,
+    mut rx: R,
+    tx: W,
+) -> Result<(), R::Error> {
+    let sockets = RefCell::new(
+        resources
+            .sockets
+            .each_mut()
+            .map(|buffers| buffers.socket(stack)),
+    );
+    let tx = Mutex::<NoopRawMutex, _>::new(tx);
+
+    match select(
+        serve_requests(stack, &sockets, &mut rx, &tx),
+        forward_datagrams(&sockets, &tx),
+    )
+    .await
+    {
+        Either::First(result) => result,
+        Either::Second(Err(err)) => Err(err),
+    }
+}
+
+/// Processes the requests received from the host on `rx`, and sends the responses on `tx`.
+///
+/// Returns once the byte stream ends.
+///
+/// # Errors
+///
+/// Returns the error of the byte stream if reading or writing fails.
+async fn serve_requests<R: Read, W: Write<Error = R::Error>>(
+    stack: Stack<'_>,
+    sockets: &Sockets<'_>,
+    rx: &mut R,
+    tx: &Mutex<NoopRawMutex, W>,
+) -> Result<(), R::Error> {
+    let mut decoder = Decoder::<{ MAX_FRAME_LEN + frame::CRC_LEN }>::new();
+    let mut bytes = [0; 64];
+    loop {
+        let len = rx.read(&mut bytes).await?;
+        if len == 0 {
+            return Ok(());
+        }
+        for &byte in bytes.get(..len).unwrap_or_default() {
+            let Some(request) = decoder.push(byte) else {
+                continue;
+            };
+            let Some((&[command, token], body)) = request.split_first_chunk() else {
+                continue;
+            };
+
+            let mut response = [0; MAX_RESPONSE_LEN];
+            let (status, len) = match process(stack, sockets, command, body, &mut response).await {
+                Ok(len) => (Status::Success, len),
+                Err(status) => (status, 0),
+            };
+            let Some((header, _)) = response.split_first_chunk_mut() else {
+                continue;
+            };
+            *header = [command | command::RESPONSE, token, status as u8];
+            let response = response.get(..HEADER_LEN + 1 + len).unwrap_or_default();
+            frame::send(&mut *tx.lock().await, response).await?;
+        }
+    }
+}
+
+/// Processes a request of `command` with `body`, and writes the body of its response after the
+/// header of `response`.
+///
+/// Returns the length of the body of the response, without the status.
+///
+/// # Errors
+///
+/// Returns the status of the failure if the request fails.
+async fn process(
+    stack: Stack<'_>,
+    sockets: &Sockets<'_>,
+    command: u8,
+    body: &[u8],
+    response: &mut [u8],
+) -> Result<usize, Status> {
+    let mut response = Writer::new(response.get_mut(HEADER_LEN + 1..).unwrap_or_default());
+    match command {
+        command::STATUS => {
+            if !body.is_empty() {
+                return Err(Status::Malformed);
+            }
+            let ipv4 = stack.config_v4().map(|config| config.address);
+            let ipv6 = stack.config_v6().map(|config| config.address);
+            let mut flags = 0;
+            if stack.is_link_up() {
+                flags |= flags::LINK_UP;
+            }
+            if ipv4.is_some() {
+                flags |= flags::IPV4;
+            }
+            if ipv6.is_some() {
+                flags |= flags::IPV6;
+            }
+            response.put(&[flags])?;
+            if let Some(cidr) = ipv4 {
+                response.put(&cidr.address().octets())?;
+                response.put(&[cidr.prefix_len()])?;
+            }
+            if let Some(cidr) = ipv6 {
+                response.put(&cidr.address().octets())?;
+                response.put(&[cidr.prefix_len()])?;
+            }
+        }
+        command::UDP_BIND => {
+            let port = u16::from_be_bytes(body.try_into().map_err(|_| Status::Malformed)?);
+            let mut sockets = sockets.borrow_mut();
+            let (index, socket) = sockets
+                .iter_mut()
+                .enumerate()
+                .find(|(_, socket)| !socket.is_open())
+                .ok_or(Status::UnavailableSocket)?;
+            socket.bind(port).map_err(|_| Status::BindFailed)?;
+            response.put(&[u8::try_from(index).map_err(|_| Status::UnavailableSocket)?])?;
+        }
+        command::UDP_SEND => {
+            let (&index, body) = body.split_first().ok_or(Status::Malformed)?;
+            let (endpoint, datagram) = parse_endpoint(body).ok_or(Status::Malformed)?;
+            poll_fn(|cx| {
+                let sockets = sockets.borrow();
+                let Some(socket) = sockets
+                    .get(usize::from(index))
+                    .filter(|socket| socket.is_open())
+                else {
+                    return Poll::Ready(Err(Status::UnavailableSocket));
+                };
+                socket
+                    .poll_send_to(datagram, endpoint, cx)
+                    .map_err(|_| Status::SendFailed)
+            })
+            .await?;
+        }
+        command::UDP_CLOSE => {
+            let &[index] = body else {
+                return Err(Status::Malformed);
+            };
+            sockets
+                .borrow_mut()
+                .get_mut(usize::from(index))
+                .filter(|socket| socket.is_open())
+                .ok_or(Status::UnavailableSocket)?
+                .close();
+        }
+        _ => return Err(Status::UnknownCommand),
+    }
+    Ok(response.len)
+}
+
+/// Forwards the datagrams received on the sockets to the host on `tx`.
+///
+/// # Errors
+///
+/// Returns the error of the byte stream if writing fails.
+async fn forward_datagrams<W: Write>(
+    sockets: &Sockets<'_>,
+    tx: &Mutex<NoopRawMutex, W>,
+) -> Result<Infallible, W::Error> {
+    let mut event = [0; MAX_FRAME_LEN];
+    loop {
+        let (index, endpoint, len) = poll_fn(|cx| {
+            let datagram = event.get_mut(MAX_DATAGRAM_HEADER_LEN..).unwrap_or_default();
+            for (index, socket) in sockets.borrow().iter().enumerate() {
+                loop {
+                    match socket.poll_recv_from(datagram, cx) {
+                        Poll::Ready(Ok((len, metadata))) => {
+                            return Poll::Ready((index, metadata.endpoint, len));
+                        }
+                        // Truncated datagrams are dropped.
+                        Poll::Ready(Err(_)) => {}
+                        Poll::Pending => break,
+                    }
+                }
+            }
+            Poll::Pending
+        })
+        .await;
+
+        let mut header = [0; MAX_DATAGRAM_HEADER_LEN];
+        let mut writer = Writer::new(&mut header);
+        let written = writer
+            .put(&[
+                command::UDP_RECEIVED,
+                0,
+                u8::try_from(index).unwrap_or_default(),
+            ])
+            .and_then(|()| put_endpoint(&mut writer, endpoint));
+        if written.is_err() {
+            continue;
+        }
+        let header_len = writer.len;
+        let header = header.get(..header_len).unwrap_or_default();
+
+        // The header is placed right before the datagram.
+        let start = MAX_DATAGRAM_HEADER_LEN - header.len();
+        let Some(frame) = event.get_mut(start..MAX_DATAGRAM_HEADER_LEN + len) else {
+            continue;
+        };
+        if let Some(slot) = frame.get_mut(..header.len()) {
+            slot.copy_from_slice(header);
+        }
+        frame::send(&mut *tx.lock().await, frame).await?;
+    }
+}
+
+/// Parses an endpoint at the start of `data`, and returns it with the rest of `data`.
+fn parse_endpoint(data: &[u8]) -> Option<(IpEndpoint, &[u8])> {
+    let (&version, data) = data.split_first()?;
+    let (address, data) = match version {
+        4 => {
+            let (octets, data) = data.split_first_chunk::<4>()?;
+            (IpAddress::Ipv4(Ipv4Addr::from(*octets)), data)
+        }
+        6 => {
+            let (octets, data) = data.split_first_chunk::<16>()?;
+            (IpAddress::Ipv6(Ipv6Addr::from(*octets)), data)
+        }
+        _ => return None,
+    };
+    let (port, data) = data.split_first_chunk()?;
+    Some((IpEndpoint::new(address, u16::from_be_bytes(*port)), data))
+}
+
+/// Writes `endpoint` to `writer`.
+///
+/// # Errors
+///
+/// Returns an error if the buffer of `writer` is too small.
+fn put_endpoint(writer: &mut Writer<'_>, endpoint: IpEndpoint) -> Result<(), Status> {
+    match endpoint.addr {
+        IpAddress::Ipv4(address) => {
+            writer.put(&[4])?;
+            writer.put(&address.octets())?;
+        }
+        IpAddress::Ipv6(address) => {
+            writer.put(&[6])?;
+            writer.put(&address.octets())?;
+        }
+    }
+    writer.put(&endpoint.port.to_be_bytes())
+}
+
+/// Writes bytes to a buffer.
+struct Writer<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn new(buffer: &'a mut [u8]) -> Self {
+        Self { buffer, len: 0 }
+    }
+
+    /// Appends `bytes` to the written bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the buffer is too small, which only happens when the buffer is not
+    /// sized for the largest response.
+    fn put(&mut self, bytes: &[u8]) -> Result<(), Status> {
+        let end = self.len + bytes.len();
+        self.buffer
+            .get_mut(self.len..end)
+            .ok_or(Status::Malformed)?
+            .copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoints() {
+        let endpoint = IpEndpoint::new(IpAddress::Ipv4(Ipv4Addr::new(192, 0, 2, 1)), 5683);
+        let mut buffer = [0; MAX_ENDPOINT_LEN];
+        let mut writer = Writer::new(&mut buffer);
+        put_endpoint(&mut writer, endpoint).unwrap();
+        let len = writer.len;
+        assert_eq!(
+            buffer.get(..len).unwrap(),
+            &[4, 192, 0, 2, 1, 0x16, 0x33][..]
+        );
+        assert_eq!(
+            parse_endpoint(&[4, 192, 0, 2, 1, 0x16, 0x33, 0xff]),
+            Some((endpoint, &[0xff][..]))
+        );
+
+        let endpoint = IpEndpoint::new(IpAddress::Ipv6(Ipv6Addr::LOCALHOST), 5683);
+        let mut writer = Writer::new(&mut buffer);
+        put_endpoint(&mut writer, endpoint).unwrap();
+        assert_eq!(writer.len, MAX_ENDPOINT_LEN);
+        assert_eq!(parse_endpoint(&buffer), Some((endpoint, &[][..])));
+
+        assert_eq!(parse_endpoint(&[4, 192, 0, 2]), None);
+        assert_eq!(parse_endpoint(&[5, 192, 0, 2, 1, 0x16, 0x33]), None);
+    }
+}
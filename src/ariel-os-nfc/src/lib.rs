@@ -0,0 +1,76 @@
+//! Provides NFC tag emulation, to hand NDEF messages to phones and other NFC readers.
+//!
+//! The device appears as an NFC Forum tag holding an [NDEF](ndef) message, eg. a provisioning URL
+//! or a credential, which readers read when tapped against it.
+//! Tags are emulated by a [`TagEmulator`], which is implemented by the NFCT peripheral of the nRF
+//! MCUs that have one, and by the drivers of external NFC controllers, eg. [`pn7150`].
+//!
+//! ```ignore
+//! let mut buf = [0; 128];
+//! let len = ndef::encode(&[ndef::Record::uri("https://example.com/setup")], &mut buf)?;
+//!
+//! loop {
+//!     nfct.emulate(&buf[..len]).await?;
+//! }
+//! ```
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+pub mod ndef;
+#[cfg(feature = "pn7150")]
+pub mod pn7150;
+pub mod type2;
+pub mod type4;
+
+/// Emulates an NFC Forum tag.
+pub trait TagEmulator {
+    /// Emulates a read-only tag holding the NDEF `message`, until a reader has selected the tag
+    /// and left the field again.
+    ///
+    /// The message is usually encoded with [`ndef::encode()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MessageTooLong`] if the tag cannot hold the message, and an error if
+    /// communicating with the NFC controller fails.
+    fn emulate(&mut self, message: &[u8]) -> impl Future<Output = Result<(), Error>>;
+}
+
+/// NFC-related errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The buffer is too small for the encoded message.
+    BufferTooSmall,
+    /// The message is too long for the tag.
+    MessageTooLong,
+    /// Communicating with the NFC controller failed.
+    ControllerAccess,
+    /// The NFC controller did not respond in time.
+    Timeout,
+    /// The NFC controller sent an unexpected response.
+    UnexpectedResponse,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::BufferTooSmall => write!(f, "buffer too small"),
+            Self::MessageTooLong => write!(f, "message too long for the tag"),
+            Self::ControllerAccess => write!(f, "NFC controller access failed"),
+            Self::Timeout => write!(f, "NFC controller timed out"),
+            Self::UnexpectedResponse => write!(f, "unexpected response from the NFC controller"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+#[cfg(feature = "pn7150")]
+impl From<embassy_time::TimeoutError> for Error {
+    fn from(_: embassy_time::TimeoutError) -> Self {
+        Self::Timeout
+    }
+}
@@ -0,0 +1,298 @@
+//! Provides encoding of NFC Data Exchange Format (NDEF) messages.
+//!
+//! An NDEF message is a sequence of [`Record`]s, each carrying a payload and its type, which
+//! readers use to dispatch it: phones open URI records in a browser, and hand records of other
+//! types to the application registered for them.
+
+use crate::Error;
+
+/// Record header flag marking the first record of a message.
+const MESSAGE_BEGIN: u8 = 0x80;
+/// Record header flag marking the last record of a message.
+const MESSAGE_END: u8 = 0x40;
+/// Record header flag marking short records, whose payload length fits into one byte.
+const SHORT_RECORD: u8 = 0x10;
+/// Record header flag marking records that have an ID.
+const ID_LENGTH_PRESENT: u8 = 0x08;
+
+/// Type Name Format of empty records.
+const TNF_EMPTY: u8 = 0x00;
+/// Type Name Format of records whose type is an NFC Forum well-known type.
+const TNF_WELL_KNOWN: u8 = 0x01;
+/// Type Name Format of records whose type is a media type.
+const TNF_MEDIA: u8 = 0x02;
+/// Type Name Format of records whose type is an NFC Forum external type.
+const TNF_EXTERNAL: u8 = 0x04;
+
+/// Well-known type of URI records.
+const URI_TYPE: &[u8] = b"U";
+
+/// URI prefixes abbreviated by URI records, indexed by their identifier code.
+const URI_PREFIXES: [&str; 36] = [
+    "",
+    "http://www.",
+    "https://www.",
+    "http://",
+    "https://",
+    "tel:",
+    "mailto:",
+    "ftp://anonymous:anonymous@",
+    "ftp://ftp.",
+    "ftps://",
+    "sftp://",
+    "smb://",
+    "nfs://",
+    "ftp://",
+    "dav://",
+    "news:",
+    "telnet://",
+    "imap:",
+    "rtsp://",
+    "urn:",
+    "pop:",
+    "sip:",
+    "sips:",
+    "tftp:",
+    "btspp://",
+    "btl2cap://",
+    "btgoep://",
+    "tcpobex://",
+    "irdaobex://",
+    "file://",
+    "urn:epc:id:",
+    "urn:epc:tag:",
+    "urn:epc:pat:",
+    "urn:epc:raw:",
+    "urn:epc:",
+    "urn:nfc:",
+];
+
+/// A record of an NDEF message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record<'a> {
+    tnf: u8,
+    kind: &'a [u8],
+    id: &'a [u8],
+    /// Byte prepended to the payload.
+    prefix: Option<u8>,
+    payload: &'a [u8],
+}
+
+impl<'a> Record<'a> {
+    /// Returns a URI record, eg. of an URL which phones open in a browser.
+    ///
+    /// Common URI prefixes, eg. `https://`, are abbreviated.
+    #[must_use]
+    pub fn uri(uri: &'a str) -> Self {
+        let (code, prefix) = (0u8..)
+            .zip(URI_PREFIXES)
+            .filter(|(_, prefix)| uri.starts_with(prefix))
+            .max_by_key(|(_, prefix)| prefix.len())
+            .unwrap_or((0, ""));
+
+        Self {
+            tnf: TNF_WELL_KNOWN,
+            kind: URI_TYPE,
+            id: &[],
+            prefix: Some(code),
+            payload: uri.get(prefix.len()..).unwrap_or_default().as_bytes(),
+        }
+    }
+
+    /// Returns a record of the media type `media_type`, eg. `application/cbor`.
+    #[must_use]
+    pub fn mime(media_type: &'a str, payload: &'a [u8]) -> Self {
+        Self {
+            tnf: TNF_MEDIA,
+            kind: media_type.as_bytes(),
+            id: &[],
+            prefix: None,
+            payload,
+        }
+    }
+
+    /// Returns a record of the NFC Forum external type `kind`, which has the form
+    /// `domain:type`, eg. `example.com:credential`.
+    #[must_use]
+    pub fn external(kind: &'a str, payload: &'a [u8]) -> Self {
+        Self {
+            tnf: TNF_EXTERNAL,
+            kind: kind.as_bytes(),
+            id: &[],
+            prefix: None,
+            payload,
+        }
+    }
+
+    /// Sets the ID of the record, which other records can refer to.
+    #[must_use]
+    pub fn with_id(self, id: &'a [u8]) -> Self {
+        Self { id, ..self }
+    }
+
+    fn payload_len(&self) -> usize {
+        usize::from(self.prefix.is_some()) + self.payload.len()
+    }
+}
+
+/// Encodes an NDEF message consisting of `records` into `buf`, and returns its length.
+///
+/// An empty message is encoded as a single empty record.
+///
+/// # Errors
+///
+/// Returns [`Error::BufferTooSmall`] if the message does not fit into `buf`, and
+/// [`Error::MessageTooLong`] if a type or ID is longer than 255 bytes.
+pub fn encode(records: &[Record<'_>], buf: &mut [u8]) -> Result<usize, Error> {
+    let empty = [Record {
+        tnf: TNF_EMPTY,
+        kind: &[],
+        id: &[],
+        prefix: None,
+        payload: &[],
+    }];
+    let records = if records.is_empty() { &empty } else { records };
+
+    let mut writer = Writer { buf, len: 0 };
+    let last = records.len() - 1;
+    for (i, record) in records.iter().enumerate() {
+        let payload_len = record.payload_len();
+        let short_payload_len = u8::try_from(payload_len).ok();
+
+        let mut header = record.tnf;
+        if i == 0 {
+            header |= MESSAGE_BEGIN;
+        }
+        if i == last {
+            header |= MESSAGE_END;
+        }
+        if short_payload_len.is_some() {
+            header |= SHORT_RECORD;
+        }
+        if !record.id.is_empty() {
+            header |= ID_LENGTH_PRESENT;
+        }
+        writer.push(header)?;
+
+        writer.push(u8::try_from(record.kind.len()).map_err(|_| Error::MessageTooLong)?)?;
+        if let Some(payload_len) = short_payload_len {
+            writer.push(payload_len)?;
+        } else {
+            let payload_len = u32::try_from(payload_len).map_err(|_| Error::MessageTooLong)?;
+            writer.push_slice(&payload_len.to_be_bytes())?;
+        }
+        if !record.id.is_empty() {
+            writer.push(u8::try_from(record.id.len()).map_err(|_| Error::MessageTooLong)?)?;
+        }
+
+        writer.push_slice(record.kind)?;
+        writer.push_slice(record.id)?;
+        if let Some(prefix) = record.prefix {
+            writer.push(prefix)?;
+        }
+        writer.push_slice(record.payload)?;
+    }
+
+    Ok(writer.len)
+}
+
+/// Appends bytes to a buffer.
+struct Writer<'b> {
+    buf: &'b mut [u8],
+    len: usize,
+}
+
+impl Writer<'_> {
+    /// # Errors
+    ///
+    /// Returns [`Error::BufferTooSmall`] if the buffer is full.
+    fn push(&mut self, byte: u8) -> Result<(), Error> {
+        self.push_slice(&[byte])
+    }
+
+    /// # Errors
+    ///
+    /// Returns [`Error::BufferTooSmall`] if the bytes do not fit into the buffer.
+    fn push_slice(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let end = self.len + bytes.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(Error::BufferTooSmall)?
+            .copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded<'b>(records: &[Record<'_>], buf: &'b mut [u8]) -> &'b [u8] {
+        let len = encode(records, buf).unwrap();
+        buf.get(..len).unwrap()
+    }
+
+    #[test]
+    fn uri() {
+        let mut buf = [0; 32];
+        assert_eq!(
+            encoded(&[Record::uri("https://example.com")], &mut buf),
+            b"\xd1\x01\x0cU\x04example.com"
+        );
+    }
+
+    #[test]
+    fn uri_longest_prefix() {
+        let mut buf = [0; 32];
+        assert_eq!(
+            encoded(&[Record::uri("urn:epc:id:123")], &mut buf),
+            b"\xd1\x01\x04U\x1e123"
+        );
+
+        assert_eq!(
+            encoded(&[Record::uri("coap://[::1]")], &mut buf),
+            b"\xd1\x01\x0dU\x00coap://[::1]"
+        );
+    }
+
+    #[test]
+    fn records() {
+        let mut buf = [0; 64];
+        let records = [
+            Record::uri("tel:123"),
+            Record::mime("application/cbor", &[0xa0]).with_id(b"c"),
+            Record::external("example.com:x", &[]),
+        ];
+        assert_eq!(
+            encoded(&records, &mut buf),
+            b"\x91\x01\x04U\x05123\
+              \x1a\x10\x01\x01application/cbor\x63\xa0\
+              \x54\x0d\x00example.com:x"
+        );
+    }
+
+    #[test]
+    fn long_payload() {
+        let payload = [0x55; 300];
+        let mut buf = [0; 320];
+        let encoded = encoded(&[Record::mime("a/b", &payload)], &mut buf);
+        assert_eq!(encoded.len(), 1 + 1 + 4 + 3 + 300);
+        assert!(encoded.starts_with(b"\xc2\x03\x00\x00\x01\x2ca/b"));
+    }
+
+    #[test]
+    fn empty() {
+        let mut buf = [0; 8];
+        assert_eq!(encoded(&[], &mut buf), b"\xd0\x00\x00");
+    }
+
+    #[test]
+    fn buffer_too_small() {
+        let mut buf = [0; 8];
+        assert_eq!(
+            encode(&[Record::uri("https://example.com")], &mut buf),
+            Err(Error::BufferTooSmall)
+        );
+    }
+}
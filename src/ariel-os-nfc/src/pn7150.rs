@@ -0,0 +1,268 @@
+//! Driver for the NXP `PN7150` NFC controller, connected through I2C, which emulates
+//! [Type 4 Tags](crate::type4).
+//!
+//! Besides the I2C bus, the controller needs its IRQ output, which signals that it has data to
+//! send, and its VEN input, which enables it.
+//! The controller talks the NFC Controller Interface (NCI) protocol, and handles the ISO-DEP
+//! protocol itself.
+
+use embassy_time::{Duration, Timer, with_timeout};
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::{digital::Wait, i2c::I2c};
+
+use crate::{
+    Error, TagEmulator,
+    type4::{MAX_RESPONSE_LEN, Type4Tag},
+};
+
+/// Maximum duration of the processing of commands by the controller.
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Length of the header of NCI packets.
+const HEADER_LEN: usize = 3;
+/// Maximum length of NCI packets, whose payload length is encoded on one byte.
+const MAX_PACKET_LEN: usize = HEADER_LEN + 255;
+
+/// Message type of data packets.
+const MT_DATA: u8 = 0x00;
+/// Message type of control packets carrying commands.
+const MT_COMMAND: u8 = 0x20;
+/// Message type of control packets carrying responses.
+const MT_RESPONSE: u8 = 0x40;
+/// Message type of control packets carrying notifications.
+const MT_NOTIFICATION: u8 = 0x60;
+/// Mask of the message type, in the first byte of the header.
+const MT_MASK: u8 = 0xe0;
+
+/// Group and opcode identifiers of the control messages.
+mod opcode {
+    pub const CORE_RESET: (u8, u8) = (0x00, 0x00);
+    pub const CORE_INIT: (u8, u8) = (0x00, 0x01);
+    pub const CORE_SET_CONFIG: (u8, u8) = (0x00, 0x02);
+    pub const RF_DISCOVER_MAP: (u8, u8) = (0x01, 0x00);
+    pub const RF_SET_LISTEN_MODE_ROUTING: (u8, u8) = (0x01, 0x01);
+    pub const RF_DISCOVER: (u8, u8) = (0x01, 0x03);
+    pub const RF_INTF_ACTIVATED: (u8, u8) = (0x01, 0x05);
+    pub const RF_DEACTIVATE: (u8, u8) = (0x01, 0x06);
+    pub const PROPRIETARY_ACT: (u8, u8) = (0x0f, 0x02);
+}
+
+/// Status of successful commands.
+const STATUS_OK: u8 = 0x00;
+
+/// Configuration of the controller, before it starts listening.
+const INIT_COMMANDS: [((u8, u8), &[u8]); 6] = [
+    // Keep the configuration.
+    (opcode::CORE_RESET, &[0x01]),
+    (opcode::CORE_INIT, &[]),
+    // Enable the proprietary extensions.
+    (opcode::PROPRIETARY_ACT, &[]),
+    // Announce ISO-DEP support in the SEL_RES (LA_SEL_INFO).
+    (opcode::CORE_SET_CONFIG, &[0x01, 0x32, 0x01, 0x20]),
+    // Map ISO-DEP in listen mode to the ISO-DEP RF interface.
+    (opcode::RF_DISCOVER_MAP, &[0x01, 0x04, 0x02, 0x02]),
+    // Route ISO-DEP to the host.
+    (
+        opcode::RF_SET_LISTEN_MODE_ROUTING,
+        &[0x00, 0x01, 0x01, 0x03, 0x00, 0x01, 0x04],
+    ),
+];
+
+/// Listens in NFC-A passive listen mode.
+const DISCOVER_PARAMETERS: &[u8] = &[0x01, 0x80, 0x01];
+
+/// Configuration of a `PN7150` controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// I2C address of the device, which depends on its address pins.
+    pub address: u8,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self { address: 0x28 }
+    }
+}
+
+/// A `PN7150` NFC controller.
+pub struct Pn7150<I, Q, V> {
+    i2c: I,
+    irq: Q,
+    ven: V,
+    address: u8,
+    listening: bool,
+    buf: [u8; MAX_PACKET_LEN],
+}
+
+impl<I: I2c, Q: Wait, V: OutputPin> Pn7150<I, Q, V> {
+    /// Returns a driver for the controller connected through `i2c`, `irq` and `ven`.
+    ///
+    /// The controller is initialized when first emulating a tag.
+    #[must_use]
+    pub fn new(i2c: I, irq: Q, ven: V, config: Config) -> Self {
+        Self {
+            i2c,
+            irq,
+            ven,
+            address: config.address,
+            listening: false,
+            buf: [0; MAX_PACKET_LEN],
+        }
+    }
+
+    /// Returns the I2C bus and pins.
+    pub fn release(self) -> (I, Q, V) {
+        (self.i2c, self.irq, self.ven)
+    }
+
+    /// Resets the controller, and makes it listen for readers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if communicating with the controller fails, or it rejects a command.
+    async fn start(&mut self) -> Result<(), Error> {
+        self.ven.set_low().map_err(|_| Error::ControllerAccess)?;
+        Timer::after_millis(10).await;
+        self.ven.set_high().map_err(|_| Error::ControllerAccess)?;
+        Timer::after_millis(10).await;
+
+        for (opcode, parameters) in INIT_COMMANDS {
+            self.command(opcode, parameters).await?;
+        }
+        self.command(opcode::RF_DISCOVER, DISCOVER_PARAMETERS)
+            .await?;
+        self.listening = true;
+        Ok(())
+    }
+
+    /// Sends a command, and waits for its response, skipping notifications.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnexpectedResponse`] if the controller rejects the command, and an error if
+    /// communicating with it fails.
+    async fn command(&mut self, (gid, oid): (u8, u8), parameters: &[u8]) -> Result<(), Error> {
+        self.send(MT_COMMAND | gid, oid, parameters).await?;
+
+        loop {
+            let len = with_timeout(RESPONSE_TIMEOUT, self.receive()).await??;
+            let packet = self.buf.get(..len).unwrap_or_default();
+            let &[first, second, _, status, ..] = packet else {
+                continue;
+            };
+            if first & MT_MASK == MT_RESPONSE {
+                return if (first & !MT_MASK, second) == (gid, oid) && status == STATUS_OK {
+                    Ok(())
+                } else {
+                    Err(Error::UnexpectedResponse)
+                };
+            }
+        }
+    }
+
+    /// Sends an NCI packet, starting with the header bytes `first` and `second`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ControllerAccess`] if the I2C transfer fails.
+    async fn send(&mut self, first: u8, second: u8, payload: &[u8]) -> Result<(), Error> {
+        let mut buf = [0; MAX_PACKET_LEN];
+        let len = u8::try_from(payload.len()).map_err(|_| Error::MessageTooLong)?;
+        let (header, rest) = buf.split_at_mut(HEADER_LEN);
+        header.copy_from_slice(&[first, second, len]);
+        rest.get_mut(..payload.len())
+            .ok_or(Error::MessageTooLong)?
+            .copy_from_slice(payload);
+        let packet = buf.get(..HEADER_LEN + payload.len()).unwrap_or_default();
+
+        // The controller may not acknowledge its address while waking up from standby.
+        if self.i2c.write(self.address, packet).await.is_err() {
+            Timer::after_millis(1).await;
+            self.i2c
+                .write(self.address, packet)
+                .await
+                .map_err(|_| Error::ControllerAccess)?;
+        }
+        Ok(())
+    }
+
+    /// Waits for an NCI packet from the controller, reads it into `self.buf`, and returns its
+    /// length.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ControllerAccess`] if the I2C transfer fails.
+    async fn receive(&mut self) -> Result<usize, Error> {
+        self.irq
+            .wait_for_high()
+            .await
+            .map_err(|_| Error::ControllerAccess)?;
+
+        let (header, payload) = self.buf.split_at_mut(HEADER_LEN);
+        self.i2c
+            .read(self.address, header)
+            .await
+            .map_err(|_| Error::ControllerAccess)?;
+        let len = header.get(2).copied().map_or(0, usize::from);
+        if len > 0 {
+            self.i2c
+                .read(self.address, payload.get_mut(..len).unwrap_or_default())
+                .await
+                .map_err(|_| Error::ControllerAccess)?;
+        }
+        Ok(HEADER_LEN + len)
+    }
+
+    /// Serves `tag` to the next reader, until it leaves the field.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if communicating with the controller fails.
+    async fn serve(&mut self, tag: &mut Type4Tag<'_>) -> Result<(), Error> {
+        let mut activated = false;
+        loop {
+            let len = self.receive().await?;
+            let packet = self.buf.get(..len).unwrap_or_default();
+            let Some((&[first, second, _], payload)) = packet.split_first_chunk::<HEADER_LEN>()
+            else {
+                continue;
+            };
+
+            if first & MT_MASK == MT_NOTIFICATION {
+                match (first & !MT_MASK, second) {
+                    opcode::RF_INTF_ACTIVATED => {
+                        activated = true;
+                        tag.reset();
+                    }
+                    // The controller goes back to listening by itself.
+                    opcode::RF_DEACTIVATE if activated => return Ok(()),
+                    _ => {}
+                }
+            } else if first & MT_MASK == MT_DATA && activated {
+                let mut response = [0; MAX_RESPONSE_LEN];
+                let len = tag.respond(payload, &mut response);
+                // Data is exchanged on the static RF connection.
+                self.send(MT_DATA, 0x00, response.get(..len).unwrap_or_default())
+                    .await?;
+            }
+        }
+    }
+}
+
+impl<I: I2c, Q: Wait, V: OutputPin> TagEmulator for Pn7150<I, Q, V> {
+    async fn emulate(&mut self, message: &[u8]) -> Result<(), Error> {
+        let mut tag = Type4Tag::new(message)?;
+        if !self.listening {
+            self.start().await?;
+        }
+
+        let result = self.serve(&mut tag).await;
+        if result.is_err() {
+            // Reset the controller when emulating the next tag.
+            self.listening = false;
+        }
+        result
+    }
+}
@@ -0,0 +1,237 @@
+//! Provides the emulation of NFC Forum Type 2 Tags, for NFC controllers exchanging raw NFC-A
+//! frames with readers.
+//!
+//! The anticollision and selection of the tag are expected to be handled by the NFC controller,
+//! using the same UID as the tag; the frames passed to [`Type2Tag::respond()`] are the commands
+//! sent by the reader after that, without their CRC.
+
+use crate::Error;
+
+/// Length of the UID of tags, which is a double-size NFCID1.
+pub const UID_LEN: usize = 7;
+/// Length of the responses to read commands, which contain four pages.
+pub const READ_RESPONSE_LEN: usize = 16;
+/// Maximum length of the NDEF messages tags can hold.
+pub const MAX_MESSAGE_LEN: usize = MAX_DATA_AREA_LEN - 5;
+
+/// Length of the pages of the memory of tags.
+const PAGE_LEN: usize = 4;
+/// Length of the UID, lock bytes and capability container, which precede the data area.
+const HEADER_LEN: usize = 16;
+/// Minimum length of the data area, which is the length of the data area of static tags.
+const MIN_DATA_AREA_LEN: usize = 48;
+/// Maximum length of the data area, such that all pages can be addressed by the one-byte page
+/// number of [`READ`] commands.
+const MAX_DATA_AREA_LEN: usize = 256 * PAGE_LEN - HEADER_LEN;
+
+/// Command reading four pages.
+const READ: u8 = 0x30;
+/// Command putting the tag to sleep.
+const HALT: u8 = 0x50;
+
+/// Cascade tag, which is part of the first check byte of double-size UIDs.
+const CASCADE_TAG: u8 = 0x88;
+/// Magic number of the capability container, indicating that the tag holds an NDEF message.
+const NDEF_MAGIC: u8 = 0xe1;
+/// Version 1.0 of the mapping of NDEF messages onto Type 2 Tags.
+const MAPPING_VERSION: u8 = 0x10;
+/// Access conditions of read-only tags.
+const READ_ONLY: u8 = 0x0f;
+
+/// Type of the TLV block holding the NDEF message.
+const NDEF_MESSAGE_TLV: u8 = 0x03;
+/// Type of the TLV block ending the data area.
+const TERMINATOR_TLV: u8 = 0xfe;
+
+/// Response to a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Response {
+    /// The data to send back, followed by its CRC.
+    Data([u8; READ_RESPONSE_LEN]),
+    /// The command is not supported, and a 4-bit NAK is to be sent back.
+    Nak,
+    /// The tag is to go to sleep, without responding.
+    Halt,
+}
+
+/// A read-only Type 2 Tag holding an NDEF message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Type2Tag<'a> {
+    uid: [u8; UID_LEN],
+    message: &'a [u8],
+    /// Length of the length field of the NDEF message TLV block.
+    length_len: usize,
+    data_area_len: usize,
+}
+
+impl<'a> Type2Tag<'a> {
+    /// Returns a tag with the UID `uid`, holding the NDEF `message`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MessageTooLong`] if the message is longer than [`MAX_MESSAGE_LEN`].
+    pub fn new(uid: [u8; UID_LEN], message: &'a [u8]) -> Result<Self, Error> {
+        if message.len() > MAX_MESSAGE_LEN {
+            return Err(Error::MessageTooLong);
+        }
+
+        // Lengths from 255 on are encoded on three bytes.
+        let length_len = if message.len() < 0xff { 1 } else { 3 };
+        let tlvs_len = 1 + length_len + message.len() + 1;
+        let data_area_len = tlvs_len.next_multiple_of(8).max(MIN_DATA_AREA_LEN);
+
+        Ok(Self {
+            uid,
+            message,
+            length_len,
+            data_area_len,
+        })
+    }
+
+    /// Returns the response to the `command` of a reader.
+    #[must_use]
+    pub fn respond(&self, command: &[u8]) -> Response {
+        match *command {
+            [READ, page] => {
+                let address = usize::from(page) * PAGE_LEN;
+                let len = HEADER_LEN + self.data_area_len;
+                if address >= len {
+                    return Response::Nak;
+                }
+
+                // Reads roll over to the beginning of the memory.
+                let mut data = [0; READ_RESPONSE_LEN];
+                for (offset, byte) in data.iter_mut().enumerate() {
+                    *byte = self.byte((address + offset) % len);
+                }
+                Response::Data(data)
+            }
+            [HALT, 0x00] => Response::Halt,
+            _ => Response::Nak,
+        }
+    }
+
+    /// Returns the byte at `address` in the memory of the tag.
+    fn byte(&self, address: usize) -> u8 {
+        let [uid0, uid1, uid2, uid3, uid4, uid5, uid6] = self.uid;
+        // The data area is at most 1008 bytes long.
+        #[expect(clippy::cast_possible_truncation)]
+        let header = [
+            uid0,
+            uid1,
+            uid2,
+            CASCADE_TAG ^ uid0 ^ uid1 ^ uid2,
+            uid3,
+            uid4,
+            uid5,
+            uid6,
+            uid3 ^ uid4 ^ uid5 ^ uid6,
+            0x00,
+            0x00,
+            0x00,
+            NDEF_MAGIC,
+            MAPPING_VERSION,
+            (self.data_area_len / 8) as u8,
+            READ_ONLY,
+        ];
+        if let Some(&byte) = header.get(address) {
+            return byte;
+        }
+
+        let offset = address - HEADER_LEN;
+        let message_offset = 1 + self.length_len;
+        if offset == 0 {
+            NDEF_MESSAGE_TLV
+        } else if offset < message_offset {
+            let [high, low] = u16::try_from(self.message.len())
+                .unwrap_or(u16::MAX)
+                .to_be_bytes();
+            match (self.length_len, offset) {
+                (1, _) => low,
+                (_, 1) => 0xff,
+                (_, 2) => high,
+                _ => low,
+            }
+        } else if let Some(&byte) = self.message.get(offset - message_offset) {
+            byte
+        } else if offset == message_offset + self.message.len() {
+            TERMINATOR_TLV
+        } else {
+            0x00
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UID: [u8; UID_LEN] = [0x5f, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+
+    fn read(tag: &Type2Tag<'_>, page: u8) -> [u8; READ_RESPONSE_LEN] {
+        let Response::Data(data) = tag.respond(&[READ, page]) else {
+            panic!("read of page {page} failed");
+        };
+        data
+    }
+
+    #[test]
+    fn header() {
+        let tag = Type2Tag::new(UID, b"\xd0\x00\x00").unwrap();
+        assert_eq!(
+            read(&tag, 0),
+            [
+                0x5f, 0x01, 0x02, 0xd4, 0x03, 0x04, 0x05, 0x06, 0x04, 0x00, 0x00, 0x00, 0xe1, 0x10,
+                0x06, 0x0f
+            ]
+        );
+        assert_eq!(
+            read(&tag, 4),
+            [
+                0x03, 0x03, 0xd0, 0x00, 0x00, 0xfe, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn long_message() {
+        let message = [0x55; 300];
+        let tag = Type2Tag::new(UID, &message).unwrap();
+        // 1 + 3 + 300 + 1 bytes of TLV blocks, rounded up to 312.
+        assert_eq!(
+            read(&tag, 3),
+            [
+                0xe1, 0x10, 39, 0x0f, 0x03, 0xff, 0x01, 0x2c, 0x55, 0x55, 0x55, 0x55, 0x55, 0x55,
+                0x55, 0x55
+            ]
+        );
+        // Reads of the last pages roll over to the first page.
+        assert_eq!(
+            read(&tag, 79),
+            [
+                0x55, 0x55, 0x55, 0x55, 0xfe, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x5f, 0x01,
+                0x02, 0xd4
+            ]
+        );
+        assert_eq!(tag.respond(&[READ, 82]), Response::Nak);
+    }
+
+    #[test]
+    fn commands() {
+        let tag = Type2Tag::new(UID, &[]).unwrap();
+        assert_eq!(tag.respond(&[HALT, 0x00]), Response::Halt);
+        assert_eq!(tag.respond(&[0xa2, 0x04, 0, 0, 0, 0]), Response::Nak);
+        assert_eq!(tag.respond(&[READ]), Response::Nak);
+    }
+
+    #[test]
+    fn message_too_long() {
+        let message = [0; MAX_MESSAGE_LEN + 1];
+        assert_eq!(Type2Tag::new(UID, &message), Err(Error::MessageTooLong));
+        let message = [0; MAX_MESSAGE_LEN];
+        let tag = Type2Tag::new(UID, &message).unwrap();
+        assert_eq!(read(&tag, 3).get(2), Some(&126));
+    }
+}
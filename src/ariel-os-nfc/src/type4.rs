@@ -0,0 +1,350 @@
+//! Provides the emulation of NFC Forum Type 4 Tags, for NFC controllers handling the ISO-DEP
+//! protocol.
+//!
+//! Type 4 Tags expose the NDEF message in a file of the NDEF application, which readers select
+//! and read with ISO/IEC 7816-4 command APDUs, passed to [`Type4Tag::respond()`].
+
+use crate::Error;
+
+/// Maximum length of the NDEF messages tags can hold.
+pub const MAX_MESSAGE_LEN: usize = 0xfffc;
+/// Maximum length of the data of responses to read commands.
+pub const MAX_READ_LEN: usize = 0xf6;
+/// Maximum length of response APDUs.
+pub const MAX_RESPONSE_LEN: usize = MAX_READ_LEN + 2;
+
+/// Name of the NDEF application.
+const NDEF_APPLICATION: [u8; 7] = [0xd2, 0x76, 0x00, 0x00, 0x85, 0x01, 0x01];
+/// Identifier of the capability container file.
+const CC_FILE: [u8; 2] = [0xe1, 0x03];
+/// Identifier of the NDEF file.
+const NDEF_FILE: [u8; 2] = [0xe1, 0x04];
+/// Length of the capability container file.
+const CC_LEN: u16 = 15;
+/// Version 2.0 of the mapping of NDEF messages onto Type 4 Tags.
+const MAPPING_VERSION: u8 = 0x20;
+/// Type of the TLV block describing the NDEF file.
+const NDEF_FILE_CONTROL_TLV: u8 = 0x04;
+/// Access condition granting access.
+const ACCESS_GRANTED: u8 = 0x00;
+/// Access condition denying access.
+const ACCESS_DENIED: u8 = 0xff;
+
+/// Instruction selecting an application or file.
+const SELECT: u8 = 0xa4;
+/// Instruction reading from the selected file.
+const READ_BINARY: u8 = 0xb0;
+/// Instruction writing to the selected file.
+const UPDATE_BINARY: u8 = 0xd6;
+
+/// Status words of response APDUs.
+mod status {
+    pub const OK: [u8; 2] = [0x90, 0x00];
+    pub const WRONG_LENGTH: [u8; 2] = [0x67, 0x00];
+    pub const SECURITY_STATUS_NOT_SATISFIED: [u8; 2] = [0x69, 0x82];
+    pub const NO_FILE_SELECTED: [u8; 2] = [0x69, 0x86];
+    pub const NOT_FOUND: [u8; 2] = [0x6a, 0x82];
+    pub const WRONG_PARAMETERS: [u8; 2] = [0x6b, 0x00];
+    pub const INSTRUCTION_NOT_SUPPORTED: [u8; 2] = [0x6d, 0x00];
+    pub const CLASS_NOT_SUPPORTED: [u8; 2] = [0x6e, 0x00];
+}
+
+/// What the reader has selected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Selection {
+    None,
+    Application,
+    CapabilityContainer,
+    Ndef,
+}
+
+/// A read-only Type 4 Tag holding an NDEF message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Type4Tag<'a> {
+    message: &'a [u8],
+    selection: Selection,
+}
+
+impl<'a> Type4Tag<'a> {
+    /// Returns a tag holding the NDEF `message`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::MessageTooLong`] if the message is longer than [`MAX_MESSAGE_LEN`].
+    pub fn new(message: &'a [u8]) -> Result<Self, Error> {
+        if message.len() > MAX_MESSAGE_LEN {
+            return Err(Error::MessageTooLong);
+        }
+
+        Ok(Self {
+            message,
+            selection: Selection::None,
+        })
+    }
+
+    /// Deselects the NDEF application, which readers need to select again, eg. after leaving the
+    /// field.
+    pub fn reset(&mut self) {
+        self.selection = Selection::None;
+    }
+
+    /// Writes the response APDU to the `command` APDU of a reader into `response`, and returns
+    /// its length.
+    pub fn respond(&mut self, command: &[u8], response: &mut [u8; MAX_RESPONSE_LEN]) -> usize {
+        let (len, status) = match self.process(command, response) {
+            Ok(len) => (len, status::OK),
+            Err(status) => (0, status),
+        };
+        let (_, data_and_status) = response.split_at_mut(len);
+        if let Some(dest) = data_and_status.first_chunk_mut::<2>() {
+            *dest = status;
+        }
+        len + status.len()
+    }
+
+    /// Processes `command`, writes the data of the response into `response`, and returns its
+    /// length.
+    ///
+    /// # Errors
+    ///
+    /// Returns the status word of the response if processing the command fails.
+    fn process(&mut self, command: &[u8], response: &mut [u8]) -> Result<usize, [u8; 2]> {
+        let [class, instruction, p1, p2, body @ ..] = command else {
+            return Err(status::WRONG_LENGTH);
+        };
+        if *class != 0x00 {
+            return Err(status::CLASS_NOT_SUPPORTED);
+        }
+
+        match *instruction {
+            SELECT => {
+                let [lc, data @ ..] = body else {
+                    return Err(status::WRONG_LENGTH);
+                };
+                // The data may be followed by an expected response length.
+                let data = data.get(..usize::from(*lc)).ok_or(status::WRONG_LENGTH)?;
+                self.select(*p1, *p2, data)?;
+                Ok(0)
+            }
+            READ_BINARY => {
+                let file = match self.selection {
+                    Selection::CapabilityContainer => File::CapabilityContainer,
+                    Selection::Ndef => File::Ndef,
+                    Selection::None | Selection::Application => {
+                        return Err(status::NO_FILE_SELECTED);
+                    }
+                };
+                let offset = usize::from(u16::from_be_bytes([*p1, *p2]));
+                // An expected length of 0 stands for 256 bytes.
+                let expected = match body {
+                    [] | [0] => 256,
+                    [le] => usize::from(*le),
+                    _ => return Err(status::WRONG_LENGTH),
+                };
+
+                let file_len = self.file_len(file);
+                if offset > file_len {
+                    return Err(status::WRONG_PARAMETERS);
+                }
+                let len = expected.min(MAX_READ_LEN).min(file_len - offset);
+                for (i, byte) in response.iter_mut().take(len).enumerate() {
+                    *byte = self.file_byte(file, offset + i);
+                }
+                Ok(len)
+            }
+            UPDATE_BINARY => Err(status::SECURITY_STATUS_NOT_SATISFIED),
+            _ => Err(status::INSTRUCTION_NOT_SUPPORTED),
+        }
+    }
+
+    /// Selects the application or file identified by `data`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the status word of the response if the selection fails.
+    fn select(&mut self, p1: u8, p2: u8, data: &[u8]) -> Result<(), [u8; 2]> {
+        let selection = match (p1, p2) {
+            // Selection by name.
+            (0x04, 0x00) if data == NDEF_APPLICATION => Selection::Application,
+            // Selection by file identifier, without response data.
+            (0x00, 0x0c) if self.selection != Selection::None => match data {
+                id if id == CC_FILE => Selection::CapabilityContainer,
+                id if id == NDEF_FILE => Selection::Ndef,
+                _ => return Err(status::NOT_FOUND),
+            },
+            (0x04 | 0x00, _) => {
+                self.selection = Selection::None;
+                return Err(status::NOT_FOUND);
+            }
+            _ => return Err(status::WRONG_PARAMETERS),
+        };
+        self.selection = selection;
+        Ok(())
+    }
+
+    fn file_len(&self, file: File) -> usize {
+        match file {
+            File::CapabilityContainer => usize::from(CC_LEN),
+            File::Ndef => 2 + self.message.len(),
+        }
+    }
+
+    // The lengths of the NDEF file and of reads fit into a `u16`, as the message length has been
+    // checked against `MAX_MESSAGE_LEN`.
+    #[expect(clippy::cast_possible_truncation)]
+    fn file_byte(&self, file: File, offset: usize) -> u8 {
+        let [nlen_high, nlen_low] = (self.message.len() as u16).to_be_bytes();
+        match file {
+            File::CapabilityContainer => {
+                let [max_read_high, max_read_low] = (MAX_READ_LEN as u16).to_be_bytes();
+                let [file_len_high, file_len_low] =
+                    (self.file_len(File::Ndef) as u16).to_be_bytes();
+                let [cc_len_high, cc_len_low] = CC_LEN.to_be_bytes();
+                let [ndef_high, ndef_low] = NDEF_FILE;
+                [
+                    cc_len_high,
+                    cc_len_low,
+                    MAPPING_VERSION,
+                    max_read_high,
+                    max_read_low,
+                    // Maximum length of the data of commands.
+                    0x00,
+                    0xff,
+                    NDEF_FILE_CONTROL_TLV,
+                    0x06,
+                    ndef_high,
+                    ndef_low,
+                    file_len_high,
+                    file_len_low,
+                    ACCESS_GRANTED,
+                    ACCESS_DENIED,
+                ]
+                .get(offset)
+                .copied()
+                .unwrap_or_default()
+            }
+            File::Ndef => match offset {
+                0 => nlen_high,
+                1 => nlen_low,
+                _ => self.message.get(offset - 2).copied().unwrap_or_default(),
+            },
+        }
+    }
+}
+
+/// File of the NDEF application.
+#[derive(Debug, Clone, Copy)]
+enum File {
+    CapabilityContainer,
+    Ndef,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SELECT_APPLICATION: &[u8] = b"\x00\xa4\x04\x00\x07\xd2\x76\x00\x00\x85\x01\x01\x00";
+    const SELECT_CC: &[u8] = b"\x00\xa4\x00\x0c\x02\xe1\x03";
+    const SELECT_NDEF: &[u8] = b"\x00\xa4\x00\x0c\x02\xe1\x04";
+
+    fn respond<'r>(
+        tag: &mut Type4Tag<'_>,
+        command: &[u8],
+        response: &'r mut [u8; MAX_RESPONSE_LEN],
+    ) -> &'r [u8] {
+        let len = tag.respond(command, response);
+        response.get(..len).unwrap()
+    }
+
+    #[test]
+    fn read() {
+        let message = b"\xd1\x01\x0cU\x04example.com";
+        let mut tag = Type4Tag::new(message).unwrap();
+        let mut response = [0; MAX_RESPONSE_LEN];
+
+        assert_eq!(
+            respond(&mut tag, SELECT_APPLICATION, &mut response),
+            b"\x90\x00"
+        );
+        assert_eq!(respond(&mut tag, SELECT_CC, &mut response), b"\x90\x00");
+        assert_eq!(
+            respond(&mut tag, b"\x00\xb0\x00\x00\x0f", &mut response),
+            b"\x00\x0f\x20\x00\xf6\x00\xff\x04\x06\xe1\x04\x00\x12\x00\xff\x90\x00"
+        );
+
+        assert_eq!(respond(&mut tag, SELECT_NDEF, &mut response), b"\x90\x00");
+        assert_eq!(
+            respond(&mut tag, b"\x00\xb0\x00\x00\x02", &mut response),
+            b"\x00\x10\x90\x00"
+        );
+        assert_eq!(
+            respond(&mut tag, b"\x00\xb0\x00\x02\x11", &mut response),
+            b"\xd1\x01\x0cU\x04example.com\x90\x00"
+        );
+        // Reads are truncated at the end of the file.
+        assert_eq!(
+            respond(&mut tag, b"\x00\xb0\x00\x0e\x20", &mut response),
+            b".com\x90\x00"
+        );
+        assert_eq!(
+            respond(&mut tag, b"\x00\xb0\x00\x13\x01", &mut response),
+            b"\x6b\x00"
+        );
+    }
+
+    #[test]
+    fn long_read() {
+        let message = [0x55; 300];
+        let mut tag = Type4Tag::new(&message).unwrap();
+        let mut response = [0; MAX_RESPONSE_LEN];
+
+        respond(&mut tag, SELECT_APPLICATION, &mut response);
+        respond(&mut tag, SELECT_NDEF, &mut response);
+        let data = respond(&mut tag, b"\x00\xb0\x00\x02\x00", &mut response);
+        assert_eq!(data.len(), MAX_READ_LEN + 2);
+        assert!(data.ends_with(b"\x55\x90\x00"));
+    }
+
+    #[test]
+    fn errors() {
+        let mut tag = Type4Tag::new(&[]).unwrap();
+        let mut response = [0; MAX_RESPONSE_LEN];
+
+        // Files cannot be selected or read before the application.
+        assert_eq!(respond(&mut tag, SELECT_CC, &mut response), b"\x6a\x82");
+        assert_eq!(
+            respond(&mut tag, b"\x00\xb0\x00\x00\x0f", &mut response),
+            b"\x69\x86"
+        );
+        assert_eq!(
+            respond(
+                &mut tag,
+                b"\x00\xa4\x04\x00\x07\xa0\x00\x00\x00\x03\x10\x10\x00",
+                &mut response
+            ),
+            b"\x6a\x82"
+        );
+
+        respond(&mut tag, SELECT_APPLICATION, &mut response);
+        assert_eq!(
+            respond(&mut tag, b"\x00\xa4\x00\x0c\x02\xe1\x05", &mut response),
+            b"\x6a\x82"
+        );
+        respond(&mut tag, SELECT_NDEF, &mut response);
+        assert_eq!(
+            respond(&mut tag, b"\x00\xd6\x00\x00\x01\x00", &mut response),
+            b"\x69\x82"
+        );
+        assert_eq!(
+            respond(&mut tag, b"\x80\xb0\x00\x00", &mut response),
+            b"\x6e\x00"
+        );
+        assert_eq!(respond(&mut tag, b"\x00\xb0", &mut response), b"\x67\x00");
+
+        tag.reset();
+        assert_eq!(
+            respond(&mut tag, b"\x00\xb0\x00\x00\x02", &mut response),
+            b"\x69\x86"
+        );
+    }
+}
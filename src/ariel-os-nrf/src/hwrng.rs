@@ -7,16 +7,21 @@ pub fn construct_rng(peripherals: &mut crate::OptionalPeripherals) {
         // The union of all contexts that wind up in a construct_rng should be synchronized
         // with laze-project.yml's hwrng module.
         if #[cfg(any(context = "nrf51", context = "nrf52", context = "nrf5340-net"))] {
-            let rng = embassy_nrf::rng::Rng::new(
+            use embassy_nrf::{peripherals::RNG, rng::Rng};
+            use static_cell::StaticCell;
+
+            // The RNG is kept, so that it can be read again to reseed the global RNG.
+            static HWRNG: StaticCell<Rng<'static, RNG>> = StaticCell::new();
+
+            let rng = Rng::new(
                 peripherals
                     .RNG
-                    // We don't even have to take it out, just use it to seed the RNG
-                    .as_mut()
+                    .take()
                     .expect("RNG has not been previously used"),
                 Irqs,
             );
 
-            ariel_os_random::construct_rng(rng);
+            ariel_os_random::construct_rng_from_hwrng(HWRNG.init(rng));
         } else if #[cfg(context = "ariel-os")] {
             compile_error!("hardware RNG is not supported on this MCU family");
         }
@@ -1,6 +1,8 @@
 //! Provides support for the I2C communication bus in controller mode.
 
-use ariel_os_embassy_common::impl_async_i2c_for_driver_enum;
+use ariel_os_embassy_common::{
+    i2c::controller::I2C_TIMEOUT, impl_async_i2c_for_driver_enum, reexports::embassy_time::Duration,
+};
 
 use embassy_nrf::{
     Peripheral, bind_interrupts,
@@ -26,6 +28,9 @@ pub struct Config {
     /// Whether to set the SCL pin's drive strength to
     /// [`DriveStrength::High`](crate::gpio::DriveStrength::High).
     pub scl_high_drive: bool,
+    /// Timeout of each operation on the bus, after which it fails with
+    /// [`Error::Timeout`](ariel_os_embassy_common::i2c::controller::Error::Timeout).
+    pub timeout: Duration,
 }
 
 impl Default for Config {
@@ -36,6 +41,7 @@ impl Default for Config {
             scl_pullup: false,
             sda_high_drive: false,
             scl_high_drive: false,
+            timeout: I2C_TIMEOUT,
         }
     }
 }
@@ -131,6 +137,7 @@ macro_rules! define_i2c_drivers {
             /// Peripheral-specific I2C driver.
             pub struct $peripheral {
                 twim: Twim<'static, peripherals::$peripheral>,
+                timeout: Duration,
             }
 
             impl $peripheral {
@@ -172,7 +179,7 @@ macro_rules! define_i2c_drivers {
                     // we implement it at a higher level, not in this HAL-specific module.
                     let twim = Twim::new(twim_peripheral, Irqs, sda_pin, scl_pin, twim_config);
 
-                    I2c::$peripheral(Self { twim })
+                    I2c::$peripheral(Self { twim, timeout: config.timeout })
                 }
             }
         )*
@@ -185,6 +192,9 @@ macro_rules! define_i2c_drivers {
             )*
         }
 
+        /// Names of the I2C peripherals for which a driver is available.
+        pub const PERIPHERALS: &[&str] = &[$( stringify!($peripheral) ),*];
+
         impl embedded_hal_async::i2c::ErrorType for I2c {
             type Error = ariel_os_embassy_common::i2c::controller::Error;
         }
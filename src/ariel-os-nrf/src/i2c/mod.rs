@@ -5,20 +5,29 @@ pub mod controller;
 
 #[doc(hidden)]
 pub fn init(peripherals: &mut crate::OptionalPeripherals) {
+    // This macro has to be defined in this function so that the `peripherals` variables exists.
+    macro_rules! take_all_i2c_peripherals {
+        ($( $peripheral:ident ),*) => {
+            $(
+                let peripheral = peripherals.$peripheral.take();
+                debug_assert!(
+                    peripheral.is_some(),
+                    concat!(stringify!($peripheral), " was taken before initialization"),
+                );
+            )*
+        }
+    }
+
     // Take all I2C peripherals and do nothing with them.
     cfg_if::cfg_if! {
         if #[cfg(context = "nrf52833")] {
-            let _ = peripherals.TWISPI0.take().unwrap();
-            let _ = peripherals.TWISPI1.take().unwrap();
+            take_all_i2c_peripherals!(TWISPI0, TWISPI1);
         } else if #[cfg(context = "nrf52840")] {
-            let _ = peripherals.TWISPI0.take().unwrap();
-            let _ = peripherals.TWISPI1.take().unwrap();
+            take_all_i2c_peripherals!(TWISPI0, TWISPI1);
         } else if #[cfg(context = "nrf5340")] {
-            let _ = peripherals.SERIAL0.take().unwrap();
-            let _ = peripherals.SERIAL1.take().unwrap();
+            take_all_i2c_peripherals!(SERIAL0, SERIAL1);
         } else if #[cfg(context = "nrf91")] {
-            let _ = peripherals.SERIAL0.take().unwrap();
-            let _ = peripherals.SERIAL1.take().unwrap();
+            take_all_i2c_peripherals!(SERIAL0, SERIAL1);
         } else {
             compile_error!("this nRF chip is not supported");
         }
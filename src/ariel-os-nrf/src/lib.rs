@@ -25,6 +25,12 @@ pub mod i2c;
 #[doc(hidden)]
 pub mod identity;
 
+#[cfg(feature = "nfct")]
+pub mod nfct;
+
+#[cfg(feature = "audio-pdm")]
+pub mod pdm;
+
 #[cfg(feature = "spi")]
 pub mod spi;
 
@@ -36,6 +42,10 @@ pub mod storage;
 #[doc(hidden)]
 pub mod usb;
 
+#[cfg(feature = "xip")]
+#[doc(hidden)]
+pub mod xip;
+
 #[cfg(feature = "executor-interrupt")]
 #[doc(hidden)]
 pub use embassy_executor::InterruptExecutor as Executor;
@@ -0,0 +1,257 @@
+//! Provides NFC tag emulation with the NFCT peripheral.
+//!
+//! The peripheral emulates [Type 2 Tags](ariel_os_nfc::type2), whose UID is derived from the
+//! device ID.
+//! The NFC antenna pins cannot be used as GPIOs at the same time.
+
+use core::{future::poll_fn, task::Poll};
+
+use ariel_os_embassy_common::identity::DeviceId as _;
+use ariel_os_nfc::{
+    Error, TagEmulator,
+    type2::{READ_RESPONSE_LEN, Response, Type2Tag, UID_LEN},
+};
+use embassy_nrf::{
+    bind_interrupts,
+    interrupt::{
+        self,
+        typelevel::{Binding, Handler, Interrupt as _},
+    },
+    pac::{
+        self,
+        common::{RW, Reg},
+        nfct::vals::{Bitframesdd, Nfcidsize},
+    },
+};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::identity::DeviceId;
+
+/// Manufacturer ID of Nordic Semiconductor, which is the first byte of the UID.
+const MANUFACTURER_ID: u8 = 0x5f;
+/// Maximum length of the commands of readers, which are at most as long as the responses.
+const MAX_COMMAND_LEN: usize = READ_RESPONSE_LEN;
+
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+struct InterruptHandler;
+
+impl Handler<interrupt::typelevel::NFCT> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        // The waiting task enables the interrupts it needs again.
+        pac::NFCT.intenclr().write(|w| w.0 = u32::MAX);
+        WAKER.wake();
+    }
+}
+
+/// The NFCT peripheral.
+pub struct Nfct {
+    uid: [u8; UID_LEN],
+}
+
+impl Nfct {
+    /// Returns a driver implementing [`TagEmulator`] for the NFCT peripheral.
+    #[must_use]
+    pub fn new() -> Self {
+        bind_interrupts!(
+            struct Irqs {
+                NFCT => InterruptHandler;
+            }
+        );
+
+        // Make this struct a compile-time-enforced singleton: having multiple statics defined
+        // with the same name would result in a compile-time error.
+        #[allow(dead_code)]
+        static PREVENT_MULTIPLE_NFCT: () = ();
+
+        let Ok(device_id) = DeviceId::get();
+        let [id0, id1, id2, id3, id4, id5, ..] = device_id.bytes();
+        let uid = [MANUFACTURER_ID, id0, id1, id2, id3, id4, id5];
+
+        let r = pac::NFCT;
+        let [uid0, uid1, uid2, uid3, uid4, uid5, uid6] = uid;
+        r.nfcid1_2nd_last().write(|w| {
+            w.set_nfcid1_t(uid0);
+            w.set_nfcid1_u(uid1);
+            w.set_nfcid1_v(uid2);
+        });
+        r.nfcid1_last().write(|w| {
+            w.set_nfcid1_w(uid3);
+            w.set_nfcid1_x(uid4);
+            w.set_nfcid1_y(uid5);
+            w.set_nfcid1_z(uid6);
+        });
+        r.sensres().write(|w| {
+            w.set_nfcidsize(Nfcidsize::NFCID1DOUBLE);
+            w.set_bitframesdd(Bitframesdd::SDD00100);
+        });
+        // Activate when a field is detected, and go back to sensing when it is lost.
+        r.shorts().write(|w| {
+            w.set_fielddetected_activate(true);
+            w.set_fieldlost_sense(true);
+        });
+
+        // Leave time for the task to respond, which the frame delay of Type 2 Tags allows.
+        r.framedelaymax().write(|w| w.0 = 0xffff);
+
+        enable_interrupt(Irqs);
+
+        Self { uid }
+    }
+}
+
+impl Default for Nfct {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TagEmulator for Nfct {
+    async fn emulate(&mut self, message: &[u8]) -> Result<(), Error> {
+        let tag = Type2Tag::new(self.uid, message)?;
+        serve(&tag).await;
+        // Stop responding to readers until the next tag is emulated.
+        pac::NFCT.tasks_disable().write_value(1);
+        Ok(())
+    }
+}
+
+fn enable_interrupt(_irqs: impl Binding<interrupt::typelevel::NFCT, InterruptHandler>) {
+    interrupt::typelevel::NFCT::unpend();
+    // SAFETY: the interrupt handler is bound.
+    unsafe { interrupt::typelevel::NFCT::enable() };
+}
+
+/// Serves `tag` to the next reader, until it leaves the field.
+async fn serve(tag: &Type2Tag<'_>) {
+    let r = pac::NFCT;
+
+    Event::Selected.clear();
+    Event::FieldLost.clear();
+    r.tasks_sense().write_value(1);
+    Event::Selected.wait().await;
+
+    let mut command = [0; MAX_COMMAND_LEN];
+    loop {
+        r.packetptr().write_value(command.as_mut_ptr() as u32);
+        // The buffer length fits into the register.
+        #[expect(clippy::cast_possible_truncation)]
+        r.maxlen().write(|w| w.set_maxlen(command.len() as u16));
+        r.rxd().frameconfig().write(|w| {
+            w.set_parity(true);
+            w.set_sof(true);
+            w.set_crcmoderx(true);
+        });
+        Event::RxFrameEnd.clear();
+        Event::RxError.clear();
+        r.tasks_enablerxdata().write_value(1);
+
+        match Event::wait_any(&[Event::RxFrameEnd, Event::RxError, Event::FieldLost]).await {
+            Event::RxFrameEnd => {}
+            Event::FieldLost => return,
+            _ => continue,
+        }
+
+        let len = usize::from(r.rxd().amount().read().rxdatabytes());
+        match tag.respond(command.get(..len).unwrap_or_default()) {
+            Response::Data(data) => {
+                if !transmit(&data, data.len() * 8, true).await {
+                    return;
+                }
+            }
+            Response::Nak => {
+                if !transmit(&[0x00], 4, false).await {
+                    return;
+                }
+            }
+            Response::Halt => {
+                // Wait for the reader to wake up the tag, or to leave the field.
+                r.tasks_gosleep().write_value(1);
+                Event::Selected.clear();
+                if Event::wait_any(&[Event::Selected, Event::FieldLost]).await == Event::FieldLost {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Transmits the first `bits` bits of `frame`, and returns `false` if the field has been lost in
+/// the meantime.
+async fn transmit(frame: &[u8], bits: usize, crc: bool) -> bool {
+    let r = pac::NFCT;
+
+    r.packetptr().write_value(frame.as_ptr() as u32);
+    // Frames are at most `READ_RESPONSE_LEN` long.
+    #[expect(clippy::cast_possible_truncation)]
+    r.txd().amount().write(|w| {
+        w.set_txdatabytes((bits / 8) as u16);
+        w.set_txdatabits((bits % 8) as u8);
+    });
+    r.txd().frameconfig().write(|w| {
+        w.set_parity(true);
+        w.set_sof(true);
+        w.set_crcmodetx(crc);
+    });
+    Event::TxFrameEnd.clear();
+    r.tasks_starttx().write_value(1);
+
+    Event::wait_any(&[Event::TxFrameEnd, Event::FieldLost]).await == Event::TxFrameEnd
+}
+
+/// Events of the NFCT peripheral.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    Selected,
+    FieldLost,
+    RxFrameEnd,
+    RxError,
+    TxFrameEnd,
+}
+
+impl Event {
+    fn register(self) -> Reg<u32, RW> {
+        let r = pac::NFCT;
+        match self {
+            Self::Selected => r.events_selected(),
+            Self::FieldLost => r.events_fieldlost(),
+            Self::RxFrameEnd => r.events_rxframeend(),
+            Self::RxError => r.events_rxerror(),
+            Self::TxFrameEnd => r.events_txframeend(),
+        }
+    }
+
+    fn clear(self) {
+        self.register().write_value(0);
+    }
+
+    fn enable_interrupt(self) {
+        pac::NFCT.intenset().write(|w| match self {
+            Self::Selected => w.set_selected(true),
+            Self::FieldLost => w.set_fieldlost(true),
+            Self::RxFrameEnd => w.set_rxframeend(true),
+            Self::RxError => w.set_rxerror(true),
+            Self::TxFrameEnd => w.set_txframeend(true),
+        });
+    }
+
+    async fn wait(self) {
+        Self::wait_any(&[self]).await;
+    }
+
+    /// Waits for one of `events`, clears it, and returns it.
+    async fn wait_any(events: &[Self]) -> Self {
+        poll_fn(|cx| {
+            WAKER.register(cx.waker());
+            if let Some(&event) = events.iter().find(|event| event.register().read() != 0) {
+                event.clear();
+                return Poll::Ready(event);
+            }
+            for event in events {
+                event.enable_interrupt();
+            }
+            Poll::Pending
+        })
+        .await
+    }
+}
@@ -0,0 +1,114 @@
+//! Provides audio capture from PDM microphones with the PDM peripheral.
+//!
+//! The peripheral clocks the microphone and decimates its 1-bit PDM stream into 16-bit PCM
+//! samples in hardware, at about 16 kHz, which it writes into two DMA buffers in turn.
+//! A single microphone is captured, on the channel selected through [`Config::edge`].
+
+use ariel_os_audio::{Error, Flow, Microphone};
+use embassy_nrf::{
+    Peripheral, bind_interrupts,
+    gpio::Pin as GpioPin,
+    pdm::{self, Edge, InterruptHandler, OperationMode, SamplerState},
+};
+
+// NOTE(hal): the PDM peripheral has a different name on the nRF5340.
+#[cfg(context = "nrf52")]
+use embassy_nrf::peripherals::PDM;
+#[cfg(context = "nrf5340")]
+use embassy_nrf::peripherals::PDM0 as PDM;
+
+// NOTE(hal): the nRF52832 does not support configuring the decimation ratio, which is 64.
+#[cfg(not(context = "nrf52832"))]
+const SAMPLE_RATE: u32 = 1_280_000 / 80;
+#[cfg(context = "nrf52832")]
+const SAMPLE_RATE: u32 = 1_032_000 / 64;
+
+/// Clock edge on which the microphone outputs its samples.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SamplingEdge {
+    /// The microphone outputs its samples while the clock is low, usually when its L/R pin is
+    /// tied low.
+    #[default]
+    Falling,
+    /// The microphone outputs its samples while the clock is high, usually when its L/R pin is
+    /// tied high.
+    Rising,
+}
+
+/// PDM microphone configuration.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// Clock edge on which the microphone is sampled.
+    pub edge: SamplingEdge,
+}
+
+/// A PDM microphone, captured by the PDM peripheral.
+pub struct Pdm {
+    pdm: pdm::Pdm<'static, PDM>,
+}
+
+impl Pdm {
+    /// Returns a driver implementing [`Microphone`] for the PDM microphone connected to `clk` and
+    /// `din`.
+    #[must_use]
+    pub fn new(
+        pdm: impl Peripheral<P = PDM> + 'static,
+        clk: impl Peripheral<P = impl GpioPin> + 'static,
+        din: impl Peripheral<P = impl GpioPin> + 'static,
+        config: Config,
+    ) -> Self {
+        #[cfg(context = "nrf52")]
+        bind_interrupts!(
+            struct Irqs {
+                PDM => InterruptHandler<PDM>;
+            }
+        );
+        #[cfg(context = "nrf5340")]
+        bind_interrupts!(
+            struct Irqs {
+                PDM0 => InterruptHandler<PDM>;
+            }
+        );
+
+        let pdm_config = pdm::Config {
+            operation_mode: OperationMode::Mono,
+            edge: match config.edge {
+                SamplingEdge::Falling => Edge::LeftFalling,
+                SamplingEdge::Rising => Edge::LeftRising,
+            },
+            #[cfg(not(context = "nrf52832"))]
+            frequency: pdm::Frequency::_1280K,
+            #[cfg(not(context = "nrf52832"))]
+            ratio: pdm::Ratio::RATIO80,
+            // The default frequency of 1.032 MHz is used otherwise.
+            ..pdm::Config::default()
+        };
+
+        Self {
+            pdm: pdm::Pdm::new(pdm, Irqs, clk, din, pdm_config),
+        }
+    }
+}
+
+impl Microphone for Pdm {
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    async fn capture<const N: usize, S>(&mut self, mut sink: S) -> Result<(), Error>
+    where
+        S: FnMut(&[i16; N]) -> Flow,
+    {
+        let mut buffers = [[0; N]; 2];
+        self.pdm
+            .run_task_sampler(&mut buffers, |frame| match sink(frame) {
+                Flow::Continue => SamplerState::Sampled,
+                Flow::Stop => SamplerState::Stopped,
+            })
+            .await
+            .map_err(|_| Error::Hardware)
+    }
+}
@@ -23,19 +23,29 @@ fn from_bit_order(bit_order: BitOrder) -> embassy_nrf::spim::BitOrder {
 
 #[doc(hidden)]
 pub fn init(peripherals: &mut crate::OptionalPeripherals) {
+    // This macro has to be defined in this function so that the `peripherals` variables exists.
+    macro_rules! take_all_spi_peripherals {
+        ($( $peripheral:ident ),*) => {
+            $(
+                let peripheral = peripherals.$peripheral.take();
+                debug_assert!(
+                    peripheral.is_some(),
+                    concat!(stringify!($peripheral), " was taken before initialization"),
+                );
+            )*
+        }
+    }
+
     // Take all SPI peripherals and do nothing with them.
     cfg_if::cfg_if! {
         if #[cfg(context = "nrf52833")] {
-            let _ = peripherals.SPI3.take().unwrap();
+            take_all_spi_peripherals!(SPI3);
         } else if #[cfg(context = "nrf52840")] {
-            let _ = peripherals.SPI2.take().unwrap();
-            let _ = peripherals.SPI3.take().unwrap();
+            take_all_spi_peripherals!(SPI2, SPI3);
         } else if #[cfg(context = "nrf5340")] {
-            let _ = peripherals.SERIAL2.take().unwrap();
-            let _ = peripherals.SERIAL3.take().unwrap();
+            take_all_spi_peripherals!(SERIAL2, SERIAL3);
         } else if #[cfg(context = "nrf91")] {
-            let _ = peripherals.SERIAL2.take().unwrap();
-            let _ = peripherals.SERIAL3.take().unwrap();
+            take_all_spi_peripherals!(SERIAL2, SERIAL3);
         } else {
             compile_error!("this nRF chip is not supported");
         }
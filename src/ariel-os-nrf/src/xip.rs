@@ -0,0 +1,55 @@
+//! Maps the external flash of the board with the QSPI peripheral, for execute-in-place.
+
+use embassy_nrf::{bind_interrupts, peripherals, qspi};
+
+bind_interrupts!(struct Irqs {
+    QSPI => qspi::InterruptHandler<peripherals::QSPI>;
+});
+
+/// Maps the external flash of the board, in which the items marked with `#[ariel_os::xip]` are
+/// placed.
+///
+/// The QSPI peripheral stays enabled, as the mapping would be lost otherwise.
+pub fn init(peripherals: &mut crate::OptionalPeripherals) {
+    let mut config = qspi::Config::default();
+    // Unlike quad reads, dual reads do not require the quad mode of the flash to be enabled.
+    config.read_opcode = qspi::ReadOpcode::READ2IO;
+    config.write_opcode = qspi::WriteOpcode::PP;
+
+    cfg_if::cfg_if! {
+        if #[cfg(context = "nrf52840dk")] {
+            // MX25R6435F
+            config.capacity = 8 * 1024 * 1024;
+            let qspi = qspi::Qspi::new(
+                peripherals.QSPI.take().unwrap(),
+                Irqs,
+                peripherals.P0_19.take().unwrap(),
+                peripherals.P0_17.take().unwrap(),
+                peripherals.P0_20.take().unwrap(),
+                peripherals.P0_21.take().unwrap(),
+                peripherals.P0_22.take().unwrap(),
+                peripherals.P0_23.take().unwrap(),
+                config,
+            );
+        } else if #[cfg(context = "nrf5340dk")] {
+            // MX25R6435F
+            config.capacity = 8 * 1024 * 1024;
+            let qspi = qspi::Qspi::new(
+                peripherals.QSPI.take().unwrap(),
+                Irqs,
+                peripherals.P0_17.take().unwrap(),
+                peripherals.P0_18.take().unwrap(),
+                peripherals.P0_13.take().unwrap(),
+                peripherals.P0_14.take().unwrap(),
+                peripherals.P0_15.take().unwrap(),
+                peripherals.P0_16.take().unwrap(),
+                config,
+            );
+        } else {
+            compile_error!("the external flash of this board is not known");
+        }
+    }
+
+    // Dropping the driver would disable the peripheral.
+    core::mem::forget(qspi);
+}
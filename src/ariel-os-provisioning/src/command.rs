@@ -0,0 +1,162 @@
+//! Parsing of the requests of the provisioning protocol.
+
+/// A request of the provisioning protocol, with its hex-encoded arguments.
+pub(crate) enum Command<'a> {
+    Info,
+    DeviceKey(&'a str),
+    Secret { name: &'a str, value: &'a str },
+    Peer { credential: &'a str, scope: &'a str },
+    Setting { key: &'a str, value: &'a str },
+    Done,
+}
+
+impl<'a> Command<'a> {
+    /// Parses a request line, without its line terminator.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error message to report if the line is not a known request.
+    pub(crate) fn parse(line: &'a str) -> Result<Self, &'static str> {
+        let mut words = line.split_ascii_whitespace();
+        let command = match (words.next(), words.next(), words.next()) {
+            (Some("INFO"), None, None) => Self::Info,
+            (Some("DEVICE-KEY"), Some(key), None) => Self::DeviceKey(key),
+            (Some("SECRET"), Some(name), Some(value)) => Self::Secret { name, value },
+            (Some("SETTING"), Some(key), Some(value)) => Self::Setting { key, value },
+            (Some("PEER"), Some(credential), Some(scope)) => Self::Peer { credential, scope },
+            (Some("DONE"), None, None) => Self::Done,
+            _ => return Err("unknown request"),
+        };
+        if words.next().is_some() {
+            return Err("unknown request");
+        }
+        Ok(command)
+    }
+}
+
+/// Decodes `hex` into `buffer`, and returns the decoded bytes.
+///
+/// Returns `None` if `hex` is not valid hex, or if it does not fit into `buffer`.
+pub(crate) fn decode_hex<'b>(hex: &str, buffer: &'b mut [u8]) -> Option<&'b [u8]> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    let decoded = buffer.get_mut(..hex.len() / 2)?;
+    for (byte, pair) in decoded.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+        let [high, low] = pair else {
+            return None;
+        };
+        *byte = (nibble(*high)? << 4) | nibble(*low)?;
+    }
+    Some(decoded)
+}
+
+fn nibble(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_requests() {
+        assert!(matches!(Command::parse("INFO"), Ok(Command::Info)));
+        assert!(matches!(Command::parse("DONE"), Ok(Command::Done)));
+        assert!(matches!(
+            Command::parse("DEVICE-KEY 0011"),
+            Ok(Command::DeviceKey("0011"))
+        ));
+        assert!(matches!(
+            Command::parse("SECRET name 0011"),
+            Ok(Command::Secret {
+                name: "name",
+                value: "0011"
+            })
+        ));
+        assert!(matches!(
+            Command::parse("SETTING key 0011"),
+            Ok(Command::Setting {
+                key: "key",
+                value: "0011"
+            })
+        ));
+        assert!(matches!(
+            Command::parse("  PEER\ta1  81 "),
+            Ok(Command::Peer {
+                credential: "a1",
+                scope: "81"
+            })
+        ));
+    }
+
+    #[test]
+    fn parse_unknown_requests() {
+        for line in [
+            "",
+            " ",
+            "HELLO",
+            "info",
+            "INFO 00",
+            "DONE now",
+            "SECRET a b c",
+        ] {
+            assert_eq!(
+                Command::parse(line).err(),
+                Some("unknown request"),
+                "{line:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_missing_arguments() {
+        for line in [
+            "DEVICE-KEY",
+            "SECRET",
+            "SECRET name",
+            "SETTING key",
+            "PEER",
+            "PEER a1",
+        ] {
+            assert_eq!(
+                Command::parse(line).err(),
+                Some("unknown request"),
+                "{line:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn decode_valid_hex() {
+        let mut buffer = [0; 4];
+        assert_eq!(
+            decode_hex("00fFa9", &mut buffer),
+            Some(&[0x00, 0xff, 0xa9][..])
+        );
+        assert_eq!(decode_hex("", &mut buffer), Some(&[][..]));
+        assert_eq!(
+            decode_hex("01234567", &mut buffer),
+            Some(&[0x01, 0x23, 0x45, 0x67][..])
+        );
+    }
+
+    #[test]
+    fn decode_invalid_hex() {
+        let mut buffer = [0; 4];
+        // Odd length.
+        assert_eq!(decode_hex("abc", &mut buffer), None);
+        // Invalid digits, including non-ASCII ones.
+        assert_eq!(decode_hex("0g", &mut buffer), None);
+        assert_eq!(decode_hex("-1", &mut buffer), None);
+        assert_eq!(decode_hex("éé", &mut buffer), None);
+        // Too long for the buffer.
+        assert_eq!(decode_hex("0102030405", &mut buffer), None);
+    }
+}
@@ -0,0 +1,406 @@
+//! Provides a provisioning service, which writes credentials and settings into devices over a
+//! byte stream, eg. on manufacturing lines.
+//!
+//! The service speaks a line-based text protocol, so that it can be driven by scripts as well as
+//! from a serial terminal. It runs on anything implementing [`embedded_io_async::Read`] and
+//! [`embedded_io_async::Write`]; with the `usb-provisioning` feature, it is served on a USB serial
+//! port (see `ariel_os::usb::provisioning`), which devices typically only expose when a button or
+//! jumper is held at boot.
+//!
+//! # Protocol
+//!
+//! Each request is a line terminated by `\n` (a preceding `\r` is ignored), which is answered by
+//! a line holding either `OK`, possibly followed by values, or `ERR` followed by an error
+//! message. Binary values are hex-encoded.
+//!
+//! | Request | Effect | Response values |
+//! | --- | --- | --- |
+//! | `INFO` | | device ID (`-` if there is none), device CCS |
+//! | `DEVICE-KEY <secret key>` | replaces the [device key](ariel_os_identity::device_key) | device CCS |
+//! | `SECRET <name> <value>` | stores a secret in the [vault](ariel_os_vault) | |
+//! | `SETTING <key> <value>` | stores a value in [storage](ariel_os_storage::insert_blob()) | |
+//! | `PEER <credential> <scope>` | authorizes a [peer](peers) of the CoAP server | |
+//! | `DONE` | ends the session | |
+//!
+//! The device CCS is the credential of the device key, which the CoAP server uses as its EDHOC
+//! credential; it can be registered with the peers of the device.
+//! Peers are given as a CCS and the AIF scope they are authorized for, as in `peers.yml`, and are
+//! authorized in addition to the peers of `peers.yml` once the device restarts. Provisioning a
+//! peer again replaces its scope.
+//!
+//! Settings are stored as blobs under their key as given, and are read by the application with
+//! [`ariel_os_storage::get_blob()`], eg. for network settings. Keys starting with `ariel-os` are
+//! reserved for the system.
+//!
+//! ```text
+//! > INFO
+//! < OK 0123456789abcdef a108a101a401020240200121582065eda5a1...
+//! > SECRET lorawan-appkey 2b7e151628aed2a6abf7158809cf4f3c
+//! < OK
+//! > SETTING wifi-ssid 6d792d6e6574776f726b
+//! < OK
+//! > PEER a2027734... 81826c2f646961672f757074696d6501
+//! < OK
+//! > DONE
+//! < OK
+//! ```
+
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+mod command;
+pub mod peers;
+
+use core::fmt::Write as _;
+
+use ariel_os_debug::log::info;
+use ariel_os_identity::device_key;
+use embedded_io_async::{Read, Write};
+
+use command::{Command, decode_hex};
+
+/// Maximum length of a request line, without its line terminator.
+pub const MAX_LINE_LEN: usize = 520;
+
+/// Maximum length of the keys of settings.
+pub const MAX_SETTING_KEY_LEN: usize = 32;
+
+/// Maximum length of the values of settings, in bytes.
+pub const MAX_SETTING_LEN: usize = 128;
+
+/// Prefix of the storage keys that settings can not be stored under.
+const RESERVED_PREFIX: &str = "ariel-os";
+
+/// Maximum length of a response line, including its line terminator.
+const MAX_RESPONSE_LEN: usize = 192;
+
+type Response = heapless::String<MAX_RESPONSE_LEN>;
+
+/// Serves a provisioning session on `io`, until the host ends it with `DONE`.
+///
+/// Each request is applied as soon as it has been received.
+///
+/// # Errors
+///
+/// Returns [`Error::Io`] if reading from or writing to `io` failed, and [`Error::Closed`] if `io`
+/// was closed before the session was ended.
+pub async fn serve<IO: Read + Write>(io: &mut IO) -> Result<(), Error<IO::Error>> {
+    // Requests may hold secrets.
+    let mut line = secretcore::Secret::new([0; MAX_LINE_LEN]);
+
+    loop {
+        let mut response = Response::new();
+        let result = match read_line(io, line.expose_mut()).await? {
+            Some(request) => match core::str::from_utf8(request) {
+                Ok(request) => handle(request, &mut response).await,
+                Err(_) => Err("invalid request"),
+            },
+            None => Err("request too long"),
+        };
+        let done = matches!(result, Ok(true));
+
+        let status = match result {
+            Ok(_) => "OK",
+            Err(message) => {
+                response.clear();
+                // Cannot fail, error messages are short.
+                let _ = write!(response, " {message}");
+                "ERR"
+            }
+        };
+        for part in [status, &response, "\n"] {
+            io.write_all(part.as_bytes()).await.map_err(Error::Io)?;
+        }
+        io.flush().await.map_err(Error::Io)?;
+
+        if done {
+            info!("provisioning: done");
+            return Ok(());
+        }
+    }
+}
+
+/// Reads a request line into `buffer`, and returns it without its line terminator.
+///
+/// Returns `None` if the line does not fit into `buffer`, in which case the rest of the line is
+/// discarded.
+///
+/// # Errors
+///
+/// Returns [`Error::Io`] if reading failed, and [`Error::Closed`] if `io` was closed.
+async fn read_line<'b, IO: Read>(
+    io: &mut IO,
+    buffer: &'b mut [u8],
+) -> Result<Option<&'b [u8]>, Error<IO::Error>> {
+    let mut len = 0;
+    let mut overflow = false;
+    loop {
+        let mut byte = [0];
+        if io.read(&mut byte).await.map_err(Error::Io)? == 0 {
+            return Err(Error::Closed);
+        }
+        if byte == [b'\n'] {
+            break;
+        }
+        match buffer.get_mut(len) {
+            Some(slot) if !overflow => {
+                *slot = byte[0];
+                len += 1;
+            }
+            _ => overflow = true,
+        }
+    }
+    if overflow {
+        return Ok(None);
+    }
+
+    let line = buffer.get(..len).unwrap_or_default();
+    Ok(Some(line.strip_suffix(b"\r").unwrap_or(line)))
+}
+
+/// Applies `request`, and writes the values of the response into `response`.
+///
+/// Returns whether the session was ended.
+///
+/// # Errors
+///
+/// Returns the error message to report if the request failed.
+async fn handle(request: &str, response: &mut Response) -> Result<bool, &'static str> {
+    match Command::parse(request)? {
+        Command::Info => {
+            let device_key = device_key::device_key()
+                .await
+                .map_err(|_| "device key unavailable")?;
+            match ariel_os_identity::device_id_bytes() {
+                Ok(device_id) => write_hex(response, device_id.as_ref())?,
+                Err(_) => response.push_str(" -").map_err(|()| "response too long")?,
+            }
+            write_hex(response, &device_key.ccs())?;
+        }
+        Command::DeviceKey(hex) => {
+            let mut secret = secretcore::Secret::new([0; 32]);
+            if decode_hex(hex, secret.expose_mut()).map(<[u8]>::len) != Some(32) {
+                return Err("invalid device key");
+            }
+            let device_key =
+                device_key::set_device_key(secret.expose())
+                    .await
+                    .map_err(|e| match e {
+                        device_key::Error::InvalidKey => "invalid device key",
+                        _ => "storage access failed",
+                    })?;
+            write_hex(response, &device_key.ccs())?;
+            info!("provisioning: device key replaced");
+        }
+        Command::Secret { name, value } => {
+            if name.len() > ariel_os_vault::MAX_NAME_LEN {
+                return Err("name too long");
+            }
+            let mut buffer = secretcore::Secret::new([0; ariel_os_vault::MAX_SECRET_LEN]);
+            let secret = decode_hex(value, buffer.expose_mut()).ok_or("invalid value")?;
+            let vault = ariel_os_vault::vault()
+                .await
+                .map_err(|_| "vault unavailable")?;
+            vault
+                .key(name)
+                .store(secret)
+                .await
+                .map_err(|_| "storage access failed")?;
+            info!("provisioning: stored secret {}", name);
+        }
+        Command::Setting { key, value } => {
+            if key.len() > MAX_SETTING_KEY_LEN || key.starts_with(RESERVED_PREFIX) {
+                return Err("invalid key");
+            }
+            let mut buffer = [0; MAX_SETTING_LEN];
+            let value = decode_hex(value, &mut buffer).ok_or("invalid value")?;
+            ariel_os_storage::insert_blob(key, value)
+                .await
+                .map_err(|_| "storage access failed")?;
+            info!("provisioning: stored setting {}", key);
+        }
+        Command::Peer { credential, scope } => {
+            let mut credential_buffer = [0; peers::MAX_CREDENTIAL_LEN];
+            let mut scope_buffer = [0; peers::MAX_SCOPE_LEN];
+            let credential =
+                decode_hex(credential, &mut credential_buffer).ok_or("invalid credential")?;
+            let scope = decode_hex(scope, &mut scope_buffer).ok_or("invalid scope")?;
+            peers::add(credential, scope).await.map_err(|e| match e {
+                peers::Error::Storage => "storage access failed",
+                peers::Error::Invalid => "invalid peer credential",
+                peers::Error::TooManyPeers => "too many peer credentials",
+            })?;
+            info!("provisioning: stored peer credential");
+        }
+        Command::Done => return Ok(true),
+    }
+    Ok(false)
+}
+
+/// Appends a space and the hex encoding of `bytes` to `response`.
+///
+/// # Errors
+///
+/// Returns the error message to report if `response` is full.
+fn write_hex(response: &mut Response, bytes: &[u8]) -> Result<(), &'static str> {
+    write!(response, " ").map_err(|_| "response too long")?;
+    for byte in bytes {
+        write!(response, "{byte:02x}").map_err(|_| "response too long")?;
+    }
+    Ok(())
+}
+
+/// Errors that can occur when serving a provisioning session.
+///
+/// Errors of individual requests are reported to the host instead.
+#[derive(Debug)]
+pub enum Error<E> {
+    /// Reading from or writing to the byte stream failed.
+    Io(E),
+    /// The byte stream was closed before the session was ended.
+    Closed,
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: {e}"),
+            Self::Closed => write!(f, "closed before the session was ended"),
+        }
+    }
+}
+
+impl<E: core::error::Error> core::error::Error for Error<E> {}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use core::convert::Infallible;
+
+    use embassy_futures::block_on;
+
+    use super::*;
+
+    /// A byte stream that reads from `input`, and collects what is written.
+    struct Stream<'a> {
+        input: &'a [u8],
+        output: Vec<u8>,
+    }
+
+    impl embedded_io_async::ErrorType for Stream<'_> {
+        type Error = Infallible;
+    }
+
+    impl Read for Stream<'_> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            self.input.read(buf).await
+        }
+    }
+
+    impl Write for Stream<'_> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+            self.output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn read_lines() {
+        block_on(async {
+            let mut input = &b"INFO\r\n\nDONE\n"[..];
+            let mut buffer = [0; MAX_LINE_LEN];
+            assert_eq!(
+                read_line(&mut input, &mut buffer).await.unwrap(),
+                Some(&b"INFO"[..])
+            );
+            assert_eq!(
+                read_line(&mut input, &mut buffer).await.unwrap(),
+                Some(&b""[..])
+            );
+            assert_eq!(
+                read_line(&mut input, &mut buffer).await.unwrap(),
+                Some(&b"DONE"[..])
+            );
+            assert!(matches!(
+                read_line(&mut input, &mut buffer).await,
+                Err(Error::Closed)
+            ));
+        });
+    }
+
+    #[test]
+    fn read_line_closed_within_line() {
+        block_on(async {
+            let mut input = &b"INF"[..];
+            let mut buffer = [0; MAX_LINE_LEN];
+            assert!(matches!(
+                read_line(&mut input, &mut buffer).await,
+                Err(Error::Closed)
+            ));
+        });
+    }
+
+    #[test]
+    fn read_line_overflow() {
+        block_on(async {
+            let mut input = vec![b'0'; MAX_LINE_LEN];
+            input.push(b'\n');
+            input.extend_from_slice(&[b'0'; MAX_LINE_LEN + 1]);
+            input.extend_from_slice(b"\nINFO\n");
+            let mut input = &input[..];
+            let mut buffer = [0; MAX_LINE_LEN];
+
+            // A line of the maximum length still fits.
+            assert_eq!(
+                read_line(&mut input, &mut buffer)
+                    .await
+                    .unwrap()
+                    .map(<[u8]>::len),
+                Some(MAX_LINE_LEN)
+            );
+            // A longer one is discarded up to its end.
+            assert_eq!(read_line(&mut input, &mut buffer).await.unwrap(), None);
+            assert_eq!(
+                read_line(&mut input, &mut buffer).await.unwrap(),
+                Some(&b"INFO"[..])
+            );
+        });
+    }
+
+    #[test]
+    fn serve_rejects_invalid_requests() {
+        let mut input = b"HELLO\nSECRET\nDEVICE-KEY abc\nSECRET name 0g\n".to_vec();
+        input.extend_from_slice(b"SETTING ariel-os-key 00\nPEER a1 0\n\xff\n");
+        input.extend_from_slice(&[b'0'; MAX_LINE_LEN + 1]);
+        input.extend_from_slice(b"\nDONE\n");
+        let mut stream = Stream {
+            input: &input,
+            output: Vec::new(),
+        };
+
+        block_on(serve(&mut stream)).unwrap();
+        assert_eq!(
+            core::str::from_utf8(&stream.output).unwrap(),
+            "ERR unknown request\n\
+             ERR unknown request\n\
+             ERR invalid device key\n\
+             ERR invalid value\n\
+             ERR invalid key\n\
+             ERR invalid scope\n\
+             ERR invalid request\n\
+             ERR request too long\n\
+             OK\n"
+        );
+    }
+
+    #[test]
+    fn serve_closed_before_done() {
+        let mut stream = Stream {
+            input: b"HELLO\n",
+            output: Vec::new(),
+        };
+        assert!(matches!(block_on(serve(&mut stream)), Err(Error::Closed)));
+        assert_eq!(stream.output, b"ERR unknown request\n");
+    }
+}
@@ -0,0 +1,175 @@
+//! Peer credentials provisioned with `PEER`, which the CoAP server authorizes.
+//!
+//! The credentials are kept in a storage blob, as a CBOR array of arrays of a CCS and an AIF
+//! scope, the same way peers are described in `peers.yml`. The CoAP server loads them at startup
+//! (with the storage-backed server configuration); they can be retired at runtime like the peers
+//! of `peers.yml`, through the credentials management resource.
+
+use ariel_os_debug::log::warn;
+use minicbor::{
+    Decoder, Encoder,
+    data::Type,
+    encode::{Write as _, write::Cursor},
+};
+
+/// Maximum number of provisioned peer credentials.
+pub const MAX_PEERS: usize = 4;
+
+/// Maximum length of a provisioned peer credential (a CCS), in bytes.
+pub const MAX_CREDENTIAL_LEN: usize = 192;
+
+/// Maximum length of the scope of a provisioned peer credential (an AIF value), in bytes.
+pub const MAX_SCOPE_LEN: usize = 64;
+
+/// Maximum length of the encoded peer credentials, allowing for the CBOR headers.
+const MAX_PEERS_LEN: usize = 1 + MAX_PEERS * (MAX_CREDENTIAL_LEN + MAX_SCOPE_LEN + 4);
+
+/// Storage key of the blob holding the provisioned peer credentials.
+const PEERS_KEY: &str = "ariel-os-provisioning.peers";
+
+/// The peer credentials provisioned into the device.
+pub struct Peers {
+    encoded: heapless::Vec<u8, MAX_PEERS_LEN>,
+}
+
+impl Peers {
+    /// Loads the provisioned peer credentials from storage.
+    ///
+    /// Unreadable credentials are ignored, with a warning.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Storage`] if reading the storage failed.
+    pub async fn load() -> Result<Self, Error> {
+        let mut buffer = [0; MAX_PEERS_LEN];
+        let encoded = ariel_os_storage::get_blob(PEERS_KEY, &mut buffer)
+            .await
+            .map_err(|_| Error::Storage)?
+            .unwrap_or_default();
+        if decode(encoded).is_none() {
+            warn!("Provisioned peer credentials are unreadable, ignoring them");
+            return Ok(Self::empty());
+        }
+        // Cannot fail, the credentials were read into a buffer of that size.
+        let encoded = heapless::Vec::from_slice(encoded).unwrap_or_default();
+        Ok(Self { encoded })
+    }
+
+    fn empty() -> Self {
+        Self {
+            encoded: heapless::Vec::new(),
+        }
+    }
+
+    /// Returns the provisioned peer credentials, as pairs of a CCS and an AIF scope.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        decode(&self.encoded).unwrap_or_default().into_iter()
+    }
+}
+
+/// Adds the peer credential `credential` with `scope`, replacing any previous scope of it.
+///
+/// # Errors
+///
+/// Returns [`Error::Invalid`] if `credential` is not a CBOR map or `scope` not a CBOR item,
+/// [`Error::TooManyPeers`] if [`MAX_PEERS`] other peers are provisioned already, and
+/// [`Error::Storage`] if accessing the storage failed.
+pub(crate) async fn add(credential: &[u8], scope: &[u8]) -> Result<(), Error> {
+    if credential.len() > MAX_CREDENTIAL_LEN
+        || scope.len() > MAX_SCOPE_LEN
+        || !is_item(credential, true)
+        || !is_item(scope, false)
+    {
+        return Err(Error::Invalid);
+    }
+
+    let peers = Peers::load().await?;
+    let mut entries = decode(&peers.encoded).unwrap_or_default();
+    entries.retain(|&(other, _)| other != credential);
+    entries
+        .push((credential, scope))
+        .map_err(|_| Error::TooManyPeers)?;
+
+    let mut buffer = [0; MAX_PEERS_LEN];
+    let len = encode(&entries, &mut buffer).map_err(|_| Error::TooManyPeers)?;
+    ariel_os_storage::insert_blob(PEERS_KEY, buffer.get(..len).unwrap_or_default())
+        .await
+        .map_err(|_| Error::Storage)
+}
+
+type Entries<'a> = heapless::Vec<(&'a [u8], &'a [u8]), MAX_PEERS>;
+
+/// Decodes encoded peer credentials, where an empty slice holds none.
+fn decode(bytes: &[u8]) -> Option<Entries<'_>> {
+    let mut entries = Entries::new();
+    if bytes.is_empty() {
+        return Some(entries);
+    }
+
+    let mut decoder = Decoder::new(bytes);
+    for _ in 0..decoder.array().ok()?? {
+        if decoder.array().ok()? != Some(2) {
+            return None;
+        }
+        let credential = decoder.bytes().ok()?;
+        let start = decoder.position();
+        decoder.skip().ok()?;
+        let scope = bytes.get(start..decoder.position())?;
+        entries.push((credential, scope)).ok()?;
+    }
+    (decoder.position() == bytes.len()).then_some(entries)
+}
+
+/// Encodes `entries` into `buffer`, and returns the encoded length.
+///
+/// # Errors
+///
+/// Returns an error if the encoded credentials do not fit into `buffer`.
+fn encode(
+    entries: &[(&[u8], &[u8])],
+    buffer: &mut [u8],
+) -> Result<usize, minicbor::encode::Error<minicbor::encode::write::EndOfSlice>> {
+    let mut encoder = Encoder::new(Cursor::new(buffer));
+    encoder.array(entries.len() as u64)?;
+    for (credential, scope) in entries {
+        encoder.array(2)?.bytes(credential)?;
+        // The scope is kept as it was provisioned.
+        encoder
+            .writer_mut()
+            .write_all(scope)
+            .map_err(minicbor::encode::Error::write)?;
+    }
+    Ok(encoder.into_writer().position())
+}
+
+/// Returns whether `bytes` is a single well-formed CBOR item, which is a map if `map` is set.
+fn is_item(bytes: &[u8], map: bool) -> bool {
+    let mut decoder = Decoder::new(bytes);
+    let is_map = decoder
+        .datatype()
+        .is_ok_and(|datatype| datatype == Type::Map);
+    (is_map || !map) && decoder.skip().is_ok() && decoder.position() == bytes.len()
+}
+
+/// Errors that can occur when provisioning peer credentials.
+#[derive(Debug)]
+pub enum Error {
+    /// Accessing the storage failed.
+    Storage,
+    /// The credential is not a CBOR map, or its scope is not a CBOR item.
+    Invalid,
+    /// [`MAX_PEERS`] peer credentials are provisioned already.
+    TooManyPeers,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Storage => write!(f, "storage access failed"),
+            Self::Invalid => write!(f, "invalid peer credential"),
+            Self::TooManyPeers => write!(f, "too many peer credentials"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
@@ -0,0 +1,335 @@
+//! The cryptographically secure global RNG, which is reseeded from an entropy pool.
+
+use ariel_os_debug::log::{error, warn};
+use rand_chacha::ChaCha20Rng;
+use rand_core::{RngCore, SeedableRng};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroize;
+
+use crate::{HealthTestError, HwrngFailurePolicy, NoiseSource, health::HealthTests};
+
+/// Amount of credited entropy after which the DRBG is reseeded from the pool.
+const RESEED_THRESHOLD_BITS: u32 = 256;
+
+/// Byte mixed into the pool ahead of application entropy, distinct from any [`NoiseSource`].
+const APPLICATION_DOMAIN: u8 = 0xff;
+
+/// Number of bytes read from the hardware RNG for the initial seed.
+const INITIAL_SAMPLE_LEN: usize = 64;
+
+/// A deterministic random bit generator built on [`ChaCha20Rng`], reseeded from an entropy pool.
+///
+/// Noise and application entropy are hashed into the pool. Once the pool holds enough credited
+/// entropy (or application entropy was added), the next output first replaces the generator's key by
+/// a hash of the pool and of output of the current state, so that the new state depends on both
+/// and reveals neither.
+pub(crate) struct Drbg {
+    rng: ChaCha20Rng,
+    pool: Sha256,
+    pool_bits: u32,
+    reseed_requested: bool,
+    health: [HealthTests; NoiseSource::COUNT],
+    policy: HwrngFailurePolicy,
+    hwrng_failure: Option<HealthTestError>,
+}
+
+impl Drbg {
+    /// Seeds a new DRBG from the hardware RNG, whose output is run through the health tests.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the hardware RNG failed to provide samples, or if they failed the
+    /// health tests and the policy does not allow seeding from them nevertheless.
+    #[expect(clippy::missing_panics_doc, reason = "does not panic")]
+    pub(crate) fn from_hwrng(
+        mut hwrng: impl RngCore,
+        policy: HwrngFailurePolicy,
+    ) -> Result<Self, InitError> {
+        let mut health = NoiseSource::ALL.map(|source| HealthTests::new(source.min_entropy_bits()));
+
+        let mut sample = [0; INITIAL_SAMPLE_LEN];
+        hwrng
+            .try_fill_bytes(&mut sample)
+            .map_err(|_| InitError::Hardware)?;
+        let hardware_tests = health
+            .get_mut(NoiseSource::Hardware as usize)
+            .expect("every source has health tests");
+        let mut hwrng_failure = None;
+        for byte in sample {
+            if let Err(e) = hardware_tests.feed(byte) {
+                // Without a previous seed, there is nothing to fall back to.
+                if policy != HwrngFailurePolicy::Degrade {
+                    return Err(InitError::HealthTest(e));
+                }
+                hwrng_failure = Some(e);
+            }
+        }
+        if hwrng_failure.is_some() {
+            warn!("hardware RNG failed the health tests, seeding from it nevertheless");
+        }
+
+        let mut seed: [u8; 32] = Sha256::digest(sample).into();
+        sample.zeroize();
+        let rng = ChaCha20Rng::from_seed(seed);
+        seed.zeroize();
+
+        Ok(Self {
+            rng,
+            pool: Sha256::new(),
+            pool_bits: 0,
+            reseed_requested: false,
+            health,
+            policy,
+            hwrng_failure,
+        })
+    }
+
+    /// Mixes samples from a noise source into the pool after running them through the source's
+    /// health tests.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the samples failed the health tests, or if they come from a hardware
+    /// RNG that is not used any more.
+    ///
+    /// # Panics
+    ///
+    /// Panics if samples from the hardware RNG failed the health tests and the policy is to halt.
+    pub(crate) fn add_noise(
+        &mut self,
+        source: NoiseSource,
+        samples: &[u8],
+    ) -> Result<(), HealthTestError> {
+        if let (NoiseSource::Hardware, HwrngFailurePolicy::FallBackToDrbg, Some(e)) =
+            (source, self.policy, self.hwrng_failure)
+        {
+            return Err(e);
+        }
+
+        let tests = self
+            .health
+            .get_mut(source as usize)
+            .expect("every source has health tests");
+        let result = samples.iter().try_for_each(|sample| tests.feed(*sample));
+        if let Err(e) = result {
+            if source == NoiseSource::Hardware {
+                self.on_hwrng_failure(e);
+            }
+            return Err(e);
+        }
+
+        self.pool.update([source as u8]);
+        self.pool.update(samples);
+        let credit = u32::try_from(samples.len())
+            .unwrap_or(u32::MAX)
+            .saturating_mul(u32::from(source.min_entropy_bits()));
+        self.pool_bits = self.pool_bits.saturating_add(credit);
+        Ok(())
+    }
+
+    /// Applies the [`HwrngFailurePolicy`] to a health test failure of the hardware RNG.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the policy is to halt.
+    fn on_hwrng_failure(&mut self, e: HealthTestError) {
+        self.hwrng_failure = Some(e);
+        match self.policy {
+            HwrngFailurePolicy::Halt => panic!("Hardware RNG failed the health tests: {e}"),
+            HwrngFailurePolicy::Degrade => {
+                warn!("hardware RNG failed the health tests, continuing to use it");
+            }
+            HwrngFailurePolicy::FallBackToDrbg => {
+                error!("hardware RNG failed the health tests, no longer using it");
+            }
+        }
+    }
+
+    /// Returns the most recent health test failure of the hardware RNG.
+    pub(crate) fn hwrng_failure(&self) -> Option<HealthTestError> {
+        self.hwrng_failure
+    }
+
+    /// Mixes data into the pool without crediting any entropy, and requests a reseed.
+    pub(crate) fn add_entropy(&mut self, data: &[u8]) {
+        self.pool.update([APPLICATION_DOMAIN]);
+        self.pool.update(data);
+        self.reseed_requested = true;
+    }
+
+    /// Reseeds from the pool if that is due.
+    fn maybe_reseed(&mut self) {
+        if self.pool_bits < RESEED_THRESHOLD_BITS && !self.reseed_requested {
+            return;
+        }
+
+        let mut current = [0; 32];
+        self.rng.fill_bytes(&mut current);
+        let pool = core::mem::take(&mut self.pool);
+        let mut seed: [u8; 32] = pool.chain_update(current).finalize().into();
+        current.zeroize();
+        self.rng = ChaCha20Rng::from_seed(seed);
+        seed.zeroize();
+
+        self.pool_bits = 0;
+        self.reseed_requested = false;
+    }
+}
+
+impl RngCore for Drbg {
+    fn next_u32(&mut self) -> u32 {
+        self.maybe_reseed();
+        self.rng.next_u32()
+    }
+    fn next_u64(&mut self) -> u64 {
+        self.maybe_reseed();
+        self.rng.next_u64()
+    }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.maybe_reseed();
+        self.rng.fill_bytes(dest);
+    }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.maybe_reseed();
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+impl rand_core::CryptoRng for Drbg {}
+
+/// Reasons why the DRBG could not be seeded.
+#[derive(Debug)]
+pub(crate) enum InitError {
+    /// The hardware RNG returned an error.
+    Hardware,
+    /// The hardware RNG's output failed the health tests.
+    HealthTest(HealthTestError),
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use rand_core::SeedableRng as _;
+    use rand_pcg::Pcg32;
+
+    use super::*;
+
+    /// A hardware RNG that is stuck at a single value.
+    struct StuckRng;
+
+    impl RngCore for StuckRng {
+        fn next_u32(&mut self) -> u32 {
+            0x4242_4242
+        }
+        fn next_u64(&mut self) -> u64 {
+            0x4242_4242_4242_4242
+        }
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            dest.fill(0x42);
+        }
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    fn healthy_drbg(policy: HwrngFailurePolicy) -> Drbg {
+        Drbg::from_hwrng(Pcg32::seed_from_u64(1), policy).unwrap()
+    }
+
+    fn healthy_samples(seed: u64) -> [u8; 64] {
+        let mut samples = [0; 64];
+        Pcg32::seed_from_u64(seed).fill_bytes(&mut samples);
+        samples
+    }
+
+    #[test]
+    fn seeds_from_healthy_hwrng() {
+        let mut drbg = healthy_drbg(HwrngFailurePolicy::Halt);
+        assert_eq!(drbg.hwrng_failure(), None);
+        assert_ne!(drbg.next_u64(), drbg.next_u64());
+    }
+
+    #[test]
+    fn stuck_initial_seed() {
+        for policy in [HwrngFailurePolicy::Halt, HwrngFailurePolicy::FallBackToDrbg] {
+            assert!(matches!(
+                Drbg::from_hwrng(StuckRng, policy),
+                Err(InitError::HealthTest(HealthTestError::RepetitionCount))
+            ));
+        }
+
+        let drbg = Drbg::from_hwrng(StuckRng, HwrngFailurePolicy::Degrade).unwrap();
+        assert_eq!(drbg.hwrng_failure(), Some(HealthTestError::RepetitionCount));
+    }
+
+    #[test]
+    #[should_panic(expected = "failed the health tests")]
+    fn stuck_hwrng_halts() {
+        let mut drbg = healthy_drbg(HwrngFailurePolicy::Halt);
+        let _ = drbg.add_noise(NoiseSource::Hardware, &[0x42; 64]);
+    }
+
+    #[test]
+    fn stuck_hwrng_falls_back_to_drbg() {
+        let mut drbg = healthy_drbg(HwrngFailurePolicy::FallBackToDrbg);
+        assert_eq!(
+            drbg.add_noise(NoiseSource::Hardware, &[0x42; 64]),
+            Err(HealthTestError::RepetitionCount)
+        );
+        assert_eq!(drbg.hwrng_failure(), Some(HealthTestError::RepetitionCount));
+
+        // The hardware RNG is not used any more, even once it recovers.
+        assert_eq!(
+            drbg.add_noise(NoiseSource::Hardware, &healthy_samples(2)),
+            Err(HealthTestError::RepetitionCount)
+        );
+        assert_eq!(drbg.pool_bits, 0);
+
+        // The DRBG continues from its previous seed, and other sources still reseed it.
+        assert_ne!(drbg.next_u64(), drbg.next_u64());
+        assert_eq!(
+            drbg.add_noise(NoiseSource::Jitter, &healthy_samples(3)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn stuck_hwrng_degrades() {
+        let mut drbg = healthy_drbg(HwrngFailurePolicy::Degrade);
+        assert_eq!(
+            drbg.add_noise(NoiseSource::Hardware, &[0x42; 64]),
+            Err(HealthTestError::RepetitionCount)
+        );
+        assert_eq!(drbg.hwrng_failure(), Some(HealthTestError::RepetitionCount));
+
+        // The hardware RNG continues to be used.
+        assert_eq!(
+            drbg.add_noise(NoiseSource::Hardware, &healthy_samples(2)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn noise_reseeds() {
+        let mut reference = healthy_drbg(HwrngFailurePolicy::Halt);
+        let mut drbg = healthy_drbg(HwrngFailurePolicy::Halt);
+        assert_eq!(drbg.next_u64(), reference.next_u64());
+
+        // 64 bytes of hardware noise are credited with 256 bits.
+        assert_eq!(
+            drbg.add_noise(NoiseSource::Hardware, &healthy_samples(2)),
+            Ok(())
+        );
+        assert_ne!(drbg.next_u64(), reference.next_u64());
+        assert_eq!(drbg.pool_bits, 0);
+    }
+
+    #[test]
+    fn application_entropy_reseeds() {
+        let mut reference = healthy_drbg(HwrngFailurePolicy::Halt);
+        let mut drbg = healthy_drbg(HwrngFailurePolicy::Halt);
+        drbg.add_entropy(b"not secret, but reseeds");
+        assert_ne!(drbg.next_u64(), reference.next_u64());
+    }
+}
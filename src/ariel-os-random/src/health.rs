@@ -0,0 +1,202 @@
+//! Continuous health tests for noise sources, following NIST SP 800-90B section 4.4.
+
+use crate::HealthTestError;
+
+/// Window size of the Adaptive Proportion Test for non-binary samples.
+const APT_WINDOW: u16 = 512;
+
+/// Runs the Repetition Count Test and the Adaptive Proportion Test on the byte-sized samples of a
+/// single noise source.
+pub(crate) struct HealthTests {
+    rct_cutoff: u16,
+    apt_cutoff: u16,
+    /// Most recent sample, and how many times in a row it has been seen.
+    rct_state: Option<(u8, u16)>,
+    /// First sample of the current window, how many times it has been seen in the window, and the
+    /// number of samples in the window so far.
+    apt_state: Option<(u8, u16, u16)>,
+}
+
+impl HealthTests {
+    /// Creates the health tests for a source that is assumed to provide at least
+    /// `min_entropy_bits` bits of min-entropy per byte.
+    ///
+    /// The cutoffs are chosen for a false positive probability of 2^-20.
+    pub(crate) const fn new(min_entropy_bits: u8) -> Self {
+        // RCT: C = 1 + ceil(20 / H); APT: the critical value of the binomial distribution with
+        // W = 512 and p = 2^-H.
+        let (rct_cutoff, apt_cutoff) = match min_entropy_bits {
+            0 | 1 => (21, 311),
+            2 => (11, 178),
+            3 => (8, 104),
+            4..=7 => (6, 63),
+            _ => (4, 14),
+        };
+        Self {
+            rct_cutoff,
+            apt_cutoff,
+            rct_state: None,
+            apt_state: None,
+        }
+    }
+
+    /// Feeds a sample to the tests.
+    ///
+    /// After a failure, the tests start over.
+    ///
+    /// # Errors
+    ///
+    /// Returns the test that failed.
+    pub(crate) fn feed(&mut self, sample: u8) -> Result<(), HealthTestError> {
+        let result = self
+            .repetition_count(sample)
+            .and(self.adaptive_proportion(sample));
+        if result.is_err() {
+            self.rct_state = None;
+            self.apt_state = None;
+        }
+        result
+    }
+
+    /// Runs the Repetition Count Test of SP 800-90B section 4.4.1.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `sample` was repeated too often in a row.
+    fn repetition_count(&mut self, sample: u8) -> Result<(), HealthTestError> {
+        let count = match self.rct_state {
+            Some((last, count)) if last == sample => count + 1,
+            _ => 1,
+        };
+        self.rct_state = Some((sample, count));
+        if count >= self.rct_cutoff {
+            return Err(HealthTestError::RepetitionCount);
+        }
+        Ok(())
+    }
+
+    /// Runs the Adaptive Proportion Test of SP 800-90B section 4.4.2.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the first sample of the window occurred too often in it.
+    fn adaptive_proportion(&mut self, sample: u8) -> Result<(), HealthTestError> {
+        let (first, count, seen) = match self.apt_state {
+            Some((first, count, seen)) if seen < APT_WINDOW => {
+                (first, count + u16::from(first == sample), seen + 1)
+            }
+            _ => (sample, 1, 1),
+        };
+        self.apt_state = Some((first, count, seen));
+        if count >= self.apt_cutoff {
+            return Err(HealthTestError::AdaptiveProportion);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use super::*;
+
+    /// Feeds `occurrences` samples of `value`, in runs of `run` samples separated by another
+    /// sample, and returns the number of samples fed.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first failure of the tests.
+    fn feed_runs(
+        tests: &mut HealthTests,
+        value: u8,
+        run: usize,
+        occurrences: usize,
+    ) -> Result<usize, HealthTestError> {
+        let mut fed = 0;
+        for i in 0..occurrences {
+            if i > 0 && i % run == 0 {
+                tests.feed(value.wrapping_add(1))?;
+                fed += 1;
+            }
+            tests.feed(value)?;
+            fed += 1;
+        }
+        Ok(fed)
+    }
+
+    #[test]
+    fn repetition_count_cutoffs() {
+        for (min_entropy_bits, cutoff) in [(1, 21), (2, 11), (3, 8), (4, 6), (8, 4)] {
+            let mut tests = HealthTests::new(min_entropy_bits);
+            for _ in 1..cutoff {
+                assert_eq!(tests.feed(0x42), Ok(()));
+            }
+            assert_eq!(tests.feed(0x42), Err(HealthTestError::RepetitionCount));
+        }
+    }
+
+    #[test]
+    fn repetition_count_resets_on_change() {
+        let mut tests = HealthTests::new(4);
+        for _ in 0..10 {
+            for _ in 1..6 {
+                assert_eq!(tests.feed(0x42), Ok(()));
+            }
+            assert_eq!(tests.feed(0x43), Ok(()));
+            assert_eq!(tests.feed(0x44), Ok(()));
+        }
+    }
+
+    #[test]
+    fn adaptive_proportion_cutoffs() {
+        for (min_entropy_bits, rct_cutoff, apt_cutoff) in [
+            (1, 21, 311),
+            (2, 11, 178),
+            (3, 8, 104),
+            (4, 6, 63),
+            (8, 4, 14),
+        ] {
+            let mut tests = HealthTests::new(min_entropy_bits);
+            assert!(feed_runs(&mut tests, 0x42, rct_cutoff - 1, apt_cutoff - 1).is_ok());
+            assert_eq!(tests.feed(0x43), Ok(()));
+            assert_eq!(tests.feed(0x42), Err(HealthTestError::AdaptiveProportion));
+        }
+    }
+
+    #[test]
+    fn adaptive_proportion_window() {
+        // Occurrences below the cutoff in one window do not count towards the next one.
+        let mut tests = HealthTests::new(4);
+        let mut fed = feed_runs(&mut tests, 0x42, 5, 62).unwrap();
+        while fed < usize::from(APT_WINDOW) {
+            let other = if fed.is_multiple_of(2) { 0x10 } else { 0x20 };
+            assert_eq!(tests.feed(other), Ok(()));
+            fed += 1;
+        }
+        assert!(feed_runs(&mut tests, 0x42, 5, 62).is_ok());
+    }
+
+    #[test]
+    fn restarts_after_failure() {
+        let mut tests = HealthTests::new(4);
+        for _ in 1..6 {
+            assert_eq!(tests.feed(0), Ok(()));
+        }
+        assert_eq!(tests.feed(0), Err(HealthTestError::RepetitionCount));
+        // The samples before the failure do not count towards the next run.
+        for _ in 1..6 {
+            assert_eq!(tests.feed(0), Ok(()));
+        }
+        assert_eq!(tests.feed(0), Err(HealthTestError::RepetitionCount));
+    }
+
+    #[test]
+    fn passes_uniform_samples() {
+        let mut tests = HealthTests::new(8);
+        for i in 0..4096_u32 {
+            // A different permutation of the byte values in every 256 samples.
+            let sample = u8::try_from((i * 167 + i / 256) % 256).unwrap();
+            assert_eq!(tests.feed(sample), Ok(()));
+        }
+    }
+}
@@ -22,10 +22,62 @@
 //! arbitrarily) uses the [`rand_chacha::ChaCha20Rng`] generator as a shared global RNG, and
 //! [`rand_pcg::Pcg32`] is decided yet for the fast one. Neither the algorithm nor the size of
 //! [`FastRng`] or [`CryptoRng`] is guaranteed.
+//!
+//! # Reseeding
+//!
+//! When the `csprng` feature is enabled, the global RNG is a deterministic random bit generator
+//! (DRBG) that is reseeded from an entropy pool during operation:
+//!
+//! * Drivers with access to noise sources (such as a hardware RNG, clock jitter or radio noise)
+//!   feed their raw samples through [`add_noise()`]. The samples are checked by continuous health
+//!   tests (the Repetition Count Test and the Adaptive Proportion Test of NIST SP 800-90B), and are
+//!   credited with a conservative entropy estimate that depends on the [`NoiseSource`]. Once
+//!   enough entropy has been credited, the DRBG is reseeded before producing further output.
+//! * Applications can mix in any data they consider unpredictable through [`add_entropy()`].
+//!   No entropy is credited for it, but it causes a reseed before the next output.
+//!
+//! The initial seed is taken from the hardware RNG, and runs through the same health tests. The
+//! hardware RNG is then kept, and read again every `CONFIG_HWRNG_RESEED_INTERVAL_SECS` seconds
+//! (see [`HWRNG_RESEED_INTERVAL_SECS`]), whose samples are fed through [`add_noise()`] as well.
+//! This needs the `time` laze module. As the hardware RNG is kept, its peripheral is taken by the
+//! system with the `hwrng` laze module, and is not available to applications any more.
+//!
+//! # Hardware RNG failures
+//!
+//! A hardware RNG that silently stops producing entropy would make the output predictable, which
+//! is catastrophic eg. for OSCORE nonces. Therefore, what happens when samples from the hardware
+//! RNG fail the health tests (both for the initial seed and later through [`add_noise()`]) is
+//! defined by a [`HwrngFailurePolicy`], which is selected at build time through the
+//! `CONFIG_HWRNG_FAILURE_POLICY` environment variable:
+//!
+//! * `halt` (the default): the system panics;
+//! * `degrade`: a warning is logged, and the hardware RNG continues to be used;
+//! * `drbg`: an error is logged, and the hardware RNG is not used any more. The DRBG continues to
+//!   operate from its previous seed, and is still reseeded from other noise sources. As there is no
+//!   previous seed when the initial seed fails, the system panics in that case.
+//!
+//! Applications can check whether a failure has occurred through [`hwrng_failure()`].
+//!
+//! # Reproducible simulations
+//!
+//! On emulated boards, the `random-seed` laze module seeds the global RNG from the
+//! `CONFIG_RANDOM_SEED` environment variable at build time instead of the hardware RNG, so that
+//! simulations of protocols (CoAP tokens, retransmission jitter, ...) produce the same random
+//! numbers in every run of the same build. Noise and entropy added through [`add_noise()`] and
+//! [`add_entropy()`] are then ignored, so that the output only depends on the seed and on the order
+//! in which random numbers are requested.
 #![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+
+#[cfg(feature = "csprng")]
+mod drbg;
+#[cfg(feature = "csprng")]
+mod health;
 
 use core::{cell::RefCell, marker::PhantomData};
 
+#[cfg(feature = "csprng")]
+use embassy_sync::blocking_mutex::{self, raw::CriticalSectionRawMutex};
 use embassy_sync::once_lock::OnceLock;
 use rand_core::{RngCore, SeedableRng};
 
@@ -39,7 +91,7 @@ static RNG: OnceLock<RefCell<SelectedRng>> = OnceLock::new();
 /// If calls to [`rng()`] are rare, it may even make sense to move the HWRNG in here to get a
 /// ZST global.
 #[cfg(feature = "csprng")]
-pub(crate) type SelectedRng = rand_chacha::ChaCha20Rng;
+pub(crate) type SelectedRng = drbg::Drbg;
 
 /// Type of the global RNG when cryptographically secure random numbers are not needed.
 #[cfg(not(feature = "csprng"))]
@@ -131,13 +183,260 @@ mod csprng {
 /// # Panics
 ///
 /// - Panics if the underlying RNG returns an error.
+/// - Panics if the underlying RNG's output fails the health tests, unless the
+///   [`HwrngFailurePolicy::Degrade`] policy is selected.
 /// - Panics if this function is called multiple times.
 #[doc(hidden)]
 pub fn construct_rng(hwrng: impl RngCore) {
-    RNG.init(RefCell::new(
-        SelectedRng::from_rng(hwrng).expect("Hardware RNG failed to provide entropy"),
-    ))
-    .unwrap();
+    #[cfg(feature = "csprng")]
+    let rng = match drbg::Drbg::from_hwrng(hwrng, HWRNG_FAILURE_POLICY) {
+        Ok(rng) => rng,
+        Err(drbg::InitError::Hardware) => panic!("Hardware RNG failed to provide entropy"),
+        Err(drbg::InitError::HealthTest(e)) => panic!("Hardware RNG failed the health tests: {e}"),
+    };
+    #[cfg(not(feature = "csprng"))]
+    let rng = SelectedRng::from_rng(hwrng).expect("Hardware RNG failed to provide entropy");
+
+    assert!(
+        RNG.init(RefCell::new(rng)).is_ok(),
+        "RNG was already initialized"
+    );
+}
+
+/// The hardware RNG, kept by [`construct_rng_from_hwrng()`] for [`reseed_from_hwrng()`].
+///
+/// As [`reseed_from_hwrng()`] can be called from any thread or core, the hardware RNG is only
+/// accessed in a critical section.
+#[cfg(feature = "csprng")]
+static HWRNG: OnceLock<
+    blocking_mutex::Mutex<CriticalSectionRawMutex, RefCell<&'static mut (dyn RngCore + Send)>>,
+> = OnceLock::new();
+
+/// Number of bytes read from the hardware RNG by [`reseed_from_hwrng()`], which are credited with
+/// enough entropy to reseed the global RNG.
+#[cfg(feature = "csprng")]
+const HWRNG_SAMPLE_LEN: usize = 64;
+
+/// Interval at which the hardware RNG is read again to reseed the global RNG, configured through
+/// the `CONFIG_HWRNG_RESEED_INTERVAL_SECS` environment variable.
+#[cfg(feature = "csprng")]
+pub const HWRNG_RESEED_INTERVAL_SECS: u64 = ariel_os_utils::u64_from_env_or!(
+    "CONFIG_HWRNG_RESEED_INTERVAL_SECS",
+    60,
+    "interval at which the hardware RNG is read again to reseed the global RNG, in seconds"
+);
+
+/// Populates the global RNG from the hardware RNG, and keeps the hardware RNG for
+/// [`reseed_from_hwrng()`].
+///
+/// # Panics
+///
+/// Panics in the same cases as [`construct_rng()`].
+#[doc(hidden)]
+pub fn construct_rng_from_hwrng(hwrng: &'static mut (dyn RngCore + Send)) {
+    construct_rng(&mut *hwrng);
+    #[cfg(feature = "csprng")]
+    {
+        // Cannot fail, as `construct_rng()` panics when called again.
+        let _ = HWRNG.init(blocking_mutex::Mutex::new(RefCell::new(hwrng)));
+    }
+}
+
+/// Reads samples from the hardware RNG kept by [`construct_rng_from_hwrng()`], and mixes them
+/// into the global RNG's entropy pool through [`add_noise()`].
+///
+/// This is called every [`HWRNG_RESEED_INTERVAL_SECS`] by the `ariel-os-embassy` initialization
+/// functions.
+///
+/// # Errors
+///
+/// Returns an error if the samples failed the health tests, see [`add_noise()`].
+///
+/// # Panics
+///
+/// Panics in the same cases as [`add_noise()`].
+#[cfg(feature = "csprng")]
+#[doc(hidden)]
+pub fn reseed_from_hwrng() -> Result<(), HealthTestError> {
+    use zeroize::Zeroize as _;
+
+    let Some(hwrng) = HWRNG.try_get() else {
+        return Ok(());
+    };
+    let mut samples = [0; HWRNG_SAMPLE_LEN];
+    let read = hwrng.lock(|hwrng| hwrng.borrow_mut().try_fill_bytes(&mut samples));
+    let result = if read.is_ok() {
+        add_noise(NoiseSource::Hardware, &samples)
+    } else {
+        // Reading is retried on the next call.
+        ariel_os_debug::log::warn!("hardware RNG failed to provide samples");
+        Ok(())
+    };
+    samples.zeroize();
+    result
+}
+
+/// Seed of the global RNG with the `seed` feature, configured through the `CONFIG_RANDOM_SEED`
+/// environment variable.
+#[cfg(feature = "seed")]
+pub const SEED: u64 = ariel_os_utils::u64_from_env_or!(
+    "CONFIG_RANDOM_SEED",
+    0,
+    "seed of the global RNG in reproducible simulations"
+);
+
+/// Populates the global RNG from [`SEED`], see the
+/// [module level documentation](crate#reproducible-simulations).
+///
+/// # Panics
+///
+/// Panics if this function is called multiple times.
+#[cfg(feature = "seed")]
+#[doc(hidden)]
+pub fn construct_seeded_rng() {
+    construct_rng(rand_pcg::Pcg32::seed_from_u64(SEED));
+}
+
+/// Mixes raw samples from a noise source into the global RNG's entropy pool.
+///
+/// The samples are run through the source's continuous health tests first; samples from sources
+/// whose entropy is concentrated in few bits (e.g., timer values) should be passed with their
+/// low-order bits in separate bytes. See the [module level documentation](crate#reseeding) for
+/// details.
+///
+/// # Errors
+///
+/// Returns an error if the samples failed the health tests, in which case they are discarded.
+/// This indicates that the noise source may be malfunctioning. Samples from the hardware RNG are
+/// also rejected with the error of the earlier failure once it has been disabled by the
+/// [`HwrngFailurePolicy::FallBackToDrbg`] policy.
+///
+/// # Panics
+///
+/// Panics if samples from the hardware RNG fail the health tests with the
+/// [`HwrngFailurePolicy::Halt`] policy.
+#[cfg(feature = "csprng")]
+pub fn add_noise(source: NoiseSource, samples: &[u8]) -> Result<(), HealthTestError> {
+    // The output of seeded RNGs only depends on the seed.
+    if cfg!(feature = "seed") {
+        return Ok(());
+    }
+    with_global(|rng| rng.add_noise(source, samples))
+}
+
+/// Mixes application provided data into the global RNG's entropy pool.
+///
+/// No entropy is credited for the data, but the RNG is reseeded before producing further output,
+/// so that the data takes effect right away.
+#[cfg(feature = "csprng")]
+pub fn add_entropy(data: &[u8]) {
+    // The output of seeded RNGs only depends on the seed.
+    if cfg!(feature = "seed") {
+        return;
+    }
+    with_global(|rng| rng.add_entropy(data));
+}
+
+/// A kind of noise source that can feed the global RNG through [`add_noise()`].
+///
+/// The kind determines how much entropy is assumed per byte of samples.
+#[cfg(feature = "csprng")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NoiseSource {
+    /// A hardware random number generator (TRNG).
+    Hardware,
+    /// Timing jitter, e.g., between independent clocks.
+    Jitter,
+    /// Noise picked up by a radio, e.g., RSSI readings.
+    Radio,
+}
+
+#[cfg(feature = "csprng")]
+impl NoiseSource {
+    const COUNT: usize = 3;
+    const ALL: [Self; Self::COUNT] = [Self::Hardware, Self::Jitter, Self::Radio];
+
+    /// Returns the min-entropy assumed per byte of samples.
+    const fn min_entropy_bits(self) -> u8 {
+        match self {
+            Self::Hardware => 4,
+            Self::Jitter | Self::Radio => 1,
+        }
+    }
+}
+
+/// Error returned when samples fail the health tests.
+#[cfg(feature = "csprng")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthTestError {
+    /// The same sample was repeated too many times in a row.
+    RepetitionCount,
+    /// A sample value occurred too often within a window of samples.
+    AdaptiveProportion,
+}
+
+#[cfg(feature = "csprng")]
+impl core::fmt::Display for HealthTestError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::RepetitionCount => write!(f, "repetition count test failed"),
+            Self::AdaptiveProportion => write!(f, "adaptive proportion test failed"),
+        }
+    }
+}
+
+#[cfg(feature = "csprng")]
+impl core::error::Error for HealthTestError {}
+
+/// What happens when samples from the hardware RNG fail the health tests.
+///
+/// See the [module level documentation](crate#hardware-rng-failures) for details.
+#[cfg(feature = "csprng")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HwrngFailurePolicy {
+    /// The system panics.
+    Halt,
+    /// A warning is logged, and the hardware RNG continues to be used.
+    Degrade,
+    /// The hardware RNG is not used any more, and the DRBG continues from its previous seed.
+    FallBackToDrbg,
+}
+
+#[cfg(feature = "csprng")]
+impl HwrngFailurePolicy {
+    /// Parses the value of `CONFIG_HWRNG_FAILURE_POLICY`.
+    ///
+    /// # Panics
+    ///
+    /// Panics on unknown values, which fails the build as this is evaluated at compile time.
+    const fn from_config(value: &str) -> Self {
+        match value.as_bytes() {
+            b"halt" => Self::Halt,
+            b"degrade" => Self::Degrade,
+            b"drbg" => Self::FallBackToDrbg,
+            _ => panic!("CONFIG_HWRNG_FAILURE_POLICY must be one of `halt`, `degrade` or `drbg`"),
+        }
+    }
+}
+
+/// The [`HwrngFailurePolicy`] selected at build time.
+#[cfg(feature = "csprng")]
+pub const HWRNG_FAILURE_POLICY: HwrngFailurePolicy =
+    HwrngFailurePolicy::from_config(ariel_os_utils::str_from_env_or!(
+        "CONFIG_HWRNG_FAILURE_POLICY",
+        "halt",
+        "policy applied when the hardware RNG fails the health tests"
+    ));
+
+/// Returns the most recent health test failure of the hardware RNG, if any has occurred.
+///
+/// With the [`HwrngFailurePolicy::FallBackToDrbg`] policy, this being `Some` means that the
+/// hardware RNG is not used any more.
+#[cfg(feature = "csprng")]
+#[must_use]
+pub fn hwrng_failure() -> Option<HealthTestError> {
+    with_global(|rng| rng.hwrng_failure())
 }
 
 /// Returns a suitably initialized fast random number generator.
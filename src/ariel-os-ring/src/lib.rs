@@ -0,0 +1,689 @@
+//! Provides lock-free ring buffers that stream bytes or frames from interrupt handlers to tasks.
+//!
+//! A [`Ring`] is split once into a [`Producer`] and a [`Consumer`].
+//! The producer never blocks, so that it can be used from interrupt handlers, eg. to pass on the
+//! bytes received by a UART or the samples of an ADC or I2S peripheral, and the consumer awaits
+//! the data in a task:
+//!
+//! ```ignore
+//! static RX: Ring<256> = Ring::new();
+//!
+//! let (mut producer, mut consumer) = RX.split().unwrap();
+//!
+//! // In the interrupt handler:
+//! producer.write(&received);
+//!
+//! // In a task:
+//! let mut buf = [0; 64];
+//! let len = consumer.read_at_least(16, &mut buf).await;
+//! ```
+//!
+//! A ring carries either a stream of bytes, or frames whose boundaries are kept, written with
+//! [`Producer::write_frame()`] and read with [`Consumer::read_frame()`].
+//! The consumer is only woken once the amount of data it waits for is available, rather than on
+//! each write.
+//!
+//! When the ring is full, writes are truncated or dropped, and counted as
+//! [overflows](Ring::overflows()).
+//! Producers running in tasks can instead wait for space with [`Producer::write_all()`], and
+//! producers that can throttle their source, eg. by pausing a DMA transfer or by deasserting RTS,
+//! can do so from the callbacks of [`Watermarks`].
+//! The producer and the consumer also implement the `embedded-io-async` traits, so that
+//! application protocols can be layered on top of rings.
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+use core::{
+    cell::UnsafeCell,
+    convert::Infallible,
+    future::poll_fn,
+    sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+    task::Poll,
+};
+
+use embassy_sync::waitqueue::AtomicWaker;
+
+/// Length of the header of frames, which holds their length.
+const FRAME_HEADER_LEN: usize = 2;
+
+/// Maximum length of frames.
+pub const MAX_FRAME_LEN: usize = u16::MAX as usize;
+
+/// Errors of rings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The ring has no space for the frame, which was dropped.
+    Full,
+    /// The frame is longer than [`MAX_FRAME_LEN`], or than the buffer it is read into; it was
+    /// dropped.
+    FrameTooLong,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Full => write!(f, "ring full"),
+            Self::FrameTooLong => write!(f, "frame too long"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Watermarks of a [`Ring`], whose callbacks let the producer throttle its source.
+#[derive(Debug, Clone, Copy)]
+pub struct Watermarks {
+    /// Number of bytes in the ring from which `on_high` is called.
+    pub high: usize,
+    /// Number of bytes in the ring down to which `on_low` is called, once `on_high` was called.
+    pub low: usize,
+    /// Called by the producer, possibly from an interrupt handler, when the ring filled up to
+    /// `high` bytes.
+    pub on_high: fn(),
+    /// Called by the consumer when the ring drained down to `low` bytes.
+    pub on_low: fn(),
+}
+
+/// A lock-free single-producer single-consumer ring buffer of `N` bytes.
+///
+/// `N` must be a power of two.
+pub struct Ring<const N: usize> {
+    buffer: UnsafeCell<[u8; N]>,
+    /// Number of bytes written so far, wrapping around; only stored by the producer.
+    written: AtomicUsize,
+    /// Number of bytes read so far, wrapping around; only stored by the consumer.
+    read: AtomicUsize,
+    /// Number of bytes the consumer waits for; only stored by the consumer.
+    wanted_data: AtomicUsize,
+    /// Number of free bytes the producer waits for; only stored by the producer.
+    wanted_space: AtomicUsize,
+    data_waker: AtomicWaker,
+    space_waker: AtomicWaker,
+    /// Only stored by the producer.
+    overflows: AtomicU32,
+    /// Only stored by the producer.
+    peak: AtomicUsize,
+    watermarks: Option<Watermarks>,
+    /// Whether the ring filled up to the high watermark, and did not drain down to the low
+    /// watermark since.
+    throttled: AtomicBool,
+    split: AtomicBool,
+}
+
+// SAFETY: the producer only accesses the free part of the buffer, and the consumer only the
+// filled part of it, as delimited by `written` and `read`; `split()` ensures that there is at
+// most one of each.
+unsafe impl<const N: usize> Sync for Ring<N> {}
+
+impl<const N: usize> Ring<N> {
+    const VALID: () = assert!(
+        N.is_power_of_two(),
+        "the size of rings must be a power of two"
+    );
+
+    /// Creates an empty ring.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self::with(None)
+    }
+
+    /// Creates an empty ring with `watermarks`.
+    ///
+    /// When initializing a `static`, invalid watermarks are rejected at compile time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the low watermark is not below the high watermark, or the high watermark is
+    /// larger than `N`.
+    #[must_use]
+    pub const fn with_watermarks(watermarks: Watermarks) -> Self {
+        assert!(
+            watermarks.low < watermarks.high,
+            "the low watermark must be below the high watermark"
+        );
+        assert!(
+            watermarks.high <= N,
+            "the high watermark must not exceed the size of the ring"
+        );
+        Self::with(Some(watermarks))
+    }
+
+    const fn with(watermarks: Option<Watermarks>) -> Self {
+        let () = Self::VALID;
+        Self {
+            buffer: UnsafeCell::new([0; N]),
+            written: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            wanted_data: AtomicUsize::new(usize::MAX),
+            wanted_space: AtomicUsize::new(usize::MAX),
+            data_waker: AtomicWaker::new(),
+            space_waker: AtomicWaker::new(),
+            overflows: AtomicU32::new(0),
+            peak: AtomicUsize::new(0),
+            watermarks,
+            throttled: AtomicBool::new(false),
+            split: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the producer and the consumer of the ring, or `None` if they were returned
+    /// already.
+    pub fn split(&self) -> Option<(Producer<'_, N>, Consumer<'_, N>)> {
+        let first = critical_section::with(|_| {
+            let first = !self.split.load(Ordering::Relaxed);
+            self.split.store(true, Ordering::Relaxed);
+            first
+        });
+        first.then_some((Producer { ring: self }, Consumer { ring: self }))
+    }
+
+    /// Returns the number of bytes in the ring.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        // `read` never passes `written`, which only grows.
+        let read = self.read.load(Ordering::SeqCst);
+        self.written.load(Ordering::SeqCst).wrapping_sub(read)
+    }
+
+    /// Returns whether the ring is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of writes that were truncated or dropped because the ring was full.
+    #[must_use]
+    pub fn overflows(&self) -> u32 {
+        self.overflows.load(Ordering::Relaxed)
+    }
+
+    /// Returns the largest number of bytes that were in the ring at once.
+    #[must_use]
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    /// Copies `bytes` into the buffer, `offset` bytes after the written bytes.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called by the producer, with `offset + bytes.len()` free bytes.
+    unsafe fn copy_in(&self, offset: usize, bytes: &[u8]) {
+        let start = self.written.load(Ordering::Relaxed).wrapping_add(offset) % N;
+        let (head, tail) = bytes.split_at(bytes.len().min(N - start));
+        let buffer = self.buffer.get().cast::<u8>();
+        // SAFETY: `head` fits between `start` and the end of the buffer, and `tail` is shorter
+        // than `start`. These bytes are free, and thus not accessed by the consumer.
+        unsafe {
+            buffer
+                .add(start)
+                .copy_from_nonoverlapping(head.as_ptr(), head.len());
+            buffer.copy_from_nonoverlapping(tail.as_ptr(), tail.len());
+        }
+    }
+
+    /// Copies the bytes of the buffer into `buf`, starting `offset` bytes after the read bytes.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called by the consumer, with `offset + buf.len()` bytes in the ring.
+    unsafe fn copy_out(&self, offset: usize, buf: &mut [u8]) {
+        let start = self.read.load(Ordering::Relaxed).wrapping_add(offset) % N;
+        let (head, tail) = buf.split_at_mut(buf.len().min(N - start));
+        let buffer = self.buffer.get().cast::<u8>().cast_const();
+        // SAFETY: `head` fits between `start` and the end of the buffer, and `tail` is shorter
+        // than `start`. These bytes are filled, and thus not accessed by the producer.
+        unsafe {
+            head.as_mut_ptr()
+                .copy_from_nonoverlapping(buffer.add(start), head.len());
+            tail.as_mut_ptr()
+                .copy_from_nonoverlapping(buffer, tail.len());
+        }
+    }
+}
+
+impl<const N: usize> Default for Ring<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The writing end of a [`Ring`].
+pub struct Producer<'a, const N: usize> {
+    ring: &'a Ring<N>,
+}
+
+impl<const N: usize> Producer<'_, N> {
+    /// Returns the number of bytes that can be written without overflowing.
+    #[must_use]
+    pub fn free(&self) -> usize {
+        N - self.ring.len()
+    }
+
+    /// Writes as many bytes of `bytes` as fit in the ring, and returns their number.
+    ///
+    /// This does not block, and can thus be called from interrupt handlers.
+    /// Writes that do not fit entirely are counted as overflows.
+    pub fn write(&mut self, bytes: &[u8]) -> usize {
+        let len = bytes.len().min(self.free());
+        if len < bytes.len() {
+            self.count_overflow();
+        }
+        self.push(bytes.get(..len).unwrap_or_default());
+        len
+    }
+
+    /// Writes `frame` as a whole.
+    ///
+    /// This does not block, and can thus be called from interrupt handlers.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Full`] if the frame does not fit in the ring, and
+    /// [`Error::FrameTooLong`] if it is longer than [`MAX_FRAME_LEN`]; the frame is then dropped
+    /// and counted as an overflow.
+    pub fn write_frame(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let Ok(len) = u16::try_from(frame.len()) else {
+            self.count_overflow();
+            return Err(Error::FrameTooLong);
+        };
+        if FRAME_HEADER_LEN + frame.len() > self.free() {
+            self.count_overflow();
+            return Err(Error::Full);
+        }
+        // SAFETY: this is the producer, and the header and the frame fit.
+        unsafe {
+            self.ring.copy_in(0, &len.to_le_bytes());
+            self.ring.copy_in(FRAME_HEADER_LEN, frame);
+        }
+        self.publish(FRAME_HEADER_LEN + frame.len());
+        Ok(())
+    }
+
+    /// Writes all of `bytes`, waiting for space as needed.
+    ///
+    /// This applies back-pressure to producers running in tasks.
+    pub async fn write_all(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            self.wait_space(1).await;
+            let (now, rest) = bytes.split_at(bytes.len().min(self.free()));
+            self.push(now);
+            bytes = rest;
+        }
+    }
+
+    /// Writes `bytes`, which must fit in the ring.
+    fn push(&mut self, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        // SAFETY: this is the producer, and the bytes fit.
+        unsafe {
+            self.ring.copy_in(0, bytes);
+        }
+        self.publish(bytes.len());
+    }
+
+    /// Makes `len` more bytes available to the consumer.
+    fn publish(&mut self, len: usize) {
+        let ring = self.ring;
+        let written = ring.written.load(Ordering::Relaxed).wrapping_add(len);
+        ring.written.store(written, Ordering::SeqCst);
+        let filled = written.wrapping_sub(ring.read.load(Ordering::SeqCst));
+
+        if filled > ring.peak.load(Ordering::Relaxed) {
+            ring.peak.store(filled, Ordering::Relaxed);
+        }
+        if let Some(watermarks) = ring.watermarks
+            && filled >= watermarks.high
+            && !ring.throttled.load(Ordering::Relaxed)
+        {
+            ring.throttled.store(true, Ordering::Relaxed);
+            (watermarks.on_high)();
+        }
+        if filled >= ring.wanted_data.load(Ordering::SeqCst) {
+            ring.data_waker.wake();
+        }
+    }
+
+    fn count_overflow(&self) {
+        // Only the producer stores the count, so that this works without CAS instructions.
+        let overflows = self.ring.overflows.load(Ordering::Relaxed);
+        self.ring
+            .overflows
+            .store(overflows.saturating_add(1), Ordering::Relaxed);
+    }
+
+    /// Waits until `len` bytes are free.
+    async fn wait_space(&mut self, len: usize) {
+        let ring = self.ring;
+        poll_fn(|cx| {
+            if self.free() >= len {
+                ring.wanted_space.store(usize::MAX, Ordering::SeqCst);
+                return Poll::Ready(());
+            }
+            ring.space_waker.register(cx.waker());
+            ring.wanted_space.store(len, Ordering::SeqCst);
+            // Check again, in case the consumer read before seeing what is wanted.
+            if self.free() >= len {
+                ring.wanted_space.store(usize::MAX, Ordering::SeqCst);
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+    }
+}
+
+/// The reading end of a [`Ring`].
+pub struct Consumer<'a, const N: usize> {
+    ring: &'a Ring<N>,
+}
+
+impl<const N: usize> Consumer<'_, N> {
+    /// Returns the number of bytes that can be read.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Returns whether there is nothing to read.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.ring.is_empty()
+    }
+
+    /// Reads as many bytes as are available into `buf`, and returns their number, without
+    /// waiting.
+    pub fn try_read(&mut self, buf: &mut [u8]) -> usize {
+        let len = buf.len().min(self.len());
+        if let Some(buf) = buf.get_mut(..len)
+            && len > 0
+        {
+            // SAFETY: this is the consumer, and there are `len` bytes in the ring.
+            unsafe {
+                self.ring.copy_out(0, buf);
+            }
+            self.consume(len);
+        }
+        len
+    }
+
+    /// Waits for bytes, reads as many as are available into `buf`, and returns their number.
+    pub async fn read(&mut self, buf: &mut [u8]) -> usize {
+        self.read_at_least(1, buf).await
+    }
+
+    /// Waits until at least `min` bytes are available, reads as many as are available into
+    /// `buf`, and returns their number.
+    ///
+    /// The consumer is only woken once `min` bytes are available, which saves wake-ups when data
+    /// is written in small pieces.
+    /// `min` is limited to the length of `buf` and to the size of the ring.
+    pub async fn read_at_least(&mut self, min: usize, buf: &mut [u8]) -> usize {
+        self.wait_data(min.min(buf.len()).min(N)).await;
+        self.try_read(buf)
+    }
+
+    /// Waits for the next frame, reads it into `buf`, and returns its length.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::FrameTooLong`] if the frame does not fit in `buf`; the frame is then
+    /// dropped.
+    pub async fn read_frame(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.wait_data(FRAME_HEADER_LEN).await;
+        let mut header = [0; FRAME_HEADER_LEN];
+        // SAFETY: this is the consumer, and frames are written as a whole, after their header.
+        unsafe {
+            self.ring.copy_out(0, &mut header);
+        }
+        let len = usize::from(u16::from_le_bytes(header));
+        let result = match buf.get_mut(..len) {
+            Some(frame) => {
+                // SAFETY: as above.
+                unsafe {
+                    self.ring.copy_out(FRAME_HEADER_LEN, frame);
+                }
+                Ok(len)
+            }
+            None => Err(Error::FrameTooLong),
+        };
+        self.consume(FRAME_HEADER_LEN + len);
+        result
+    }
+
+    /// Frees `len` bytes for the producer.
+    fn consume(&mut self, len: usize) {
+        let ring = self.ring;
+        let read = ring.read.load(Ordering::Relaxed).wrapping_add(len);
+        ring.read.store(read, Ordering::SeqCst);
+        let filled = ring.written.load(Ordering::SeqCst).wrapping_sub(read);
+
+        if let Some(watermarks) = ring.watermarks
+            && filled <= watermarks.low
+            && ring.throttled.load(Ordering::Relaxed)
+        {
+            ring.throttled.store(false, Ordering::Relaxed);
+            (watermarks.on_low)();
+        }
+        if N - filled >= ring.wanted_space.load(Ordering::SeqCst) {
+            ring.space_waker.wake();
+        }
+    }
+
+    /// Waits until `len` bytes are available.
+    async fn wait_data(&mut self, len: usize) {
+        let ring = self.ring;
+        poll_fn(|cx| {
+            if ring.len() >= len {
+                ring.wanted_data.store(usize::MAX, Ordering::SeqCst);
+                return Poll::Ready(());
+            }
+            ring.data_waker.register(cx.waker());
+            ring.wanted_data.store(len, Ordering::SeqCst);
+            // Check again, in case the producer wrote before seeing what is wanted.
+            if ring.len() >= len {
+                ring.wanted_data.store(usize::MAX, Ordering::SeqCst);
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+    }
+}
+
+impl<const N: usize> embedded_io_async::ErrorType for Producer<'_, N> {
+    type Error = Infallible;
+}
+
+impl<const N: usize> embedded_io_async::Write for Producer<'_, N> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.wait_space(1).await;
+        let len = buf.len().min(self.free());
+        self.push(buf.get(..len).unwrap_or_default());
+        Ok(len)
+    }
+}
+
+impl<const N: usize> embedded_io_async::ErrorType for Consumer<'_, N> {
+    type Error = Infallible;
+}
+
+impl<const N: usize> embedded_io_async::Read for Consumer<'_, N> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        Ok(Consumer::read(self, buf).await)
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use embassy_futures::{block_on, join::join};
+
+    use super::*;
+
+    #[test]
+    fn split_once() {
+        let ring = Ring::<8>::new();
+        assert!(ring.split().is_some());
+        assert!(ring.split().is_none());
+    }
+
+    #[test]
+    fn bytes() {
+        let ring = Ring::<8>::new();
+        let (mut producer, mut consumer) = ring.split().unwrap();
+        let mut buf = [0; 8];
+
+        // Wraps around the end of the buffer.
+        for round in 0..4 {
+            assert_eq!(producer.write(&[round, 1, 2, 3, 4]), 5);
+            assert_eq!(consumer.len(), 5);
+            assert_eq!(block_on(consumer.read(&mut buf)), 5);
+            assert_eq!(buf.get(..5), Some(&[round, 1, 2, 3, 4][..]));
+        }
+        assert!(consumer.is_empty());
+        assert_eq!(consumer.try_read(&mut buf), 0);
+        assert_eq!(ring.overflows(), 0);
+        assert_eq!(ring.peak(), 5);
+    }
+
+    #[test]
+    fn overflows() {
+        let ring = Ring::<4>::new();
+        let (mut producer, mut consumer) = ring.split().unwrap();
+
+        assert_eq!(producer.write(&[1, 2, 3]), 3);
+        assert_eq!(producer.write(&[4, 5, 6]), 1);
+        assert_eq!(producer.write(&[7]), 0);
+        assert_eq!(ring.overflows(), 2);
+
+        let mut buf = [0; 8];
+        assert_eq!(consumer.try_read(&mut buf), 4);
+        assert_eq!(buf.get(..4), Some(&[1, 2, 3, 4][..]));
+    }
+
+    #[test]
+    fn frames() {
+        let ring = Ring::<16>::new();
+        let (mut producer, mut consumer) = ring.split().unwrap();
+
+        assert_eq!(producer.write_frame(&[1, 2, 3]), Ok(()));
+        assert_eq!(producer.write_frame(&[4, 5, 6, 7, 8, 9]), Ok(()));
+        assert_eq!(producer.write_frame(&[0; 4]), Err(Error::Full));
+        assert_eq!(ring.overflows(), 1);
+
+        let mut buf = [0; 4];
+        assert_eq!(block_on(consumer.read_frame(&mut buf)), Ok(3));
+        assert_eq!(buf.get(..3), Some(&[1, 2, 3][..]));
+        assert_eq!(
+            block_on(consumer.read_frame(&mut buf)),
+            Err(Error::FrameTooLong)
+        );
+        assert!(consumer.is_empty());
+
+        // Wraps around the end of the buffer.
+        assert_eq!(producer.write_frame(&[10, 11, 12, 13]), Ok(()));
+        assert_eq!(block_on(consumer.read_frame(&mut buf)), Ok(4));
+        assert_eq!(buf, [10, 11, 12, 13]);
+    }
+
+    #[test]
+    fn watermarks() {
+        static HIGH: AtomicU32 = AtomicU32::new(0);
+        static LOW: AtomicU32 = AtomicU32::new(0);
+
+        let ring = Ring::<8>::with_watermarks(Watermarks {
+            high: 6,
+            low: 2,
+            on_high: || {
+                HIGH.store(HIGH.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+            },
+            on_low: || {
+                LOW.store(LOW.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+            },
+        });
+        let (mut producer, mut consumer) = ring.split().unwrap();
+        let mut buf = [0; 2];
+
+        producer.write(&[0; 6]);
+        producer.write(&[0; 1]);
+        assert_eq!(HIGH.load(Ordering::Relaxed), 1);
+        consumer.try_read(&mut buf);
+        consumer.try_read(&mut buf);
+        assert_eq!(LOW.load(Ordering::Relaxed), 0);
+        consumer.try_read(&mut buf);
+        assert_eq!(LOW.load(Ordering::Relaxed), 1);
+        consumer.try_read(&mut buf);
+        assert_eq!(LOW.load(Ordering::Relaxed), 1);
+
+        producer.write(&[0; 6]);
+        assert_eq!(HIGH.load(Ordering::Relaxed), 2);
+    }
+
+    /// Returns watermarks between `low` and `high`, without callbacks.
+    fn watermarks_between(low: usize, high: usize) -> Watermarks {
+        Watermarks {
+            high,
+            low,
+            on_high: || {},
+            on_low: || {},
+        }
+    }
+
+    #[test]
+    fn valid_watermarks() {
+        let _ = Ring::<8>::with_watermarks(watermarks_between(0, 8));
+        let _ = Ring::<8>::with_watermarks(watermarks_between(7, 8));
+    }
+
+    #[test]
+    #[should_panic(expected = "low watermark must be below")]
+    fn inverted_watermarks() {
+        let _ = Ring::<8>::with_watermarks(watermarks_between(6, 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "low watermark must be below")]
+    fn equal_watermarks() {
+        let _ = Ring::<8>::with_watermarks(watermarks_between(4, 4));
+    }
+
+    #[test]
+    #[should_panic(expected = "must not exceed the size")]
+    fn high_watermark_beyond_size() {
+        let _ = Ring::<8>::with_watermarks(watermarks_between(2, 9));
+    }
+
+    #[test]
+    fn back_pressure() {
+        let ring = Ring::<4>::new();
+        let (mut producer, mut consumer) = ring.split().unwrap();
+        let data: [u8; 32] = core::array::from_fn(|i| u8::try_from(i).unwrap());
+
+        let mut received = [0; 32];
+        block_on(join(producer.write_all(&data), async {
+            let mut len = 0;
+            while let Some(buf) = received.get_mut(len..)
+                && !buf.is_empty()
+            {
+                len += consumer.read_at_least(2, buf).await;
+            }
+        }));
+        assert_eq!(received, data);
+        assert_eq!(ring.overflows(), 0);
+    }
+}
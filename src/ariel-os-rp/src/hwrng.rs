@@ -1,4 +1,13 @@
+use static_cell::StaticCell;
+
 pub fn construct_rng(peripherals: &mut crate::OptionalPeripherals) {
+    // The RNG is kept, so that it can be read again to reseed the global RNG.
+    #[cfg(context = "rp2040")]
+    static HWRNG: StaticCell<embassy_rp::clocks::RoscRng> = StaticCell::new();
+    #[cfg(context = "rp235xa")]
+    static HWRNG: StaticCell<embassy_rp::trng::Trng<'static, embassy_rp::peripherals::TRNG>> =
+        StaticCell::new();
+
     #[cfg(context = "rp2040")]
     let hwrng = {
         let _ = peripherals; // Mark used
@@ -25,5 +34,5 @@ pub fn construct_rng(peripherals: &mut crate::OptionalPeripherals) {
         embassy_rp::trng::Trng::new(trng, Irqs, config)
     };
 
-    ariel_os_random::construct_rng(hwrng);
+    ariel_os_random::construct_rng_from_hwrng(HWRNG.init(hwrng));
 }
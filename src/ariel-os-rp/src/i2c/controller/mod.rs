@@ -1,6 +1,10 @@
 //! Provides support for the I2C communication bus in controller mode.
 
-use ariel_os_embassy_common::{i2c::controller::Kilohertz, impl_async_i2c_for_driver_enum};
+use ariel_os_embassy_common::{
+    i2c::controller::{I2C_TIMEOUT, Kilohertz},
+    impl_async_i2c_for_driver_enum,
+    reexports::embassy_time::Duration,
+};
 use embassy_rp::{
     Peripheral, bind_interrupts,
     i2c::{InterruptHandler, SclPin, SdaPin},
@@ -17,12 +21,16 @@ const KHZ_TO_HZ: u32 = 1000;
 pub struct Config {
     /// The frequency at which the bus should operate.
     pub frequency: Frequency,
+    /// Timeout of each operation on the bus, after which it fails with
+    /// [`Error::Timeout`](ariel_os_embassy_common::i2c::controller::Error::Timeout).
+    pub timeout: Duration,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             frequency: Frequency::UpTo100k(Kilohertz::kHz(100)),
+            timeout: I2C_TIMEOUT,
         }
     }
 }
@@ -112,6 +120,7 @@ macro_rules! define_i2c_drivers {
             /// Peripheral-specific I2C driver.
             pub struct $peripheral {
                 twim: embassy_rp::i2c::I2c<'static, peripherals::$peripheral, embassy_rp::i2c::Async>,
+                timeout: Duration,
             }
 
             impl $peripheral {
@@ -155,7 +164,7 @@ macro_rules! define_i2c_drivers {
                         i2c_config,
                     );
 
-                    I2c::$peripheral(Self { twim: i2c })
+                    I2c::$peripheral(Self { twim: i2c, timeout: config.timeout })
                 }
             }
         )*
@@ -168,6 +177,9 @@ macro_rules! define_i2c_drivers {
             )*
         }
 
+        /// Names of the I2C peripherals for which a driver is available.
+        pub const PERIPHERALS: &[&str] = &[$( stringify!($peripheral) ),*];
+
         impl embedded_hal_async::i2c::ErrorType for I2c {
             type Error = ariel_os_embassy_common::i2c::controller::Error;
         }
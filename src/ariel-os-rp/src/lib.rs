@@ -54,7 +54,6 @@ pub use embassy_rp::peripherals;
 #[cfg(feature = "executor-interrupt")]
 #[doc(hidden)]
 pub use embassy_executor::InterruptExecutor as Executor;
-#[cfg(feature = "executor-interrupt")]
 #[doc(hidden)]
 pub use embassy_rp::interrupt;
 
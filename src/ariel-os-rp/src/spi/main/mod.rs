@@ -116,6 +116,9 @@ macro_rules! define_spi_drivers {
             ),*
         }
 
+        /// Names of the SPI peripherals for which a driver is available.
+        pub const PERIPHERALS: &[&str] = &[$( stringify!($peripheral) ),*];
+
         impl embedded_hal_async::spi::ErrorType for Spi {
             type Error = embassy_rp::spi::Error;
         }
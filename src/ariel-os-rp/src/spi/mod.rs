@@ -17,11 +17,23 @@ fn from_mode(mode: Mode) -> (Polarity, Phase) {
 
 #[doc(hidden)]
 pub fn init(peripherals: &mut crate::OptionalPeripherals) {
+    // This macro has to be defined in this function so that the `peripherals` variables exists.
+    macro_rules! take_all_spi_peripherals {
+        ($( $peripheral:ident ),*) => {
+            $(
+                let peripheral = peripherals.$peripheral.take();
+                debug_assert!(
+                    peripheral.is_some(),
+                    concat!(stringify!($peripheral), " was taken before initialization"),
+                );
+            )*
+        }
+    }
+
     // Take all SPI peripherals and do nothing with them.
     cfg_if::cfg_if! {
         if #[cfg(context = "rp")] {
-            let _ = peripherals.SPI0.take().unwrap();
-            let _ = peripherals.SPI1.take().unwrap();
+            take_all_spi_peripherals!(SPI0, SPI1);
         } else {
             compile_error!("this RP chip is not supported");
         }
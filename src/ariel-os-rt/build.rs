@@ -48,6 +48,9 @@ fn main() {
     #[cfg(feature = "memory-x")]
     write_memoryx();
 
+    #[cfg(feature = "xip")]
+    write_xip(out);
+
     println!("cargo:rerun-if-changed=linkme.x");
     println!("cargo:rerun-if-changed=eheap.x");
     println!("cargo:rerun-if-changed=keep-stack-sizes.x");
@@ -98,6 +101,36 @@ fn write_memoryx() {
     memory.to_cargo_outdir("memory.x").expect("wrote memory.x");
 }
 
+/// Writes `xip.x`, which places the items marked for execute-in-place in the external flash, to
+/// `out`.
+///
+/// # Panics
+/// Panics if the MCU cannot execute in place, or if the external flash of the board is not known.
+#[cfg(feature = "xip")]
+fn write_xip(out: &std::path::Path) {
+    // Address at which the QSPI peripheral maps the external flash.
+    let origin: u32 = if context("nrf52840") {
+        0x1200_0000
+    } else if context("nrf5340") {
+        0x1000_0000
+    } else {
+        panic!("execute-in-place is not supported on this MCU");
+    };
+
+    let length: u32 = if context_any(&["nrf52840dk", "nrf5340dk"]).is_some() {
+        // MX25R6435F
+        8 * 1024 * 1024
+    } else {
+        panic!("the external flash of this board is not known");
+    };
+
+    let mut xip_template = std::fs::read_to_string("xip.ld.in").unwrap();
+    xip_template = xip_template.replace("${ORIGIN}", &format!("{origin:#x}"));
+    xip_template = xip_template.replace("${LENGTH}", &format!("{length:#x}"));
+    std::fs::write(out.join("xip.x"), &xip_template).unwrap();
+    println!("cargo:rerun-if-changed=xip.ld.in");
+}
+
 /// Returns the first of the given contexts that is in the current `cfg` contexts
 fn context_any(contexts: &[&'static str]) -> Option<&'static str> {
     // Contexts cannot include commas.
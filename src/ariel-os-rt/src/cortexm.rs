@@ -37,6 +37,11 @@ pub fn ipsr_isr_number_to_str(isr_number: usize) -> &'static str {
 #[exception]
 unsafe fn HardFault(ef: &ExceptionFrame) -> ! {
     use core::arch::asm;
+
+    // Record the fault before anything else, as the panic below would be recorded otherwise.
+    #[cfg(feature = "crash-report")]
+    ariel_os_crash::record_fault(ef.pc(), ef.lr(), ef.xpsr());
+
     asm!("bkpt");
 
     let mode_str = "Kernel";
@@ -6,6 +6,8 @@
 
 pub mod stack;
 
+mod xip;
+
 #[cfg(feature = "threading")]
 mod threading;
 
@@ -124,9 +126,16 @@ mod isr_stack {
 #[cfg(all(feature = "_panic-handler", not(feature = "_test")))]
 #[panic_handler]
 fn panic(_info: &core::panic::PanicInfo) -> ! {
+    #[cfg(feature = "crash-report")]
+    ariel_os_crash::record_panic(_info);
+
     #[cfg(feature = "panic-printing")]
     ariel_os_debug::print_panic(_info);
 
+    // Reset, so that the device comes back up and the crash report can be retrieved.
+    #[cfg(all(feature = "crash-report", context = "cortex-m"))]
+    cortex_m::peripheral::SCB::sys_reset();
+
     ariel_os_debug::exit(ariel_os_debug::ExitCode::FAILURE);
 
     #[allow(clippy::empty_loop)]
@@ -0,0 +1,22 @@
+//! Places items in the external flash, for execute-in-place.
+
+/// Places `$item` in the section `$section` of the external flash.
+#[cfg(feature = "xip")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! xip_section {
+    ($section:literal, $item:item) => {
+        #[unsafe(link_section = $section)]
+        $item
+    };
+}
+
+/// Leaves `$item` in the internal flash, as the external flash is not used.
+#[cfg(not(feature = "xip"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! xip_section {
+    ($section:literal, $item:item) => {
+        $item
+    };
+}
@@ -0,0 +1,209 @@
+//! Provides exclusive access to a block device shared by several users, eg. a FAT filesystem
+//! mounted by the firmware and a USB mass-storage class exposing the device to a host.
+//!
+//! Neither a filesystem nor a host expects the blocks of the device to change underneath it:
+//! accessing the device from both at the same time corrupts the filesystem, even if every single
+//! block access is atomic. An [`Arbiter`] thus hands the whole device out to one [`Owner`] at a
+//! time, through a [`Lease`] that is itself a [`BlockDevice`]:
+//!
+//! ```ignore
+//! static CARD: StaticCell<Arbiter<SdCard<...>>> = StaticCell::new();
+//! let card = CARD.init(Arbiter::new(SdCard::init(spi_bus, cs).await?));
+//!
+//! let mut lease = card.acquire(Owner::Device).await;
+//! let volume = fat::find_volume(&mut lease).await?.ok_or(NoFilesystem)?;
+//! let fs = fat::mount(lease);
+//! // ...
+//! // Dropping the filesystem, once its files and volumes are closed, releases the device.
+//! drop(fs);
+//! ```
+//!
+//! Owners hand the device over cooperatively. [`Arbiter::acquire()`] waits until the device is
+//! released, and meanwhile publishes the request in the [`Status`] of the arbiter, which the
+//! current owner follows through [`Arbiter::receiver()`] to release the device once it is safe:
+//!
+//! - a USB mass-storage class reports the medium as removed to the host, which unmounts it, and
+//!   then drops its lease; while it holds no lease, it reports the medium as not present, and
+//!   reports a medium change once it acquired the device again with [`Arbiter::try_acquire()`]
+//!   after the device was released;
+//! - the firmware closes its files and drops its filesystem, which drops the lease.
+
+use aligned::Aligned;
+use block_device_driver::BlockDevice;
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    mutex::{Mutex, MutexGuard},
+    watch::{DynReceiver, Watch},
+};
+
+use crate::BLOCK_LEN;
+
+/// Maximum number of [`Arbiter::receiver()`]s of an arbiter at a time.
+const MAX_RECEIVERS: usize = 2;
+
+/// An owner of a shared block device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Owner {
+    /// The firmware, eg. through a filesystem.
+    Device,
+    /// A host the device is exposed to, eg. through USB mass storage.
+    Host,
+}
+
+/// Who owns a shared block device, and who waits for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Status {
+    /// The current owner of the device, if any.
+    pub owner: Option<Owner>,
+    /// The owner waiting in [`Arbiter::acquire()`], if any, which the current owner should
+    /// release the device to.
+    pub requested: Option<Owner>,
+}
+
+impl Status {
+    const RELEASED: Self = Self {
+        owner: None,
+        requested: None,
+    };
+}
+
+/// Shares the block device `D` between [`Owner`]s, handing it to one of them at a time.
+pub struct Arbiter<D> {
+    device: Mutex<CriticalSectionRawMutex, D>,
+    status: Watch<CriticalSectionRawMutex, Status, MAX_RECEIVERS>,
+}
+
+impl<D: BlockDevice<BLOCK_LEN>> Arbiter<D> {
+    /// Creates an arbiter sharing `device`, which is released.
+    #[must_use]
+    pub const fn new(device: D) -> Self {
+        Self {
+            device: Mutex::new(device),
+            status: Watch::new_with(Status::RELEASED),
+        }
+    }
+
+    /// Acquires the device for `owner`, waiting until its current owner releases it.
+    ///
+    /// While waiting, `owner` is published as [`Status::requested`].
+    pub async fn acquire(&self, owner: Owner) -> Lease<'_, D> {
+        if let Some(lease) = self.try_acquire(owner) {
+            return lease;
+        }
+        self.status.sender().send_modify(|status| {
+            status.get_or_insert(Status::RELEASED).requested = Some(owner);
+        });
+        // Withdraws the request if this is cancelled while waiting.
+        let _request = Request {
+            status: &self.status,
+            owner,
+        };
+        let guard = self.device.lock().await;
+        self.lease(guard, owner)
+    }
+
+    /// Acquires the device for `owner` if it is released, without waiting.
+    ///
+    /// Returns `None` if the device is owned, or if another owner is waiting for it, which takes
+    /// precedence.
+    pub fn try_acquire(&self, owner: Owner) -> Option<Lease<'_, D>> {
+        let requested = self.status().requested;
+        if requested.is_some_and(|requested| requested != owner) {
+            return None;
+        }
+        let guard = self.device.try_lock().ok()?;
+        Some(self.lease(guard, owner))
+    }
+
+    /// Returns the current status.
+    #[must_use]
+    pub fn status(&self) -> Status {
+        self.status.try_get().unwrap_or(Status::RELEASED)
+    }
+
+    /// Returns a receiver of the changes of the [`Status`], or `None` if there are too many
+    /// receivers already.
+    #[must_use]
+    pub fn receiver(&self) -> Option<DynReceiver<'_, Status>> {
+        self.status.dyn_receiver()
+    }
+
+    fn lease<'a>(
+        &'a self,
+        guard: MutexGuard<'a, CriticalSectionRawMutex, D>,
+        owner: Owner,
+    ) -> Lease<'a, D> {
+        self.status.sender().send_modify(|status| {
+            let status = status.get_or_insert(Status::RELEASED);
+            status.owner = Some(owner);
+            if status.requested == Some(owner) {
+                status.requested = None;
+            }
+        });
+        Lease {
+            device: guard,
+            status: &self.status,
+        }
+    }
+}
+
+/// A pending [`Arbiter::acquire()`], whose request is withdrawn when dropped.
+struct Request<'a> {
+    status: &'a Watch<CriticalSectionRawMutex, Status, MAX_RECEIVERS>,
+    owner: Owner,
+}
+
+impl Drop for Request<'_> {
+    fn drop(&mut self) {
+        self.status.sender().send_if_modified(|status| {
+            let status = status.get_or_insert(Status::RELEASED);
+            let withdrawn = status.requested == Some(self.owner);
+            if withdrawn {
+                status.requested = None;
+            }
+            withdrawn
+        });
+    }
+}
+
+/// Exclusive access to the device of an [`Arbiter`], which is released when dropped.
+pub struct Lease<'a, D> {
+    device: MutexGuard<'a, CriticalSectionRawMutex, D>,
+    status: &'a Watch<CriticalSectionRawMutex, Status, MAX_RECEIVERS>,
+}
+
+impl<D> Drop for Lease<'_, D> {
+    fn drop(&mut self) {
+        // The device itself is unlocked right after, when the guard is dropped.
+        self.status.sender().send_modify(|status| {
+            status.get_or_insert(Status::RELEASED).owner = None;
+        });
+    }
+}
+
+impl<D: BlockDevice<BLOCK_LEN>> BlockDevice<BLOCK_LEN> for Lease<'_, D> {
+    type Error = D::Error;
+    type Align = D::Align;
+
+    async fn read(
+        &mut self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; BLOCK_LEN]>],
+    ) -> Result<(), Self::Error> {
+        self.device.read(block_address, data).await
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; BLOCK_LEN]>],
+    ) -> Result<(), Self::Error> {
+        self.device.write(block_address, data).await
+    }
+
+    async fn size(&mut self) -> Result<u64, Self::Error> {
+        self.device.size().await
+    }
+}
@@ -0,0 +1,304 @@
+//! Provides FAT filesystems on block devices, through [`embedded-sdmmc`].
+//!
+//! The FAT volume to use is looked up in the MBR partition table of the device by
+//! [`find_volume()`], and the device is then handed to [`mount()`], which returns a
+//! [`VolumeManager`] through which the volume is opened, and whose files implement the
+//! [`embedded_io`] traits.
+//! Only FAT16 and FAT32 volumes in MBR partition tables are supported, as by `embedded-sdmmc`.
+//! Files must be closed before removing the card or powering it down, so that their directory
+//! entries are updated.
+//!
+//! As `embedded-sdmmc` is not async, the block device is accessed by blocking on its futures:
+//! filesystem operations block the executor they are called from until the device completes
+//! them.
+//!
+//! Files are timestamped with the start of the FAT epoch (1980-01-01), as no calendar time is
+//! available; an [`embedded_sdmmc::TimeSource`] can be given to
+//! [`embedded_sdmmc::VolumeManager::new()`] instead.
+//!
+//! [`embedded-sdmmc`]: https://docs.rs/embedded-sdmmc
+
+use core::cell::RefCell;
+
+use aligned::Aligned;
+use block_device_driver::BlockDevice;
+use embedded_sdmmc::{Block, BlockCount, BlockIdx, TimeSource, Timestamp, VolumeIdx};
+
+use crate::BLOCK_LEN;
+
+#[doc(no_inline)]
+pub use embedded_io;
+#[doc(no_inline)]
+pub use embedded_sdmmc;
+
+/// Manages the FAT volumes of the block device `D`.
+pub type VolumeManager<D> = embedded_sdmmc::VolumeManager<Blocking<D>, FatEpoch>;
+
+/// MBR partition types of FAT16 and FAT32 filesystems.
+const FAT_PARTITION_TYPES: [u8; 5] = [0x04, 0x06, 0x0b, 0x0c, 0x0e];
+/// Offset of the partition table in the MBR.
+const PARTITION_TABLE_OFFSET: usize = 446;
+/// Length of the entries of the partition table.
+const PARTITION_ENTRY_LEN: usize = 16;
+/// Signature ending MBRs, and FAT boot sectors.
+const BOOT_SIGNATURE: [u8; 2] = [0x55, 0xaa];
+
+/// Returns the first FAT volume of the MBR partition table of `device`, if any.
+///
+/// # Errors
+///
+/// Returns the error of the device if reading from it fails.
+pub async fn find_volume<D: BlockDevice<BLOCK_LEN>>(
+    device: &mut D,
+) -> Result<Option<VolumeIdx>, D::Error> {
+    let mut mbr = [Aligned([0; BLOCK_LEN])];
+    device.read(0, &mut mbr).await?;
+    let [mbr] = mbr;
+    Ok(first_fat_partition(&mbr).map(VolumeIdx))
+}
+
+/// Returns a [`VolumeManager`] of the FAT volumes of `device`.
+pub fn mount<D: BlockDevice<BLOCK_LEN>>(device: D) -> VolumeManager<D> {
+    embedded_sdmmc::VolumeManager::new(Blocking::new(device), FatEpoch)
+}
+
+/// The block device `D`, accessed by blocking on its futures.
+pub struct Blocking<D> {
+    device: RefCell<D>,
+}
+
+impl<D> Blocking<D> {
+    /// Wraps `device`.
+    pub const fn new(device: D) -> Self {
+        Self {
+            device: RefCell::new(device),
+        }
+    }
+
+    /// Returns the block device.
+    pub fn release(self) -> D {
+        self.device.into_inner()
+    }
+}
+
+impl<D: BlockDevice<BLOCK_LEN>> embedded_sdmmc::BlockDevice for Blocking<D> {
+    type Error = D::Error;
+
+    fn read(&self, blocks: &mut [Block], start_block_idx: BlockIdx) -> Result<(), Self::Error> {
+        let mut device = self.device.borrow_mut();
+        let mut buffer = [Aligned([0; BLOCK_LEN])];
+        for (address, block) in (start_block_idx.0..).zip(blocks) {
+            embassy_futures::block_on(device.read(address, &mut buffer))?;
+            let [data] = &buffer;
+            block.contents = **data;
+        }
+        Ok(())
+    }
+
+    fn write(&self, blocks: &[Block], start_block_idx: BlockIdx) -> Result<(), Self::Error> {
+        let mut device = self.device.borrow_mut();
+        for (address, block) in (start_block_idx.0..).zip(blocks) {
+            embassy_futures::block_on(device.write(address, &[Aligned(block.contents)]))?;
+        }
+        Ok(())
+    }
+
+    fn num_blocks(&self) -> Result<BlockCount, Self::Error> {
+        let size = embassy_futures::block_on(self.device.borrow_mut().size())?;
+        let blocks = size / BLOCK_LEN as u64;
+        Ok(BlockCount(u32::try_from(blocks).unwrap_or(u32::MAX)))
+    }
+}
+
+/// Timestamps files with the start of the FAT epoch, 1980-01-01.
+pub struct FatEpoch;
+
+impl TimeSource for FatEpoch {
+    fn get_timestamp(&self) -> Timestamp {
+        Timestamp {
+            year_since_1970: 10,
+            zero_indexed_month: 0,
+            zero_indexed_day: 0,
+            hours: 0,
+            minutes: 0,
+            seconds: 0,
+        }
+    }
+}
+
+/// Returns the index of the first FAT partition in the partition table of `mbr`.
+fn first_fat_partition(mbr: &[u8; BLOCK_LEN]) -> Option<usize> {
+    if !mbr.ends_with(&BOOT_SIGNATURE) {
+        return None;
+    }
+
+    // FAT boot sectors end with the same signature, but hold boot code instead of a partition
+    // table, which is unlikely to contain valid entries.
+    mbr.get(PARTITION_TABLE_OFFSET..BLOCK_LEN - BOOT_SIGNATURE.len())?
+        .chunks_exact(PARTITION_ENTRY_LEN)
+        .position(|entry| {
+            let Some((&[status, _, _, _, kind, _, _, _], rest)) = entry.split_first_chunk::<8>()
+            else {
+                return false;
+            };
+            let Some((first_block, rest)) = rest.split_first_chunk::<4>() else {
+                return false;
+            };
+            let Some(block_count) = rest.first_chunk::<4>() else {
+                return false;
+            };
+            (status == 0x00 || status == 0x80)
+                && FAT_PARTITION_TYPES.contains(&kind)
+                && u32::from_le_bytes(*first_block) != 0
+                && u32::from_le_bytes(*block_count) != 0
+        })
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use aligned::A1;
+    use embassy_futures::block_on;
+    use embedded_sdmmc::BlockDevice as _;
+
+    use super::*;
+
+    /// A block device in RAM.
+    struct RamDisk {
+        blocks: Vec<[u8; BLOCK_LEN]>,
+    }
+
+    impl BlockDevice<BLOCK_LEN> for RamDisk {
+        type Error = ();
+        type Align = A1;
+
+        async fn read(
+            &mut self,
+            block_address: u32,
+            data: &mut [Aligned<Self::Align, [u8; BLOCK_LEN]>],
+        ) -> Result<(), Self::Error> {
+            let first = usize::try_from(block_address).unwrap();
+            let blocks = self.blocks.get(first..first + data.len()).ok_or(())?;
+            for (data, block) in data.iter_mut().zip(blocks) {
+                **data = *block;
+            }
+            Ok(())
+        }
+
+        async fn write(
+            &mut self,
+            block_address: u32,
+            data: &[Aligned<Self::Align, [u8; BLOCK_LEN]>],
+        ) -> Result<(), Self::Error> {
+            let first = usize::try_from(block_address).unwrap();
+            let blocks = self.blocks.get_mut(first..first + data.len()).ok_or(())?;
+            for (block, data) in blocks.iter_mut().zip(data) {
+                *block = **data;
+            }
+            Ok(())
+        }
+
+        async fn size(&mut self) -> Result<u64, Self::Error> {
+            Ok(u64::try_from(self.blocks.len() * BLOCK_LEN).unwrap())
+        }
+    }
+
+    /// Returns an MBR with the partition table `entries`, as (status, type, first block, number
+    /// of blocks).
+    fn mbr(entries: &[(u8, u8, u32, u32)]) -> [u8; BLOCK_LEN] {
+        let mut mbr = [0; BLOCK_LEN];
+        let table = mbr.get_mut(PARTITION_TABLE_OFFSET..).unwrap();
+        for ((status, kind, first_block, block_count), entry) in entries
+            .iter()
+            .zip(table.chunks_exact_mut(PARTITION_ENTRY_LEN))
+        {
+            entry.fill(0);
+            *entry.get_mut(0).unwrap() = *status;
+            *entry.get_mut(4).unwrap() = *kind;
+            entry
+                .get_mut(8..12)
+                .unwrap()
+                .copy_from_slice(&first_block.to_le_bytes());
+            entry
+                .get_mut(12..16)
+                .unwrap()
+                .copy_from_slice(&block_count.to_le_bytes());
+        }
+        mbr.get_mut(BLOCK_LEN - BOOT_SIGNATURE.len()..)
+            .unwrap()
+            .copy_from_slice(&BOOT_SIGNATURE);
+        mbr
+    }
+
+    #[test]
+    fn fat_partition() {
+        // A single FAT32 partition, as cards are formatted.
+        assert_eq!(
+            first_fat_partition(&mbr(&[(0x00, 0x0c, 8192, 31_108_096)])),
+            Some(0)
+        );
+        // Other partitions, and FAT partitions with invalid status or empty, are skipped.
+        let partitions = mbr(&[
+            (0x00, 0x83, 2048, 1000),
+            (0x7f, 0x0c, 4096, 1000),
+            (0x80, 0x06, 0, 1000),
+            (0x80, 0x0e, 8192, 1000),
+        ]);
+        assert_eq!(first_fat_partition(&partitions), Some(3));
+        // FAT12 is not supported.
+        assert_eq!(first_fat_partition(&mbr(&[(0x00, 0x01, 63, 2000)])), None);
+    }
+
+    #[test]
+    fn no_partition_table() {
+        assert_eq!(first_fat_partition(&mbr(&[])), None);
+        assert_eq!(first_fat_partition(&[0; BLOCK_LEN]), None);
+
+        // A FAT boot sector, as on cards formatted without a partition table.
+        let mut boot_sector = [0; BLOCK_LEN];
+        boot_sector
+            .get_mut(..11)
+            .unwrap()
+            .copy_from_slice(b"\xeb\x3c\x90MSDOS5.0");
+        boot_sector
+            .get_mut(BLOCK_LEN - BOOT_SIGNATURE.len()..)
+            .unwrap()
+            .copy_from_slice(&BOOT_SIGNATURE);
+        assert_eq!(first_fat_partition(&boot_sector), None);
+    }
+
+    #[test]
+    fn find() {
+        let mut disk = RamDisk {
+            blocks: vec![mbr(&[(0x00, 0x0b, 1, 3)]), [0; BLOCK_LEN], [0; BLOCK_LEN]],
+        };
+        assert_eq!(block_on(find_volume(&mut disk)), Ok(Some(VolumeIdx(0))));
+
+        let mut disk = RamDisk { blocks: vec![] };
+        assert_eq!(block_on(find_volume(&mut disk)), Err(()));
+    }
+
+    #[test]
+    fn blocking() {
+        let disk = Blocking::new(RamDisk {
+            blocks: vec![[0; BLOCK_LEN]; 4],
+        });
+        assert_eq!(disk.num_blocks().unwrap(), BlockCount(4));
+
+        let blocks = [1, 2].map(|value| Block {
+            contents: [value; BLOCK_LEN],
+        });
+        disk.write(&blocks, BlockIdx(2)).unwrap();
+        assert_eq!(disk.write(&blocks, BlockIdx(4)), Err(()));
+
+        let mut read = [Block::new(), Block::new(), Block::new()];
+        disk.read(&mut read, BlockIdx(1)).unwrap();
+        assert_eq!(
+            read.map(|block| block.contents),
+            [[0; BLOCK_LEN], [1; BLOCK_LEN], [2; BLOCK_LEN]]
+        );
+
+        let disk = disk.release();
+        assert_eq!(disk.blocks.get(2), Some(&[1; BLOCK_LEN]));
+    }
+}
@@ -0,0 +1,79 @@
+//! Provides a driver for SD cards, and a FAT filesystem on top of it.
+//!
+//! SD cards are driven in SPI mode by [`SdCard`], which exposes them as a [`BlockDevice`] of
+//! 512-byte blocks.
+//! With the `fat` feature, the [`fat`] module mounts the FAT filesystem of block devices, so that
+//! data written by the device, eg. CSV or CBOR logs, can be read directly by a computer:
+//!
+//! ```ignore
+//! use ariel_os::sdcard::fat::{self, embedded_io::Write as _, embedded_sdmmc::Mode};
+//!
+//! let mut card = SdCard::init(spi_bus, cs).await?;
+//! let volume = fat::find_volume(&mut card).await?.ok_or(NoFilesystem)?;
+//! let fs = fat::mount(card);
+//!
+//! let volume = fs.open_volume(volume)?;
+//! let root = volume.open_root_dir()?;
+//! let mut file = root.open_file_in_dir("LOG.CSV", Mode::ReadWriteCreateOrAppend)?;
+//! file.write_all(b"time,temperature\n")?;
+//! file.close()?;
+//! ```
+//!
+//! With the `arbiter` feature, the [`arbiter`] module shares a card between the firmware and a
+//! host it is exposed to, eg. through USB mass storage, so that they do not access it at the same
+//! time.
+//!
+//! SD cards behind SD/MMC host controllers are not supported yet, only cards connected through
+//! SPI.
+
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+#[cfg(feature = "arbiter")]
+pub mod arbiter;
+#[cfg(feature = "fat")]
+pub mod fat;
+mod spi;
+
+#[doc(no_inline)]
+pub use block_device_driver::BlockDevice;
+
+pub use spi::SdCard;
+
+/// Size of the blocks of SD cards, in bytes.
+pub const BLOCK_LEN: usize = 512;
+
+/// Errors of SD cards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// Communicating over the bus failed.
+    Bus,
+    /// The card did not respond in time, or no card is inserted.
+    Timeout,
+    /// The card is not an SD card, or does not support the voltage of the host.
+    UnsupportedCard,
+    /// The card returned an error in response to a command.
+    CommandFailed,
+    /// The card rejected the data written.
+    WriteRejected,
+    /// The block address is out of the range of the card.
+    OutOfRange,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Bus => write!(f, "bus error"),
+            Self::Timeout => write!(f, "timeout"),
+            Self::UnsupportedCard => write!(f, "unsupported card"),
+            Self::CommandFailed => write!(f, "command failed"),
+            Self::WriteRejected => write!(f, "write rejected"),
+            Self::OutOfRange => write!(f, "block address out of range"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
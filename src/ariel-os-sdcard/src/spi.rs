@@ -0,0 +1,728 @@
+//! Driver for SD cards in SPI mode.
+
+use aligned::{A1, Aligned, Alignment};
+use block_device_driver::BlockDevice;
+use embassy_time::{Duration, Instant, Timer};
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::spi::SpiBus;
+
+use crate::{BLOCK_LEN, Error};
+
+const CMD_GO_IDLE_STATE: u8 = 0;
+const CMD_SEND_IF_COND: u8 = 8;
+const CMD_SEND_CSD: u8 = 9;
+const CMD_STOP_TRANSMISSION: u8 = 12;
+const CMD_SET_BLOCKLEN: u8 = 16;
+const CMD_READ_SINGLE_BLOCK: u8 = 17;
+const CMD_READ_MULTIPLE_BLOCK: u8 = 18;
+const CMD_WRITE_BLOCK: u8 = 24;
+const CMD_WRITE_MULTIPLE_BLOCK: u8 = 25;
+const CMD_APP_CMD: u8 = 55;
+const CMD_READ_OCR: u8 = 58;
+const ACMD_SD_SEND_OP_COND: u8 = 41;
+
+/// Argument of [`CMD_SEND_IF_COND`]: 2.7–3.6 V, and a check pattern echoed by the card.
+const IF_COND_ARG: u32 = 0x1aa;
+/// Bit of the argument of [`ACMD_SD_SEND_OP_COND`] announcing support for high-capacity cards.
+const HCS: u32 = 1 << 30;
+/// Bit of the OCR set by high-capacity cards, which are addressed by block instead of by byte.
+const OCR_CCS: u8 = 0x40;
+
+const R1_READY: u8 = 0x00;
+const R1_IDLE: u8 = 0x01;
+const R1_ILLEGAL_COMMAND: u8 = 0x04;
+
+const TOKEN_START_BLOCK: u8 = 0xfe;
+const TOKEN_START_MULTIPLE_WRITE: u8 = 0xfc;
+const TOKEN_STOP_TRANSMISSION: u8 = 0xfd;
+const DATA_RESPONSE_MASK: u8 = 0x1f;
+const DATA_ACCEPTED: u8 = 0x05;
+
+/// Byte clocked out while reading, and read while the card is idle.
+const IDLE: u8 = 0xff;
+
+const INIT_TIMEOUT: Duration = Duration::from_secs(1);
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Number of bytes after which a card that did not respond to a command is considered absent.
+const RESPONSE_BYTES: usize = 10;
+
+/// An SD card in SPI mode, connected through the SPI bus `B` and selected by the pin `C`.
+///
+/// The bus is used exclusively by the card: SD cards need to stay selected across several
+/// transfers, as they only respond once ready.
+/// SDSC, SDHC and SDXC cards are supported, but not MMC cards.
+pub struct SdCard<B, C> {
+    bus: B,
+    cs: C,
+    /// Whether the card is addressed by block instead of by byte.
+    high_capacity: bool,
+}
+
+impl<B: SpiBus, C: OutputPin> SdCard<B, C> {
+    /// Initializes the SD card connected through `bus`, and selected by `cs`.
+    ///
+    /// The bus must run at 100–400 kHz during the initialization; it may then be replaced by a
+    /// bus running at up to 25 MHz with [`SdCard::replace_bus()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Timeout`] if no card responds, [`Error::UnsupportedCard`] if the card is
+    /// not supported, and an error if communicating with the card fails.
+    pub async fn init(bus: B, cs: C) -> Result<Self, Error> {
+        let mut card = Self {
+            bus,
+            cs,
+            high_capacity: false,
+        };
+
+        // Cards enter SPI mode when receiving the reset command while selected, after at least
+        // 74 clock cycles with their chip select high.
+        card.cs.set_high().map_err(|_| Error::Bus)?;
+        card.bus.write(&[IDLE; 10]).await.map_err(|_| Error::Bus)?;
+
+        card.selected(async |card| card.init_selected().await)
+            .await?;
+        Ok(card)
+    }
+
+    /// Replaces the bus of the card by `bus`, eg. one running faster once the card is
+    /// initialized, and returns the previous bus.
+    pub fn replace_bus<B2: SpiBus>(self, bus: B2) -> (SdCard<B2, C>, B) {
+        let card = SdCard {
+            bus,
+            cs: self.cs,
+            high_capacity: self.high_capacity,
+        };
+        (card, self.bus)
+    }
+
+    /// Returns the bus and chip select pin of the card.
+    pub fn release(self) -> (B, C) {
+        (self.bus, self.cs)
+    }
+
+    /// Returns the number of blocks of the card.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if communicating with the card fails.
+    pub async fn block_count(&mut self) -> Result<u64, Error> {
+        let mut csd = [0; 16];
+        self.selected(async |card| {
+            card.expect_ready(CMD_SEND_CSD, 0).await?;
+            card.read_data(&mut csd).await
+        })
+        .await?;
+        block_count(&csd).ok_or(Error::UnsupportedCard)
+    }
+
+    /// Reads the blocks starting at the block of address `first` into `blocks`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::OutOfRange`] if the blocks are out of the card, and an error if
+    /// communicating with the card fails.
+    pub async fn read_blocks(
+        &mut self,
+        first: u32,
+        blocks: &mut [[u8; BLOCK_LEN]],
+    ) -> Result<(), Error> {
+        self.read(first, blocks).await
+    }
+
+    /// Writes `blocks` to the blocks starting at the block of address `first`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::OutOfRange`] if the blocks are out of the card, [`Error::WriteRejected`]
+    /// if the card rejects the data, and an error if communicating with the card fails.
+    pub async fn write_blocks(
+        &mut self,
+        first: u32,
+        blocks: &[[u8; BLOCK_LEN]],
+    ) -> Result<(), Error> {
+        self.write(first, blocks).await
+    }
+
+    /// Runs the initialization sequence, while the card is selected.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`SdCard::init()`].
+    async fn init_selected(&mut self) -> Result<(), Error> {
+        let mut idle = false;
+        for _ in 0..10 {
+            if self.command(CMD_GO_IDLE_STATE, 0).await? == R1_IDLE {
+                idle = true;
+                break;
+            }
+        }
+        if !idle {
+            return Err(Error::Timeout);
+        }
+
+        // Cards predating version 2.00 of the specification do not know the command.
+        let version_2 =
+            self.command(CMD_SEND_IF_COND, IF_COND_ARG).await? & R1_ILLEGAL_COMMAND == 0;
+        if version_2 {
+            let mut r7 = [IDLE; 4];
+            self.transfer(&mut r7).await?;
+            if u32::from_be_bytes(r7) & 0xfff != IF_COND_ARG {
+                return Err(Error::UnsupportedCard);
+            }
+        }
+
+        let deadline = Instant::now() + INIT_TIMEOUT;
+        loop {
+            self.command(CMD_APP_CMD, 0).await?;
+            let r1 = self
+                .command(ACMD_SD_SEND_OP_COND, if version_2 { HCS } else { 0 })
+                .await?;
+            match r1 {
+                R1_READY => break,
+                R1_IDLE if Instant::now() < deadline => Timer::after_millis(10).await,
+                R1_IDLE => return Err(Error::Timeout),
+                // MMC cards do not know the command.
+                _ => return Err(Error::UnsupportedCard),
+            }
+        }
+
+        if version_2 {
+            self.expect_ready(CMD_READ_OCR, 0).await?;
+            let mut ocr = [IDLE; 4];
+            self.transfer(&mut ocr).await?;
+            let [ocr_high, ..] = ocr;
+            self.high_capacity = ocr_high & OCR_CCS != 0;
+        }
+        if !self.high_capacity {
+            // The block length fits into a `u32`.
+            #[expect(clippy::cast_possible_truncation)]
+            self.expect_ready(CMD_SET_BLOCKLEN, BLOCK_LEN as u32)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Reads the blocks starting at the block of address `first` into `blocks`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`SdCard::read_blocks()`].
+    async fn read<T: Block>(&mut self, first: u32, blocks: &mut [T]) -> Result<(), Error> {
+        let address = self.address(first)?;
+        match blocks {
+            [] => Ok(()),
+            [block] => {
+                self.selected(async |card| {
+                    card.expect_ready(CMD_READ_SINGLE_BLOCK, address).await?;
+                    card.read_data(block.bytes_mut()).await
+                })
+                .await
+            }
+            blocks => {
+                self.selected(async |card| {
+                    card.expect_ready(CMD_READ_MULTIPLE_BLOCK, address).await?;
+                    for block in blocks.iter_mut() {
+                        card.read_data(block.bytes_mut()).await?;
+                    }
+                    card.command(CMD_STOP_TRANSMISSION, 0).await?;
+                    card.wait_idle(READ_TIMEOUT).await
+                })
+                .await
+            }
+        }
+    }
+
+    /// Writes `blocks` to the blocks starting at the block of address `first`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`SdCard::write_blocks()`].
+    async fn write<T: Block>(&mut self, first: u32, blocks: &[T]) -> Result<(), Error> {
+        let address = self.address(first)?;
+        match blocks {
+            [] => Ok(()),
+            [block] => {
+                self.selected(async |card| {
+                    card.expect_ready(CMD_WRITE_BLOCK, address).await?;
+                    card.write_data(TOKEN_START_BLOCK, block.bytes()).await?;
+                    card.wait_idle(WRITE_TIMEOUT).await
+                })
+                .await
+            }
+            blocks => {
+                self.selected(async |card| {
+                    card.expect_ready(CMD_WRITE_MULTIPLE_BLOCK, address).await?;
+                    for block in blocks {
+                        card.write_data(TOKEN_START_MULTIPLE_WRITE, block.bytes())
+                            .await?;
+                        card.wait_idle(WRITE_TIMEOUT).await?;
+                    }
+                    card.bus
+                        .write(&[TOKEN_STOP_TRANSMISSION, IDLE])
+                        .await
+                        .map_err(|_| Error::Bus)?;
+                    card.wait_idle(WRITE_TIMEOUT).await
+                })
+                .await
+            }
+        }
+    }
+
+    /// Returns the address of the block of address `block` in commands.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::OutOfRange`] if the address of the block does not fit into a command.
+    fn address(&self, block: u32) -> Result<u32, Error> {
+        if self.high_capacity {
+            Ok(block)
+        } else {
+            // The block length fits into a `u32`.
+            #[expect(clippy::cast_possible_truncation)]
+            block.checked_mul(BLOCK_LEN as u32).ok_or(Error::OutOfRange)
+        }
+    }
+
+    /// Runs `f` with the card selected, and deselects it afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `f`, and an error if selecting the card fails.
+    async fn selected<T>(
+        &mut self,
+        f: impl AsyncFnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        self.cs.set_low().map_err(|_| Error::Bus)?;
+        let result = f(self).await;
+        let deselected = self.cs.set_high().map_err(|_| Error::Bus);
+        // Cards release their data output on the next clock cycles.
+        let released = self.bus.write(&[IDLE]).await.map_err(|_| Error::Bus);
+        let value = result?;
+        deselected?;
+        released?;
+        Ok(value)
+    }
+
+    /// Sends `command` with `arg`, and returns the R1 response of the card.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Timeout`] if the card does not respond, and an error if communicating with
+    /// the card fails.
+    async fn command(&mut self, command: u8, arg: u32) -> Result<u8, Error> {
+        if command != CMD_GO_IDLE_STATE && command != CMD_STOP_TRANSMISSION {
+            self.wait_idle(WRITE_TIMEOUT).await?;
+        }
+
+        // The CRC is only checked for these commands, as long as it is not enabled.
+        let crc = match command {
+            CMD_GO_IDLE_STATE => 0x95,
+            CMD_SEND_IF_COND => 0x87,
+            _ => 0x01,
+        };
+        let [a0, a1, a2, a3] = arg.to_be_bytes();
+        self.bus
+            .write(&[0x40 | command, a0, a1, a2, a3, crc])
+            .await
+            .map_err(|_| Error::Bus)?;
+        if command == CMD_STOP_TRANSMISSION {
+            // Skip the stuff byte following the command.
+            self.read_byte().await?;
+        }
+
+        for _ in 0..RESPONSE_BYTES {
+            let r1 = self.read_byte().await?;
+            if r1 & 0x80 == 0 {
+                return Ok(r1);
+            }
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Sends `command` with `arg`, and checks that the card is ready.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::CommandFailed`] if the card returned an error, and the errors of
+    /// [`SdCard::command()`].
+    async fn expect_ready(&mut self, command: u8, arg: u32) -> Result<(), Error> {
+        match self.command(command, arg).await? {
+            R1_READY => Ok(()),
+            _ => Err(Error::CommandFailed),
+        }
+    }
+
+    /// Reads a block of data, following its start token, into `data`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::CommandFailed`] if the card sends an error token instead of data,
+    /// [`Error::Timeout`] if it sends nothing, and an error if communicating with the card fails.
+    async fn read_data(&mut self, data: &mut [u8]) -> Result<(), Error> {
+        let deadline = Instant::now() + READ_TIMEOUT;
+        loop {
+            match self.read_byte().await? {
+                TOKEN_START_BLOCK => break,
+                IDLE if Instant::now() < deadline => {}
+                IDLE => return Err(Error::Timeout),
+                _ => return Err(Error::CommandFailed),
+            }
+        }
+
+        data.fill(IDLE);
+        self.transfer(data).await?;
+        // The CRC is not checked.
+        self.transfer(&mut [IDLE; 2]).await
+    }
+
+    /// Writes a block of `data`, preceded by `token`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::WriteRejected`] if the card rejects the data, and an error if
+    /// communicating with the card fails.
+    async fn write_data(&mut self, token: u8, data: &[u8]) -> Result<(), Error> {
+        self.bus.write(&[token]).await.map_err(|_| Error::Bus)?;
+        self.bus.write(data).await.map_err(|_| Error::Bus)?;
+        // The CRC is not checked.
+        self.bus.write(&[IDLE; 2]).await.map_err(|_| Error::Bus)?;
+
+        if self.read_byte().await? & DATA_RESPONSE_MASK == DATA_ACCEPTED {
+            Ok(())
+        } else {
+            Err(Error::WriteRejected)
+        }
+    }
+
+    /// Waits for the card to be idle, ie. to release its data output.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Timeout`] if the card is still busy after `timeout`, and an error if
+    /// communicating with the card fails.
+    async fn wait_idle(&mut self, timeout: Duration) -> Result<(), Error> {
+        let deadline = Instant::now() + timeout;
+        while self.read_byte().await? != IDLE {
+            if Instant::now() >= deadline {
+                return Err(Error::Timeout);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a byte.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Bus`] if communicating over the bus fails.
+    async fn read_byte(&mut self) -> Result<u8, Error> {
+        let mut byte = [IDLE];
+        self.transfer(&mut byte).await?;
+        let [byte] = byte;
+        Ok(byte)
+    }
+
+    /// Reads into `data`, whose content is sent at the same time.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Bus`] if communicating over the bus fails.
+    async fn transfer(&mut self, data: &mut [u8]) -> Result<(), Error> {
+        self.bus
+            .transfer_in_place(data)
+            .await
+            .map_err(|_| Error::Bus)
+    }
+}
+
+impl<B: SpiBus, C: OutputPin> BlockDevice<BLOCK_LEN> for SdCard<B, C> {
+    type Error = Error;
+    type Align = A1;
+
+    async fn read(
+        &mut self,
+        block_address: u32,
+        data: &mut [Aligned<Self::Align, [u8; BLOCK_LEN]>],
+    ) -> Result<(), Self::Error> {
+        SdCard::read(self, block_address, data).await
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<Self::Align, [u8; BLOCK_LEN]>],
+    ) -> Result<(), Self::Error> {
+        SdCard::write(self, block_address, data).await
+    }
+
+    async fn size(&mut self) -> Result<u64, Self::Error> {
+        Ok(self.block_count().await? * BLOCK_LEN as u64)
+    }
+}
+
+/// A block of data, aligned or not.
+trait Block {
+    fn bytes(&self) -> &[u8; BLOCK_LEN];
+    fn bytes_mut(&mut self) -> &mut [u8; BLOCK_LEN];
+}
+
+impl Block for [u8; BLOCK_LEN] {
+    fn bytes(&self) -> &[u8; BLOCK_LEN] {
+        self
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8; BLOCK_LEN] {
+        self
+    }
+}
+
+impl<A: Alignment> Block for Aligned<A, [u8; BLOCK_LEN]> {
+    fn bytes(&self) -> &[u8; BLOCK_LEN] {
+        self
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8; BLOCK_LEN] {
+        self
+    }
+}
+
+/// Returns the number of blocks of a card, given its card-specific data (CSD) register.
+fn block_count(csd: &[u8; 16]) -> Option<u64> {
+    let &[c0, _, _, _, _, c5, c6, c7, c8, c9, c10, ..] = csd;
+    match c0 >> 6 {
+        // CSD version 1.0, of SDSC cards.
+        0 => {
+            let read_block_len = u32::from(c5 & 0x0f);
+            let c_size = (u64::from(c6 & 0x03) << 10) | (u64::from(c7) << 2) | u64::from(c8 >> 6);
+            let c_size_mult = u32::from(((c9 & 0x03) << 1) | (c10 >> 7));
+            let bytes = (c_size + 1) << (c_size_mult + 2 + read_block_len);
+            Some(bytes / BLOCK_LEN as u64)
+        }
+        // CSD version 2.0, of SDHC and SDXC cards, whose capacity is a multiple of 512 KiB.
+        1 => {
+            let c_size = (u64::from(c7 & 0x3f) << 16) | (u64::from(c8) << 8) | u64::from(c9);
+            Some((c_size + 1) * 1024)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use ariel_os_embassy_common::spi::main::mock::{Expectation, SpiMock};
+    use embassy_futures::block_on;
+
+    use super::*;
+
+    /// A chip select pin that cannot fail.
+    struct Cs;
+
+    impl embedded_hal::digital::ErrorType for Cs {
+        type Error = core::convert::Infallible;
+    }
+
+    impl OutputPin for Cs {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    /// Card waiting for the chip select to be released after a command.
+    const RELEASE: Expectation<'static> = Expectation::Write(&[IDLE]);
+
+    /// Returns the expectation of the card responding with `r1`.
+    fn response(r1: &'static [u8]) -> Expectation<'static> {
+        Expectation::Transfer {
+            write: &[IDLE],
+            read: r1,
+        }
+    }
+
+    /// Returns the expectations of `command` being sent to an idle card, which responds with `r1`.
+    fn command(command: &'static [u8], r1: &'static [u8]) -> [Expectation<'static>; 3] {
+        [response(&[IDLE]), Expectation::Write(command), response(r1)]
+    }
+
+    /// `SEND_IF_COND` command, with its argument.
+    const SEND_IF_COND: &[u8] = &[0x48, 0, 0, 0x01, 0xaa, 0x87];
+
+    /// Returns the expectations of the reset of a card into SPI mode.
+    fn reset() -> Vec<Expectation<'static>> {
+        vec![
+            Expectation::Write(&[IDLE; 10]),
+            Expectation::Write(&[0x40, 0, 0, 0, 0, 0x95]),
+            response(&[R1_IDLE]),
+        ]
+    }
+
+    /// Returns the expectations of the initialization of a card of version 2.00 or later, whose
+    /// OCR is `ocr`.
+    fn init_version_2(ocr: &'static [u8]) -> Vec<Expectation<'static>> {
+        let mut expectations = reset();
+        expectations.extend(command(SEND_IF_COND, &[R1_IDLE]));
+        expectations.push(Expectation::Transfer {
+            write: &[IDLE; 4],
+            read: &[0, 0, 0x01, 0xaa],
+        });
+        expectations.extend(command(&[0x77, 0, 0, 0, 0, 0x01], &[R1_IDLE]));
+        expectations.extend(command(&[0x69, 0x40, 0, 0, 0, 0x01], &[R1_READY]));
+        expectations.extend(command(&[0x7a, 0, 0, 0, 0, 0x01], &[R1_READY]));
+        expectations.push(Expectation::Transfer {
+            write: &[IDLE; 4],
+            read: ocr,
+        });
+        expectations
+    }
+
+    /// Returns the expectations of reading a block of `data` with `command`.
+    fn read_block(command: &'static [u8], data: &'static [u8]) -> Vec<Expectation<'static>> {
+        let mut expectations = self::command(command, &[R1_READY]).to_vec();
+        expectations.extend([
+            response(&[IDLE]),
+            response(&[TOKEN_START_BLOCK]),
+            Expectation::Transfer {
+                write: &[IDLE; BLOCK_LEN],
+                read: data,
+            },
+            Expectation::Transfer {
+                write: &[IDLE; 2],
+                read: &[0x12, 0x34],
+            },
+            RELEASE,
+        ]);
+        expectations
+    }
+
+    #[test]
+    fn high_capacity_card() {
+        let mut expectations = init_version_2(&[0xc0, 0xff, 0x80, 0]);
+        expectations.push(RELEASE);
+        // High-capacity cards are addressed by block.
+        expectations.extend(read_block(&[0x51, 0, 0, 0, 5, 0x01], &[0xa5; BLOCK_LEN]));
+        let mut spi = SpiMock::new(&expectations);
+
+        block_on(async {
+            let mut card = SdCard::init(&mut spi, Cs).await.unwrap();
+            assert!(card.high_capacity);
+            let mut block = [[0; BLOCK_LEN]];
+            card.read_blocks(5, &mut block).await.unwrap();
+            assert_eq!(block, [[0xa5; BLOCK_LEN]]);
+        });
+        spi.done();
+    }
+
+    #[test]
+    fn standard_capacity_card() {
+        let mut expectations = init_version_2(&[0x80, 0xff, 0x80, 0]);
+        // Standard-capacity cards are set to blocks of 512 bytes, and addressed by byte.
+        expectations.extend(command(&[0x50, 0, 0, 0x02, 0, 0x01], &[R1_READY]));
+        expectations.push(RELEASE);
+        expectations.extend(read_block(&[0x51, 0, 0, 0x0a, 0, 0x01], &[0x5a; BLOCK_LEN]));
+        let mut spi = SpiMock::new(&expectations);
+
+        block_on(async {
+            let mut card = SdCard::init(&mut spi, Cs).await.unwrap();
+            assert!(!card.high_capacity);
+            let mut block = [[0; BLOCK_LEN]];
+            card.read_blocks(5, &mut block).await.unwrap();
+            assert_eq!(block, [[0x5a; BLOCK_LEN]]);
+        });
+        spi.done();
+    }
+
+    #[test]
+    fn version_1_card() {
+        // Cards predating version 2.00 do not know SEND_IF_COND, are not asked about high
+        // capacity, and their OCR is not read.
+        let mut expectations = reset();
+        expectations.extend(command(SEND_IF_COND, &[R1_IDLE | R1_ILLEGAL_COMMAND]));
+        expectations.extend(command(&[0x77, 0, 0, 0, 0, 0x01], &[R1_IDLE]));
+        expectations.extend(command(&[0x69, 0, 0, 0, 0, 0x01], &[R1_IDLE]));
+        expectations.extend(command(&[0x77, 0, 0, 0, 0, 0x01], &[R1_IDLE]));
+        expectations.extend(command(&[0x69, 0, 0, 0, 0, 0x01], &[R1_READY]));
+        expectations.extend(command(&[0x50, 0, 0, 0x02, 0, 0x01], &[R1_READY]));
+        expectations.push(RELEASE);
+        let mut spi = SpiMock::new(&expectations);
+
+        let card = block_on(SdCard::init(&mut spi, Cs)).unwrap();
+        assert!(!card.high_capacity);
+        spi.done();
+    }
+
+    #[test]
+    fn mmc_card_is_unsupported() {
+        let mut expectations = reset();
+        expectations.extend(command(SEND_IF_COND, &[R1_IDLE | R1_ILLEGAL_COMMAND]));
+        expectations.extend(command(&[0x77, 0, 0, 0, 0, 0x01], &[R1_IDLE]));
+        expectations.extend(command(
+            &[0x69, 0, 0, 0, 0, 0x01],
+            &[R1_IDLE | R1_ILLEGAL_COMMAND],
+        ));
+        expectations.push(RELEASE);
+        let mut spi = SpiMock::new(&expectations);
+
+        let result = block_on(SdCard::init(&mut spi, Cs));
+        assert!(matches!(result, Err(Error::UnsupportedCard)));
+        spi.done();
+    }
+
+    #[test]
+    fn unsupported_voltage() {
+        let mut expectations = reset();
+        expectations.extend(command(SEND_IF_COND, &[R1_IDLE]));
+        // The card does not echo the voltage range of the host.
+        expectations.push(Expectation::Transfer {
+            write: &[IDLE; 4],
+            read: &[0, 0, 0, 0xaa],
+        });
+        expectations.push(RELEASE);
+        let mut spi = SpiMock::new(&expectations);
+
+        let result = block_on(SdCard::init(&mut spi, Cs));
+        assert!(matches!(result, Err(Error::UnsupportedCard)));
+        spi.done();
+    }
+
+    #[test]
+    fn no_card() {
+        // The data output of the card is never driven.
+        let mut expectations = vec![
+            Expectation::Write(&[IDLE; 10]),
+            Expectation::Write(&[0x40, 0, 0, 0, 0, 0x95]),
+        ];
+        expectations.extend([response(&[IDLE]); RESPONSE_BYTES]);
+        expectations.push(RELEASE);
+        let mut spi = SpiMock::new(&expectations);
+
+        let result = block_on(SdCard::init(&mut spi, Cs));
+        assert!(matches!(result, Err(Error::Timeout)));
+        spi.done();
+    }
+
+    #[test]
+    fn csd_block_count() {
+        // CSD version 2.0 of a 16 GB SDHC card, with a C_SIZE of 0x7647.
+        let csd = [
+            0x40, 0x0e, 0, 0x32, 0x5b, 0x59, 0, 0, 0x76, 0x47, 0x7f, 0x80, 0x0a, 0x40, 0, 0x8b,
+        ];
+        assert_eq!(block_count(&csd), Some((0x7647 + 1) * 1024));
+
+        // CSD version 1.0 of a 1 GB SDSC card: READ_BL_LEN 9, C_SIZE 0xf03 and C_SIZE_MULT 7.
+        let csd = [
+            0x00, 0x2e, 0, 0x32, 0x5f, 0x59, 0x83, 0xc0, 0xfe, 0xfb, 0xcf, 0xff, 0x92, 0x40, 0x40,
+            0xd7,
+        ];
+        assert_eq!(block_count(&csd), Some((0xf03 + 1) << 9));
+
+        // Reserved CSD structure versions.
+        assert_eq!(block_count(&[0x80; 16]), None);
+    }
+}
@@ -0,0 +1,611 @@
+//! Provides alerts on sensor values crossing thresholds.
+//!
+//! [`Rule`]s added to [`ALERTS`] are evaluated on the readings obtained by the
+//! [`SAMPLER`](crate::sampling::SAMPLER), so that applications are notified when a value crosses
+//! a threshold instead of going through every reading:
+//!
+//! ```ignore
+//! use ariel_os::sensors::alerts::{ALERTS, AlertKind, Rule, Threshold};
+//!
+//! // Alerts when the temperature exceeds 30 °C, until it falls below 29 °C again.
+//! let rule = Rule::new(&TEMP_SENSOR, 0, Threshold::Above(3000)).with_hysteresis(100);
+//! ALERTS.add_rule(rule)?;
+//!
+//! let mut subscriber = ALERTS.subscribe()?;
+//! loop {
+//!     let alert = subscriber.next_message_pure().await;
+//!     if alert.kind() == AlertKind::Raised {
+//!         // ...
+//!     }
+//! }
+//! ```
+//!
+//! Thresholds and hysteresis are expressed like the values of the samples of the
+//! [`ReadingChannel`](crate::ReadingChannel) they apply to, ie. with its scaling.
+//! A rule is raised once the value has been beyond its threshold for a number of consecutive
+//! readings given by its [debounce](Rule::with_debounce), and cleared once it has been back by
+//! more than its hysteresis for as many readings. Failed readings leave rules unchanged.
+//!
+//! Alerts are delivered to subscribers, which forward them as needed, eg. as MQTT messages;
+//! observing them over CoAP is not supported by the CoAP handlers yet.
+//!
+//! # Configuration
+//!
+//! - `CONFIG_SENSORS_ALERTS_MAX_RULES` (default: 8): maximum number of rules.
+//! - `CONFIG_SENSORS_ALERTS_MAX_SUBSCRIBERS` (default: 2): maximum number of subscribers.
+//! - `CONFIG_SENSORS_ALERTS_QUEUE_SIZE` (default: 4): number of alerts queued for each
+//!   subscriber; subscribers that lag behind miss the oldest alerts.
+
+use core::cell::RefCell;
+
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    pubsub::{self, PubSubChannel},
+};
+use embassy_time::Instant;
+
+use crate::{Sample, Sensor, sampling::SampledReading};
+
+/// Maximum number of [`Rule`]s, configured through the `CONFIG_SENSORS_ALERTS_MAX_RULES`
+/// environment variable.
+pub const MAX_RULES: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SENSORS_ALERTS_MAX_RULES",
+    8,
+    "maximum number of sensor alert rules"
+);
+
+/// Maximum number of subscribers of [`ALERTS`], configured through the
+/// `CONFIG_SENSORS_ALERTS_MAX_SUBSCRIBERS` environment variable.
+pub const MAX_SUBSCRIBERS: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SENSORS_ALERTS_MAX_SUBSCRIBERS",
+    2,
+    "maximum number of subscribers to sensor alerts"
+);
+
+/// Number of alerts queued for each subscriber, configured through the
+/// `CONFIG_SENSORS_ALERTS_QUEUE_SIZE` environment variable.
+pub const QUEUE_SIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SENSORS_ALERTS_QUEUE_SIZE",
+    4,
+    "number of sensor alerts queued for each subscriber"
+);
+
+/// The alert rules of the system.
+pub static ALERTS: Alerts = Alerts::new();
+
+/// Receives the alerts of [`ALERTS`].
+pub type Subscriber =
+    pubsub::Subscriber<'static, CriticalSectionRawMutex, Alert, QUEUE_SIZE, MAX_SUBSCRIBERS, 0>;
+
+/// Threshold of a [`Rule`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Threshold {
+    /// Alerts when the value is above the threshold.
+    Above(i32),
+    /// Alerts when the value is below the threshold.
+    Below(i32),
+}
+
+impl Threshold {
+    /// Returns whether `value` is beyond the threshold.
+    fn exceeded(self, value: i32) -> bool {
+        match self {
+            Self::Above(threshold) => value > threshold,
+            Self::Below(threshold) => value < threshold,
+        }
+    }
+
+    /// Returns whether `value` is back from the threshold by more than `hysteresis`.
+    fn recovered(self, value: i32, hysteresis: i32) -> bool {
+        match self {
+            Self::Above(threshold) => value <= threshold.saturating_sub(hysteresis),
+            Self::Below(threshold) => value >= threshold.saturating_add(hysteresis),
+        }
+    }
+}
+
+/// A rule alerting when the values of a reading channel of a sensor cross a [`Threshold`].
+#[derive(Clone, Copy)]
+pub struct Rule {
+    sensor: &'static dyn Sensor,
+    channel: usize,
+    threshold: Threshold,
+    hysteresis: i32,
+    debounce: u8,
+}
+
+impl Rule {
+    /// Creates a rule on the reading channel at `channel` of `sensor`, without hysteresis, and
+    /// raised on the first reading beyond `threshold`.
+    #[must_use]
+    pub const fn new(sensor: &'static dyn Sensor, channel: usize, threshold: Threshold) -> Self {
+        Self {
+            sensor,
+            channel,
+            threshold,
+            hysteresis: 0,
+            debounce: 1,
+        }
+    }
+
+    /// Only clears the rule once the value is back from the threshold by more than `hysteresis`.
+    #[must_use]
+    pub const fn with_hysteresis(self, hysteresis: i32) -> Self {
+        Self { hysteresis, ..self }
+    }
+
+    /// Only raises or clears the rule after `debounce` consecutive readings; `0` is handled
+    /// like `1`.
+    #[must_use]
+    pub const fn with_debounce(self, debounce: u8) -> Self {
+        Self { debounce, ..self }
+    }
+
+    /// Returns the sensor of the rule.
+    #[must_use]
+    pub fn sensor(&self) -> &'static dyn Sensor {
+        self.sensor
+    }
+
+    /// Returns the index of the reading channel of the rule.
+    #[must_use]
+    pub const fn channel(&self) -> usize {
+        self.channel
+    }
+
+    /// Returns the threshold of the rule.
+    #[must_use]
+    pub const fn threshold(&self) -> Threshold {
+        self.threshold
+    }
+
+    /// Returns the hysteresis of the rule.
+    #[must_use]
+    pub const fn hysteresis(&self) -> i32 {
+        self.hysteresis
+    }
+
+    /// Returns the number of consecutive readings needed to raise or clear the rule.
+    #[must_use]
+    pub const fn debounce(&self) -> u8 {
+        self.debounce
+    }
+}
+
+/// Identifies a [`Rule`] added to [`ALERTS`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RuleId(u32);
+
+/// Whether an [`Alert`] was raised or cleared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AlertKind {
+    /// The value crossed the threshold.
+    Raised,
+    /// The value is back from the threshold.
+    Cleared,
+}
+
+/// An alert, sent when a [`Rule`] is raised or cleared.
+#[derive(Clone, Copy)]
+pub struct Alert {
+    rule: RuleId,
+    sensor: &'static dyn Sensor,
+    channel: usize,
+    kind: AlertKind,
+    timestamp: Instant,
+    sample: Sample,
+}
+
+impl Alert {
+    /// Returns the rule that was raised or cleared.
+    #[must_use]
+    pub fn rule(&self) -> RuleId {
+        self.rule
+    }
+
+    /// Returns the sensor of the rule.
+    #[must_use]
+    pub fn sensor(&self) -> &'static dyn Sensor {
+        self.sensor
+    }
+
+    /// Returns the index of the reading channel of the rule.
+    #[must_use]
+    pub fn channel(&self) -> usize {
+        self.channel
+    }
+
+    /// Returns whether the rule was raised or cleared.
+    #[must_use]
+    pub fn kind(&self) -> AlertKind {
+        self.kind
+    }
+
+    /// Returns the instant the measurement of the reading that raised or cleared the rule was
+    /// triggered at.
+    #[must_use]
+    pub fn timestamp(&self) -> Instant {
+        self.timestamp
+    }
+
+    /// Returns the sample that raised or cleared the rule.
+    #[must_use]
+    pub fn sample(&self) -> Sample {
+        self.sample
+    }
+}
+
+/// Error returned by [`Alerts`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum AlertError {
+    /// [`MAX_RULES`] rules were already added.
+    TooManyRules,
+    /// [`MAX_SUBSCRIBERS`] subscribers are already subscribed.
+    TooManySubscribers,
+}
+
+impl core::fmt::Display for AlertError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::TooManyRules => write!(f, "too many alert rules"),
+            Self::TooManySubscribers => write!(f, "too many subscribers"),
+        }
+    }
+}
+
+impl core::error::Error for AlertError {}
+
+struct Entry {
+    id: RuleId,
+    rule: Rule,
+    raised: bool,
+    /// Number of consecutive readings that would change whether the rule is raised.
+    pending: u8,
+}
+
+struct Rules {
+    entries: heapless::Vec<Entry, MAX_RULES>,
+    next_id: u32,
+}
+
+/// Evaluates [`Rule`]s on sampled readings.
+pub struct Alerts {
+    rules: Mutex<CriticalSectionRawMutex, RefCell<Rules>>,
+    alerts: PubSubChannel<CriticalSectionRawMutex, Alert, QUEUE_SIZE, MAX_SUBSCRIBERS, 0>,
+}
+
+impl Alerts {
+    const fn new() -> Self {
+        Self {
+            rules: Mutex::new(RefCell::new(Rules {
+                entries: heapless::Vec::new(),
+                next_id: 0,
+            })),
+            alerts: PubSubChannel::new(),
+        }
+    }
+
+    /// Adds `rule`, which is initially cleared, and returns its identifier.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AlertError::TooManyRules`] if [`MAX_RULES`] rules were already added.
+    pub fn add_rule(&self, rule: Rule) -> Result<RuleId, AlertError> {
+        self.rules.lock(|rules| {
+            let mut rules = rules.borrow_mut();
+            let id = RuleId(rules.next_id);
+            let entry = Entry {
+                id,
+                rule,
+                raised: false,
+                pending: 0,
+            };
+            rules
+                .entries
+                .push(entry)
+                .map_err(|_| AlertError::TooManyRules)?;
+            rules.next_id = rules.next_id.wrapping_add(1);
+            Ok(id)
+        })
+    }
+
+    /// Removes the rule identified by `id`, and returns it if it was not removed already.
+    pub fn remove_rule(&self, id: RuleId) -> Option<Rule> {
+        self.rules.lock(|rules| {
+            let mut rules = rules.borrow_mut();
+            let position = rules.entries.iter().position(|entry| entry.id == id)?;
+            Some(rules.entries.swap_remove(position).rule)
+        })
+    }
+
+    /// Returns whether the rule identified by `id` is raised, if it was not removed.
+    pub fn is_raised(&self, id: RuleId) -> Option<bool> {
+        self.rules.lock(|rules| {
+            rules
+                .borrow()
+                .entries
+                .iter()
+                .find(|entry| entry.id == id)
+                .map(|entry| entry.raised)
+        })
+    }
+
+    /// Returns a new subscriber to the alerts.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AlertError::TooManySubscribers`] if [`MAX_SUBSCRIBERS`] subscribers are already
+    /// subscribed.
+    pub fn subscribe(&'static self) -> Result<Subscriber, AlertError> {
+        self.alerts
+            .subscriber()
+            .map_err(|_| AlertError::TooManySubscribers)
+    }
+
+    /// Evaluates the rules on the reading of `sampled`, and sends the resulting alerts.
+    pub(crate) fn evaluate(&self, sampled: &SampledReading) {
+        let Ok(reading) = sampled.reading() else {
+            return;
+        };
+        let mut alerts = heapless::Vec::<Alert, MAX_RULES>::new();
+        self.rules.lock(|rules| {
+            let mut rules = rules.borrow_mut();
+            for entry in &mut rules.entries {
+                let rule = entry.rule;
+                if !core::ptr::addr_eq(rule.sensor, sampled.sensor()) {
+                    continue;
+                }
+                let Some(sample) = reading.iter().nth(rule.channel) else {
+                    continue;
+                };
+                let changing = if entry.raised {
+                    rule.threshold.recovered(sample.value(), rule.hysteresis)
+                } else {
+                    rule.threshold.exceeded(sample.value())
+                };
+                if !changing {
+                    entry.pending = 0;
+                    continue;
+                }
+                entry.pending = entry.pending.saturating_add(1);
+                if entry.pending < rule.debounce {
+                    continue;
+                }
+                entry.raised = !entry.raised;
+                entry.pending = 0;
+                let kind = if entry.raised {
+                    AlertKind::Raised
+                } else {
+                    AlertKind::Cleared
+                };
+                // Cannot fail, there are no more alerts than rules.
+                let _ = alerts.push(Alert {
+                    rule: entry.id,
+                    sensor: rule.sensor,
+                    channel: rule.channel,
+                    kind,
+                    timestamp: sampled.timestamp(),
+                    sample,
+                });
+            }
+        });
+        let publisher = self.alerts.immediate_publisher();
+        for alert in alerts {
+            publisher.publish_immediate(alert);
+        }
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use super::*;
+
+    use crate::{Accuracy, Category, Error, Mode, ReadingChannel, ReadingWaiter, Samples, State};
+
+    /// A sensor that is only used to tell readings apart, labelled with its field.
+    struct TestSensor(&'static str);
+
+    impl Sensor for TestSensor {
+        fn trigger_measurement(&self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn wait_for_reading(&'static self) -> ReadingWaiter {
+            ReadingWaiter::err(Error::SensorAccess)
+        }
+
+        fn set_mode(&self, mode: Mode) -> Result<State, Error> {
+            Ok(mode.into())
+        }
+
+        fn state(&self) -> State {
+            State::OneShot
+        }
+
+        fn categories(&self) -> &'static [Category] {
+            &[]
+        }
+
+        fn reading_channels(&self) -> &'static [ReadingChannel] {
+            &[]
+        }
+
+        fn label(&self) -> Option<&'static str> {
+            Some(self.0)
+        }
+
+        fn display_name(&self) -> Option<&'static str> {
+            None
+        }
+
+        fn part_number(&self) -> Option<&'static str> {
+            None
+        }
+    }
+
+    static SENSOR: TestSensor = TestSensor("sensor");
+    static OTHER_SENSOR: TestSensor = TestSensor("other");
+
+    /// Returns a reading of `sensor` whose second sample is `value`.
+    fn reading(sensor: &'static TestSensor, value: i32) -> SampledReading {
+        let samples = Samples::from_array([
+            Sample::new(0, Accuracy::Unknown),
+            Sample::new(value, Accuracy::Unknown),
+        ]);
+        SampledReading::new(sensor, Instant::MIN, Ok(samples))
+    }
+
+    /// Evaluates the readings of `values` in turn, and returns whether the rule `id` is raised
+    /// after each of them.
+    fn evaluate(alerts: &Alerts, id: RuleId, values: &[i32]) -> heapless::Vec<bool, 16> {
+        values
+            .iter()
+            .map(|&value| {
+                alerts.evaluate(&reading(&SENSOR, value));
+                alerts.is_raised(id).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn crossing_raises_and_clears() {
+        let alerts = Alerts::new();
+        let above = alerts
+            .add_rule(Rule::new(&SENSOR, 1, Threshold::Above(30)))
+            .unwrap();
+        let below = alerts
+            .add_rule(Rule::new(&SENSOR, 1, Threshold::Below(10)))
+            .unwrap();
+        assert_eq!(alerts.is_raised(above), Some(false));
+
+        // Reaching the threshold does not cross it.
+        assert_eq!(
+            evaluate(&alerts, above, &[29, 30, 31, 35, 30, 31]),
+            [false, false, true, true, false, true]
+        );
+        assert_eq!(alerts.is_raised(below), Some(false));
+        assert_eq!(evaluate(&alerts, below, &[10, 9, 10]), [false, true, false]);
+        assert_eq!(alerts.is_raised(above), Some(false));
+    }
+
+    #[test]
+    fn hysteresis_rearms() {
+        let alerts = Alerts::new();
+        let id = alerts
+            .add_rule(Rule::new(&SENSOR, 1, Threshold::Above(30)).with_hysteresis(2))
+            .unwrap();
+
+        // Only cleared once back to the threshold minus the hysteresis, and then raised again on
+        // the next crossing.
+        assert_eq!(
+            evaluate(&alerts, id, &[31, 30, 29, 31, 28, 29, 30, 31]),
+            [true, true, true, true, false, false, false, true]
+        );
+
+        let id = alerts
+            .add_rule(Rule::new(&SENSOR, 1, Threshold::Below(10)).with_hysteresis(2))
+            .unwrap();
+        assert_eq!(
+            evaluate(&alerts, id, &[9, 11, 12, 11, 9]),
+            [true, true, false, false, true]
+        );
+    }
+
+    #[test]
+    fn debounce_needs_consecutive_readings() {
+        let alerts = Alerts::new();
+        let id = alerts
+            .add_rule(
+                Rule::new(&SENSOR, 1, Threshold::Above(30))
+                    .with_hysteresis(2)
+                    .with_debounce(3),
+            )
+            .unwrap();
+
+        // A reading within the threshold restarts the count.
+        assert_eq!(
+            evaluate(&alerts, id, &[31, 32, 30, 31, 32, 33]),
+            [false, false, false, false, false, true]
+        );
+        // Readings within the hysteresis restart the count of clearing readings too.
+        assert_eq!(
+            evaluate(&alerts, id, &[28, 27, 29, 28, 27, 26]),
+            [true, true, true, true, true, false]
+        );
+
+        // A debounce of `0` is handled like `1`.
+        let id = alerts
+            .add_rule(Rule::new(&SENSOR, 1, Threshold::Above(30)).with_debounce(0))
+            .unwrap();
+        assert_eq!(evaluate(&alerts, id, &[31, 30]), [true, false]);
+    }
+
+    #[test]
+    fn failed_readings_are_ignored() {
+        let alerts = Alerts::new();
+        let id = alerts
+            .add_rule(Rule::new(&SENSOR, 1, Threshold::Above(30)).with_debounce(2))
+            .unwrap();
+
+        // Failed readings neither raise the rule nor restart the count.
+        let failed = SampledReading::new(&SENSOR, Instant::MIN, Err(Error::SensorAccess));
+        alerts.evaluate(&reading(&SENSOR, 31));
+        alerts.evaluate(&failed);
+        assert_eq!(alerts.is_raised(id), Some(false));
+        alerts.evaluate(&reading(&SENSOR, 31));
+        assert_eq!(alerts.is_raised(id), Some(true));
+        alerts.evaluate(&failed);
+        assert_eq!(alerts.is_raised(id), Some(true));
+    }
+
+    #[test]
+    fn other_sensors_and_channels_are_ignored() {
+        let alerts = Alerts::new();
+        let id = alerts
+            .add_rule(Rule::new(&SENSOR, 1, Threshold::Above(30)))
+            .unwrap();
+        let missing = alerts
+            .add_rule(Rule::new(&SENSOR, 2, Threshold::Below(100)))
+            .unwrap();
+
+        alerts.evaluate(&reading(&OTHER_SENSOR, 31));
+        assert_eq!(alerts.is_raised(id), Some(false));
+        alerts.evaluate(&reading(&SENSOR, 31));
+        assert_eq!(alerts.is_raised(id), Some(true));
+        assert_eq!(alerts.is_raised(missing), Some(false));
+
+        assert!(alerts.remove_rule(id).is_some());
+        assert!(alerts.remove_rule(id).is_none());
+        assert_eq!(alerts.is_raised(id), None);
+    }
+
+    #[test]
+    fn alerts_are_published() {
+        static ALERTS: Alerts = Alerts::new();
+        let id = ALERTS
+            .add_rule(Rule::new(&SENSOR, 1, Threshold::Above(30)).with_hysteresis(2))
+            .unwrap();
+        let mut subscriber = ALERTS.subscribe().unwrap();
+
+        for value in [31, 32, 29, 28] {
+            ALERTS.evaluate(&reading(&SENSOR, value));
+        }
+
+        let alert = subscriber.try_next_message_pure().unwrap();
+        assert_eq!(alert.rule(), id);
+        assert_eq!(alert.kind(), AlertKind::Raised);
+        assert_eq!(alert.sensor().label(), Some("sensor"));
+        assert_eq!(alert.channel(), 1);
+        assert_eq!(alert.sample().value(), 31);
+        let alert = subscriber.try_next_message_pure().unwrap();
+        assert_eq!(alert.kind(), AlertKind::Cleared);
+        assert_eq!(alert.sample().value(), 28);
+        assert!(subscriber.try_next_message_pure().is_none());
+    }
+}
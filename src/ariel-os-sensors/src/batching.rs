@@ -0,0 +1,253 @@
+//! Provides batching of sensor readings in the FIFOs of their devices.
+//!
+//! Drivers of devices with a hardware FIFO provide `run_batching` methods, which make the device
+//! measure into its FIFO and raise its interrupt pin once the FIFO holds the configured number of
+//! readings. The MCU sleeps until the interrupt fires, then drains the FIFO in a single burst, and
+//! delivers the timestamped readings as one [`Batch`] to the subscribers of the [`BATCHER`]:
+//!
+//! ```ignore
+//! use ariel_os::sensors::{batching::{self, BATCHER}, drivers::lis3dh};
+//!
+//! #[ariel_os::task(autostart, peripherals)]
+//! async fn accelerometer(peripherals: pins::Peripherals) {
+//!     // ...
+//!     let interrupt = gpio::Input::builder(peripherals.accel_int1, gpio::Pull::None).build_with_interrupt();
+//!     let _ = ACCEL.run_batching_i2c(i2c_device, interrupt, lis3dh::Config::default(), batching::Config::default()).await;
+//! }
+//!
+//! #[ariel_os::task(autostart)]
+//! async fn app() {
+//!     let mut subscriber = BATCHER.subscribe().unwrap();
+//!     loop {
+//!         let batch = subscriber.next_message_pure().await;
+//!         for (timestamp, samples) in batch.readings() {
+//!             // ...
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! A sensor run in batching mode is not measured through the [`Sensor`] trait: it stays
+//! [`State::Uninitialized`](crate::State::Uninitialized).
+//! With the `calibration` feature, the calibration of the sensor is applied to each reading.
+//!
+//! # Configuration
+//!
+//! - `CONFIG_SENSORS_BATCHING_MAX_SIZE` (default: 32): maximum number of readings in a batch.
+//! - `CONFIG_SENSORS_BATCHING_MAX_SUBSCRIBERS` (default: 2): maximum number of subscribers.
+//! - `CONFIG_SENSORS_BATCHING_QUEUE_SIZE` (default: 1): number of batches queued for each
+//!   subscriber; subscribers that lag behind miss the oldest batches.
+
+use core::convert::Infallible;
+
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    pubsub::{self, PubSubChannel},
+};
+use embassy_time::{Duration, Instant};
+use embedded_hal_async::digital::Wait;
+
+use crate::{Error, Samples, Sensor};
+
+/// Maximum number of readings in a [`Batch`], configured through the
+/// `CONFIG_SENSORS_BATCHING_MAX_SIZE` environment variable.
+pub const MAX_SIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SENSORS_BATCHING_MAX_SIZE",
+    32,
+    "maximum number of sensor readings in a batch"
+);
+
+/// Maximum number of subscribers of the [`BATCHER`], configured through the
+/// `CONFIG_SENSORS_BATCHING_MAX_SUBSCRIBERS` environment variable.
+pub const MAX_SUBSCRIBERS: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SENSORS_BATCHING_MAX_SUBSCRIBERS",
+    2,
+    "maximum number of subscribers to batches of sensor readings"
+);
+
+/// Number of batches queued for each subscriber, configured through the
+/// `CONFIG_SENSORS_BATCHING_QUEUE_SIZE` environment variable.
+pub const QUEUE_SIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SENSORS_BATCHING_QUEUE_SIZE",
+    1,
+    "number of batches of sensor readings queued for each subscriber"
+);
+
+/// The batcher of the system.
+pub static BATCHER: Batcher = Batcher::new();
+
+/// Receives the batches of the [`BATCHER`].
+pub type Subscriber =
+    pubsub::Subscriber<'static, CriticalSectionRawMutex, Batch, QUEUE_SIZE, MAX_SUBSCRIBERS, 0>;
+
+/// Configuration of the batching of a sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// Number of readings the FIFO of the device holds before its interrupt fires.
+    ///
+    /// It is limited to [`MAX_SIZE`] and to the size of the FIFO of the device.
+    pub size: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self { size: MAX_SIZE }
+    }
+}
+
+/// Readings of a sensor, drained from the FIFO of its device at once.
+#[derive(Clone)]
+pub struct Batch {
+    sensor: &'static dyn Sensor,
+    timestamp: Instant,
+    period: Duration,
+    overrun: bool,
+    readings: heapless::Vec<Samples, MAX_SIZE>,
+}
+
+impl Batch {
+    /// Returns the sensor the readings were obtained from.
+    #[must_use]
+    pub fn sensor(&self) -> &'static dyn Sensor {
+        self.sensor
+    }
+
+    /// Returns the readings, oldest first, along with the instant each was measured at.
+    ///
+    /// The instants are derived from the instant the interrupt of the device was handled at, and
+    /// from the data rate of the device.
+    pub fn readings(&self) -> impl Iterator<Item = (Instant, Samples)> + '_ {
+        let mut age = self.readings.len();
+        self.readings.iter().map(move |samples| {
+            age -= 1;
+            let age = self.period * u32::try_from(age).unwrap_or(u32::MAX);
+            let timestamp = self.timestamp.checked_sub(age).unwrap_or(Instant::MIN);
+            (timestamp, *samples)
+        })
+    }
+
+    /// Returns the number of readings.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.readings.len()
+    }
+
+    /// Returns whether there are no readings.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.readings.is_empty()
+    }
+
+    /// Returns whether the FIFO of the device overflowed since the previous batch, in which case
+    /// readings were lost before this batch.
+    #[must_use]
+    pub fn overrun(&self) -> bool {
+        self.overrun
+    }
+}
+
+/// Error returned by the [`Batcher`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum BatchingError {
+    /// [`MAX_SUBSCRIBERS`] subscribers are already subscribed.
+    TooManySubscribers,
+}
+
+impl core::fmt::Display for BatchingError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::TooManySubscribers => write!(f, "too many subscribers"),
+        }
+    }
+}
+
+impl core::error::Error for BatchingError {}
+
+/// Delivers the batches of the sensors run in batching mode.
+pub struct Batcher {
+    batches: PubSubChannel<CriticalSectionRawMutex, Batch, QUEUE_SIZE, MAX_SUBSCRIBERS, 0>,
+}
+
+impl Batcher {
+    const fn new() -> Self {
+        Self {
+            batches: PubSubChannel::new(),
+        }
+    }
+
+    /// Returns a new subscriber to the batches.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BatchingError::TooManySubscribers`] if [`MAX_SUBSCRIBERS`] subscribers are
+    /// already subscribed.
+    pub fn subscribe(&'static self) -> Result<Subscriber, BatchingError> {
+        self.batches
+            .subscriber()
+            .map_err(|_| BatchingError::TooManySubscribers)
+    }
+}
+
+/// A device whose readings are batched in its FIFO.
+pub(crate) trait FifoDevice {
+    /// Returns the interval between the readings of the FIFO.
+    fn reading_period(&self) -> Duration;
+
+    /// Empties the FIFO, starts filling it with readings, and makes the interrupt pin of the
+    /// device fire once the FIFO holds `size` readings, or as many as it can hold.
+    async fn start_batching(&mut self, size: usize) -> Result<(), Error>;
+
+    /// Drains the FIFO into `readings`, as long as they have room, and returns whether the FIFO
+    /// overflowed.
+    async fn drain_fifo(
+        &mut self,
+        readings: &mut heapless::Vec<Samples, MAX_SIZE>,
+    ) -> Result<bool, Error>;
+}
+
+/// Runs `device` in batching mode, until accessing it fails.
+///
+/// # Errors
+///
+/// Returns the error of `device` if accessing it failed, or [`Error::SensorAccess`] if waiting
+/// for `interrupt` failed.
+#[cfg_attr(not(feature = "lis3dh"), expect(dead_code))]
+pub(crate) async fn run<D: FifoDevice, W: Wait>(
+    sensor: &'static dyn Sensor,
+    device: &mut D,
+    mut interrupt: W,
+    config: Config,
+) -> Result<Infallible, Error> {
+    let publisher = BATCHER.batches.immediate_publisher();
+    let period = device.reading_period();
+    device
+        .start_batching(config.size.clamp(1, MAX_SIZE))
+        .await?;
+    loop {
+        // The interrupt stays high as long as the FIFO holds enough readings.
+        interrupt
+            .wait_for_high()
+            .await
+            .map_err(|_| Error::SensorAccess)?;
+        let timestamp = Instant::now();
+        let mut readings = heapless::Vec::new();
+        let overrun = device.drain_fifo(&mut readings).await?;
+        #[cfg(feature = "calibration")]
+        for samples in &mut readings {
+            if let Ok(calibrated) = crate::calibration::apply(sensor, Ok(*samples)) {
+                *samples = calibrated;
+            }
+        }
+        publisher.publish_immediate(Batch {
+            sensor,
+            timestamp,
+            period,
+            overrun,
+            readings,
+        });
+    }
+}
@@ -0,0 +1,536 @@
+//! Provides calibration of sensors, kept in persistent storage.
+//!
+//! A [`Calibration`] holds a [`Correction`] for each [`ReadingChannel`](crate::ReadingChannel) of
+//! a sensor, which is applied to the samples of its readings obtained through
+//! [`measure()`](crate::measure) and the sampler:
+//!
+//! ```ignore
+//! use ariel_os::sensors::calibration::{self, Calibration, Correction};
+//!
+//! // The sensor reads 0.5 °C too high, its humidity is right.
+//! let calibration = Calibration::new().with(0, Correction::Linear { scale: 1_000_000, offset: -50 });
+//! calibration::set_calibration(&TEMP_SENSOR, Some(calibration)).await?;
+//! ```
+//!
+//! Calibrations are stored under the index of their sensor in the [`REGISTRY`](crate::REGISTRY),
+//! and are loaded by the system at startup. With the `coap` feature, they can be read and set at
+//! `/sensors/<n>/calibration`; access to it is controlled by the security configuration of the
+//! CoAP server, like any other resource.
+//!
+//! # Configuration
+//!
+//! The maximum number of calibrated sensors is configured through the
+//! `CONFIG_SENSORS_CALIBRATION_MAX_SENSORS` environment variable (default: 8).
+
+use core::cell::RefCell;
+
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    signal::Signal,
+};
+
+use crate::{MAX_SAMPLES, ReadingResult, Sample, Sensor, registry::SENSOR_REFS};
+
+/// Maximum number of calibrated sensors, configured through the
+/// `CONFIG_SENSORS_CALIBRATION_MAX_SENSORS` environment variable.
+pub const MAX_SENSORS: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SENSORS_CALIBRATION_MAX_SENSORS",
+    8,
+    "maximum number of calibrated sensors"
+);
+
+/// Prefix of the storage keys under which calibrations are stored.
+const KEY_PREFIX: &str = "ariel-os-sensors.calibration.";
+
+/// Length of an encoded [`Correction`].
+const CORRECTION_LEN: usize = 9;
+
+/// Tag of an encoded [`Correction::Identity`].
+const TAG_IDENTITY: u8 = 0;
+/// Tag of an encoded [`Correction::Linear`].
+const TAG_LINEAR: u8 = 1;
+
+/// The scale of a [`Correction::Linear`] that keeps values unchanged.
+pub const UNIT_SCALE: i32 = 1_000_000;
+
+static CALIBRATIONS: Calibrations = Calibrations {
+    entries: Mutex::new(RefCell::new(heapless::Vec::new())),
+    changed: Signal::new(),
+};
+
+/// Correction applied to the samples of a reading channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Correction {
+    /// The samples are left unchanged.
+    Identity,
+    /// The values of the samples are multiplied by `scale`/[`UNIT_SCALE`], and then `offset` is
+    /// added to them; `offset` is scaled like values.
+    Linear {
+        /// Scale, in millionths.
+        scale: i32,
+        /// Offset.
+        offset: i32,
+    },
+}
+
+impl Correction {
+    fn apply(self, sample: Sample) -> Sample {
+        match self {
+            Self::Identity => sample,
+            Self::Linear { scale, offset } => {
+                let value = i64::from(sample.value()) * i64::from(scale) / i64::from(UNIT_SCALE)
+                    + i64::from(offset);
+                let value = value.clamp(i64::from(i32::MIN), i64::from(i32::MAX));
+                // Cannot fail, the value was clamped.
+                Sample::new(i32::try_from(value).unwrap_or_default(), sample.accuracy())
+            }
+        }
+    }
+
+    fn encode(self) -> [u8; CORRECTION_LEN] {
+        let (tag, scale, offset) = match self {
+            Self::Identity => (TAG_IDENTITY, 0, 0),
+            Self::Linear { scale, offset } => (TAG_LINEAR, scale, offset),
+        };
+        let [s0, s1, s2, s3] = scale.to_le_bytes();
+        let [o0, o1, o2, o3] = offset.to_le_bytes();
+        [tag, s0, s1, s2, s3, o0, o1, o2, o3]
+    }
+
+    fn decode(encoded: [u8; CORRECTION_LEN]) -> Option<Self> {
+        let [tag, s0, s1, s2, s3, o0, o1, o2, o3] = encoded;
+        match tag {
+            TAG_IDENTITY => Some(Self::Identity),
+            TAG_LINEAR => Some(Self::Linear {
+                scale: i32::from_le_bytes([s0, s1, s2, s3]),
+                offset: i32::from_le_bytes([o0, o1, o2, o3]),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// The calibration of a sensor: a [`Correction`] per reading channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Calibration {
+    corrections: [Correction; MAX_SAMPLES],
+}
+
+impl Calibration {
+    /// Creates a calibration that leaves all samples unchanged.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            corrections: [Correction::Identity; MAX_SAMPLES],
+        }
+    }
+
+    /// Sets the correction of the reading channel at `channel`.
+    ///
+    /// Corrections of channels beyond [`MAX_SAMPLES`] are ignored.
+    #[must_use]
+    pub fn with(mut self, channel: usize, correction: Correction) -> Self {
+        if let Some(slot) = self.corrections.get_mut(channel) {
+            *slot = correction;
+        }
+        self
+    }
+
+    /// Returns the correction of the reading channel at `channel`.
+    #[must_use]
+    pub fn correction(&self, channel: usize) -> Correction {
+        self.corrections
+            .get(channel)
+            .copied()
+            .unwrap_or(Correction::Identity)
+    }
+
+    /// Returns the reading with the corrections applied to its samples.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `reading`.
+    pub fn apply(&self, reading: ReadingResult) -> ReadingResult {
+        let mut samples = reading?;
+        for (sample, correction) in samples.iter_mut().zip(self.corrections) {
+            *sample = correction.apply(*sample);
+        }
+        Ok(samples)
+    }
+
+    fn encode(&self) -> [u8; CORRECTION_LEN * MAX_SAMPLES] {
+        let mut encoded = [0; CORRECTION_LEN * MAX_SAMPLES];
+        let (chunks, _) = encoded.as_chunks_mut::<CORRECTION_LEN>();
+        for (chunk, correction) in chunks.iter_mut().zip(self.corrections) {
+            *chunk = correction.encode();
+        }
+        encoded
+    }
+
+    fn decode(encoded: &[u8]) -> Option<Self> {
+        let (chunks, []) = encoded.as_chunks::<CORRECTION_LEN>() else {
+            return None;
+        };
+        if chunks.len() > MAX_SAMPLES {
+            return None;
+        }
+        let mut calibration = Self::new();
+        for (slot, chunk) in calibration.corrections.iter_mut().zip(chunks) {
+            *slot = Correction::decode(*chunk)?;
+        }
+        Some(calibration)
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Error returned when calibrating sensors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum CalibrationError {
+    /// The sensor is not registered in the [`REGISTRY`](crate::REGISTRY).
+    NotRegistered,
+    /// [`MAX_SENSORS`] other sensors are already calibrated.
+    TooManySensors,
+    /// Accessing the storage failed.
+    Storage,
+}
+
+impl core::fmt::Display for CalibrationError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::NotRegistered => write!(f, "sensor not registered"),
+            Self::TooManySensors => write!(f, "too many calibrated sensors"),
+            Self::Storage => write!(f, "storage error"),
+        }
+    }
+}
+
+impl core::error::Error for CalibrationError {}
+
+/// The calibration of a registered sensor.
+struct Entry {
+    index: usize,
+    calibration: Option<Calibration>,
+    /// Whether the calibration was changed without being stored yet.
+    dirty: bool,
+}
+
+struct Calibrations {
+    entries: Mutex<CriticalSectionRawMutex, RefCell<heapless::Vec<Entry, MAX_SENSORS>>>,
+    changed: Signal<CriticalSectionRawMutex, ()>,
+}
+
+impl Calibrations {
+    fn get(&self, index: usize) -> Option<Calibration> {
+        self.entries.lock(|entries| {
+            entries
+                .borrow()
+                .iter()
+                .find(|entry| entry.index == index)
+                .and_then(|entry| entry.calibration)
+        })
+    }
+
+    /// Sets the calibration of the sensor at `index`, which needs to be stored if `dirty`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CalibrationError::TooManySensors`] if there is no room for another calibration.
+    fn set(
+        &self,
+        index: usize,
+        calibration: Option<Calibration>,
+        dirty: bool,
+    ) -> Result<(), CalibrationError> {
+        self.entries.lock(|entries| {
+            let mut entries = entries.borrow_mut();
+            if let Some(entry) = entries.iter_mut().find(|entry| entry.index == index) {
+                entry.calibration = calibration;
+                entry.dirty |= dirty;
+                return Ok(());
+            }
+            if calibration.is_none() && !dirty {
+                return Ok(());
+            }
+            entries
+                .push(Entry {
+                    index,
+                    calibration,
+                    dirty,
+                })
+                .map_err(|_| CalibrationError::TooManySensors)
+        })
+    }
+
+    /// Sets the stored calibration of the sensor at `index`, unless it was changed already.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CalibrationError::TooManySensors`] if there is no room for another calibration.
+    fn load(&self, index: usize, calibration: Calibration) -> Result<(), CalibrationError> {
+        self.entries.lock(|entries| {
+            let mut entries = entries.borrow_mut();
+            if entries.iter().any(|entry| entry.index == index) {
+                return Ok(());
+            }
+            entries
+                .push(Entry {
+                    index,
+                    calibration: Some(calibration),
+                    dirty: false,
+                })
+                .map_err(|_| CalibrationError::TooManySensors)
+        })
+    }
+
+    /// Returns a calibration that was changed without being stored yet, and marks it as stored.
+    fn take_dirty(&self) -> Option<(usize, Option<Calibration>)> {
+        self.entries.lock(|entries| {
+            let mut entries = entries.borrow_mut();
+            let entry = entries.iter_mut().find(|entry| entry.dirty)?;
+            entry.dirty = false;
+            Some((entry.index, entry.calibration))
+        })
+    }
+}
+
+/// Returns the index of `sensor` in the registry.
+///
+/// # Errors
+///
+/// Returns [`CalibrationError::NotRegistered`] if `sensor` is not registered.
+fn index_of(sensor: &'static dyn Sensor) -> Result<usize, CalibrationError> {
+    SENSOR_REFS
+        .iter()
+        .position(|registered| core::ptr::addr_eq(*registered, sensor))
+        .ok_or(CalibrationError::NotRegistered)
+}
+
+/// Returns the storage key of the calibration of the sensor at `index` in the registry.
+fn storage_key(index: usize) -> heapless::String<40> {
+    let mut key = heapless::String::new();
+    // Cannot fail, the key is shorter than its capacity.
+    let _ = core::fmt::write(&mut key, format_args!("{KEY_PREFIX}{index}"));
+    key
+}
+
+/// Returns the calibration of `sensor`, if it has one.
+pub fn calibration(sensor: &'static dyn Sensor) -> Option<Calibration> {
+    CALIBRATIONS.get(index_of(sensor).ok()?)
+}
+
+/// Returns `reading` of `sensor`, calibrated if `sensor` has a calibration.
+///
+/// # Errors
+///
+/// Returns the error of `reading`.
+pub fn apply(sensor: &'static dyn Sensor, reading: ReadingResult) -> ReadingResult {
+    match calibration(sensor) {
+        Some(calibration) => calibration.apply(reading),
+        None => reading,
+    }
+}
+
+/// Sets the calibration of `sensor`, or removes it if `calibration` is `None`, and stores it.
+///
+/// # Errors
+///
+/// - Returns [`CalibrationError::NotRegistered`] if `sensor` is not registered.
+/// - Returns [`CalibrationError::TooManySensors`] if [`MAX_SENSORS`] other sensors are already
+///   calibrated.
+/// - Returns [`CalibrationError::Storage`] if storing the calibration failed; it is applied
+///   nevertheless.
+pub async fn set_calibration(
+    sensor: &'static dyn Sensor,
+    calibration: Option<Calibration>,
+) -> Result<(), CalibrationError> {
+    let index = index_of(sensor)?;
+    CALIBRATIONS.set(index, calibration, false)?;
+    store(index, calibration).await
+}
+
+/// Sets the calibration of the sensor at `index` in the registry, which is stored in the
+/// background.
+///
+/// # Errors
+///
+/// Returns the errors of [`set_calibration()`], except storage errors.
+#[cfg_attr(not(feature = "coap"), expect(dead_code))]
+pub(crate) fn set_calibration_deferred(
+    index: usize,
+    calibration: Option<Calibration>,
+) -> Result<(), CalibrationError> {
+    if index >= SENSOR_REFS.len() {
+        return Err(CalibrationError::NotRegistered);
+    }
+    CALIBRATIONS.set(index, calibration, true)?;
+    CALIBRATIONS.changed.signal(());
+    Ok(())
+}
+
+/// Returns the calibration of the sensor at `index` in the registry, if it has one.
+#[cfg_attr(not(feature = "coap"), expect(dead_code))]
+pub(crate) fn calibration_at(index: usize) -> Option<Calibration> {
+    CALIBRATIONS.get(index)
+}
+
+/// Stores the calibration of the sensor at `index` in the registry.
+///
+/// # Errors
+///
+/// Returns [`CalibrationError::Storage`] if storing the calibration failed.
+async fn store(index: usize, calibration: Option<Calibration>) -> Result<(), CalibrationError> {
+    let key = storage_key(index);
+    // Removing is not supported on all platforms, an empty calibration stands for none.
+    let encoded = calibration.map(|calibration| calibration.encode());
+    let data = encoded.as_ref().map_or(&[][..], |encoded| &encoded[..]);
+    ariel_os_storage::insert_blob(&key, data)
+        .await
+        .map_err(|_| CalibrationError::Storage)
+}
+
+/// Loads the stored calibrations, and stores the calibrations changed afterwards.
+///
+/// This is called by the system, and must not be called by applications.
+#[doc(hidden)]
+pub async fn run() -> ! {
+    let mut buffer = [0; CORRECTION_LEN * MAX_SAMPLES];
+    for index in 0..SENSOR_REFS.len() {
+        let key = storage_key(index);
+        if let Ok(Some(encoded)) = ariel_os_storage::get_blob(&key, &mut buffer).await
+            && !encoded.is_empty()
+            && let Some(calibration) = Calibration::decode(encoded)
+        {
+            // Calibrations beyond the capacity are skipped.
+            let _ = CALIBRATIONS.load(index, calibration);
+        }
+    }
+
+    loop {
+        CALIBRATIONS.changed.wait().await;
+        while let Some((index, calibration)) = CALIBRATIONS.take_dirty() {
+            // There is nobody to report the error to; the calibration is applied nevertheless.
+            let _ = store(index, calibration).await;
+        }
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use super::*;
+
+    use crate::{Accuracy, Error, Samples};
+
+    fn sample(value: i32) -> Sample {
+        Sample::new(value, Accuracy::NoError)
+    }
+
+    fn linear(scale: i32, offset: i32) -> Correction {
+        Correction::Linear { scale, offset }
+    }
+
+    #[test]
+    fn identity() {
+        assert_eq!(Correction::Identity.apply(sample(-1234)), sample(-1234));
+        assert_eq!(linear(UNIT_SCALE, 0).apply(sample(-1234)), sample(-1234));
+        assert_eq!(Calibration::new().correction(0), Correction::Identity);
+    }
+
+    #[test]
+    fn offset() {
+        assert_eq!(linear(UNIT_SCALE, -50).apply(sample(2150)), sample(2100));
+        assert_eq!(linear(UNIT_SCALE, 50).apply(sample(-10)), sample(40));
+    }
+
+    #[test]
+    fn scale() {
+        assert_eq!(linear(1_500_000, 0).apply(sample(1000)), sample(1500));
+        assert_eq!(linear(-UNIT_SCALE, 0).apply(sample(1000)), sample(-1000));
+        // Scaled first, then offset, rounding towards zero.
+        assert_eq!(linear(999_999, 10).apply(sample(3)), sample(12));
+        assert_eq!(linear(2 * UNIT_SCALE, -100).apply(sample(100)), sample(100));
+    }
+
+    #[test]
+    fn overflow_saturates() {
+        for (correction, value, expected) in [
+            (linear(2 * UNIT_SCALE, 0), i32::MAX, i32::MAX),
+            (linear(2 * UNIT_SCALE, 0), i32::MIN, i32::MIN),
+            (linear(UNIT_SCALE, 1), i32::MAX, i32::MAX),
+            (linear(UNIT_SCALE, -1), i32::MIN, i32::MIN),
+            (linear(i32::MIN, i32::MIN), i32::MAX, i32::MIN),
+            (linear(-UNIT_SCALE, 0), i32::MIN, i32::MAX),
+        ] {
+            assert_eq!(
+                correction.apply(sample(value)),
+                sample(expected),
+                "{correction:?} of {value}"
+            );
+        }
+    }
+
+    #[test]
+    fn apply_to_reading() {
+        let calibration = Calibration::new()
+            .with(1, linear(UNIT_SCALE, 5))
+            .with(MAX_SAMPLES, linear(0, 0));
+        let accuracy = Accuracy::SymmetricalError {
+            deviation: 10,
+            bias: 0,
+            scaling: -1,
+        };
+        let reading = Ok(Samples::from_array([
+            Sample::new(100, accuracy),
+            Sample::new(100, accuracy),
+        ]));
+
+        let samples = calibration.apply(reading).unwrap();
+        let samples: Vec<_> = samples.iter().collect();
+        assert_eq!(
+            samples,
+            [Sample::new(100, accuracy), Sample::new(105, accuracy)]
+        );
+        assert_eq!(
+            calibration.apply(Err(Error::SensorAccess)),
+            Err(Error::SensorAccess)
+        );
+    }
+
+    #[test]
+    fn encoding() {
+        let calibration = Calibration::new()
+            .with(0, linear(-7, i32::MIN))
+            .with(MAX_SAMPLES - 1, linear(i32::MAX, 3));
+        assert_eq!(
+            Calibration::decode(&calibration.encode()),
+            Some(calibration)
+        );
+
+        // Shorter encodings leave the remaining channels unchanged.
+        let encoded = linear(2, 1).encode();
+        assert_eq!(
+            Calibration::decode(&encoded),
+            Some(Calibration::new().with(0, linear(2, 1)))
+        );
+
+        // Unknown tags, partial and too many corrections are rejected.
+        let mut encoded = calibration.encode();
+        encoded[0] = 2;
+        assert_eq!(Calibration::decode(&encoded), None);
+        assert_eq!(Calibration::decode(&[TAG_IDENTITY]), None);
+        assert_eq!(
+            Calibration::decode(&[0; CORRECTION_LEN * (MAX_SAMPLES + 1)]),
+            None
+        );
+    }
+}
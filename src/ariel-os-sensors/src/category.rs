@@ -0,0 +1,30 @@
+/// Categories a [`Sensor`](crate::Sensor) can be part of.
+///
+/// A sensor can be part of several categories, eg. a combined temperature and humidity sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Category {
+    /// Accelerometer.
+    Accelerometer,
+    /// Electric current sensor.
+    Current,
+    /// Gas sensor, eg. for volatile organic compounds or CO₂.
+    Gas,
+    /// Gyroscope.
+    Gyroscope,
+    /// Ambient light sensor.
+    Light,
+    /// Location sensor, eg. a GNSS receiver.
+    Location,
+    /// Magnetometer.
+    Magnetometer,
+    /// Pressure sensor.
+    Pressure,
+    /// Relative humidity sensor.
+    RelativeHumidity,
+    /// Temperature sensor.
+    Temperature,
+    /// Voltage sensor, eg. an analog input.
+    Voltage,
+}
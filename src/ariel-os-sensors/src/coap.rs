@@ -0,0 +1,554 @@
+//! Serves the sensors of the [`REGISTRY`](crate::REGISTRY) as CoAP resources, in the SenML CBOR
+//! format ([RFC 8428](https://www.rfc-editor.org/rfc/rfc8428)).
+//!
+//! Mounted below `/sensors`, [`SensorsResource`] serves the latest readings of all sensors at
+//! `/sensors`, and the ones of the `n`-th sensor of the registry at `/sensors/<n>`. Each sample
+//! of a reading is a SenML record, eg. for a temperature and humidity sensor:
+//!
+//! ```text
+//! [
+//!   {-2: "0/", -3: -12.0, 0: "temperature", 1: "Cel", 2: 21.5, 7: 60.0},
+//!   {0: "humidity", 1: "%RH", 2: 40.25, 7: 60.0}
+//! ]
+//! ```
+//!
+//! The base time is relative to the time of the response, and the update time `ut` is the period
+//! the sensor is sampled at. Units that have no SenML equivalent are omitted.
+//! The resources are listed in `/.well-known/core`, with the label of their sensor as title:
+//!
+//! ```ignore
+//! let handler = new_dispatcher().below(&["sensors"], SensorsResource::new());
+//! ```
+//!
+//! Readings are the ones obtained by the [`SAMPLER`]. Observing the resources is not supported
+//! by the CoAP handlers yet; clients can poll them at their update time instead.
+//!
+//! With the `calibration` feature, the [`Calibration`](crate::calibration::Calibration) of the
+//! `n`-th sensor can be read with GET and set with PUT at `/sensors/<n>/calibration`, as a CBOR
+//! array holding, for each reading channel, either `null` or the `[scale, offset]` of a linear
+//! correction; `null` instead of the array removes the calibration. Which clients may set it is
+//! up to the security configuration of the CoAP server.
+//!
+//! # Configuration
+//!
+//! Sensors that have no [`Schedule`] when the resource is created are sampled every
+//! `CONFIG_SENSORS_COAP_PERIOD_SECS` seconds (default: 60).
+
+use coap_handler::{Attribute, Record, Reporting};
+use coap_message::{
+    Code as _, MinimalWritableMessage, MutableWritableMessage, OptionNumber as _, ReadableMessage,
+};
+use coap_message_utils::{Error as CoAPError, OptionsExt as _};
+use embassy_time::{Duration, Instant};
+use minicbor::{
+    Encoder,
+    encode::{
+        Error as EncodeError,
+        write::{Cursor, EndOfSlice},
+    },
+};
+
+use crate::{
+    Label, MeasurementUnit, Sensor,
+    registry::SENSOR_REFS,
+    sampling::{SAMPLER, SampledReading, Schedule},
+};
+
+/// CoAP Content-Format of `application/cbor`.
+#[cfg(feature = "calibration")]
+const CONTENT_FORMAT_CBOR: u16 = 60;
+
+/// CoAP Content-Format of `application/senml+cbor`.
+const CONTENT_FORMAT_SENML_CBOR: u16 = 112;
+
+/// Maximum length of a response.
+const MAX_LEN: usize = 1024;
+
+/// Path segment of the calibration of a sensor.
+#[cfg(feature = "calibration")]
+const CALIBRATION: &str = "calibration";
+
+const DEFAULT_PERIOD_SECS: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SENSORS_COAP_PERIOD_SECS",
+    60,
+    "sampling period of sensors served over CoAP, in seconds"
+);
+
+// SenML labels, see RFC 8428, Section 6.
+const BASE_NAME: i8 = -2;
+const BASE_TIME: i8 = -3;
+const NAME: i8 = 0;
+const UNIT: i8 = 1;
+const VALUE: i8 = 2;
+const UPDATE_TIME: i8 = 7;
+
+/// A CoAP resource that serves the readings of the registered sensors.
+#[derive(Debug)]
+pub struct SensorsResource {
+    _private: (),
+}
+
+impl SensorsResource {
+    /// Creates the resource, and gives the registered sensors that have no [`Schedule`] the
+    /// default one.
+    #[must_use]
+    pub fn new() -> Self {
+        let period = Duration::from_secs(DEFAULT_PERIOD_SECS as u64);
+        for sensor in SENSOR_REFS.iter().copied() {
+            if SAMPLER.schedule(sensor).is_none() {
+                // Sensors that cannot be sampled are served without readings.
+                let _ = SAMPLER.set_schedule(sensor, Some(Schedule::new(period)));
+            }
+        }
+        Self { _private: () }
+    }
+}
+
+impl Default for SensorsResource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The sensors a request is for.
+#[derive(Clone, Copy)]
+pub enum Target {
+    /// All registered sensors.
+    All,
+    /// The sensor of the given index in the registry.
+    Sensor(usize),
+    /// The calibration of the sensor of the given index in the registry.
+    #[cfg(feature = "calibration")]
+    Calibration(usize),
+    /// The calibration of the sensor of the given index in the registry, which was set.
+    #[cfg(feature = "calibration")]
+    CalibrationChanged,
+}
+
+impl coap_handler::Handler for SensorsResource {
+    type RequestData = Target;
+    type ExtractRequestError = CoAPError;
+    type BuildResponseError<M: MinimalWritableMessage> = CoAPError;
+
+    fn extract_request_data<M: ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, Self::ExtractRequestError> {
+        let mut segments = 0;
+        let mut index = None;
+        #[cfg_attr(not(feature = "calibration"), expect(unused_mut, unused_variables))]
+        let mut sub_resource = false;
+        request
+            .options()
+            .take_uri_path(|segment| {
+                segments += 1;
+                match segments {
+                    1 => index = segment.parse::<usize>().ok(),
+                    #[cfg(feature = "calibration")]
+                    2 => sub_resource = segment == CALIBRATION,
+                    _ => {}
+                }
+            })
+            .ignore_elective_others()?;
+        let target = match (segments, index) {
+            (0, _) => Target::All,
+            (1, Some(index)) if index < SENSOR_REFS.len() => Target::Sensor(index),
+            #[cfg(feature = "calibration")]
+            (2, Some(index)) if sub_resource && index < SENSOR_REFS.len() => {
+                Target::Calibration(index)
+            }
+            _ => return Err(CoAPError::not_found()),
+        };
+        match (request.code().into(), target) {
+            (coap_numbers::code::GET, _) => Ok(target),
+            #[cfg(feature = "calibration")]
+            (coap_numbers::code::PUT, Target::Calibration(index)) => {
+                let calibration = decode_calibration(request.payload())?;
+                crate::calibration::set_calibration_deferred(index, calibration)
+                    .map_err(|_| CoAPError::service_unavailable())?;
+                Ok(Target::CalibrationChanged)
+            }
+            _ => Err(CoAPError::method_not_allowed()),
+        }
+    }
+
+    fn estimate_length(&mut self, _target: &Self::RequestData) -> usize {
+        // Content-Format option and payload marker
+        MAX_LEN + 4
+    }
+
+    fn build_response<M: MutableWritableMessage>(
+        &mut self,
+        response: &mut M,
+        target: Self::RequestData,
+    ) -> Result<(), Self::BuildResponseError<M>> {
+        #[cfg(feature = "calibration")]
+        match target {
+            Target::Calibration(index) => return build_calibration(response, index),
+            Target::CalibrationChanged => {
+                response.set_code(
+                    M::Code::new(coap_numbers::code::CHANGED)
+                        .map_err(CoAPError::from_unionerror)?,
+                );
+                return Ok(());
+            }
+            Target::All | Target::Sensor(_) => {}
+        }
+        if let Target::Sensor(index) = target
+            && SENSOR_REFS
+                .get(index)
+                .and_then(|sensor| SAMPLER.latest(*sensor))
+                .is_none_or(|sampled| sampled.reading().is_err())
+        {
+            // The sensor was not measured yet, or its measurement failed.
+            return Err(CoAPError::service_unavailable());
+        }
+        let readings = SENSOR_REFS
+            .iter()
+            .enumerate()
+            .filter(move |(index, _)| match target {
+                Target::All => true,
+                Target::Sensor(target) => *index == target,
+                #[cfg(feature = "calibration")]
+                Target::Calibration(_) | Target::CalibrationChanged => false,
+            })
+            .filter_map(|(index, sensor)| Some((index, SAMPLER.latest(*sensor)?)));
+        build_senml(response, readings)
+    }
+}
+
+/// Sets `response` to the SenML pack of `readings`, with their index in the registry.
+///
+/// # Errors
+///
+/// Returns an error if the response cannot be built.
+fn build_senml<M: MutableWritableMessage>(
+    response: &mut M,
+    readings: impl Iterator<Item = (usize, SampledReading)>,
+) -> Result<(), CoAPError> {
+    response
+        .set_code(M::Code::new(coap_numbers::code::CONTENT).map_err(CoAPError::from_unionerror)?);
+    response
+        .add_option_uint(
+            M::OptionNumber::new(coap_numbers::option::CONTENT_FORMAT)
+                .map_err(CoAPError::from_unionerror)?,
+            CONTENT_FORMAT_SENML_CBOR,
+        )
+        .map_err(CoAPError::from_unionerror)?;
+    let available = response.available_space().saturating_sub(1).min(MAX_LEN);
+    let payload = response
+        .payload_mut_with_len(available)
+        .map_err(CoAPError::from_unionerror)?;
+    let len = encode(payload, readings, Instant::now())
+        .map_err(|_| CoAPError::internal_server_error())?;
+    response.truncate(len).map_err(CoAPError::from_unionerror)?;
+    Ok(())
+}
+
+/// Sets `response` to the calibration of the sensor at `index` in the registry.
+///
+/// # Errors
+///
+/// Returns an error if the response cannot be built.
+#[cfg(feature = "calibration")]
+fn build_calibration<M: MutableWritableMessage>(
+    response: &mut M,
+    index: usize,
+) -> Result<(), CoAPError> {
+    response
+        .set_code(M::Code::new(coap_numbers::code::CONTENT).map_err(CoAPError::from_unionerror)?);
+    response
+        .add_option_uint(
+            M::OptionNumber::new(coap_numbers::option::CONTENT_FORMAT)
+                .map_err(CoAPError::from_unionerror)?,
+            CONTENT_FORMAT_CBOR,
+        )
+        .map_err(CoAPError::from_unionerror)?;
+    let channels = SENSOR_REFS
+        .get(index)
+        .map_or(0, |sensor| sensor.reading_channels().len());
+    let calibration = crate::calibration::calibration_at(index);
+    let available = response.available_space().saturating_sub(1).min(MAX_LEN);
+    let payload = response
+        .payload_mut_with_len(available)
+        .map_err(CoAPError::from_unionerror)?;
+    let len = encode_calibration(payload, calibration, channels)
+        .map_err(|_| CoAPError::internal_server_error())?;
+    response.truncate(len).map_err(CoAPError::from_unionerror)?;
+    Ok(())
+}
+
+/// Encodes `calibration` of a sensor with `channels` reading channels into `buffer`, and returns
+/// the encoded length.
+///
+/// # Errors
+///
+/// Returns an error if the encoded calibration does not fit into `buffer`.
+#[cfg(feature = "calibration")]
+fn encode_calibration(
+    buffer: &mut [u8],
+    calibration: Option<crate::calibration::Calibration>,
+    channels: usize,
+) -> Result<usize, EncodeError<EndOfSlice>> {
+    use crate::calibration::Correction;
+
+    let mut encoder = Encoder::new(Cursor::new(buffer));
+    if let Some(calibration) = calibration {
+        encoder.array(channels as u64)?;
+        for channel in 0..channels {
+            match calibration.correction(channel) {
+                Correction::Linear { scale, offset } => {
+                    encoder.array(2)?.i32(scale)?.i32(offset)?;
+                }
+                _ => {
+                    encoder.null()?;
+                }
+            }
+        }
+    } else {
+        encoder.null()?;
+    }
+    Ok(encoder.into_writer().position())
+}
+
+/// Decodes a calibration set by a client, which is `None` to remove the calibration.
+///
+/// # Errors
+///
+/// Returns a Bad Request error if `payload` is not a valid calibration.
+#[cfg(feature = "calibration")]
+fn decode_calibration(
+    payload: &[u8],
+) -> Result<Option<crate::calibration::Calibration>, CoAPError> {
+    use crate::calibration::{Calibration, Correction};
+    use minicbor::data::Type;
+
+    let invalid = |_| CoAPError::bad_request();
+    let mut decoder = minicbor::Decoder::new(payload);
+    let calibration = if decoder.datatype().map_err(invalid)? == Type::Null {
+        decoder.null().map_err(invalid)?;
+        None
+    } else {
+        let channels = decoder
+            .array()
+            .map_err(invalid)?
+            .ok_or_else(CoAPError::bad_request)?;
+        let mut calibration = Calibration::new();
+        for channel in 0..usize::try_from(channels).map_err(|_| CoAPError::bad_request())? {
+            if channel >= crate::MAX_SAMPLES {
+                return Err(CoAPError::bad_request());
+            }
+            if decoder.datatype().map_err(invalid)? == Type::Null {
+                decoder.null().map_err(invalid)?;
+                continue;
+            }
+            if decoder.array().map_err(invalid)? != Some(2) {
+                return Err(CoAPError::bad_request());
+            }
+            let scale = decoder.i32().map_err(invalid)?;
+            let offset = decoder.i32().map_err(invalid)?;
+            calibration = calibration.with(channel, Correction::Linear { scale, offset });
+        }
+        Some(calibration)
+    };
+    if decoder.position() != payload.len() {
+        return Err(CoAPError::bad_request());
+    }
+    Ok(calibration)
+}
+
+/// Encodes the SenML pack of `readings` into `buffer` at `now`, and returns the encoded length.
+///
+/// Readings that failed are omitted.
+///
+/// # Errors
+///
+/// Returns an error if the encoded pack does not fit into `buffer`.
+fn encode(
+    buffer: &mut [u8],
+    readings: impl Iterator<Item = (usize, SampledReading)>,
+    now: Instant,
+) -> Result<usize, EncodeError<EndOfSlice>> {
+    let mut encoder = Encoder::new(Cursor::new(buffer));
+    // The latest readings may change while encoding, so their number is not known in advance.
+    encoder.begin_array()?;
+    for (index, sampled) in readings {
+        let Ok(samples) = sampled.reading() else {
+            continue;
+        };
+        let sensor = sampled.sensor();
+        let update_time = SAMPLER
+            .schedule(sensor)
+            .map(|schedule| seconds(schedule.period()));
+        let age = now
+            .checked_duration_since(sampled.timestamp())
+            .unwrap_or_default();
+        let channels = sensor.reading_channels().iter();
+        for (position, (sample, channel)) in samples.iter().zip(channels).enumerate() {
+            let unit = senml_unit(channel.unit(), channel.label());
+            let name = senml_name(channel.label());
+            let first = position == 0;
+            let entries = 1
+                + 2 * u64::from(first)
+                + u64::from(name.is_some())
+                + u64::from(unit.is_some())
+                + u64::from(update_time.is_some());
+            encoder.map(entries)?;
+            if first {
+                let mut base_name = heapless::String::<8>::new();
+                // Cannot fail, the index has less digits.
+                let _ = core::fmt::write(&mut base_name, format_args!("{index}/"));
+                encoder.i8(BASE_NAME)?.str(&base_name)?;
+                encoder.i8(BASE_TIME)?.f64(-seconds(age))?;
+            }
+            if let Some(name) = name {
+                encoder.i8(NAME)?.str(name)?;
+            }
+            if let Some(unit) = unit {
+                encoder.i8(UNIT)?.str(unit)?;
+            }
+            encoder
+                .i8(VALUE)?
+                .f64(scaled(sample.value(), channel.scaling()))?;
+            if let Some(update_time) = update_time {
+                encoder.i8(UPDATE_TIME)?.f64(update_time)?;
+            }
+        }
+    }
+    encoder.end()?;
+    Ok(encoder.into_writer().position())
+}
+
+#[expect(
+    clippy::cast_precision_loss,
+    reason = "durations of sensor readings are far below 2^52 µs"
+)]
+fn seconds(duration: Duration) -> f64 {
+    duration.as_micros() as f64 / 1_000_000.
+}
+
+/// Returns `value`·10^`scaling`.
+fn scaled(value: i32, scaling: i8) -> f64 {
+    let mut scaled = f64::from(value);
+    for _ in 0..scaling.unsigned_abs() {
+        if scaling < 0 {
+            scaled /= 10.;
+        } else {
+            scaled *= 10.;
+        }
+    }
+    scaled
+}
+
+/// Returns the SenML name of the samples of a channel labeled `label`.
+fn senml_name(label: Label) -> Option<&'static str> {
+    match label {
+        Label::Main => None,
+        Label::Altitude => Some("altitude"),
+        Label::Co2 => Some("co2"),
+        Label::Current => Some("current"),
+        Label::Humidity => Some("humidity"),
+        Label::Latitude => Some("latitude"),
+        Label::Light => Some("light"),
+        Label::Longitude => Some("longitude"),
+        Label::Pressure => Some("pressure"),
+        Label::Temperature => Some("temperature"),
+        Label::Voltage => Some("voltage"),
+        Label::X => Some("x"),
+        Label::Y => Some("y"),
+        Label::Z => Some("z"),
+    }
+}
+
+/// Returns the SenML unit of `unit`, if it has one.
+fn senml_unit(unit: MeasurementUnit, label: Label) -> Option<&'static str> {
+    match unit {
+        MeasurementUnit::Ampere => Some("A"),
+        MeasurementUnit::Celsius => Some("Cel"),
+        MeasurementUnit::Degree if label == Label::Latitude => Some("lat"),
+        MeasurementUnit::Degree if label == Label::Longitude => Some("lon"),
+        MeasurementUnit::Degree | MeasurementUnit::DegreePerSecond => None,
+        MeasurementUnit::Lux => Some("lx"),
+        MeasurementUnit::Meter => Some("m"),
+        MeasurementUnit::MeterPerSecondSquared => Some("m/s2"),
+        MeasurementUnit::Pascal => Some("Pa"),
+        MeasurementUnit::PartsPerMillion => Some("ppm"),
+        MeasurementUnit::Percent if label == Label::Humidity => Some("%RH"),
+        MeasurementUnit::Percent => Some("%"),
+        MeasurementUnit::Tesla => Some("T"),
+        MeasurementUnit::Volt => Some("V"),
+    }
+}
+
+/// A link to a resource of [`SensorsResource`] in `/.well-known/core`.
+pub struct SensorRecord {
+    path: Option<heapless::String<8>>,
+    title: Option<&'static str>,
+}
+
+impl SensorRecord {
+    /// Creates the record of `sensor`, at `index` in the registry.
+    fn new((index, sensor): (usize, &'static &'static dyn Sensor)) -> Self {
+        let mut path = heapless::String::new();
+        // Cannot fail, the index has less digits.
+        let _ = core::fmt::write(&mut path, format_args!("{index}"));
+        Self {
+            path: Some(path),
+            title: sensor.label(),
+        }
+    }
+}
+
+impl Record for SensorRecord {
+    type PathElement = heapless::String<8>;
+    type PathElements = core::option::IntoIter<heapless::String<8>>;
+    type Attributes = core::iter::Flatten<core::array::IntoIter<Option<Attribute>, 3>>;
+
+    fn path(&self) -> Self::PathElements {
+        self.path.clone().into_iter()
+    }
+
+    fn rel(&self) -> Option<&str> {
+        None
+    }
+
+    fn attributes(&self) -> Self::Attributes {
+        // The batch interface for all sensors, the sensor interface for single ones.
+        let interface = if self.path.is_some() {
+            "core.s"
+        } else {
+            "core.b"
+        };
+        [
+            Some(Attribute::Interface(interface)),
+            Some(Attribute::Ct(CONTENT_FORMAT_SENML_CBOR)),
+            self.title.map(Attribute::Title),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+impl Reporting for SensorsResource {
+    type Record<'res> = SensorRecord;
+    type Reporter<'res> = core::iter::Chain<
+        core::iter::Once<SensorRecord>,
+        core::iter::Map<
+            core::iter::Enumerate<core::slice::Iter<'static, &'static dyn Sensor>>,
+            fn((usize, &'static &'static dyn Sensor)) -> SensorRecord,
+        >,
+    >;
+
+    fn report(&self) -> Self::Reporter<'_> {
+        let all = SensorRecord {
+            path: None,
+            title: None,
+        };
+        core::iter::once(all).chain(
+            SENSOR_REFS
+                .iter()
+                .enumerate()
+                .map(SensorRecord::new as fn(_) -> _),
+        )
+    }
+}
@@ -0,0 +1,218 @@
+//! Driver for analog sensors read through an ADC channel of the MCU, eg. potentiometers, NTC
+//! thermistors or current shunts.
+//!
+//! Readings consist of a single sample, obtained by averaging conversions of the ADC channel and
+//! scaling their raw value with a polynomial, and described by the [`ReadingChannel`] the sensor
+//! is created with. The ADC channel is given to the driver through the [`AdcChannel`] trait,
+//! which is implemented by the application for the ADC of its MCU.
+//!
+//! For instance, a 0.1 Ω current shunt followed by an amplifier with a gain of 50, read by a
+//! 12-bit ADC with a reference of 3.3 V:
+//!
+//! ```ignore
+//! static SHUNT: Analog = Analog::new(
+//!     Some("motor"),
+//!     &[Category::Current],
+//!     &ReadingChannel::new(Label::Current, -3, MeasurementUnit::Ampere),
+//! );
+//! ariel_os::sensors::register_sensor!(SHUNT);
+//!
+//! let mut config = analog::Config::default();
+//! // Current in mA: raw · 3300 mV / 4095 / 50 / 0.1 Ω.
+//! config.polynomial = &[0., 0.1612];
+//! config.oversampling = 8;
+//! let _ = SHUNT.run(adc_channel, config).await;
+//! ```
+//!
+//! Non-linear sensors, eg. NTC thermistors, are approximated by higher-degree polynomials over
+//! their range.
+//!
+//! The sensor only supports [`Mode::OneShot`].
+
+use core::convert::Infallible;
+
+use embassy_time::{Duration, Timer};
+
+use super::{Common, Device};
+use crate::{
+    Accuracy, Category, Error, Mode, ReadingChannel, ReadingResult, ReadingWaiter, Sample, Samples,
+    Sensor, State,
+};
+
+/// An ADC channel of the MCU, which an [`Analog`] sensor reads.
+pub trait AdcChannel {
+    /// Converts the input of the channel, and returns its raw value.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SensorAccess`] if the conversion failed.
+    fn read(&mut self) -> impl Future<Output = Result<i32, Error>>;
+}
+
+/// Configuration of an analog sensor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// Coefficients of the polynomial scaling the raw value into the value of the sample, lowest
+    /// degree first.
+    ///
+    /// The value is expressed with the scaling of the [`ReadingChannel`] of the sensor: with a
+    /// scaling of `-3` and a raw value in mV, `&[0., 1.]` gives a value in V.
+    pub polynomial: &'static [f32],
+    /// Number of conversions averaged into each reading, at least 1.
+    pub oversampling: u16,
+    /// Duration to wait before the first conversion of each reading, eg. for the input to
+    /// settle once the circuit of the sensor was powered by the application.
+    pub settling_time: Duration,
+    /// Accuracy of the samples.
+    pub accuracy: Accuracy,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            polynomial: &[0., 1.],
+            oversampling: 1,
+            settling_time: Duration::from_ticks(0),
+            accuracy: Accuracy::Unknown,
+        }
+    }
+}
+
+/// An analog sensor.
+pub struct Analog {
+    common: Common,
+    categories: &'static [Category],
+    channel: &'static ReadingChannel,
+}
+
+impl Analog {
+    /// Creates an analog sensor, labeled `label`, part of `categories`, whose readings are
+    /// described by `channel`.
+    #[must_use]
+    pub const fn new(
+        label: Option<&'static str>,
+        categories: &'static [Category],
+        channel: &'static ReadingChannel,
+    ) -> Self {
+        Self {
+            common: Common::new(label, &[Mode::OneShot]),
+            categories,
+            channel,
+        }
+    }
+
+    /// Runs the driver, reading `adc_channel`.
+    ///
+    /// # Errors
+    ///
+    /// Does not fail: the errors of the conversions are returned as readings.
+    pub async fn run<A: AdcChannel>(
+        &self,
+        adc_channel: A,
+        config: Config,
+    ) -> Result<Infallible, Error> {
+        let mut device = AnalogDevice {
+            adc_channel,
+            config,
+        };
+        self.common.run(&mut device).await
+    }
+}
+
+impl Sensor for Analog {
+    fn trigger_measurement(&self) -> Result<(), Error> {
+        self.common.trigger_measurement()
+    }
+
+    fn wait_for_reading(&'static self) -> ReadingWaiter {
+        self.common.wait_for_reading()
+    }
+
+    fn set_mode(&self, mode: Mode) -> Result<State, Error> {
+        self.common.set_mode(mode)
+    }
+
+    fn state(&self) -> State {
+        self.common.state()
+    }
+
+    fn categories(&self) -> &'static [Category] {
+        self.categories
+    }
+
+    fn reading_channels(&self) -> &'static [ReadingChannel] {
+        core::slice::from_ref(self.channel)
+    }
+
+    fn label(&self) -> Option<&'static str> {
+        self.common.label()
+    }
+
+    fn display_name(&self) -> Option<&'static str> {
+        Some("analog sensor")
+    }
+
+    fn part_number(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+struct AnalogDevice<A> {
+    adc_channel: A,
+    config: Config,
+}
+
+impl<A: AdcChannel> AnalogDevice<A> {
+    /// Returns the average of the raw values of the configured number of conversions.
+    async fn read_average(&mut self) -> Result<f32, Error> {
+        let oversampling = self.config.oversampling.max(1);
+        let mut sum = 0;
+        for _ in 0..oversampling {
+            sum += i64::from(self.adc_channel.read().await?);
+        }
+        let average = sum / i64::from(oversampling);
+        let average = i32::try_from(average).map_err(|_| Error::SensorAccess)?;
+        #[expect(clippy::cast_precision_loss, reason = "raw values fit into 24 bits")]
+        let average = average as f32;
+        Ok(average)
+    }
+}
+
+impl<A: AdcChannel> Device for AnalogDevice<A> {
+    async fn apply_mode(&mut self, _mode: Mode) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn measure(&mut self) -> ReadingResult {
+        Timer::after(self.config.settling_time).await;
+        let raw = self.read_average().await?;
+        let value = scale(self.config.polynomial, raw).ok_or(Error::SensorAccess)?;
+        Ok(Samples::from_array([Sample::new(
+            value,
+            self.config.accuracy,
+        )]))
+    }
+}
+
+/// Evaluates `polynomial` at `raw`, and rounds the result; returns `None` if it does not fit into
+/// an `i32`.
+fn scale(polynomial: &[f32], raw: f32) -> Option<i32> {
+    let value = polynomial
+        .iter()
+        .rev()
+        .fold(0., |value, coefficient| value * raw + coefficient);
+    let rounded = if value < 0. { value - 0.5 } else { value + 0.5 };
+    // `i32::MAX` rounds up to 2³¹, which the range excludes.
+    #[expect(clippy::cast_precision_loss, reason = "the bounds are powers of two")]
+    let range = i32::MIN as f32..i32::MAX as f32;
+    if !range.contains(&rounded) {
+        return None;
+    }
+    #[expect(
+        clippy::cast_possible_truncation,
+        reason = "the value was range-checked"
+    )]
+    Some(rounded as i32)
+}
@@ -0,0 +1,481 @@
+//! Driver for the Bosch BME280 temperature, humidity and pressure sensor, connected through I2C
+//! or SPI.
+//!
+//! Readings consist of the temperature, in hundredths of degree Celsius, followed by the relative
+//! humidity, in hundredths of percent, and the pressure, in pascals.
+
+use core::convert::Infallible;
+
+use embassy_time::Timer;
+use embedded_hal_async::{i2c::I2c, spi::SpiDevice};
+
+use super::{
+    Common, Device,
+    registers::{I2cRegisters, Registers, SpiRegisters},
+};
+use crate::{
+    Accuracy, Category, Error, Label, MeasurementUnit, Mode, ReadingChannel, ReadingResult,
+    ReadingWaiter, Sample, Samples, Sensor, State,
+};
+
+const REG_CALIB_00: u8 = 0x88;
+const REG_CHIP_ID: u8 = 0xd0;
+const REG_RESET: u8 = 0xe0;
+const REG_CALIB_26: u8 = 0xe1;
+const REG_CTRL_HUM: u8 = 0xf2;
+const REG_STATUS: u8 = 0xf3;
+const REG_CTRL_MEAS: u8 = 0xf4;
+const REG_PRESS_MSB: u8 = 0xf7;
+
+const CHIP_ID: u8 = 0x60;
+const RESET: u8 = 0xb6;
+
+/// Oversampling ×1 of humidity, in `ctrl_hum`.
+const CTRL_HUM_OSRS_1: u8 = 0b001;
+/// Oversampling ×1 of temperature and pressure in forced mode, in `ctrl_meas`.
+const CTRL_MEAS_FORCED_OSRS_1: u8 = 0b001 << 5 | 0b001 << 2 | 0b01;
+/// Conversion running, in `status`.
+const STATUS_MEASURING: u8 = 1 << 3;
+
+/// Maximum duration of a measurement with oversampling ×1, in milliseconds.
+const MEASUREMENT_MS: u64 = 10;
+
+const CHANNELS: [ReadingChannel; 3] = [
+    ReadingChannel::new(Label::Temperature, -2, MeasurementUnit::Celsius),
+    ReadingChannel::new(Label::Humidity, -2, MeasurementUnit::Percent),
+    ReadingChannel::new(Label::Pressure, 0, MeasurementUnit::Pascal),
+];
+
+/// Configuration of a BME280 sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// I2C address of the device, which depends on its `SDO` pin; not used with SPI.
+    pub address: u8,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self { address: 0x76 }
+    }
+}
+
+/// A BME280 sensor.
+pub struct Bme280 {
+    common: Common,
+}
+
+impl Bme280 {
+    /// Creates a BME280 sensor, labeled `label`.
+    #[must_use]
+    pub const fn new(label: Option<&'static str>) -> Self {
+        Self {
+            common: Common::new(label, &[Mode::OneShot]),
+        }
+    }
+
+    /// Initializes the device connected through `i2c`, and runs the driver.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SensorAccess`] if the device cannot be initialized.
+    pub async fn run_i2c<I: I2c>(&self, i2c: I, config: Config) -> Result<Infallible, Error> {
+        let registers = I2cRegisters {
+            i2c,
+            address: config.address,
+            auto_increment: 0,
+        };
+        self.run(registers).await
+    }
+
+    /// Initializes the device connected through `spi`, and runs the driver.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SensorAccess`] if the device cannot be initialized.
+    pub async fn run_spi<S: SpiDevice>(&self, spi: S) -> Result<Infallible, Error> {
+        let registers = SpiRegisters {
+            spi,
+            auto_increment: 0,
+            dummy_byte: false,
+        };
+        self.run(registers).await
+    }
+
+    async fn run<R: Registers>(&self, mut registers: R) -> Result<Infallible, Error> {
+        let calibration = init(&mut registers).await?;
+        let mut device = Bme280Device {
+            registers,
+            calibration,
+        };
+        self.common.run(&mut device).await
+    }
+}
+
+impl Sensor for Bme280 {
+    fn trigger_measurement(&self) -> Result<(), Error> {
+        self.common.trigger_measurement()
+    }
+
+    fn wait_for_reading(&'static self) -> ReadingWaiter {
+        self.common.wait_for_reading()
+    }
+
+    fn set_mode(&self, mode: Mode) -> Result<State, Error> {
+        self.common.set_mode(mode)
+    }
+
+    fn state(&self) -> State {
+        self.common.state()
+    }
+
+    fn categories(&self) -> &'static [Category] {
+        &[
+            Category::Pressure,
+            Category::RelativeHumidity,
+            Category::Temperature,
+        ]
+    }
+
+    fn reading_channels(&self) -> &'static [ReadingChannel] {
+        &CHANNELS
+    }
+
+    fn label(&self) -> Option<&'static str> {
+        self.common.label()
+    }
+
+    fn display_name(&self) -> Option<&'static str> {
+        Some("temperature, humidity and pressure sensor")
+    }
+
+    fn part_number(&self) -> Option<&'static str> {
+        Some("BME280")
+    }
+}
+
+/// Resets the device, and returns its calibration.
+async fn init<R: Registers>(registers: &mut R) -> Result<Calibration, Error> {
+    if registers.read_u8(REG_CHIP_ID).await? != CHIP_ID {
+        return Err(Error::SensorAccess);
+    }
+    registers.write(REG_RESET, RESET).await?;
+    Timer::after_millis(2).await;
+    let mut calib_00 = [0; 26];
+    registers.read(REG_CALIB_00, &mut calib_00).await?;
+    let mut calib_26 = [0; 7];
+    registers.read(REG_CALIB_26, &mut calib_26).await?;
+    Ok(Calibration::parse(&calib_00, calib_26))
+}
+
+/// The calibration parameters of a device, named as in its datasheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Calibration {
+    t1: u16,
+    t2: i16,
+    t3: i16,
+    p1: u16,
+    p2: i16,
+    p3: i16,
+    p4: i16,
+    p5: i16,
+    p6: i16,
+    p7: i16,
+    p8: i16,
+    p9: i16,
+    h1: u8,
+    h2: i16,
+    h3: u8,
+    h4: i16,
+    h5: i16,
+    h6: i8,
+}
+
+impl Calibration {
+    /// Parses the calibration parameters read from `calib00`…`calib25` and
+    /// `calib26`…`calib32`.
+    fn parse(calib_00: &[u8; 26], calib_26: [u8; 7]) -> Self {
+        let (words, _) = calib_00.as_chunks::<2>();
+        let mut words = words.iter().map(|word| u16::from_le_bytes(*word));
+        let [t1, t2, t3, p1, p2, p3, p4, p5, p6, p7, p8, p9] =
+            core::array::from_fn(|_| words.next().unwrap_or_default());
+        let [.., h1] = *calib_00;
+        let [h2 @ .., h3, e4, e5, e6, h6] = calib_26;
+        Self {
+            t1,
+            t2: t2.cast_signed(),
+            t3: t3.cast_signed(),
+            p1,
+            p2: p2.cast_signed(),
+            p3: p3.cast_signed(),
+            p4: p4.cast_signed(),
+            p5: p5.cast_signed(),
+            p6: p6.cast_signed(),
+            p7: p7.cast_signed(),
+            p8: p8.cast_signed(),
+            p9: p9.cast_signed(),
+            h1,
+            h2: i16::from_le_bytes(h2),
+            h3,
+            // 12-bit signed values.
+            h4: i16::from(e4.cast_signed()) << 4 | i16::from(e5 & 0x0f),
+            h5: i16::from(e6.cast_signed()) << 4 | i16::from(e5 >> 4),
+            h6: h6.cast_signed(),
+        }
+    }
+
+    /// Returns the temperature, in hundredths of degree Celsius, and the `t_fine` value the
+    /// compensation of the other values depends on.
+    fn temperature(&self, adc_t: i32) -> (i32, i32) {
+        let (t1, t2, t3) = (i32::from(self.t1), i32::from(self.t2), i32::from(self.t3));
+        let var1 = (((adc_t >> 3) - (t1 << 1)) * t2) >> 11;
+        let var2 = (((((adc_t >> 4) - t1) * ((adc_t >> 4) - t1)) >> 12) * t3) >> 14;
+        let t_fine = var1 + var2;
+        ((t_fine * 5 + 128) >> 8, t_fine)
+    }
+
+    /// Returns the pressure, in 1/256 Pa.
+    fn pressure(&self, adc_p: i32, t_fine: i32) -> u32 {
+        let mut var1 = i64::from(t_fine) - 128_000;
+        let mut var2 = var1 * var1 * i64::from(self.p6);
+        var2 += (var1 * i64::from(self.p5)) << 17;
+        var2 += i64::from(self.p4) << 35;
+        var1 = ((var1 * var1 * i64::from(self.p3)) >> 8) + ((var1 * i64::from(self.p2)) << 12);
+        var1 = (((1_i64 << 47) + var1) * i64::from(self.p1)) >> 33;
+        if var1 == 0 {
+            return 0;
+        }
+        let mut p = 1_048_576 - i64::from(adc_p);
+        p = (((p << 31) - var2) * 3125) / var1;
+        var1 = (i64::from(self.p9) * (p >> 13) * (p >> 13)) >> 25;
+        var2 = (i64::from(self.p8) * p) >> 19;
+        p = ((p + var1 + var2) >> 8) + (i64::from(self.p7) << 4);
+        u32::try_from(p).unwrap_or_default()
+    }
+
+    /// Returns the relative humidity, in 1/1024 %.
+    fn humidity(&self, adc_h: i32, t_fine: i32) -> u32 {
+        let (h1, h2, h3) = (i32::from(self.h1), i32::from(self.h2), i32::from(self.h3));
+        let (h4, h5, h6) = (i32::from(self.h4), i32::from(self.h5), i32::from(self.h6));
+        let x = t_fine - 76_800;
+        let mut v = ((((adc_h << 14) - (h4 << 20) - (h5 * x)) + 16_384) >> 15)
+            * ((((((((x * h6) >> 10) * (((x * h3) >> 11) + 32_768)) >> 10) + 2_097_152) * h2)
+                + 8192)
+                >> 14);
+        v -= ((((v >> 15) * (v >> 15)) >> 7) * h1) >> 4;
+        u32::try_from(v.clamp(0, 419_430_400) >> 12).unwrap_or_default()
+    }
+}
+
+struct Bme280Device<R> {
+    registers: R,
+    calibration: Calibration,
+}
+
+impl<R: Registers> Device for Bme280Device<R> {
+    async fn apply_mode(&mut self, _mode: Mode) -> Result<(), Error> {
+        // The device sleeps between forced measurements.
+        Ok(())
+    }
+
+    async fn measure(&mut self) -> ReadingResult {
+        self.registers.write(REG_CTRL_HUM, CTRL_HUM_OSRS_1).await?;
+        self.registers
+            .write(REG_CTRL_MEAS, CTRL_MEAS_FORCED_OSRS_1)
+            .await?;
+        Timer::after_millis(MEASUREMENT_MS).await;
+        while self.registers.read_u8(REG_STATUS).await? & STATUS_MEASURING != 0 {
+            Timer::after_millis(1).await;
+        }
+
+        let mut data = [0; 8];
+        self.registers.read(REG_PRESS_MSB, &mut data).await?;
+        // 20-bit values, followed by the 16-bit humidity.
+        let adc_p = i32::from(data[0]) << 12 | i32::from(data[1]) << 4 | i32::from(data[2] >> 4);
+        let adc_t = i32::from(data[3]) << 12 | i32::from(data[4]) << 4 | i32::from(data[5] >> 4);
+        let adc_h = i32::from(u16::from_be_bytes([data[6], data[7]]));
+
+        let (temperature, t_fine) = self.calibration.temperature(adc_t);
+        let pressure = self.calibration.pressure(adc_p, t_fine) / 256;
+        let humidity = self.calibration.humidity(adc_h, t_fine) * 100 / 1024;
+        Ok(Samples::from_array([
+            Sample::new(
+                temperature,
+                Accuracy::SymmetricalError {
+                    deviation: 100,
+                    bias: 0,
+                    scaling: -2,
+                },
+            ),
+            Sample::new(
+                i32::try_from(humidity).map_err(|_| Error::SensorAccess)?,
+                Accuracy::SymmetricalError {
+                    deviation: 300,
+                    bias: 0,
+                    scaling: -2,
+                },
+            ),
+            Sample::new(
+                i32::try_from(pressure).map_err(|_| Error::SensorAccess)?,
+                Accuracy::SymmetricalError {
+                    deviation: 100,
+                    bias: 0,
+                    scaling: 0,
+                },
+            ),
+        ]))
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use ariel_os_embassy_common::{
+        i2c::controller::mock::{Expectation, I2cMock},
+        spi::main::mock::{self, SpiMock},
+    };
+    use embassy_futures::block_on;
+
+    use super::*;
+
+    const ADDRESS: u8 = 0x76;
+
+    /// `calib00`…`calib25`: the temperature and pressure parameters of the compensation example
+    /// of the BMP280 datasheet, whose compensation the BME280 shares, followed by a reserved byte
+    /// and `dig_H1`.
+    const CALIB_00: [u8; 26] = [
+        0x70, 0x6b, 0x43, 0x67, 0x18, 0xfc, 0x7d, 0x8e, 0x43, 0xd6, 0xd0, 0x0b, 0x27, 0x0b, 0x8c,
+        0x00, 0xf9, 0xff, 0x8c, 0x3c, 0xf8, 0xc6, 0x70, 0x17, 0x00, 0x4b,
+    ];
+    /// `calib26`…`calib32`: the humidity parameters of a sample device.
+    const CALIB_26: [u8; 7] = [0x6a, 0x01, 0x00, 0x13, 0x29, 0x03, 0x1e];
+    /// `press_msb`…`hum_lsb`: the raw temperature and pressure of the datasheet example, and a
+    /// raw humidity of 30000.
+    const DATA: [u8; 8] = [0x65, 0x5a, 0xc0, 0x7e, 0xed, 0x00, 0x75, 0x30];
+
+    #[test]
+    fn parse_calibration() {
+        assert_eq!(
+            Calibration::parse(&CALIB_00, CALIB_26),
+            Calibration {
+                t1: 27504,
+                t2: 26435,
+                t3: -1000,
+                p1: 36477,
+                p2: -10685,
+                p3: 3024,
+                p4: 2855,
+                p5: 140,
+                p6: -7,
+                p7: 15500,
+                p8: -14600,
+                p9: 6000,
+                h1: 75,
+                h2: 362,
+                h3: 0,
+                h4: 313,
+                h5: 50,
+                h6: 30,
+            }
+        );
+    }
+
+    #[test]
+    fn measure_i2c() {
+        let mut i2c = I2cMock::new(&[
+            // Initialization.
+            Expectation::Write {
+                address: ADDRESS,
+                data: &[REG_CHIP_ID],
+            },
+            Expectation::Read {
+                address: ADDRESS,
+                data: &[CHIP_ID],
+            },
+            Expectation::Write {
+                address: ADDRESS,
+                data: &[REG_RESET, RESET],
+            },
+            Expectation::Write {
+                address: ADDRESS,
+                data: &[REG_CALIB_00],
+            },
+            Expectation::Read {
+                address: ADDRESS,
+                data: &CALIB_00,
+            },
+            Expectation::Write {
+                address: ADDRESS,
+                data: &[REG_CALIB_26],
+            },
+            Expectation::Read {
+                address: ADDRESS,
+                data: &CALIB_26,
+            },
+            // Measurement.
+            Expectation::Write {
+                address: ADDRESS,
+                data: &[REG_CTRL_HUM, CTRL_HUM_OSRS_1],
+            },
+            Expectation::Write {
+                address: ADDRESS,
+                data: &[REG_CTRL_MEAS, CTRL_MEAS_FORCED_OSRS_1],
+            },
+            Expectation::Write {
+                address: ADDRESS,
+                data: &[REG_STATUS],
+            },
+            Expectation::Read {
+                address: ADDRESS,
+                data: &[0],
+            },
+            Expectation::Write {
+                address: ADDRESS,
+                data: &[REG_PRESS_MSB],
+            },
+            Expectation::Read {
+                address: ADDRESS,
+                data: &DATA,
+            },
+        ]);
+
+        let samples = block_on(async {
+            let mut registers = I2cRegisters {
+                i2c: &mut i2c,
+                address: ADDRESS,
+                auto_increment: 0,
+            };
+            let calibration = init(&mut registers).await.unwrap();
+            let mut device = Bme280Device {
+                registers,
+                calibration,
+            };
+            device.measure().await.unwrap()
+        });
+        i2c.done();
+
+        let values: Vec<_> = samples.iter().map(|sample| sample.value()).collect();
+        // 25.08 °C, 55.00 % and 100653 Pa, as given by the floating-point compensation of the
+        // datasheet.
+        assert_eq!(values, [2508, 5499, 100_653]);
+    }
+
+    #[test]
+    fn unknown_chip_id_spi() {
+        let mut spi = SpiMock::new(&[
+            mock::Expectation::Write(&[REG_CHIP_ID | 0x80]),
+            mock::Expectation::Read(&[]),
+            mock::Expectation::Read(&[0x58]),
+        ]);
+
+        let result = block_on(init(&mut SpiRegisters {
+            spi: &mut spi,
+            auto_increment: 0,
+            dummy_byte: false,
+        }));
+        spi.done();
+
+        assert_eq!(result, Err(Error::SensorAccess));
+    }
+}
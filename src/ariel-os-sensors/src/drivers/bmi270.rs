@@ -0,0 +1,583 @@
+//! Driver for the Bosch BMI270 6-axis IMU, connected through I2C or SPI.
+//!
+//! Readings consist of the acceleration along the X, Y and Z axes, in thousandths of m/s²,
+//! followed by the angular velocity around the X, Y and Z axes, in thousandths of °/s.
+//!
+//! The device needs to be initialized with the configuration file provided by Bosch Sensortec
+//! (`bmi270_config_file` in its `BMI270_SensorAPI`), which is not included, and is given in the
+//! [`Config`].
+//!
+//! In [`Mode::Triggered`], the device measures at the configured [`DataRate`]. In
+//! [`Mode::OneShot`], its accelerometer and gyroscope are disabled between measurements.
+//! With the `fusion` feature, the device can also provide its orientation through
+//! [`run_fusion_i2c()`] or [`run_fusion_spi()`].
+
+use core::convert::Infallible;
+
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::{i2c::I2c, spi::SpiDevice};
+
+use super::{
+    Common, Device,
+    registers::{I2cRegisters, Registers, SpiRegisters},
+};
+use crate::{
+    Accuracy, Category, Error, Label, MeasurementUnit, Mode, ReadingChannel, ReadingResult,
+    ReadingWaiter, Sample, Samples, Sensor, State,
+};
+
+const REG_CHIP_ID: u8 = 0x00;
+const REG_STATUS: u8 = 0x03;
+const REG_DATA_8: u8 = 0x0c;
+const REG_INTERNAL_STATUS: u8 = 0x21;
+const REG_ACC_CONF: u8 = 0x40;
+const REG_ACC_RANGE: u8 = 0x41;
+const REG_GYR_CONF: u8 = 0x42;
+const REG_GYR_RANGE: u8 = 0x43;
+const REG_INIT_CTRL: u8 = 0x59;
+const REG_INIT_ADDR_0: u8 = 0x5b;
+const REG_INIT_ADDR_1: u8 = 0x5c;
+const REG_INIT_DATA: u8 = 0x5e;
+const REG_PWR_CONF: u8 = 0x7c;
+const REG_PWR_CTRL: u8 = 0x7d;
+const REG_CMD: u8 = 0x7e;
+
+const CHIP_ID: u8 = 0x24;
+const CMD_SOFT_RESET: u8 = 0xb6;
+
+/// Initialization completed, in the `message` bits of `INTERNAL_STATUS`.
+const INIT_OK: u8 = 0x01;
+/// Accelerometer and gyroscope enabled, in `PWR_CTRL`.
+const PWR_CTRL_ACC_GYR: u8 = 0b0110;
+/// Filter performance mode and normal bandwidth, in `ACC_CONF` and `GYR_CONF`.
+const CONF_PERFORMANCE: u8 = 0xa0;
+/// Data of the accelerometer and of the gyroscope ready, in `STATUS`.
+const DRDY_ACC_GYR: u8 = 0b1100_0000;
+
+/// Length of the chunks the configuration file is written in.
+const CONFIG_CHUNK_LEN: usize = 256;
+
+/// Duration the device needs to reset, in milliseconds.
+const RESET_MS: u64 = 2;
+/// Duration the device needs to leave the advanced power save mode, in microseconds.
+const POWER_SAVE_EXIT_US: u64 = 450;
+/// Duration the device needs to process the configuration file, in milliseconds.
+const INIT_MS: u64 = 20;
+/// Duration the gyroscope needs to start, in milliseconds.
+const GYRO_START_MS: u64 = 45;
+
+const CHANNELS: [ReadingChannel; 6] = [
+    ReadingChannel::new(Label::X, -3, MeasurementUnit::MeterPerSecondSquared),
+    ReadingChannel::new(Label::Y, -3, MeasurementUnit::MeterPerSecondSquared),
+    ReadingChannel::new(Label::Z, -3, MeasurementUnit::MeterPerSecondSquared),
+    ReadingChannel::new(Label::X, -3, MeasurementUnit::DegreePerSecond),
+    ReadingChannel::new(Label::Y, -3, MeasurementUnit::DegreePerSecond),
+    ReadingChannel::new(Label::Z, -3, MeasurementUnit::DegreePerSecond),
+];
+
+/// Measurement range of the accelerometer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AccelRange {
+    /// ±2 g.
+    G2,
+    /// ±4 g.
+    G4,
+    /// ±8 g.
+    G8,
+    /// ±16 g.
+    G16,
+}
+
+impl AccelRange {
+    /// Returns the value of `ACC_RANGE`.
+    fn bits(self) -> u8 {
+        match self {
+            Self::G2 => 0,
+            Self::G4 => 1,
+            Self::G8 => 2,
+            Self::G16 => 3,
+        }
+    }
+
+    /// Returns the sensitivity, in digits per g.
+    fn sensitivity(self) -> i32 {
+        match self {
+            Self::G2 => 16384,
+            Self::G4 => 8192,
+            Self::G8 => 4096,
+            Self::G16 => 2048,
+        }
+    }
+}
+
+/// Measurement range of the gyroscope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GyroRange {
+    /// ±125 °/s.
+    Dps125,
+    /// ±250 °/s.
+    Dps250,
+    /// ±500 °/s.
+    Dps500,
+    /// ±1000 °/s.
+    Dps1000,
+    /// ±2000 °/s.
+    Dps2000,
+}
+
+impl GyroRange {
+    /// Returns the value of `GYR_RANGE`.
+    fn bits(self) -> u8 {
+        match self {
+            Self::Dps2000 => 0,
+            Self::Dps1000 => 1,
+            Self::Dps500 => 2,
+            Self::Dps250 => 3,
+            Self::Dps125 => 4,
+        }
+    }
+
+    /// Returns the sensitivity, in tenths of digit per °/s.
+    fn sensitivity(self) -> i32 {
+        match self {
+            Self::Dps125 => 2624,
+            Self::Dps250 => 1312,
+            Self::Dps500 => 656,
+            Self::Dps1000 => 328,
+            Self::Dps2000 => 164,
+        }
+    }
+}
+
+/// Data rate of the accelerometer and of the gyroscope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DataRate {
+    /// 25 Hz.
+    Hz25,
+    /// 50 Hz.
+    Hz50,
+    /// 100 Hz.
+    Hz100,
+    /// 200 Hz.
+    Hz200,
+    /// 400 Hz.
+    Hz400,
+    /// 800 Hz.
+    Hz800,
+    /// 1600 Hz.
+    Hz1600,
+}
+
+impl DataRate {
+    /// Returns the value of the `odr` bits of `ACC_CONF` and `GYR_CONF`.
+    fn bits(self) -> u8 {
+        match self {
+            Self::Hz25 => 0x06,
+            Self::Hz50 => 0x07,
+            Self::Hz100 => 0x08,
+            Self::Hz200 => 0x09,
+            Self::Hz400 => 0x0a,
+            Self::Hz800 => 0x0b,
+            Self::Hz1600 => 0x0c,
+        }
+    }
+
+    fn period(self) -> Duration {
+        let hz = match self {
+            Self::Hz25 => 25,
+            Self::Hz50 => 50,
+            Self::Hz100 => 100,
+            Self::Hz200 => 200,
+            Self::Hz400 => 400,
+            Self::Hz800 => 800,
+            Self::Hz1600 => 1600,
+        };
+        Duration::from_hz(hz)
+    }
+}
+
+/// Configuration of a BMI270 sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// I2C address of the device, which depends on its `SDO` pin; not used with SPI.
+    pub address: u8,
+    /// Measurement range of the accelerometer.
+    pub accel_range: AccelRange,
+    /// Measurement range of the gyroscope.
+    pub gyro_range: GyroRange,
+    /// Data rate.
+    pub data_rate: DataRate,
+    /// The configuration file the device is initialized with.
+    pub config_file: &'static [u8],
+}
+
+impl Config {
+    /// Creates the default configuration, with `config_file` as configuration file.
+    #[must_use]
+    pub const fn new(config_file: &'static [u8]) -> Self {
+        Self {
+            address: 0x68,
+            accel_range: AccelRange::G2,
+            gyro_range: GyroRange::Dps250,
+            data_rate: DataRate::Hz100,
+            config_file,
+        }
+    }
+}
+
+/// A BMI270 sensor.
+pub struct Bmi270 {
+    common: Common,
+}
+
+impl Bmi270 {
+    /// Creates a BMI270 sensor, labeled `label`.
+    #[must_use]
+    pub const fn new(label: Option<&'static str>) -> Self {
+        Self {
+            common: Common::new(label, &[Mode::OneShot, Mode::Triggered]),
+        }
+    }
+
+    /// Initializes the device connected through `i2c`, and runs the driver.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SensorAccess`] if the device cannot be initialized.
+    pub async fn run_i2c<I: I2c>(&self, i2c: I, config: Config) -> Result<Infallible, Error> {
+        let mut device = Bmi270Device::new(i2c_registers(i2c, &config), config);
+        device.init().await?;
+        self.common.run(&mut device).await
+    }
+
+    /// Initializes the device connected through `spi`, and runs the driver.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SensorAccess`] if the device cannot be initialized.
+    pub async fn run_spi<S: SpiDevice>(&self, spi: S, config: Config) -> Result<Infallible, Error> {
+        let mut device = Bmi270Device::new(spi_registers(spi), config);
+        device.init().await?;
+        self.common.run(&mut device).await
+    }
+}
+
+impl Sensor for Bmi270 {
+    fn trigger_measurement(&self) -> Result<(), Error> {
+        self.common.trigger_measurement()
+    }
+
+    fn wait_for_reading(&'static self) -> ReadingWaiter {
+        self.common.wait_for_reading()
+    }
+
+    fn set_mode(&self, mode: Mode) -> Result<State, Error> {
+        self.common.set_mode(mode)
+    }
+
+    fn state(&self) -> State {
+        self.common.state()
+    }
+
+    fn categories(&self) -> &'static [Category] {
+        &[Category::Accelerometer, Category::Gyroscope]
+    }
+
+    fn reading_channels(&self) -> &'static [ReadingChannel] {
+        &CHANNELS
+    }
+
+    fn label(&self) -> Option<&'static str> {
+        self.common.label()
+    }
+
+    fn display_name(&self) -> Option<&'static str> {
+        Some("6-axis IMU")
+    }
+
+    fn part_number(&self) -> Option<&'static str> {
+        Some("BMI270")
+    }
+}
+
+/// Initializes the device connected through `i2c`, and runs the fusion of its measurements into
+/// `fusion`.
+///
+/// # Errors
+///
+/// Returns [`Error::SensorAccess`] if the device cannot be initialized, or accessing it fails.
+#[cfg(feature = "fusion")]
+pub async fn run_fusion_i2c<I: I2c>(
+    i2c: I,
+    config: Config,
+    fusion: &crate::fusion::Fusion,
+    fusion_config: crate::fusion::Config,
+) -> Result<Infallible, Error> {
+    let mut device = Bmi270Device::new(i2c_registers(i2c, &config), config);
+    device.init().await?;
+    device.power(true).await?;
+    crate::fusion::run(&mut device, fusion, fusion_config).await
+}
+
+/// Initializes the device connected through `spi`, and runs the fusion of its measurements into
+/// `fusion`.
+///
+/// # Errors
+///
+/// Returns [`Error::SensorAccess`] if the device cannot be initialized, or accessing it fails.
+#[cfg(feature = "fusion")]
+pub async fn run_fusion_spi<S: SpiDevice>(
+    spi: S,
+    config: Config,
+    fusion: &crate::fusion::Fusion,
+    fusion_config: crate::fusion::Config,
+) -> Result<Infallible, Error> {
+    let mut device = Bmi270Device::new(spi_registers(spi), config);
+    device.init().await?;
+    device.power(true).await?;
+    crate::fusion::run(&mut device, fusion, fusion_config).await
+}
+
+fn i2c_registers<I: I2c>(i2c: I, config: &Config) -> I2cRegisters<I> {
+    I2cRegisters {
+        i2c,
+        address: config.address,
+        auto_increment: 0,
+    }
+}
+
+fn spi_registers<S: SpiDevice>(spi: S) -> SpiRegisters<S> {
+    SpiRegisters {
+        spi,
+        auto_increment: 0,
+        dummy_byte: true,
+    }
+}
+
+struct Bmi270Device<R> {
+    registers: R,
+    config: Config,
+}
+
+impl<R: Registers> Bmi270Device<R> {
+    fn new(registers: R, config: Config) -> Self {
+        Self { registers, config }
+    }
+
+    async fn init(&mut self) -> Result<(), Error> {
+        // Switches the interface to SPI, if the device is connected through SPI.
+        let _ = self.registers.read_u8(REG_CHIP_ID).await;
+        if self.registers.read_u8(REG_CHIP_ID).await? != CHIP_ID {
+            return Err(Error::SensorAccess);
+        }
+        self.registers.write(REG_CMD, CMD_SOFT_RESET).await?;
+        Timer::after_millis(RESET_MS).await;
+        let _ = self.registers.read_u8(REG_CHIP_ID).await;
+
+        self.registers.write(REG_PWR_CONF, 0).await?;
+        Timer::after_micros(POWER_SAVE_EXIT_US).await;
+        self.registers.write(REG_INIT_CTRL, 0).await?;
+        let (chunks, remainder) = self.config.config_file.as_chunks::<CONFIG_CHUNK_LEN>();
+        for (index, chunk) in chunks
+            .iter()
+            .map(<[u8; CONFIG_CHUNK_LEN]>::as_slice)
+            .chain(core::iter::once(remainder))
+            .enumerate()
+        {
+            // The initialization address is counted in words.
+            let address = index * CONFIG_CHUNK_LEN / 2;
+            let [low, ..] = (address & 0x0f).to_le_bytes();
+            let [high, ..] = (address >> 4).to_le_bytes();
+            self.registers.write(REG_INIT_ADDR_0, low).await?;
+            self.registers.write(REG_INIT_ADDR_1, high).await?;
+            if !chunk.is_empty() {
+                self.registers.write_burst(REG_INIT_DATA, chunk).await?;
+            }
+        }
+        self.registers.write(REG_INIT_CTRL, 1).await?;
+        Timer::after_millis(INIT_MS).await;
+        if self.registers.read_u8(REG_INTERNAL_STATUS).await? & 0x0f != INIT_OK {
+            return Err(Error::SensorAccess);
+        }
+
+        let odr = self.config.data_rate.bits();
+        self.registers
+            .write(REG_ACC_CONF, CONF_PERFORMANCE | odr)
+            .await?;
+        self.registers
+            .write(REG_ACC_RANGE, self.config.accel_range.bits())
+            .await?;
+        self.registers
+            .write(REG_GYR_CONF, CONF_PERFORMANCE | odr)
+            .await?;
+        self.registers
+            .write(REG_GYR_RANGE, self.config.gyro_range.bits())
+            .await?;
+        self.power(false).await
+    }
+
+    /// Disables the accelerometer and the gyroscope, or makes them measure at the configured
+    /// data rate.
+    async fn power(&mut self, on: bool) -> Result<(), Error> {
+        let enabled = if on { PWR_CTRL_ACC_GYR } else { 0 };
+        self.registers.write(REG_PWR_CTRL, enabled).await?;
+        if on {
+            Timer::after_millis(GYRO_START_MS).await;
+        }
+        Ok(())
+    }
+
+    async fn read_motion(&mut self) -> ReadingResult {
+        let mut data = [0; 12];
+        self.registers.read(REG_DATA_8, &mut data).await?;
+        let (words, _) = data.as_chunks::<2>();
+        let accel_sensitivity = i64::from(self.config.accel_range.sensitivity());
+        let gyro_sensitivity = i64::from(self.config.gyro_range.sensitivity());
+        let mut samples = [Sample::new(0, Accuracy::Unknown); 6];
+        for (index, (sample, word)) in samples.iter_mut().zip(words).enumerate() {
+            let raw = i64::from(i16::from_le_bytes(*word));
+            *sample = if index < 3 {
+                // 1 g = 9.80665 m/s²
+                let value = raw * 9_806_650 / accel_sensitivity / 1000;
+                Sample::new(
+                    i32::try_from(value).map_err(|_| Error::SensorAccess)?,
+                    Accuracy::SymmetricalError {
+                        // Typical zero-g offset of ±20 mg.
+                        deviation: 196,
+                        bias: 0,
+                        scaling: -3,
+                    },
+                )
+            } else {
+                let value = raw * 10_000 / gyro_sensitivity;
+                Sample::new(
+                    i32::try_from(value).map_err(|_| Error::SensorAccess)?,
+                    Accuracy::SymmetricalError {
+                        // Typical zero-rate offset of ±0.5 °/s.
+                        deviation: 500,
+                        bias: 0,
+                        scaling: -3,
+                    },
+                )
+            };
+        }
+        Ok(Samples::from_array(samples))
+    }
+
+    async fn is_data_available(&mut self) -> Result<bool, Error> {
+        Ok(self.registers.read_u8(REG_STATUS).await? & DRDY_ACC_GYR == DRDY_ACC_GYR)
+    }
+}
+
+impl<R: Registers> Device for Bmi270Device<R> {
+    async fn apply_mode(&mut self, mode: Mode) -> Result<(), Error> {
+        self.power(mode == Mode::Triggered).await
+    }
+
+    async fn measure(&mut self) -> ReadingResult {
+        self.power(true).await?;
+        let period = self.config.data_rate.period();
+        while !self.is_data_available().await? {
+            Timer::after(period).await;
+        }
+        let reading = self.read_motion().await;
+        self.power(false).await?;
+        reading
+    }
+
+    async fn poll(&mut self) -> Option<ReadingResult> {
+        match self.is_data_available().await {
+            Ok(true) => Some(self.read_motion().await),
+            Ok(false) => None,
+            Err(error) => Some(Err(error)),
+        }
+    }
+
+    fn poll_interval(&self) -> Duration {
+        self.config.data_rate.period()
+    }
+}
+
+#[cfg(feature = "fusion")]
+mod fifo {
+    use embassy_time::Duration;
+
+    use super::{Bmi270Device, GyroRange, REG_CMD};
+    use crate::{
+        Error,
+        drivers::registers::Registers,
+        fusion::{FRAMES_PER_READ, FifoImu, Frame, radians_per_second},
+    };
+
+    const REG_FIFO_LENGTH_0: u8 = 0x24;
+    const REG_FIFO_DATA: u8 = 0x26;
+    const REG_FIFO_CONFIG_1: u8 = 0x49;
+
+    const CMD_FIFO_FLUSH: u8 = 0xb0;
+
+    /// Accelerometer and gyroscope values written into the FIFO, without headers, in
+    /// `FIFO_CONFIG_1`.
+    const FIFO_ACC_GYR_HEADERLESS: u8 = 0b1100_0000;
+
+    /// Length of a headerless FIFO frame: the gyroscope values, followed by the accelerometer
+    /// values.
+    const FRAME_LEN: usize = 12;
+
+    /// Returns the sensitivity of the gyroscope, in digits per °/s.
+    fn gyro_sensitivity(range: GyroRange) -> f32 {
+        match range {
+            GyroRange::Dps125 => 262.4,
+            GyroRange::Dps250 => 131.2,
+            GyroRange::Dps500 => 65.6,
+            GyroRange::Dps1000 => 32.8,
+            GyroRange::Dps2000 => 16.4,
+        }
+    }
+
+    impl<R: Registers> FifoImu for Bmi270Device<R> {
+        fn frame_period(&self) -> Duration {
+            self.config.data_rate.period()
+        }
+
+        async fn start_fifo(&mut self) -> Result<(), Error> {
+            self.registers
+                .write(REG_FIFO_CONFIG_1, FIFO_ACC_GYR_HEADERLESS)
+                .await?;
+            self.registers.write(REG_CMD, CMD_FIFO_FLUSH).await
+        }
+
+        async fn read_fifo(&mut self, frames: &mut [Frame]) -> Result<usize, Error> {
+            let mut length = [0; 2];
+            self.registers.read(REG_FIFO_LENGTH_0, &mut length).await?;
+            // 14-bit length.
+            let length = usize::from(u16::from_le_bytes(length) & 0x3fff);
+            let count = (length / FRAME_LEN).min(frames.len()).min(FRAMES_PER_READ);
+            let mut data = [0; FRAME_LEN * FRAMES_PER_READ];
+            let Some(data) = data.get_mut(..count * FRAME_LEN) else {
+                return Ok(0);
+            };
+            if data.is_empty() {
+                return Ok(0);
+            }
+            self.registers.read(REG_FIFO_DATA, data).await?;
+            let sensitivity = gyro_sensitivity(self.config.gyro_range);
+            let (chunks, _) = data.as_chunks::<FRAME_LEN>();
+            for (frame, chunk) in frames.iter_mut().zip(chunks) {
+                let (words, _) = chunk.as_chunks::<2>();
+                let mut values = words
+                    .iter()
+                    .map(|word| f32::from(i16::from_le_bytes(*word)));
+                let mut next = || values.next().unwrap_or_default();
+                frame.angular_velocity =
+                    core::array::from_fn(|_| radians_per_second(next() / sensitivity));
+                frame.acceleration = core::array::from_fn(|_| next());
+            }
+            Ok(count)
+        }
+    }
+}
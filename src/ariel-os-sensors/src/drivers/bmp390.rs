@@ -0,0 +1,272 @@
+//! Driver for the Bosch BMP390 pressure sensor, connected through I2C or SPI.
+//!
+//! Readings consist of the pressure, in pascals, followed by the temperature, in hundredths of
+//! degree Celsius.
+
+use core::convert::Infallible;
+
+use embassy_time::Timer;
+use embedded_hal_async::{i2c::I2c, spi::SpiDevice};
+
+use super::{
+    Common, Device,
+    registers::{I2cRegisters, Registers, SpiRegisters},
+};
+use crate::{
+    Accuracy, Category, Error, Label, MeasurementUnit, Mode, ReadingChannel, ReadingResult,
+    ReadingWaiter, Sample, Samples, Sensor, State,
+};
+
+const REG_CHIP_ID: u8 = 0x00;
+const REG_STATUS: u8 = 0x03;
+const REG_DATA_0: u8 = 0x04;
+const REG_PWR_CTRL: u8 = 0x1b;
+const REG_OSR: u8 = 0x1c;
+const REG_NVM_PAR_T1: u8 = 0x31;
+const REG_CMD: u8 = 0x7e;
+
+const CHIP_ID: u8 = 0x60;
+const CMD_SOFT_RESET: u8 = 0xb6;
+
+/// Pressure oversampling ×8 and temperature oversampling ×1, in `OSR`.
+const OSR: u8 = 0b011;
+/// Pressure and temperature enabled in forced mode, in `PWR_CTRL`.
+const PWR_CTRL_FORCED: u8 = 0b01 << 4 | 0b11;
+/// Pressure and temperature data ready, in `STATUS`.
+const STATUS_DRDY: u8 = 0b11 << 5;
+
+/// Maximum duration of a measurement with the configured oversampling, in milliseconds.
+const MEASUREMENT_MS: u64 = 20;
+
+const CHANNELS: [ReadingChannel; 2] = [
+    ReadingChannel::new(Label::Pressure, 0, MeasurementUnit::Pascal),
+    ReadingChannel::new(Label::Temperature, -2, MeasurementUnit::Celsius),
+];
+
+/// Configuration of a BMP390 sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// I2C address of the device, which depends on its `SDO` pin; not used with SPI.
+    pub address: u8,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self { address: 0x77 }
+    }
+}
+
+/// A BMP390 sensor.
+pub struct Bmp390 {
+    common: Common,
+}
+
+impl Bmp390 {
+    /// Creates a BMP390 sensor, labeled `label`.
+    #[must_use]
+    pub const fn new(label: Option<&'static str>) -> Self {
+        Self {
+            common: Common::new(label, &[Mode::OneShot]),
+        }
+    }
+
+    /// Initializes the device connected through `i2c`, and runs the driver.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SensorAccess`] if the device cannot be initialized.
+    pub async fn run_i2c<I: I2c>(&self, i2c: I, config: Config) -> Result<Infallible, Error> {
+        let registers = I2cRegisters {
+            i2c,
+            address: config.address,
+            auto_increment: 0,
+        };
+        self.run(registers).await
+    }
+
+    /// Initializes the device connected through `spi`, and runs the driver.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SensorAccess`] if the device cannot be initialized.
+    pub async fn run_spi<S: SpiDevice>(&self, spi: S) -> Result<Infallible, Error> {
+        let registers = SpiRegisters {
+            spi,
+            auto_increment: 0,
+            dummy_byte: true,
+        };
+        self.run(registers).await
+    }
+
+    async fn run<R: Registers>(&self, mut registers: R) -> Result<Infallible, Error> {
+        let calibration = init(&mut registers).await?;
+        let mut device = Bmp390Device {
+            registers,
+            calibration,
+        };
+        self.common.run(&mut device).await
+    }
+}
+
+impl Sensor for Bmp390 {
+    fn trigger_measurement(&self) -> Result<(), Error> {
+        self.common.trigger_measurement()
+    }
+
+    fn wait_for_reading(&'static self) -> ReadingWaiter {
+        self.common.wait_for_reading()
+    }
+
+    fn set_mode(&self, mode: Mode) -> Result<State, Error> {
+        self.common.set_mode(mode)
+    }
+
+    fn state(&self) -> State {
+        self.common.state()
+    }
+
+    fn categories(&self) -> &'static [Category] {
+        &[Category::Pressure, Category::Temperature]
+    }
+
+    fn reading_channels(&self) -> &'static [ReadingChannel] {
+        &CHANNELS
+    }
+
+    fn label(&self) -> Option<&'static str> {
+        self.common.label()
+    }
+
+    fn display_name(&self) -> Option<&'static str> {
+        Some("pressure sensor")
+    }
+
+    fn part_number(&self) -> Option<&'static str> {
+        Some("BMP390")
+    }
+}
+
+/// Resets the device, and returns its calibration.
+async fn init<R: Registers>(registers: &mut R) -> Result<Calibration, Error> {
+    if registers.read_u8(REG_CHIP_ID).await? != CHIP_ID {
+        return Err(Error::SensorAccess);
+    }
+    registers.write(REG_CMD, CMD_SOFT_RESET).await?;
+    Timer::after_millis(2).await;
+    let mut nvm = [0; 21];
+    registers.read(REG_NVM_PAR_T1, &mut nvm).await?;
+    registers.write(REG_OSR, OSR).await?;
+    Ok(Calibration::parse(&nvm))
+}
+
+/// Returns 2<sup>`exp`</sup>.
+const fn pow2(exp: i32) -> f32 {
+    f32::from_bits((127 + exp).cast_unsigned() << 23)
+}
+
+/// The calibration coefficients of a device, named as in its datasheet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Calibration {
+    t: [f32; 3],
+    p: [f32; 11],
+}
+
+impl Calibration {
+    /// Parses the calibration coefficients read from `NVM_PAR_T1`…`NVM_PAR_P11`.
+    fn parse(nvm: &[u8; 21]) -> Self {
+        let unsigned = |lsb, msb| f32::from(u16::from_le_bytes([lsb, msb]));
+        let signed = |lsb, msb| f32::from(i16::from_le_bytes([lsb, msb]));
+        let signed_byte = |value: u8| f32::from(value.cast_signed());
+        Self {
+            t: [
+                unsigned(nvm[0], nvm[1]) / pow2(-8),
+                unsigned(nvm[2], nvm[3]) / pow2(30),
+                signed_byte(nvm[4]) / pow2(48),
+            ],
+            p: [
+                (signed(nvm[5], nvm[6]) - pow2(14)) / pow2(20),
+                (signed(nvm[7], nvm[8]) - pow2(14)) / pow2(29),
+                signed_byte(nvm[9]) / pow2(32),
+                signed_byte(nvm[10]) / pow2(37),
+                unsigned(nvm[11], nvm[12]) / pow2(-3),
+                unsigned(nvm[13], nvm[14]) / pow2(6),
+                signed_byte(nvm[15]) / pow2(8),
+                signed_byte(nvm[16]) / pow2(15),
+                signed(nvm[17], nvm[18]) / pow2(48),
+                signed_byte(nvm[19]) / pow2(48),
+                signed_byte(nvm[20]) / pow2(65),
+            ],
+        }
+    }
+
+    /// Returns the temperature, in degree Celsius.
+    fn temperature(&self, raw: f32) -> f32 {
+        let [t1, t2, t3] = self.t;
+        let data1 = raw - t1;
+        data1 * t2 + data1 * data1 * t3
+    }
+
+    /// Returns the pressure, in pascals, at `temperature`.
+    fn pressure(&self, raw: f32, temperature: f32) -> f32 {
+        let [p1, p2, p3, p4, p5, p6, p7, p8, p9, p10, p11] = self.p;
+        let t = temperature;
+        let (t2, t3) = (t * t, t * t * t);
+        let out1 = p5 + p6 * t + p7 * t2 + p8 * t3;
+        let out2 = raw * (p1 + p2 * t + p3 * t2 + p4 * t3);
+        let out3 = raw * raw * (p9 + p10 * t) + raw * raw * raw * p11;
+        out1 + out2 + out3
+    }
+}
+
+struct Bmp390Device<R> {
+    registers: R,
+    calibration: Calibration,
+}
+
+impl<R: Registers> Device for Bmp390Device<R> {
+    async fn apply_mode(&mut self, _mode: Mode) -> Result<(), Error> {
+        // The device sleeps between forced measurements.
+        Ok(())
+    }
+
+    #[expect(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        reason = "raw values have 24 bits, and results are within range"
+    )]
+    async fn measure(&mut self) -> ReadingResult {
+        self.registers.write(REG_PWR_CTRL, PWR_CTRL_FORCED).await?;
+        Timer::after_millis(MEASUREMENT_MS).await;
+        while self.registers.read_u8(REG_STATUS).await? & STATUS_DRDY != STATUS_DRDY {
+            Timer::after_millis(1).await;
+        }
+
+        let mut data = [0; 6];
+        self.registers.read(REG_DATA_0, &mut data).await?;
+        let raw_pressure = u32::from_le_bytes([data[0], data[1], data[2], 0]) as f32;
+        let raw_temperature = u32::from_le_bytes([data[3], data[4], data[5], 0]) as f32;
+
+        let temperature = self.calibration.temperature(raw_temperature);
+        let pressure = self.calibration.pressure(raw_pressure, temperature);
+        Ok(Samples::from_array([
+            Sample::new(
+                pressure as i32,
+                Accuracy::SymmetricalError {
+                    deviation: 50,
+                    bias: 0,
+                    scaling: 0,
+                },
+            ),
+            Sample::new(
+                (temperature * 100.) as i32,
+                Accuracy::SymmetricalError {
+                    deviation: 50,
+                    bias: 0,
+                    scaling: -2,
+                },
+            ),
+        ]))
+    }
+}
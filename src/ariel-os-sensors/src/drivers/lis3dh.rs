@@ -0,0 +1,474 @@
+//! Driver for the ST LIS3DH 3-axis accelerometer (and the compatible accelerometer of the
+//! LSM303AGR), connected through I2C or SPI.
+//!
+//! Readings consist of the acceleration along the X, Y and Z axes, in thousandths of m/s².
+//!
+//! In [`Mode::Triggered`], the device measures at the configured [`DataRate`]. In
+//! [`Mode::OneShot`], it is powered down between measurements.
+//! With the `batching` feature, its readings can also be [batched](crate::batching) in its FIFO
+//! through [`Lis3dh::run_batching_i2c()`] or [`Lis3dh::run_batching_spi()`], the `INT1` pin of
+//! the device being connected to an input of the MCU.
+
+use core::convert::Infallible;
+
+use embassy_time::{Duration, Timer};
+#[cfg(feature = "batching")]
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::{i2c::I2c, spi::SpiDevice};
+
+use super::{
+    Common, Device,
+    registers::{I2cRegisters, Registers, SpiRegisters},
+};
+#[cfg(feature = "batching")]
+use crate::batching;
+use crate::{
+    Accuracy, Category, Error, Label, MeasurementUnit, Mode, ReadingChannel, ReadingResult,
+    ReadingWaiter, Sample, Samples, Sensor, State,
+};
+
+const REG_WHO_AM_I: u8 = 0x0f;
+const REG_CTRL_REG1: u8 = 0x20;
+const REG_CTRL_REG4: u8 = 0x23;
+const REG_STATUS_REG: u8 = 0x27;
+const REG_OUT_X_L: u8 = 0x28;
+
+const DEVICE_ID: u8 = 0x33;
+
+/// Enables the X, Y and Z axes in `CTRL_REG1`.
+const XYZ_ENABLE: u8 = 0b111;
+/// Block data update, in `CTRL_REG4`.
+const BDU: u8 = 1 << 7;
+/// High-resolution mode, in `CTRL_REG4`.
+const HR: u8 = 1 << 3;
+/// New X, Y and Z data available, in `STATUS_REG`.
+const ZYXDA: u8 = 1 << 3;
+
+/// Number of data periods until the first measurement is available in high-resolution mode.
+const TURN_ON_PERIODS: u32 = 7;
+
+/// Measurement range of the accelerometer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Range {
+    /// ±2 g.
+    G2,
+    /// ±4 g.
+    G4,
+    /// ±8 g.
+    G8,
+    /// ±16 g.
+    G16,
+}
+
+impl Range {
+    /// Returns the value of the `FS` bits of `CTRL_REG4`.
+    fn bits(self) -> u8 {
+        match self {
+            Self::G2 => 0,
+            Self::G4 => 1,
+            Self::G8 => 2,
+            Self::G16 => 3,
+        }
+    }
+
+    /// Returns the sensitivity in high-resolution mode, in mg per digit.
+    fn sensitivity(self) -> i32 {
+        match self {
+            Self::G2 => 1,
+            Self::G4 => 2,
+            Self::G8 => 4,
+            Self::G16 => 12,
+        }
+    }
+}
+
+/// Data rate of the accelerometer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DataRate {
+    /// 1 Hz.
+    Hz1,
+    /// 10 Hz.
+    Hz10,
+    /// 25 Hz.
+    Hz25,
+    /// 50 Hz.
+    Hz50,
+    /// 100 Hz.
+    Hz100,
+    /// 200 Hz.
+    Hz200,
+    /// 400 Hz.
+    Hz400,
+}
+
+impl DataRate {
+    /// Returns the value of the `ODR` bits of `CTRL_REG1`.
+    fn bits(self) -> u8 {
+        match self {
+            Self::Hz1 => 1,
+            Self::Hz10 => 2,
+            Self::Hz25 => 3,
+            Self::Hz50 => 4,
+            Self::Hz100 => 5,
+            Self::Hz200 => 6,
+            Self::Hz400 => 7,
+        }
+    }
+
+    fn period(self) -> Duration {
+        let hz = match self {
+            Self::Hz1 => 1,
+            Self::Hz10 => 10,
+            Self::Hz25 => 25,
+            Self::Hz50 => 50,
+            Self::Hz100 => 100,
+            Self::Hz200 => 200,
+            Self::Hz400 => 400,
+        };
+        Duration::from_hz(hz)
+    }
+}
+
+const CHANNELS: [ReadingChannel; 3] = [
+    ReadingChannel::new(Label::X, -3, MeasurementUnit::MeterPerSecondSquared),
+    ReadingChannel::new(Label::Y, -3, MeasurementUnit::MeterPerSecondSquared),
+    ReadingChannel::new(Label::Z, -3, MeasurementUnit::MeterPerSecondSquared),
+];
+
+/// Configuration of a LIS3DH sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// I2C address of the device, which depends on its `SA0` pin; not used with SPI.
+    pub address: u8,
+    /// Measurement range.
+    pub range: Range,
+    /// Data rate.
+    pub data_rate: DataRate,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address: 0x19,
+            range: Range::G2,
+            data_rate: DataRate::Hz100,
+        }
+    }
+}
+
+/// A LIS3DH sensor.
+pub struct Lis3dh {
+    common: Common,
+}
+
+impl Lis3dh {
+    /// Creates a LIS3DH sensor, labeled `label`.
+    #[must_use]
+    pub const fn new(label: Option<&'static str>) -> Self {
+        Self {
+            common: Common::new(label, &[Mode::OneShot, Mode::Triggered]),
+        }
+    }
+
+    /// Initializes the device connected through `i2c`, and runs the driver.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SensorAccess`] if the device cannot be initialized.
+    pub async fn run_i2c<I: I2c>(&self, i2c: I, config: Config) -> Result<Infallible, Error> {
+        self.run(i2c_registers(i2c, config), config).await
+    }
+
+    /// Initializes the device connected through `spi`, and runs the driver.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SensorAccess`] if the device cannot be initialized.
+    pub async fn run_spi<S: SpiDevice>(&self, spi: S, config: Config) -> Result<Infallible, Error> {
+        self.run(spi_registers(spi), config).await
+    }
+
+    /// Initializes the device connected through `i2c`, and runs it in batching mode, its `INT1`
+    /// pin being connected to `interrupt`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SensorAccess`] if the device cannot be initialized, or if accessing it
+    /// fails.
+    #[cfg(feature = "batching")]
+    pub async fn run_batching_i2c<I: I2c, W: Wait>(
+        &'static self,
+        i2c: I,
+        interrupt: W,
+        config: Config,
+        batching: batching::Config,
+    ) -> Result<Infallible, Error> {
+        let mut device = Lis3dhDevice {
+            registers: i2c_registers(i2c, config),
+            config,
+        };
+        device.init().await?;
+        batching::run(self, &mut device, interrupt, batching).await
+    }
+
+    /// Initializes the device connected through `spi`, and runs it in batching mode, its `INT1`
+    /// pin being connected to `interrupt`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SensorAccess`] if the device cannot be initialized, or if accessing it
+    /// fails.
+    #[cfg(feature = "batching")]
+    pub async fn run_batching_spi<S: SpiDevice, W: Wait>(
+        &'static self,
+        spi: S,
+        interrupt: W,
+        config: Config,
+        batching: batching::Config,
+    ) -> Result<Infallible, Error> {
+        let mut device = Lis3dhDevice {
+            registers: spi_registers(spi),
+            config,
+        };
+        device.init().await?;
+        batching::run(self, &mut device, interrupt, batching).await
+    }
+
+    async fn run<R: Registers>(&self, registers: R, config: Config) -> Result<Infallible, Error> {
+        let mut device = Lis3dhDevice { registers, config };
+        device.init().await?;
+        self.common.run(&mut device).await
+    }
+}
+
+impl Sensor for Lis3dh {
+    fn trigger_measurement(&self) -> Result<(), Error> {
+        self.common.trigger_measurement()
+    }
+
+    fn wait_for_reading(&'static self) -> ReadingWaiter {
+        self.common.wait_for_reading()
+    }
+
+    fn set_mode(&self, mode: Mode) -> Result<State, Error> {
+        self.common.set_mode(mode)
+    }
+
+    fn state(&self) -> State {
+        self.common.state()
+    }
+
+    fn categories(&self) -> &'static [Category] {
+        &[Category::Accelerometer]
+    }
+
+    fn reading_channels(&self) -> &'static [ReadingChannel] {
+        &CHANNELS
+    }
+
+    fn label(&self) -> Option<&'static str> {
+        self.common.label()
+    }
+
+    fn display_name(&self) -> Option<&'static str> {
+        Some("3-axis accelerometer")
+    }
+
+    fn part_number(&self) -> Option<&'static str> {
+        Some("LIS3DH")
+    }
+}
+
+fn i2c_registers<I: I2c>(i2c: I, config: Config) -> I2cRegisters<I> {
+    I2cRegisters {
+        i2c,
+        address: config.address,
+        auto_increment: 0x80,
+    }
+}
+
+fn spi_registers<S: SpiDevice>(spi: S) -> SpiRegisters<S> {
+    SpiRegisters {
+        spi,
+        auto_increment: 0x40,
+        dummy_byte: false,
+    }
+}
+
+struct Lis3dhDevice<R> {
+    registers: R,
+    config: Config,
+}
+
+impl<R: Registers> Lis3dhDevice<R> {
+    async fn init(&mut self) -> Result<(), Error> {
+        if self.registers.read_u8(REG_WHO_AM_I).await? != DEVICE_ID {
+            return Err(Error::SensorAccess);
+        }
+        self.registers
+            .write(REG_CTRL_REG4, BDU | self.config.range.bits() << 4 | HR)
+            .await
+    }
+
+    /// Powers the device down, or makes it measure at the configured data rate.
+    async fn power(&mut self, on: bool) -> Result<(), Error> {
+        let data_rate = if on { self.config.data_rate.bits() } else { 0 };
+        self.registers
+            .write(REG_CTRL_REG1, data_rate << 4 | XYZ_ENABLE)
+            .await
+    }
+
+    async fn read_acceleration(&mut self) -> ReadingResult {
+        let mut data = [0; 6];
+        self.registers.read(REG_OUT_X_L, &mut data).await?;
+        self.samples(data)
+    }
+
+    /// Returns the samples of the output registers `data`.
+    fn samples(&self, data: [u8; 6]) -> ReadingResult {
+        let (axes, _) = data.as_chunks::<2>();
+        let sensitivity = self.config.range.sensitivity();
+        let mut samples = [Sample::new(0, Accuracy::Unknown); 3];
+        for (sample, axis) in samples.iter_mut().zip(axes) {
+            // Left-justified 12-bit values in high-resolution mode.
+            let milli_g = i32::from(i16::from_le_bytes(*axis) >> 4) * sensitivity;
+            // 1 g = 9.80665 m/s²
+            let value = i64::from(milli_g) * 980_665 / 100_000;
+            *sample = Sample::new(
+                i32::try_from(value).map_err(|_| Error::SensorAccess)?,
+                Accuracy::SymmetricalError {
+                    // Typical zero-g offset of ±40 mg.
+                    deviation: 392,
+                    bias: 0,
+                    scaling: -3,
+                },
+            );
+        }
+        Ok(Samples::from_array(samples))
+    }
+
+    async fn is_data_available(&mut self) -> Result<bool, Error> {
+        Ok(self.registers.read_u8(REG_STATUS_REG).await? & ZYXDA != 0)
+    }
+}
+
+impl<R: Registers> Device for Lis3dhDevice<R> {
+    async fn apply_mode(&mut self, mode: Mode) -> Result<(), Error> {
+        self.power(mode == Mode::Triggered).await
+    }
+
+    async fn measure(&mut self) -> ReadingResult {
+        self.power(true).await?;
+        let period = self.config.data_rate.period();
+        Timer::after(period * TURN_ON_PERIODS).await;
+        while !self.is_data_available().await? {
+            Timer::after(period).await;
+        }
+        let reading = self.read_acceleration().await;
+        self.power(false).await?;
+        reading
+    }
+
+    async fn poll(&mut self) -> Option<ReadingResult> {
+        match self.is_data_available().await {
+            Ok(true) => Some(self.read_acceleration().await),
+            Ok(false) => None,
+            Err(error) => Some(Err(error)),
+        }
+    }
+
+    fn poll_interval(&self) -> Duration {
+        self.config.data_rate.period()
+    }
+}
+
+#[cfg(feature = "batching")]
+mod fifo {
+    use embassy_time::Duration;
+
+    use super::{Lis3dhDevice, REG_OUT_X_L};
+    use crate::{
+        Error, Samples,
+        batching::{FifoDevice, MAX_SIZE},
+        drivers::registers::Registers,
+    };
+
+    const REG_CTRL_REG3: u8 = 0x22;
+    const REG_CTRL_REG5: u8 = 0x24;
+    const REG_FIFO_CTRL_REG: u8 = 0x2e;
+    const REG_FIFO_SRC_REG: u8 = 0x2f;
+
+    /// Number of readings the FIFO holds.
+    const FIFO_SIZE: usize = 32;
+    /// Length of a reading in the FIFO.
+    const READING_LEN: usize = 6;
+
+    /// FIFO watermark interrupt on `INT1`, in `CTRL_REG3`.
+    const I1_WTM: u8 = 1 << 2;
+    /// FIFO enabled, in `CTRL_REG5`.
+    const FIFO_EN: u8 = 1 << 6;
+    /// Bypass mode, which empties the FIFO, in the `FM` bits of `FIFO_CTRL_REG`.
+    const FM_BYPASS: u8 = 0b00 << 6;
+    /// Stream mode, in which the oldest readings are overwritten once the FIFO is full, in the
+    /// `FM` bits of `FIFO_CTRL_REG`.
+    const FM_STREAM: u8 = 0b10 << 6;
+    /// FIFO overrun, in `FIFO_SRC_REG`.
+    const OVRN_FIFO: u8 = 1 << 6;
+    /// FIFO empty, in `FIFO_SRC_REG`.
+    const EMPTY: u8 = 1 << 5;
+    /// Number of unread readings, in `FIFO_SRC_REG`.
+    const FSS_MASK: u8 = 0b1_1111;
+
+    impl<R: Registers> FifoDevice for Lis3dhDevice<R> {
+        fn reading_period(&self) -> Duration {
+            self.config.data_rate.period()
+        }
+
+        async fn start_batching(&mut self, size: usize) -> Result<(), Error> {
+            let [threshold, ..] = (size.clamp(1, FIFO_SIZE) - 1).to_le_bytes();
+            self.registers.write(REG_FIFO_CTRL_REG, FM_BYPASS).await?;
+            self.registers.write(REG_CTRL_REG5, FIFO_EN).await?;
+            self.registers
+                .write(REG_FIFO_CTRL_REG, FM_STREAM | threshold)
+                .await?;
+            self.registers.write(REG_CTRL_REG3, I1_WTM).await?;
+            self.power(true).await
+        }
+
+        async fn drain_fifo(
+            &mut self,
+            readings: &mut heapless::Vec<Samples, MAX_SIZE>,
+        ) -> Result<bool, Error> {
+            let source = self.registers.read_u8(REG_FIFO_SRC_REG).await?;
+            let overrun = source & OVRN_FIFO != 0;
+            let count = if source & EMPTY != 0 {
+                0
+            } else if overrun {
+                FIFO_SIZE
+            } else {
+                usize::from(source & FSS_MASK)
+            };
+            let count = count.min(readings.capacity() - readings.len());
+            let mut data = [0; READING_LEN * FIFO_SIZE];
+            let Some(data) = data.get_mut(..count * READING_LEN) else {
+                return Ok(overrun);
+            };
+            if data.is_empty() {
+                return Ok(overrun);
+            }
+            // The address wraps around to `OUT_X_L` after `OUT_Z_H` while the FIFO is enabled, so
+            // that the FIFO is drained in a single burst.
+            self.registers.read(REG_OUT_X_L, data).await?;
+            let (chunks, _) = data.as_chunks::<READING_LEN>();
+            for chunk in chunks {
+                // Cannot fail, the number of readings was limited to the capacity.
+                let _ = readings.push(self.samples(*chunk)?);
+            }
+            Ok(overrun)
+        }
+    }
+}
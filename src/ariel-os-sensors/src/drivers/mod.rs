@@ -0,0 +1,199 @@
+//! Provides drivers for common sensor devices.
+//!
+//! Each driver provides a sensor type implementing [`Sensor`](crate::Sensor), which is
+//! instantiated as a `static` and registered with [`register_sensor!`](crate::register_sensor).
+//! The driver runs through the `run()` method of the sensor, which is given the bus the device is
+//! connected to, eg. an `ariel_os::i2c::controller::I2cDevice`:
+//!
+//! ```ignore
+//! static SENSOR: Sht4x = Sht4x::new(Some("indoor"));
+//! ariel_os::sensors::register_sensor!(SENSOR);
+//!
+//! #[ariel_os::task(autostart, peripherals)]
+//! async fn sensors(peripherals: pins::Peripherals) {
+//!     let i2c_bus = pins::SensorI2c::new(peripherals.i2c_sda, peripherals.i2c_scl, i2c_config);
+//!     let _ = I2C_BUS.set(Mutex::new(i2c_bus));
+//!     let i2c_device = I2cDevice::new(I2C_BUS.get().unwrap());
+//!     let _ = SENSOR.run(i2c_device, sht4x::Config::default()).await;
+//! }
+//! ```
+//!
+//! Once initialized, sensors are in [`Mode::OneShot`]. Each driver is enabled through the
+//! feature, and the laze module `sensor-<driver>`, of the same name as its module.
+
+#![expect(
+    clippy::missing_errors_doc,
+    reason = "the device functions of the drivers fail when accessing the device fails"
+)]
+
+#[cfg(feature = "analog")]
+pub mod analog;
+#[cfg(feature = "bme280")]
+pub mod bme280;
+#[cfg(feature = "bmi270")]
+pub mod bmi270;
+#[cfg(feature = "bmp390")]
+pub mod bmp390;
+#[cfg(feature = "lis3dh")]
+pub mod lis3dh;
+#[cfg(feature = "mpu6050")]
+pub mod mpu6050;
+#[cfg(any(
+    feature = "bme280",
+    feature = "bmi270",
+    feature = "bmp390",
+    feature = "lis3dh",
+    feature = "mpu6050"
+))]
+mod registers;
+#[cfg(feature = "scd4x")]
+pub mod scd4x;
+#[cfg(any(feature = "scd4x", feature = "sht4x"))]
+mod sensirion;
+#[cfg(feature = "sht4x")]
+pub mod sht4x;
+
+use core::{
+    convert::Infallible,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use embassy_futures::select::{Either, select};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::{Duration, Timer};
+
+use crate::{Error, Mode, ReadingResult, ReadingWaiter, State, signaling::Signaling};
+
+/// The device-specific part of a driver, which is run by [`Common::run()`].
+pub(crate) trait Device {
+    /// Sets the device up for `mode`.
+    async fn apply_mode(&mut self, mode: Mode) -> Result<(), Error>;
+
+    /// Measures in [`Mode::OneShot`].
+    async fn measure(&mut self) -> ReadingResult;
+
+    /// Returns the reading of the device in [`Mode::Triggered`], if a new one is available.
+    async fn poll(&mut self) -> Option<ReadingResult> {
+        None
+    }
+
+    /// Returns the interval at which the device is [polled](Device::poll) in
+    /// [`Mode::Triggered`].
+    fn poll_interval(&self) -> Duration {
+        Duration::MAX
+    }
+}
+
+/// The device-independent part of a driver: its state, and the signaling of its readings.
+pub(crate) struct Common {
+    label: Option<&'static str>,
+    modes: &'static [Mode],
+    state: AtomicU8,
+    signaling: Signaling,
+    mode_changed: Signal<CriticalSectionRawMutex, ()>,
+}
+
+impl Common {
+    /// Creates the state of a sensor supporting `modes`, besides [`Mode::Disabled`].
+    pub(crate) const fn new(label: Option<&'static str>, modes: &'static [Mode]) -> Self {
+        Self {
+            label,
+            modes,
+            state: AtomicU8::new(State::Uninitialized as u8),
+            signaling: Signaling::new(),
+            mode_changed: Signal::new(),
+        }
+    }
+
+    pub(crate) fn label(&self) -> Option<&'static str> {
+        self.label
+    }
+
+    pub(crate) fn state(&self) -> State {
+        match self.state.load(Ordering::Acquire) {
+            x if x == State::Disabled as u8 => State::Disabled,
+            x if x == State::OneShot as u8 => State::OneShot,
+            x if x == State::Triggered as u8 => State::Triggered,
+            _ => State::Uninitialized,
+        }
+    }
+
+    fn set_state(&self, state: State) {
+        self.state.store(state as u8, Ordering::Release);
+    }
+
+    pub(crate) fn trigger_measurement(&self) -> Result<(), Error> {
+        match self.state() {
+            State::Uninitialized => Err(Error::Uninitialized),
+            State::Disabled => Err(Error::Disabled),
+            State::OneShot => {
+                self.signaling.trigger_measurement();
+                Ok(())
+            }
+            // The next reading is measured by the device anyway.
+            State::Triggered => Ok(()),
+        }
+    }
+
+    pub(crate) fn wait_for_reading(&'static self) -> ReadingWaiter {
+        match self.state() {
+            State::Uninitialized => ReadingWaiter::err(Error::Uninitialized),
+            State::Disabled => ReadingWaiter::err(Error::Disabled),
+            State::OneShot | State::Triggered => self.signaling.wait_for_reading(),
+        }
+    }
+
+    pub(crate) fn set_mode(&self, mode: Mode) -> Result<State, Error> {
+        if self.state() == State::Uninitialized {
+            return Err(Error::Uninitialized);
+        }
+        if mode != Mode::Disabled && !self.modes.contains(&mode) {
+            return Err(Error::ModeNotSupported);
+        }
+        let state = State::from(mode);
+        self.set_state(state);
+        self.mode_changed.signal(());
+        Ok(state)
+    }
+
+    /// Runs `device`, once it was initialized, until it fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of [`Device::apply_mode()`] if setting up the device failed.
+    pub(crate) async fn run<D: Device>(&self, device: &mut D) -> Result<Infallible, Error> {
+        let mut applied = Mode::Disabled;
+        self.set_state(State::OneShot);
+        loop {
+            let mode = match self.state() {
+                State::OneShot => Mode::OneShot,
+                State::Triggered => Mode::Triggered,
+                State::Uninitialized | State::Disabled => Mode::Disabled,
+            };
+            if mode != applied {
+                if let Err(error) = device.apply_mode(mode).await {
+                    self.set_state(State::Uninitialized);
+                    return Err(error);
+                }
+                applied = mode;
+            }
+            match mode {
+                Mode::Disabled => self.mode_changed.wait().await,
+                Mode::OneShot => {
+                    let trigger = self.signaling.wait_for_trigger();
+                    if let Either::First(()) = select(trigger, self.mode_changed.wait()).await {
+                        self.signaling.signal_reading(device.measure().await);
+                    }
+                }
+                Mode::Triggered => {
+                    let interval = Timer::after(device.poll_interval());
+                    if let Either::First(()) = select(interval, self.mode_changed.wait()).await
+                        && let Some(reading) = device.poll().await
+                    {
+                        self.signaling.signal_reading(reading);
+                    }
+                }
+            }
+        }
+    }
+}
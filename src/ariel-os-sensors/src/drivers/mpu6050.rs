@@ -0,0 +1,481 @@
+//! Driver for the TDK `InvenSense` MPU-6050 6-axis IMU (and the compatible MPU-6500 and MPU-9250
+//! class of devices), connected through I2C.
+//!
+//! Readings consist of the acceleration along the X, Y and Z axes, in thousandths of m/s²,
+//! followed by the angular velocity around the X, Y and Z axes, in thousandths of °/s.
+//!
+//! In [`Mode::Triggered`], the device measures at the configured sample rate. In
+//! [`Mode::OneShot`], it sleeps between measurements.
+//! With the `fusion` feature, the device can also provide its orientation through
+//! [`run_fusion()`].
+
+use core::convert::Infallible;
+
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::i2c::I2c;
+
+use super::{
+    Common, Device,
+    registers::{I2cRegisters, Registers},
+};
+use crate::{
+    Accuracy, Category, Error, Label, MeasurementUnit, Mode, ReadingChannel, ReadingResult,
+    ReadingWaiter, Sample, Samples, Sensor, State,
+};
+
+const REG_SMPLRT_DIV: u8 = 0x19;
+const REG_CONFIG: u8 = 0x1a;
+const REG_GYRO_CONFIG: u8 = 0x1b;
+const REG_ACCEL_CONFIG: u8 = 0x1c;
+const REG_INT_STATUS: u8 = 0x3a;
+const REG_ACCEL_XOUT_H: u8 = 0x3b;
+const REG_PWR_MGMT_1: u8 = 0x6b;
+const REG_WHO_AM_I: u8 = 0x75;
+
+/// Device identifiers of the MPU-6050, MPU-6500 and MPU-9250.
+const DEVICE_IDS: [u8; 3] = [0x68, 0x70, 0x71];
+
+/// Device reset, in `PWR_MGMT_1`.
+const DEVICE_RESET: u8 = 1 << 7;
+/// Sleep mode, in `PWR_MGMT_1`.
+const SLEEP: u8 = 1 << 6;
+/// Clock source: PLL with the X axis gyroscope, in `PWR_MGMT_1`.
+const CLKSEL_PLL: u8 = 0b001;
+/// Digital low-pass filter at 44 Hz, which makes the gyroscope output at 1 kHz, in `CONFIG`.
+const DLPF_44HZ: u8 = 3;
+/// New data available, in `INT_STATUS`.
+const DATA_RDY_INT: u8 = 1;
+
+/// Output rate of the gyroscope with the digital low-pass filter enabled, in Hz.
+const GYRO_OUTPUT_RATE: u32 = 1000;
+
+/// Duration the device needs to reset, in milliseconds.
+const RESET_MS: u64 = 100;
+/// Duration the gyroscope needs to wake up, in milliseconds.
+const WAKE_UP_MS: u64 = 35;
+
+const CHANNELS: [ReadingChannel; 6] = [
+    ReadingChannel::new(Label::X, -3, MeasurementUnit::MeterPerSecondSquared),
+    ReadingChannel::new(Label::Y, -3, MeasurementUnit::MeterPerSecondSquared),
+    ReadingChannel::new(Label::Z, -3, MeasurementUnit::MeterPerSecondSquared),
+    ReadingChannel::new(Label::X, -3, MeasurementUnit::DegreePerSecond),
+    ReadingChannel::new(Label::Y, -3, MeasurementUnit::DegreePerSecond),
+    ReadingChannel::new(Label::Z, -3, MeasurementUnit::DegreePerSecond),
+];
+
+/// Measurement range of the accelerometer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AccelRange {
+    /// ±2 g.
+    G2,
+    /// ±4 g.
+    G4,
+    /// ±8 g.
+    G8,
+    /// ±16 g.
+    G16,
+}
+
+impl AccelRange {
+    /// Returns the value of the `AFS_SEL` bits of `ACCEL_CONFIG`.
+    fn bits(self) -> u8 {
+        match self {
+            Self::G2 => 0,
+            Self::G4 => 1,
+            Self::G8 => 2,
+            Self::G16 => 3,
+        }
+    }
+
+    /// Returns the sensitivity, in digits per g.
+    fn sensitivity(self) -> i32 {
+        match self {
+            Self::G2 => 16384,
+            Self::G4 => 8192,
+            Self::G8 => 4096,
+            Self::G16 => 2048,
+        }
+    }
+}
+
+/// Measurement range of the gyroscope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GyroRange {
+    /// ±250 °/s.
+    Dps250,
+    /// ±500 °/s.
+    Dps500,
+    /// ±1000 °/s.
+    Dps1000,
+    /// ±2000 °/s.
+    Dps2000,
+}
+
+impl GyroRange {
+    /// Returns the value of the `FS_SEL` bits of `GYRO_CONFIG`.
+    fn bits(self) -> u8 {
+        match self {
+            Self::Dps250 => 0,
+            Self::Dps500 => 1,
+            Self::Dps1000 => 2,
+            Self::Dps2000 => 3,
+        }
+    }
+
+    /// Returns the sensitivity, in tenths of digit per °/s.
+    fn sensitivity(self) -> i32 {
+        match self {
+            Self::Dps250 => 1310,
+            Self::Dps500 => 655,
+            Self::Dps1000 => 328,
+            Self::Dps2000 => 164,
+        }
+    }
+}
+
+/// Configuration of an MPU-6050 sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// I2C address of the device, which depends on its `AD0` pin.
+    pub address: u8,
+    /// Measurement range of the accelerometer.
+    pub accel_range: AccelRange,
+    /// Measurement range of the gyroscope.
+    pub gyro_range: GyroRange,
+    /// Sample rate, in Hz, between 4 and 1000; the device uses the closest rate dividing 1 kHz.
+    pub sample_rate_hz: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address: 0x68,
+            accel_range: AccelRange::G2,
+            gyro_range: GyroRange::Dps250,
+            sample_rate_hz: 100,
+        }
+    }
+}
+
+impl Config {
+    /// Returns the value of `SMPLRT_DIV`.
+    fn sample_rate_divider(self) -> u8 {
+        let divider = GYRO_OUTPUT_RATE / self.sample_rate_hz.clamp(4, GYRO_OUTPUT_RATE);
+        u8::try_from(divider - 1).unwrap_or(u8::MAX)
+    }
+
+    /// Returns the interval between measurements.
+    fn sample_period(self) -> Duration {
+        Duration::from_hz(u64::from(GYRO_OUTPUT_RATE)) * (u32::from(self.sample_rate_divider()) + 1)
+    }
+}
+
+/// An MPU-6050 sensor.
+pub struct Mpu6050 {
+    common: Common,
+}
+
+impl Mpu6050 {
+    /// Creates an MPU-6050 sensor, labeled `label`.
+    #[must_use]
+    pub const fn new(label: Option<&'static str>) -> Self {
+        Self {
+            common: Common::new(label, &[Mode::OneShot, Mode::Triggered]),
+        }
+    }
+
+    /// Initializes the device connected through `i2c`, and runs the driver.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SensorAccess`] if the device cannot be initialized.
+    pub async fn run<I: I2c>(&self, i2c: I, config: Config) -> Result<Infallible, Error> {
+        let mut device = Mpu6050Device::new(i2c, config);
+        device.init().await?;
+        self.common.run(&mut device).await
+    }
+}
+
+impl Sensor for Mpu6050 {
+    fn trigger_measurement(&self) -> Result<(), Error> {
+        self.common.trigger_measurement()
+    }
+
+    fn wait_for_reading(&'static self) -> ReadingWaiter {
+        self.common.wait_for_reading()
+    }
+
+    fn set_mode(&self, mode: Mode) -> Result<State, Error> {
+        self.common.set_mode(mode)
+    }
+
+    fn state(&self) -> State {
+        self.common.state()
+    }
+
+    fn categories(&self) -> &'static [Category] {
+        &[Category::Accelerometer, Category::Gyroscope]
+    }
+
+    fn reading_channels(&self) -> &'static [ReadingChannel] {
+        &CHANNELS
+    }
+
+    fn label(&self) -> Option<&'static str> {
+        self.common.label()
+    }
+
+    fn display_name(&self) -> Option<&'static str> {
+        Some("6-axis IMU")
+    }
+
+    fn part_number(&self) -> Option<&'static str> {
+        Some("MPU-6050")
+    }
+}
+
+/// Initializes the device connected through `i2c`, and runs the fusion of its measurements into
+/// `fusion`.
+///
+/// # Errors
+///
+/// Returns [`Error::SensorAccess`] if the device cannot be initialized, or accessing it fails.
+#[cfg(feature = "fusion")]
+pub async fn run_fusion<I: I2c>(
+    i2c: I,
+    config: Config,
+    fusion: &crate::fusion::Fusion,
+    fusion_config: crate::fusion::Config,
+) -> Result<Infallible, Error> {
+    let mut device = Mpu6050Device::new(i2c, config);
+    device.init().await?;
+    device.power(true).await?;
+    crate::fusion::run(&mut device, fusion, fusion_config).await
+}
+
+struct Mpu6050Device<I> {
+    registers: I2cRegisters<I>,
+    config: Config,
+}
+
+impl<I: I2c> Mpu6050Device<I> {
+    fn new(i2c: I, config: Config) -> Self {
+        Self {
+            registers: I2cRegisters {
+                i2c,
+                address: config.address,
+                auto_increment: 0,
+            },
+            config,
+        }
+    }
+
+    async fn init(&mut self) -> Result<(), Error> {
+        if !DEVICE_IDS.contains(&self.registers.read_u8(REG_WHO_AM_I).await?) {
+            return Err(Error::SensorAccess);
+        }
+        self.registers.write(REG_PWR_MGMT_1, DEVICE_RESET).await?;
+        Timer::after_millis(RESET_MS).await;
+        self.registers
+            .write(REG_SMPLRT_DIV, self.config.sample_rate_divider())
+            .await?;
+        self.registers.write(REG_CONFIG, DLPF_44HZ).await?;
+        self.registers
+            .write(REG_GYRO_CONFIG, self.config.gyro_range.bits() << 3)
+            .await?;
+        self.registers
+            .write(REG_ACCEL_CONFIG, self.config.accel_range.bits() << 3)
+            .await?;
+        self.power(false).await
+    }
+
+    /// Makes the device sleep, or measure at the configured sample rate.
+    async fn power(&mut self, on: bool) -> Result<(), Error> {
+        let sleep = if on { 0 } else { SLEEP };
+        self.registers
+            .write(REG_PWR_MGMT_1, sleep | CLKSEL_PLL)
+            .await?;
+        if on {
+            Timer::after_millis(WAKE_UP_MS).await;
+        }
+        Ok(())
+    }
+
+    /// Returns the raw accelerometer and gyroscope values.
+    async fn read_raw(&mut self) -> Result<([i16; 3], [i16; 3]), Error> {
+        // Accelerometer, temperature and gyroscope values.
+        let mut data = [0; 14];
+        self.registers.read(REG_ACCEL_XOUT_H, &mut data).await?;
+        let (words, _) = data.as_chunks::<2>();
+        let mut words = words.iter().map(|word| i16::from_be_bytes(*word));
+        let [ax, ay, az, _, gx, gy, gz] =
+            core::array::from_fn(|_| words.next().unwrap_or_default());
+        Ok(([ax, ay, az], [gx, gy, gz]))
+    }
+
+    async fn read_motion(&mut self) -> ReadingResult {
+        let (acceleration, angular_velocity) = self.read_raw().await?;
+        let accel_sensitivity = i64::from(self.config.accel_range.sensitivity());
+        let gyro_sensitivity = i64::from(self.config.gyro_range.sensitivity());
+        let mut samples = [Sample::new(0, Accuracy::Unknown); 6];
+        let (accel_samples, gyro_samples) = samples.split_at_mut(3);
+        for (sample, raw) in accel_samples.iter_mut().zip(acceleration) {
+            // 1 g = 9.80665 m/s²
+            let value = i64::from(raw) * 9_806_650 / accel_sensitivity / 1000;
+            *sample = Sample::new(
+                i32::try_from(value).map_err(|_| Error::SensorAccess)?,
+                Accuracy::SymmetricalError {
+                    // Typical zero-g offset of ±50 mg along X and Y.
+                    deviation: 490,
+                    bias: 0,
+                    scaling: -3,
+                },
+            );
+        }
+        for (sample, raw) in gyro_samples.iter_mut().zip(angular_velocity) {
+            let value = i64::from(raw) * 10_000 / gyro_sensitivity;
+            *sample = Sample::new(
+                i32::try_from(value).map_err(|_| Error::SensorAccess)?,
+                Accuracy::SymmetricalError {
+                    // Typical zero-rate offset of ±20 °/s.
+                    deviation: 20_000,
+                    bias: 0,
+                    scaling: -3,
+                },
+            );
+        }
+        Ok(Samples::from_array(samples))
+    }
+
+    async fn is_data_available(&mut self) -> Result<bool, Error> {
+        Ok(self.registers.read_u8(REG_INT_STATUS).await? & DATA_RDY_INT != 0)
+    }
+}
+
+impl<I: I2c> Device for Mpu6050Device<I> {
+    async fn apply_mode(&mut self, mode: Mode) -> Result<(), Error> {
+        self.power(mode == Mode::Triggered).await
+    }
+
+    async fn measure(&mut self) -> ReadingResult {
+        self.power(true).await?;
+        while !self.is_data_available().await? {
+            Timer::after(self.config.sample_period()).await;
+        }
+        let reading = self.read_motion().await;
+        self.power(false).await?;
+        reading
+    }
+
+    async fn poll(&mut self) -> Option<ReadingResult> {
+        match self.is_data_available().await {
+            Ok(true) => Some(self.read_motion().await),
+            Ok(false) => None,
+            Err(error) => Some(Err(error)),
+        }
+    }
+
+    fn poll_interval(&self) -> Duration {
+        self.config.sample_period()
+    }
+}
+
+#[cfg(feature = "fusion")]
+mod fifo {
+    use embassy_time::Duration;
+    use embedded_hal_async::i2c::I2c;
+
+    use super::{GyroRange, Mpu6050Device, REG_INT_STATUS};
+    use crate::{
+        Error,
+        drivers::registers::Registers,
+        fusion::{FRAMES_PER_READ, FifoImu, Frame, radians_per_second},
+    };
+
+    const REG_FIFO_EN: u8 = 0x23;
+    const REG_USER_CTRL: u8 = 0x6a;
+    const REG_FIFO_COUNT_H: u8 = 0x72;
+    const REG_FIFO_R_W: u8 = 0x74;
+
+    /// Accelerometer and gyroscope values written into the FIFO, in `FIFO_EN`.
+    const FIFO_EN_ACCEL_GYRO: u8 = 0b0111_1000;
+    /// FIFO enabled, in `USER_CTRL`.
+    const USER_CTRL_FIFO_EN: u8 = 1 << 6;
+    /// FIFO reset, in `USER_CTRL`.
+    const USER_CTRL_FIFO_RESET: u8 = 1 << 2;
+    /// FIFO overflow, in `INT_STATUS`.
+    const FIFO_OFLOW_INT: u8 = 1 << 4;
+
+    /// Length of a FIFO frame: the accelerometer values, followed by the gyroscope values.
+    const FRAME_LEN: usize = 12;
+
+    /// Returns the sensitivity of the gyroscope, in digits per °/s.
+    fn gyro_sensitivity(range: GyroRange) -> f32 {
+        match range {
+            GyroRange::Dps250 => 131.,
+            GyroRange::Dps500 => 65.5,
+            GyroRange::Dps1000 => 32.8,
+            GyroRange::Dps2000 => 16.4,
+        }
+    }
+
+    impl<I: I2c> Mpu6050Device<I> {
+        async fn reset_fifo(&mut self) -> Result<(), Error> {
+            self.registers
+                .write(REG_USER_CTRL, USER_CTRL_FIFO_RESET)
+                .await?;
+            self.registers.write(REG_USER_CTRL, USER_CTRL_FIFO_EN).await
+        }
+    }
+
+    impl<I: I2c> FifoImu for Mpu6050Device<I> {
+        fn frame_period(&self) -> Duration {
+            self.config.sample_period()
+        }
+
+        async fn start_fifo(&mut self) -> Result<(), Error> {
+            self.registers
+                .write(REG_FIFO_EN, FIFO_EN_ACCEL_GYRO)
+                .await?;
+            self.reset_fifo().await
+        }
+
+        async fn read_fifo(&mut self, frames: &mut [Frame]) -> Result<usize, Error> {
+            if self.registers.read_u8(REG_INT_STATUS).await? & FIFO_OFLOW_INT != 0 {
+                // The frames cannot be told apart anymore.
+                self.reset_fifo().await?;
+                return Ok(0);
+            }
+            let mut count = [0; 2];
+            self.registers.read(REG_FIFO_COUNT_H, &mut count).await?;
+            let available = usize::from(u16::from_be_bytes(count)) / FRAME_LEN;
+            let count = available.min(frames.len()).min(FRAMES_PER_READ);
+            let mut data = [0; FRAME_LEN * FRAMES_PER_READ];
+            let Some(data) = data.get_mut(..count * FRAME_LEN) else {
+                return Ok(0);
+            };
+            if data.is_empty() {
+                return Ok(0);
+            }
+            // Burst reads of the FIFO register return consecutive FIFO bytes.
+            self.registers.read(REG_FIFO_R_W, data).await?;
+            let sensitivity = gyro_sensitivity(self.config.gyro_range);
+            let (chunks, _) = data.as_chunks::<FRAME_LEN>();
+            for (frame, chunk) in frames.iter_mut().zip(chunks) {
+                let (words, _) = chunk.as_chunks::<2>();
+                let mut values = words
+                    .iter()
+                    .map(|word| f32::from(i16::from_be_bytes(*word)));
+                let mut next = || values.next().unwrap_or_default();
+                frame.acceleration = core::array::from_fn(|_| next());
+                frame.angular_velocity =
+                    core::array::from_fn(|_| radians_per_second(next() / sensitivity));
+            }
+            Ok(count)
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Access to the registers of devices connected through I2C or SPI.
+
+use embedded_hal_async::{
+    i2c::{self, I2c},
+    spi::{Operation, SpiDevice},
+};
+
+use crate::Error;
+
+/// The registers of a device.
+pub(crate) trait Registers {
+    /// Reads the registers starting at `register` into `buf`.
+    async fn read(&mut self, register: u8, buf: &mut [u8]) -> Result<(), Error>;
+
+    /// Writes `value` into `register`.
+    async fn write(&mut self, register: u8, value: u8) -> Result<(), Error>;
+
+    /// Writes `data` into `register`, in a single transfer.
+    #[cfg_attr(not(feature = "bmi270"), expect(dead_code))]
+    async fn write_burst(&mut self, register: u8, data: &[u8]) -> Result<(), Error>;
+
+    /// Reads `register`.
+    async fn read_u8(&mut self, register: u8) -> Result<u8, Error> {
+        let mut value = [0];
+        self.read(register, &mut value).await?;
+        let [value] = value;
+        Ok(value)
+    }
+}
+
+/// The registers of a device connected through I2C.
+pub(crate) struct I2cRegisters<I> {
+    pub(crate) i2c: I,
+    pub(crate) address: u8,
+    /// Set in the register address when reading several registers, on devices that need it.
+    pub(crate) auto_increment: u8,
+}
+
+impl<I: I2c> Registers for I2cRegisters<I> {
+    async fn read(&mut self, register: u8, buf: &mut [u8]) -> Result<(), Error> {
+        let register = if buf.len() > 1 {
+            register | self.auto_increment
+        } else {
+            register
+        };
+        self.i2c
+            .write_read(self.address, &[register], buf)
+            .await
+            .map_err(|_| Error::SensorAccess)
+    }
+
+    async fn write(&mut self, register: u8, value: u8) -> Result<(), Error> {
+        self.i2c
+            .write(self.address, &[register, value])
+            .await
+            .map_err(|_| Error::SensorAccess)
+    }
+
+    async fn write_burst(&mut self, register: u8, data: &[u8]) -> Result<(), Error> {
+        // Adjacent writes are sent without a repeated start.
+        self.i2c
+            .transaction(
+                self.address,
+                &mut [
+                    i2c::Operation::Write(&[register]),
+                    i2c::Operation::Write(data),
+                ],
+            )
+            .await
+            .map_err(|_| Error::SensorAccess)
+    }
+}
+
+/// The registers of a device connected through SPI, whose register addresses have the read flag
+/// in their most significant bit.
+pub(crate) struct SpiRegisters<S> {
+    pub(crate) spi: S,
+    /// Set in the register address when reading several registers, on devices that need it.
+    pub(crate) auto_increment: u8,
+    /// Whether the device sends a dummy byte before the contents of the registers.
+    pub(crate) dummy_byte: bool,
+}
+
+/// Set in the register address to read it through SPI.
+const SPI_READ: u8 = 0x80;
+
+impl<S: SpiDevice> Registers for SpiRegisters<S> {
+    async fn read(&mut self, register: u8, buf: &mut [u8]) -> Result<(), Error> {
+        let register = if buf.len() > 1 {
+            register | self.auto_increment
+        } else {
+            register
+        };
+        let address = [register | SPI_READ];
+        let mut dummy = [0];
+        let dummy_len = usize::from(self.dummy_byte);
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&address),
+                Operation::Read(dummy.get_mut(..dummy_len).unwrap_or_default()),
+                Operation::Read(buf),
+            ])
+            .await
+            .map_err(|_| Error::SensorAccess)
+    }
+
+    async fn write(&mut self, register: u8, value: u8) -> Result<(), Error> {
+        self.spi
+            .write(&[register & !SPI_READ, value])
+            .await
+            .map_err(|_| Error::SensorAccess)
+    }
+
+    async fn write_burst(&mut self, register: u8, data: &[u8]) -> Result<(), Error> {
+        let address = [register & !SPI_READ];
+        self.spi
+            .transaction(&mut [Operation::Write(&address), Operation::Write(data)])
+            .await
+            .map_err(|_| Error::SensorAccess)
+    }
+}
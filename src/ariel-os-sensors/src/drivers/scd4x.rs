@@ -0,0 +1,199 @@
+//! Driver for the Sensirion `SCD4x` CO₂ sensors (SCD40, SCD41), connected through I2C.
+//!
+//! Readings consist of the CO₂ concentration, in parts per million, followed by the temperature,
+//! in hundredths of degree Celsius, and the relative humidity, in hundredths of percent.
+//!
+//! In [`Mode::Triggered`], the device measures every 5 seconds. [`Mode::OneShot`] is only
+//! supported by the SCD41, and takes 5 seconds per measurement.
+
+use core::convert::Infallible;
+
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::i2c::I2c;
+
+use super::{Common, Device, sensirion};
+use crate::{
+    Accuracy, Category, Error, Label, MeasurementUnit, Mode, ReadingChannel, ReadingResult,
+    ReadingWaiter, Sample, Samples, Sensor, State,
+};
+
+const ADDRESS: u8 = 0x62;
+
+const CMD_GET_DATA_READY_STATUS: u16 = 0xe4b8;
+const CMD_GET_SERIAL_NUMBER: u16 = 0x3682;
+const CMD_MEASURE_SINGLE_SHOT: u16 = 0x219d;
+const CMD_READ_MEASUREMENT: u16 = 0xec05;
+const CMD_START_PERIODIC_MEASUREMENT: u16 = 0x21b1;
+const CMD_STOP_PERIODIC_MEASUREMENT: u16 = 0x3f86;
+
+/// Bits of the data ready status that are set when a measurement is available.
+const DATA_READY_MASK: u16 = 0x07ff;
+
+/// Duration of a single-shot measurement, in milliseconds.
+const SINGLE_SHOT_MS: u64 = 5000;
+
+/// Interval of periodic measurements.
+const PERIODIC_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Duration the device needs to stop periodic measurements, in milliseconds.
+const STOP_MS: u64 = 500;
+
+const CHANNELS: [ReadingChannel; 3] = [
+    ReadingChannel::new(Label::Co2, 0, MeasurementUnit::PartsPerMillion),
+    ReadingChannel::new(Label::Temperature, -2, MeasurementUnit::Celsius),
+    ReadingChannel::new(Label::Humidity, -2, MeasurementUnit::Percent),
+];
+
+/// An `SCD4x` sensor.
+pub struct Scd4x {
+    common: Common,
+}
+
+impl Scd4x {
+    /// Creates an `SCD4x` sensor, labeled `label`.
+    #[must_use]
+    pub const fn new(label: Option<&'static str>) -> Self {
+        Self {
+            common: Common::new(label, &[Mode::OneShot, Mode::Triggered]),
+        }
+    }
+
+    /// Initializes the device connected through `i2c`, and runs the driver.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SensorAccess`] if the device cannot be initialized.
+    pub async fn run<I: I2c>(&self, i2c: I) -> Result<Infallible, Error> {
+        let mut device = Scd4xDevice { i2c };
+        device.init().await?;
+        self.common.run(&mut device).await
+    }
+}
+
+impl Sensor for Scd4x {
+    fn trigger_measurement(&self) -> Result<(), Error> {
+        self.common.trigger_measurement()
+    }
+
+    fn wait_for_reading(&'static self) -> ReadingWaiter {
+        self.common.wait_for_reading()
+    }
+
+    fn set_mode(&self, mode: Mode) -> Result<State, Error> {
+        self.common.set_mode(mode)
+    }
+
+    fn state(&self) -> State {
+        self.common.state()
+    }
+
+    fn categories(&self) -> &'static [Category] {
+        &[
+            Category::Gas,
+            Category::RelativeHumidity,
+            Category::Temperature,
+        ]
+    }
+
+    fn reading_channels(&self) -> &'static [ReadingChannel] {
+        &CHANNELS
+    }
+
+    fn label(&self) -> Option<&'static str> {
+        self.common.label()
+    }
+
+    fn display_name(&self) -> Option<&'static str> {
+        Some("CO₂ sensor")
+    }
+
+    fn part_number(&self) -> Option<&'static str> {
+        Some("SCD4x")
+    }
+}
+
+struct Scd4xDevice<I> {
+    i2c: I,
+}
+
+impl<I: I2c> Scd4xDevice<I> {
+    async fn init(&mut self) -> Result<(), Error> {
+        // Periodic measurements may still be running, eg. after a reboot.
+        self.stop().await?;
+        // Checks that the device responds.
+        let [_, _, _]: [u16; 3] =
+            sensirion::read_words(&mut self.i2c, ADDRESS, CMD_GET_SERIAL_NUMBER, 1).await?;
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<(), Error> {
+        sensirion::write_command(&mut self.i2c, ADDRESS, CMD_STOP_PERIODIC_MEASUREMENT).await?;
+        Timer::after_millis(STOP_MS).await;
+        Ok(())
+    }
+
+    async fn read_measurement(&mut self) -> ReadingResult {
+        let [co2, temperature, humidity] =
+            sensirion::read_words(&mut self.i2c, ADDRESS, CMD_READ_MEASUREMENT, 1).await?;
+        let co2 = i32::from(co2);
+        let temperature = sensirion::centi_celsius(temperature, 65536);
+        let humidity = 10000 * i32::from(humidity) / 65536;
+        Ok(Samples::from_array([
+            Sample::new(
+                co2,
+                Accuracy::SymmetricalError {
+                    // ±(50 ppm + 5 % of the reading)
+                    deviation: u16::try_from(50 + co2 / 20).unwrap_or(u16::MAX),
+                    bias: 0,
+                    scaling: 0,
+                },
+            ),
+            Sample::new(
+                temperature,
+                Accuracy::SymmetricalError {
+                    deviation: 80,
+                    bias: 0,
+                    scaling: -2,
+                },
+            ),
+            Sample::new(
+                humidity,
+                Accuracy::SymmetricalError {
+                    deviation: 600,
+                    bias: 0,
+                    scaling: -2,
+                },
+            ),
+        ]))
+    }
+}
+
+impl<I: I2c> Device for Scd4xDevice<I> {
+    async fn apply_mode(&mut self, mode: Mode) -> Result<(), Error> {
+        match mode {
+            Mode::Disabled | Mode::OneShot => self.stop().await,
+            Mode::Triggered => {
+                sensirion::write_command(&mut self.i2c, ADDRESS, CMD_START_PERIODIC_MEASUREMENT)
+                    .await
+            }
+        }
+    }
+
+    async fn measure(&mut self) -> ReadingResult {
+        sensirion::write_command(&mut self.i2c, ADDRESS, CMD_MEASURE_SINGLE_SHOT).await?;
+        Timer::after_millis(SINGLE_SHOT_MS).await;
+        self.read_measurement().await
+    }
+
+    async fn poll(&mut self) -> Option<ReadingResult> {
+        match sensirion::read_words(&mut self.i2c, ADDRESS, CMD_GET_DATA_READY_STATUS, 1).await {
+            Ok([status]) if status & DATA_READY_MASK == 0 => None,
+            Ok(_) => Some(self.read_measurement().await),
+            Err(error) => Some(Err(error)),
+        }
+    }
+
+    fn poll_interval(&self) -> Duration {
+        PERIODIC_INTERVAL
+    }
+}
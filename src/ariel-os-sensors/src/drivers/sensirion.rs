@@ -0,0 +1,62 @@
+//! The command interface shared by Sensirion sensors.
+
+use embassy_time::Timer;
+use embedded_hal_async::i2c::I2c;
+
+use crate::Error;
+
+/// Length of a word read from the device, with its CRC.
+const WORD_LEN: usize = 3;
+
+/// Sends `command` to the device at `address`.
+pub(crate) async fn write_command<I: I2c>(
+    i2c: &mut I,
+    address: u8,
+    command: u16,
+) -> Result<(), Error> {
+    i2c.write(address, &command.to_be_bytes())
+        .await
+        .map_err(|_| Error::SensorAccess)
+}
+
+/// Sends `command` to the device at `address`, and reads the `N` words of its response after
+/// `delay_ms` milliseconds.
+pub(crate) async fn read_words<I: I2c, const N: usize>(
+    i2c: &mut I,
+    address: u8,
+    command: u16,
+    delay_ms: u64,
+) -> Result<[u16; N], Error> {
+    write_command(i2c, address, command).await?;
+    Timer::after_millis(delay_ms).await;
+    let mut buf = [[0; WORD_LEN]; N];
+    i2c.read(address, buf.as_flattened_mut())
+        .await
+        .map_err(|_| Error::SensorAccess)?;
+    let mut words = [0; N];
+    for (word, [msb, lsb, crc]) in words.iter_mut().zip(buf) {
+        if crc8(&[msb, lsb]) != crc {
+            return Err(Error::SensorAccess);
+        }
+        *word = u16::from_be_bytes([msb, lsb]);
+    }
+    Ok(words)
+}
+
+/// Returns the CRC-8 of `data` used by Sensirion sensors (polynomial 0x31, initial value 0xff).
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0xff, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x31
+            }
+        })
+    })
+}
+
+/// Converts a raw temperature to hundredths of degree Celsius, as `-45 + 175 · raw / divisor`.
+pub(crate) fn centi_celsius(raw: u16, divisor: i32) -> i32 {
+    -4500 + 17500 * i32::from(raw) / divisor
+}
@@ -0,0 +1,163 @@
+//! Driver for the Sensirion `SHT4x` temperature and humidity sensors (SHT40, SHT41, SHT43,
+//! SHT45), connected through I2C.
+//!
+//! Readings consist of the temperature, in hundredths of degree Celsius, followed by the relative
+//! humidity, in hundredths of percent.
+
+use core::convert::Infallible;
+
+use embassy_time::Timer;
+use embedded_hal_async::i2c::I2c;
+
+use super::{Common, Device, sensirion};
+use crate::{
+    Accuracy, Category, Error, Label, MeasurementUnit, Mode, ReadingChannel, ReadingResult,
+    ReadingWaiter, Sample, Samples, Sensor, State,
+};
+
+const CMD_MEASURE_HIGH_PRECISION: u16 = 0xfd;
+const CMD_READ_SERIAL_NUMBER: u16 = 0x89;
+const CMD_SOFT_RESET: u16 = 0x94;
+
+/// Maximum duration of a high-precision measurement, in milliseconds.
+const MEASUREMENT_MS: u64 = 9;
+
+const CHANNELS: [ReadingChannel; 2] = [
+    ReadingChannel::new(Label::Temperature, -2, MeasurementUnit::Celsius),
+    ReadingChannel::new(Label::Humidity, -2, MeasurementUnit::Percent),
+];
+
+/// Configuration of an `SHT4x` sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// I2C address of the device, which depends on its part number.
+    pub address: u8,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self { address: 0x44 }
+    }
+}
+
+/// An `SHT4x` sensor.
+pub struct Sht4x {
+    common: Common,
+}
+
+impl Sht4x {
+    /// Creates an `SHT4x` sensor, labeled `label`.
+    #[must_use]
+    pub const fn new(label: Option<&'static str>) -> Self {
+        Self {
+            common: Common::new(label, &[Mode::OneShot]),
+        }
+    }
+
+    /// Initializes the device connected through `i2c`, and runs the driver.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SensorAccess`] if the device cannot be initialized.
+    pub async fn run<I: I2c>(&self, i2c: I, config: Config) -> Result<Infallible, Error> {
+        let mut device = Sht4xDevice {
+            i2c,
+            address: config.address,
+        };
+        device.init().await?;
+        self.common.run(&mut device).await
+    }
+}
+
+impl Sensor for Sht4x {
+    fn trigger_measurement(&self) -> Result<(), Error> {
+        self.common.trigger_measurement()
+    }
+
+    fn wait_for_reading(&'static self) -> ReadingWaiter {
+        self.common.wait_for_reading()
+    }
+
+    fn set_mode(&self, mode: Mode) -> Result<State, Error> {
+        self.common.set_mode(mode)
+    }
+
+    fn state(&self) -> State {
+        self.common.state()
+    }
+
+    fn categories(&self) -> &'static [Category] {
+        &[Category::RelativeHumidity, Category::Temperature]
+    }
+
+    fn reading_channels(&self) -> &'static [ReadingChannel] {
+        &CHANNELS
+    }
+
+    fn label(&self) -> Option<&'static str> {
+        self.common.label()
+    }
+
+    fn display_name(&self) -> Option<&'static str> {
+        Some("temperature and humidity sensor")
+    }
+
+    fn part_number(&self) -> Option<&'static str> {
+        Some("SHT4x")
+    }
+}
+
+struct Sht4xDevice<I> {
+    i2c: I,
+    address: u8,
+}
+
+impl<I: I2c> Sht4xDevice<I> {
+    async fn init(&mut self) -> Result<(), Error> {
+        sensirion::write_command(&mut self.i2c, self.address, CMD_SOFT_RESET).await?;
+        Timer::after_millis(1).await;
+        // Checks that the device responds.
+        let [_, _]: [u16; 2] =
+            sensirion::read_words(&mut self.i2c, self.address, CMD_READ_SERIAL_NUMBER, 1).await?;
+        Ok(())
+    }
+}
+
+impl<I: I2c> Device for Sht4xDevice<I> {
+    async fn apply_mode(&mut self, _mode: Mode) -> Result<(), Error> {
+        // The device sleeps between measurements.
+        Ok(())
+    }
+
+    async fn measure(&mut self) -> ReadingResult {
+        let [temperature, humidity] = sensirion::read_words(
+            &mut self.i2c,
+            self.address,
+            CMD_MEASURE_HIGH_PRECISION,
+            MEASUREMENT_MS,
+        )
+        .await?;
+        let temperature = sensirion::centi_celsius(temperature, 65535);
+        let humidity = (-600 + 12500 * i32::from(humidity) / 65535).clamp(0, 10000);
+        Ok(Samples::from_array([
+            Sample::new(
+                temperature,
+                Accuracy::SymmetricalError {
+                    deviation: 20,
+                    bias: 0,
+                    scaling: -2,
+                },
+            ),
+            Sample::new(
+                humidity,
+                Accuracy::SymmetricalError {
+                    deviation: 180,
+                    bias: 0,
+                    scaling: -2,
+                },
+            ),
+        ]))
+    }
+}
@@ -0,0 +1,489 @@
+//! Provides the orientation of IMUs, fusing their accelerometer and gyroscope measurements.
+//!
+//! IMU drivers that support fusion provide `run_fusion` functions, which set up the
+//! hardware FIFO of the device and drains it in bursts, at the rate given in the [`Config`], so
+//! that the MCU is not woken up for every measurement. Each measurement is fed into a [`Mahony`]
+//! filter, and the resulting orientation is sent to a [`Fusion`] after each burst:
+//!
+//! ```ignore
+//! use ariel_os::sensors::{drivers::mpu6050, fusion::{self, Fusion}};
+//!
+//! static ORIENTATION: Fusion = Fusion::new();
+//!
+//! #[ariel_os::task(autostart, peripherals)]
+//! async fn imu(peripherals: pins::Peripherals) {
+//!     // ...
+//!     let _ = mpu6050::run_fusion(i2c_device, mpu6050::Config::default(), &ORIENTATION, fusion::Config::default()).await;
+//! }
+//!
+//! #[ariel_os::task(autostart)]
+//! async fn app() {
+//!     let mut receiver = ORIENTATION.receiver().unwrap();
+//!     loop {
+//!         let orientation = receiver.changed().await;
+//!         // ...
+//!     }
+//! }
+//! ```
+//!
+//! An IMU used for fusion is not also available as a [`Sensor`](crate::Sensor).
+//!
+//! # Configuration
+//!
+//! The default rate of orientations is configured through the `CONFIG_SENSORS_FUSION_RATE_HZ`
+//! environment variable (default: 50).
+
+use core::convert::Infallible;
+
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    watch::{Receiver as WatchReceiver, Watch},
+};
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::Error;
+
+/// Maximum number of receivers of a [`Fusion`].
+pub const MAX_RECEIVERS: usize = 2;
+
+/// Maximum number of FIFO frames read from the device at once.
+pub(crate) const FRAMES_PER_READ: usize = 16;
+
+const DEFAULT_RATE_HZ: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SENSORS_FUSION_RATE_HZ",
+    50,
+    "default rate of fused IMU orientations, in Hz"
+);
+
+/// Receives the orientations of a [`Fusion`].
+pub type Receiver<'a> = WatchReceiver<'a, CriticalSectionRawMutex, Orientation, MAX_RECEIVERS>;
+
+/// A rotation, as a unit quaternion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Quaternion {
+    w: f32,
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+impl Quaternion {
+    /// The identity rotation.
+    pub const IDENTITY: Self = Self {
+        w: 1.,
+        x: 0.,
+        y: 0.,
+        z: 0.,
+    };
+
+    /// Returns the scalar part of the quaternion.
+    #[must_use]
+    pub const fn w(&self) -> f32 {
+        self.w
+    }
+
+    /// Returns the X component of the vector part of the quaternion.
+    #[must_use]
+    pub const fn x(&self) -> f32 {
+        self.x
+    }
+
+    /// Returns the Y component of the vector part of the quaternion.
+    #[must_use]
+    pub const fn y(&self) -> f32 {
+        self.y
+    }
+
+    /// Returns the Z component of the vector part of the quaternion.
+    #[must_use]
+    pub const fn z(&self) -> f32 {
+        self.z
+    }
+
+    /// Returns the quaternion scaled to unit length.
+    fn normalized(self) -> Self {
+        let norm = self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z;
+        let scale = inv_sqrt(norm);
+        Self {
+            w: self.w * scale,
+            x: self.x * scale,
+            y: self.y * scale,
+            z: self.z * scale,
+        }
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Returns an approximation of `1/√x`, precise to about 10⁻⁶.
+fn inv_sqrt(x: f32) -> f32 {
+    // Initial guess, refined by Newton iterations.
+    let mut y = f32::from_bits(0x5f37_59df - (x.to_bits() >> 1));
+    for _ in 0..3 {
+        y *= 1.5 - 0.5 * x * y * y;
+    }
+    y
+}
+
+/// The Mahony filter, a complementary filter that corrects the integration of the angular
+/// velocity with the direction of gravity given by the acceleration.
+///
+/// The yaw drifts, as it cannot be corrected without a magnetometer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Mahony {
+    kp: f32,
+    ki: f32,
+    quaternion: Quaternion,
+    integral: [f32; 3],
+}
+
+impl Mahony {
+    /// Creates a filter with the proportional gain `kp` and the integral gain `ki`, starting
+    /// from the identity rotation.
+    ///
+    /// Higher gains trust the accelerometer more; an integral gain of `0` disables the
+    /// compensation of the gyroscope bias.
+    #[must_use]
+    pub const fn new(kp: f32, ki: f32) -> Self {
+        Self {
+            kp,
+            ki,
+            quaternion: Quaternion::IDENTITY,
+            integral: [0.; 3],
+        }
+    }
+
+    /// Returns the current orientation.
+    #[must_use]
+    pub const fn quaternion(&self) -> Quaternion {
+        self.quaternion
+    }
+
+    /// Updates the orientation with a measurement of the `acceleration`, in any unit, and the
+    /// `angular_velocity`, in rad/s, `dt` seconds after the previous one, and returns it.
+    pub fn update(
+        &mut self,
+        acceleration: [f32; 3],
+        angular_velocity: [f32; 3],
+        dt: f32,
+    ) -> Quaternion {
+        let Quaternion { w, x, y, z } = self.quaternion;
+        let [mut gx, mut gy, mut gz] = angular_velocity;
+        let [ax, ay, az] = acceleration;
+        let norm = ax * ax + ay * ay + az * az;
+        // Free fall gives no direction of gravity.
+        if norm > 0. {
+            let scale = inv_sqrt(norm);
+            let (ax, ay, az) = (ax * scale, ay * scale, az * scale);
+            // Half of the direction of gravity estimated from the orientation.
+            let vx = x * z - w * y;
+            let vy = w * x + y * z;
+            let vz = w * w - 0.5 + z * z;
+            // Half of the error between both directions.
+            let error = [ay * vz - az * vy, az * vx - ax * vz, ax * vy - ay * vx];
+            if self.ki > 0. {
+                for (integral, error) in self.integral.iter_mut().zip(error) {
+                    *integral += 2. * self.ki * error * dt;
+                }
+            } else {
+                self.integral = [0.; 3];
+            }
+            let [ix, iy, iz] = self.integral;
+            let [ex, ey, ez] = error;
+            gx += ix + 2. * self.kp * ex;
+            gy += iy + 2. * self.kp * ey;
+            gz += iz + 2. * self.kp * ez;
+        }
+        let (gx, gy, gz) = (gx * 0.5 * dt, gy * 0.5 * dt, gz * 0.5 * dt);
+        self.quaternion = Quaternion {
+            w: w - x * gx - y * gy - z * gz,
+            x: x + w * gx + y * gz - z * gy,
+            y: y + w * gy - x * gz + z * gx,
+            z: z + w * gz + x * gy - y * gx,
+        }
+        .normalized();
+        self.quaternion
+    }
+}
+
+impl Default for Mahony {
+    fn default() -> Self {
+        Self::new(0.5, 0.)
+    }
+}
+
+/// Configuration of the fusion of an IMU.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// Rate at which the FIFO of the device is drained and orientations are sent, in Hz.
+    ///
+    /// It needs to be low enough for the measurements of a period to fit into the FIFO of the
+    /// device, and at most its data rate.
+    pub rate_hz: u32,
+    /// The filter the measurements are fed into.
+    pub filter: Mahony,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            rate_hz: u32::try_from(DEFAULT_RATE_HZ).unwrap_or(u32::MAX),
+            filter: Mahony::default(),
+        }
+    }
+}
+
+/// An orientation obtained by a [`Fusion`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Orientation {
+    timestamp: Instant,
+    quaternion: Quaternion,
+}
+
+impl Orientation {
+    /// Returns the instant of the latest measurement the orientation includes.
+    #[must_use]
+    pub fn timestamp(&self) -> Instant {
+        self.timestamp
+    }
+
+    /// Returns the rotation from the reference frame, in which the Z axis points up, to the
+    /// frame of the device.
+    #[must_use]
+    pub fn quaternion(&self) -> Quaternion {
+        self.quaternion
+    }
+}
+
+/// Receives the orientations of an IMU, whose fusion is run by its driver.
+pub struct Fusion {
+    orientation: Watch<CriticalSectionRawMutex, Orientation, MAX_RECEIVERS>,
+}
+
+impl Fusion {
+    /// Creates a fusion, which has no orientation until its driver runs.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            orientation: Watch::new(),
+        }
+    }
+
+    /// Returns the latest orientation, if there is one.
+    pub fn latest(&self) -> Option<Orientation> {
+        self.orientation.try_get()
+    }
+
+    /// Returns a receiver of the orientations, or `None` if there are [`MAX_RECEIVERS`]
+    /// receivers already.
+    pub fn receiver(&self) -> Option<Receiver<'_>> {
+        self.orientation.receiver()
+    }
+}
+
+impl Default for Fusion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A measurement read from the FIFO of an IMU.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Frame {
+    /// Acceleration, in any unit.
+    pub(crate) acceleration: [f32; 3],
+    /// Angular velocity, in rad/s.
+    pub(crate) angular_velocity: [f32; 3],
+}
+
+/// An IMU whose measurements are read from its FIFO.
+pub(crate) trait FifoImu {
+    /// Returns the interval between the frames of the FIFO.
+    fn frame_period(&self) -> Duration;
+
+    /// Empties the FIFO, and starts filling it with measurements.
+    async fn start_fifo(&mut self) -> Result<(), Error>;
+
+    /// Reads frames from the FIFO into `frames`, and returns their number.
+    ///
+    /// Returns less frames than `frames` holds if the FIFO is emptied.
+    async fn read_fifo(&mut self, frames: &mut [Frame]) -> Result<usize, Error>;
+}
+
+/// Runs the fusion of `imu` into `fusion`, until accessing the device fails.
+///
+/// # Errors
+///
+/// Returns the error of `imu` if accessing the device failed.
+#[cfg_attr(not(any(feature = "bmi270", feature = "mpu6050")), expect(dead_code))]
+pub(crate) async fn run<I: FifoImu>(
+    imu: &mut I,
+    fusion: &Fusion,
+    config: Config,
+) -> Result<Infallible, Error> {
+    let mut filter = config.filter;
+    let period = Duration::from_hz(u64::from(config.rate_hz.max(1)));
+    let dt = seconds(imu.frame_period());
+    let sender = fusion.orientation.sender();
+    let mut frames = [Frame::default(); FRAMES_PER_READ];
+    imu.start_fifo().await?;
+    loop {
+        Timer::after(period).await;
+        let timestamp = Instant::now();
+        let mut updated = false;
+        loop {
+            let count = imu.read_fifo(&mut frames).await?;
+            for frame in frames.iter().take(count) {
+                filter.update(frame.acceleration, frame.angular_velocity, dt);
+                updated = true;
+            }
+            if count < frames.len() {
+                break;
+            }
+        }
+        if updated {
+            sender.send(Orientation {
+                timestamp,
+                quaternion: filter.quaternion(),
+            });
+        }
+    }
+}
+
+#[expect(
+    clippy::cast_precision_loss,
+    reason = "frame periods are far below 2^24 µs"
+)]
+fn seconds(duration: Duration) -> f32 {
+    duration.as_micros() as f32 / 1_000_000.
+}
+
+/// Returns `degrees_per_second` in rad/s.
+#[cfg_attr(not(any(feature = "bmi270", feature = "mpu6050")), expect(dead_code))]
+pub(crate) fn radians_per_second(degrees_per_second: f32) -> f32 {
+    degrees_per_second * core::f32::consts::PI / 180.
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use super::*;
+
+    /// Interval between the measurements, in seconds.
+    const DT: f32 = 0.01;
+
+    fn assert_close(actual: Quaternion, expected: Quaternion, tolerance: f32) {
+        let components = |q: Quaternion| [q.w(), q.x(), q.y(), q.z()];
+        for (actual_component, expected_component) in
+            components(actual).into_iter().zip(components(expected))
+        {
+            assert!(
+                (actual_component - expected_component).abs() < tolerance,
+                "{actual:?} differs from {expected:?}"
+            );
+        }
+    }
+
+    /// Returns the acceleration measured at rest by a device rolled by `degrees` about its X
+    /// axis.
+    fn rolled_gravity(degrees: f32) -> [f32; 3] {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        [0., 9.81 * sin, 9.81 * cos]
+    }
+
+    /// Returns the rotation of a device rolled by `degrees` about its X axis.
+    fn rolled(degrees: f32) -> Quaternion {
+        let (sin, cos) = (degrees.to_radians() / 2.).sin_cos();
+        Quaternion {
+            w: cos,
+            x: sin,
+            y: 0.,
+            z: 0.,
+        }
+    }
+
+    fn roll_degrees(quaternion: Quaternion) -> f32 {
+        2. * quaternion.x().atan2(quaternion.w()).to_degrees()
+    }
+
+    #[test]
+    fn inv_sqrt_precision() {
+        for x in [1e-6_f32, 0.25, 1., 2., 96.236_1, 1e6] {
+            let expected = 1. / x.sqrt();
+            assert!((inv_sqrt(x) - expected).abs() < expected * 1e-6, "1/√{x}");
+        }
+    }
+
+    #[test]
+    fn level_device_stays_level() {
+        let mut filter = Mahony::default();
+        for _ in 0..1000 {
+            filter.update(rolled_gravity(0.), [0.; 3], DT);
+        }
+        assert_close(filter.quaternion(), Quaternion::IDENTITY, 1e-6);
+    }
+
+    #[test]
+    fn steady_input_converges() {
+        let mut filter = Mahony::default();
+        for _ in 0..2000 {
+            filter.update(rolled_gravity(30.), [0.; 3], DT);
+        }
+        assert_close(filter.quaternion(), rolled(30.), 1e-3);
+    }
+
+    #[test]
+    fn step_response() {
+        // With the integral gain disabled, the error decays with a time constant of 1/kp.
+        let mut filter = Mahony::new(1., 0.);
+        for _ in 0..100 {
+            filter.update(rolled_gravity(10.), [0.; 3], DT);
+        }
+        let expected = 10. * (1. - (-1_f32).exp());
+        assert!((roll_degrees(filter.quaternion()) - expected).abs() < 0.1);
+
+        for _ in 0..900 {
+            filter.update(rolled_gravity(10.), [0.; 3], DT);
+        }
+        assert!((roll_degrees(filter.quaternion()) - 10.).abs() < 0.01);
+    }
+
+    #[test]
+    fn integrates_angular_velocity() {
+        // A quarter turn about Z, in free fall so that the accelerometer does not correct it.
+        let mut filter = Mahony::default();
+        for _ in 0..100 {
+            filter.update([0.; 3], [0., 0., core::f32::consts::FRAC_PI_2], DT);
+        }
+        let half_turn = core::f32::consts::FRAC_1_SQRT_2;
+        assert_close(
+            filter.quaternion(),
+            Quaternion {
+                w: half_turn,
+                x: 0.,
+                y: 0.,
+                z: half_turn,
+            },
+            1e-4,
+        );
+    }
+
+    #[test]
+    fn compensates_gyroscope_bias() {
+        let mut filter = Mahony::new(1., 0.1);
+        for _ in 0..20_000 {
+            filter.update(rolled_gravity(0.), [0.02, 0., 0.], DT);
+        }
+        assert_close(filter.quaternion(), Quaternion::IDENTITY, 1e-4);
+        let [ix, iy, iz] = filter.integral;
+        assert!((ix + 0.02).abs() < 1e-3 && iy.abs() < 1e-6 && iz.abs() < 1e-6);
+    }
+}
@@ -0,0 +1,56 @@
+/// Labels of the [`ReadingChannel`](crate::ReadingChannel)s of a sensor, which tell apart the
+/// samples of a reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Label {
+    /// The only channel of the sensor.
+    Main,
+    /// Altitude above mean sea level.
+    Altitude,
+    /// CO₂ concentration.
+    Co2,
+    /// Electric current.
+    Current,
+    /// Relative humidity.
+    Humidity,
+    /// Latitude, positive north of the equator.
+    Latitude,
+    /// Light intensity.
+    Light,
+    /// Longitude, positive east of the prime meridian.
+    Longitude,
+    /// Pressure.
+    Pressure,
+    /// Temperature.
+    Temperature,
+    /// Voltage.
+    Voltage,
+    /// X axis.
+    X,
+    /// Y axis.
+    Y,
+    /// Z axis.
+    Z,
+}
+
+impl core::fmt::Display for Label {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Main => write!(f, ""),
+            Self::Altitude => write!(f, "Altitude"),
+            Self::Co2 => write!(f, "CO₂"),
+            Self::Current => write!(f, "Current"),
+            Self::Humidity => write!(f, "Humidity"),
+            Self::Latitude => write!(f, "Latitude"),
+            Self::Light => write!(f, "Light"),
+            Self::Longitude => write!(f, "Longitude"),
+            Self::Pressure => write!(f, "Pressure"),
+            Self::Temperature => write!(f, "Temperature"),
+            Self::Voltage => write!(f, "Voltage"),
+            Self::X => write!(f, "X"),
+            Self::Y => write!(f, "Y"),
+            Self::Z => write!(f, "Z"),
+        }
+    }
+}
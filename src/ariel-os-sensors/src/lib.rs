@@ -0,0 +1,85 @@
+//! Provides a sensor abstraction layer.
+//!
+//! Sensor drivers implement the [`Sensor`] trait, which allows to trigger measurements and obtain
+//! their readings in a portable way. A reading consists of one or more [`Sample`]s, each of which
+//! is described by a [`ReadingChannel`] of the sensor: its [`Label`], its [`MeasurementUnit`],
+//! and the scaling of its values. Samples also carry the [`Accuracy`] of their value.
+//!
+//! Sensors are instantiated by sensor drivers as `static`s, and are registered into the
+//! [`REGISTRY`] with [`register_sensor!`], which allows applications to enumerate all sensors of
+//! the board, eg. all temperature sensors:
+//!
+//! ```ignore
+//! for sensor in REGISTRY.sensors_of(Category::Temperature) {
+//!     if let Ok(samples) = ariel_os::sensors::measure(sensor).await {
+//!         // ...
+//!     }
+//! }
+//! ```
+//!
+//! Which sensors are instantiated depends on the laze configuration: boards select the laze
+//! modules of the drivers for the sensors they carry, which register the sensors at startup.
+//! Drivers for common sensor devices are provided in the `drivers` module, behind features.
+//!
+//! Sensors can be measured periodically through the `sampling` module, calibrated through the
+//! `calibration` module, and their readings served over CoAP through the `coap` module.
+//! The `alerts` module notifies when sampled values cross thresholds, the `batching` module
+//! delivers readings batched in the FIFOs of devices, and the `fusion` module provides the
+//! orientation of IMUs.
+//!
+//! # Configuration
+//!
+//! The maximum number of samples in a reading is configured through the
+//! `CONFIG_SENSORS_MAX_SAMPLES` environment variable (default: 6).
+
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+#[cfg(feature = "alerts")]
+pub mod alerts;
+#[cfg(feature = "batching")]
+pub mod batching;
+#[cfg(feature = "calibration")]
+pub mod calibration;
+mod category;
+#[cfg(feature = "coap")]
+pub mod coap;
+#[cfg(feature = "_drivers")]
+pub mod drivers;
+#[cfg(feature = "fusion")]
+pub mod fusion;
+mod label;
+pub mod registry;
+mod sample;
+#[cfg(feature = "sampling")]
+pub mod sampling;
+mod sensor;
+pub mod signaling;
+
+pub use category::Category;
+pub use label::Label;
+pub use registry::REGISTRY;
+pub use sample::{Accuracy, MAX_SAMPLES, MeasurementUnit, Sample, Samples};
+pub use sensor::{Error, Mode, ReadingChannel, ReadingResult, ReadingWaiter, Sensor, State};
+
+/// Triggers a measurement of `sensor` and waits for its reading.
+///
+/// With the `calibration` feature, the calibration of `sensor` is applied to the reading.
+///
+/// # Errors
+///
+/// Returns the errors of [`Sensor::trigger_measurement()`] and of the reading.
+pub async fn measure(sensor: &'static dyn Sensor) -> ReadingResult {
+    sensor.trigger_measurement()?;
+    let reading = sensor.wait_for_reading().await;
+    #[cfg(feature = "calibration")]
+    let reading = calibration::apply(sensor, reading);
+    reading
+}
+
+#[doc(hidden)]
+pub mod macro_reexports {
+    // Used by `register_sensor`
+    pub use linkme;
+}
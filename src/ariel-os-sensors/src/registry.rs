@@ -0,0 +1,45 @@
+//! Provides the registry of the sensors of the board.
+
+use crate::{Category, Sensor};
+
+/// The sensors registered through [`register_sensor!`](crate::register_sensor).
+#[linkme::distributed_slice]
+pub static SENSOR_REFS: [&'static dyn Sensor] = [..];
+
+/// The registry of all sensors of the board.
+pub static REGISTRY: Registry = Registry { _private: () };
+
+/// Enumerates the registered sensors.
+pub struct Registry {
+    _private: (),
+}
+
+impl Registry {
+    /// Returns an iterator over all registered sensors.
+    pub fn sensors(&self) -> impl ExactSizeIterator<Item = &'static dyn Sensor> {
+        SENSOR_REFS.iter().copied()
+    }
+
+    /// Returns an iterator over the registered sensors that are part of `category`.
+    pub fn sensors_of(&self, category: Category) -> impl Iterator<Item = &'static dyn Sensor> {
+        self.sensors()
+            .filter(move |sensor| sensor.categories().contains(&category))
+    }
+}
+
+/// Registers the sensor `static` into the [`REGISTRY`].
+///
+/// ```ignore
+/// static TEMP_SENSOR: MySensor = MySensor::new(Some("outdoor"));
+/// ariel_os::sensors::register_sensor!(TEMP_SENSOR);
+/// ```
+#[macro_export]
+macro_rules! register_sensor {
+    ($sensor:path) => {
+        const _: () = {
+            #[$crate::macro_reexports::linkme::distributed_slice($crate::registry::SENSOR_REFS)]
+            #[linkme(crate = $crate::macro_reexports::linkme)]
+            static SENSOR_REF: &'static dyn $crate::Sensor = &$sensor;
+        };
+    };
+}
@@ -0,0 +1,170 @@
+/// Maximum number of [`Sample`]s in a reading, configured through the
+/// `CONFIG_SENSORS_MAX_SAMPLES` environment variable.
+pub const MAX_SAMPLES: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SENSORS_MAX_SAMPLES",
+    6,
+    "maximum number of samples in a sensor reading"
+);
+
+/// A value measured by a sensor, along with its accuracy.
+///
+/// The value needs to be scaled and interpreted according to the
+/// [`ReadingChannel`](crate::ReadingChannel) of the sample: a value of `v` stands for
+/// `v·10^scaling` in the unit of the channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Sample {
+    value: i32,
+    accuracy: Accuracy,
+}
+
+impl Sample {
+    /// Creates a sample of `value`, measured with `accuracy`.
+    #[must_use]
+    pub const fn new(value: i32, accuracy: Accuracy) -> Self {
+        Self { value, accuracy }
+    }
+
+    /// Returns the unscaled value of the sample.
+    #[must_use]
+    pub const fn value(&self) -> i32 {
+        self.value
+    }
+
+    /// Returns the accuracy of the value.
+    #[must_use]
+    pub const fn accuracy(&self) -> Accuracy {
+        self.accuracy
+    }
+}
+
+/// The accuracy of a [`Sample`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Accuracy {
+    /// The accuracy is not known.
+    Unknown,
+    /// The value is exact, eg. a count.
+    NoError,
+    /// The measurement error lies within `bias ± deviation`, both scaled by `10^scaling` like
+    /// values.
+    SymmetricalError {
+        /// The maximum deviation from the value.
+        deviation: u16,
+        /// The systematic error of the value.
+        bias: i16,
+        /// The scaling of `deviation` and `bias`.
+        scaling: i8,
+    },
+}
+
+/// The samples of a reading, one per [`ReadingChannel`](crate::ReadingChannel) of the sensor, in
+/// the same order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Samples {
+    samples: [Sample; MAX_SAMPLES],
+    len: usize,
+}
+
+impl Samples {
+    /// Creates a reading from `samples`.
+    ///
+    /// Fails to compile if there are more than [`MAX_SAMPLES`] samples.
+    #[must_use]
+    pub fn from_array<const N: usize>(samples: [Sample; N]) -> Self {
+        const {
+            assert!(
+                N <= MAX_SAMPLES,
+                "too many samples, consider increasing CONFIG_SENSORS_MAX_SAMPLES"
+            );
+        }
+        let mut all = [Sample::new(0, Accuracy::Unknown); MAX_SAMPLES];
+        for (slot, sample) in all.iter_mut().zip(samples) {
+            *slot = sample;
+        }
+        Self {
+            samples: all,
+            len: N,
+        }
+    }
+
+    /// Returns the number of samples.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether there are no samples.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the first sample, which is the only one of single-channel sensors.
+    #[must_use]
+    pub fn first(&self) -> Option<Sample> {
+        self.iter().next()
+    }
+
+    /// Returns an iterator over the samples.
+    #[must_use]
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = Sample> + '_ {
+        self.samples.iter().take(self.len).copied()
+    }
+
+    #[cfg_attr(not(feature = "calibration"), expect(dead_code))]
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut Sample> {
+        self.samples.iter_mut().take(self.len)
+    }
+}
+
+/// Units of measurement of [`Sample`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum MeasurementUnit {
+    /// Electric current, in A.
+    Ampere,
+    /// Temperature, in °C.
+    Celsius,
+    /// Angle, in °; eg. latitude and longitude.
+    Degree,
+    /// Angular velocity, in °/s.
+    DegreePerSecond,
+    /// Illuminance, in lx.
+    Lux,
+    /// Length, in m; eg. altitude.
+    Meter,
+    /// Acceleration, in m/s².
+    MeterPerSecondSquared,
+    /// Pressure, in Pa.
+    Pascal,
+    /// Concentration, in parts per million.
+    PartsPerMillion,
+    /// A ratio, in %; eg. relative humidity.
+    Percent,
+    /// Magnetic flux density, in T.
+    Tesla,
+    /// Electric potential, in V.
+    Volt,
+}
+
+impl core::fmt::Display for MeasurementUnit {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Ampere => write!(f, "A"),
+            Self::Celsius => write!(f, "°C"),
+            Self::Degree => write!(f, "°"),
+            Self::DegreePerSecond => write!(f, "°/s"),
+            Self::Lux => write!(f, "lx"),
+            Self::Meter => write!(f, "m"),
+            Self::MeterPerSecondSquared => write!(f, "m/s²"),
+            Self::Pascal => write!(f, "Pa"),
+            Self::PartsPerMillion => write!(f, "ppm"),
+            Self::Percent => write!(f, "%"),
+            Self::Tesla => write!(f, "T"),
+            Self::Volt => write!(f, "V"),
+        }
+    }
+}
@@ -0,0 +1,415 @@
+//! Provides periodic sampling of sensors.
+//!
+//! Sensors are given a [`Schedule`] through the [`SAMPLER`], which then measures them
+//! periodically, and delivers their timestamped readings to its subscribers:
+//!
+//! ```ignore
+//! use ariel_os::{sensors::sampling::{SAMPLER, Schedule}, time::Duration};
+//!
+//! SAMPLER.set_schedule(&TEMP_SENSOR, Some(Schedule::new(Duration::from_secs(60))))?;
+//!
+//! let mut subscriber = SAMPLER.subscribe()?;
+//! loop {
+//!     let sampled = subscriber.next_message_pure().await;
+//!     // ...
+//! }
+//! ```
+//!
+//! The measurements of sensors that are due within the [jitter](Schedule::with_jitter) of their
+//! schedule are triggered together, so that sensors sharing a bus are measured in one batch
+//! instead of waking the system up for each of them; the drivers serialize their accesses to the
+//! shared bus.
+//! Sensors whose schedule [powers them down](Schedule::with_power_down) are enabled, one after
+//! the other, right before their measurement is triggered, and are disabled again once their
+//! reading was received.
+//!
+//! The sampler is run by the system when the `sensors-sampling` laze module is selected.
+//! With the `alerts` feature, the [alert rules](crate::alerts) are evaluated on each reading.
+//!
+//! # Configuration
+//!
+//! - `CONFIG_SENSORS_SAMPLING_MAX_SENSORS` (default: 8): maximum number of sensors with a
+//!   schedule.
+//! - `CONFIG_SENSORS_SAMPLING_MAX_SUBSCRIBERS` (default: 4): maximum number of subscribers.
+//! - `CONFIG_SENSORS_SAMPLING_QUEUE_SIZE` (default: 4): number of readings queued for each
+//!   subscriber; subscribers that lag behind miss the oldest readings.
+
+use core::cell::RefCell;
+
+use embassy_futures::select::select;
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    pubsub::{self, PubSubChannel},
+    signal::Signal,
+};
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+
+use crate::{Error, Mode, ReadingResult, Sensor, State};
+
+/// Maximum number of sensors with a [`Schedule`], configured through the
+/// `CONFIG_SENSORS_SAMPLING_MAX_SENSORS` environment variable.
+pub const MAX_SENSORS: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SENSORS_SAMPLING_MAX_SENSORS",
+    8,
+    "maximum number of periodically sampled sensors"
+);
+
+/// Maximum number of subscribers of the [`SAMPLER`], configured through the
+/// `CONFIG_SENSORS_SAMPLING_MAX_SUBSCRIBERS` environment variable.
+pub const MAX_SUBSCRIBERS: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SENSORS_SAMPLING_MAX_SUBSCRIBERS",
+    4,
+    "maximum number of subscribers to sampled sensor readings"
+);
+
+/// Number of readings queued for each subscriber, configured through the
+/// `CONFIG_SENSORS_SAMPLING_QUEUE_SIZE` environment variable.
+pub const QUEUE_SIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SENSORS_SAMPLING_QUEUE_SIZE",
+    4,
+    "number of sampled sensor readings queued for each subscriber"
+);
+
+/// The sampler of the system.
+pub static SAMPLER: Sampler = Sampler::new();
+
+/// Receives the readings of the [`SAMPLER`].
+pub type Subscriber = pubsub::Subscriber<
+    'static,
+    CriticalSectionRawMutex,
+    SampledReading,
+    QUEUE_SIZE,
+    MAX_SUBSCRIBERS,
+    0,
+>;
+
+/// Sampling schedule of a sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Schedule {
+    period: Duration,
+    jitter: Duration,
+    power_down: bool,
+}
+
+impl Schedule {
+    /// Creates a schedule measuring every `period`.
+    #[must_use]
+    pub const fn new(period: Duration) -> Self {
+        Self {
+            period,
+            jitter: Duration::from_ticks(0),
+            power_down: false,
+        }
+    }
+
+    /// Allows measuring up to `jitter` before the sensor is due, so that its measurement can be
+    /// batched with the ones of other sensors.
+    #[must_use]
+    pub const fn with_jitter(self, jitter: Duration) -> Self {
+        Self { jitter, ..self }
+    }
+
+    /// Disables the sensor between its measurements if `power_down` is `true`.
+    #[must_use]
+    pub const fn with_power_down(self, power_down: bool) -> Self {
+        Self { power_down, ..self }
+    }
+
+    /// Returns the period of the schedule.
+    #[must_use]
+    pub const fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Returns the jitter of the schedule.
+    #[must_use]
+    pub const fn jitter(&self) -> Duration {
+        self.jitter
+    }
+
+    /// Returns whether the sensor is disabled between its measurements.
+    #[must_use]
+    pub const fn power_down(&self) -> bool {
+        self.power_down
+    }
+}
+
+/// A reading obtained by the [`SAMPLER`].
+#[derive(Clone, Copy)]
+pub struct SampledReading {
+    sensor: &'static dyn Sensor,
+    timestamp: Instant,
+    reading: ReadingResult,
+}
+
+impl SampledReading {
+    /// Returns the sensor the reading was obtained from.
+    #[must_use]
+    pub fn sensor(&self) -> &'static dyn Sensor {
+        self.sensor
+    }
+
+    /// Returns the instant the measurement was triggered at.
+    #[must_use]
+    pub fn timestamp(&self) -> Instant {
+        self.timestamp
+    }
+
+    /// Returns the reading.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the measurement, or [`Error::SensorAccess`] if no reading was received
+    /// within the period of the sensor.
+    pub fn reading(&self) -> ReadingResult {
+        self.reading
+    }
+
+    /// Returns a reading of `sensor`, as if it had been obtained by the [`SAMPLER`].
+    #[cfg(test)]
+    pub(crate) fn new(
+        sensor: &'static dyn Sensor,
+        timestamp: Instant,
+        reading: ReadingResult,
+    ) -> Self {
+        Self {
+            sensor,
+            timestamp,
+            reading,
+        }
+    }
+}
+
+/// Error returned by the [`Sampler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum SamplingError {
+    /// [`MAX_SENSORS`] sensors already have a schedule.
+    TooManySensors,
+    /// [`MAX_SUBSCRIBERS`] subscribers are already subscribed.
+    TooManySubscribers,
+}
+
+impl core::fmt::Display for SamplingError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::TooManySensors => write!(f, "too many sampled sensors"),
+            Self::TooManySubscribers => write!(f, "too many subscribers"),
+        }
+    }
+}
+
+impl core::error::Error for SamplingError {}
+
+struct Entry {
+    sensor: &'static dyn Sensor,
+    schedule: Schedule,
+    next: Instant,
+    latest: Option<SampledReading>,
+}
+
+/// A sensor whose measurement was triggered.
+struct Triggered {
+    sensor: &'static dyn Sensor,
+    schedule: Schedule,
+    waiter: Result<crate::ReadingWaiter, Error>,
+}
+
+/// Measures sensors according to their [`Schedule`].
+pub struct Sampler {
+    entries: Mutex<CriticalSectionRawMutex, RefCell<heapless::Vec<Entry, MAX_SENSORS>>>,
+    schedule_changed: Signal<CriticalSectionRawMutex, ()>,
+    readings:
+        PubSubChannel<CriticalSectionRawMutex, SampledReading, QUEUE_SIZE, MAX_SUBSCRIBERS, 0>,
+}
+
+impl Sampler {
+    const fn new() -> Self {
+        Self {
+            entries: Mutex::new(RefCell::new(heapless::Vec::new())),
+            schedule_changed: Signal::new(),
+            readings: PubSubChannel::new(),
+        }
+    }
+
+    /// Sets the schedule of `sensor`, or stops sampling it if `schedule` is `None`.
+    ///
+    /// A sensor given a new schedule is measured right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SamplingError::TooManySensors`] if [`MAX_SENSORS`] other sensors already have a
+    /// schedule.
+    pub fn set_schedule(
+        &self,
+        sensor: &'static dyn Sensor,
+        schedule: Option<Schedule>,
+    ) -> Result<(), SamplingError> {
+        self.entries.lock(|entries| {
+            let mut entries = entries.borrow_mut();
+            let position = entries
+                .iter()
+                .position(|entry| core::ptr::addr_eq(entry.sensor, sensor));
+            match (position, schedule) {
+                (Some(position), Some(schedule)) => {
+                    if let Some(entry) = entries.get_mut(position) {
+                        entry.schedule = schedule;
+                    }
+                }
+                (Some(position), None) => {
+                    entries.swap_remove(position);
+                }
+                (None, Some(schedule)) => {
+                    let entry = Entry {
+                        sensor,
+                        schedule,
+                        next: Instant::now(),
+                        latest: None,
+                    };
+                    entries
+                        .push(entry)
+                        .map_err(|_| SamplingError::TooManySensors)?;
+                }
+                (None, None) => {}
+            }
+            Ok(())
+        })?;
+        self.schedule_changed.signal(());
+        Ok(())
+    }
+
+    /// Returns the schedule of `sensor`, if it is sampled.
+    pub fn schedule(&self, sensor: &'static dyn Sensor) -> Option<Schedule> {
+        self.entries.lock(|entries| {
+            entries
+                .borrow()
+                .iter()
+                .find(|entry| core::ptr::addr_eq(entry.sensor, sensor))
+                .map(|entry| entry.schedule)
+        })
+    }
+
+    /// Returns the latest reading of `sensor`, if it is sampled and was measured already.
+    pub fn latest(&self, sensor: &'static dyn Sensor) -> Option<SampledReading> {
+        self.entries.lock(|entries| {
+            entries
+                .borrow()
+                .iter()
+                .find(|entry| core::ptr::addr_eq(entry.sensor, sensor))
+                .and_then(|entry| entry.latest)
+        })
+    }
+
+    /// Returns a new subscriber to the readings of the sampled sensors.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SamplingError::TooManySubscribers`] if [`MAX_SUBSCRIBERS`] subscribers are
+    /// already subscribed.
+    pub fn subscribe(&'static self) -> Result<Subscriber, SamplingError> {
+        self.readings
+            .subscriber()
+            .map_err(|_| SamplingError::TooManySubscribers)
+    }
+
+    /// Runs the sampler.
+    ///
+    /// This is called by the system, and must not be called by applications.
+    #[doc(hidden)]
+    pub async fn run(&self) -> ! {
+        let publisher = self.readings.immediate_publisher();
+        loop {
+            let (batch, next) = self.take_due(Instant::now());
+
+            let timestamp = Instant::now();
+            let mut triggered = heapless::Vec::<Triggered, MAX_SENSORS>::new();
+            for (sensor, schedule) in batch {
+                if schedule.power_down && sensor.state() == State::Disabled {
+                    // Ignore the error: the failing trigger reports it.
+                    let _ = sensor.set_mode(Mode::OneShot);
+                }
+                let waiter = sensor
+                    .trigger_measurement()
+                    .map(|()| sensor.wait_for_reading());
+                // Cannot fail, both vectors have the same capacity.
+                let _ = triggered.push(Triggered {
+                    sensor,
+                    schedule,
+                    waiter,
+                });
+            }
+
+            for Triggered {
+                sensor,
+                schedule,
+                waiter,
+            } in triggered
+            {
+                let reading = match waiter {
+                    Ok(waiter) => with_timeout(schedule.period, waiter)
+                        .await
+                        .unwrap_or(Err(Error::SensorAccess)),
+                    Err(error) => Err(error),
+                };
+                #[cfg(feature = "calibration")]
+                let reading = crate::calibration::apply(sensor, reading);
+                if schedule.power_down {
+                    let _ = sensor.set_mode(Mode::Disabled);
+                }
+                let sampled = SampledReading {
+                    sensor,
+                    timestamp,
+                    reading,
+                };
+                self.entries.lock(|entries| {
+                    let mut entries = entries.borrow_mut();
+                    if let Some(entry) = entries
+                        .iter_mut()
+                        .find(|entry| core::ptr::addr_eq(entry.sensor, sensor))
+                    {
+                        entry.latest = Some(sampled);
+                    }
+                });
+                publisher.publish_immediate(sampled);
+                #[cfg(feature = "alerts")]
+                crate::alerts::ALERTS.evaluate(&sampled);
+            }
+
+            select(Timer::at(next), self.schedule_changed.wait()).await;
+        }
+    }
+
+    /// Returns the sensors due at `now`, along with the instant the next sensor is due at.
+    fn take_due(
+        &self,
+        now: Instant,
+    ) -> (
+        heapless::Vec<(&'static dyn Sensor, Schedule), MAX_SENSORS>,
+        Instant,
+    ) {
+        self.entries.lock(|entries| {
+            let mut batch = heapless::Vec::new();
+            let mut next = Instant::MAX;
+            for entry in entries.borrow_mut().iter_mut() {
+                let window_start = entry
+                    .next
+                    .checked_sub(entry.schedule.jitter)
+                    .unwrap_or(Instant::MIN);
+                if window_start <= now {
+                    // Cannot fail, both vectors have the same capacity.
+                    let _ = batch.push((entry.sensor, entry.schedule));
+                    // Do not try to catch up on missed measurements.
+                    entry.next = entry.next.max(now);
+                    entry.next = entry
+                        .next
+                        .checked_add(entry.schedule.period)
+                        .unwrap_or(Instant::MAX);
+                }
+                next = next.min(entry.next);
+            }
+            (batch, next)
+        })
+    }
+}
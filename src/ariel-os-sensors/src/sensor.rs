@@ -0,0 +1,215 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{Category, Label, MeasurementUnit, Samples, signaling::Signaling};
+
+/// The result of a measurement.
+pub type ReadingResult = Result<Samples, Error>;
+
+/// A sensor, as implemented by sensor drivers.
+///
+/// Sensors are used through `&'static dyn Sensor` references, as obtained from the
+/// [`REGISTRY`](crate::REGISTRY). Drivers can rely on [`Signaling`] to implement the
+/// measurement methods.
+pub trait Sensor: Send + Sync {
+    /// Triggers a measurement, whose reading is then obtained through
+    /// [`wait_for_reading()`](Sensor::wait_for_reading).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Uninitialized`] or [`Error::Disabled`] if the sensor cannot measure in its
+    /// current [`State`].
+    fn trigger_measurement(&self) -> Result<(), Error>;
+
+    /// Waits for the reading of the measurement that was triggered last, or in
+    /// [`Mode::Triggered`], for the next reading of the sensor.
+    ///
+    /// Only one task can wait for readings of a sensor at a time.
+    fn wait_for_reading(&'static self) -> ReadingWaiter;
+
+    /// Sets the measurement mode of the sensor, and returns its resulting state.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Uninitialized`] if the sensor is not initialized, and
+    /// [`Error::ModeNotSupported`] if the sensor does not support `mode`.
+    fn set_mode(&self, mode: Mode) -> Result<State, Error>;
+
+    /// Returns the current state of the sensor.
+    fn state(&self) -> State;
+
+    /// Returns the categories the sensor is part of.
+    fn categories(&self) -> &'static [Category];
+
+    /// Returns the channels of the readings of the sensor, in the order of their samples.
+    fn reading_channels(&self) -> &'static [ReadingChannel];
+
+    /// Returns the label of this sensor instance, which tells it apart from other sensors of the
+    /// board, eg. `"outdoor"`.
+    fn label(&self) -> Option<&'static str>;
+
+    /// Returns a human-readable name of the sensor.
+    fn display_name(&self) -> Option<&'static str>;
+
+    /// Returns the part number of the sensor device.
+    fn part_number(&self) -> Option<&'static str>;
+}
+
+/// Describes the samples of a channel of sensor readings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ReadingChannel {
+    label: Label,
+    scaling: i8,
+    unit: MeasurementUnit,
+}
+
+impl ReadingChannel {
+    /// Creates a channel of samples in `unit`, whose values are scaled by `10^scaling`.
+    #[must_use]
+    pub const fn new(label: Label, scaling: i8, unit: MeasurementUnit) -> Self {
+        Self {
+            label,
+            scaling,
+            unit,
+        }
+    }
+
+    /// Returns the label of the channel.
+    #[must_use]
+    pub const fn label(&self) -> Label {
+        self.label
+    }
+
+    /// Returns the scaling of the values of the samples, as a power of ten.
+    #[must_use]
+    pub const fn scaling(&self) -> i8 {
+        self.scaling
+    }
+
+    /// Returns the unit of the samples.
+    #[must_use]
+    pub const fn unit(&self) -> MeasurementUnit {
+        self.unit
+    }
+}
+
+/// Measurement modes of sensors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mode {
+    /// The sensor does not measure, and saves power where possible.
+    Disabled,
+    /// The sensor measures when triggered through [`Sensor::trigger_measurement()`].
+    OneShot,
+    /// The sensor measures by itself, eg. periodically or when its data-ready interrupt fires;
+    /// readings are obtained through [`Sensor::wait_for_reading()`].
+    Triggered,
+}
+
+/// States of sensors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum State {
+    /// The sensor was not initialized by its driver yet, or its initialization failed.
+    Uninitialized,
+    /// The sensor is in [`Mode::Disabled`].
+    Disabled,
+    /// The sensor is in [`Mode::OneShot`].
+    OneShot,
+    /// The sensor is in [`Mode::Triggered`].
+    Triggered,
+}
+
+impl From<Mode> for State {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Disabled => Self::Disabled,
+            Mode::OneShot => Self::OneShot,
+            Mode::Triggered => Self::Triggered,
+        }
+    }
+}
+
+/// Errors of sensors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The sensor is not initialized.
+    Uninitialized,
+    /// The sensor is disabled.
+    Disabled,
+    /// The sensor does not support the requested mode.
+    ModeNotSupported,
+    /// Communicating with the sensor device failed.
+    SensorAccess,
+    /// The sensor has no reading to provide yet, eg. a GNSS receiver without a fix.
+    NotReady,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Uninitialized => write!(f, "sensor not initialized"),
+            Self::Disabled => write!(f, "sensor disabled"),
+            Self::ModeNotSupported => write!(f, "sensor mode not supported"),
+            Self::SensorAccess => write!(f, "sensor access failed"),
+            Self::NotReady => write!(f, "sensor not ready"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Future returned by [`Sensor::wait_for_reading()`], which resolves to the reading.
+#[must_use = "futures do nothing unless polled"]
+pub struct ReadingWaiter {
+    inner: WaiterInner,
+}
+
+enum WaiterInner {
+    Waiting(&'static Signaling),
+    Err(Error),
+    Resolved,
+}
+
+impl ReadingWaiter {
+    /// Returns a future that waits for the reading signaled through `signaling`.
+    pub fn new(signaling: &'static Signaling) -> Self {
+        Self {
+            inner: WaiterInner::Waiting(signaling),
+        }
+    }
+
+    /// Returns a future that resolves to `error` right away, eg. when the sensor is disabled.
+    pub fn err(error: Error) -> Self {
+        Self {
+            inner: WaiterInner::Err(error),
+        }
+    }
+}
+
+impl Future for ReadingWaiter {
+    type Output = ReadingResult;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.inner {
+            WaiterInner::Waiting(signaling) => {
+                let reading = signaling.poll_reading(cx);
+                if reading.is_ready() {
+                    self.inner = WaiterInner::Resolved;
+                }
+                reading
+            }
+            WaiterInner::Err(error) => {
+                self.inner = WaiterInner::Resolved;
+                Poll::Ready(Err(error))
+            }
+            WaiterInner::Resolved => Poll::Pending,
+        }
+    }
+}
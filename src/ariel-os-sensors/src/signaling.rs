@@ -0,0 +1,87 @@
+//! Helpers for sensor drivers.
+//!
+//! A driver typically runs a task per sensor, which waits for measurement triggers, measures, and
+//! signals the reading:
+//!
+//! ```ignore
+//! static SIGNALING: Signaling = Signaling::new();
+//!
+//! loop {
+//!     SIGNALING.wait_for_trigger().await;
+//!     let reading = measure(&mut device).await;
+//!     SIGNALING.signal_reading(reading);
+//! }
+//! ```
+//!
+//! Its [`Sensor`](crate::Sensor) implementation then forwards
+//! [`trigger_measurement()`](crate::Sensor::trigger_measurement) and
+//! [`wait_for_reading()`](crate::Sensor::wait_for_reading) to the [`Signaling`].
+
+use core::{
+    cell::Cell,
+    task::{Context, Poll},
+};
+
+use embassy_sync::{
+    blocking_mutex::{Mutex, raw::CriticalSectionRawMutex},
+    signal::Signal,
+    waitqueue::AtomicWaker,
+};
+
+use crate::{ReadingResult, ReadingWaiter};
+
+/// Passes measurement triggers and readings between a sensor and its driver.
+pub struct Signaling {
+    trigger: Signal<CriticalSectionRawMutex, ()>,
+    reading: Mutex<CriticalSectionRawMutex, Cell<Option<ReadingResult>>>,
+    waker: AtomicWaker,
+}
+
+impl Signaling {
+    /// Creates a new signaling.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            trigger: Signal::new(),
+            reading: Mutex::new(Cell::new(None)),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Triggers a measurement, discarding the reading of the previous one if it was not
+    /// obtained.
+    pub fn trigger_measurement(&self) {
+        self.reading.lock(|reading| reading.set(None));
+        self.trigger.signal(());
+    }
+
+    /// Waits for a measurement to be triggered.
+    pub async fn wait_for_trigger(&self) {
+        self.trigger.wait().await;
+    }
+
+    /// Signals the reading of a measurement to the task waiting for it.
+    pub fn signal_reading(&self, reading: ReadingResult) {
+        self.reading.lock(|cell| cell.set(Some(reading)));
+        self.waker.wake();
+    }
+
+    /// Returns a future that waits for the next reading.
+    pub fn wait_for_reading(&'static self) -> ReadingWaiter {
+        ReadingWaiter::new(self)
+    }
+
+    pub(crate) fn poll_reading(&self, cx: &Context<'_>) -> Poll<ReadingResult> {
+        self.waker.register(cx.waker());
+        match self.reading.lock(Cell::take) {
+            Some(reading) => Poll::Ready(reading),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl Default for Signaling {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,212 @@
+//! Provides a typed registry of the services of the system, eg. the network stack or storage.
+//!
+//! Libraries obtain handles to services with [`get()`], instead of having applications pass them
+//! through function arguments:
+//!
+//! ```ignore
+//! use ariel_os::services::{self, Network};
+//!
+//! let stack = services::get::<Network>().await.unwrap();
+//! ```
+//!
+//! The built-in services only exist when their subsystem is enabled, so that a library requiring a
+//! disabled subsystem fails to build rather than at runtime.
+//!
+//! Libraries and applications declare services of their own with [`service!`], which one of them
+//! provides once with [`provide()`]:
+//!
+//! ```ignore
+//! ariel_os::services::service!(
+//!     /// The MQTT client of the application.
+//!     pub Mqtt: MqttClient
+//! );
+//!
+//! services::provide::<Mqtt>(MqttClient::new(stack)).unwrap();
+//!
+//! // Elsewhere, waits for the client to be provided.
+//! let client: &'static MqttClient = services::get::<Mqtt>().await.unwrap();
+//! ```
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+use embassy_sync::once_lock::OnceLock;
+
+/// A service of the system, identified by its type.
+pub trait Service: 'static {
+    /// The handle through which the service is used.
+    type Handle;
+
+    /// Returns the handle to the service, waiting for the service to be initialized.
+    ///
+    /// Returns `None` if the service is not available from the calling context.
+    fn handle() -> impl Future<Output = Option<Self::Handle>>;
+}
+
+/// A [`Service`] provided once by a library or an application, declared with [`service!`].
+pub trait Provided: Service<Handle = &'static Self::Value> {
+    /// The value provided for the service.
+    type Value: Sync + 'static;
+
+    #[doc(hidden)]
+    fn slot() -> &'static OnceLock<Self::Value>;
+}
+
+/// Returns the handle to the service `S`, waiting for the service to be initialized.
+///
+/// Returns `None` if the service is not available from the calling context, eg. for the network
+/// stack from another executor than the one running it.
+pub async fn get<S: Service>() -> Option<S::Handle> {
+    S::handle().await
+}
+
+/// Returns the handle to the service `S` if it was provided already, without waiting.
+#[must_use]
+pub fn try_get<S: Provided>() -> Option<&'static S::Value> {
+    S::slot().try_get()
+}
+
+/// Provides `value` for the service `S`, which is then returned by [`get()`].
+///
+/// # Errors
+///
+/// Returns [`Error::AlreadyProvided`] if the service was provided already; the previous value is
+/// kept.
+pub fn provide<S: Provided>(value: S::Value) -> Result<(), Error> {
+    S::slot().init(value).map_err(|_| Error::AlreadyProvided)
+}
+
+/// Errors when providing a service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The service was provided already.
+    AlreadyProvided,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::AlreadyProvided => write!(f, "service already provided"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Declares the service `$name`, whose handle is a `&'static $value` once [`provide()`]d.
+///
+/// ```ignore
+/// ariel_os::services::service!(
+///     /// The MQTT client of the application.
+///     pub Mqtt: MqttClient
+/// );
+/// ```
+#[macro_export]
+macro_rules! service {
+    ($(#[$attr:meta])* $vis:vis $name:ident: $value:ty) => {
+        $(#[$attr])*
+        #[derive(Debug)]
+        $vis enum $name {}
+
+        impl $crate::Service for $name {
+            type Handle = &'static $value;
+
+            async fn handle() -> Option<Self::Handle> {
+                Some(<Self as $crate::Provided>::slot().get().await)
+            }
+        }
+
+        impl $crate::Provided for $name {
+            type Value = $value;
+
+            fn slot() -> &'static $crate::macro_reexports::embassy_sync::once_lock::OnceLock<$value> {
+                static SLOT: $crate::macro_reexports::embassy_sync::once_lock::OnceLock<$value> =
+                    $crate::macro_reexports::embassy_sync::once_lock::OnceLock::new();
+                &SLOT
+            }
+        }
+    };
+}
+
+/// The network stack, see [`ariel_os_embassy::net`].
+#[cfg(feature = "net")]
+#[derive(Debug)]
+pub enum Network {}
+
+#[cfg(feature = "net")]
+impl Service for Network {
+    type Handle = ariel_os_embassy::NetworkStack;
+
+    async fn handle() -> Option<Self::Handle> {
+        ariel_os_embassy::net::network_stack().await
+    }
+}
+
+/// The global storage, see [`ariel_os_storage`].
+#[cfg(feature = "storage")]
+#[derive(Debug)]
+pub enum Storage {}
+
+#[cfg(feature = "storage")]
+impl Service for Storage {
+    type Handle = &'static ariel_os_storage::StorageMutex;
+
+    async fn handle() -> Option<Self::Handle> {
+        Some(ariel_os_storage::storage().await)
+    }
+}
+
+/// The registry of the sensors of the board, see [`ariel_os_sensors::registry`].
+#[cfg(feature = "sensors")]
+#[derive(Debug)]
+pub enum Sensors {}
+
+#[cfg(feature = "sensors")]
+impl Service for Sensors {
+    type Handle = &'static ariel_os_sensors::registry::Registry;
+
+    async fn handle() -> Option<Self::Handle> {
+        Some(&ariel_os_sensors::REGISTRY)
+    }
+}
+
+#[doc(hidden)]
+pub mod macro_reexports {
+    // Used by `service`
+    pub use embassy_sync;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Client {
+        id: u8,
+    }
+
+    service!(Mqtt: Client);
+    service!(Other: u32);
+
+    #[test]
+    fn provide_once() {
+        assert!(try_get::<Mqtt>().is_none());
+        assert_eq!(provide::<Mqtt>(Client { id: 1 }), Ok(()));
+        assert_eq!(
+            provide::<Mqtt>(Client { id: 2 }),
+            Err(Error::AlreadyProvided)
+        );
+
+        let client = embassy_futures::block_on(get::<Mqtt>());
+        assert_eq!(client.map(|client| client.id), Some(1));
+        assert_eq!(try_get::<Mqtt>().map(|client| client.id), Some(1));
+    }
+
+    #[test]
+    fn distinct_services() {
+        assert_eq!(provide::<Other>(7), Ok(()));
+        assert_eq!(try_get::<Other>(), Some(&7));
+    }
+}
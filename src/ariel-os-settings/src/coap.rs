@@ -0,0 +1,188 @@
+//! Serves the settings as a CoAP resource, as a uniform remote-configuration surface.
+//!
+//! A GET request returns the schema and the current values of the settings in CBOR with the
+//! Content-Format `application/cbor`, as encoded by [`encode()`](crate::encode()):
+//!
+//! ```text
+//! { "led-brightness": { "type": "int", "value": 50 },
+//!   "device-name": { "type": "text", "max-len": 32, "value": "sensor" } }
+//! ```
+//!
+//! A PUT request with a CBOR update (see the [crate documentation](crate)) changes the settings:
+//!
+//! ```text
+//! { "led-brightness": 20, "device-name": null }
+//! ```
+//!
+//! The update is validated before the response is sent, which is 4.00 Bad Request if any of its
+//! entries is invalid. As applying it accesses storage, it completes shortly after the response
+//! has been sent; controllers can check its outcome with GET requests.
+//!
+//! Access to the resource needs to be limited to the administrators of the device. Applications
+//! running their own CoAP server can add the resource to their handler:
+//!
+//! ```ignore
+//! let handler = new_dispatcher().at(&["settings"], SettingsResource::new());
+//! ```
+
+use core::cell::RefCell;
+
+use ariel_os_debug::log::{info, warn};
+use coap_message::{
+    Code as _, MinimalWritableMessage, MutableWritableMessage, OptionNumber as _, ReadableMessage,
+};
+use coap_message_utils::{Error as CoAPError, OptionsExt as _};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+
+use crate::MAX_SIZE;
+
+/// CoAP Content-Format of `application/cbor`.
+const CONTENT_FORMAT_CBOR: u16 = 60;
+
+/// The update received last, until it is applied.
+static PENDING: critical_section::Mutex<RefCell<Option<heapless::Vec<u8, MAX_SIZE>>>> =
+    critical_section::Mutex::new(RefCell::new(None));
+
+/// Wakes up [`apply()`] when an update is pending.
+static APPLY: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// A CoAP resource that reports and changes the settings.
+#[derive(Debug, Default)]
+pub struct SettingsResource {
+    _private: (),
+}
+
+impl SettingsResource {
+    /// Creates the resource.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+/// The operation requested on the resource.
+#[derive(Debug, Clone, Copy)]
+pub enum Request {
+    /// Report the settings.
+    Get,
+    /// Change the settings.
+    Put,
+}
+
+impl coap_handler::Handler for SettingsResource {
+    type RequestData = Request;
+    type ExtractRequestError = CoAPError;
+    type BuildResponseError<M: MinimalWritableMessage> = CoAPError;
+
+    fn extract_request_data<M: ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, Self::ExtractRequestError> {
+        let request_data = match request.code().into() {
+            coap_numbers::code::GET => Request::Get,
+            coap_numbers::code::PUT => Request::Put,
+            _ => return Err(CoAPError::method_not_allowed()),
+        };
+        request.options().ignore_elective_others()?;
+
+        if let Request::Put = request_data {
+            stage(request.payload())?;
+        }
+        Ok(request_data)
+    }
+
+    fn estimate_length(&mut self, _request: &Self::RequestData) -> usize {
+        // Content-Format option and payload marker
+        MAX_SIZE + 4
+    }
+
+    fn build_response<M: MutableWritableMessage>(
+        &mut self,
+        response: &mut M,
+        request: Self::RequestData,
+    ) -> Result<(), Self::BuildResponseError<M>> {
+        let code = match request {
+            Request::Get => coap_numbers::code::CONTENT,
+            Request::Put => coap_numbers::code::CHANGED,
+        };
+        response.set_code(M::Code::new(code).map_err(CoAPError::from_unionerror)?);
+        if let Request::Put = request {
+            return Ok(());
+        }
+
+        let mut buffer = [0; MAX_SIZE];
+        let len = crate::encode(&mut buffer).map_err(|_| CoAPError::internal_server_error())?;
+        response
+            .add_option_uint(
+                M::OptionNumber::new(coap_numbers::option::CONTENT_FORMAT)
+                    .map_err(CoAPError::from_unionerror)?,
+                CONTENT_FORMAT_CBOR,
+            )
+            .map_err(CoAPError::from_unionerror)?;
+        response
+            .set_payload(
+                buffer
+                    .get(..len)
+                    .ok_or_else(CoAPError::internal_server_error)?,
+            )
+            .map_err(CoAPError::from_unionerror)?;
+        Ok(())
+    }
+}
+
+/// Validates the update in `payload`, and hands it to [`apply()`].
+///
+/// # Errors
+///
+/// Returns an error if the update is invalid, or if another update is still being applied.
+fn stage(payload: &[u8]) -> Result<(), CoAPError> {
+    crate::check(payload).map_err(|_| CoAPError::bad_request())?;
+    let update = heapless::Vec::from_slice(payload).map_err(|()| CoAPError::bad_request())?;
+    critical_section::with(|cs| {
+        let mut pending = PENDING.borrow_ref_mut(cs);
+        if pending.is_some() {
+            return Err(CoAPError::service_unavailable());
+        }
+        *pending = Some(update);
+        Ok(())
+    })?;
+    APPLY.signal(());
+    Ok(())
+}
+
+/// Applies the updates received through [`SettingsResource`].
+#[ariel_os_macros::task(autostart)]
+async fn apply() {
+    loop {
+        APPLY.wait().await;
+        let Some(update) = critical_section::with(|cs| PENDING.borrow_ref(cs).clone()) else {
+            continue;
+        };
+        match crate::update(&update).await {
+            Ok(()) => info!("settings: applied update"),
+            Err(err) => warn!("settings: applying update failed: {}", err),
+        }
+        critical_section::with(|cs| PENDING.replace(cs, None));
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use coap_handler::Handler as _;
+    use coap_message_implementations::inmemory::Message;
+
+    use super::*;
+
+    #[test]
+    fn invalid_updates_are_rejected() {
+        // `{ "test-enabled": true, "test-level": 42 }`, of which the second entry fails validation.
+        let mut request = std::vec![0xff, 0xa2];
+        request.extend_from_slice(b"\x6ctest-enabled\xf5\x6atest-level\x18\x2a");
+        let result = SettingsResource::new()
+            .extract_request_data(&Message::new(coap_numbers::code::PUT, &request));
+        assert!(result.is_err());
+        let pending = critical_section::with(|cs| PENDING.borrow_ref(cs).is_some());
+        assert!(!pending);
+    }
+}
@@ -0,0 +1,819 @@
+//! Provides typed device settings, which are kept in storage and can be changed at runtime.
+//!
+//! Settings are declared as [`Setting`]s, with a default value, an optional validation, and an
+//! optional callback that is called when their value changes, and registered with
+//! [`register_setting!`]:
+//!
+//! ```ignore
+//! use ariel_os::settings::{Setting, register_setting};
+//!
+//! static BRIGHTNESS: Setting<i64> = Setting::new("led-brightness", || 50)
+//!     .with_validation(|brightness| (0..=100).contains(brightness))
+//!     .with_on_change(|brightness| info!("brightness is now {}", brightness));
+//! register_setting!(BRIGHTNESS);
+//!
+//! #[ariel_os::task(autostart)]
+//! async fn dim() {
+//!     ariel_os::settings::loaded().await;
+//!     info!("brightness is {}", BRIGHTNESS.get());
+//!     BRIGHTNESS.set(20).await.unwrap();
+//! }
+//! ```
+//!
+//! Settings hold booleans, integers ([`i64`]) or text ([`heapless::String`]). The values of all
+//! settings are kept together in storage, so that an [`update()`] changing several settings is
+//! applied completely or not at all. Updates are encoded in CBOR as a map from the names of the
+//! settings to their new values, where `null` resets a setting to its default:
+//!
+//! ```text
+//! { "led-brightness": 20, "device-name": null }
+//! ```
+//!
+//! At boot, the stored values are loaded, which [`loaded()`] waits for; the on-change callbacks
+//! of the settings whose stored values differ from their defaults are then called. Until then,
+//! [`Setting::get()`] returns the default value.
+//!
+//! With the `coap` feature, the settings are served as a CoAP resource, see [`coap`].
+//!
+//! # Configuration
+//!
+//! - `CONFIG_SETTINGS_MAX_SIZE` (default: 512): maximum size of the encoded settings, and of the
+//!   updates, in bytes.
+
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+#[cfg(feature = "coap")]
+pub mod coap;
+
+use core::{cell::RefCell, mem::ManuallyDrop};
+
+use ariel_os_debug::log::warn;
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex, once_lock::OnceLock,
+};
+use minicbor::{
+    Decoder, Encoder,
+    data::Type,
+    encode::write::{Cursor, EndOfSlice},
+};
+
+/// Maximum size of the encoded settings, and of the updates.
+pub const MAX_SIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SETTINGS_MAX_SIZE",
+    512,
+    "maximum size of the encoded settings, in bytes"
+);
+
+/// Maximum length of text values, in bytes.
+pub const MAX_TEXT_LEN: usize = 64;
+
+/// Storage key under which the values of the settings are stored.
+const STORAGE_KEY: &str = "ariel-os-settings";
+
+/// Set once the stored values have been loaded.
+static LOADED: OnceLock<()> = OnceLock::new();
+
+/// Serializes updates, so that concurrent ones do not overwrite each other in storage.
+static UPDATE: Mutex<CriticalSectionRawMutex, ()> = Mutex::new(());
+
+type EncodeError = minicbor::encode::Error<EndOfSlice>;
+
+/// The settings registered through [`register_setting!`].
+#[linkme::distributed_slice]
+pub static SETTINGS: [&'static dyn AnySetting] = [..];
+
+/// Errors of settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The update is malformed, names an unknown setting, or a value has the wrong type or fails
+    /// validation.
+    Invalid,
+    /// The encoded settings exceed [`MAX_SIZE`].
+    TooLarge,
+    /// Accessing the storage failed.
+    Storage,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Invalid => write!(f, "invalid settings"),
+            Self::TooLarge => write!(f, "settings too large"),
+            Self::Storage => write!(f, "storage access failed"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// The value of a setting, independently of its type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// A boolean.
+    Bool(bool),
+    /// An integer.
+    Int(i64),
+    /// A text.
+    Text(heapless::String<MAX_TEXT_LEN>),
+}
+
+/// The type of a setting, as reported in the schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// A boolean.
+    Bool,
+    /// An integer.
+    Int,
+    /// A text of up to `max_len` bytes.
+    Text {
+        /// Maximum length of the text, in bytes.
+        max_len: usize,
+    },
+}
+
+impl Kind {
+    /// Returns the name of the type in the schema.
+    fn name(self) -> &'static str {
+        match self {
+            Self::Bool => "bool",
+            Self::Int => "int",
+            Self::Text { .. } => "text",
+        }
+    }
+}
+
+/// A type that settings can have.
+pub trait SettingValue: Clone + PartialEq + Send + 'static {
+    /// The type of settings holding this type.
+    const KIND: Kind;
+
+    /// Converts the value into a [`Value`].
+    fn to_value(&self) -> Value;
+
+    /// Converts a [`Value`] back, returning `None` if it is of a different type or out of range.
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+impl SettingValue for bool {
+    const KIND: Kind = Kind::Bool;
+
+    fn to_value(&self) -> Value {
+        Value::Bool(*self)
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl SettingValue for i64 {
+    const KIND: Kind = Kind::Int;
+
+    fn to_value(&self) -> Value {
+        Value::Int(*self)
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl<const N: usize> SettingValue for heapless::String<N> {
+    const KIND: Kind = {
+        assert!(
+            N <= MAX_TEXT_LEN,
+            "text settings are limited to MAX_TEXT_LEN"
+        );
+        Kind::Text { max_len: N }
+    };
+
+    fn to_value(&self) -> Value {
+        // Cannot fail, `N` is at most `MAX_TEXT_LEN`.
+        Value::Text(self.as_str().try_into().unwrap_or_default())
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Text(value) => value.as_str().try_into().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// A setting, independently of its type.
+///
+/// This is implemented by [`Setting`].
+pub trait AnySetting: Sync {
+    /// Returns the name of the setting, which identifies it in storage and in updates.
+    fn name(&self) -> &'static str;
+
+    /// Returns the type of the setting.
+    fn kind(&self) -> Kind;
+
+    /// Returns the current value.
+    fn value(&self) -> Value;
+
+    /// Returns the stored value, or `None` if the setting has its default value.
+    fn stored_value(&self) -> Option<Value>;
+
+    /// Returns whether `value` has the type of the setting and passes its validation.
+    fn check(&self, value: &Value) -> bool;
+
+    /// Makes the stored `value` current, or the default value if `value` is `None`, and calls the
+    /// on-change callback if the current value changed.
+    ///
+    /// This does not write to storage, and is used by [`update()`] once the value is stored.
+    #[doc(hidden)]
+    fn apply(&self, value: Option<&Value>);
+}
+
+/// A typed setting, which is kept in storage once registered with [`register_setting!`].
+pub struct Setting<T> {
+    name: &'static str,
+    default: fn() -> T,
+    validate: fn(&T) -> bool,
+    on_change: Option<fn(&T)>,
+    // Without drop glue, so that `Setting` can be built in const contexts; text values do not own
+    // any resources anyway.
+    stored: ManuallyDrop<critical_section::Mutex<RefCell<Option<Value>>>>,
+}
+
+impl<T: SettingValue> Setting<T> {
+    /// Creates a setting named `name`, whose default value is returned by `default`.
+    ///
+    /// The name needs to be unique among the registered settings.
+    #[must_use]
+    pub const fn new(name: &'static str, default: fn() -> T) -> Self {
+        Self {
+            name,
+            default,
+            validate: |_| true,
+            on_change: None,
+            stored: ManuallyDrop::new(critical_section::Mutex::new(RefCell::new(None))),
+        }
+    }
+
+    /// Only accepts values for which `validate` returns `true`.
+    ///
+    /// The default value is not validated.
+    #[must_use]
+    pub const fn with_validation(self, validate: fn(&T) -> bool) -> Self {
+        Self { validate, ..self }
+    }
+
+    /// Calls `on_change` with the new value whenever the value changes, including when the stored
+    /// value is loaded at boot.
+    ///
+    /// `on_change` should not block, as it delays the completion of updates.
+    #[must_use]
+    pub const fn with_on_change(self, on_change: fn(&T)) -> Self {
+        Self {
+            on_change: Some(on_change),
+            ..self
+        }
+    }
+
+    /// Returns the current value.
+    #[must_use]
+    pub fn get(&self) -> T {
+        critical_section::with(|cs| self.stored.borrow_ref(cs).as_ref().and_then(T::from_value))
+            .unwrap_or_else(self.default)
+    }
+
+    /// Stores `value`, and makes it the current value.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Invalid`] if the setting is not registered or `value` fails validation,
+    /// and the errors of [`update()`] otherwise.
+    pub async fn set(&self, value: T) -> Result<(), Error> {
+        self.update(Some(&value.to_value())).await
+    }
+
+    /// Resets the setting to its default value.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Setting::set()`].
+    pub async fn reset(&self) -> Result<(), Error> {
+        self.update(None).await
+    }
+
+    /// Updates the setting to `value`, or to its default value if `value` is `None`.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`update()`].
+    async fn update(&self, value: Option<&Value>) -> Result<(), Error> {
+        let mut buffer = [0; MAX_SIZE];
+        let len = encode_entry(&mut buffer, self.name, value).map_err(|_| Error::TooLarge)?;
+        update(buffer.get(..len).ok_or(Error::TooLarge)?).await
+    }
+}
+
+impl<T> core::fmt::Debug for Setting<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Setting")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: SettingValue> AnySetting for Setting<T> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn kind(&self) -> Kind {
+        T::KIND
+    }
+
+    fn value(&self) -> Value {
+        self.get().to_value()
+    }
+
+    fn stored_value(&self) -> Option<Value> {
+        critical_section::with(|cs| self.stored.borrow_ref(cs).clone())
+    }
+
+    fn check(&self, value: &Value) -> bool {
+        T::from_value(value).is_some_and(|value| (self.validate)(&value))
+    }
+
+    fn apply(&self, value: Option<&Value>) {
+        let previous = self.get();
+        critical_section::with(|cs| self.stored.replace(cs, value.cloned()));
+        let current = self.get();
+        if current != previous
+            && let Some(on_change) = self.on_change
+        {
+            on_change(&current);
+        }
+    }
+}
+
+/// Registers the [`Setting`] `static`, so that it is kept in storage.
+///
+/// ```ignore
+/// static DEVICE_NAME: Setting<heapless::String<32>> = Setting::new("device-name", || {
+///     heapless::String::try_from("sensor").unwrap()
+/// });
+/// ariel_os::settings::register_setting!(DEVICE_NAME);
+/// ```
+#[macro_export]
+macro_rules! register_setting {
+    ($setting:path) => {
+        const _: () = {
+            #[$crate::macro_reexports::linkme::distributed_slice($crate::SETTINGS)]
+            #[linkme(crate = $crate::macro_reexports::linkme)]
+            static SETTING_REF: &'static dyn $crate::AnySetting = &$setting;
+        };
+    };
+}
+
+#[doc(hidden)]
+pub mod macro_reexports {
+    // Used by `register_setting`
+    pub use linkme;
+}
+
+/// Waits until the stored values of the settings have been loaded at boot.
+pub async fn loaded() {
+    LOADED.get().await;
+}
+
+/// Returns the registered setting named `name`.
+#[must_use]
+pub fn find(name: &str) -> Option<&'static dyn AnySetting> {
+    SETTINGS
+        .iter()
+        .find(|setting| setting.name() == name)
+        .copied()
+}
+
+/// Encodes the schema and the current values of all registered settings into `buffer`, and
+/// returns the encoded length.
+///
+/// The settings are encoded in CBOR as a map from their names to maps of their type and current
+/// value, and for text settings, their maximum length in bytes:
+///
+/// ```text
+/// { "led-brightness": { "type": "int", "value": 50 },
+///   "device-name": { "type": "text", "max-len": 32, "value": "sensor" } }
+/// ```
+///
+/// # Errors
+///
+/// Returns [`Error::TooLarge`] if the encoded settings do not fit into `buffer`.
+pub fn encode(buffer: &mut [u8]) -> Result<usize, Error> {
+    encode_schema(buffer).map_err(|_| Error::TooLarge)
+}
+
+/// Checks that all entries of the encoded `update` name registered settings and pass their
+/// validation, without applying it.
+///
+/// # Errors
+///
+/// Returns [`Error::Invalid`] if the update is malformed or has an invalid entry.
+pub fn check(update: &[u8]) -> Result<(), Error> {
+    for_each_entry(update, |_, setting, value| match (setting, value) {
+        (Some(setting), Some(value)) if setting.check(&value) => Ok(()),
+        (Some(_), None) => Ok(()),
+        _ => Err(Error::Invalid),
+    })
+}
+
+/// Applies the encoded `update`, see the [module documentation](crate), and calls the on-change
+/// callbacks of the settings whose value changed.
+///
+/// The update is only applied if all of its entries are valid, and once it is stored.
+///
+/// # Errors
+///
+/// Returns [`Error::Invalid`] if the update is malformed or has an invalid entry,
+/// [`Error::TooLarge`] if the resulting settings are too large to be stored, and
+/// [`Error::Storage`] if they could not be written to storage.
+pub async fn update(update: &[u8]) -> Result<(), Error> {
+    loaded().await;
+    let _guard = UPDATE.lock().await;
+    check(update)?;
+
+    // All values are stored with a single write, so that the update is atomic.
+    let mut buffer = [0; MAX_SIZE];
+    let len = encode_stored(&mut buffer, update).map_err(|_| Error::TooLarge)?;
+    let stored = buffer.get(..len).ok_or(Error::TooLarge)?;
+    ariel_os_storage::insert_blob(STORAGE_KEY, stored)
+        .await
+        .map_err(|_| Error::Storage)?;
+
+    for_each_entry(update, |_, setting, value| {
+        if let Some(setting) = setting {
+            setting.apply(value.as_ref());
+        }
+        Ok(())
+    })
+}
+
+/// Calls `f` with the name, the registered setting if any, and the value (`None` for `null`) of
+/// each entry of the encoded `update`.
+///
+/// # Errors
+///
+/// Returns [`Error::Invalid`] if the update is malformed, and the errors of `f`.
+fn for_each_entry(
+    update: &[u8],
+    mut f: impl FnMut(&str, Option<&'static dyn AnySetting>, Option<Value>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut decoder = Decoder::new(update);
+    let len = decoder.map().ok().flatten().ok_or(Error::Invalid)?;
+    for _ in 0..len {
+        let name = decoder.str().map_err(|_| Error::Invalid)?;
+        let value = decode_value(&mut decoder)?;
+        f(name, find(name), value)?;
+    }
+    if decoder.position() != update.len() {
+        return Err(Error::Invalid);
+    }
+    Ok(())
+}
+
+/// Returns the value `setting` has in storage once the encoded `update` is applied, or `None` if
+/// it then has its default value.
+fn stored_after(update: &[u8], setting: &dyn AnySetting) -> Option<Value> {
+    let mut stored = setting.stored_value();
+    // The update was checked.
+    let _ = for_each_entry(update, |name, _, value| {
+        if name == setting.name() {
+            stored = value;
+        }
+        Ok(())
+    });
+    stored
+}
+
+/// Decodes a value, returning `None` for `null`.
+///
+/// # Errors
+///
+/// Returns [`Error::Invalid`] if the value is malformed or of an unsupported type.
+fn decode_value(decoder: &mut Decoder<'_>) -> Result<Option<Value>, Error> {
+    let value = match decoder.datatype().map_err(|_| Error::Invalid)? {
+        Type::Null => {
+            decoder.null().map_err(|_| Error::Invalid)?;
+            return Ok(None);
+        }
+        Type::Bool => Value::Bool(decoder.bool().map_err(|_| Error::Invalid)?),
+        Type::U8
+        | Type::U16
+        | Type::U32
+        | Type::U64
+        | Type::I8
+        | Type::I16
+        | Type::I32
+        | Type::I64
+        | Type::Int => Value::Int(decoder.i64().map_err(|_| Error::Invalid)?),
+        Type::String => Value::Text(
+            decoder
+                .str()
+                .map_err(|_| Error::Invalid)?
+                .try_into()
+                .map_err(|()| Error::Invalid)?,
+        ),
+        _ => return Err(Error::Invalid),
+    };
+    Ok(Some(value))
+}
+
+/// Encodes `value`, or `null` if it is `None`.
+///
+/// # Errors
+///
+/// Returns the errors of the writer of `encoder`.
+fn encode_value<W: minicbor::encode::Write>(
+    encoder: &mut Encoder<W>,
+    value: Option<&Value>,
+) -> Result<(), minicbor::encode::Error<W::Error>> {
+    match value {
+        None => encoder.null()?,
+        Some(Value::Bool(value)) => encoder.bool(*value)?,
+        Some(Value::Int(value)) => encoder.i64(*value)?,
+        Some(Value::Text(value)) => encoder.str(value)?,
+    };
+    Ok(())
+}
+
+/// Encodes an update of the single setting named `name` into `buffer`, and returns the encoded
+/// length.
+///
+/// # Errors
+///
+/// Returns an error if the update does not fit into `buffer`.
+fn encode_entry(
+    buffer: &mut [u8],
+    name: &str,
+    value: Option<&Value>,
+) -> Result<usize, EncodeError> {
+    let mut encoder = Encoder::new(Cursor::new(buffer));
+    encoder.map(1)?.str(name)?;
+    encode_value(&mut encoder, value)?;
+    Ok(encoder.into_writer().position())
+}
+
+/// Encodes the stored values of the registered settings, with `update` applied, into `buffer`,
+/// and returns the encoded length.
+///
+/// # Errors
+///
+/// Returns an error if the values do not fit into `buffer`.
+fn encode_stored(buffer: &mut [u8], update: &[u8]) -> Result<usize, EncodeError> {
+    let mut encoder = Encoder::new(Cursor::new(buffer));
+    let count = SETTINGS
+        .iter()
+        .filter(|setting| stored_after(update, **setting).is_some())
+        .count();
+    encoder.map(count as u64)?;
+    for setting in SETTINGS {
+        if let Some(value) = stored_after(update, *setting) {
+            encoder.str(setting.name())?;
+            encode_value(&mut encoder, Some(&value))?;
+        }
+    }
+    Ok(encoder.into_writer().position())
+}
+
+/// Encodes the schema and the current values of the registered settings into `buffer`, see
+/// [`encode()`], and returns the encoded length.
+///
+/// # Errors
+///
+/// Returns an error if the settings do not fit into `buffer`.
+fn encode_schema(buffer: &mut [u8]) -> Result<usize, EncodeError> {
+    let mut encoder = Encoder::new(Cursor::new(buffer));
+    encoder.map(SETTINGS.len() as u64)?;
+    for setting in SETTINGS {
+        let kind = setting.kind();
+        let max_len = match kind {
+            Kind::Text { max_len } => Some(max_len),
+            Kind::Bool | Kind::Int => None,
+        };
+        encoder
+            .str(setting.name())?
+            .map(2 + u64::from(max_len.is_some()))?
+            .str("type")?
+            .str(kind.name())?;
+        if let Some(max_len) = max_len {
+            encoder.str("max-len")?.u64(max_len as u64)?;
+        }
+        encoder.str("value")?;
+        encode_value(&mut encoder, Some(&setting.value()))?;
+    }
+    Ok(encoder.into_writer().position())
+}
+
+/// Loads the stored values of the registered settings.
+///
+/// Stored values of settings that are no longer registered, or that no longer pass validation,
+/// are ignored.
+async fn load() {
+    let mut buffer = [0; MAX_SIZE];
+    let Ok(stored) = ariel_os_storage::get_blob(STORAGE_KEY, &mut buffer).await else {
+        warn!("settings: stored values could not be read");
+        return;
+    };
+    if let Some(stored) = stored {
+        apply_stored(stored);
+    }
+}
+
+/// Makes the encoded `stored` values current.
+///
+/// Values of settings that are no longer registered, or that no longer pass validation, are
+/// ignored.
+fn apply_stored(stored: &[u8]) {
+    let applied = for_each_entry(stored, |name, setting, value| {
+        match setting {
+            Some(setting) if value.as_ref().is_none_or(|value| setting.check(value)) => {
+                setting.apply(value.as_ref());
+            }
+            _ => warn!("settings: ignoring stored value of {}", name),
+        }
+        Ok(())
+    });
+    if applied.is_err() {
+        warn!("settings: stored values are malformed");
+    }
+}
+
+/// Loads the stored values of the settings at boot.
+#[ariel_os_macros::task(autostart)]
+async fn settings() {
+    load().await;
+    let _ = LOADED.init(());
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use core::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+
+    use embassy_futures::block_on;
+
+    use super::*;
+
+    /// Number of calls of the on-change callback of [`LEVEL`].
+    static LEVEL_CHANGES: AtomicUsize = AtomicUsize::new(0);
+    /// Value passed to the latest call of the on-change callback of [`LEVEL`].
+    static LEVEL_SEEN: AtomicI64 = AtomicI64::new(0);
+
+    static LEVEL: Setting<i64> = Setting::new("test-level", || 5)
+        .with_validation(|level| (0..=10).contains(level))
+        .with_on_change(|level| {
+            LEVEL_CHANGES.fetch_add(1, Ordering::Relaxed);
+            LEVEL_SEEN.store(*level, Ordering::Relaxed);
+        });
+    register_setting!(LEVEL);
+
+    static ENABLED: Setting<bool> = Setting::new("test-enabled", || false);
+    register_setting!(ENABLED);
+
+    static NAME: Setting<heapless::String<4>> = Setting::new("test-name", heapless::String::new);
+    register_setting!(NAME);
+
+    /// Serializes the tests, which share the settings.
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    /// Resets the settings to their defaults without calling their callbacks, marks the stored
+    /// values as loaded, and returns the guard serializing the tests.
+    fn reset() -> std::sync::MutexGuard<'static, ()> {
+        let guard = LOCK
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        critical_section::with(|cs| {
+            LEVEL.stored.replace(cs, None);
+            ENABLED.stored.replace(cs, None);
+            NAME.stored.replace(cs, None);
+        });
+        LEVEL_CHANGES.store(0, Ordering::Relaxed);
+        let _ = LOADED.init(());
+        guard
+    }
+
+    /// Encodes an update of the settings named in `entries`.
+    fn update_of(entries: &[(&str, Option<Value>)]) -> std::vec::Vec<u8> {
+        let mut buffer = [0; MAX_SIZE];
+        let mut encoder = Encoder::new(Cursor::new(buffer.as_mut_slice()));
+        encoder.map(entries.len() as u64).unwrap();
+        for (name, value) in entries {
+            encoder.str(name).unwrap();
+            encode_value(&mut encoder, value.as_ref()).unwrap();
+        }
+        let len = encoder.into_writer().position();
+        buffer.get(..len).unwrap().to_vec()
+    }
+
+    fn text(text: &str) -> Value {
+        Value::Text(text.try_into().unwrap())
+    }
+
+    #[test]
+    fn validation() {
+        let _guard = reset();
+        assert_eq!(
+            check(&update_of(&[("test-level", Some(Value::Int(10)))])),
+            Ok(())
+        );
+        assert_eq!(check(&update_of(&[("test-level", None)])), Ok(()));
+        assert_eq!(
+            check(&update_of(&[("test-name", Some(text("abcd")))])),
+            Ok(())
+        );
+
+        // Out of range, of the wrong type, too long, or unknown.
+        for entry in [
+            ("test-level", Some(Value::Int(11))),
+            ("test-level", Some(Value::Bool(true))),
+            ("test-name", Some(text("abcde"))),
+            ("test-unknown", Some(Value::Int(1))),
+        ] {
+            assert_eq!(check(&update_of(&[entry])), Err(Error::Invalid));
+        }
+        // Malformed: not a map, and trailing data.
+        assert_eq!(check(&[0x80]), Err(Error::Invalid));
+        let mut trailing = update_of(&[("test-level", None)]);
+        trailing.push(0xf6);
+        assert_eq!(check(&trailing), Err(Error::Invalid));
+
+        assert_eq!(block_on(LEVEL.set(11)), Err(Error::Invalid));
+        assert_eq!(LEVEL.get(), 5);
+    }
+
+    #[test]
+    fn partial_update_is_not_applied() {
+        let _guard = reset();
+        let update = update_of(&[
+            ("test-enabled", Some(Value::Bool(true))),
+            ("test-level", Some(Value::Int(42))),
+        ]);
+        assert_eq!(block_on(super::update(&update)), Err(Error::Invalid));
+        assert!(!ENABLED.get());
+        assert_eq!(LEVEL.get(), 5);
+        assert_eq!(LEVEL_CHANGES.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn on_change_callbacks() {
+        let _guard = reset();
+        LEVEL.apply(Some(&Value::Int(7)));
+        assert_eq!(LEVEL.get(), 7);
+        assert_eq!(LEVEL_CHANGES.load(Ordering::Relaxed), 1);
+        assert_eq!(LEVEL_SEEN.load(Ordering::Relaxed), 7);
+
+        // Unchanged values do not call the callback.
+        LEVEL.apply(Some(&Value::Int(7)));
+        assert_eq!(LEVEL_CHANGES.load(Ordering::Relaxed), 1);
+
+        // Resetting to the default value does.
+        LEVEL.apply(None);
+        assert_eq!(LEVEL.get(), 5);
+        assert_eq!(LEVEL_CHANGES.load(Ordering::Relaxed), 2);
+        assert_eq!(LEVEL_SEEN.load(Ordering::Relaxed), 5);
+
+        // Storing the default value does not change the current value.
+        LEVEL.apply(Some(&Value::Int(5)));
+        assert_eq!(LEVEL_CHANGES.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn stored_values() {
+        let _guard = reset();
+        apply_stored(&update_of(&[
+            ("test-level", Some(Value::Int(3))),
+            ("test-enabled", Some(Value::Int(1))),
+            ("test-removed", Some(Value::Int(1))),
+            ("test-name", Some(text("abc"))),
+        ]));
+        assert_eq!(LEVEL.get(), 3);
+        assert_eq!(LEVEL_CHANGES.load(Ordering::Relaxed), 1);
+        assert_eq!(LEVEL_SEEN.load(Ordering::Relaxed), 3);
+        // Values that no longer fit their setting are ignored.
+        assert!(!ENABLED.get());
+        assert_eq!(NAME.get(), "abc");
+
+        // Only the settings with stored values are stored again on updates.
+        let mut buffer = [0; MAX_SIZE];
+        let len = encode_stored(&mut buffer, &update_of(&[("test-level", None)])).unwrap();
+        assert_eq!(
+            buffer.get(..len).unwrap(),
+            update_of(&[("test-name", Some(text("abc")))])
+        );
+    }
+}
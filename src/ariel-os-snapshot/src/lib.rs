@@ -0,0 +1,307 @@
+//! Provides periodic snapshots of the state of subsystems into storage, which are restored at boot.
+//!
+//! Subsystems keep state that is costly to lose on a reset, eg. counters or session data, in a
+//! [`State`], which they register with [`register_state!`]:
+//!
+//! ```ignore
+//! use ariel_os::snapshot::{State, register_state};
+//!
+//! static BOOT_COUNT: State<u32> = State::new("boot-count", 0);
+//! register_state!(BOOT_COUNT);
+//!
+//! #[ariel_os::task(autostart)]
+//! async fn count_boots() {
+//!     ariel_os::snapshot::restored().await;
+//!     BOOT_COUNT.update(|count| *count += 1);
+//! }
+//! ```
+//!
+//! At boot, the registered states are restored from their latest snapshot, which
+//! [`restored()`] waits for. They are then snapshotted periodically, and on orderly shutdown
+//! through [`reboot()`], so that a watchdog reset only loses the changes made since the latest
+//! snapshot. To spare the flash, snapshots are only written when the state changed.
+//!
+//! States are serialized with [`postcard`]: a snapshot taken before the type of a state changed
+//! fails to be restored, and the state then keeps its initial value.
+//!
+//! # Configuration
+//!
+//! - `CONFIG_SNAPSHOT_INTERVAL_SECS` (default: 300): interval between snapshots, in seconds.
+//! - `CONFIG_SNAPSHOT_MAX_STATE_SIZE` (default: 256): maximum size of serialized states, in bytes.
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+use core::cell::RefCell;
+
+use ariel_os_debug::log::warn;
+use embassy_sync::once_lock::OnceLock;
+use embassy_time::{Duration, Ticker};
+use serde::{Serialize, de::DeserializeOwned};
+
+/// Interval between snapshots.
+const INTERVAL: Duration = Duration::from_secs(ariel_os_utils::u64_from_env_or!(
+    "CONFIG_SNAPSHOT_INTERVAL_SECS",
+    300,
+    "interval between snapshots of the registered states, in seconds"
+));
+
+/// Maximum size of serialized states.
+const MAX_STATE_SIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_SNAPSHOT_MAX_STATE_SIZE",
+    256,
+    "maximum size of serialized states, in bytes"
+);
+
+/// Prefix of the storage keys under which snapshots are stored.
+const KEY_PREFIX: &str = "ariel-os-snapshot.";
+
+/// Set once the registered states have been restored.
+static RESTORED: OnceLock<()> = OnceLock::new();
+
+/// The states registered through [`register_state!`].
+#[linkme::distributed_slice]
+pub static SNAPSHOTS: [&'static dyn Snapshot] = [..];
+
+/// Errors of snapshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The state could not be serialized, eg. because it is too large, or its snapshot could not
+    /// be deserialized.
+    Serialization,
+    /// Accessing the storage failed.
+    Storage,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Serialization => write!(f, "serialization failed"),
+            Self::Storage => write!(f, "storage access failed"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// State that can be snapshotted and restored.
+///
+/// This is implemented by [`State`], which is sufficient for most purposes.
+pub trait Snapshot: Sync {
+    /// Returns the name of the state, which identifies its snapshots.
+    fn name(&self) -> &'static str;
+
+    /// Serializes the state into `buffer`, and returns the serialized length.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Serialization`] if the state cannot be serialized into `buffer`.
+    fn save(&self, buffer: &mut [u8]) -> Result<usize, Error>;
+
+    /// Restores the state from its `serialized` snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Serialization`] if the snapshot cannot be deserialized.
+    fn restore(&self, serialized: &[u8]) -> Result<(), Error>;
+}
+
+/// A value that is snapshotted once registered with [`register_state!`].
+pub struct State<T> {
+    name: &'static str,
+    value: critical_section::Mutex<RefCell<T>>,
+}
+
+impl<T> State<T> {
+    /// Creates a state named `name`, which holds `initial` until it is restored.
+    ///
+    /// The name needs to be unique among the registered states.
+    #[must_use]
+    pub const fn new(name: &'static str, initial: T) -> Self {
+        Self {
+            name,
+            value: critical_section::Mutex::new(RefCell::new(initial)),
+        }
+    }
+
+    /// Returns the current value.
+    #[must_use]
+    pub fn get(&self) -> T
+    where
+        T: Clone,
+    {
+        critical_section::with(|cs| self.value.borrow_ref(cs).clone())
+    }
+
+    /// Replaces the current value with `value`.
+    pub fn set(&self, value: T) {
+        critical_section::with(|cs| *self.value.borrow_ref_mut(cs) = value);
+    }
+
+    /// Updates the current value through `f`, in a critical section.
+    pub fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        critical_section::with(|cs| f(&mut self.value.borrow_ref_mut(cs)))
+    }
+}
+
+impl<T> core::fmt::Debug for State<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("State")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Send> Snapshot for State<T> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn save(&self, buffer: &mut [u8]) -> Result<usize, Error> {
+        critical_section::with(|cs| {
+            postcard::to_slice(&*self.value.borrow_ref(cs), buffer)
+                .map(|serialized| serialized.len())
+                .map_err(|_| Error::Serialization)
+        })
+    }
+
+    fn restore(&self, serialized: &[u8]) -> Result<(), Error> {
+        let value = postcard::from_bytes(serialized).map_err(|_| Error::Serialization)?;
+        self.set(value);
+        Ok(())
+    }
+}
+
+/// Registers the [`State`] `static` to be snapshotted.
+///
+/// ```ignore
+/// static SESSION: State<Option<[u8; 16]>> = State::new("session", None);
+/// ariel_os::snapshot::register_state!(SESSION);
+/// ```
+#[macro_export]
+macro_rules! register_state {
+    ($state:path) => {
+        const _: () = {
+            #[$crate::macro_reexports::linkme::distributed_slice($crate::SNAPSHOTS)]
+            #[linkme(crate = $crate::macro_reexports::linkme)]
+            static SNAPSHOT_REF: &'static dyn $crate::Snapshot = &$state;
+        };
+    };
+}
+
+#[doc(hidden)]
+pub mod macro_reexports {
+    // Used by `register_state`
+    pub use linkme;
+}
+
+/// Waits until the registered states have been restored from their latest snapshot at boot.
+pub async fn restored() {
+    RESTORED.get().await;
+}
+
+/// Snapshots all registered states that changed since their latest snapshot.
+///
+/// This waits until the states have been restored, so that their snapshots are not overwritten
+/// by their initial values.
+///
+/// # Errors
+///
+/// Returns the error of the last state that could not be snapshotted; the other states are
+/// snapshotted nevertheless.
+pub async fn save_all() -> Result<(), Error> {
+    restored().await;
+
+    let mut result = Ok(());
+    for snapshot in SNAPSHOTS {
+        if let Err(err) = save(*snapshot).await {
+            warn!("snapshot of {} failed: {}", snapshot.name(), err);
+            result = Err(err);
+        }
+    }
+    result
+}
+
+/// Snapshots all registered states, and reboots.
+pub async fn reboot() -> ! {
+    // Errors are logged, and rebooting is the best that can be done.
+    let _ = save_all().await;
+    // Values held in RAM by a write-behind storage would be lost otherwise.
+    if ariel_os_storage::flush().await.is_err() {
+        warn!("flushing the storage failed");
+    }
+    ariel_os_power::reboot()
+}
+
+/// Snapshots `snapshot` if it changed since its latest snapshot.
+///
+/// # Errors
+///
+/// Returns an error if the state cannot be serialized, or the storage cannot be accessed.
+async fn save(snapshot: &dyn Snapshot) -> Result<(), Error> {
+    let key = storage_key(snapshot.name())?;
+    let mut buffer = [0; MAX_STATE_SIZE];
+    let len = snapshot.save(&mut buffer)?;
+    let serialized = buffer.get(..len).ok_or(Error::Serialization)?;
+
+    let mut stored = [0; MAX_STATE_SIZE];
+    let latest = ariel_os_storage::get_blob(&key, &mut stored)
+        .await
+        .map_err(|_| Error::Storage)?;
+    if latest == Some(serialized) {
+        return Ok(());
+    }
+    ariel_os_storage::insert_blob(&key, serialized)
+        .await
+        .map_err(|_| Error::Storage)
+}
+
+/// Restores all registered states from their latest snapshot.
+///
+/// States without snapshot, or whose snapshot cannot be restored, keep their initial value.
+async fn restore_all() {
+    for snapshot in SNAPSHOTS {
+        let Ok(key) = storage_key(snapshot.name()) else {
+            warn!("snapshot name {} is too long", snapshot.name());
+            continue;
+        };
+        let mut buffer = [0; MAX_STATE_SIZE];
+        match ariel_os_storage::get_blob(&key, &mut buffer).await {
+            Ok(Some(serialized)) => {
+                if snapshot.restore(serialized).is_err() {
+                    warn!("snapshot of {} could not be restored", snapshot.name());
+                }
+            }
+            Ok(None) => {}
+            Err(_) => warn!("snapshot of {} could not be read", snapshot.name()),
+        }
+    }
+}
+
+/// Returns the storage key of the snapshots of the state named `name`.
+///
+/// # Errors
+///
+/// Returns [`Error::Storage`] if the name is too long.
+fn storage_key(name: &str) -> Result<heapless::String<64>, Error> {
+    let mut key = heapless::String::new();
+    key.push_str(KEY_PREFIX).map_err(|()| Error::Storage)?;
+    key.push_str(name).map_err(|()| Error::Storage)?;
+    Ok(key)
+}
+
+/// Restores the registered states at boot, and snapshots them periodically.
+#[ariel_os_macros::task(autostart)]
+async fn snapshot() {
+    restore_all().await;
+    let _ = RESTORED.init(());
+
+    let mut ticker = Ticker::every(INTERVAL);
+    loop {
+        ticker.next().await;
+        // Errors are logged, and retried at the next snapshot.
+        let _ = save_all().await;
+    }
+}
@@ -0,0 +1,175 @@
+//! Buses through which flash chips are driven.
+
+use embedded_hal_async::spi::{Operation, SpiDevice};
+
+/// Number of lanes over which a phase of a [`Command`] is transferred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Width {
+    /// One lane, as in plain SPI.
+    Single,
+    /// Two lanes.
+    Dual,
+    /// Four lanes.
+    Quad,
+}
+
+/// A command to a flash chip.
+///
+/// The opcode is always transferred on a single lane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Command {
+    /// Opcode of the command.
+    pub opcode: u8,
+    /// Address of the command, if any, and its length in bytes (3 or 4).
+    pub address: Option<(u32, u8)>,
+    /// Width of the address phase, and of the mode bits which are part of the dummy cycles.
+    pub address_width: Width,
+    /// Number of clock cycles between the address and the data.
+    pub dummy_cycles: u8,
+    /// Width of the data phase.
+    pub data_width: Width,
+}
+
+impl Command {
+    /// Returns a single-lane command with neither address nor dummy cycles.
+    #[must_use]
+    pub const fn new(opcode: u8) -> Self {
+        Self {
+            opcode,
+            address: None,
+            address_width: Width::Single,
+            dummy_cycles: 0,
+            data_width: Width::Single,
+        }
+    }
+
+    /// Returns the command with the given address of `len` bytes.
+    #[must_use]
+    pub const fn with_address(self, address: u32, len: u8) -> Self {
+        Self {
+            address: Some((address, len)),
+            ..self
+        }
+    }
+
+    /// Returns the command with the given number of dummy cycles.
+    #[must_use]
+    pub const fn with_dummy_cycles(self, dummy_cycles: u8) -> Self {
+        Self {
+            dummy_cycles,
+            ..self
+        }
+    }
+}
+
+/// A bus to a single flash chip, eg. an SPI device or a QSPI peripheral.
+pub trait Bus {
+    /// Error returned when a transfer fails.
+    type Error;
+
+    /// Returns the widest [`Width`] the bus supports.
+    ///
+    /// Commands passed to the bus only use widths up to this one.
+    fn max_width(&self) -> Width;
+
+    /// Sends `command`, and then reads `data` from the chip.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transfer failed.
+    fn read(
+        &mut self,
+        command: Command,
+        data: &mut [u8],
+    ) -> impl Future<Output = Result<(), Self::Error>>;
+
+    /// Sends `command`, and then writes `data` to the chip.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transfer failed.
+    fn write(
+        &mut self,
+        command: Command,
+        data: &[u8],
+    ) -> impl Future<Output = Result<(), Self::Error>>;
+}
+
+/// Maximum length of the header of a command sent over SPI: opcode, address and dummy bytes.
+const MAX_HEADER_LEN: usize = 1 + 4 + u8::MAX.div_ceil(8) as usize;
+
+/// A [`Bus`] over an SPI device, which transfers data on a single lane.
+///
+/// Dummy cycles are sent as whole bytes, which the driver ensures for single-lane commands.
+pub struct SpiDeviceBus<D> {
+    device: D,
+}
+
+impl<D: SpiDevice> SpiDeviceBus<D> {
+    /// Creates a bus over `device`.
+    #[must_use]
+    pub const fn new(device: D) -> Self {
+        Self { device }
+    }
+
+    /// Releases the SPI device.
+    #[must_use]
+    pub fn release(self) -> D {
+        self.device
+    }
+
+    /// Writes the header of `command` into `buffer`, and returns its length.
+    fn header(command: Command, buffer: &mut [u8; MAX_HEADER_LEN]) -> usize {
+        buffer[0] = command.opcode;
+        let mut len = 1;
+        if let Some((address, address_len)) = command.address {
+            let bytes = address.to_be_bytes();
+            let address_len = usize::from(address_len.min(4));
+            for (to, from) in buffer
+                .iter_mut()
+                .skip(len)
+                .zip(bytes.iter().skip(4 - address_len))
+            {
+                *to = *from;
+            }
+            len += address_len;
+        }
+        let dummy_len = usize::from(command.dummy_cycles.div_ceil(8));
+        for byte in buffer.iter_mut().skip(len).take(dummy_len) {
+            *byte = 0;
+        }
+        len + dummy_len
+    }
+}
+
+impl<D: SpiDevice> Bus for SpiDeviceBus<D> {
+    type Error = D::Error;
+
+    fn max_width(&self) -> Width {
+        Width::Single
+    }
+
+    async fn read(&mut self, command: Command, data: &mut [u8]) -> Result<(), Self::Error> {
+        let mut header = [0; MAX_HEADER_LEN];
+        let len = Self::header(command, &mut header);
+        self.device
+            .transaction(&mut [
+                Operation::Write(header.get(..len).unwrap_or_default()),
+                Operation::Read(data),
+            ])
+            .await
+    }
+
+    async fn write(&mut self, command: Command, data: &[u8]) -> Result<(), Self::Error> {
+        let mut header = [0; MAX_HEADER_LEN];
+        let len = Self::header(command, &mut header);
+        self.device
+            .transaction(&mut [
+                Operation::Write(header.get(..len).unwrap_or_default()),
+                Operation::Write(data),
+            ])
+            .await
+    }
+}
@@ -0,0 +1,349 @@
+//! Driver for serial NOR flash chips.
+
+use embassy_time::{Duration, Instant, Timer};
+use embedded_storage_async::nor_flash::{ErrorType, MultiwriteNorFlash, NorFlash, ReadNorFlash};
+
+use crate::{
+    Error,
+    bus::{Bus, Command, Width},
+    sfdp::{self, AddressMode, Parameters, QuadEnable},
+};
+
+const CMD_WRITE_STATUS: u8 = 0x01;
+const CMD_PAGE_PROGRAM: u8 = 0x02;
+const CMD_READ_STATUS: u8 = 0x05;
+const CMD_WRITE_ENABLE: u8 = 0x06;
+const CMD_FAST_READ: u8 = 0x0b;
+const CMD_WRITE_STATUS_2: u8 = 0x31;
+const CMD_READ_STATUS_2: u8 = 0x35;
+const CMD_WRITE_STATUS_3E: u8 = 0x3e;
+const CMD_READ_STATUS_3F: u8 = 0x3f;
+const CMD_READ_SFDP: u8 = 0x5a;
+const CMD_ENTER_4_BYTE_ADDRESS: u8 = 0xb7;
+
+/// Bit of status register 1 set while the chip is busy programming or erasing.
+const STATUS_BUSY: u8 = 0x01;
+
+/// Number of dummy cycles of [`CMD_READ_SFDP`] and [`CMD_FAST_READ`].
+const FAST_READ_DUMMY_CYCLES: u8 = 8;
+
+/// Capacity above which chips need 4-byte addresses.
+const MAX_3_BYTE_CAPACITY: u32 = 16 * 1024 * 1024;
+
+const PROGRAM_TIMEOUT: Duration = Duration::from_millis(20);
+const ERASE_TIMEOUT: Duration = Duration::from_secs(1);
+const STATUS_WRITE_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Interval at which the status of a busy chip is polled.
+const POLL_INTERVAL: Duration = Duration::from_micros(50);
+
+/// A serial NOR flash chip, connected through the bus `B`.
+///
+/// The chip is erased in sectors of 4 KiB, and can be written with any alignment, also several
+/// times between erases as long as bits are only cleared.
+pub struct SpiFlash<B> {
+    bus: B,
+    parameters: Parameters,
+    /// Length of the addresses, in bytes.
+    address_len: u8,
+    /// Command used to read data, without its address.
+    read_command: Command,
+}
+
+impl<B: Bus> SpiFlash<B> {
+    /// Detects the flash chip connected through `bus` from its SFDP tables, and prepares it for
+    /// the fastest reads supported by both the chip and the bus.
+    ///
+    /// Chips of more than 16 MiB are switched to 4-byte addresses, and the quad transfers of
+    /// chips are enabled if needed, which may change their non-volatile configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoSfdp`] if the chip does not provide SFDP tables,
+    /// [`Error::Unsupported`] if the chip is not supported, and an error if communicating with the
+    /// chip fails.
+    pub async fn new(mut bus: B) -> Result<Self, Error> {
+        let parameters = read_parameters(&mut bus).await?;
+        if parameters.erase_4k_opcode.is_none() {
+            return Err(Error::Unsupported);
+        }
+
+        let mut flash = Self {
+            bus,
+            parameters,
+            address_len: 3,
+            read_command: Command::new(CMD_FAST_READ),
+        };
+
+        if parameters.capacity > MAX_3_BYTE_CAPACITY
+            || parameters.address_mode == AddressMode::FourByte
+        {
+            match parameters.address_mode {
+                AddressMode::ThreeByte => return Err(Error::Unsupported),
+                AddressMode::ThreeOrFourByte => {
+                    flash
+                        .command(Command::new(CMD_ENTER_4_BYTE_ADDRESS))
+                        .await?;
+                }
+                AddressMode::FourByte => {}
+            }
+            flash.address_len = 4;
+        }
+
+        let max_width = flash.bus.max_width();
+        let quad = max_width >= Width::Quad
+            && (parameters.quad_io_read.is_some() || parameters.quad_output_read.is_some())
+            && flash.enable_quad().await?;
+        flash.read_command = read_command(&parameters, max_width, quad);
+
+        Ok(flash)
+    }
+
+    /// Returns the parameters of the chip, as read from its SFDP tables.
+    #[must_use]
+    pub fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+
+    /// Releases the bus.
+    #[must_use]
+    pub fn release(self) -> B {
+        self.bus
+    }
+
+    /// Enables quad transfers as described by the chip, and returns whether they are enabled.
+    async fn enable_quad(&mut self) -> Result<bool, Error> {
+        match self.parameters.quad_enable {
+            QuadEnable::NotNeeded => {}
+            QuadEnable::Sr1Bit6 => {
+                let status = self.read_register(CMD_READ_STATUS).await?;
+                if status & 1 << 6 == 0 {
+                    self.write_registers(CMD_WRITE_STATUS, &[status | 1 << 6])
+                        .await?;
+                }
+            }
+            QuadEnable::Sr2Bit1 => {
+                let status_2 = self.read_register(CMD_READ_STATUS_2).await?;
+                if status_2 & 1 << 1 == 0 {
+                    let status = self.read_register(CMD_READ_STATUS).await?;
+                    self.write_registers(CMD_WRITE_STATUS, &[status, status_2 | 1 << 1])
+                        .await?;
+                }
+            }
+            QuadEnable::Sr2Bit1Separate => {
+                let status_2 = self.read_register(CMD_READ_STATUS_2).await?;
+                if status_2 & 1 << 1 == 0 {
+                    self.write_registers(CMD_WRITE_STATUS_2, &[status_2 | 1 << 1])
+                        .await?;
+                }
+            }
+            QuadEnable::Sr2Bit7 => {
+                let status_2 = self.read_register(CMD_READ_STATUS_3F).await?;
+                if status_2 & 1 << 7 == 0 {
+                    self.write_registers(CMD_WRITE_STATUS_3E, &[status_2 | 1 << 7])
+                        .await?;
+                }
+            }
+            QuadEnable::Unknown => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Sends `command`, without data.
+    async fn command(&mut self, command: Command) -> Result<(), Error> {
+        self.bus.write(command, &[]).await.map_err(|_| Error::Bus)
+    }
+
+    /// Reads the status register read by `opcode`.
+    async fn read_register(&mut self, opcode: u8) -> Result<u8, Error> {
+        let mut value = [0];
+        self.bus
+            .read(Command::new(opcode), &mut value)
+            .await
+            .map_err(|_| Error::Bus)?;
+        let [value] = value;
+        Ok(value)
+    }
+
+    /// Writes the status registers written by `opcode`, and waits until they are written.
+    async fn write_registers(&mut self, opcode: u8, values: &[u8]) -> Result<(), Error> {
+        self.command(Command::new(CMD_WRITE_ENABLE)).await?;
+        self.bus
+            .write(Command::new(opcode), values)
+            .await
+            .map_err(|_| Error::Bus)?;
+        self.wait_ready(STATUS_WRITE_TIMEOUT).await
+    }
+
+    /// Sends the program or erase `command` with `data`, and waits until it completed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Timeout`] if the chip is still busy after `timeout`, and an error if
+    /// communicating with the chip fails.
+    async fn program(
+        &mut self,
+        command: Command,
+        data: &[u8],
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        self.command(Command::new(CMD_WRITE_ENABLE)).await?;
+        self.bus
+            .write(command, data)
+            .await
+            .map_err(|_| Error::Bus)?;
+        self.wait_ready(timeout).await
+    }
+
+    /// Waits until the chip is no longer busy.
+    async fn wait_ready(&mut self, timeout: Duration) -> Result<(), Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.read_register(CMD_READ_STATUS).await? & STATUS_BUSY == 0 {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::Timeout);
+            }
+            Timer::after(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Checks that `len` bytes from `offset` are in the range of the chip.
+    fn check_bounds(&self, offset: u32, len: usize) -> Result<(), Error> {
+        let end = u32::try_from(len)
+            .ok()
+            .and_then(|len| offset.checked_add(len));
+        match end {
+            Some(end) if end <= self.parameters.capacity => Ok(()),
+            _ => Err(Error::OutOfBounds),
+        }
+    }
+}
+
+/// Returns the fastest read command supported by both the chip and a bus of `max_width`, with
+/// quad transfers enabled if `quad` is true.
+fn read_command(parameters: &Parameters, max_width: Width, quad: bool) -> Command {
+    let reads = [
+        (quad, parameters.quad_io_read, Width::Quad, Width::Quad),
+        (
+            quad,
+            parameters.quad_output_read,
+            Width::Single,
+            Width::Quad,
+        ),
+        (true, parameters.dual_io_read, Width::Dual, Width::Dual),
+        (
+            true,
+            parameters.dual_output_read,
+            Width::Single,
+            Width::Dual,
+        ),
+    ];
+    reads
+        .into_iter()
+        .filter(|(enabled, _, _, data_width)| *enabled && *data_width <= max_width)
+        .find_map(|(_, read, address_width, data_width)| {
+            let read = read?;
+            Some(Command {
+                address_width,
+                data_width,
+                ..Command::new(read.opcode).with_dummy_cycles(read.dummy_cycles)
+            })
+        })
+        .unwrap_or(Command::new(CMD_FAST_READ).with_dummy_cycles(FAST_READ_DUMMY_CYCLES))
+}
+
+/// Reads the parameters of the chip connected through `bus` from its BFPT.
+async fn read_parameters<B: Bus>(bus: &mut B) -> Result<Parameters, Error> {
+    let mut header = [0; sfdp::HEADER_LEN];
+    read_sfdp(bus, 0, &mut header).await?;
+    let count = sfdp::parameter_header_count(header).ok_or(Error::NoSfdp)?;
+
+    for index in 0..count {
+        read_sfdp(bus, sfdp::parameter_header_address(index), &mut header).await?;
+        let header = sfdp::ParameterHeader::parse(header);
+        if !header.is_bfpt() {
+            continue;
+        }
+
+        let mut table = [0; sfdp::BFPT_MAX_LEN];
+        let table = table
+            .get_mut(..header.len.min(sfdp::BFPT_MAX_LEN))
+            .ok_or(Error::NoSfdp)?;
+        read_sfdp(bus, header.pointer, table).await?;
+        return Parameters::parse(table).ok_or(Error::NoSfdp);
+    }
+    Err(Error::NoSfdp)
+}
+
+/// Reads `data` from the SFDP tables of the chip, starting at `address`.
+async fn read_sfdp<B: Bus>(bus: &mut B, address: u32, data: &mut [u8]) -> Result<(), Error> {
+    let command = Command::new(CMD_READ_SFDP)
+        .with_address(address, 3)
+        .with_dummy_cycles(FAST_READ_DUMMY_CYCLES);
+    bus.read(command, data).await.map_err(|_| Error::Bus)
+}
+
+impl<B: Bus> ErrorType for SpiFlash<B> {
+    type Error = Error;
+}
+
+impl<B: Bus> ReadNorFlash for SpiFlash<B> {
+    const READ_SIZE: usize = 1;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        self.check_bounds(offset, bytes.len())?;
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        let command = Command {
+            address: Some((offset, self.address_len)),
+            ..self.read_command
+        };
+        self.bus.read(command, bytes).await.map_err(|_| Error::Bus)
+    }
+
+    fn capacity(&self) -> usize {
+        self.parameters.capacity as usize
+    }
+}
+
+impl<B: Bus> NorFlash for SpiFlash<B> {
+    const WRITE_SIZE: usize = 1;
+    const ERASE_SIZE: usize = 4096;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        if from > to || to > self.parameters.capacity {
+            return Err(Error::OutOfBounds);
+        }
+        if !(from as usize).is_multiple_of(Self::ERASE_SIZE)
+            || !(to as usize).is_multiple_of(Self::ERASE_SIZE)
+        {
+            return Err(Error::NotAligned);
+        }
+        // Checked in `new()`.
+        let opcode = self.parameters.erase_4k_opcode.ok_or(Error::Unsupported)?;
+        for sector in (from..to).step_by(Self::ERASE_SIZE) {
+            let command = Command::new(opcode).with_address(sector, self.address_len);
+            self.program(command, &[], ERASE_TIMEOUT).await?;
+        }
+        Ok(())
+    }
+
+    async fn write(&mut self, mut offset: u32, mut bytes: &[u8]) -> Result<(), Self::Error> {
+        self.check_bounds(offset, bytes.len())?;
+        // Programs do not cross page boundaries, as they would wrap around within the page.
+        while !bytes.is_empty() {
+            let page_left = self.parameters.page_size - offset % self.parameters.page_size;
+            let (page, rest) = bytes.split_at(bytes.len().min(page_left as usize));
+            let command = Command::new(CMD_PAGE_PROGRAM).with_address(offset, self.address_len);
+            self.program(command, page, PROGRAM_TIMEOUT).await?;
+            offset = offset.saturating_add(page_left);
+            bytes = rest;
+        }
+        Ok(())
+    }
+}
+
+impl<B: Bus> MultiwriteNorFlash for SpiFlash<B> {}
@@ -0,0 +1,82 @@
+//! Provides a driver for serial NOR flash chips, which detects their geometry from their SFDP
+//! tables.
+//!
+//! [`SpiFlash`] reads the Serial Flash Discoverable Parameters (JESD216) of the chip to find its
+//! capacity, page size, erase commands and fast-read commands, so that any SFDP-compliant chip is
+//! supported without a per-chip table.
+//! It exposes the chip as a [`NorFlash`], so that it can directly back an
+//! `ariel_os::storage::Storage` or firmware slots:
+//!
+//! ```ignore
+//! let flash = SpiFlash::new(SpiDeviceBus::new(spi_device)).await?;
+//! info!("flash of {} bytes", flash.capacity());
+//! let storage = Storage::new(flash, 0..64 * 1024);
+//! ```
+//!
+//! Chips connected through SPI use [`SpiDeviceBus`], which only transfers data on a single lane.
+//! Chips connected through a (Q)SPI peripheral supporting dual or quad transfers can be driven
+//! through that peripheral by implementing [`Bus`] for it, in which case the fastest read command
+//! supported by both the chip and the bus is used.
+//!
+//! Chips need to support 4 KiB erases, which all SFDP-compliant chips currently on the market
+//! do.
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+mod bus;
+mod flash;
+pub mod sfdp;
+
+#[doc(no_inline)]
+pub use embedded_storage_async::nor_flash::{MultiwriteNorFlash, NorFlash, ReadNorFlash};
+
+pub use bus::{Bus, Command, SpiDeviceBus, Width};
+pub use flash::SpiFlash;
+
+use embedded_storage_async::nor_flash::{NorFlashError, NorFlashErrorKind};
+
+/// Errors of flash chips.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// Communicating over the bus failed.
+    Bus,
+    /// The chip does not provide SFDP tables, or they are invalid.
+    NoSfdp,
+    /// The chip does not support an operation needed by the driver, eg. 4 KiB erases.
+    Unsupported,
+    /// The chip is still busy after the time an operation may take.
+    Timeout,
+    /// The range accessed is out of the range of the chip.
+    OutOfBounds,
+    /// The range erased is not aligned to [`SpiFlash::ERASE_SIZE`](NorFlash::ERASE_SIZE).
+    NotAligned,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Bus => write!(f, "bus error"),
+            Self::NoSfdp => write!(f, "no valid SFDP tables"),
+            Self::Unsupported => write!(f, "unsupported chip"),
+            Self::Timeout => write!(f, "timeout"),
+            Self::OutOfBounds => write!(f, "out of bounds"),
+            Self::NotAligned => write!(f, "not aligned"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl NorFlashError for Error {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            Self::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            Self::NotAligned => NorFlashErrorKind::NotAligned,
+            _ => NorFlashErrorKind::Other,
+        }
+    }
+}
@@ -0,0 +1,310 @@
+//! Parses the Serial Flash Discoverable Parameters (SFDP, JESD216) of flash chips.
+//!
+//! Only the Basic Flash Parameter Table (BFPT) is used, from which [`Parameters`] are derived.
+
+/// Length of the SFDP header, and of each parameter header, in bytes.
+pub(crate) const HEADER_LEN: usize = 8;
+
+/// Maximum length of the BFPT read, in bytes: the parameters used are in its first 15 DWORDs.
+pub(crate) const BFPT_MAX_LEN: usize = 15 * 4;
+
+/// Signature at the start of the SFDP header.
+const SIGNATURE: [u8; 4] = *b"SFDP";
+
+/// Identifier of the BFPT.
+const BFPT_ID: u16 = 0xff00;
+
+/// Major revision of the BFPT this parser understands.
+const BFPT_MAJOR: u8 = 1;
+
+/// The geometry and capabilities of a flash chip, as described by its BFPT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Parameters {
+    /// Capacity of the chip, in bytes.
+    pub capacity: u32,
+    /// Size of the pages that can be programmed at once, in bytes.
+    pub page_size: u32,
+    /// Opcode of the 4 KiB erase, if supported.
+    pub erase_4k_opcode: Option<u8>,
+    /// How the chip is addressed.
+    pub address_mode: AddressMode,
+    /// Dual output (1-1-2) read, if supported.
+    pub dual_output_read: Option<ReadCommand>,
+    /// Dual I/O (1-2-2) read, if supported.
+    pub dual_io_read: Option<ReadCommand>,
+    /// Quad output (1-1-4) read, if supported.
+    pub quad_output_read: Option<ReadCommand>,
+    /// Quad I/O (1-4-4) read, if supported.
+    pub quad_io_read: Option<ReadCommand>,
+    /// How quad transfers are enabled.
+    pub quad_enable: QuadEnable,
+}
+
+/// How a chip is addressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AddressMode {
+    /// With 3-byte addresses only.
+    ThreeByte,
+    /// With 3-byte addresses by default, and with 4-byte addresses once switched to.
+    ThreeOrFourByte,
+    /// With 4-byte addresses only.
+    FourByte,
+}
+
+/// A fast-read command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ReadCommand {
+    /// Opcode of the command.
+    pub opcode: u8,
+    /// Number of clock cycles between the address and the data, including the mode bits.
+    pub dummy_cycles: u8,
+}
+
+/// How quad transfers are enabled, from the Quad Enable Requirements (QER) of the BFPT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum QuadEnable {
+    /// Quad transfers need not be enabled.
+    NotNeeded,
+    /// Bit 6 of status register 1 enables quad transfers.
+    Sr1Bit6,
+    /// Bit 1 of status register 2 enables quad transfers; it is read with `0x35`, and written
+    /// together with status register 1 with `0x01`.
+    Sr2Bit1,
+    /// Bit 1 of status register 2 enables quad transfers; it is read with `0x35`, and written
+    /// with `0x31`.
+    Sr2Bit1Separate,
+    /// Bit 7 of status register 2 enables quad transfers; it is read with `0x3f`, and written
+    /// with `0x3e`.
+    Sr2Bit7,
+    /// The chip does not describe how to enable quad transfers, or in a way that is not
+    /// supported.
+    Unknown,
+}
+
+/// A parameter header, which locates a parameter table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ParameterHeader {
+    id: u16,
+    major: u8,
+    /// Length of the table, in bytes.
+    pub(crate) len: usize,
+    /// Address of the table.
+    pub(crate) pointer: u32,
+}
+
+impl ParameterHeader {
+    pub(crate) fn parse(bytes: [u8; HEADER_LEN]) -> Self {
+        let [id_low, _minor, major, len_dwords, pointer @ .., id_high] = bytes;
+        let [pointer_0, pointer_1, pointer_2] = pointer;
+        Self {
+            id: u16::from_be_bytes([id_high, id_low]),
+            major,
+            len: usize::from(len_dwords) * 4,
+            pointer: u32::from_le_bytes([pointer_0, pointer_1, pointer_2, 0]),
+        }
+    }
+
+    /// Returns whether the header locates a BFPT this parser understands.
+    pub(crate) fn is_bfpt(&self) -> bool {
+        self.id == BFPT_ID && self.major == BFPT_MAJOR
+    }
+}
+
+/// Returns the number of parameter headers following the SFDP `header`, or `None` if it is not
+/// an SFDP header.
+pub(crate) fn parameter_header_count(header: [u8; HEADER_LEN]) -> Option<u32> {
+    let [signature @ .., _minor, _major, count, _access] = header;
+    // The count is stored minus one.
+    (signature == SIGNATURE).then_some(u32::from(count) + 1)
+}
+
+/// Returns the address of the parameter header `index`, which follow the SFDP header.
+pub(crate) fn parameter_header_address(index: u32) -> u32 {
+    (index + 1) * 8
+}
+
+impl Parameters {
+    /// Parses the BFPT `table`.
+    ///
+    /// Returns `None` if the table is shorter than the 9 DWORDs of the first revision of the
+    /// BFPT, or if it describes a chip of more than 4 GiB.
+    pub(crate) fn parse(table: &[u8]) -> Option<Self> {
+        let dword = |n: usize| -> Option<u32> {
+            let bytes = table.get((n - 1) * 4..n * 4)?;
+            Some(u32::from_le_bytes(bytes.try_into().ok()?))
+        };
+        let dword1 = dword(1)?;
+        let dword2 = dword(2)?;
+        let dword3 = dword(3)?;
+        let dword4 = dword(4)?;
+        let erase_types = [dword(8)?, dword(9)?];
+
+        let capacity_bits = if dword2 & (1 << 31) == 0 {
+            u64::from(dword2) + 1
+        } else {
+            1u64.checked_shl(dword2 & !(1 << 31))?
+        };
+        let capacity = u32::try_from(capacity_bits / 8).ok()?;
+
+        // Tables of the first revision do not describe the page size, which is then 256 bytes.
+        let page_size = dword(11).map_or(256, |dword11| 1 << field(dword11, 4, 4));
+
+        let erase_4k_opcode = erase_types
+            .iter()
+            .flat_map(|dword| [field(*dword, 0, 16), field(*dword, 16, 16)])
+            .find(|erase_type| field(*erase_type, 0, 8) == 12)
+            .map(|erase_type| byte(erase_type, 8))
+            .or_else(|| (field(dword1, 0, 2) == 0b01).then_some(byte(dword1, 8)));
+
+        let address_mode = match field(dword1, 17, 2) {
+            0b00 => AddressMode::ThreeByte,
+            0b01 => AddressMode::ThreeOrFourByte,
+            0b10 => AddressMode::FourByte,
+            _ => return None,
+        };
+
+        let read_command = |supported_bit: u32, dword: u32, shift: u32| {
+            (dword1 & (1 << supported_bit) != 0).then(|| ReadCommand {
+                opcode: byte(dword, shift + 8),
+                dummy_cycles: byte(field(dword, shift, 5) + field(dword, shift + 5, 3), 0),
+            })
+        };
+
+        // The QER are only described from the second revision on.
+        let quad_enable = dword(15).map_or(QuadEnable::Unknown, |dword15| {
+            match field(dword15, 20, 3) {
+                0b000 => QuadEnable::NotNeeded,
+                0b010 => QuadEnable::Sr1Bit6,
+                0b011 => QuadEnable::Sr2Bit7,
+                0b100 | 0b101 => QuadEnable::Sr2Bit1,
+                0b110 => QuadEnable::Sr2Bit1Separate,
+                // 0b001 does not allow reading status register 2, which would be cleared.
+                _ => QuadEnable::Unknown,
+            }
+        });
+
+        Some(Self {
+            capacity,
+            page_size,
+            erase_4k_opcode,
+            address_mode,
+            dual_output_read: read_command(16, dword4, 0),
+            dual_io_read: read_command(20, dword4, 16),
+            quad_output_read: read_command(22, dword3, 16),
+            quad_io_read: read_command(21, dword3, 0),
+            quad_enable,
+        })
+    }
+}
+
+/// Returns the `len` bits of `dword` starting at bit `shift`.
+fn field(dword: u32, shift: u32, len: u32) -> u32 {
+    (dword >> shift) & ((1 << len) - 1)
+}
+
+/// Returns the byte of `dword` starting at bit `shift`.
+fn byte(dword: u32, shift: u32) -> u8 {
+    let [byte, ..] = (dword >> shift).to_le_bytes();
+    byte
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SFDP header of a Winbond W25Q128JV.
+    const SFDP_HEADER: [u8; HEADER_LEN] = [0x53, 0x46, 0x44, 0x50, 0x06, 0x01, 0x00, 0xff];
+
+    /// Parameter header of the BFPT of a Winbond W25Q128JV.
+    const BFPT_HEADER: [u8; HEADER_LEN] = [0x00, 0x06, 0x01, 0x10, 0x80, 0x00, 0x00, 0xff];
+
+    /// BFPT of a Winbond W25Q128JV.
+    const BFPT: [u8; 64] = [
+        0xe5, 0x20, 0xf9, 0xff, 0xff, 0xff, 0xff, 0x07, 0x44, 0xeb, 0x08, 0x6b, 0x08, 0x3b, 0x42,
+        0xbb, 0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0xff, 0xff, 0x40, 0xeb, 0x0c, 0x20,
+        0x0f, 0x52, 0x10, 0xd8, 0x00, 0x00, 0x36, 0x02, 0xa6, 0x00, 0x82, 0xea, 0x14, 0xc9, 0xe9,
+        0x63, 0x76, 0x33, 0x7a, 0x75, 0x7a, 0x75, 0xf7, 0xa2, 0xd5, 0x5c, 0x19, 0xf7, 0x4d, 0xff,
+        0xe9, 0x70, 0xf9, 0xa5,
+    ];
+
+    #[test]
+    fn headers() {
+        assert_eq!(parameter_header_count(SFDP_HEADER), Some(1));
+        assert_eq!(parameter_header_count([0; HEADER_LEN]), None);
+
+        let header = ParameterHeader::parse(BFPT_HEADER);
+        assert!(header.is_bfpt());
+        assert_eq!(header.len, 64);
+        assert_eq!(header.pointer, 0x80);
+    }
+
+    #[test]
+    fn bfpt() {
+        let parameters = Parameters::parse(&BFPT).unwrap();
+        assert_eq!(parameters.capacity, 16 * 1024 * 1024);
+        assert_eq!(parameters.page_size, 256);
+        assert_eq!(parameters.erase_4k_opcode, Some(0x20));
+        assert_eq!(parameters.address_mode, AddressMode::ThreeByte);
+        assert_eq!(
+            parameters.dual_output_read,
+            Some(ReadCommand {
+                opcode: 0x3b,
+                dummy_cycles: 8
+            })
+        );
+        assert_eq!(
+            parameters.dual_io_read,
+            Some(ReadCommand {
+                opcode: 0xbb,
+                dummy_cycles: 4
+            })
+        );
+        assert_eq!(
+            parameters.quad_output_read,
+            Some(ReadCommand {
+                opcode: 0x6b,
+                dummy_cycles: 8
+            })
+        );
+        assert_eq!(
+            parameters.quad_io_read,
+            Some(ReadCommand {
+                opcode: 0xeb,
+                dummy_cycles: 6
+            })
+        );
+        assert_eq!(parameters.quad_enable, QuadEnable::Sr2Bit1);
+    }
+
+    #[test]
+    fn first_revision() {
+        // Only the first 9 DWORDs: no page size nor QER.
+        let parameters = Parameters::parse(BFPT.get(..36).unwrap()).unwrap();
+        assert_eq!(parameters.page_size, 256);
+        assert_eq!(parameters.quad_enable, QuadEnable::Unknown);
+        assert!(Parameters::parse(BFPT.get(..32).unwrap()).is_none());
+    }
+
+    #[test]
+    fn large_density() {
+        let with_density = |density: u32| {
+            let mut bfpt = BFPT;
+            for (to, from) in bfpt.iter_mut().skip(4).zip(density.to_le_bytes()) {
+                *to = from;
+            }
+            Parameters::parse(&bfpt)
+        };
+        // 2^31 bits, ie. 256 MiB, in the power-of-two form.
+        assert_eq!(
+            with_density(0x8000_0000 | 0x1f).unwrap().capacity,
+            256 * 1024 * 1024
+        );
+        // 2^35 bits do not fit.
+        assert!(with_density(0x8000_0000 | 0x23).is_none());
+    }
+}
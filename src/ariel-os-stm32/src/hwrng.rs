@@ -1,5 +1,6 @@
 use embassy_stm32::rng::Rng;
 use embassy_stm32::{bind_interrupts, peripherals, rng};
+use static_cell::StaticCell;
 
 #[cfg(not(any(
     capability = "hw/stm32-aes-rng",
@@ -27,14 +28,16 @@ bind_interrupts!(struct Irqs {
 });
 
 pub fn construct_rng(peripherals: &mut crate::OptionalPeripherals) {
+    // The RNG is kept, so that it can be read again to reseed the global RNG.
+    static HWRNG: StaticCell<Rng<'static, peripherals::RNG>> = StaticCell::new();
+
     let rng = Rng::new(
         peripherals
             .RNG
-            // We don't even have to take it out, just use it to seed the RNG
-            .as_mut()
+            .take()
             .expect("RNG has not been previously used"),
         Irqs,
     );
 
-    ariel_os_random::construct_rng(rng);
+    ariel_os_random::construct_rng_from_hwrng(HWRNG.init(rng));
 }
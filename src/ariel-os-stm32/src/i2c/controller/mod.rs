@@ -1,6 +1,10 @@
 //! Provides support for the I2C communication bus in controller mode.
 
-use ariel_os_embassy_common::{i2c::controller::Kilohertz, impl_async_i2c_for_driver_enum};
+use ariel_os_embassy_common::{
+    i2c::controller::{I2C_TIMEOUT, Kilohertz},
+    impl_async_i2c_for_driver_enum,
+    reexports::embassy_time::Duration,
+};
 use embassy_embedded_hal::adapter::{BlockingAsync, YieldingAsync};
 use embassy_stm32::{
     Peripheral, bind_interrupts,
@@ -20,6 +24,9 @@ pub struct Config {
     pub sda_pullup: bool,
     /// Whether to enable the internal pull-up resistor on the SCL pin.
     pub scl_pullup: bool,
+    /// Timeout of each operation on the bus, after which it fails with
+    /// [`Error::Timeout`](ariel_os_embassy_common::i2c::controller::Error::Timeout).
+    pub timeout: Duration,
 }
 
 impl Default for Config {
@@ -28,6 +35,7 @@ impl Default for Config {
             frequency: Frequency::UpTo100k(Kilohertz::kHz(100)),
             sda_pullup: false,
             scl_pullup: false,
+            timeout: I2C_TIMEOUT,
         }
     }
 }
@@ -128,6 +136,7 @@ macro_rules! define_i2c_drivers {
             // other HALs.
             pub struct $peripheral {
                 twim: YieldingAsync<BlockingAsync<InnerI2c<'static, Blocking>>>,
+                timeout: Duration,
             }
 
             impl $peripheral {
@@ -143,7 +152,7 @@ macro_rules! define_i2c_drivers {
                     let mut i2c_config = embassy_stm32::i2c::Config::default();
                     i2c_config.sda_pullup = config.sda_pullup;
                     i2c_config.scl_pullup = config.scl_pullup;
-                    i2c_config.timeout = ariel_os_embassy_common::i2c::controller::I2C_TIMEOUT;
+                    i2c_config.timeout = config.timeout;
 
                     bind_interrupts!(
                         struct Irqs {
@@ -173,7 +182,10 @@ macro_rules! define_i2c_drivers {
                         i2c_config,
                     );
 
-                    I2c::$peripheral(Self { twim: YieldingAsync::new(BlockingAsync::new(i2c)) })
+                    I2c::$peripheral(Self {
+                        twim: YieldingAsync::new(BlockingAsync::new(i2c)),
+                        timeout: config.timeout,
+                    })
                 }
             }
         )*
@@ -186,6 +198,9 @@ macro_rules! define_i2c_drivers {
             )*
         }
 
+        /// Names of the I2C peripherals for which a driver is available.
+        pub const PERIPHERALS: &[&str] = &[$( stringify!($peripheral) ),*];
+
         impl embedded_hal_async::i2c::ErrorType for I2c {
             type Error = ariel_os_embassy_common::i2c::controller::Error;
         }
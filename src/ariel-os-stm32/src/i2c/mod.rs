@@ -9,7 +9,11 @@ pub fn init(peripherals: &mut crate::OptionalPeripherals) {
     macro_rules! take_all_i2c_peripherals {
         ($( $peripheral:ident ),*) => {
             $(
-                let _ = peripherals.$peripheral.take().unwrap();
+                let peripheral = peripherals.$peripheral.take();
+                debug_assert!(
+                    peripheral.is_some(),
+                    concat!(stringify!($peripheral), " was taken before initialization"),
+                );
             )*
         }
     }
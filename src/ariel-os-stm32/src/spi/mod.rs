@@ -27,7 +27,11 @@ pub fn init(peripherals: &mut crate::OptionalPeripherals) {
     macro_rules! take_all_spi_peripherals {
         ($peripherals:ident, $( $peripheral:ident ),*) => {
             $(
-                let _ = peripherals.$peripheral.take().unwrap();
+                let peripheral = peripherals.$peripheral.take();
+                debug_assert!(
+                    peripheral.is_some(),
+                    concat!(stringify!($peripheral), " was taken before initialization"),
+                );
             )*
         }
     }
@@ -0,0 +1,51 @@
+//! Decodes arbitrary bytes as [`PostcardValue`]s of various types, as the storage does with the
+//! values it reads from flash, and checks that the values decoded can be encoded again.
+#![no_main]
+
+use ariel_os_storage::PostcardValue;
+use libfuzzer_sys::fuzz_target;
+use sequential_storage::map::Value;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Record<'a> {
+    id: u32,
+    name: &'a str,
+    data: &'a [u8],
+    flags: Option<[bool; 4]>,
+    kind: Kind,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Kind {
+    Unit,
+    Tuple(i64, u16),
+    Struct { value: f32 },
+}
+
+fn decode<'d, T: Serialize + Deserialize<'d>>(data: &'d [u8]) {
+    let Ok(value) = PostcardValue::<T>::deserialize_from(data) else {
+        return;
+    };
+    // The encoding of a value is never longer than the data it was decoded from.
+    let mut buffer = vec![0; data.len()];
+    value
+        .serialize_into(&mut buffer)
+        .expect("decoded values can be encoded again");
+}
+
+fuzz_target!(|data: &[u8]| {
+    decode::<bool>(data);
+    decode::<u8>(data);
+    decode::<u32>(data);
+    decode::<i64>(data);
+    decode::<f64>(data);
+    decode::<char>(data);
+    decode::<&str>(data);
+    decode::<&[u8]>(data);
+    decode::<[u8; 16]>(data);
+    decode::<Option<u16>>(data);
+    decode::<(u8, &str)>(data);
+    decode::<Kind>(data);
+    decode::<Record<'_>>(data);
+});
@@ -0,0 +1,176 @@
+//! A flash backend for tests, which simulates power losses.
+
+use embedded_storage_async::nor_flash::{
+    ErrorType, MultiwriteNorFlash, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+
+const PAGE_SIZE: usize = 1024;
+
+/// NOR flash held in RAM, which loses power once a configurable number of bytes have been
+/// written or erased.
+///
+/// As on actual flash, the byte that is being written or erased when the power is lost only has
+/// some of its bits changed. All operations then fail until [`FaultyFlash::restore_power()`] is
+/// called, which simulates a reboot.
+pub(crate) struct FaultyFlash {
+    data: Vec<u8>,
+    budget: Option<usize>,
+    powered: bool,
+    random: u32,
+}
+
+/// Error returned by a [`FaultyFlash`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FaultyFlashError {
+    PowerLoss,
+    NotAligned,
+    OutOfBounds,
+}
+
+impl NorFlashError for FaultyFlashError {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            Self::PowerLoss => NorFlashErrorKind::Other,
+            Self::NotAligned => NorFlashErrorKind::NotAligned,
+            Self::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+        }
+    }
+}
+
+impl FaultyFlash {
+    /// Returns an erased flash of `pages` pages.
+    pub(crate) fn new(pages: usize) -> Self {
+        Self {
+            data: vec![0xff; pages * PAGE_SIZE],
+            budget: None,
+            powered: true,
+            random: 0x2545_f491,
+        }
+    }
+
+    /// Makes the power be lost once `bytes` more bytes have been written or erased.
+    pub(crate) fn cut_power_after(&mut self, bytes: usize) {
+        self.budget = Some(bytes);
+    }
+
+    /// Restores the power, and returns whether it was lost.
+    pub(crate) fn restore_power(&mut self) -> bool {
+        self.budget = None;
+        !core::mem::replace(&mut self.powered, true)
+    }
+
+    fn check_power(&self) -> Result<(), FaultyFlashError> {
+        if self.powered {
+            Ok(())
+        } else {
+            Err(FaultyFlashError::PowerLoss)
+        }
+    }
+
+    fn check_range(
+        &self,
+        offset: u32,
+        len: usize,
+        align: usize,
+    ) -> Result<usize, FaultyFlashError> {
+        let offset = offset as usize;
+        if !offset.is_multiple_of(align) || !len.is_multiple_of(align) {
+            return Err(FaultyFlashError::NotAligned);
+        }
+        if offset + len > self.data.len() {
+            return Err(FaultyFlashError::OutOfBounds);
+        }
+        Ok(offset)
+    }
+
+    /// Changes the byte at `index` into `target`, unless the power is lost, in which case only
+    /// some of its bits are changed.
+    fn program(&mut self, index: usize, target: u8) -> Result<(), FaultyFlashError> {
+        let lost = self
+            .budget
+            .as_mut()
+            .is_some_and(|budget| match budget.checked_sub(1) {
+                Some(remaining) => {
+                    *budget = remaining;
+                    false
+                }
+                None => true,
+            });
+        let mask = if lost { self.next_random() } else { 0xff };
+
+        let byte = self
+            .data
+            .get_mut(index)
+            .ok_or(FaultyFlashError::OutOfBounds)?;
+        *byte ^= (*byte ^ target) & mask;
+
+        if lost {
+            self.budget = None;
+            self.powered = false;
+            return Err(FaultyFlashError::PowerLoss);
+        }
+        Ok(())
+    }
+
+    /// Returns a pseudo-random byte, from a xorshift generator.
+    fn next_random(&mut self) -> u8 {
+        let mut x = self.random;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.random = x;
+        let [byte, ..] = x.to_le_bytes();
+        byte
+    }
+}
+
+impl ErrorType for FaultyFlash {
+    type Error = FaultyFlashError;
+}
+
+impl ReadNorFlash for FaultyFlash {
+    const READ_SIZE: usize = 1;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        self.check_power()?;
+        let offset = self.check_range(offset, bytes.len(), Self::READ_SIZE)?;
+        let data = self
+            .data
+            .get(offset..offset + bytes.len())
+            .ok_or(FaultyFlashError::OutOfBounds)?;
+        bytes.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.data.len()
+    }
+}
+
+impl NorFlash for FaultyFlash {
+    const WRITE_SIZE: usize = 4;
+    const ERASE_SIZE: usize = PAGE_SIZE;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        self.check_power()?;
+        let len = to.checked_sub(from).ok_or(FaultyFlashError::OutOfBounds)? as usize;
+        let from = self.check_range(from, len, Self::ERASE_SIZE)?;
+        for index in from..from + len {
+            self.program(index, 0xff)?;
+        }
+        Ok(())
+    }
+
+    async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.check_power()?;
+        let offset = self.check_range(offset, bytes.len(), Self::WRITE_SIZE)?;
+        for (index, byte) in (offset..).zip(bytes) {
+            // Writing can only clear bits.
+            let target = self.data.get(index).ok_or(FaultyFlashError::OutOfBounds)? & byte;
+            self.program(index, target)?;
+        }
+        Ok(())
+    }
+}
+
+impl MultiwriteNorFlash for FaultyFlash {}
@@ -2,16 +2,36 @@
 //!
 //! Currently the same type used for serializing must be used for deserializing.
 //! While not doing so won't cause unsafety, it might return garbage data, or panic.
+//!
+//! With the `write-behind` feature, values inserted into the global storage with
+//! [`insert_deferred()`] are held in RAM, and written to flash periodically (every 60 seconds by
+//! default, configured through the `CONFIG_STORAGE_WRITE_BEHIND_FLUSH_INTERVAL_SECS` environment
+//! variable) or on [`flush()`]. All other inserts are written to flash right away.
 
 #![cfg_attr(not(test), no_std)]
 #![deny(missing_docs)]
 // TODO: overhaul errors
 #![expect(clippy::missing_errors_doc)]
-
+#![cfg_attr(
+    all(feature = "no-panics", not(test)),
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic,
+        clippy::unreachable,
+        clippy::todo,
+        clippy::unimplemented,
+        clippy::indexing_slicing
+    )
+)]
+
+#[cfg(test)]
+mod faulty_flash;
 mod postcard_value;
 mod storage;
-
-use core::ops::Range;
+pub mod table;
+#[cfg(feature = "write-behind")]
+mod write_behind;
 
 use ariel_os_hal::{
     OptionalPeripherals,
@@ -24,61 +44,33 @@ use embassy_sync::{
 };
 
 pub use storage::*;
+pub use table::{Record, Table};
+#[cfg(feature = "write-behind")]
+pub use write_behind::{WRITE_BEHIND_ENTRIES, WRITE_BEHIND_FLUSH_INTERVAL_SECS};
+
+/// The mutex guarding the global storage, see [`storage()`].
+pub type StorageMutex = Mutex<CriticalSectionRawMutex, Storage<Flash>>;
 
-static STORAGE: OnceLock<Mutex<CriticalSectionRawMutex, Storage<Flash>>> = OnceLock::new();
+static STORAGE: OnceLock<StorageMutex> = OnceLock::new();
 
 const MARKER_KEY: &str = "ARIEL_INIT_MARK";
 const MARKER_VALUE: u8 = 0;
 
-/// Gets a [`Range`] from the linker that can be used for a global [`Storage`].
-///
-/// This expects two symbols `__storage_start` and `__storage_end`.
-/// This function is also the place to configure a platform dependent `OFFSET`,
-/// which configures an offset between the linker flash address map and the
-/// flash driver address map.
-fn flash_range_from_linker() -> Range<u32> {
-    #[cfg(all(context = "nrf", not(context = "nrf5340-net")))]
-    const OFFSET: usize = 0x0;
-    #[cfg(context = "nrf5340-net")]
-    const OFFSET: usize = 0x0100_0000;
-    #[cfg(context = "rp")]
-    const OFFSET: usize = 0x1000_0000;
-    #[cfg(context = "stm32")]
-    const OFFSET: usize = 0x0800_0000;
-    // Default for platform-independent tooling.
-    #[cfg(not(context = "ariel-os"))]
-    const OFFSET: usize = 0x0;
-
-    unsafe extern "C" {
-        static __storage_start: u32;
-        static __storage_end: u32;
-    }
-
-    let start = &raw const __storage_start as usize - OFFSET;
-    let end = &raw const __storage_end as usize - OFFSET;
-
-    #[expect(clippy::cast_possible_truncation)]
-    let (start, end) = (start as u32, end as u32);
-
-    start..end
-}
-
 fn init_(p: &mut OptionalPeripherals) {
     use ariel_os_debug::log::info;
-    let flash_range = flash_range_from_linker();
+    let flash_range = ariel_os_hal::inventory::storage_range();
     info!("storage: using flash range {:?}", &flash_range);
 
     let flash = flash_init(p);
-    let _ = STORAGE.init(Mutex::new(Storage::new(flash, flash_range)));
+    let storage = Storage::new(flash, flash_range);
+    #[cfg(feature = "write-behind")]
+    let storage = storage.with_write_behind();
+    let _ = STORAGE.init(Mutex::new(storage));
 }
 
 /// Initializes the global storage.
 ///
 /// Note: this is automatically called by the Ariel OS initialization code.
-///
-/// # Panics
-///
-/// Panics when initializing the flash fails.
 #[doc(hidden)]
 pub async fn init(p: &mut OptionalPeripherals) {
     init_(p);
@@ -93,7 +85,9 @@ pub async fn init(p: &mut OptionalPeripherals) {
     // Use a marker to ensure that this storage is initialized.
     if Ok(Some(MARKER_VALUE)) != get::<u8>(MARKER_KEY).await {
         ariel_os_debug::log::info!("storage: initializing");
-        erase_all().await.unwrap();
+        if erase_all().await.is_err() {
+            ariel_os_debug::log::error!("storage: erasing the flash failed");
+        }
     }
 }
 
@@ -107,6 +101,20 @@ where
     lock().await.insert::<V>(key, value).await
 }
 
+/// Stores a key-value pair, holding it in RAM with the `write-behind` feature until it is
+/// [flushed](flush()).
+///
+/// See [`Storage::insert_deferred()`] for details.
+pub async fn insert_deferred<'d, V>(
+    key: &str,
+    value: V,
+) -> Result<(), sequential_storage::Error<FlashError>>
+where
+    V: Serialize + Deserialize<'d> + Into<PostcardValue<V>>,
+{
+    lock().await.insert_deferred::<V>(key, value).await
+}
+
 /// Gets the last stored value from the flash that is associated with the given key.
 ///
 /// Note: Always [`get()`] the same value type that was [`insert()`]!
@@ -119,6 +127,26 @@ where
     lock().await.get(key).await
 }
 
+/// Stores a byte string of arbitrary length into flash memory.
+///
+/// See [`Storage::insert_blob()`] for details.
+pub async fn insert_blob(
+    key: &str,
+    data: &[u8],
+) -> Result<(), sequential_storage::Error<FlashError>> {
+    lock().await.insert_blob(key, data).await
+}
+
+/// Gets a byte string stored with [`insert_blob()`] into `buffer`.
+///
+/// See [`Storage::get_blob()`] for details.
+pub async fn get_blob<'b>(
+    key: &str,
+    buffer: &'b mut [u8],
+) -> Result<Option<&'b [u8]>, sequential_storage::Error<FlashError>> {
+    lock().await.get_blob(key, buffer).await
+}
+
 /// Deletes an item from flash.
 ///
 /// Additional calls to [`get()`] with the same key will return `None` until
@@ -136,11 +164,20 @@ pub async fn remove(key: &str) -> Result<(), sequential_storage::Error<FlashErro
     lock().await.remove(key).await
 }
 
+/// Writes the items held in RAM with the `write-behind` feature to flash.
+///
+/// See [`Storage::flush()`] for details.
+pub async fn flush() -> Result<(), sequential_storage::Error<FlashError>> {
+    lock().await.flush().await
+}
+
 /// Resets the flash in the entire flash range.
 pub async fn erase_all() -> Result<(), sequential_storage::Error<FlashError>> {
     let mut s = lock().await;
     s.erase_all().await?;
-    s.insert(MARKER_KEY, MARKER_VALUE).await
+    s.insert(MARKER_KEY, MARKER_VALUE).await?;
+    // Without the marker, the storage would be erased again at the next boot.
+    s.flush().await
 }
 
 /// Gets a [`MutexGuard`] of the global [`Storage`] object.
@@ -165,3 +202,8 @@ pub async fn erase_all() -> Result<(), sequential_storage::Error<FlashError>> {
 pub async fn lock() -> MutexGuard<'static, CriticalSectionRawMutex, storage::Storage<Flash>> {
     STORAGE.get().await.lock().await
 }
+
+/// Returns the mutex guarding the global storage, eg. to keep as a handle to [`lock()`] it later.
+pub async fn storage() -> &'static StorageMutex {
+    STORAGE.get().await
+}
@@ -1,30 +1,61 @@
 //! Storage module wrapping [`sequential_storage`] in an object together with
 //! a flash range and backend.
-use core::ops::Range;
+use core::{fmt::Write, ops::Range};
 
-use arrayvec::ArrayString;
+use arrayvec::{ArrayString, ArrayVec};
 use embedded_storage_async::nor_flash::{ErrorType, MultiwriteNorFlash, NorFlash};
 use sequential_storage::{
     cache::NoCache,
     erase_all,
-    map::{Value, fetch_item, remove_item, store_item},
+    map::{SerializationError, Value, fetch_all_items, fetch_item, remove_item, store_item},
 };
 
 pub use crate::postcard_value::PostcardValue;
 pub use serde::{Deserialize, Serialize};
 
-/// Maximum key length.
-pub const MAX_KEY_LEN: usize = 64usize;
-/// Data buffer length.
-pub const DATA_BUFFER_SIZE: usize = 128usize;
+#[cfg(feature = "write-behind")]
+use crate::write_behind::{Cache, Entry};
+
+/// Maximum key length, configured through the `CONFIG_STORAGE_MAX_KEY_LEN` environment variable.
+pub const MAX_KEY_LEN: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_STORAGE_MAX_KEY_LEN",
+    64,
+    "maximum length of storage keys"
+);
+/// Data buffer length, configured through the `CONFIG_STORAGE_DATA_BUFFER_SIZE` environment
+/// variable.
+pub const DATA_BUFFER_SIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_STORAGE_DATA_BUFFER_SIZE",
+    128,
+    "size of the buffer storage items are serialized into"
+);
+/// Length of the chunks into which [`Storage::insert_blob()`] splits its data, configured through
+/// the `CONFIG_STORAGE_BLOB_CHUNK_LEN` environment variable.
+///
+/// It should be chosen so that a chunk and a key close to [`MAX_KEY_LEN`] fit in
+/// [`DATA_BUFFER_SIZE`].
+pub const BLOB_CHUNK_LEN: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_STORAGE_BLOB_CHUNK_LEN",
+    48,
+    "length of the chunks blobs are split into"
+);
 
 /// Object holding an instance of a key-value pair storage.
 ///
 /// You should probably look into using the global instance accessible via
 /// `ariel_os_storage::storage::{get,insert,remove}`.
+#[cfg_attr(
+    feature = "write-behind",
+    expect(
+        clippy::struct_field_names,
+        reason = "`storage_range` predates the cache, and is the range of the storage"
+    )
+)]
 pub struct Storage<F> {
     flash: F,
     storage_range: Range<u32>,
+    #[cfg(feature = "write-behind")]
+    cache: Option<Cache>,
 }
 
 impl<F: NorFlash> Storage<F> {
@@ -33,21 +64,46 @@ impl<F: NorFlash> Storage<F> {
         Self {
             flash,
             storage_range,
+            #[cfg(feature = "write-behind")]
+            cache: None,
         }
     }
 
+    /// Makes this [`Storage`] instance hold items inserted with [`Storage::insert_deferred()`] in
+    /// RAM until they are [flushed](Storage::flush()).
+    ///
+    /// Repeated deferred inserts under the same key then only write the latest value to flash,
+    /// which reduces flash wear for frequently updated values like counters. At most
+    /// [`WRITE_BEHIND_ENTRIES`](crate::WRITE_BEHIND_ENTRIES) keys are held: deferring another key
+    /// flushes first. Other inserts are still written to flash right away.
+    ///
+    /// <div class="warning">
+    /// Items that have not been flushed are lost on reset or power loss.
+    /// </div>
+    #[cfg(feature = "write-behind")]
+    #[must_use]
+    pub fn with_write_behind(mut self) -> Self {
+        self.cache = Some(Cache::new());
+        self
+    }
+
     /// Gets a [`Value`] from this [`Storage`] instance.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Currently panics if `key.len() > MAX_KEY_LEN`.
+    /// Returns [`SerializationError::InvalidData`] if `key.len() > MAX_KEY_LEN`.
     pub async fn get_raw<V: for<'d> Value<'d>>(
         &mut self,
         key: &str,
     ) -> Result<Option<V>, sequential_storage::Error<<F as ErrorType>::Error>> {
-        let key = ArrayString::<MAX_KEY_LEN>::from(key).unwrap();
-        let mut data_buffer = [0; DATA_BUFFER_SIZE];
+        let key = key_from(key)?;
+
+        #[cfg(feature = "write-behind")]
+        if let Some(data) = self.cache.as_ref().and_then(|cache| cache.get(&key)) {
+            return Ok(Some(V::deserialize_from(data)?));
+        }
 
+        let mut data_buffer = [0; DATA_BUFFER_SIZE];
         fetch_item::<_, V, _>(
             &mut self.flash,
             self.storage_range.clone(),
@@ -58,17 +114,113 @@ impl<F: NorFlash> Storage<F> {
         .await
     }
 
+    /// Gets the serialized value of an item into `buffer`, whatever type it was stored as.
+    ///
+    /// On success, the part of the buffer that was populated is returned. If no item with the key
+    /// is found, `None` is returned. The value can be stored back with [`Storage::insert_raw()`],
+    /// which makes this suitable for inspecting and editing items from the outside, eg. by host
+    /// tools.
+    ///
+    /// As the item is read into `buffer` as a whole, `buffer` should be [`DATA_BUFFER_SIZE`] bytes
+    /// long.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializationError::InvalidData`] if `key.len() > MAX_KEY_LEN`, and
+    /// [`sequential_storage::Error::BufferTooSmall`] if the item does not fit into `buffer`.
+    pub async fn get_serialized<'b>(
+        &mut self,
+        key: &str,
+        buffer: &'b mut [u8],
+    ) -> Result<Option<&'b [u8]>, sequential_storage::Error<<F as ErrorType>::Error>> {
+        let key = key_from(key)?;
+
+        #[cfg(feature = "write-behind")]
+        if let Some(data) = self.cache.as_ref().and_then(|cache| cache.get(&key)) {
+            let Some(buffer) = buffer.get_mut(..data.len()) else {
+                return Err(sequential_storage::Error::BufferTooSmall(data.len()));
+            };
+            buffer.copy_from_slice(data);
+            return Ok(Some(buffer));
+        }
+
+        fetch_item::<_, &[u8], _>(
+            &mut self.flash,
+            self.storage_range.clone(),
+            &mut NoCache::new(),
+            buffer,
+            &key,
+        )
+        .await
+    }
+
+    /// Returns the keys of the items in this [`Storage`] instance, in no particular order.
+    ///
+    /// This includes the keys under which [blobs](Storage::insert_blob()) store their chunks.
+    ///
+    /// <div class="warning">
+    /// This is slow, as all items in flash have to be read.
+    /// </div>
+    ///
+    /// # Errors
+    ///
+    /// Returns [`sequential_storage::Error::BufferTooSmall`] if there are more than `N` keys.
+    pub async fn keys<const N: usize>(
+        &mut self,
+    ) -> Result<
+        ArrayVec<ArrayString<MAX_KEY_LEN>, N>,
+        sequential_storage::Error<<F as ErrorType>::Error>,
+    > {
+        let mut keys = ArrayVec::<_, N>::new();
+        let mut add = |key| -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
+            // Replaced items are only erased once their page is reclaimed.
+            if !keys.contains(&key) {
+                keys.try_push(key)
+                    .map_err(|_| sequential_storage::Error::BufferTooSmall(N + 1))?;
+            }
+            Ok(())
+        };
+
+        #[cfg(feature = "write-behind")]
+        if let Some(cache) = &self.cache {
+            for key in cache.keys() {
+                add(*key)?;
+            }
+        }
+
+        let mut data_buffer = [0; DATA_BUFFER_SIZE];
+        let mut cache = NoCache::new();
+        let mut items = fetch_all_items::<ArrayString<MAX_KEY_LEN>, _, _>(
+            &mut self.flash,
+            self.storage_range.clone(),
+            &mut cache,
+            &mut data_buffer,
+        )
+        .await?;
+        while let Some((key, _)) = items
+            .next::<ArrayString<MAX_KEY_LEN>, &[u8]>(&mut data_buffer)
+            .await?
+        {
+            add(key)?;
+        }
+        Ok(keys)
+    }
+
     /// Inserts a [`Value`] into this [`Storage`] instance.
     ///
-    /// # Panics
+    /// The value is written to flash right away, replacing any value held in RAM under the same key
+    /// in write-behind mode.
+    ///
+    /// # Errors
     ///
-    /// Currently panics if `key.len() > MAX_KEY_LEN`.
+    /// Returns [`SerializationError::InvalidData`] if `key.len() > MAX_KEY_LEN`.
     pub async fn insert_raw<'d, V: Value<'d>>(
         &mut self,
         key: &str,
         value: V,
     ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
-        let key = ArrayString::<MAX_KEY_LEN>::from(key).unwrap();
+        let key = key_from(key)?;
+
         let mut data_buffer = [0; DATA_BUFFER_SIZE];
         store_item(
             &mut self.flash,
@@ -78,7 +230,47 @@ impl<F: NorFlash> Storage<F> {
             &key,
             &value,
         )
-        .await
+        .await?;
+
+        // A flush would overwrite the value with the older one otherwise.
+        #[cfg(feature = "write-behind")]
+        if let Some(cache) = &mut self.cache {
+            cache.remove(&key);
+        }
+        Ok(())
+    }
+
+    /// Inserts a [`Value`] into this [`Storage`] instance, holding it in RAM in write-behind mode
+    /// until it is [flushed](Storage::flush()).
+    ///
+    /// Without write-behind mode, this is the same as [`Storage::insert_raw()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializationError::InvalidData`] if `key.len() > MAX_KEY_LEN`.
+    pub async fn insert_raw_deferred<'d, V: Value<'d>>(
+        &mut self,
+        key: &str,
+        value: V,
+    ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
+        #[cfg(feature = "write-behind")]
+        if let Some(cache) = &self.cache {
+            let key = key_from(key)?;
+            let mut data = ArrayVec::from([0; DATA_BUFFER_SIZE]);
+            let len = value.serialize_into(&mut data)?;
+            data.truncate(len);
+            if !cache.has_room_for(&key) {
+                self.flush().await?;
+            }
+            if let Some(cache) = &mut self.cache {
+                cache
+                    .insert(Entry { key, data })
+                    .map_err(|_| sequential_storage::Error::FullStorage)?;
+            }
+            return Ok(());
+        }
+
+        self.insert_raw(key, value).await
     }
 
     /// Stores a key-value pair into flash memory.
@@ -95,13 +287,34 @@ impl<F: NorFlash> Storage<F> {
         self.insert_raw(key, value.into()).await
     }
 
+    /// Stores a key-value pair, holding it in RAM in write-behind mode until it is
+    /// [flushed](Storage::flush()).
+    ///
+    /// This suits values that are updated frequently and can afford to lose their latest updates,
+    /// like counters; see [`Storage::with_write_behind()`]. Without write-behind mode, this is the
+    /// same as [`Storage::insert()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializationError::InvalidData`] if `key.len() > MAX_KEY_LEN`.
+    pub async fn insert_deferred<'d, V>(
+        &mut self,
+        key: &str,
+        value: V,
+    ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>>
+    where
+        V: Serialize + Deserialize<'d> + Into<PostcardValue<V>>,
+    {
+        self.insert_raw_deferred(key, value.into()).await
+    }
+
     /// Gets the last stored value from the flash that is associated with the given key.
     ///
     /// If no value with the key is found, `None` is returned.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Currently panics if `key.len() > MAX_KEY_LEN`.
+    /// Returns [`SerializationError::InvalidData`] if `key.len() > MAX_KEY_LEN`.
     pub async fn get<V>(
         &mut self,
         key: &str,
@@ -109,24 +322,129 @@ impl<F: NorFlash> Storage<F> {
     where
         V: Serialize + for<'d> Deserialize<'d> + Into<PostcardValue<V>>,
     {
-        let key = ArrayString::<MAX_KEY_LEN>::from(key).unwrap();
-        let mut data_buffer = [0; DATA_BUFFER_SIZE];
+        let postcard_value = self.get_raw::<PostcardValue<V>>(key).await?;
+        Ok(postcard_value.map(PostcardValue::into_inner))
+    }
 
-        let postcard_value = fetch_item::<_, PostcardValue<V>, _>(
-            &mut self.flash,
-            self.storage_range.clone(),
-            &mut NoCache::new(),
-            &mut data_buffer,
-            &key,
+    /// Stores a byte string of arbitrary length into flash memory.
+    ///
+    /// Unlike [`Storage::insert()`], this is not limited by [`DATA_BUFFER_SIZE`]: The data is split
+    /// into chunks of [`BLOB_CHUNK_LEN`] bytes, which are stored under keys derived from `key`,
+    /// while `key` itself holds the total length.
+    ///
+    /// <div class="warning">
+    /// Replacing a blob is not atomic: if power is lost during the operation, the blob may read
+    /// back as a mix of the old and the new data.
+    /// </div>
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializationError::InvalidData`] if the derived chunk keys are longer than
+    /// [`MAX_KEY_LEN`], which happens when `key` is close to that length.
+    pub async fn insert_blob(
+        &mut self,
+        key: &str,
+        data: &[u8],
+    ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
+        let len = u32::try_from(data.len()).map_err(|_| sequential_storage::Error::ItemTooBig)?;
+        for (index, chunk) in data.chunks(BLOB_CHUNK_LEN).enumerate() {
+            let mut padded = [0; BLOB_CHUNK_LEN];
+            for (padded, byte) in padded.iter_mut().zip(chunk) {
+                *padded = *byte;
+            }
+            self.insert_raw(&blob_chunk_key(key, index)?, padded)
+                .await?;
+        }
+        // Written last, so that a blob that was never completely written does not show up.
+        self.insert_raw(key, len).await
+    }
+
+    /// Gets a byte string stored with [`Storage::insert_blob()`] into `buffer`.
+    ///
+    /// On success, the part of the buffer that was populated is returned. If no blob with the key
+    /// is found, `None` is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`sequential_storage::Error::BufferTooSmall`] with the required length if the blob
+    /// does not fit into `buffer`, and the same errors as [`Storage::insert_blob()`] otherwise.
+    pub async fn get_blob<'b>(
+        &mut self,
+        key: &str,
+        buffer: &'b mut [u8],
+    ) -> Result<Option<&'b [u8]>, sequential_storage::Error<<F as ErrorType>::Error>> {
+        let Some(len) = self.get_raw::<u32>(key).await? else {
+            return Ok(None);
+        };
+        let len = len as usize;
+        let Some(buffer) = buffer.get_mut(..len) else {
+            return Err(sequential_storage::Error::BufferTooSmall(len));
+        };
+        for (index, chunk) in buffer.chunks_mut(BLOB_CHUNK_LEN).enumerate() {
+            let padded: [u8; BLOB_CHUNK_LEN] = self
+                .get_raw(&blob_chunk_key(key, index)?)
+                .await?
+                .ok_or(sequential_storage::Error::Corrupted {})?;
+            for (byte, padded) in chunk.iter_mut().zip(padded) {
+                *byte = padded;
+            }
+        }
+        Ok(Some(buffer))
+    }
+
+    /// Returns the flash driver of this [`Storage`] instance.
+    ///
+    /// This allows other components (such as firmware updates) to use the parts of the flash that
+    /// lie outside of the storage range. Writing inside the storage range corrupts the storage.
+    pub fn flash_mut(&mut self) -> &mut F {
+        &mut self.flash
+    }
+
+    /// Writes the items held in RAM in write-behind mode to flash.
+    ///
+    /// The global instance is flushed periodically; this should also be called when power is about
+    /// to be lost, eg. on a power-fail notification, and before rebooting.
+    ///
+    /// Items are written oldest first; on error, the items not written yet are kept in RAM.
+    #[cfg_attr(
+        not(feature = "write-behind"),
+        expect(
+            clippy::unused_async,
+            reason = "nothing is held in RAM without write-behind mode"
         )
-        .await?;
-        Ok(postcard_value.map(PostcardValue::into_inner))
+    )]
+    pub async fn flush(
+        &mut self,
+    ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
+        #[cfg(feature = "write-behind")]
+        while let Some(entry) = self.cache.as_ref().and_then(Cache::first) {
+            let mut data_buffer = [0; DATA_BUFFER_SIZE];
+            store_item(
+                &mut self.flash,
+                self.storage_range.clone(),
+                &mut NoCache::new(),
+                &mut data_buffer,
+                &entry.key,
+                &entry.data.as_slice(),
+            )
+            .await?;
+            if let Some(cache) = &mut self.cache {
+                cache.pop_first();
+            }
+        }
+        Ok(())
     }
 
     /// Resets the flash in the entire flash range of this [`Storage`] instance.
+    ///
+    /// This also drops the items held in RAM in write-behind mode.
     pub async fn erase_all(
         &mut self,
     ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
+        #[cfg(feature = "write-behind")]
+        if let Some(cache) = &mut self.cache {
+            cache.clear();
+        }
         erase_all(&mut self.flash, self.storage_range.clone()).await
     }
 }
@@ -144,14 +462,21 @@ impl<F: MultiwriteNorFlash> Storage<F> {
     /// This is unlikely to be cached well.
     /// </div>
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Currently panics if `key.len() > MAX_KEY_LEN`.
+    /// Returns [`SerializationError::InvalidData`] if `key.len() > MAX_KEY_LEN`.
     pub async fn remove(
         &mut self,
         key: &str,
     ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
-        let key = ArrayString::<MAX_KEY_LEN>::from(key).unwrap();
+        let key = key_from(key)?;
+
+        // An older value may have been flushed already.
+        #[cfg(feature = "write-behind")]
+        if let Some(cache) = &mut self.cache {
+            cache.remove(&key);
+        }
+
         let mut data_buffer = [0; DATA_BUFFER_SIZE];
         remove_item(
             &mut self.flash,
@@ -163,3 +488,295 @@ impl<F: MultiwriteNorFlash> Storage<F> {
         .await
     }
 }
+
+/// Copies `key` into a key buffer.
+///
+/// # Errors
+///
+/// Returns [`SerializationError::InvalidData`] if `key` is longer than [`MAX_KEY_LEN`].
+pub(crate) fn key_from(key: &str) -> Result<ArrayString<MAX_KEY_LEN>, SerializationError> {
+    ArrayString::from(key).map_err(|_| SerializationError::InvalidData)
+}
+
+/// Builds the key under which chunk number `index` of the blob stored at `key` is found.
+///
+/// # Errors
+///
+/// Returns [`SerializationError::InvalidData`] if the resulting key is longer than
+/// [`MAX_KEY_LEN`].
+fn blob_chunk_key(key: &str, index: usize) -> Result<ArrayString<MAX_KEY_LEN>, SerializationError> {
+    let mut chunk_key = ArrayString::new();
+    write!(chunk_key, "{key}#{index}").map_err(|_| SerializationError::InvalidData)?;
+    Ok(chunk_key)
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use arrayvec::ArrayString;
+    use embassy_futures::block_on;
+    use embedded_storage_async::nor_flash::ReadNorFlash;
+
+    use sequential_storage::{Error, map::SerializationError};
+
+    use super::{DATA_BUFFER_SIZE, MAX_KEY_LEN, Storage};
+    use crate::faulty_flash::FaultyFlash;
+
+    fn storage(pages: usize) -> Storage<FaultyFlash> {
+        let flash = FaultyFlash::new(pages);
+        let end = u32::try_from(flash.capacity()).unwrap();
+        Storage::new(flash, 0..end)
+    }
+
+    #[test]
+    fn insert_get_remove() {
+        block_on(async {
+            let mut storage = storage(2);
+
+            assert_eq!(storage.get::<u32>("key").await.unwrap(), None);
+            storage.insert("key", 1u32).await.unwrap();
+            storage.insert("key", 2u32).await.unwrap();
+            assert_eq!(storage.get::<u32>("key").await.unwrap(), Some(2));
+            storage.remove("key").await.unwrap();
+            assert_eq!(storage.get::<u32>("key").await.unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn keys_and_serialized_values() {
+        block_on(async {
+            let mut storage = storage(2);
+            storage.insert("a", 1u32).await.unwrap();
+            storage.insert("b", 2u32).await.unwrap();
+            storage.insert("a", 3u32).await.unwrap();
+            storage.remove("b").await.unwrap();
+            storage.insert_blob("c", &[4, 5]).await.unwrap();
+
+            let keys = storage.keys::<4>().await.unwrap();
+            let mut keys: Vec<_> = keys.iter().map(ArrayString::as_str).collect();
+            keys.sort_unstable();
+            assert_eq!(keys, ["a", "c", "c#0"]);
+            assert!(storage.keys::<2>().await.is_err());
+
+            let mut buffer = [0; DATA_BUFFER_SIZE];
+            let serialized = storage.get_serialized("a", &mut buffer).await.unwrap();
+            storage.insert_raw("d", serialized.unwrap()).await.unwrap();
+            assert_eq!(storage.get::<u32>("d").await.unwrap(), Some(3));
+            assert_eq!(
+                storage.get_serialized("b", &mut buffer).await.unwrap(),
+                None
+            );
+        });
+    }
+
+    /// Checks that keys too long for [`MAX_KEY_LEN`] are reported as errors instead of panicking,
+    /// which the `no-panics` feature relies on.
+    #[test]
+    fn overlong_keys_are_errors() {
+        fn is_invalid<T, E>(result: &Result<T, Error<E>>) -> bool {
+            matches!(
+                result,
+                Err(Error::SerializationError(SerializationError::InvalidData))
+            )
+        }
+
+        block_on(async {
+            let mut storage = storage(2);
+            let long = "k".repeat(MAX_KEY_LEN + 1);
+            let mut buffer = [0; DATA_BUFFER_SIZE];
+
+            assert!(is_invalid(&storage.insert(&long, 1u32).await));
+            assert!(is_invalid(&storage.get::<u32>(&long).await));
+            assert!(is_invalid(
+                &storage.get_serialized(&long, &mut buffer).await
+            ));
+            assert!(is_invalid(&storage.remove(&long).await));
+            assert!(is_invalid(&storage.get_blob(&long, &mut buffer).await));
+
+            // The key itself fits, but the keys derived for its chunks do not.
+            let longest = "k".repeat(MAX_KEY_LEN);
+            assert!(is_invalid(&storage.insert_blob(&longest, &[1, 2]).await));
+
+            // The storage is still usable afterwards.
+            storage.insert(&longest, 1u32).await.unwrap();
+            assert_eq!(storage.get::<u32>(&longest).await.unwrap(), Some(1));
+        });
+    }
+
+    /// Loses power at every possible point while replacing a value, and checks that the key then
+    /// holds either the old or the new value, and that the storage is still usable.
+    #[test]
+    fn power_loss_during_insert() {
+        block_on(async {
+            for cut in 0.. {
+                let mut storage = storage(2);
+                storage.insert("key", 1u32).await.unwrap();
+                storage.insert("other", 10u32).await.unwrap();
+
+                storage.flash_mut().cut_power_after(cut);
+                let result = storage.insert("key", 2u32).await;
+                let lost = storage.flash_mut().restore_power();
+                assert_eq!(result.is_err(), lost);
+
+                let value = storage.get::<u32>("key").await.unwrap();
+                assert_eq!(storage.get::<u32>("other").await.unwrap(), Some(10));
+                if !lost {
+                    assert_eq!(value, Some(2));
+                    break;
+                }
+                assert!(
+                    matches!(value, Some(1 | 2)),
+                    "cut after {cut} bytes: {value:?}"
+                );
+
+                storage.insert("key", 3u32).await.unwrap();
+                assert_eq!(storage.get::<u32>("key").await.unwrap(), Some(3));
+            }
+        });
+    }
+
+    #[cfg(feature = "write-behind")]
+    #[test]
+    fn write_behind_coalesces_inserts() {
+        block_on(async {
+            let mut storage = storage(2).with_write_behind();
+
+            // Any write to flash would lose power.
+            storage.flash_mut().cut_power_after(0);
+            for i in 0..100u32 {
+                storage.insert_deferred("counter", i).await.unwrap();
+            }
+            assert_eq!(storage.get::<u32>("counter").await.unwrap(), Some(99));
+            assert!(!storage.flash_mut().restore_power());
+
+            storage.flush().await.unwrap();
+            let Storage {
+                flash,
+                storage_range,
+                ..
+            } = storage;
+            let mut storage = Storage::new(flash, storage_range);
+            assert_eq!(storage.get::<u32>("counter").await.unwrap(), Some(99));
+        });
+    }
+
+    #[cfg(feature = "write-behind")]
+    #[test]
+    fn write_behind_flushes_when_full() {
+        use crate::WRITE_BEHIND_ENTRIES;
+
+        block_on(async {
+            let mut storage = storage(2).with_write_behind();
+            storage.insert_deferred("key", 1u32).await.unwrap();
+            storage.remove("key").await.unwrap();
+            assert_eq!(storage.get::<u32>("key").await.unwrap(), None);
+
+            storage.flash_mut().cut_power_after(0);
+            for i in 0..WRITE_BEHIND_ENTRIES {
+                storage
+                    .insert_deferred(&format!("key{i}"), i)
+                    .await
+                    .unwrap();
+            }
+            // A new key does not fit, and the held items fail to be flushed.
+            assert!(storage.insert_deferred("other", 0usize).await.is_err());
+            assert!(storage.flash_mut().restore_power());
+
+            storage.insert_deferred("other", 0usize).await.unwrap();
+            assert_eq!(storage.get::<usize>("key0").await.unwrap(), Some(0));
+            assert_eq!(storage.get::<usize>("other").await.unwrap(), Some(0));
+        });
+    }
+
+    #[cfg(feature = "write-behind")]
+    #[test]
+    fn write_behind_writes_through_other_inserts() {
+        block_on(async {
+            let mut storage = storage(2).with_write_behind();
+            storage.insert_deferred("key", 1u32).await.unwrap();
+            storage.insert("key", 2u32).await.unwrap();
+            storage.insert_blob("blob", &[3, 4]).await.unwrap();
+
+            // Nothing is held in RAM: the held value was replaced, and would not overwrite the new
+            // one on flush.
+            storage.flash_mut().cut_power_after(0);
+            storage.flush().await.unwrap();
+            assert!(!storage.flash_mut().restore_power());
+
+            let Storage {
+                flash,
+                storage_range,
+                ..
+            } = storage;
+            let mut storage = Storage::new(flash, storage_range);
+            assert_eq!(storage.get::<u32>("key").await.unwrap(), Some(2));
+            let mut buffer = [0; 2];
+            assert_eq!(
+                storage.get_blob("blob", &mut buffer).await.unwrap(),
+                Some(&[3, 4][..])
+            );
+        });
+    }
+
+    /// Loses power at every possible point while removing a value, and checks that the key is
+    /// then either removed or untouched.
+    #[test]
+    fn power_loss_during_remove() {
+        block_on(async {
+            for cut in 0.. {
+                let mut storage = storage(2);
+                storage.insert("key", 1u32).await.unwrap();
+                storage.insert("other", 10u32).await.unwrap();
+
+                storage.flash_mut().cut_power_after(cut);
+                let result = storage.remove("key").await;
+                let lost = storage.flash_mut().restore_power();
+                assert_eq!(result.is_err(), lost);
+
+                let value = storage.get::<u32>("key").await.unwrap();
+                assert_eq!(storage.get::<u32>("other").await.unwrap(), Some(10));
+                if !lost {
+                    assert_eq!(value, None);
+                    break;
+                }
+                assert!(
+                    matches!(value, Some(1) | None),
+                    "cut after {cut} bytes: {value:?}"
+                );
+            }
+        });
+    }
+
+    /// Loses power during many replacements of a value, which make the storage erase pages to
+    /// reclaim space, and checks that no value is lost.
+    #[test]
+    fn power_loss_during_garbage_collection() {
+        block_on(async {
+            let mut storage = storage(3);
+            storage.insert("other", 10u32).await.unwrap();
+            let mut previous = None;
+
+            for i in 0..1000u32 {
+                // Large enough for some page erasures to complete.
+                storage
+                    .flash_mut()
+                    .cut_power_after((i as usize * 37) % 1500);
+                let result = storage.insert("key", i).await;
+                let lost = storage.flash_mut().restore_power();
+                assert_eq!(result.is_err(), lost);
+
+                let value = storage.get::<u32>("key").await.unwrap();
+                assert_eq!(storage.get::<u32>("other").await.unwrap(), Some(10));
+                if lost {
+                    assert!(
+                        value == previous || value == Some(i),
+                        "iteration {i}: {value:?}"
+                    );
+                } else {
+                    assert_eq!(value, Some(i));
+                }
+                previous = value;
+            }
+        });
+    }
+}
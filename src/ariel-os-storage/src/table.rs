@@ -0,0 +1,459 @@
+//! Tables of structured records, kept in the key-value storage.
+//!
+//! A table holds records of a single type, which implements [`Record`], usually through its
+//! derive macro:
+//!
+//! ```ignore
+//! use ariel_os::storage::Record;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize, Record)]
+//! #[record(table = "peers")]
+//! struct Peer {
+//!     #[record(key)]
+//!     id: u32,
+//!     #[record(index)]
+//!     kid: [u8; 4],
+//!     sequence_number: u64,
+//! }
+//!
+//! let mut storage = ariel_os::storage::lock().await;
+//! let mut peers = storage.table::<Peer>();
+//! peers.insert(&Peer { id: 7, kid: [1, 2, 3, 4], sequence_number: 0 }).await?;
+//! let peer = peers.find(&[1, 2, 3, 4]).await?;
+//! for id in peers.keys(..100).await? {
+//!     let peer = peers.get(id).await?;
+//! }
+//! ```
+//!
+//! Records are identified by an integer key, by which they can be listed in ranges, and can be
+//! found by the value of one further field, which is indexed.
+//!
+//! Each table has a directory, stored under the name of the table, which holds the schema of the
+//! table, and the key and a hash of the indexed field of each record. Records are stored under
+//! `<table>/<key>`, serialized with [`postcard`]. A table holds at most [`TABLE_MAX_RECORDS`]
+//! records, of at most [`TABLE_MAX_RECORD_SIZE`] bytes each once serialized.
+//!
+//! The schema of a table is a hash of the names and types of the fields of its records. A table
+//! whose schema changed, eg. after a firmware update, fails to be read until it is
+//! [cleared](Table::clear()).
+use core::{marker::PhantomData, ops::RangeBounds};
+
+use arrayvec::{ArrayString, ArrayVec};
+use embedded_storage_async::nor_flash::{ErrorType, NorFlash};
+use sequential_storage::map::SerializationError;
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{MAX_KEY_LEN, Storage};
+
+/// Derives [`Record`] for a struct with named fields.
+///
+/// See the [`table`](crate::table) module for an example.
+pub use ariel_os_macros::Record;
+
+/// Maximum number of records in a table, configured through the
+/// `CONFIG_STORAGE_TABLE_MAX_RECORDS` environment variable.
+pub const TABLE_MAX_RECORDS: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_STORAGE_TABLE_MAX_RECORDS",
+    16,
+    "maximum number of records in a storage table"
+);
+
+/// Maximum size of a serialized record, configured through the
+/// `CONFIG_STORAGE_TABLE_MAX_RECORD_SIZE` environment variable.
+pub const TABLE_MAX_RECORD_SIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_STORAGE_TABLE_MAX_RECORD_SIZE",
+    128,
+    "maximum size of a serialized storage table record, in bytes"
+);
+
+/// Size of a serialized directory entry: the key and the index hash.
+const ENTRY_SIZE: usize = 8;
+/// Size of a serialized directory: the schema, and the entries.
+const DIRECTORY_SIZE: usize = 4 + TABLE_MAX_RECORDS * ENTRY_SIZE;
+
+/// A record of a table.
+///
+/// This is usually implemented through its derive macro.
+pub trait Record: Serialize + for<'d> Deserialize<'d> {
+    /// Name of the table, under which it is stored.
+    const TABLE: &'static str;
+    /// Hash of the names and types of the fields of the record.
+    const SCHEMA: u32;
+
+    /// Type of the indexed field.
+    type Index: Serialize + PartialEq;
+
+    /// Returns the key of the record.
+    fn key(&self) -> u32;
+
+    /// Returns the value of the indexed field.
+    fn index(&self) -> &Self::Index;
+}
+
+impl<F: NorFlash> Storage<F> {
+    /// Returns the table of records `R` in this [`Storage`] instance.
+    pub fn table<R: Record>(&mut self) -> Table<'_, F, R> {
+        Table {
+            storage: self,
+            _record: PhantomData,
+        }
+    }
+}
+
+/// The table of records `R` in a [`Storage`] instance, obtained through [`Storage::table()`].
+///
+/// # Errors
+///
+/// Besides the errors of the storage, operations return
+/// [`SerializationError::InvalidData`] if the schema of the table changed, or a record cannot be
+/// deserialized, and [`SerializationError::BufferTooSmall`] if a record is larger than
+/// [`TABLE_MAX_RECORD_SIZE`].
+pub struct Table<'s, F, R> {
+    storage: &'s mut Storage<F>,
+    _record: PhantomData<R>,
+}
+
+impl<F: NorFlash, R: Record> Table<'_, F, R> {
+    /// Inserts `record`, replacing the record with the same key, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`sequential_storage::Error::FullStorage`] if the table already holds
+    /// [`TABLE_MAX_RECORDS`] records, and [`SerializationError::InvalidData`] if the name of the
+    /// table is too long for the keys of its records.
+    pub async fn insert(
+        &mut self,
+        record: &R,
+    ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
+        let mut buffer = [0; TABLE_MAX_RECORD_SIZE];
+        let serialized = serialize(record, &mut buffer)?;
+        let entry = Entry {
+            key: record.key(),
+            index: index_hash(record.index())?,
+        };
+
+        let mut directory = self.directory().await?;
+        match directory.binary_search_by_key(&entry.key, |e| e.key) {
+            Ok(position) => {
+                if let Some(existing) = directory.get_mut(position) {
+                    *existing = entry;
+                }
+            }
+            Err(position) => directory
+                .try_insert(position, entry)
+                .map_err(|_| sequential_storage::Error::FullStorage)?,
+        }
+
+        // Written first, so that the directory never lists a record that was never written.
+        self.storage
+            .insert_blob(&record_key::<R>(entry.key)?, serialized)
+            .await?;
+        self.store_directory(&directory).await
+    }
+
+    /// Returns the record with the given key, if any.
+    pub async fn get(
+        &mut self,
+        key: u32,
+    ) -> Result<Option<R>, sequential_storage::Error<<F as ErrorType>::Error>> {
+        let directory = self.directory().await?;
+        if directory.binary_search_by_key(&key, |e| e.key).is_err() {
+            return Ok(None);
+        }
+        self.read(key).await.map(Some)
+    }
+
+    /// Returns the first record, by key, whose indexed field equals `index`, if any.
+    pub async fn find(
+        &mut self,
+        index: &R::Index,
+    ) -> Result<Option<R>, sequential_storage::Error<<F as ErrorType>::Error>> {
+        let hash = index_hash(index)?;
+        let directory = self.directory().await?;
+        for entry in directory.iter().filter(|e| e.index == hash) {
+            let record = self.read(entry.key).await?;
+            // Hashes may collide.
+            if record.index() == index {
+                return Ok(Some(record));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the keys of the records in `range`, in ascending order.
+    pub async fn keys(
+        &mut self,
+        range: impl RangeBounds<u32>,
+    ) -> Result<ArrayVec<u32, TABLE_MAX_RECORDS>, sequential_storage::Error<<F as ErrorType>::Error>>
+    {
+        let directory = self.directory().await?;
+        Ok(directory
+            .iter()
+            .map(|e| e.key)
+            .filter(|key| range.contains(key))
+            .collect())
+    }
+
+    /// Removes the record with the given key, if any.
+    ///
+    /// The record is only removed from the directory of the table; its data stays in flash until
+    /// it is overwritten.
+    pub async fn remove(
+        &mut self,
+        key: u32,
+    ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
+        let mut directory = self.directory().await?;
+        if let Ok(position) = directory.binary_search_by_key(&key, |e| e.key) {
+            directory.remove(position);
+            self.store_directory(&directory).await?;
+        }
+        Ok(())
+    }
+
+    /// Removes all records, and adopts the current schema of the table.
+    pub async fn clear(
+        &mut self,
+    ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
+        self.store_directory(&[]).await
+    }
+
+    /// Reads the record with the given key, which the directory lists.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`sequential_storage::Error::Corrupted`] if the record is missing.
+    async fn read(
+        &mut self,
+        key: u32,
+    ) -> Result<R, sequential_storage::Error<<F as ErrorType>::Error>> {
+        let mut buffer = [0; TABLE_MAX_RECORD_SIZE];
+        let serialized = self
+            .storage
+            .get_blob(&record_key::<R>(key)?, &mut buffer)
+            .await?
+            .ok_or(sequential_storage::Error::Corrupted {})?;
+        postcard::from_bytes(serialized).map_err(|_| SerializationError::InvalidData.into())
+    }
+
+    /// Reads the entries of the directory of the table, sorted by key.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializationError::InvalidData`] if the schema of the table changed.
+    async fn directory(
+        &mut self,
+    ) -> Result<
+        ArrayVec<Entry, TABLE_MAX_RECORDS>,
+        sequential_storage::Error<<F as ErrorType>::Error>,
+    > {
+        let mut buffer = [0; DIRECTORY_SIZE];
+        let Some(serialized) = self.storage.get_blob(R::TABLE, &mut buffer).await? else {
+            return Ok(ArrayVec::new());
+        };
+        let Some((schema, entries)) = serialized.split_first_chunk::<4>() else {
+            return Err(sequential_storage::Error::Corrupted {});
+        };
+        if u32::from_le_bytes(*schema) != R::SCHEMA {
+            return Err(SerializationError::InvalidData.into());
+        }
+        // The buffer does not fit more than `TABLE_MAX_RECORDS` entries.
+        let entries = entries.chunks_exact(ENTRY_SIZE);
+        if !entries.remainder().is_empty() {
+            return Err(sequential_storage::Error::Corrupted {});
+        }
+        entries
+            .map(|entry| {
+                let (key, index) = entry.split_first_chunk::<4>()?;
+                Some(Entry {
+                    key: u32::from_le_bytes(*key),
+                    index: u32::from_le_bytes(index.try_into().ok()?),
+                })
+            })
+            .collect::<Option<_>>()
+            .ok_or(sequential_storage::Error::Corrupted {})
+    }
+
+    /// Stores the entries of the directory of the table, sorted by key.
+    async fn store_directory(
+        &mut self,
+        entries: &[Entry],
+    ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
+        let mut buffer = ArrayVec::<u8, DIRECTORY_SIZE>::new();
+        buffer.extend(R::SCHEMA.to_le_bytes());
+        for entry in entries {
+            buffer.extend(entry.key.to_le_bytes());
+            buffer.extend(entry.index.to_le_bytes());
+        }
+        self.storage.insert_blob(R::TABLE, &buffer).await
+    }
+}
+
+/// An entry of the directory of a table.
+#[derive(Clone, Copy)]
+struct Entry {
+    key: u32,
+    /// Hash of the indexed field.
+    index: u32,
+}
+
+/// Returns the key under which the record with the given key is stored.
+///
+/// # Errors
+///
+/// Returns [`SerializationError::InvalidData`] if the resulting key is longer than
+/// [`MAX_KEY_LEN`].
+fn record_key<R: Record>(key: u32) -> Result<ArrayString<MAX_KEY_LEN>, SerializationError> {
+    use core::fmt::Write;
+
+    let mut record_key = ArrayString::new();
+    write!(record_key, "{}/{key}", R::TABLE).map_err(|_| SerializationError::InvalidData)?;
+    Ok(record_key)
+}
+
+/// Serializes `value` into `buffer`.
+///
+/// # Errors
+///
+/// Returns [`SerializationError::BufferTooSmall`] if `value` does not fit.
+fn serialize<'b, T: Serialize, E>(
+    value: &T,
+    buffer: &'b mut [u8],
+) -> Result<&'b [u8], sequential_storage::Error<E>> {
+    postcard::to_slice(value, buffer)
+        .map(|serialized| &*serialized)
+        .map_err(|e| match e {
+            postcard::Error::SerializeBufferFull => SerializationError::BufferTooSmall.into(),
+            _ => SerializationError::Custom(0).into(),
+        })
+}
+
+/// Returns the FNV-1a hash of the serialized `index`.
+///
+/// # Errors
+///
+/// Returns [`SerializationError::BufferTooSmall`] if `index` is larger than
+/// [`TABLE_MAX_RECORD_SIZE`] once serialized.
+fn index_hash<T: Serialize, E>(index: &T) -> Result<u32, sequential_storage::Error<E>> {
+    let mut buffer = [0; TABLE_MAX_RECORD_SIZE];
+    let serialized = serialize(index, &mut buffer)?;
+    Ok(serialized.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    }))
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use embassy_futures::block_on;
+    use embedded_storage_async::nor_flash::ReadNorFlash;
+    use sequential_storage::map::SerializationError;
+    use serde::{Deserialize, Serialize};
+
+    use super::Record;
+    use crate::{faulty_flash::FaultyFlash, storage::Storage};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Peer {
+        id: u32,
+        kid: [u8; 2],
+        sequence_number: u64,
+    }
+
+    impl Record for Peer {
+        const TABLE: &'static str = "peers";
+        const SCHEMA: u32 = 1;
+
+        type Index = [u8; 2];
+
+        fn key(&self) -> u32 {
+            self.id
+        }
+
+        fn index(&self) -> &Self::Index {
+            &self.kid
+        }
+    }
+
+    /// The same table as [`Peer`], with another schema.
+    #[derive(Serialize, Deserialize)]
+    struct PeerV2 {
+        id: u32,
+    }
+
+    impl Record for PeerV2 {
+        const TABLE: &'static str = "peers";
+        const SCHEMA: u32 = 2;
+
+        type Index = ();
+
+        fn key(&self) -> u32 {
+            self.id
+        }
+
+        fn index(&self) -> &Self::Index {
+            &()
+        }
+    }
+
+    fn storage() -> Storage<FaultyFlash> {
+        let flash = FaultyFlash::new(4);
+        let end = u32::try_from(flash.capacity()).unwrap();
+        Storage::new(flash, 0..end)
+    }
+
+    fn peer(id: u32, kid: [u8; 2]) -> Peer {
+        Peer {
+            id,
+            kid,
+            sequence_number: u64::from(id) * 100,
+        }
+    }
+
+    #[test]
+    fn insert_get_find_remove() {
+        block_on(async {
+            let mut storage = storage();
+            let mut peers = storage.table::<Peer>();
+
+            for id in [30, 10, 20] {
+                peers.insert(&peer(u32::from(id), [id, 0])).await.unwrap();
+            }
+            peers.insert(&peer(20, [2, 2])).await.unwrap();
+
+            assert_eq!(peers.get(10).await.unwrap(), Some(peer(10, [10, 0])));
+            assert_eq!(peers.get(15).await.unwrap(), None);
+            assert_eq!(peers.find(&[2, 2]).await.unwrap(), Some(peer(20, [2, 2])));
+            assert_eq!(peers.find(&[20, 0]).await.unwrap(), None);
+            assert_eq!(peers.keys(..).await.unwrap().as_slice(), [10, 20, 30]);
+            assert_eq!(peers.keys(15..=30).await.unwrap().as_slice(), [20, 30]);
+
+            peers.remove(20).await.unwrap();
+            assert_eq!(peers.get(20).await.unwrap(), None);
+            assert_eq!(peers.find(&[2, 2]).await.unwrap(), None);
+            assert_eq!(peers.keys(..).await.unwrap().as_slice(), [10, 30]);
+        });
+    }
+
+    #[test]
+    fn schema_change() {
+        block_on(async {
+            let mut storage = storage();
+            storage
+                .table::<Peer>()
+                .insert(&peer(1, [1, 1]))
+                .await
+                .unwrap();
+
+            let mut peers = storage.table::<PeerV2>();
+            assert_eq!(
+                peers.get(1).await.map(|_| ()),
+                Err(SerializationError::InvalidData.into())
+            );
+            peers.clear().await.unwrap();
+            assert!(peers.get(1).await.unwrap().is_none());
+            peers.insert(&PeerV2 { id: 2 }).await.unwrap();
+            assert_eq!(peers.keys(..).await.unwrap().as_slice(), [2]);
+        });
+    }
+}
@@ -0,0 +1,127 @@
+//! RAM cache of serialized items that have not been written to flash yet.
+use arrayvec::{ArrayString, ArrayVec, CapacityError};
+
+use crate::storage::{DATA_BUFFER_SIZE, MAX_KEY_LEN};
+
+/// Number of items held in RAM in write-behind mode, configured through the
+/// `CONFIG_STORAGE_WRITE_BEHIND_ENTRIES` environment variable.
+pub const WRITE_BEHIND_ENTRIES: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_STORAGE_WRITE_BEHIND_ENTRIES",
+    8,
+    "number of storage items held in RAM in write-behind mode"
+);
+
+/// Interval at which items held in RAM in write-behind mode are flushed to flash, configured
+/// through the `CONFIG_STORAGE_WRITE_BEHIND_FLUSH_INTERVAL_SECS` environment variable.
+pub const WRITE_BEHIND_FLUSH_INTERVAL_SECS: u64 = ariel_os_utils::u64_from_env_or!(
+    "CONFIG_STORAGE_WRITE_BEHIND_FLUSH_INTERVAL_SECS",
+    60,
+    "interval at which storage items held in RAM are written to flash, in seconds"
+);
+
+/// An item waiting to be written.
+pub(crate) struct Entry {
+    pub(crate) key: ArrayString<MAX_KEY_LEN>,
+    pub(crate) data: ArrayVec<u8, DATA_BUFFER_SIZE>,
+}
+
+/// Items waiting to be written, at most one per key.
+pub(crate) struct Cache {
+    entries: ArrayVec<Entry, WRITE_BEHIND_ENTRIES>,
+}
+
+impl Cache {
+    pub(crate) const fn new() -> Self {
+        Self {
+            entries: ArrayVec::new_const(),
+        }
+    }
+
+    /// Returns the serialized item waiting to be written under `key`, if any.
+    pub(crate) fn get(&self, key: &str) -> Option<&[u8]> {
+        self.entries
+            .iter()
+            .find(|entry| entry.key.as_str() == key)
+            .map(|entry| entry.data.as_slice())
+    }
+
+    /// Returns the keys of the items waiting to be written, oldest first.
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &ArrayString<MAX_KEY_LEN>> {
+        self.entries.iter().map(|entry| &entry.key)
+    }
+
+    /// Returns whether an item can be held under `key`.
+    pub(crate) fn has_room_for(&self, key: &str) -> bool {
+        !self.entries.is_full() || self.get(key).is_some()
+    }
+
+    /// Holds `entry` as the newest item, replacing any item waiting to be written under the same
+    /// key.
+    ///
+    /// A replaced item does not keep its place: items are written in the order of their latest
+    /// insert.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entry has a new key and the cache is full.
+    pub(crate) fn insert(&mut self, entry: Entry) -> Result<(), CapacityError> {
+        self.remove(&entry.key);
+        self.entries
+            .try_push(entry)
+            .map_err(CapacityError::simplify)
+    }
+
+    /// Drops the item waiting to be written under `key`, if any.
+    pub(crate) fn remove(&mut self, key: &str) {
+        self.entries.retain(|entry| entry.key.as_str() != key);
+    }
+
+    /// Returns the oldest item waiting to be written.
+    pub(crate) fn first(&self) -> Option<&Entry> {
+        self.entries.first()
+    }
+
+    /// Drops the oldest item waiting to be written, once it has been written.
+    pub(crate) fn pop_first(&mut self) {
+        if !self.entries.is_empty() {
+            let _ = self.entries.remove(0);
+        }
+    }
+
+    /// Drops all items waiting to be written.
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use arrayvec::{ArrayString, ArrayVec};
+
+    use super::{Cache, Entry};
+
+    fn entry(key: &str, byte: u8) -> Entry {
+        let mut data = ArrayVec::new();
+        data.push(byte);
+        Entry {
+            key: ArrayString::from(key).unwrap(),
+            data,
+        }
+    }
+
+    #[test]
+    fn replaced_items_move_to_the_end() {
+        let mut cache = Cache::new();
+        cache.insert(entry("a", 1)).unwrap();
+        cache.insert(entry("b", 2)).unwrap();
+        cache.insert(entry("a", 3)).unwrap();
+
+        let keys: Vec<_> = cache.keys().map(ArrayString::as_str).collect();
+        assert_eq!(keys, ["b", "a"]);
+        assert_eq!(cache.get("a"), Some(&[3][..]));
+
+        cache.pop_first();
+        assert_eq!(cache.first().map(|entry| entry.key.as_str()), Some("a"));
+    }
+}
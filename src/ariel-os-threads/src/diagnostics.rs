@@ -0,0 +1,214 @@
+//! Detection of probable deadlocks and starved threads, in debug builds.
+//!
+//! Waits on [`Mutex`](crate::sync::Mutex)es are tracked in a wait-for graph: a thread that blocks
+//! on a mutex whose owner transitively waits for a mutex owned by the blocking thread is
+//! deadlocked, and the threads and mutexes of the cycle are logged.
+//! [`Lock`](crate::sync::Lock)s have no owner, and are not tracked.
+//!
+//! The scheduler records the scheduling decision at which each thread last ran: a thread that is
+//! ready but has not run for [`STARVATION_THRESHOLD`] decisions is logged as starved, once until
+//! it runs again.
+//!
+//! Threads are logged with the name of their function, and mutexes with their address, which
+//! the symbol table of the firmware resolves for `static` mutexes.
+
+use ariel_os_debug::log::warn;
+
+use crate::{THREAD_COUNT, ThreadId};
+
+/// Number of scheduling decisions after which a ready thread that has not run is considered
+/// starved, configured through the `CONFIG_THREAD_STARVATION_THRESHOLD` environment variable.
+pub(crate) const STARVATION_THRESHOLD: u32 = ariel_os_utils::u32_from_env_or!(
+    "CONFIG_THREAD_STARVATION_THRESHOLD",
+    1000,
+    "number of scheduling decisions after which a ready thread is considered starved"
+);
+
+/// A thread waiting for a mutex.
+#[derive(Clone, Copy)]
+struct Wait {
+    /// Address of the mutex.
+    mutex: usize,
+    /// Current owner of the mutex.
+    owner: ThreadId,
+}
+
+/// Diagnostics state of the scheduler.
+pub(crate) struct Diagnostics {
+    /// Names of the threads.
+    names: [Option<&'static str>; THREAD_COUNT],
+    /// Mutex each thread waits for.
+    waits: [Option<Wait>; THREAD_COUNT],
+    /// Scheduling decision at which each thread last ran.
+    last_run: [u32; THREAD_COUNT],
+    /// Whether each thread has been logged as starved since it last ran.
+    starved: [bool; THREAD_COUNT],
+    /// Number of scheduling decisions, wrapping.
+    decisions: u32,
+}
+
+impl Diagnostics {
+    pub(crate) const fn new() -> Self {
+        Self {
+            names: [None; THREAD_COUNT],
+            waits: [None; THREAD_COUNT],
+            last_run: [0; THREAD_COUNT],
+            starved: [false; THREAD_COUNT],
+            decisions: 0,
+        }
+    }
+
+    /// Resets the state of a newly created thread.
+    pub(crate) fn created(&mut self, thread_id: ThreadId) {
+        let i = usize::from(thread_id);
+        self.names[i] = None;
+        self.waits[i] = None;
+        self.last_run[i] = self.decisions;
+        self.starved[i] = false;
+    }
+
+    /// Sets the name under which a thread is logged.
+    pub(crate) fn set_name(&mut self, thread_id: ThreadId, name: &'static str) {
+        self.names[usize::from(thread_id)] = Some(name);
+    }
+
+    /// Records that a thread blocks on the mutex at address `mutex`, owned by `owner`, and logs
+    /// the cycle of the wait-for graph this closes, if any.
+    pub(crate) fn mutex_wait(&mut self, thread_id: ThreadId, mutex: usize, owner: ThreadId) {
+        self.waits[usize::from(thread_id)] = Some(Wait { mutex, owner });
+        if !self.is_deadlocked(thread_id) {
+            return;
+        }
+
+        warn!(
+            "ariel-os-threads: probable deadlock of thread {} ({})",
+            usize::from(thread_id),
+            self.name(thread_id)
+        );
+        let mut waiter = thread_id;
+        while let Some(Wait { mutex, owner }) = self.waits[usize::from(waiter)] {
+            warn!(
+                "ariel-os-threads: thread {} ({}) waits for mutex {:#x} owned by thread {} ({})",
+                usize::from(waiter),
+                self.name(waiter),
+                mutex,
+                usize::from(owner),
+                self.name(owner)
+            );
+            waiter = owner;
+            if waiter == thread_id {
+                break;
+            }
+        }
+    }
+
+    /// Records that the mutex at address `mutex` has been handed over to `owner`, which stops
+    /// waiting for it.
+    pub(crate) fn mutex_handed_over(&mut self, mutex: usize, owner: ThreadId) {
+        self.waits[usize::from(owner)] = None;
+        for wait in self.waits.iter_mut().flatten() {
+            if wait.mutex == mutex {
+                wait.owner = owner;
+            }
+        }
+    }
+
+    /// Returns whether the wait of a thread closes a cycle of the wait-for graph.
+    fn is_deadlocked(&self, thread_id: ThreadId) -> bool {
+        let mut waiter = thread_id;
+        // A cycle that does not go through the thread is left after at most `THREAD_COUNT` hops.
+        for _ in 0..THREAD_COUNT {
+            let Some(Wait { owner, .. }) = self.waits[usize::from(waiter)] else {
+                return false;
+            };
+            if owner == thread_id {
+                return true;
+            }
+            waiter = owner;
+        }
+        false
+    }
+
+    /// Records that the scheduler picked `next` to run, and logs the threads among `ready` that
+    /// are newly starved.
+    pub(crate) fn scheduled(&mut self, next: ThreadId, ready: impl Iterator<Item = ThreadId>) {
+        self.decisions = self.decisions.wrapping_add(1);
+        self.last_run[usize::from(next)] = self.decisions;
+        self.starved[usize::from(next)] = false;
+
+        for thread_id in ready {
+            if self.starve(thread_id) {
+                warn!(
+                    "ariel-os-threads: thread {} ({}) starved for {} scheduling decisions",
+                    usize::from(thread_id),
+                    self.name(thread_id),
+                    STARVATION_THRESHOLD
+                );
+            }
+        }
+    }
+
+    /// Returns whether a ready thread is newly starved, and marks it as starved.
+    fn starve(&mut self, thread_id: ThreadId) -> bool {
+        let i = usize::from(thread_id);
+        if self.starved[i] || self.decisions.wrapping_sub(self.last_run[i]) < STARVATION_THRESHOLD {
+            return false;
+        }
+        self.starved[i] = true;
+        true
+    }
+
+    fn name(&self, thread_id: ThreadId) -> &'static str {
+        self.names[usize::from(thread_id)].unwrap_or("unnamed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: ThreadId = ThreadId::new(0);
+    const B: ThreadId = ThreadId::new(1);
+    const C: ThreadId = ThreadId::new(2);
+
+    #[test]
+    fn deadlock() {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.mutex_wait(A, 0x100, B);
+        assert!(!diagnostics.is_deadlocked(A));
+        diagnostics.mutex_wait(B, 0x200, C);
+        assert!(!diagnostics.is_deadlocked(B));
+        diagnostics.mutex_wait(C, 0x300, A);
+        assert!(diagnostics.is_deadlocked(C));
+    }
+
+    #[test]
+    fn handover() {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.mutex_wait(A, 0x100, C);
+        diagnostics.mutex_wait(B, 0x100, C);
+        diagnostics.mutex_handed_over(0x100, A);
+        // `A` now owns the mutex, which `B` still waits for.
+        diagnostics.mutex_wait(C, 0x200, A);
+        assert!(!diagnostics.is_deadlocked(C));
+        diagnostics.mutex_wait(A, 0x300, B);
+        assert!(diagnostics.is_deadlocked(A));
+    }
+
+    #[test]
+    fn starvation() {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.created(A);
+        diagnostics.created(B);
+        for _ in 1..STARVATION_THRESHOLD {
+            diagnostics.scheduled(A, core::iter::empty());
+        }
+        assert!(!diagnostics.starve(B));
+        diagnostics.scheduled(A, core::iter::empty());
+        assert!(diagnostics.starve(B));
+        // Starvation is only reported once until the thread runs again.
+        assert!(!diagnostics.starve(B));
+        diagnostics.scheduled(B, core::iter::empty());
+        assert!(!diagnostics.starve(B));
+    }
+}
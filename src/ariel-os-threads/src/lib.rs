@@ -12,7 +12,7 @@
 //! which the threads are declared.
 //!
 //! Optionally, the stacksize and a priority between 1 and [`SCHED_PRIO_LEVELS`] can be configured.
-//! By default, the stack size is 2048 bytes and priority is 1.
+//! By default, the stack size is [`THREAD_STACKSIZE_DEFAULT`] and priority is 1.
 //!
 //! # Synchronization
 //!
@@ -20,6 +20,13 @@
 //! - [`Channel`](sync::Channel): synchronous (blocking) channel for sending data between threads
 //! - [`Lock`](sync::Lock): basic locking object
 //! - [`thread_flags`]: thread-flag implementation for signaling between threads
+//!
+//! # Diagnostics
+//!
+//! With the `diagnostics` feature enabled, debug builds log probable deadlocks on
+//! [`Mutex`](sync::Mutex)es, and threads that are ready but starved by higher-priority threads.
+//! The number of scheduling decisions after which a ready thread that has not run is considered
+//! starved is configured through the `CONFIG_THREAD_STARVATION_THRESHOLD` environment variable.
 
 #![cfg_attr(not(test), no_std)]
 #![cfg_attr(target_arch = "xtensa", feature(asm_experimental_arch))]
@@ -31,6 +38,8 @@
 
 mod arch;
 mod autostart_thread;
+#[cfg(all(feature = "diagnostics", debug_assertions))]
+mod diagnostics;
 mod ensure_once;
 mod thread;
 mod threadlist;
@@ -67,7 +76,7 @@ pub use smp::isr_stack_core1_get_limits;
 use arch::{Arch, Cpu, ThreadData, schedule};
 use ariel_os_runqueue::RunQueue;
 use ensure_once::EnsureOnce;
-use thread::{Thread, ThreadState};
+use thread::{STACK_PAINT_COLOR, Thread, ThreadState};
 
 #[cfg(feature = "multi-core")]
 use smp::{Multicore, schedule_on_core};
@@ -86,8 +95,27 @@ pub struct CoreAffinity {
 /// The number of possible priority levels.
 pub const SCHED_PRIO_LEVELS: usize = THREAD_COUNT;
 
-/// The maximum number of concurrent threads that can be created.
-pub const THREAD_COUNT: usize = 16;
+/// The maximum number of concurrent threads that can be created, configured through the
+/// `CONFIG_THREAD_COUNT` environment variable.
+pub const THREAD_COUNT: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_THREAD_COUNT",
+    16,
+    "maximum number of concurrent threads"
+);
+
+// The run queue keeps track of the priority levels in a `usize` bitmap.
+const _: () = assert!(
+    THREAD_COUNT <= usize::BITS as usize,
+    "`CONFIG_THREAD_COUNT` exceeds the number of bits of a `usize`"
+);
+
+/// Default stack size of the threads (in bytes), configured through the
+/// `CONFIG_THREAD_STACKSIZE_DEFAULT` environment variable.
+pub const THREAD_STACKSIZE_DEFAULT: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_THREAD_STACKSIZE_DEFAULT",
+    2048,
+    "default stack size of the threads (in bytes)"
+);
 
 /// Number of processor cores.
 pub const CORE_COUNT: usize = {
@@ -125,6 +153,10 @@ struct Scheduler {
     current_threads: [Option<ThreadId>; CORE_COUNT],
     #[cfg(not(feature = "multi-core"))]
     current_thread: Option<ThreadId>,
+
+    /// Deadlock and starvation detection.
+    #[cfg(all(feature = "diagnostics", debug_assertions))]
+    diagnostics: diagnostics::Diagnostics,
 }
 
 impl Scheduler {
@@ -137,6 +169,8 @@ impl Scheduler {
             current_threads: [None; CORE_COUNT],
             #[cfg(not(feature = "multi-core"))]
             current_thread: None,
+            #[cfg(all(feature = "diagnostics", debug_assertions))]
+            diagnostics: diagnostics::Diagnostics::new(),
         }
     }
 
@@ -209,6 +243,8 @@ impl Scheduler {
         {
             thread.core_affinity = _core_affinity.unwrap_or_default();
         }
+        #[cfg(all(feature = "diagnostics", debug_assertions))]
+        self.diagnostics.created(tid);
 
         Some(tid)
     }
@@ -413,6 +449,17 @@ impl Scheduler {
     /// times by the scheduler when it is invoked on different cores.
     #[allow(dead_code, reason = "used in scheduler implementation")]
     fn get_next_tid(&mut self) -> Option<ThreadId> {
+        let next = self.pick_next_tid();
+        #[cfg(all(feature = "diagnostics", debug_assertions))]
+        if let Some(next) = next {
+            self.record_scheduled(next);
+        }
+        next
+    }
+
+    /// Returns the next thread from the runqueue, see [`Self::get_next_tid()`].
+    #[allow(dead_code, reason = "used in scheduler implementation")]
+    fn pick_next_tid(&mut self) -> Option<ThreadId> {
         // On single-core, only read the head of the runqueue.
         #[cfg(not(feature = "multi-core"))]
         {
@@ -439,6 +486,25 @@ impl Scheduler {
         }
     }
 
+    /// Records a scheduling decision for the starvation detection.
+    #[cfg(all(feature = "diagnostics", debug_assertions))]
+    fn record_scheduled(&mut self, next: ThreadId) {
+        // Bitmap of the threads that are ready but not running; `THREAD_COUNT` fits its bits.
+        let ready = self
+            .threads
+            .iter()
+            .filter(|thread| {
+                thread.state == ThreadState::Running && self.is_running(thread.tid).is_none()
+            })
+            .fold(0usize, |ready, thread| ready | 1 << usize::from(thread.tid));
+        self.diagnostics.scheduled(
+            next,
+            (0..THREAD_COUNT)
+                .filter(|i| ready & 1 << i != 0)
+                .map(|i| ThreadId::new(i as u8)),
+        );
+    }
+
     /// Searches for the lowest priority thread among the currently running threads.
     ///
     /// Returns the core that the lowest priority thread is running on, and its priority.
@@ -625,6 +691,17 @@ pub unsafe fn create_raw(
     })
 }
 
+/// Sets the name under which a thread is logged by the diagnostics.
+///
+/// This is a no-op unless the `diagnostics` feature is enabled in a debug build.
+#[doc(hidden)]
+pub fn set_name(thread_id: ThreadId, name: &'static str) {
+    #[cfg(all(feature = "diagnostics", debug_assertions))]
+    SCHEDULER.with_mut(|mut scheduler| scheduler.diagnostics.set_name(thread_id, name));
+    #[cfg(not(all(feature = "diagnostics", debug_assertions)))]
+    let _ = (thread_id, name);
+}
+
 /// Returns the [`ThreadId`] of the currently active thread.
 ///
 /// Note: when called from ISRs, this will return the thread id of the thread
@@ -756,3 +833,30 @@ pub fn current_stack_limits() -> Option<(usize, usize)> {
             .map(|thread| (thread.stack_lowest, thread.stack_highest))
     })
 }
+
+/// Returns a thread's stack limits (lowest, highest).
+///
+/// Returns `None` if this is not a valid thread.
+pub fn stack_limits(thread_id: ThreadId) -> Option<(usize, usize)> {
+    SCHEDULER.with(|scheduler| {
+        scheduler.is_valid_tid(thread_id).then(|| {
+            let thread = scheduler.get_unchecked(thread_id);
+            (thread.stack_lowest, thread.stack_highest)
+        })
+    })
+}
+
+/// Returns the maximum stack space used by a thread since it was created.
+///
+/// This is measured through stack painting, and runs in `O(n)` of the stack size.
+///
+/// Returns `None` if this is not a valid thread.
+pub fn stack_used_max(thread_id: ThreadId) -> Option<usize> {
+    let (lowest, highest) = stack_limits(thread_id)?;
+    let free = (lowest..highest)
+        // SAFETY: thread stacks are `'static`, so this stays valid memory to read from even if
+        // the thread ends meanwhile.
+        .filter(|&pos| unsafe { core::ptr::read_volatile(pos as *const u8) } == STACK_PAINT_COLOR)
+        .count();
+    Some(highest - lowest - free)
+}
@@ -98,6 +98,15 @@ impl<T> Mutex<T> {
                     owner_id,
                     owner_prio,
                 } => {
+                    #[cfg(all(feature = "diagnostics", debug_assertions))]
+                    SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+                        let tid = scheduler
+                            .current_tid()
+                            .expect("Function should be called inside a thread context.");
+                        scheduler
+                            .diagnostics
+                            .mutex_wait(tid, self.address(), *owner_id);
+                    });
                     // Insert thread in waitlist, which also triggers the scheduler.
                     match waiters.put_current(cs, ThreadState::LockBlocked) {
                         // `Some` when the inserted thread is the highest priority
@@ -138,6 +147,12 @@ impl<T> Mutex<T> {
         })
     }
 
+    /// Returns the address of the mutex, which identifies it in diagnostics.
+    #[cfg(all(feature = "diagnostics", debug_assertions))]
+    fn address(&self) -> usize {
+        core::ptr::from_ref(self).addr()
+    }
+
     /// Releases the mutex.
     ///
     /// If there are waiters, the first waiter will be woken up.
@@ -161,6 +176,10 @@ impl<T> Mutex<T> {
                         *owner_id = tid;
                         *owner_prio = scheduler.get_unchecked(tid).prio;
                     });
+                    #[cfg(all(feature = "diagnostics", debug_assertions))]
+                    SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+                        scheduler.diagnostics.mutex_handed_over(self.address(), tid);
+                    });
                 } else {
                     // Unlock if waitlist was empty.
                     *state = LockState::Unlocked;
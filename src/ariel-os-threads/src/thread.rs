@@ -1,5 +1,8 @@
 use crate::{Arch, Cpu, RunqueueId, ThreadData, ThreadId, thread_flags::ThreadFlags};
 
+/// Byte that's used to paint stacks.
+pub(crate) const STACK_PAINT_COLOR: u8 = 0xCC;
+
 /// Main struct for holding thread data.
 #[derive(Debug)]
 pub struct Thread {
@@ -76,9 +79,6 @@ impl Thread {
     /// - must only be called before the stack is active (within `arch::setup_stack()`).
     #[allow(dead_code, reason = "not used in all configurations")]
     pub(crate) unsafe fn stack_paint_init(&mut self, sp: usize) {
-        // Byte that's used to pain stacks.
-        const STACK_PAINT_COLOR: u8 = 0xCC;
-
         for pos in self.stack_lowest..sp {
             // SAFETY: Writing to the slice that was passed to `setup_stack()` is fine
             unsafe {
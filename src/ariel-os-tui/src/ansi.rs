@@ -0,0 +1,37 @@
+//! Provides ANSI control sequences, to be written with [`Terminal::write_str()`].
+//!
+//! [`Terminal::write_str()`]: crate::Terminal::write_str()
+
+/// Clears the screen, without moving the cursor.
+pub const CLEAR_SCREEN: &str = "\x1b[2J";
+/// Clears the line of the cursor, without moving the cursor.
+pub const CLEAR_LINE: &str = "\x1b[2K";
+/// Moves the cursor to the top left corner.
+pub const CURSOR_HOME: &str = "\x1b[H";
+/// Moves the cursor one line up.
+pub const CURSOR_UP: &str = "\x1b[A";
+/// Moves the cursor one line down.
+pub const CURSOR_DOWN: &str = "\x1b[B";
+/// Saves the position of the cursor.
+pub const SAVE_CURSOR: &str = "\x1b7";
+/// Restores the position of the cursor saved by [`SAVE_CURSOR`].
+pub const RESTORE_CURSOR: &str = "\x1b8";
+/// Hides the cursor.
+pub const HIDE_CURSOR: &str = "\x1b[?25l";
+/// Shows the cursor.
+pub const SHOW_CURSOR: &str = "\x1b[?25h";
+
+/// Resets the colors and style of the text.
+pub const RESET: &str = "\x1b[0m";
+/// Makes the text bold.
+pub const BOLD: &str = "\x1b[1m";
+/// Swaps the colors of the text and of its background.
+pub const REVERSE: &str = "\x1b[7m";
+/// Makes the text red.
+pub const RED: &str = "\x1b[31m";
+/// Makes the text green.
+pub const GREEN: &str = "\x1b[32m";
+/// Makes the text yellow.
+pub const YELLOW: &str = "\x1b[33m";
+/// Makes the text blue.
+pub const BLUE: &str = "\x1b[34m";
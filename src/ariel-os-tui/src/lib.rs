@@ -0,0 +1,283 @@
+//! Provides text user interface widgets, for consoles on byte streams.
+//!
+//! A [`Terminal`] wraps any byte stream implementing the [`embedded_io_async`] traits, eg. a USB
+//! serial port (`ariel_os::usb::cdc_acm::CdcAcmSerial`) or a TCP socket, and draws on it with
+//! ANSI control sequences, which common terminal emulators support:
+//!
+//! ```ignore
+//! let mut terminal = Terminal::new(serial);
+//! terminal.clear().await?;
+//!
+//! let choice = Menu::new("Self-test", &["LEDs", "Buttons", "Radio"])
+//!     .select(&mut terminal)
+//!     .await?;
+//!
+//! let progress = ProgressBar::new("Flashing", 40);
+//! for done in 0..=100 {
+//!     progress.draw(&mut terminal, done, 100).await?;
+//! }
+//! ```
+//!
+//! The widgets are a [`Menu`], a [`ProgressBar`] and [`StatusLines`], which are updated in place.
+//!
+//! # Configuration
+//!
+//! Formatted lines are truncated to `CONFIG_TUI_LINE_LEN` bytes (default: 128).
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+pub mod ansi;
+mod menu;
+mod progress;
+mod status;
+
+use core::fmt::Write as _;
+
+use embedded_io_async::{Read, Write};
+
+pub use menu::Menu;
+pub use progress::ProgressBar;
+pub use status::StatusLines;
+
+/// Maximum length of formatted lines, in bytes.
+const LINE_LEN: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_TUI_LINE_LEN",
+    128,
+    "maximum length of lines formatted by the text user interface"
+);
+
+/// A terminal on the byte stream `T`.
+pub struct Terminal<T> {
+    stream: T,
+}
+
+impl<T> Terminal<T> {
+    /// Creates a terminal on `stream`.
+    #[must_use]
+    pub const fn new(stream: T) -> Self {
+        Self { stream }
+    }
+
+    /// Returns the byte stream of the terminal.
+    pub fn release(self) -> T {
+        self.stream
+    }
+}
+
+impl<T: Write> Terminal<T> {
+    /// Writes `text`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the byte stream if writing fails.
+    pub async fn write_str(&mut self, text: &str) -> Result<(), T::Error> {
+        self.stream.write_all(text.as_bytes()).await
+    }
+
+    /// Writes formatted text, truncated to `CONFIG_TUI_LINE_LEN` bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the byte stream if writing fails.
+    pub async fn write_fmt(&mut self, args: core::fmt::Arguments<'_>) -> Result<(), T::Error> {
+        let mut line = Line::default();
+        // Writing to a `Line` never fails, it truncates instead.
+        let _ = line.write_fmt(args);
+        self.write_str(&line.0).await
+    }
+
+    /// Clears the screen, and moves the cursor to the top left corner.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the byte stream if writing fails.
+    pub async fn clear(&mut self) -> Result<(), T::Error> {
+        self.write_str(ansi::CLEAR_SCREEN).await?;
+        self.write_str(ansi::CURSOR_HOME).await
+    }
+
+    /// Moves the cursor to `row` and `column`, counted from 1.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the byte stream if writing fails.
+    pub async fn move_to(&mut self, row: u16, column: u16) -> Result<(), T::Error> {
+        self.write_fmt(format_args!("\x1b[{row};{column}H")).await
+    }
+
+    /// Flushes the byte stream.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the byte stream if flushing fails.
+    pub async fn flush(&mut self) -> Result<(), T::Error> {
+        self.stream.flush().await
+    }
+}
+
+impl<T: Read> Terminal<T> {
+    /// Waits for a key to be pressed, and returns it.
+    ///
+    /// Returns `None` if the byte stream ended.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the byte stream if reading fails.
+    pub async fn read_key(&mut self) -> Result<Option<Key>, T::Error> {
+        let Some(byte) = self.read_byte().await? else {
+            return Ok(None);
+        };
+
+        let key = match byte {
+            b'\r' | b'\n' => Key::Enter,
+            0x08 | 0x7f => Key::Backspace,
+            0x1b => {
+                // Arrow keys are sent as `ESC [ A` to `ESC [ D`; a lone escape cannot be told
+                // apart from them without timing, so it is only recognized as escape when
+                // followed by another escape.
+                match self.read_byte().await? {
+                    Some(b'[') => match self.read_byte().await? {
+                        Some(b'A') => Key::Up,
+                        Some(b'B') => Key::Down,
+                        Some(b'C') => Key::Right,
+                        Some(b'D') => Key::Left,
+                        _ => Key::Other,
+                    },
+                    Some(0x1b) => Key::Escape,
+                    None => return Ok(None),
+                    Some(_) => Key::Other,
+                }
+            }
+            byte if byte.is_ascii_graphic() || byte == b' ' => Key::Char(char::from(byte)),
+            _ => Key::Other,
+        };
+        Ok(Some(key))
+    }
+
+    /// Reads a byte, or returns `None` if the byte stream ended.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the byte stream if reading fails.
+    async fn read_byte(&mut self) -> Result<Option<u8>, T::Error> {
+        let mut byte = [0];
+        let len = self.stream.read(&mut byte).await?;
+        Ok((len != 0).then_some(byte[0]))
+    }
+}
+
+/// A key pressed on the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    /// A printable ASCII character.
+    Char(char),
+    /// The Enter key.
+    Enter,
+    /// The Backspace key.
+    Backspace,
+    /// The Escape key, pressed twice.
+    Escape,
+    /// The Up arrow key.
+    Up,
+    /// The Down arrow key.
+    Down,
+    /// The Left arrow key.
+    Left,
+    /// The Right arrow key.
+    Right,
+    /// Any other key.
+    Other,
+}
+
+/// A line of formatted text, truncated to [`LINE_LEN`] bytes.
+#[derive(Default)]
+struct Line(heapless::String<LINE_LEN>);
+
+impl core::fmt::Write for Line {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars() {
+            if self.0.push(c).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+
+    use super::*;
+
+    /// A byte stream reading from `input`, and writing to `output`.
+    #[derive(Default)]
+    pub(crate) struct Stream {
+        pub input: &'static [u8],
+        pub output: heapless::Vec<u8, 1024>,
+    }
+
+    impl embedded_io_async::ErrorType for Stream {
+        type Error = core::convert::Infallible;
+    }
+
+    impl Read for Stream {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.input.read(buf).await
+        }
+    }
+
+    impl Write for Stream {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            let len = buf.len().min(self.output.capacity() - self.output.len());
+            let _ = self
+                .output
+                .extend_from_slice(buf.get(..len).unwrap_or_default());
+            Ok(len)
+        }
+    }
+
+    pub(crate) fn output(terminal: &Terminal<Stream>) -> &str {
+        core::str::from_utf8(&terminal.stream.output).unwrap()
+    }
+
+    #[test]
+    fn keys() {
+        let mut terminal = Terminal::new(Stream {
+            input: b"a\r\x1b[A\x1b[B\x1b\x1b\x7f\x01",
+            ..Default::default()
+        });
+
+        let keys = block_on(async {
+            let mut keys = heapless::Vec::<_, 8>::new();
+            while let Some(key) = terminal.read_key().await.unwrap() {
+                keys.push(key).unwrap();
+            }
+            keys
+        });
+
+        assert_eq!(
+            keys,
+            [
+                Key::Char('a'),
+                Key::Enter,
+                Key::Up,
+                Key::Down,
+                Key::Escape,
+                Key::Backspace,
+                Key::Other
+            ]
+        );
+    }
+
+    #[test]
+    fn long_lines_are_truncated() {
+        let mut terminal = Terminal::new(Stream::default());
+
+        block_on(terminal.write_fmt(format_args!("{:x<1$}", "", LINE_LEN + 10))).unwrap();
+
+        assert_eq!(output(&terminal).len(), LINE_LEN);
+    }
+}
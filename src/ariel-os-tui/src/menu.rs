@@ -0,0 +1,139 @@
+//! Provides a menu.
+
+use embedded_io_async::{Read, Write};
+
+use crate::{Key, Terminal, ansi};
+
+/// A menu, whose items are selected with the arrow keys and Enter, or with their number.
+#[derive(Debug, Clone, Copy)]
+pub struct Menu<'a> {
+    title: &'a str,
+    items: &'a [&'a str],
+}
+
+impl<'a> Menu<'a> {
+    /// Creates a menu titled `title`, which lists `items`.
+    #[must_use]
+    pub const fn new(title: &'a str, items: &'a [&'a str]) -> Self {
+        Self { title, items }
+    }
+
+    /// Draws the menu from the line of the cursor, and waits for an item to be selected.
+    ///
+    /// Returns the index of the item selected, or `None` if the Escape key was pressed, the byte
+    /// stream ended, or the menu has no items.
+    /// The items can also be selected by pressing the digit of their number, for the first nine
+    /// of them.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the byte stream if reading or writing fails.
+    pub async fn select<T: Read + Write>(
+        &self,
+        terminal: &mut Terminal<T>,
+    ) -> Result<Option<usize>, T::Error> {
+        if self.items.is_empty() {
+            return Ok(None);
+        }
+
+        terminal.write_str(ansi::HIDE_CURSOR).await?;
+        terminal.write_str(ansi::BOLD).await?;
+        terminal.write_str(self.title).await?;
+        terminal.write_str(ansi::RESET).await?;
+        terminal.write_str("\r\n").await?;
+
+        let mut selected = 0;
+        self.draw_items(terminal, selected).await?;
+
+        let selection = loop {
+            let Some(key) = terminal.read_key().await? else {
+                break None;
+            };
+            match key {
+                Key::Enter => break Some(selected),
+                Key::Escape => break None,
+                Key::Up => selected = selected.checked_sub(1).unwrap_or(self.items.len() - 1),
+                Key::Down => selected = (selected + 1) % self.items.len(),
+                Key::Char(c) => {
+                    let index = c.to_digit(10).and_then(|digit| digit.checked_sub(1));
+                    match index.and_then(|index| usize::try_from(index).ok()) {
+                        Some(index) if index < self.items.len() => break Some(index),
+                        _ => continue,
+                    }
+                }
+                _ => continue,
+            }
+
+            terminal
+                .write_fmt(format_args!("\x1b[{}A", self.items.len()))
+                .await?;
+            self.draw_items(terminal, selected).await?;
+        };
+
+        terminal.write_str(ansi::SHOW_CURSOR).await?;
+        terminal.flush().await?;
+        Ok(selection)
+    }
+
+    /// Draws the items, leaving the cursor below them.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the byte stream if writing fails.
+    async fn draw_items<T: Write>(
+        &self,
+        terminal: &mut Terminal<T>,
+        selected: usize,
+    ) -> Result<(), T::Error> {
+        for (index, item) in self.items.iter().enumerate() {
+            terminal.write_str("\r").await?;
+            terminal.write_str(ansi::CLEAR_LINE).await?;
+            if index == selected {
+                terminal.write_str(ansi::REVERSE).await?;
+            }
+            terminal
+                .write_fmt(format_args!("{:>3}. {item}", index + 1))
+                .await?;
+            terminal.write_str(ansi::RESET).await?;
+            terminal.write_str("\r\n").await?;
+        }
+        terminal.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+
+    use super::*;
+    use crate::tests::Stream;
+
+    const ITEMS: &[&str] = &["LEDs", "Buttons", "Radio"];
+
+    fn select(input: &'static [u8]) -> Option<usize> {
+        let mut terminal = Terminal::new(Stream {
+            input,
+            ..Default::default()
+        });
+        block_on(Menu::new("Self-test", ITEMS).select(&mut terminal)).unwrap()
+    }
+
+    #[test]
+    fn select_with_arrows() {
+        assert_eq!(select(b"\x1b[B\x1b[B\r"), Some(2));
+        assert_eq!(select(b"\x1b[A\r"), Some(2));
+        assert_eq!(select(b"\x1b[B\x1b[B\x1b[B\r"), Some(0));
+    }
+
+    #[test]
+    fn select_with_digits() {
+        assert_eq!(select(b"2"), Some(1));
+        assert_eq!(select(b"4\r"), Some(0));
+    }
+
+    #[test]
+    fn cancel() {
+        assert_eq!(select(b"\x1b\x1b"), None);
+        assert_eq!(select(b""), None);
+    }
+}
@@ -0,0 +1,90 @@
+//! Provides a progress bar.
+
+use core::fmt::Write as _;
+
+use embedded_io_async::Write;
+
+use crate::{Line, Terminal, ansi};
+
+/// A progress bar, drawn on the line of the cursor, eg. `Flashing [#####-----]  50%`.
+#[derive(Debug, Clone, Copy)]
+pub struct ProgressBar<'a> {
+    label: &'a str,
+    width: u16,
+}
+
+impl<'a> ProgressBar<'a> {
+    /// Creates a progress bar preceded by `label`, whose bar is `width` characters wide.
+    #[must_use]
+    pub const fn new(label: &'a str, width: u16) -> Self {
+        Self { label, width }
+    }
+
+    /// Draws the progress bar with `done` out of `total` steps done, replacing the line of the
+    /// cursor.
+    ///
+    /// The cursor stays on the line, so that the progress bar can be redrawn as progress is made.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the byte stream if writing fails.
+    pub async fn draw<T: Write>(
+        &self,
+        terminal: &mut Terminal<T>,
+        done: u32,
+        total: u32,
+    ) -> Result<(), T::Error> {
+        let (filled, percent) = progress(done, total, self.width);
+
+        let mut line = Line::default();
+        // Writing to a `Line` never fails, it truncates instead.
+        let _ = write!(line, "\r{}{} [", ansi::CLEAR_LINE, self.label);
+        for i in 0..self.width {
+            let _ = line.write_char(if i < filled { '#' } else { '-' });
+        }
+        let _ = write!(line, "] {percent:3}%");
+
+        terminal.write_str(&line.0).await?;
+        terminal.flush().await
+    }
+}
+
+/// Returns the number of filled characters out of `width`, and the percentage done.
+fn progress(done: u32, total: u32, width: u16) -> (u16, u8) {
+    if total == 0 {
+        return (width, 100);
+    }
+    let done = u64::from(done.min(total));
+    let total = u64::from(total);
+
+    // Both are at most `width` and 100, respectively.
+    #[expect(clippy::cast_possible_truncation)]
+    (
+        (done * u64::from(width) / total) as u16,
+        (done * 100 / total) as u8,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+
+    use super::*;
+    use crate::tests::{Stream, output};
+
+    #[test]
+    fn draw() {
+        let mut terminal = Terminal::new(Stream::default());
+
+        block_on(ProgressBar::new("Flashing", 10).draw(&mut terminal, 1, 2)).unwrap();
+
+        assert_eq!(output(&terminal), "\r\x1b[2KFlashing [#####-----]  50%");
+    }
+
+    #[test]
+    fn progress_is_clamped() {
+        assert_eq!(progress(3, 2, 10), (10, 100));
+        assert_eq!(progress(0, 0, 10), (10, 100));
+        assert_eq!(progress(0, 7, 10), (0, 0));
+    }
+}
@@ -0,0 +1,92 @@
+//! Provides status lines, which are updated in place.
+
+use embedded_io_async::Write;
+
+use crate::{Terminal, ansi};
+
+/// A block of status lines, which can each be updated in place, eg. to show the live readings of
+/// sensors.
+#[derive(Debug, Clone, Copy)]
+pub struct StatusLines {
+    count: u16,
+}
+
+impl StatusLines {
+    /// Reserves `count` lines, starting from the line of the cursor.
+    ///
+    /// The cursor is left below the reserved lines.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the byte stream if writing fails.
+    pub async fn reserve<T: Write>(
+        terminal: &mut Terminal<T>,
+        count: u16,
+    ) -> Result<Self, T::Error> {
+        for _ in 0..count {
+            terminal.write_str("\r\n").await?;
+        }
+        Ok(Self { count })
+    }
+
+    /// Replaces the content of the line at `index`, counted from 0, with formatted text.
+    ///
+    /// The cursor must be where [`StatusLines::reserve()`] left it, and is moved back there.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the byte stream if writing fails.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not less than the number of lines reserved.
+    pub async fn update<T: Write>(
+        &self,
+        terminal: &mut Terminal<T>,
+        index: u16,
+        args: core::fmt::Arguments<'_>,
+    ) -> Result<(), T::Error> {
+        assert!(index < self.count, "no such status line");
+
+        terminal.write_str(ansi::SAVE_CURSOR).await?;
+        terminal
+            .write_fmt(format_args!("\x1b[{}A\r", self.count - index))
+            .await?;
+        terminal.write_str(ansi::CLEAR_LINE).await?;
+        terminal.write_fmt(args).await?;
+        terminal.write_str(ansi::RESTORE_CURSOR).await?;
+        terminal.flush().await
+    }
+
+    /// Returns the number of lines reserved.
+    #[must_use]
+    pub fn count(&self) -> u16 {
+        self.count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+
+    use super::*;
+    use crate::tests::{Stream, output};
+
+    #[test]
+    fn update() {
+        let mut terminal = Terminal::new(Stream::default());
+
+        block_on(async {
+            let status = StatusLines::reserve(&mut terminal, 2).await.unwrap();
+            status
+                .update(&mut terminal, 1, format_args!("temperature: {}", 21))
+                .await
+                .unwrap();
+        });
+
+        assert_eq!(
+            output(&terminal),
+            "\r\n\r\n\x1b7\x1b[1A\r\x1b[2Ktemperature: 21\x1b8"
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! The bootloader side of A/B updates: swapping the slots as requested through the
+//! [`BootState`].
+//!
+//! The slots are swapped page by page through a scratch page, which is the last erase page of the
+//! state partition. Each page takes three steps, which are recorded after the state record, so
+//! that a swap interrupted by a power loss is resumed where it stopped at the next boot:
+//!
+//! 1. The page of the active slot is copied to the scratch page.
+//! 2. The page of the inactive slot is copied to the active slot.
+//! 3. The scratch page is copied to the inactive slot.
+//!
+//! This requires the state partition to span at least two erase pages. Besides the scratch page,
+//! it needs to hold all records of an update: the pending state, the steps of the swap, the state
+//! on trial and its boot attempts, and the revert with the steps of swapping back. Swapped-in
+//! updates are put on trial by appending the new state record, so that a power loss cannot drop
+//! the state before it is complete.
+
+use embedded_storage_async::nor_flash::NorFlash;
+
+use crate::{BOOT_ATTEMPTS, BootState, Error, Updater, layout::Partition, state};
+
+/// Length of the chunks in which pages are copied.
+const COPY_CHUNK_LEN: u32 = 256;
+
+/// Number of recorded steps needed to swap one page.
+const STEPS_PER_PAGE: u32 = 3;
+
+/// Applies the boot state, and returns the boot state the active slot is booted in.
+///
+/// A [pending](BootState::Pending) update is swapped into the active slot and put
+/// [on trial](BootState::Testing); a [revert](BootState::Revert) swaps the previous firmware back
+/// in, which is then [confirmed](BootState::Idle). Swaps interrupted before are completed first.
+///
+/// # Errors
+///
+/// Returns [`Error::Layout`] if the state partition cannot hold the scratch page and the progress
+/// of the swap, and [`Error::Flash`] if accessing the flash failed.
+pub async fn apply<F: NorFlash>(updater: &mut Updater<F>) -> Result<BootState, Error> {
+    let next = match updater.state().await? {
+        BootState::Pending => BootState::Testing,
+        BootState::Revert => BootState::Idle,
+        state @ (BootState::Idle | BootState::Testing) => return Ok(state),
+    };
+    swap(updater).await?;
+    next.write(&mut updater.flash, &updater.layout.state)
+        .await?;
+    Ok(next)
+}
+
+/// Swaps the contents of the active slot with the start of the inactive slot, resuming a swap
+/// that was interrupted before.
+async fn swap<F: NorFlash>(updater: &mut Updater<F>) -> Result<(), Error> {
+    let erase_size = u32::try_from(F::ERASE_SIZE).map_err(|_| Error::Layout)?;
+    if !erase_size.is_multiple_of(COPY_CHUNK_LEN)
+        || !(COPY_CHUNK_LEN as usize).is_multiple_of(F::READ_SIZE)
+        || !(COPY_CHUNK_LEN as usize).is_multiple_of(F::WRITE_SIZE)
+    {
+        return Err(Error::Layout);
+    }
+    let layout = updater.layout;
+    let scratch_offset = layout
+        .state
+        .size
+        .checked_sub(erase_size)
+        .filter(|&offset| offset > 0)
+        .ok_or(Error::Layout)?;
+    let scratch = Partition::new(layout.state.offset + scratch_offset, erase_size);
+    let progress = Partition::new(layout.state.offset, scratch_offset);
+    let steps = layout.active.size / erase_size * STEPS_PER_PAGE;
+    // The records of a whole update, followed by an erased record that ends them.
+    let records = steps
+        .checked_mul(2)
+        .and_then(|records| records.checked_add(u32::from(BOOT_ATTEMPTS) + 3))
+        .ok_or(Error::Layout)?;
+    if state::record_offset::<F>(progress, records).is_none() {
+        return Err(Error::Layout);
+    }
+
+    let records = state::Records::read(&mut updater.flash, &progress).await?;
+    let done = records.steps(steps);
+    for step in done..steps {
+        let page = step / STEPS_PER_PAGE * erase_size;
+        let active = Partition::new(layout.active.offset + page, erase_size);
+        let inactive = Partition::new(layout.inactive.offset + page, erase_size);
+        let (from, to) = match step % STEPS_PER_PAGE {
+            0 => (active, scratch),
+            1 => (inactive, active),
+            _ => (scratch, inactive),
+        };
+        copy_page(&mut updater.flash, from, to).await?;
+        records
+            .write_step(&mut updater.flash, &progress, step)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Erases the page `to`, and copies the page `from` into it.
+async fn copy_page<F: NorFlash>(
+    flash: &mut F,
+    from: Partition,
+    to: Partition,
+) -> Result<(), Error> {
+    flash
+        .erase(to.offset, to.end())
+        .await
+        .map_err(|_| Error::Flash)?;
+    let mut chunk = [0; COPY_CHUNK_LEN as usize];
+    let mut offset = 0;
+    while offset < from.size {
+        flash
+            .read(from.offset + offset, &mut chunk)
+            .await
+            .map_err(|_| Error::Flash)?;
+        // Erased chunks need not be written.
+        if chunk.iter().any(|&byte| byte != 0xff) {
+            flash
+                .write(to.offset + offset, &chunk)
+                .await
+                .map_err(|_| Error::Flash)?;
+        }
+        offset += COPY_CHUNK_LEN;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use embassy_futures::block_on;
+    use embedded_storage_async::nor_flash::ReadNorFlash;
+
+    use super::*;
+    use crate::{Layout, faulty_flash::FaultyFlash};
+
+    const SLOT_SIZE: u32 = 1024;
+
+    /// Returns an updater with firmware in the active slot, and an update pending in the inactive
+    /// slot.
+    async fn pending() -> Updater<FaultyFlash> {
+        let layout = Layout::new(
+            Partition::new(0, SLOT_SIZE),
+            Partition::new(SLOT_SIZE, SLOT_SIZE),
+            Partition::new(2 * SLOT_SIZE, 2 * SLOT_SIZE),
+        );
+        let mut updater = Updater::new(FaultyFlash::new(4), layout).unwrap();
+        let firmware: Vec<_> = (0..SLOT_SIZE)
+            .map(|i| u8::try_from(i % 251).unwrap())
+            .collect();
+        updater.flash.write(0, &firmware).await.unwrap();
+
+        let update: Vec<_> = (0..1000)
+            .map(|i| u8::try_from(i % 241).unwrap() ^ 0x5a)
+            .collect();
+        let mut writer = updater.open().await.unwrap();
+        writer.write(&update).await.unwrap();
+        writer.finalize().await.unwrap();
+        updater
+    }
+
+    /// Returns the contents of the active and the inactive slot.
+    async fn slots(updater: &mut Updater<FaultyFlash>) -> (Vec<u8>, Vec<u8>) {
+        let mut active = vec![0; SLOT_SIZE as usize];
+        updater.flash.read(0, &mut active).await.unwrap();
+        let mut inactive = vec![0; SLOT_SIZE as usize];
+        updater.flash.read(SLOT_SIZE, &mut inactive).await.unwrap();
+        (active, inactive)
+    }
+
+    #[test]
+    fn unconfirmed_update_is_reverted() {
+        block_on(async {
+            let mut updater = pending().await;
+            let (firmware, update) = slots(&mut updater).await;
+
+            assert_eq!(apply(&mut updater).await, Ok(BootState::Testing));
+            assert_eq!(
+                slots(&mut updater).await,
+                (update.clone(), firmware.clone())
+            );
+            // Nothing is swapped while the update is on trial.
+            assert_eq!(apply(&mut updater).await, Ok(BootState::Testing));
+
+            for attempts in 0..BOOT_ATTEMPTS {
+                assert_eq!(updater.boot_attempts().await, Ok(u32::from(attempts)));
+                assert_eq!(updater.record_boot().await, Ok(BootState::Testing));
+            }
+            assert_eq!(updater.record_boot().await, Ok(BootState::Revert));
+
+            assert_eq!(apply(&mut updater).await, Ok(BootState::Idle));
+            assert_eq!(slots(&mut updater).await, (firmware, update));
+        });
+    }
+
+    #[test]
+    fn confirmed_update_is_kept() {
+        block_on(async {
+            let mut updater = pending().await;
+            let (firmware, update) = slots(&mut updater).await;
+
+            assert_eq!(apply(&mut updater).await, Ok(BootState::Testing));
+            assert_eq!(updater.record_boot().await, Ok(BootState::Testing));
+            updater.mark_booted().await.unwrap();
+            assert_eq!(updater.state().await, Ok(BootState::Idle));
+            assert_eq!(apply(&mut updater).await, Ok(BootState::Idle));
+            assert_eq!(slots(&mut updater).await, (update, firmware));
+        });
+    }
+
+    #[test]
+    fn interrupted_swap_is_resumed() {
+        block_on(async {
+            for budget in 0.. {
+                let mut updater = pending().await;
+                let (firmware, update) = slots(&mut updater).await;
+
+                updater.flash.cut_power_after(budget);
+                let result = apply(&mut updater).await;
+                if !updater.flash.restore_power() {
+                    assert_eq!(result, Ok(BootState::Testing));
+                    break;
+                }
+                assert_eq!(result, Err(Error::Flash));
+
+                // The update is never accepted without being on trial.
+                let state = updater.state().await.unwrap();
+                assert!(
+                    matches!(state, BootState::Pending | BootState::Testing),
+                    "{state:?} after {budget} bytes"
+                );
+                assert_eq!(apply(&mut updater).await, Ok(BootState::Testing));
+                assert_eq!(
+                    slots(&mut updater).await,
+                    (update, firmware),
+                    "after {budget} bytes"
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn interrupted_revert_is_resumed() {
+        block_on(async {
+            for budget in 0.. {
+                let mut updater = pending().await;
+                let (firmware, update) = slots(&mut updater).await;
+                assert_eq!(apply(&mut updater).await, Ok(BootState::Testing));
+
+                updater.flash.cut_power_after(budget);
+                let result = updater.mark_revert().await;
+                let reverting = !updater.flash.restore_power();
+                if !reverting {
+                    assert_eq!(result, Err(Error::Flash));
+                    // The update stays on trial, and is reverted at the latest once it ran out
+                    // of boot attempts.
+                    let state = updater.state().await.unwrap();
+                    assert_eq!(state, BootState::Testing, "after {budget} bytes");
+                    updater.mark_revert().await.unwrap();
+                }
+
+                updater.flash.cut_power_after(budget);
+                let result = apply(&mut updater).await;
+                if updater.flash.restore_power() {
+                    assert_eq!(result, Err(Error::Flash));
+                    let state = updater.state().await.unwrap();
+                    assert!(
+                        matches!(state, BootState::Revert | BootState::Idle),
+                        "{state:?} after {budget} bytes"
+                    );
+                    assert_eq!(apply(&mut updater).await, Ok(BootState::Idle));
+                } else {
+                    assert_eq!(result, Ok(BootState::Idle));
+                }
+                assert_eq!(
+                    slots(&mut updater).await,
+                    (firmware, update),
+                    "after {budget} bytes"
+                );
+                if reverting && result.is_ok() {
+                    break;
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn state_partition_needs_room_for_an_update() {
+        block_on(async {
+            // Besides the scratch page, a page holds the records of swapping slots of up to 41
+            // pages: 3 records per page and swap, and 6 more records with the default number of
+            // boot attempts.
+            for (pages, expected) in [(41, Ok(BootState::Testing)), (42, Err(Error::Layout))] {
+                let slot_size = pages * SLOT_SIZE;
+                let layout = Layout::new(
+                    Partition::new(0, slot_size),
+                    Partition::new(slot_size, slot_size),
+                    Partition::new(2 * slot_size, 2 * SLOT_SIZE),
+                );
+                let flash = FaultyFlash::new(usize::try_from(2 * pages + 2).unwrap());
+                let mut updater = Updater::new(flash, layout).unwrap();
+                let mut writer = updater.open().await.unwrap();
+                writer.write(&[0; 4]).await.unwrap();
+                writer.finalize().await.unwrap();
+                assert_eq!(apply(&mut updater).await, expected, "{pages} pages");
+            }
+        });
+    }
+}
@@ -0,0 +1,691 @@
+//! Delta updates, which produce the new image from the running firmware and a patch.
+//!
+//! Over constrained links (eg. LPWAN), downloading a patch from the running firmware to the new
+//! image is much cheaper than downloading the whole image. Patches are created with
+//! [detools](https://github.com/eerimoq/detools) from the image in the active slot:
+//!
+//! ```sh
+//! detools create_patch --compression heatshrink old.bin new.bin patch.bin
+//! ```
+//!
+//! The patch is fed into a [`PatchWriter`], which reads the running firmware from the active slot
+//! and writes the resulting image through a [`SlotWriter`]. Only sequential patches are
+//! supported, either uncompressed or compressed with heatshrink with the default parameters of
+//! detools (a window of 2⁸ bytes and a lookahead of 2⁷ bytes).
+//!
+//! The resulting image is not checked here: as with full images, its authenticity needs to be
+//! checked before finalizing it, eg. through the image digest of a SUIT manifest or the hash of an
+//! MCUboot image.
+
+use embedded_storage_async::nor_flash::NorFlash;
+
+use crate::{Error, SlotWriter};
+
+/// Patch type of sequential patches.
+const PATCH_TYPE_SEQUENTIAL: u8 = 0;
+/// Compression of uncompressed patches.
+const COMPRESSION_NONE: u8 = 0;
+/// Compression of patches compressed with heatshrink.
+const COMPRESSION_HEATSHRINK: u8 = 4;
+
+/// Base-2 logarithm of the heatshrink window length.
+const WINDOW_SZ2: u8 = 8;
+/// Base-2 logarithm of the heatshrink lookahead length.
+const LOOKAHEAD_SZ2: u8 = 7;
+/// Length of the heatshrink window.
+const WINDOW_LEN: usize = 1 << WINDOW_SZ2;
+
+/// Length of the chunks in which the patch is decompressed and applied.
+const CHUNK_LEN: usize = 64;
+
+/// Applies a patch to the running firmware, writing the resulting image into the inactive slot.
+///
+/// Like [`SlotWriter`], the patch can be written as a stream through [`PatchWriter::write()`] or
+/// the [`embedded_io_async::Write`] implementation, or as numbered blocks through
+/// [`PatchWriter::write_at()`]. Once the whole patch was written, [`PatchWriter::finish()`]
+/// returns the [`SlotWriter`] to finalize the image with.
+pub struct PatchWriter<'u, F: NorFlash> {
+    writer: SlotWriter<'u, F>,
+    /// Number of bytes of the patch accepted so far.
+    position: u32,
+    step: Step,
+    /// The size that is currently being decoded.
+    size: Size,
+    /// The decompressor, for compressed patches.
+    decompressor: Option<Heatshrink>,
+    /// Offset in the active slot of the next byte the patch refers to.
+    from_offset: u32,
+    /// Length of the resulting image.
+    to_size: u32,
+}
+
+impl<'u, F: NorFlash> PatchWriter<'u, F> {
+    /// Creates a patch writer that writes the resulting image through `writer`.
+    ///
+    /// `writer` needs to be freshly [opened](crate::Updater::open).
+    #[must_use]
+    pub fn new(writer: SlotWriter<'u, F>) -> Self {
+        Self {
+            writer,
+            position: 0,
+            step: Step::Header,
+            size: Size::default(),
+            decompressor: None,
+            from_offset: 0,
+            to_size: 0,
+        }
+    }
+
+    /// Returns the number of bytes of the patch written so far.
+    #[must_use]
+    pub fn position(&self) -> u32 {
+        self.position
+    }
+
+    /// Appends `patch` to the patch.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidPatch`] if the patch is invalid or not supported, does not apply to
+    /// the running firmware, or produces an image that does not fit into the inactive slot, and
+    /// otherwise errors like [`SlotWriter::write()`].
+    pub async fn write(&mut self, mut patch: &[u8]) -> Result<(), Error> {
+        let len = u32::try_from(patch.len()).map_err(|_| Error::InvalidPatch)?;
+        self.position = self.position.checked_add(len).ok_or(Error::InvalidPatch)?;
+
+        // The header is never compressed.
+        while matches!(self.step, Step::Header | Step::ToSize) {
+            let Some((&byte, rest)) = patch.split_first() else {
+                return Ok(());
+            };
+            self.control(byte)?;
+            patch = rest;
+        }
+
+        if self.decompressor.is_none() {
+            return self.apply(patch).await;
+        }
+        let mut chunk = [0; CHUNK_LEN];
+        loop {
+            let len = match &mut self.decompressor {
+                Some(decompressor) => decompressor.decompress(&mut patch, &mut chunk),
+                None => 0,
+            };
+            if len == 0 {
+                return Ok(());
+            }
+            self.apply(chunk.get(..len).ok_or(Error::InvalidPatch)?)
+                .await?;
+        }
+    }
+
+    /// Writes `patch` at `offset` into the patch.
+    ///
+    /// Data that was already written is ignored, like with [`SlotWriter::write_at()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::OutOfOrder`] if `offset` lies after the data written so far or data would
+    /// be written only partially, and otherwise errors like [`PatchWriter::write()`].
+    pub async fn write_at(&mut self, offset: u32, patch: &[u8]) -> Result<(), Error> {
+        let len = u32::try_from(patch.len()).map_err(|_| Error::InvalidPatch)?;
+        match offset.checked_add(len) {
+            Some(end) if end <= self.position => Ok(()),
+            _ if offset == self.position => self.write(patch).await,
+            _ => Err(Error::OutOfOrder),
+        }
+    }
+
+    /// Completes applying the patch, and returns the writer holding the resulting image, through
+    /// which it can be finalized.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidPatch`] if the patch is incomplete.
+    pub fn finish(self) -> Result<SlotWriter<'u, F>, Error> {
+        if self.step == Step::Done {
+            Ok(self.writer)
+        } else {
+            Err(Error::InvalidPatch)
+        }
+    }
+
+    /// Applies (decompressed) patch `data`.
+    async fn apply(&mut self, mut data: &[u8]) -> Result<(), Error> {
+        while !data.is_empty() {
+            match self.step {
+                Step::Diff(remaining) => {
+                    let len = data.len().min(CHUNK_LEN).min(remaining as usize);
+                    let (diff, rest) = data.split_at(len);
+                    let mut chunk = [0; CHUNK_LEN];
+                    let chunk = chunk.get_mut(..len).ok_or(Error::InvalidPatch)?;
+                    self.read_from(chunk).await?;
+                    for (byte, diff) in chunk.iter_mut().zip(diff) {
+                        *byte = byte.wrapping_add(*diff);
+                    }
+                    self.writer.write(chunk).await?;
+                    self.step = Step::Diff(
+                        remaining - u32::try_from(len).map_err(|_| Error::InvalidPatch)?,
+                    )
+                    .or_next(Step::ExtraSize);
+                    data = rest;
+                }
+                Step::Extra(remaining) => {
+                    let len = data.len().min(remaining as usize);
+                    let (extra, rest) = data.split_at(len);
+                    self.writer.write(extra).await?;
+                    self.step = Step::Extra(
+                        remaining - u32::try_from(len).map_err(|_| Error::InvalidPatch)?,
+                    )
+                    .or_next(Step::Adjustment);
+                    data = rest;
+                }
+                Step::Done => return Err(Error::InvalidPatch),
+                _ => {
+                    let Some((&byte, rest)) = data.split_first() else {
+                        break;
+                    };
+                    self.control(byte)?;
+                    data = rest;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Processes a byte of the header or of a size.
+    fn control(&mut self, byte: u8) -> Result<(), Error> {
+        if self.step == Step::Header {
+            if byte >> 4 != PATCH_TYPE_SEQUENTIAL {
+                return Err(Error::InvalidPatch);
+            }
+            self.decompressor = match byte & 0x0f {
+                COMPRESSION_NONE => None,
+                COMPRESSION_HEATSHRINK => Some(Heatshrink::new()),
+                _ => return Err(Error::InvalidPatch),
+            };
+            self.step = Step::ToSize;
+            return Ok(());
+        }
+
+        let Some(value) = self.size.push(byte)? else {
+            return Ok(());
+        };
+        let written = self.writer.position();
+        self.step = match self.step {
+            Step::ToSize => {
+                self.to_size = u32::try_from(value)
+                    .ok()
+                    .filter(|&size| size <= self.writer.layout().inactive.size)
+                    .ok_or(Error::InvalidPatch)?;
+                if self.to_size == 0 {
+                    Step::Done
+                } else {
+                    Step::DiffSize
+                }
+            }
+            Step::DiffSize | Step::ExtraSize => {
+                let size = u32::try_from(value).map_err(|_| Error::InvalidPatch)?;
+                if written
+                    .checked_add(size)
+                    .is_none_or(|end| end > self.to_size)
+                {
+                    return Err(Error::InvalidPatch);
+                }
+                if self.step == Step::DiffSize {
+                    Step::Diff(size).or_next(Step::ExtraSize)
+                } else {
+                    Step::Extra(size).or_next(Step::Adjustment)
+                }
+            }
+            Step::Adjustment => {
+                let adjustment = i32::try_from(value).map_err(|_| Error::InvalidPatch)?;
+                self.from_offset = self
+                    .from_offset
+                    .checked_add_signed(adjustment)
+                    .ok_or(Error::InvalidPatch)?;
+                if written < self.to_size {
+                    Step::DiffSize
+                } else {
+                    Step::Done
+                }
+            }
+            _ => return Err(Error::InvalidPatch),
+        };
+        Ok(())
+    }
+
+    /// Reads the next bytes of the running firmware that the patch refers to.
+    async fn read_from(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        let len = u32::try_from(buffer.len()).map_err(|_| Error::InvalidPatch)?;
+        let end = self
+            .from_offset
+            .checked_add(len)
+            .filter(|&end| end <= self.writer.layout().active.size)
+            .ok_or(Error::InvalidPatch)?;
+        self.writer.read_active(self.from_offset, buffer).await?;
+        self.from_offset = end;
+        Ok(())
+    }
+}
+
+impl<F: NorFlash> embedded_io_async::ErrorType for PatchWriter<'_, F> {
+    type Error = Error;
+}
+
+impl<F: NorFlash> embedded_io_async::Write for PatchWriter<'_, F> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        PatchWriter::write(self, buf).await?;
+        Ok(buf.len())
+    }
+}
+
+/// The part of a sequential patch that is expected next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    /// The byte holding the patch type and compression.
+    Header,
+    /// The size of the resulting image.
+    ToSize,
+    /// The size of the next diff data.
+    DiffSize,
+    /// Diff data, which is added to the running firmware, with the number of bytes remaining.
+    Diff(u32),
+    /// The size of the next extra data.
+    ExtraSize,
+    /// Extra data, which is written as it is, with the number of bytes remaining.
+    Extra(u32),
+    /// The adjustment of the offset into the running firmware.
+    Adjustment,
+    /// The patch is complete.
+    Done,
+}
+
+impl Step {
+    /// Returns `next` instead of data steps that have no bytes remaining.
+    fn or_next(self, next: Self) -> Self {
+        match self {
+            Self::Diff(0) | Self::Extra(0) => next,
+            _ => self,
+        }
+    }
+}
+
+/// Decodes a size, which is a variable-length signed integer.
+///
+/// The first byte holds a continuation bit, the sign and the 6 least significant bits of the
+/// value; the following bytes hold a continuation bit and the next 7 bits of the value each.
+#[derive(Default)]
+struct Size {
+    value: u64,
+    shift: u32,
+    negative: bool,
+}
+
+impl Size {
+    /// Adds a byte of the size, and returns the size once it is complete.
+    fn push(&mut self, byte: u8) -> Result<Option<i64>, Error> {
+        let bits = if self.shift == 0 {
+            self.negative = byte & 0x40 != 0;
+            byte & 0x3f
+        } else {
+            byte & 0x7f
+        };
+        if self.shift > 32 {
+            return Err(Error::InvalidPatch);
+        }
+        self.value |= u64::from(bits) << self.shift;
+        self.shift += if self.shift == 0 { 6 } else { 7 };
+        if byte & 0x80 != 0 {
+            return Ok(None);
+        }
+
+        let value =
+            i64::try_from(core::mem::take(&mut self.value)).map_err(|_| Error::InvalidPatch)?;
+        self.shift = 0;
+        Ok(Some(if self.negative { -value } else { value }))
+    }
+}
+
+/// Decompresses data compressed with heatshrink.
+struct Heatshrink {
+    /// The most recently decompressed bytes, which back-references refer to.
+    window: [u8; WINDOW_LEN],
+    /// Number of bytes decompressed so far, modulo the window length.
+    head: usize,
+    state: HeatshrinkState,
+    /// The current input byte, whose unread bits are the most significant ones.
+    byte: u8,
+    /// Number of unread bits of the current input byte.
+    unread: u8,
+    /// The bits read so far of the value that is currently being read.
+    value: u16,
+    /// Number of bits of the value that is currently being read.
+    value_len: u8,
+}
+
+/// The part of heatshrink data that is expected next.
+#[derive(Clone, Copy)]
+enum HeatshrinkState {
+    /// The bit indicating whether a literal or a back-reference follows.
+    Tag,
+    /// A literal byte.
+    Literal,
+    /// The index of a back-reference.
+    Index,
+    /// The count of a back-reference, with its distance.
+    Count(u16),
+    /// A back-reference that is being copied, with its distance and the number of bytes left.
+    Copy(u16, u16),
+}
+
+impl Heatshrink {
+    fn new() -> Self {
+        Self {
+            window: [0; WINDOW_LEN],
+            head: 0,
+            state: HeatshrinkState::Tag,
+            byte: 0,
+            unread: 0,
+            value: 0,
+            value_len: 0,
+        }
+    }
+
+    /// Decompresses `input` into `output`, and returns the number of bytes decompressed.
+    ///
+    /// Consumes as much of `input` as possible; fewer bytes than fit into `output` are returned
+    /// only once `input` is consumed.
+    fn decompress(&mut self, input: &mut &[u8], output: &mut [u8]) -> usize {
+        let mut len = 0;
+        while let Some(out) = output.get_mut(len) {
+            match self.state {
+                HeatshrinkState::Tag => {
+                    let Some(tag) = self.bits(input, 1) else {
+                        break;
+                    };
+                    self.state = if tag == 1 {
+                        HeatshrinkState::Literal
+                    } else {
+                        HeatshrinkState::Index
+                    };
+                }
+                HeatshrinkState::Literal => {
+                    let Some(byte) = self.bits(input, 8) else {
+                        break;
+                    };
+                    // The most significant byte is zero, as 8 bits were read.
+                    let [byte, _] = byte.to_le_bytes();
+                    *out = self.push(byte);
+                    len += 1;
+                    self.state = HeatshrinkState::Tag;
+                }
+                HeatshrinkState::Index => {
+                    let Some(index) = self.bits(input, WINDOW_SZ2) else {
+                        break;
+                    };
+                    self.state = HeatshrinkState::Count(index + 1);
+                }
+                HeatshrinkState::Count(distance) => {
+                    let Some(count) = self.bits(input, LOOKAHEAD_SZ2) else {
+                        break;
+                    };
+                    self.state = HeatshrinkState::Copy(distance, count + 1);
+                }
+                HeatshrinkState::Copy(distance, count) => {
+                    let position = self.head.wrapping_sub(usize::from(distance)) % WINDOW_LEN;
+                    let byte = self.window.get(position).copied().unwrap_or(0);
+                    *out = self.push(byte);
+                    len += 1;
+                    self.state = if count > 1 {
+                        HeatshrinkState::Copy(distance, count - 1)
+                    } else {
+                        HeatshrinkState::Tag
+                    };
+                }
+            }
+        }
+        len
+    }
+
+    /// Appends a decompressed byte to the window, and returns it.
+    fn push(&mut self, byte: u8) -> u8 {
+        if let Some(slot) = self.window.get_mut(self.head % WINDOW_LEN) {
+            *slot = byte;
+        }
+        self.head = (self.head + 1) % WINDOW_LEN;
+        byte
+    }
+
+    /// Reads a value of `count` bits, most significant bit first.
+    ///
+    /// Returns `None` if `input` is consumed before the value is complete; the bits read so far
+    /// are kept for the next call.
+    fn bits(&mut self, input: &mut &[u8], count: u8) -> Option<u16> {
+        while self.value_len < count {
+            if self.unread == 0 {
+                let (&byte, rest) = input.split_first()?;
+                *input = rest;
+                self.byte = byte;
+                self.unread = 8;
+            }
+            self.value = (self.value << 1) | u16::from(self.byte >> 7);
+            self.byte <<= 1;
+            self.unread -= 1;
+            self.value_len += 1;
+        }
+        self.value_len = 0;
+        Some(core::mem::take(&mut self.value))
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use embassy_futures::block_on;
+    use embedded_storage_async::nor_flash::ReadNorFlash;
+
+    use super::*;
+    use crate::{Layout, Partition, Updater, faulty_flash::FaultyFlash};
+
+    const ACTIVE_SIZE: u32 = 1024;
+    const INACTIVE_SIZE: u32 = 2048;
+
+    const OLD: &[u8; 44] = b"The quick brown fox jumps over the lazy dog.";
+    const NEW: &[u8; 45] = b"the quick brown cat jumps over the lazy dog!!";
+
+    /// Uncompressed sequential patch from [`OLD`] to [`NEW`], in the format created by detools.
+    const PATCH: &[u8] = &[
+        // Sequential, uncompressed, to size 45.
+        0x00, 45, //
+        // 16 bytes of diff: "The quick brown " becomes "the quick brown ".
+        16, 0x20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+        // 3 bytes of extra data, and skipping "fox".
+        3, b'c', b'a', b't', 3, //
+        // 24 bytes of diff: " jumps over the lazy dog".
+        24, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+        // 2 bytes of extra data, and no adjustment.
+        2, b'!', b'!', 0,
+    ];
+
+    /// [`PATCH`], compressed with heatshrink (window 2⁸ bytes, lookahead 2⁷ bytes).
+    const COMPRESSED_PATCH: &[u8] = &[
+        0x04, 45, 0x88, 0x48, 0x20, 0x00, 0x01, 0xb0, 0x3b, 0x1d, 0x86, 0xe9, 0x03, 0x8c, 0x05,
+        0x07, 0x00, 0x04, 0x40, 0xa4, 0x32, 0x18, 0x00,
+    ];
+
+    /// Returns an updater whose active slot holds [`OLD`].
+    fn updater() -> Updater<FaultyFlash> {
+        let layout = Layout::new(
+            Partition::new(0, ACTIVE_SIZE),
+            Partition::new(ACTIVE_SIZE, INACTIVE_SIZE),
+            Partition::new(ACTIVE_SIZE + INACTIVE_SIZE, 1024),
+        );
+        let mut updater = Updater::new(FaultyFlash::new(4), layout).unwrap();
+        block_on(updater.flash.write(0, OLD)).unwrap();
+        updater
+    }
+
+    /// Applies `patch` in chunks of `chunk_len` bytes, and returns the resulting image.
+    async fn apply(
+        updater: &mut Updater<FaultyFlash>,
+        patch: &[u8],
+        chunk_len: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let mut writer = PatchWriter::new(updater.open().await?);
+        for chunk in patch.chunks(chunk_len) {
+            writer.write(chunk).await?;
+        }
+        let writer = writer.finish()?;
+        let len = writer.position() as usize;
+        writer.finalize().await?;
+
+        let mut image = vec![0; len];
+        updater
+            .flash
+            .read(ACTIVE_SIZE, &mut image)
+            .await
+            .map_err(|_| Error::Flash)?;
+        Ok(image)
+    }
+
+    /// Returns whether everything after the inactive slot is still erased.
+    async fn state_untouched(updater: &mut Updater<FaultyFlash>) -> bool {
+        let mut data = [0; 4];
+        updater
+            .flash
+            .read(ACTIVE_SIZE + INACTIVE_SIZE, &mut data)
+            .await
+            .unwrap();
+        data == [0xff; 4]
+    }
+
+    #[test]
+    fn patch_is_applied() {
+        block_on(async {
+            for patch in [PATCH, COMPRESSED_PATCH] {
+                for chunk_len in [1, 7, patch.len()] {
+                    let mut updater = updater();
+                    assert_eq!(
+                        apply(&mut updater, patch, chunk_len).await.as_deref(),
+                        Ok(&NEW[..]),
+                        "{chunk_len} byte chunks"
+                    );
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn patch_is_written_in_blocks() {
+        block_on(async {
+            let mut updater = updater();
+            let mut writer = PatchWriter::new(updater.open().await.unwrap());
+            let blocks: Vec<_> = (0u32..).zip(COMPRESSED_PATCH.chunks(8)).collect();
+            for &(number, block) in &blocks {
+                writer.write_at(number * 8, block).await.unwrap();
+                // Retransmitted blocks are ignored.
+                writer.write_at(number * 8, block).await.unwrap();
+            }
+            assert_eq!(writer.write_at(1000, &[0]).await, Err(Error::OutOfOrder));
+            assert_eq!(writer.position() as usize, COMPRESSED_PATCH.len());
+            assert_eq!(writer.finish().unwrap().position() as usize, NEW.len());
+        });
+    }
+
+    #[test]
+    fn incomplete_patch_is_rejected() {
+        block_on(async {
+            let mut updater = updater();
+            let mut writer = PatchWriter::new(updater.open().await.unwrap());
+            writer.write(PATCH.split_last().unwrap().1).await.unwrap();
+            assert!(matches!(writer.finish(), Err(Error::InvalidPatch)));
+        });
+    }
+
+    #[test]
+    fn malformed_patches_are_rejected() {
+        let malformed: &[(&str, &[u8])] = &[
+            ("in-place patch", &[0x10, 1]),
+            ("unknown compression", &[0x01, 1]),
+            ("negative to size", &[0x00, 0x41]),
+            (
+                "overlong size",
+                &[0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+            ),
+            ("diff beyond the to size", &[0x00, 2, 3, 0, 0, 0]),
+            ("extra data beyond the to size", &[0x00, 2, 0, 3, 1, 2, 3]),
+            ("negative diff size", &[0x00, 2, 0x41]),
+            // Skipping back before the start of the active slot.
+            ("negative adjustment", &[0x00, 2, 1, 0, 0, 0x42, 1, 0]),
+            // Skipping beyond the end of the active slot.
+            (
+                "adjustment too large",
+                &[0x00, 2, 1, 0, 0, 0x80, 0x10, 1, 0],
+            ),
+            ("data after the end", &[0x00, 1, 1, 0, 0, 0, 0]),
+        ];
+        block_on(async {
+            for (name, patch) in malformed {
+                let mut updater = updater();
+                assert_eq!(
+                    apply(&mut updater, patch, patch.len()).await,
+                    Err(Error::InvalidPatch),
+                    "{name}"
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn diff_beyond_the_active_slot_is_rejected() {
+        block_on(async {
+            // Diff data reaching one byte beyond the active slot, with the image ending there.
+            let mut patch = vec![0x00, 0x81, 0x10, 0x81, 0x10];
+            patch.extend([0; ACTIVE_SIZE as usize + 1]);
+            let mut updater = updater();
+            assert_eq!(
+                apply(&mut updater, &patch, 64).await,
+                Err(Error::InvalidPatch)
+            );
+        });
+    }
+
+    #[test]
+    fn image_beyond_the_inactive_slot_is_rejected() {
+        block_on(async {
+            // The image just fits into the slot.
+            let mut patch = vec![0x00, 0x80, 0x20, 0, 0x80, 0x20];
+            patch.extend([0xaa; INACTIVE_SIZE as usize]);
+            patch.push(0);
+            let mut updater = self::updater();
+            assert_eq!(
+                apply(&mut updater, &patch, 100)
+                    .await
+                    .map(|image| image.len()),
+                Ok(INACTIVE_SIZE as usize)
+            );
+
+            // One more byte of extra data.
+            let mut patch = vec![0x00, 0x81, 0x20, 0, 0x81, 0x20];
+            patch.extend([0xaa; INACTIVE_SIZE as usize + 1]);
+            patch.push(0);
+            let mut updater = self::updater();
+            assert_eq!(
+                apply(&mut updater, &patch, 100).await,
+                Err(Error::InvalidPatch)
+            );
+            assert!(state_untouched(&mut updater).await);
+
+            // Extra data beyond the to size of a patch that fits.
+            let mut patch = vec![0x00, 0x80, 0x20, 0, 0x81, 0x20];
+            patch.extend([0xaa; INACTIVE_SIZE as usize + 1]);
+            let mut updater = self::updater();
+            assert_eq!(
+                apply(&mut updater, &patch, 100).await,
+                Err(Error::InvalidPatch)
+            );
+            assert!(state_untouched(&mut updater).await);
+        });
+    }
+}
@@ -0,0 +1,176 @@
+//! Downloads firmware images through HTTP(S).
+//!
+//! [`fetch()`] requests the image with a `Range` header starting at the position of the
+//! [`Download`], so that an interrupted download only fetches what is missing. Servers that do
+//! not support range requests send the whole image, which is then written from the start.
+//!
+//! The [`HttpClient`] is set up by the application, which decides whether and how TLS is used:
+//!
+//! ```ignore
+//! let mut client = HttpClient::new_with_tls(&tcp_client, &dns_client, tls_config);
+//! let mut download = Download::start(&mut updater, URL).await?;
+//! while let Err(err) = http::fetch(&mut client, URL, &mut download, &mut buffer).await {
+//!     // Retry after a while, continuing where the download stopped.
+//! }
+//! ```
+
+use core::fmt::Write as _;
+
+use embedded_io_async::Read as _;
+use embedded_nal_async::{Dns, TcpConnect};
+use embedded_storage_async::nor_flash::NorFlash;
+use reqwless::{
+    client::HttpClient,
+    request::{Method, RequestBuilder as _},
+    response::Response,
+};
+
+use super::Download;
+use crate::Error;
+
+/// Length of the chunks in which the response body is written.
+const CHUNK_LEN: usize = 256;
+
+/// Maximum length of the value of the `Range` header.
+const RANGE_LEN: usize = 24;
+
+/// Errors returned by [`fetch()`].
+#[derive(Debug)]
+pub enum FetchError {
+    /// The HTTP request failed, eg. because the connection was lost.
+    Http(reqwless::Error),
+    /// The server responded with an unexpected status code.
+    Status(u16),
+    /// The response does not match the request.
+    InvalidResponse,
+    /// Writing the image failed.
+    Update(Error),
+}
+
+impl From<reqwless::Error> for FetchError {
+    fn from(error: reqwless::Error) -> Self {
+        Self::Http(error)
+    }
+}
+
+impl From<Error> for FetchError {
+    fn from(error: Error) -> Self {
+        Self::Update(error)
+    }
+}
+
+impl core::fmt::Display for FetchError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Http(_) => write!(f, "HTTP request failed"),
+            Self::Status(status) => write!(f, "unexpected HTTP status {status}"),
+            Self::InvalidResponse => write!(f, "invalid HTTP response"),
+            Self::Update(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl core::error::Error for FetchError {}
+
+/// Fetches the rest of the image at `url` into `download`, using `buffer` for the response
+/// headers.
+///
+/// This returns once the whole image was received. If the download is interrupted, calling this
+/// again continues where it stopped.
+///
+/// # Errors
+///
+/// Returns [`FetchError::Http`] if the request failed, [`FetchError::Status`] and
+/// [`FetchError::InvalidResponse`] if the server did not respond with the image, and
+/// [`FetchError::Update`] if writing the image failed.
+pub async fn fetch<T: TcpConnect, D: Dns, F: NorFlash>(
+    client: &mut HttpClient<'_, T, D>,
+    url: &str,
+    download: &mut Download<'_, F>,
+    buffer: &mut [u8],
+) -> Result<(), FetchError> {
+    // The download starts over at most once, when the image changed on the server.
+    for _ in 0..2 {
+        if download.is_complete() {
+            return Ok(());
+        }
+        let position = download.position();
+        let mut range = heapless::String::<RANGE_LEN>::new();
+        write!(range, "bytes={position}-").map_err(|_| FetchError::InvalidResponse)?;
+        let headers = [("Range", range.as_str())];
+
+        let mut request = client.request(Method::GET, url).await?.headers(&headers);
+        let response = request.send(buffer).await?;
+        let start = match response.status.0 {
+            206 => {
+                let (start, total) = content_range(&response).ok_or(FetchError::InvalidResponse)?;
+                if let Some(total) = total
+                    && download.set_total(total).await?
+                {
+                    continue;
+                }
+                start
+            }
+            200 => {
+                if position > 0 {
+                    download.restart().await?;
+                }
+                if let Some(len) = response.content_length {
+                    let len = u32::try_from(len).map_err(|_| Error::ImageTooLarge)?;
+                    download.set_total(len).await?;
+                }
+                0
+            }
+            // The image shrank since the download was started.
+            416 => {
+                download.restart().await?;
+                continue;
+            }
+            status => return Err(FetchError::Status(status)),
+        };
+        if start != download.position() {
+            return Err(FetchError::InvalidResponse);
+        }
+
+        let mut body = response.body().reader();
+        let mut chunk = [0; CHUNK_LEN];
+        loop {
+            let len = body.read(&mut chunk).await?;
+            if len == 0 {
+                break;
+            }
+            download
+                .write(chunk.get(..len).ok_or(FetchError::InvalidResponse)?)
+                .await?;
+        }
+        return if download
+            .total()
+            .is_none_or(|total| total == download.position())
+        {
+            Ok(())
+        } else {
+            Err(FetchError::InvalidResponse)
+        };
+    }
+    Err(FetchError::InvalidResponse)
+}
+
+/// Returns the first position and the total length given in the `Content-Range` header of
+/// `response`.
+fn content_range<C: embedded_io_async::Read>(
+    response: &Response<'_, '_, C>,
+) -> Option<(u32, Option<u32>)> {
+    let (_, value) = response
+        .headers()
+        .find(|(name, _)| name.eq_ignore_ascii_case("Content-Range"))?;
+    let (range, total) = core::str::from_utf8(value)
+        .ok()?
+        .strip_prefix("bytes ")?
+        .split_once('/')?;
+    let (start, _) = range.split_once('-')?;
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+    Some((start.trim().parse().ok()?, total))
+}
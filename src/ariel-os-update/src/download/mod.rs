@@ -0,0 +1,276 @@
+//! Downloads of firmware images that survive interruptions.
+//!
+//! A [`Download`] wraps the [`SlotWriter`] of an image fetched from a `source` (eg. its URL or
+//! MQTT topic), and persists how much of it was written in [`ariel_os_storage`]: when a download
+//! is started again for the same source, eg. after a reboot or a lost connection, it continues
+//! where it stopped instead of starting over. Transport drivers drive the download:
+//!
+//! * [`http`] fetches images through HTTP(S), resuming with range requests;
+//! * [`mqtt`] reassembles images published in chunks on an MQTT topic.
+//!
+//! Every write sends a [`Progress`] event, which can be followed through [`progress()`]:
+//!
+//! ```ignore
+//! let mut updater = ariel_os::update::updater()?;
+//! let mut download = Download::start(&mut updater, URL).await?;
+//! http::fetch(&mut client, URL, &mut download).await?;
+//! download.finish().await?.finalize().await?;
+//! ```
+//!
+//! Progress is persisted once per flash page, so that an interrupted download continues at the
+//! start of the last page written. As pages written before cannot be checked when resuming,
+//! images should be authenticated before they are finalized (eg. through [`suit`](crate::suit)
+//! manifests).
+
+#[cfg(feature = "http")]
+pub mod http;
+pub mod mqtt;
+
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    watch::{DynReceiver, Watch},
+};
+use embedded_storage_async::nor_flash::NorFlash;
+
+use crate::{Error, SlotWriter, Updater};
+
+/// Storage key under which the progress of the download is persisted.
+const STATE_KEY: &str = "ariel-os-update.download";
+
+/// Length of the persisted state: the source identifier, the total length and the position.
+const STATE_LEN: usize = 12;
+
+/// Persisted instead of the total length when it is not known.
+const UNKNOWN_TOTAL: u32 = u32::MAX;
+
+/// Maximum number of [`progress()`] receivers at a time.
+const PROGRESS_RECEIVERS: usize = 2;
+
+static PROGRESS: Watch<CriticalSectionRawMutex, Progress, PROGRESS_RECEIVERS> = Watch::new();
+
+/// The progress of a download.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Number of bytes of the image received so far.
+    pub received: u32,
+    /// Length of the image, if known.
+    pub total: Option<u32>,
+}
+
+/// Returns a receiver of [`Progress`] events, or `None` if there are too many receivers already.
+#[must_use]
+pub fn progress() -> Option<DynReceiver<'static, Progress>> {
+    PROGRESS.dyn_receiver()
+}
+
+/// A download of a firmware image into the inactive slot, which can be resumed.
+pub struct Download<'u, F: NorFlash> {
+    writer: SlotWriter<'u, F>,
+    /// Identifier of the source the image is downloaded from.
+    source: u32,
+    total: Option<u32>,
+    /// Position up to which the progress was persisted.
+    persisted: u32,
+}
+
+impl<'u, F: NorFlash> Download<'u, F> {
+    /// Starts downloading the image identified by `source`, or resumes downloading it if a
+    /// download of the same source was interrupted before.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Storage`] if accessing the storage failed, and otherwise errors like
+    /// [`Updater::open()`].
+    pub async fn start(updater: &'u mut Updater<F>, source: &str) -> Result<Self, Error> {
+        let source = source_id(source);
+        let (writer, total) = match load().await? {
+            Some(state) if state.source == source => {
+                (updater.resume(state.position).await?, state.total)
+            }
+            _ => (updater.open().await?, None),
+        };
+        let mut download = Self {
+            source,
+            total,
+            persisted: writer.position(),
+            writer,
+        };
+        download.persist().await?;
+        download.report();
+        Ok(download)
+    }
+
+    /// Returns the number of bytes of the image received so far, which is where the transport
+    /// needs to continue.
+    #[must_use]
+    pub fn position(&self) -> u32 {
+        self.writer.position()
+    }
+
+    /// Returns the length of the image, if known.
+    #[must_use]
+    pub fn total(&self) -> Option<u32> {
+        self.total
+    }
+
+    /// Returns whether the whole image was received.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.total == Some(self.position())
+    }
+
+    /// Sets the length of the image, as announced by the transport.
+    ///
+    /// If a different length was known before, the image changed since the download was started:
+    /// the download then starts over, and `true` is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ImageTooLarge`] if the image does not fit into the inactive slot, and
+    /// [`Error::Storage`] if accessing the storage failed.
+    pub async fn set_total(&mut self, total: u32) -> Result<bool, Error> {
+        if total > self.writer.layout().inactive.size {
+            return Err(Error::ImageTooLarge);
+        }
+        let changed = self.total.is_some_and(|known| known != total);
+        if changed {
+            self.rewind();
+        }
+        if changed || self.total.is_none() {
+            self.total = Some(total);
+            self.persist().await?;
+            self.report();
+        }
+        Ok(changed)
+    }
+
+    /// Starts the download over, eg. because the transport cannot resume it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Storage`] if accessing the storage failed.
+    pub async fn restart(&mut self) -> Result<(), Error> {
+        self.rewind();
+        self.total = None;
+        self.persist().await?;
+        self.report();
+        Ok(())
+    }
+
+    /// Appends `data` to the image.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ImageTooLarge`] if the image exceeds its announced length or does not fit
+    /// into the inactive slot, and otherwise errors like [`SlotWriter::write()`] and
+    /// [`Error::Storage`] if accessing the storage failed.
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        let len = u32::try_from(data.len()).map_err(|_| Error::ImageTooLarge)?;
+        if self
+            .total
+            .is_some_and(|total| self.position().saturating_add(len) > total)
+        {
+            return Err(Error::ImageTooLarge);
+        }
+        self.writer.write(data).await?;
+        let erase_size = u32::try_from(F::ERASE_SIZE).map_err(|_| Error::Layout)?;
+        // Whole pages are written up to here, see `Updater::resume()`.
+        let page = self.position() - self.position() % erase_size;
+        if page > self.persisted {
+            self.persisted = page;
+            self.persist().await?;
+        }
+        self.report();
+        Ok(())
+    }
+
+    /// Completes the download, and returns the writer of the image to finalize it.
+    ///
+    /// The persisted progress is discarded, so that the next download of the same source starts
+    /// over.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidState`] if the image was not received completely, and
+    /// [`Error::Storage`] if accessing the storage failed.
+    pub async fn finish(self) -> Result<SlotWriter<'u, F>, Error> {
+        if self.total.is_some_and(|total| total != self.position()) {
+            return Err(Error::InvalidState);
+        }
+        clear().await?;
+        Ok(self.writer)
+    }
+
+    /// Discards the image written so far.
+    fn rewind(&mut self) {
+        self.writer.rewind();
+        self.persisted = 0;
+    }
+
+    /// Persists the progress of the download.
+    async fn persist(&mut self) -> Result<(), Error> {
+        let mut state = [0; STATE_LEN];
+        for (field, value) in state.chunks_exact_mut(4).zip([
+            self.source,
+            self.total.unwrap_or(UNKNOWN_TOTAL),
+            self.persisted,
+        ]) {
+            field.copy_from_slice(&value.to_le_bytes());
+        }
+        ariel_os_storage::insert_blob(STATE_KEY, &state)
+            .await
+            .map_err(|_| Error::Storage)
+    }
+
+    /// Sends the current progress to the [`progress()`] receivers.
+    fn report(&self) {
+        PROGRESS.sender().send(Progress {
+            received: self.position(),
+            total: self.total,
+        });
+    }
+}
+
+/// The persisted progress of a download.
+struct State {
+    source: u32,
+    total: Option<u32>,
+    position: u32,
+}
+
+/// Loads the persisted progress of a download, if any.
+async fn load() -> Result<Option<State>, Error> {
+    let mut buffer = [0; STATE_LEN];
+    let Some(state) = ariel_os_storage::get_blob(STATE_KEY, &mut buffer)
+        .await
+        .map_err(|_| Error::Storage)?
+    else {
+        return Ok(None);
+    };
+    let ([source, total, position], []) = state.as_chunks::<4>() else {
+        // Cleared, or written by an incompatible version.
+        return Ok(None);
+    };
+    let total = u32::from_le_bytes(*total);
+    Ok(Some(State {
+        source: u32::from_le_bytes(*source),
+        total: (total != UNKNOWN_TOTAL).then_some(total),
+        position: u32::from_le_bytes(*position),
+    }))
+}
+
+/// Discards the persisted progress of a download.
+async fn clear() -> Result<(), Error> {
+    // Removing is not supported by all flash drivers.
+    ariel_os_storage::insert_blob(STATE_KEY, &[])
+        .await
+        .map_err(|_| Error::Storage)
+}
+
+/// Returns the identifier of `source` under which its progress is persisted, which is its 32-bit
+/// FNV-1a hash.
+fn source_id(source: &str) -> u32 {
+    source.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
@@ -0,0 +1,83 @@
+//! Reassembles firmware images published in chunks over MQTT.
+//!
+//! This does not depend on a particular MQTT client: the application subscribes to the topic the
+//! image is published on, and passes the received messages to [`receive()`]. Each message holds
+//! one chunk of the image, prefixed with its offset and the length of the whole image, both as
+//! 32-bit big-endian integers:
+//!
+//! ```text
+//! | offset (4 bytes) | total length (4 bytes) | data |
+//! ```
+//!
+//! Chunks need to be published in order. As MQTT does not retransmit messages to clients that
+//! were disconnected, a device that missed chunks (or that resumes a download after a reboot)
+//! asks the publisher to continue at the position it needs, by publishing the payload of
+//! [`request()`] on a topic the publisher listens to. Chunks that were received before are
+//! ignored, so that the publisher can serve several devices at once.
+
+use embedded_storage_async::nor_flash::NorFlash;
+
+use super::Download;
+use crate::Error;
+
+/// What the reception of a chunk resulted in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Received {
+    /// The chunk was written, or was received before; more chunks are needed.
+    Progress,
+    /// The whole image was received; the download can be [finished](Download::finish).
+    Complete,
+    /// The chunk lies after the data received so far: the chunks from the given offset need to
+    /// be [requested](request()) again.
+    Missing {
+        /// The offset at which the image needs to continue.
+        offset: u32,
+    },
+}
+
+/// Writes the chunk in the MQTT message `payload` into `download`.
+///
+/// If the length of the image differs from what was announced before, the image changed and the
+/// download starts over.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidChunk`] if the message is malformed, and otherwise errors like
+/// [`Download::write()`].
+pub async fn receive<F: NorFlash>(
+    download: &mut Download<'_, F>,
+    payload: &[u8],
+) -> Result<Received, Error> {
+    let (offset, rest) = payload
+        .split_first_chunk::<4>()
+        .ok_or(Error::InvalidChunk)?;
+    let (total, data) = rest.split_first_chunk::<4>().ok_or(Error::InvalidChunk)?;
+    let (offset, total) = (u32::from_be_bytes(*offset), u32::from_be_bytes(*total));
+    let len = u32::try_from(data.len()).map_err(|_| Error::InvalidChunk)?;
+    if offset.checked_add(len).is_none_or(|end| end > total) {
+        return Err(Error::InvalidChunk);
+    }
+
+    download.set_total(total).await?;
+    let position = download.position();
+    if offset > position {
+        return Ok(Received::Missing { offset: position });
+    }
+    // Skip what was received before.
+    let new = data.get((position - offset) as usize..).unwrap_or_default();
+    if !new.is_empty() {
+        download.write(new).await?;
+    }
+    Ok(if download.is_complete() {
+        Received::Complete
+    } else {
+        Received::Progress
+    })
+}
+
+/// Returns the payload of a message that asks the publisher to continue at the position
+/// `download` needs, which is that position as a 32-bit big-endian integer.
+#[must_use]
+pub fn request<F: NorFlash>(download: &Download<'_, F>) -> [u8; 4] {
+    download.position().to_be_bytes()
+}
@@ -0,0 +1,306 @@
+//! The system-wide updater, which shares the flash driver with [`ariel_os_storage`], and the
+//! confirmation of the running firmware.
+
+use core::{
+    convert::Infallible,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use ariel_os_hal::storage::Flash;
+use embedded_storage_async::nor_flash::{ErrorType, NorFlash, ReadNorFlash};
+
+use crate::{BootState, Error, Updater, layout::CONFIGURED};
+
+/// Whether the running firmware is confirmed, as last seen by itself.
+static CONFIRMED: AtomicU8 = AtomicU8::new(CONFIRMED_UNKNOWN);
+
+const CONFIRMED_UNKNOWN: u8 = 0;
+const CONFIRMED_YES: u8 = 1;
+const CONFIRMED_NO: u8 = 2;
+
+/// The last error reported through [`report_error()`], as its index in [`ERRORS`] plus one, or
+/// zero if none was reported.
+static LAST_ERROR: AtomicU8 = AtomicU8::new(0);
+
+/// The errors that can be reported through [`report_error()`].
+const ERRORS: [Error; 10] = [
+    Error::Flash,
+    Error::Layout,
+    Error::ImageTooLarge,
+    Error::OutOfOrder,
+    Error::InvalidState,
+    Error::InvalidImage,
+    Error::InvalidPatch,
+    Error::InvalidChunk,
+    Error::Storage,
+    Error::Unauthenticated,
+];
+
+/// Handle to the system flash, which is shared with [`ariel_os_storage`].
+///
+/// Every operation locks the storage for its duration, so that updates can be written while the
+/// storage is in use.
+pub struct GlobalFlash {
+    _private: (),
+}
+
+impl ErrorType for GlobalFlash {
+    type Error = <Flash as ErrorType>::Error;
+}
+
+impl ReadNorFlash for GlobalFlash {
+    const READ_SIZE: usize = <Flash as ReadNorFlash>::READ_SIZE;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        ariel_os_storage::lock()
+            .await
+            .flash_mut()
+            .read(offset, bytes)
+            .await
+    }
+
+    fn capacity(&self) -> usize {
+        CONFIGURED
+            .active
+            .end()
+            .max(CONFIGURED.inactive.end())
+            .max(CONFIGURED.state.end()) as usize
+    }
+}
+
+impl NorFlash for GlobalFlash {
+    const WRITE_SIZE: usize = <Flash as NorFlash>::WRITE_SIZE;
+    const ERASE_SIZE: usize = <Flash as NorFlash>::ERASE_SIZE;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        ariel_os_storage::lock()
+            .await
+            .flash_mut()
+            .erase(from, to)
+            .await
+    }
+
+    async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        ariel_os_storage::lock()
+            .await
+            .flash_mut()
+            .write(offset, bytes)
+            .await
+    }
+}
+
+/// Returns an [`Updater`] for the system flash, using the [configured layout](CONFIGURED).
+///
+/// The layout must not overlap with the storage range.
+///
+/// # Errors
+///
+/// Returns [`Error::Layout`] if the configured layout is not valid for the system flash.
+pub fn updater() -> Result<Updater<GlobalFlash>, Error> {
+    Updater::new(GlobalFlash { _private: () }, CONFIGURED)
+}
+
+/// Confirms the running firmware, so that the bootloader keeps it.
+///
+/// This does nothing if the running firmware is already confirmed. See [`confirm_after()`] to
+/// confirm it only once self-tests have passed.
+///
+/// # Errors
+///
+/// Returns [`Error::Layout`] if the configured layout is not valid, and [`Error::Flash`] if
+/// accessing the flash failed.
+pub async fn confirm() -> Result<(), Error> {
+    let mut updater = updater()?;
+    updater.mark_booted().await?;
+    #[cfg(feature = "mcuboot")]
+    crate::mcuboot::confirm(&mut updater).await?;
+    CONFIRMED.store(CONFIRMED_YES, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Returns whether the running firmware is confirmed, or `None` if that is not known.
+///
+/// This does not access the flash, but returns what was found at startup and updated by
+/// [`confirm()`]; it is unknown if the configured layout is not valid.
+#[must_use]
+pub fn is_confirmed() -> Option<bool> {
+    match CONFIRMED.load(Ordering::Relaxed) {
+        CONFIRMED_YES => Some(true),
+        CONFIRMED_NO => Some(false),
+        _ => None,
+    }
+}
+
+/// Reverts to the previous firmware, by rebooting into it.
+///
+/// This is only possible while the running firmware is on trial.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidState`] if the running firmware is already confirmed,
+/// [`Error::Layout`] if the configured layout is not valid, and [`Error::Flash`] if accessing
+/// the flash failed; it does not return otherwise.
+pub async fn revert() -> Result<Infallible, Error> {
+    let mut updater = updater()?;
+    #[cfg(feature = "mcuboot")]
+    if updater.layout.state.size == 0 {
+        // MCUboot reverts an image on its own at the next boot, unless it is confirmed.
+        let trailer = crate::mcuboot::trailer(&mut updater, crate::mcuboot::Slot::Primary).await?;
+        if !trailer.magic || trailer.image_ok {
+            return Err(Error::InvalidState);
+        }
+        ariel_os_power::reboot();
+    }
+    updater.mark_revert().await?;
+    ariel_os_power::reboot()
+}
+
+/// Requests the bootloader to swap in the pending update, by rebooting.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidState`] if no update is [pending](BootState::Pending),
+/// [`Error::Layout`] if the configured layout is not valid, and [`Error::Flash`] if accessing
+/// the flash failed; it does not return otherwise.
+pub async fn install() -> Result<Infallible, Error> {
+    if status().await?.state != BootState::Pending {
+        return Err(Error::InvalidState);
+    }
+    ariel_os_power::reboot()
+}
+
+/// The status of the firmware slots, as returned by [`status()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Status {
+    /// The boot state.
+    ///
+    /// With [MCUboot](crate::mcuboot), it is derived from the trailers of the slots, and is never
+    /// [`BootState::Revert`].
+    pub state: BootState,
+    /// Whether the running firmware is confirmed, see [`is_confirmed()`].
+    pub confirmed: Option<bool>,
+    /// Number of times the running firmware was booted while on trial, see
+    /// [`Updater::boot_attempts()`].
+    pub boot_attempts: u32,
+    /// The last error reported since startup, see [`report_error()`].
+    pub last_error: Option<Error>,
+}
+
+/// Returns the status of the firmware slots, eg. for device management.
+///
+/// # Errors
+///
+/// Returns [`Error::Layout`] if the configured layout is not valid, and [`Error::Flash`] if
+/// reading the flash failed.
+pub async fn status() -> Result<Status, Error> {
+    let mut updater = updater()?;
+    #[cfg_attr(not(feature = "mcuboot"), expect(unused_mut))]
+    let mut state = updater.state().await?;
+    #[cfg(feature = "mcuboot")]
+    if updater.layout.state.size == 0 {
+        use crate::mcuboot::{Slot, SwapType, trailer};
+
+        let active = trailer(&mut updater, Slot::Primary).await?;
+        let inactive = trailer(&mut updater, Slot::Secondary).await?;
+        state = if active.magic && !active.image_ok {
+            BootState::Testing
+        } else if inactive.magic
+            && matches!(
+                inactive.swap_type,
+                Some(SwapType::Test | SwapType::Permanent)
+            )
+        {
+            BootState::Pending
+        } else {
+            BootState::Idle
+        };
+    }
+    Ok(Status {
+        state,
+        confirmed: is_confirmed(),
+        boot_attempts: updater.boot_attempts().await?,
+        last_error: last_error(),
+    })
+}
+
+/// Records `error` as the last error of an update, which is reported by [`status()`].
+///
+/// Transports call this when receiving or finalizing an image failed, so that device management
+/// can tell why an update did not take place. Only the last error is kept, and only until the
+/// next reboot.
+pub fn report_error(error: Error) {
+    let index = ERRORS
+        .iter()
+        .position(|&known| known == error)
+        .map_or(0, |index| index + 1);
+    LAST_ERROR.store(u8::try_from(index).unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Returns the last error reported through [`report_error()`] since startup, if any.
+#[must_use]
+pub fn last_error() -> Option<Error> {
+    let index = LAST_ERROR.load(Ordering::Relaxed).checked_sub(1)?;
+    ERRORS.get(usize::from(index)).copied()
+}
+
+/// Runs `self_test` if the running firmware is on trial, and confirms it if the test passes.
+///
+/// Applications can use this to check that the firmware works as intended before confirming it,
+/// eg. that the network comes up and the sensors can be read. If `self_test` returns `false`,
+/// this [reverts](revert()) to the previous firmware. If the running firmware is already
+/// confirmed, `self_test` is not run.
+///
+/// Self-tests should time out on their own: if they never finish, the firmware is only reverted
+/// once it was rebooted [`BOOT_ATTEMPTS`](crate::BOOT_ATTEMPTS) times.
+///
+/// # Errors
+///
+/// Returns [`Error::Layout`] if the configured layout is not valid, and [`Error::Flash`] if
+/// accessing the flash failed.
+pub async fn confirm_after(self_test: impl AsyncFnOnce() -> bool) -> Result<(), Error> {
+    if !on_trial().await? {
+        return Ok(());
+    }
+    if self_test().await {
+        confirm().await
+    } else {
+        revert().await.map(|never| match never {})
+    }
+}
+
+/// Returns whether the running firmware is on trial.
+async fn on_trial() -> Result<bool, Error> {
+    let mut updater = updater()?;
+    if updater.state().await? == BootState::Testing {
+        return Ok(true);
+    }
+    #[cfg(feature = "mcuboot")]
+    if updater.layout.state.size == 0 {
+        let trailer = crate::mcuboot::trailer(&mut updater, crate::mcuboot::Slot::Primary).await?;
+        return Ok(trailer.magic && !trailer.image_ok);
+    }
+    Ok(false)
+}
+
+/// Records the boot of the running firmware, rebooting into the previous firmware if the running
+/// firmware was booted too often without being confirmed (see [`Updater::record_boot()`]).
+///
+/// This is called by the system at startup.
+#[doc(hidden)]
+pub async fn record_boot() {
+    let Ok(mut updater) = updater() else {
+        return;
+    };
+    if let Ok(BootState::Revert) = updater.record_boot().await {
+        ariel_os_power::reboot();
+    }
+    if let Ok(on_trial) = on_trial().await {
+        let confirmed = if on_trial {
+            CONFIRMED_NO
+        } else {
+            CONFIRMED_YES
+        };
+        CONFIRMED.store(confirmed, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,134 @@
+//! Describes how the flash is partitioned into image slots.
+
+use embedded_storage_async::nor_flash::NorFlash;
+
+use crate::{BOOT_ATTEMPTS, Error, MAX_WRITE_SIZE, state};
+
+/// A contiguous region of flash.
+///
+/// Offsets are given in the address space of the flash driver, which may differ from the address
+/// the flash is mapped to in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Partition {
+    /// Offset of the first byte of the partition.
+    pub offset: u32,
+    /// Size of the partition in bytes.
+    pub size: u32,
+}
+
+impl Partition {
+    /// Creates a partition.
+    #[must_use]
+    pub const fn new(offset: u32, size: u32) -> Self {
+        Self { offset, size }
+    }
+
+    /// Returns the offset of the first byte after the partition.
+    #[must_use]
+    pub const fn end(&self) -> u32 {
+        self.offset.saturating_add(self.size)
+    }
+
+    const fn overlaps(self, other: Self) -> bool {
+        self.size > 0 && other.size > 0 && self.offset < other.end() && other.offset < self.end()
+    }
+
+    const fn is_aligned(self, alignment: u32) -> bool {
+        self.offset.is_multiple_of(alignment) && self.size.is_multiple_of(alignment)
+    }
+}
+
+/// The partitions used for firmware updates.
+///
+/// The running firmware is always executed from the active slot. An update is written into the
+/// inactive slot; at the next boot, the bootloader swaps the contents of the two slots, as
+/// requested through the state partition. This keeps the previous firmware in the inactive slot,
+/// so that it can be reverted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    /// The slot the running firmware is executed from.
+    pub active: Partition,
+    /// The slot updates are written to.
+    pub inactive: Partition,
+    /// The partition holding the [`BootState`](crate::BootState).
+    ///
+    /// This may be empty if the bootloader keeps track of the boot state in the slots themselves
+    /// (see the `mcuboot` module).
+    pub state: Partition,
+}
+
+impl Layout {
+    /// Creates a layout from its partitions.
+    #[must_use]
+    pub const fn new(active: Partition, inactive: Partition, state: Partition) -> Self {
+        Self {
+            active,
+            inactive,
+            state,
+        }
+    }
+
+    /// Checks that the layout can be used with the flash `F`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Layout`] if a slot is empty, if a partition is not aligned to erase pages,
+    /// if the partitions overlap, if the inactive slot is smaller than the active slot, or if the
+    /// state partition is not empty but too small to record [`BOOT_ATTEMPTS`] boots and a revert.
+    pub fn check<F: NorFlash>(&self) -> Result<(), Error> {
+        let erase_size = u32::try_from(F::ERASE_SIZE).map_err(|_| Error::Layout)?;
+        let valid = F::WRITE_SIZE <= MAX_WRITE_SIZE
+            && self.active.size > 0
+            && self.inactive.size > 0
+            && [self.active, self.inactive, self.state]
+                .iter()
+                .all(|partition| partition.is_aligned(erase_size))
+            && !self.active.overlaps(self.inactive)
+            && !self.active.overlaps(self.state)
+            && !self.inactive.overlaps(self.state)
+            && self.inactive.size >= self.active.size
+            && (self.state.size == 0
+                || state::record_offset::<F>(self.state, u32::from(BOOT_ATTEMPTS) + 1).is_some());
+        if valid { Ok(()) } else { Err(Error::Layout) }
+    }
+}
+
+/// The layout configured at build time.
+///
+/// It is configured through the `CONFIG_UPDATE_ACTIVE_OFFSET`, `CONFIG_UPDATE_INACTIVE_OFFSET`,
+/// `CONFIG_UPDATE_SLOT_SIZE`, `CONFIG_UPDATE_STATE_OFFSET` and `CONFIG_UPDATE_STATE_SIZE`
+/// environment variables, which need to match the configuration of the bootloader. Without
+/// configuration, the layout is empty and fails [`Layout::check()`].
+pub const CONFIGURED: Layout = Layout::new(
+    Partition::new(
+        ariel_os_utils::u32_from_env_or!(
+            "CONFIG_UPDATE_ACTIVE_OFFSET",
+            0,
+            "offset of the active firmware slot in flash"
+        ),
+        SLOT_SIZE,
+    ),
+    Partition::new(
+        ariel_os_utils::u32_from_env_or!(
+            "CONFIG_UPDATE_INACTIVE_OFFSET",
+            0,
+            "offset of the inactive firmware slot in flash"
+        ),
+        SLOT_SIZE,
+    ),
+    Partition::new(
+        ariel_os_utils::u32_from_env_or!(
+            "CONFIG_UPDATE_STATE_OFFSET",
+            0,
+            "offset of the firmware update state partition in flash"
+        ),
+        ariel_os_utils::u32_from_env_or!(
+            "CONFIG_UPDATE_STATE_SIZE",
+            4096,
+            "size of the firmware update state partition"
+        ),
+    ),
+);
+
+const SLOT_SIZE: u32 =
+    ariel_os_utils::u32_from_env_or!("CONFIG_UPDATE_SLOT_SIZE", 0, "size of each firmware slot");
@@ -0,0 +1,326 @@
+//! Provides A/B firmware updates.
+//!
+//! The flash is divided into two image slots and a small state partition (see [`Layout`]). The
+//! running firmware is executed from the active slot, while an update is written into the
+//! inactive slot. A bootloader sharing the same layout then swaps the slots as requested through
+//! the [`BootState`]:
+//!
+//! 1. The update is written into the inactive slot through a [`SlotWriter`] obtained from
+//!    [`Updater::open()`]; [finalizing](SlotWriter::finalize) it marks the update as
+//!    [pending](BootState::Pending).
+//! 2. At the next boot, the bootloader swaps the slots and marks the new firmware as
+//!    [on trial](BootState::Testing).
+//! 3. Every boot of the new firmware is recorded early at startup through
+//!    [`Updater::record_boot()`]. Once the new firmware has checked that it works as intended, it
+//!    confirms itself with [`Updater::mark_booted()`]. If it is not confirmed within
+//!    [`BOOT_ATTEMPTS`] boots (eg. because it keeps crashing before), the previous firmware is
+//!    swapped back in.
+//!
+//! Transports (eg. CoAP or USB handlers) feed the received image into a [`SlotWriter`], either as a
+//! stream or as numbered blocks (see [`SlotWriter`]); images fetched through HTTP or MQTT can be
+//! [`download`]ed so that interrupted downloads are resumed. Images are not authenticated here;
+//! transports need to check their authenticity before finalizing them, eg. by processing a signed
+//! [`suit`] manifest.
+//!
+//! # Configuration
+//!
+//! The layout is configured at build time through the following environment variables, given in
+//! bytes as decimal numbers, which need to match the configuration of the bootloader:
+//!
+//! | Environment variable            | Description                                 |
+//! | ------------------------------- | ------------------------------------------- |
+//! | `CONFIG_UPDATE_ACTIVE_OFFSET`   | Offset of the active slot in flash          |
+//! | `CONFIG_UPDATE_INACTIVE_OFFSET` | Offset of the inactive slot in flash        |
+//! | `CONFIG_UPDATE_SLOT_SIZE`       | Size of each slot                           |
+//! | `CONFIG_UPDATE_STATE_OFFSET`    | Offset of the state partition in flash      |
+//! | `CONFIG_UPDATE_STATE_SIZE`      | Size of the state partition (default: 4096) |
+//! | `CONFIG_UPDATE_BOOT_ATTEMPTS`   | Value of [`BOOT_ATTEMPTS`] (default: 3)     |
+//!
+//! The state partition may be empty when the bootloader keeps the boot state elsewhere, as
+//! [MCUboot](mcuboot) does.
+
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+#![expect(clippy::missing_errors_doc)]
+
+#[cfg(feature = "bootloader")]
+pub mod boot;
+#[cfg(feature = "delta")]
+pub mod delta;
+#[cfg(feature = "download")]
+pub mod download;
+#[cfg(test)]
+mod faulty_flash;
+#[cfg(feature = "storage")]
+mod global;
+pub mod layout;
+#[cfg(feature = "mcuboot")]
+pub mod mcuboot;
+mod state;
+#[cfg(feature = "suit")]
+pub mod suit;
+mod writer;
+
+use embedded_storage_async::nor_flash::NorFlash;
+
+#[cfg(feature = "storage")]
+pub use global::{
+    GlobalFlash, Status, confirm, confirm_after, install, is_confirmed, last_error, record_boot,
+    report_error, revert, status, updater,
+};
+pub use layout::{Layout, Partition};
+pub use state::BootState;
+pub use writer::SlotWriter;
+
+/// Length of the chunks in which [`read_unaligned()`] reads the flash.
+const READ_CHUNK_LEN: usize = 64;
+
+/// The largest write size of flash drivers supported.
+pub const MAX_WRITE_SIZE: usize = 32;
+
+/// Number of times a firmware on trial is booted without being confirmed before it is reverted.
+pub const BOOT_ATTEMPTS: u8 = ariel_os_utils::u8_from_env_or!(
+    "CONFIG_UPDATE_BOOT_ATTEMPTS",
+    3,
+    "number of boots of an unconfirmed firmware before it is reverted"
+);
+
+/// Manages the firmware slots on a flash.
+pub struct Updater<F> {
+    pub(crate) flash: F,
+    pub(crate) layout: Layout,
+}
+
+impl<F: NorFlash> Updater<F> {
+    /// Creates an updater for the slots described by `layout` on `flash`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Layout`] if the layout is not valid for the flash (see
+    /// [`Layout::check()`]).
+    pub fn new(flash: F, layout: Layout) -> Result<Self, Error> {
+        layout.check::<F>()?;
+        Ok(Self { flash, layout })
+    }
+
+    /// Returns the layout of the slots.
+    #[must_use]
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    /// Reads the current boot state.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Flash`] if reading the flash failed.
+    pub async fn state(&mut self) -> Result<BootState, Error> {
+        BootState::read(&mut self.flash, &self.layout.state).await
+    }
+
+    /// Opens the inactive slot for writing a new image.
+    ///
+    /// An update that is pending but was not swapped in yet is discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidState`] if the running firmware is still on trial, as its
+    /// predecessor in the inactive slot is needed to revert to it, and [`Error::Flash`] if
+    /// accessing the flash failed.
+    pub async fn open(&mut self) -> Result<SlotWriter<'_, F>, Error> {
+        self.discard_pending().await?;
+        Ok(SlotWriter::new(self))
+    }
+
+    /// Reopens the inactive slot to continue an image of which `written` bytes were written
+    /// before, eg. by a download that was interrupted by a reboot.
+    ///
+    /// As the last page may have been written only partially, the image is continued at the start
+    /// of that page: the transport needs to continue at the [`SlotWriter::position()`] of the
+    /// returned writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ImageTooLarge`] if `written` exceeds the inactive slot, and otherwise
+    /// errors like [`Updater::open()`].
+    pub async fn resume(&mut self, written: u32) -> Result<SlotWriter<'_, F>, Error> {
+        if written > self.layout.inactive.size {
+            return Err(Error::ImageTooLarge);
+        }
+        self.discard_pending().await?;
+        let erase_size = u32::try_from(F::ERASE_SIZE).map_err(|_| Error::Layout)?;
+        Ok(SlotWriter::resumed(self, written - written % erase_size))
+    }
+
+    /// Discards an update that is pending but was not swapped in yet, before writing a new one.
+    async fn discard_pending(&mut self) -> Result<(), Error> {
+        match self.state().await? {
+            BootState::Idle => {}
+            BootState::Pending => {
+                BootState::Idle
+                    .write(&mut self.flash, &self.layout.state)
+                    .await?;
+            }
+            BootState::Testing | BootState::Revert => return Err(Error::InvalidState),
+        }
+        Ok(())
+    }
+
+    /// Confirms the running firmware, so that the bootloader keeps it.
+    ///
+    /// This needs to be called after booting an update, once the firmware has checked that it
+    /// works as intended; it does nothing if the running firmware is already confirmed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Flash`] if accessing the flash failed.
+    pub async fn mark_booted(&mut self) -> Result<(), Error> {
+        if self.state().await? == BootState::Testing {
+            BootState::Idle
+                .write(&mut self.flash, &self.layout.state)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of times the running firmware was booted while on trial.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Flash`] if reading the flash failed.
+    pub async fn boot_attempts(&mut self) -> Result<u32, Error> {
+        let records = state::Records::read(&mut self.flash, &self.layout.state).await?;
+        if records.state != BootState::Testing {
+            return Ok(0);
+        }
+        Ok(records.steps(u32::from(BOOT_ATTEMPTS)))
+    }
+
+    /// Records a boot of the running firmware, and returns the resulting boot state.
+    ///
+    /// This needs to be called early at startup. While the running firmware is on trial, this
+    /// counts the boot; once the firmware was booted [`BOOT_ATTEMPTS`] times without being
+    /// confirmed, this [requests it to be reverted](Updater::mark_revert) instead and returns
+    /// [`BootState::Revert`], after which the system should be rebooted.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Flash`] if accessing the flash failed.
+    pub async fn record_boot(&mut self) -> Result<BootState, Error> {
+        let records = state::Records::read(&mut self.flash, &self.layout.state).await?;
+        if records.state != BootState::Testing {
+            return Ok(records.state);
+        }
+        let attempts = records.steps(u32::from(BOOT_ATTEMPTS));
+        if attempts >= u32::from(BOOT_ATTEMPTS) {
+            self.mark_revert().await?;
+            return Ok(BootState::Revert);
+        }
+        records
+            .write_step(&mut self.flash, &self.layout.state, attempts)
+            .await?;
+        Ok(records.state)
+    }
+
+    /// Requests the bootloader to swap the previous firmware back in at the next boot.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidState`] if the running firmware is not on trial, as the inactive
+    /// slot may then not hold a usable firmware, and [`Error::Flash`] if accessing the flash
+    /// failed.
+    pub async fn mark_revert(&mut self) -> Result<(), Error> {
+        if self.state().await? != BootState::Testing {
+            return Err(Error::InvalidState);
+        }
+        BootState::Revert
+            .write(&mut self.flash, &self.layout.state)
+            .await
+    }
+}
+
+/// Reads `buffer.len()` bytes at `offset`, which need not be aligned to the read size.
+pub(crate) async fn read_unaligned<F: NorFlash>(
+    flash: &mut F,
+    offset: u32,
+    buffer: &mut [u8],
+) -> Result<(), Error> {
+    if F::READ_SIZE > READ_CHUNK_LEN {
+        return Err(Error::Layout);
+    }
+    let read_size = u32::try_from(F::READ_SIZE).map_err(|_| Error::Layout)?;
+    let mut chunk = [0; READ_CHUNK_LEN];
+    let mut done = 0;
+    while let Some(rest) = buffer.get_mut(done..).filter(|rest| !rest.is_empty()) {
+        let position = offset + u32::try_from(done).map_err(|_| Error::Layout)?;
+        let aligned = position - position % read_size;
+        let skip = (position - aligned) as usize;
+        let len = (skip + rest.len())
+            .next_multiple_of(F::READ_SIZE)
+            .min(READ_CHUNK_LEN - READ_CHUNK_LEN % F::READ_SIZE);
+        let chunk = chunk.get_mut(..len).ok_or(Error::Layout)?;
+        flash.read(aligned, chunk).await.map_err(|_| Error::Flash)?;
+        let available = chunk.get(skip..).ok_or(Error::Layout)?;
+        let copied = available.len().min(rest.len());
+        rest.get_mut(..copied)
+            .ok_or(Error::Layout)?
+            .copy_from_slice(available.get(..copied).ok_or(Error::Layout)?);
+        done += copied;
+    }
+    Ok(())
+}
+
+/// Errors returned by firmware updates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// Accessing the flash failed.
+    Flash,
+    /// The layout is not valid for the flash.
+    Layout,
+    /// The image does not fit into the inactive slot.
+    ImageTooLarge,
+    /// Data was not written in order.
+    OutOfOrder,
+    /// The operation is not possible in the current boot state.
+    InvalidState,
+    /// The image is not in the format expected by the bootloader.
+    InvalidImage,
+    /// The patch of a delta update is invalid or not supported.
+    InvalidPatch,
+    /// A chunk of a download is malformed.
+    InvalidChunk,
+    /// Accessing the storage failed.
+    Storage,
+    /// The image is not signed by a trusted key.
+    Unauthenticated,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Flash => write!(f, "flash access failed"),
+            Self::Layout => write!(f, "invalid slot layout"),
+            Self::ImageTooLarge => write!(f, "image too large for the slot"),
+            Self::OutOfOrder => write!(f, "image data written out of order"),
+            Self::InvalidState => write!(f, "not possible in the current boot state"),
+            Self::InvalidImage => write!(f, "invalid image"),
+            Self::InvalidPatch => write!(f, "invalid patch"),
+            Self::InvalidChunk => write!(f, "invalid download chunk"),
+            Self::Storage => write!(f, "storage access failed"),
+            Self::Unauthenticated => write!(f, "image not signed by a trusted key"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl embedded_io_async::Error for Error {
+    fn kind(&self) -> embedded_io_async::ErrorKind {
+        match self {
+            Self::ImageTooLarge => embedded_io_async::ErrorKind::OutOfMemory,
+            _ => embedded_io_async::ErrorKind::Other,
+        }
+    }
+}
@@ -0,0 +1,1034 @@
+//! Support for images booted by [MCUboot](https://docs.mcuboot.com/).
+//!
+//! With MCUboot, the active slot is MCUboot's primary slot and the inactive slot its secondary
+//! slot. Images need to be built with MCUboot's image header and TLVs (eg. by signing them with
+//! `imgtool`); they are written into the inactive slot as they are, through a [`SlotWriter`].
+//! Instead of the state partition, MCUboot keeps the boot state in a trailer at the end of each
+//! slot, so the state partition of the [`Layout`](crate::Layout) should be empty:
+//!
+//! 1. [`finalize()`] checks the header and the hash of the written image, and sets the flags that
+//!    request MCUboot to swap the image in at the next boot, either for testing or permanently.
+//! 2. After a test swap, the new image confirms itself with [`confirm()`]; otherwise, MCUboot
+//!    reverts to the previous image at the next boot. Boots are not counted as with the state
+//!    partition, so [`BOOT_ATTEMPTS`](crate::BOOT_ATTEMPTS) does not apply.
+//!
+//! The trailer is written as expected by MCUboot with its default maximum alignment of 8 bytes
+//! (`MCUBOOT_BOOT_MAX_ALIGN`), so flash drivers with a larger write size are not supported.
+//! Signatures are not checked when writing images, as MCUboot checks them before booting an
+//! image. With the `bootloader` feature, [`verify()`] checks them for bootloaders that boot
+//! MCUboot images themselves.
+
+use embedded_storage_async::nor_flash::NorFlash;
+use sha2::{Digest, Sha256};
+
+use crate::{Error, MAX_WRITE_SIZE, SlotWriter, Updater, layout::Partition, read_unaligned};
+
+/// Magic value at the start of an image header.
+const IMAGE_MAGIC: u32 = 0x96f3_b83d;
+/// Length of the fixed part of the image header.
+pub const IMAGE_HEADER_LEN: usize = 32;
+/// Magic value of the TLV area that is not covered by the hash.
+const TLV_INFO_MAGIC: u16 = 0x6907;
+/// Magic value of the TLV area that is covered by the hash.
+const TLV_PROTECTED_INFO_MAGIC: u16 = 0x6908;
+/// Length of the TLV info that precedes the TLVs.
+const TLV_INFO_LEN: u32 = 4;
+/// Length of the type and length that precede the value of a TLV.
+const TLV_HEADER_LEN: u32 = 4;
+/// Type of the TLV holding the SHA-256 hash of the image.
+const TLV_SHA256: u16 = 0x10;
+/// Type of the TLV holding the DER-encoded ECDSA signature of the hash.
+#[cfg(feature = "bootloader")]
+const TLV_ECDSA_SIG: u16 = 0x22;
+
+/// Length of the magic value at the end of the trailer.
+const BOOT_MAGIC_LEN: u32 = 16;
+/// Magic value at the end of the trailer, for a maximum alignment of 8 bytes.
+const BOOT_MAGIC: [u8; BOOT_MAGIC_LEN as usize] = [
+    0x77, 0xc2, 0x95, 0xf3, 0x60, 0xd2, 0xef, 0x7f, 0x35, 0x52, 0x50, 0x0f, 0x2c, 0xb6, 0x79, 0x80,
+];
+/// Distance between the flags of the trailer.
+const BOOT_MAX_ALIGN: u32 = 8;
+/// Value of a flag that is set.
+const BOOT_FLAG_SET: u8 = 0x01;
+/// Value of erased flash.
+const ERASED: u8 = 0xff;
+
+/// Length of the trailer written by [`finalize()`], which images must not overlap.
+pub const TRAILER_LEN: u32 = BOOT_MAGIC_LEN + 4 * BOOT_MAX_ALIGN;
+
+/// Length of the chunks in which the flash is read.
+const CHUNK_LEN: usize = 64;
+
+/// The version of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ImageVersion {
+    /// Major version.
+    pub major: u8,
+    /// Minor version.
+    pub minor: u8,
+    /// Revision.
+    pub revision: u16,
+    /// Build number.
+    pub build_num: u32,
+}
+
+impl core::fmt::Display for ImageVersion {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}+{}",
+            self.major, self.minor, self.revision, self.build_num
+        )
+    }
+}
+
+/// The header at the start of an MCUboot image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageHeader {
+    /// Address the image is loaded to, for images that are not executed in place.
+    pub load_addr: u32,
+    /// Length of the header, after which the executable image starts.
+    pub header_size: u16,
+    /// Length of the TLVs that are covered by the hash.
+    pub protected_tlv_size: u16,
+    /// Length of the executable image, excluding the header.
+    pub image_size: u32,
+    /// Image flags.
+    pub flags: u32,
+    /// Version of the image.
+    pub version: ImageVersion,
+}
+
+impl ImageHeader {
+    /// Parses the fixed part of an image header.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidImage`] if the header does not start with the image magic value.
+    pub fn parse(header: &[u8; IMAGE_HEADER_LEN]) -> Result<Self, Error> {
+        let mut reader = Reader(header);
+        if reader.u32()? != IMAGE_MAGIC {
+            return Err(Error::InvalidImage);
+        }
+        Ok(Self {
+            load_addr: reader.u32()?,
+            header_size: reader.u16()?,
+            protected_tlv_size: reader.u16()?,
+            image_size: reader.u32()?,
+            flags: reader.u32()?,
+            version: ImageVersion {
+                major: reader.u8()?,
+                minor: reader.u8()?,
+                revision: reader.u16()?,
+                build_num: reader.u32()?,
+            },
+        })
+    }
+
+    /// Returns the length of the part of the image that is covered by its hash.
+    fn hashed_len(&self) -> Option<u32> {
+        u32::from(self.header_size)
+            .checked_add(self.image_size)?
+            .checked_add(u32::from(self.protected_tlv_size))
+    }
+}
+
+/// The slots as named by MCUboot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    /// The active slot.
+    Primary,
+    /// The inactive slot.
+    Secondary,
+}
+
+impl Slot {
+    fn partition<F>(self, updater: &Updater<F>) -> Partition {
+        match self {
+            Self::Primary => updater.layout.active,
+            Self::Secondary => updater.layout.inactive,
+        }
+    }
+}
+
+/// The kind of swap requested from MCUboot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapType {
+    /// No swap.
+    None,
+    /// Swap the image in for testing; it is reverted unless confirmed.
+    Test,
+    /// Swap the image in permanently.
+    Permanent,
+    /// Swap the previous image back in.
+    Revert,
+}
+
+impl SwapType {
+    const fn from_swap_info(swap_info: u8) -> Option<Self> {
+        match swap_info & 0x0f {
+            1 => Some(Self::None),
+            2 => Some(Self::Test),
+            3 => Some(Self::Permanent),
+            4 => Some(Self::Revert),
+            _ => None,
+        }
+    }
+
+    const fn swap_info(self) -> u8 {
+        // The image number (in the upper nibble) is always 0, as there is a single image.
+        match self {
+            Self::None => 1,
+            Self::Test => 2,
+            Self::Permanent => 3,
+            Self::Revert => 4,
+        }
+    }
+}
+
+/// The boot state kept in the trailer of a slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trailer {
+    /// Whether the trailer holds the magic value; the flags are only meaningful if it does.
+    pub magic: bool,
+    /// Whether the image is confirmed.
+    pub image_ok: bool,
+    /// Whether the image was copied into the slot by MCUboot.
+    pub copy_done: bool,
+    /// The requested swap, if any was recorded.
+    pub swap_type: Option<SwapType>,
+}
+
+/// Reads the image header of the image in `slot`.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidImage`] if the slot does not hold an MCUboot image, and
+/// [`Error::Flash`] if reading the flash failed.
+pub async fn image_header<F: NorFlash>(
+    updater: &mut Updater<F>,
+    slot: Slot,
+) -> Result<ImageHeader, Error> {
+    let partition = slot.partition(updater);
+    let mut header = [0; IMAGE_HEADER_LEN];
+    read_unaligned(&mut updater.flash, partition.offset, &mut header).await?;
+    ImageHeader::parse(&header)
+}
+
+/// Reads the trailer of `slot`.
+///
+/// # Errors
+///
+/// Returns [`Error::Flash`] if reading the flash failed.
+pub async fn trailer<F: NorFlash>(updater: &mut Updater<F>, slot: Slot) -> Result<Trailer, Error> {
+    let partition = slot.partition(updater);
+    let magic_offset = magic_offset(partition)?;
+    let mut magic = [0; BOOT_MAGIC.len()];
+    read_unaligned(&mut updater.flash, magic_offset, &mut magic).await?;
+
+    let mut flags = [[0]; 3];
+    for (distance, flag) in (1..).zip(flags.iter_mut()) {
+        read_unaligned(
+            &mut updater.flash,
+            magic_offset - distance * BOOT_MAX_ALIGN,
+            flag,
+        )
+        .await?;
+    }
+    let [[image_ok], [copy_done], [swap_info]] = flags;
+
+    Ok(Trailer {
+        magic: magic == BOOT_MAGIC,
+        image_ok: image_ok == BOOT_FLAG_SET,
+        copy_done: copy_done == BOOT_FLAG_SET,
+        swap_type: SwapType::from_swap_info(swap_info),
+    })
+}
+
+/// Completes the image written by `writer`, and requests MCUboot to swap it in at the next boot.
+///
+/// The header and the SHA-256 hash of the image are checked first. With `permanent`, the image is
+/// marked as confirmed right away; otherwise, it needs to [`confirm()`] itself after booting.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidImage`] if the image is not a complete MCUboot image or its hash does
+/// not match, [`Error::ImageTooLarge`] if it overlaps the trailer, [`Error::Layout`] if the write
+/// size of the flash is not supported, and [`Error::Flash`] if accessing the flash failed.
+pub async fn finalize<F: NorFlash>(
+    writer: SlotWriter<'_, F>,
+    permanent: bool,
+) -> Result<(), Error> {
+    if F::WRITE_SIZE > BOOT_MAX_ALIGN as usize {
+        return Err(Error::Layout);
+    }
+    let completed = writer.complete().await?;
+    let updater = completed.updater;
+    let slot = updater.layout.inactive;
+
+    let image = check_image(updater, Slot::Secondary, completed.len).await?;
+    if image.end > slot.size.saturating_sub(TRAILER_LEN) {
+        return Err(Error::ImageTooLarge);
+    }
+
+    // MCUboot expects everything after the image to be erased.
+    if completed.erased < slot.size {
+        updater
+            .flash
+            .erase(slot.offset + completed.erased, slot.end())
+            .await
+            .map_err(|_| Error::Flash)?;
+    }
+
+    let magic_offset = magic_offset(slot)?;
+    let swap_type = if permanent {
+        SwapType::Permanent
+    } else {
+        SwapType::Test
+    };
+    write_flag(
+        &mut updater.flash,
+        magic_offset - 3 * BOOT_MAX_ALIGN,
+        swap_type.swap_info(),
+    )
+    .await?;
+    if permanent {
+        write_flag(
+            &mut updater.flash,
+            magic_offset - BOOT_MAX_ALIGN,
+            BOOT_FLAG_SET,
+        )
+        .await?;
+    }
+    // The magic value is written last, so that MCUboot ignores an incomplete trailer.
+    updater
+        .flash
+        .write(magic_offset, &BOOT_MAGIC)
+        .await
+        .map_err(|_| Error::Flash)
+}
+
+/// Confirms the running image, so that MCUboot does not revert it at the next boot.
+///
+/// This does nothing if the image is already confirmed, or if it was not swapped in for testing.
+///
+/// # Errors
+///
+/// Returns [`Error::Layout`] if the write size of the flash is not supported, and
+/// [`Error::Flash`] if accessing the flash failed.
+pub async fn confirm<F: NorFlash>(updater: &mut Updater<F>) -> Result<(), Error> {
+    if F::WRITE_SIZE > BOOT_MAX_ALIGN as usize {
+        return Err(Error::Layout);
+    }
+    let trailer = trailer(updater, Slot::Primary).await?;
+    if !trailer.magic || trailer.image_ok {
+        return Ok(());
+    }
+    let magic_offset = magic_offset(updater.layout.active)?;
+    write_flag(
+        &mut updater.flash,
+        magic_offset - BOOT_MAX_ALIGN,
+        BOOT_FLAG_SET,
+    )
+    .await
+}
+
+/// Checks the image in `slot` and its ECDSA P-256 signature by one of `keys`, as MCUboot does
+/// before booting it.
+///
+/// Images are signed with `imgtool sign --key <key.pem>` using an `ecdsa-p256` key.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidImage`] if the slot does not hold a complete MCUboot image or its hash
+/// does not match, [`Error::Unauthenticated`] if it is not signed by any of `keys`, and
+/// [`Error::Flash`] if reading the flash failed.
+#[cfg(feature = "bootloader")]
+pub async fn verify<F: NorFlash>(
+    updater: &mut Updater<F>,
+    slot: Slot,
+    keys: &[p256::ecdsa::VerifyingKey],
+) -> Result<ImageHeader, Error> {
+    use p256::ecdsa::signature::hazmat::PrehashVerifier;
+
+    let partition = slot.partition(updater);
+    let image = check_image(updater, slot, partition.size).await?;
+    // A DER-encoded signature takes at most 72 bytes.
+    let mut signature = [0; 72];
+    let len = find_tlv(
+        updater,
+        partition,
+        image.tlv_start,
+        image.end,
+        TLV_ECDSA_SIG,
+        &mut signature,
+    )
+    .await?
+    .ok_or(Error::Unauthenticated)?;
+    let signature = parse_der_signature(signature.get(..len).ok_or(Error::InvalidImage)?)?;
+    if keys
+        .iter()
+        .any(|key| key.verify_prehash(&image.hash, &signature).is_ok())
+    {
+        Ok(image.header)
+    } else {
+        Err(Error::Unauthenticated)
+    }
+}
+
+/// An image whose hash was checked.
+struct CheckedImage {
+    #[cfg_attr(not(feature = "bootloader"), expect(dead_code))]
+    header: ImageHeader,
+    /// Offset of the TLV area that is not covered by the hash, relative to the slot.
+    #[cfg_attr(not(feature = "bootloader"), expect(dead_code))]
+    tlv_start: u32,
+    /// Offset of the end of the image, relative to the slot.
+    end: u32,
+    /// The hash of the image.
+    #[cfg_attr(not(feature = "bootloader"), expect(dead_code))]
+    hash: [u8; 32],
+}
+
+/// Checks the header, the TLV areas and the hash of the image in `slot`, which takes up at most
+/// `len` bytes.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidImage`] if the slot does not hold a complete MCUboot image or its hash
+/// does not match, and [`Error::Flash`] if reading the flash failed.
+async fn check_image<F: NorFlash>(
+    updater: &mut Updater<F>,
+    slot: Slot,
+    len: u32,
+) -> Result<CheckedImage, Error> {
+    let partition = slot.partition(updater);
+    let header = image_header(updater, slot).await?;
+    let tlv_start = header
+        .hashed_len()
+        .filter(|start| start.saturating_add(TLV_INFO_LEN) <= len)
+        .ok_or(Error::InvalidImage)?;
+    let mut tlv_info = [0; TLV_INFO_LEN as usize];
+    read_unaligned(
+        &mut updater.flash,
+        partition.offset + tlv_start,
+        &mut tlv_info,
+    )
+    .await?;
+    let mut reader = Reader(&tlv_info);
+    let (magic, tlv_len) = (reader.u16()?, reader.u16()?);
+    let end = tlv_start + u32::from(tlv_len);
+    if magic != TLV_INFO_MAGIC || end > len {
+        return Err(Error::InvalidImage);
+    }
+    if header.protected_tlv_size > 0 {
+        let mut protected_info = [0; 2];
+        read_unaligned(
+            &mut updater.flash,
+            partition.offset + tlv_start - u32::from(header.protected_tlv_size),
+            &mut protected_info,
+        )
+        .await?;
+        if u16::from_le_bytes(protected_info) != TLV_PROTECTED_INFO_MAGIC {
+            return Err(Error::InvalidImage);
+        }
+    }
+
+    let mut hash = [0; 32];
+    let hash_len = find_tlv(updater, partition, tlv_start, end, TLV_SHA256, &mut hash)
+        .await?
+        .ok_or(Error::InvalidImage)?;
+    if hash_len != hash.len() {
+        return Err(Error::InvalidImage);
+    }
+    let mut digest = Sha256::new();
+    let mut chunk = [0; CHUNK_LEN];
+    let mut offset = 0;
+    while offset < tlv_start {
+        let len = CHUNK_LEN.min((tlv_start - offset) as usize);
+        let chunk = chunk.get_mut(..len).ok_or(Error::Layout)?;
+        read_unaligned(&mut updater.flash, partition.offset + offset, chunk).await?;
+        digest.update(&*chunk);
+        offset += u32::try_from(len).map_err(|_| Error::Layout)?;
+    }
+    if *digest.finalize() != hash {
+        return Err(Error::InvalidImage);
+    }
+    Ok(CheckedImage {
+        header,
+        tlv_start,
+        end,
+        hash,
+    })
+}
+
+/// Finds the first TLV of type `kind` in the TLV area between `tlv_start` and `tlv_end` of
+/// `slot`, reads its value into `value`, and returns its length.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidImage`] if the TLV area is malformed or the value does not fit into
+/// `value`, and [`Error::Flash`] if reading the flash failed.
+async fn find_tlv<F: NorFlash>(
+    updater: &mut Updater<F>,
+    slot: Partition,
+    tlv_start: u32,
+    tlv_end: u32,
+    kind: u16,
+    value: &mut [u8],
+) -> Result<Option<usize>, Error> {
+    let mut offset = tlv_start + TLV_INFO_LEN;
+    while offset + TLV_HEADER_LEN <= tlv_end {
+        let mut tlv = [0; TLV_HEADER_LEN as usize];
+        read_unaligned(&mut updater.flash, slot.offset + offset, &mut tlv).await?;
+        let mut reader = Reader(&tlv);
+        let (tlv_kind, len) = (reader.u16()?, reader.u16()?);
+        offset += TLV_HEADER_LEN;
+        if offset + u32::from(len) > tlv_end {
+            return Err(Error::InvalidImage);
+        }
+        if tlv_kind == kind {
+            let value = value
+                .get_mut(..usize::from(len))
+                .ok_or(Error::InvalidImage)?;
+            read_unaligned(&mut updater.flash, slot.offset + offset, value).await?;
+            return Ok(Some(value.len()));
+        }
+        offset += u32::from(len);
+    }
+    Ok(None)
+}
+
+/// Parses a DER-encoded ECDSA signature, which is a sequence of the integers `r` and `s`.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidImage`] if the signature is malformed.
+#[cfg(feature = "bootloader")]
+fn parse_der_signature(der: &[u8]) -> Result<p256::ecdsa::Signature, Error> {
+    /// Splits a DER element with the tag `tag` and a short length off `der`.
+    fn element(tag: u8, der: &[u8]) -> Result<(&[u8], &[u8]), Error> {
+        let [found, len, rest @ ..] = der else {
+            return Err(Error::InvalidImage);
+        };
+        if *found != tag || *len >= 0x80 {
+            return Err(Error::InvalidImage);
+        }
+        rest.split_at_checked(usize::from(*len))
+            .ok_or(Error::InvalidImage)
+    }
+
+    let (sequence, rest) = element(0x30, der)?;
+    if !rest.is_empty() {
+        return Err(Error::InvalidImage);
+    }
+    let mut bytes = [0; 64];
+    let mut integers = sequence;
+    for scalar in bytes.chunks_exact_mut(32) {
+        let (integer, rest) = element(0x02, integers)?;
+        integers = rest;
+        // Integers are encoded with a leading zero byte if their most significant bit is set.
+        let start = integer
+            .iter()
+            .position(|&byte| byte != 0)
+            .unwrap_or(integer.len());
+        let integer = integer.get(start..).ok_or(Error::InvalidImage)?;
+        let padding = scalar
+            .len()
+            .checked_sub(integer.len())
+            .ok_or(Error::InvalidImage)?;
+        scalar
+            .get_mut(padding..)
+            .ok_or(Error::InvalidImage)?
+            .copy_from_slice(integer);
+    }
+    if !integers.is_empty() {
+        return Err(Error::InvalidImage);
+    }
+    p256::ecdsa::Signature::from_slice(&bytes).map_err(|_| Error::InvalidImage)
+}
+
+/// Returns the offset of the trailer's magic value at the end of `slot`.
+///
+/// # Errors
+///
+/// Returns [`Error::Layout`] if the slot is too small to hold a trailer.
+fn magic_offset(slot: Partition) -> Result<u32, Error> {
+    if slot.size < TRAILER_LEN {
+        return Err(Error::Layout);
+    }
+    Ok(slot.end() - BOOT_MAGIC_LEN)
+}
+
+/// Writes a single-byte flag of the trailer, padded to the write size.
+async fn write_flag<F: NorFlash>(flash: &mut F, offset: u32, value: u8) -> Result<(), Error> {
+    let mut record = [ERASED; MAX_WRITE_SIZE];
+    let record = record.get_mut(..F::WRITE_SIZE).ok_or(Error::Layout)?;
+    if let Some(first) = record.first_mut() {
+        *first = value;
+    }
+    flash.write(offset, record).await.map_err(|_| Error::Flash)
+}
+
+/// Reads little-endian integers.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let (value, rest) = self.0.split_first_chunk().ok_or(Error::InvalidImage)?;
+        self.0 = rest;
+        Ok(*value)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(u8::from_le_bytes(self.array()?))
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::indexing_slicing, clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use embassy_futures::block_on;
+    use embedded_storage_async::nor_flash::ReadNorFlash;
+
+    use super::*;
+    use crate::{Layout, faulty_flash::FaultyFlash};
+
+    const SLOT_SIZE: u32 = 8192;
+    const HEADER_SIZE: u16 = 0x200;
+    /// Type of a protected TLV, holding the security counter.
+    const TLV_SEC_CNT: u16 = 0x50;
+
+    fn updater() -> Updater<FaultyFlash> {
+        let layout = Layout::new(
+            Partition::new(0, SLOT_SIZE),
+            Partition::new(SLOT_SIZE, SLOT_SIZE),
+            Partition::new(2 * SLOT_SIZE, 0),
+        );
+        Updater::new(FaultyFlash::new(16), layout).unwrap()
+    }
+
+    fn tlv(tlvs: &mut Vec<u8>, kind: u16, value: &[u8]) {
+        tlvs.extend(kind.to_le_bytes());
+        tlvs.extend(u16::try_from(value.len()).unwrap().to_le_bytes());
+        tlvs.extend(value);
+    }
+
+    /// Builds an image of version 1.2.3+4 as `imgtool sign --header-size 0x200 --pad-header`
+    /// does, with protected TLVs if `protected` (as added by `--security-counter`).
+    ///
+    /// `sign` returns the DER-encoded signature of the image hash, if the image is signed.
+    fn image(
+        image_size: u32,
+        protected: bool,
+        sign: impl FnOnce(&[u8; 32]) -> Option<Vec<u8>>,
+    ) -> Vec<u8> {
+        let mut protected_tlvs = Vec::new();
+        if protected {
+            let mut tlvs = Vec::new();
+            tlv(&mut tlvs, TLV_SEC_CNT, &7u32.to_le_bytes());
+            protected_tlvs.extend(TLV_PROTECTED_INFO_MAGIC.to_le_bytes());
+            protected_tlvs.extend(u16::try_from(tlvs.len() + 4).unwrap().to_le_bytes());
+            protected_tlvs.extend(tlvs);
+        }
+
+        let mut image = Vec::new();
+        image.extend(IMAGE_MAGIC.to_le_bytes());
+        image.extend(0u32.to_le_bytes());
+        image.extend(HEADER_SIZE.to_le_bytes());
+        image.extend(u16::try_from(protected_tlvs.len()).unwrap().to_le_bytes());
+        image.extend(image_size.to_le_bytes());
+        image.extend(0u32.to_le_bytes());
+        image.extend([1, 2]);
+        image.extend(3u16.to_le_bytes());
+        image.extend(4u32.to_le_bytes());
+        image.resize(usize::from(HEADER_SIZE), 0);
+        image.extend((0..image_size).map(|i| u8::try_from(i % 251).unwrap()));
+        image.extend(protected_tlvs);
+
+        let hash: [u8; 32] = Sha256::digest(&image).into();
+        let mut tlvs = Vec::new();
+        tlv(&mut tlvs, TLV_SHA256, &hash);
+        if let Some(signature) = sign(&hash) {
+            tlv(&mut tlvs, 0x22, &signature);
+        }
+        image.extend(TLV_INFO_MAGIC.to_le_bytes());
+        image.extend(u16::try_from(tlvs.len() + 4).unwrap().to_le_bytes());
+        image.extend(tlvs);
+        image
+    }
+
+    fn unsigned(image_size: u32, protected: bool) -> Vec<u8> {
+        image(image_size, protected, |_| None)
+    }
+
+    /// Writes `image` into the inactive slot, and finalizes it.
+    async fn install(
+        updater: &mut Updater<FaultyFlash>,
+        image: &[u8],
+        permanent: bool,
+    ) -> Result<(), Error> {
+        let mut writer = updater.open().await?;
+        writer.write(image).await?;
+        finalize(writer, permanent).await
+    }
+
+    async fn read(updater: &mut Updater<FaultyFlash>, offset: u32, len: usize) -> Vec<u8> {
+        let mut data = vec![0; len];
+        updater.flash.read(offset, &mut data).await.unwrap();
+        data
+    }
+
+    #[test]
+    fn header_is_parsed() {
+        let image = unsigned(100, false);
+        let header = ImageHeader::parse(image.first_chunk().unwrap()).unwrap();
+        assert_eq!(
+            header,
+            ImageHeader {
+                load_addr: 0,
+                header_size: HEADER_SIZE,
+                protected_tlv_size: 0,
+                image_size: 100,
+                flags: 0,
+                version: ImageVersion {
+                    major: 1,
+                    minor: 2,
+                    revision: 3,
+                    build_num: 4,
+                },
+            }
+        );
+        assert_eq!(header.version.to_string(), "1.2.3+4");
+        assert_eq!(header.hashed_len(), Some(0x200 + 100));
+
+        let mut other = *image.first_chunk().unwrap();
+        other[0] ^= 1;
+        assert_eq!(ImageHeader::parse(&other), Err(Error::InvalidImage));
+    }
+
+    #[test]
+    fn test_swap_is_requested() {
+        block_on(async {
+            let mut updater = updater();
+            let image = unsigned(3001, true);
+            install(&mut updater, &image, false).await.unwrap();
+
+            assert_eq!(
+                image_header(&mut updater, Slot::Secondary)
+                    .await
+                    .unwrap()
+                    .protected_tlv_size,
+                12
+            );
+            assert_eq!(
+                trailer(&mut updater, Slot::Secondary).await,
+                Ok(Trailer {
+                    magic: true,
+                    image_ok: false,
+                    copy_done: false,
+                    swap_type: Some(SwapType::Test),
+                })
+            );
+            // The trailer is laid out as MCUboot expects it at the end of the slot: the swap
+            // info, the copy done and the image ok flags 8 bytes apart, then the magic value.
+            let trailer = read(&mut updater, 2 * SLOT_SIZE - TRAILER_LEN, 48).await;
+            assert_eq!(trailer[..8], [0xff; 8]);
+            assert_eq!(
+                trailer[8..16],
+                [2, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+            );
+            assert_eq!(trailer[16..32], [0xff; 16]);
+            assert_eq!(trailer[32..], BOOT_MAGIC);
+            assert_eq!(read(&mut updater, SLOT_SIZE, image.len()).await, image);
+        });
+    }
+
+    #[test]
+    fn permanent_swap_is_requested() {
+        block_on(async {
+            let mut updater = updater();
+            install(&mut updater, &unsigned(3001, false), true)
+                .await
+                .unwrap();
+            assert_eq!(
+                trailer(&mut updater, Slot::Secondary).await,
+                Ok(Trailer {
+                    magic: true,
+                    image_ok: true,
+                    copy_done: false,
+                    swap_type: Some(SwapType::Permanent),
+                })
+            );
+            let trailer = read(&mut updater, 2 * SLOT_SIZE - TRAILER_LEN, 48).await;
+            assert_eq!(trailer[8], 3);
+            assert_eq!(trailer[24], BOOT_FLAG_SET);
+        });
+    }
+
+    #[test]
+    fn previous_trailer_is_erased() {
+        block_on(async {
+            let mut updater = updater();
+            install(&mut updater, &unsigned(SLOT_SIZE - 600, false), true)
+                .await
+                .unwrap();
+            // A smaller image does not reach the pages of the previous trailer.
+            install(&mut updater, &unsigned(100, false), false)
+                .await
+                .unwrap();
+            let trailer = trailer(&mut updater, Slot::Secondary).await.unwrap();
+            assert!(trailer.magic);
+            assert!(!trailer.image_ok);
+            assert_eq!(trailer.swap_type, Some(SwapType::Test));
+        });
+    }
+
+    #[test]
+    fn invalid_images_are_rejected() {
+        block_on(async {
+            let mut updater = updater();
+
+            let mut corrupted = unsigned(3001, false);
+            corrupted[0x300] ^= 1;
+            assert_eq!(
+                install(&mut updater, &corrupted, false).await,
+                Err(Error::InvalidImage)
+            );
+
+            let mut protected_magic = unsigned(3001, true);
+            protected_magic[0x200 + 3001] ^= 1;
+            assert_eq!(
+                install(&mut updater, &protected_magic, false).await,
+                Err(Error::InvalidImage)
+            );
+
+            let truncated = unsigned(3001, false);
+            assert_eq!(
+                install(&mut updater, &truncated[..truncated.len() - 1], false).await,
+                Err(Error::InvalidImage)
+            );
+
+            assert_eq!(
+                install(&mut updater, &[0; 64], false).await,
+                Err(Error::InvalidImage)
+            );
+
+            // The image itself fits into the slot, but overlaps the trailer.
+            let overlapping = unsigned(SLOT_SIZE - 0x200 - 40 - TRAILER_LEN + 4, false);
+            assert!(overlapping.len() <= SLOT_SIZE as usize);
+            assert_eq!(
+                install(&mut updater, &overlapping, false).await,
+                Err(Error::ImageTooLarge)
+            );
+
+            assert!(!trailer(&mut updater, Slot::Secondary).await.unwrap().magic);
+        });
+    }
+
+    #[test]
+    fn test_swapped_image_is_confirmed() {
+        block_on(async {
+            let mut updater = updater();
+            // Nothing is confirmed without a trailer.
+            confirm(&mut updater).await.unwrap();
+            assert!(!trailer(&mut updater, Slot::Primary).await.unwrap().image_ok);
+
+            // The trailer of a test swap, as left by MCUboot.
+            let magic_offset = SLOT_SIZE - BOOT_MAGIC_LEN;
+            write_flag(&mut updater.flash, magic_offset - 3 * BOOT_MAX_ALIGN, 2)
+                .await
+                .unwrap();
+            write_flag(&mut updater.flash, magic_offset - 2 * BOOT_MAX_ALIGN, 1)
+                .await
+                .unwrap();
+            updater
+                .flash
+                .write(magic_offset, &BOOT_MAGIC)
+                .await
+                .unwrap();
+            assert_eq!(
+                trailer(&mut updater, Slot::Primary).await,
+                Ok(Trailer {
+                    magic: true,
+                    image_ok: false,
+                    copy_done: true,
+                    swap_type: Some(SwapType::Test),
+                })
+            );
+
+            confirm(&mut updater).await.unwrap();
+            assert!(trailer(&mut updater, Slot::Primary).await.unwrap().image_ok);
+            assert_eq!(
+                read(&mut updater, magic_offset - BOOT_MAX_ALIGN, 8).await,
+                [1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+            );
+            // Confirming again does not write the flag again.
+            confirm(&mut updater).await.unwrap();
+        });
+    }
+
+    #[test]
+    fn state_partition_is_not_used() {
+        block_on(async {
+            let mut updater = updater();
+            let mut writer = updater.open().await.unwrap();
+            writer.write(&unsigned(100, false)).await.unwrap();
+            assert_eq!(writer.finalize().await, Err(Error::Layout));
+        });
+    }
+
+    #[cfg(feature = "bootloader")]
+    mod signatures {
+        use p256::ecdsa::{Signature, SigningKey, signature::hazmat::PrehashSigner};
+
+        use super::*;
+
+        /// Encodes a DER integer, with a leading zero byte if its most significant bit is set.
+        fn der_integer(der: &mut Vec<u8>, bytes: &[u8]) {
+            let start = bytes
+                .iter()
+                .position(|&byte| byte != 0)
+                .unwrap_or(bytes.len() - 1);
+            let bytes = &bytes[start..];
+            let padded = bytes[0] & 0x80 != 0;
+            der.push(0x02);
+            der.push(u8::try_from(bytes.len() + usize::from(padded)).unwrap());
+            if padded {
+                der.push(0);
+            }
+            der.extend(bytes);
+        }
+
+        fn der_signature(signature: &Signature) -> Vec<u8> {
+            let mut integers = Vec::new();
+            der_integer(&mut integers, &signature.r().to_bytes());
+            der_integer(&mut integers, &signature.s().to_bytes());
+            let mut der = vec![0x30, u8::try_from(integers.len()).unwrap()];
+            der.extend(integers);
+            der
+        }
+
+        fn key() -> SigningKey {
+            SigningKey::from_slice(&[0x2a; 32]).unwrap()
+        }
+
+        fn signed(key: &SigningKey, image_size: u32) -> Vec<u8> {
+            image(image_size, true, |hash| {
+                let signature: Signature = key.sign_prehash(hash).unwrap();
+                Some(der_signature(&signature))
+            })
+        }
+
+        #[test]
+        fn der_signatures_are_parsed() {
+            let mut r = [0x80; 32];
+            let mut s = [0x01; 32];
+            s[..3].fill(0);
+            let signature = Signature::from_scalars(r, s).unwrap();
+            let der = der_signature(&signature);
+            // `r` is padded with a zero byte, `s` is shortened.
+            assert_eq!(der[..4], [0x30, 35 + 31, 0x02, 33]);
+            assert_eq!(parse_der_signature(&der), Ok(signature));
+
+            r[0] = 0x7f;
+            let signature = Signature::from_scalars(r, s).unwrap();
+            assert_eq!(
+                parse_der_signature(&der_signature(&signature)),
+                Ok(signature)
+            );
+        }
+
+        #[test]
+        fn malformed_der_signatures_are_rejected() {
+            let signature = Signature::from_scalars([0x11; 32], [0x22; 32]).unwrap();
+            let der = der_signature(&signature);
+            assert_eq!(parse_der_signature(&der), Ok(signature));
+
+            let mut malformed = vec![
+                // Truncated.
+                der[..der.len() - 1].to_vec(),
+                // Trailing bytes.
+                [der.as_slice(), &[0]].concat(),
+                // Not a sequence.
+                [&[0x31], &der[1..]].concat(),
+                // Long form length.
+                [&[0x30, 0x81], &der[1..]].concat(),
+                // A single integer.
+                [&[0x30, 34], &der[2..36]].concat(),
+                // A third integer.
+                [&[0x30, 71], &der[2..], &[0x02, 1, 1]].concat(),
+                // Empty.
+                Vec::new(),
+            ];
+            // An integer of 33 significant bytes.
+            let mut long = vec![0x30, 69, 0x02, 33];
+            long.extend([0x11; 33]);
+            long.extend(&der[36..]);
+            malformed.push(long);
+            // A zero scalar.
+            let mut zero = vec![0x30, 37, 0x02, 1, 0];
+            zero.extend(&der[36..]);
+            malformed.push(zero);
+
+            for der in malformed {
+                assert_eq!(
+                    parse_der_signature(&der),
+                    Err(Error::InvalidImage),
+                    "{der:02x?}"
+                );
+            }
+        }
+
+        #[test]
+        fn signed_image_is_verified() {
+            block_on(async {
+                let mut updater = updater();
+                let image = signed(&key(), 3001);
+                install(&mut updater, &image, false).await.unwrap();
+
+                let other = SigningKey::from_slice(&[0x2b; 32]).unwrap();
+                let header = verify(
+                    &mut updater,
+                    Slot::Secondary,
+                    &[*other.verifying_key(), *key().verifying_key()],
+                )
+                .await
+                .unwrap();
+                assert_eq!(header.image_size, 3001);
+                assert_eq!(
+                    verify(&mut updater, Slot::Secondary, &[*other.verifying_key()]).await,
+                    Err(Error::Unauthenticated)
+                );
+            });
+        }
+
+        #[test]
+        fn unsigned_image_is_not_verified() {
+            block_on(async {
+                let mut updater = updater();
+                install(&mut updater, &unsigned(3001, false), false)
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    verify(&mut updater, Slot::Secondary, &[*key().verifying_key()]).await,
+                    Err(Error::Unauthenticated)
+                );
+                // The primary slot does not hold an image.
+                assert_eq!(
+                    verify(&mut updater, Slot::Primary, &[*key().verifying_key()]).await,
+                    Err(Error::InvalidImage)
+                );
+            });
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! The boot state, through which the firmware and the bootloader coordinate swapping slots.
+//!
+//! The state partition holds a sequence of records, each one write size long (and at least as
+//! long as a magic value). State records hold a magic value that identifies the state; the last
+//! state record determines the current state, and an erased partition stands for
+//! [`BootState::Idle`]. Every boot of a firmware [on trial](BootState::Testing), and every step of
+//! swapping the slots, is recorded after the state record by programming one more zeroed record,
+//! so that they can be counted without erasing.
+//!
+//! Changing the state to [`BootState::Idle`] or [`BootState::Pending`] erases the partition and
+//! writes the new state record at its start: if this is interrupted, the state falls back to
+//! `Idle`, which boots the active slot as it is. Changing it to [`BootState::Testing`] or
+//! [`BootState::Revert`] instead appends the new state record after the existing records, as
+//! falling back to `Idle` would keep a firmware that was never confirmed: if this is interrupted,
+//! the previous state is kept.
+
+use embedded_storage_async::nor_flash::NorFlash;
+
+use crate::{Error, MAX_WRITE_SIZE, layout::Partition};
+
+const MAGIC_PENDING: u32 = 0x4152_5550;
+const MAGIC_TESTING: u32 = 0x4152_5554;
+const MAGIC_REVERT: u32 = 0x4152_5552;
+
+/// Length of the magic value at the start of the state partition.
+const MAGIC_LEN: usize = 4;
+
+/// The state of the firmware slots, as seen by the bootloader at the next boot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootState {
+    /// The running firmware is confirmed, and no update is pending.
+    Idle,
+    /// An update was written to the inactive slot, and will be swapped in at the next boot.
+    Pending,
+    /// The running firmware was just swapped in and is on trial: unless it is confirmed with
+    /// [`Updater::mark_booted()`](crate::Updater::mark_booted) within
+    /// [`BOOT_ATTEMPTS`](crate::BOOT_ATTEMPTS) boots, the previous firmware is swapped back in
+    /// (see [`Updater::record_boot()`](crate::Updater::record_boot)).
+    Testing,
+    /// The previous firmware will be swapped back in at the next boot.
+    Revert,
+}
+
+impl BootState {
+    const fn magic(self) -> Option<u32> {
+        match self {
+            Self::Idle => None,
+            Self::Pending => Some(MAGIC_PENDING),
+            Self::Testing => Some(MAGIC_TESTING),
+            Self::Revert => Some(MAGIC_REVERT),
+        }
+    }
+
+    /// Reads the state from the state `partition`.
+    ///
+    /// Unknown contents and an empty partition are read as [`BootState::Idle`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Flash`] if reading the flash failed.
+    pub async fn read<F: NorFlash>(flash: &mut F, partition: &Partition) -> Result<Self, Error> {
+        Ok(Records::read(flash, partition).await?.state)
+    }
+
+    /// Writes the state into the state `partition`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Layout`] if the partition is empty, or if it is too full to append the
+    /// state to, and [`Error::Flash`] if accessing the flash failed.
+    pub async fn write<F: NorFlash>(
+        self,
+        flash: &mut F,
+        partition: &Partition,
+    ) -> Result<(), Error> {
+        if partition.size == 0 {
+            return Err(Error::Layout);
+        }
+        let index = match self {
+            Self::Idle | Self::Pending => {
+                flash
+                    .erase(partition.offset, partition.end())
+                    .await
+                    .map_err(|_| Error::Flash)?;
+                0
+            }
+            // The previous state stays valid until the new state record is complete.
+            Self::Testing | Self::Revert => Records::read(flash, partition).await?.end(),
+        };
+        let Some(magic) = self.magic() else {
+            return Ok(());
+        };
+
+        let mut record = [0xff; MAX_WRITE_SIZE];
+        let record = record.get_mut(..record_len::<F>()).ok_or(Error::Layout)?;
+        let (magic_bytes, _) = record
+            .split_first_chunk_mut::<MAGIC_LEN>()
+            .ok_or(Error::Layout)?;
+        *magic_bytes = magic.to_le_bytes();
+        let offset = record_offset::<F>(*partition, index).ok_or(Error::Layout)?;
+        flash.write(offset, record).await.map_err(|_| Error::Flash)
+    }
+
+    fn from_magic(magic: u32) -> Option<Self> {
+        match magic {
+            MAGIC_PENDING => Some(Self::Pending),
+            MAGIC_TESTING => Some(Self::Testing),
+            MAGIC_REVERT => Some(Self::Revert),
+            _ => None,
+        }
+    }
+}
+
+/// The records found in a state partition.
+pub(crate) struct Records {
+    /// The state of the last state record.
+    pub(crate) state: BootState,
+    /// Index of the record after the last state record, or 0 if there is none.
+    start: u32,
+    /// Index of the first erased record.
+    end: u32,
+}
+
+impl Records {
+    /// Reads the records of the state `partition`, up to the first erased record.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Flash`] if reading the flash failed.
+    pub(crate) async fn read<F: NorFlash>(
+        flash: &mut F,
+        partition: &Partition,
+    ) -> Result<Self, Error> {
+        let mut records = Self {
+            state: BootState::Idle,
+            start: 0,
+            end: 0,
+        };
+        let mut record = [0; MAX_WRITE_SIZE];
+        let record = record.get_mut(..record_len::<F>()).ok_or(Error::Layout)?;
+        while let Some(offset) = record_offset::<F>(*partition, records.end) {
+            flash.read(offset, record).await.map_err(|_| Error::Flash)?;
+            if record.iter().all(|&byte| byte == 0xff) {
+                break;
+            }
+            records.end += 1;
+            // Records that were only partially written are counted as steps.
+            let (magic, _) = record.split_first_chunk().ok_or(Error::Layout)?;
+            if let Some(state) = BootState::from_magic(u32::from_le_bytes(*magic)) {
+                records.state = state;
+                records.start = records.end;
+            }
+        }
+        Ok(records)
+    }
+
+    /// Returns the index of the first erased record.
+    pub(crate) fn end(&self) -> u32 {
+        self.end
+    }
+
+    /// Returns the number of boot attempts or swap steps recorded after the last state record,
+    /// counting at most `max`.
+    pub(crate) fn steps(&self, max: u32) -> u32 {
+        (self.end - self.start).min(max)
+    }
+
+    /// Records one more boot attempt or swap step after the `steps` recorded so far.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Layout`] if the record does not fit into the `partition`, and
+    /// [`Error::Flash`] if writing the flash failed.
+    pub(crate) async fn write_step<F: NorFlash>(
+        &self,
+        flash: &mut F,
+        partition: &Partition,
+        steps: u32,
+    ) -> Result<(), Error> {
+        let offset = self
+            .start
+            .checked_add(steps)
+            .and_then(|index| record_offset::<F>(*partition, index))
+            .ok_or(Error::Layout)?;
+        let record = [0; MAX_WRITE_SIZE];
+        flash
+            .write(
+                offset,
+                record.get(..record_len::<F>()).ok_or(Error::Layout)?,
+            )
+            .await
+            .map_err(|_| Error::Flash)
+    }
+}
+
+/// Returns the offset of the record `index`, if it fits into the `partition`.
+pub(crate) fn record_offset<F: NorFlash>(partition: Partition, index: u32) -> Option<u32> {
+    let record_len = u32::try_from(record_len::<F>()).ok()?;
+    let offset = index
+        .checked_mul(record_len)?
+        .checked_add(partition.offset)?;
+    (offset.checked_add(record_len)? <= partition.end()).then_some(offset)
+}
+
+/// Returns the length of the record holding the magic value, which is a multiple of the write
+/// size.
+fn record_len<F: NorFlash>() -> usize {
+    MAGIC_LEN.next_multiple_of(F::WRITE_SIZE)
+}
@@ -0,0 +1,1024 @@
+//! Processing of [SUIT](https://datatracker.ietf.org/doc/draft-ietf-suit-manifest/) manifests.
+//!
+//! A SUIT envelope carries a signed manifest that describes an update: which devices it applies
+//! to, where the image is fetched from, and which digest the image needs to have. Updates
+//! described that way are processed in two steps:
+//!
+//! 1. [`Envelope::authenticate()`] checks the manifest's signature against a set of trust anchors,
+//!    and only then gives access to the [`Manifest`].
+//! 2. [`process()`] checks the manifest against the [`Device`], runs its command sequences to
+//!    fetch the image into the inactive slot, verifies the image's digest, and finalizes the
+//!    update.
+//!
+//! ```ignore
+//! let manifest = suit::Envelope::decode(&envelope)?.authenticate(&[trusted_key])?;
+//! let mut updater = ariel_os::update::updater()?;
+//! suit::process(&manifest, &device, &mut updater, async |uri, writer| {
+//!     // Fetch the image at `uri` into `writer`, eg. through CoAP block-wise transfers.
+//!     Ok(())
+//! })
+//! .await?;
+//! ```
+//!
+//! Only the subset of SUIT that applies to a device with a single updatable component is
+//! supported: manifests need to be signed with ES256 and use SHA-256 digests, and they need to
+//! describe a single component. Images are fetched through the caller's transport, or taken from
+//! payloads integrated into the envelope. Sequences that try alternatives (`try-each`), severed
+//! members and dependencies are not supported.
+
+use embedded_storage_async::nor_flash::NorFlash;
+use minicbor::{Decoder, data::Type};
+use p256::ecdsa::VerifyingKey;
+use sha2::{Digest, Sha256};
+
+use crate::{SlotWriter, Updater};
+
+/// CBOR tag of a tagged envelope.
+const TAG_ENVELOPE: u64 = 107;
+
+// Envelope members.
+const ENVELOPE_AUTHENTICATION: u64 = 2;
+const ENVELOPE_MANIFEST: u64 = 3;
+
+// Manifest members.
+const MANIFEST_VERSION: u64 = 1;
+const MANIFEST_SEQUENCE_NUMBER: u64 = 2;
+const MANIFEST_COMMON: u64 = 3;
+const MANIFEST_VALIDATE: u64 = 7;
+const MANIFEST_PAYLOAD_FETCH: u64 = 16;
+const MANIFEST_INSTALL: u64 = 17;
+
+// Common members.
+const COMMON_COMPONENTS: u64 = 2;
+const COMMON_SHARED_SEQUENCE: u64 = 4;
+
+// Conditions.
+const CONDITION_VENDOR_IDENTIFIER: u64 = 1;
+const CONDITION_CLASS_IDENTIFIER: u64 = 2;
+const CONDITION_IMAGE_MATCH: u64 = 3;
+const CONDITION_COMPONENT_SLOT: u64 = 5;
+const CONDITION_ABORT: u64 = 14;
+const CONDITION_DEVICE_IDENTIFIER: u64 = 24;
+
+// Directives.
+const DIRECTIVE_SET_COMPONENT_INDEX: u64 = 12;
+const DIRECTIVE_WRITE: u64 = 18;
+const DIRECTIVE_OVERRIDE_PARAMETERS: u64 = 20;
+const DIRECTIVE_FETCH: u64 = 21;
+
+// Parameters.
+const PARAMETER_VENDOR_IDENTIFIER: u64 = 1;
+const PARAMETER_CLASS_IDENTIFIER: u64 = 2;
+const PARAMETER_IMAGE_DIGEST: u64 = 3;
+const PARAMETER_IMAGE_SIZE: u64 = 14;
+const PARAMETER_CONTENT: u64 = 18;
+const PARAMETER_URI: u64 = 21;
+const PARAMETER_DEVICE_IDENTIFIER: u64 = 24;
+
+/// The only supported manifest version.
+const SUPPORTED_VERSION: u64 = 1;
+
+/// COSE algorithm identifier of SHA-256.
+const ALG_SHA256: i64 = -16;
+
+/// Length of the buffer for the `Sig_structure`, which holds the manifest digest.
+const SIG_STRUCTURE_LEN: usize = 128;
+
+/// A decoded SUIT envelope, whose manifest is not authenticated yet.
+#[derive(Debug, Clone, Copy)]
+pub struct Envelope<'a> {
+    data: &'a [u8],
+    authentication: &'a [u8],
+    /// The byte string holding the manifest, including its CBOR head.
+    manifest: &'a [u8],
+}
+
+impl<'a> Envelope<'a> {
+    /// Decodes a SUIT envelope, which may be tagged.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Decode`] if `data` is not a well-formed envelope.
+    pub fn decode(data: &'a [u8]) -> Result<Self, Error> {
+        let mut decoder = Decoder::new(data);
+        if decoder.datatype()? == Type::Tag && u64::from(decoder.tag()?) != TAG_ENVELOPE {
+            return Err(Error::Decode);
+        }
+
+        let mut authentication = None;
+        let mut manifest = None;
+        for _ in 0..definite(decoder.map()?)? {
+            match decoder.datatype()? {
+                // Integrated payloads are looked up when they are needed.
+                Type::String => {
+                    decoder.str()?;
+                    decoder.skip()?;
+                }
+                _ => match decoder.u64()? {
+                    ENVELOPE_AUTHENTICATION => authentication = Some(decoder.bytes()?),
+                    ENVELOPE_MANIFEST => {
+                        let start = decoder.position();
+                        decoder.bytes()?;
+                        manifest = Some(data.get(start..decoder.position()).ok_or(Error::Decode)?);
+                    }
+                    _ => decoder.skip()?,
+                },
+            }
+        }
+
+        Ok(Self {
+            data,
+            authentication: authentication.ok_or(Error::Decode)?,
+            manifest: manifest.ok_or(Error::Decode)?,
+        })
+    }
+
+    /// Checks that the manifest is signed by one of the `trust_anchors`, and returns it.
+    ///
+    /// The authentication wrapper holds the SHA-256 digest of the manifest, and `COSE_Sign1`
+    /// structures that sign the digest as a detached payload with ES256. The manifest is accepted
+    /// if the digest matches and any of the signatures verifies with any of the trust anchors.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Unauthenticated`] if the digest does not match or no signature verifies,
+    /// [`Error::Unsupported`] if the digest algorithm is not supported, and [`Error::Decode`] if
+    /// the envelope or the manifest is malformed.
+    pub fn authenticate(&self, trust_anchors: &[VerifyingKey]) -> Result<Manifest<'a>, Error> {
+        let mut decoder = Decoder::new(self.authentication);
+        let blocks = definite(decoder.array()?)?;
+        let digest = decoder.bytes()?;
+        if decode_digest(digest)? != &*Sha256::digest(self.manifest) {
+            return Err(Error::Unauthenticated);
+        }
+
+        let mut buffer = [0; SIG_STRUCTURE_LEN];
+        let mut authenticated = false;
+        for _ in 1..blocks {
+            // Blocks that are not `COSE_Sign1` (eg. `COSE_Mac0`) are not supported, but other
+            // blocks may still authenticate the manifest.
+            let Ok(sign1) = cosecore::Sign1::decode(decoder.bytes()?) else {
+                continue;
+            };
+            authenticated |= trust_anchors.iter().any(|key| {
+                cosecore::es256::verify_detached(&sign1, key, digest, &[], &mut buffer).is_ok()
+            });
+        }
+        if !authenticated {
+            return Err(Error::Unauthenticated);
+        }
+
+        Manifest::decode(*self)
+    }
+
+    /// Returns the payload integrated into the envelope under `name`.
+    fn integrated_payload(&self, name: &str) -> Result<Option<&'a [u8]>, Error> {
+        let mut decoder = Decoder::new(self.data);
+        if decoder.datatype()? == Type::Tag {
+            decoder.tag()?;
+        }
+        for _ in 0..definite(decoder.map()?)? {
+            let key = if decoder.datatype()? == Type::String {
+                Some(decoder.str()?)
+            } else {
+                decoder.skip()?;
+                None
+            };
+            if key == Some(name) {
+                return Ok(Some(decoder.bytes()?));
+            }
+            decoder.skip()?;
+        }
+        Ok(None)
+    }
+}
+
+/// An authenticated SUIT manifest.
+///
+/// Obtained through [`Envelope::authenticate()`], and processed by [`process()`].
+#[derive(Debug, Clone, Copy)]
+pub struct Manifest<'a> {
+    envelope: Envelope<'a>,
+    sequence_number: u64,
+    shared_sequence: Option<&'a [u8]>,
+    payload_fetch: Option<&'a [u8]>,
+    install: Option<&'a [u8]>,
+    validate: Option<&'a [u8]>,
+}
+
+impl<'a> Manifest<'a> {
+    fn decode(envelope: Envelope<'a>) -> Result<Self, Error> {
+        let mut decoder = Decoder::new(envelope.manifest);
+        let mut decoder = Decoder::new(decoder.bytes()?);
+
+        let mut manifest = Self {
+            envelope,
+            sequence_number: 0,
+            shared_sequence: None,
+            payload_fetch: None,
+            install: None,
+            validate: None,
+        };
+        let mut version = None;
+        let mut sequence_number = None;
+        for _ in 0..definite(decoder.map()?)? {
+            match decoder.u64()? {
+                MANIFEST_VERSION => version = Some(decoder.u64()?),
+                MANIFEST_SEQUENCE_NUMBER => sequence_number = Some(decoder.u64()?),
+                MANIFEST_COMMON => manifest.shared_sequence = decode_common(decoder.bytes()?)?,
+                MANIFEST_PAYLOAD_FETCH => manifest.payload_fetch = Some(sequence(&mut decoder)?),
+                MANIFEST_INSTALL => manifest.install = Some(sequence(&mut decoder)?),
+                MANIFEST_VALIDATE => manifest.validate = Some(sequence(&mut decoder)?),
+                // Members that are not processed here (eg. text descriptions or the invoke
+                // sequence, which is up to the bootloader).
+                _ => decoder.skip()?,
+            }
+        }
+
+        match version {
+            Some(SUPPORTED_VERSION) => {}
+            Some(_) => return Err(Error::Unsupported),
+            None => return Err(Error::Decode),
+        }
+        manifest.sequence_number = sequence_number.ok_or(Error::Decode)?;
+        Ok(manifest)
+    }
+
+    /// Returns the sequence number of the manifest.
+    ///
+    /// Newer manifests of the same firmware carry higher sequence numbers.
+    #[must_use]
+    pub fn sequence_number(&self) -> u64 {
+        self.sequence_number
+    }
+}
+
+/// The properties of the device and of its running firmware, against which manifests are checked.
+#[derive(Debug, Clone, Copy)]
+pub struct Device<'d> {
+    /// The vendor identifier (a UUID) of the device.
+    pub vendor_id: &'d [u8],
+    /// The class identifier (a UUID) of the device.
+    pub class_id: &'d [u8],
+    /// The device identifier (a UUID) of the device, if it has one.
+    pub device_id: Option<&'d [u8]>,
+    /// The sequence number of the manifest of the running firmware.
+    ///
+    /// Only manifests with a higher sequence number are processed, which prevents rolling back to
+    /// older firmware.
+    pub sequence_number: u64,
+}
+
+/// Processes an authenticated `manifest`, and installs the image it describes.
+///
+/// The manifest is checked against `device` before anything is written. Its payload fetch,
+/// install and validate sequences are then run, in that order. Images are written into the
+/// inactive slot of `updater`: integrated payloads are written directly, other URIs are passed to
+/// `fetch`, which writes the image found there into the given writer. Once the image has been
+/// checked against its digest by the manifest, the update is [finalized](SlotWriter::finalize).
+///
+/// # Errors
+///
+/// Returns [`Error::Rollback`] if the manifest is not newer than the running firmware,
+/// [`Error::ConditionFailed`] if a condition of the manifest fails, [`Error::NotVerified`] if
+/// the manifest does not write an image and check its digest, and [`Error::Unsupported`] if the
+/// manifest uses SUIT features that are not supported. Errors of `fetch` and of the `updater` are
+/// passed on.
+pub async fn process<F: NorFlash>(
+    manifest: &Manifest<'_>,
+    device: &Device<'_>,
+    updater: &mut Updater<F>,
+    mut fetch: impl AsyncFnMut(&str, &mut SlotWriter<'_, F>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    if manifest.sequence_number <= device.sequence_number {
+        return Err(Error::Rollback);
+    }
+
+    let mut interpreter = Interpreter {
+        manifest,
+        device,
+        parameters: Parameters::default(),
+        verified: false,
+    };
+
+    // Check the device before touching the inactive slot.
+    if let Some(shared_sequence) = manifest.shared_sequence {
+        interpreter.run(shared_sequence, None, &mut fetch).await?;
+    }
+
+    let mut writer = updater.open().await?;
+    for sequence in [manifest.payload_fetch, manifest.install, manifest.validate]
+        .into_iter()
+        .flatten()
+    {
+        interpreter.parameters = Parameters::default();
+        if let Some(shared_sequence) = manifest.shared_sequence {
+            interpreter
+                .run(shared_sequence, Some(&mut writer), &mut fetch)
+                .await?;
+        }
+        interpreter
+            .run(sequence, Some(&mut writer), &mut fetch)
+            .await?;
+    }
+
+    if !interpreter.verified {
+        return Err(Error::NotVerified);
+    }
+    writer.finalize().await?;
+    Ok(())
+}
+
+/// The parameters of the single component.
+#[derive(Default)]
+struct Parameters<'a> {
+    vendor_id: Option<&'a [u8]>,
+    class_id: Option<&'a [u8]>,
+    device_id: Option<&'a [u8]>,
+    image_digest: Option<&'a [u8]>,
+    image_size: Option<u64>,
+    content: Option<&'a [u8]>,
+    uri: Option<&'a str>,
+}
+
+struct Interpreter<'m, 'a, 'd> {
+    manifest: &'m Manifest<'a>,
+    device: &'d Device<'d>,
+    parameters: Parameters<'a>,
+    /// Whether the image in the slot was checked against its digest since it was last written.
+    verified: bool,
+}
+
+impl<'a> Interpreter<'_, 'a, '_> {
+    /// Runs a command sequence.
+    ///
+    /// Without a `writer`, only conditions on the device can be checked.
+    async fn run<F: NorFlash>(
+        &mut self,
+        sequence: &'a [u8],
+        mut writer: Option<&mut SlotWriter<'_, F>>,
+        fetch: &mut impl AsyncFnMut(&str, &mut SlotWriter<'_, F>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut decoder = Decoder::new(sequence);
+        let len = definite(decoder.array()?)?;
+        if len % 2 != 0 {
+            return Err(Error::Decode);
+        }
+
+        for _ in 0..len / 2 {
+            match decoder.u64()? {
+                CONDITION_VENDOR_IDENTIFIER => {
+                    decoder.skip()?;
+                    check(self.parameters.vendor_id == Some(self.device.vendor_id))?;
+                }
+                CONDITION_CLASS_IDENTIFIER => {
+                    decoder.skip()?;
+                    check(self.parameters.class_id == Some(self.device.class_id))?;
+                }
+                CONDITION_DEVICE_IDENTIFIER => {
+                    decoder.skip()?;
+                    check(
+                        self.parameters.device_id.is_some()
+                            && self.parameters.device_id == self.device.device_id,
+                    )?;
+                }
+                CONDITION_IMAGE_MATCH => {
+                    decoder.skip()?;
+                    let writer = writer.as_deref_mut().ok_or(Error::ConditionFailed)?;
+                    check(self.image_matches(writer).await?)?;
+                    self.verified = true;
+                }
+                CONDITION_COMPONENT_SLOT => {
+                    // Slots are managed by the bootloader.
+                    decoder.skip()?;
+                }
+                CONDITION_ABORT => return Err(Error::ConditionFailed),
+                DIRECTIVE_SET_COMPONENT_INDEX => match decoder.datatype()? {
+                    Type::Bool if decoder.bool()? => {}
+                    Type::U8 | Type::U16 | Type::U32 | Type::U64 if decoder.u64()? == 0 => {}
+                    _ => return Err(Error::Unsupported),
+                },
+                DIRECTIVE_OVERRIDE_PARAMETERS => self.override_parameters(&mut decoder)?,
+                DIRECTIVE_FETCH => {
+                    decoder.skip()?;
+                    let writer = writer.as_deref_mut().ok_or(Error::Unsupported)?;
+                    let uri = self.parameters.uri.ok_or(Error::Decode)?;
+                    writer.rewind();
+                    self.verified = false;
+                    if uri.starts_with('#') {
+                        let payload = self.manifest.envelope.integrated_payload(uri)?;
+                        writer.write(payload.ok_or(Error::Fetch)?).await?;
+                    } else {
+                        fetch(uri, writer).await?;
+                    }
+                }
+                DIRECTIVE_WRITE => {
+                    decoder.skip()?;
+                    let writer = writer.as_deref_mut().ok_or(Error::Unsupported)?;
+                    let content = self.parameters.content.ok_or(Error::Decode)?;
+                    writer.rewind();
+                    self.verified = false;
+                    writer.write(content).await?;
+                }
+                _ => return Err(Error::Unsupported),
+            }
+        }
+        Ok(())
+    }
+
+    fn override_parameters(&mut self, decoder: &mut Decoder<'a>) -> Result<(), Error> {
+        let parameters = &mut self.parameters;
+        for _ in 0..definite(decoder.map()?)? {
+            match decoder.u64()? {
+                PARAMETER_VENDOR_IDENTIFIER => parameters.vendor_id = Some(decoder.bytes()?),
+                PARAMETER_CLASS_IDENTIFIER => parameters.class_id = Some(decoder.bytes()?),
+                PARAMETER_DEVICE_IDENTIFIER => parameters.device_id = Some(decoder.bytes()?),
+                PARAMETER_IMAGE_DIGEST => {
+                    parameters.image_digest = Some(decode_digest(decoder.bytes()?)?);
+                }
+                PARAMETER_IMAGE_SIZE => parameters.image_size = Some(decoder.u64()?),
+                PARAMETER_CONTENT => parameters.content = Some(decoder.bytes()?),
+                PARAMETER_URI => parameters.uri = Some(decoder.str()?),
+                // Parameters that do not affect processing here (eg. the component slot).
+                _ => decoder.skip()?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the image written so far against the image digest and size parameters.
+    async fn image_matches<F: NorFlash>(
+        &self,
+        writer: &mut SlotWriter<'_, F>,
+    ) -> Result<bool, Error> {
+        let image_digest = self.parameters.image_digest.ok_or(Error::Decode)?;
+        if self
+            .parameters
+            .image_size
+            .is_some_and(|size| size != u64::from(writer.position()))
+        {
+            return Ok(false);
+        }
+
+        let mut digest = Sha256::new();
+        writer.read_back(|chunk| digest.update(chunk)).await?;
+        Ok(*digest.finalize() == *image_digest)
+    }
+}
+
+/// Decodes the common members, and returns the shared sequence.
+fn decode_common(common: &[u8]) -> Result<Option<&[u8]>, Error> {
+    let mut decoder = Decoder::new(common);
+    let mut shared_sequence = None;
+    for _ in 0..definite(decoder.map()?)? {
+        match decoder.u64()? {
+            COMMON_COMPONENTS => {
+                // Only a single component (the inactive slot) is supported.
+                if definite(decoder.array()?)? != 1 {
+                    return Err(Error::Unsupported);
+                }
+                decoder.skip()?;
+            }
+            COMMON_SHARED_SEQUENCE => shared_sequence = Some(decoder.bytes()?),
+            _ => decoder.skip()?,
+        }
+    }
+    Ok(shared_sequence)
+}
+
+/// Decodes a command sequence member, which is not supported if it was severed.
+fn sequence<'a>(decoder: &mut Decoder<'a>) -> Result<&'a [u8], Error> {
+    match decoder.datatype()? {
+        Type::Bytes => Ok(decoder.bytes()?),
+        _ => Err(Error::Unsupported),
+    }
+}
+
+/// Decodes a `SUIT_Digest`, and returns the digest bytes.
+fn decode_digest(digest: &[u8]) -> Result<&[u8], Error> {
+    let mut decoder = Decoder::new(digest);
+    if definite(decoder.array()?)? != 2 {
+        return Err(Error::Decode);
+    }
+    if decoder.i64()? != ALG_SHA256 {
+        return Err(Error::Unsupported);
+    }
+    let bytes = decoder.bytes()?;
+    if bytes.len() != Sha256::output_size() {
+        return Err(Error::Decode);
+    }
+    Ok(bytes)
+}
+
+/// Requires a definite length, which SUIT mandates.
+fn definite(len: Option<u64>) -> Result<u64, Error> {
+    len.ok_or(Error::Decode)
+}
+
+fn check(condition: bool) -> Result<(), Error> {
+    if condition {
+        Ok(())
+    } else {
+        Err(Error::ConditionFailed)
+    }
+}
+
+/// Errors returned when processing SUIT manifests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The envelope or the manifest is malformed.
+    Decode,
+    /// The manifest uses SUIT features or algorithms that are not supported.
+    Unsupported,
+    /// The manifest is not signed by a trust anchor.
+    Unauthenticated,
+    /// The manifest is not newer than the running firmware.
+    Rollback,
+    /// A condition of the manifest failed, eg. because it is meant for a different device.
+    ConditionFailed,
+    /// The manifest did not write an image and check it against its digest.
+    NotVerified,
+    /// Fetching the image failed.
+    Fetch,
+    /// Writing the image failed.
+    Update(crate::Error),
+}
+
+impl From<minicbor::decode::Error> for Error {
+    fn from(_: minicbor::decode::Error) -> Self {
+        Self::Decode
+    }
+}
+
+impl From<crate::Error> for Error {
+    fn from(error: crate::Error) -> Self {
+        Self::Update(error)
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Decode => write!(f, "malformed SUIT manifest"),
+            Self::Unsupported => write!(f, "unsupported SUIT manifest"),
+            Self::Unauthenticated => write!(f, "SUIT manifest not authenticated"),
+            Self::Rollback => write!(f, "SUIT manifest older than the running firmware"),
+            Self::ConditionFailed => write!(f, "SUIT condition failed"),
+            Self::NotVerified => write!(f, "image not verified by the SUIT manifest"),
+            Self::Fetch => write!(f, "fetching the image failed"),
+            Self::Update(error) => write!(f, "writing the image failed: {error}"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use embassy_futures::block_on;
+    use embedded_storage_async::nor_flash::ReadNorFlash;
+    use minicbor::{
+        Encoder,
+        data::Tag,
+        encode::write::{Cursor, EndOfSlice},
+    };
+    use p256::ecdsa::SigningKey;
+
+    use super::*;
+    use crate::{BootState, Layout, Partition, faulty_flash::FaultyFlash};
+
+    const VENDOR_ID: [u8; 16] = [0x11; 16];
+    const CLASS_ID: [u8; 16] = [0x22; 16];
+    const IMAGE_URI: &str = "coap://[2001:db8::1]/firmware";
+    const INTEGRATED_URI: &str = "#image";
+
+    const SLOT_SIZE: u32 = 4096;
+
+    /// What the manifest of a test envelope describes.
+    struct Update<'a> {
+        sequence_number: u64,
+        class_id: &'a [u8],
+        /// The image whose digest and size the manifest carries.
+        image: &'a [u8],
+        uri: &'a str,
+        /// Whether the install sequence checks the image against its digest.
+        image_match: bool,
+        /// The payload integrated into the envelope under [`INTEGRATED_URI`].
+        integrated: Option<&'a [u8]>,
+    }
+
+    impl<'a> Update<'a> {
+        fn new(sequence_number: u64, image: &'a [u8]) -> Self {
+            Self {
+                sequence_number,
+                class_id: &CLASS_ID,
+                image,
+                uri: IMAGE_URI,
+                image_match: true,
+                integrated: None,
+            }
+        }
+    }
+
+    type TestEncoder<'b> = Encoder<Cursor<&'b mut [u8]>>;
+
+    /// Encodes CBOR items into a vector.
+    fn cbor(
+        f: impl for<'e, 'b> FnOnce(
+            &'e mut TestEncoder<'b>,
+        ) -> Result<
+            &'e mut TestEncoder<'b>,
+            minicbor::encode::Error<EndOfSlice>,
+        >,
+    ) -> Vec<u8> {
+        let mut buffer = [0; 1024];
+        let mut encoder = Encoder::new(Cursor::new(&mut buffer[..]));
+        f(&mut encoder).unwrap();
+        let len = encoder.into_writer().position();
+        buffer.get(..len).unwrap().to_vec()
+    }
+
+    fn sha256_digest(data: &[u8]) -> Vec<u8> {
+        cbor(|e| e.array(2)?.i64(ALG_SHA256)?.bytes(&Sha256::digest(data)))
+    }
+
+    /// Returns the manifest describing `update`, as a byte string.
+    fn manifest(update: &Update<'_>) -> Vec<u8> {
+        let shared_sequence = cbor(|e| {
+            e.array(6)?
+                .u64(DIRECTIVE_OVERRIDE_PARAMETERS)?
+                .map(4)?
+                .u64(PARAMETER_VENDOR_IDENTIFIER)?
+                .bytes(&VENDOR_ID)?
+                .u64(PARAMETER_CLASS_IDENTIFIER)?
+                .bytes(update.class_id)?
+                .u64(PARAMETER_IMAGE_DIGEST)?
+                .bytes(&sha256_digest(update.image))?
+                .u64(PARAMETER_IMAGE_SIZE)?
+                .u64(update.image.len() as u64)?
+                .u64(CONDITION_VENDOR_IDENTIFIER)?
+                .u64(15)?
+                .u64(CONDITION_CLASS_IDENTIFIER)?
+                .u64(15)
+        });
+        let common = cbor(|e| {
+            e.map(2)?
+                .u64(COMMON_COMPONENTS)?
+                .array(1)?
+                .array(1)?
+                .bytes(&[0])?
+                .u64(COMMON_SHARED_SEQUENCE)?
+                .bytes(&shared_sequence)
+        });
+        let install = cbor(|e| {
+            e.array(if update.image_match { 6 } else { 4 })?
+                .u64(DIRECTIVE_OVERRIDE_PARAMETERS)?
+                .map(1)?
+                .u64(PARAMETER_URI)?
+                .str(update.uri)?
+                .u64(DIRECTIVE_FETCH)?
+                .u64(2)?;
+            if update.image_match {
+                e.u64(CONDITION_IMAGE_MATCH)?.u64(15)?;
+            }
+            Ok(e)
+        });
+        let manifest = cbor(|e| {
+            e.map(4)?
+                .u64(MANIFEST_VERSION)?
+                .u64(SUPPORTED_VERSION)?
+                .u64(MANIFEST_SEQUENCE_NUMBER)?
+                .u64(update.sequence_number)?
+                .u64(MANIFEST_COMMON)?
+                .bytes(&common)?
+                .u64(MANIFEST_INSTALL)?
+                .bytes(&install)
+        });
+        cbor(|e| e.bytes(&manifest))
+    }
+
+    /// Returns an envelope of `manifest`, whose authentication wrapper holds `digest` signed by
+    /// `key`.
+    fn envelope(
+        key: &SigningKey,
+        digest: &[u8],
+        manifest: &[u8],
+        integrated: Option<&[u8]>,
+    ) -> Vec<u8> {
+        let mut buffer = [0; 256];
+        let signed = cosecore::es256::sign(key, None, digest, &[], &mut buffer).unwrap();
+        let mut sign1 = cosecore::Sign1::decode(signed).unwrap();
+        sign1.payload = None;
+        let sign1 = cbor(|e| e.encode(&sign1));
+        let authentication = cbor(|e| e.array(2)?.bytes(digest)?.bytes(&sign1));
+
+        let mut envelope = cbor(|e| {
+            e.tag(Tag::new(TAG_ENVELOPE))?
+                .map(if integrated.is_some() { 3 } else { 2 })?
+                .u64(ENVELOPE_AUTHENTICATION)?
+                .bytes(&authentication)?
+                .u64(ENVELOPE_MANIFEST)
+        });
+        envelope.extend_from_slice(manifest);
+        if let Some(payload) = integrated {
+            envelope.extend(cbor(|e| e.str(INTEGRATED_URI)?.bytes(payload)));
+        }
+        envelope
+    }
+
+    /// Returns an envelope of the manifest describing `update`, correctly signed by `key`.
+    fn signed(key: &SigningKey, update: &Update<'_>) -> Vec<u8> {
+        let manifest = manifest(update);
+        envelope(key, &sha256_digest(&manifest), &manifest, update.integrated)
+    }
+
+    fn key() -> SigningKey {
+        SigningKey::from_slice(&[0x2a; 32]).unwrap()
+    }
+
+    fn other_key() -> SigningKey {
+        SigningKey::from_slice(&[0x2b; 32]).unwrap()
+    }
+
+    fn device() -> Device<'static> {
+        Device {
+            vendor_id: &VENDOR_ID,
+            class_id: &CLASS_ID,
+            device_id: None,
+            sequence_number: 1,
+        }
+    }
+
+    fn updater() -> Updater<FaultyFlash> {
+        let layout = Layout::new(
+            Partition::new(0, SLOT_SIZE),
+            Partition::new(SLOT_SIZE, SLOT_SIZE),
+            Partition::new(2 * SLOT_SIZE, 1024),
+        );
+        Updater::new(FaultyFlash::new(9), layout).unwrap()
+    }
+
+    fn image(len: usize) -> Vec<u8> {
+        (0..len).map(|i| u8::try_from(i % 251).unwrap()).collect()
+    }
+
+    /// Returns the first `len` bytes of the inactive slot.
+    async fn inactive_slot(updater: &mut Updater<FaultyFlash>, len: usize) -> Vec<u8> {
+        let mut data = vec![0; len];
+        updater.flash.read(SLOT_SIZE, &mut data).await.unwrap();
+        data
+    }
+
+    /// Writes `image` into the writer, in chunks as a transport would.
+    async fn fetch_image(
+        image: &[u8],
+        writer: &mut SlotWriter<'_, FaultyFlash>,
+    ) -> Result<(), Error> {
+        for chunk in image.chunks(100) {
+            writer.write(chunk).await?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn fetched_image_is_installed() {
+        block_on(async {
+            let image = image(1234);
+            let envelope = signed(&key(), &Update::new(2, &image));
+            let manifest = Envelope::decode(&envelope)
+                .unwrap()
+                .authenticate(&[*other_key().verifying_key(), *key().verifying_key()])
+                .unwrap();
+            assert_eq!(manifest.sequence_number(), 2);
+
+            let mut updater = updater();
+            let mut uri = None;
+            process(
+                &manifest,
+                &device(),
+                &mut updater,
+                async |fetched, writer| {
+                    uri = Some(fetched.to_owned());
+                    fetch_image(&image, writer).await
+                },
+            )
+            .await
+            .unwrap();
+            assert_eq!(uri.as_deref(), Some(IMAGE_URI));
+            assert_eq!(updater.state().await, Ok(BootState::Pending));
+            assert_eq!(inactive_slot(&mut updater, image.len()).await, image);
+        });
+    }
+
+    #[test]
+    fn integrated_payload_is_installed() {
+        block_on(async {
+            let image = image(77);
+            let update = Update {
+                uri: INTEGRATED_URI,
+                integrated: Some(&image),
+                ..Update::new(2, &image)
+            };
+            let envelope = signed(&key(), &update);
+            let manifest = Envelope::decode(&envelope)
+                .unwrap()
+                .authenticate(&[*key().verifying_key()])
+                .unwrap();
+
+            let mut updater = updater();
+            process(&manifest, &device(), &mut updater, async |_, _| {
+                panic!("integrated payloads are not fetched")
+            })
+            .await
+            .unwrap();
+            assert_eq!(updater.state().await, Ok(BootState::Pending));
+            assert_eq!(inactive_slot(&mut updater, image.len()).await, image);
+        });
+    }
+
+    #[test]
+    fn missing_integrated_payload_fails() {
+        block_on(async {
+            let image = image(77);
+            let update = Update {
+                uri: INTEGRATED_URI,
+                ..Update::new(2, &image)
+            };
+            let envelope = signed(&key(), &update);
+            let manifest = Envelope::decode(&envelope)
+                .unwrap()
+                .authenticate(&[*key().verifying_key()])
+                .unwrap();
+
+            let mut updater = updater();
+            assert_eq!(
+                process(&manifest, &device(), &mut updater, async |_, _| Ok(())).await,
+                Err(Error::Fetch)
+            );
+            assert_eq!(updater.state().await, Ok(BootState::Idle));
+        });
+    }
+
+    #[test]
+    fn untrusted_signature_is_rejected() {
+        let envelope = signed(&other_key(), &Update::new(2, &image(10)));
+        let envelope = Envelope::decode(&envelope).unwrap();
+        assert_eq!(
+            envelope
+                .authenticate(&[*key().verifying_key()])
+                .unwrap_err(),
+            Error::Unauthenticated
+        );
+        assert_eq!(
+            envelope.authenticate(&[]).unwrap_err(),
+            Error::Unauthenticated
+        );
+    }
+
+    #[test]
+    fn corrupted_signature_is_rejected() {
+        let manifest = manifest(&Update::new(2, &image(10)));
+        let digest = sha256_digest(&manifest);
+        let mut envelope = envelope(&key(), &digest, &manifest, None);
+        // The signature ends the authentication wrapper, which is followed by the manifest key.
+        let index = envelope.len() - manifest.len() - 2;
+        *envelope.get_mut(index).unwrap() ^= 1;
+        assert_eq!(
+            Envelope::decode(&envelope)
+                .unwrap()
+                .authenticate(&[*key().verifying_key()])
+                .unwrap_err(),
+            Error::Unauthenticated
+        );
+    }
+
+    #[test]
+    fn manifest_digest_mismatch_is_rejected() {
+        let manifest = manifest(&Update::new(2, &image(10)));
+        let other_manifest = self::manifest(&Update::new(3, &image(10)));
+        // Correctly signed, but over the digest of a different manifest.
+        let envelope = envelope(&key(), &sha256_digest(&other_manifest), &manifest, None);
+        assert_eq!(
+            Envelope::decode(&envelope)
+                .unwrap()
+                .authenticate(&[*key().verifying_key()])
+                .unwrap_err(),
+            Error::Unauthenticated
+        );
+    }
+
+    #[test]
+    fn image_digest_mismatch_is_rejected() {
+        block_on(async {
+            let image = image(1234);
+            let envelope = signed(&key(), &Update::new(2, &image));
+            let manifest = Envelope::decode(&envelope)
+                .unwrap()
+                .authenticate(&[*key().verifying_key()])
+                .unwrap();
+
+            let mut tampered = image.clone();
+            *tampered.get_mut(1000).unwrap() ^= 1;
+            let mut updater = updater();
+            assert_eq!(
+                process(&manifest, &device(), &mut updater, async |_, writer| {
+                    fetch_image(&tampered, writer).await
+                })
+                .await,
+                Err(Error::ConditionFailed)
+            );
+            assert_eq!(updater.state().await, Ok(BootState::Idle));
+
+            // A truncated image fails the size check.
+            assert_eq!(
+                process(&manifest, &device(), &mut updater, async |_, writer| {
+                    fetch_image(image.get(..1000).unwrap(), writer).await
+                })
+                .await,
+                Err(Error::ConditionFailed)
+            );
+            assert_eq!(updater.state().await, Ok(BootState::Idle));
+        });
+    }
+
+    #[test]
+    fn rollback_is_rejected() {
+        block_on(async {
+            let image = image(10);
+            let envelope = signed(&key(), &Update::new(2, &image));
+            let manifest = Envelope::decode(&envelope)
+                .unwrap()
+                .authenticate(&[*key().verifying_key()])
+                .unwrap();
+
+            let mut updater = updater();
+            for sequence_number in [2, 3] {
+                let device = Device {
+                    sequence_number,
+                    ..device()
+                };
+                assert_eq!(
+                    process(&manifest, &device, &mut updater, async |_, _| {
+                        panic!("rolled back manifests are not processed")
+                    })
+                    .await,
+                    Err(Error::Rollback)
+                );
+            }
+            assert_eq!(updater.state().await, Ok(BootState::Idle));
+        });
+    }
+
+    #[test]
+    fn fetch_without_image_match_is_not_verified() {
+        block_on(async {
+            let image = image(1234);
+            let update = Update {
+                image_match: false,
+                ..Update::new(2, &image)
+            };
+            let envelope = signed(&key(), &update);
+            let manifest = Envelope::decode(&envelope)
+                .unwrap()
+                .authenticate(&[*key().verifying_key()])
+                .unwrap();
+
+            let mut updater = updater();
+            assert_eq!(
+                process(&manifest, &device(), &mut updater, async |_, writer| {
+                    fetch_image(&image, writer).await
+                })
+                .await,
+                Err(Error::NotVerified)
+            );
+            assert_eq!(updater.state().await, Ok(BootState::Idle));
+        });
+    }
+
+    #[test]
+    fn other_device_class_is_rejected() {
+        block_on(async {
+            let image = image(10);
+            let update = Update {
+                class_id: &[0x33; 16],
+                ..Update::new(2, &image)
+            };
+            let envelope = signed(&key(), &update);
+            let manifest = Envelope::decode(&envelope)
+                .unwrap()
+                .authenticate(&[*key().verifying_key()])
+                .unwrap();
+
+            let mut updater = updater();
+            assert_eq!(
+                process(&manifest, &device(), &mut updater, async |_, _| {
+                    panic!("images for other devices are not fetched")
+                })
+                .await,
+                Err(Error::ConditionFailed)
+            );
+        });
+    }
+}
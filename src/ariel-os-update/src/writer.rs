@@ -0,0 +1,259 @@
+//! Writing firmware images into the inactive slot.
+
+use embedded_storage_async::nor_flash::NorFlash;
+
+use crate::{BootState, Error, Layout, MAX_WRITE_SIZE, Updater, read_unaligned};
+
+/// Writes a firmware image into the inactive slot.
+///
+/// Obtained through [`Updater::open()`]. The image is written sequentially; flash pages are erased
+/// just before they are written. The update only takes effect once the writer is
+/// [finalized](SlotWriter::finalize).
+///
+/// Transports can feed the writer in the way that suits them:
+///
+/// * stream based transports (eg. HTTP or USB) through [`SlotWriter::write()`] or the
+///   [`embedded_io_async::Write`] implementation;
+/// * block based transports (eg. CoAP block-wise transfers) through [`SlotWriter::write_at()`],
+///   which also tolerates retransmitted blocks and allows resuming an interrupted transfer.
+pub struct SlotWriter<'u, F: NorFlash> {
+    updater: &'u mut Updater<F>,
+    /// Number of bytes accepted so far.
+    position: u32,
+    /// Number of bytes written to the flash so far; the rest is buffered.
+    flushed: u32,
+    /// Number of bytes of the slot that have been erased so far.
+    erased: u32,
+    buffer: [u8; MAX_WRITE_SIZE],
+}
+
+impl<'u, F: NorFlash> SlotWriter<'u, F> {
+    pub(crate) fn new(updater: &'u mut Updater<F>) -> Self {
+        Self::resumed(updater, 0)
+    }
+
+    /// Creates a writer that continues an image at `page`, which is the start of a flash page
+    /// that is erased before being written.
+    pub(crate) fn resumed(updater: &'u mut Updater<F>, page: u32) -> Self {
+        Self {
+            updater,
+            position: page,
+            flushed: page,
+            erased: page,
+            buffer: [0xff; MAX_WRITE_SIZE],
+        }
+    }
+
+    /// Returns the number of bytes written so far.
+    #[must_use]
+    pub fn position(&self) -> u32 {
+        self.position
+    }
+
+    /// Appends `data` to the image.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ImageTooLarge`] if the image does not fit into the inactive slot, and
+    /// [`Error::Flash`] if writing the flash failed.
+    pub async fn write(&mut self, mut data: &[u8]) -> Result<(), Error> {
+        let len = u32::try_from(data.len()).map_err(|_| Error::ImageTooLarge)?;
+        if self
+            .position
+            .checked_add(len)
+            .is_none_or(|end| end > self.updater.layout.inactive.size)
+        {
+            return Err(Error::ImageTooLarge);
+        }
+
+        while !data.is_empty() {
+            let buffered = self.buffered();
+            let (chunk, rest) = if buffered == 0 && data.len() >= F::WRITE_SIZE {
+                // Write whole words straight from the input.
+                let (words, rest) = data.split_at(data.len() - data.len() % F::WRITE_SIZE);
+                self.program(words).await?;
+                (words, rest)
+            } else {
+                let (chunk, rest) = data.split_at(data.len().min(F::WRITE_SIZE - buffered));
+                self.buffer
+                    .get_mut(buffered..buffered + chunk.len())
+                    .ok_or(Error::Layout)?
+                    .copy_from_slice(chunk);
+                (chunk, rest)
+            };
+            // Cannot overflow, as the total length was checked above.
+            self.position += u32::try_from(chunk.len()).map_err(|_| Error::ImageTooLarge)?;
+            data = rest;
+
+            if self.buffered() == F::WRITE_SIZE {
+                self.flush_buffer().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes `data` at `offset` into the image.
+    ///
+    /// Data needs to be written in order, but data that was already written is ignored: this
+    /// allows block based transports to retransmit blocks, and to resume a transfer at
+    /// [`SlotWriter::position()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::OutOfOrder`] if `offset` lies after the data written so far or data would
+    /// be written only partially, and otherwise errors like [`SlotWriter::write()`].
+    pub async fn write_at(&mut self, offset: u32, data: &[u8]) -> Result<(), Error> {
+        let len = u32::try_from(data.len()).map_err(|_| Error::ImageTooLarge)?;
+        match offset.checked_add(len) {
+            Some(end) if end <= self.position => Ok(()),
+            _ if offset == self.position => self.write(data).await,
+            _ => Err(Error::OutOfOrder),
+        }
+    }
+
+    /// Completes the image, and requests the bootloader to swap it in at the next boot.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Flash`] if writing the flash failed.
+    pub async fn finalize(self) -> Result<(), Error> {
+        let completed = self.complete().await?;
+        let updater = completed.updater;
+        BootState::Pending
+            .write(&mut updater.flash, &updater.layout.state)
+            .await
+    }
+
+    /// Writes out the buffered data, after which no more data can be written.
+    pub(crate) async fn complete(mut self) -> Result<Completed<'u, F>, Error> {
+        if self.buffered() > 0 {
+            // The padding is left in the erased state.
+            self.flush_buffer().await?;
+        }
+        Ok(Completed {
+            updater: self.updater,
+            len: self.position,
+            erased: self.erased,
+        })
+    }
+
+    /// Discards the image written so far, so that a new image can be written from the start.
+    #[cfg_attr(not(any(feature = "suit", feature = "download")), expect(dead_code))]
+    pub(crate) fn rewind(&mut self) {
+        self.position = 0;
+        self.flushed = 0;
+        self.erased = 0;
+        self.buffer.fill(0xff);
+    }
+
+    /// Passes the image written so far to `f` in chunks, reading it back from the flash.
+    #[cfg_attr(not(feature = "suit"), expect(dead_code))]
+    pub(crate) async fn read_back(&mut self, mut f: impl FnMut(&[u8])) -> Result<(), Error> {
+        const CHUNK_LEN: usize = 64;
+
+        if !CHUNK_LEN.is_multiple_of(F::READ_SIZE) {
+            return Err(Error::Layout);
+        }
+        let slot = self.updater.layout.inactive;
+        let mut chunk = [0; CHUNK_LEN];
+        let mut offset = 0;
+        while offset < self.flushed {
+            let len = CHUNK_LEN.min((self.flushed - offset) as usize);
+            // Reading up to the read size is fine, as the slot is aligned to erase pages.
+            let read = chunk
+                .get_mut(..len.next_multiple_of(F::READ_SIZE))
+                .ok_or(Error::Layout)?;
+            self.updater
+                .flash
+                .read(slot.offset + offset, read)
+                .await
+                .map_err(|_| Error::Flash)?;
+            f(read.get(..len).ok_or(Error::Layout)?);
+            offset += u32::try_from(len).map_err(|_| Error::Layout)?;
+        }
+        f(self.buffer.get(..self.buffered()).ok_or(Error::Layout)?);
+        Ok(())
+    }
+
+    /// Returns the layout of the slots.
+    #[cfg_attr(not(any(feature = "delta", feature = "download")), expect(dead_code))]
+    pub(crate) fn layout(&self) -> &Layout {
+        &self.updater.layout
+    }
+
+    /// Reads `buffer.len()` bytes at `offset` of the active slot.
+    #[cfg_attr(not(feature = "delta"), expect(dead_code))]
+    pub(crate) async fn read_active(
+        &mut self,
+        offset: u32,
+        buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        let slot = self.updater.layout.active;
+        let len = u32::try_from(buffer.len()).map_err(|_| Error::Layout)?;
+        if offset.checked_add(len).is_none_or(|end| end > slot.size) {
+            return Err(Error::Layout);
+        }
+        read_unaligned(&mut self.updater.flash, slot.offset + offset, buffer).await
+    }
+
+    /// Returns the number of bytes held in the buffer.
+    fn buffered(&self) -> usize {
+        // At most the write size, as the buffer is flushed once it is full.
+        (self.position - self.flushed) as usize
+    }
+
+    /// Writes the buffered word.
+    async fn flush_buffer(&mut self) -> Result<(), Error> {
+        let buffer = self.buffer;
+        self.program(buffer.get(..F::WRITE_SIZE).ok_or(Error::Layout)?)
+            .await?;
+        self.buffer.fill(0xff);
+        Ok(())
+    }
+
+    /// Writes whole words after the flushed data, erasing pages as needed.
+    async fn program(&mut self, words: &[u8]) -> Result<(), Error> {
+        let slot = self.updater.layout.inactive;
+        let len = u32::try_from(words.len()).map_err(|_| Error::ImageTooLarge)?;
+        let end = self.flushed + len;
+        let erase_size = u32::try_from(F::ERASE_SIZE).map_err(|_| Error::Layout)?;
+        while self.erased < end {
+            let page = slot.offset + self.erased;
+            self.updater
+                .flash
+                .erase(page, page + erase_size)
+                .await
+                .map_err(|_| Error::Flash)?;
+            self.erased += erase_size;
+        }
+        self.updater
+            .flash
+            .write(slot.offset + self.flushed, words)
+            .await
+            .map_err(|_| Error::Flash)?;
+        self.flushed = end;
+        Ok(())
+    }
+}
+
+/// A completely written image.
+pub(crate) struct Completed<'u, F> {
+    pub(crate) updater: &'u mut Updater<F>,
+    /// Length of the image.
+    #[cfg_attr(not(feature = "mcuboot"), expect(dead_code))]
+    pub(crate) len: u32,
+    /// Number of bytes of the slot that have been erased.
+    #[cfg_attr(not(feature = "mcuboot"), expect(dead_code))]
+    pub(crate) erased: u32,
+}
+
+impl<F: NorFlash> embedded_io_async::ErrorType for SlotWriter<'_, F> {
+    type Error = Error;
+}
+
+impl<F: NorFlash> embedded_io_async::Write for SlotWriter<'_, F> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        SlotWriter::write(self, buf).await?;
+        Ok(buf.len())
+    }
+}
@@ -2,6 +2,19 @@ pub use {const_panic, konst};
 
 macro_rules! define_env_with_default_macro {
     ($macro_name:ident, $parse_fn_name:ident, $output_type_name:literal) => {
+        #[doc = concat!(
+            "Reads a value at compile time from the given environment variable, parsed as ",
+            $output_type_name,
+            ", with a default."
+        )]
+        ///
+        /// - The `$default` parameter allows to provide a fallback value for when the environment
+        ///   variable is not found.
+        /// - The `$doc` parameter allows to provide a documentation string for this tunable (see
+        ///   [`str_from_env!`](str_from_env)).
+        ///
+        /// Produces a compile-time error if the value of the environment variable cannot be
+        /// parsed, or when [`option_env!`](option_env) does.
         #[macro_export]
         macro_rules! $macro_name {
             // $doc is currently unused
@@ -31,6 +44,10 @@ macro_rules! define_env_with_default_macro {
 
 define_env_with_default_macro!(usize_from_env_or, parse_usize, "a usize");
 define_env_with_default_macro!(u8_from_env_or, parse_u8, "a u8");
+define_env_with_default_macro!(u16_from_env_or, parse_u16, "a u16");
+define_env_with_default_macro!(u32_from_env_or, parse_u32, "a u32");
+define_env_with_default_macro!(u64_from_env_or, parse_u64, "a u64");
+define_env_with_default_macro!(bool_from_env_or, parse_bool, "a bool");
 
 /// Reads a value at compile time from the given environment variable, with a default.
 ///
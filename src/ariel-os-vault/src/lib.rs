@@ -0,0 +1,408 @@
+//! Provides a vault for secrets that are kept in persistent storage in sealed form.
+//!
+//! Secrets such as network keys or application keys are stored in the
+//! [storage](ariel_os_storage) like any other value, but they are encrypted and authenticated
+//! with a key-wrapping key that is unique to the device, so that they never sit in flash in
+//! plaintext.
+//!
+//! Secrets are accessed through named handles:
+//!
+//! ```ignore
+//! let vault = ariel_os::vault::vault().await?;
+//! let appkey = vault.key("lorawan-appkey");
+//! if appkey.load::<16>().await?.is_none() {
+//!     appkey.store(&provisioned_key).await?;
+//! }
+//! ```
+//!
+//! Secrets loaded from the vault are returned as a [`Secret`], which overwrites its content when
+//! dropped. The [`Vault`] itself erases its key-wrapping key when dropped.
+//!
+//! # Key-wrapping key
+//!
+//! The key-wrapping key is derived from the device key (see
+//! [`ariel_os_identity::device_key`]), using the device ID (see
+//! [`ariel_os_identity::device_id_bytes()`]) as salt where the device has one. Each secret is
+//! bound to its name, so sealed secrets can not be swapped between names, nor moved to a different
+//! device.
+//!
+//! <div class="warning">
+//! The device key is itself kept in storage, and the device ID is not secret.
+//! Sealing thus keeps secrets from showing up in plain sight in flash and binds them to the
+//! device, but it does not protect them from an attacker who can read the complete storage.
+//! </div>
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+use ariel_os_debug::log::debug;
+use chacha20poly1305::{AeadInPlace, ChaCha20Poly1305, KeyInit, Nonce, Tag};
+use rand_core::RngCore;
+
+/// Maximum length of the name of a secret.
+pub const MAX_NAME_LEN: usize = 32;
+
+/// Maximum length of a secret.
+pub const MAX_SECRET_LEN: usize = 64;
+
+/// Prefix of the storage keys under which secrets are stored.
+const KEY_PREFIX: &str = "ariel-os-vault.";
+/// HKDF info used to derive the key-wrapping key from the device key.
+const KDF_INFO: &[u8] = b"ariel-os-vault key-wrapping key";
+
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+/// Maximum length of a secret in its sealed form, as kept in storage.
+const MAX_SEALED_LEN: usize = NONCE_LEN + MAX_SECRET_LEN + TAG_LEN;
+/// Amount of stack scrubbed after deriving the key-wrapping key.
+const KDF_STACK_USAGE: usize = 1024;
+
+/// Opens the device's vault.
+///
+/// This loads (or, on first use, generates) the device key, and derives the key-wrapping key from
+/// it.
+///
+/// # Errors
+///
+/// Returns [`Error::Storage`] if the device key could not be loaded.
+#[expect(clippy::missing_panics_doc, reason = "does not panic")]
+pub async fn vault() -> Result<Vault, Error> {
+    let device_key = ariel_os_identity::device_key::device_key()
+        .await
+        .map_err(|_| Error::Storage)?;
+
+    let ikm = secretcore::Secret::new(device_key.secret_key_bytes());
+    let device_id = ariel_os_identity::device_id_bytes().ok();
+    let salt = device_id.as_ref().map(AsRef::as_ref);
+
+    let mut wrapping_key = secretcore::Secret::new([0; 32]);
+    // The HMAC state holds key-dependent values.
+    secretcore::scrubbing_stack::<KDF_STACK_USAGE, _>(|| {
+        hkdf::Hkdf::<sha2::Sha256>::new(salt, ikm.expose())
+            .expand(KDF_INFO, wrapping_key.expose_mut())
+            .expect("32 bytes is a valid output length for HKDF-SHA256");
+    });
+
+    Ok(Vault { wrapping_key })
+}
+
+/// The device's vault of sealed secrets.
+///
+/// Obtain it through [`vault()`].
+pub struct Vault {
+    wrapping_key: secretcore::Secret<[u8; 32]>,
+}
+
+impl Vault {
+    /// Returns a handle to the secret stored under `name`.
+    ///
+    /// The secret does not need to exist yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is longer than [`MAX_NAME_LEN`].
+    #[must_use]
+    pub fn key<'v>(&'v self, name: &'v str) -> KeyHandle<'v> {
+        assert!(name.len() <= MAX_NAME_LEN, "secret name too long");
+        KeyHandle { vault: self, name }
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(self.wrapping_key.expose().into())
+    }
+
+    /// Seals `secret` under `name` into `buffer`, returning the sealed form.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SecretTooLong`] if the sealed form does not fit into `buffer`.
+    #[expect(clippy::missing_panics_doc, reason = "does not panic")]
+    fn seal<'b>(
+        &self,
+        name: &str,
+        nonce: &[u8; NONCE_LEN],
+        secret: &[u8],
+        buffer: &'b mut [u8; MAX_SEALED_LEN],
+    ) -> Result<&'b [u8], Error> {
+        let sealed = buffer
+            .get_mut(..NONCE_LEN + secret.len() + TAG_LEN)
+            .ok_or(Error::SecretTooLong)?;
+        let (nonce_part, rest) = sealed.split_at_mut(NONCE_LEN);
+        let (ciphertext, tag) = rest.split_at_mut(secret.len());
+
+        nonce_part.copy_from_slice(nonce);
+        ciphertext.copy_from_slice(secret);
+        let computed_tag = self
+            .cipher()
+            .encrypt_in_place_detached(Nonce::from_slice(nonce), name.as_bytes(), ciphertext)
+            .expect("secrets are far below the length limit of ChaCha20-Poly1305");
+        tag.copy_from_slice(&computed_tag);
+        Ok(sealed)
+    }
+
+    /// Unseals the `sealed` form of the secret stored under `name`.
+    ///
+    /// Returns the secret, padded with zeros, and its length.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::LengthMismatch`] if the secret is longer than `N` bytes, and
+    /// [`Error::Unsealing`] if it could not be unsealed.
+    fn unseal<const N: usize>(
+        &self,
+        name: &str,
+        sealed: &[u8],
+    ) -> Result<(Secret<N>, usize), Error> {
+        let len = sealed
+            .len()
+            .checked_sub(NONCE_LEN + TAG_LEN)
+            .filter(|len| *len <= N)
+            .ok_or(Error::LengthMismatch)?;
+        let (nonce, rest) = sealed.split_at(NONCE_LEN);
+        let (ciphertext, tag) = rest.split_at(len);
+
+        // Decrypting right inside the `Secret` ensures that the plaintext is erased even if
+        // unsealing fails.
+        let mut secret = Secret::new([0; N]);
+        let plaintext = secret
+            .expose_mut()
+            .get_mut(..len)
+            .ok_or(Error::LengthMismatch)?;
+        plaintext.copy_from_slice(ciphertext);
+        self.cipher()
+            .decrypt_in_place_detached(
+                Nonce::from_slice(nonce),
+                name.as_bytes(),
+                plaintext,
+                Tag::from_slice(tag),
+            )
+            .map_err(|_| Error::Unsealing)?;
+        Ok((secret, len))
+    }
+}
+
+/// A handle to a named secret in the [`Vault`].
+///
+/// Obtain it through [`Vault::key()`].
+pub struct KeyHandle<'v> {
+    vault: &'v Vault,
+    name: &'v str,
+}
+
+impl KeyHandle<'_> {
+    /// Returns the name of the secret.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// Seals `secret` and persists it, replacing any previous secret of the same name.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SecretTooLong`] if `secret` is longer than [`MAX_SECRET_LEN`], and
+    /// [`Error::Storage`] if it could not be written to storage.
+    pub async fn store(&self, secret: &[u8]) -> Result<(), Error> {
+        let mut nonce = [0; NONCE_LEN];
+        ariel_os_random::crypto_rng().fill_bytes(&mut nonce);
+        let mut buffer = [0; MAX_SEALED_LEN];
+        let sealed = self.vault.seal(self.name, &nonce, secret, &mut buffer)?;
+
+        ariel_os_storage::insert_blob(&self.storage_key(), sealed)
+            .await
+            .map_err(|_| Error::Storage)?;
+        debug!("vault: stored secret {}", self.name);
+        Ok(())
+    }
+
+    /// Loads and unseals the secret.
+    ///
+    /// Returns `None` if no secret of this name has been stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::LengthMismatch`] if the stored secret is not `N` bytes long,
+    /// [`Error::Unsealing`] if it could not be unsealed, and [`Error::Storage`] if it could not be
+    /// read from storage.
+    pub async fn load<const N: usize>(&self) -> Result<Option<Secret<N>>, Error> {
+        match self.load_up_to::<N>().await? {
+            Some((secret, len)) if len == N => Ok(Some(secret)),
+            Some(_) => Err(Error::LengthMismatch),
+            None => Ok(None),
+        }
+    }
+
+    /// Loads and unseals a secret of variable length, eg. a password.
+    ///
+    /// Returns the secret, padded with zeros, and its length, or `None` if no secret of this name
+    /// has been stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::LengthMismatch`] if the stored secret is longer than `N` bytes,
+    /// [`Error::Unsealing`] if it could not be unsealed, and [`Error::Storage`] if it could not be
+    /// read from storage.
+    pub async fn load_up_to<const N: usize>(&self) -> Result<Option<(Secret<N>, usize)>, Error> {
+        let mut buffer = [0; MAX_SEALED_LEN];
+        let Some(sealed) = ariel_os_storage::get_blob(&self.storage_key(), &mut buffer)
+            .await
+            .map_err(|e| match e {
+                sequential_storage::Error::BufferTooSmall(_) => Error::LengthMismatch,
+                _ => Error::Storage,
+            })?
+        else {
+            return Ok(None);
+        };
+
+        self.vault.unseal(self.name, sealed).map(Some)
+    }
+
+    /// Generates a random secret of `N` bytes and persists it, replacing any previous secret of
+    /// the same name.
+    ///
+    /// This is useful for secrets that originate on the device and never need to leave it in
+    /// plaintext.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`KeyHandle::store()`].
+    pub async fn generate<const N: usize>(&self) -> Result<Secret<N>, Error> {
+        let mut secret = Secret::new([0; N]);
+        ariel_os_random::crypto_rng().fill_bytes(secret.expose_mut());
+        self.store(secret.expose()).await?;
+        Ok(secret)
+    }
+
+    /// Deletes the secret.
+    ///
+    /// Additional calls to [`KeyHandle::load()`] will return `None` until a new secret is stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Storage`] if the secret could not be removed from storage.
+    // STM32 flash drivers do not implement `MultiwriteNorFlash`.
+    #[cfg(not(context = "stm32"))]
+    pub async fn remove(&self) -> Result<(), Error> {
+        ariel_os_storage::remove(&self.storage_key())
+            .await
+            .map_err(|_| Error::Storage)
+    }
+
+    fn storage_key(&self) -> heapless::String<{ KEY_PREFIX.len() + MAX_NAME_LEN }> {
+        let mut key = heapless::String::new();
+        key.push_str(KEY_PREFIX)
+            .and_then(|()| key.push_str(self.name))
+            .expect("name length was checked when creating the handle");
+        key
+    }
+}
+
+/// A secret of `N` bytes loaded from the [`Vault`].
+///
+/// The secret is overwritten when this is dropped. Its [`Debug`](core::fmt::Debug) implementation
+/// does not show the secret.
+pub type Secret<const N: usize> = secretcore::Secret<[u8; N]>;
+
+/// Errors that can occur when working with the vault.
+#[derive(Debug)]
+pub enum Error {
+    /// Accessing the storage failed.
+    Storage,
+    /// The secret is longer than [`MAX_SECRET_LEN`].
+    SecretTooLong,
+    /// The stored secret has a different length than requested.
+    LengthMismatch,
+    /// The stored secret could not be unsealed, as it has been tampered with or was sealed on a
+    /// different device.
+    Unsealing,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Storage => write!(f, "storage access failed"),
+            Self::SecretTooLong => write!(f, "secret too long"),
+            Self::LengthMismatch => write!(f, "stored secret has a different length"),
+            Self::Unsealing => write!(f, "secret could not be unsealed"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use super::*;
+
+    const NONCE: [u8; NONCE_LEN] = [0x42; NONCE_LEN];
+
+    fn vault() -> Vault {
+        Vault {
+            wrapping_key: secretcore::Secret::new([0x17; 32]),
+        }
+    }
+
+    fn sealed<'b>(
+        vault: &Vault,
+        name: &str,
+        secret: &[u8],
+        buffer: &'b mut [u8; MAX_SEALED_LEN],
+    ) -> &'b [u8] {
+        vault.seal(name, &NONCE, secret, buffer).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let vault = vault();
+        let mut buffer = [0; MAX_SEALED_LEN];
+        let sealed = sealed(&vault, "appkey", b"secret", &mut buffer);
+        assert_eq!(sealed.len(), NONCE_LEN + 6 + TAG_LEN);
+        assert_ne!(sealed.get(NONCE_LEN..NONCE_LEN + 6), Some(&b"secret"[..]));
+
+        let (secret, len) = vault.unseal::<8>("appkey", sealed).unwrap();
+        assert_eq!(len, 6);
+        assert_eq!(secret.expose(), b"secret\0\0");
+    }
+
+    #[test]
+    fn other_name_fails() {
+        let vault = vault();
+        let mut buffer = [0; MAX_SEALED_LEN];
+        let sealed = sealed(&vault, "appkey", b"secret", &mut buffer);
+        assert!(matches!(
+            vault.unseal::<8>("netkey", sealed),
+            Err(Error::Unsealing)
+        ));
+    }
+
+    #[test]
+    fn tampering_is_rejected() {
+        let vault = vault();
+        let mut buffer = [0; MAX_SEALED_LEN];
+        let len = sealed(&vault, "appkey", b"secret", &mut buffer).len();
+        for i in 0..len {
+            let mut tampered = buffer;
+            *tampered.get_mut(i).unwrap() ^= 1;
+            assert!(matches!(
+                vault.unseal::<8>("appkey", tampered.get(..len).unwrap()),
+                Err(Error::Unsealing)
+            ));
+        }
+    }
+
+    #[test]
+    fn lengths_are_checked() {
+        let vault = vault();
+        let mut buffer = [0; MAX_SEALED_LEN];
+        assert!(matches!(
+            vault.seal("appkey", &NONCE, &[0; MAX_SECRET_LEN + 1], &mut buffer),
+            Err(Error::SecretTooLong)
+        ));
+        let sealed = sealed(&vault, "appkey", b"secret", &mut buffer);
+        assert!(matches!(
+            vault.unseal::<4>("appkey", sealed),
+            Err(Error::LengthMismatch)
+        ));
+    }
+}
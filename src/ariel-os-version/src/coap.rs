@@ -0,0 +1,128 @@
+//! Serves the versions of the running firmware as a CoAP resource.
+//!
+//! A GET request returns a CBOR map with the Content-Format `application/cbor`, whose text keys
+//! follow the fields of [`Versions`](crate::Versions):
+//!
+//! ```text
+//! {
+//!   "app": "coap-server", "app-version": "1.2.0",
+//!   "os": "Ariel OS", "os-version": "0.2.0",
+//!   "board": "nrf52840dk", "build": "3f2a9c1",
+//!   "confirmed": true
+//! }
+//! ```
+//!
+//! The `confirmed` entry is omitted if that is not known. Applications running their own CoAP
+//! server can add the resource to their handler:
+//!
+//! ```ignore
+//! let handler = new_dispatcher().at(&["version"], VersionResource::new());
+//! ```
+
+use coap_message::{
+    Code as _, MinimalWritableMessage, MutableWritableMessage, OptionNumber as _, ReadableMessage,
+};
+use coap_message_utils::{Error as CoAPError, OptionsExt as _};
+use minicbor::{Encoder, encode::write::Cursor};
+
+use crate::versions;
+
+/// CoAP Content-Format of `application/cbor`.
+const CONTENT_FORMAT_CBOR: u16 = 60;
+
+/// Maximum length of the encoded versions.
+const MAX_LEN: usize = 256;
+
+/// A CoAP resource that reports the [`Versions`](crate::Versions) of the running firmware.
+#[derive(Debug, Default)]
+pub struct VersionResource {
+    _private: (),
+}
+
+impl VersionResource {
+    /// Creates the resource.
+    #[must_use]
+    pub fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl coap_handler::Handler for VersionResource {
+    type RequestData = ();
+    type ExtractRequestError = CoAPError;
+    type BuildResponseError<M: MinimalWritableMessage> = CoAPError;
+
+    fn extract_request_data<M: ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, Self::ExtractRequestError> {
+        if request.code().into() != coap_numbers::code::GET {
+            return Err(CoAPError::method_not_allowed());
+        }
+        request.options().ignore_elective_others()?;
+        Ok(())
+    }
+
+    fn estimate_length(&mut self, (): &Self::RequestData) -> usize {
+        // Content-Format option and payload marker
+        MAX_LEN + 4
+    }
+
+    fn build_response<M: MutableWritableMessage>(
+        &mut self,
+        response: &mut M,
+        (): Self::RequestData,
+    ) -> Result<(), Self::BuildResponseError<M>> {
+        let mut buffer = [0; MAX_LEN];
+        let len = encode(&mut buffer).map_err(|_| CoAPError::internal_server_error())?;
+
+        response.set_code(
+            M::Code::new(coap_numbers::code::CONTENT).map_err(CoAPError::from_unionerror)?,
+        );
+        response
+            .add_option_uint(
+                M::OptionNumber::new(coap_numbers::option::CONTENT_FORMAT)
+                    .map_err(CoAPError::from_unionerror)?,
+                CONTENT_FORMAT_CBOR,
+            )
+            .map_err(CoAPError::from_unionerror)?;
+        response
+            .set_payload(
+                buffer
+                    .get(..len)
+                    .ok_or_else(CoAPError::internal_server_error)?,
+            )
+            .map_err(CoAPError::from_unionerror)?;
+        Ok(())
+    }
+}
+
+/// Encodes the versions into `buffer`, and returns the encoded length.
+///
+/// # Errors
+///
+/// Returns an error if the encoded versions do not fit into `buffer`.
+fn encode(
+    buffer: &mut [u8],
+) -> Result<usize, minicbor::encode::Error<minicbor::encode::write::EndOfSlice>> {
+    let versions = versions();
+    let mut encoder = Encoder::new(Cursor::new(buffer));
+    encoder
+        .map(6 + u64::from(versions.confirmed.is_some()))?
+        .str("app")?
+        .str(versions.app_name)?
+        .str("app-version")?
+        .str(versions.app_version)?
+        .str("os")?
+        .str(versions.os_name)?
+        .str("os-version")?
+        .str(versions.os_version)?
+        .str("board")?
+        .str(versions.board)?
+        .str("build")?
+        .str(versions.build_hash)?;
+    if let Some(confirmed) = versions.confirmed {
+        encoder.str("confirmed")?.bool(confirmed)?;
+    }
+    Ok(encoder.into_writer().position())
+}
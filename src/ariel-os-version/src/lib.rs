@@ -0,0 +1,66 @@
+//! Reports the versions of the running firmware.
+//!
+//! To inventory what is actually running on a fleet of devices, the [`Versions`] of the running
+//! firmware are available at runtime through [`versions()`], and exposed automatically:
+//!
+//! * as a CoAP resource at `/version` (see the [`coap`] module), unless the application runs
+//!   its own CoAP server, in which case it can add the resource to its handler;
+//! * in the USB product string, as the application name followed by its version.
+//!
+//! The application version and the build hash are configured through the `CONFIG_APP_VERSION`
+//! and `CONFIG_BUILD_HASH` environment variables (see [`ariel_os_buildinfo`]).
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+#[cfg(feature = "coap")]
+pub mod coap;
+
+/// The versions of the running firmware and its components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Versions {
+    /// The application name.
+    pub app_name: &'static str,
+    /// The application version.
+    pub app_version: &'static str,
+    /// The operating system's name.
+    pub os_name: &'static str,
+    /// The operating system's version.
+    pub os_version: &'static str,
+    /// The board the firmware was built for.
+    pub board: &'static str,
+    /// An identifier of the exact build.
+    pub build_hash: &'static str,
+    /// Whether the running firmware is confirmed, or `None` if that is not known (eg. without
+    /// firmware updates).
+    ///
+    /// A firmware that is not confirmed is on trial after an update, and is reverted unless it
+    /// confirms itself.
+    pub confirmed: Option<bool>,
+}
+
+/// Returns the versions of the running firmware.
+#[must_use]
+pub fn versions() -> Versions {
+    Versions {
+        app_name: ariel_os_buildinfo::APP_NAME,
+        app_version: ariel_os_buildinfo::APP_VERSION,
+        os_name: ariel_os_buildinfo::OS_NAME,
+        os_version: ariel_os_buildinfo::OS_VERSION,
+        board: ariel_os_buildinfo::BOARD,
+        build_hash: ariel_os_buildinfo::BUILD_HASH,
+        confirmed: confirmed(),
+    }
+}
+
+fn confirmed() -> Option<bool> {
+    #[cfg(feature = "update")]
+    {
+        ariel_os_update::is_confirmed()
+    }
+    #[cfg(not(feature = "update"))]
+    {
+        None
+    }
+}
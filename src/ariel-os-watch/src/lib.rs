@@ -0,0 +1,245 @@
+//! Provides watchpoint-style tracing of registered values, to watch state machines evolve without
+//! stopping the target.
+//!
+//! Values of interest, eg. atomics holding the state of a state machine, are registered with
+//! [`watch!`]:
+//!
+//! ```ignore
+//! use core::sync::atomic::{AtomicU8, AtomicU32, Ordering};
+//!
+//! use ariel_os::watch::watch;
+//!
+//! static CONNECTION_STATE: AtomicU8 = AtomicU8::new(0);
+//! watch!(CONNECTION_STATE);
+//!
+//! static RECEIVED_BYTES: AtomicU32 = AtomicU32::new(0);
+//! watch!("received-kib", || (RECEIVED_BYTES.load(Ordering::Relaxed) / 1024).into());
+//! ```
+//!
+//! The registered values are sampled periodically, and whenever [`sample_all()`] is called.
+//! Each change is logged at the info level as `watch <uptime in ms> #<index>=<value>`, and the
+//! name of each index is logged once at startup as `watch: #<index> is <name>`.
+//! With the `defmt` logging backend, each change is thereby only transmitted as a few bytes.
+//!
+//! Values are only sampled at these points in time: changes that are reverted between two samples
+//! are not seen.
+//!
+//! # Configuration
+//!
+//! - `CONFIG_WATCH_INTERVAL_MS` (default: 100): interval between samples, in milliseconds.
+
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+use core::{
+    cell::Cell,
+    sync::atomic::{self, Ordering},
+};
+
+use ariel_os_debug::log::info;
+use embassy_time::{Duration, Instant, Ticker};
+
+/// Interval between samples.
+const INTERVAL: Duration = Duration::from_millis(ariel_os_utils::u64_from_env_or!(
+    "CONFIG_WATCH_INTERVAL_MS",
+    100,
+    "interval between samples of the watched values, in milliseconds"
+));
+
+/// The watchpoints registered through [`watch!`].
+#[linkme::distributed_slice]
+pub static WATCHPOINTS: [Watchpoint] = [..];
+
+/// A value that can be watched.
+///
+/// This is implemented for the atomic integer types and [`AtomicBool`](atomic::AtomicBool), and
+/// by [`Probe`], which samples any value through a function.
+pub trait Sample: Sync {
+    /// Returns the current value.
+    fn sample(&self) -> i64;
+}
+
+impl Sample for atomic::AtomicBool {
+    fn sample(&self) -> i64 {
+        self.load(Ordering::Relaxed).into()
+    }
+}
+
+macro_rules! impl_sample_lossless {
+    ($($atomic:ident),*) => {
+        $(
+            impl Sample for atomic::$atomic {
+                fn sample(&self) -> i64 {
+                    self.load(Ordering::Relaxed).into()
+                }
+            }
+        )*
+    };
+}
+
+impl_sample_lossless!(
+    AtomicU8, AtomicI8, AtomicU16, AtomicI16, AtomicU32, AtomicI32
+);
+
+macro_rules! impl_sample_saturating {
+    ($($atomic:ident),*) => {
+        $(
+            impl Sample for atomic::$atomic {
+                fn sample(&self) -> i64 {
+                    // Values outside of the `i64` range saturate.
+                    i64::try_from(self.load(Ordering::Relaxed)).unwrap_or(i64::MAX)
+                }
+            }
+        )*
+    };
+}
+
+impl_sample_saturating!(AtomicUsize, AtomicIsize);
+#[cfg(target_has_atomic = "64")]
+impl_sample_saturating!(AtomicU64);
+
+#[cfg(target_has_atomic = "64")]
+impl Sample for atomic::AtomicI64 {
+    fn sample(&self) -> i64 {
+        self.load(Ordering::Relaxed)
+    }
+}
+
+/// Samples a value through a function, eg. to watch a value behind a mutex.
+#[derive(Debug, Clone, Copy)]
+pub struct Probe(fn() -> i64);
+
+impl Probe {
+    /// Creates a probe sampling the value returned by `probe`.
+    ///
+    /// `probe` is called from the sampling task, and should not block.
+    #[must_use]
+    pub const fn new(probe: fn() -> i64) -> Self {
+        Self(probe)
+    }
+}
+
+impl Sample for Probe {
+    fn sample(&self) -> i64 {
+        (self.0)()
+    }
+}
+
+/// A value registered with [`watch!`].
+pub struct Watchpoint {
+    name: &'static str,
+    value: &'static dyn Sample,
+    last: critical_section::Mutex<Cell<Option<i64>>>,
+}
+
+impl Watchpoint {
+    #[doc(hidden)]
+    #[must_use]
+    pub const fn new(name: &'static str, value: &'static dyn Sample) -> Self {
+        Self {
+            name,
+            value,
+            last: critical_section::Mutex::new(Cell::new(None)),
+        }
+    }
+
+    /// Returns the name of the watchpoint.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the current value.
+    #[must_use]
+    pub fn value(&self) -> i64 {
+        self.value.sample()
+    }
+
+    /// Samples the value, and returns it if it changed since it was last sampled.
+    fn changed(&self) -> Option<i64> {
+        let value = self.value.sample();
+        let last = critical_section::with(|cs| self.last.borrow(cs).replace(Some(value)));
+        (last != Some(value)).then_some(value)
+    }
+}
+
+impl core::fmt::Debug for Watchpoint {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Watchpoint")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Registers a value to be watched.
+///
+/// A `static` implementing [`Sample`], eg. an atomic, is watched under its own name:
+///
+/// ```ignore
+/// static STATE: AtomicU8 = AtomicU8::new(0);
+/// ariel_os::watch::watch!(STATE);
+/// ```
+///
+/// Any other value is watched under the given name through a [`Probe`] function:
+///
+/// ```ignore
+/// ariel_os::watch::watch!("heap-used", || ariel_os::alloc::stats().used as i64);
+/// ```
+#[macro_export]
+macro_rules! watch {
+    ($value:path) => {
+        const _: () = {
+            #[$crate::macro_reexports::linkme::distributed_slice($crate::WATCHPOINTS)]
+            #[linkme(crate = $crate::macro_reexports::linkme)]
+            static WATCHPOINT: $crate::Watchpoint =
+                $crate::Watchpoint::new(stringify!($value), &$value);
+        };
+    };
+    ($name:literal, $probe:expr) => {
+        const _: () = {
+            static PROBE: $crate::Probe = $crate::Probe::new($probe);
+
+            #[$crate::macro_reexports::linkme::distributed_slice($crate::WATCHPOINTS)]
+            #[linkme(crate = $crate::macro_reexports::linkme)]
+            static WATCHPOINT: $crate::Watchpoint = $crate::Watchpoint::new($name, &PROBE);
+        };
+    };
+}
+
+#[doc(hidden)]
+pub mod macro_reexports {
+    // Used by `watch`
+    pub use linkme;
+}
+
+/// Samples all watched values now, and logs those that changed since they were last sampled.
+///
+/// This can be called at points of interest, eg. after a state transition, in addition to the
+/// periodic samples.
+pub fn sample_all() {
+    let now = Instant::now().as_millis();
+    for (index, watchpoint) in WATCHPOINTS.iter().enumerate() {
+        if let Some(value) = watchpoint.changed() {
+            info!("watch {} #{}={}", now, index, value);
+        }
+    }
+}
+
+/// Logs the names of the watchpoints, and samples them periodically.
+#[ariel_os_macros::task(autostart)]
+async fn watch() {
+    if WATCHPOINTS.is_empty() {
+        return;
+    }
+
+    for (index, watchpoint) in WATCHPOINTS.iter().enumerate() {
+        info!("watch: #{} is {}", index, watchpoint.name());
+    }
+
+    let mut ticker = Ticker::every(INTERVAL);
+    loop {
+        sample_all();
+        ticker.next().await;
+    }
+}
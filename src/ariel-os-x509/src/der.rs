@@ -0,0 +1,254 @@
+//! Minimal DER decoder for the structures of X.509 certificates.
+//!
+//! Only definite lengths of up to 64 KiB are supported, which covers any certificate that fits
+//! into the memory of a constrained device; this is not a general purpose ASN.1 library.
+
+use crate::Error;
+
+pub(crate) const TAG_BOOLEAN: u8 = 0x01;
+pub(crate) const TAG_INTEGER: u8 = 0x02;
+pub(crate) const TAG_BIT_STRING: u8 = 0x03;
+pub(crate) const TAG_OCTET_STRING: u8 = 0x04;
+pub(crate) const TAG_OID: u8 = 0x06;
+pub(crate) const TAG_UTC_TIME: u8 = 0x17;
+pub(crate) const TAG_GENERALIZED_TIME: u8 = 0x18;
+pub(crate) const TAG_SEQUENCE: u8 = 0x30;
+
+/// Reads consecutive TLVs from a byte slice.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the tag of the next TLV without consuming it.
+    pub(crate) fn peek_tag(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+
+    /// Reads the next TLV, returning its tag, its content and the complete encoded TLV.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Malformed`] if there is no complete TLV, or it uses an unsupported length.
+    pub(crate) fn read_any(&mut self) -> Result<(u8, &'a [u8], &'a [u8]), Error> {
+        let (&tag, rest) = self.data.split_first().ok_or(Error::Malformed)?;
+        let (&first, rest) = rest.split_first().ok_or(Error::Malformed)?;
+        let (len, rest) = match first {
+            0..=0x7f => (usize::from(first), rest),
+            0x81 => {
+                let (&len, rest) = rest.split_first().ok_or(Error::Malformed)?;
+                (usize::from(len), rest)
+            }
+            0x82 => {
+                let (len, rest) = rest.split_at_checked(2).ok_or(Error::Malformed)?;
+                let len = u16::from_be_bytes(len.try_into().map_err(|_| Error::Malformed)?);
+                (usize::from(len), rest)
+            }
+            _ => return Err(Error::Malformed),
+        };
+        let (content, rest) = rest.split_at_checked(len).ok_or(Error::Malformed)?;
+        let header_len = self.data.len() - rest.len() - len;
+        let whole = self.data.get(..header_len + len).ok_or(Error::Malformed)?;
+        self.data = rest;
+        Ok((tag, content, whole))
+    }
+
+    /// Reads the next TLV, which needs to have the given tag, and returns its content.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Malformed`] if the next TLV is missing or has a different tag.
+    pub(crate) fn read(&mut self, tag: u8) -> Result<&'a [u8], Error> {
+        match self.read_any()? {
+            (found, content, _) if found == tag => Ok(content),
+            _ => Err(Error::Malformed),
+        }
+    }
+
+    /// Reads the next TLV if it has the given tag, and returns its content.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Malformed`] if the next TLV has the given tag, but is not complete.
+    pub(crate) fn read_optional(&mut self, tag: u8) -> Result<Option<&'a [u8]>, Error> {
+        if self.peek_tag() == Some(tag) {
+            self.read(tag).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Reads a BIT STRING that has no unused bits, and returns its content without the unused
+    /// bits indicator.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Malformed`] if the next TLV is not such a BIT STRING.
+    pub(crate) fn read_bit_string(&mut self) -> Result<&'a [u8], Error> {
+        match self.read(TAG_BIT_STRING)?.split_first() {
+            Some((0, bits)) => Ok(bits),
+            _ => Err(Error::Malformed),
+        }
+    }
+
+    /// Reads a `UTCTime` or `GeneralizedTime`, and returns it as seconds since the Unix epoch.
+    ///
+    /// Times before the epoch are returned as 0.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Malformed`] if the next TLV is not a valid time in UTC.
+    pub(crate) fn read_time(&mut self) -> Result<u64, Error> {
+        let (tag, content, _) = self.read_any()?;
+        let (year, rest) = match tag {
+            TAG_UTC_TIME if content.len() == 13 => {
+                let (year, rest) = content.split_at(2);
+                let year = digits(year)?;
+                // As specified in RFC5280 Section 4.1.2.5.1
+                (if year >= 50 { 1900 + year } else { 2000 + year }, rest)
+            }
+            TAG_GENERALIZED_TIME if content.len() == 15 => {
+                let (year, rest) = content.split_at(4);
+                (digits(year)?, rest)
+            }
+            _ => return Err(Error::Malformed),
+        };
+        // Month, day, hour, minute and second, each as two digits, and the time zone
+        let mut fields = [0; 5];
+        let mut rest = rest;
+        for field in &mut fields {
+            let (value, remaining) = rest.split_at_checked(2).ok_or(Error::Malformed)?;
+            *field = u8::try_from(digits(value)?).map_err(|_| Error::Malformed)?;
+            rest = remaining;
+        }
+        if rest != b"Z" {
+            return Err(Error::Malformed);
+        }
+        let [month, day, hour, minute, second] = fields;
+        if !(1..=12).contains(&month)
+            || !(1..=31).contains(&day)
+            || hour > 23
+            || minute > 59
+            || second > 59
+        {
+            return Err(Error::Malformed);
+        }
+
+        let days = days_from_civil(i64::from(year), month, day);
+        let seconds =
+            days * 86400 + i64::from(hour) * 3600 + i64::from(minute) * 60 + i64::from(second);
+        Ok(u64::try_from(seconds).unwrap_or(0))
+    }
+}
+
+/// Parses the content of a non-negative INTEGER, saturating at [`u8::MAX`].
+///
+/// # Errors
+///
+/// Returns [`Error::Malformed`] if the integer is empty, negative or not minimally encoded.
+pub(crate) fn unsigned_saturating(integer: &[u8]) -> Result<u8, Error> {
+    match integer {
+        [] => Err(Error::Malformed),
+        [first, ..] if first & 0x80 != 0 => Err(Error::Malformed),
+        [0, second, ..] if second & 0x80 == 0 => Err(Error::Malformed),
+        // The leading zero byte keeps a value with the highest bit set positive.
+        [0, value] | [value] => Ok(*value),
+        _ => Ok(u8::MAX),
+    }
+}
+
+/// Parses a string of ASCII digits.
+///
+/// # Errors
+///
+/// Returns [`Error::Malformed`] on characters other than digits.
+fn digits(text: &[u8]) -> Result<u32, Error> {
+    text.iter().try_fold(0_u32, |value, digit| match digit {
+        b'0'..=b'9' => Ok(value * 10 + u32::from(digit - b'0')),
+        _ => Err(Error::Malformed),
+    })
+}
+
+/// Returns the number of days between 1970-01-01 and the given date of the proleptic Gregorian
+/// calendar.
+// Algorithm from <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (i64::from(month) + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Converts a DER encoded `ECDSA-Sig-Value` into the fixed size `r || s` form.
+///
+/// # Errors
+///
+/// Returns [`Error::Malformed`] if `der` is not an `ECDSA-Sig-Value` with P-256 sized integers.
+pub(crate) fn ecdsa_signature(der: &[u8]) -> Result<[u8; 64], Error> {
+    let mut outer = Reader::new(der);
+    let mut sequence = Reader::new(outer.read(TAG_SEQUENCE)?);
+    if !outer.is_empty() {
+        return Err(Error::Malformed);
+    }
+
+    let mut signature = [0; 64];
+    let (r, s) = signature.split_at_mut(32);
+    for target in [r, s] {
+        let mut integer = sequence.read(TAG_INTEGER)?;
+        // Strip the sign byte
+        if let Some((0, rest)) = integer.split_first() {
+            integer = rest;
+        }
+        let padding = target
+            .len()
+            .checked_sub(integer.len())
+            .ok_or(Error::Malformed)?;
+        let (_, value) = target.split_at_mut(padding);
+        value.copy_from_slice(integer);
+    }
+    if !sequence.is_empty() {
+        return Err(Error::Malformed);
+    }
+    Ok(signature)
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsigned_integers() {
+        assert_eq!(unsigned_saturating(&[]), Err(Error::Malformed));
+        assert_eq!(unsigned_saturating(&[0x00]), Ok(0));
+        assert_eq!(unsigned_saturating(&[0x05]), Ok(5));
+        assert_eq!(unsigned_saturating(&[0x00, 0x80]), Ok(0x80));
+        assert_eq!(unsigned_saturating(&[0x01, 0x2c]), Ok(u8::MAX));
+        // Negative
+        assert_eq!(unsigned_saturating(&[0x80]), Err(Error::Malformed));
+        // Not minimally encoded
+        assert_eq!(unsigned_saturating(&[0x00, 0x05]), Err(Error::Malformed));
+    }
+
+    #[test]
+    fn times() {
+        let mut reader = Reader::new(b"\x17\x0d491231235959Z\x18\x0f20500101000000Z");
+        assert_eq!(reader.read_time(), Ok(2_524_607_999));
+        assert_eq!(reader.read_time(), Ok(2_524_608_000));
+        assert_eq!(
+            Reader::new(b"\x17\x0d491331235959Z").read_time(),
+            Err(Error::Malformed)
+        );
+    }
+}
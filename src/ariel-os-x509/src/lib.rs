@@ -0,0 +1,714 @@
+//! Provides minimal X.509 certificate parsing and validation.
+//!
+//! Certificates are parsed from their DER encoding without copying through
+//! [`Certificate::from_der()`]. A certificate presented by a peer (eg. the server of a TLS
+//! connection, or an EDHOC peer using certificates) is validated with [`verify_chain()`], which
+//! checks that there is a path of valid signatures from the certificate through the provided
+//! intermediate certificates to one of the trust anchors, and that all certificates on the path
+//! are valid at the current time. The peer's identity can then be checked against the certificate's
+//! subject alternative names through [`Certificate::matches_dns_name()`] and
+//! [`Certificate::matches_ip_address()`].
+//!
+//! ```ignore
+//! let leaf = Certificate::from_der(&received[0])?;
+//! let intermediate = Certificate::from_der(&received[1])?;
+//! let root = Certificate::from_der(ROOT_CA)?;
+//! verify_chain(&leaf, &[intermediate], &[root], now)?;
+//! if !leaf.matches_dns_name("example.com") {
+//!     return Err(...);
+//! }
+//! ```
+//!
+//! The current time is provided by the caller, in seconds since the Unix epoch. With the `calendar`
+//! feature, [`verify_chain_now()`] takes it from the wall clock of [`ariel_os_calendar`] instead,
+//! which the application needs to set once it knows the time (eg. from a real time clock or
+//! obtained through the network).
+//!
+//! With the `storage` feature, trust anchors can be kept in [storage](ariel_os_storage), see the
+//! [`trust_anchors`] module.
+//!
+//! # Limitations
+//!
+//! Only certificates with ECDSA P-256 keys that are signed using ECDSA with SHA-256 are supported.
+//! Of the certificate extensions, basic constraints, key usage and subject alternative names are
+//! processed; certificates with other critical extensions are rejected. Revocation is not checked.
+#![no_std]
+#![cfg_attr(nightly, feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+mod der;
+#[cfg(feature = "storage")]
+pub mod trust_anchors;
+
+use der::{
+    Reader, TAG_BOOLEAN, TAG_INTEGER, TAG_OCTET_STRING, TAG_OID, TAG_SEQUENCE, ecdsa_signature,
+};
+use p256::ecdsa::{Signature, VerifyingKey, signature::Verifier};
+
+/// Length of a serialized public key, see [`Certificate::public_key()`].
+pub const PUBLIC_KEY_LEN: usize = 65;
+
+/// Maximum number of intermediate certificates between a certificate and its trust anchor.
+pub const MAX_PATH_LEN: usize = 4;
+
+/// `AlgorithmIdentifier` content for `ecdsa-with-SHA256`.
+const ECDSA_WITH_SHA256: [u8; 10] = [0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+/// `AlgorithmIdentifier` content for `id-ecPublicKey` with `prime256v1`.
+const EC_PUBLIC_KEY_P256: [u8; 19] = [
+    0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d,
+    0x03, 0x01, 0x07,
+];
+
+/// OID of the basic constraints extension (2.5.29.19).
+const OID_BASIC_CONSTRAINTS: [u8; 3] = [0x55, 0x1d, 0x13];
+/// OID of the key usage extension (2.5.29.15).
+const OID_KEY_USAGE: [u8; 3] = [0x55, 0x1d, 0x0f];
+/// OID of the subject alternative name extension (2.5.29.17).
+const OID_SUBJECT_ALT_NAME: [u8; 3] = [0x55, 0x1d, 0x11];
+
+/// Tag of the explicit `version` field of a `TBSCertificate`.
+const TAG_VERSION: u8 = 0xa0;
+/// Tag of the `issuerUniqueID` field of a `TBSCertificate`.
+const TAG_ISSUER_UNIQUE_ID: u8 = 0x81;
+/// Tag of the `subjectUniqueID` field of a `TBSCertificate`.
+const TAG_SUBJECT_UNIQUE_ID: u8 = 0x82;
+/// Tag of the explicit `extensions` field of a `TBSCertificate`.
+const TAG_EXTENSIONS: u8 = 0xa3;
+/// Tag of a `dNSName` in `GeneralNames`.
+const TAG_DNS_NAME: u8 = 0x82;
+/// Tag of an `iPAddress` in `GeneralNames`.
+const TAG_IP_ADDRESS: u8 = 0x87;
+
+/// Mask of the `keyCertSign` bit in the first byte of the key usage bits.
+const KEY_USAGE_KEY_CERT_SIGN: u8 = 0x04;
+
+/// A parsed X.509 certificate.
+///
+/// The certificate borrows from its DER encoding.
+#[derive(Debug, Clone)]
+pub struct Certificate<'a> {
+    tbs: &'a [u8],
+    serial_number: &'a [u8],
+    issuer: &'a [u8],
+    subject: &'a [u8],
+    not_before: u64,
+    not_after: u64,
+    public_key: &'a [u8; PUBLIC_KEY_LEN],
+    is_ca: bool,
+    path_len_constraint: Option<u8>,
+    may_sign_certificates: bool,
+    subject_alt_names: &'a [u8],
+    signature: [u8; 64],
+}
+
+impl<'a> Certificate<'a> {
+    /// Parses a DER encoded certificate.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Malformed`] if `der` is not a well-formed certificate,
+    /// [`Error::UnsupportedAlgorithm`] if it uses algorithms other than ECDSA with P-256 and
+    /// SHA-256, and [`Error::UnsupportedCriticalExtension`] if it has critical extensions that are
+    /// not processed by this crate.
+    pub fn from_der(der: &'a [u8]) -> Result<Self, Error> {
+        let mut outer = Reader::new(der);
+        let mut certificate = Reader::new(outer.read(TAG_SEQUENCE)?);
+        if !outer.is_empty() {
+            return Err(Error::Malformed);
+        }
+
+        let (tag, tbs_content, tbs) = certificate.read_any()?;
+        if tag != TAG_SEQUENCE {
+            return Err(Error::Malformed);
+        }
+        if certificate.read(TAG_SEQUENCE)? != ECDSA_WITH_SHA256 {
+            return Err(Error::UnsupportedAlgorithm);
+        }
+        let signature = ecdsa_signature(certificate.read_bit_string()?)?;
+        if !certificate.is_empty() {
+            return Err(Error::Malformed);
+        }
+
+        let mut tbs_reader = Reader::new(tbs_content);
+        // Any version is accepted; extensions are processed if present.
+        tbs_reader.read_optional(TAG_VERSION)?;
+        let serial_number = tbs_reader.read(TAG_INTEGER)?;
+        if tbs_reader.read(TAG_SEQUENCE)? != ECDSA_WITH_SHA256 {
+            return Err(Error::UnsupportedAlgorithm);
+        }
+        let (_, _, issuer) = tbs_reader.read_any()?;
+        let mut validity = Reader::new(tbs_reader.read(TAG_SEQUENCE)?);
+        let not_before = validity.read_time()?;
+        let not_after = validity.read_time()?;
+        let (_, _, subject) = tbs_reader.read_any()?;
+
+        let mut spki = Reader::new(tbs_reader.read(TAG_SEQUENCE)?);
+        if spki.read(TAG_SEQUENCE)? != EC_PUBLIC_KEY_P256 {
+            return Err(Error::UnsupportedAlgorithm);
+        }
+        let public_key = spki
+            .read_bit_string()?
+            .try_into()
+            .map_err(|_| Error::UnsupportedAlgorithm)?;
+
+        tbs_reader.read_optional(TAG_ISSUER_UNIQUE_ID)?;
+        tbs_reader.read_optional(TAG_SUBJECT_UNIQUE_ID)?;
+
+        let mut parsed = Self {
+            tbs,
+            serial_number,
+            issuer,
+            subject,
+            not_before,
+            not_after,
+            public_key,
+            is_ca: false,
+            path_len_constraint: None,
+            // Without a key usage extension, the key is not restricted.
+            may_sign_certificates: true,
+            subject_alt_names: &[],
+            signature,
+        };
+        if let Some(extensions) = tbs_reader.read_optional(TAG_EXTENSIONS)? {
+            parsed.process_extensions(extensions)?;
+        }
+        if !tbs_reader.is_empty() {
+            return Err(Error::Malformed);
+        }
+
+        Ok(parsed)
+    }
+
+    /// Processes the `extensions` field of the `TBSCertificate`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Malformed`] if the extensions are not well-formed, and
+    /// [`Error::UnsupportedCriticalExtension`] on critical extensions that are not processed.
+    fn process_extensions(&mut self, extensions: &'a [u8]) -> Result<(), Error> {
+        let mut outer = Reader::new(extensions);
+        let mut extensions = Reader::new(outer.read(TAG_SEQUENCE)?);
+        while !extensions.is_empty() {
+            let mut extension = Reader::new(extensions.read(TAG_SEQUENCE)?);
+            let id = extension.read(TAG_OID)?;
+            let critical = extension
+                .read_optional(TAG_BOOLEAN)?
+                .is_some_and(|value| value != [0]);
+            let mut value = Reader::new(extension.read(TAG_OCTET_STRING)?);
+
+            match id {
+                id if id == OID_BASIC_CONSTRAINTS => {
+                    let mut constraints = Reader::new(value.read(TAG_SEQUENCE)?);
+                    self.is_ca = constraints
+                        .read_optional(TAG_BOOLEAN)?
+                        .is_some_and(|value| value != [0]);
+                    self.path_len_constraint = constraints
+                        .read_optional(TAG_INTEGER)?
+                        .map(der::unsigned_saturating)
+                        .transpose()?;
+                }
+                id if id == OID_KEY_USAGE => {
+                    let (_unused_bits, bits) = value
+                        .read(der::TAG_BIT_STRING)?
+                        .split_first()
+                        .ok_or(Error::Malformed)?;
+                    self.may_sign_certificates = bits
+                        .first()
+                        .is_some_and(|bits| bits & KEY_USAGE_KEY_CERT_SIGN != 0);
+                }
+                id if id == OID_SUBJECT_ALT_NAME => {
+                    self.subject_alt_names = value.read(TAG_SEQUENCE)?;
+                }
+                _ if critical => return Err(Error::UnsupportedCriticalExtension),
+                _ => continue,
+            }
+            if !value.is_empty() {
+                return Err(Error::Malformed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the certificate's serial number, as the content of a DER integer.
+    #[must_use]
+    pub fn serial_number(&self) -> &'a [u8] {
+        self.serial_number
+    }
+
+    /// Returns the DER encoded name of the issuer.
+    #[must_use]
+    pub fn issuer(&self) -> &'a [u8] {
+        self.issuer
+    }
+
+    /// Returns the DER encoded name of the subject.
+    #[must_use]
+    pub fn subject(&self) -> &'a [u8] {
+        self.subject
+    }
+
+    /// Returns the start and end of the validity period, in seconds since the Unix epoch.
+    #[must_use]
+    pub fn validity(&self) -> (u64, u64) {
+        (self.not_before, self.not_after)
+    }
+
+    /// Returns the subject's public key as an uncompressed SEC1 encoded point
+    /// (`0x04 || x || y`).
+    #[must_use]
+    pub fn public_key(&self) -> &'a [u8; PUBLIC_KEY_LEN] {
+        self.public_key
+    }
+
+    /// Returns whether the certificate is for a certification authority.
+    #[must_use]
+    pub fn is_ca(&self) -> bool {
+        self.is_ca
+    }
+
+    /// Returns whether `name` matches one of the certificate's DNS subject alternative names.
+    ///
+    /// Names are compared case-insensitively; a wildcard `*` is supported as the complete leftmost
+    /// label of a name in the certificate. The subject's common name is not considered.
+    #[must_use]
+    pub fn matches_dns_name(&self, name: &str) -> bool {
+        self.alt_names(TAG_DNS_NAME)
+            .any(|pattern| dns_name_matches(pattern, name.as_bytes()))
+    }
+
+    /// Returns whether `address` (4 bytes for IPv4, 16 bytes for IPv6) matches one of the
+    /// certificate's IP address subject alternative names.
+    #[must_use]
+    pub fn matches_ip_address(&self, address: &[u8]) -> bool {
+        self.alt_names(TAG_IP_ADDRESS).any(|found| found == address)
+    }
+
+    /// Iterates over the subject alternative names of the given kind.
+    fn alt_names(&self, tag: u8) -> impl Iterator<Item = &'a [u8]> {
+        let mut names = Reader::new(self.subject_alt_names);
+        core::iter::from_fn(move || names.read_any().ok())
+            .filter_map(move |(found, content, _)| (found == tag).then_some(content))
+    }
+
+    /// Returns whether the certificate is valid at `now` (in seconds since the Unix epoch).
+    #[must_use]
+    pub fn is_valid_at(&self, now: u64) -> bool {
+        (self.not_before..=self.not_after).contains(&now)
+    }
+
+    /// Checks that this certificate was issued by `issuer`.
+    ///
+    /// This checks the names and the signature, but not whether `issuer` is allowed to issue
+    /// certificates.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnknownIssuer`] if the certificate's issuer is not the subject of `issuer`,
+    /// and [`Error::BadSignature`] if the signature does not verify.
+    pub fn verify_issued_by(&self, issuer: &Certificate<'_>) -> Result<(), Error> {
+        if self.issuer != issuer.subject {
+            return Err(Error::UnknownIssuer);
+        }
+        let key = VerifyingKey::from_sec1_bytes(issuer.public_key)
+            .map_err(|_| Error::UnsupportedAlgorithm)?;
+        let signature = Signature::from_slice(&self.signature).map_err(|_| Error::Malformed)?;
+        key.verify(self.tbs, &signature)
+            .map_err(|_| Error::BadSignature)
+    }
+
+    /// Returns whether the certificate is self-issued.
+    fn is_self_issued(&self) -> bool {
+        self.issuer == self.subject
+    }
+}
+
+/// Validates the path from `leaf` through `intermediates` to `trust_anchor`.
+///
+/// Intermediates may be given in any order, and may include certificates that are not on the
+/// path; when several intermediates could have issued a certificate (eg. after a key rollover),
+/// each of them is tried. Self-issued certificates among them (eg. a copy of the root certificate
+/// sent by a TLS server) are ignored.
+///
+/// The trust anchor is given as a certificate (typically a self-signed root certificate); only
+/// its subject and public key are used.
+///
+/// # Errors
+///
+/// Returns [`Error::OutsideValidity`] if a certificate on the path is not valid at `now`,
+/// [`Error::NotCa`] if an intermediate is not allowed to issue certificates,
+/// [`Error::PathTooLong`] if a path length constraint is violated or the path is longer than
+/// [`MAX_PATH_LEN`], [`Error::BadSignature`] if a signature does not verify, and
+/// [`Error::UnknownIssuer`] if there is no path to the trust anchor at all. If several paths were
+/// tried, the error of the first one that failed for another reason than a missing issuer is
+/// returned.
+pub fn verify_path(
+    leaf: &Certificate<'_>,
+    intermediates: &[Certificate<'_>],
+    trust_anchor: &Certificate<'_>,
+    now: u64,
+) -> Result<(), Error> {
+    verify_path_from(leaf, intermediates, trust_anchor, now, 0)
+}
+
+/// Validates the path from `current`, which has `depth` intermediates below it, to
+/// `trust_anchor`, see [`verify_path()`].
+///
+/// # Errors
+///
+/// See [`verify_path()`].
+fn verify_path_from(
+    current: &Certificate<'_>,
+    intermediates: &[Certificate<'_>],
+    trust_anchor: &Certificate<'_>,
+    now: u64,
+    depth: usize,
+) -> Result<(), Error> {
+    if !current.is_valid_at(now) {
+        return Err(Error::OutsideValidity);
+    }
+    let mut result = current.verify_issued_by(trust_anchor);
+    if result.is_ok() {
+        return result;
+    }
+    for issuer in intermediates
+        .iter()
+        .filter(|candidate| candidate.subject == current.issuer && !candidate.is_self_issued())
+    {
+        let attempt = if depth >= MAX_PATH_LEN {
+            Err(Error::PathTooLong)
+        } else {
+            check_may_issue(issuer, depth)
+                .and_then(|()| current.verify_issued_by(issuer))
+                .and_then(|()| {
+                    verify_path_from(issuer, intermediates, trust_anchor, now, depth + 1)
+                })
+        };
+        if attempt.is_ok() {
+            return attempt;
+        }
+        if result == Err(Error::UnknownIssuer) {
+            result = attempt;
+        }
+    }
+    result
+}
+
+/// Validates `leaf` against the `trust_anchors`, see [`verify_path()`] for details.
+///
+/// # Errors
+///
+/// Returns [`Error::UnknownIssuer`] if there is no path to any of the trust anchors, and
+/// otherwise errors like [`verify_path()`].
+pub fn verify_chain(
+    leaf: &Certificate<'_>,
+    intermediates: &[Certificate<'_>],
+    trust_anchors: &[Certificate<'_>],
+    now: u64,
+) -> Result<(), Error> {
+    let mut result = Err(Error::UnknownIssuer);
+    for anchor in trust_anchors {
+        let attempt = verify_path(leaf, intermediates, anchor, now);
+        if attempt.is_ok() {
+            return attempt;
+        }
+        if result == Err(Error::UnknownIssuer) {
+            result = attempt;
+        }
+    }
+    result
+}
+
+/// Validates `leaf` against the `trust_anchors` at the current time of the wall clock, see
+/// [`verify_chain()`].
+///
+/// # Errors
+///
+/// Returns [`Error::ClockNotSet`] if the wall clock has not been set (see
+/// [`ariel_os_calendar::set_now()`]), and otherwise errors like [`verify_chain()`].
+#[cfg(feature = "calendar")]
+pub fn verify_chain_now(
+    leaf: &Certificate<'_>,
+    intermediates: &[Certificate<'_>],
+    trust_anchors: &[Certificate<'_>],
+) -> Result<(), Error> {
+    let now = ariel_os_calendar::now().ok_or(Error::ClockNotSet)?;
+    verify_chain(leaf, intermediates, trust_anchors, now)
+}
+
+/// Checks whether `issuer` may issue a certificate that has `depth` intermediate certificates
+/// below it.
+///
+/// # Errors
+///
+/// Returns [`Error::NotCa`] if `issuer` may not issue certificates at all, and
+/// [`Error::PathTooLong`] if its path length constraint is violated.
+fn check_may_issue(issuer: &Certificate<'_>, depth: usize) -> Result<(), Error> {
+    if !issuer.is_ca || !issuer.may_sign_certificates {
+        return Err(Error::NotCa);
+    }
+    if issuer
+        .path_len_constraint
+        .is_some_and(|max| depth > usize::from(max))
+    {
+        return Err(Error::PathTooLong);
+    }
+    Ok(())
+}
+
+/// Compares a DNS name from a certificate, which may have a wildcard label, to a name.
+fn dns_name_matches(pattern: &[u8], name: &[u8]) -> bool {
+    let name = name.strip_suffix(b".").unwrap_or(name);
+    match pattern.strip_prefix(b"*.") {
+        // The wildcard stands for exactly one non-empty label.
+        Some(suffix) => {
+            let mut labels = name.splitn(2, |byte| *byte == b'.');
+            match (labels.next(), labels.next()) {
+                (Some(label), Some(rest)) => !label.is_empty() && rest.eq_ignore_ascii_case(suffix),
+                _ => false,
+            }
+        }
+        None => pattern.eq_ignore_ascii_case(name),
+    }
+}
+
+/// Errors that can occur when parsing or validating certificates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The certificate is not well-formed.
+    Malformed,
+    /// The certificate uses a key type or signature algorithm that is not supported.
+    UnsupportedAlgorithm,
+    /// The certificate has a critical extension that is not supported.
+    UnsupportedCriticalExtension,
+    /// A certificate is not valid at the current time.
+    OutsideValidity,
+    /// No issuer was found for a certificate.
+    UnknownIssuer,
+    /// A certificate was issued by a certificate that may not issue certificates.
+    NotCa,
+    /// The path to the trust anchor is too long.
+    PathTooLong,
+    /// A signature did not verify.
+    BadSignature,
+    /// Accessing the storage failed.
+    Storage,
+    /// The provided buffer is too small.
+    BufferTooSmall,
+    /// The trust anchor slot does not exist.
+    InvalidSlot,
+    /// The wall clock has not been set.
+    ClockNotSet,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Malformed => write!(f, "malformed certificate"),
+            Self::UnsupportedAlgorithm => write!(f, "unsupported algorithm"),
+            Self::UnsupportedCriticalExtension => write!(f, "unsupported critical extension"),
+            Self::OutsideValidity => write!(f, "certificate not valid at this time"),
+            Self::UnknownIssuer => write!(f, "unknown issuer"),
+            Self::NotCa => write!(f, "issuer is not a certification authority"),
+            Self::PathTooLong => write!(f, "certification path too long"),
+            Self::BadSignature => write!(f, "bad signature"),
+            Self::Storage => write!(f, "storage access failed"),
+            Self::BufferTooSmall => write!(f, "buffer too small"),
+            Self::InvalidSlot => write!(f, "invalid trust anchor slot"),
+            Self::ClockNotSet => write!(f, "wall clock not set"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use super::*;
+
+    // Generated by `testdata/generate.py`.
+    const ROOT: &[u8] = include_bytes!("../testdata/root.der");
+    const OTHER_ROOT: &[u8] = include_bytes!("../testdata/other-root.der");
+    const INTERMEDIATE: &[u8] = include_bytes!("../testdata/intermediate.der");
+    const STALE_INTERMEDIATE: &[u8] = include_bytes!("../testdata/stale-intermediate.der");
+    const LONG_PATH_LEN: &[u8] = include_bytes!("../testdata/long-path-len.der");
+    const SUB_INTERMEDIATE: &[u8] = include_bytes!("../testdata/sub-intermediate.der");
+    const LEAF: &[u8] = include_bytes!("../testdata/leaf.der");
+    const LEAF_BELOW_SUB: &[u8] = include_bytes!("../testdata/leaf-below-sub.der");
+    const LEAF_UNKNOWN_CRITICAL: &[u8] = include_bytes!("../testdata/leaf-unknown-critical.der");
+    const LEAF_BY_LEAF: &[u8] = include_bytes!("../testdata/leaf-by-leaf.der");
+
+    /// 2024-01-01T00:00:00Z and 2030-01-01T00:00:00Z, the validity of the leaf certificates.
+    const LEAF_NOT_BEFORE: u64 = 1_704_067_200;
+    const LEAF_NOT_AFTER: u64 = 1_893_456_000;
+    /// 2025-01-01T00:00:00Z
+    const NOW: u64 = 1_735_689_600;
+
+    fn parse(der: &[u8]) -> Certificate<'_> {
+        Certificate::from_der(der).unwrap()
+    }
+
+    #[test]
+    fn parse_certificates() {
+        let leaf = parse(LEAF);
+        assert_eq!(leaf.validity(), (LEAF_NOT_BEFORE, LEAF_NOT_AFTER));
+        assert!(!leaf.is_ca());
+        assert_eq!(leaf.path_len_constraint, None);
+        assert_eq!(leaf.public_key().first(), Some(&0x04));
+
+        let intermediate = parse(INTERMEDIATE);
+        assert!(intermediate.is_ca());
+        assert!(intermediate.may_sign_certificates);
+        assert_eq!(intermediate.path_len_constraint, Some(0));
+        assert_eq!(leaf.issuer(), intermediate.subject());
+
+        let root = parse(ROOT);
+        assert!(root.is_self_issued());
+        assert_eq!(root.path_len_constraint, None);
+    }
+
+    #[test]
+    fn reject_malformed() {
+        assert_eq!(Certificate::from_der(&[]).err(), Some(Error::Malformed));
+        let truncated = LEAF.get(..LEAF.len() - 1).unwrap();
+        assert_eq!(
+            Certificate::from_der(truncated).err(),
+            Some(Error::Malformed)
+        );
+        let mut trailing = LEAF.to_vec();
+        trailing.push(0);
+        assert_eq!(
+            Certificate::from_der(&trailing).err(),
+            Some(Error::Malformed)
+        );
+    }
+
+    #[test]
+    fn reject_unknown_critical_extension() {
+        assert_eq!(
+            Certificate::from_der(LEAF_UNKNOWN_CRITICAL).err(),
+            Some(Error::UnsupportedCriticalExtension)
+        );
+    }
+
+    #[test]
+    fn long_path_len_constraint() {
+        // A constraint of 300 is encoded in two bytes, and saturates.
+        let intermediate = parse(LONG_PATH_LEN);
+        assert_eq!(intermediate.path_len_constraint, Some(u8::MAX));
+
+        let chain = [parse(SUB_INTERMEDIATE), intermediate];
+        assert_eq!(
+            verify_chain(&parse(LEAF_BELOW_SUB), &chain, &[parse(ROOT)], NOW),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn valid_chain() {
+        let leaf = parse(LEAF);
+        let root = parse(ROOT);
+        assert_eq!(
+            verify_chain(&leaf, &[parse(INTERMEDIATE)], &[parse(ROOT)], NOW),
+            Ok(())
+        );
+        // Intermediates in any order, including a copy of the root certificate.
+        let intermediates = [root.clone(), parse(INTERMEDIATE)];
+        assert_eq!(verify_chain(&leaf, &intermediates, &[root], NOW), Ok(()));
+        // Any of the trust anchors.
+        let anchors = [parse(OTHER_ROOT), parse(ROOT)];
+        assert_eq!(
+            verify_chain(&leaf, &[parse(INTERMEDIATE)], &anchors, NOW),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn try_all_issuer_candidates() {
+        // The stale intermediate has the expected subject, but did not sign the leaf.
+        let intermediates = [parse(STALE_INTERMEDIATE), parse(INTERMEDIATE)];
+        assert_eq!(
+            verify_chain(&parse(LEAF), &intermediates, &[parse(ROOT)], NOW),
+            Ok(())
+        );
+
+        let intermediates = [parse(STALE_INTERMEDIATE)];
+        assert_eq!(
+            verify_chain(&parse(LEAF), &intermediates, &[parse(ROOT)], NOW),
+            Err(Error::BadSignature)
+        );
+    }
+
+    #[test]
+    fn wrong_issuer() {
+        let leaf = parse(LEAF);
+        // Same name as the root, but a different key.
+        assert_eq!(
+            verify_chain(&leaf, &[parse(INTERMEDIATE)], &[parse(OTHER_ROOT)], NOW),
+            Err(Error::BadSignature)
+        );
+        assert_eq!(
+            verify_chain(&leaf, &[], &[parse(ROOT)], NOW),
+            Err(Error::UnknownIssuer)
+        );
+        assert_eq!(
+            verify_chain(&leaf, &[parse(INTERMEDIATE)], &[], NOW),
+            Err(Error::UnknownIssuer)
+        );
+        assert_eq!(
+            leaf.verify_issued_by(&parse(ROOT)),
+            Err(Error::UnknownIssuer)
+        );
+    }
+
+    #[test]
+    fn validity_period() {
+        let leaf = parse(LEAF);
+        assert!(leaf.is_valid_at(LEAF_NOT_BEFORE));
+        assert!(leaf.is_valid_at(LEAF_NOT_AFTER));
+        assert!(!leaf.is_valid_at(LEAF_NOT_BEFORE - 1));
+        assert!(!leaf.is_valid_at(LEAF_NOT_AFTER + 1));
+
+        for now in [LEAF_NOT_BEFORE - 1, LEAF_NOT_AFTER + 1] {
+            assert_eq!(
+                verify_chain(&leaf, &[parse(INTERMEDIATE)], &[parse(ROOT)], now),
+                Err(Error::OutsideValidity)
+            );
+        }
+    }
+
+    #[test]
+    fn path_len_violation() {
+        // The intermediate has a path length constraint of 0, but issued another intermediate.
+        let intermediates = [parse(SUB_INTERMEDIATE), parse(INTERMEDIATE)];
+        assert_eq!(
+            verify_chain(&parse(LEAF_BELOW_SUB), &intermediates, &[parse(ROOT)], NOW),
+            Err(Error::PathTooLong)
+        );
+    }
+
+    #[test]
+    fn issuer_not_ca() {
+        let intermediates = [parse(LEAF), parse(INTERMEDIATE)];
+        assert_eq!(
+            verify_chain(&parse(LEAF_BY_LEAF), &intermediates, &[parse(ROOT)], NOW),
+            Err(Error::NotCa)
+        );
+    }
+
+    #[test]
+    fn subject_alt_names() {
+        let leaf = parse(LEAF);
+        assert!(leaf.matches_dns_name("example.com"));
+        assert!(leaf.matches_dns_name("EXAMPLE.com."));
+        assert!(!leaf.matches_dns_name("www.example.com"));
+        assert!(leaf.matches_dns_name("www.example.net"));
+        assert!(!leaf.matches_dns_name("example.net"));
+        assert!(!leaf.matches_dns_name(".example.net"));
+        assert!(!leaf.matches_dns_name("a.b.example.net"));
+        assert!(leaf.matches_ip_address(&[192, 0, 2, 1]));
+        assert!(!leaf.matches_ip_address(&[192, 0, 2, 2]));
+        assert!(!parse(ROOT).matches_dns_name("example.com"));
+    }
+}
@@ -0,0 +1,132 @@
+//! Keeps trust anchors in storage.
+//!
+//! Up to [`MAX_TRUST_ANCHORS`] certificates can be stored in numbered slots, eg. during
+//! provisioning or when a backend rotates its certification authority. Certificates can then be
+//! validated against all stored trust anchors with [`verify_chain_with_stored_anchors()`]:
+//!
+//! ```ignore
+//! ariel_os::x509::trust_anchors::set_trust_anchor(0, ROOT_CA).await?;
+//! // ...
+//! let mut buffer = [0; 1024];
+//! verify_chain_with_stored_anchors(&leaf, &intermediates, now, &mut buffer).await?;
+//! ```
+
+use crate::{Certificate, Error, verify_path};
+
+/// Number of slots for trust anchors.
+pub const MAX_TRUST_ANCHORS: usize = 4;
+
+/// Storage keys of the trust anchor slots.
+const SLOT_KEYS: [&str; MAX_TRUST_ANCHORS] = [
+    "ariel-os-x509.trust-anchor.0",
+    "ariel-os-x509.trust-anchor.1",
+    "ariel-os-x509.trust-anchor.2",
+    "ariel-os-x509.trust-anchor.3",
+];
+
+/// Returns the storage key of `slot`.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidSlot`] if `slot` is not below [`MAX_TRUST_ANCHORS`].
+fn slot_key(slot: usize) -> Result<&'static str, Error> {
+    SLOT_KEYS.get(slot).copied().ok_or(Error::InvalidSlot)
+}
+
+/// Persists a DER encoded certificate as a trust anchor in `slot`, replacing any previous trust
+/// anchor in that slot.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidSlot`] if `slot` is not below [`MAX_TRUST_ANCHORS`], the errors of
+/// [`Certificate::from_der()`] if `der` is not a supported certificate, and [`Error::Storage`]
+/// if it could not be written to storage.
+pub async fn set_trust_anchor(slot: usize, der: &[u8]) -> Result<(), Error> {
+    let key = slot_key(slot)?;
+    Certificate::from_der(der)?;
+    ariel_os_storage::insert_blob(key, der)
+        .await
+        .map_err(|_| Error::Storage)
+}
+
+/// Loads the DER encoded trust anchor in `slot` into `buffer`.
+///
+/// Returns `None` if the slot is empty.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidSlot`] if `slot` is not below [`MAX_TRUST_ANCHORS`],
+/// [`Error::BufferTooSmall`] if the certificate does not fit into `buffer`, and
+/// [`Error::Storage`] if it could not be read from storage.
+pub async fn trust_anchor(slot: usize, buffer: &mut [u8]) -> Result<Option<&[u8]>, Error> {
+    ariel_os_storage::get_blob(slot_key(slot)?, buffer)
+        .await
+        .map_err(|e| match e {
+            sequential_storage::Error::BufferTooSmall(_) => Error::BufferTooSmall,
+            _ => Error::Storage,
+        })
+}
+
+/// Empties `slot`.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidSlot`] if `slot` is not below [`MAX_TRUST_ANCHORS`], and
+/// [`Error::Storage`] if the trust anchor could not be removed from storage.
+// STM32 flash drivers do not implement `MultiwriteNorFlash`.
+#[cfg(not(context = "stm32"))]
+pub async fn remove_trust_anchor(slot: usize) -> Result<(), Error> {
+    ariel_os_storage::remove(slot_key(slot)?)
+        .await
+        .map_err(|_| Error::Storage)
+}
+
+/// Validates `leaf` against the stored trust anchors, see [`crate::verify_chain()`] for details.
+///
+/// `buffer` is used to load the trust anchors one after the other, and needs to be large enough
+/// for any of them.
+///
+/// # Errors
+///
+/// Returns [`Error::UnknownIssuer`] if there is no path to any of the stored trust anchors,
+/// errors from reading the trust anchors like [`trust_anchor()`], and otherwise errors like
+/// [`crate::verify_path()`].
+pub async fn verify_chain_with_stored_anchors(
+    leaf: &Certificate<'_>,
+    intermediates: &[Certificate<'_>],
+    now: u64,
+    buffer: &mut [u8],
+) -> Result<(), Error> {
+    let mut result = Err(Error::UnknownIssuer);
+    for slot in 0..MAX_TRUST_ANCHORS {
+        let Some(anchor) = trust_anchor(slot, buffer).await? else {
+            continue;
+        };
+        let attempt = verify_path(leaf, intermediates, &Certificate::from_der(anchor)?, now);
+        if attempt.is_ok() {
+            return attempt;
+        }
+        if result == Err(Error::UnknownIssuer) {
+            result = attempt;
+        }
+    }
+    result
+}
+
+/// Validates `leaf` against the stored trust anchors at the current time of the wall clock, see
+/// [`verify_chain_with_stored_anchors()`].
+///
+/// # Errors
+///
+/// Returns [`Error::ClockNotSet`] if the wall clock has not been set (see
+/// [`ariel_os_calendar::set_now()`]), and otherwise errors like
+/// [`verify_chain_with_stored_anchors()`].
+#[cfg(feature = "calendar")]
+pub async fn verify_chain_with_stored_anchors_now(
+    leaf: &Certificate<'_>,
+    intermediates: &[Certificate<'_>],
+    buffer: &mut [u8],
+) -> Result<(), Error> {
+    let now = ariel_os_calendar::now().ok_or(Error::ClockNotSet)?;
+    verify_chain_with_stored_anchors(leaf, intermediates, now, buffer).await
+}
@@ -0,0 +1,108 @@
+#!/usr/bin/env python3
+"""Generates the certificates used by the tests of ariel-os-x509.
+
+Run from this directory; requires the `cryptography` package. ECDSA signatures are randomized, so
+every run produces different (but equivalent) files.
+"""
+
+import datetime
+import ipaddress
+
+from cryptography import x509
+from cryptography.hazmat.primitives import hashes, serialization
+from cryptography.hazmat.primitives.asymmetric import ec
+from cryptography.x509.oid import NameOID
+
+
+def name(common_name):
+    return x509.Name([x509.NameAttribute(NameOID.COMMON_NAME, common_name)])
+
+
+def date(year):
+    return datetime.datetime(year, 1, 1, tzinfo=datetime.timezone.utc)
+
+
+def certificate(
+    subject, key, issuer, issuer_key, *, ca=False, path_length=None, valid=(2020, 2040),
+    extensions=(),
+):
+    builder = (
+        x509.CertificateBuilder()
+        .subject_name(name(subject))
+        .issuer_name(name(issuer))
+        .public_key(key.public_key())
+        .serial_number(x509.random_serial_number())
+        .not_valid_before(date(valid[0]))
+        .not_valid_after(date(valid[1]))
+    )
+    if ca:
+        builder = builder.add_extension(
+            x509.BasicConstraints(ca=True, path_length=path_length), critical=True
+        ).add_extension(
+            x509.KeyUsage(
+                digital_signature=False, content_commitment=False, key_encipherment=False,
+                data_encipherment=False, key_agreement=False, key_cert_sign=True,
+                crl_sign=True, encipher_only=False, decipher_only=False,
+            ),
+            critical=True,
+        )
+    for extension, critical in extensions:
+        builder = builder.add_extension(extension, critical=critical)
+    return builder.sign(issuer_key, hashes.SHA256())
+
+
+def write(filename, cert):
+    with open(filename, "wb") as file:
+        file.write(cert.public_bytes(serialization.Encoding.DER))
+
+
+def main():
+    root_key = ec.generate_private_key(ec.SECP256R1())
+    other_root_key = ec.generate_private_key(ec.SECP256R1())
+    intermediate_key = ec.generate_private_key(ec.SECP256R1())
+    stale_intermediate_key = ec.generate_private_key(ec.SECP256R1())
+    sub_intermediate_key = ec.generate_private_key(ec.SECP256R1())
+    leaf_key = ec.generate_private_key(ec.SECP256R1())
+
+    san = x509.SubjectAlternativeName([
+        x509.DNSName("example.com"),
+        x509.DNSName("*.example.net"),
+        x509.IPAddress(ipaddress.IPv4Address("192.0.2.1")),
+    ])
+
+    write("root.der", certificate("Root", root_key, "Root", root_key, ca=True))
+    write("other-root.der", certificate("Root", other_root_key, "Root", other_root_key, ca=True))
+    write("intermediate.der", certificate(
+        "Intermediate", intermediate_key, "Root", root_key, ca=True, path_length=0,
+    ))
+    # Same subject as the intermediate, but a different key, as after a key rollover.
+    write("stale-intermediate.der", certificate(
+        "Intermediate", stale_intermediate_key, "Root", root_key, ca=True, path_length=0,
+    ))
+    write("long-path-len.der", certificate(
+        "Intermediate", intermediate_key, "Root", root_key, ca=True, path_length=300,
+    ))
+    write("sub-intermediate.der", certificate(
+        "Sub-intermediate", sub_intermediate_key, "Intermediate", intermediate_key, ca=True,
+    ))
+    write("leaf.der", certificate(
+        "Leaf", leaf_key, "Intermediate", intermediate_key, valid=(2024, 2030),
+        extensions=[(san, False)],
+    ))
+    write("leaf-below-sub.der", certificate(
+        "Leaf", leaf_key, "Sub-intermediate", sub_intermediate_key, valid=(2024, 2030),
+    ))
+    write("leaf-unknown-critical.der", certificate(
+        "Leaf", leaf_key, "Intermediate", intermediate_key, valid=(2024, 2030),
+        extensions=[(
+            x509.UnrecognizedExtension(x509.ObjectIdentifier("1.3.6.1.4.1.55555.1"), b"\x05\x00"),
+            True,
+        )],
+    ))
+    write("leaf-by-leaf.der", certificate(
+        "Other leaf", leaf_key, "Leaf", leaf_key, valid=(2024, 2030),
+    ))
+
+
+if __name__ == "__main__":
+    main()
@@ -23,47 +23,153 @@
 #![cfg_attr(nightly, feature(doc_auto_cfg))]
 #![deny(missing_docs)]
 
+#[cfg(feature = "alloc")]
+#[doc(inline)]
+pub use ariel_os_alloc as alloc;
+#[cfg(feature = "at")]
+#[doc(inline)]
+pub use ariel_os_at as at;
+#[cfg(feature = "attestation")]
+#[doc(inline)]
+pub use ariel_os_attestation as attestation;
+#[cfg(feature = "audio")]
+#[doc(inline)]
+pub use ariel_os_audio as audio;
 #[cfg(feature = "bench")]
 #[doc(inline)]
 pub use ariel_os_bench as bench;
+#[cfg(feature = "bootloader")]
+#[doc(inline)]
+pub use ariel_os_bootloader as bootloader;
 #[doc(inline)]
 pub use ariel_os_buildinfo as buildinfo;
+#[cfg(feature = "calendar")]
+#[doc(inline)]
+pub use ariel_os_calendar as calendar;
 #[cfg(feature = "coap")]
 #[doc(inline)]
 pub use ariel_os_coap as coap;
+#[cfg(feature = "connectivity")]
+#[doc(inline)]
+pub use ariel_os_connectivity as connectivity;
+#[cfg(feature = "crash-report")]
+#[doc(inline)]
+pub use ariel_os_crash as crash;
 #[doc(inline)]
 pub use ariel_os_debug as debug;
+#[cfg(feature = "display")]
+#[doc(inline)]
+pub use ariel_os_display as display;
+#[cfg(feature = "fixed")]
+#[doc(inline)]
+pub use ariel_os_fixed as fixed;
+#[cfg(feature = "gnss")]
+#[doc(inline)]
+pub use ariel_os_gnss as gnss;
 #[doc(inline)]
 pub use ariel_os_identity as identity;
+#[cfg(feature = "inspect")]
+#[doc(inline)]
+pub use ariel_os_inspect as inspect;
+#[cfg(feature = "ir")]
+#[doc(inline)]
+pub use ariel_os_ir as ir;
+#[cfg(feature = "keyboard")]
+#[doc(inline)]
+pub use ariel_os_keyboard as keyboard;
+#[cfg(feature = "latency")]
+#[doc(inline)]
+pub use ariel_os_latency as latency;
+#[cfg(feature = "modbus")]
+#[doc(inline)]
+pub use ariel_os_modbus as modbus;
+#[cfg(feature = "motion")]
+#[doc(inline)]
+pub use ariel_os_motion as motion;
+#[cfg(feature = "ncp")]
+#[doc(inline)]
+pub use ariel_os_ncp as ncp;
+#[cfg(feature = "nfc")]
+#[doc(inline)]
+pub use ariel_os_nfc as nfc;
 #[doc(inline)]
 pub use ariel_os_power as power;
+#[cfg(feature = "provisioning")]
+#[doc(inline)]
+pub use ariel_os_provisioning as provisioning;
 #[cfg(feature = "random")]
 #[doc(inline)]
 pub use ariel_os_random as random;
+#[cfg(feature = "ring")]
+#[doc(inline)]
+pub use ariel_os_ring as ring;
 #[doc(hidden)]
 pub use ariel_os_rt as rt;
+#[cfg(feature = "sdcard")]
+#[doc(inline)]
+pub use ariel_os_sdcard as sdcard;
+#[cfg(feature = "sensors")]
+#[doc(inline)]
+pub use ariel_os_sensors as sensors;
+#[cfg(feature = "services")]
+#[doc(inline)]
+pub use ariel_os_services as services;
+#[cfg(feature = "settings")]
+#[doc(inline)]
+pub use ariel_os_settings as settings;
+#[cfg(feature = "snapshot")]
+#[doc(inline)]
+pub use ariel_os_snapshot as snapshot;
+#[cfg(feature = "spi-flash")]
+#[doc(inline)]
+pub use ariel_os_spi_flash as spi_flash;
 #[cfg(feature = "storage")]
 #[doc(inline)]
 pub use ariel_os_storage as storage;
 #[cfg(feature = "threading")]
 #[doc(inline)]
 pub use ariel_os_threads as thread;
+#[cfg(feature = "tui")]
+#[doc(inline)]
+pub use ariel_os_tui as tui;
+#[cfg(feature = "update")]
+#[doc(inline)]
+pub use ariel_os_update as update;
+#[cfg(feature = "vault")]
+#[doc(inline)]
+pub use ariel_os_vault as vault;
+#[cfg(feature = "version")]
+#[doc(inline)]
+pub use ariel_os_version as version;
+#[cfg(feature = "watch")]
+#[doc(inline)]
+pub use ariel_os_watch as watch;
+#[cfg(feature = "x509")]
+#[doc(inline)]
+pub use ariel_os_x509 as x509;
 
 // Attribute macros
 pub use ariel_os_macros::config;
+pub use ariel_os_macros::init;
 pub use ariel_os_macros::spawner;
 pub use ariel_os_macros::task;
+pub use ariel_os_macros::test;
 #[cfg(any(feature = "threading", doc))]
 pub use ariel_os_macros::thread;
+pub use ariel_os_macros::xip;
 
 pub use ariel_os_embassy::api::*;
 
 pub mod config {
     //! Provides configuration to the system and the application.
+    //!
+    //! Tunables are read at compile time from `CONFIG_*` environment variables, into typed
+    //! constants.
 
     pub use ariel_os_utils::{
-        ipv4_addr_from_env, ipv4_addr_from_env_or, ipv6_addr_from_env, ipv6_addr_from_env_or,
-        str_from_env, str_from_env_or,
+        bool_from_env_or, ipv4_addr_from_env, ipv4_addr_from_env_or, ipv6_addr_from_env,
+        ipv6_addr_from_env_or, str_from_env, str_from_env_or, u8_from_env_or, u16_from_env_or,
+        u32_from_env_or, u64_from_env_or, usize_from_env_or,
     };
 }
 
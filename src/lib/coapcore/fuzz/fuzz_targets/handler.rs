@@ -0,0 +1,73 @@
+//! Feeds a sequence of arbitrary requests into an [`OscoreEdhocHandler`], which parses their CoAP
+//! options, and depending on those, EDHOC messages, ACE tokens or OSCORE-protected requests.
+//!
+//! Each request is encoded as its code, the length of its options and payload as a big-endian
+//! `u16`, and its options and payload. Requests are processed by the same handler, so that the
+//! security contexts set up by earlier requests are used by later ones.
+//!
+//! [`OscoreEdhocHandler`]: coapcore::OscoreEdhocHandler
+#![no_main]
+
+use coap_handler::Handler as _;
+use coap_handler_implementations::{HandlerBuilder as _, SimpleRendered, new_dispatcher};
+use coap_message::error::RenderableOnMinimal as _;
+use coap_message_implementations::{inmemory, inmemory_write};
+use hexlit::hex;
+use libfuzzer_sys::fuzz_target;
+use rand_core::SeedableRng as _;
+
+/// Credential presented by the server (the same as the demo devices of Ariel OS).
+const OWN_CREDENTIAL: &[u8] = &hex!(
+    "A2026008A101A5010202410A2001215820BBC34960526EA4D32E940CAD2A234148DDC21791A12AFBCBAC93622046DD44F02258204519E257236B2A0CE2023F0931F1F386CA7AFDA64FCDE0108C224C51EABF6072"
+);
+/// Private key for `OWN_CREDENTIAL`.
+const OWN_KEY: [u8; 32] = hex!("72cc4761dbd4c78f758931aa589d348d1ef874a7e303ede2f140dcf3e6aa4aac");
+
+/// Key shared with the authorization server whose tokens are accepted.
+const AS_KEY: [u8; 32] = [0x55; 32];
+
+/// Maximum size of the responses, which fits into the usual MTU.
+const RESPONSE_SIZE: usize = 1152;
+
+fuzz_target!(|data: &[u8]| {
+    let own_credential =
+        lakers::Credential::parse_ccs(OWN_CREDENTIAL).expect("hard-coded credential is valid");
+    let security_config = coapcore::seccfg::ConfigBuilder::new()
+        .allow_unauthenticated(coapcore::scope::AllowAll.into())
+        .with_own_edhoc_credential(own_credential, OWN_KEY)
+        .with_aif_symmetric_as_aesccm256(AS_KEY);
+
+    // Fixed seeds make crashes reproducible.
+    let mut handler = coapcore::OscoreEdhocHandler::new(
+        new_dispatcher().at(&["hello"], SimpleRendered("Hello")),
+        security_config,
+        || lakers_crypto_rustcrypto::Crypto::new(rand_chacha::ChaCha20Rng::seed_from_u64(0)),
+        rand_chacha::ChaCha20Rng::seed_from_u64(1),
+        coapcore::time::TimeUnknown,
+    );
+
+    let mut data = data;
+    while let [code, len_high, len_low, rest @ ..] = data {
+        let len = usize::from(u16::from_be_bytes([*len_high, *len_low])).min(rest.len());
+        let (tail, next) = rest.split_at(len);
+        data = next;
+
+        let request = inmemory::Message::new(*code, tail);
+
+        let mut response_code = 0;
+        let mut response_buffer = [0; RESPONSE_SIZE];
+        let mut response = inmemory_write::Message::new(&mut response_code, &mut response_buffer);
+
+        // Same as what CoAP stacks do with requests.
+        match handler.extract_request_data(&request) {
+            Ok(request_data) => {
+                if let Err(error) = handler.build_response(&mut response, request_data) {
+                    let _ = error.render(&mut response);
+                }
+            }
+            Err(error) => {
+                let _ = error.render(&mut response);
+            }
+        }
+    }
+});
@@ -0,0 +1,21 @@
+//! Parses arbitrary bytes as the code, options and payload of a CoAP message, as received from
+//! the network.
+#![no_main]
+
+use coap_message::{MessageOption as _, ReadableMessage as _};
+use coap_message_implementations::inmemory;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&code, tail)) = data.split_first() else {
+        return;
+    };
+
+    let message = inmemory::Message::new(code, tail);
+    for option in message.options() {
+        let _ = option.number();
+        let _ = option.value_uint::<u32>();
+        let _ = option.value_str();
+    }
+    let _ = message.payload();
+});
@@ -6,6 +6,7 @@
 //! <https://github.com/namib-project/dcaf-rs/issues/29>.
 
 use coap_message::Code as _;
+use coap_message_utils::Error as CoAPError;
 use defmt_or_log::trace;
 
 use crate::error::{CredentialError, CredentialErrorDetail};
@@ -55,29 +56,9 @@ type UnprotectedAuthzInfoPost<'a> = AceCbor<'a>;
 
 /// A COSE header map.
 ///
-/// Full attribute references are in the [COSE Header Parameters
-/// registry](https://www.iana.org/assignments/cose/cose.xhtml#header-parameters).
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
-#[derive(minicbor::Decode)]
-#[cbor(map)]
-#[non_exhaustive]
-pub struct HeaderMap<'a> {
-    #[n(1)]
-    // Might be extended as more exotic algorithms are supported
-    pub(crate) alg: Option<i32>,
-    #[cbor(b(5), with = "minicbor::bytes")]
-    pub(crate) iv: Option<&'a [u8]>,
-}
-
-impl HeaderMap<'_> {
-    /// Merge two header maps, using the latter's value in case of conflict.
-    fn updated_with(&self, other: &Self) -> Self {
-        Self {
-            alg: self.alg.or(other.alg),
-            iv: self.iv.or(other.iv),
-        }
-    }
-}
+/// This is provided by the [`cosecore`] crate, and only re-exported here because it is part of the
+/// [`ServerSecurityConfig`](crate::seccfg::ServerSecurityConfig) interface.
+pub use cosecore::HeaderMap;
 
 /// A `COSE_Key` as described in Section 7 of RFC9052.
 ///
@@ -110,116 +91,52 @@ pub(crate) struct CoseKey<'a> {
     pub(crate) y: Option<&'a [u8]>, // or bool (unsupported here so far)
 }
 
-/// A `COSE_Encrypt0` structure as defined in [RFC8152](https://www.rfc-editor.org/rfc/rfc8152)
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
-#[derive(minicbor::Decode)]
-#[cbor(tag(16))]
-#[non_exhaustive]
-struct CoseEncrypt0<'a> {
-    #[cbor(b(0), with = "minicbor::bytes")]
-    protected: &'a [u8],
-    #[b(1)]
-    unprotected: HeaderMap<'a>,
-    #[cbor(b(2), with = "minicbor::bytes")]
-    encrypted: &'a [u8],
-}
-
-/// The `Encrypt0` object that feeds the AAD during the processing of a `COSE_Encrypt0`.
-#[derive(minicbor::Encode)]
-struct Encrypt0<'a> {
-    #[n(0)]
-    context: &'static str,
-    #[cbor(b(1), with = "minicbor::bytes")]
-    protected: &'a [u8],
-    #[cbor(b(2), with = "minicbor::bytes")]
-    external_aad: &'a [u8],
-}
-/// The maximal encoded size of an [`Encrypt0`], provided its protected data stays within the
-/// bounds of [`MAX_SUPPORTED_ENCRYPT_PROTECTED_LEN`].
+/// Maximum size of the `Enc_structure` built during the processing of a `COSE_Encrypt0`,
+/// provided its protected data stays within the bounds of [`MAX_SUPPORTED_ENCRYPT_PROTECTED_LEN`].
 const AADSIZE: usize = 1 + 1 + 8 + 1 + MAX_SUPPORTED_ENCRYPT_PROTECTED_LEN + 1;
 
-impl CoseEncrypt0<'_> {
-    /// Performs the common steps of processing the inner headers and building an AAD before
-    /// passing the output on to an authority's `.decrypt_symmetric_token` method.
-    ///
-    /// The buffer could be initialized anew and place-returned, but as it is large, it is taken as
-    /// a reference so that (eg. in `process_edhoc_token`) it can be guaranteed to be shared with
-    /// the large buffer of the other path.
-    ///
-    /// # Errors
-    ///
-    /// This produces errors if the input (which is typically received from the network) is
-    /// malformed or contains unsupported items.
-    fn prepare_decryption<'t>(
-        &self,
-        buffer: &'t mut heapless::Vec<u8, MAX_SUPPORTED_ACCESSTOKEN_LEN>,
-    ) -> Result<(HeaderMap<'_>, impl AsRef<[u8]>, &'t mut [u8]), CredentialError> {
-        trace!("Preparing decryption of {:?}", self);
-
-        // Could have the extra exception for empty byte strings expressing the empty map, but we don't
-        // encounter this here
-        let protected: HeaderMap = minicbor::decode(self.protected)?;
-        trace!("Protected decoded as header map: {:?}", protected);
-        let headers = self.unprotected.updated_with(&protected);
-
-        let aad = Encrypt0 {
-            context: "Encrypt0",
-            protected: self.protected,
-            external_aad: &[],
-        };
-        let mut aad_encoded = heapless::Vec::<u8, AADSIZE>::new();
-        minicbor::encode(&aad, minicbor_adapters::WriteToHeapless(&mut aad_encoded))
-            .map_err(|_| CredentialErrorDetail::ConstraintExceeded)?;
-        trace!("Serialized AAD: {:02x}", aad_encoded); // :02x could be :cbor
-
-        buffer.clear();
-        // Copying around is not a constraint of this function (well that too but that could
-        // change) -- but the callers don't usually get their data in a mutable buffer for in-place
-        // decryption.
-        #[expect(
-            clippy::ignored_unit_patterns,
-            reason = "heapless has non-recommended error type"
-        )]
-        buffer
-            .extend_from_slice(self.encrypted)
-            .map_err(|_| CredentialErrorDetail::ConstraintExceeded)?;
-
-        Ok((headers, aad_encoded, buffer))
-    }
-}
-
-type EncryptedCwt<'a> = CoseEncrypt0<'a>;
-
-/// A `COSE_Sign1` structure as defined in [RFC8152](https://www.rfc-editor.org/rfc/rfc8152)
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
-#[derive(minicbor::Decode)]
-#[cbor(tag(18))]
-#[non_exhaustive]
-struct CoseSign1<'a> {
-    #[cbor(b(0), with = "minicbor::bytes")]
-    protected: &'a [u8],
-    #[b(1)]
-    unprotected: HeaderMap<'a>,
-    // Payload could also be nil, but we don't support detached signatures here right now.
-    #[cbor(b(2), with = "minicbor::bytes")]
-    payload: &'a [u8],
-    #[cbor(b(3), with = "minicbor::bytes")]
-    signature: &'a [u8],
-}
-
-type SignedCwt<'a> = CoseSign1<'a>;
+/// Performs the common steps of processing the inner headers and building an AAD before
+/// passing the output on to an authority's `.decrypt_symmetric_token` method.
+///
+/// The buffer could be initialized anew and place-returned, but as it is large, it is taken as
+/// a reference so that (eg. in `process_edhoc_token`) it can be guaranteed to be shared with
+/// the large buffer of the other path.
+///
+/// # Errors
+///
+/// This produces errors if the input (which is typically received from the network) is
+/// malformed or contains unsupported items.
+fn prepare_decryption<'a, 't>(
+    encrypt0: &cosecore::Encrypt0<'a>,
+    buffer: &'t mut heapless::Vec<u8, MAX_SUPPORTED_ACCESSTOKEN_LEN>,
+) -> Result<(HeaderMap<'a>, impl AsRef<[u8]>, &'t mut [u8]), CredentialError> {
+    trace!("Preparing decryption of {:?}", encrypt0);
+
+    let headers = encrypt0.headers()?;
+    trace!("Combined header map: {:?}", headers);
+
+    let mut aad_buffer = [0; AADSIZE];
+    #[expect(
+        clippy::ignored_unit_patterns,
+        reason = "heapless has non-recommended error type"
+    )]
+    let aad_encoded = heapless::Vec::<u8, AADSIZE>::from_slice(encrypt0.aad(&[], &mut aad_buffer)?)
+        .map_err(|_| CredentialErrorDetail::ConstraintExceeded)?;
+    trace!("Serialized AAD: {:02x}", aad_encoded); // :02x could be :cbor
+
+    buffer.clear();
+    // Copying around is not a constraint of this function (well that too but that could
+    // change) -- but the callers don't usually get their data in a mutable buffer for in-place
+    // decryption.
+    #[expect(
+        clippy::ignored_unit_patterns,
+        reason = "heapless has non-recommended error type"
+    )]
+    buffer
+        .extend_from_slice(encrypt0.ciphertext)
+        .map_err(|_| CredentialErrorDetail::ConstraintExceeded)?;
 
-/// The `Signature1` object that feeds the AAD during the processing of a `COSE_Sign1`.
-#[derive(minicbor::Encode)]
-struct SigStructureForSignature1<'a> {
-    #[n(0)]
-    context: &'static str,
-    #[cbor(b(1), with = "minicbor::bytes")]
-    body_protected: &'a [u8],
-    #[cbor(b(2), with = "minicbor::bytes")]
-    external_aad: &'a [u8],
-    #[cbor(b(3), with = "minicbor::bytes")]
-    payload: &'a [u8],
+    Ok((headers, aad_encoded, buffer))
 }
 
 /// A CWT Claims Set.
@@ -369,24 +286,27 @@ impl AceCborAuthzInfoResponse {
     pub(crate) fn render<M: coap_message::MutableWritableMessage>(
         &self,
         message: &mut M,
-    ) -> Result<(), M::UnionError> {
+    ) -> Result<(), Result<CoAPError, M::UnionError>> {
         let full = AceCbor {
             nonce2: Some(&self.nonce2),
             ace_server_recipientid: Some(self.ace_server_recipientid.as_slice()),
             ..Default::default()
         };
 
-        message.set_code(M::Code::new(coap_numbers::code::CHANGED)?);
+        message.set_code(M::Code::new(coap_numbers::code::CHANGED).map_err(|e| Err(e.into()))?);
 
         const { assert!(OWN_NONCE_LEN < 256) };
         const { assert!(COwn::MAX_SLICE_LEN < 256) };
         let required_len = 1 + 2 + 2 + OWN_NONCE_LEN + 2 + 2 + COwn::MAX_SLICE_LEN;
-        let payload = message.payload_mut_with_len(required_len)?;
+        let payload = message
+            .payload_mut_with_len(required_len)
+            .map_err(|e| Err(e.into()))?;
 
         let mut cursor = minicbor::encode::write::Cursor::new(payload);
-        minicbor::encode(full, &mut cursor).expect("Sufficient size was requested");
+        // Sufficient size was requested
+        minicbor::encode(full, &mut cursor).map_err(|_| Ok(CoAPError::internal_server_error()))?;
         let written = cursor.position();
-        message.truncate(written)?;
+        message.truncate(written).map_err(|e| Err(e.into()))?;
 
         Ok(())
     }
@@ -425,7 +345,7 @@ pub(crate) fn process_acecbor_authz_info<GC: crate::GeneralClaims>(
     payload: &[u8],
     authorities: &impl crate::seccfg::ServerSecurityConfig<GeneralClaims = GC>,
     nonce2: [u8; OWN_NONCE_LEN],
-    server_recipient_id: impl FnOnce(&[u8]) -> COwn,
+    server_recipient_id: impl FnOnce(&[u8]) -> Option<COwn>,
 ) -> Result<(AceCborAuthzInfoResponse, liboscore::PrimitiveContext, GC), CredentialError> {
     trace!("Processing authz_info {=[u8]:02x}", payload); // :02x could be :cbor
 
@@ -447,21 +367,23 @@ pub(crate) fn process_acecbor_authz_info<GC: crate::GeneralClaims>(
         decoded
     );
 
-    let encrypt0: EncryptedCwt = minicbor::decode(access_token)?;
+    let encrypt0 = cosecore::Encrypt0::decode(access_token)?;
 
     let mut buffer = heapless::Vec::new();
-    let (headers, aad_encoded, buffer) = encrypt0.prepare_decryption(&mut buffer)?;
+    let (headers, aad_encoded, buffer) = prepare_decryption(&encrypt0, &mut buffer)?;
+    // The decrypted token contains the OSCORE input material.
+    let mut buffer = secretcore::EraseOnDrop::new(buffer);
 
     // Can't go through liboscore's decryption backend b/c that expects unprotect-in-place; doing
     // something more custom on a bounded copy instead, and this is part of where dcaf on alloc
     // could shine by getting an exclusive copy of something in RAM
 
-    if headers.alg != Some(31) {
+    if headers.alg != Some(cosecore::alg::AES_CCM_16_128_256) {
         return Err(CredentialErrorDetail::UnsupportedAlgorithm.into());
     }
 
     let (processed, parsed) =
-        authorities.decrypt_symmetric_token(&headers, aad_encoded.as_ref(), buffer)?;
+        authorities.decrypt_symmetric_token(&headers, aad_encoded.as_ref(), &mut buffer)?;
 
     // Currently disabled because no formatting is available while there; works with
     // <https://codeberg.org/chrysn/minicbor-adapters/pulls/1>
@@ -475,7 +397,8 @@ pub(crate) fn process_acecbor_authz_info<GC: crate::GeneralClaims>(
         return Err(CredentialErrorDetail::InconsistentDetails.into());
     };
 
-    let ace_server_recipientid = server_recipient_id(ace_client_recipientid);
+    let ace_server_recipientid = server_recipient_id(ace_client_recipientid)
+        .ok_or(CredentialErrorDetail::ConstraintExceeded)?;
 
     let derived = osc.derive(
         nonce1,
@@ -512,29 +435,30 @@ pub(crate) fn process_edhoc_token<GeneralClaims>(
     // Trying and falling back means that the minicbor error is not too great ("Expected tag 16"
     // rather than "Expected tag 16 or 18"), but we don't
     // show much of that anyway.
-    let (processed, parsed) = if let Ok(encrypt0) = minicbor::decode::<EncryptedCwt>(ead3) {
-        let (headers, aad_encoded, buffer) = encrypt0.prepare_decryption(&mut buffer)?;
+    let (processed, parsed) = if let Ok(encrypt0) = cosecore::Encrypt0::decode(ead3) {
+        let (headers, aad_encoded, buffer) = prepare_decryption(&encrypt0, &mut buffer)?;
 
         authorities.decrypt_symmetric_token(&headers, aad_encoded.as_ref(), buffer)?
-    } else if let Ok(sign1) = minicbor::decode::<SignedCwt>(ead3) {
-        let protected: HeaderMap = minicbor::decode(sign1.protected)?;
+    } else if let Ok(sign1) = cosecore::Sign1::decode(ead3) {
+        let headers = sign1.headers()?;
         trace!(
-            "Decoded protected header map {:?} inside sign1 container {:?}",
-            &protected, &sign1
+            "Decoded header map {:?} inside sign1 container {:?}",
+            &headers, &sign1
         );
-        let headers = sign1.unprotected.updated_with(&protected);
-
-        let aad = SigStructureForSignature1 {
-            context: "Signature1",
-            body_protected: sign1.protected,
-            external_aad: &[],
-            payload: sign1.payload,
-        };
-        buffer = heapless::Vec::new();
-        minicbor::encode(&aad, minicbor_adapters::WriteToHeapless(&mut buffer))?;
-        trace!("Serialized AAD: {:#02x}", buffer);
 
-        authorities.verify_asymmetric_token(&headers, &buffer, sign1.signature, sign1.payload)?
+        buffer.clear();
+        buffer
+            .resize_default(buffer.capacity())
+            .map_err(|()| CredentialErrorDetail::ConstraintExceeded)?;
+        let aad = sign1.to_be_signed(&[], &mut buffer)?;
+        trace!("Serialized AAD: {:#02x}", aad);
+
+        authorities.verify_asymmetric_token(
+            &headers,
+            aad,
+            sign1.signature,
+            sign1.payload.ok_or(cosecore::Error::Decode)?,
+        )?
     } else {
         return Err(CredentialErrorDetail::UnsupportedExtension.into());
     };
@@ -551,7 +475,7 @@ pub(crate) fn process_edhoc_token<GeneralClaims>(
     // The prefix for naked COSE_Keys from Section 3.5.2 of RFC9528
     prefixed
         .extend_from_slice(&[0xa1, 0x08, 0xa1, 0x01])
-        .unwrap();
+        .map_err(|_| CredentialErrorDetail::ConstraintExceeded)?;
     prefixed
         .extend_from_slice(&cose_key.opaque)
         .map_err(|_| CredentialErrorDetail::ConstraintExceeded)?;
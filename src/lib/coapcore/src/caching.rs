@@ -0,0 +1,211 @@
+//! Helpers for making responses cacheable and cheaply revalidated.
+//!
+//! Clients and proxies that hold a representation of a resource can ask whether it is still
+//! current by sending the [`ETag`]s they know along with their request. When one of them matches
+//! the current representation, the server answers with a short 2.03 Valid response instead of
+//! sending the representation again, which saves radio traffic for large representations that
+//! rarely change. The Max-Age option tells them how long they may use a response without asking
+//! at all.
+//!
+//! A handler collects the request's ETags in its `extract_request_data()`, and checks them when
+//! building the response:
+//!
+//! ```ignore
+//! fn extract_request_data<M: ReadableMessage>(&mut self, request: &M) -> Result<RequestETags, CoAPError> {
+//!     let etags = RequestETags::from_request(request);
+//!     request.options().ignore_elective_others()?;
+//!     Ok(etags)
+//! }
+//!
+//! fn build_response<M: MutableWritableMessage>(&mut self, response: &mut M, etags: RequestETags) -> Result<(), CoAPError> {
+//!     let etag = ETag::from_version(self.version);
+//!     if etags.contains(&etag) {
+//!         return respond_valid(response, &etag, MAX_AGE);
+//!     }
+//!     response.set_code(M::Code::new(coap_numbers::code::CONTENT).map_err(CoAPError::from_unionerror)?);
+//!     add_etag(response, &etag)?;
+//!     add_max_age(response, MAX_AGE)?;
+//!     // …
+//! }
+//! ```
+//!
+//! Options need to be added in ascending order of their numbers: the ETag option (4) comes before
+//! Content-Format (12), which comes before Max-Age (14).
+
+use coap_message::{
+    Code as _, MessageOption as _, MinimalWritableMessage, OptionNumber as _, ReadableMessage,
+};
+use coap_message_utils::Error as CoAPError;
+
+/// Maximum number of ETags of a request that are kept by [`RequestETags`].
+const MAX_REQUEST_ETAGS: usize = 4;
+
+/// An entity tag, which identifies a representation of a resource.
+///
+/// ETags are opaque to clients: any 1 to 8 bytes long value that changes whenever the
+/// representation changes is suitable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ETag {
+    bytes: [u8; Self::MAX_LEN],
+    len: u8,
+}
+
+impl ETag {
+    /// Maximum length of an ETag, in bytes.
+    pub const MAX_LEN: usize = 8;
+
+    /// Creates an ETag from its encoded value.
+    ///
+    /// Returns `None` if `value` is empty or longer than [`Self::MAX_LEN`].
+    #[must_use]
+    pub fn new(value: &[u8]) -> Option<Self> {
+        if value.is_empty() {
+            return None;
+        }
+        let mut bytes = [0; Self::MAX_LEN];
+        bytes.get_mut(..value.len())?.copy_from_slice(value);
+        Some(Self {
+            bytes,
+            len: u8::try_from(value.len()).ok()?,
+        })
+    }
+
+    /// Creates an ETag from a version number of the representation, which the resource increments
+    /// whenever the representation changes.
+    ///
+    /// This is the cheapest way to create ETags, and produces the shortest ones.
+    #[must_use]
+    pub fn from_version(version: u64) -> Self {
+        let bytes = version.to_be_bytes();
+        let leading_zeros = bytes.iter().take_while(|&&byte| byte == 0).count();
+        // An ETag is at least one byte long, even for version 0.
+        let start = leading_zeros.min(Self::MAX_LEN - 1);
+        Self {
+            bytes: u64::to_be_bytes(version << (8 * start)),
+            len: u8::try_from(Self::MAX_LEN - start).unwrap_or_default(),
+        }
+    }
+
+    /// Creates an ETag from the representation itself, by hashing it.
+    ///
+    /// This suits resources that do not keep track of changes to their representation, at the cost
+    /// of building the representation even when it is then not sent. The hash (FNV-1a) is not
+    /// cryptographic: it detects changes, but is not suitable where representations may be chosen
+    /// to collide.
+    #[must_use]
+    pub fn from_representation(representation: &[u8]) -> Self {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        let hash = representation.iter().fold(FNV_OFFSET_BASIS, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+        });
+        Self {
+            bytes: hash.to_be_bytes(),
+            len: u8::try_from(Self::MAX_LEN).unwrap_or_default(),
+        }
+    }
+
+    /// Returns the encoded value of the ETag, as sent in the ETag option.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes
+            .get(..usize::from(self.len))
+            .unwrap_or(&self.bytes)
+    }
+}
+
+/// The ETags a request asks to be validated.
+///
+/// Only the first few ETags of a request are kept. Any further ETag is ignored, which is safe:
+/// the client then merely receives the full representation rather than a 2.03 Valid response.
+#[derive(Debug, Clone, Default)]
+pub struct RequestETags {
+    etags: heapless::Vec<ETag, MAX_REQUEST_ETAGS>,
+}
+
+impl RequestETags {
+    /// Collects the ETag options of `request`.
+    ///
+    /// As the ETag option is elective, this does not mark any option as processed; the handler
+    /// still needs to check the request for critical options it does not understand.
+    #[must_use]
+    pub fn from_request<M: ReadableMessage>(request: &M) -> Self {
+        let etags = request
+            .options()
+            .filter(|option| option.number() == coap_numbers::option::ETAG)
+            // Malformed ETags can never match, and are thus ignored.
+            .filter_map(|option| ETag::new(option.value()))
+            .take(MAX_REQUEST_ETAGS)
+            .collect();
+        Self { etags }
+    }
+
+    /// Returns whether the request has no ETag option, ie. asks for the full representation.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.etags.is_empty()
+    }
+
+    /// Returns whether `etag` is one of the ETags of the request, ie. the client already has the
+    /// current representation.
+    #[must_use]
+    pub fn contains(&self, etag: &ETag) -> bool {
+        self.etags.contains(etag)
+    }
+}
+
+/// Adds the ETag option carrying `etag` to `response`.
+///
+/// # Errors
+///
+/// Returns an error if the option cannot be added, eg. because options with larger numbers were
+/// already added.
+pub fn add_etag<M: MinimalWritableMessage>(response: &mut M, etag: &ETag) -> Result<(), CoAPError> {
+    response
+        .add_option(
+            M::OptionNumber::new(coap_numbers::option::ETAG).map_err(CoAPError::from_unionerror)?,
+            etag.as_bytes(),
+        )
+        .map_err(CoAPError::from_unionerror)
+}
+
+/// Adds the Max-Age option to `response`, allowing it to be used for `seconds` without
+/// revalidation.
+///
+/// Without this option, clients use a response for 60 seconds; a `seconds` of 0 prevents them
+/// from using it without revalidation at all.
+///
+/// # Errors
+///
+/// Returns an error if the option cannot be added, eg. because options with larger numbers were
+/// already added.
+pub fn add_max_age<M: MinimalWritableMessage>(
+    response: &mut M,
+    seconds: u32,
+) -> Result<(), CoAPError> {
+    response
+        .add_option_uint(
+            M::OptionNumber::new(coap_numbers::option::MAX_AGE)
+                .map_err(CoAPError::from_unionerror)?,
+            seconds,
+        )
+        .map_err(CoAPError::from_unionerror)
+}
+
+/// Builds a 2.03 Valid response, telling the client that its representation tagged `etag` is
+/// current and may be used for another `max_age` seconds.
+///
+/// # Errors
+///
+/// Returns an error if the response cannot be built, eg. because options were already added.
+pub fn respond_valid<M: MinimalWritableMessage>(
+    response: &mut M,
+    etag: &ETag,
+    max_age: u32,
+) -> Result<(), CoAPError> {
+    response.set_code(M::Code::new(coap_numbers::code::VALID).map_err(CoAPError::from_unionerror)?);
+    add_etag(response, etag)?;
+    add_max_age(response, max_age)
+}
@@ -0,0 +1,190 @@
+//! Helpers for resources whose requests and responses carry CBOR.
+//!
+//! Request payloads are decoded with [`minicbor`] straight from the message buffer, and responses
+//! are encoded straight into the outgoing message, without copying them through intermediate
+//! buffers.
+//!
+//! A [`CborHandler`] turns a function from a decoded request to a response into a resource:
+//!
+//! ```ignore
+//! #[derive(minicbor::Decode)]
+//! struct Move {
+//!     #[n(0)]
+//!     x: i32,
+//!     #[n(1)]
+//!     y: i32,
+//! }
+//!
+//! #[derive(minicbor::Encode)]
+//! struct Position {
+//!     #[n(0)]
+//!     x: i32,
+//!     #[n(1)]
+//!     y: i32,
+//! }
+//!
+//! let handler = CborHandler::new(|request: Move| -> Result<Position, CoAPError> {
+//!     Ok(robot.move_by(request.x, request.y))
+//! });
+//! ```
+//!
+//! Requests that borrow from the payload, eg. byte strings, do not outlive the
+//! [`extract_request_data()`](Handler::extract_request_data) of a handler; such handlers use
+//! [`decode_request()`] and [`encode_response()`] directly.
+
+use core::marker::PhantomData;
+
+use coap_handler::Handler;
+use coap_message::{
+    Code as _, MessageOption as _, MinimalWritableMessage, MutableWritableMessage,
+    OptionNumber as _, ReadableMessage,
+};
+use coap_message_utils::{Error as CoAPError, OptionsExt as _};
+use minicbor::encode::write::Cursor;
+
+/// Content-Format of `application/cbor`.
+const CONTENT_FORMAT_CBOR: u16 = 60;
+
+/// Length of a response estimated by a [`CborHandler`] by default.
+const DEFAULT_ESTIMATED_LENGTH: usize = 128;
+
+/// Decodes the CBOR payload of `request`, borrowing from the message buffer.
+///
+/// This checks the Content-Format option of the request, if any; the handler still needs to
+/// process the other options.
+///
+/// # Errors
+///
+/// Returns 4.15 Unsupported Content-Format if the request is not CBOR, and 4.00 Bad Request if
+/// the payload cannot be decoded as `T`.
+pub fn decode_request<'m, M: ReadableMessage, T: minicbor::Decode<'m, ()>>(
+    request: &'m M,
+) -> Result<T, CoAPError> {
+    let is_cbor = request
+        .options()
+        .filter(|option| option.number() == coap_numbers::option::CONTENT_FORMAT)
+        .all(|option| option.value_uint() == Some(CONTENT_FORMAT_CBOR));
+    if !is_cbor {
+        return Err(CoAPError::unsupported_content_format());
+    }
+    minicbor::decode(request.payload()).map_err(|_| CoAPError::bad_request())
+}
+
+/// Adds the Content-Format option of CBOR to `response`, and encodes `value` as its payload,
+/// straight into the message.
+///
+/// The response code and any option with a number below Content-Format (12), eg. ETag, need to be
+/// set before; options with larger numbers, eg. Max-Age, can not be added afterwards.
+///
+/// # Errors
+///
+/// Returns an error if the option cannot be added, or the encoded value does not fit into the
+/// message.
+pub fn encode_response<M: MutableWritableMessage, T: minicbor::Encode<()>>(
+    response: &mut M,
+    value: &T,
+) -> Result<(), CoAPError> {
+    response
+        .add_option_uint(
+            M::OptionNumber::new(coap_numbers::option::CONTENT_FORMAT)
+                .map_err(CoAPError::from_unionerror)?,
+            CONTENT_FORMAT_CBOR,
+        )
+        .map_err(CoAPError::from_unionerror)?;
+    // One byte is taken by the payload marker.
+    let available = response.available_space().saturating_sub(1);
+    let payload = response
+        .payload_mut_with_len(available)
+        .map_err(CoAPError::from_unionerror)?;
+    let mut cursor = Cursor::new(payload);
+    minicbor::encode(value, &mut cursor).map_err(|_| CoAPError::internal_server_error())?;
+    let len = cursor.position();
+    response.truncate(len).map_err(CoAPError::from_unionerror)
+}
+
+/// A resource that answers POST requests carrying a CBOR `Req` with a CBOR `Resp`, which a
+/// function computes.
+///
+/// The response is sent with code 2.04 Changed; errors returned by the function are sent as
+/// error responses.
+pub struct CborHandler<F, Req, Resp> {
+    handle: F,
+    estimated_length: usize,
+    _types: PhantomData<fn(Req) -> Resp>,
+}
+
+impl<F, Req, Resp> CborHandler<F, Req, Resp>
+where
+    F: FnMut(Req) -> Result<Resp, CoAPError>,
+    Req: for<'b> minicbor::Decode<'b, ()>,
+    Resp: minicbor::Encode<()>,
+{
+    /// Creates a resource that answers requests with what `handle` returns for them.
+    pub fn new(handle: F) -> Self {
+        Self {
+            handle,
+            estimated_length: DEFAULT_ESTIMATED_LENGTH,
+            _types: PhantomData,
+        }
+    }
+
+    /// Sets the length of responses estimated towards the CoAP stack, which defaults to 128
+    /// bytes.
+    #[must_use]
+    pub fn with_estimated_length(mut self, estimated_length: usize) -> Self {
+        self.estimated_length = estimated_length;
+        self
+    }
+}
+
+impl<F, Req, Resp> Handler for CborHandler<F, Req, Resp>
+where
+    F: FnMut(Req) -> Result<Resp, CoAPError>,
+    Req: for<'b> minicbor::Decode<'b, ()>,
+    Resp: minicbor::Encode<()>,
+{
+    type RequestData = Resp;
+    type ExtractRequestError = CoAPError;
+    type BuildResponseError<M: MinimalWritableMessage> = CoAPError;
+
+    fn extract_request_data<M: ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, Self::ExtractRequestError> {
+        if request.code().into() != coap_numbers::code::POST {
+            return Err(CoAPError::method_not_allowed());
+        }
+        let mut acceptable = true;
+        request
+            .options()
+            .filter(|option| {
+                if option.number() != coap_numbers::option::ACCEPT {
+                    return true;
+                }
+                acceptable &= option.value_uint() == Some(CONTENT_FORMAT_CBOR);
+                false
+            })
+            // Content-Format is checked when decoding.
+            .filter(|option| option.number() != coap_numbers::option::CONTENT_FORMAT)
+            .ignore_elective_others()?;
+        if !acceptable {
+            return Err(CoAPError::not_acceptable());
+        }
+        (self.handle)(decode_request(request)?)
+    }
+
+    fn estimate_length(&mut self, _response: &Self::RequestData) -> usize {
+        self.estimated_length
+    }
+
+    fn build_response<M: MutableWritableMessage>(
+        &mut self,
+        response: &mut M,
+        value: Self::RequestData,
+    ) -> Result<(), Self::BuildResponseError<M>> {
+        response.set_code(
+            M::Code::new(coap_numbers::code::CHANGED).map_err(CoAPError::from_unionerror)?,
+        );
+        encode_response(response, &value)
+    }
+}
@@ -70,3 +70,19 @@ impl From<minicbor::encode::Error<minicbor_adapters::OutOfSpace>> for Credential
         }
     }
 }
+
+impl From<cosecore::Error> for CredentialError {
+    fn from(value: cosecore::Error) -> Self {
+        let detail = match value {
+            cosecore::Error::BufferTooSmall => CredentialErrorDetail::ConstraintExceeded,
+            cosecore::Error::VerifyFailed => CredentialErrorDetail::VerifyFailed,
+            cosecore::Error::UnsupportedAlgorithm => CredentialErrorDetail::UnsupportedAlgorithm,
+            // Decoding errors, as for `minicbor::decode::Error`
+            _ => CredentialErrorDetail::UnsupportedExtension,
+        };
+        Self {
+            detail,
+            position: None,
+        }
+    }
+}
@@ -29,22 +29,9 @@ impl COwn {
 
     /// Find a value of self that is not found in the iterator.
     ///
-    /// This asserts that the iterator is (known to be) short enough that this will always succeed.
-    ///
-    /// # Panics
-    ///
-    /// Panics if the iterator produces (or even may produce, per its size hints) more items than
-    /// `GENERATABLE_VALUES`.
-    pub(crate) fn not_in_iter(iterator: impl Iterator<Item = Self>) -> Self {
-        // In theory, this would allow the compiler to see that the unreachable below is indeed
-        // unreachable
-        assert!(
-            iterator
-                .size_hint()
-                .1
-                .is_some_and(|v| v < Self::GENERATABLE_VALUES),
-            "Too many slots to reliably assign connection identifier"
-        );
+    /// This always succeeds when the iterator is (known to be) short enough, ie. produces fewer
+    /// items than `GENERATABLE_VALUES`; otherwise, `None` may be returned.
+    pub(crate) fn not_in_iter(iterator: impl Iterator<Item = Self>) -> Option<Self> {
         let mut seen_pos = 0u32;
         let mut seen_neg = 0u32;
         for i in iterator {
@@ -64,19 +51,19 @@ impl COwn {
         if pos_to < 24 {
             #[expect(
                 clippy::cast_possible_truncation,
-                reason = "ensured by the GENERATABLE_VALUES check"
+                reason = "checked to be less than 24"
             )]
-            return Self(pos_to as u8);
+            return Some(Self(pos_to as u8));
         }
         let neg_to = seen_neg.trailing_ones();
         if neg_to < 24 {
             #[expect(
                 clippy::cast_possible_truncation,
-                reason = "ensured by the GENERATABLE_VALUES check"
+                reason = "checked to be less than 24"
             )]
-            return Self(0x20 | neg_to as u8);
+            return Some(Self(0x20 | neg_to as u8));
         }
-        unreachable!("Iterator is not long enough to set this many bits.");
+        None
     }
 
     /// Given an OSCORE Key ID (kid), find the corresponding context identifier value
@@ -102,7 +89,10 @@ impl COwn {
 
 impl From<COwn> for lakers::ConnId {
     fn from(cown: COwn) -> Self {
-        lakers::ConnId::from_slice(cown.as_slice())
-            .expect("ConnId is always big enough for at least COwn")
+        #[expect(
+            deprecated,
+            reason = "COwn values are exactly the single-byte integers this constructor supports"
+        )]
+        lakers::ConnId::from_int_raw(cown.0)
     }
 }
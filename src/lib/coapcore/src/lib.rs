@@ -25,7 +25,15 @@
 //! into that concrete stack, if any), a [`OscoreEdhocHandler`] is
 //! [created][OscoreEdhocHandler::new] from the application, and passed into the stack.
 //!
-//! The arguments passed to the [`OscoreEdhocHandler`] at construction guide its behavior.
+//! The arguments passed to the [`OscoreEdhocHandler`] at construction guide its behavior. Before
+//! exposing it to untrusted networks, the [`limits`] on unauthenticated peers can be adjusted
+//! through [`OscoreEdhocHandler::with_limits()`].
+//!
+//! Applications can use the helpers of the [`caching`] module to let clients and proxies revalidate
+//! their responses cheaply, and those of the [`cbor`] module to serve resources that exchange CBOR
+//! without copying it around.
+//! Behaviors shared by several resources (eg. logging, or answering revalidation requests) are
+//! added by wrapping their handlers in the layers of the [`middleware`] module.
 //!
 //! # Logging
 //!
@@ -54,10 +62,22 @@
 //! provided (it needs to be a [`coap_message_implementations::inmemory_write::Message`]) by the
 //! stack. There are plans for removing this limitation by integrating deeper with libOSCORE.
 #![doc = document_features::document_features!(feature_label = r#"<span class="stab portability"><code>{feature}</code></span>"#)]
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![cfg_attr(feature = "_nightly_docs", feature(doc_auto_cfg))]
 #![deny(missing_docs)]
 #![allow(clippy::too_many_lines)]
+#![cfg_attr(
+    feature = "no-panics",
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic,
+        clippy::unreachable,
+        clippy::todo,
+        clippy::unimplemented,
+        clippy::indexing_slicing
+    )
+)]
 
 mod iana;
 
@@ -65,6 +85,14 @@ mod helpers;
 
 pub mod time;
 
+pub mod caching;
+
+pub mod cbor;
+
+pub mod middleware;
+
+pub mod limits;
+
 pub mod ace;
 mod generalclaims;
 pub mod scope;
@@ -0,0 +1,328 @@
+//! Limits that keep unauthenticated peers from exhausting a server, and counters of how often they
+//! were enforced.
+//!
+//! An [`OscoreEdhocHandler`](crate::OscoreEdhocHandler) enforces [`Limits`] on the requests it
+//! processes itself, ie. before any peer is authenticated:
+//!
+//! * The number of EDHOC handshakes that are pending at the same time is limited, so that a flood
+//!   of EDHOC message 1 does not evict established security contexts. A message 1 replaces any
+//!   pending handshake with the same connection identifier `C_I`, so that a peer retrying its
+//!   message 1 does not take up a second slot. `C_I` is chosen by the unauthenticated initiator,
+//!   and is no identity of the peer: anyone who learns it can cancel that pending handshake by
+//!   sending a message 1 with it, which is no more disruption than taking up all pending slots
+//!   causes. The cap applies to all handshakes regardless of their `C_I`.
+//! * The number of ACE tokens verified per second is limited, as each verification takes
+//!   cryptographic operations. This is only enforced when the time provider's lower bound of the
+//!   time advances; with [`TimeUnknown`](crate::time::TimeUnknown), any number of tokens is
+//!   verified.
+//! * EDHOC message 2 is only sent when it is at most [`Limits::amplification_factor`] times as large
+//!   as message 1, so that the server can not be used to amplify traffic towards a spoofed address.
+//!   Clients that send short messages 1 can pad them as described in RFC 9528 Section 3.8.1.
+//!
+//! Requests rejected by the first two limits are answered with 5.03 Service Unavailable and a
+//! Max-Age, after which clients can retry.
+//!
+//! The CoAP handler interface does not expose the transport addresses of peers; limits that need
+//! them, eg. the number of requests per address, are up to the CoAP stack.
+//!
+//! Counters are kept in a [`Stats`] that is passed to the handler, and can be read from anywhere
+//! meanwhile:
+//!
+//! ```ignore
+//! static STATS: Stats = Stats::new();
+//!
+//! let handler = OscoreEdhocHandler::new(inner, authorities, crypto_factory, rng, time)
+//!     .with_limits(Limits::new().with_max_pending_handshakes(1))
+//!     .with_stats(&STATS);
+//! // …
+//! info!("{} handshakes rejected", STATS.handshakes_rejected());
+//! ```
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// Max-Age of the 5.03 Service Unavailable responses sent when a limit is reached, in seconds.
+pub(crate) const RETRY_AFTER: u32 = 2;
+
+/// Limits enforced by an [`OscoreEdhocHandler`](crate::OscoreEdhocHandler) on the requests of
+/// unauthenticated peers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Limits {
+    max_pending_handshakes: usize,
+    token_verifications_per_second: u32,
+    amplification_factor: usize,
+}
+
+impl Limits {
+    /// Creates the default limits: 2 pending EDHOC handshakes, 4 token verifications per second,
+    /// and an amplification factor of 3.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            max_pending_handshakes: 2,
+            token_verifications_per_second: 4,
+            amplification_factor: 3,
+        }
+    }
+
+    /// Sets the maximum number of EDHOC handshakes that are pending at the same time.
+    #[must_use]
+    pub const fn with_max_pending_handshakes(mut self, max_pending_handshakes: usize) -> Self {
+        self.max_pending_handshakes = max_pending_handshakes;
+        self
+    }
+
+    /// Sets the maximum number of ACE tokens that are verified per second.
+    #[must_use]
+    pub const fn with_token_verifications_per_second(
+        mut self,
+        token_verifications_per_second: u32,
+    ) -> Self {
+        self.token_verifications_per_second = token_verifications_per_second;
+        self
+    }
+
+    /// Sets the factor by which EDHOC message 2 may be larger than message 1.
+    #[must_use]
+    pub const fn with_amplification_factor(mut self, amplification_factor: usize) -> Self {
+        self.amplification_factor = amplification_factor;
+        self
+    }
+
+    /// Returns the maximum number of EDHOC handshakes that are pending at the same time.
+    #[must_use]
+    pub const fn max_pending_handshakes(&self) -> usize {
+        self.max_pending_handshakes
+    }
+
+    /// Returns the maximum number of ACE tokens that are verified per second.
+    #[must_use]
+    pub const fn token_verifications_per_second(&self) -> u32 {
+        self.token_verifications_per_second
+    }
+
+    /// Returns the factor by which EDHOC message 2 may be larger than message 1.
+    #[must_use]
+    pub const fn amplification_factor(&self) -> usize {
+        self.amplification_factor
+    }
+
+    /// Returns whether another EDHOC handshake may start while `pending` handshakes are pending.
+    pub(crate) const fn admits_handshake(&self, pending: usize) -> bool {
+        pending < self.max_pending_handshakes
+    }
+
+    /// Returns whether sending a message 2 of `m2_len` bytes in response to a message 1 of
+    /// `m1_len` bytes would exceed the amplification factor.
+    pub(crate) const fn amplifies(&self, m1_len: usize, m2_len: usize) -> bool {
+        m2_len > m1_len.saturating_mul(self.amplification_factor)
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counters of the requests processed by an [`OscoreEdhocHandler`](crate::OscoreEdhocHandler), and
+/// of those rejected by its [`Limits`].
+///
+/// Counters wrap around on overflow.
+#[derive(Debug, Default)]
+pub struct Stats {
+    handshakes_started: AtomicU32,
+    handshakes_rejected: AtomicU32,
+    handshakes_amplification_limited: AtomicU32,
+    tokens_verified: AtomicU32,
+    tokens_rate_limited: AtomicU32,
+}
+
+impl Stats {
+    /// Creates counters that are all zero.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            handshakes_started: AtomicU32::new(0),
+            handshakes_rejected: AtomicU32::new(0),
+            handshakes_amplification_limited: AtomicU32::new(0),
+            tokens_verified: AtomicU32::new(0),
+            tokens_rate_limited: AtomicU32::new(0),
+        }
+    }
+
+    /// Returns the number of EDHOC handshakes started, ie. of messages 1 processed.
+    pub fn handshakes_started(&self) -> u32 {
+        self.handshakes_started.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of EDHOC messages 1 rejected because too many handshakes were pending.
+    pub fn handshakes_rejected(&self) -> u32 {
+        self.handshakes_rejected.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of EDHOC messages 2 not sent because they would have amplified traffic.
+    pub fn handshakes_amplification_limited(&self) -> u32 {
+        self.handshakes_amplification_limited
+            .load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of ACE tokens verified.
+    pub fn tokens_verified(&self) -> u32 {
+        self.tokens_verified.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of ACE tokens rejected because too many were verified in the same second.
+    pub fn tokens_rate_limited(&self) -> u32 {
+        self.tokens_rate_limited.load(Ordering::Relaxed)
+    }
+
+    /// Increments the counter of `event`.
+    pub(crate) fn count(&self, event: Event) {
+        let counter = match event {
+            Event::HandshakeStarted => &self.handshakes_started,
+            Event::HandshakeRejected => &self.handshakes_rejected,
+            Event::HandshakeAmplificationLimited => &self.handshakes_amplification_limited,
+            Event::TokenVerified => &self.tokens_verified,
+            Event::TokenRateLimited => &self.tokens_rate_limited,
+        };
+        // A handler is the only writer of its counters, which spares the read-modify-write
+        // atomics not all targets have.
+        counter.store(
+            counter.load(Ordering::Relaxed).wrapping_add(1),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+/// Events counted in [`Stats`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Event {
+    HandshakeStarted,
+    HandshakeRejected,
+    HandshakeAmplificationLimited,
+    TokenVerified,
+    TokenRateLimited,
+}
+
+/// Limits the number of events per second of a clock.
+#[derive(Debug, Default)]
+pub(crate) struct RateLimiter {
+    /// Second of the current window.
+    second: u64,
+    /// Number of events allowed in the current window.
+    count: u32,
+}
+
+impl RateLimiter {
+    pub(crate) const fn new() -> Self {
+        Self {
+            second: 0,
+            count: 0,
+        }
+    }
+
+    /// Returns whether an event at `now` stays within `per_second` events per second, and counts it
+    /// if so.
+    ///
+    /// A `now` of 0, as given by a clock that does not know the time, is never limited.
+    pub(crate) fn allow(&mut self, now: u64, per_second: u32) -> bool {
+        if now == 0 {
+            return true;
+        }
+        if now != self.second {
+            self.second = now;
+            self.count = 0;
+        }
+        if self.count >= per_second {
+            return false;
+        }
+        self.count += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_builder() {
+        let limits = Limits::new()
+            .with_max_pending_handshakes(5)
+            .with_token_verifications_per_second(10)
+            .with_amplification_factor(2);
+        assert_eq!(limits.max_pending_handshakes(), 5);
+        assert_eq!(limits.token_verifications_per_second(), 10);
+        assert_eq!(limits.amplification_factor(), 2);
+        assert_eq!(Limits::default(), Limits::new());
+    }
+
+    #[test]
+    fn pending_handshake_cap() {
+        let limits = Limits::new().with_max_pending_handshakes(2);
+        assert!(limits.admits_handshake(0));
+        assert!(limits.admits_handshake(1));
+        assert!(!limits.admits_handshake(2));
+        assert!(!limits.admits_handshake(3));
+
+        assert!(
+            !Limits::new()
+                .with_max_pending_handshakes(0)
+                .admits_handshake(0)
+        );
+    }
+
+    #[test]
+    fn amplification() {
+        let limits = Limits::new().with_amplification_factor(3);
+        assert!(!limits.amplifies(40, 120));
+        assert!(limits.amplifies(40, 121));
+        // An empty message 1 admits no message 2 at all.
+        assert!(limits.amplifies(0, 1));
+        // Large lengths saturate rather than wrap around.
+        assert!(!limits.amplifies(usize::MAX, usize::MAX));
+    }
+
+    #[test]
+    fn rate_limiter() {
+        let mut limiter = RateLimiter::new();
+        assert!(limiter.allow(10, 2));
+        assert!(limiter.allow(10, 2));
+        assert!(!limiter.allow(10, 2));
+        assert!(!limiter.allow(10, 2));
+
+        // A new second starts a new window.
+        assert!(limiter.allow(11, 2));
+        assert!(limiter.allow(11, 2));
+        assert!(!limiter.allow(11, 2));
+
+        // Without a known time, nothing is limited.
+        for _ in 0..10 {
+            assert!(limiter.allow(0, 2));
+        }
+
+        assert!(!RateLimiter::new().allow(10, 0));
+    }
+
+    #[test]
+    fn stats() {
+        let stats = Stats::new();
+        stats.count(Event::HandshakeStarted);
+        stats.count(Event::HandshakeStarted);
+        stats.count(Event::HandshakeRejected);
+        stats.count(Event::HandshakeAmplificationLimited);
+        stats.count(Event::TokenVerified);
+        stats.count(Event::TokenRateLimited);
+        assert_eq!(stats.handshakes_started(), 2);
+        assert_eq!(stats.handshakes_rejected(), 1);
+        assert_eq!(stats.handshakes_amplification_limited(), 1);
+        assert_eq!(stats.tokens_verified(), 1);
+        assert_eq!(stats.tokens_rate_limited(), 1);
+
+        stats.tokens_verified.store(u32::MAX, Ordering::Relaxed);
+        stats.count(Event::TokenVerified);
+        assert_eq!(stats.tokens_verified(), 0);
+    }
+}
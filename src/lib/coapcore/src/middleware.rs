@@ -0,0 +1,404 @@
+//! Behaviors shared by several resources, wrapped around their handlers.
+//!
+//! A [`Layer`] processes requests before the handler it wraps, and can build parts of the
+//! response or answer on its own. Any [`coap_handler::Handler`], including a whole tree of
+//! resources, can be wrapped through [`HandlerExt::with_layer()`], and layers can be stacked:
+//!
+//! ```ignore
+//! let handler = new_dispatcher()
+//!     .at(&["config"], config_resource.with_layer(Revalidate::new(|| ETag::from_version(version()))))
+//!     .with_layer(ContentFormats { consumes: &[CBOR], produces: &[CBOR] })
+//!     .with_layer(Log::new("app"));
+//! ```
+//!
+//! The outermost layer processes requests first. Layers provided by this module:
+//!
+//! * [`Log`] logs the requests and whether they were answered successfully.
+//! * [`Restrict`] only lets through requests allowed by a [`Scope`], on top of the access policy
+//!   applied to the peer.
+//! * [`ContentFormats`] rejects requests whose Content-Format or Accept option the handler does
+//!   not support.
+//! * [`Revalidate`] adds an [`ETag`] to responses, and answers requests of clients that hold the
+//!   current representation with 2.03 Valid.
+
+use coap_handler::Handler;
+use coap_message::{
+    MessageOption as _, MinimalWritableMessage, MutableWritableMessage, ReadableMessage,
+};
+use coap_message_utils::Error as CoAPError;
+use defmt_or_log::{debug, info};
+
+use crate::{
+    OrInner,
+    caching::{ETag, RequestETags, add_etag, respond_valid},
+    scope::Scope,
+};
+
+/// A behavior wrapped around a handler, processing requests before it.
+pub trait Layer {
+    /// Numbers of the request options processed by the layer, which the wrapped handler does not
+    /// see.
+    ///
+    /// This lets layers process critical options, which the wrapped handler would otherwise reject
+    /// as not understood.
+    const PROCESSED_OPTIONS: &'static [u16] = &[];
+
+    /// Number of bytes the layer adds to responses, on top of what the wrapped handler estimates.
+    const ADDED_LENGTH: usize = 0;
+
+    /// Data the layer extracts from the request, to build its part of the response.
+    type RequestData;
+
+    /// Processes the request, before the wrapped handler extracts its own data from it.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error sent as response instead of running the wrapped handler.
+    fn extract_request_data<M: ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, CoAPError>;
+
+    /// Builds the response, calling `inner` to let the wrapped handler build it unless the layer
+    /// answers the request on its own.
+    ///
+    /// Options added by the layer before calling `inner` need to have numbers lower than those
+    /// added by the wrapped handler.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the layer, or that of the wrapped handler.
+    fn build_response<M: MutableWritableMessage, E>(
+        &mut self,
+        response: &mut M,
+        request: Self::RequestData,
+        inner: impl FnOnce(&mut M) -> Result<(), E>,
+    ) -> Result<(), OrInner<CoAPError, E>>;
+}
+
+/// A handler wrapped in a [`Layer`].
+pub struct Layered<L, H> {
+    layer: L,
+    inner: H,
+}
+
+impl<L: Layer, H: Handler> Layered<L, H> {
+    /// Wraps `inner` in `layer`.
+    pub fn new(layer: L, inner: H) -> Self {
+        Self { layer, inner }
+    }
+}
+
+/// Extension trait for wrapping handlers in [`Layer`]s.
+pub trait HandlerExt: Handler + Sized {
+    /// Wraps the handler in `layer`.
+    fn with_layer<L: Layer>(self, layer: L) -> Layered<L, Self> {
+        Layered::new(layer, self)
+    }
+}
+
+impl<H: Handler> HandlerExt for H {}
+
+impl<L: Layer, H: Handler> Handler for Layered<L, H> {
+    type RequestData = (L::RequestData, H::RequestData);
+    type ExtractRequestError = OrInner<CoAPError, H::ExtractRequestError>;
+    type BuildResponseError<M: MinimalWritableMessage> =
+        OrInner<CoAPError, H::BuildResponseError<M>>;
+
+    fn extract_request_data<M: ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, Self::ExtractRequestError> {
+        let own = self.layer.extract_request_data(request)?;
+        let inner = self
+            .inner
+            .extract_request_data(&Unprocessed {
+                request,
+                processed: L::PROCESSED_OPTIONS,
+            })
+            .map_err(OrInner::Inner)?;
+        Ok((own, inner))
+    }
+
+    fn estimate_length(&mut self, (_, inner): &Self::RequestData) -> usize {
+        self.inner.estimate_length(inner) + L::ADDED_LENGTH
+    }
+
+    fn build_response<M: MutableWritableMessage>(
+        &mut self,
+        response: &mut M,
+        (own, inner): Self::RequestData,
+    ) -> Result<(), Self::BuildResponseError<M>> {
+        let handler = &mut self.inner;
+        self.layer.build_response(response, own, |response| {
+            handler.build_response(response, inner)
+        })
+    }
+}
+
+impl<L, H: coap_handler::Reporting> coap_handler::Reporting for Layered<L, H> {
+    type Record<'res>
+        = H::Record<'res>
+    where
+        Self: 'res;
+    type Reporter<'res>
+        = H::Reporter<'res>
+    where
+        Self: 'res;
+
+    fn report(&self) -> Self::Reporter<'_> {
+        self.inner.report()
+    }
+
+    fn write_extra_link_format(
+        &self,
+        writer: &mut impl core::fmt::Write,
+        is_first: &mut bool,
+    ) -> core::fmt::Result {
+        self.inner.write_extra_link_format(writer, is_first)
+    }
+}
+
+/// A request whose options processed by a layer are hidden.
+struct Unprocessed<'a, M> {
+    request: &'a M,
+    processed: &'static [u16],
+}
+
+impl<M: ReadableMessage> ReadableMessage for Unprocessed<'_, M> {
+    type Code = M::Code;
+    type MessageOption<'b>
+        = M::MessageOption<'b>
+    where
+        Self: 'b;
+    type OptionsIter<'b>
+        = UnprocessedOptions<M::OptionsIter<'b>>
+    where
+        Self: 'b;
+
+    fn code(&self) -> Self::Code {
+        self.request.code()
+    }
+
+    fn options(&self) -> Self::OptionsIter<'_> {
+        UnprocessedOptions {
+            options: self.request.options(),
+            processed: self.processed,
+        }
+    }
+
+    fn payload(&self) -> &[u8] {
+        self.request.payload()
+    }
+}
+
+/// The options of an [`Unprocessed`] request.
+struct UnprocessedOptions<I> {
+    options: I,
+    processed: &'static [u16],
+}
+
+impl<I: Iterator<Item: coap_message::MessageOption>> Iterator for UnprocessedOptions<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.options
+            .by_ref()
+            .find(|option| !self.processed.contains(&option.number()))
+    }
+}
+
+/// Logs the code of requests, and whether they were answered successfully.
+pub struct Log {
+    name: &'static str,
+}
+
+impl Log {
+    /// Creates a layer logging requests under `name`, eg. the name of the resource.
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self { name }
+    }
+}
+
+impl Layer for Log {
+    type RequestData = u8;
+
+    fn extract_request_data<M: ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, CoAPError> {
+        let code = request.code().into();
+        debug!("{}: request with code {}", self.name, code);
+        Ok(code)
+    }
+
+    fn build_response<M: MutableWritableMessage, E>(
+        &mut self,
+        response: &mut M,
+        code: Self::RequestData,
+        inner: impl FnOnce(&mut M) -> Result<(), E>,
+    ) -> Result<(), OrInner<CoAPError, E>> {
+        let result = inner(response);
+        if result.is_err() {
+            info!("{}: request with code {} failed", self.name, code);
+        }
+        result.map_err(OrInner::Inner)
+    }
+}
+
+/// Only lets requests allowed by a [`Scope`] through, answering others with 4.03 Forbidden.
+///
+/// This applies on top of the scope of the peer (which the
+/// [`OscoreEdhocHandler`](crate::OscoreEdhocHandler) checks before), eg. to keep a subtree of
+/// resources read-only whichever peer accesses it.
+pub struct Restrict<S: Scope> {
+    scope: S,
+}
+
+impl<S: Scope> Restrict<S> {
+    /// Creates a layer only letting through requests allowed by `scope`.
+    pub fn new(scope: S) -> Self {
+        Self { scope }
+    }
+}
+
+impl<S: Scope> Layer for Restrict<S> {
+    type RequestData = ();
+
+    fn extract_request_data<M: ReadableMessage>(&mut self, request: &M) -> Result<(), CoAPError> {
+        if self.scope.request_is_allowed(request) {
+            Ok(())
+        } else {
+            Err(CoAPError::forbidden())
+        }
+    }
+
+    fn build_response<M: MutableWritableMessage, E>(
+        &mut self,
+        response: &mut M,
+        (): Self::RequestData,
+        inner: impl FnOnce(&mut M) -> Result<(), E>,
+    ) -> Result<(), OrInner<CoAPError, E>> {
+        inner(response).map_err(OrInner::Inner)
+    }
+}
+
+/// Rejects requests in, or asking for, a content format the wrapped handler does not support.
+///
+/// Requests whose Content-Format is not in [`consumes`](Self::consumes) are answered with 4.15
+/// Unsupported Content-Format, and those whose Accept option is not in
+/// [`produces`](Self::produces) with 4.06 Not Acceptable. Requests without these options are let
+/// through, and the wrapped handler does not see these options.
+#[derive(Debug, Clone, Copy)]
+pub struct ContentFormats {
+    /// Content formats of the request payloads the wrapped handler reads.
+    ///
+    /// When empty, requests carrying a Content-Format option are rejected.
+    pub consumes: &'static [u16],
+    /// Content formats of the response payloads the wrapped handler builds.
+    pub produces: &'static [u16],
+}
+
+impl Layer for ContentFormats {
+    const PROCESSED_OPTIONS: &'static [u16] = &[
+        coap_numbers::option::CONTENT_FORMAT,
+        coap_numbers::option::ACCEPT,
+    ];
+
+    type RequestData = ();
+
+    fn extract_request_data<M: ReadableMessage>(&mut self, request: &M) -> Result<(), CoAPError> {
+        for option in request.options() {
+            let supported = match option.number() {
+                coap_numbers::option::CONTENT_FORMAT => self.consumes,
+                coap_numbers::option::ACCEPT => self.produces,
+                _ => continue,
+            };
+            let format = option
+                .value_uint::<u16>()
+                .ok_or_else(|| CoAPError::bad_option(option.number()))?;
+            if !supported.contains(&format) {
+                return Err(match option.number() {
+                    coap_numbers::option::ACCEPT => CoAPError::not_acceptable(),
+                    _ => CoAPError::unsupported_content_format(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn build_response<M: MutableWritableMessage, E>(
+        &mut self,
+        response: &mut M,
+        (): Self::RequestData,
+        inner: impl FnOnce(&mut M) -> Result<(), E>,
+    ) -> Result<(), OrInner<CoAPError, E>> {
+        inner(response).map_err(OrInner::Inner)
+    }
+}
+
+/// Adds the current [`ETag`] of the representation to responses to GET requests, and answers
+/// those of clients that already hold it with 2.03 Valid, without running the wrapped handler.
+///
+/// The wrapped handler must not add the ETag option itself.
+pub struct Revalidate<F> {
+    current: F,
+    max_age: u32,
+}
+
+impl<F: FnMut() -> ETag> Revalidate<F> {
+    /// Creates a layer tagging responses with the ETag `current` returns.
+    ///
+    /// `current` is called for each request, so cheap ETags (eg. from
+    /// [`ETag::from_version()`]) are preferred.
+    pub fn new(current: F) -> Self {
+        Self {
+            current,
+            max_age: DEFAULT_MAX_AGE,
+        }
+    }
+
+    /// Sets the Max-Age of 2.03 Valid responses, which defaults to 60 seconds.
+    #[must_use]
+    pub fn with_max_age(mut self, seconds: u32) -> Self {
+        self.max_age = seconds;
+        self
+    }
+}
+
+/// Max-Age of 2.03 Valid responses, in seconds: the default of the Max-Age option.
+const DEFAULT_MAX_AGE: u32 = 60;
+
+impl<F: FnMut() -> ETag> Layer for Revalidate<F> {
+    // The ETag option, with a value of up to 8 bytes.
+    const ADDED_LENGTH: usize = 1 + ETag::MAX_LEN;
+
+    type RequestData = Option<RequestETags>;
+
+    fn extract_request_data<M: ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, CoAPError> {
+        if request.code().into() != coap_numbers::code::GET {
+            return Ok(None);
+        }
+        Ok(Some(RequestETags::from_request(request)))
+    }
+
+    fn build_response<M: MutableWritableMessage, E>(
+        &mut self,
+        response: &mut M,
+        etags: Self::RequestData,
+        inner: impl FnOnce(&mut M) -> Result<(), E>,
+    ) -> Result<(), OrInner<CoAPError, E>> {
+        if let Some(etags) = etags {
+            let etag = (self.current)();
+            if etags.contains(&etag) {
+                return Ok(respond_valid(response, &etag, self.max_age)?);
+            }
+            add_etag(response, &etag)?;
+        }
+        inner(response).map_err(OrInner::Inner)
+    }
+}
@@ -13,7 +13,6 @@
 //! indicate numeric values (including of priorities, where "high" corresponds to "small" and "low"
 //! to "large").
 #![forbid(unsafe_code)]
-#![expect(clippy::indexing_slicing, reason = "module is scheduled for overhaul")]
 #![expect(clippy::pedantic)]
 
 use arrayvec::ArrayVec;
@@ -123,9 +122,9 @@ impl<T: PriorityLevel, const N: usize, const L: usize> OrderedPool<T, N, L> {
     /// Create an empty cache.
     #[must_use]
     pub const fn new() -> Self {
-        assert!(N < u16::MAX as usize, "Capacity overflow");
+        const { assert!(N < u16::MAX as usize, "Capacity overflow") };
         // Clipping levels to u16 because they may be stored if the implementation changes.
-        assert!(L < u16::MAX as usize, "Level overflow");
+        const { assert!(L < u16::MAX as usize, "Level overflow") };
         OrderedPool {
             entries: ArrayVec::new_const(),
             sorted: ArrayVec::new_const(),
@@ -153,8 +152,12 @@ impl<T: PriorityLevel, const N: usize, const L: usize> OrderedPool<T, N, L> {
         Fuse: FnOnce(&mut T) -> R,
     {
         for (position, &index) in self.sorted.iter().enumerate() {
-            if f_test(&self.entries[usize::from(index)]) {
-                let r = f_use(&mut self.entries[usize::from(index)]);
+            // Indices are in range by the invariants.
+            let Some(entry) = self.entries.get_mut(usize::from(index)) else {
+                continue;
+            };
+            if f_test(entry) {
+                let r = f_use(entry);
                 self.touch(position);
                 return Some(r);
             }
@@ -176,16 +179,19 @@ impl<T: PriorityLevel, const N: usize, const L: usize> OrderedPool<T, N, L> {
         let new_index = self.entries.len();
         if new_index < N {
             self.entries.push(new);
-            self.sorted.push(
-                new_index
-                    .try_into()
-                    .expect("Range is checked at construction time"),
-            );
+            // Range is checked at construction time.
+            self.sorted.push(new_index as u16);
             self.touch(new_index);
             Ok(None)
         } else {
-            let last_slot = &mut self.entries
-                [usize::from(*self.sorted.last().expect("Full array is not empty"))];
+            // A full pool is only empty if its capacity is zero, in which case nothing fits.
+            let Some(last_slot) = self
+                .sorted
+                .last()
+                .and_then(|&index| self.entries.get_mut(usize::from(index)))
+            else {
+                return Err(new);
+            };
             let last_level = last_slot.level();
             let new_level = new.level();
             debug_assert!(new_level < L, "Level exceeds limit L={L} in type");
@@ -218,16 +224,19 @@ impl<T: PriorityLevel, const N: usize, const L: usize> OrderedPool<T, N, L> {
         let new_index = self.entries.len();
         if new_index < N {
             self.entries.push(new);
-            self.sorted.push(
-                new_index
-                    .try_into()
-                    .expect("Range is checked at construction time"),
-            );
+            // Range is checked at construction time.
+            self.sorted.push(new_index as u16);
             self.touch(new_index);
             None
         } else {
-            let last_slot = &mut self.entries
-                [usize::from(*self.sorted.last().expect("Full array is not empty"))];
+            // A full pool is only empty if its capacity is zero, in which case nothing fits.
+            let Some(last_slot) = self
+                .sorted
+                .last()
+                .and_then(|&index| self.entries.get_mut(usize::from(index)))
+            else {
+                return Some(new);
+            };
             let last = core::mem::replace(last_slot, new);
             self.touch(N - 1);
             Some(last)
@@ -235,34 +244,42 @@ impl<T: PriorityLevel, const N: usize, const L: usize> OrderedPool<T, N, L> {
     }
 
     fn touch(&mut self, position: usize) {
-        let level = self.entries[usize::from(self.sorted[position])].level();
+        let Some(level) = self.level_at(position) else {
+            return;
+        };
         debug_assert!(level < L, "Level exceeds limit L={L} in type");
         let mut new_position = position;
         // Common case: level stayed the same, but we move to front; also applicable when numeric
         // level decrased
         while new_position
             .checked_sub(1)
-            .is_some_and(|n| self.entries[usize::from(self.sorted[n])].level() >= level)
+            .is_some_and(|n| self.level_at(n).is_some_and(|l| l >= level))
         {
             new_position -= 1;
         }
         if new_position == position {
             // Level may instead have increased
-            while new_position < self.sorted.len() - 1
-                && self.entries[usize::from(self.sorted[new_position + 1])].level() < level
-            {
+            while self.level_at(new_position + 1).is_some_and(|l| l < level) {
                 new_position += 1;
             }
             // Push our entry out left and in right in the rear
-            if new_position != position {
-                self.sorted[position..=new_position].rotate_left(1);
+            if let Some(moved) = self.sorted.get_mut(position..=new_position) {
+                moved.rotate_left(1);
             }
         } else {
             // Push our entry out right and in left in the front
-            self.sorted[new_position..=position].rotate_right(1);
+            if let Some(moved) = self.sorted.get_mut(new_position..=position) {
+                moved.rotate_right(1);
+            }
         }
     }
 
+    /// Returns the level of the entry at the given position, if there is one.
+    fn level_at(&self, position: usize) -> Option<usize> {
+        let index = self.sorted.get(position)?;
+        Some(self.entries.get(usize::from(*index))?.level())
+    }
+
     /// Returns an iterator visiting all items in arbitrary order.
     pub(crate) fn iter(&self) -> impl Iterator<Item = &T> {
         self.entries.iter()
@@ -99,16 +99,23 @@ impl AifValue {
 impl Scope for AifValue {
     fn request_is_allowed<M: ReadableMessage>(&self, request: &M) -> bool {
         let code: u8 = request.code().into();
-        let (codebit, false) = 1u32.overflowing_shl(
-            u32::from(code)
-                .checked_sub(1)
-                .expect("Request codes are != 0"),
-        ) else {
+        // Request codes are != 0; 0 is an empty message, to which no access is granted.
+        let Some(shift) = u32::from(code).checked_sub(1) else {
+            return false;
+        };
+        let (codebit, false) = 1u32.overflowing_shl(shift) else {
             return false;
         };
         let mut decoder = minicbor::Decoder::new(&self.0);
-        'outer: for item in decoder.array_iter::<(&str, u32)>().unwrap() {
-            let (path, perms) = item.unwrap();
+        // The value was checked at construction time, so errors can only be treated as a
+        // mismatch.
+        let Ok(items) = decoder.array_iter::<(&str, u32)>() else {
+            return false;
+        };
+        'outer: for item in items {
+            let Ok((path, perms)) = item else {
+                return false;
+            };
             if perms & codebit == 0 {
                 continue;
             }
@@ -123,8 +130,10 @@ impl Scope for AifValue {
                 // Special case: For consistency should be a single empty option.
                 return true;
             }
-            assert!(path.starts_with('/'), "Invalid AIF");
-            let mut remainder = &path[1..];
+            let Some(mut remainder) = path.strip_prefix('/') else {
+                // Invalid AIF, rejected at construction time
+                return false;
+            };
             while !remainder.is_empty() {
                 let (next_part, next_remainder) = match remainder.split_once('/') {
                     Some((next_part, next_remainder)) => (next_part, next_remainder),
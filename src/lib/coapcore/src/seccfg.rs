@@ -199,7 +199,7 @@ impl ServerSecurityConfig for AllowAll {
 /// of multiple competing token based contexts.
 pub struct ConfigBuilder {
     /// Symmetric used when tokens are symmetrically encrypted with AES-CCM-16-128-256
-    as_key_31: Option<[u8; 32]>,
+    as_key_31: Option<secretcore::Secret<[u8; 32]>>,
     /// Asymmetric key used when tokens are signed with ES256
     ///
     /// Alogn with the key, this also holds the audience value of this RS (as signed tokens only
@@ -232,12 +232,12 @@ impl ServerSecurityConfig for ConfigBuilder {
         const TAG_SIZE: usize = 16;
         const NONCE_SIZE: usize = 13;
 
-        let key = self.as_key_31.ok_or_else(|| {
+        let key = self.as_key_31.as_ref().ok_or_else(|| {
             error!("Symmetrically encrypted token was sent, but no symmetric key is configured.");
             CredentialErrorDetail::KeyNotPresent
         })?;
 
-        let cipher = Aes256Ccm::new((&key).into());
+        let cipher = Aes256Ccm::new(key.expose().into());
 
         let nonce: &[u8; NONCE_SIZE] = headers
             .iv
@@ -296,8 +296,7 @@ impl ServerSecurityConfig for ConfigBuilder {
     ) -> Result<(Self::GeneralClaims, crate::ace::CwtClaimsSet<'b>), CredentialError> {
         use p256::ecdsa::{VerifyingKey, signature::Verifier};
 
-        if headers.alg != Some(-7) {
-            // ES256
+        if headers.alg != Some(cosecore::alg::ES256) {
             return Err(CredentialErrorDetail::UnsupportedAlgorithm.into());
         }
 
@@ -485,7 +484,7 @@ impl ConfigBuilder {
     #[must_use]
     pub fn with_aif_symmetric_as_aesccm256(self, key: [u8; 32]) -> Self {
         Self {
-            as_key_31: Some(key),
+            as_key_31: Some(secretcore::Secret::new(key)),
             ..self
         }
     }
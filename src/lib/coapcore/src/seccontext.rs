@@ -15,6 +15,7 @@ use defmt_or_log::{Debug2Format, debug, error, trace};
 
 use crate::generalclaims::{self, GeneralClaims as _};
 use crate::helpers::COwn;
+use crate::limits::{Event, Limits, RETRY_AFTER, RateLimiter, Stats};
 use crate::scope::Scope;
 use crate::seccfg::ServerSecurityConfig;
 
@@ -89,6 +90,8 @@ enum SecContextStage<Crypto: lakers::Crypto> {
         // all
         c_r: COwn,
         c_i: lakers::ConnId,
+        /// Length of message 1, which limits the length of message 2.
+        m1_len: usize,
     },
     //
     EdhocResponderSentM2 {
@@ -152,6 +155,15 @@ impl<Crypto: lakers::Crypto, GeneralClaims: generalclaims::GeneralClaims>
             SecContextStage::Oscore(ctx) => COwn::from_kid(ctx.recipient_id()),
         }
     }
+
+    /// Returns the peer's connection identifier if an EDHOC handshake with the peer is pending.
+    fn pending_c_i(&self) -> Option<&lakers::ConnId> {
+        match &self.protocol_stage {
+            SecContextStage::EdhocResponderProcessedM1 { c_i, .. }
+            | SecContextStage::EdhocResponderSentM2 { c_i, .. } => Some(c_i),
+            _ => None,
+        }
+    }
 }
 
 /// A CoAP handler wrapping inner resources, and adding EDHOC, OSCORE and ACE support.
@@ -189,6 +201,10 @@ pub struct OscoreEdhocHandler<
 
     crypto_factory: CryptoFactory,
     rng: RNG,
+
+    limits: Limits,
+    stats: Option<&'static Stats>,
+    token_rate_limiter: RateLimiter,
 }
 
 impl<
@@ -229,12 +245,39 @@ impl<
             authorities,
             rng,
             time,
+            limits: Limits::new(),
+            stats: None,
+            token_rate_limiter: RateLimiter::new(),
+        }
+    }
+
+    /// Sets the limits enforced on unauthenticated peers, replacing the defaults of
+    /// [`Limits::new()`].
+    #[must_use]
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Counts processed and rejected requests in `stats`.
+    #[must_use]
+    pub fn with_stats(mut self, stats: &'static Stats) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Counts `event` in the stats, if any.
+    fn count(&self, event: Event) {
+        if let Some(stats) = self.stats {
+            stats.count(event);
         }
     }
 
     /// Produces a [`COwn`] (as a recipient identifier) that is both available and not equal to the
     /// peer's recipient identifier.
-    fn cown_but_not(&self, c_peer: &[u8]) -> COwn {
+    ///
+    /// This only returns `None` if all identifiers are taken, which the size of the pool prevents.
+    fn cown_but_not(&self, c_peer: &[u8]) -> Option<COwn> {
         // Let's pick one now already: this allows us to use the identifier in our
         // request data.
         COwn::not_in_iter(
@@ -298,13 +341,35 @@ impl<
                 return Err(CoAPError::bad_request());
             }
 
-            let c_r = self.cown_but_not(c_i.as_slice());
+            // A repeated message 1 replaces the pending handshake with the same C_I, rather than
+            // taking up another slot. C_I is chosen by the (unauthenticated) initiator, so this is
+            // no per-peer limit: the cap below applies to all pending handshakes alike.
+            let _replaced = self.pool.lookup(
+                |c| c.pending_c_i() == Some(&c_i),
+                |matched| *matched = SecContextState::default(),
+            );
+            let pending = self
+                .pool
+                .iter()
+                .filter(|c| c.pending_c_i().is_some())
+                .count();
+            if !self.limits.admits_handshake(pending) {
+                debug!("Rejecting EDHOC message 1: {} handshakes pending", pending);
+                self.count(Event::HandshakeRejected);
+                return Err(CoAPError::service_unavailable().with_max_age(RETRY_AFTER));
+            }
+            self.count(Event::HandshakeStarted);
+
+            let c_r = self
+                .cown_but_not(c_i.as_slice())
+                .ok_or_else(CoAPError::internal_server_error)?;
 
             let _evicted = self.pool.force_insert(SecContextState {
                 protocol_stage: SecContextStage::EdhocResponderProcessedM1 {
                     c_r,
                     c_i,
                     responder,
+                    m1_len: request.payload().len(),
                 },
                 authorization: self.authorities.nosec_authorization(),
             });
@@ -331,6 +396,7 @@ impl<
         response: &mut M,
         c_r: COwn,
     ) -> Result<(), Result<CoAPError, M::UnionError>> {
+        let limits = self.limits;
         let message_2 = self.pool.lookup(
             |c| c.corresponding_cown() == Some(c_r),
             |matched| -> Result<_, lakers::EDHOCError> {
@@ -343,11 +409,13 @@ impl<
                             c_r: matched_c_r,
                             c_i,
                             responder: taken,
+                            m1_len,
                         },
                     authorization,
                 } = taken
                 else {
-                    todo!();
+                    // The first lookup function only matches this stage.
+                    return Err(lakers::EDHOCError::AccessDenied);
                 };
                 debug_assert_eq!(
                     matched_c_r, c_r,
@@ -363,6 +431,10 @@ impl<
                         Some(c_r.into()),
                         &None,
                     )?;
+                if limits.amplifies(m1_len, message_2.len()) {
+                    // The state stays empty: the peer needs to start over with a padded message 1.
+                    return Ok(None);
+                }
                 *matched = SecContextState {
                     protocol_stage: SecContextStage::EdhocResponderSentM2 {
                         responder,
@@ -371,12 +443,21 @@ impl<
                     },
                     authorization,
                 };
-                Ok(message_2)
+                Ok(Some(message_2))
             },
         );
 
         let message_2 = match message_2 {
-            Some(Ok(m)) => m,
+            Some(Ok(Some(m))) => m,
+            Some(Ok(None)) => {
+                debug!("Not sending EDHOC message 2, which would amplify message 1");
+                self.count(Event::HandshakeAmplificationLimited);
+                CoAPError::bad_request()
+                    .with_title("message 1 needs padding")
+                    .render(response)
+                    .map_err(Err)?;
+                return Ok(());
+            }
             Some(Err(e)) => {
                 render_error(e).render(response).map_err(Err)?;
                 return Ok(());
@@ -448,9 +529,8 @@ impl<
 
         let (taken, front_trim_payload) = if with_edhoc {
             if !SSC::HAS_EDHOC {
-                unreachable!(
-                    "In this variant, that option is not consumed so the argument is always false"
-                );
+                // In this variant, that option is not consumed so the argument is always false.
+                return Err(CoAPError::internal_server_error());
             }
             self.process_edhoc_in_payload(payload, taken)?
         } else {
@@ -510,13 +590,14 @@ impl<
                     CoAPError::internal_server_error()
                 })?;
         }
-        #[allow(clippy::indexing_slicing, reason = "slice fits by construction")]
-        copied_message
-            .set_payload(&payload[front_trim_payload..])
-            .map_err(|_| {
-                error!("Unexpectedly large EDHOC-less message");
-                CoAPError::internal_server_error()
-            })?;
+        let payload = payload.get(front_trim_payload..).ok_or_else(|| {
+            error!("EDHOC message exceeds the payload.");
+            CoAPError::internal_server_error()
+        })?;
+        copied_message.set_payload(payload).map_err(|_| {
+            error!("Unexpectedly large EDHOC-less message");
+            CoAPError::internal_server_error()
+        })?;
 
         let decrypted = liboscore::unprotect_request(
             &mut copied_message,
@@ -570,12 +651,8 @@ impl<
     /// # Errors
     ///
     /// This produces errors if the input (which is typically received from the network) is
-    /// malformed or contains unsupported items.
-    ///
-    /// # Panics
-    ///
-    /// This panics if cipher suite negotiation passed for a suite whose algorithms are unsupported
-    /// in libOSCORE.
+    /// malformed or contains unsupported items, or if cipher suite negotiation passed for a suite
+    /// whose algorithms are unsupported in libOSCORE.
     fn process_edhoc_in_payload(
         &self,
         payload: &[u8],
@@ -604,9 +681,10 @@ impl<
             .. // Discarding original authorization
         } = sec_context_state
         {
-            #[allow(clippy::indexing_slicing, reason = "slice fits by construction")]
-            let msg_3 = lakers::EdhocMessageBuffer::new_from_slice(&payload[..cutoff])
-                .map_err(too_small)?;
+            let msg_3 = payload
+                .get(..cutoff)
+                .ok_or_else(CoAPError::bad_request)?;
+            let msg_3 = lakers::EdhocMessageBuffer::new_from_slice(msg_3).map_err(too_small)?;
 
             let (responder, id_cred_i, mut ead_3) =
                 responder.parse_message_3(&msg_3).map_err(render_error)?;
@@ -660,8 +738,10 @@ impl<
             let recipient_id = c_r.as_slice();
 
             // FIXME probe cipher suite
-            let hkdf = liboscore::HkdfAlg::from_number(crate::iana::cose_alg::HKDF_HMAC256256).unwrap();
-            let aead = liboscore::AeadAlg::from_number(crate::iana::cose_alg::AES_CCM_16_64_128).unwrap();
+            let hkdf = liboscore::HkdfAlg::from_number(crate::iana::cose_alg::HKDF_HMAC256256)
+                .map_err(|_| CoAPError::internal_server_error())?;
+            let aead = liboscore::AeadAlg::from_number(crate::iana::cose_alg::AES_CCM_16_64_128)
+                .map_err(|_| CoAPError::internal_server_error())?;
 
             let immutables = liboscore::PrimitiveImmutables::derive(
                 hkdf,
@@ -672,8 +752,7 @@ impl<
                 sender_id,
                 recipient_id,
             )
-            // FIXME convert error
-            .unwrap();
+            .map_err(|_| CoAPError::internal_server_error())?;
 
             let context = liboscore::PrimitiveContext::new_from_fresh_material(immutables);
 
@@ -702,13 +781,9 @@ impl<
     ///
     /// # Errors
     ///
-    /// This produces errors if requests are processed in unexpected out-of-order ways.
-    ///
-    /// # Panics
-    ///
-    /// Panics if the writable message is not a
-    /// [`coap_message_implementations::inmemory_write::Message`]. See module level documentation
-    /// for details.
+    /// This produces errors if requests are processed in unexpected out-of-order ways, or if the
+    /// writable message is not a [`coap_message_implementations::inmemory_write::Message`]. See
+    /// module level documentation for details.
     fn build_oscore_response<M: MutableWritableMessage>(
         &mut self,
         response: &mut M,
@@ -745,8 +820,10 @@ impl<
                             return Err(CoAPError::internal_server_error());
                         };
 
-                        let response = coap_message_implementations::inmemory_write::Message::downcast_from(response)
-                            .expect("OSCORE handler currently requires a response message implementation that is of fixed type");
+                        let Some(response) = coap_message_implementations::inmemory_write::Message::downcast_from(response) else {
+                            error!("OSCORE handler currently requires a response message implementation that is of fixed type");
+                            return Err(CoAPError::internal_server_error());
+                        };
 
                         response.set_code(coap_numbers::code::CHANGED);
 
@@ -1110,21 +1187,19 @@ impl<
             }
         }
 
-        // This will always be Some in practice, just taken while it is being updated.
-        let mut state = Some(Recognition::<SSC>::Start);
+        let mut state = Recognition::<SSC>::Start;
 
         // Some small potential for optimization by cutting iteration short on Edhoc, but probably
         // not worth it.
         let extra_options = request
             .options()
             .filter(|o| {
-                let (new_state, filter) = state.take().unwrap().update(o);
-                state = Some(new_state);
+                let (new_state, filter) = core::mem::replace(&mut state, Start).update(o);
+                state = new_state;
                 filter
             })
             // FIXME: This aborts early on critical options, even when the result is later ignored
             .ignore_elective_others();
-        let state = state.unwrap();
 
         if state.errors_handled_here() {
             if let Err(error) = extra_options {
@@ -1158,7 +1233,7 @@ impl<
             }
             WellKnownEdhoc => {
                 if !SSC::HAS_EDHOC {
-                    unreachable!("State is not constructed");
+                    return Err(Own(CoAPError::internal_server_error()));
                 }
                 require_post()?;
                 self.extract_edhoc(&request).map(Own).map_err(Own)
@@ -1171,9 +1246,21 @@ impl<
                     // The compiler should be able to eliminiate even this one statement based on
                     // this variant not being constructed under the same condition, but that
                     // property is not being tested.
-                    unreachable!("State is not constructed");
+                    return Err(Own(CoAPError::internal_server_error()));
                 }
                 require_post()?;
+                let now = self.time.now().0;
+                if !self
+                    .token_rate_limiter
+                    .allow(now, self.limits.token_verifications_per_second())
+                {
+                    debug!("Rejecting token: too many verified in this second");
+                    self.count(Event::TokenRateLimited);
+                    return Err(Own(
+                        CoAPError::service_unavailable().with_max_age(RETRY_AFTER)
+                    ));
+                }
+                self.count(Event::TokenVerified);
                 self.extract_token(request.payload())
                     .map(|r| Own(OwnRequestData::ProcessedToken(r)))
                     .map_err(Own)
@@ -1184,7 +1271,7 @@ impl<
                     // but the optimizer may not see that, and this is the place where a reviewer of
                     // extract_oscore_edhoc can convince themself that indeed the with_edhoc=true case is
                     // unreachable when HAS_EDHOC is not set.
-                    unreachable!("State is not constructed");
+                    return Err(Own(CoAPError::internal_server_error()));
                 }
                 self.extract_oscore_edhoc(&request, &oscore, true)
                     .map(Own)
@@ -1192,7 +1279,7 @@ impl<
             }
             Oscore { oscore } => {
                 if !has_oscore::<SSC>() {
-                    unreachable!("State is not constructed");
+                    return Err(Own(CoAPError::internal_server_error()));
                 }
                 self.extract_oscore_edhoc(&request, &oscore, false)
                     .map(Own)
@@ -1217,15 +1304,15 @@ impl<
         match req {
             Own(OwnRequestData::EdhocOkSend2(c_r)) => {
                 if !SSC::HAS_EDHOC {
-                    unreachable!("State is not constructed");
+                    return Err(Own(Ok(CoAPError::internal_server_error())));
                 }
                 self.build_edhoc_message_2(response, c_r).map_err(Own)?;
             }
             Own(OwnRequestData::ProcessedToken(r)) => {
                 if !SSC::PARSES_TOKENS {
-                    unreachable!("State is not constructed");
+                    return Err(Own(Ok(CoAPError::internal_server_error())));
                 }
-                r.render(response).map_err(|e| Own(Err(e)))?;
+                r.render(response).map_err(Own)?;
             }
             Own(OwnRequestData::EdhocOscoreRequest {
                 kid,
@@ -1233,7 +1320,7 @@ impl<
                 extracted,
             }) => {
                 if !has_oscore::<SSC>() {
-                    unreachable!("State is not constructed");
+                    return Err(Own(Ok(CoAPError::internal_server_error())));
                 }
                 self.build_oscore_response(response, kid, correlation, extracted)
                     .map_err(Own)?;
@@ -0,0 +1,28 @@
+//! Values from the [COSE Algorithms](https://www.iana.org/assignments/cose/cose.xhtml#algorithms)
+//! registry for use in [`HeaderMap::alg`](crate::HeaderMap::alg).
+//!
+//! Only algorithms that are in use with Ariel OS are listed.
+
+/// ECDSA w/ SHA-256
+pub const ES256: i32 = -7;
+
+/// `EdDSA`
+pub const EDDSA: i32 = -8;
+
+/// HMAC w/ SHA-256 (HMAC 256/256)
+pub const HMAC_256_256: i32 = 5;
+
+/// AES-CCM mode 128-bit key, 64-bit tag, 13-byte nonce
+pub const AES_CCM_16_64_128: i32 = 10;
+
+/// AES-CCM mode 128-bit key, 128-bit tag, 13-byte nonce
+pub const AES_CCM_16_128_128: i32 = 30;
+
+/// AES-CCM mode 256-bit key, 128-bit tag, 13-byte nonce
+pub const AES_CCM_16_128_256: i32 = 31;
+
+/// AES-GCM mode w/ 128-bit key, 128-bit tag
+pub const A128GCM: i32 = 1;
+
+/// `ChaCha20`/`Poly1305` w/ 256-bit key, 128-bit tag
+pub const CHACHA20_POLY1305: i32 = 24;
@@ -0,0 +1,132 @@
+//! `COSE_Encrypt0` processing.
+
+use minicbor::encode::write::Cursor;
+
+use crate::{Error, HeaderMap, ToBeProcessed, write_concatenated_bytes};
+
+/// A `COSE_Encrypt0` structure as defined in [Section 5.2 of
+/// RFC9052](https://www.rfc-editor.org/rfc/rfc9052#name-single-recipient-encrypted).
+///
+/// Detached ciphertexts are not supported.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(minicbor::Decode, minicbor::Encode, Debug)]
+#[cbor(tag(16))]
+#[non_exhaustive]
+pub struct Encrypt0<'a> {
+    /// Serialized protected header map.
+    #[cbor(b(0), with = "minicbor::bytes")]
+    pub protected: &'a [u8],
+    /// Unprotected header map.
+    #[b(1)]
+    pub unprotected: HeaderMap<'a>,
+    /// Ciphertext, including the authentication tag.
+    #[cbor(b(2), with = "minicbor::bytes")]
+    pub ciphertext: &'a [u8],
+}
+
+impl<'a> Encrypt0<'a> {
+    /// Decodes a tagged `COSE_Encrypt0` structure.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Decode`] if the input is not a well-formed `COSE_Encrypt0`.
+    pub fn decode(data: &'a [u8]) -> Result<Self, Error> {
+        Ok(minicbor::decode(data)?)
+    }
+
+    /// Returns the combination of protected and unprotected headers.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Decode`] if the protected header is malformed.
+    pub fn headers(&self) -> Result<HeaderMap<'a>, Error> {
+        Ok(HeaderMap::decode_protected(self.protected)?.updated_with(&self.unprotected))
+    }
+
+    /// Builds the `Enc_structure` into `buffer`, which is the AAD for decrypting the
+    /// [`ciphertext`](Self::ciphertext).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::BufferTooSmall`] if the structure does not fit into `buffer`.
+    pub fn aad<'b>(&self, external_aad: &[u8], buffer: &'b mut [u8]) -> Result<&'b [u8], Error> {
+        ToBeProcessed {
+            context: "Encrypt0",
+            body_protected: self.protected,
+            external_aad,
+            payload: None,
+        }
+        .encode(buffer)
+    }
+
+    /// Decrypts the ciphertext using `decrypt`, and returns the plaintext.
+    ///
+    /// The ciphertext is copied to the start of `buffer`, and the `Enc_structure` is placed after
+    /// it. `decrypt` is called with the combined headers (which indicate the algorithm and IV), the
+    /// `Enc_structure` as AAD and the ciphertext, which it decrypts in place; it returns the length
+    /// of the plaintext, or `None` if decryption failed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::VerifyFailed`] if `decrypt` returns `None`, and otherwise errors like
+    /// [`Encrypt0::headers()`] and [`Encrypt0::aad()`].
+    pub fn decrypt<'b>(
+        &self,
+        external_aad: &[u8],
+        buffer: &'b mut [u8],
+        decrypt: impl FnOnce(&HeaderMap<'a>, &[u8], &mut [u8]) -> Option<usize>,
+    ) -> Result<&'b mut [u8], Error> {
+        let headers = self.headers()?;
+        if buffer.len() < self.ciphertext.len() {
+            return Err(Error::BufferTooSmall);
+        }
+        let (in_place, aad_buffer) = buffer.split_at_mut(self.ciphertext.len());
+        in_place.copy_from_slice(self.ciphertext);
+        let aad = self.aad(external_aad, aad_buffer)?;
+
+        let plaintext_len = decrypt(&headers, aad, in_place).ok_or(Error::VerifyFailed)?;
+        in_place.get_mut(..plaintext_len).ok_or(Error::VerifyFailed)
+    }
+}
+
+/// Builds a tagged `COSE_Encrypt0` structure into `buffer`.
+///
+/// `encrypt` is called with the `Enc_structure` as AAD and `plaintext`, which it encrypts in
+/// place according to the algorithm indicated in the headers, returning the detached
+/// authentication tag. `buffer` is also used to hold the `Enc_structure` while encrypting.
+///
+/// # Errors
+///
+/// Returns [`Error::BufferTooSmall`] if the structure does not fit into `buffer`, or if the
+/// protected header exceeds [`MAX_PROTECTED_LEN`](crate::MAX_PROTECTED_LEN).
+pub fn encrypt0<'b, T: AsRef<[u8]>>(
+    protected: &HeaderMap<'_>,
+    unprotected: &HeaderMap<'_>,
+    external_aad: &[u8],
+    plaintext: &mut [u8],
+    encrypt: impl FnOnce(&[u8], &mut [u8]) -> T,
+    buffer: &'b mut [u8],
+) -> Result<&'b [u8], Error> {
+    let protected = protected.encode_protected()?;
+    let tag = encrypt(
+        ToBeProcessed {
+            context: "Encrypt0",
+            body_protected: &protected,
+            external_aad,
+            payload: None,
+        }
+        .encode(buffer)?,
+        plaintext,
+    );
+
+    let mut encoder = minicbor::Encoder::new(Cursor::new(&mut *buffer));
+    encoder
+        .tag(minicbor::data::Tag::new(16))
+        .and_then(|encoder| encoder.array(3))
+        .and_then(|encoder| encoder.bytes(&protected))
+        .and_then(|encoder| encoder.encode(unprotected))
+        .map_err(|_| Error::BufferTooSmall)?;
+    write_concatenated_bytes(&mut encoder, &[plaintext, tag.as_ref()])?;
+    let written = encoder.into_writer().position();
+    buffer.get(..written).ok_or(Error::BufferTooSmall)
+}
@@ -0,0 +1,89 @@
+//! Signing and verifying `COSE_Sign1` structures with ES256 (ECDSA with P-256 and SHA-256).
+
+use p256::ecdsa::{
+    Signature, SigningKey, VerifyingKey,
+    signature::{Signer, Verifier},
+};
+
+use crate::{Error, HeaderMap, Sign1, alg};
+
+/// Builds a tagged `COSE_Sign1` structure signed with `key` into `buffer`.
+///
+/// The algorithm is placed in the protected header, and the key identifier (if any) in the
+/// unprotected header.
+///
+/// # Errors
+///
+/// Same as [`crate::sign1()`].
+pub fn sign<'b>(
+    key: &SigningKey,
+    kid: Option<&[u8]>,
+    payload: &[u8],
+    external_aad: &[u8],
+    buffer: &'b mut [u8],
+) -> Result<&'b [u8], Error> {
+    let unprotected = match kid {
+        Some(kid) => HeaderMap::new().with_kid(kid),
+        None => HeaderMap::new(),
+    };
+    crate::sign1(
+        &HeaderMap::new().with_alg(alg::ES256),
+        &unprotected,
+        payload,
+        external_aad,
+        |to_be_signed| {
+            let signature: Signature = key.sign(to_be_signed);
+            signature.to_bytes()
+        },
+        buffer,
+    )
+}
+
+/// Verifies a `COSE_Sign1` structure against `key`, and returns its payload on success.
+///
+/// # Errors
+///
+/// Returns [`Error::UnsupportedAlgorithm`] if the structure does not indicate ES256, and
+/// otherwise errors like [`Sign1::verify()`].
+pub fn verify<'a>(
+    sign1: &Sign1<'a>,
+    key: &VerifyingKey,
+    external_aad: &[u8],
+    buffer: &mut [u8],
+) -> Result<&'a [u8], Error> {
+    if sign1.headers()?.alg != Some(alg::ES256) {
+        return Err(Error::UnsupportedAlgorithm);
+    }
+    sign1.verify(external_aad, buffer, |_headers, to_be_signed, signature| {
+        verify_signature(key, to_be_signed, signature)
+    })
+}
+
+/// Verifies a `COSE_Sign1` structure over a detached `payload` against `key`.
+///
+/// # Errors
+///
+/// Returns [`Error::UnsupportedAlgorithm`] if the structure does not indicate ES256, and
+/// otherwise errors like [`Sign1::verify_detached()`].
+pub fn verify_detached(
+    sign1: &Sign1<'_>,
+    key: &VerifyingKey,
+    payload: &[u8],
+    external_aad: &[u8],
+    buffer: &mut [u8],
+) -> Result<(), Error> {
+    if sign1.headers()?.alg != Some(alg::ES256) {
+        return Err(Error::UnsupportedAlgorithm);
+    }
+    sign1.verify_detached(
+        payload,
+        external_aad,
+        buffer,
+        |_headers, to_be_signed, signature| verify_signature(key, to_be_signed, signature),
+    )
+}
+
+fn verify_signature(key: &VerifyingKey, to_be_signed: &[u8], signature: &[u8]) -> bool {
+    Signature::from_slice(signature)
+        .is_ok_and(|signature| key.verify(to_be_signed, &signature).is_ok())
+}
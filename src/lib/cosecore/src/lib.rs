@@ -0,0 +1,475 @@
+//! Building and processing COSE structures on embedded devices.
+//!
+//! This crate implements the message structures of [CBOR Object Signing and Encryption
+//! (COSE)](https://www.rfc-editor.org/rfc/rfc9052) that are used with a single recipient and no
+//! key distribution: [`COSE_Sign1`](Sign1), [`COSE_Encrypt0`](Encrypt0) and [`COSE_Mac0`](Mac0).
+//! It is shared between the components that need them (ACE tokens in coapcore, firmware update
+//! manifests, attestation), so that the structures and the data that goes into signatures and
+//! AADs are only implemented once.
+//!
+//! The crate does not implement any cryptographic algorithms itself: It decodes and encodes the
+//! structures, produces the bytes that are to be signed, authenticated or used as AAD, and calls
+//! out to closures that perform the cryptographic operation. The algorithm to use is indicated in
+//! the [`HeaderMap`]; values are listed in [`alg`]. Convenience functions for ES256 are available
+//! with the `es256` feature.
+//!
+//! All processing happens on caller provided buffers, and borrows from the input where possible.
+//!
+//! # Usage
+//!
+//! A `COSE_Sign1` is built by [`sign1()`] and processed through [`Sign1::decode()`]:
+//!
+//! ```
+//! # // A stand-in for an actual signature algorithm
+//! # fn sign(data: &[u8]) -> [u8; 4] { [data.len() as u8; 4] }
+//! # fn verify(data: &[u8], signature: &[u8]) -> bool { signature == sign(data) }
+//! let mut buffer = [0; 64];
+//! let signed = cosecore::sign1(
+//!     &cosecore::HeaderMap::new().with_alg(cosecore::alg::ES256),
+//!     &cosecore::HeaderMap::new().with_kid(b"11"),
+//!     b"This is the content.",
+//!     b"",
+//!     sign,
+//!     &mut buffer,
+//! )
+//! .unwrap();
+//!
+//! let received = cosecore::Sign1::decode(signed).unwrap();
+//! let mut scratch = [0; 64];
+//! let payload = received
+//!     .verify(b"", &mut scratch, |headers, to_be_signed, signature| {
+//!         headers.alg == Some(cosecore::alg::ES256) && verify(to_be_signed, signature)
+//!     })
+//!     .unwrap();
+//! assert_eq!(payload, b"This is the content.");
+//! ```
+//!
+//! # Cargo features
+#![doc = document_features::document_features!(feature_label = r#"<span class="stab portability"><code>{feature}</code></span>"#)]
+#![no_std]
+#![cfg_attr(feature = "_nightly_docs", feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+pub mod alg;
+mod encrypt0;
+#[cfg(feature = "es256")]
+pub mod es256;
+mod mac0;
+mod sign1;
+
+pub use encrypt0::{Encrypt0, encrypt0};
+pub use mac0::{Mac0, mac0};
+pub use sign1::{Sign1, sign1};
+
+use minicbor::encode::write::{Cursor, Write};
+
+/// Maximum encoded length of a protected header map that can be built by this crate.
+///
+/// This only limits headers passed into [`sign1()`], [`encrypt0()`] and [`mac0()`], as they need
+/// to be encoded into a temporary buffer; decoded protected headers may be of any length.
+pub const MAX_PROTECTED_LEN: usize = 64;
+
+/// A COSE header map.
+///
+/// Only the common header parameters needed for single-recipient structures are represented;
+/// others are ignored when decoding. Full attribute references are in the [COSE Header Parameters
+/// registry](https://www.iana.org/assignments/cose/cose.xhtml#header-parameters).
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(minicbor::Decode, minicbor::Encode, Default, Debug, Clone, PartialEq, Eq)]
+#[cbor(map)]
+#[non_exhaustive]
+pub struct HeaderMap<'a> {
+    /// Algorithm (label 1).
+    // Might be extended as more exotic algorithms are supported; text values are not supported.
+    #[n(1)]
+    pub alg: Option<i32>,
+    /// Key identifier (label 4).
+    #[cbor(b(4), with = "minicbor::bytes")]
+    pub kid: Option<&'a [u8]>,
+    /// Full initialization vector (label 5).
+    #[cbor(b(5), with = "minicbor::bytes")]
+    pub iv: Option<&'a [u8]>,
+}
+
+impl<'a> HeaderMap<'a> {
+    /// Creates an empty header map.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            alg: None,
+            kid: None,
+            iv: None,
+        }
+    }
+
+    /// Sets the algorithm.
+    #[must_use]
+    pub const fn with_alg(mut self, alg: i32) -> Self {
+        self.alg = Some(alg);
+        self
+    }
+
+    /// Sets the key identifier.
+    #[must_use]
+    pub const fn with_kid(mut self, kid: &'a [u8]) -> Self {
+        self.kid = Some(kid);
+        self
+    }
+
+    /// Sets the initialization vector.
+    #[must_use]
+    pub const fn with_iv(mut self, iv: &'a [u8]) -> Self {
+        self.iv = Some(iv);
+        self
+    }
+
+    /// Merges two header maps, using this map's value in case of conflict.
+    ///
+    /// This is used to combine protected (`self`) and unprotected (`other`) headers: COSE does not
+    /// allow a parameter to be present in both, and preferring the protected one ensures that the
+    /// unprotected one can never override it.
+    #[must_use]
+    pub fn updated_with(&self, other: &Self) -> Self {
+        Self {
+            alg: self.alg.or(other.alg),
+            kid: self.kid.or(other.kid),
+            iv: self.iv.or(other.iv),
+        }
+    }
+
+    /// Decodes a serialized protected header.
+    ///
+    /// A zero-length byte string is equivalent to an empty map.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Decode`] if the header is not a well-formed header map.
+    fn decode_protected(protected: &'a [u8]) -> Result<Self, Error> {
+        if protected.is_empty() {
+            return Ok(Self::new());
+        }
+        Ok(minicbor::decode(protected)?)
+    }
+
+    /// Serializes as a protected header into a temporary buffer.
+    ///
+    /// An empty map is serialized to a zero-length byte string, as recommended in Section 3 of
+    /// RFC 9052.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::BufferTooSmall`] if the header does not fit into [`MAX_PROTECTED_LEN`]
+    /// bytes.
+    fn encode_protected(&self) -> Result<heapless::Vec<u8, MAX_PROTECTED_LEN>, Error> {
+        let mut buffer = [0; MAX_PROTECTED_LEN];
+        let encoded = if *self == Self::new() {
+            &[]
+        } else {
+            encode_into(self, &mut buffer)?
+        };
+        heapless::Vec::from_slice(encoded).map_err(|()| Error::BufferTooSmall)
+    }
+}
+
+/// Errors that can occur when building or processing COSE structures.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The input is not a well-formed COSE structure of the expected kind.
+    Decode,
+    /// A provided buffer is too small.
+    BufferTooSmall,
+    /// The signature or authentication tag did not verify.
+    VerifyFailed,
+    /// The algorithm indicated in the headers is not supported.
+    UnsupportedAlgorithm,
+}
+
+impl From<minicbor::decode::Error> for Error {
+    fn from(_: minicbor::decode::Error) -> Self {
+        Self::Decode
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Decode => write!(f, "malformed COSE structure"),
+            Self::BufferTooSmall => write!(f, "buffer too small"),
+            Self::VerifyFailed => write!(f, "verification failed"),
+            Self::UnsupportedAlgorithm => write!(f, "unsupported algorithm"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// The `Sig_structure`, `Enc_structure` and `MAC_structure` that are fed into the cryptographic
+/// algorithms.
+///
+/// Those structures only differ in their context string and in whether a payload is present.
+#[derive(minicbor::Encode)]
+struct ToBeProcessed<'a> {
+    #[n(0)]
+    context: &'static str,
+    #[cbor(b(1), with = "minicbor::bytes")]
+    body_protected: &'a [u8],
+    #[cbor(b(2), with = "minicbor::bytes")]
+    external_aad: &'a [u8],
+    #[cbor(b(3), with = "minicbor::bytes")]
+    payload: Option<&'a [u8]>,
+}
+
+impl ToBeProcessed<'_> {
+    /// Encodes the structure into the start of `buffer`, returning the encoded part.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::BufferTooSmall`] if the structure does not fit into `buffer`.
+    fn encode(self, buffer: &mut [u8]) -> Result<&[u8], Error> {
+        encode_into(&self, buffer)
+    }
+}
+
+/// Encodes `value` into the start of `buffer`, returning the encoded part.
+///
+/// # Errors
+///
+/// Returns [`Error::BufferTooSmall`] if `value` does not fit into `buffer`.
+fn encode_into<'b>(
+    value: &impl minicbor::Encode<()>,
+    buffer: &'b mut [u8],
+) -> Result<&'b [u8], Error> {
+    let mut cursor = Cursor::new(&mut *buffer);
+    minicbor::encode(value, &mut cursor).map_err(|_| Error::BufferTooSmall)?;
+    let written = cursor.position();
+    buffer.get(..written).ok_or(Error::BufferTooSmall)
+}
+
+/// Writes a byte string that is the concatenation of `parts`.
+///
+/// This is used where the content of a byte string is not available in one piece.
+///
+/// # Errors
+///
+/// Returns [`Error::BufferTooSmall`] if the writer runs out of space.
+#[expect(clippy::missing_panics_doc, reason = "does not panic")]
+fn write_concatenated_bytes<W: Write>(
+    encoder: &mut minicbor::Encoder<W>,
+    parts: &[&[u8]],
+) -> Result<(), Error> {
+    const MAJOR_BYTES: u8 = 2 << 5;
+
+    let len = parts.iter().map(|part| part.len()).sum::<usize>() as u64;
+    let (additional_info, argument_len) = match len {
+        0..24 => (u8::try_from(len).expect("checked range"), 0),
+        24..0x100 => (24, 1),
+        0x100..0x1_0000 => (25, 2),
+        0x1_0000..0x1_0000_0000 => (26, 4),
+        _ => (27, 8),
+    };
+    let len = len.to_be_bytes();
+    let (_, argument) = len.split_at(len.len() - argument_len);
+
+    let writer = encoder.writer_mut();
+    writer
+        .write_all(&[MAJOR_BYTES | additional_info])
+        .and_then(|()| writer.write_all(argument))
+        .map_err(|_| Error::BufferTooSmall)?;
+    for part in parts {
+        writer.write_all(part).map_err(|_| Error::BufferTooSmall)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[expect(clippy::missing_panics_doc, reason = "tests")]
+mod tests {
+    use super::*;
+
+    /// `COSE_Sign1` from Appendix C.2.1 of RFC 9052.
+    const RFC9052_SIGN1: &[u8] = &[
+        0xd2, 0x84, 0x43, 0xa1, 0x01, 0x26, 0xa1, 0x04, 0x42, 0x31, 0x31, 0x54, 0x54, 0x68, 0x69,
+        0x73, 0x20, 0x69, 0x73, 0x20, 0x74, 0x68, 0x65, 0x20, 0x63, 0x6f, 0x6e, 0x74, 0x65, 0x6e,
+        0x74, 0x2e, 0x58, 0x40, 0x8e, 0xb3, 0x3e, 0x4c, 0xa3, 0x1d, 0x1c, 0x46, 0x5a, 0xb0, 0x5a,
+        0xac, 0x34, 0xcc, 0x6b, 0x23, 0xd5, 0x8f, 0xef, 0x5c, 0x08, 0x31, 0x06, 0xc4, 0xd2, 0x5a,
+        0x91, 0xae, 0xf0, 0xb0, 0x11, 0x7e, 0x2a, 0xf9, 0xa2, 0x91, 0xaa, 0x32, 0xe1, 0x4a, 0xb8,
+        0x34, 0xdc, 0x56, 0xed, 0x2a, 0x22, 0x34, 0x44, 0x54, 0x7e, 0x01, 0xf1, 0x1d, 0x3b, 0x09,
+        0x16, 0xe5, 0xa4, 0xc3, 0x45, 0xca, 0xcb, 0x36,
+    ];
+
+    /// Public key "11" from the COSE examples, as used in [`RFC9052_SIGN1`].
+    #[cfg(feature = "es256")]
+    const RFC9052_KEY_X: [u8; 32] = [
+        0xba, 0xc5, 0xb1, 0x1c, 0xad, 0x8f, 0x99, 0xf9, 0xc7, 0x2b, 0x05, 0xcf, 0x4b, 0x9e, 0x26,
+        0xd2, 0x44, 0xdc, 0x18, 0x9f, 0x74, 0x52, 0x28, 0x25, 0x5a, 0x21, 0x9a, 0x86, 0xd6, 0xa0,
+        0x9e, 0xff,
+    ];
+    #[cfg(feature = "es256")]
+    const RFC9052_KEY_Y: [u8; 32] = [
+        0x20, 0x13, 0x8b, 0xf8, 0x2d, 0xc1, 0xb6, 0xd5, 0x62, 0xbe, 0x0f, 0xa5, 0x4a, 0xb7, 0x80,
+        0x4a, 0x3a, 0x64, 0xb6, 0xd7, 0x2c, 0xcf, 0xed, 0x6b, 0x6f, 0xb6, 0xed, 0x28, 0xbb, 0xfc,
+        0x11, 0x7e,
+    ];
+
+    #[test]
+    fn sign1_decode() {
+        let sign1 = Sign1::decode(RFC9052_SIGN1).unwrap();
+        let headers = sign1.headers().unwrap();
+        assert_eq!(headers.alg, Some(alg::ES256));
+        assert_eq!(headers.kid, Some(&b"11"[..]));
+        assert_eq!(sign1.payload, Some(&b"This is the content."[..]));
+
+        let mut buffer = [0; 64];
+        assert_eq!(
+            sign1.to_be_signed(b"", &mut buffer).unwrap(),
+            b"\x84\x6aSignature1\x43\xa1\x01\x26\x40\x54This is the content."
+        );
+    }
+
+    #[test]
+    fn sign1_roundtrip() {
+        let mut buffer = [0; 128];
+        let built = sign1(
+            &HeaderMap::new().with_alg(alg::ES256),
+            &HeaderMap::new().with_kid(b"11"),
+            b"This is the content.",
+            b"",
+            |_| [0; 64],
+            &mut buffer,
+        )
+        .unwrap();
+        // Identical to the RFC example up to the signature.
+        assert_eq!(built.get(..34), RFC9052_SIGN1.get(..34));
+        assert_eq!(built.len(), RFC9052_SIGN1.len());
+    }
+
+    #[cfg(feature = "es256")]
+    #[test]
+    fn es256_verify() {
+        let key = p256::ecdsa::VerifyingKey::from_encoded_point(
+            &p256::EncodedPoint::from_affine_coordinates(
+                &RFC9052_KEY_X.into(),
+                &RFC9052_KEY_Y.into(),
+                false,
+            ),
+        )
+        .unwrap();
+        let sign1 = Sign1::decode(RFC9052_SIGN1).unwrap();
+        let mut buffer = [0; 64];
+        assert_eq!(
+            es256::verify(&sign1, &key, b"", &mut buffer),
+            Ok(&b"This is the content."[..])
+        );
+        assert_eq!(
+            es256::verify(&sign1, &key, b"other", &mut buffer),
+            Err(Error::VerifyFailed)
+        );
+    }
+
+    #[cfg(feature = "es256")]
+    #[test]
+    fn es256_roundtrip() {
+        let key = p256::ecdsa::SigningKey::from_bytes(&[0x2a; 32].into()).unwrap();
+        let mut buffer = [0; 128];
+        let built = es256::sign(&key, Some(b"kid"), b"payload", b"aad", &mut buffer).unwrap();
+        let sign1 = Sign1::decode(built).unwrap();
+        let mut scratch = [0; 64];
+        assert_eq!(
+            es256::verify(&sign1, key.verifying_key(), b"aad", &mut scratch),
+            Ok(&b"payload"[..])
+        );
+    }
+
+    #[cfg(feature = "es256")]
+    #[test]
+    fn es256_detached() {
+        let key = p256::ecdsa::SigningKey::from_bytes(&[0x2a; 32].into()).unwrap();
+        let mut buffer = [0; 128];
+        let built = es256::sign(&key, None, b"payload", b"", &mut buffer).unwrap();
+        let mut sign1 = Sign1::decode(built).unwrap();
+        sign1.payload = None;
+        let mut detached = [0; 128];
+        let detached = encode_into(&sign1, &mut detached).unwrap();
+
+        let sign1 = Sign1::decode(detached).unwrap();
+        assert_eq!(sign1.payload, None);
+        let mut scratch = [0; 64];
+        assert_eq!(
+            es256::verify(&sign1, key.verifying_key(), b"", &mut scratch),
+            Err(Error::Decode)
+        );
+        assert_eq!(
+            es256::verify_detached(&sign1, key.verifying_key(), b"payload", b"", &mut scratch),
+            Ok(())
+        );
+        assert_eq!(
+            es256::verify_detached(&sign1, key.verifying_key(), b"other", b"", &mut scratch),
+            Err(Error::VerifyFailed)
+        );
+    }
+
+    #[test]
+    fn encrypt0_roundtrip() {
+        // A stand-in for an AEAD algorithm: XOR with a fixed byte, and the AAD's length as tag.
+        fn encrypt(aad: &[u8], data: &mut [u8]) -> [u8; 1] {
+            for b in data.iter_mut() {
+                *b ^= 0x55;
+            }
+            [u8::try_from(aad.len()).unwrap()]
+        }
+
+        let mut plaintext = *b"secret";
+        let mut buffer = [0; 64];
+        let built = encrypt0(
+            &HeaderMap::new().with_alg(alg::AES_CCM_16_64_128),
+            &HeaderMap::new().with_iv(b"iv"),
+            b"",
+            &mut plaintext,
+            |aad, data| {
+                assert_eq!(aad, b"\x83\x68Encrypt0\x43\xa1\x01\x0a\x40");
+                encrypt(aad, data)
+            },
+            &mut buffer,
+        )
+        .unwrap();
+
+        let encrypt0 = Encrypt0::decode(built).unwrap();
+        assert_eq!(encrypt0.headers().unwrap().iv, Some(&b"iv"[..]));
+        assert_eq!(encrypt0.ciphertext.len(), 7);
+        let mut scratch = [0; 64];
+        let decrypted = encrypt0
+            .decrypt(b"", &mut scratch, |_, aad, data| {
+                let (ciphertext, tag) = data.split_at_mut(data.len() - 1);
+                let expected = encrypt(aad, ciphertext);
+                (tag == expected).then_some(ciphertext.len())
+            })
+            .unwrap();
+        assert_eq!(decrypted, b"secret");
+    }
+
+    #[test]
+    fn mac0_roundtrip() {
+        let mut buffer = [0; 64];
+        let built = mac0(
+            &HeaderMap::new().with_alg(alg::HMAC_256_256),
+            &HeaderMap::new(),
+            b"payload",
+            b"",
+            |to_be_maced| [u8::try_from(to_be_maced.len()).unwrap()],
+            &mut buffer,
+        )
+        .unwrap();
+
+        let mac0 = Mac0::decode(built).unwrap();
+        let mut scratch = [0; 64];
+        assert_eq!(
+            mac0.verify(b"", &mut scratch, |_, to_be_maced, tag| {
+                tag == [u8::try_from(to_be_maced.len()).unwrap()]
+            }),
+            Ok(&b"payload"[..])
+        );
+        assert!(Sign1::decode(built).is_err());
+    }
+}
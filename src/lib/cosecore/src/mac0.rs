@@ -0,0 +1,130 @@
+//! `COSE_Mac0` processing.
+
+use crate::{Error, HeaderMap, ToBeProcessed, encode_into};
+
+/// A `COSE_Mac0` structure as defined in [Section 6.2 of
+/// RFC9052](https://www.rfc-editor.org/rfc/rfc9052#name-maced-messages-with-implici).
+///
+/// Detached payloads are not supported.
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(minicbor::Decode, minicbor::Encode, Debug)]
+#[cbor(tag(17))]
+#[non_exhaustive]
+pub struct Mac0<'a> {
+    /// Serialized protected header map.
+    #[cbor(b(0), with = "minicbor::bytes")]
+    pub protected: &'a [u8],
+    /// Unprotected header map.
+    #[b(1)]
+    pub unprotected: HeaderMap<'a>,
+    /// Payload.
+    ///
+    /// Until the tag has been verified, this must not be used.
+    #[cbor(b(2), with = "minicbor::bytes")]
+    pub payload: &'a [u8],
+    /// Authentication tag.
+    #[cbor(b(3), with = "minicbor::bytes")]
+    pub tag: &'a [u8],
+}
+
+impl<'a> Mac0<'a> {
+    /// Decodes a tagged `COSE_Mac0` structure.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Decode`] if the input is not a well-formed `COSE_Mac0`.
+    pub fn decode(data: &'a [u8]) -> Result<Self, Error> {
+        Ok(minicbor::decode(data)?)
+    }
+
+    /// Returns the combination of protected and unprotected headers.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Decode`] if the protected header is malformed.
+    pub fn headers(&self) -> Result<HeaderMap<'a>, Error> {
+        Ok(HeaderMap::decode_protected(self.protected)?.updated_with(&self.unprotected))
+    }
+
+    /// Builds the `MAC_structure` into `buffer`, which is what the [`tag`](Self::tag) needs to be
+    /// verified against.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::BufferTooSmall`] if the structure does not fit into `buffer`.
+    pub fn to_be_maced<'b>(
+        &self,
+        external_aad: &[u8],
+        buffer: &'b mut [u8],
+    ) -> Result<&'b [u8], Error> {
+        ToBeProcessed {
+            context: "MAC0",
+            body_protected: self.protected,
+            external_aad,
+            payload: Some(self.payload),
+        }
+        .encode(buffer)
+    }
+
+    /// Verifies the tag using `verify`, and returns the payload on success.
+    ///
+    /// `verify` is called with the combined headers (which indicate the algorithm), the
+    /// `MAC_structure` and the tag; it returns whether the tag is valid, and should compare tags
+    /// in constant time (eg. with `secretcore::ct_eq()`). `buffer` is used for the `MAC_structure`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::VerifyFailed`] if `verify` returns `false`, and otherwise errors like
+    /// [`Mac0::headers()`] and [`Mac0::to_be_maced()`].
+    pub fn verify(
+        &self,
+        external_aad: &[u8],
+        buffer: &mut [u8],
+        verify: impl FnOnce(&HeaderMap<'a>, &[u8], &[u8]) -> bool,
+    ) -> Result<&'a [u8], Error> {
+        let headers = self.headers()?;
+        let to_be_maced = self.to_be_maced(external_aad, buffer)?;
+        if !verify(&headers, to_be_maced, self.tag) {
+            return Err(Error::VerifyFailed);
+        }
+        Ok(self.payload)
+    }
+}
+
+/// Builds a tagged `COSE_Mac0` structure into `buffer`.
+///
+/// `mac` is called with the `MAC_structure`, and returns the tag according to the algorithm
+/// indicated in the headers. `buffer` is also used to hold the `MAC_structure` while computing the
+/// tag.
+///
+/// # Errors
+///
+/// Returns [`Error::BufferTooSmall`] if the structure does not fit into `buffer`, or if the
+/// protected header exceeds [`MAX_PROTECTED_LEN`](crate::MAX_PROTECTED_LEN).
+pub fn mac0<'b, T: AsRef<[u8]>>(
+    protected: &HeaderMap<'_>,
+    unprotected: &HeaderMap<'_>,
+    payload: &[u8],
+    external_aad: &[u8],
+    mac: impl FnOnce(&[u8]) -> T,
+    buffer: &'b mut [u8],
+) -> Result<&'b [u8], Error> {
+    let protected = protected.encode_protected()?;
+    let tag = mac(ToBeProcessed {
+        context: "MAC0",
+        body_protected: &protected,
+        external_aad,
+        payload: Some(payload),
+    }
+    .encode(buffer)?);
+
+    encode_into(
+        &Mac0 {
+            protected: &protected,
+            unprotected: unprotected.clone(),
+            payload,
+            tag: tag.as_ref(),
+        },
+        buffer,
+    )
+}
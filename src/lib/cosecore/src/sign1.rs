@@ -0,0 +1,170 @@
+//! `COSE_Sign1` processing.
+
+use crate::{Error, HeaderMap, ToBeProcessed, encode_into};
+
+/// A `COSE_Sign1` structure as defined in [Section 4.2 of
+/// RFC9052](https://www.rfc-editor.org/rfc/rfc9052#name-signing-with-one-signer).
+///
+/// Structures with detached payloads are verified with [`Sign1::verify_detached()`].
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[derive(minicbor::Decode, minicbor::Encode, Debug)]
+#[cbor(tag(18))]
+#[non_exhaustive]
+pub struct Sign1<'a> {
+    /// Serialized protected header map.
+    #[cbor(b(0), with = "minicbor::bytes")]
+    pub protected: &'a [u8],
+    /// Unprotected header map.
+    #[b(1)]
+    pub unprotected: HeaderMap<'a>,
+    /// Payload, or `None` if the payload is detached.
+    ///
+    /// Until the signature has been verified, this must not be used.
+    #[cbor(b(2), with = "minicbor::bytes")]
+    pub payload: Option<&'a [u8]>,
+    /// Signature.
+    #[cbor(b(3), with = "minicbor::bytes")]
+    pub signature: &'a [u8],
+}
+
+impl<'a> Sign1<'a> {
+    /// Decodes a tagged `COSE_Sign1` structure.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Decode`] if the input is not a well-formed `COSE_Sign1`.
+    pub fn decode(data: &'a [u8]) -> Result<Self, Error> {
+        Ok(minicbor::decode(data)?)
+    }
+
+    /// Returns the combination of protected and unprotected headers.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Decode`] if the protected header is malformed.
+    pub fn headers(&self) -> Result<HeaderMap<'a>, Error> {
+        Ok(HeaderMap::decode_protected(self.protected)?.updated_with(&self.unprotected))
+    }
+
+    /// Builds the `Sig_structure` into `buffer`, which is what the [`signature`](Self::signature)
+    /// needs to be verified against.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::BufferTooSmall`] if the structure does not fit into `buffer`, and
+    /// [`Error::Decode`] if the payload is detached.
+    pub fn to_be_signed<'b>(
+        &self,
+        external_aad: &[u8],
+        buffer: &'b mut [u8],
+    ) -> Result<&'b [u8], Error> {
+        self.to_be_signed_detached(self.payload.ok_or(Error::Decode)?, external_aad, buffer)
+    }
+
+    /// Builds the `Sig_structure` into `buffer` like [`Sign1::to_be_signed()`], but for a
+    /// `payload` that is transported separately.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::BufferTooSmall`] if the structure does not fit into `buffer`.
+    pub fn to_be_signed_detached<'b>(
+        &self,
+        payload: &[u8],
+        external_aad: &[u8],
+        buffer: &'b mut [u8],
+    ) -> Result<&'b [u8], Error> {
+        ToBeProcessed {
+            context: "Signature1",
+            body_protected: self.protected,
+            external_aad,
+            payload: Some(payload),
+        }
+        .encode(buffer)
+    }
+
+    /// Verifies the signature using `verify`, and returns the payload on success.
+    ///
+    /// `verify` is called with the combined headers (which indicate the algorithm), the
+    /// `Sig_structure` and the signature; it returns whether the signature is valid. `buffer` is
+    /// used for the `Sig_structure`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Decode`] if the payload is detached, [`Error::VerifyFailed`] if `verify`
+    /// returns `false`, and otherwise errors like [`Sign1::headers()`] and
+    /// [`Sign1::to_be_signed()`].
+    pub fn verify(
+        &self,
+        external_aad: &[u8],
+        buffer: &mut [u8],
+        verify: impl FnOnce(&HeaderMap<'a>, &[u8], &[u8]) -> bool,
+    ) -> Result<&'a [u8], Error> {
+        let payload = self.payload.ok_or(Error::Decode)?;
+        self.verify_detached(payload, external_aad, buffer, verify)?;
+        Ok(payload)
+    }
+
+    /// Verifies the signature over a `payload` that is transported separately using `verify`,
+    /// like [`Sign1::verify()`].
+    ///
+    /// The structure's own payload is not considered; it is usually absent.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::VerifyFailed`] if `verify` returns `false`, and otherwise errors like
+    /// [`Sign1::headers()`] and [`Sign1::to_be_signed_detached()`].
+    pub fn verify_detached(
+        &self,
+        payload: &[u8],
+        external_aad: &[u8],
+        buffer: &mut [u8],
+        verify: impl FnOnce(&HeaderMap<'a>, &[u8], &[u8]) -> bool,
+    ) -> Result<(), Error> {
+        let headers = self.headers()?;
+        let to_be_signed = self.to_be_signed_detached(payload, external_aad, buffer)?;
+        if !verify(&headers, to_be_signed, self.signature) {
+            return Err(Error::VerifyFailed);
+        }
+        Ok(())
+    }
+}
+
+/// Builds a tagged `COSE_Sign1` structure into `buffer`.
+///
+/// `sign` is called with the `Sig_structure`, and returns the signature according to the
+/// algorithm indicated in the headers. `buffer` is also used to hold the `Sig_structure` while
+/// signing.
+///
+/// # Errors
+///
+/// Returns [`Error::BufferTooSmall`] if the structure does not fit into `buffer`, or if the
+/// protected header exceeds [`MAX_PROTECTED_LEN`](crate::MAX_PROTECTED_LEN).
+pub fn sign1<'b, S: AsRef<[u8]>>(
+    protected: &HeaderMap<'_>,
+    unprotected: &HeaderMap<'_>,
+    payload: &[u8],
+    external_aad: &[u8],
+    sign: impl FnOnce(&[u8]) -> S,
+    buffer: &'b mut [u8],
+) -> Result<&'b [u8], Error> {
+    let protected = protected.encode_protected()?;
+    let signature = sign(
+        ToBeProcessed {
+            context: "Signature1",
+            body_protected: &protected,
+            external_aad,
+            payload: Some(payload),
+        }
+        .encode(buffer)?,
+    );
+
+    encode_into(
+        &Sign1 {
+            protected: &protected,
+            unprotected: unprotected.clone(),
+            payload: Some(payload),
+            signature: signature.as_ref(),
+        },
+        buffer,
+    )
+}
@@ -0,0 +1,189 @@
+//! Helpers for handling secrets on embedded devices.
+//!
+//! Key material and other secrets should not outlive their use, and should not leak through the
+//! time it takes to process them. This crate provides the building blocks that are shared by the
+//! components handling secrets (such as ACE token processing in coapcore and the key vault):
+//!
+//! * [`ct_eq()`] compares byte strings in constant time, for checks that are not already done by
+//!   a cryptographic implementation (eg. of a MAC computed separately, or of a password);
+//! * [`Secret`] owns a secret, erases it when dropped, and keeps it out of debug output;
+//! * [`EraseOnDrop`] erases a borrowed buffer (eg. one that held decrypted data) once it goes out
+//!   of scope, on all return paths;
+//! * [`scrub_stack()`] and [`scrubbing_stack()`] overwrite stack memory that was used by
+//!   cryptographic operations, which may leave copies of keys or intermediate values behind.
+//!
+//! Erasure is implemented through the [`zeroize`] crate, whose [`Zeroize`] trait is re-exported.
+//!
+//! The authentication tags of ACE tokens in coapcore and of sealed secrets in the vault are checked
+//! by their AEAD implementations, which already compare them in constant time; they do not need
+//! [`ct_eq()`].
+//!
+//! ```
+//! use secretcore::Secret;
+//!
+//! let key = Secret::new([0x2a_u8; 16]);
+//! assert!(key.ct_eq(&[0x2a; 16]));
+//! assert_eq!(format!("{key:?}"), "Secret(..)");
+//! ```
+#![cfg_attr(not(test), no_std)]
+#![cfg_attr(feature = "_nightly_docs", feature(doc_auto_cfg))]
+#![deny(missing_docs)]
+
+use core::ops::{Deref, DerefMut};
+
+pub use zeroize::Zeroize;
+
+/// Compares two byte strings in constant time.
+///
+/// The time taken depends only on the lengths of the inputs, not on their content. (The lengths
+/// are not considered secret.)
+#[must_use]
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    subtle::ConstantTimeEq::ct_eq(a, b).into()
+}
+
+/// An owned secret that is erased when dropped.
+///
+/// The [`Debug`](core::fmt::Debug) implementation does not show the secret.
+#[derive(Default, Clone)]
+pub struct Secret<T: Zeroize>(T);
+
+impl<T: Zeroize> Secret<T> {
+    /// Wraps a secret.
+    pub const fn new(secret: T) -> Self {
+        Self(secret)
+    }
+
+    /// Returns a reference to the secret.
+    ///
+    /// Callers should avoid copying the secret out, as copies are not erased.
+    #[must_use]
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    /// Returns a mutable reference to the secret, eg. to fill it in place.
+    #[must_use]
+    pub fn expose_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Zeroize + AsRef<[u8]>> Secret<T> {
+    /// Compares the secret to `other` in constant time, see [`ct_eq()`].
+    #[must_use]
+    pub fn ct_eq(&self, other: &[u8]) -> bool {
+        ct_eq(self.0.as_ref(), other)
+    }
+}
+
+impl<T: Zeroize> From<T> for Secret<T> {
+    fn from(secret: T) -> Self {
+        Self(secret)
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<T: Zeroize> zeroize::ZeroizeOnDrop for Secret<T> {}
+
+impl<T: Zeroize> core::fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Secret(..)")
+    }
+}
+
+/// A borrowed buffer that is erased when this goes out of scope.
+///
+/// This is useful where secrets are processed in buffers that are not owned by the code handling
+/// them, eg. when decrypting into a caller provided buffer.
+pub struct EraseOnDrop<'a, T: Zeroize + ?Sized>(&'a mut T);
+
+impl<'a, T: Zeroize + ?Sized> EraseOnDrop<'a, T> {
+    /// Arranges for `buffer` to be erased when the returned value is dropped.
+    pub fn new(buffer: &'a mut T) -> Self {
+        Self(buffer)
+    }
+}
+
+impl<T: Zeroize + ?Sized> Deref for EraseOnDrop<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.0
+    }
+}
+
+impl<T: Zeroize + ?Sized> DerefMut for EraseOnDrop<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.0
+    }
+}
+
+impl<T: Zeroize + ?Sized> Drop for EraseOnDrop<'_, T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// Overwrites `N` bytes of stack below the caller's stack frame.
+///
+/// Functions called earlier from the same frame have used that memory, and may have left secrets
+/// behind. `N` should be chosen to cover the stack usage of those functions.
+#[inline(never)]
+pub fn scrub_stack<const N: usize>() {
+    let mut area = [0u8; N];
+    area.zeroize();
+    core::hint::black_box(&mut area);
+}
+
+/// Runs `f`, and then overwrites `N` bytes of the stack that `f` used.
+///
+/// See [`scrub_stack()`] for how to choose `N`.
+pub fn scrubbing_stack<const N: usize, R>(f: impl FnOnce() -> R) -> R {
+    let result = f();
+    scrub_stack::<N>();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare() {
+        assert!(ct_eq(b"secret", b"secret"));
+        assert!(!ct_eq(b"secret", b"secreT"));
+        assert!(!ct_eq(b"secret", b"secret!"));
+        assert!(ct_eq(b"", b""));
+    }
+
+    #[test]
+    fn secret() {
+        let mut secret = Secret::new([0_u8; 4]);
+        secret.expose_mut().copy_from_slice(b"abcd");
+        assert_eq!(secret.expose(), b"abcd");
+        assert!(secret.ct_eq(b"abcd"));
+        assert_eq!(format!("{secret:?}"), "Secret(..)");
+    }
+
+    #[test]
+    fn erase_on_drop() {
+        let mut buffer = *b"plaintext";
+        {
+            let mut guard = EraseOnDrop::new(buffer.as_mut_slice());
+            guard.copy_from_slice(b"Plaintext");
+            assert_eq!(&*guard, b"Plaintext");
+        }
+        assert_eq!(buffer, [0; 9]);
+    }
+
+    #[test]
+    fn scrubbing() {
+        assert_eq!(scrubbing_stack::<256, _>(|| 42), 42);
+    }
+}
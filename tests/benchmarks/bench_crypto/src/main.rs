@@ -0,0 +1,19 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::debug::println;
+
+// Elliptic curve operations need considerably more stack than the default.
+#[ariel_os::thread(autostart, stacksize = 8192)]
+fn main() {
+    match ariel_os::bench::crypto::software(10) {
+        Ok(measurements) => {
+            for measurement in measurements {
+                println!("{}", measurement);
+            }
+        }
+        Err(_) => {
+            println!("benchmark returned error");
+        }
+    }
+}
@@ -0,0 +1,212 @@
+#!/usr/bin/env -S pipx run
+# /// script
+# requires-python = ">= 3.10"
+# dependencies = [
+#   "aiocoap == 0.4.12",
+#   "pyserial == 3.5",
+#   "pyyaml",
+# ]
+# ///
+"""
+Hardware-in-the-loop test harness
+
+Flashes and runs an application on a board through laze, follows the debug
+output of the device, and runs the steps of a scenario against it, failing as
+soon as a step fails. See the README for the format of scenarios.
+"""
+
+import argparse
+import asyncio
+import re
+import sys
+from pathlib import Path
+
+import serial
+import yaml
+from aiocoap import Context, Message, numbers
+
+REPO_ROOT = Path(__file__).resolve().parents[2]
+
+DEFAULT_TIMEOUT = 30
+
+STEP_KINDS = ("expect", "coap", "sleep", "restart")
+
+
+class StepFailed(Exception):
+    pass
+
+
+class Device:
+    """A running application, whose debug output is followed line by line."""
+
+    def __init__(self, board, app, laze_args, serial_port):
+        self.board = board
+        self.app = app
+        self.laze_args = laze_args
+        self.serial_port = serial_port
+        self.lines = asyncio.Queue()
+        self.process = None
+        self.readers = []
+
+    async def start(self):
+        """Flashes and starts the application, and starts following its output."""
+        self.process = await asyncio.create_subprocess_exec(
+            "laze",
+            "-C",
+            str(REPO_ROOT / self.app),
+            "build",
+            "-b",
+            self.board,
+            *self.laze_args,
+            "run",
+            stdout=asyncio.subprocess.PIPE,
+            stderr=asyncio.subprocess.STDOUT,
+        )
+        self.readers.append(asyncio.create_task(self._follow_runner()))
+        if self.serial_port is not None:
+            self.readers.append(asyncio.create_task(self._follow_serial()))
+
+    async def stop(self):
+        for reader in self.readers:
+            reader.cancel()
+        self.readers = []
+        if self.process is not None and self.process.returncode is None:
+            self.process.terminate()
+            await self.process.wait()
+        self.process = None
+        # Output of the previous run must not satisfy the steps of the next one.
+        self.lines = asyncio.Queue()
+
+    async def _follow_runner(self):
+        async for line in self.process.stdout:
+            self._push(line.decode(errors="replace").rstrip())
+        self._push(None)
+
+    async def _follow_serial(self):
+        with serial.Serial(self.serial_port, 115200, timeout=0.1) as port:
+            while True:
+                line = await asyncio.to_thread(port.readline)
+                if line:
+                    self._push(line.decode(errors="replace").rstrip())
+
+    def _push(self, line):
+        if line is not None:
+            print(f"  | {line}")
+        self.lines.put_nowait(line)
+
+    async def expect(self, pattern):
+        """Waits for a line of output matching `pattern`, and returns the match."""
+        regex = re.compile(pattern)
+        while True:
+            line = await self.lines.get()
+            if line is None:
+                raise StepFailed(f"application ended without printing {pattern!r}")
+            if match := regex.search(line):
+                return match
+
+
+async def coap_request(context, step, variables):
+    method = getattr(numbers.codes.Code, step.get("method", "GET"))
+    uri = step["uri"].format(**variables)
+    payload = step.get("payload", "").encode()
+
+    request = Message(code=method, uri=uri, payload=payload)
+    response = await context.request(request).response
+
+    expected = step.get("response", {})
+    if "code" in expected and response.code.dotted != str(expected["code"]):
+        raise StepFailed(
+            f"{method} {uri}: expected code {expected['code']}, got {response.code}"
+        )
+    if "payload" in expected:
+        text = response.payload.decode(errors="replace")
+        if not re.fullmatch(expected["payload"], text):
+            raise StepFailed(
+                f"{method} {uri}: expected payload {expected['payload']!r}, got {text!r}"
+            )
+
+
+async def run_step(device, context, step, variables):
+    kinds = [kind for kind in step if kind in STEP_KINDS]
+    if len(kinds) != 1:
+        raise StepFailed(f"a step must have exactly one of {', '.join(STEP_KINDS)}")
+    kind = kinds[0]
+    argument = step[kind]
+    timeout = step.get("timeout", DEFAULT_TIMEOUT)
+
+    match kind:
+        case "expect":
+            found = await asyncio.wait_for(device.expect(argument), timeout)
+            variables.update(found.groupdict())
+        case "coap":
+            await asyncio.wait_for(coap_request(context, argument, variables), timeout)
+        case "sleep":
+            await asyncio.sleep(argument)
+        case "restart":
+            await device.stop()
+            await device.start()
+
+
+async def run_scenario(scenario, args):
+    variables = {"address": args.address}
+    device = Device(
+        args.board,
+        scenario["app"],
+        scenario.get("laze_args", []) + args.laze_args,
+        args.serial,
+    )
+    context = await Context.create_client_context()
+
+    await device.start()
+    try:
+        for number, step in enumerate(scenario["steps"], start=1):
+            print(f"step {number}: {step}")
+            try:
+                await run_step(device, context, step, variables)
+            except asyncio.TimeoutError:
+                raise StepFailed(f"step {number} timed out") from None
+    finally:
+        await device.stop()
+        await context.shutdown()
+
+
+def main():
+    p = argparse.ArgumentParser(description=__doc__.strip().splitlines()[0])
+    p.add_argument("scenarios", nargs="+", type=Path, help="Scenario files to run")
+    p.add_argument("-b", "--board", required=True, help="laze builder of the board")
+    p.add_argument(
+        "--address",
+        default="10.42.0.61",
+        help="IP address of the device, available as {address} in scenarios (default: %(default)s)",
+    )
+    p.add_argument(
+        "--serial",
+        help="Serial port to follow the debug output on, for boards whose debug output is not forwarded by the runner",
+    )
+    p.add_argument(
+        "--laze-arg",
+        dest="laze_args",
+        action="append",
+        default=[],
+        help="Additional argument passed to laze, may be repeated",
+    )
+    args = p.parse_args()
+
+    failed = []
+    for path in args.scenarios:
+        print(f"== {path}")
+        scenario = yaml.safe_load(path.read_text())
+        try:
+            asyncio.run(run_scenario(scenario, args))
+        except StepFailed as e:
+            print(f"== {path}: FAILED: {e}")
+            failed.append(path)
+        else:
+            print(f"== {path}: ok")
+
+    print(f"{len(args.scenarios) - len(failed)} passed, {len(failed)} failed")
+    sys.exit(1 if failed else 0)
+
+
+if __name__ == "__main__":
+    main()
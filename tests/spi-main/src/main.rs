@@ -17,12 +17,12 @@ use ariel_os::{
     gpio, hal,
     spi::{
         Mode,
-        main::{Kilohertz, SpiDevice, highest_freq_in},
+        main::{Kilohertz, Operation, SpiDevice, highest_freq_in},
     },
 };
 
 use embassy_sync::mutex::Mutex;
-use embedded_hal_async::spi::{Operation, SpiDevice as _};
+use embedded_hal_async::spi::SpiDevice as _;
 
 // WHO_AM_I register of the sensor
 #[cfg(not(context = "nordic-thingy-91-x-nrf9151"))]